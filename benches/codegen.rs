@@ -0,0 +1,50 @@
+//! Parallel against serial code generation
+//!
+//! Generates an object for a module of 200 small independent functions, each with
+//! a little control flow and arithmetic, compiling the bodies on one thread and on
+//! all available cores.
+
+use bract::codegen::cranelift::CraneliftCodeGenerator;
+use bract::codegen::CodegenOptions;
+use bract::parser::Parser;
+use bract::semantic::SymbolTable;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+/// Number of functions in the module
+const FUNCTIONS: usize = 200;
+
+/// Source of `functions` independent functions and a `main` calling the last
+fn independent_functions(functions: usize) -> String {
+    let mut source = String::new();
+    for i in 0..functions {
+        source.push_str(&format!(
+            "fn g{i}(x: i64, y: i64) -> i64 {{ let mut total = x * {i}; if total > y {{ total = total - y; }} else {{ total = total + y * 2; }} return total; }}\n"
+        ));
+    }
+    source.push_str(&format!("fn main() -> i32 {{ return g{}(1, 2) as i32; }}\n", functions - 1));
+    source
+}
+
+fn codegen(c: &mut Criterion) {
+    let source = independent_functions(FUNCTIONS);
+    let mut parser = Parser::new(&source, 0).expect("benchmark source parses");
+    let module = parser.parse_module().expect("benchmark source parses");
+    let interner = parser.take_interner();
+
+    let mut group = c.benchmark_group("codegen");
+    for (name, parallel) in [("serial_200_functions", false), ("parallel_200_functions", true)] {
+        group.bench_function(name, |b| b.iter_batched(
+            || {
+                let options = CodegenOptions { parallel, ..CodegenOptions::default() };
+                CraneliftCodeGenerator::new(SymbolTable::new(), interner.clone(), options)
+                    .expect("code generator is created")
+            },
+            |mut generator| generator.generate(black_box(&module)).expect("benchmark source compiles"),
+            BatchSize::SmallInput,
+        ));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, codegen);
+criterion_main!(benches);
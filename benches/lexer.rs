@@ -0,0 +1,57 @@
+//! Lexer throughput
+//!
+//! Lexes about a megabyte of synthetic source: functions mixing keywords, short
+//! and long identifiers that recur across the file, numbers, strings and comments,
+//! in roughly the proportions of hand-written code.
+
+use bract::{Lexer, TokenType};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+/// Bytes of source to lex
+const SOURCE_BYTES: usize = 1 << 20;
+
+/// Synthetic source of at least `bytes` bytes
+fn synthetic_source(bytes: usize) -> String {
+    let mut source = String::with_capacity(bytes + 1024);
+    let mut index = 0;
+    while source.len() < bytes {
+        source.push_str(&format!(
+            "// Sums the elements of buffer number {index}\n\
+             fn accumulate_{index}(values: &[i32], count: usize, scale: i32) -> i32 {{\n    \
+                 let mut total = 0;\n    \
+                 let mut position = 0;\n    \
+                 while position < count {{\n        \
+                     let value = values[position] * scale + 0x{index:x};\n        \
+                     if value > 1_000 && !is_negative(value) {{\n            \
+                         total = total + value;\n        \
+                     }} else {{\n            \
+                         total = total - value % 7;\n        \
+                     }}\n        \
+                     position += 1;\n    \
+                 }}\n    \
+                 print_str(\"accumulated buffer {index}\\n\");\n    \
+                 return total as i32;\n\
+             }}\n\n"
+        ));
+        index += 1;
+    }
+    source
+}
+
+fn lexing(c: &mut Criterion) {
+    let source = synthetic_source(SOURCE_BYTES);
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("synthetic_1mb", |b| b.iter(|| {
+        let mut lexer = Lexer::new(black_box(&source), 0);
+        let mut tokens = 0;
+        while lexer.next_token().expect("synthetic source lexes").token_type != TokenType::Eof {
+            tokens += 1;
+        }
+        tokens
+    }));
+    group.finish();
+}
+
+criterion_group!(benches, lexing);
+criterion_main!(benches);
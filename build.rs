@@ -0,0 +1,91 @@
+//! Build script compiling the native runtime
+//!
+//! Programs compiled by Bract call into a small C runtime, `src/codegen/native_runtime.c`.
+//! It is compiled here into a static archive in `OUT_DIR` that the crate embeds, so
+//! linking a program needs no runtime files on disk. Without a C compiler the archive
+//! is left empty, the crate still builds, and linking a program that needs the
+//! runtime reports that it is missing.
+//!
+//! The runtime is built for the target the crate is built for. Cross-compiling needs
+//! the target's tools in `CC_<target>` and `AR_<target>` (or `TARGET_CC` and
+//! `TARGET_AR`); `CC` and `AR` name host tools then and are not used.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const RUNTIME_SOURCE: &str = "src/codegen/native_runtime.c";
+
+/// Name of the archive in `OUT_DIR`, as `codegen::runtime_native` includes it
+const ARCHIVE: &str = "bract_runtime.archive";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", RUNTIME_SOURCE);
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").expect("cargo sets OUT_DIR"));
+    let archive = out_dir.join(ARCHIVE);
+    let msvc = env::var("CARGO_CFG_TARGET_ENV").is_ok_and(|target_env| target_env == "msvc");
+    let built = if msvc { build_msvc(&out_dir, &archive) } else { build_unix(&out_dir, &archive) };
+
+    if let Err(reason) = built {
+        println!("cargo:warning=the Bract runtime was not built ({}); linking programs that need it will fail", reason);
+        std::fs::write(&archive, []).expect("writing the empty runtime archive");
+    }
+}
+
+fn build_unix(out_dir: &Path, archive: &Path) -> Result<(), String> {
+    let object = out_dir.join("native_runtime.o");
+    let compiler = target_tool("CC", "cc")?;
+    run(Command::new(&compiler)
+        .args(["-c", "-O2", "-fPIC", "-fno-stack-protector", "-o"])
+        .arg(&object)
+        .arg(RUNTIME_SOURCE))?;
+
+    let archiver = target_tool("AR", "ar")?;
+    let _ = std::fs::remove_file(archive);
+    run(Command::new(&archiver).arg("crs").arg(archive).arg(&object))
+}
+
+fn build_msvc(out_dir: &Path, archive: &Path) -> Result<(), String> {
+    let object = out_dir.join("native_runtime.obj");
+    run(Command::new("cl.exe")
+        .args(["/nologo", "/c", "/O2", "/GS-"])
+        .arg(format!("/Fo{}", object.display()))
+        .arg(RUNTIME_SOURCE))?;
+    run(Command::new("lib.exe")
+        .arg("/nologo")
+        .arg(format!("/OUT:{}", archive.display()))
+        .arg(&object))
+}
+
+/// The tool `var` names for the target: `<var>_<target>`, `TARGET_<var>`, then, unless
+/// cross-compiling, `<var>` or `default`
+fn target_tool(var: &str, default: &str) -> Result<String, String> {
+    let target = env::var("TARGET").expect("cargo sets TARGET");
+    let host = env::var("HOST").expect("cargo sets HOST");
+    let candidates = [
+        format!("{}_{}", var, target),
+        format!("{}_{}", var, target.replace('-', "_")),
+        format!("TARGET_{}", var),
+    ];
+    for name in &candidates {
+        println!("cargo:rerun-if-env-changed={}", name);
+    }
+    if let Some(tool) = candidates.iter().find_map(|name| env::var(name).ok()) {
+        return Ok(tool);
+    }
+    if target != host {
+        return Err(format!("cross-compiling for {} needs {}_{} or TARGET_{}", target, var, target, var));
+    }
+    println!("cargo:rerun-if-env-changed={}", var);
+    Ok(env::var(var).unwrap_or_else(|_| default.to_string()))
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command.output().map_err(|error| format!("cannot run {}: {}", program, error))?;
+    if !output.status.success() {
+        return Err(format!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
@@ -8,9 +8,110 @@
 //! - Type safety (proper Rust enums/structs)
 
 use crate::lexer::{Position, TokenType};
+use crate::parser::StringInterner;
+use serde::ser::{Error as _, SerializeStruct, Serializer};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fmt;
 
-/// Unique identifier for AST nodes (used for arena allocation)
-pub type NodeId = u32;
+pub mod printer;
+
+pub use printer::{to_source, PrettyPrinter};
+
+/// Identifier of an AST node, unique within its module
+///
+/// The parser builds every expression, statement, item, pattern and type with
+/// `NodeId::DUMMY` and numbers them once the module is parsed, in source order from
+/// 0, so the ids of a module are dense and the same on every parse of the same
+/// source. Nodes built later (desugaring, lifted closures, tests) keep the dummy id.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    /// Id of a node the parser has not numbered
+    pub const DUMMY: NodeId = NodeId(u32::MAX);
+    
+    /// Whether this is a real id rather than `NodeId::DUMMY`
+    pub fn is_assigned(self) -> bool {
+        self != Self::DUMMY
+    }
+    
+    /// Position of the node in a `SideTable`
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Facts an analysis found about AST nodes, by node id
+///
+/// Analyses publish per-node results (resolved types, escape facts, ...) in side
+/// tables rather than in the tree. Ids are dense, so a table is a vector indexed by
+/// id; nodes with `NodeId::DUMMY` never have an entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SideTable<T> {
+    entries: Vec<Option<T>>,
+    len: usize,
+}
+
+impl<T> SideTable<T> {
+    pub fn new() -> Self {
+        Self { entries: Vec::new(), len: 0 }
+    }
+    
+    /// Record `value` for node `id`, returning the value it replaces
+    ///
+    /// Values for `NodeId::DUMMY` are dropped, since that id is shared by every
+    /// unnumbered node.
+    pub fn insert(&mut self, id: NodeId, value: T) -> Option<T> {
+        if !id.is_assigned() {
+            return None;
+        }
+        if id.index() >= self.entries.len() {
+            self.entries.resize_with(id.index() + 1, || None);
+        }
+        let previous = self.entries[id.index()].replace(value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+    
+    /// The value recorded for node `id`
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.entries.get(id.index()).and_then(Option::as_ref)
+    }
+    
+    /// Mutable access to the value recorded for node `id`
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.entries.get_mut(id.index()).and_then(Option::as_mut)
+    }
+    
+    /// Whether a value is recorded for node `id`
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+    
+    /// Number of nodes with a value
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    
+    /// Recorded values with their node ids, in id order
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &T)> {
+        self.entries.iter().enumerate()
+            .filter_map(|(index, value)| value.as_ref().map(|value| (NodeId(index as u32), value)))
+    }
+}
+
+impl<T> Default for SideTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 /// Unique identifier for symbols
 pub type SymbolId = u32;
@@ -19,7 +120,7 @@ pub type SymbolId = u32;
 pub type FileId = usize;
 
 /// Memory Strategy for Bract's Hybrid Memory Management
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MemoryStrategy {
     /// Manual memory management - explicit malloc/free
     Manual,
@@ -60,7 +161,7 @@ impl MemoryStrategy {
 }
 
 /// Ownership information for type checking and memory safety
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
 pub enum Ownership {
     /// Owned value - can be moved
     Owned,
@@ -87,7 +188,7 @@ impl Ownership {
 }
 
 /// Lifetime identifier for region analysis
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct LifetimeId(pub u32);
 
 impl LifetimeId {
@@ -101,7 +202,7 @@ impl LifetimeId {
 }
 
 /// Source span for error reporting and debugging
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Span {
     pub start: Position,
     pub end: Position,
@@ -116,6 +217,11 @@ impl Span {
         Self { start: pos, end: pos }
     }
     
+    /// Byte offsets of the span in its source
+    pub fn byte_range(&self) -> std::ops::Range<usize> {
+        self.start.offset..self.end.offset.max(self.start.offset)
+    }
+    
     pub fn merge(self, other: Span) -> Span {
         Span {
             start: if self.start.offset < other.start.offset { self.start } else { other.start },
@@ -124,7 +230,51 @@ impl Span {
     }
 }
 
+/// File names for the file ids in spans, so locations read like `main.bract:42:8`
+///
+/// File ids without a registered name (e.g. a single file parsed on its own) are
+/// reported as the default file.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: std::collections::HashMap<FileId, String>,
+    default_file: String,
+}
+
+impl SourceMap {
+    pub fn new(default_file: &str) -> Self {
+        Self {
+            files: std::collections::HashMap::new(),
+            default_file: default_file.to_string(),
+        }
+    }
+    
+    /// Register the name of file `file_id`
+    pub fn add_file(&mut self, file_id: FileId, name: &str) {
+        self.files.insert(file_id, name.to_string());
+    }
+    
+    pub fn set_default_file(&mut self, name: &str) {
+        self.default_file = name.to_string();
+    }
+    
+    pub fn default_file(&self) -> &str {
+        &self.default_file
+    }
+    
+    /// Name of file `file_id`
+    pub fn file_name(&self, file_id: FileId) -> &str {
+        self.files.get(&file_id).map_or(&self.default_file, String::as_str)
+    }
+    
+    /// `file:line:column` of a position
+    pub fn location(&self, position: &Position) -> String {
+        format!("{}:{}:{}", self.file_name(position.file_id), position.line, position.column)
+    }
+}
+
 /// Interned string for efficient storage of identifiers and literals
+///
+/// Serializes as its text inside a `SerializableModule` and as its id elsewhere.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct InternedString {
     pub id: u32,
@@ -137,7 +287,7 @@ impl InternedString {
 }
 
 /// Binary operators
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinaryOp {
     // Arithmetic
     Add,        // +
@@ -170,7 +320,7 @@ pub enum BinaryOp {
 }
 
 /// Unary operators
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnaryOp {
     Not,        // !
     Negate,     // -
@@ -182,16 +332,18 @@ pub enum UnaryOp {
 }
 
 /// Literal values
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
 pub enum Literal {
     Integer {
         value: String,
         base: crate::lexer::token::NumberBase,
-        suffix: Option<InternedString>,
+        /// Type written after the digits, e.g. `u8` in `255u8`
+        suffix: Option<PrimitiveType>,
     },
     Float {
         value: String,
-        suffix: Option<InternedString>,
+        suffix: Option<PrimitiveType>,
     },
     String {
         value: InternedString,
@@ -204,24 +356,28 @@ pub enum Literal {
 }
 
 /// Expression nodes - the core of the AST
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant")]
 pub enum Expr {
     /// Literal values
     Literal {
         literal: Literal,
         span: Span,
+        id: NodeId,
     },
     
     /// Identifiers and paths
     Identifier {
         name: InternedString,
         span: Span,
+        id: NodeId,
     },
     
     /// Path expressions (module::item)
     Path {
         segments: Vec<InternedString>,
         span: Span,
+        id: NodeId,
     },
     
     /// Binary operations
@@ -230,6 +386,7 @@ pub enum Expr {
         op: BinaryOp,
         right: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Unary operations
@@ -237,6 +394,7 @@ pub enum Expr {
         op: UnaryOp,
         expr: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Function calls
@@ -244,6 +402,7 @@ pub enum Expr {
         callee: Box<Expr>,
         args: Vec<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Method calls (syntactic sugar for function calls)
@@ -252,6 +411,7 @@ pub enum Expr {
         method: InternedString,
         args: Vec<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Field access
@@ -259,6 +419,7 @@ pub enum Expr {
         object: Box<Expr>,
         field: InternedString,
         span: Span,
+        id: NodeId,
     },
     
     /// Array/slice indexing
@@ -266,6 +427,7 @@ pub enum Expr {
         object: Box<Expr>,
         index: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Type casting
@@ -273,24 +435,36 @@ pub enum Expr {
         expr: Box<Expr>,
         target_type: Type,
         span: Span,
+        id: NodeId,
     },
     
     /// Parenthesized expressions
     Parenthesized {
         expr: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Array literals
     Array {
         elements: Vec<Expr>,
         span: Span,
+        id: NodeId,
+    },
+    
+    /// Array repeat expressions: `[value; count]`, with a constant `count`
+    ArrayRepeat {
+        value: Box<Expr>,
+        count: Box<Expr>,
+        span: Span,
+        id: NodeId,
     },
     
     /// Tuple expressions
     Tuple {
         elements: Vec<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Struct initialization
@@ -298,6 +472,7 @@ pub enum Expr {
         path: Vec<InternedString>,
         fields: Vec<FieldInit>,
         span: Span,
+        id: NodeId,
     },
     
     /// Range expressions
@@ -306,14 +481,17 @@ pub enum Expr {
         end: Option<Box<Expr>>,
         inclusive: bool,
         span: Span,
+        id: NodeId,
     },
     
     /// Closure expressions
     Closure {
         is_move: bool,
         params: Vec<Parameter>,
+        return_type: Option<Type>,
         body: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Block expressions
@@ -321,6 +499,7 @@ pub enum Expr {
         statements: Vec<Stmt>,
         trailing_expr: Option<Box<Expr>>,
         span: Span,
+        id: NodeId,
     },
     
     /// If expressions
@@ -329,6 +508,7 @@ pub enum Expr {
         then_block: Box<Expr>,
         else_block: Option<Box<Expr>>,
         span: Span,
+        id: NodeId,
     },
     
     /// Match expressions
@@ -336,6 +516,7 @@ pub enum Expr {
         expr: Box<Expr>,
         arms: Vec<MatchArm>,
         span: Span,
+        id: NodeId,
     },
     
     /// Loop expressions
@@ -343,6 +524,7 @@ pub enum Expr {
         label: Option<InternedString>,
         body: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// While expressions
@@ -350,6 +532,7 @@ pub enum Expr {
         condition: Box<Expr>,
         body: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// For expressions
@@ -358,6 +541,7 @@ pub enum Expr {
         iterator: Box<Expr>,
         body: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Break expressions
@@ -365,24 +549,28 @@ pub enum Expr {
         label: Option<InternedString>,
         value: Option<Box<Expr>>,
         span: Span,
+        id: NodeId,
     },
     
     /// Continue expressions
     Continue {
         label: Option<InternedString>,
         span: Span,
+        id: NodeId,
     },
     
     /// Return expressions
     Return {
         value: Option<Box<Expr>>,
         span: Span,
+        id: NodeId,
     },
     
     /// Box expressions (heap allocation)
     Box {
         expr: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Reference expressions
@@ -390,36 +578,41 @@ pub enum Expr {
         is_mutable: bool,
         expr: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Dereference expressions
     Dereference {
         expr: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Question mark operator (error propagation)
     Try {
         expr: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Await expressions (async)
     Await {
         expr: Box<Expr>,
         span: Span,
+        id: NodeId,
     },
     
     /// Macro invocations
     Macro {
         name: InternedString,
-        args: Vec<TokenType>, // Raw tokens for macro expansion
+        args: Vec<TokenType<'static>>, // Raw tokens for macro expansion
         span: Span,
+        id: NodeId,
     },
 }
 
 /// Field initialization in struct literals
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct FieldInit {
     pub name: InternedString,
     pub value: Option<Expr>, // None for shorthand syntax
@@ -427,7 +620,7 @@ pub struct FieldInit {
 }
 
 /// Match arms for pattern matching
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct MatchArm {
     pub pattern: Pattern,
     pub guard: Option<Expr>,
@@ -436,12 +629,14 @@ pub struct MatchArm {
 }
 
 /// Statement AST nodes
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant")]
 pub enum Stmt {
     /// Expression statement
     Expression {
         expr: Expr,
         span: Span,
+        id: NodeId,
     },
     /// Let binding: let [mut] pattern [: type] [= expr];
     Let {
@@ -450,12 +645,14 @@ pub enum Stmt {
         initializer: Option<Expr>,
         is_mutable: bool,
         span: Span,
+        id: NodeId,
     },
     /// Assignment: lvalue = expr;
     Assignment {
         target: Expr,
         value: Expr,
         span: Span,
+        id: NodeId,
     },
     /// Compound assignment: lvalue op= expr;
     CompoundAssignment {
@@ -463,6 +660,7 @@ pub enum Stmt {
         op: BinaryOp,
         value: Expr,
         span: Span,
+        id: NodeId,
     },
     /// If statement: if expr block [else block]
     If {
@@ -470,12 +668,14 @@ pub enum Stmt {
         then_block: Vec<Stmt>,
         else_block: Option<Box<Stmt>>,
         span: Span,
+        id: NodeId,
     },
     /// While loop: while expr block
     While {
         condition: Expr,
         body: Vec<Stmt>,
         span: Span,
+        id: NodeId,
     },
     /// For loop: for pattern in expr block
     For {
@@ -483,53 +683,71 @@ pub enum Stmt {
         iterable: Expr,
         body: Vec<Stmt>,
         span: Span,
+        id: NodeId,
     },
     /// Infinite loop: [label:] loop block
     Loop {
         label: Option<InternedString>,
         body: Vec<Stmt>,
         span: Span,
+        id: NodeId,
     },
     /// Match statement: match expr { arms }
     Match {
         expr: Expr,
         arms: Vec<MatchArm>,
         span: Span,
+        id: NodeId,
     },
     /// Break statement: break [label] [expr];
     Break {
         label: Option<InternedString>,
         expr: Option<Expr>,
         span: Span,
+        id: NodeId,
     },
     /// Continue statement: continue [label];
     Continue {
         label: Option<InternedString>,
         span: Span,
+        id: NodeId,
     },
     /// Return statement: return [expr];
     Return {
         expr: Option<Expr>,
         span: Span,
+        id: NodeId,
     },
     /// Block statement: { statements... }
     Block {
         statements: Vec<Stmt>,
         span: Span,
+        id: NodeId,
+    },
+    /// Region block: [@memory(size_hint = N)] region name { statements... }
+    Region {
+        name: InternedString,
+        size_hint: Option<u64>,
+        body: Vec<Stmt>,
+        span: Span,
+        id: NodeId,
     },
     /// Item declaration
     Item {
         item: Item,
         span: Span,
+        id: NodeId,
     },
     /// Empty statement
     Empty {
         span: Span,
+        id: NodeId,
     },
 }
 
 /// Top-level items (declarations)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant")]
 pub enum Item {
     /// Function definitions
     Function {
@@ -540,7 +758,13 @@ pub enum Item {
         return_type: Option<Type>,
         body: Option<Expr>, // None for extern functions
         is_extern: bool,
+        /// Declared `@performance` contract, if any
+        performance_contract: Option<PerformanceContract>,
+        /// Allocation strategy requested by `@memory`, if any
+        allocation_strategy: Option<MemoryStrategy>,
+        attributes: Vec<Attribute>,
         span: Span,
+        id: NodeId,
     },
     
     /// Struct definitions
@@ -549,7 +773,9 @@ pub enum Item {
         name: InternedString,
         generics: Vec<GenericParam>,
         fields: StructFields,
+        attributes: Vec<Attribute>,
         span: Span,
+        id: NodeId,
     },
     
     /// Enum definitions
@@ -558,7 +784,9 @@ pub enum Item {
         name: InternedString,
         generics: Vec<GenericParam>,
         variants: Vec<EnumVariant>,
+        attributes: Vec<Attribute>,
         span: Span,
+        id: NodeId,
     },
     
     /// Type aliases
@@ -567,7 +795,9 @@ pub enum Item {
         name: InternedString,
         generics: Vec<GenericParam>,
         target_type: Type,
+        attributes: Vec<Attribute>,
         span: Span,
+        id: NodeId,
     },
     
     /// Constant declarations
@@ -576,7 +806,9 @@ pub enum Item {
         name: InternedString,
         type_annotation: Type,
         value: Expr,
+        attributes: Vec<Attribute>,
         span: Span,
+        id: NodeId,
     },
     
     /// Module declarations
@@ -584,7 +816,9 @@ pub enum Item {
         visibility: Visibility,
         name: InternedString,
         items: Option<Vec<Item>>, // None for external modules
+        attributes: Vec<Attribute>,
         span: Span,
+        id: NodeId,
     },
     
     /// Implementation blocks
@@ -593,19 +827,83 @@ pub enum Item {
         target_type: Type,
         trait_ref: Option<Type>, // For trait implementations
         items: Vec<ImplItem>,
+        attributes: Vec<Attribute>,
         span: Span,
+        id: NodeId,
     },
     
     /// Use declarations (imports)
     Use {
         path: Vec<InternedString>,
+        /// Span of each segment of `path`
+        path_spans: Vec<Span>,
         alias: Option<InternedString>,
+        attributes: Vec<Attribute>,
         span: Span,
+        id: NodeId,
     },
 }
 
+/// Attribute written in front of an item: `@name` or `@name(key = value, ...)`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: InternedString,
+    pub args: Vec<AttributeArg>,
+    /// Predicate of a `@cfg(...)` attribute, which has no `args`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cfg: Option<CfgPredicate>,
+    pub span: Span,
+}
+
+/// Condition of a `@cfg(...)` attribute, deciding whether its item is compiled
+/// for a target
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "variant", content = "value")]
+pub enum CfgPredicate {
+    /// `target_os = "linux"`
+    TargetOs(String),
+    /// `target_arch = "x86_64"`
+    TargetArch(String),
+    /// `debug`: compiled without optimizations
+    Debug,
+    /// `all(...)`: every predicate holds, true when empty
+    All(Vec<CfgPredicate>),
+    /// `any(...)`: some predicate holds, false when empty
+    Any(Vec<CfgPredicate>),
+    /// `not(...)`
+    Not(Box<CfgPredicate>),
+}
+
+impl fmt::Display for CfgPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, name: &str, predicates: &[CfgPredicate]| {
+            let predicates: Vec<String> = predicates.iter().map(ToString::to_string).collect();
+            write!(f, "{}({})", name, predicates.join(", "))
+        };
+        match self {
+            CfgPredicate::TargetOs(os) => write!(f, "target_os = \"{}\"", os),
+            CfgPredicate::TargetArch(arch) => write!(f, "target_arch = \"{}\"", arch),
+            CfgPredicate::Debug => write!(f, "debug"),
+            CfgPredicate::All(predicates) => list(f, "all", predicates),
+            CfgPredicate::Any(predicates) => list(f, "any", predicates),
+            CfgPredicate::Not(predicate) => write!(f, "not({})", predicate),
+        }
+    }
+}
+
+/// One argument of an attribute
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AttributeArg {
+    /// None for positional arguments such as the `16` in `@align(16)`
+    pub key: Option<InternedString>,
+    /// Bare identifiers, as in `@region(temp)`, are kept as string literals
+    pub value: Literal,
+    pub span: Span,
+}
+
 /// Struct field definitions
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
 pub enum StructFields {
     /// Named fields: struct Foo { x: i32, y: i32 }
     Named(Vec<StructField>),
@@ -616,7 +914,7 @@ pub enum StructFields {
 }
 
 /// Named struct field
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct StructField {
     pub visibility: Visibility,
     pub name: InternedString,
@@ -625,7 +923,7 @@ pub struct StructField {
 }
 
 /// Enum variant definition
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct EnumVariant {
     pub name: InternedString,
     pub fields: StructFields,
@@ -634,7 +932,8 @@ pub struct EnumVariant {
 }
 
 /// Items that can appear in impl blocks
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant")]
 pub enum ImplItem {
     /// Method definitions
     Function {
@@ -666,8 +965,85 @@ pub enum ImplItem {
     },
 }
 
+/// Performance contract declared on a function
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PerformanceContract {
+    pub cpu_bound: Option<CpuBound>,
+    pub memory_bound: Option<MemoryBound>,
+    pub allocation_bound: Option<AllocationBound>,
+    pub latency_bound: Option<LatencyBound>,
+    pub stack_bound: Option<StackBound>,
+    /// Same inputs always take the same path
+    pub deterministic: bool,
+    /// Never blocks on other threads
+    pub wait_free: bool,
+    pub span: Span,
+}
+
+/// Bound on CPU time
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
+pub enum CpuBound {
+    /// At most this many cycles
+    Cycles(u64),
+    /// At most this much wall-clock time
+    Time(std::time::Duration),
+    /// Asymptotic bound in the named parameter
+    Complexity(BigOComplexity, Option<InternedString>),
+}
+
+/// Bound on memory usage
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
+pub enum MemoryBound {
+    /// At most this many bytes
+    Bytes(u64),
+    /// No memory beyond the stack frame
+    Zero,
+    /// Bytes per element of the named parameter
+    Proportional(u64, InternedString),
+}
+
+/// Bound on heap allocations
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
+pub enum AllocationBound {
+    /// At most this many allocations
+    Count(u32),
+    /// No heap allocations
+    None,
+}
+
+/// Bound on latency from call to return
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LatencyBound {
+    pub max_latency: std::time::Duration,
+}
+
+/// Bound on stack usage
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
+pub enum StackBound {
+    /// At most this many bytes of stack
+    Bytes(u32),
+    /// No stack frame
+    Zero,
+}
+
+/// Asymptotic complexity classes
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BigOComplexity {
+    Constant,     // O(1)
+    Logarithmic,  // O(log n)
+    Linear,       // O(n)
+    Linearithmic, // O(n log n)
+    Quadratic,    // O(n^2)
+    Cubic,        // O(n^3)
+    Exponential,  // O(2^n)
+}
+
 /// Function parameters
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct Parameter {
     pub pattern: Pattern,
     pub type_annotation: Option<Type>,
@@ -675,7 +1051,7 @@ pub struct Parameter {
 }
 
 /// Generic parameters
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct GenericParam {
     pub name: InternedString,
     pub bounds: Vec<Type>, // Trait bounds
@@ -684,18 +1060,20 @@ pub struct GenericParam {
 }
 
 /// Visibility modifiers
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Visibility {
     Public,
     Private,
 }
 
 /// Pattern matching patterns
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant")]
 pub enum Pattern {
     /// Wildcard pattern (_)
     Wildcard {
         span: Span,
+        id: NodeId,
     },
     
     /// Identifier patterns
@@ -703,24 +1081,28 @@ pub enum Pattern {
         name: InternedString,
         is_mutable: bool,
         span: Span,
+        id: NodeId,
     },
     
     /// Literal patterns
     Literal {
         literal: Literal,
         span: Span,
+        id: NodeId,
     },
     
     /// Tuple patterns
     Tuple {
         patterns: Vec<Pattern>,
         span: Span,
+        id: NodeId,
     },
     
     /// Array patterns
     Array {
         patterns: Vec<Pattern>,
         span: Span,
+        id: NodeId,
     },
     
     /// Struct patterns
@@ -729,6 +1111,7 @@ pub enum Pattern {
         fields: Vec<FieldPattern>,
         rest: bool, // true if pattern ends with ..
         span: Span,
+        id: NodeId,
     },
     
     /// Enum patterns
@@ -736,6 +1119,7 @@ pub enum Pattern {
         path: Vec<InternedString>,
         patterns: Option<Vec<Pattern>>,
         span: Span,
+        id: NodeId,
     },
     
     /// Reference patterns
@@ -743,6 +1127,7 @@ pub enum Pattern {
         is_mutable: bool,
         pattern: Box<Pattern>,
         span: Span,
+        id: NodeId,
     },
     
     /// Range patterns
@@ -751,17 +1136,19 @@ pub enum Pattern {
         end: Option<Box<Pattern>>,
         inclusive: bool,
         span: Span,
+        id: NodeId,
     },
     
     /// Or patterns (pattern1 | pattern2)
     Or {
         patterns: Vec<Pattern>,
         span: Span,
+        id: NodeId,
     },
 }
 
 /// Field patterns in struct patterns
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct FieldPattern {
     pub name: InternedString,
     pub pattern: Option<Pattern>, // None for shorthand syntax
@@ -769,13 +1156,15 @@ pub struct FieldPattern {
 }
 
 /// Type system representation with memory management integration
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant")]
 pub enum Type {
     /// Primitive types with memory strategy
     Primitive {
         kind: PrimitiveType,
         memory_strategy: MemoryStrategy,
         span: Span,
+        id: NodeId,
     },
     
     /// Path types (user-defined types) with memory strategy
@@ -784,6 +1173,7 @@ pub enum Type {
         generics: Vec<Type>,
         memory_strategy: MemoryStrategy,
         span: Span,
+        id: NodeId,
     },
     
     /// Array types [T; N] with memory strategy
@@ -792,6 +1182,7 @@ pub enum Type {
         size: Box<Expr>, // Constant expression
         memory_strategy: MemoryStrategy,
         span: Span,
+        id: NodeId,
     },
     
     /// Slice types &[T] with lifetime tracking
@@ -799,6 +1190,7 @@ pub enum Type {
         element_type: Box<Type>,
         lifetime: Option<LifetimeId>,
         span: Span,
+        id: NodeId,
     },
     
     /// Tuple types with memory strategy
@@ -806,6 +1198,7 @@ pub enum Type {
         types: Vec<Type>,
         memory_strategy: MemoryStrategy,
         span: Span,
+        id: NodeId,
     },
     
     /// Function types with ownership annotations
@@ -814,6 +1207,7 @@ pub enum Type {
         return_type: Box<Type>,
         is_variadic: bool,
         span: Span,
+        id: NodeId,
     },
     
     /// Reference types with lifetime and ownership
@@ -823,6 +1217,7 @@ pub enum Type {
         lifetime: Option<LifetimeId>,
         ownership: Ownership,
         span: Span,
+        id: NodeId,
     },
     
     /// Pointer types with memory strategy
@@ -831,6 +1226,7 @@ pub enum Type {
         target_type: Box<Type>,
         memory_strategy: MemoryStrategy,
         span: Span,
+        id: NodeId,
     },
     
     /// Generic type parameters with bounds
@@ -838,22 +1234,26 @@ pub enum Type {
         name: InternedString,
         bounds: Vec<TypeBound>,
         span: Span,
+        id: NodeId,
     },
     
     /// Inferred types (type holes) with constraints
     Inferred {
         constraints: Vec<TypeConstraint>,
         span: Span,
+        id: NodeId,
     },
     
     /// Never type (!)
     Never {
         span: Span,
+        id: NodeId,
     },
 }
 
 /// Type bounds for generic parameters
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
 pub enum TypeBound {
     /// Memory strategy constraint
     MemoryStrategy(MemoryStrategy),
@@ -866,7 +1266,8 @@ pub enum TypeBound {
 }
 
 /// Type constraints for inference
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
 pub enum TypeConstraint {
     /// Must be compatible with another type
     CompatibleWith(Type),
@@ -883,7 +1284,7 @@ pub enum TypeConstraint {
 }
 
 /// Primitive type kinds
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PrimitiveType {
     // Integers
     I8, I16, I32, I64, I128, ISize,
@@ -899,10 +1300,123 @@ pub enum PrimitiveType {
     Unit, // ()
 }
 
+impl PrimitiveType {
+    /// Primitive type spelled `name` in source, e.g. `u8`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "i8" => PrimitiveType::I8,
+            "i16" => PrimitiveType::I16,
+            "i32" => PrimitiveType::I32,
+            "i64" => PrimitiveType::I64,
+            "i128" => PrimitiveType::I128,
+            "isize" => PrimitiveType::ISize,
+            "u8" => PrimitiveType::U8,
+            "u16" => PrimitiveType::U16,
+            "u32" => PrimitiveType::U32,
+            "u64" => PrimitiveType::U64,
+            "u128" => PrimitiveType::U128,
+            "usize" => PrimitiveType::USize,
+            "f32" => PrimitiveType::F32,
+            "f64" => PrimitiveType::F64,
+            "bool" => PrimitiveType::Bool,
+            "char" => PrimitiveType::Char,
+            "str" => PrimitiveType::Str,
+            _ => return None,
+        })
+    }
+    
+    /// Name of the type as spelled in source, the inverse of `from_name`
+    pub fn name(&self) -> &'static str {
+        match self {
+            PrimitiveType::I8 => "i8",
+            PrimitiveType::I16 => "i16",
+            PrimitiveType::I32 => "i32",
+            PrimitiveType::I64 => "i64",
+            PrimitiveType::I128 => "i128",
+            PrimitiveType::ISize => "isize",
+            PrimitiveType::U8 => "u8",
+            PrimitiveType::U16 => "u16",
+            PrimitiveType::U32 => "u32",
+            PrimitiveType::U64 => "u64",
+            PrimitiveType::U128 => "u128",
+            PrimitiveType::USize => "usize",
+            PrimitiveType::F32 => "f32",
+            PrimitiveType::F64 => "f64",
+            PrimitiveType::Bool => "bool",
+            PrimitiveType::Char => "char",
+            PrimitiveType::Str => "str",
+            PrimitiveType::Unit => "()",
+        }
+    }
+    
+    /// Check if this is an integer type
+    pub fn is_integer(&self) -> bool {
+        self.integer_bits().is_some()
+    }
+    
+    /// Check if this is a floating-point type
+    pub fn is_float(&self) -> bool {
+        matches!(self, PrimitiveType::F32 | PrimitiveType::F64)
+    }
+    
+    /// Check if this is an unsigned integer type
+    pub fn is_unsigned(&self) -> bool {
+        matches!(
+            self,
+            PrimitiveType::U8 | PrimitiveType::U16 | PrimitiveType::U32
+                | PrimitiveType::U64 | PrimitiveType::U128 | PrimitiveType::USize
+        )
+    }
+    
+    /// Width of an integer type in bits, with `isize`/`usize` taken as 64-bit
+    pub fn integer_bits(&self) -> Option<u32> {
+        match self {
+            PrimitiveType::I8 | PrimitiveType::U8 => Some(8),
+            PrimitiveType::I16 | PrimitiveType::U16 => Some(16),
+            PrimitiveType::I32 | PrimitiveType::U32 => Some(32),
+            PrimitiveType::I64 | PrimitiveType::U64 | PrimitiveType::ISize | PrimitiveType::USize => Some(64),
+            PrimitiveType::I128 | PrimitiveType::U128 => Some(128),
+            _ => None,
+        }
+    }
+    
+    /// Smallest and largest value of an integer type
+    pub fn integer_range(&self) -> Option<(i128, u128)> {
+        let bits = self.integer_bits()?;
+        Some(if self.is_unsigned() {
+            (0, u128::MAX >> (128 - bits))
+        } else {
+            (i128::MIN >> (128 - bits), (i128::MAX >> (128 - bits)) as u128)
+        })
+    }
+}
+
+impl Literal {
+    /// Value of an integer literal, whatever base it was written in
+    ///
+    /// `None` for other literals and for values that do not fit in 128 bits.
+    pub fn integer_value(&self) -> Option<u128> {
+        use crate::lexer::token::NumberBase;
+        let Literal::Integer { value, base, .. } = self else {
+            return None;
+        };
+        let digits = value.replace('_', "");
+        let (digits, radix) = match base {
+            NumberBase::Decimal => (digits.as_str(), 10),
+            NumberBase::Hexadecimal => (digits.trim_start_matches("0x").trim_start_matches("0X"), 16),
+            NumberBase::Octal => (digits.trim_start_matches("0o").trim_start_matches("0O"), 8),
+            NumberBase::Binary => (digits.trim_start_matches("0b").trim_start_matches("0B"), 2),
+        };
+        u128::from_str_radix(digits, radix).ok()
+    }
+}
+
 /// Root AST node representing a complete source file
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct Module {
     pub items: Vec<Item>,
+    /// Attributes of the file itself, i.e. its leading `//!` doc comments
+    pub attributes: Vec<Attribute>,
     pub span: Span,
 }
 
@@ -927,6 +1441,82 @@ pub trait AstVisitorMut<T> {
 }
 
 impl Expr {
+    /// Identifier of this expression, unique within its module once the parser numbered it
+    pub fn id(&self) -> NodeId {
+        match self {
+            Expr::Literal { id, .. }
+            | Expr::Identifier { id, .. }
+            | Expr::Path { id, .. }
+            | Expr::Binary { id, .. }
+            | Expr::Unary { id, .. }
+            | Expr::Call { id, .. }
+            | Expr::MethodCall { id, .. }
+            | Expr::FieldAccess { id, .. }
+            | Expr::Index { id, .. }
+            | Expr::Cast { id, .. }
+            | Expr::Parenthesized { id, .. }
+            | Expr::Array { id, .. }
+            | Expr::ArrayRepeat { id, .. }
+            | Expr::Tuple { id, .. }
+            | Expr::StructInit { id, .. }
+            | Expr::Range { id, .. }
+            | Expr::Closure { id, .. }
+            | Expr::Block { id, .. }
+            | Expr::If { id, .. }
+            | Expr::Match { id, .. }
+            | Expr::Loop { id, .. }
+            | Expr::While { id, .. }
+            | Expr::For { id, .. }
+            | Expr::Break { id, .. }
+            | Expr::Continue { id, .. }
+            | Expr::Return { id, .. }
+            | Expr::Box { id, .. }
+            | Expr::Reference { id, .. }
+            | Expr::Dereference { id, .. }
+            | Expr::Try { id, .. }
+            | Expr::Await { id, .. }
+            | Expr::Macro { id, .. } => *id,
+        }
+    }
+    
+    /// Mutable access to the identifier of this expression
+    pub fn id_mut(&mut self) -> &mut NodeId {
+        match self {
+            Expr::Literal { id, .. }
+            | Expr::Identifier { id, .. }
+            | Expr::Path { id, .. }
+            | Expr::Binary { id, .. }
+            | Expr::Unary { id, .. }
+            | Expr::Call { id, .. }
+            | Expr::MethodCall { id, .. }
+            | Expr::FieldAccess { id, .. }
+            | Expr::Index { id, .. }
+            | Expr::Cast { id, .. }
+            | Expr::Parenthesized { id, .. }
+            | Expr::Array { id, .. }
+            | Expr::ArrayRepeat { id, .. }
+            | Expr::Tuple { id, .. }
+            | Expr::StructInit { id, .. }
+            | Expr::Range { id, .. }
+            | Expr::Closure { id, .. }
+            | Expr::Block { id, .. }
+            | Expr::If { id, .. }
+            | Expr::Match { id, .. }
+            | Expr::Loop { id, .. }
+            | Expr::While { id, .. }
+            | Expr::For { id, .. }
+            | Expr::Break { id, .. }
+            | Expr::Continue { id, .. }
+            | Expr::Return { id, .. }
+            | Expr::Box { id, .. }
+            | Expr::Reference { id, .. }
+            | Expr::Dereference { id, .. }
+            | Expr::Try { id, .. }
+            | Expr::Await { id, .. }
+            | Expr::Macro { id, .. } => id,
+        }
+    }
+    
     /// Get the span of any expression
     pub fn span(&self) -> Span {
         match self {
@@ -942,6 +1532,7 @@ impl Expr {
             Expr::Cast { span, .. } => *span,
             Expr::Parenthesized { span, .. } => *span,
             Expr::Array { span, .. } => *span,
+            Expr::ArrayRepeat { span, .. } => *span,
             Expr::Tuple { span, .. } => *span,
             Expr::StructInit { span, .. } => *span,
             Expr::Range { span, .. } => *span,
@@ -974,6 +1565,35 @@ impl Expr {
         matches!(self, Expr::Identifier { .. })
     }
     
+    /// Value of an integer constant expression, folding arithmetic over literals
+    ///
+    /// `constant` supplies the values of named constants. `None` when the expression is
+    /// not constant, or is negative or overflows along the way.
+    pub fn fold_integer(&self, constant: &dyn Fn(InternedString) -> Option<u128>) -> Option<u128> {
+        match self {
+            Expr::Literal { literal, .. } => literal.integer_value(),
+            Expr::Identifier { name, .. } => constant(*name),
+            Expr::Parenthesized { expr, .. } | Expr::Cast { expr, .. } => expr.fold_integer(constant),
+            Expr::Binary { op, left, right, .. } => {
+                let (left, right) = (left.fold_integer(constant)?, right.fold_integer(constant)?);
+                match op {
+                    BinaryOp::Add => left.checked_add(right),
+                    BinaryOp::Subtract => left.checked_sub(right),
+                    BinaryOp::Multiply => left.checked_mul(right),
+                    BinaryOp::Divide => left.checked_div(right),
+                    BinaryOp::Modulo => left.checked_rem(right),
+                    BinaryOp::LeftShift => u32::try_from(right).ok().and_then(|right| left.checked_shl(right)),
+                    BinaryOp::RightShift => u32::try_from(right).ok().and_then(|right| left.checked_shr(right)),
+                    BinaryOp::BitwiseAnd => Some(left & right),
+                    BinaryOp::BitwiseOr => Some(left | right),
+                    BinaryOp::BitwiseXor => Some(left ^ right),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+    
     /// Check if expression has side effects
     pub fn has_side_effects(&self) -> bool {
         match self {
@@ -987,6 +1607,50 @@ impl Expr {
 }
 
 impl Stmt {
+    /// Identifier of this statement, unique within its module once the parser numbered it
+    pub fn id(&self) -> NodeId {
+        match self {
+            Stmt::Expression { id, .. }
+            | Stmt::Let { id, .. }
+            | Stmt::Assignment { id, .. }
+            | Stmt::CompoundAssignment { id, .. }
+            | Stmt::If { id, .. }
+            | Stmt::While { id, .. }
+            | Stmt::For { id, .. }
+            | Stmt::Loop { id, .. }
+            | Stmt::Match { id, .. }
+            | Stmt::Break { id, .. }
+            | Stmt::Continue { id, .. }
+            | Stmt::Return { id, .. }
+            | Stmt::Block { id, .. }
+            | Stmt::Region { id, .. }
+            | Stmt::Item { id, .. }
+            | Stmt::Empty { id, .. } => *id,
+        }
+    }
+    
+    /// Mutable access to the identifier of this statement
+    pub fn id_mut(&mut self) -> &mut NodeId {
+        match self {
+            Stmt::Expression { id, .. }
+            | Stmt::Let { id, .. }
+            | Stmt::Assignment { id, .. }
+            | Stmt::CompoundAssignment { id, .. }
+            | Stmt::If { id, .. }
+            | Stmt::While { id, .. }
+            | Stmt::For { id, .. }
+            | Stmt::Loop { id, .. }
+            | Stmt::Match { id, .. }
+            | Stmt::Break { id, .. }
+            | Stmt::Continue { id, .. }
+            | Stmt::Return { id, .. }
+            | Stmt::Block { id, .. }
+            | Stmt::Region { id, .. }
+            | Stmt::Item { id, .. }
+            | Stmt::Empty { id, .. } => id,
+        }
+    }
+    
     /// Get the span of this statement
     pub fn span(&self) -> Span {
         match self {
@@ -1003,6 +1667,7 @@ impl Stmt {
             Stmt::Continue { span, .. } => *span,
             Stmt::Return { span, .. } => *span,
             Stmt::Block { span, .. } => *span,
+            Stmt::Region { span, .. } => *span,
             Stmt::Item { span, .. } => *span,
             Stmt::Empty { span, .. } => *span,
         }
@@ -1042,13 +1707,230 @@ impl Stmt {
             Stmt::Continue { .. } => false,
             Stmt::Return { expr, .. } => expr.as_ref().map_or(false, |e| e.has_side_effects()),
             Stmt::Block { statements, .. } => statements.iter().any(|s| s.has_side_effects()),
+            Stmt::Region { body, .. } => body.iter().any(|s| s.has_side_effects()),
             Stmt::Item { .. } => true,
             Stmt::Empty { .. } => false,
         }
     }
 }
 
+/// Version of the JSON form of the AST, bumped whenever its schema changes
+pub const AST_JSON_VERSION: u32 = 3;
+
+/// Interner and source map of the `SerializableModule` being serialized on this thread
+type SerializationContext = (StringInterner, Option<SourceMap>);
+
+thread_local! {
+    static SERIALIZATION_CONTEXT: RefCell<Option<SerializationContext>> = const { RefCell::new(None) };
+}
+
+/// A module paired with the interner resolving its names, for serializing it
+///
+/// Names serialize as their text and positions as `file`, `line`, `column` and
+/// `offset`, the file being its name in the source map if one is given and its id
+/// otherwise. Enum variants are tagged by name under `variant`; enums that have
+/// tuple variants nest the fields of each variant under `value`.
+pub struct SerializableModule<'a> {
+    module: &'a Module,
+    interner: &'a StringInterner,
+    source_map: Option<&'a SourceMap>,
+}
+
+impl<'a> SerializableModule<'a> {
+    pub fn new(module: &'a Module, interner: &'a StringInterner) -> Self {
+        Self { module, interner, source_map: None }
+    }
+    
+    /// Name files through `source_map` rather than by id
+    pub fn with_source_map(mut self, source_map: &'a SourceMap) -> Self {
+        self.source_map = Some(source_map);
+        self
+    }
+}
+
+impl Serialize for SerializableModule<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        /// Puts back the enclosing context, also when serialization fails partway
+        struct Restore(Option<SerializationContext>);
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                SERIALIZATION_CONTEXT.with(|context| *context.borrow_mut() = previous);
+            }
+        }
+        
+        let context = (self.interner.clone(), self.source_map.cloned());
+        let _restore = Restore(SERIALIZATION_CONTEXT.with(|current| current.replace(Some(context))));
+        let mut document = serializer.serialize_struct("Ast", 2)?;
+        document.serialize_field("version", &AST_JSON_VERSION)?;
+        document.serialize_field("module", self.module)?;
+        document.end()
+    }
+}
+
+impl Serialize for InternedString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let text = SERIALIZATION_CONTEXT.with(|context| {
+            context.borrow().as_ref().map(|(interner, _)| interner.resolve(*self))
+        });
+        match text {
+            Some(Some(text)) => serializer.serialize_str(&text),
+            Some(None) => Err(S::Error::custom(format!("interned string {} is not in the interner", self.id))),
+            None => serializer.serialize_u32(self.id),
+        }
+    }
+}
+
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let file_name = SERIALIZATION_CONTEXT.with(|context| {
+            context.borrow().as_ref()
+                .and_then(|(_, source_map)| source_map.as_ref())
+                .map(|source_map| source_map.file_name(self.file_id).to_string())
+        });
+        let mut position = serializer.serialize_struct("Position", 4)?;
+        match file_name {
+            Some(file_name) => position.serialize_field("file", &file_name)?,
+            None => position.serialize_field("file", &self.file_id)?,
+        }
+        position.serialize_field("line", &self.line)?;
+        position.serialize_field("column", &self.column)?;
+        position.serialize_field("offset", &self.offset)?;
+        position.end()
+    }
+}
+
+/// Pretty-printed JSON of `module`, with names resolved through `interner`
+///
+/// # Panics
+///
+/// If `module` has names `interner` did not intern.
+pub fn to_json(module: &Module, interner: &StringInterner) -> String {
+    serde_json::to_string_pretty(&SerializableModule::new(module, interner))
+        .expect("module names must come from the given interner")
+}
+
+impl Module {
+    /// Documentation from the file's `//!` doc comments
+    pub fn doc(&self, interner: &StringInterner) -> Option<String> {
+        doc_text(&self.attributes, interner)
+    }
+}
+
+/// Text of the `doc` attributes among `attributes`, joined by newlines
+fn doc_text(attributes: &[Attribute], interner: &StringInterner) -> Option<String> {
+    let paragraphs: Vec<String> = attributes.iter()
+        .filter(|attribute| interner.matches(attribute.name, "doc"))
+        .filter_map(|attribute| match attribute.args.as_slice() {
+            [AttributeArg { value: Literal::String { value, .. }, .. }] => interner.resolve(*value),
+            _ => None,
+        })
+        .collect();
+    if paragraphs.is_empty() {
+        None
+    } else {
+        Some(paragraphs.join("\n"))
+    }
+}
+
+impl Item {
+    /// Identifier of this item, unique within its module once the parser numbered it
+    pub fn id(&self) -> NodeId {
+        match self {
+            Item::Function { id, .. }
+            | Item::Struct { id, .. }
+            | Item::Enum { id, .. }
+            | Item::TypeAlias { id, .. }
+            | Item::Const { id, .. }
+            | Item::Module { id, .. }
+            | Item::Impl { id, .. }
+            | Item::Use { id, .. } => *id,
+        }
+    }
+    
+    /// Mutable access to the identifier of this item
+    pub fn id_mut(&mut self) -> &mut NodeId {
+        match self {
+            Item::Function { id, .. }
+            | Item::Struct { id, .. }
+            | Item::Enum { id, .. }
+            | Item::TypeAlias { id, .. }
+            | Item::Const { id, .. }
+            | Item::Module { id, .. }
+            | Item::Impl { id, .. }
+            | Item::Use { id, .. } => id,
+        }
+    }
+    
+    /// Attributes written in front of this item, in source order
+    pub fn attributes(&self) -> &[Attribute] {
+        match self {
+            Item::Function { attributes, .. }
+            | Item::Struct { attributes, .. }
+            | Item::Enum { attributes, .. }
+            | Item::TypeAlias { attributes, .. }
+            | Item::Const { attributes, .. }
+            | Item::Module { attributes, .. }
+            | Item::Impl { attributes, .. }
+            | Item::Use { attributes, .. } => attributes,
+        }
+    }
+    
+    /// Documentation from the item's doc comments and `@doc` attributes
+    pub fn doc(&self, interner: &StringInterner) -> Option<String> {
+        doc_text(self.attributes(), interner)
+    }
+    
+    /// Mutable access to the attributes written in front of this item
+    pub fn attributes_mut(&mut self) -> &mut Vec<Attribute> {
+        match self {
+            Item::Function { attributes, .. }
+            | Item::Struct { attributes, .. }
+            | Item::Enum { attributes, .. }
+            | Item::TypeAlias { attributes, .. }
+            | Item::Const { attributes, .. }
+            | Item::Module { attributes, .. }
+            | Item::Impl { attributes, .. }
+            | Item::Use { attributes, .. } => attributes,
+        }
+    }
+}
+
 impl Type {
+    /// Identifier of this type, unique within its module once the parser numbered it
+    pub fn id(&self) -> NodeId {
+        match self {
+            Type::Primitive { id, .. }
+            | Type::Path { id, .. }
+            | Type::Array { id, .. }
+            | Type::Slice { id, .. }
+            | Type::Tuple { id, .. }
+            | Type::Function { id, .. }
+            | Type::Reference { id, .. }
+            | Type::Pointer { id, .. }
+            | Type::Generic { id, .. }
+            | Type::Inferred { id, .. }
+            | Type::Never { id, .. } => *id,
+        }
+    }
+    
+    /// Mutable access to the identifier of this type
+    pub fn id_mut(&mut self) -> &mut NodeId {
+        match self {
+            Type::Primitive { id, .. }
+            | Type::Path { id, .. }
+            | Type::Array { id, .. }
+            | Type::Slice { id, .. }
+            | Type::Tuple { id, .. }
+            | Type::Function { id, .. }
+            | Type::Reference { id, .. }
+            | Type::Pointer { id, .. }
+            | Type::Generic { id, .. }
+            | Type::Inferred { id, .. }
+            | Type::Never { id, .. } => id,
+        }
+    }
+    
     /// Get the span of any type
     pub fn span(&self) -> Span {
         match self {
@@ -1137,6 +2019,7 @@ impl Type {
             kind,
             memory_strategy: MemoryStrategy::Stack,
             span,
+            id: NodeId::DUMMY,
         }
     }
     
@@ -1147,6 +2030,7 @@ impl Type {
             generics: Vec::new(),
             memory_strategy: MemoryStrategy::Linear,
             span,
+            id: NodeId::DUMMY,
         }
     }
     
@@ -1158,11 +2042,44 @@ impl Type {
             lifetime,
             ownership: if is_mutable { Ownership::MutBorr } else { Ownership::Borrowed },
             span,
+            id: NodeId::DUMMY,
         }
     }
 }
 
 impl Pattern {
+    /// Identifier of this pattern, unique within its module once the parser numbered it
+    pub fn id(&self) -> NodeId {
+        match self {
+            Pattern::Wildcard { id, .. }
+            | Pattern::Identifier { id, .. }
+            | Pattern::Literal { id, .. }
+            | Pattern::Tuple { id, .. }
+            | Pattern::Array { id, .. }
+            | Pattern::Struct { id, .. }
+            | Pattern::Enum { id, .. }
+            | Pattern::Reference { id, .. }
+            | Pattern::Range { id, .. }
+            | Pattern::Or { id, .. } => *id,
+        }
+    }
+    
+    /// Mutable access to the identifier of this pattern
+    pub fn id_mut(&mut self) -> &mut NodeId {
+        match self {
+            Pattern::Wildcard { id, .. }
+            | Pattern::Identifier { id, .. }
+            | Pattern::Literal { id, .. }
+            | Pattern::Tuple { id, .. }
+            | Pattern::Array { id, .. }
+            | Pattern::Struct { id, .. }
+            | Pattern::Enum { id, .. }
+            | Pattern::Reference { id, .. }
+            | Pattern::Range { id, .. }
+            | Pattern::Or { id, .. } => id,
+        }
+    }
+    
     /// Get the span of any pattern
     pub fn span(&self) -> Span {
         match self {
@@ -1195,6 +2112,27 @@ impl Pattern {
     }
 }
 
+impl EnumVariant {
+    /// Discriminant of each variant, in declaration order
+    ///
+    /// An explicit `= value` sets the discriminant; other variants take one more than the
+    /// variant before them, starting at 0. `None` for discriminants that cannot be folded
+    /// (see `Expr::fold_integer`) and for the variants counting on from them.
+    pub fn discriminants(variants: &[EnumVariant], constant: &dyn Fn(InternedString) -> Option<u128>) -> Vec<Option<u128>> {
+        let mut next = Some(0u128);
+        variants.iter()
+            .map(|variant| {
+                let discriminant = match &variant.discriminant {
+                    Some(value) => value.fold_integer(constant),
+                    None => next,
+                };
+                next = discriminant.and_then(|value| value.checked_add(1));
+                discriminant
+            })
+            .collect()
+    }
+}
+
 /// Default implementations for common cases
 impl Default for Visibility {
     fn default() -> Self {
@@ -1278,6 +2216,7 @@ mod tests {
         let lit_expr = Expr::Literal {
             literal: Literal::Bool(true),
             span,
+            id: NodeId::DUMMY,
         };
         assert!(lit_expr.is_literal());
         assert!(!lit_expr.is_identifier());
@@ -1287,6 +2226,7 @@ mod tests {
         let id_expr = Expr::Identifier {
             name: dummy_interned_string(1),
             span,
+            id: NodeId::DUMMY,
         };
         assert!(!id_expr.is_literal());
         assert!(id_expr.is_identifier());
@@ -1297,6 +2237,7 @@ mod tests {
             callee: Box::new(id_expr.clone()),
             args: vec![lit_expr.clone()],
             span,
+            id: NodeId::DUMMY,
         };
         assert!(!call_expr.is_literal());
         assert!(!call_expr.is_identifier());
@@ -1308,7 +2249,7 @@ mod tests {
         let span = dummy_span();
         
         // Wildcard pattern
-        let wildcard = Pattern::Wildcard { span };
+        let wildcard = Pattern::Wildcard { span, id: NodeId::DUMMY };
         assert!(!wildcard.binds_variables());
         
         // Identifier pattern
@@ -1316,6 +2257,7 @@ mod tests {
             name: dummy_interned_string(1),
             is_mutable: false,
             span,
+            id: NodeId::DUMMY,
         };
         assert!(id_pattern.binds_variables());
         
@@ -1323,6 +2265,7 @@ mod tests {
         let tuple_pattern = Pattern::Tuple {
             patterns: vec![wildcard.clone(), id_pattern.clone()],
             span,
+            id: NodeId::DUMMY,
         };
         assert!(tuple_pattern.binds_variables());
     }
@@ -1336,6 +2279,7 @@ mod tests {
             kind: PrimitiveType::I32,
             memory_strategy: MemoryStrategy::Inferred,
             span: Span::new(Position::start(0), Position::start(0)),
+            id: NodeId::DUMMY,
         };
         assert!(prim_type.is_primitive());
         assert!(!prim_type.is_reference());
@@ -1347,6 +2291,7 @@ mod tests {
             lifetime: None,
             ownership: Ownership::Borrowed,
             span: Span::new(Position::start(0), Position::start(0)),
+            id: NodeId::DUMMY,
         };
         assert!(!ref_type.is_primitive());
         assert!(ref_type.is_reference());
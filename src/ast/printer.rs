@@ -0,0 +1,1578 @@
+//! Pretty-printer turning an AST back into canonical Bract source
+//!
+//! The output puts one statement on each line, indents blocks by a configurable
+//! width and writes parentheses only where they appear in the tree or where the
+//! precedence of the operators requires them, so parsing the output gives back the
+//! same AST up to spans. Attributes, including `@memory` and `@performance`, and
+//! doc comments are kept; ordinary comments are not part of the AST and are lost.
+
+use super::{
+    Attribute, AttributeArg, BinaryOp, CpuBound, EnumVariant, Expr, FieldInit, GenericParam, ImplItem,
+    InternedString, Item, Literal, MatchArm, MemoryBound, MemoryStrategy, Module, Parameter, Pattern,
+    PerformanceContract, Stmt, StructFields, Type, UnaryOp, Visibility,
+};
+use crate::lexer::token::NumberBase;
+use crate::lexer::{Lexer, Token, TokenType};
+use crate::parser::StringInterner;
+
+/// Binding strength of an expression: an operand binding looser than its
+/// operator requires needs parentheses
+type Precedence = u8;
+
+const ASSIGN: Precedence = 0;
+const TERNARY: Precedence = 1;
+const LOGICAL_OR: Precedence = 2;
+const LOGICAL_AND: Precedence = 3;
+const BITWISE_OR: Precedence = 4;
+const BITWISE_XOR: Precedence = 5;
+const BITWISE_AND: Precedence = 6;
+const EQUALITY: Precedence = 7;
+const RELATIONAL: Precedence = 8;
+const RANGE: Precedence = 9;
+const SHIFT: Precedence = 10;
+const ADDITIVE: Precedence = 11;
+const MULTIPLICATIVE: Precedence = 12;
+const CAST: Precedence = 13;
+const UNARY: Precedence = 14;
+const POSTFIX: Precedence = 15;
+const PRIMARY: Precedence = 16;
+
+/// What the source has right after an expression
+///
+/// Closures and `return` take everything after them as part of their body or
+/// value, and a range without an end takes the next operand as its end.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Follow {
+    /// Nothing that could continue the expression: a closing delimiter, `,`, `;` or `=>`
+    Nothing,
+    /// A token that can also start an operand, such as `-`, `&` or the `{` after a condition
+    OperandStart,
+    /// Any other operator
+    Operator,
+}
+
+/// Pretty-printer rendering AST nodes as Bract source
+///
+/// ```ignore
+/// let source = PrettyPrinter::new(&interner).with_indent_width(2).print_module(&module);
+/// ```
+pub struct PrettyPrinter<'a> {
+    interner: &'a StringInterner,
+    indent_width: usize,
+    /// Indent with one tab per level instead of `indent_width` spaces
+    use_tabs: bool,
+    output: String,
+    depth: usize,
+    /// Whether `Name {` may start a struct literal here, as in the parser
+    struct_literals: bool,
+    /// Labels of the loops being printed, innermost last
+    loop_labels: Vec<InternedString>,
+}
+
+/// Canonical source of `module`, indented by four spaces
+///
+/// # Panics
+///
+/// If `module` has names `interner` did not intern.
+pub fn to_source(module: &Module, interner: &StringInterner) -> String {
+    PrettyPrinter::new(interner).print_module(module)
+}
+
+impl<'a> PrettyPrinter<'a> {
+    /// Printer resolving names through `interner`, indenting by four spaces
+    pub fn new(interner: &'a StringInterner) -> Self {
+        Self {
+            interner,
+            indent_width: 4,
+            use_tabs: false,
+            output: String::new(),
+            depth: 0,
+            struct_literals: true,
+            loop_labels: Vec::new(),
+        }
+    }
+
+    /// Indent each nested block by `width` spaces
+    pub fn with_indent_width(mut self, width: usize) -> Self {
+        self.indent_width = width;
+        self
+    }
+
+    /// Indent each nested block by a tab rather than by spaces
+    pub fn with_tabs(mut self) -> Self {
+        self.use_tabs = true;
+        self
+    }
+
+    /// Source of a whole file, ending in a newline
+    pub fn print_module(&mut self, module: &Module) -> String {
+        self.start();
+        for attribute in &module.attributes {
+            if let Some(text) = self.doc_text(attribute) {
+                for line in text.lines() {
+                    self.comment_line("//!", line);
+                }
+            }
+        }
+        if !module.attributes.is_empty() && !module.items.is_empty() {
+            self.output.push('\n');
+        }
+        self.items(&module.items);
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.finish()
+    }
+
+    pub fn print_item(&mut self, item: &Item) -> String {
+        self.start();
+        self.item(item);
+        self.finish()
+    }
+
+    pub fn print_stmt(&mut self, stmt: &Stmt) -> String {
+        self.start();
+        self.stmt(stmt);
+        self.finish()
+    }
+
+    pub fn print_expr(&mut self, expr: &Expr) -> String {
+        self.start();
+        self.expr(expr);
+        self.finish()
+    }
+
+    pub fn print_type(&mut self, ty: &Type) -> String {
+        self.start();
+        self.ty(ty);
+        self.finish()
+    }
+
+    pub fn print_pattern(&mut self, pattern: &Pattern) -> String {
+        self.start();
+        self.pattern(pattern);
+        self.finish()
+    }
+
+    fn start(&mut self) {
+        self.output.clear();
+        self.depth = 0;
+        self.struct_literals = true;
+    }
+
+    fn finish(&mut self) -> String {
+        std::mem::take(&mut self.output)
+    }
+
+    // Output helpers
+
+    fn write(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    /// Start a new line at the current indentation
+    fn newline(&mut self) {
+        self.output.push('\n');
+        if self.use_tabs {
+            self.output.push_str(&"\t".repeat(self.depth));
+        } else {
+            self.output.push_str(&" ".repeat(self.depth * self.indent_width));
+        }
+    }
+
+    /// Write `prefix`, then `text` after a space unless it is empty, and start a new line
+    fn comment_line(&mut self, prefix: &str, text: &str) {
+        self.write(prefix);
+        if !text.is_empty() {
+            self.write(" ");
+            self.write(text);
+        }
+        self.newline();
+    }
+
+    fn name(&mut self, name: InternedString) {
+        let text = self.resolve(name);
+        self.write(&text);
+    }
+
+    fn resolve(&self, name: InternedString) -> String {
+        self.interner.resolve(name).expect("names must come from the printer's interner")
+    }
+
+    fn path(&mut self, segments: &[InternedString]) {
+        for (index, segment) in segments.iter().enumerate() {
+            if index > 0 {
+                self.write("::");
+            }
+            self.name(*segment);
+        }
+    }
+
+    fn comma_separated<T>(&mut self, elements: &[T], mut each: impl FnMut(&mut Self, &T)) {
+        for (index, element) in elements.iter().enumerate() {
+            if index > 0 {
+                self.write(", ");
+            }
+            each(self, element);
+        }
+    }
+
+    fn with_struct_literals(&mut self, allowed: bool, print: impl FnOnce(&mut Self)) {
+        let saved = std::mem::replace(&mut self.struct_literals, allowed);
+        print(self);
+        self.struct_literals = saved;
+    }
+
+    // Items
+
+    fn items(&mut self, items: &[Item]) {
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 {
+                self.output.push('\n');
+                self.newline();
+            }
+            self.item(item);
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        self.attributes(item.attributes());
+        match item {
+            Item::Function {
+                visibility, name, generics, params, return_type, body, is_extern,
+                performance_contract, allocation_strategy, attributes, ..
+            } => {
+                self.contract_attributes(attributes, performance_contract.as_ref(), *allocation_strategy);
+                self.visibility(*visibility);
+                if *is_extern {
+                    self.write("extern ");
+                }
+                self.function(*name, generics, params, return_type.as_ref(), body.as_ref());
+            }
+            Item::Struct { visibility, name, generics, fields, .. } => {
+                self.visibility(*visibility);
+                self.write("struct ");
+                self.name(*name);
+                self.generic_params(generics);
+                match fields {
+                    StructFields::Named(fields) if fields.is_empty() => self.write(" {}"),
+                    StructFields::Named(fields) => {
+                        self.write(" {");
+                        self.depth += 1;
+                        for field in fields {
+                            self.newline();
+                            self.visibility(field.visibility);
+                            self.name(field.name);
+                            self.write(": ");
+                            self.ty(&field.field_type);
+                            self.write(",");
+                        }
+                        self.depth -= 1;
+                        self.newline();
+                        self.write("}");
+                    }
+                    StructFields::Tuple(types) => {
+                        self.write("(");
+                        self.comma_separated(types, Self::ty);
+                        self.write(");");
+                    }
+                    StructFields::Unit => self.write(";"),
+                }
+            }
+            Item::Enum { visibility, name, generics, variants, .. } => {
+                self.visibility(*visibility);
+                self.write("enum ");
+                self.name(*name);
+                self.generic_params(generics);
+                if variants.is_empty() {
+                    self.write(" {}");
+                    return;
+                }
+                self.write(" {");
+                self.depth += 1;
+                for variant in variants {
+                    self.newline();
+                    self.enum_variant(variant);
+                    self.write(",");
+                }
+                self.depth -= 1;
+                self.newline();
+                self.write("}");
+            }
+            Item::TypeAlias { visibility, name, generics, target_type, .. } => {
+                self.visibility(*visibility);
+                self.write("type ");
+                self.name(*name);
+                self.generic_params(generics);
+                self.write(" = ");
+                self.ty(target_type);
+                self.write(";");
+            }
+            Item::Const { visibility, name, type_annotation, value, .. } => {
+                self.visibility(*visibility);
+                self.write("const ");
+                self.name(*name);
+                self.write(": ");
+                self.ty(type_annotation);
+                self.write(" = ");
+                self.expr(value);
+                self.write(";");
+            }
+            Item::Module { visibility, name, items, .. } => {
+                self.visibility(*visibility);
+                self.write("mod ");
+                self.name(*name);
+                match items {
+                    None => self.write(";"),
+                    Some(items) if items.is_empty() => self.write(" {}"),
+                    Some(items) => {
+                        self.write(" {");
+                        self.depth += 1;
+                        self.newline();
+                        self.items(items);
+                        self.depth -= 1;
+                        self.newline();
+                        self.write("}");
+                    }
+                }
+            }
+            Item::Impl { generics, target_type, trait_ref, items, .. } => {
+                self.write("impl");
+                self.generic_params(generics);
+                self.write(" ");
+                self.ty(target_type);
+                if let Some(trait_ref) = trait_ref {
+                    self.write(" for ");
+                    self.ty(trait_ref);
+                }
+                if items.is_empty() {
+                    self.write(" {}");
+                    return;
+                }
+                self.write(" {");
+                self.depth += 1;
+                for (index, impl_item) in items.iter().enumerate() {
+                    if index > 0 {
+                        self.output.push('\n');
+                    }
+                    self.newline();
+                    self.impl_item(impl_item);
+                }
+                self.depth -= 1;
+                self.newline();
+                self.write("}");
+            }
+            Item::Use { path, alias, .. } => {
+                self.write("use ");
+                self.path(path);
+                if let Some(alias) = alias {
+                    self.write(" as ");
+                    self.name(*alias);
+                }
+                self.write(";");
+            }
+        }
+    }
+
+    fn impl_item(&mut self, impl_item: &ImplItem) {
+        match impl_item {
+            ImplItem::Function { visibility, name, generics, params, return_type, body, .. } => {
+                self.visibility(*visibility);
+                self.function(*name, generics, params, return_type.as_ref(), body.as_ref());
+            }
+            ImplItem::Type { visibility, name, generics, target_type, .. } => {
+                self.visibility(*visibility);
+                self.write("type ");
+                self.name(*name);
+                self.generic_params(generics);
+                self.write(" = ");
+                self.ty(target_type);
+                self.write(";");
+            }
+            ImplItem::Const { visibility, name, type_annotation, value, .. } => {
+                self.visibility(*visibility);
+                self.write("const ");
+                self.name(*name);
+                self.write(": ");
+                self.ty(type_annotation);
+                if let Some(value) = value {
+                    self.write(" = ");
+                    self.expr(value);
+                }
+                self.write(";");
+            }
+        }
+    }
+
+    /// `fn name<generics>(params) -> return_type` and the body, or `;` without one
+    fn function(
+        &mut self,
+        name: InternedString,
+        generics: &[GenericParam],
+        params: &[Parameter],
+        return_type: Option<&Type>,
+        body: Option<&Expr>,
+    ) {
+        self.write("fn ");
+        self.name(name);
+        self.generic_params(generics);
+        self.write("(");
+        self.comma_separated(params, Self::function_param);
+        self.write(")");
+        if let Some(return_type) = return_type {
+            self.write(" -> ");
+            self.ty(return_type);
+        }
+        match body {
+            Some(body) => {
+                self.write(" ");
+                self.block_expr(body);
+            }
+            None => self.write(";"),
+        }
+    }
+
+    fn function_param(&mut self, param: &Parameter) {
+        if let Some(self_param) = self.self_param(param) {
+            self.write(self_param);
+            return;
+        }
+        // A leading `&` reads as `&self`; the parentheses around a pattern vanish when parsed
+        match &param.pattern {
+            pattern @ Pattern::Reference { .. } => {
+                self.write("(");
+                self.pattern(pattern);
+                self.write(")");
+            }
+            pattern => self.pattern(pattern),
+        }
+        if let Some(type_annotation) = &param.type_annotation {
+            self.write(": ");
+            self.ty(type_annotation);
+        }
+    }
+
+    /// `self`, `&self` or `&mut self` for the parameters the parser builds from them
+    fn self_param(&self, param: &Parameter) -> Option<&'static str> {
+        let Pattern::Identifier { name, is_mutable: false, .. } = &param.pattern else {
+            return None;
+        };
+        if !self.interner.matches(*name, "self") {
+            return None;
+        }
+        let is_self_type = |ty: &Type| matches!(
+            ty,
+            Type::Path { segments, generics, .. } if generics.is_empty()
+                && matches!(segments.as_slice(), [segment] if self.interner.matches(*segment, "Self"))
+        );
+        match param.type_annotation.as_ref()? {
+            ty if is_self_type(ty) => Some("self"),
+            Type::Reference { is_mutable: false, target_type, .. } if is_self_type(target_type) => Some("&self"),
+            Type::Reference { is_mutable: true, target_type, .. } if is_self_type(target_type) => Some("&mut self"),
+            _ => None,
+        }
+    }
+
+    fn enum_variant(&mut self, variant: &EnumVariant) {
+        self.name(variant.name);
+        match &variant.fields {
+            StructFields::Named(fields) => {
+                self.write(" { ");
+                self.comma_separated(fields, |printer, field| {
+                    printer.name(field.name);
+                    printer.write(": ");
+                    printer.ty(&field.field_type);
+                });
+                self.write(" }");
+            }
+            StructFields::Tuple(types) => {
+                self.write("(");
+                self.comma_separated(types, Self::ty);
+                self.write(")");
+            }
+            StructFields::Unit => {}
+        }
+        if let Some(discriminant) = &variant.discriminant {
+            self.write(" = ");
+            self.expr(discriminant);
+        }
+    }
+
+    fn visibility(&mut self, visibility: Visibility) {
+        if visibility == Visibility::Public {
+            self.write("pub ");
+        }
+    }
+
+    fn generic_params(&mut self, generics: &[GenericParam]) {
+        if generics.is_empty() {
+            return;
+        }
+        self.write("<");
+        self.comma_separated(generics, |printer, param| {
+            printer.name(param.name);
+            for (index, bound) in param.bounds.iter().enumerate() {
+                printer.write(if index == 0 { ": " } else { " + " });
+                printer.ty(bound);
+            }
+            if let Some(default) = &param.default {
+                printer.write(" = ");
+                printer.ty(default);
+            }
+        });
+        self.close_angle();
+    }
+
+    /// `>` closing generic arguments; `>>` would lex as a shift
+    fn close_angle(&mut self) {
+        if self.output.ends_with('>') {
+            self.write(" ");
+        }
+        self.write(">");
+    }
+
+    // Attributes
+
+    /// Attributes on their own lines, with doc attributes written as `///` comments
+    ///
+    /// Doc comments next to each other merge into one attribute when parsed, so a doc
+    /// attribute right after another, or with text a comment cannot carry, is written
+    /// as `@doc("...")`.
+    fn attributes(&mut self, attributes: &[Attribute]) {
+        let mut after_doc_comment = false;
+        for attribute in attributes {
+            match self.doc_text(attribute).filter(|text| !after_doc_comment && is_comment_text(text)) {
+                Some(text) => {
+                    for line in text.split('\n') {
+                        self.comment_line("///", line);
+                    }
+                    after_doc_comment = true;
+                }
+                None => {
+                    self.attribute(attribute);
+                    self.newline();
+                    after_doc_comment = false;
+                }
+            }
+        }
+    }
+
+    /// Text of a `@doc("...")` attribute, as the parser makes from doc comments
+    fn doc_text(&self, attribute: &Attribute) -> Option<String> {
+        if !self.interner.matches(attribute.name, "doc") {
+            return None;
+        }
+        match attribute.args.as_slice() {
+            [AttributeArg { key: None, value: Literal::String { value, raw: false, raw_delimiter: None }, .. }] => {
+                Some(self.resolve(*value))
+            }
+            _ => None,
+        }
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) {
+        self.write("@");
+        self.name(attribute.name);
+        if let Some(predicate) = &attribute.cfg {
+            self.write(&format!("({})", predicate));
+        }
+        if attribute.args.is_empty() {
+            return;
+        }
+        self.write("(");
+        self.comma_separated(&attribute.args, |printer, arg| {
+            if let Some(key) = arg.key {
+                printer.name(key);
+                printer.write(" = ");
+            }
+            printer.literal(&arg.value);
+        });
+        self.write(")");
+    }
+
+    /// `@memory` and `@performance` for a strategy or contract set on a function
+    /// without the attribute it is normally read from
+    fn contract_attributes(
+        &mut self,
+        attributes: &[Attribute],
+        performance_contract: Option<&PerformanceContract>,
+        allocation_strategy: Option<MemoryStrategy>,
+    ) {
+        let has_attribute = |name: &str| attributes.iter().any(|attribute| self.interner.matches(attribute.name, name));
+        let write_memory = allocation_strategy.filter(|_| !has_attribute("memory"));
+        let write_performance = performance_contract.filter(|_| !has_attribute("performance"));
+
+        if let Some(strategy) = write_memory {
+            self.write(&format!("@memory(strategy = \"{}\")", strategy_name(strategy)));
+            self.newline();
+        }
+        if let Some(contract) = write_performance {
+            let mut args = Vec::new();
+            if let Some(CpuBound::Cycles(cycles)) = contract.cpu_bound {
+                args.push(format!("max_cost = {}", cycles));
+            }
+            if let Some(MemoryBound::Bytes(bytes)) = contract.memory_bound {
+                args.push(format!("max_memory = {}", bytes));
+            }
+            if let Some(latency) = &contract.latency_bound {
+                args.push(format!("max_latency_ms = {}", latency.max_latency.as_millis()));
+            }
+            if contract.deterministic {
+                args.push("deterministic = true".to_string());
+            }
+            self.write(&format!("@performance({})", args.join(", ")));
+            self.newline();
+        }
+    }
+
+    // Statements
+
+    /// `{ statements }` with one statement per line, then the trailing expression
+    fn block(&mut self, statements: &[Stmt], trailing_expr: Option<&Expr>) {
+        if statements.is_empty() && trailing_expr.is_none() {
+            self.write("{}");
+            return;
+        }
+        self.write("{");
+        self.depth += 1;
+        for stmt in statements {
+            self.newline();
+            self.stmt(stmt);
+        }
+        if let Some(trailing_expr) = trailing_expr {
+            self.newline();
+            self.expr(trailing_expr);
+        }
+        self.depth -= 1;
+        self.newline();
+        self.write("}");
+    }
+
+    /// A block expression, or any other expression as the value of a block
+    fn block_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Block { statements, trailing_expr, .. } => self.block(statements, trailing_expr.as_deref()),
+            expr => self.block(&[], Some(expr)),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression { expr, .. } => {
+                self.expr(expr);
+                self.write(";");
+            }
+            Stmt::Let { pattern, type_annotation, initializer, is_mutable, .. } => {
+                self.write(if *is_mutable { "let mut " } else { "let " });
+                self.pattern(pattern);
+                if let Some(type_annotation) = type_annotation {
+                    self.write(": ");
+                    self.ty(type_annotation);
+                }
+                if let Some(initializer) = initializer {
+                    self.write(" = ");
+                    self.expr(initializer);
+                }
+                self.write(";");
+            }
+            Stmt::Assignment { target, value, .. } => {
+                self.expr_at(target, ASSIGN, Follow::Operator);
+                self.write(" = ");
+                self.expr(value);
+                self.write(";");
+            }
+            Stmt::CompoundAssignment { target, op, value, .. } => {
+                self.expr_at(target, ASSIGN, Follow::Operator);
+                self.write(&format!(" {}= ", binary_symbol(*op)));
+                self.expr(value);
+                self.write(";");
+            }
+            Stmt::If { condition, then_block, else_block, .. } => {
+                self.write("if ");
+                self.condition(condition);
+                self.write(" ");
+                self.block(then_block, None);
+                if let Some(else_block) = else_block {
+                    self.write(" else ");
+                    match else_block.as_ref() {
+                        else_if @ Stmt::If { .. } => self.stmt(else_if),
+                        Stmt::Block { statements, .. } => self.block(statements, None),
+                        other => self.block(std::slice::from_ref(other), None),
+                    }
+                }
+            }
+            Stmt::While { condition, body, .. } => {
+                self.write("while ");
+                self.condition(condition);
+                self.write(" ");
+                self.block(body, None);
+            }
+            Stmt::For { pattern, iterable, body, .. } => {
+                self.write("for ");
+                self.pattern(pattern);
+                self.write(" in ");
+                self.condition(iterable);
+                self.write(" ");
+                self.block(body, None);
+            }
+            Stmt::Loop { label, body, .. } => {
+                self.label(*label);
+                self.write("loop ");
+                self.loop_labels.extend(*label);
+                self.block(body, None);
+                if label.is_some() {
+                    self.loop_labels.pop();
+                }
+            }
+            Stmt::Match { expr, arms, .. } => self.match_expr(expr, arms),
+            Stmt::Break { label, expr, .. } => {
+                self.write("break");
+                self.jump_target(*label, expr.as_ref());
+                self.write(";");
+            }
+            Stmt::Continue { label, .. } => {
+                self.write("continue");
+                if let Some(label) = label {
+                    self.write(" ");
+                    self.name(*label);
+                }
+                self.write(";");
+            }
+            Stmt::Return { expr, .. } => {
+                self.write("return");
+                if let Some(expr) = expr {
+                    self.write(" ");
+                    self.expr(expr);
+                }
+                self.write(";");
+            }
+            Stmt::Block { statements, .. } => self.block(statements, None),
+            Stmt::Region { name, size_hint, body, .. } => {
+                if let Some(size_hint) = size_hint {
+                    self.write(&format!("@memory(size_hint = {})", size_hint));
+                    self.newline();
+                }
+                self.write("region ");
+                // Region names that are not plain identifiers were written as strings
+                let name = self.resolve(*name);
+                if is_identifier(&name) {
+                    self.write(&name);
+                } else {
+                    self.write(&string_literal(&name, false, None));
+                }
+                self.write(" ");
+                self.block(body, None);
+            }
+            Stmt::Item { item, .. } => self.item(item),
+            Stmt::Empty { .. } => self.write(";"),
+        }
+    }
+
+    /// `label: ` in front of a loop
+    fn label(&mut self, label: Option<InternedString>) {
+        if let Some(label) = label {
+            self.name(label);
+            self.write(": ");
+        }
+    }
+
+    /// The label and value after `break`
+    fn jump_target(&mut self, label: Option<InternedString>, value: Option<&Expr>) {
+        if let Some(label) = label {
+            self.write(" ");
+            self.name(label);
+        }
+        if let Some(value) = value {
+            self.write(" ");
+            // Without a label, the label of an enclosing loop right after `break` would be read as one
+            if label.is_none() && leading_name(value).is_some_and(|name| self.loop_labels.contains(&name)) {
+                self.parenthesized(value);
+            } else {
+                self.expr(value);
+            }
+        }
+    }
+
+    /// The head of an if, while, for or match, where `Name {` opens the body
+    fn condition(&mut self, expr: &Expr) {
+        self.with_struct_literals(false, |printer| printer.expr_at(expr, ASSIGN, Follow::OperandStart));
+    }
+
+    fn match_expr(&mut self, expr: &Expr, arms: &[MatchArm]) {
+        self.write("match ");
+        self.condition(expr);
+        if arms.is_empty() {
+            self.write(" {}");
+            return;
+        }
+        self.write(" {");
+        self.depth += 1;
+        for arm in arms {
+            self.newline();
+            self.pattern(&arm.pattern);
+            if let Some(guard) = &arm.guard {
+                self.write(" if ");
+                self.expr(guard);
+            }
+            self.write(" => ");
+            match &arm.body {
+                Expr::Block { statements, trailing_expr, .. } => self.block(statements, trailing_expr.as_deref()),
+                body => {
+                    self.expr(body);
+                    self.write(",");
+                }
+            }
+        }
+        self.depth -= 1;
+        self.newline();
+        self.write("}");
+    }
+
+    // Expressions
+
+    fn expr(&mut self, expr: &Expr) {
+        self.expr_at(expr, ASSIGN, Follow::Nothing);
+    }
+
+    fn parenthesized(&mut self, expr: &Expr) {
+        self.write("(");
+        self.with_struct_literals(true, |printer| printer.expr(expr));
+        self.write(")");
+    }
+
+    /// Print `expr` as an operand binding at least as tightly as `min_precedence`,
+    /// followed by `follow`, adding parentheses where it would otherwise parse differently
+    fn expr_at(&mut self, expr: &Expr, min_precedence: Precedence, follow: Follow) {
+        let needs_parens = precedence(expr) < min_precedence
+            || (is_open_ended(expr) && follow != Follow::Nothing)
+            || (matches!(expr, Expr::Range { end: None, .. }) && follow == Follow::OperandStart)
+            || (matches!(expr, Expr::StructInit { .. }) && !self.struct_literals);
+        if needs_parens {
+            self.parenthesized(expr);
+            return;
+        }
+
+        match expr {
+            Expr::Literal { literal, .. } => self.literal(literal),
+            Expr::Identifier { name, .. } => self.name(*name),
+            Expr::Path { segments, .. } => self.path(segments),
+            Expr::Binary { left, op, right, .. } => {
+                let precedence = binary_precedence(*op);
+                let op_follow = if binary_starts_operand(*op) { Follow::OperandStart } else { Follow::Operator };
+                self.expr_at(left, precedence, op_follow);
+                self.write(&format!(" {} ", binary_symbol(*op)));
+                self.expr_at(right, precedence + 1, follow);
+            }
+            Expr::Unary { op, expr: operand, .. } => {
+                self.write(unary_symbol(*op));
+                self.prefix_operand(*op == UnaryOp::AddressOf, operand, follow);
+            }
+            Expr::Reference { is_mutable, expr: operand, .. } => {
+                self.write(if *is_mutable { "&mut " } else { "&" });
+                self.prefix_operand(!*is_mutable, operand, follow);
+            }
+            Expr::Dereference { expr: operand, .. } => {
+                self.write("*");
+                self.prefix_operand(false, operand, follow);
+            }
+            Expr::Box { expr: operand, .. } => {
+                self.write("box ");
+                self.prefix_operand(false, operand, follow);
+            }
+            Expr::Call { callee, args, .. } => {
+                self.expr_at(callee, POSTFIX, Follow::Operator);
+                self.write("(");
+                self.with_struct_literals(true, |printer| printer.comma_separated(args, Self::expr));
+                self.write(")");
+            }
+            Expr::MethodCall { receiver, method, args, .. } => {
+                self.receiver(receiver);
+                self.write(".");
+                self.name(*method);
+                self.write("(");
+                self.comma_separated(args, Self::expr);
+                self.write(")");
+            }
+            Expr::FieldAccess { object, field, .. } => {
+                self.receiver(object);
+                self.write(".");
+                self.name(*field);
+            }
+            Expr::Index { object, index, .. } => {
+                self.expr_at(object, POSTFIX, Follow::Operator);
+                self.write("[");
+                self.with_struct_literals(true, |printer| printer.expr(index));
+                self.write("]");
+            }
+            Expr::Try { expr: operand, .. } => {
+                self.expr_at(operand, POSTFIX, Follow::Operator);
+                self.write("?");
+            }
+            Expr::Await { expr: operand, .. } => {
+                self.receiver(operand);
+                self.write(".await");
+            }
+            Expr::Cast { expr: operand, target_type, .. } => {
+                self.expr_at(operand, CAST, Follow::Operator);
+                self.write(" as ");
+                self.ty(target_type);
+            }
+            Expr::Parenthesized { expr: inner, .. } => self.parenthesized(inner),
+            Expr::Array { elements, .. } => {
+                self.write("[");
+                self.comma_separated(elements, Self::expr);
+                self.write("]");
+            }
+            Expr::ArrayRepeat { value, count, .. } => {
+                self.write("[");
+                self.expr(value);
+                self.write("; ");
+                self.expr(count);
+                self.write("]");
+            }
+            Expr::Tuple { elements, .. } => {
+                self.write("(");
+                self.with_struct_literals(true, |printer| printer.comma_separated(elements, Self::expr));
+                self.write(if elements.len() == 1 { ",)" } else { ")" });
+            }
+            Expr::StructInit { path, fields, .. } => {
+                self.path(path);
+                if fields.is_empty() {
+                    self.write(" {}");
+                    return;
+                }
+                self.write(" { ");
+                self.comma_separated(fields, Self::field_init);
+                self.write(" }");
+            }
+            Expr::Range { start, end, inclusive, .. } => {
+                if let Some(start) = start {
+                    self.expr_at(start, SHIFT, Follow::Operator);
+                }
+                self.write(if *inclusive { "..=" } else { ".." });
+                if let Some(end) = end {
+                    self.expr_at(end, SHIFT, follow);
+                }
+            }
+            Expr::Closure { is_move, params, return_type, body, .. } => {
+                if *is_move {
+                    self.write("move ");
+                }
+                if params.is_empty() {
+                    self.write("||");
+                } else {
+                    self.write("|");
+                    self.comma_separated(params, Self::closure_param);
+                    self.write("|");
+                }
+                match return_type {
+                    Some(return_type) => {
+                        self.write(" -> ");
+                        self.ty(return_type);
+                        self.write(" ");
+                        self.block_expr(body);
+                    }
+                    None => {
+                        self.write(" ");
+                        self.expr(body);
+                    }
+                }
+            }
+            Expr::Block { statements, trailing_expr, .. } => self.block(statements, trailing_expr.as_deref()),
+            Expr::If { condition, then_block, else_block, .. } => match else_block {
+                Some(else_block) if !is_block_if(then_block, Some(else_block)) => {
+                    self.expr_at(condition, LOGICAL_OR, Follow::Operator);
+                    self.write(" ? ");
+                    self.expr(then_block);
+                    self.write(" : ");
+                    self.expr_at(else_block, TERNARY, follow);
+                }
+                _ => {
+                    self.write("if ");
+                    self.condition(condition);
+                    self.write(" ");
+                    self.block_expr(then_block);
+                    if let Some(else_block) = else_block {
+                        self.write(" else ");
+                        self.block_expr(else_block);
+                    }
+                }
+            },
+            Expr::Match { expr: scrutinee, arms, .. } => self.match_expr(scrutinee, arms),
+            Expr::Loop { label, body, .. } => {
+                self.label(*label);
+                self.write("loop ");
+                self.block_expr(body);
+            }
+            Expr::While { condition, body, .. } => {
+                self.write("while ");
+                self.condition(condition);
+                self.write(" ");
+                self.block_expr(body);
+            }
+            Expr::For { pattern, iterator, body, .. } => {
+                self.write("for ");
+                self.pattern(pattern);
+                self.write(" in ");
+                self.condition(iterator);
+                self.write(" ");
+                self.block_expr(body);
+            }
+            Expr::Break { label, value, .. } => {
+                self.write("break");
+                self.jump_target(*label, value.as_deref());
+            }
+            Expr::Continue { label, .. } => {
+                self.write("continue");
+                if let Some(label) = label {
+                    self.write(" ");
+                    self.name(*label);
+                }
+            }
+            Expr::Return { value, .. } => {
+                self.write("return");
+                if let Some(value) = value {
+                    self.write(" ");
+                    self.expr(value);
+                }
+            }
+            Expr::Macro { name, args, .. } => {
+                self.name(*name);
+                self.write("!(");
+                for (index, token) in args.iter().filter_map(token_source).enumerate() {
+                    if index > 0 && token != "," && token != ";" {
+                        self.write(" ");
+                    }
+                    self.write(&token);
+                }
+                self.write(")");
+            }
+        }
+    }
+
+    /// Operand of a prefix operator; `& &x` keeps its space, as `&&` is one token
+    fn prefix_operand(&mut self, after_ampersand: bool, operand: &Expr, follow: Follow) {
+        let starts_with_ampersand = matches!(
+            operand,
+            Expr::Unary { op: UnaryOp::AddressOf | UnaryOp::MutableRef, .. } | Expr::Reference { .. }
+        );
+        if after_ampersand && starts_with_ampersand && precedence(operand) >= UNARY {
+            self.write(" ");
+        }
+        self.expr_at(operand, UNARY, follow);
+    }
+
+    /// Object of a field access or method call; `1.x` would lex as a malformed float
+    fn receiver(&mut self, object: &Expr) {
+        match object {
+            Expr::Literal { literal: Literal::Integer { base: NumberBase::Decimal, suffix: None, .. }, .. } => {
+                self.parenthesized(object);
+            }
+            object => self.expr_at(object, POSTFIX, Follow::Operator),
+        }
+    }
+
+    fn field_init(&mut self, field: &FieldInit) {
+        self.name(field.name);
+        if let Some(value) = &field.value {
+            self.write(": ");
+            self.expr(value);
+        }
+    }
+
+    fn closure_param(&mut self, param: &Parameter) {
+        // `|` ends the parameter list, and `&` would take the rest of it as its pattern
+        match &param.pattern {
+            pattern @ (Pattern::Or { .. } | Pattern::Reference { .. }) => {
+                self.write("(");
+                self.pattern(pattern);
+                self.write(")");
+            }
+            pattern => self.pattern(pattern),
+        }
+        if let Some(type_annotation) = &param.type_annotation {
+            self.write(": ");
+            self.ty(type_annotation);
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Integer { value, suffix, .. } | Literal::Float { value, suffix } => {
+                self.write(value);
+                if let Some(suffix) = suffix {
+                    self.write(suffix.name());
+                }
+            }
+            Literal::String { value, raw, raw_delimiter } => {
+                let value = self.resolve(*value);
+                self.write(&string_literal(&value, *raw, *raw_delimiter));
+            }
+            Literal::Char(ch) => self.write(&char_literal(*ch)),
+            Literal::Bool(value) => self.write(if *value { "true" } else { "false" }),
+            Literal::Null => self.write("null"),
+        }
+    }
+
+    // Types
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Primitive { kind, .. } => self.write(kind.name()),
+            Type::Path { segments, generics, .. } => {
+                self.path(segments);
+                if !generics.is_empty() {
+                    self.write("<");
+                    self.comma_separated(generics, Self::ty);
+                    self.close_angle();
+                }
+            }
+            Type::Array { element_type, size, .. } => {
+                self.write("[");
+                self.ty(element_type);
+                self.write("; ");
+                self.expr(size);
+                self.write("]");
+            }
+            Type::Slice { element_type, .. } => {
+                self.write("[");
+                self.ty(element_type);
+                self.write("]");
+            }
+            Type::Tuple { types, .. } => {
+                self.write("(");
+                self.comma_separated(types, Self::ty);
+                self.write(if types.len() == 1 { ",)" } else { ")" });
+            }
+            Type::Function { params, return_type, is_variadic, .. } => {
+                self.write("fn(");
+                self.comma_separated(params, Self::ty);
+                if *is_variadic {
+                    self.write(if params.is_empty() { "..." } else { ", ..." });
+                }
+                self.write(")");
+                // Without `->` the parser returns the unit tuple
+                if !matches!(return_type.as_ref(), Type::Tuple { types, .. } if types.is_empty()) {
+                    self.write(" -> ");
+                    self.ty(return_type);
+                }
+            }
+            Type::Reference { is_mutable, target_type, .. } => {
+                self.write(if *is_mutable { "&mut " } else { "&" });
+                if !*is_mutable && matches!(target_type.as_ref(), Type::Reference { .. }) {
+                    self.write(" ");
+                }
+                self.ty(target_type);
+            }
+            Type::Pointer { is_mutable, target_type, memory_strategy, .. } => match strategy_wrapper(*memory_strategy) {
+                Some(wrapper) => {
+                    self.write(wrapper);
+                    self.write("<");
+                    self.ty(target_type);
+                    self.close_angle();
+                }
+                None => {
+                    self.write(if *is_mutable { "*mut " } else { "*const " });
+                    self.ty(target_type);
+                }
+            },
+            Type::Generic { name, .. } => self.name(*name),
+            Type::Inferred { .. } => self.write("_"),
+            Type::Never { .. } => self.write("!"),
+        }
+    }
+
+    // Patterns
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard { .. } => self.write("_"),
+            Pattern::Identifier { name, is_mutable, .. } => {
+                if *is_mutable {
+                    self.write("mut ");
+                }
+                self.name(*name);
+            }
+            Pattern::Literal { literal, .. } => self.literal(literal),
+            Pattern::Tuple { patterns, .. } => {
+                self.write("(");
+                self.comma_separated(patterns, Self::pattern);
+                self.write(if patterns.len() == 1 { ",)" } else { ")" });
+            }
+            Pattern::Array { patterns, .. } => {
+                self.write("[");
+                self.comma_separated(patterns, Self::pattern);
+                self.write("]");
+            }
+            Pattern::Struct { path, fields, rest, .. } => {
+                self.path(path);
+                if fields.is_empty() && !*rest {
+                    self.write(" {}");
+                    return;
+                }
+                self.write(" { ");
+                self.comma_separated(fields, |printer, field| {
+                    printer.name(field.name);
+                    if let Some(pattern) = &field.pattern {
+                        printer.write(": ");
+                        printer.pattern(pattern);
+                    }
+                });
+                if *rest {
+                    self.write(if fields.is_empty() { ".." } else { ", .." });
+                }
+                self.write(" }");
+            }
+            Pattern::Enum { path, patterns, .. } => {
+                self.path(path);
+                if let Some(patterns) = patterns {
+                    self.write("(");
+                    self.comma_separated(patterns, Self::pattern);
+                    self.write(")");
+                }
+            }
+            Pattern::Reference { is_mutable, pattern, .. } => {
+                self.write(if *is_mutable { "&mut " } else { "&" });
+                self.pattern(pattern);
+            }
+            Pattern::Range { start, end, inclusive, .. } => {
+                if let Some(start) = start {
+                    self.range_bound(start);
+                }
+                self.write(if *inclusive { "..=" } else { ".." });
+                if let Some(end) = end {
+                    self.range_bound(end);
+                }
+            }
+            Pattern::Or { patterns, .. } => {
+                for (index, alternative) in patterns.iter().enumerate() {
+                    if index > 0 {
+                        self.write(" | ");
+                    }
+                    // `&` takes the alternatives after it into its pattern
+                    let is_last = index + 1 == patterns.len();
+                    match alternative {
+                        Pattern::Or { .. } => self.grouped_pattern(alternative),
+                        Pattern::Reference { .. } if !is_last => self.grouped_pattern(alternative),
+                        _ => self.pattern(alternative),
+                    }
+                }
+            }
+        }
+    }
+
+    fn range_bound(&mut self, bound: &Pattern) {
+        match bound {
+            Pattern::Reference { .. } | Pattern::Range { .. } | Pattern::Or { .. } => self.grouped_pattern(bound),
+            bound => self.pattern(bound),
+        }
+    }
+
+    /// A pattern in parentheses, which group without adding a node
+    fn grouped_pattern(&mut self, pattern: &Pattern) {
+        self.write("(");
+        self.pattern(pattern);
+        self.write(")");
+    }
+}
+
+/// Binding strength of an expression's outermost operator
+fn precedence(expr: &Expr) -> Precedence {
+    match expr {
+        Expr::Binary { op, .. } => binary_precedence(*op),
+        Expr::If { then_block, else_block, .. } if else_block.is_some() && !is_block_if(then_block, else_block.as_deref()) => TERNARY,
+        Expr::Range { .. } => RANGE,
+        Expr::Cast { .. } => CAST,
+        Expr::Unary { .. } | Expr::Reference { .. } | Expr::Dereference { .. } | Expr::Box { .. } => UNARY,
+        Expr::Call { .. } | Expr::MethodCall { .. } | Expr::FieldAccess { .. } | Expr::Index { .. }
+        | Expr::Try { .. } | Expr::Await { .. } | Expr::StructInit { .. } => POSTFIX,
+        _ => PRIMARY,
+    }
+}
+
+fn binary_precedence(op: BinaryOp) -> Precedence {
+    match op {
+        BinaryOp::Assign => ASSIGN,
+        BinaryOp::LogicalOr => LOGICAL_OR,
+        BinaryOp::LogicalAnd => LOGICAL_AND,
+        BinaryOp::BitwiseOr => BITWISE_OR,
+        BinaryOp::BitwiseXor => BITWISE_XOR,
+        BinaryOp::BitwiseAnd => BITWISE_AND,
+        BinaryOp::Equal | BinaryOp::NotEqual => EQUALITY,
+        BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => RELATIONAL,
+        BinaryOp::LeftShift | BinaryOp::RightShift => SHIFT,
+        BinaryOp::Add | BinaryOp::Subtract => ADDITIVE,
+        BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => MULTIPLICATIVE,
+    }
+}
+
+fn binary_symbol(op: BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::Add => "+",
+        BinaryOp::Subtract => "-",
+        BinaryOp::Multiply => "*",
+        BinaryOp::Divide => "/",
+        BinaryOp::Modulo => "%",
+        BinaryOp::BitwiseAnd => "&",
+        BinaryOp::BitwiseOr => "|",
+        BinaryOp::BitwiseXor => "^",
+        BinaryOp::LeftShift => "<<",
+        BinaryOp::RightShift => ">>",
+        BinaryOp::LogicalAnd => "&&",
+        BinaryOp::LogicalOr => "||",
+        BinaryOp::Equal => "==",
+        BinaryOp::NotEqual => "!=",
+        BinaryOp::Less => "<",
+        BinaryOp::LessEqual => "<=",
+        BinaryOp::Greater => ">",
+        BinaryOp::GreaterEqual => ">=",
+        BinaryOp::Assign => "=",
+    }
+}
+
+/// Whether the operator's token could also start an operand, e.g. the `-` of `-x`
+fn binary_starts_operand(op: BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::BitwiseAnd
+            | BinaryOp::BitwiseOr | BinaryOp::LogicalOr
+    )
+}
+
+fn unary_symbol(op: UnaryOp) -> &'static str {
+    match op {
+        UnaryOp::Not => "!",
+        UnaryOp::Negate => "-",
+        UnaryOp::Plus => "+",
+        UnaryOp::BitwiseNot => "~",
+        UnaryOp::Dereference => "*",
+        UnaryOp::AddressOf => "&",
+        UnaryOp::MutableRef => "&mut ",
+    }
+}
+
+/// Expressions whose body or value extends as far to the right as it can
+fn is_open_ended(expr: &Expr) -> bool {
+    matches!(expr, Expr::Closure { .. } | Expr::Return { .. } | Expr::Break { .. })
+}
+
+/// Whether an if expression is written with blocks rather than as `a ? b : c`
+///
+/// Only statements have `else if`, so an if expression with another in its else
+/// branch is written as a chain of ternaries.
+fn is_block_if(then_block: &Expr, else_block: Option<&Expr>) -> bool {
+    let is_block = |expr: &Expr| matches!(expr, Expr::Block { .. });
+    is_block(then_block) && else_block.is_none_or(is_block)
+}
+
+/// The name `expr` starts with, if its first token is an identifier
+fn leading_name(expr: &Expr) -> Option<InternedString> {
+    match expr {
+        Expr::Identifier { name, .. } | Expr::Macro { name, .. } => Some(*name),
+        Expr::Path { segments: names, .. } | Expr::StructInit { path: names, .. } => names.first().copied(),
+        Expr::Binary { left: first, .. }
+        | Expr::Call { callee: first, .. }
+        | Expr::MethodCall { receiver: first, .. }
+        | Expr::FieldAccess { object: first, .. }
+        | Expr::Index { object: first, .. }
+        | Expr::Cast { expr: first, .. }
+        | Expr::Try { expr: first, .. }
+        | Expr::Await { expr: first, .. }
+        | Expr::Range { start: Some(first), .. } => leading_name(first),
+        Expr::If { condition, .. } if precedence(expr) == TERNARY => leading_name(condition),
+        _ => None,
+    }
+}
+
+/// Whether `name` lexes as a single identifier rather than a keyword or something else
+fn is_identifier(name: &str) -> bool {
+    let mut lexer = Lexer::new(name, 0);
+    let first_is_name = matches!(lexer.next_token(), Ok(Token { token_type: TokenType::Identifier(ident), .. }) if ident == name);
+    first_is_name && matches!(lexer.next_token(), Ok(token) if token.token_type == TokenType::Eof)
+}
+
+/// Whether doc text survives being written as `///` comments and read back
+///
+/// The parser drops leading whitespace, a leading `*`, trailing whitespace and
+/// blank lines at either end of a doc comment.
+fn is_comment_text(text: &str) -> bool {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let edge_blank = lines.len() > 1 && (lines[0].is_empty() || lines[lines.len() - 1].is_empty());
+    !edge_blank
+        && lines.iter().all(|line| {
+            line.trim() == *line && !line.starts_with('*') && !line.contains('\r')
+        })
+}
+
+/// Name of a strategy in `@memory(strategy = "...")`
+fn strategy_name(strategy: MemoryStrategy) -> &'static str {
+    match strategy {
+        MemoryStrategy::Manual => "manual",
+        MemoryStrategy::SmartPtr => "smartptr",
+        MemoryStrategy::Linear => "linear",
+        MemoryStrategy::Region => "region",
+        MemoryStrategy::Stack => "stack",
+        MemoryStrategy::Inferred => "inferred",
+    }
+}
+
+/// Wrapper type such as `LinearPtr<T>` for pointers managed by a strategy
+fn strategy_wrapper(strategy: MemoryStrategy) -> Option<&'static str> {
+    match strategy {
+        MemoryStrategy::Linear => Some("LinearPtr"),
+        MemoryStrategy::SmartPtr => Some("SmartPtr"),
+        MemoryStrategy::Region => Some("RegionPtr"),
+        MemoryStrategy::Stack => Some("StackPtr"),
+        MemoryStrategy::Manual | MemoryStrategy::Inferred => None,
+    }
+}
+
+/// String literal with the given contents; raw strings keep their `#` delimiters
+fn string_literal(value: &str, raw: bool, raw_delimiter: Option<usize>) -> String {
+    if raw {
+        let hashes = "#".repeat(raw_delimiter.unwrap_or(0));
+        return format!("r{}\"{}\"{}", hashes, value, hashes);
+    }
+    let mut literal = String::with_capacity(value.len() + 2);
+    literal.push('"');
+    for ch in value.chars() {
+        push_escaped(&mut literal, ch, '"');
+    }
+    literal.push('"');
+    literal
+}
+
+fn char_literal(ch: char) -> String {
+    let mut literal = String::from("'");
+    push_escaped(&mut literal, ch, '\'');
+    literal.push('\'');
+    literal
+}
+
+/// Push `ch` as it is written inside a literal delimited by `quote`, escaping the
+/// characters the lexer reads back from escape sequences
+fn push_escaped(literal: &mut String, ch: char, quote: char) {
+    match ch {
+        '\n' => literal.push_str("\\n"),
+        '\r' => literal.push_str("\\r"),
+        '\t' => literal.push_str("\\t"),
+        '\\' => literal.push_str("\\\\"),
+        '\0' => literal.push_str("\\0"),
+        ch if ch == quote => {
+            literal.push('\\');
+            literal.push(ch);
+        }
+        ch if ch.is_control() => literal.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+        ch => literal.push(ch),
+    }
+}
+
+/// Source text of a token in a macro invocation; comments have none
+fn token_source(token: &TokenType) -> Option<String> {
+    Some(match token {
+        TokenType::Identifier(name) => name.to_string(),
+        TokenType::Integer { value, suffix, .. } | TokenType::Float { value, suffix } => {
+            format!("{}{}", value, suffix.as_deref().unwrap_or(""))
+        }
+        TokenType::String { value, raw, raw_delimiter } => string_literal(value, *raw, *raw_delimiter),
+        TokenType::Char(ch) => char_literal(*ch),
+        TokenType::Bool(value) => value.to_string(),
+        TokenType::Null => "null".to_string(),
+        TokenType::Eof
+        | TokenType::LineComment(_)
+        | TokenType::BlockComment(_)
+        | TokenType::DocLineComment(_)
+        | TokenType::DocBlockComment(_)
+        | TokenType::InnerDocLineComment(_)
+        | TokenType::InnerDocBlockComment(_) => return None,
+        other => other.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{NodeId, Span};
+    use crate::lexer::Position;
+
+    fn span() -> Span {
+        Span::single(Position::new(1, 1, 0, 0))
+    }
+
+    fn ident(interner: &StringInterner, name: &str) -> Expr {
+        Expr::Identifier { name: interner.intern(name), span: span(), id: NodeId::DUMMY }
+    }
+
+    fn binary(left: Expr, op: BinaryOp, right: Expr) -> Expr {
+        Expr::Binary { left: Box::new(left), op, right: Box::new(right), span: span(), id: NodeId::DUMMY }
+    }
+
+    #[test]
+    fn test_precedence_adds_parentheses() {
+        let interner = StringInterner::new();
+        let sum = binary(ident(&interner, "a"), BinaryOp::Add, ident(&interner, "b"));
+        let product = binary(sum.clone(), BinaryOp::Multiply, ident(&interner, "c"));
+        let difference = binary(ident(&interner, "c"), BinaryOp::Subtract, sum);
+
+        let mut printer = PrettyPrinter::new(&interner);
+        assert_eq!(printer.print_expr(&product), "(a + b) * c");
+        assert_eq!(printer.print_expr(&difference), "c - (a + b)");
+    }
+
+    #[test]
+    fn test_expressions_without_parser_support() {
+        let interner = StringInterner::new();
+        let value = || Box::new(ident(&interner, "value"));
+        let mut printer = PrettyPrinter::new(&interner);
+
+        let tried = Expr::Try { expr: value(), span: span(), id: NodeId::DUMMY };
+        assert_eq!(printer.print_expr(&Expr::Await { expr: Box::new(tried), span: span(), id: NodeId::DUMMY }), "value?.await");
+        assert_eq!(printer.print_expr(&Expr::Box { expr: value(), span: span(), id: NodeId::DUMMY }), "box value");
+
+        let args = vec![
+            TokenType::String { value: "{}\n".into(), raw: false, raw_delimiter: None },
+            TokenType::Comma,
+            TokenType::Identifier("x".into()),
+            TokenType::Plus,
+            TokenType::Integer { value: "1".into(), base: NumberBase::Decimal, suffix: None },
+        ];
+        let call = Expr::Macro { name: interner.intern("println"), args, span: span(), id: NodeId::DUMMY };
+        assert_eq!(printer.print_expr(&call), "println!(\"{}\\n\", x + 1)");
+    }
+
+    #[test]
+    fn test_break_value_named_like_a_label() {
+        let interner = StringInterner::new();
+        let outer = interner.intern("outer");
+        let value = Expr::Identifier { name: outer, span: span(), id: NodeId::DUMMY };
+        let exit = Stmt::Break { label: None, expr: Some(value), span: span(), id: NodeId::DUMMY };
+        assert_eq!(PrettyPrinter::new(&interner).print_stmt(&exit), "break outer;");
+
+        // Inside a loop labelled `outer` the name would be read back as the label
+        let stmt = Stmt::Loop { label: Some(outer), body: vec![exit], span: span(), id: NodeId::DUMMY };
+        assert_eq!(PrettyPrinter::new(&interner).print_stmt(&stmt), "outer: loop {\n    break (outer);\n}");
+    }
+
+    #[test]
+    fn test_indent_width() {
+        let interner = StringInterner::new();
+        let body = vec![Stmt::Break { label: Some(interner.intern("outer")), expr: None, span: span(), id: NodeId::DUMMY }];
+        let stmt = Stmt::Loop { label: Some(interner.intern("outer")), body, span: span(), id: NodeId::DUMMY };
+        assert_eq!(
+            PrettyPrinter::new(&interner).with_indent_width(2).print_stmt(&stmt),
+            "outer: loop {\n  break outer;\n}"
+        );
+        assert_eq!(
+            PrettyPrinter::new(&interner).with_tabs().print_stmt(&stmt),
+            "outer: loop {\n\tbreak outer;\n}"
+        );
+    }
+
+    #[test]
+    fn test_escapes() {
+        assert_eq!(string_literal("a\"b\\c\n\t\u{1b}", false, None), r#""a\"b\\c\n\t\u{1b}""#);
+        assert_eq!(string_literal("no \\ escapes", true, Some(1)), r###"r#"no \ escapes"#"###);
+        assert_eq!(char_literal('\''), r"'\''");
+        assert_eq!(char_literal('"'), "'\"'");
+    }
+}
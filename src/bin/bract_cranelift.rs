@@ -10,9 +10,12 @@
 //! - JIT compilation capabilities
 
 use bract::{
-    Parser,
-    semantic::SemanticAnalyzer,
-    codegen::cranelift::CraneliftCodeGenerator,
+    compile_source,
+    CompileOptions,
+    DiagnosticCode,
+    ast::SourceMap,
+    compile::EmitKind,
+    codegen::OptLevel,
     profiling::CycleProfiler,
 };
 use std::env;
@@ -36,6 +39,16 @@ struct Args {
     jit: bool,
     /// Optimization level (0-3)
     optimization: u8,
+    /// Target triple to cross-compile for (defaults to the host)
+    target: Option<String>,
+    /// Run the Cranelift IR verifier on generated functions
+    verify: bool,
+    /// Instrument functions with the runtime profiling hooks
+    profile: bool,
+    /// Emit DWARF line tables for source-level debugging
+    debug_info: bool,
+    /// Print the memory manager's allocation, leak and region reports
+    memory_report: bool,
 }
 
 impl Args {
@@ -61,20 +74,30 @@ impl Args {
         let mut stats = false;
         let mut jit = false;
         let mut optimization = 2;
+        let mut target = None;
+        let mut verify = false;
+        let mut profile = false;
+        let mut debug_info = false;
+        let mut memory_report = false;
         
         for (i, arg) in args.iter().enumerate().skip(2) {
             match arg.as_str() {
                 "-v" | "--verbose" => verbose = true,
                 "-s" | "--stats" => stats = true,
                 "-j" | "--jit" => jit = true,
+                "--verify" => verify = true,
+                "--profile" => profile = true,
+                "-g" | "--debug-info" => debug_info = true,
+                "--memory-report" => memory_report = true,
                 "-O0" => optimization = 0,
                 "-O1" => optimization = 1,
                 "-O2" => optimization = 2,
                 "-O3" => optimization = 3,
-                "-o" | "--output" => {
-                    if i + 1 < args.len() {
-                        output_file = PathBuf::from(&args[i + 1]);
-                    }
+                "-o" | "--output" if i + 1 < args.len() => {
+                    output_file = PathBuf::from(&args[i + 1]);
+                }
+                "-t" | "--target" if i + 1 < args.len() => {
+                    target = Some(args[i + 1].clone());
                 }
                 _ => {}
             }
@@ -87,11 +110,21 @@ impl Args {
             stats,
             jit,
             optimization,
+            target,
+            verify,
+            profile,
+            debug_info,
+            memory_report,
         })
     }
 }
 
 fn main() {
+    let cli_args: Vec<String> = env::args().collect();
+    if cli_args.get(1).map(String::as_str) == Some("--explain") {
+        process::exit(explain(cli_args.get(2).map(String::as_str)));
+    }
+    
     let args = match Args::parse() {
         Ok(args) => args,
         Err(e) => {
@@ -127,7 +160,7 @@ fn main() {
         println!("   Backend: Cranelift");
         
         if let Some(profile) = profile_result {
-            println!("🔄 Code generation cycles: {}", profile.cpu_cycles);
+            println!("🔄 Compilation cycles: {}", profile.cpu_cycles);
             if let Some(freq_ghz) = profile.cpu_freq_ghz() {
                 println!("⚡ Estimated CPU frequency: {:.2} GHz", freq_ghz);
             }
@@ -143,12 +176,12 @@ fn main() {
     }
 }
 
+/// Compile the input to an executable with `compile_source`, printing its diagnostics
 fn compile_native(args: &Args) -> Result<Option<bract::profiling::ProfilingResult>, String> {
     let start_time = Instant::now();
     
-    // Phase 1: Read source code
     if args.verbose {
-        println!("📖 Phase 1: Reading source code...");
+        println!("📖 Reading source code...");
     }
     
     let source_code = fs::read_to_string(&args.input_file)
@@ -156,175 +189,86 @@ fn compile_native(args: &Args) -> Result<Option<bract::profiling::ProfilingResul
     
     if args.verbose {
         println!("   Source code: {} characters", source_code.len());
+        println!("⚡ Compiling to native code...");
     }
     
-    // Phase 2: Lexical analysis and parsing
-    if args.verbose {
-        println!("🔍 Phase 2: Parsing...");
-    }
-    
-    let parse_start = Instant::now();
-    
-    let mut parser = Parser::new(&source_code, 0)
-        .map_err(|e| format!("Parser creation failed: {}", e))?;
-    let module = parser.parse_module()
-        .map_err(|e| format!("Parse error: {:?}", e))?;
-    
-    // Extract the string interner from the parser
-    let interner = parser.take_interner();
-    
-    if args.verbose {
-        println!("   Parsed {} items in {:?}", module.items.len(), parse_start.elapsed());
-    }
-    
-    // Phase 3: Semantic analysis
-    if args.verbose {
-        println!("🧠 Phase 3: Semantic analysis...");
-    }
-    
-    let semantic_start = Instant::now();
-    
-    let mut analyzer = SemanticAnalyzer::new();
-    let analysis_result = analyzer.analyze(&module);
-    
-    let symbol_table = match analysis_result.errors.is_empty() {
-        true => analysis_result.symbol_table,
-        false => {
-            let error_msg = analysis_result.errors
-                .into_iter()
-                .map(|e| format!("{:?}", e))
-                .collect::<Vec<_>>()
-                .join(", ");
-            return Err(format!("Semantic errors: {}", error_msg));
-        }
+    // -O0 is the debug-friendly mode: no optimizations, IR verification on
+    let options = CompileOptions {
+        target: args.target.clone(),
+        opt_level: match args.optimization {
+            0 => OptLevel::None,
+            1 | 2 => OptLevel::Speed,
+            _ => OptLevel::SpeedAndSize,
+        },
+        emit: EmitKind::Executable,
+        output: Some(args.output_file.clone()),
+        // Leaks are reported even without --memory-report
+        emit_memory_report: true,
+        debug_info: args.debug_info,
+        collect_metrics: args.stats,
+        verify: args.verify || args.optimization == 0,
+        profile_instrumentation: args.profile,
+        ..CompileOptions::default()
     };
     
-    if args.verbose {
-        println!("   Semantic analysis completed in {:?}", semantic_start.elapsed());
-    }
-    
-    // Phase 4: Native code generation with Cranelift
-    if args.verbose {
-        println!("⚡ Phase 4: Native code generation...");
-    }
-    
-    let codegen_start = Instant::now();
+    let file_name = args.input_file.display().to_string();
     let mut cycle_profiler = CycleProfiler::new();
     cycle_profiler.start();
-    
-    let mut code_generator = CraneliftCodeGenerator::new(symbol_table, interner)
-        .map_err(|e| format!("Failed to create code generator: {}", e))?;
-    
-    if args.verbose {
-        println!("   Target: {:?}", code_generator.target_triple());
-    }
-    
-    let object_code = code_generator.generate(&module)
-        .map_err(|e| format!("Code generation failed: {}", e))?;
-    
+    let result = compile_source(&source_code, &file_name, &options);
     let profile_result = cycle_profiler.stop();
     
-    if args.verbose {
-        println!("   Generated {} bytes of object code in {:?}", object_code.len(), codegen_start.elapsed());
-        println!("📊 Detailed profiling:");
-        print!("{}", profile_result.display());
+    let source_map = SourceMap::new(&file_name);
+    for diagnostic in &result.diagnostics {
+        eprintln!("{}", diagnostic.render_with_source(&source_map, &source_code));
     }
     
-    // Phase 5: Object file creation and linking
-    if args.verbose {
-        println!("🔗 Phase 5: Object file creation and linking...");
+    if let Some(report) = &result.memory_report {
+        if args.memory_report {
+            println!("{}", report.type_report);
+            println!("{}", report.summary);
+            println!("{}", report.leak_report);
+            println!("{}", report.region_report);
+            println!("{}", report.performance_report);
+        } else if !report.leaks.is_empty() {
+            eprintln!("{}", report.leak_report);
+        }
     }
     
-    let link_start = Instant::now();
-    
-    // Write object file
-    let object_path = args.output_file.with_extension("o");
-    fs::write(&object_path, &object_code)
-        .map_err(|e| format!("Failed to write object file: {}", e))?;
-    
-    if args.verbose {
-        println!("   Object file: {}", object_path.display());
+    let errors = result.errors().count();
+    if errors > 0 {
+        return Err(format!("{} error{}", errors, if errors == 1 { "" } else { "s" }));
     }
     
-    // Link to executable (platform-specific)
-    link_executable(&object_path, &args.output_file, args.verbose)?;
-    
-    if args.verbose {
-        println!("   Linked executable in {:?}", link_start.elapsed());
-    }
-    
-    // Clean up object file
-    let _ = fs::remove_file(&object_path);
-    
     if args.verbose {
+        println!("📊 Detailed profiling:");
+        print!("{}", profile_result.display());
         println!("   Total compilation time: {:?}", start_time.elapsed());
     }
+    if let Some(metrics) = result.metrics.as_ref().filter(|_| args.stats) {
+        println!("{}", metrics.summary());
+    }
     
     Ok(Some(profile_result))
 }
 
-fn link_executable(object_path: &PathBuf, output_path: &PathBuf, verbose: bool) -> Result<(), String> {
-    use std::process::Command;
-    
-    let mut cmd = if cfg!(windows) {
-        // Try LLD (LLVM linker) first, then fall back to Microsoft linker
-        if Command::new("lld-link").arg("--version").output().is_ok() {
-            let mut cmd = Command::new("lld-link");
-            cmd.arg("/ENTRY:main")
-               .arg("/SUBSYSTEM:CONSOLE")
-               .arg(format!("/OUT:{}", output_path.display()))
-               .arg("/OPT:REF")          // Remove unreferenced functions (speed)
-               .arg("/OPT:ICF")          // Identical COMDAT folding (speed) 
-               .arg("/DEBUG:NONE")       // No debug info (major speed boost)
-               .arg("/INCREMENTAL:NO")   // Disable incremental linking (speed)
-               .arg("/MACHINE:X64")      // Explicit target architecture
-               .arg("/NODEFAULTLIB")     // No default libraries (major speed boost!)
-               .arg(object_path)
-               .arg("native_runtime.o"); // ONLY our runtime - zero external deps
-            cmd
-        } else {
-            // Use Microsoft linker on Windows
-            let mut cmd = Command::new("link");
-            cmd.arg("/ENTRY:main")
-               .arg("/SUBSYSTEM:CONSOLE")
-               .arg(format!("/OUT:{}", output_path.display()))
-               .arg("/OPT:REF")          // Remove unreferenced functions (speed)
-               .arg("/OPT:ICF")          // Identical COMDAT folding (speed)
-               .arg("/DEBUG:NONE")       // No debug info (major speed boost) 
-               .arg("/INCREMENTAL:NO")   // Disable incremental linking (speed)
-               .arg("/MACHINE:X64")      // Explicit target architecture
-               .arg("/NODEFAULTLIB")     // No default libraries (major speed boost!)
-               .arg(object_path)
-               .arg("native_runtime.o"); // ONLY our runtime - zero external deps
-            cmd
-        }
-    } else {
-        // Use system linker on Unix-like systems
-        let mut cmd = Command::new("ld");
-        cmd.arg("-o")
-           .arg(output_path)
-           .arg("--gc-sections")        // Remove unused sections (speed)
-           .arg("--strip-all")          // Strip all symbols (speed)
-           .arg("--build-id=none")      // No build ID (speed)
-           .arg("--nostdlib")           // No standard library (major speed boost!)
-           .arg(object_path)
-           .arg("native_runtime.o");    // ONLY our runtime - zero external deps
-        cmd
+/// Print the explanation of a diagnostic code, returning the exit status
+fn explain(code: Option<&str>) -> i32 {
+    let Some(code) = code else {
+        eprintln!("Error: --explain requires a diagnostic code, e.g. E0120");
+        return 1;
     };
-    
-    if verbose {
-        println!("   Linking command: {:?}", cmd);
-    }
-    
-    let output = cmd.output()
-        .map_err(|e| format!("Failed to run linker: {}", e))?;
-    
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Linker failed: {}", stderr));
+    match DiagnosticCode::lookup(code) {
+        Some(code) => {
+            println!("{}: {}", code, code.summary());
+            println!();
+            print!("{}", code.explanation());
+            0
+        }
+        None => {
+            eprintln!("Error: unknown diagnostic code `{}`", code);
+            1
+        }
     }
-    
-    Ok(())
 }
 
 fn print_usage() {
@@ -332,13 +276,19 @@ fn print_usage() {
     println!();
     println!("USAGE:");
     println!("    bract_cranelift <input.bract> [OPTIONS]");
+    println!("    bract_cranelift --explain <CODE>");
     println!();
     println!("OPTIONS:");
     println!("    -o, --output <FILE>    Output executable [default: <input>]");
     println!("    -v, --verbose          Enable verbose output");
     println!("    -s, --stats            Show compilation statistics");
     println!("    -j, --jit              Enable JIT execution");
-    println!("    -O0, -O1, -O2, -O3     Optimization level [default: -O2]");
+    println!("    -O0, -O1, -O2, -O3     Optimization level [default: -O2]; -O0 also verifies IR");
+    println!("    --verify               Run the Cranelift IR verifier");
+    println!("    --profile              Call bract_profile_enter/exit around every function");
+    println!("    -g, --debug-info       Emit DWARF line tables for gdb/lldb");
+    println!("    --memory-report        Print allocations, leaks and hotspots after code generation");
+    println!("    -t, --target <TRIPLE>  Cross-compile for a target triple [default: host]");
     println!();
     println!("FEATURES:");
     println!("    ✅ Direct machine code generation (no C transpilation)");
@@ -350,6 +300,7 @@ fn print_usage() {
     println!("EXAMPLES:");
     println!("    bract_cranelift hello.bract");
     println!("    bract_cranelift hello.bract -v -s -O3");
+    println!("    bract_cranelift --explain E0230");
     println!("    bract_cranelift hello.bract --jit");
     println!("    bract_cranelift hello.bract -o hello_native");
 } 
\ No newline at end of file
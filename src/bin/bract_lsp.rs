@@ -9,13 +9,18 @@
 //! - Workspace symbol search
 //! - Document formatting and refactoring
 
-use bract::lsp::{LspServer, CompletionProvider, Position};
+use bract::lsp::{
+    LspServer, CancellationToken, CompletionItem, CompletionProvider, Diagnostic, FileEvent, FormattingOptions, Position,
+    ProgressParams, ProgressSink, Range, RequestError, WorkDoneProgress, MEMORY_REPORT_METHOD, SETTINGS_SECTION,
+};
+use bract::lsp::settings;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncBufReadExt, AsyncWriteExt, AsyncReadExt, BufReader};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex as AsyncMutex};
 use std::process;
 
 /// LSP request/response message
@@ -45,8 +50,14 @@ pub struct BractLspServer {
     completion_provider: Arc<CompletionProvider>,
     /// Request counter for generating IDs
     request_counter: Arc<Mutex<u64>>,
-    /// Active requests
-    active_requests: Arc<Mutex<HashMap<String, tokio::sync::oneshot::Sender<Message>>>>,
+    /// Cancellation tokens of the requests not answered yet, by id
+    active_requests: Arc<Mutex<HashMap<String, CancellationToken>>>,
+    /// Client accepts progress the server starts itself
+    work_done_progress: Arc<AtomicBool>,
+    /// Client sends settings changes only once they are registered for
+    configuration_registration: Arc<AtomicBool>,
+    /// Stops workspace indexing when the server shuts down
+    indexing: CancellationToken,
 }
 
 impl BractLspServer {
@@ -57,6 +68,9 @@ impl BractLspServer {
             completion_provider: Arc::new(CompletionProvider::new()),
             request_counter: Arc::new(Mutex::new(0)),
             active_requests: Arc::new(Mutex::new(HashMap::new())),
+            work_done_progress: Arc::new(AtomicBool::new(false)),
+            configuration_registration: Arc::new(AtomicBool::new(false)),
+            indexing: CancellationToken::new(),
         }
     }
 
@@ -75,7 +89,7 @@ impl BractLspServer {
         W: AsyncWrite + Unpin + Send + 'static,
     {
         let (tx, mut rx) = mpsc::channel::<Message>(100);
-        let output = Arc::new(Mutex::new(output));
+        let output = Arc::new(AsyncMutex::new(output));
         let output_clone = output.clone();
         
         // Spawn input handler
@@ -86,10 +100,38 @@ impl BractLspServer {
             }
         });
 
+        // Progress is written as it comes, even while a request is being handled
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel::<ProgressParams>();
+        self.core.set_progress_sink(ProgressSink::new(move |params| {
+            let _ = progress_tx.send(params);
+        }));
+        let server = self.clone();
+        let progress_output = output.clone();
+        tokio::spawn(async move {
+            while let Some(params) = progress_rx.recv().await {
+                if let Err(e) = server.send_progress(params, progress_output.clone()).await {
+                    eprintln!("Progress error: {}", e);
+                }
+            }
+        });
+
         // Process messages
         while let Some(message) = rx.recv().await {
+            // A request that fails, panicking analyses included, is answered with an internal error
+            let request_id = message.id.clone().filter(|_| message.method.is_some());
+            let uri = message.params.as_ref()
+                .and_then(|params| params["textDocument"]["uri"].as_str())
+                .map(|uri| json!({ "uri": uri }));
             if let Err(e) = self.handle_message(message, output_clone.clone()).await {
                 eprintln!("Message handler error: {}", e);
+                if let Some(id) = &request_id {
+                    if let Err(e) = self.send_error_response(id.clone(), -32603, e.to_string(), uri, output_clone.clone()).await {
+                        eprintln!("Message handler error: {}", e);
+                    }
+                }
+            }
+            if let Some(id) = request_id {
+                self.active_requests.lock().unwrap().remove(&id.to_string());
             }
         }
 
@@ -136,6 +178,21 @@ impl BractLspServer {
 
             // Parse JSON message
             let message: Message = serde_json::from_slice(&buffer)?;
+
+            // Requests get their cancellation token as they arrive, so a cancel can
+            // reach one that is queued or already running
+            match (message.method.as_deref(), &message.id) {
+                (Some("$/cancelRequest"), _) => {
+                    let id = message.params.as_ref().map(|params| params["id"].to_string()).unwrap_or_default();
+                    if let Some(token) = self.active_requests.lock().unwrap().get(&id) {
+                        token.cancel();
+                    }
+                },
+                (Some(_), Some(id)) => {
+                    self.active_requests.lock().unwrap().insert(id.to_string(), CancellationToken::new());
+                },
+                _ => {}
+            }
             
             // Send message to handler
             if tx.send(message).await.is_err() {
@@ -147,9 +204,9 @@ impl BractLspServer {
     }
 
     /// Handle a single message
-    async fn handle_message<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_message<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
-        W: AsyncWrite + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
     {
         match message.method.as_deref() {
             Some("initialize") => {
@@ -160,7 +217,29 @@ impl BractLspServer {
                 self.send_notification("window/logMessage", json!({
                     "type": 3, // Info
                     "message": "Bract Language Server initialized successfully"
-                }), output).await?;
+                }), output.clone()).await?;
+                // Keep the workspace index fresh as files change on disk
+                let mut registrations = vec![json!({
+                    "id": "bract-watched-files",
+                    "method": "workspace/didChangeWatchedFiles",
+                    "registerOptions": { "watchers": [{ "globPattern": "**/*.bract" }] }
+                })];
+                if self.configuration_registration.load(Ordering::Relaxed) {
+                    registrations.push(json!({
+                        "id": "bract-configuration",
+                        "method": "workspace/didChangeConfiguration",
+                        "registerOptions": { "section": SETTINGS_SECTION }
+                    }));
+                }
+                // Features the configuration turns on, where the client registers them itself
+                for registration in self.core.registrations() {
+                    registrations.push(serde_json::to_value(registration)?);
+                }
+                self.send_request("client/registerCapability", json!({ "registrations": registrations }), output).await?;
+                // Index the workspace folders in the background
+                let core = self.core.clone();
+                let indexing = self.indexing.clone();
+                tokio::task::spawn_blocking(move || core.index_workspace(&indexing));
             },
             Some("shutdown") => {
                 self.handle_shutdown(message, output).await?;
@@ -168,6 +247,9 @@ impl BractLspServer {
             Some("exit") => {
                 process::exit(0);
             },
+            Some("$/cancelRequest") => {
+                // Already applied to the request's token as the message came in
+            },
             Some("textDocument/didOpen") => {
                 self.handle_did_open(message, output).await?;
             },
@@ -180,9 +262,27 @@ impl BractLspServer {
             Some("textDocument/completion") => {
                 self.handle_completion(message, output).await?;
             },
+            Some("completionItem/resolve") => {
+                self.handle_completion_resolve(message, output).await?;
+            },
             Some("textDocument/hover") => {
                 self.handle_hover(message, output).await?;
             },
+            Some("textDocument/foldingRange") => {
+                self.handle_folding_range(message, output).await?;
+            },
+            Some("textDocument/selectionRange") => {
+                self.handle_selection_range(message, output).await?;
+            },
+            Some("textDocument/formatting") => {
+                self.handle_formatting(message, output).await?;
+            },
+            Some("textDocument/semanticTokens/full") => {
+                self.handle_semantic_tokens_full(message, output).await?;
+            },
+            Some("textDocument/codeAction") => {
+                self.handle_code_action(message, output).await?;
+            },
             Some("textDocument/definition") => {
                 self.handle_definition(message, output).await?;
             },
@@ -192,9 +292,21 @@ impl BractLspServer {
             Some("textDocument/documentSymbol") => {
                 self.handle_document_symbol(message, output).await?;
             },
+            Some("workspace/didChangeWatchedFiles") => {
+                self.handle_did_change_watched_files(message).await?;
+            },
+            Some("workspace/didChangeConfiguration") => {
+                self.handle_did_change_configuration(message, output).await?;
+            },
             Some("workspace/symbol") => {
                 self.handle_workspace_symbol(message, output).await?;
             },
+            Some(MEMORY_REPORT_METHOD) => {
+                self.handle_memory_report(message, output).await?;
+            },
+            None => {
+                // Responses to our own requests need no answer
+            },
             _ => {
                 // Unknown method - send method not found error
                 if message.id.is_some() {
@@ -213,12 +325,37 @@ impl BractLspServer {
     }
 
     /// Handle initialize request
-    async fn handle_initialize<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    ///
+    /// `initializationOptions` may hold settings like those of
+    /// `workspace/didChangeConfiguration`, so features can be off from the start.
+    async fn handle_initialize<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
+        let mut warnings = Vec::new();
+
+        // Workspace folders, falling back to the root, are indexed once the client is initialized
+        if let Some(params) = &message.params {
+            let folders: Vec<String> = match params["workspaceFolders"].as_array() {
+                Some(folders) => folders.iter().filter_map(|folder| folder["uri"].as_str().map(str::to_string)).collect(),
+                None => params["rootUri"].as_str().map(str::to_string).into_iter().collect(),
+            };
+            self.core.set_workspace_folders(&folders);
+            let work_done_progress = params["capabilities"]["window"]["workDoneProgress"].as_bool().unwrap_or(false);
+            self.work_done_progress.store(work_done_progress, Ordering::Relaxed);
+
+            let configuration_registration = params["capabilities"]["workspace"]["didChangeConfiguration"]["dynamicRegistration"]
+                .as_bool()
+                .unwrap_or(false);
+            self.configuration_registration.store(configuration_registration, Ordering::Relaxed);
+            self.core.set_dynamic_registration(settings::dynamic_registration_support(&params["capabilities"]));
+            let (config, rejected) = settings::apply_settings(&self.core.config(), &params["initializationOptions"]);
+            self.core.update_config(config);
+            warnings = rejected;
+        }
+
+        // Advertised after the settings are applied, leaving out what the client registers itself
         let capabilities = self.core.capabilities();
-        
         let response = json!({
             "capabilities": capabilities,
             "serverInfo": {
@@ -227,21 +364,25 @@ impl BractLspServer {
             }
         });
 
-        self.send_response(message.id.unwrap(), response, output).await?;
+        self.send_response(message.id.unwrap(), response, output.clone()).await?;
+        for warning in warnings {
+            self.send_notification("window/logMessage", json!({ "type": 2, "message": warning }), output.clone()).await?;
+        }
         Ok(())
     }
 
     /// Handle shutdown request
-    async fn handle_shutdown<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_shutdown<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
+        self.indexing.cancel();
         self.send_response(message.id.unwrap(), json!(null), output).await?;
         Ok(())
     }
 
     /// Handle textDocument/didOpen notification
-    async fn handle_did_open<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_did_open<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
@@ -263,9 +404,12 @@ impl BractLspServer {
     }
 
     /// Handle textDocument/didChange notification
-    async fn handle_did_change<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    ///
+    /// With an analysis debounce the document is analyzed once that long has passed
+    /// without another edit, rather than after each one.
+    async fn handle_did_change<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
-        W: AsyncWrite + Unpin,
+        W: AsyncWrite + Unpin + Send + 'static,
     {
         if let Some(params) = message.params {
             if let Some(text_document) = params.get("textDocument") {
@@ -281,7 +425,22 @@ impl BractLspServer {
                             self.core.update_document(uri.clone(), text, version)?;
                             
                             // Analyze document and send diagnostics
-                            self.analyze_and_send_diagnostics(uri, output).await?;
+                            let debounce = self.core.config().analysis_debounce;
+                            if debounce == 0 {
+                                self.analyze_and_send_diagnostics(uri, output).await?;
+                            } else {
+                                let server = self.clone();
+                                tokio::spawn(async move {
+                                    tokio::time::sleep(std::time::Duration::from_millis(debounce)).await;
+                                    // A later edit has its own analysis coming
+                                    let current = server.core.get_document(&uri).ok().flatten().map(|document| document.version);
+                                    if current == Some(version) {
+                                        if let Err(e) = server.analyze_and_send_diagnostics(uri, output).await {
+                                            eprintln!("Analysis error: {}", e);
+                                        }
+                                    }
+                                });
+                            }
                         }
                     }
                 }
@@ -292,7 +451,7 @@ impl BractLspServer {
     }
 
     /// Handle textDocument/didClose notification
-    async fn handle_did_close<W>(&self, _message: Message, _output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_did_close<W>(&self, _message: Message, _output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
@@ -302,7 +461,7 @@ impl BractLspServer {
     }
 
     /// Handle textDocument/completion request
-    async fn handle_completion<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_completion<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
@@ -338,12 +497,9 @@ impl BractLspServer {
                     });
 
                     // Get completions
-                    let completions = self.completion_provider.provide_completions(
-                        &self.core,
-                        uri,
-                        &pos,
-                        &context,
-                    ).unwrap_or_default();
+                    let completions = self.core.catch_panic(uri, || {
+                        self.completion_provider.provide_completions(&self.core, uri, &pos, &context)
+                    }).unwrap_or_default();
 
                     let response = json!({
                         "isIncomplete": false,
@@ -358,41 +514,178 @@ impl BractLspServer {
         Ok(())
     }
 
+    /// Handle completionItem/resolve request
+    async fn handle_completion_resolve<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let item: CompletionItem = serde_json::from_value(message.params.unwrap_or_default())?;
+        let uri = item.data.as_ref().and_then(|data| data["uri"].as_str()).unwrap_or_default().to_string();
+        let item = self.core.catch_panic(&uri, || self.core.resolve_completion(item))?;
+
+        let response = serde_json::to_value(item)?;
+        self.send_response(message.id.unwrap(), response, output).await?;
+        Ok(())
+    }
+
     /// Handle textDocument/hover request
-    async fn handle_hover<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_hover<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
-        // For now, return empty hover
-        let response = json!(null);
+        let mut hover = None;
+        if let Some(params) = &message.params {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+            let pos = Position {
+                line: params["position"]["line"].as_u64().unwrap_or(0) as u32,
+                character: params["position"]["character"].as_u64().unwrap_or(0) as u32,
+            };
+            hover = self.core.catch_panic(uri, || self.core.hover(uri, &pos))?;
+        }
+
+        let response = serde_json::to_value(hover)?;
+        self.send_response(message.id.unwrap(), response, output).await?;
+        Ok(())
+    }
+
+    /// Handle textDocument/foldingRange request
+    async fn handle_folding_range<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut ranges = Vec::new();
+        if let Some(params) = &message.params {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+            ranges = self.core.catch_panic(uri, || self.core.folding_ranges(uri))?;
+        }
+
+        let response = serde_json::to_value(ranges)?;
+        self.send_response(message.id.unwrap(), response, output).await?;
+        Ok(())
+    }
+
+    /// Handle textDocument/selectionRange request
+    async fn handle_selection_range<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut ranges = Vec::new();
+        if let Some(params) = &message.params {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+            let positions: Vec<Position> = serde_json::from_value(params["positions"].clone())?;
+            ranges = self.core.catch_panic(uri, || self.core.selection_ranges(uri, positions))?;
+        }
+
+        let response = serde_json::to_value(ranges)?;
+        self.send_response(message.id.unwrap(), response, output).await?;
+        Ok(())
+    }
+
+    /// Handle textDocument/formatting request
+    async fn handle_formatting<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let id = message.id.unwrap();
+        let cancel = self.cancellation(&id);
+        let mut edits = Vec::new();
+        let mut uri = None;
+        if let Some(params) = &message.params {
+            let document_uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+            let options: FormattingOptions = serde_json::from_value(params["options"].clone()).unwrap_or_default();
+            match self.core.catch_panic(&document_uri, || self.core.format_document(&document_uri, options, &cancel)) {
+                Ok(formatted) => edits = formatted,
+                Err(error) => return self.send_request_error(id, error, output).await,
+            }
+            uri = Some(document_uri);
+        }
+
+        let response = serde_json::to_value(&edits)?;
+        self.send_response(id, response, output.clone()).await?;
+
+        // A document left unformatted has a warning saying why
+        if let (Some(uri), true) = (uri, edits.is_empty()) {
+            self.analyze_and_send_diagnostics(uri, output).await?;
+        }
+        Ok(())
+    }
+
+    /// Handle textDocument/semanticTokens/full request
+    async fn handle_semantic_tokens_full<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut tokens = None;
+        if let Some(params) = &message.params {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+            tokens = Some(self.core.catch_panic(uri, || self.core.semantic_tokens_full(uri))?);
+        }
+
+        let response = serde_json::to_value(tokens)?;
+        self.send_response(message.id.unwrap(), response, output).await?;
+        Ok(())
+    }
+
+    /// Handle textDocument/codeAction request
+    async fn handle_code_action<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut actions = Vec::new();
+        if let Some(params) = &message.params {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+            let range: Range = serde_json::from_value(params["range"].clone())?;
+            let diagnostics: Vec<Diagnostic> = serde_json::from_value(params["context"]["diagnostics"].clone())?;
+            actions = self.core.catch_panic(uri, || self.core.code_actions(uri, &range, &diagnostics))?;
+        }
+
+        let response = serde_json::to_value(actions)?;
         self.send_response(message.id.unwrap(), response, output).await?;
         Ok(())
     }
 
     /// Handle textDocument/definition request
-    async fn handle_definition<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_definition<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
-        // For now, return empty definition
-        let response = json!(null);
+        let mut locations = Vec::new();
+        if let Some(params) = &message.params {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+            let position: Position = serde_json::from_value(params["position"].clone())?;
+            locations = self.core.catch_panic(uri, || self.core.definition(uri, &position))?;
+        }
+
+        let response = serde_json::to_value(locations)?;
         self.send_response(message.id.unwrap(), response, output).await?;
         Ok(())
     }
 
     /// Handle textDocument/references request
-    async fn handle_references<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_references<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
-        // For now, return empty references
-        let response = json!([]);
-        self.send_response(message.id.unwrap(), response, output).await?;
+        let id = message.id.unwrap();
+        let cancel = self.cancellation(&id);
+        let mut locations = Vec::new();
+        if let Some(params) = &message.params {
+            let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+            let position: Position = serde_json::from_value(params["position"].clone())?;
+            let include_declaration = params["context"]["includeDeclaration"].as_bool().unwrap_or(true);
+            match self.core.catch_panic(uri, || self.core.references(uri, &position, include_declaration, &cancel)) {
+                Ok(found) => locations = found,
+                Err(error) => return self.send_request_error(id, error, output).await,
+            }
+        }
+
+        let response = serde_json::to_value(locations)?;
+        self.send_response(id, response, output).await?;
         Ok(())
     }
 
     /// Handle textDocument/documentSymbol request
-    async fn handle_document_symbol<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_document_symbol<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
@@ -403,18 +696,94 @@ impl BractLspServer {
     }
 
     /// Handle workspace/symbol request
-    async fn handle_workspace_symbol<W>(&self, message: Message, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn handle_workspace_symbol<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
-        // For now, return empty symbols
-        let response = json!([]);
+        let query = message.params.as_ref()
+            .and_then(|params| params["query"].as_str())
+            .unwrap_or_default();
+        let symbols = self.core.workspace_symbols(query);
+
+        let response = serde_json::to_value(symbols)?;
         self.send_response(message.id.unwrap(), response, output).await?;
         Ok(())
     }
 
+    /// Handle workspace/didChangeWatchedFiles notification
+    async fn handle_did_change_watched_files(&self, message: Message) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(params) = message.params {
+            let changes: Vec<FileEvent> = serde_json::from_value(params["changes"].clone())?;
+            self.core.watched_files_changed(&changes);
+        }
+        Ok(())
+    }
+
+    /// Handle workspace/didChangeConfiguration notification
+    ///
+    /// Rejected settings are logged as warnings and keep their value. Capabilities
+    /// turned on or off are registered or unregistered where the client registers
+    /// them dynamically; elsewhere the client keeps what it was told at `initialize`
+    /// and is asked to restart the server, while requests for a feature turned off
+    /// are answered with nothing in the meantime.
+    async fn handle_did_change_configuration<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let settings = message.params.map(|params| params["settings"].clone()).unwrap_or_default();
+        let before = self.core.config();
+        let (config, warnings) = settings::apply_settings(&before, &settings);
+        for warning in warnings {
+            self.send_notification("window/logMessage", json!({ "type": 2, "message": warning }), output.clone()).await?;
+        }
+        let diagnostics_changed = (config.enable_diagnostics, config.enable_performance_diagnostics)
+            != (before.enable_diagnostics, before.enable_performance_diagnostics);
+
+        let update = self.core.update_config(config);
+        if !update.register.is_empty() {
+            self.send_request("client/registerCapability", json!({ "registrations": update.register }), output.clone()).await?;
+        }
+        if !update.unregister.is_empty() {
+            // The protocol spells it this way
+            self.send_request("client/unregisterCapability", json!({ "unregisterations": update.unregister }), output.clone()).await?;
+        }
+        if !update.needs_restart.is_empty() {
+            self.send_notification("window/showMessage", json!({
+                "type": 3, // Info
+                "message": format!("Restart the Bract Language Server to apply the settings for {}", update.needs_restart.join(", "))
+            }), output.clone()).await?;
+        }
+
+        // Open documents get their diagnostics again, or have them cleared
+        if diagnostics_changed {
+            for uri in self.core.document_uris() {
+                self.analyze_and_send_diagnostics(uri, output.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle bract/memoryReport request
+    async fn handle_memory_report<W>(&self, message: Message, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let id = message.id.unwrap();
+        let uri = message.params.as_ref()
+            .and_then(|params| params["textDocument"]["uri"].as_str())
+            .unwrap_or_default();
+        let cancel = self.cancellation(&id);
+        match self.core.catch_panic(uri, || self.core.memory_report(uri, &cancel)) {
+            Ok(report) => self.send_response(id, serde_json::to_value(report)?, output).await?,
+            // RequestFailed: the document could not be compiled
+            Err(RequestError::Failed(error)) => self.send_error_response(id, -32803, error, None, output).await?,
+            Err(error) => self.send_request_error(id, error, output).await?,
+        }
+        Ok(())
+    }
+
     /// Analyze document and send diagnostics
-    async fn analyze_and_send_diagnostics<W>(&self, uri: String, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn analyze_and_send_diagnostics<W>(&self, uri: String, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
@@ -431,8 +800,39 @@ impl BractLspServer {
         Ok(())
     }
 
+    /// Cancellation token of the request `id`
+    fn cancellation(&self, id: &Value) -> CancellationToken {
+        self.active_requests.lock().unwrap().get(&id.to_string()).cloned().unwrap_or_default()
+    }
+
+    /// Answer a request that has no result: RequestCancelled when it was cancelled,
+    /// otherwise the failure goes on to be answered as an internal error
+    async fn send_request_error<W>(&self, id: Value, error: RequestError, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        match error {
+            RequestError::Cancelled => self.send_error_response(id, -32800, error.to_string(), None, output).await,
+            RequestError::Failed(message) => Err(message.into()),
+        }
+    }
+
+    /// Send a `$/progress` notification, asking the client to create its token first
+    async fn send_progress<W>(&self, params: ProgressParams, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        if !self.work_done_progress.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        if let WorkDoneProgress::Begin { .. } = params.value {
+            self.send_request("window/workDoneProgress/create", json!({ "token": params.token }), output.clone()).await?;
+        }
+        self.send_notification("$/progress", serde_json::to_value(params)?, output).await
+    }
+
     /// Send response message
-    async fn send_response<W>(&self, id: Value, result: Value, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn send_response<W>(&self, id: Value, result: Value, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
@@ -446,7 +846,7 @@ impl BractLspServer {
     }
 
     /// Send error response
-    async fn send_error_response<W>(&self, id: Value, code: i32, message: String, data: Option<Value>, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn send_error_response<W>(&self, id: Value, code: i32, message: String, data: Option<Value>, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
@@ -465,8 +865,28 @@ impl BractLspServer {
         self.send_message(response, output).await
     }
 
+    /// Send a request to the client; its response is not waited for
+    async fn send_request<W>(&self, method: &str, params: Value, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let id = {
+            let mut counter = self.request_counter.lock().unwrap();
+            *counter += 1;
+            *counter
+        };
+        let message = json!({
+            "jsonrpc": "2.0",
+            "id": format!("bract-{}", id),
+            "method": method,
+            "params": params
+        });
+
+        self.send_message(message, output).await
+    }
+
     /// Send notification message
-    async fn send_notification<W>(&self, method: &str, params: Value, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn send_notification<W>(&self, method: &str, params: Value, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
@@ -480,14 +900,14 @@ impl BractLspServer {
     }
 
     /// Send message to client
-    async fn send_message<W>(&self, message: Value, output: Arc<Mutex<W>>) -> Result<(), Box<dyn std::error::Error>>
+    async fn send_message<W>(&self, message: Value, output: Arc<AsyncMutex<W>>) -> Result<(), Box<dyn std::error::Error>>
     where
         W: AsyncWrite + Unpin,
     {
         let content = serde_json::to_string(&message)?;
         let response = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
 
-        let mut output = output.lock().unwrap();
+        let mut output = output.lock().await;
         output.write_all(response.as_bytes()).await?;
         output.flush().await?;
 
@@ -502,6 +922,9 @@ impl Clone for BractLspServer {
             completion_provider: self.completion_provider.clone(),
             request_counter: self.request_counter.clone(),
             active_requests: self.active_requests.clone(),
+            work_done_progress: self.work_done_progress.clone(),
+            configuration_registration: self.configuration_registration.clone(),
+            indexing: self.indexing.clone(),
         }
     }
 }
@@ -545,8 +968,39 @@ mod tests {
         };
 
         let _output = Vec::new();
-        let output_arc = Arc::new(Mutex::new(_output));
+        let output_arc = Arc::new(AsyncMutex::new(_output));
         
         assert!(server.handle_message(message, output_arc).await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_configuration_change_unregisters_capabilities() {
+        let server = BractLspServer::new();
+        let message = |method: &str, id: Option<Value>, params: Value| Message {
+            jsonrpc: "2.0".to_string(),
+            id,
+            method: Some(method.to_string()),
+            params: Some(params),
+            result: None,
+            error: None,
+        };
+        let output = Arc::new(AsyncMutex::new(Vec::new()));
+
+        let initialize = message("initialize", Some(json!(1)), json!({
+            "capabilities": { "textDocument": { "completion": { "dynamicRegistration": true } } }
+        }));
+        server.handle_message(initialize, output.clone()).await.unwrap();
+        let sent = String::from_utf8(std::mem::take(&mut *output.lock().await)).unwrap();
+        assert!(!sent.contains("completionProvider\":{"), "{}", sent);
+
+        let change = message("workspace/didChangeConfiguration", None, json!({
+            "settings": { "bract": { "enableCompletion": false, "cacheSizeLimit": 0 } }
+        }));
+        server.handle_message(change, output.clone()).await.unwrap();
+        let sent = String::from_utf8(std::mem::take(&mut *output.lock().await)).unwrap();
+        assert!(sent.contains("\"method\":\"client/unregisterCapability\""), "{}", sent);
+        assert!(sent.contains("Ignoring setting bract.cacheSizeLimit = 0"), "{}", sent);
+        assert!(!server.core.config().enable_completion);
+        assert_eq!(server.core.config().cache_size_limit, 100);
+    }
 } 
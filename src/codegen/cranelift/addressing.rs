@@ -0,0 +1,99 @@
+//! Address Computation for Array Elements and Struct Fields
+//!
+//! Elements and fields live at a base pointer plus an offset. Every read and write
+//! of one goes through an `Address` built here, so the stride of an array is the
+//! size of its element type and each load or store has the width of the value's
+//! own Cranelift type: an `[i64; 4]` is stepped through 8 bytes at a time, a
+//! `[u8; 16]` one byte at a time. Indices are brought to pointer width with the
+//! extension their signedness calls for, and a constant index becomes the offset
+//! of the access itself rather than a multiply.
+
+use super::utils;
+use cranelift::prelude::{InstBuilder, MemFlags, Type, Value};
+use cranelift_frontend::FunctionBuilder;
+
+/// Flags of element and field accesses
+///
+/// Layouts align every element and field to its size, and indices are
+/// bounds-checked before the access, so the access neither traps nor is misaligned.
+pub fn access_flags() -> MemFlags {
+    MemFlags::trusted()
+}
+
+/// Bytes from one element of type `element_type` to the next
+pub fn stride(element_type: Type, pointer_type: Type) -> u32 {
+    utils::type_size(element_type, pointer_type) as u32
+}
+
+/// Index of an array element
+#[derive(Debug, Clone, Copy)]
+pub enum ElementIndex {
+    /// Known at compile time
+    Constant(u32),
+    /// Computed at run time, already at pointer width
+    Dynamic(Value),
+}
+
+/// `index` extended or reduced to pointer width
+///
+/// A signed index is sign-extended, so a negative index stays out of bounds
+/// rather than wrapping around to a small offset.
+pub fn index_to_pointer_width(builder: &mut FunctionBuilder, index: Value, unsigned: bool, pointer_type: Type) -> Value {
+    let index_type = builder.func.dfg.value_type(index);
+    match index_type.bits().cmp(&pointer_type.bits()) {
+        std::cmp::Ordering::Less if unsigned => builder.ins().uextend(pointer_type, index),
+        std::cmp::Ordering::Less => builder.ins().sextend(pointer_type, index),
+        std::cmp::Ordering::Greater => builder.ins().ireduce(pointer_type, index),
+        std::cmp::Ordering::Equal => index,
+    }
+}
+
+/// Memory at a base pointer plus a constant offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Address {
+    pub base: Value,
+    pub offset: i32,
+}
+
+impl Address {
+    pub fn new(base: Value, offset: i32) -> Self {
+        Self { base, offset }
+    }
+
+    /// Element `index` of the array of `element_type` values starting at `array`
+    pub fn element(builder: &mut FunctionBuilder, array: Value, index: ElementIndex, element_type: Type, pointer_type: Type) -> Self {
+        let stride = stride(element_type, pointer_type);
+        let index = match index {
+            ElementIndex::Constant(index) => match i32::try_from(u64::from(index) * u64::from(stride)) {
+                Ok(offset) => return Self::new(array, offset),
+                Err(_) => builder.ins().iconst(pointer_type, i64::from(index)),
+            },
+            ElementIndex::Dynamic(index) => index,
+        };
+        let byte_offset = builder.ins().imul_imm(index, i64::from(stride));
+        Self::new(builder.ins().iadd(array, byte_offset), 0)
+    }
+
+    /// The field `offset` bytes into the struct, tuple or enum payload at `pointer`
+    pub fn field(pointer: Value, offset: u32) -> Self {
+        Self::new(pointer, offset as i32)
+    }
+
+    /// The address as a single pointer value, e.g. for a reference to it
+    pub fn pointer(self, builder: &mut FunctionBuilder) -> Value {
+        match self.offset {
+            0 => self.base,
+            offset => builder.ins().iadd_imm(self.base, i64::from(offset)),
+        }
+    }
+
+    /// Load a value of type `value_type` from here
+    pub fn load(self, builder: &mut FunctionBuilder, value_type: Type) -> Value {
+        builder.ins().load(value_type, access_flags(), self.base, self.offset)
+    }
+
+    /// Store `value` here, at the width of its type
+    pub fn store(self, builder: &mut FunctionBuilder, value: Value) {
+        builder.ins().store(access_flags(), value, self.base, self.offset);
+    }
+}
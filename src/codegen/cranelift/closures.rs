@@ -0,0 +1,283 @@
+//! Closure Lifting
+//!
+//! Non-capturing closures compile to synthetic module-level functions named after
+//! their source position; the closure expression itself evaluates to the address
+//! of that function. Closures that use variables of an enclosing scope are
+//! rejected until captures are supported.
+
+use super::{CodegenError, CodegenResult};
+use crate::ast::{BinaryOp, Expr, InternedString, Item, NodeId, Literal, Parameter, Pattern, PrimitiveType, SourceMap, Span, Stmt, Type as AstType, UnaryOp, Visibility};
+use crate::parser::StringInterner;
+use std::collections::HashSet;
+
+/// Name of the function a closure is lifted into
+pub fn closure_function_name(span: &Span) -> String {
+    format!("__closure_{}_{}", span.start.line, span.start.column)
+}
+
+/// Lift every closure in the bodies of `functions`, including closures nested in
+/// closures, into a function item
+///
+/// Fails on the first closure that captures a variable or whose parameter or
+/// return types cannot be determined.
+pub fn lift_closures(functions: &[&Item], interner: &StringInterner, source_map: &SourceMap) -> CodegenResult<Vec<Item>> {
+    let function_names: HashSet<InternedString> = functions.iter()
+        .filter_map(|item| match item {
+            Item::Function { name, .. } => Some(*name),
+            _ => None,
+        })
+        .collect();
+
+    let mut closures = Vec::new();
+    for item in functions {
+        if let Item::Function { body: Some(body), .. } = item {
+            walk(Node::Expr(body), &mut |node| {
+                if let Node::Expr(closure @ Expr::Closure { .. }) = node {
+                    closures.push(closure);
+                }
+            });
+        }
+    }
+
+    closures.into_iter()
+        .map(|closure| lift_closure(closure, &function_names, interner, source_map))
+        .collect()
+}
+
+/// Build the function item for one closure expression
+fn lift_closure(
+    closure: &Expr,
+    function_names: &HashSet<InternedString>,
+    interner: &StringInterner,
+    source_map: &SourceMap,
+) -> CodegenResult<Item> {
+    let Expr::Closure { params, return_type, body, span, .. } = closure else {
+        return Err(CodegenError::InternalError("Expected closure expression".to_string()));
+    };
+    let location = |span: &Span| source_map.location(&span.start);
+
+    if let Some(param) = params.iter().find(|param| param.type_annotation.is_none()) {
+        return Err(CodegenError::UnsupportedFeature(format!(
+            "{}: closure parameter needs a type annotation",
+            location(&param.span)
+        )));
+    }
+    if let Some((name, use_span)) = first_capture(params, body, function_names) {
+        return Err(CodegenError::UnsupportedFeature(format!(
+            "{}: captured variable '{}' not supported yet",
+            location(&use_span),
+            interner.resolve(name).unwrap_or_else(|| "<unknown>".to_string())
+        )));
+    }
+
+    let return_type = match return_type {
+        Some(return_type) => Some(return_type.clone()),
+        None => match body.as_ref() {
+            // A block without a trailing expression returns nothing
+            Expr::Block { trailing_expr: None, .. } => None,
+            body => Some(value_type(body, params).ok_or_else(|| CodegenError::UnsupportedFeature(format!(
+                "{}: cannot infer the closure's return type, add '-> T' with a block body",
+                location(span)
+            )))?),
+        },
+    };
+
+    Ok(Item::Function {
+        visibility: Visibility::Private,
+        name: interner.intern(&closure_function_name(span)),
+        generics: Vec::new(),
+        params: params.clone(),
+        return_type,
+        body: Some(body.as_ref().clone()),
+        is_extern: false,
+        performance_contract: None,
+        allocation_strategy: None,
+        attributes: Vec::new(),
+        span: *span,
+        id: NodeId::DUMMY,
+    })
+}
+
+/// First identifier in a closure body that is neither bound inside the closure nor a function
+fn first_capture(
+    params: &[Parameter],
+    body: &Expr,
+    function_names: &HashSet<InternedString>,
+) -> Option<(InternedString, Span)> {
+    // Every name the closure binds, wherever it is bound
+    let mut bound = HashSet::new();
+    for param in params {
+        pattern_bindings(&param.pattern, &mut bound);
+    }
+    let mut uses = Vec::new();
+    walk(Node::Expr(body), &mut |node| match node {
+        Node::Expr(Expr::Identifier { name, span, .. }) => uses.push((*name, *span)),
+        Node::Expr(Expr::Closure { params, .. }) => {
+            for param in params {
+                pattern_bindings(&param.pattern, &mut bound);
+            }
+        }
+        Node::Expr(Expr::For { pattern, .. }) | Node::Stmt(Stmt::Let { pattern, .. } | Stmt::For { pattern, .. }) => {
+            pattern_bindings(pattern, &mut bound);
+        }
+        Node::Expr(Expr::Match { arms, .. }) | Node::Stmt(Stmt::Match { arms, .. }) => {
+            for arm in arms {
+                pattern_bindings(&arm.pattern, &mut bound);
+            }
+        }
+        _ => {}
+    });
+
+    uses.into_iter().find(|(name, _)| !bound.contains(name) && !function_names.contains(name))
+}
+
+/// Names bound by a pattern
+fn pattern_bindings(pattern: &Pattern, bound: &mut HashSet<InternedString>) {
+    match pattern {
+        Pattern::Identifier { name, .. } => {
+            bound.insert(*name);
+        }
+        Pattern::Tuple { patterns, .. } | Pattern::Array { patterns, .. } | Pattern::Or { patterns, .. } => {
+            for pattern in patterns {
+                pattern_bindings(pattern, bound);
+            }
+        }
+        Pattern::Enum { patterns: Some(patterns), .. } => {
+            for pattern in patterns {
+                pattern_bindings(pattern, bound);
+            }
+        }
+        Pattern::Struct { fields, .. } => {
+            for field in fields {
+                match &field.pattern {
+                    Some(pattern) => pattern_bindings(pattern, bound),
+                    // Shorthand `Point { x }` binds the field name
+                    None => {
+                        bound.insert(field.name);
+                    }
+                }
+            }
+        }
+        Pattern::Reference { pattern, .. } => pattern_bindings(pattern, bound),
+        Pattern::Wildcard { .. } | Pattern::Literal { .. } | Pattern::Enum { patterns: None, .. } | Pattern::Range { .. } => {}
+    }
+}
+
+/// Type of a value expression, where it follows from the syntax and the closure's parameters
+fn value_type(expr: &Expr, params: &[Parameter]) -> Option<AstType> {
+    let primitive = |kind| Some(AstType::stack_primitive(kind, expr.span()));
+    match expr {
+        Expr::Literal { literal: Literal::Integer { suffix, .. }, .. } => primitive(suffix.unwrap_or(PrimitiveType::I32)),
+        Expr::Literal { literal: Literal::Float { suffix, .. }, .. } => primitive(suffix.unwrap_or(PrimitiveType::F64)),
+        Expr::Literal { literal: Literal::Bool(_), .. } => primitive(PrimitiveType::Bool),
+        Expr::Identifier { name, .. } => params.iter()
+            .find(|param| matches!(&param.pattern, Pattern::Identifier { name: param_name, .. } if param_name == name))
+            .and_then(|param| param.type_annotation.clone()),
+        Expr::Binary { op, left, right, .. } => match op {
+            BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater
+            | BinaryOp::GreaterEqual | BinaryOp::LogicalAnd | BinaryOp::LogicalOr => primitive(PrimitiveType::Bool),
+            // An unsuffixed literal operand adapts to the other side
+            _ => value_type(left, params)
+                .filter(|_| match left.as_ref() {
+                    Expr::Literal { literal: Literal::Integer { suffix, .. } | Literal::Float { suffix, .. }, .. } => suffix.is_some(),
+                    Expr::Literal { .. } => false,
+                    _ => true,
+                })
+                .or_else(|| value_type(right, params))
+                .or_else(|| value_type(left, params)),
+        },
+        Expr::Unary { op: UnaryOp::Not, .. } => primitive(PrimitiveType::Bool),
+        Expr::Unary { op: UnaryOp::Negate | UnaryOp::Plus | UnaryOp::BitwiseNot, expr, .. }
+        | Expr::Parenthesized { expr, .. } => value_type(expr, params),
+        Expr::Cast { target_type, .. } => Some(target_type.clone()),
+        Expr::If { then_block, .. } => value_type(then_block, params),
+        Expr::Block { trailing_expr: Some(trailing), .. } => value_type(trailing, params),
+        _ => None,
+    }
+}
+
+/// Expression or statement visited by `walk`
+#[derive(Clone, Copy)]
+pub(super) enum Node<'e> {
+    Expr(&'e Expr),
+    Stmt(&'e Stmt),
+}
+
+/// Visit `node` and everything inside it, closure bodies included, in source order
+pub(super) fn walk<'e>(node: Node<'e>, visit: &mut impl FnMut(Node<'e>)) {
+    visit(node);
+    let mut exprs: Vec<&Expr> = Vec::new();
+    let mut stmts: Vec<&Stmt> = Vec::new();
+    match node {
+        Node::Expr(expr) => match expr {
+            Expr::Literal { .. } | Expr::Identifier { .. } | Expr::Path { .. } | Expr::Continue { .. }
+            | Expr::Macro { .. } => {}
+            Expr::Binary { left, right, .. } => exprs.extend([left.as_ref(), right.as_ref()]),
+            Expr::Unary { expr, .. } | Expr::Cast { expr, .. } | Expr::Parenthesized { expr, .. }
+            | Expr::Box { expr, .. } | Expr::Reference { expr, .. } | Expr::Dereference { expr, .. }
+            | Expr::Try { expr, .. } | Expr::Await { expr, .. } => exprs.push(expr),
+            Expr::Call { callee: receiver, args, .. } | Expr::MethodCall { receiver, args, .. } => {
+                exprs.push(receiver);
+                exprs.extend(args);
+            }
+            Expr::FieldAccess { object, .. } => exprs.push(object),
+            Expr::Index { object, index, .. } => exprs.extend([object.as_ref(), index.as_ref()]),
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => exprs.extend(elements),
+            Expr::ArrayRepeat { value, count, .. } => exprs.extend([value.as_ref(), count.as_ref()]),
+            Expr::StructInit { fields, .. } => exprs.extend(fields.iter().filter_map(|field| field.value.as_ref())),
+            Expr::Range { start, end, .. } => exprs.extend(start.iter().chain(end.iter()).map(|bound| bound.as_ref())),
+            Expr::Closure { body, .. } | Expr::Loop { body, .. } => exprs.push(body),
+            Expr::Block { statements, trailing_expr, .. } => {
+                stmts.extend(statements);
+                exprs.extend(trailing_expr.as_deref());
+            }
+            Expr::If { condition, then_block, else_block, .. } => {
+                exprs.extend([condition.as_ref(), then_block.as_ref()]);
+                exprs.extend(else_block.as_deref());
+            }
+            Expr::Match { expr, arms, .. } => {
+                exprs.push(expr);
+                for arm in arms {
+                    exprs.extend(arm.guard.as_ref());
+                    exprs.push(&arm.body);
+                }
+            }
+            Expr::While { condition, body, .. } => exprs.extend([condition.as_ref(), body.as_ref()]),
+            Expr::For { iterator, body, .. } => exprs.extend([iterator.as_ref(), body.as_ref()]),
+            Expr::Break { value, .. } | Expr::Return { value, .. } => exprs.extend(value.as_deref()),
+        },
+        Node::Stmt(stmt) => match stmt {
+            Stmt::Expression { expr, .. } => exprs.push(expr),
+            Stmt::Let { initializer, .. } => exprs.extend(initializer.as_ref()),
+            Stmt::Assignment { target, value, .. } | Stmt::CompoundAssignment { target, value, .. } => {
+                exprs.extend([target, value]);
+            }
+            Stmt::If { condition, then_block, else_block, .. } => {
+                exprs.push(condition);
+                stmts.extend(then_block);
+                stmts.extend(else_block.as_deref());
+            }
+            Stmt::While { condition: header, body, .. } | Stmt::For { iterable: header, body, .. } => {
+                exprs.push(header);
+                stmts.extend(body);
+            }
+            Stmt::Loop { body, .. } | Stmt::Block { statements: body, .. } | Stmt::Region { body, .. } => stmts.extend(body),
+            Stmt::Match { expr, arms, .. } => {
+                exprs.push(expr);
+                for arm in arms {
+                    exprs.extend(arm.guard.as_ref());
+                    exprs.push(&arm.body);
+                }
+            }
+            Stmt::Break { expr, .. } | Stmt::Return { expr, .. } => exprs.extend(expr.as_ref()),
+            // Closures in nested items are lifted from those items' own bodies
+            Stmt::Continue { .. } | Stmt::Item { .. } | Stmt::Empty { .. } => {}
+        },
+    }
+    for expr in exprs {
+        walk(Node::Expr(expr), visit);
+    }
+    for stmt in stmts {
+        walk(Node::Stmt(stmt), visit);
+    }
+}
@@ -5,35 +5,223 @@
 
 use super::*;
 use cranelift::prelude::{types as ctypes, Type, Value};
-use cranelift_module::FuncId;
+use cranelift_codegen::ir::{ExtFuncData, ExternalName, FuncRef, Function, Signature, UserExternalName};
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_module::{DataId, FuncId, ModuleDeclarations};
+use super::memory::TypeStrategy;
+use super::intrinsics::Intrinsics;
+use super::profiling::ProfileHooks;
+use crate::ast::{SourceMap, Type as AstType};
+use crate::lexer::Position;
+use crate::semantic::EscapeFact;
 use std::collections::HashMap;
 
+/// Source file name used until the real one is set
+pub const UNNAMED_SOURCE: &str = "<input>";
+
+/// Bytes occupied by every struct field, whatever its type
+pub const STRUCT_FIELD_SLOT_BYTES: u32 = 8;
+
+/// Layout of one struct field
+#[derive(Debug, Clone)]
+pub struct FieldLayout {
+    pub name: String,
+    pub cranelift_type: Type,
+    /// Unsigned integers (and bools) are zero-extended when widened
+    pub unsigned: bool,
+    /// Name of the field's struct type, for nested field access
+    pub struct_name: Option<String>,
+    /// Byte offset from the start of the struct
+    pub offset: u32,
+}
+
+/// Memory layout of a struct: fields occupy 8-byte slots in declaration order
+#[derive(Debug, Clone)]
+pub struct StructLayout {
+    pub fields: Vec<FieldLayout>,
+    /// Total size in bytes (at least one slot, so every instance has a distinct address)
+    pub size: u32,
+}
+
+impl StructLayout {
+    /// Look up a field by name
+    pub fn field(&self, name: &str) -> Option<&FieldLayout> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+/// Layout of one enum variant
+#[derive(Debug, Clone)]
+pub struct VariantLayout {
+    pub name: String,
+    pub discriminant: i64,
+    /// Payload fields, named by position for tuple variants, at offsets past the tag
+    pub fields: Vec<FieldLayout>,
+}
+
+/// Memory layout of an enum
+///
+/// Enums whose variants carry no payload are represented by their discriminant, a
+/// pointer-sized integer. Other enums are pointers to a tag followed by the payload of the
+/// active variant: the tag (the discriminant, pointer-sized) takes the first 8-byte slot and
+/// the payload fields take 8-byte slots after it, so every field is 8-byte aligned whichever
+/// variant is active. The size fits the variant with the most fields.
+#[derive(Debug, Clone)]
+pub struct EnumLayout {
+    pub variants: Vec<VariantLayout>,
+    /// Total size in bytes of a value with payloads, 0 for enums represented by their discriminant
+    pub size: u32,
+}
+
+impl EnumLayout {
+    /// Look up a variant by name
+    pub fn variant(&self, name: &str) -> Option<&VariantLayout> {
+        self.variants.iter().find(|variant| variant.name == name)
+    }
+    
+    /// Whether values are pointers to a tag and payload rather than bare discriminants
+    pub fn has_payload(&self) -> bool {
+        self.size > 0
+    }
+}
+
+/// Layout of a named struct or enum type
+#[derive(Debug, Clone)]
+pub enum TypeLayout {
+    Struct(StructLayout),
+    Enum(EnumLayout),
+}
+
+impl TypeLayout {
+    /// The struct layout, if this is a struct
+    pub fn as_struct(&self) -> Option<&StructLayout> {
+        match self {
+            TypeLayout::Struct(layout) => Some(layout),
+            TypeLayout::Enum(_) => None,
+        }
+    }
+    
+    /// The enum layout, if this is an enum
+    pub fn as_enum(&self) -> Option<&EnumLayout> {
+        match self {
+            TypeLayout::Enum(layout) => Some(layout),
+            TypeLayout::Struct(_) => None,
+        }
+    }
+}
+
+/// Function declared in the module being compiled
+#[derive(Debug, Clone)]
+pub struct FunctionEntry {
+    pub func_id: FuncId,
+    /// Signature the function was declared with; its body and every call site use it
+    pub signature: Signature,
+    /// Declared return type, None for functions returning nothing
+    pub return_type: Option<AstType>,
+    /// Method taking `self` by value, so calling it moves the receiver
+    pub moves_receiver: bool,
+}
+
+/// Read-only view of the module that function bodies are compiled against
+///
+/// Holds only what building and compiling a body needs, so bodies can be compiled
+/// on several threads while the module itself stays with the thread defining them.
+#[derive(Clone, Copy)]
+pub struct ModuleView<'a> {
+    declarations: &'a ModuleDeclarations,
+    isa: &'a dyn TargetIsa,
+}
+
+impl<'a> ModuleView<'a> {
+    /// View the declarations and target of `module`
+    pub fn new(module: &'a dyn CraneliftModule) -> Self {
+        Self {
+            declarations: module.declarations(),
+            isa: module.isa(),
+        }
+    }
+
+    /// Target the module is compiled for
+    pub fn isa(&self) -> &'a dyn TargetIsa {
+        self.isa
+    }
+
+    /// Pointer type of the compilation target
+    pub fn pointer_type(&self) -> Type {
+        self.isa.pointer_type()
+    }
+
+    /// Import a declared function into `func`
+    ///
+    /// Mirrors `Module::declare_func_in_func`, which needs the module mutably.
+    pub fn declare_func_in_func(&self, func_id: FuncId, func: &mut Function) -> FuncRef {
+        let decl = self.declarations.get_function_decl(func_id);
+        let signature = func.import_signature(decl.signature.clone());
+        let name_ref = func.declare_imported_user_function(UserExternalName::new(0, func_id.as_u32()));
+        func.import_function(ExtFuncData {
+            name: ExternalName::user(name_ref),
+            signature,
+            colocated: decl.linkage.is_final(),
+        })
+    }
+}
+
 /// Cranelift compilation context
+///
+/// Owns the module-wide tables filled while declaring items: functions, type layouts
+/// and data objects. Function bodies are compiled against a borrowed view of them.
 pub struct CraneliftContext {
     /// Variable tracking (variable name -> Cranelift value)
     variables: HashMap<String, Value>,
-    /// Function ID mapping (function name -> Cranelift function ID)
-    functions: HashMap<String, FuncId>,
-    /// Function signatures (function name -> signature)
-    function_signatures: HashMap<String, cranelift_codegen::ir::Signature>,
+    /// Declared functions, by name
+    functions: HashMap<InternedString, FunctionEntry>,
+    /// Struct and enum layouts, by type name
+    layouts: HashMap<String, TypeLayout>,
+    /// Data objects declared in the module, by symbol name
+    data: HashMap<String, DataId>,
+    /// Interner the module's names were interned with
+    interner: StringInterner,
     /// Function scope stack
     function_scopes: Vec<String>,
     /// Type mapping cache
     type_cache: HashMap<String, Type>,
     /// Current function has return statement
     has_return: bool,
+    /// Names of the source files spans point into (for diagnostics)
+    source_map: SourceMap,
+    /// Profiling hooks, declared only for instrumented builds
+    profile_hooks: Option<ProfileHooks>,
+    /// Runtime functions of the intrinsics called and data of the string literals
+    intrinsics: Intrinsics,
+    /// Record the source location of every statement, for DWARF line info
+    debug_info: bool,
+    /// Types semantic analysis resolved for `let` bindings, by the start of their pattern
+    binding_types: HashMap<Position, AstType>,
+    /// Escape analysis facts for allocating expressions, by their start
+    escape_facts: HashMap<Position, EscapeFact>,
+    /// Memory strategy decided for each struct type, by type name
+    type_strategies: HashMap<String, TypeStrategy>,
 }
 
 impl CraneliftContext {
-    /// Create a new Cranelift context
-    pub fn new() -> Self {
+    /// Create a new Cranelift context for a module whose names were interned with `interner`
+    pub fn new(interner: StringInterner) -> Self {
         let mut context = Self {
             variables: HashMap::new(),
             functions: HashMap::new(),
-            function_signatures: HashMap::new(),
+            layouts: HashMap::new(),
+            data: HashMap::new(),
+            interner,
             function_scopes: Vec::new(),
             type_cache: HashMap::new(),
             has_return: false,
+            source_map: SourceMap::new(UNNAMED_SOURCE),
+            profile_hooks: None,
+            intrinsics: Intrinsics::default(),
+            debug_info: false,
+            binding_types: HashMap::new(),
+            escape_facts: HashMap::new(),
+            type_strategies: HashMap::new(),
         };
         
         // Initialize standard type mappings
@@ -78,24 +266,101 @@ impl CraneliftContext {
         self.variables.get(name).copied()
     }
     
-    /// Register a function with its signature
-    pub fn register_function(&mut self, name: &str, func_id: FuncId) {
-        self.functions.insert(name.to_string(), func_id);
+    /// Register a declared function
+    pub fn register_function(&mut self, name: InternedString, entry: FunctionEntry) {
+        self.functions.insert(name, entry);
+    }
+    
+    /// Get a declared function
+    pub fn get_function(&self, name: InternedString) -> Option<&FunctionEntry> {
+        self.functions.get(&name)
+    }
+    
+    /// Look up a function to call, reporting functions that were never declared
+    pub fn lookup_callable(&self, name: InternedString) -> CodegenResult<&FunctionEntry> {
+        self.functions.get(&name).ok_or_else(|| CodegenError::SymbolResolution(format!(
+            "Unknown function '{}'",
+            self.interner.resolve(name).unwrap_or_else(|| format!("<unknown #{}>", name.id))
+        )))
+    }
+    
+    /// Register the layout of a struct
+    pub fn register_struct_layout(&mut self, name: &str, layout: StructLayout) {
+        self.layouts.insert(name.to_string(), TypeLayout::Struct(layout));
     }
     
-    /// Register a function signature
-    pub fn register_function_signature(&mut self, name: &str, signature: cranelift_codegen::ir::Signature) {
-        self.function_signatures.insert(name.to_string(), signature);
+    /// Register the layout of an enum
+    pub fn register_enum_layout(&mut self, name: &str, layout: EnumLayout) {
+        self.layouts.insert(name.to_string(), TypeLayout::Enum(layout));
     }
     
-    /// Get a function signature
-    pub fn get_function_signature(&self, name: &str) -> Option<&cranelift_codegen::ir::Signature> {
-        self.function_signatures.get(name)
+    /// Get the layout of a struct
+    pub fn get_struct_layout(&self, name: &str) -> Option<&StructLayout> {
+        self.layouts.get(name).and_then(TypeLayout::as_struct)
     }
     
-    /// Get a function ID
-    pub fn get_function_id(&self, name: &str) -> Option<FuncId> {
-        self.functions.get(name).copied()
+    /// Get the layout of an enum
+    pub fn get_enum_layout(&self, name: &str) -> Option<&EnumLayout> {
+        self.layouts.get(name).and_then(TypeLayout::as_enum)
+    }
+    
+    /// Look up the layout of a struct or enum, reporting types that have none
+    pub fn lookup_layout(&self, name: &str) -> CodegenResult<&TypeLayout> {
+        self.layouts.get(name).ok_or_else(|| CodegenError::SymbolResolution(format!(
+            "Unknown struct or enum type '{}'", name
+        )))
+    }
+    
+    /// Names and layouts of all registered enums
+    pub fn enum_layouts(&self) -> impl Iterator<Item = (&str, &EnumLayout)> {
+        self.layouts.iter().filter_map(|(name, layout)| Some((name.as_str(), layout.as_enum()?)))
+    }
+    
+    /// Register a data object declared in the module
+    pub fn register_data(&mut self, name: &str, data_id: DataId) {
+        self.data.insert(name.to_string(), data_id);
+    }
+    
+    /// Look up a data object, reporting symbols that were never declared
+    pub fn lookup_data(&self, name: &str) -> CodegenResult<DataId> {
+        self.data.get(name).copied().ok_or_else(|| CodegenError::SymbolResolution(format!(
+            "Unknown data object '{}'", name
+        )))
+    }
+    
+    /// Use the types semantic analysis resolved for `let` bindings
+    pub fn set_binding_types(&mut self, binding_types: HashMap<Position, AstType>) {
+        self.binding_types = binding_types;
+    }
+    
+    /// Get the types resolved for `let` bindings, by the start of their pattern
+    pub fn get_binding_types(&self) -> &HashMap<Position, AstType> {
+        &self.binding_types
+    }
+    
+    /// Use the escape facts semantic analysis found for allocating expressions
+    pub fn set_escape_facts(&mut self, escape_facts: HashMap<Position, EscapeFact>) {
+        self.escape_facts = escape_facts;
+    }
+    
+    /// Get the escape facts for allocating expressions, by their start
+    pub fn get_escape_facts(&self) -> &HashMap<Position, EscapeFact> {
+        &self.escape_facts
+    }
+    
+    /// Record the memory strategy decided for the struct `name`
+    pub fn register_type_strategy(&mut self, name: &str, strategy: TypeStrategy) {
+        self.type_strategies.insert(name.to_string(), strategy);
+    }
+    
+    /// Get the memory strategy decided for the struct `name`
+    pub fn get_type_strategy(&self, name: &str) -> Option<&TypeStrategy> {
+        self.type_strategies.get(name)
+    }
+    
+    /// Memory strategies decided for the struct types, by type name
+    pub fn get_type_strategies(&self) -> &HashMap<String, TypeStrategy> {
+        &self.type_strategies
     }
     
     /// Push a function scope
@@ -125,6 +390,61 @@ impl CraneliftContext {
         self.has_return
     }
     
+    /// Set the name of the source file being compiled
+    pub fn set_source_file(&mut self, source_file: &str) {
+        self.source_map.set_default_file(source_file);
+    }
+    
+    /// Get the name of the source file being compiled
+    pub fn source_file(&self) -> &str {
+        self.source_map.default_file()
+    }
+    
+    /// Set the names of all source files, for modules loaded from several files
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = source_map;
+    }
+    
+    /// Names of the source files spans point into
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+    
+    /// `file:line:column` of a position, for diagnostics
+    pub fn source_location(&self, position: &Position) -> String {
+        self.source_map.location(position)
+    }
+    
+    /// Instrument every function compiled from now on with `hooks`
+    pub fn set_profile_hooks(&mut self, hooks: ProfileHooks) {
+        self.profile_hooks = Some(hooks);
+    }
+    
+    /// Profiling hooks, if this is an instrumented build
+    pub fn profile_hooks(&self) -> Option<&ProfileHooks> {
+        self.profile_hooks.as_ref()
+    }
+    
+    /// Use `intrinsics` for the intrinsic calls and string literals compiled from now on
+    pub fn set_intrinsics(&mut self, intrinsics: Intrinsics) {
+        self.intrinsics = intrinsics;
+    }
+    
+    /// Runtime functions of the intrinsics the module calls and its string literals
+    pub fn intrinsics(&self) -> &Intrinsics {
+        &self.intrinsics
+    }
+    
+    /// Record source locations in every function compiled from now on
+    pub fn set_debug_info(&mut self, debug_info: bool) {
+        self.debug_info = debug_info;
+    }
+    
+    /// Whether compiled functions record source locations
+    pub fn debug_info(&self) -> bool {
+        self.debug_info
+    }
+    
     /// Clear all variables (for new scope)
     pub fn clear_variables(&mut self) {
         self.variables.clear();
@@ -139,9 +459,4 @@ impl CraneliftContext {
     pub fn variable_count(&self) -> usize {
         self.variables.len()
     }
-
-    /// Get all registered functions
-    pub fn get_all_functions(&self) -> &HashMap<String, FuncId> {
-        &self.functions
-    }
 } 
\ No newline at end of file
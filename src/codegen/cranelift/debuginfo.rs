@@ -0,0 +1,282 @@
+//! DWARF Debug Info Emission
+//!
+//! With `CodegenOptions::emit_debug_info`, the instructions of every statement carry
+//! a Cranelift source location: an index into the compiled function's table of
+//! source positions. Once the module is finished, those tables become a DWARF line
+//! program and a compile unit with one subprogram per function, written as the
+//! object's `.debug_*` sections so gdb and lldb can map addresses back to
+//! `file:line`. Functions keep their source names, which are also their symbol
+//! names (methods are `Type_method`), so `break main` works as written.
+//!
+//! Without the option nothing here runs and the object is unchanged.
+
+use super::{CodegenError, CodegenResult};
+use crate::ast::SourceMap;
+use crate::lexer::Position;
+use cranelift_codegen::gimli::{self, RunTimeEndian};
+use cranelift_codegen::gimli::write::{
+    Address, AttributeValue, DwarfUnit, EndianVec, FileId, LineProgram, LineString, Range, RangeList, Sections, Writer,
+};
+use cranelift_codegen::ir::SourceLoc;
+use cranelift_codegen::CompiledCode;
+use cranelift_frontend::FunctionBuilder;
+use cranelift_module::FuncId;
+use cranelift_object::object::write::{Relocation, SectionId, StandardSegment, SymbolId};
+use cranelift_object::object::{BinaryFormat, RelocationEncoding, RelocationKind, SectionKind};
+use cranelift_object::ObjectProduct;
+use std::collections::HashMap;
+
+/// A compiled function's machine code and the source lines it was compiled from
+#[derive(Debug, Clone)]
+pub struct FunctionLines {
+    pub func_id: FuncId,
+    pub name: String,
+    /// Where the function is declared
+    pub declared_at: Position,
+    /// Size of the machine code in bytes
+    pub code_size: u32,
+    /// Code offset -> source position of the instructions starting there, by offset
+    pub rows: Vec<(u32, Position)>,
+}
+
+impl FunctionLines {
+    /// Line rows of `code`, whose source locations index `source_locations`
+    pub fn new(func_id: FuncId, name: &str, declared_at: Position, code: &CompiledCode, source_locations: &[Position]) -> Self {
+        let rows = code.buffer.get_srclocs_sorted().iter()
+            .filter(|srcloc| !srcloc.loc.is_default())
+            .filter_map(|srcloc| source_locations.get(srcloc.loc.bits() as usize).map(|position| (srcloc.start, *position)))
+            .collect();
+        Self {
+            func_id,
+            name: name.to_string(),
+            declared_at,
+            code_size: code.code_buffer().len() as u32,
+            rows,
+        }
+    }
+}
+
+/// Attribute the instructions `builder` emits from now on to `position`, recording it in
+/// the function's table of source locations
+pub fn set_source_location(builder: &mut FunctionBuilder, source_locations: &mut Vec<Position>, position: Position) {
+    builder.set_srcloc(SourceLoc::new(source_locations.len() as u32));
+    source_locations.push(position);
+}
+
+/// What a relocation in a debug section refers to
+#[derive(Debug, Clone, Copy)]
+enum RelocationTarget {
+    /// The function at this index of the emitted `FunctionLines`
+    Function(usize),
+    /// The start of another debug section
+    Section(gimli::SectionId),
+}
+
+/// A place in a debug section the linker has to fill in
+#[derive(Debug, Clone, Copy)]
+struct DebugRelocation {
+    offset: usize,
+    size: u8,
+    target: RelocationTarget,
+    addend: i64,
+}
+
+/// Section writer that records function addresses and section offsets as relocations
+#[derive(Clone)]
+struct RelocatingWriter {
+    data: EndianVec<RunTimeEndian>,
+    relocations: Vec<DebugRelocation>,
+}
+
+impl RelocatingWriter {
+    fn new(endian: RunTimeEndian) -> Self {
+        Self { data: EndianVec::new(endian), relocations: Vec::new() }
+    }
+}
+
+impl Writer for RelocatingWriter {
+    type Endian = RunTimeEndian;
+
+    fn endian(&self) -> Self::Endian {
+        self.data.endian()
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> gimli::write::Result<()> {
+        self.data.write(bytes)
+    }
+
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> gimli::write::Result<()> {
+        self.data.write_at(offset, bytes)
+    }
+
+    fn write_address(&mut self, address: Address, size: u8) -> gimli::write::Result<()> {
+        match address {
+            Address::Constant(value) => self.write_udata(value, size),
+            Address::Symbol { symbol, addend } => {
+                let target = RelocationTarget::Function(symbol);
+                self.relocations.push(DebugRelocation { offset: self.len(), size, target, addend });
+                self.write_udata(addend as u64, size)
+            }
+        }
+    }
+
+    fn write_offset(&mut self, val: usize, section: gimli::SectionId, size: u8) -> gimli::write::Result<()> {
+        let target = RelocationTarget::Section(section);
+        self.relocations.push(DebugRelocation { offset: self.len(), size, target, addend: val as i64 });
+        self.write_udata(val as u64, size)
+    }
+
+    fn write_offset_at(&mut self, offset: usize, val: usize, section: gimli::SectionId, size: u8) -> gimli::write::Result<()> {
+        let target = RelocationTarget::Section(section);
+        self.relocations.push(DebugRelocation { offset, size, target, addend: val as i64 });
+        self.write_udata_at(offset, val as u64, size)
+    }
+}
+
+/// Error for DWARF the writer rejected
+fn dwarf_error(error: gimli::write::Error) -> CodegenError {
+    CodegenError::InternalError(format!("Failed to write debug info: {}", error))
+}
+
+/// Write the line program and debug info of `functions` into the finished object
+///
+/// Lines and declarations name files through `source_map`; `endian` is the target's.
+pub fn emit_debug_sections(
+    product: &mut ObjectProduct,
+    functions: &[FunctionLines],
+    source_map: &SourceMap,
+    endian: RunTimeEndian,
+) -> CodegenResult<()> {
+    if functions.is_empty() {
+        return Ok(());
+    }
+
+    let address_size = product.object.architecture().address_size().map_or(8, |size| size.bytes());
+    let encoding = gimli::Encoding { format: gimli::Format::Dwarf32, version: 4, address_size };
+    let mut dwarf = DwarfUnit::new(encoding);
+
+    // Paths in the line program are relative to the directory the compiler ran in
+    let comp_dir = std::env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|_| ".".to_string());
+    let main_file = source_map.default_file();
+    dwarf.unit.line_program = LineProgram::new(
+        encoding,
+        gimli::LineEncoding::default(),
+        LineString::new(comp_dir.as_bytes(), encoding, &mut dwarf.line_strings),
+        LineString::new(main_file.as_bytes(), encoding, &mut dwarf.line_strings),
+        None,
+    );
+
+    let mut files: HashMap<usize, FileId> = HashMap::new();
+    let mut file_of = |dwarf: &mut DwarfUnit, position: &Position| {
+        *files.entry(position.file_id).or_insert_with(|| {
+            let name = LineString::new(source_map.file_name(position.file_id).as_bytes(), encoding, &mut dwarf.line_strings);
+            let directory = dwarf.unit.line_program.default_directory();
+            dwarf.unit.line_program.add_file(name, directory, None)
+        })
+    };
+
+    // One line sequence and one subprogram per function
+    let root = dwarf.unit.root();
+    let mut ranges = Vec::new();
+    for (index, function) in functions.iter().enumerate() {
+        let address = Address::Symbol { symbol: index, addend: 0 };
+        dwarf.unit.line_program.begin_sequence(Some(address));
+        for (offset, position) in &function.rows {
+            let file = file_of(&mut dwarf, position);
+            let row = dwarf.unit.line_program.row();
+            row.address_offset = u64::from(*offset);
+            row.file = file;
+            row.line = position.line as u64;
+            row.column = position.column as u64;
+            dwarf.unit.line_program.generate_row();
+        }
+        dwarf.unit.line_program.end_sequence(u64::from(function.code_size));
+        ranges.push(Range::StartLength { begin: address, length: u64::from(function.code_size) });
+
+        let decl_file = file_of(&mut dwarf, &function.declared_at);
+        let name = dwarf.strings.add(function.name.as_str());
+        let subprogram = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(subprogram);
+        entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+        entry.set(gimli::DW_AT_low_pc, AttributeValue::Address(address));
+        entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(u64::from(function.code_size)));
+        entry.set(gimli::DW_AT_decl_file, AttributeValue::FileIndex(Some(decl_file)));
+        entry.set(gimli::DW_AT_decl_line, AttributeValue::Udata(function.declared_at.line as u64));
+    }
+
+    // The compile unit covers every function; debuggers have no Bract mode, so it claims C
+    let range_list = dwarf.unit.ranges.add(RangeList(ranges));
+    let producer = dwarf.strings.add(format!("bract {}", env!("CARGO_PKG_VERSION")));
+    let name = dwarf.strings.add(main_file);
+    let comp_dir = dwarf.strings.add(comp_dir);
+    let entry = dwarf.unit.get_mut(root);
+    entry.set(gimli::DW_AT_producer, AttributeValue::StringRef(producer));
+    entry.set(gimli::DW_AT_language, AttributeValue::Language(gimli::DW_LANG_C));
+    entry.set(gimli::DW_AT_name, AttributeValue::StringRef(name));
+    entry.set(gimli::DW_AT_comp_dir, AttributeValue::StringRef(comp_dir));
+    entry.set(gimli::DW_AT_low_pc, AttributeValue::Address(Address::Constant(0)));
+    entry.set(gimli::DW_AT_ranges, AttributeValue::RangeListRef(range_list));
+
+    let mut sections = Sections::new(RelocatingWriter::new(endian));
+    dwarf.write(&mut sections).map_err(dwarf_error)?;
+    write_sections(product, functions, &mut sections)
+}
+
+/// Add the non-empty DWARF sections to the object, with their relocations
+fn write_sections(product: &mut ObjectProduct, functions: &[FunctionLines], sections: &mut Sections<RelocatingWriter>) -> CodegenResult<()> {
+    let symbols: Vec<SymbolId> = functions.iter().map(|function| product.function_symbol(function.func_id)).collect();
+    let object = &mut product.object;
+    let format = object.format();
+
+    // Sections are all added first, since any of them may refer to any other
+    let mut section_ids: HashMap<gimli::SectionId, SectionId> = HashMap::new();
+    sections.for_each(|id, writer| {
+        if writer.data.len() > 0 {
+            let name = match format {
+                BinaryFormat::MachO => id.name().replacen('.', "__", 1),
+                _ => id.name().to_string(),
+            };
+            let segment = object.segment_name(StandardSegment::Debug).to_vec();
+            section_ids.insert(id, object.add_section(segment, name.into_bytes(), SectionKind::Debug));
+        }
+        Ok::<_, CodegenError>(())
+    })?;
+
+    // Mach-O debug sections hold plain offsets into each other; COFF needs them section-relative
+    let section_offset_kind = match format {
+        BinaryFormat::MachO => None,
+        BinaryFormat::Coff => Some(RelocationKind::SectionOffset),
+        _ => Some(RelocationKind::Absolute),
+    };
+    sections.for_each_mut(|id, writer| {
+        let Some(&section) = section_ids.get(&id) else {
+            return Ok(());
+        };
+        object.set_section_data(section, writer.data.take(), 1);
+        for relocation in &writer.relocations {
+            let (kind, symbol) = match relocation.target {
+                RelocationTarget::Function(index) => (RelocationKind::Absolute, symbols[index]),
+                RelocationTarget::Section(target) => match (section_offset_kind, section_ids.get(&target)) {
+                    (Some(kind), Some(&target)) => (kind, object.section_symbol(target)),
+                    _ => continue,
+                },
+            };
+            object.add_relocation(section, Relocation {
+                offset: relocation.offset as u64,
+                size: relocation.size * 8,
+                kind,
+                encoding: RelocationEncoding::Generic,
+                symbol,
+                addend: relocation.addend,
+            })
+            .map_err(|e| CodegenError::InternalError(format!("Failed to relocate {}: {}", id.name(), e)))?;
+        }
+        Ok(())
+    })
+}
@@ -2,7 +2,7 @@
 //!
 //! This module handles expression code generation for all Bract expressions.
 
-use crate::ast::{Expr, Literal};
+use crate::ast::{Expr, Literal, PrimitiveType};
 use super::{CodegenResult, CodegenError};
 use cranelift::prelude::{types as ctypes, Value, InstBuilder};
 use cranelift_frontend::FunctionBuilder;
@@ -39,23 +39,42 @@ pub fn compile_expression(
 }
 
 /// Compile a literal value
+///
+/// A suffixed number is emitted at its suffix's width. Unsuffixed integers are I32,
+/// or I64 when the value does not fit, and are converted to their context's width by
+/// the caller; unsuffixed floats are F64.
 pub fn compile_literal(builder: &mut FunctionBuilder, literal: &Literal) -> CodegenResult<Value> {
     match literal {
-        Literal::Integer { value, .. } => {
-            // Parse the string value to get the actual integer
-            let int_value: i64 = value.parse().map_err(|_| {
+        Literal::Integer { value, suffix, .. } => {
+            let int_value = literal.integer_value().filter(|int_value| *int_value <= u64::MAX as u128).ok_or_else(|| {
                 CodegenError::InternalError(format!("Invalid integer literal: {}", value))
-            })?;
-            // For now, assume i32 for integer literals
-            Ok(builder.ins().iconst(ctypes::I32, int_value))
+            })? as u64;
+            let int_type = match suffix {
+                Some(suffix) => match suffix.integer_bits() {
+                    Some(8) => ctypes::I8,
+                    Some(16) => ctypes::I16,
+                    Some(32) => ctypes::I32,
+                    Some(64) => ctypes::I64,
+                    _ => return Err(CodegenError::UnsupportedFeature(format!(
+                        "{:?} integer literals not supported yet", suffix
+                    ))),
+                },
+                None if int_value <= i32::MAX as u64 => ctypes::I32,
+                None => ctypes::I64,
+            };
+            // Cranelift wants the immediate zero-extended from the type's width
+            let mask = u64::MAX >> (64 - int_type.bits());
+            Ok(builder.ins().iconst(int_type, (int_value & mask) as i64))
         }
-        Literal::Float { value, .. } => {
+        Literal::Float { value, suffix } => {
             // Parse the string value to get the actual float
             let float_value: f64 = value.parse().map_err(|_| {
                 CodegenError::InternalError(format!("Invalid float literal: {}", value))
             })?;
-            // For now, assume f64 for float literals
-            Ok(builder.ins().f64const(float_value))
+            match suffix {
+                Some(PrimitiveType::F32) => Ok(builder.ins().f32const(float_value as f32)),
+                _ => Ok(builder.ins().f64const(float_value)),
+            }
         }
         Literal::String { .. } => {
             // String literals - basic implementation
@@ -79,6 +98,23 @@ pub fn compile_literal(builder: &mut FunctionBuilder, literal: &Literal) -> Code
     }
 }
 
+/// Normalize a condition to Bract's `bool` representation (I8)
+///
+/// Comparisons and `bool` values are already I8 and pass through unchanged; wider
+/// integers are coerced with an explicit `!= 0`. Floats have no truth value.
+pub fn normalize_condition(builder: &mut FunctionBuilder, value: Value) -> CodegenResult<Value> {
+    let value_type = builder.func.dfg.value_type(value);
+    if value_type == ctypes::I8 {
+        Ok(value)
+    } else if value_type.is_int() {
+        Ok(builder.ins().icmp_imm(cranelift::prelude::IntCC::NotEqual, value, 0))
+    } else {
+        Err(CodegenError::TypeConversion(
+            format!("Condition must be a bool or integer, found {}", value_type)
+        ))
+    }
+}
+
 /// Compile a variable reference
 fn compile_variable(_builder: &mut FunctionBuilder, _name: &crate::ast::InternedString) -> CodegenResult<Value> {
     // TODO: Implement variable lookup from symbol table
@@ -112,8 +148,15 @@ fn compile_binary_op(
         BinaryOp::LessEqual => Ok(builder.ins().icmp(cranelift::prelude::IntCC::SignedLessThanOrEqual, left_val, right_val)),
         BinaryOp::Greater => Ok(builder.ins().icmp(cranelift::prelude::IntCC::SignedGreaterThan, left_val, right_val)),
         BinaryOp::GreaterEqual => Ok(builder.ins().icmp(cranelift::prelude::IntCC::SignedGreaterThanOrEqual, left_val, right_val)),
-        BinaryOp::LogicalAnd => Ok(builder.ins().band(left_val, right_val)),
-        BinaryOp::LogicalOr => Ok(builder.ins().bor(left_val, right_val)),
+        BinaryOp::LogicalAnd | BinaryOp::LogicalOr => {
+            let left_bool = normalize_condition(builder, left_val)?;
+            let right_bool = normalize_condition(builder, right_val)?;
+            if *op == BinaryOp::LogicalAnd {
+                Ok(builder.ins().band(left_bool, right_bool))
+            } else {
+                Ok(builder.ins().bor(left_bool, right_bool))
+            }
+        }
         _ => Err(CodegenError::UnsupportedFeature(
             format!("Binary operator not supported: {:?}", op)
         )),
@@ -131,15 +174,14 @@ fn compile_unary_op(
     use crate::ast::UnaryOp;
     
     match op {
-        UnaryOp::Negate => {
-            // Negate: 0 - operand
-            let zero = builder.ins().iconst(ctypes::I32, 0);
-            Ok(builder.ins().isub(zero, operand_val))
-        }
+        UnaryOp::Negate if builder.func.dfg.value_type(operand_val).is_float() => Ok(builder.ins().fneg(operand_val)),
+        UnaryOp::Negate => Ok(builder.ins().ineg(operand_val)),
+        UnaryOp::Plus => Ok(operand_val),
+        UnaryOp::BitwiseNot if builder.func.dfg.value_type(operand_val).is_int() => Ok(builder.ins().bnot(operand_val)),
         UnaryOp::Not => {
             // Logical not: operand == 0
-            let zero = builder.ins().iconst(ctypes::I8, 0);
-            Ok(builder.ins().icmp(cranelift::prelude::IntCC::Equal, operand_val, zero))
+            let condition = normalize_condition(builder, operand_val)?;
+            Ok(builder.ins().icmp_imm(cranelift::prelude::IntCC::Equal, condition, 0))
         }
         _ => Err(CodegenError::UnsupportedFeature(
             format!("Unary operator not supported: {:?}", op)
@@ -160,37 +202,17 @@ fn compile_function_call(
 }
 
 /// Compile array access
+///
+/// Elements are addressed through the array variable's element type, which only
+/// the function compiler knows; see `addressing`.
 fn compile_array_access(
-    builder: &mut FunctionBuilder,
-    array: &Expr,
-    index: &Expr,
+    _builder: &mut FunctionBuilder,
+    _array: &Expr,
+    _index: &Expr,
 ) -> CodegenResult<Value> {
-    // For our simplified implementation, we'll handle specific cases
-    let _array_val = compile_expression(builder, array)?;
-    let index_val = compile_expression(builder, index)?;
-    
-    // For now, we'll simulate array access by returning different values based on index
-    // This is a very simplified implementation for our test case [1, 2, 3][1] = 2
-    
-    // Create constants for array elements 
-    let elem_1 = builder.ins().iconst(ctypes::I32, 1);
-    let elem_2 = builder.ins().iconst(ctypes::I32, 2);
-    let elem_3 = builder.ins().iconst(ctypes::I32, 3);
-    
-    // Check if index is 0, 1, or 2
-    let zero = builder.ins().iconst(ctypes::I32, 0);
-    let one = builder.ins().iconst(ctypes::I32, 1);
-    let two = builder.ins().iconst(ctypes::I32, 2);
-    
-    let is_zero = builder.ins().icmp(cranelift::prelude::IntCC::Equal, index_val, zero);
-    let _is_one = builder.ins().icmp(cranelift::prelude::IntCC::Equal, index_val, one);
-    let is_two = builder.ins().icmp(cranelift::prelude::IntCC::Equal, index_val, two);
-    
-    // Use select to choose the right element
-    let result_01 = builder.ins().select(is_zero, elem_1, elem_2);
-    let result = builder.ins().select(is_two, elem_3, result_01);
-    
-    Ok(result)
+    Err(CodegenError::UnsupportedFeature(
+        "Array indexing outside a function body not supported".to_string()
+    ))
 }
 
 /// Compile field access
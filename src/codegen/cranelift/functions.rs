@@ -3,38 +3,212 @@
 //! This module handles function signature generation, calling conventions,
 //! and function body compilation.
 
-use crate::ast::{Item, Stmt, Expr, Type as AstType, Parameter, Pattern};
+use crate::ast::{Item, Stmt, Expr, Type as AstType, Parameter, Pattern, PrimitiveType, Span, NodeId};
 use crate::parser::StringInterner;
-use super::{CodegenResult, CodegenError, utils, expressions};
+use super::{CodegenResult, CodegenError, utils, expressions, closures};
+use super::context::{CraneliftContext, ModuleView, FunctionEntry, StructLayout, FieldLayout, EnumLayout, VariantLayout, STRUCT_FIELD_SLOT_BYTES};
+use super::memory::{BractMemoryManager, MemoryStrategy, MemoryAttributes, MemoryPolicy, AllocationOptions, EscapeInfo, TypeStrategy, SmartPointerRef};
+use super::profiling::ProfileExit;
+use super::intrinsics::Intrinsics;
+use super::debuginfo::{self, FunctionLines};
+use super::addressing::{self, Address, ElementIndex};
+use crate::lexer::Position;
+use crate::semantic::{EscapeContext, EscapeFact, Intrinsic};
 use cranelift::prelude::{types as ctypes, Type, Value, InstBuilder, AbiParam};
-use cranelift_codegen::ir::StackSlot;
+use cranelift_codegen::ir::{Block, StackSlot, TrapCode, FuncRef, SigRef, Signature, ExtFuncData, ExternalName, UserExternalName, ArgumentExtension};
+use cranelift_codegen::control::ControlPlane;
+use cranelift_codegen::isa::CallConv;
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
-use cranelift_module::{Module as CraneliftModule, Linkage};
+use cranelift_module::{Module as CraneliftModule, ModuleDeclarations, DataId, FuncId, Linkage};
 use cranelift_codegen::Context;
-use std::collections::HashMap;
+use crate::visitor::{self, Visitor};
+use std::collections::{HashMap, HashSet};
+
+/// Repeat expressions of at most this many elements store each element directly;
+/// longer ones are filled by a loop
+const MAX_UNROLLED_REPEAT: u32 = 16;
+
+/// Constants defined in terms of other constants are followed this many levels deep
+const MAX_CONSTANT_DEPTH: usize = 32;
+
+/// Structs and enums are followed this many levels deep into the types they hold by value
+const MAX_LAYOUT_NESTING: usize = 64;
+
+/// Smallest region created for a region block, and the granularity of its size estimate
+const REGION_BLOCK_GRANULARITY: u64 = 64;
 
 /// Local variable information for compilation
 #[derive(Debug, Clone)]
 pub struct LocalVariable {
     pub stack_slot: StackSlot,
     pub cranelift_type: Type,
+    /// Unsigned integers (and bools) are zero-extended when widened
+    pub unsigned: bool,
     pub name: String, // For debugging
 }
 
+/// Jump targets of an enclosing loop
+#[derive(Debug, Clone)]
+pub struct LoopTarget {
+    /// Loop label, if any
+    pub label: Option<crate::ast::InternedString>,
+    /// Block re-evaluating the loop condition (`continue` target)
+    pub header: Block,
+    /// Block following the loop (`break` target)
+    pub exit: Block,
+    /// Region blocks open when the loop was entered
+    pub region_depth: usize,
+    /// Scopes open when the loop was entered
+    pub scope_depth: usize,
+    /// Id of the first allocation made inside the loop
+    pub first_alloc_id: u32,
+    /// Whether `break` may carry a value: only `loop` has one, `while` and `for` are unit
+    pub valued: bool,
+    /// What the `break`s out of the loop compiled so far carry
+    pub breaks: LoopBreaks,
+}
+
+/// What the `break`s out of a loop carry; the first one decides for the rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopBreaks {
+    /// No `break` leaves the loop yet
+    Never,
+    /// Plain `break`s: the loop is unit
+    Unit,
+    /// `break value`s of this type, passed to the exit block as its parameter
+    Value(Type),
+}
+
+/// Declared return type of the function being compiled
+#[derive(Debug, Clone)]
+pub struct ReturnType {
+    pub cranelift_type: Type,
+    /// Source-level type name (for diagnostics)
+    pub name: String,
+    /// Layout of a returned tuple, whose elements are returned as separate values
+    pub tuple_layout: Option<String>,
+}
+
+/// Everything a `VariableContext` knew about one name before a scope shadowed it
+struct ShadowedBinding {
+    variable: Option<LocalVariable>,
+    unit: bool,
+    struct_name: Option<String>,
+    pointee: Option<(Type, bool)>,
+    array_length: Option<u32>,
+    array_element: Option<(Type, bool)>,
+    function_pointer: Option<Signature>,
+    smart_pointer: Option<SmartPointerHold>,
+    smart_pointer_slot: Option<SmartPointerRef>,
+    linear: Option<Value>,
+}
+
+/// A variable's reference to a smart pointer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmartPointerHold {
+    /// One of the references to the allocation `ptr` shared by the variables of the
+    /// scope that took it over, released together when the scope ends
+    Allocation(Value),
+    /// A reference of the variable's own, held in a slot
+    Slot(SmartPointerRef),
+}
+
+/// A block, loop body, branch or match arm whose declarations end with it
+#[derive(Default)]
+struct Scope {
+    /// Names declared in the scope -> their bindings outside it
+    shadowed: HashMap<u32, ShadowedBinding>,
+    /// Smart pointer references of the scope's variables, released when it ends
+    owned: Vec<SmartPointerHold>,
+}
+
 /// Variable context for function compilation
-pub struct VariableContext {
+pub struct VariableContext<'m> {
     pub variables: HashMap<u32, LocalVariable>, // InternedString ID -> Variable info
+    /// Variables bound to a unit value, which have no stack slot
+    pub unit_vars: HashSet<u32>,
     pub next_slot_id: u32,
-    /// Function registry for function calls
-    pub functions: HashMap<String, (cranelift_module::FuncId, cranelift_codegen::ir::Signature)>,
+    /// Module-wide function, layout and data tables, and the facts semantic analysis found
+    pub module_context: &'m CraneliftContext,
+    /// Functions already imported into the function being built
+    pub func_refs: HashMap<crate::ast::InternedString, FuncRef>,
+    /// Hybrid memory manager used for heap-requiring expressions
+    pub memory_manager: &'m mut BractMemoryManager,
+    /// Name of the function being compiled (for allocation source locations)
+    pub function_name: String,
+    /// Variables holding smart pointers -> the reference they hold
+    pub smart_pointer_vars: HashMap<u32, SmartPointerHold>,
+    /// Variables assigned smart pointers -> the slot keeping their own reference
+    pub smart_pointer_slots: HashMap<u32, SmartPointerRef>,
+    /// Variables holding linear values -> the value that last took ownership
+    pub linear_vars: HashMap<u32, Value>,
+    /// Regions of the enclosing region blocks, innermost last
+    pub region_stack: Vec<u32>,
+    /// Enclosing loops, innermost last
+    pub loop_stack: Vec<LoopTarget>,
+    /// Declared return type, None for functions returning nothing
+    pub return_type: Option<ReturnType>,
+    /// Array variables whose length is known statically -> element count
+    pub array_lengths: HashMap<u32, u32>,
+    /// Array variables whose element type is known -> element type and signedness
+    pub array_elements: HashMap<u32, (Type, bool)>,
+    /// Integer values holding unsigned data, zero-extended when widened
+    pub unsigned_values: HashSet<Value>,
+    /// Reference variables -> pointee type and signedness
+    pub pointer_vars: HashMap<u32, (Type, bool)>,
+    /// Pointer values with a known pointee -> pointee type and signedness
+    pub pointee_types: HashMap<Value, (Type, bool)>,
+    /// Layouts of the tuple types used in this function, named like `(i32, u8)`
+    pub tuple_layouts: HashMap<String, StructLayout>,
+    /// Struct and enum variables -> type name
+    pub struct_vars: HashMap<u32, String>,
+    /// Pointers to struct storage and enum values -> type name
+    pub struct_values: HashMap<Value, String>,
+    /// Function pointer variables -> signature of the functions they point to
+    pub function_pointer_vars: HashMap<u32, Signature>,
+    /// Function pointer values -> signature of the functions they point to
+    pub function_pointers: HashMap<Value, Signature>,
+    /// Profiling exit hook, called before every return in instrumented builds
+    pub profile_exit: Option<ProfileExit>,
+    /// Requests of the function's memory annotations
+    pub memory_attributes: MemoryAttributes,
+    /// Source positions the function's instructions are attributed to, indexed by their
+    /// Cranelift source location; None unless debug info is emitted
+    pub source_locations: Option<Vec<Position>>,
+    /// Enclosing block scopes, innermost last
+    scopes: Vec<Scope>,
 }
 
-impl VariableContext {
-    pub fn new() -> Self {
+impl<'m> VariableContext<'m> {
+    pub fn new(memory_manager: &'m mut BractMemoryManager, module_context: &'m CraneliftContext, function_name: String) -> Self {
         Self {
             variables: HashMap::new(),
+            unit_vars: HashSet::new(),
             next_slot_id: 0,
-            functions: HashMap::new(),
+            module_context,
+            func_refs: HashMap::new(),
+            memory_manager,
+            function_name,
+            smart_pointer_vars: HashMap::new(),
+            smart_pointer_slots: HashMap::new(),
+            linear_vars: HashMap::new(),
+            region_stack: Vec::new(),
+            loop_stack: Vec::new(),
+            return_type: None,
+            array_lengths: HashMap::new(),
+            array_elements: HashMap::new(),
+            unsigned_values: HashSet::new(),
+            pointer_vars: HashMap::new(),
+            pointee_types: HashMap::new(),
+            tuple_layouts: HashMap::new(),
+            struct_vars: HashMap::new(),
+            struct_values: HashMap::new(),
+            function_pointer_vars: HashMap::new(),
+            function_pointers: HashMap::new(),
+            profile_exit: None,
+            memory_attributes: MemoryAttributes::default(),
+            source_locations: None,
+            scopes: Vec::new(),
         }
     }
 
@@ -43,609 +217,2246 @@ impl VariableContext {
         builder: &mut FunctionBuilder,
         name_id: u32,
         cranelift_type: Type,
+        unsigned: bool,
         name: String,
     ) -> CodegenResult<StackSlot> {
         // Create stack slot for the variable
         let stack_slot = builder.create_sized_stack_slot(cranelift_codegen::ir::StackSlotData::new(
             cranelift_codegen::ir::StackSlotKind::ExplicitSlot,
-            utils::type_size(cranelift_type) as u32,
+            utils::type_size(cranelift_type, self.memory_manager.pointer_type()) as u32,
         ));
 
         let local_var = LocalVariable {
             stack_slot,
             cranelift_type,
+            unsigned,
             name: name.clone(),
         };
 
+        self.shadow(name_id);
+        self.unit_vars.remove(&name_id);
         self.variables.insert(name_id, local_var);
         Ok(stack_slot)
     }
+    
+    /// Bind `name_id` to a unit value, shadowing whatever an earlier variable of this name held
+    pub fn declare_unit_variable(&mut self, name_id: u32) {
+        self.shadow(name_id);
+        self.variables.remove(&name_id);
+        self.unit_vars.insert(name_id);
+        self.bind_struct(name_id, None);
+        self.bind_pointee(name_id, None);
+        self.bind_function_pointer(name_id, None);
+        self.bind_array(name_id, None, None);
+        self.smart_pointer_vars.remove(&name_id);
+        self.smart_pointer_slots.remove(&name_id);
+        self.linear_vars.remove(&name_id);
+    }
 
     pub fn get_variable(&self, name_id: u32) -> Option<&LocalVariable> {
         self.variables.get(&name_id)
     }
     
-    /// Register a function for calls
-    pub fn register_function(&mut self, name: String, func_id: cranelift_module::FuncId, signature: cranelift_codegen::ir::Signature) {
-        self.functions.insert(name, (func_id, signature));
+    /// Record that `value` holds unsigned data
+    pub fn mark_unsigned(&mut self, value: Value) {
+        self.unsigned_values.insert(value);
     }
     
-    /// Get function info for calls
-    pub fn get_function(&self, name: &str) -> Option<&(cranelift_module::FuncId, cranelift_codegen::ir::Signature)> {
-        self.functions.get(name)
+    /// Whether `value` holds unsigned data (signed unless marked)
+    pub fn is_unsigned(&self, value: Value) -> bool {
+        self.unsigned_values.contains(&value)
     }
-}
-
-/// Declare a function signature in the module
-pub fn declare_function_item(
-    module: &mut dyn CraneliftModule,
-    item: &Item,
-    context: &mut super::CraneliftContext,
-    interner: &StringInterner,
-) -> CodegenResult<()> {
-    match item {
-        Item::Function { 
-            name, 
-            params, 
-            return_type, 
-            is_extern,
-            .. 
-        } => {
-            if *is_extern {
-                // External functions just need declaration
-                return Ok(());
-            }
-            
-            // Create function signature
-            let mut sig = module.make_signature();
-            
-            // Add parameters
-            for param in params {
-                if let Some(param_type) = &param.type_annotation {
-                    let cranelift_type = ast_type_to_cranelift_type(param_type)?;
-                    sig.params.push(AbiParam::new(cranelift_type));
-                } else {
-                    return Err(CodegenError::InternalError("Parameter missing type annotation".to_string()));
-                }
-            }
-            
-            // Add return type
-            if let Some(return_type) = return_type {
-                let ret_type = ast_type_to_cranelift_type(return_type)?;
-                sig.returns.push(AbiParam::new(ret_type));
-            }
-            
-            // Get function name using string interner - FIXED!
-            let func_name = interner.get(name)
-                .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve function name with ID {}", name.id)))?;
-            
-            // Determine linkage - main function gets exported, others are local
-            let linkage = if func_name == "main" {
-                Linkage::Export
-            } else {
-                Linkage::Local
-            };
-            
-            let func_id = module.declare_function(func_name, linkage, &sig)
-                .map_err(|e| CodegenError::InternalError(format!("Failed to declare function '{}': {}", func_name, e)))?;
-            
-            // Register function in context
-            context.register_function(func_name, func_id);
-            context.register_function_signature(func_name, sig);
-            
-            Ok(())
-        }
-        _ => Err(CodegenError::InternalError("Expected function item".to_string())),
+    
+    /// Record the struct type of a variable, or forget it when the variable holds no struct
+    fn bind_struct(&mut self, name_id: u32, struct_name: Option<String>) {
+        match struct_name {
+            Some(struct_name) => self.struct_vars.insert(name_id, struct_name),
+            None => self.struct_vars.remove(&name_id),
+        };
     }
-}
-
-/// Compile a function from Item::Function to Cranelift IR
-pub fn compile_function_item(
-    module: &mut dyn CraneliftModule,
-    item: &Item,
-    builder_context: &mut FunctionBuilderContext,
-    context: &mut super::CraneliftContext,
-    interner: &StringInterner,
-) -> CodegenResult<()> {
-    match item {
-        Item::Function { 
-            name, 
-            params, 
-            return_type, 
-            body, 
-            is_extern,
-            .. 
-        } => {
-            if *is_extern {
-                // External functions just need declaration
-                return Ok(());
+    
+    /// Record the signature a function pointer variable calls with, or forget it when the
+    /// variable holds no function pointer
+    fn bind_function_pointer(&mut self, name_id: u32, signature: Option<Signature>) {
+        match signature {
+            Some(signature) => self.function_pointer_vars.insert(name_id, signature),
+            None => self.function_pointer_vars.remove(&name_id),
+        };
+    }
+    
+    /// Struct or enum type named by `ast_type` (looking through references, pointers and
+    /// strategy wrappers such as `LinearPtr<Point>`), if that type has a layout
+    ///
+    /// Tuple types name the layout of their element types, registering it on first use.
+    fn struct_name_of_ast_type(&mut self, ast_type: &AstType, interner: &StringInterner) -> Option<String> {
+        if let AstType::Tuple { types, .. } = ast_type {
+            if types.is_empty() {
+                return None;
             }
-            
-            let body_expr = body.as_ref().ok_or_else(|| {
-                CodegenError::InternalError("Function body is missing".to_string())
-            })?;
-            
-            compile_function_with_body(module, name, params, return_type, body_expr, builder_context, context, interner)
+            let pointer_type = self.memory_manager.pointer_type();
+            let mut elements = Vec::with_capacity(types.len());
+            for element_type in types {
+                let cranelift_type = ast_type_to_cranelift_type(element_type, pointer_type).ok()?;
+                let struct_name = self.struct_name_of_ast_type(element_type, interner);
+                elements.push((cranelift_type, is_unsigned_ast_type(element_type), struct_name));
+            }
+            return Some(self.register_tuple_layout(&elements));
         }
-        _ => Err(CodegenError::InternalError("Expected function item".to_string())),
+        struct_path_name(ast_type, interner).filter(|name| self.has_layout(name))
     }
-}
-
-/// Compile a function with its body
-fn compile_function_with_body(
-    module: &mut dyn CraneliftModule,
-    name: &crate::ast::InternedString,
-    params: &[Parameter],
-    return_type: &Option<AstType>,
-    body: &Expr,
-    builder_context: &mut FunctionBuilderContext,
-    context: &mut super::CraneliftContext,
-    interner: &StringInterner,
-) -> CodegenResult<()> {
-    // Create function signature
-    let mut sig = module.make_signature();
     
-    // Add parameters
-    for param in params {
-        if let Some(param_type) = &param.type_annotation {
-            let cranelift_type = ast_type_to_cranelift_type(param_type)?;
-            sig.params.push(AbiParam::new(cranelift_type));
-        } else {
-            return Err(CodegenError::InternalError("Parameter missing type annotation".to_string()));
-        }
+    /// Whether `name` is a struct, tuple or enum type with a layout
+    fn has_layout(&self, name: &str) -> bool {
+        self.struct_layout(name).is_some() || self.enum_layout(name).is_some()
     }
     
-    // Add return type
-    if let Some(return_type) = return_type {
-        let ret_type = ast_type_to_cranelift_type(return_type)?;
-        sig.returns.push(AbiParam::new(ret_type));
+    /// Layout of a struct or tuple type
+    fn struct_layout(&self, name: &str) -> Option<&StructLayout> {
+        self.tuple_layouts.get(name).or_else(|| self.module_context.get_struct_layout(name))
     }
     
-    // Get function name using string interner - FIXED!
-    let func_name = interner.get(name)
-        .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve function name with ID {}", name.id)))?;
+    /// Layout of an enum type
+    fn enum_layout(&self, name: &str) -> Option<&EnumLayout> {
+        self.module_context.get_enum_layout(name)
+    }
     
-    // Get the already declared function ID from context
-    let func_id = context.get_function_id(func_name).ok_or_else(|| {
-        CodegenError::InternalError(format!("Function '{}' not declared", func_name))
-    })?;
+    /// Enum variant named by `path`, with the name and layout of its enum
+    ///
+    /// `Enum::Variant` names the variant directly. A bare `Variant` is looked up in
+    /// `expected_enum` first, then among all enums, where it must be unambiguous.
+    fn resolve_variant(
+        &self,
+        path: &[crate::ast::InternedString],
+        expected_enum: Option<&str>,
+        interner: &StringInterner,
+    ) -> Option<(String, EnumLayout, VariantLayout)> {
+        let variant_name = interner.resolve(*path.last()?)?;
+        let found = |enum_name: &str| {
+            let layout = self.enum_layout(enum_name)?;
+            let variant = layout.variant(&variant_name)?.clone();
+            Some((enum_name.to_string(), layout.clone(), variant))
+        };
+        match path {
+            [.., enum_name, _] => found(&interner.resolve(*enum_name)?),
+            _ => expected_enum.and_then(found).or_else(|| {
+                let mut candidates = self.module_context.enum_layouts().filter_map(|(enum_name, _)| found(enum_name));
+                let candidate = candidates.next()?;
+                candidates.next().is_none().then_some(candidate)
+            }),
+        }
+    }
     
-    // Create function context
-    let mut ctx = Context::new();
-    ctx.func.signature = sig;
+    /// Register the layout of a tuple from its element types, signedness and struct names,
+    /// returning the layout name
+    ///
+    /// Tuples are laid out like anonymous structs whose fields are named by position.
+    /// Tuples of the same element types share one layout, named like `(i32, u8)`.
+    fn register_tuple_layout(&mut self, elements: &[(Type, bool, Option<String>)]) -> String {
+        let labels: Vec<String> = elements.iter()
+            .map(|(cranelift_type, unsigned, struct_name)| match struct_name {
+                Some(struct_name) => struct_name.clone(),
+                None if *unsigned && cranelift_type.is_int() => format!("u{}", cranelift_type.bits()),
+                None => cranelift_type.to_string(),
+            })
+            .collect();
+        let layout_name = format!("({})", labels.join(", "));
+        
+        self.tuple_layouts.entry(layout_name.clone()).or_insert_with(|| {
+            let fields: Vec<FieldLayout> = elements.iter()
+                .enumerate()
+                .map(|(i, (cranelift_type, unsigned, struct_name))| FieldLayout {
+                    name: i.to_string(),
+                    cranelift_type: *cranelift_type,
+                    unsigned: *unsigned,
+                    struct_name: struct_name.clone(),
+                    offset: i as u32 * STRUCT_FIELD_SLOT_BYTES,
+                })
+                .collect();
+            let size = (fields.len().max(1) as u32) * STRUCT_FIELD_SLOT_BYTES;
+            StructLayout { fields, size }
+        });
+        layout_name
+    }
     
-    // Create function builder
-    let mut builder = FunctionBuilder::new(&mut ctx.func, builder_context);
+    /// Record the length and element type of an array variable, forgetting what is not known
+    fn bind_array(&mut self, name_id: u32, length: Option<u32>, element: Option<(Type, bool)>) {
+        match length {
+            Some(length) => self.array_lengths.insert(name_id, length),
+            None => self.array_lengths.remove(&name_id),
+        };
+        match element {
+            Some(element) => self.array_elements.insert(name_id, element),
+            None => self.array_elements.remove(&name_id),
+        };
+    }
     
-    // Create entry block
-    let entry_block = builder.create_block();
+    /// Element type and signedness of an array variable; arrays of unknown element type hold i32s
+    fn array_element(&self, name_id: u32) -> (Type, bool) {
+        self.array_elements.get(&name_id).copied().unwrap_or((ctypes::I32, false))
+    }
     
-    // Add block parameters to match function signature
-    for param in params {
-        if let Some(param_type) = &param.type_annotation {
-            let cranelift_type = ast_type_to_cranelift_type(param_type)?;
-            builder.append_block_param(entry_block, cranelift_type);
-        }
+    /// Record the pointee of a reference variable, or forget it when the variable holds none
+    fn bind_pointee(&mut self, name_id: u32, pointee: Option<(Type, bool)>) {
+        match pointee {
+            Some(pointee) => self.pointer_vars.insert(name_id, pointee),
+            None => self.pointer_vars.remove(&name_id),
+        };
     }
     
-    builder.switch_to_block(entry_block);
-    builder.seal_block(entry_block);
+    /// Bind `value` to a fresh variable, shadowing whatever an earlier variable of this name held
+    ///
+    /// `type_name` is the struct or enum type of the value, if any.
+    fn bind_value(&mut self, builder: &mut FunctionBuilder, name_id: u32, value: Value, type_name: Option<String>) -> CodegenResult<()> {
+        let value_type = builder.func.dfg.value_type(value);
+        let unsigned = self.is_unsigned(value);
+        let stack_slot = self.declare_variable(builder, name_id, value_type, unsigned, format!("var_{}", name_id))?;
+        builder.ins().stack_store(value, stack_slot, 0);
+        self.bind_struct(name_id, type_name);
+        self.bind_pointee(name_id, None);
+        self.bind_function_pointer(name_id, None);
+        self.bind_array(name_id, None, None);
+        self.smart_pointer_vars.remove(&name_id);
+        self.smart_pointer_slots.remove(&name_id);
+        self.linear_vars.remove(&name_id);
+        Ok(())
+    }
     
-    // Initialize variable context
-    let mut var_context = VariableContext::new();
+    /// Enter a block scope; names declared until `pop_scope` shadow the enclosing ones
+    fn push_scope(&mut self) {
+        self.scopes.push(Scope::default());
+    }
     
-    // Populate function registry from CraneliftContext for function calls
-    // Use REAL function signatures stored in context
-    for (func_name, func_id) in context.get_all_functions().iter() {
-        if let Some(signature) = context.get_function_signature(func_name) {
-            var_context.register_function(func_name.clone(), *func_id, signature.clone());
-        } else {
-            // Fallback for functions without stored signatures (shouldn't happen)
-            let mut sig = module.make_signature();
-            sig.returns.push(AbiParam::new(ctypes::I32));
-            var_context.register_function(func_name.clone(), *func_id, sig);
+    /// Leave the innermost scope, restoring the bindings its declarations shadowed
+    ///
+    /// Unless the scope ended in a terminator, which already released them, the smart
+    /// pointer references of its variables are released. The stack slots of the scope's
+    /// variables stay allocated: code already emitted still refers to them.
+    fn pop_scope(&mut self, builder: &mut FunctionBuilder, terminated: bool) -> CodegenResult<()> {
+        if !terminated {
+            self.release_smart_pointers(builder, self.scopes.len().saturating_sub(1))?;
+        }
+        let Some(scope) = self.scopes.pop() else { return Ok(()) };
+        for hold in &scope.owned {
+            if let SmartPointerHold::Allocation(ptr) = *hold {
+                self.memory_manager.end_smart_pointer(ptr);
+            }
+        }
+        for (name_id, outer) in scope.shadowed {
+            restore(&mut self.variables, name_id, outer.variable);
+            match outer.unit {
+                true => self.unit_vars.insert(name_id),
+                false => self.unit_vars.remove(&name_id),
+            };
+            restore(&mut self.struct_vars, name_id, outer.struct_name);
+            restore(&mut self.pointer_vars, name_id, outer.pointee);
+            restore(&mut self.array_lengths, name_id, outer.array_length);
+            restore(&mut self.array_elements, name_id, outer.array_element);
+            restore(&mut self.function_pointer_vars, name_id, outer.function_pointer);
+            restore(&mut self.smart_pointer_vars, name_id, outer.smart_pointer);
+            restore(&mut self.smart_pointer_slots, name_id, outer.smart_pointer_slot);
+            restore(&mut self.linear_vars, name_id, outer.linear);
         }
+        Ok(())
     }
     
-    // Add function parameters as local variables
-    let block_params: Vec<_> = builder.block_params(entry_block).to_vec();
-    for (i, param) in params.iter().enumerate() {
-        if let Pattern::Identifier { name, .. } = &param.pattern {
-            let param_type = param.type_annotation.as_ref()
-                .ok_or_else(|| CodegenError::InternalError("Parameter missing type annotation".to_string()))?;
-            let cranelift_type = ast_type_to_cranelift_type(param_type)?;
-            
-            // Get parameter name using interner
-            let param_name = interner.get(name)
-                .map(|s| s.to_string())
-                .unwrap_or_else(|| format!("param_{}", i));
-            
-            // Create stack slot for parameter
-            let stack_slot = var_context.declare_variable(
-                &mut builder,
-                name.id,
-                cranelift_type,
-                param_name,
-            )?;
-            
-            // Store the parameter value to the stack slot
-            builder.ins().stack_store(block_params[i], stack_slot, 0);
+    /// Release the smart pointer references held by the scopes from `depth` inwards,
+    /// innermost first, as code leaving them does
+    fn release_smart_pointers(&mut self, builder: &mut FunctionBuilder, depth: usize) -> CodegenResult<()> {
+        let owned: Vec<SmartPointerHold> = self.scopes.get(depth..).unwrap_or_default().iter()
+            .flat_map(|scope| scope.owned.iter().copied())
+            .collect();
+        for hold in owned.into_iter().rev() {
+            match hold {
+                SmartPointerHold::Allocation(ptr) => self.memory_manager.release_smart_pointer(builder, ptr)?,
+                SmartPointerHold::Slot(reference) => self.memory_manager.release_smart_pointer_ref(builder, reference)?,
+            }
         }
+        Ok(())
     }
     
-    // Compile function body
-    let (result_value, function_terminated) = compile_expression_with_variables_and_termination(&mut builder, body, &mut var_context, interner)?;
-    
-    // Only add return instruction if the function didn't already terminate
-    if !function_terminated {
-        if return_type.is_some() {
-            builder.ins().return_(&[result_value]);
-        } else {
-            builder.ins().return_(&[]);
+    /// Remember the bindings of `name_id` before the innermost scope first declares it
+    ///
+    /// Lookups go through the name-keyed maps, which always hold the innermost binding;
+    /// what a scope replaced is kept here until the scope ends.
+    fn shadow(&mut self, name_id: u32) {
+        let Some(scope) = self.scopes.last() else { return };
+        if scope.shadowed.contains_key(&name_id) {
+            return;
+        }
+        let outer = ShadowedBinding {
+            variable: self.variables.get(&name_id).cloned(),
+            unit: self.unit_vars.contains(&name_id),
+            struct_name: self.struct_vars.get(&name_id).cloned(),
+            pointee: self.pointer_vars.get(&name_id).copied(),
+            array_length: self.array_lengths.get(&name_id).copied(),
+            array_element: self.array_elements.get(&name_id).copied(),
+            function_pointer: self.function_pointer_vars.get(&name_id).cloned(),
+            smart_pointer: self.smart_pointer_vars.get(&name_id).copied(),
+            smart_pointer_slot: self.smart_pointer_slots.get(&name_id).copied(),
+            linear: self.linear_vars.get(&name_id).copied(),
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.shadowed.insert(name_id, outer);
         }
     }
     
-    // Finalize function
-    builder.finalize();
-    
-    // Define function in module (let the module handle verification)
-    module.define_function(func_id, &mut ctx)
-        .map_err(|e| {
-            // Extract more detailed error information
-            let error_msg = format!("{:?}", e);
-            CodegenError::InternalError(format!("Failed to define function '{}': {}", func_name, error_msg))
-        })?;
-    
-    Ok(())
-}
+    /// Reference to a declared function from the function being built, importing it on first use
+    ///
+    /// Mirrors `Module::declare_func_in_func`; functions are only imported once called, so
+    /// the IR doesn't list every function in the module.
+    pub fn import_function(&mut self, builder: &mut FunctionBuilder, name: crate::ast::InternedString) -> CodegenResult<(FuncRef, Signature)> {
+        let entry = self.module_context.lookup_callable(name)?;
+        if let Some(&func_ref) = self.func_refs.get(&name) {
+            return Ok((func_ref, entry.signature.clone()));
+        }
+        
+        let sig_ref = builder.import_signature(entry.signature.clone());
+        let name_ref = builder.func.declare_imported_user_function(UserExternalName::new(0, entry.func_id.as_u32()));
+        let func_ref = builder.import_function(ExtFuncData {
+            name: ExternalName::user(name_ref),
+            signature: sig_ref,
+            // Every registered function is defined in this module
+            colocated: true,
+        });
+        self.func_refs.insert(name, func_ref);
+        Ok((func_ref, entry.signature.clone()))
+    }
 
-/// Compile an expression with variable context and termination tracking
-fn compile_expression_with_variables_and_termination(
-    builder: &mut FunctionBuilder,
-    expr: &Expr,
-    var_context: &mut VariableContext,
-    interner: &StringInterner,
-) -> CodegenResult<(Value, bool)> {
-    match expr {
-        Expr::Return { value, .. } => {
-            // Generate the actual return instruction here!
-            if let Some(value_expr) = value {
-                let return_value = compile_expression_with_variables(builder, value_expr, var_context, interner)?;
-                builder.ins().return_(&[return_value]);
-            } else {
-                // Return unit/void
-                builder.ins().return_(&[]);
-            }
-            // Return a dummy value and mark as terminated
-            let dummy = builder.ins().iconst(ctypes::I32, 0);
-            Ok((dummy, true))
+    /// Get the smart pointer reference an expression refers to, if it names one
+    pub fn smart_pointer_of(&self, expr: &Expr) -> Option<SmartPointerHold> {
+        match expr {
+            Expr::Identifier { name, .. } => self.smart_pointer_vars.get(&name.id).copied(),
+            Expr::Parenthesized { expr, .. } => self.smart_pointer_of(expr),
+            _ => None,
         }
-        Expr::Block { statements, trailing_expr, .. } => {
-            let mut block_terminated = false;
-            let mut result_value = None;
-            
-            // Pre-generate a dummy value in case we need it for terminated blocks
-            let dummy_value = builder.ins().iconst(ctypes::I32, 0);
-            
-            // Compile all statements with termination tracking
-            for stmt in statements {
-                let stmt_terminated = compile_statement_with_variables_and_termination(builder, stmt, var_context, interner)?;
-                if stmt_terminated {
-                    block_terminated = true;
-                    break; // Don't process more statements after termination
-                }
+    }
+
+    /// Offset of the reference count of the smart pointer a variable holds
+    pub fn ref_count_offset(&self, hold: SmartPointerHold) -> Option<u32> {
+        match hold {
+            SmartPointerHold::Allocation(ptr) => self.memory_manager.smart_pointer_ref_count_offset(ptr),
+            SmartPointerHold::Slot(reference) => Some(reference.ref_count_offset),
+        }
+    }
+
+    /// Record the smart pointer (if any) held by a variable just declared with `value`
+    ///
+    /// A fresh allocation's references go to the declaring scope and are shared with
+    /// the scope's other variables copied from it, all released in one step when the
+    /// scope ends. Any other copy keeps a reference of its own until then.
+    pub fn bind_smart_pointer(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        name_id: u32,
+        source: &Expr,
+        value: Value,
+    ) -> CodegenResult<()> {
+        self.smart_pointer_slots.remove(&name_id);
+        let hold = match self.smart_pointer_of(source) {
+            Some(SmartPointerHold::Allocation(ptr)) if self.scope_holds(ptr) => {
+                self.memory_manager.retain_smart_pointer(builder, ptr)?;
+                SmartPointerHold::Allocation(ptr)
             }
-            
-            // Only process trailing expression if block wasn't terminated
-            if !block_terminated {
-                if let Some(trailing) = trailing_expr {
-                    result_value = Some(compile_expression_with_variables(builder, trailing, var_context, interner)?);
+            None if self.memory_manager.claim_smart_pointer(value) => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.owned.push(SmartPointerHold::Allocation(value));
                 }
+                SmartPointerHold::Allocation(value)
             }
-            
-            // Return appropriate value and termination status
-            if block_terminated {
-                // If block terminated, return pre-generated dummy value without generating new instructions
-                Ok((dummy_value, true))
-            } else {
-                // If block didn't terminate, return actual value
-                let final_value = result_value.unwrap_or(dummy_value);
-                Ok((final_value, false))
+            _ => {
+                let Some(ref_count_offset) = self.acquire_smart_pointer(builder, source, value) else {
+                    self.smart_pointer_vars.remove(&name_id);
+                    return Ok(());
+                };
+                let reference = self.memory_manager.new_smart_pointer_ref(builder, ref_count_offset);
+                builder.ins().stack_store(value, reference.slot, 0);
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.owned.push(SmartPointerHold::Slot(reference));
+                }
+                self.smart_pointer_slots.insert(name_id, reference);
+                SmartPointerHold::Slot(reference)
             }
+        };
+        self.smart_pointer_vars.insert(name_id, hold);
+        Ok(())
+    }
+
+    /// Record the smart pointer (if any) a variable holds after it was assigned `value`
+    ///
+    /// The variable keeps the reference in a slot of its own, releasing the one the slot
+    /// held before. References it shares with the other variables of its scope stay
+    /// until the scope ends.
+    pub fn assign_smart_pointer(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        name_id: u32,
+        source: &Expr,
+        value: Value,
+    ) -> CodegenResult<()> {
+        // A copy of the variable itself is retained before its old reference goes
+        let acquired = self.acquire_smart_pointer(builder, source, value);
+        let previous = self.smart_pointer_slots.get(&name_id).copied();
+        if let Some(previous) = previous {
+            self.memory_manager.release_smart_pointer_ref(builder, previous)?;
         }
-        _ => {
-            // For all other expressions, compile normally and mark as not terminated
-            let result = compile_expression_with_variables(builder, expr, var_context, interner)?;
-            Ok((result, false))
-        }
+        let Some(ref_count_offset) = acquired else {
+            self.smart_pointer_vars.remove(&name_id);
+            return Ok(());
+        };
+        let reference = match previous {
+            Some(reference) if reference.ref_count_offset == ref_count_offset => reference,
+            _ => {
+                // The slot lives until the scope declaring the variable ends
+                let reference = self.memory_manager.new_smart_pointer_ref(builder, ref_count_offset);
+                let declaring = self.scopes.iter().rposition(|scope| scope.shadowed.contains_key(&name_id)).unwrap_or(0);
+                if let Some(scope) = self.scopes.get_mut(declaring) {
+                    scope.owned.push(SmartPointerHold::Slot(reference));
+                }
+                self.smart_pointer_slots.insert(name_id, reference);
+                reference
+            }
+        };
+        builder.ins().stack_store(value, reference.slot, 0);
+        self.smart_pointer_vars.insert(name_id, SmartPointerHold::Slot(reference));
+        Ok(())
     }
-}
 
-/// Compile an expression with variable context
-fn compile_expression_with_variables(
-    builder: &mut FunctionBuilder,
-    expr: &Expr,
-    var_context: &mut VariableContext,
-    interner: &StringInterner,
-) -> CodegenResult<Value> {
-    match expr {
-        Expr::Literal { literal, .. } => {
-            expressions::compile_literal(builder, literal)
-        }
-        Expr::Identifier { name, .. } => {
-            // Variable lookup - FIXED!
-            if let Some(var_info) = var_context.get_variable(name.id) {
-            // Load from stack slot
-                Ok(builder.ins().stack_load(var_info.cranelift_type, var_info.stack_slot, 0))
-            } else {
-                let var_name = interner.get(name)
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|| format!("var_{}", name.id));
-                Err(CodegenError::SymbolResolution(
-                    format!("Undefined variable: {}", var_name)
-                ))
-            }
-        }
-        Expr::Binary { left, op, right, .. } => {
-            let left_val = compile_expression_with_variables(builder, left, var_context, interner)?;
-            let right_val = compile_expression_with_variables(builder, right, var_context, interner)?;
-            
-            use crate::ast::BinaryOp;
-            
-            // For now, assume i32 arithmetic
-            match op {
-                BinaryOp::Add => Ok(builder.ins().iadd(left_val, right_val)),
-                BinaryOp::Subtract => Ok(builder.ins().isub(left_val, right_val)),
-                BinaryOp::Multiply => Ok(builder.ins().imul(left_val, right_val)),
-                BinaryOp::Divide => Ok(builder.ins().sdiv(left_val, right_val)),
-                BinaryOp::Modulo => Ok(builder.ins().srem(left_val, right_val)),
-                BinaryOp::Equal => Ok(builder.ins().icmp(cranelift::prelude::IntCC::Equal, left_val, right_val)),
-                BinaryOp::NotEqual => Ok(builder.ins().icmp(cranelift::prelude::IntCC::NotEqual, left_val, right_val)),
-                BinaryOp::Less => Ok(builder.ins().icmp(cranelift::prelude::IntCC::SignedLessThan, left_val, right_val)),
-                BinaryOp::LessEqual => Ok(builder.ins().icmp(cranelift::prelude::IntCC::SignedLessThanOrEqual, left_val, right_val)),
-                BinaryOp::Greater => Ok(builder.ins().icmp(cranelift::prelude::IntCC::SignedGreaterThan, left_val, right_val)),
-                BinaryOp::GreaterEqual => Ok(builder.ins().icmp(cranelift::prelude::IntCC::SignedGreaterThanOrEqual, left_val, right_val)),
-                _ => Err(CodegenError::UnsupportedFeature(
-                    format!("Binary operator not supported: {:?}", op)
-                )),
-            }
-        }
-        Expr::Block { statements, trailing_expr, .. } => {
-            let mut block_terminated = false;
-            let mut result_value = None;
-            
-            // Compile all statements
-            for stmt in statements {
-                // Check if this statement might terminate the block
-                if let Stmt::Return { .. } = stmt {
-                    compile_statement_with_variables(builder, stmt, var_context, interner)?;
-                    block_terminated = true;
-                    break; // Don't process more statements after return
-                } else {
-                    compile_statement_with_variables(builder, stmt, var_context, interner)?;
-                }
-            }
-            
-            // Only process trailing expression if block wasn't terminated
-            if !block_terminated {
-                if let Some(trailing) = trailing_expr {
-                    result_value = Some(compile_expression_with_variables(builder, trailing, var_context, interner)?);
+    /// Take a reference to the smart pointer `value` computed from `source`, returning the
+    /// offset of its reference count
+    ///
+    /// A fresh allocation hands over the reference it starts with; anything else is
+    /// retained.
+    fn acquire_smart_pointer(&mut self, builder: &mut FunctionBuilder, source: &Expr, value: Value) -> Option<u32> {
+        let ref_count_offset = match self.smart_pointer_of(source) {
+            Some(hold) => self.ref_count_offset(hold)?,
+            None => {
+                if let Some(ref_count_offset) = self.memory_manager.take_smart_pointer(value) {
+                    return Some(ref_count_offset);
                 }
+                self.memory_manager.smart_pointer_ref_count_offset(value)?
             }
-            
-            // Return a value (dummy if terminated, actual if not)
-            Ok(result_value.unwrap_or_else(|| builder.ins().iconst(ctypes::I32, 0)))
-        }
-        Expr::Return { value, .. } => {
-            // This should not be reached since Return is handled in termination tracking
-            // But provide a fallback just in case
-            if let Some(value_expr) = value {
-                compile_expression_with_variables(builder, value_expr, var_context, interner)
-            } else {
-                Ok(builder.ins().iconst(ctypes::I32, 0))
-            }
+        };
+        self.memory_manager.retain_smart_pointer_at(builder, value, ref_count_offset);
+        Some(ref_count_offset)
+    }
+
+    /// Whether the innermost scope holds the references to the allocation `ptr`
+    fn scope_holds(&self, ptr: Value) -> bool {
+        self.scopes.last().is_some_and(|scope| scope.owned.contains(&SmartPointerHold::Allocation(ptr)))
+    }
+
+    /// Get the linear value an expression refers to, if it names a linear variable
+    pub fn linear_value_of(&self, expr: &Expr) -> Option<Value> {
+        match expr {
+            Expr::Identifier { name, .. } => self.linear_vars.get(&name.id).copied(),
+            Expr::Parenthesized { expr, .. } => self.linear_value_of(expr),
+            _ => None,
         }
-        Expr::Parenthesized { expr, .. } => {
-            // Parentheses are just for grouping - compile the inner expression
-            compile_expression_with_variables(builder, expr, var_context, interner)
+    }
+
+    /// Check that a variable being read has not had its linear value moved out
+    pub fn check_linear_read(&self, name_id: u32, span: &Span) -> CodegenResult<()> {
+        match self.linear_vars.get(&name_id) {
+            Some(value) => self.memory_manager.check_linear_usage(*value, &self.source_location(span)),
+            None => Ok(()),
         }
-        Expr::Call { callee, args, .. } => {
-            // Handle function calls
-            compile_function_call_with_variables(builder, callee, args, var_context, interner)
+    }
+
+    /// Transfer ownership to `to` if `source` names a linear variable (assignment,
+    /// by-value argument or return)
+    pub fn move_linear_out(&mut self, source: &Expr, to: Value) -> CodegenResult<()> {
+        if let Some(from) = self.linear_value_of(source) {
+            let location = self.source_location(&source.span());
+            self.memory_manager.move_linear(from, to, &location)?;
         }
-        Expr::If { condition, then_block, else_block, .. } => {
-            // Handle if expressions
-            compile_if_expression_with_variables(builder, condition, then_block, else_block, var_context, interner)
+        Ok(())
+    }
+
+    /// Record the linear value (if any) now held by a variable after it was assigned `value`
+    ///
+    /// `declared_linear` marks bindings whose type carries the Linear strategy, so
+    /// values that were not allocated here (calls, parameters) are tracked too.
+    pub fn bind_linear(
+        &mut self,
+        name_id: u32,
+        source: &Expr,
+        value: Value,
+        declared_linear: bool,
+    ) -> CodegenResult<()> {
+        self.move_linear_out(source, value)?;
+        if self.memory_manager.is_linear(value) {
+            self.linear_vars.insert(name_id, value);
+        } else if declared_linear {
+            let location = self.source_location(&source.span());
+            self.memory_manager.track_linear(value, &location);
+            self.linear_vars.insert(name_id, value);
+        } else {
+            self.linear_vars.remove(&name_id);
         }
-        Expr::Match { expr, arms, .. } => {
-            // Handle match expressions
-            compile_match_expression_with_variables(builder, expr, arms, var_context, interner)
+        Ok(())
+    }
+
+    /// Describe a source span for allocation tracking, leak reports and diagnostics
+    pub fn source_location(&self, span: &Span) -> String {
+        format!("{} (in {})", self.module_context.source_location(&span.start), self.function_name)
+    }
+
+    /// Attribute the instructions emitted from now on to `span`, when debug info is emitted
+    pub fn mark_source_location(&mut self, builder: &mut FunctionBuilder, span: &Span) {
+        if let Some(source_locations) = &mut self.source_locations {
+            debuginfo::set_source_location(builder, source_locations, span.start);
         }
-        Expr::Unary { op, expr, .. } => {
-            // Handle unary operations
-            let operand_val = compile_expression_with_variables(builder, expr, var_context, interner)?;
-            
-            use crate::ast::UnaryOp;
-            match op {
-                UnaryOp::Negate => {
-                    // Negate: 0 - operand
-                    let zero = builder.ins().iconst(ctypes::I32, 0);
-                    Ok(builder.ins().isub(zero, operand_val))
-                }
-                UnaryOp::Not => {
-                    // Logical not: operand == 0
-                    let zero = builder.ins().iconst(ctypes::I32, 0);
-                    Ok(builder.ins().icmp(cranelift::prelude::IntCC::Equal, operand_val, zero))
-                }
-                _ => Err(CodegenError::UnsupportedFeature(
-                    format!("Unary operator not supported: {:?}", op)
-                )),
-            }
+    }
+    
+    /// Whether escape analysis found that the value allocated at `span` leaves the function
+    pub fn escapes(&self, span: &Span) -> bool {
+        self.module_context.get_escape_facts().get(&span.start).is_some_and(EscapeFact::escapes)
+    }
+
+    /// Strategy for a value allocated at `span` whose type requests none
+    ///
+    /// A strategy annotation on the function decides for all of them; otherwise values
+    /// without escape facts (semantic analysis did not run) stay in the function.
+    pub fn inferred_strategy(&self, span: &Span, size: u32) -> MemoryStrategy {
+        if let Some(strategy) = self.memory_attributes.strategy {
+            return strategy;
         }
-        Expr::Index { object, index, .. } => {
-            // Handle array indexing with variable support
-            compile_array_index_with_variables(builder, object, index, var_context, interner)
+        match self.module_context.get_escape_facts().get(&span.start) {
+            Some(fact) => MemoryStrategy::infer_for_escape(size, fact.is_shared, fact.escapes(), self.memory_manager.policy()),
+            None => MemoryStrategy::infer_for_escape(size, false, false, self.memory_manager.policy()),
         }
-        Expr::Array { elements, .. } => {
-            // Handle array literals with variable support
-            compile_array_literal_with_variables(builder, elements, var_context, interner)
+    }
+
+    /// Strategy for a literal of the struct `struct_name` whose type annotation requests none
+    ///
+    /// An annotation on the struct wins, then the function's. A strategy inferred for the
+    /// struct is used where the literal can hold it: a region needs an enclosing region
+    /// block the value does not outlive, and a value escaping its function never takes
+    /// frame storage. Otherwise the literal's own escape facts decide, as for other
+    /// allocations.
+    pub fn struct_strategy(&self, struct_name: &str, span: &Span, size: u32) -> MemoryStrategy {
+        let decision = self.module_context.get_type_strategy(struct_name);
+        if let Some(decision) = decision.filter(|decision| decision.annotated) {
+            return decision.strategy;
         }
-        Expr::StructInit { path, fields, .. } => {
-            // Handle struct initialization - BASIC IMPLEMENTATION
-            compile_struct_init_with_variables(builder, path, fields, var_context, interner)
+        if self.memory_attributes.strategy.is_some() {
+            return self.inferred_strategy(span, size);
         }
-        Expr::FieldAccess { object, field, .. } => {
-            // Handle field access - BASIC IMPLEMENTATION
-            compile_field_access_with_variables(builder, object, field, var_context, interner)
+        let fact = self.module_context.get_escape_facts().get(&span.start);
+        let escapes = fact.is_some_and(EscapeFact::escapes);
+        let escapes_region = fact.is_some_and(EscapeFact::escapes_region);
+        match decision.map(|decision| decision.strategy) {
+            Some(MemoryStrategy::Region) if self.region_stack.is_empty() || escapes_region => self.inferred_strategy(span, size),
+            Some(MemoryStrategy::Stack | MemoryStrategy::Linear) if escapes => self.inferred_strategy(span, size),
+            Some(strategy) => strategy,
+            None => self.inferred_strategy(span, size),
         }
-        _ => {
-            // Use the expressions module for other expression types
-            expressions::compile_expression(builder, expr)
+    }
+
+    /// Allocate memory through the hybrid memory manager
+    ///
+    /// The allocation's escape facts, if any, are passed on to the leak tracker. A smart
+    /// pointer that escapes keeps a reference for its outside holder.
+    pub fn allocate(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        strategy: MemoryStrategy,
+        object_type: Type,
+        size: u32,
+        span: &Span,
+    ) -> CodegenResult<Value> {
+        // Region allocations go to the innermost enclosing region block, as do linear
+        // values too large for the stack
+        let region_id = match strategy {
+            MemoryStrategy::Region | MemoryStrategy::Linear => self.region_stack.last().copied(),
+            _ => None,
+        };
+        let options = AllocationOptions {
+            region_id,
+            source_location: self.source_location(span),
+            span: Some(*span),
+            alignment: self.memory_attributes.alignment,
+            gc_allowed: false,
+            region_overflow: None,
+        };
+        let result = self.memory_manager.allocate(builder, strategy, object_type, size, options)?;
+        
+        if let Some(fact) = self.module_context.get_escape_facts().get(&span.start) {
+            self.memory_manager.update_escape_analysis(result.alloc_id, escape_info(fact));
+            if fact.escapes() && strategy == MemoryStrategy::SmartPtr {
+                self.memory_manager.share_smart_pointer(builder, result.ptr)?;
+            }
         }
+        Ok(result.ptr)
     }
 }
 
-/// Compile a single statement with variable context and termination tracking
-fn compile_statement_with_variables_and_termination(
+/// What the leak tracker needs to know of an allocation's escape facts
+/// Put back the binding a scope shadowed, or forget a name the scope introduced
+fn restore<T>(bindings: &mut HashMap<u32, T>, name_id: u32, outer: Option<T>) {
+    match outer {
+        Some(outer) => bindings.insert(name_id, outer),
+        None => bindings.remove(&name_id),
+    };
+}
+
+fn escape_info(fact: &EscapeFact) -> EscapeInfo {
+    EscapeInfo {
+        escapes_function: fact.escapes(),
+        stored_globally: matches!(fact.context, EscapeContext::Global | EscapeContext::Heap),
+        returned: fact.context == EscapeContext::FunctionReturn,
+        confidence: fact.confidence,
+    }
+}
+
+/// Compile a returned expression, moving a returned linear value out to the caller
+///
+/// A unit expression, such as a call of a function returning nothing, returns no value.
+fn compile_return_value(
     builder: &mut FunctionBuilder,
-    statement: &Stmt,
+    expr: &Expr,
     var_context: &mut VariableContext,
     interner: &StringInterner,
-) -> CodegenResult<bool> {
-    match statement {
-        Stmt::Return { expr, .. } => {
-            if let Some(expr) = expr {
-                let value = compile_expression_with_variables(builder, expr, var_context, interner)?;
-                builder.ins().return_(&[value]);
-            } else {
-                builder.ins().return_(&[]);
-            }
-            Ok(true) // Return true to indicate termination
-        }
-        Stmt::Expression { expr, .. } => {
-            // Evaluate expression but ignore result
-            compile_expression_with_variables(builder, expr, var_context, interner)?;
-            Ok(false) // Non-terminating statement
-        }
-        Stmt::Let { pattern, type_annotation, initializer, .. } => {
-            // Handle variable declaration
-            compile_let_statement(builder, pattern, type_annotation, initializer, var_context, interner)?;
-            Ok(false) // Non-terminating statement
-        }
-        Stmt::Assignment { target, value, .. } => {
-            // Handle assignment
-            compile_assignment_statement(builder, target, value, var_context, interner)?;
-            Ok(false) // Non-terminating statement  
+) -> CodegenResult<Option<Value>> {
+    let value = compile_expression_with_variables(builder, expr, var_context, interner)?;
+    if let Some(value) = value {
+        var_context.move_linear_out(expr, value)?;
+    }
+    Ok(value)
+}
+
+/// Emit a return of `value`, reconciling it with the declared return type first
+fn emit_checked_return(
+    builder: &mut FunctionBuilder,
+    value: Option<Value>,
+    var_context: &mut VariableContext,
+) -> CodegenResult<()> {
+    let values = match (&var_context.return_type, value) {
+        // Only a unit `main` has a return in its signature, its exit status
+        (None, None) => builder.func.signature.returns.clone().iter()
+            .map(|ret| builder.ins().iconst(ret.value_type, 0))
+            .collect(),
+        (Some(expected), Some(value)) if expected.tuple_layout.is_some() => {
+            let expected = expected.clone();
+            tuple_return_values(builder, value, &expected, var_context)?
         }
-        Stmt::If { condition, then_block, else_block, .. } => {
-            // Handle if statement by compiling as expression and ignoring result
-            compile_if_statement_with_variables(builder, condition, then_block, else_block, var_context, interner)?;
-            Ok(false) // Non-terminating statement
+        (Some(expected), Some(value)) => {
+            let found = builder.func.dfg.value_type(value);
+            let converted = convert_value(builder, value, expected.cranelift_type, var_context.is_unsigned(value))
+                .ok_or_else(|| CodegenError::TypeConversion(format!(
+                    "Function '{}' is declared to return {} but returns {}",
+                    var_context.function_name, expected.name, found
+                )))?;
+            vec![converted]
         }
-        Stmt::While { condition, body, .. } => {
-            // Handle while loop
-            compile_while_statement_with_variables(builder, condition, body, var_context, interner)?;
-            Ok(false) // Non-terminating statement
+        (Some(expected), None) => {
+            return Err(CodegenError::TypeConversion(format!(
+                "Function '{}' is declared to return {} but returns nothing",
+                var_context.function_name, expected.name
+            )));
         }
-        Stmt::For { pattern, iterable, body, .. } => {
-            // Handle for loop - simplified to while loop for now
-            compile_for_statement_with_variables(builder, pattern, iterable, body, var_context, interner)?;
-            Ok(false) // Non-terminating statement
+        (None, Some(value)) => {
+            return Err(CodegenError::TypeConversion(format!(
+                "Function '{}' has no return type but returns {}",
+                var_context.function_name, builder.func.dfg.value_type(value)
+            )));
         }
-        Stmt::Block { statements, .. } => {
-            // Handle block statement by compiling all statements inside with termination tracking
-            for stmt in statements {
-                let stmt_terminated = compile_statement_with_variables_and_termination(builder, stmt, var_context, interner)?;
-                if stmt_terminated {
-                    return Ok(true); // Propagate termination
-                }
-            }
-            Ok(false) // Non-terminating statement
+    };
+    emit_return(builder, &values, var_context)
+}
+
+/// Element values of a returned tuple, converted to the declared element types
+fn tuple_return_values(
+    builder: &mut FunctionBuilder,
+    value: Value,
+    expected: &ReturnType,
+    var_context: &mut VariableContext,
+) -> CodegenResult<Vec<Value>> {
+    let mismatch = || CodegenError::TypeConversion(format!(
+        "Function '{}' is declared to return {} but returns {}",
+        var_context.function_name, expected.name,
+        var_context.struct_values.get(&value).map_or("a value that is not a tuple", String::as_str)
+    ));
+    let expected_layout = expected.tuple_layout.as_ref().and_then(|name| var_context.struct_layout(name));
+    let found_layout = var_context.struct_values.get(&value).and_then(|name| var_context.struct_layout(name));
+    let (Some(expected_layout), Some(found_layout)) = (expected_layout, found_layout) else {
+        return Err(mismatch());
+    };
+    if expected_layout.fields.len() != found_layout.fields.len() {
+        return Err(mismatch());
+    }
+    
+    let fields: Vec<(FieldLayout, FieldLayout)> = found_layout.fields.iter().cloned()
+        .zip(expected_layout.fields.iter().cloned())
+        .collect();
+    let mut values = Vec::with_capacity(fields.len());
+    for (found, expected_field) in fields {
+        let element = Address::field(value, found.offset).load(builder, found.cranelift_type);
+        let converted = convert_value(builder, element, expected_field.cranelift_type, found.unsigned)
+            .ok_or_else(mismatch)?;
+        values.push(converted);
+    }
+    Ok(values)
+}
+
+/// Convert `value` to `target` when Bract defines the conversion
+///
+/// Integers widen (zero-extended when `unsigned` describes `value`, sign-extended
+/// otherwise) and narrow (truncated), floats are promoted or demoted. Integer/float
+/// mixes have no implicit conversion.
+fn convert_value(builder: &mut FunctionBuilder, value: Value, target: Type, unsigned: bool) -> Option<Value> {
+    let found = builder.func.dfg.value_type(value);
+    if found == target {
+        return Some(value);
+    }
+    
+    if found.is_int() && target.is_int() {
+        Some(match found.bits().cmp(&target.bits()) {
+            std::cmp::Ordering::Less if unsigned => builder.ins().uextend(target, value),
+            std::cmp::Ordering::Less => builder.ins().sextend(target, value),
+            _ => builder.ins().ireduce(target, value),
+        })
+    } else if found == ctypes::F32 && target == ctypes::F64 {
+        Some(builder.ins().fpromote(target, value))
+    } else if found == ctypes::F64 && target == ctypes::F32 {
+        Some(builder.ins().fdemote(target, value))
+    } else {
+        None
+    }
+}
+
+/// Emit a return, running function memory cleanup first
+fn emit_return(
+    builder: &mut FunctionBuilder,
+    values: &[Value],
+    var_context: &mut VariableContext,
+) -> CodegenResult<()> {
+    // Leaving the function leaves every enclosing region block, innermost first
+    for region_id in var_context.region_stack.iter().rev() {
+        var_context.memory_manager.release_region(builder, *region_id)?;
+    }
+    var_context.release_smart_pointers(builder, 0)?;
+    var_context.memory_manager.cleanup_function(builder)?;
+    if let Some(profile_exit) = &var_context.profile_exit {
+        profile_exit.emit(builder);
+    }
+    builder.ins().return_(values);
+    Ok(())
+}
+
+/// Continue emitting into a fresh block after a terminator
+///
+/// The block has no predecessors; whatever the caller emits next is dead code,
+/// but it still ends up with a terminator, which keeps the verifier happy.
+fn switch_to_unreachable_block(builder: &mut FunctionBuilder) {
+    let dead_bb = builder.create_block();
+    builder.switch_to_block(dead_bb);
+    builder.seal_block(dead_bb);
+}
+
+/// Compile a statement list, stopping at the first statement that terminates
+///
+/// Returns true if the list terminated.
+fn compile_statements_with_termination(
+    builder: &mut FunctionBuilder,
+    statements: &[Stmt],
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<bool> {
+    for stmt in statements {
+        if compile_statement_with_variables_and_termination(builder, stmt, var_context, interner)? {
+            return Ok(true);
         }
-        _ => Err(CodegenError::UnsupportedFeature(
-            format!("Statement not yet supported: {:?}", statement)
-        )),
     }
+    Ok(false)
 }
 
-/// Compile a single statement with variable context
-fn compile_statement_with_variables(
+/// Compile the statements of a block, branch or loop body in a scope of their own
+///
+/// Returns true if the list terminated.
+fn compile_scoped_statements(
     builder: &mut FunctionBuilder,
-    statement: &Stmt,
+    statements: &[Stmt],
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<bool> {
+    var_context.push_scope();
+    let terminated = compile_statements_with_termination(builder, statements, var_context, interner)?;
+    var_context.pop_scope(builder, terminated)?;
+    Ok(terminated)
+}
+
+/// Compile `break` / `continue`: leave the region blocks opened inside the loop and jump out
+///
+/// A `break` passes its value, if any, to the loop's exit block.
+fn compile_loop_exit(
+    builder: &mut FunctionBuilder,
+    label: &Option<crate::ast::InternedString>,
+    is_break: bool,
+    value: Option<&Expr>,
+    span: &Span,
     var_context: &mut VariableContext,
     interner: &StringInterner,
 ) -> CodegenResult<()> {
-    match statement {
-        Stmt::Return { expr, .. } => {
-            if let Some(expr) = expr {
-                let value = compile_expression_with_variables(builder, expr, var_context, interner)?;
-                builder.ins().return_(&[value]);
-            } else {
-                builder.ins().return_(&[]);
-            }
-            Ok(())
-        }
-        Stmt::Expression { expr, .. } => {
-            // Evaluate expression but ignore result
-            compile_expression_with_variables(builder, expr, var_context, interner)?;
-            Ok(())
-        }
-        Stmt::Let { pattern, type_annotation, initializer, .. } => {
-            // Handle variable declaration
-            compile_let_statement(builder, pattern, type_annotation, initializer, var_context, interner)
-        }
-        Stmt::Assignment { target, value, .. } => {
-            // Handle assignment
-            compile_assignment_statement(builder, target, value, var_context, interner)
+    let keyword = if is_break { "break" } else { "continue" };
+    let index = match label {
+        Some(label) => var_context.loop_stack.iter().rposition(|target| target.label == Some(*label)),
+        None => var_context.loop_stack.len().checked_sub(1),
+    };
+    let index = index.ok_or_else(|| match label {
+        Some(label) => CodegenError::SymbolResolution(format!(
+            "Undeclared loop label '{}' in {}",
+            interner.resolve(*label).unwrap_or_else(|| "<unknown>".to_string()),
+            keyword
+        )),
+        None => CodegenError::InternalError(format!("'{}' outside of a loop", keyword)),
+    })?;
+    
+    let arguments: Vec<Value> = if is_break {
+        compile_break_value(builder, index, value, span, var_context, interner)?.into_iter().collect()
+    } else {
+        Vec::new()
+    };
+    let target = var_context.loop_stack[index].clone();
+    let open_regions: Vec<u32> = var_context.region_stack[target.region_depth..].to_vec();
+    for region_id in open_regions.iter().rev() {
+        var_context.memory_manager.release_region(builder, *region_id)?;
+    }
+    var_context.release_smart_pointers(builder, target.scope_depth)?;
+    if !is_break {
+        var_context.memory_manager.release_unscoped_smart_pointers(builder, target.first_alloc_id)?;
+    }
+    builder.ins().jump(if is_break { target.exit } else { target.header }, &arguments);
+    Ok(())
+}
+
+/// Compile the value of a `break` out of the loop at `index` in the loop stack
+///
+/// The first `break` decides the loop's type, adding the exit block's parameter when
+/// it has a value; every later `break` must agree with it. Untyped integer literals
+/// adapt to the loop's integer type.
+fn compile_break_value(
+    builder: &mut FunctionBuilder,
+    index: usize,
+    value: Option<&Expr>,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<Value>> {
+    let Some(value_expr) = value else {
+        let target = &mut var_context.loop_stack[index];
+        if matches!(target.breaks, LoopBreaks::Value(_)) {
+            return Err(CodegenError::TypeConversion(
+                "`break` without a value out of a loop whose other breaks give one".to_string()
+            ).at(*span));
         }
-        Stmt::If { condition, then_block, else_block, .. } => {
-            // Handle if statement by compiling as expression and ignoring result
-            compile_if_statement_with_variables(builder, condition, then_block, else_block, var_context, interner)
+        target.breaks = LoopBreaks::Unit;
+        return Ok(None);
+    };
+    if !var_context.loop_stack[index].valued {
+        return Err(CodegenError::UnsupportedFeature("`break` with a value is only allowed in `loop`".to_string()).at(*span));
+    }
+    
+    let value = compile_value_with_variables(builder, value_expr, var_context, interner)?;
+    let found = builder.func.dfg.value_type(value);
+    let target = &mut var_context.loop_stack[index];
+    match target.breaks {
+        LoopBreaks::Never => {
+            target.breaks = LoopBreaks::Value(found);
+            let param = builder.append_block_param(target.exit, found);
+            if var_context.is_unsigned(value) {
+                var_context.mark_unsigned(param);
+            }
+            Ok(Some(value))
         }
-        Stmt::While { condition, body, .. } => {
-            // Handle while loop
-            compile_while_statement_with_variables(builder, condition, body, var_context, interner)
+        LoopBreaks::Unit => Err(CodegenError::TypeConversion(
+            "`break` with a value out of a loop whose other breaks give none".to_string()
+        ).at(*span)),
+        LoopBreaks::Value(expected) if expected == found => Ok(Some(value)),
+        LoopBreaks::Value(expected) if expected.is_int() && found.is_int() && is_integer_literal(value_expr) => {
+            Ok(convert_value(builder, value, expected, false))
         }
-        Stmt::For { pattern, iterable, body, .. } => {
-            // Handle for loop - simplified to while loop for now
-            compile_for_statement_with_variables(builder, pattern, iterable, body, var_context, interner)
+        LoopBreaks::Value(expected) => Err(CodegenError::TypeConversion(format!(
+            "`break` value of type {} does not match the loop's type {}", found, expected
+        )).at(*span)),
+    }
+}
+
+/// Compile a `loop`: a header block running `body`, whose end jumps back to it unconditionally
+///
+/// `break`s jump to the exit block, which carries their value as its parameter. A loop
+/// without a `break` never exits: its exit block has no predecessors and is left out.
+///
+/// Returns the loop's value, None for unit, and whether the loop never exits.
+fn compile_loop_with_variables(
+    builder: &mut FunctionBuilder,
+    label: Option<crate::ast::InternedString>,
+    body: impl FnOnce(&mut FunctionBuilder, &mut VariableContext) -> CodegenResult<bool>,
+    var_context: &mut VariableContext,
+) -> CodegenResult<(Option<Value>, bool)> {
+    let header_bb = builder.create_block();
+    let exit_bb = builder.create_block();
+    builder.ins().jump(header_bb, &[]);
+    
+    builder.switch_to_block(header_bb);
+    var_context.loop_stack.push(LoopTarget {
+        label,
+        header: header_bb,
+        exit: exit_bb,
+        region_depth: var_context.region_stack.len(),
+        scope_depth: var_context.scopes.len(),
+        first_alloc_id: var_context.memory_manager.next_alloc_id(),
+        valued: true,
+        breaks: LoopBreaks::Never,
+    });
+    let body_result = body(builder, var_context);
+    let target = var_context.loop_stack.pop().expect("loop target pushed above");
+    if !body_result? {
+        var_context.memory_manager.release_unscoped_smart_pointers(builder, target.first_alloc_id)?;
+        builder.ins().jump(header_bb, &[]);
+    }
+    
+    // Every back-edge and break is emitted now
+    builder.seal_block(header_bb);
+    builder.seal_block(exit_bb);
+    match target.breaks {
+        LoopBreaks::Never => Ok((None, true)),
+        LoopBreaks::Unit => {
+            builder.switch_to_block(exit_bb);
+            Ok((None, false))
         }
-        Stmt::Block { statements, .. } => {
-            // Handle block statement by compiling all statements inside
-            for stmt in statements {
-                compile_statement_with_variables(builder, stmt, var_context, interner)?;
-            }
-            Ok(())
+        LoopBreaks::Value(_) => {
+            builder.switch_to_block(exit_bb);
+            Ok((Some(builder.block_params(exit_bb)[0]), false))
         }
-        _ => Err(CodegenError::UnsupportedFeature(
-            format!("Statement not yet supported: {:?}", statement)
-        )),
     }
 }
 
-/// Compile a let statement (variable declaration)
-fn compile_let_statement(
+/// Compile a `loop` expression, whose value is that of its `break`s
+fn compile_loop_expression(
     builder: &mut FunctionBuilder,
-    pattern: &Pattern,
-    type_annotation: &Option<AstType>,
-    initializer: &Option<Expr>,
+    label: Option<crate::ast::InternedString>,
+    body: &Expr,
     var_context: &mut VariableContext,
     interner: &StringInterner,
+) -> CodegenResult<(Option<Value>, bool)> {
+    compile_loop_with_variables(builder, label, |builder, var_context| {
+        // The body's own value, if any, is discarded on every iteration
+        let (_, terminated) = compile_expression_with_variables_and_termination(builder, body, var_context, interner)?;
+        Ok(terminated)
+    }, var_context)
+}
+
+/// Declare a function signature in the module
+pub fn declare_function_item(
+    module: &mut dyn CraneliftModule,
+    item: &Item,
+    context: &mut super::CraneliftContext,
+    interner: &StringInterner,
 ) -> CodegenResult<()> {
-    match pattern {
-        Pattern::Identifier { name, .. } => {
-            // Determine variable type
-            let var_type = if let Some(type_ann) = type_annotation {
-                ast_type_to_cranelift_type(type_ann)?
-            } else if let Some(init_expr) = initializer {
-                // Infer type from initializer
-                match init_expr {
-                    Expr::Array { .. } => ctypes::I64, // Arrays are stored as pointers
-                    _ => ctypes::I32, // Default to i32 for other types
+    match item {
+        Item::Function { 
+            name, 
+            params, 
+            return_type, 
+            is_extern,
+            .. 
+        } => {
+            if *is_extern {
+                // External functions just need declaration
+                return Ok(());
+            }
+            
+            // Get function name using string interner - FIXED!
+            let func_name = interner.resolve(*name)
+                .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve function name with ID {}", name.id)))?;
+            
+            // Create function signature - the body and every caller use this one. A
+            // `main` returning unit still gives the process an exit status: 0
+            let mut sig = function_signature(module, params, return_type)?;
+            if func_name == "main" && sig.returns.is_empty() {
+                sig.returns.push(AbiParam::new(ctypes::I32));
+            }
+            
+            // Determine linkage - main function gets exported, others are local
+            let linkage = if func_name == "main" {
+                Linkage::Export
+            } else {
+                Linkage::Local
+            };
+            
+            let func_id = module.declare_function(&func_name, linkage, &sig)
+                .map_err(|e| CodegenError::InternalError(format!("Failed to declare function '{}': {}", func_name, e)))?;
+            
+            // Register function in context
+            let moves_receiver = params.first().is_some_and(|param| is_self_by_value(param, interner));
+            context.register_function(*name, FunctionEntry { func_id, signature: sig, return_type: return_type.clone(), moves_receiver });
+            
+            Ok(())
+        }
+        _ => Err(CodegenError::InternalError("Expected function item".to_string())),
+    }
+}
+
+/// Whether `param` is a `self` receiver taken by value rather than by reference
+fn is_self_by_value(param: &Parameter, interner: &StringInterner) -> bool {
+    matches!(&param.pattern, Pattern::Identifier { name, .. } if interner.resolve(*name).as_deref() == Some("self"))
+        && !matches!(param.type_annotation, Some(AstType::Reference { .. }))
+}
+
+/// Build the Cranelift signature of a function from its declaration
+fn function_signature(
+    module: &dyn CraneliftModule,
+    params: &[Parameter],
+    return_type: &Option<AstType>,
+) -> CodegenResult<Signature> {
+    let pointer_type = module.target_config().pointer_type();
+    let mut sig = module.make_signature();
+    
+    // Add parameters
+    for param in params {
+        if let Some(param_type) = &param.type_annotation {
+            sig.params.push(abi_param(param_type, pointer_type)?);
+        } else {
+            return Err(CodegenError::InternalError("Parameter missing type annotation".to_string()));
+        }
+    }
+    
+    // Add return type
+    if let Some(return_type) = return_type {
+        sig.returns.extend(return_params(return_type, pointer_type)?);
+    }
+    
+    Ok(sig)
+}
+
+/// Signature of the functions a `fn(A, B) -> R` typed value points to, None for other types
+///
+/// Matches `function_signature` for a function declared with the same types, so
+/// closures and functions can be called through the pointer.
+fn function_pointer_signature(ast_type: &AstType, call_conv: CallConv, pointer_type: Type) -> CodegenResult<Option<Signature>> {
+    let AstType::Function { params, return_type, .. } = ast_type else {
+        return Ok(None);
+    };
+    
+    let mut sig = Signature::new(call_conv);
+    for param_type in params {
+        sig.params.push(abi_param(param_type, pointer_type)?);
+    }
+    // `fn(A)` without `->` returns unit, which has no ABI return
+    sig.returns.extend(return_params(return_type, pointer_type)?);
+    Ok(Some(sig))
+}
+
+/// ABI returns for a value of `return_type`
+///
+/// Tuples are returned as one value per element rather than as a pointer into the
+/// callee's stack frame; the caller stores them into a tuple of its own. Unit is
+/// returned as nothing, like a function without a return type.
+fn return_params(return_type: &AstType, pointer_type: Type) -> CodegenResult<Vec<AbiParam>> {
+    match return_type {
+        return_type if is_unit_ast_type(return_type) => Ok(Vec::new()),
+        AstType::Tuple { types, .. } if !types.is_empty() => {
+            if types.len() == 1 {
+                return Err(CodegenError::UnsupportedFeature(
+                    "Returning a single-element tuple is not supported yet".to_string()
+                ));
+            }
+            types.iter()
+                .map(|element_type| match element_type {
+                    AstType::Tuple { types, .. } if !types.is_empty() => Err(CodegenError::UnsupportedFeature(
+                        "Returning nested tuples is not supported yet".to_string()
+                    )),
+                    _ => abi_param(element_type, pointer_type),
+                })
+                .collect()
+        }
+        _ => Ok(vec![abi_param(return_type, pointer_type)?]),
+    }
+}
+
+/// ABI parameter for a value of `ast_type`
+///
+/// Narrow integers carry their extension so the ABI widens them according to their signedness.
+fn abi_param(ast_type: &AstType, pointer_type: Type) -> CodegenResult<AbiParam> {
+    let cranelift_type = ast_type_to_cranelift_type(ast_type, pointer_type)?;
+    let param = AbiParam::new(cranelift_type);
+    Ok(match cranelift_type.is_int() && cranelift_type.bits() < 32 {
+        true if is_unsigned_ast_type(ast_type) => param.uext(),
+        true => param.sext(),
+        false => param,
+    })
+}
+
+/// All function items of a module in source order, including functions declared inside
+/// function bodies
+///
+/// Nested functions are compiled as ordinary module-level functions under their own name.
+pub fn function_items(items: &[Item]) -> Vec<&Item> {
+    fn collect_from_statements<'a>(statements: &'a [Stmt], functions: &mut Vec<&'a Item>) {
+        for stmt in statements {
+            match stmt {
+                Stmt::Item { item, .. } => collect_from_item(item, functions),
+                Stmt::Expression { expr, .. } => collect_from_expr(expr, functions),
+                Stmt::If { then_block, else_block, .. } => {
+                    collect_from_statements(then_block, functions);
+                    if let Some(else_stmt) = else_block {
+                        collect_from_statements(std::slice::from_ref(else_stmt.as_ref()), functions);
+                    }
+                }
+                Stmt::While { body, .. } | Stmt::For { body, .. } | Stmt::Loop { body, .. }
+                | Stmt::Region { body, .. } => collect_from_statements(body, functions),
+                Stmt::Block { statements, .. } => collect_from_statements(statements, functions),
+                _ => {}
+            }
+        }
+    }
+    
+    fn collect_from_expr<'a>(expr: &'a Expr, functions: &mut Vec<&'a Item>) {
+        if let Expr::Block { statements, .. } = expr {
+            collect_from_statements(statements, functions);
+        }
+    }
+    
+    fn collect_from_item<'a>(item: &'a Item, functions: &mut Vec<&'a Item>) {
+        if let Item::Function { body, .. } = item {
+            functions.push(item);
+            if let Some(body) = body {
+                collect_from_expr(body, functions);
+            }
+        }
+    }
+    
+    let mut functions = Vec::new();
+    for item in items {
+        collect_from_item(item, &mut functions);
+    }
+    functions
+}
+
+/// Names of the functions with bodies among `items`, in order, for profiling ids
+///
+/// Extern functions are defined elsewhere, so there is nothing to instrument.
+pub fn profiled_function_names(items: &[&Item], interner: &StringInterner) -> Vec<String> {
+    items.iter()
+        .filter_map(|item| match item {
+            Item::Function { name, is_extern: false, body: Some(_), .. } => interner.resolve(*name),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check that no struct or enum of `items` nests others by value more than `MAX_LAYOUT_NESTING` deep
+///
+/// Semantic analysis rejects types containing themselves by value; this keeps one it
+/// missed from sending the layout code into endless recursion.
+pub fn check_layout_nesting(items: &[Item], interner: &StringInterner) -> CodegenResult<()> {
+    let types: HashMap<crate::ast::InternedString, &Item> = items.iter()
+        .filter_map(|item| match item {
+            Item::Struct { name, .. } | Item::Enum { name, .. } => Some((*name, item)),
+            _ => None,
+        })
+        .collect();
+    let mut checked = HashSet::new();
+    for item in items {
+        if let Item::Struct { name, .. } | Item::Enum { name, .. } = item {
+            check_type_nesting(*name, &types, 0, &mut checked, interner)?;
+        }
+    }
+    Ok(())
+}
+
+/// Follow the by-value fields of type `name`, `depth` levels into the type being checked
+fn check_type_nesting(
+    name: crate::ast::InternedString,
+    types: &HashMap<crate::ast::InternedString, &Item>,
+    depth: usize,
+    checked: &mut HashSet<crate::ast::InternedString>,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    if checked.contains(&name) {
+        return Ok(());
+    }
+    let Some(item) = types.get(&name) else {
+        return Ok(());
+    };
+    let field_lists: Vec<&crate::ast::StructFields> = match item {
+        Item::Struct { fields, .. } => vec![fields],
+        Item::Enum { variants, .. } => variants.iter().map(|variant| &variant.fields).collect(),
+        _ => Vec::new(),
+    };
+    for fields in field_lists {
+        let field_types: Vec<(&AstType, Span)> = match fields {
+            crate::ast::StructFields::Named(fields) => fields.iter().map(|field| (&field.field_type, field.span)).collect(),
+            crate::ast::StructFields::Tuple(types) => types.iter().map(|field_type| (field_type, field_type.span())).collect(),
+            crate::ast::StructFields::Unit => Vec::new(),
+        };
+        for (field_type, span) in field_types {
+            let mut held = Vec::new();
+            by_value_type_names(field_type, &mut held);
+            for inner in held.into_iter().filter(|inner| types.contains_key(inner)) {
+                if depth >= MAX_LAYOUT_NESTING {
+                    return Err(CodegenError::TypeConversion(format!(
+                        "'{}' holds types by value more than {} levels deep; a type containing itself needs a pointer",
+                        interner.resolve(name).unwrap_or_else(|| "<unknown>".to_string()), MAX_LAYOUT_NESTING
+                    )).at(span));
+                }
+                check_type_nesting(inner, types, depth + 1, checked, interner)?;
+            }
+        }
+    }
+    checked.insert(name);
+    Ok(())
+}
+
+/// Names of the types a value of `ast_type` holds by value, through tuples and arrays
+fn by_value_type_names(ast_type: &AstType, names: &mut Vec<crate::ast::InternedString>) {
+    match ast_type {
+        AstType::Path { segments, .. } => names.extend(segments.last()),
+        AstType::Tuple { types, .. } => types.iter().for_each(|element| by_value_type_names(element, names)),
+        AstType::Array { element_type, .. } => by_value_type_names(element_type, names),
+        _ => {}
+    }
+}
+
+/// Compute and register the memory layout of a struct declaration
+pub fn declare_struct_item(
+    module: &dyn CraneliftModule,
+    item: &Item,
+    context: &mut super::CraneliftContext,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    let Item::Struct { name, fields, .. } = item else {
+        return Err(CodegenError::InternalError("Expected struct item".to_string()));
+    };
+    let struct_name = interner.resolve(*name)
+        .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve struct name with ID {}", name.id)))?;
+    let pointer_type = module.target_config().pointer_type();
+    
+    let layout_fields = field_layouts(fields, 0, pointer_type, interner)?;
+    let size = (layout_fields.len().max(1) as u32) * STRUCT_FIELD_SLOT_BYTES;
+    
+    context.register_struct_layout(&struct_name, StructLayout { fields: layout_fields, size });
+    Ok(())
+}
+
+/// Starts of the literals of each struct in `module`, by struct name
+pub fn struct_literal_sites(module: &crate::ast::Module, interner: &StringInterner) -> HashMap<String, Vec<crate::lexer::Position>> {
+    struct Sites<'i> {
+        interner: &'i StringInterner,
+        sites: HashMap<String, Vec<crate::lexer::Position>>,
+    }
+    
+    impl<'ast> Visitor<'ast> for Sites<'_> {
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            if let Expr::StructInit { path, span, .. } = expr {
+                if let Some(name) = path.last().and_then(|name| self.interner.resolve(*name)) {
+                    self.sites.entry(name).or_default().push(span.start);
+                }
+            }
+            visitor::walk_expr(self, expr);
+        }
+    }
+    
+    let mut collector = Sites { interner, sites: HashMap::new() };
+    collector.visit_module(module);
+    collector.sites
+}
+
+/// Decide and record the memory strategy of a laid out struct declaration
+///
+/// An annotation on the struct always wins. Otherwise the strategy is inferred from
+/// the struct's size and the escape facts of its literals at `sites`: the type is
+/// shared if any instance is, and its lifetime is known if no instance escapes its
+/// function, with the size thresholds of `policy`. Alignment is the widest field's, or
+/// the struct's `@align` if larger.
+pub fn decide_struct_strategy(
+    item: &Item,
+    sites: &HashMap<String, Vec<crate::lexer::Position>>,
+    context: &mut super::CraneliftContext,
+    interner: &StringInterner,
+    policy: &MemoryPolicy,
+) -> CodegenResult<()> {
+    let Item::Struct { name, attributes, .. } = item else {
+        return Err(CodegenError::InternalError("Expected struct item".to_string()));
+    };
+    let struct_name = interner.resolve(*name)
+        .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve struct name with ID {}", name.id)))?;
+    let layout = context.get_struct_layout(&struct_name)
+        .ok_or_else(|| CodegenError::InternalError(format!("Struct '{}' has no layout", struct_name)))?;
+    let memory = MemoryAttributes::from_attributes(attributes, interner, context.source_map(), &HashSet::new())?;
+    
+    let size = layout.size;
+    let alignment = layout.fields.iter()
+        .map(|field| field.cranelift_type.bytes())
+        .chain(memory.alignment)
+        .max()
+        .unwrap_or(1);
+    let decision = match memory.strategy {
+        Some(strategy) => {
+            let annotation = attributes.iter()
+                .filter_map(|attribute| interner.resolve(attribute.name))
+                .find(|name| matches!(name.as_str(), "memory" | "manual" | "smart" | "linear" | "stack" | "region"))
+                .unwrap_or_default();
+            TypeStrategy::annotated(strategy, &format!("@{}", annotation), size, alignment)
+        }
+        None => {
+            let facts: Vec<&EscapeFact> = sites.get(&struct_name).into_iter().flatten()
+                .filter_map(|site| context.get_escape_facts().get(site))
+                .collect();
+            let is_shared = facts.iter().any(|fact| fact.is_shared);
+            let lifetime_known = !facts.iter().any(|fact| fact.escapes());
+            TypeStrategy::inferred(size, alignment, is_shared, lifetime_known, policy)
+        }
+    };
+    context.register_type_strategy(&struct_name, decision);
+    Ok(())
+}
+
+/// Compute and register the memory layout of an enum declaration
+///
+/// Discriminants are folded like array lengths, with `constants` supplying the values of
+/// the module's `const` items.
+pub fn declare_enum_item(
+    module: &dyn CraneliftModule,
+    item: &Item,
+    constants: &HashMap<crate::ast::InternedString, &Expr>,
+    context: &mut super::CraneliftContext,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    let Item::Enum { name, variants, .. } = item else {
+        return Err(CodegenError::InternalError("Expected enum item".to_string()));
+    };
+    let enum_name = interner.resolve(*name)
+        .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve enum name with ID {}", name.id)))?;
+    let pointer_type = module.target_config().pointer_type();
+    
+    let discriminants = crate::ast::EnumVariant::discriminants(variants, &|name| fold_constant(name, constants, MAX_CONSTANT_DEPTH));
+    let mut layout_variants = Vec::with_capacity(variants.len());
+    for (variant, discriminant) in variants.iter().zip(discriminants) {
+        let variant_name = interner.resolve(variant.name).unwrap_or_else(|| "<unknown>".to_string());
+        let discriminant = discriminant.and_then(|value| i64::try_from(value).ok()).ok_or_else(|| CodegenError::UnsupportedFeature(format!(
+            "discriminant of '{}::{}' must be a non-negative integer constant that fits in 64 bits",
+            enum_name, variant_name
+        )).at(variant.span))?;
+        // The tag takes the first slot, the payload follows it
+        let fields = field_layouts(&variant.fields, STRUCT_FIELD_SLOT_BYTES, pointer_type, interner)?;
+        layout_variants.push(VariantLayout { name: variant_name, discriminant, fields });
+    }
+    
+    let payload_slots = layout_variants.iter().map(|variant| variant.fields.len() as u32).max().unwrap_or(0);
+    let size = match payload_slots {
+        0 => 0,
+        slots => (slots + 1) * STRUCT_FIELD_SLOT_BYTES,
+    };
+    context.register_enum_layout(&enum_name, EnumLayout { variants: layout_variants, size });
+    Ok(())
+}
+
+/// Value of the integer constant `name` among `constants`
+fn fold_constant(
+    name: crate::ast::InternedString,
+    constants: &HashMap<crate::ast::InternedString, &Expr>,
+    depth: usize,
+) -> Option<u128> {
+    let value = constants.get(&name).filter(|_| depth > 0)?;
+    value.fold_integer(&|name| fold_constant(name, constants, depth - 1))
+}
+
+/// Layouts of struct or variant fields in 8-byte slots, the first at `first_offset`
+///
+/// Tuple fields are named by position, as in `pair.0`.
+fn field_layouts(
+    fields: &crate::ast::StructFields,
+    first_offset: u32,
+    pointer_type: Type,
+    interner: &StringInterner,
+) -> CodegenResult<Vec<FieldLayout>> {
+    let field_types: Vec<(String, &AstType)> = match fields {
+        crate::ast::StructFields::Named(fields) => fields.iter()
+            .map(|field| (interner.resolve(field.name).unwrap_or_else(|| "<unknown>".to_string()), &field.field_type))
+            .collect(),
+        crate::ast::StructFields::Tuple(types) => types.iter()
+            .enumerate()
+            .map(|(i, field_type)| (i.to_string(), field_type))
+            .collect(),
+        crate::ast::StructFields::Unit => Vec::new(),
+    };
+    
+    field_types.into_iter()
+        .enumerate()
+        .map(|(i, (field_name, field_type))| Ok(FieldLayout {
+            name: field_name,
+            cranelift_type: ast_type_to_cranelift_type(field_type, pointer_type)?,
+            unsigned: is_unsigned_ast_type(field_type),
+            struct_name: struct_path_name(field_type, interner),
+            offset: first_offset + i as u32 * STRUCT_FIELD_SLOT_BYTES,
+        }))
+        .collect()
+}
+
+/// Symbol name of a method compiled from an impl block
+pub fn mangle_method_name(type_name: &str, method_name: &str) -> String {
+    format!("{}_{}", type_name, method_name)
+}
+
+/// Flatten the methods of every impl block into ordinary functions
+///
+/// Each method becomes a function named `Type_method` whose `self` parameter is its first
+/// parameter, with `Self` replaced by the implementing type.
+pub fn flatten_impl_items(items: &[Item], interner: &StringInterner) -> CodegenResult<Vec<Item>> {
+    let mut functions = Vec::new();
+    for item in items {
+        let Item::Impl { target_type, items: impl_items, .. } = item else {
+            continue;
+        };
+        let type_name = struct_path_name(target_type, interner).ok_or_else(|| CodegenError::UnsupportedFeature(
+            "Impl blocks are only supported for named types".to_string()
+        ))?;
+        
+        for impl_item in impl_items {
+            // TODO: Associated types and constants
+            let crate::ast::ImplItem::Function { visibility, name, generics, params, return_type, body, span } = impl_item else {
+                continue;
+            };
+            let method_name = interner.resolve(*name).unwrap_or_else(|| "<unknown>".to_string());
+            let params = params.iter()
+                .map(|param| Parameter {
+                    type_annotation: param.type_annotation.as_ref().map(|ty| substitute_self_type(ty, target_type, interner)),
+                    ..param.clone()
+                })
+                .collect();
+            let return_type = return_type.as_ref().map(|ty| substitute_self_type(ty, target_type, interner));
+            
+            functions.push(Item::Function {
+                visibility: *visibility,
+                name: interner.intern(&mangle_method_name(&type_name, &method_name)),
+                generics: generics.clone(),
+                params,
+                return_type,
+                body: body.clone(),
+                is_extern: false,
+                performance_contract: None,
+                allocation_strategy: None,
+                attributes: Vec::new(),
+                span: *span,
+                id: NodeId::DUMMY,
+            });
+        }
+    }
+    Ok(functions)
+}
+
+/// Replace `Self` in a method's parameter or return type with the implementing type
+fn substitute_self_type(ast_type: &AstType, self_type: &AstType, interner: &StringInterner) -> AstType {
+    match ast_type {
+        AstType::Path { segments, .. } if segments.len() == 1 && interner.matches(segments[0], "Self") => {
+            self_type.clone()
+        }
+        AstType::Reference { is_mutable, target_type, lifetime, ownership, span, .. } => AstType::Reference {
+            is_mutable: *is_mutable,
+            target_type: Box::new(substitute_self_type(target_type, self_type, interner)),
+            lifetime: *lifetime,
+            ownership: ownership.clone(),
+            span: *span,
+            id: NodeId::DUMMY,
+        },
+        AstType::Pointer { is_mutable, target_type, memory_strategy, span, .. } => AstType::Pointer {
+            is_mutable: *is_mutable,
+            target_type: Box::new(substitute_self_type(target_type, self_type, interner)),
+            memory_strategy: *memory_strategy,
+            span: *span,
+            id: NodeId::DUMMY,
+        },
+        _ => ast_type.clone(),
+    }
+}
+
+/// A function body compiled to machine code, waiting to be defined in the module
+pub struct CompiledFunction {
+    func_id: FuncId,
+    name: String,
+    ctx: Context,
+    /// The function item it was compiled from
+    span: Span,
+    /// Positions its instructions' source locations index, empty without debug info
+    source_locations: Vec<Position>,
+}
+
+impl CompiledFunction {
+    /// Line rows of the machine code, when it was compiled with debug info
+    pub fn lines(&self) -> Option<FunctionLines> {
+        let code = self.ctx.compiled_code()?;
+        if self.source_locations.is_empty() {
+            return None;
+        }
+        Some(FunctionLines::new(self.func_id, &self.name, self.span.start, code, &self.source_locations))
+    }
+    
+
+    /// The function's IR as compiled, with the symbol behind each external name it uses
+    pub fn ir(&self, declarations: &ModuleDeclarations) -> FunctionIr {
+        let mut clif = self.ctx.func.display().to_string();
+        for name in self.ctx.func.params.user_named_funcs().values() {
+            let symbol = match name.namespace {
+                0 => declarations.get_function_decl(FuncId::from_u32(name.index)).linkage_name(FuncId::from_u32(name.index)),
+                _ => declarations.get_data_decl(DataId::from_u32(name.index)).linkage_name(DataId::from_u32(name.index)),
+            };
+            clif.push_str(&format!("; {} = {}\n", name, symbol));
+        }
+        FunctionIr { name: self.name.clone(), clif }
+    }
+}
+
+/// Textual Cranelift IR of a compiled function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionIr {
+    pub name: String,
+    /// The IR as Cranelift prints it, then one `; u0:N = symbol` line per external name
+    pub clif: String,
+}
+
+/// Compile a function from Item::Function to machine code
+///
+/// Only reads the module, so functions may be compiled on several threads; each needs
+/// its own builder context and memory manager. Returns None for extern functions, which
+/// have nothing to define.
+pub fn compile_function_item(
+    module: ModuleView,
+    item: &Item,
+    builder_context: &mut FunctionBuilderContext,
+    context: &CraneliftContext,
+    interner: &StringInterner,
+    memory_manager: &mut BractMemoryManager,
+) -> CodegenResult<Option<CompiledFunction>> {
+    match item {
+        Item::Function { 
+            name, 
+            params, 
+            return_type, 
+            body, 
+            is_extern,
+            attributes,
+            span,
+            .. 
+        } => {
+            if *is_extern {
+                // External functions just need declaration
+                return Ok(None);
+            }
+            
+            let body_expr = body.as_ref().ok_or_else(|| {
+                CodegenError::InternalError("Function body is missing".to_string())
+            })?;
+            
+            // Track allocations per function so leaks are attributed correctly
+            let function_name = interner.resolve(*name)
+                .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve function name with ID {}", name.id)))?;
+            let memory_attributes = MemoryAttributes::from_attributes(attributes, interner, context.source_map(), &region_names(body_expr, interner))
+                .map_err(|error| error.at(*span))?;
+            memory_manager.enter_function_scope(&function_name);
+            let function = FunctionSource {
+                name: *name,
+                params,
+                return_type,
+                body: body_expr,
+                span: *span,
+                memory_attributes: &memory_attributes,
+            };
+            let result = compile_function_with_body(module, function, builder_context, context, interner, memory_manager)
+                .and_then(|compiled| memory_manager.check_stack_usage(&function_name).map(|()| compiled));
+            memory_manager.exit_function_scope();
+            
+            // Errors nothing inside the function located, such as verifier failures, point at the function
+            result.map(Some).map_err(|error| error.at(*span))
+        }
+        _ => Err(CodegenError::InternalError("Expected function item".to_string())),
+    }
+}
+
+/// Define a compiled function in the module
+pub fn define_compiled_function(module: &mut dyn CraneliftModule, compiled: CompiledFunction) -> CodegenResult<()> {
+    let code = compiled.ctx.compiled_code()
+        .ok_or_else(|| CodegenError::InternalError(format!("Function '{}' was not compiled", compiled.name)).at(compiled.span))?;
+    
+    module.define_function_bytes(
+        compiled.func_id,
+        &compiled.ctx.func,
+        u64::from(code.buffer.alignment),
+        code.code_buffer(),
+        code.buffer.relocs(),
+    )
+    .map_err(|e| CodegenError::InternalError(format!("Failed to define function '{}': {}", compiled.name, e)).at(compiled.span))
+}
+
+/// Function being compiled, for `compile_function_with_body`
+struct FunctionSource<'f> {
+    name: crate::ast::InternedString,
+    params: &'f [Parameter],
+    return_type: &'f Option<AstType>,
+    body: &'f Expr,
+    span: Span,
+    /// Memory attributes the function is annotated with
+    memory_attributes: &'f MemoryAttributes,
+}
+
+/// Compile a function with its body
+fn compile_function_with_body(
+    module: ModuleView,
+    function: FunctionSource,
+    builder_context: &mut FunctionBuilderContext,
+    context: &CraneliftContext,
+    interner: &StringInterner,
+    memory_manager: &mut BractMemoryManager,
+) -> CodegenResult<CompiledFunction> {
+    let FunctionSource { name, params, return_type, body, span, memory_attributes } = function;
+    let pointer_type = module.pointer_type();
+    
+    // Get function name using string interner - FIXED!
+    let func_name = interner.resolve(name)
+        .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve function name with ID {}", name.id)))?;
+    
+    // Get the already declared function ID and signature from context
+    let (func_id, sig) = context.get_function(name)
+        .map(|entry| (entry.func_id, entry.signature.clone()))
+        .ok_or_else(|| CodegenError::InternalError(format!("Function '{}' not declared", func_name)))?;
+    
+    // Create function context
+    let mut ctx = Context::new();
+    ctx.func.signature = sig;
+    
+    // Create function builder
+    let mut builder = FunctionBuilder::new(&mut ctx.func, builder_context);
+    
+    // Create entry block
+    let entry_block = builder.create_block();
+    
+    // Add block parameters to match function signature
+    for param in params {
+        if let Some(param_type) = &param.type_annotation {
+            let cranelift_type = ast_type_to_cranelift_type(param_type, pointer_type)?;
+            builder.append_block_param(entry_block, cranelift_type);
+        }
+    }
+    
+    builder.switch_to_block(entry_block);
+    builder.seal_block(entry_block);
+    
+    // With debug info, the entry code is attributed to the function's declaration
+    let mut source_locations = context.debug_info().then(Vec::new);
+    if let Some(source_locations) = &mut source_locations {
+        debuginfo::set_source_location(&mut builder, source_locations, span.start);
+    }
+    
+    // Make the memory runtime callable from this function
+    memory_manager.prepare_function(module, builder.func)?;
+    
+    // Instrumented builds report entry before anything else runs
+    let profile_exit = context.profile_hooks()
+        .and_then(|hooks| hooks.enter_function(module, &mut builder, &func_name));
+    
+    // Initialize variable context; calls and layouts are looked up in the module's tables
+    let mut var_context = VariableContext::new(memory_manager, context, func_name.to_string());
+    var_context.profile_exit = profile_exit;
+    var_context.memory_attributes = memory_attributes.clone();
+    var_context.source_locations = source_locations;
+    
+    // A region declared with `@region` lives until the function returns
+    if let Some((region_name, overflow)) = &memory_attributes.region {
+        let statements = match body {
+            Expr::Block { statements, .. } => &statements[..],
+            _ => &[],
+        };
+        let size = memory_attributes.size_hint
+            .unwrap_or_else(|| estimate_region_size(statements, interner))
+            .max(REGION_BLOCK_GRANULARITY);
+        let region_id = var_context.memory_manager.create_region_with_policy(format!("{}::{}", func_name, region_name), size, *overflow);
+        var_context.memory_manager.initialize_region(&mut builder, region_id)?;
+        var_context.region_stack.push(region_id);
+    }
+    var_context.return_type = match return_type {
+        // `-> ()` returns nothing, like a function without a return type
+        Some(ast_type) if is_unit_ast_type(ast_type) => None,
+        Some(ast_type) => {
+            let tuple_layout = match ast_type {
+                AstType::Tuple { types, .. } if !types.is_empty() => var_context.struct_name_of_ast_type(ast_type, interner),
+                _ => None,
+            };
+            Some(ReturnType {
+                cranelift_type: ast_type_to_cranelift_type(ast_type, pointer_type)?,
+                name: tuple_layout.clone().map_or_else(|| ast_type_name(ast_type, pointer_type), Ok)?,
+                tuple_layout,
+            })
+        }
+        None => None,
+    };
+    
+    // Add function parameters as local variables
+    let block_params: Vec<_> = builder.block_params(entry_block).to_vec();
+    for (i, param) in params.iter().enumerate() {
+        if let Pattern::Identifier { name, .. } = &param.pattern {
+            let param_type = param.type_annotation.as_ref()
+                .ok_or_else(|| CodegenError::InternalError("Parameter missing type annotation".to_string()))?;
+            let cranelift_type = ast_type_to_cranelift_type(param_type, pointer_type)?;
+            
+            // Get parameter name using interner
+            let param_name = interner.resolve(*name)
+                .unwrap_or_else(|| format!("param_{}", i));
+            
+            // Create stack slot for parameter
+            let stack_slot = var_context.declare_variable(
+                &mut builder,
+                name.id,
+                cranelift_type,
+                is_unsigned_ast_type(param_type),
+                param_name,
+            )?;
+            
+            // Store the parameter value to the stack slot
+            builder.ins().stack_store(block_params[i], stack_slot, 0);
+            var_context.bind_pointee(name.id, pointee_of_ast_type(param_type, pointer_type)?);
+            var_context.bind_array(name.id, static_array_length(Some(param_type), None), array_element_of_ast_type(param_type, pointer_type)?);
+            let struct_name = var_context.struct_name_of_ast_type(param_type, interner);
+            var_context.bind_struct(name.id, struct_name);
+            let signature = function_pointer_signature(param_type, builder.func.signature.call_conv, pointer_type)?;
+            var_context.bind_function_pointer(name.id, signature);
+            
+            // Linear parameters are owned by this function from entry
+            if utils::strategy_from_ast_type(param_type, interner) == Some(MemoryStrategy::Linear) {
+                let location = var_context.source_location(&param.span);
+                var_context.memory_manager.track_linear(block_params[i], &location);
+                var_context.linear_vars.insert(name.id, block_params[i]);
+            }
+        }
+    }
+    
+    // Compile function body
+    let (result_value, function_terminated) = compile_expression_with_variables_and_termination(&mut builder, body, &mut var_context, interner)?;
+    
+    // Only add return instruction if the function didn't already terminate
+    if !function_terminated {
+        // The value of the body is dropped when the function returns unit
+        let value = var_context.return_type.as_ref().and(result_value);
+        emit_checked_return(&mut builder, value, &mut var_context)?;
+    }
+    let source_locations = var_context.source_locations.take().unwrap_or_default();
+    
+    // Finalize function
+    builder.finalize();
+    
+    // Compile to machine code here, so the serial define step only copies bytes
+    // (the verifier runs as part of compilation when enabled)
+    ctx.compile(module.isa(), &mut ControlPlane::default())
+        .map_err(|e| {
+            // Verifier failures are printed against the IR
+            let error_msg = match &e.inner {
+                cranelift_codegen::CodegenError::Verifier(errors) => {
+                    cranelift_codegen::print_errors::pretty_verifier_error(e.func, None, errors.clone())
+                }
+                inner => format!("{:?}", inner),
+            };
+            CodegenError::InternalError(format!("Failed to define function '{}': {}", func_name, error_msg))
+        })?;
+    
+    Ok(CompiledFunction { func_id, name: func_name, ctx, span, source_locations })
+}
+
+/// Compile an expression with variable context and termination tracking
+///
+/// Returns the expression's value, None for unit or when it terminated, and whether it terminated.
+fn compile_expression_with_variables_and_termination(
+    builder: &mut FunctionBuilder,
+    expr: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<(Option<Value>, bool)> {
+    match expr {
+        Expr::Return { value, .. } => {
+            // Generate the actual return instruction here!
+            if let Some(value_expr) = value {
+                let return_value = compile_return_value(builder, value_expr, var_context, interner)?;
+                emit_checked_return(builder, return_value, var_context)?;
+            } else {
+                // Return unit/void
+                emit_checked_return(builder, None, var_context)?;
+            }
+            // Mark as terminated
+            Ok((None, true))
+        }
+        Expr::Block { statements, trailing_expr, .. } => {
+            let mut block_terminated = false;
+            let mut result_value = None;
+            
+            // Compile all statements with termination tracking, in the block's own scope
+            var_context.push_scope();
+            for stmt in statements {
+                let stmt_terminated = compile_statement_with_variables_and_termination(builder, stmt, var_context, interner)?;
+                if stmt_terminated {
+                    block_terminated = true;
+                    break; // Don't process more statements after termination
+                }
+            }
+            
+            // Only process trailing expression if block wasn't terminated
+            if !block_terminated {
+                if let Some(trailing) = trailing_expr {
+                    var_context.mark_source_location(builder, &trailing.span());
+                    result_value = compile_expression_with_variables(builder, trailing, var_context, interner)?;
+                }
+            }
+            var_context.pop_scope(builder, block_terminated)?;
+            
+            // A block without a trailing expression is unit
+            Ok((result_value, block_terminated))
+        }
+        Expr::Loop { label, body, .. } => compile_loop_expression(builder, *label, body, var_context, interner),
+        Expr::Break { label, value, span, .. } => {
+            compile_loop_exit(builder, label, true, value.as_deref(), span, var_context, interner)?;
+            Ok((None, true))
+        }
+        Expr::Continue { label, span, .. } => {
+            compile_loop_exit(builder, label, false, None, span, var_context, interner)?;
+            Ok((None, true))
+        }
+        _ => {
+            // For all other expressions, compile normally and mark as not terminated
+            let result = compile_expression_with_variables(builder, expr, var_context, interner)?;
+            Ok((result, false))
+        }
+    }
+}
+
+/// Compile an expression whose value is needed, such as an operand, argument or condition
+///
+/// Unit expressions have no value to give; semantic analysis reports them, so one
+/// reaching code generation is an error here too.
+fn compile_value_with_variables(
+    builder: &mut FunctionBuilder,
+    expr: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    compile_expression_with_variables(builder, expr, var_context, interner)?
+        .ok_or_else(|| CodegenError::TypeConversion("expression of type () has no value to use".to_string()).at(expr.span()))
+}
+
+/// Compile an expression with variable context
+///
+/// Returns the expression's value, or None for unit expressions: blocks without a
+/// trailing expression, calls of functions returning nothing, and `if` and `match`
+/// expressions with a unit branch. Unit has no SSA value. Errors the expression's
+/// subexpressions did not locate are located at the expression.
+fn compile_expression_with_variables(
+    builder: &mut FunctionBuilder,
+    expr: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<Value>> {
+    lower_expression(builder, expr, var_context, interner).map_err(|error| error.at(expr.span()))
+}
+
+fn lower_expression(
+    builder: &mut FunctionBuilder,
+    expr: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<Value>> {
+    let value = match expr {
+        Expr::Literal { literal: crate::ast::Literal::String { value, .. }, .. } => {
+            let text = interner.resolve(*value).unwrap_or_default();
+            let pointer_type = var_context.memory_manager.pointer_type();
+            var_context.module_context.intrinsics().string_literal(builder, &text, pointer_type)?
+        }
+        Expr::Literal { literal, .. } => {
+            let value = expressions::compile_literal(builder, literal)?;
+            if matches!(literal, crate::ast::Literal::Integer { suffix: Some(suffix), .. } if suffix.is_unsigned()) {
+                var_context.mark_unsigned(value);
+            }
+            value
+        }
+        // A variable bound to a unit value has no value to load
+        Expr::Identifier { name, .. } if var_context.unit_vars.contains(&name.id) => return Ok(None),
+        Expr::Identifier { name, span, .. } => {
+            // Variable lookup - FIXED!
+            if let Some(var_info) = var_context.get_variable(name.id) {
+                // Reading a linear variable after its value was moved out is an error
+                var_context.check_linear_read(name.id, span)?;
+                // Load from stack slot, at the variable's own width
+                let (cranelift_type, stack_slot, unsigned) = (var_info.cranelift_type, var_info.stack_slot, var_info.unsigned);
+                let value = builder.ins().stack_load(cranelift_type, stack_slot, 0);
+                if unsigned {
+                    var_context.mark_unsigned(value);
+                }
+                if let Some(&pointee) = var_context.pointer_vars.get(&name.id) {
+                    var_context.pointee_types.insert(value, pointee);
+                }
+                if let Some(struct_name) = var_context.struct_vars.get(&name.id).cloned() {
+                    var_context.struct_values.insert(value, struct_name);
+                }
+                if let Some(signature) = var_context.function_pointer_vars.get(&name.id).cloned() {
+                    var_context.function_pointers.insert(value, signature);
+                }
+                value
+            } else if let Some(variant) = var_context.resolve_variant(std::slice::from_ref(name), None, interner) {
+                // A bare unit variant such as `None`
+                compile_variant_construction(builder, variant, VariantArgs::Unit, None, span, var_context, interner)?
+            } else {
+                let var_name = interner.resolve(*name)
+                    .unwrap_or_else(|| format!("var_{}", name.id));
+                return Err(CodegenError::SymbolResolution(
+                    format!("Undefined variable: {}", var_name)
+                ));
+            }
+        }
+        Expr::Path { segments, span, .. } => {
+            // Handle unit variants such as `Color::Red`
+            let variant = resolve_pattern_variant(segments, None, span, var_context, interner)?;
+            compile_variant_construction(builder, variant, VariantArgs::Unit, None, span, var_context, interner)?
+        }
+        Expr::Binary { left, op: op @ (crate::ast::BinaryOp::LogicalAnd | crate::ast::BinaryOp::LogicalOr), right, .. } => {
+            // Short-circuit: the right operand is only evaluated when it decides the result
+            compile_logical_with_variables(builder, left, *op, right, var_context, interner)?
+        }
+        Expr::Binary { left, op, right, .. } => {
+            compile_binary_with_variables(builder, left, *op, right, var_context, interner)?
+        }
+        Expr::Block { statements, trailing_expr, .. } => {
+            // Compile all statements, stopping at a return/break/continue
+            var_context.push_scope();
+            let result_value = if compile_statements_with_termination(builder, statements, var_context, interner)? {
+                // The block's value is never observed - keep emitting into an unreachable block
+                switch_to_unreachable_block(builder);
+                var_context.pop_scope(builder, true)?;
+                Some(builder.ins().iconst(ctypes::I32, 0))
+            } else {
+                let value = match trailing_expr {
+                    Some(trailing) => {
+                        var_context.mark_source_location(builder, &trailing.span());
+                        compile_expression_with_variables(builder, trailing, var_context, interner)?
+                    }
+                    None => None,
+                };
+                var_context.pop_scope(builder, false)?;
+                value
+            };
+            return Ok(result_value);
+        }
+        Expr::Loop { label, body, .. } => {
+            let (value, never_exits) = compile_loop_expression(builder, *label, body, var_context, interner)?;
+            if !never_exits {
+                return Ok(value);
+            }
+            // The loop's value is never observed - keep emitting into an unreachable block
+            switch_to_unreachable_block(builder);
+            builder.ins().iconst(ctypes::I32, 0)
+        }
+        Expr::Return { value, .. } => {
+            // This should not be reached since Return is handled in termination tracking
+            // But provide a fallback just in case
+            return match value {
+                Some(value_expr) => compile_expression_with_variables(builder, value_expr, var_context, interner),
+                None => Ok(None),
+            };
+        }
+        Expr::Parenthesized { expr, .. } => {
+            // Parentheses are just for grouping - compile the inner expression
+            return compile_expression_with_variables(builder, expr, var_context, interner);
+        }
+        Expr::Call { callee, args, span, .. } => {
+            // Handle function calls, unit when the function returns nothing
+            return compile_function_call_with_variables(builder, callee, args, span, var_context, interner);
+        }
+        Expr::If { condition, then_block, else_block, span, .. } => {
+            // Handle if expressions
+            return compile_if_expression_with_variables(builder, condition, then_block, else_block, span, var_context, interner);
+        }
+        Expr::Match { expr, arms, span, .. } => {
+            // Handle match expressions
+            return compile_match_expression_with_variables(builder, expr, arms, span, var_context, interner);
+        }
+        Expr::Unary { op, expr, span, .. } => {
+            // Handle unary operations
+            compile_unary_with_variables(builder, *op, expr, span, var_context, interner)?
+        }
+        Expr::Closure { span, .. } => {
+            // Closures were lifted into functions; the closure is that function's address
+            compile_closure_address(builder, span, var_context, interner)?
+        }
+        Expr::Cast { expr, target_type, span, .. } => {
+            // Handle explicit numeric conversions
+            compile_cast_with_variables(builder, expr, target_type, span, var_context, interner)?
+        }
+        Expr::Index { object, index, span, .. } => {
+            // Handle array indexing with variable support
+            compile_array_index_with_variables(builder, object, index, span, var_context, interner)?
+        }
+        Expr::Array { elements, span, .. } => {
+            // Handle array literals with variable support
+            compile_array_literal_with_variables(builder, elements, (ctypes::I32, false), span, var_context, interner)?
+        }
+        Expr::ArrayRepeat { .. } => {
+            compile_array_initializer(builder, expr, None, (ctypes::I32, false), var_context, interner)?
+        }
+        Expr::Tuple { elements, span, .. } => {
+            // Handle tuple expressions, laid out like anonymous structs
+            compile_tuple_with_variables(builder, elements, span, var_context, interner)?
+        }
+        Expr::StructInit { path, fields, span, .. } => {
+            // Handle struct initialization - no annotation, so the strategy is inferred
+            compile_struct_init_with_variables(builder, path, fields, None, span, var_context, interner)?
+        }
+        Expr::FieldAccess { object, field, span, .. } => {
+            // Handle field access through the struct layout
+            compile_field_access_with_variables(builder, object, field, span, var_context, interner)?
+        }
+        Expr::MethodCall { receiver, method, args, span, .. } => {
+            // Handle method calls: `Type_method(receiver, args...)`
+            return compile_method_call_with_variables(builder, receiver, method, args, span, var_context, interner);
+        }
+        _ => {
+            // Use the expressions module for other expression types
+            expressions::compile_expression(builder, expr)?
+        }
+    };
+    Ok(Some(value))
+}
+
+/// Compile a single statement with variable context and termination tracking
+///
+/// Errors its expressions did not locate are located at the statement.
+fn compile_statement_with_variables_and_termination(
+    builder: &mut FunctionBuilder,
+    statement: &Stmt,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<bool> {
+    lower_statement(builder, statement, var_context, interner).map_err(|error| error.at(statement.span()))
+}
+
+fn lower_statement(
+    builder: &mut FunctionBuilder,
+    statement: &Stmt,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<bool> {
+    var_context.mark_source_location(builder, &statement.span());
+    match statement {
+        Stmt::Return { expr, .. } => {
+            if let Some(expr) = expr {
+                let value = compile_return_value(builder, expr, var_context, interner)?;
+                emit_checked_return(builder, value, var_context)?;
+            } else {
+                emit_checked_return(builder, None, var_context)?;
+            }
+            Ok(true) // Return true to indicate termination
+        }
+        Stmt::Expression { expr, .. } => {
+            // Evaluate expression but ignore result, which unit expressions don't have
+            compile_expression_with_variables(builder, expr, var_context, interner)?;
+            Ok(false) // Non-terminating statement
+        }
+        Stmt::Let { pattern, type_annotation, initializer, .. } => {
+            // Handle variable declaration
+            compile_let_statement(builder, pattern, type_annotation, initializer, var_context, interner)?;
+            Ok(false) // Non-terminating statement
+        }
+        Stmt::Assignment { target, value, .. } => {
+            // Handle assignment
+            compile_assignment_statement(builder, target, value, var_context, interner)?;
+            Ok(false) // Non-terminating statement  
+        }
+        Stmt::CompoundAssignment { target, op, value, .. } => {
+            // Handle `target op= value`
+            compile_compound_assignment_statement(builder, target, *op, value, var_context, interner)?;
+            Ok(false) // Non-terminating statement
+        }
+        Stmt::If { condition, then_block, else_block, .. } => {
+            // Handle if statement - terminates only when both branches do
+            compile_if_statement_with_variables(builder, condition, then_block, else_block, var_context, interner)
+        }
+        Stmt::Match { expr, arms, .. } => {
+            // Handle match statement - terminates only when every arm does
+            compile_match_statement_with_variables(builder, expr, arms, var_context, interner)
+        }
+        Stmt::While { condition, body, .. } => {
+            // Handle while loop
+            compile_while_statement_with_variables(builder, condition, body, var_context, interner)?;
+            Ok(false) // Non-terminating statement
+        }
+        Stmt::Loop { label, body, .. } => {
+            // Handle loop - terminates when no `break` leaves it
+            let (_, never_exits) = compile_loop_with_variables(
+                builder,
+                *label,
+                |builder, var_context| compile_scoped_statements(builder, body, var_context, interner),
+                var_context,
+            )?;
+            Ok(never_exits)
+        }
+        Stmt::Break { label, expr, span, .. } => {
+            compile_loop_exit(builder, label, true, expr.as_ref(), span, var_context, interner)?;
+            Ok(true)
+        }
+        Stmt::Continue { label, span, .. } => {
+            compile_loop_exit(builder, label, false, None, span, var_context, interner)?;
+            Ok(true)
+        }
+        Stmt::For { pattern, iterable, body, .. } => {
+            // Handle for loop - simplified to while loop for now
+            compile_for_statement_with_variables(builder, pattern, iterable, body, var_context, interner)?;
+            Ok(false) // Non-terminating statement
+        }
+        Stmt::Block { statements, .. } => {
+            // Handle block statement by compiling all statements inside with termination tracking
+            compile_scoped_statements(builder, statements, var_context, interner)
+        }
+        Stmt::Region { name, size_hint, body, span, .. } => {
+            // Handle region block - termination inside the block propagates out
+            compile_region_statement(builder, name, *size_hint, body, span, var_context, interner)
+        }
+        Stmt::Item { item: Item::Function { .. }, .. } => {
+            // Nested functions are compiled as separate functions (see `function_items`)
+            Ok(false)
+        }
+        _ => Err(CodegenError::UnsupportedFeature(
+            format!("Statement not yet supported: {:?}", statement)
+        )),
+    }
+}
+
+/// Compile a region block: create and initialize the region on entry, then free it in bulk on exit
+///
+/// Returns true if the block terminated (its region was then released by the return).
+fn compile_region_statement(
+    builder: &mut FunctionBuilder,
+    name: &crate::ast::InternedString,
+    size_hint: Option<u64>,
+    body: &[Stmt],
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<bool> {
+    let region_name = interner.resolve(*name)
+        .ok_or_else(|| CodegenError::InternalError(format!("Cannot resolve region name with ID {}", name.id)))?;
+    if size_hint == Some(0) {
+        return Err(CodegenError::MemoryManagement(format!(
+            "Size hint of region '{}' must be positive\nSuggestion: Give the expected size in bytes, or remove size_hint",
+            region_name
+        )).at(*span));
+    }
+    let size = size_hint
+        .unwrap_or_else(|| estimate_region_size(body, interner))
+        .max(REGION_BLOCK_GRANULARITY);
+    
+    let region_id = var_context.memory_manager.create_region(format!("{}::{}", var_context.function_name, region_name), size);
+    var_context.memory_manager.initialize_region(builder, region_id)?;
+    var_context.region_stack.push(region_id);
+    
+    let terminated = compile_scoped_statements(builder, body, var_context, interner)?;
+    
+    if !terminated {
+        var_context.memory_manager.release_region(builder, region_id)?;
+    }
+    var_context.region_stack.pop();
+    
+    Ok(terminated)
+}
+
+/// Names of the region blocks in a function body
+fn region_names(body: &Expr, interner: &StringInterner) -> HashSet<String> {
+    let mut names = HashSet::new();
+    closures::walk(closures::Node::Expr(body), &mut |node| {
+        if let closures::Node::Stmt(Stmt::Region { name, .. }) = node {
+            names.extend(interner.resolve(*name));
+        }
+    });
+    names
+}
+
+/// Estimate the bytes a region block allocates directly (nested region blocks get their own region)
+fn estimate_region_size(body: &[Stmt], interner: &StringInterner) -> u64 {
+    let round_up = |bytes: u64| bytes.div_ceil(REGION_BLOCK_GRANULARITY) * REGION_BLOCK_GRANULARITY;
+    
+    body.iter().map(|stmt| match stmt {
+        Stmt::Let { type_annotation: Some(type_ann), initializer: Some(Expr::StructInit { fields, .. }), .. }
+            if utils::strategy_from_ast_type(type_ann, interner) == Some(MemoryStrategy::Region) => {
+            round_up(fields.len().max(1) as u64 * 8)
+        }
+        Stmt::If { then_block, else_block, .. } => {
+            estimate_region_size(then_block, interner)
+                + else_block.as_ref().map_or(0, |else_stmt| estimate_region_size(std::slice::from_ref(else_stmt.as_ref()), interner))
+        }
+        Stmt::While { body, .. } | Stmt::For { body, .. } | Stmt::Loop { body, .. } => estimate_region_size(body, interner),
+        Stmt::Block { statements, .. } => estimate_region_size(statements, interner),
+        _ => 0,
+    }).sum()
+}
+
+/// Compile a let statement (variable declaration)
+fn compile_let_statement(
+    builder: &mut FunctionBuilder,
+    pattern: &Pattern,
+    type_annotation: &Option<AstType>,
+    initializer: &Option<Expr>,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    match pattern {
+        Pattern::Identifier { name, span, .. } => {
+            // Without an annotation, the scalar type semantic analysis resolved stands in for one.
+            // Its array types replace the annotation, as they carry `const` lengths folded
+            let resolved_type = match var_context.module_context.get_binding_types().get(&span.start) {
+                Some(binding_type @ AstType::Array { .. }) => Some(binding_type.clone()),
+                binding_type => type_annotation.clone().or_else(|| {
+                    binding_type
+                        .filter(|ty| matches!(ty, AstType::Primitive { kind, .. } if kind.is_integer() || kind.is_float() || *kind == PrimitiveType::Bool))
+                        .cloned()
+                }),
+            };
+            let type_annotation = &resolved_type;
+            let pointer_type = var_context.memory_manager.pointer_type();
+            let array_length = static_array_length(type_annotation.as_ref(), initializer.as_ref());
+            let array_element = match type_annotation {
+                Some(type_ann) => array_element_of_ast_type(type_ann, pointer_type)?,
+                None => None,
+            };
+            
+            // Compile the initializer before declaring, so it still sees any variable it shadows
+            let init_value = match initializer {
+                // Struct literals honour the strategy requested by the type annotation
+                Some(Expr::StructInit { path, fields, span, .. }) => {
+                    let strategy = type_annotation.as_ref()
+                        .and_then(|type_ann| utils::strategy_from_ast_type(type_ann, interner));
+                    Some(compile_struct_init_with_variables(builder, path, fields, strategy, span, var_context, interner)?)
                 }
-            } else {
+                // Array literals are laid out with the annotated element type and length
+                Some(init_expr @ (Expr::Array { .. } | Expr::ArrayRepeat { .. })) => {
+                    let element = array_element.unwrap_or((ctypes::I32, false));
+                    Some(compile_array_initializer(builder, init_expr, array_length, element, var_context, interner)?)
+                }
+                Some(init_expr) => match compile_expression_with_variables(builder, init_expr, var_context, interner)? {
+                    Some(value) => Some(value),
+                    // A unit initializer leaves the variable nothing to store
+                    None => {
+                        if let Some(type_ann) = type_annotation.as_ref().filter(|type_ann| !is_unit_ast_type(type_ann)) {
+                            return Err(CodegenError::TypeConversion(format!(
+                                "cannot initialize {} variable from a value of type ()",
+                                ast_type_name(type_ann, pointer_type)?
+                            )).at(init_expr.span()));
+                        }
+                        var_context.declare_unit_variable(name.id);
+                        return Ok(());
+                    }
+                },
+                None => None,
+            };
+            
+            // The annotation fixes the variable's type; otherwise it follows the initializer
+            let (var_type, unsigned) = match (type_annotation, init_value) {
+                (Some(type_ann), _) => (ast_type_to_cranelift_type(type_ann, pointer_type)?, is_unsigned_ast_type(type_ann)),
+                (None, Some(value)) => (builder.func.dfg.value_type(value), var_context.is_unsigned(value)),
                 // No type annotation or initializer - default to i32
-                ctypes::I32
+                (None, None) => (ctypes::I32, false),
             };
             
             // Create stack slot for variable
@@ -653,109 +2464,773 @@ fn compile_let_statement(
                 builder, 
                 name.id, 
                 var_type, 
+                unsigned,
                 format!("var_{}", name.id) // Placeholder name
             )?;
             
-            // Compile initializer if present
-            if let Some(init_expr) = initializer {
-                let init_value = compile_expression_with_variables(builder, init_expr, var_context, interner)?;
+            // References remember their pointee so dereferences load the right type
+            let pointee = match (type_annotation, init_value) {
+                (Some(type_ann), _) => pointee_of_ast_type(type_ann, pointer_type)?,
+                (None, Some(value)) => var_context.pointee_types.get(&value).copied(),
+                (None, None) => None,
+            };
+            var_context.bind_pointee(name.id, pointee);
+            let struct_name = match (type_annotation, init_value) {
+                (Some(type_ann), _) => var_context.struct_name_of_ast_type(type_ann, interner),
+                (None, Some(value)) => var_context.struct_values.get(&value).cloned().or_else(|| {
+                    // Values such as call results carry no type name; semantic analysis knows it
+                    let binding_type = var_context.module_context.get_binding_types().get(&span.start)?.clone();
+                    var_context.struct_name_of_ast_type(&binding_type, interner)
+                }),
+                (None, None) => None,
+            };
+            var_context.bind_struct(name.id, struct_name);
+            let signature = match (type_annotation, init_value) {
+                (Some(type_ann), _) => function_pointer_signature(type_ann, builder.func.signature.call_conv, pointer_type)?,
+                (None, Some(value)) => var_context.function_pointers.get(&value).cloned(),
+                (None, None) => None,
+            };
+            var_context.bind_function_pointer(name.id, signature);
+            
+            // Record the array length when the binding fixes it, for bounds checks on indexing
+            var_context.bind_array(name.id, array_length, array_element);
+            
+            if let (Some(init_expr), Some(init_value)) = (initializer, init_value) {
+                // An annotated binding converts its initializer, e.g. an i32 literal into a u8 slot
+                let init_value = match type_annotation {
+                    Some(type_ann) => {
+                        let found = builder.func.dfg.value_type(init_value);
+                        convert_value(builder, init_value, var_type, var_context.is_unsigned(init_value))
+                            .ok_or_else(|| CodegenError::TypeConversion(format!(
+                                "cannot initialize {} variable from {} value",
+                                ast_type_name(type_ann, pointer_type).unwrap_or_else(|_| var_type.to_string()),
+                                found
+                            )).at(init_expr.span()))?
+                    }
+                    None => init_value,
+                };
                 
                 // Store initial value in stack slot
                 builder.ins().stack_store(init_value, stack_slot, 0);
+                var_context.bind_smart_pointer(builder, name.id, init_expr, init_value)?;
+                let declared_linear = type_annotation.as_ref()
+                    .and_then(|type_ann| utils::strategy_from_ast_type(type_ann, interner)) == Some(MemoryStrategy::Linear);
+                var_context.bind_linear(name.id, init_expr, init_value, declared_linear)?;
+            }
+            
+            Ok(())
+        }
+        Pattern::Tuple { patterns, span, .. } => {
+            let init_expr = initializer.as_ref().ok_or_else(|| CodegenError::UnsupportedFeature("a tuple pattern needs an initializer to destructure".to_string()).at(*span))?;
+            let tuple_ptr = compile_value_with_variables(builder, init_expr, var_context, interner)?;
+            let layout_name = match type_annotation {
+                Some(type_ann) => var_context.struct_name_of_ast_type(type_ann, interner),
+                None => var_context.struct_values.get(&tuple_ptr).cloned(),
+            };
+            let layout_name = layout_name.ok_or_else(|| CodegenError::TypeConversion("cannot destructure a value that is not a tuple".to_string()).at(init_expr.span()))?;
+            bind_tuple_pattern(builder, patterns, tuple_ptr, &layout_name, span, var_context)
+        }
+        Pattern::Wildcard { .. } => {
+            // `let _ = expr;` evaluates the initializer for its effects, unit or not
+            if let Some(init_expr) = initializer {
+                compile_expression_with_variables(builder, init_expr, var_context, interner)?;
+            }
+            Ok(())
+        }
+        _ => Err(CodegenError::UnsupportedFeature(
+            "Only identifier, tuple and wildcard patterns supported for let statements".to_string()
+        )),
+    }
+}
+
+/// Compile an assignment statement (variable assignment)
+fn compile_assignment_statement(
+    builder: &mut FunctionBuilder,
+    target: &Expr,
+    value: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    match target {
+        Expr::Identifier { name, span, .. } => {
+            // Get variable info first
+            let (stack_slot, var_type) = if let Some(var_info) = var_context.get_variable(name.id) {
+                (var_info.stack_slot, var_info.cranelift_type)
+            } else {
+                let var_name = interner.resolve(*name)
+                    .unwrap_or_else(|| format!("var_{}", name.id));
+                return Err(CodegenError::SymbolResolution(
+                    format!("Assignment target '{}' is not a declared variable", var_name)
+                ));
+            };
+            
+            // Compile value, bring it to the variable's width (e.g. truncate into a u8) and store.
+            // A new array literal keeps the variable's element type
+            let value_to_store = match value {
+                Expr::Array { .. } | Expr::ArrayRepeat { .. } => {
+                    let element = var_context.array_element(name.id);
+                    compile_array_initializer(builder, value, None, element, var_context, interner)?
+                }
+                value => compile_value_with_variables(builder, value, var_context, interner)?,
+            };
+            let found = builder.func.dfg.value_type(value_to_store);
+            let converted = convert_value(builder, value_to_store, var_type, var_context.is_unsigned(value_to_store))
+                .ok_or_else(|| CodegenError::TypeConversion(format!(
+                    "cannot assign {} value to {} variable",
+                    found, var_type
+                )).at(*span))?;
+            builder.ins().stack_store(converted, stack_slot, 0);
+            match array_literal_length(value) {
+                Some(length) => {
+                    var_context.array_lengths.insert(name.id, length);
+                }
+                None => var_context.bind_array(name.id, None, None),
             }
+            var_context.assign_smart_pointer(builder, name.id, value, value_to_store)?;
+            let declared_linear = var_context.linear_vars.contains_key(&name.id);
+            var_context.bind_linear(name.id, value, value_to_store, declared_linear)?;
+            Ok(())
+        }
+        Expr::Index { object, index, span, .. } => {
+            let Expr::Identifier { name, .. } = object.as_ref() else {
+                return Err(CodegenError::UnsupportedFeature(
+                    "Only array variables can be indexed in assignment targets".to_string()
+                ));
+            };
+            
+            let index_val = compile_value_with_variables(builder, index, var_context, interner)?;
+            let value_to_store = compile_value_with_variables(builder, value, var_context, interner)?;
+            
+            // Narrower and wider integers are converted to the element type on store
+            let (element_type, _) = var_context.array_element(name.id);
+            let found = builder.func.dfg.value_type(value_to_store);
+            let element_value = convert_value(builder, value_to_store, element_type, var_context.is_unsigned(value_to_store))
+                .ok_or_else(|| CodegenError::TypeConversion(format!(
+                    "cannot store {} value into an {} array element",
+                    found, element_type
+                )).at(*span))?;
+            
+            // Same address computation and bounds check as element reads
+            let element_addr = array_element_address(builder, name, index, index_val, span, var_context, interner)?;
+            element_addr.store(builder, element_value);
+            Ok(())
+        }
+        Expr::Unary { op: crate::ast::UnaryOp::Dereference, expr: pointer_expr, span, .. } => {
+            let pointer = compile_value_with_variables(builder, pointer_expr, var_context, interner)?;
+            let (pointee_type, _) = var_context.pointee_types.get(&pointer).copied()
+                .ok_or_else(|| CodegenError::TypeConversion("cannot assign through a value that is not a known reference".to_string()).at(*span))?;
+            
+            let value_to_store = compile_value_with_variables(builder, value, var_context, interner)?;
+            let found = builder.func.dfg.value_type(value_to_store);
+            let converted = convert_value(builder, value_to_store, pointee_type, var_context.is_unsigned(value_to_store))
+                .ok_or_else(|| CodegenError::TypeConversion(format!(
+                    "cannot assign {} value through a reference to {}",
+                    found, pointee_type
+                )).at(*span))?;
+            Address::new(pointer, 0).store(builder, converted);
+            Ok(())
+        }
+        Expr::FieldAccess { object, field, span, .. } => {
+            let (struct_ptr, field_layout) = compile_field_address(builder, object, field, span, var_context, interner)?;
             
+            let value_to_store = compile_value_with_variables(builder, value, var_context, interner)?;
+            let found = builder.func.dfg.value_type(value_to_store);
+            let converted = convert_value(builder, value_to_store, field_layout.cranelift_type, var_context.is_unsigned(value_to_store))
+                .ok_or_else(|| CodegenError::TypeConversion(format!(
+                    "cannot assign {} value to field '{}' of type {}",
+                    found, field_layout.name, field_layout.cranelift_type
+                )).at(*span))?;
+            Address::field(struct_ptr, field_layout.offset).store(builder, converted);
             Ok(())
         }
         _ => Err(CodegenError::UnsupportedFeature(
-            "Only identifier patterns supported for let statements".to_string()
+            "Only identifier, index and field targets supported for assignments".to_string()
+        )),
+    }
+}
+
+/// Storage of a compound assignment target, computed once
+enum Place {
+    /// Local variable
+    Slot(StackSlot),
+    /// Memory, e.g. an array element or a field
+    Memory(Address),
+}
+
+/// Compile a compound assignment `target op= value`: load the target, apply `op`, store back
+///
+/// The target's location is computed once, so an index or object expression with side
+/// effects (`values[next()] += 1`) runs once.
+fn compile_compound_assignment_statement(
+    builder: &mut FunctionBuilder,
+    target: &Expr,
+    op: crate::ast::BinaryOp,
+    value: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    let (place, target_type, unsigned) = match target {
+        Expr::Identifier { name, span, .. } => {
+            let Some(var_info) = var_context.get_variable(name.id) else {
+                let var_name = interner.resolve(*name)
+                    .unwrap_or_else(|| format!("var_{}", name.id));
+                return Err(CodegenError::SymbolResolution(
+                    format!("Assignment target '{}' is not a declared variable", var_name)
+                ));
+            };
+            let (stack_slot, var_type, unsigned) = (var_info.stack_slot, var_info.cranelift_type, var_info.unsigned);
+            var_context.check_linear_read(name.id, span)?;
+            (Place::Slot(stack_slot), var_type, unsigned)
+        }
+        Expr::Index { object, index, span, .. } => {
+            let Expr::Identifier { name, .. } = object.as_ref() else {
+                return Err(CodegenError::UnsupportedFeature(
+                    "Only array variables can be indexed in assignment targets".to_string()
+                ));
+            };
+            let index_val = compile_value_with_variables(builder, index, var_context, interner)?;
+            let element_addr = array_element_address(builder, name, index, index_val, span, var_context, interner)?;
+            let (element_type, unsigned) = var_context.array_element(name.id);
+            (Place::Memory(element_addr), element_type, unsigned)
+        }
+        Expr::Unary { op: crate::ast::UnaryOp::Dereference, expr: pointer_expr, span, .. } => {
+            let pointer = compile_value_with_variables(builder, pointer_expr, var_context, interner)?;
+            let (pointee_type, unsigned) = var_context.pointee_types.get(&pointer).copied()
+                .ok_or_else(|| CodegenError::TypeConversion("cannot assign through a value that is not a known reference".to_string()).at(*span))?;
+            (Place::Memory(Address::new(pointer, 0)), pointee_type, unsigned)
+        }
+        Expr::FieldAccess { object, field, span, .. } => {
+            let (struct_ptr, field_layout) = compile_field_address(builder, object, field, span, var_context, interner)?;
+            (Place::Memory(Address::field(struct_ptr, field_layout.offset)), field_layout.cranelift_type, field_layout.unsigned)
+        }
+        _ => return Err(CodegenError::UnsupportedFeature(
+            "Only identifier, index, dereference and field targets supported for compound assignments".to_string()
+        )),
+    };
+    
+    let current = match place {
+        Place::Slot(stack_slot) => builder.ins().stack_load(target_type, stack_slot, 0),
+        Place::Memory(address) => address.load(builder, target_type),
+    };
+    if unsigned {
+        var_context.mark_unsigned(current);
+    }
+    let operand = compile_value_with_variables(builder, value, var_context, interner)?;
+    let result = emit_binary(builder, op, current, operand, (false, is_integer_literal(value)), var_context)?;
+    
+    // The result goes back at the target's width, like a plain assignment
+    let found = builder.func.dfg.value_type(result);
+    let converted = convert_value(builder, result, target_type, var_context.is_unsigned(result))
+        .ok_or_else(|| CodegenError::TypeConversion(format!(
+            "cannot assign {} value to {} target",
+            found, target_type
+        )).at(target.span()))?;
+    match place {
+        Place::Slot(stack_slot) => {
+            builder.ins().stack_store(converted, stack_slot, 0);
+        }
+        Place::Memory(address) => address.store(builder, converted),
+    }
+    Ok(())
+}
+
+/// Compile an if expression with variable context
+///
+/// The result type follows the branch values: integer branches of different widths are
+/// widened to the wider one and `f32` is promoted to `f64`. Without an `else` the
+/// expression has unit type, represented as an I32 zero like `()` elsewhere.
+fn compile_if_expression_with_variables(
+    builder: &mut FunctionBuilder,
+    condition: &Expr,
+    then_block: &Expr,
+    else_block: &Option<Box<Expr>>,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<Value>> {
+    // Compile the condition
+    let condition_val = compile_value_with_variables(builder, condition, var_context, interner)?;
+    
+    // Create blocks for then, else, and merge
+    let then_bb = builder.create_block();
+    let else_bb = builder.create_block();
+    let merge_bb = builder.create_block();
+    
+    // Branch based on condition (non-zero means true)
+    let is_true = expressions::normalize_condition(builder, condition_val)?;
+    builder.ins().brif(is_true, then_bb, &[], else_bb, &[]);
+    
+    let Some(else_expr) = else_block else {
+        // No else block: evaluate the then branch for its effects, the result is unit
+        builder.switch_to_block(then_bb);
+        builder.seal_block(then_bb);
+        let (_, then_terminated) = compile_expression_with_variables_and_termination(builder, then_block, var_context, interner)?;
+        if !then_terminated {
+            builder.ins().jump(merge_bb, &[]);
+        }
+        builder.switch_to_block(else_bb);
+        builder.seal_block(else_bb);
+        builder.ins().jump(merge_bb, &[]);
+        
+        builder.switch_to_block(merge_bb);
+        builder.seal_block(merge_bb);
+        return Ok(None);
+    };
+    
+    // Each branch hands its value to a tail block typed after that value; once both
+    // types are known the tails convert to the common result type and jump to the merge
+    let then_tail = compile_if_branch(builder, then_bb, then_block, var_context, interner)?;
+    let else_tail = compile_if_branch(builder, else_bb, else_expr, var_context, interner)?;
+    
+    // A unit branch makes the whole if unit, dropping the other branch's value
+    if [then_tail, else_tail].iter().flatten().any(|(_, tail_type)| tail_type.is_none()) {
+        discard_branch_values(builder, [then_tail, else_tail].into_iter().flatten(), merge_bb);
+        builder.switch_to_block(merge_bb);
+        builder.seal_block(merge_bb);
+        return Ok(None);
+    }
+    let (then_tail, else_tail) = (valued_tail(then_tail), valued_tail(else_tail));
+    
+    let result_type = match (then_tail, else_tail) {
+        (Some((_, then_type)), Some((_, else_type))) => unify_branch_types(then_type, else_type)
+            .ok_or_else(|| CodegenError::TypeConversion(format!(
+                "if branches have incompatible types: then branch is {}, else branch is {}",
+                then_type, else_type
+            )).at(*span))?,
+        (Some((_, branch_type)), None) | (None, Some((_, branch_type))) => branch_type,
+        // Both branches left the function - the merge block is unreachable
+        (None, None) => ctypes::I32,
+    };
+    let merge_param = builder.append_block_param(merge_bb, result_type);
+    
+    let mut all_unsigned = true;
+    for (tail_bb, tail_type) in [then_tail, else_tail].into_iter().flatten() {
+        builder.switch_to_block(tail_bb);
+        builder.seal_block(tail_bb);
+        let tail_value = builder.block_params(tail_bb)[0];
+        let unsigned = var_context.is_unsigned(tail_value);
+        all_unsigned &= unsigned;
+        let converted = convert_value(builder, tail_value, result_type, unsigned)
+            .ok_or_else(|| CodegenError::InternalError(format!("Cannot convert if branch from {} to {}", tail_type, result_type)))?;
+        builder.ins().jump(merge_bb, &[converted]);
+    }
+    
+    // Both jumps into the merge block are emitted - it can be sealed
+    builder.switch_to_block(merge_bb);
+    builder.seal_block(merge_bb);
+    if all_unsigned {
+        var_context.mark_unsigned(merge_param);
+    }
+    
+    // Return the merged value
+    Ok(Some(merge_param))
+}
+
+/// Tail block of an if or match branch, with the type of the value it receives; None for a unit branch
+type BranchTail = (Block, Option<Type>);
+
+/// Compile one branch of an if or match expression into `branch_bb`
+///
+/// Returns the tail block receiving the branch value and that value's type, or None if the
+/// branch terminated. The tail of a unit branch takes no value.
+fn compile_if_branch(
+    builder: &mut FunctionBuilder,
+    branch_bb: Block,
+    branch: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<BranchTail>> {
+    builder.switch_to_block(branch_bb);
+    builder.seal_block(branch_bb);
+    let (value, terminated) = compile_expression_with_variables_and_termination(builder, branch, var_context, interner)?;
+    if terminated {
+        return Ok(None);
+    }
+    
+    let tail_bb = builder.create_block();
+    let Some(value) = value else {
+        builder.ins().jump(tail_bb, &[]);
+        return Ok(Some((tail_bb, None)));
+    };
+    let value_type = builder.func.dfg.value_type(value);
+    let tail_param = builder.append_block_param(tail_bb, value_type);
+    if var_context.is_unsigned(value) {
+        var_context.mark_unsigned(tail_param);
+    }
+    builder.ins().jump(tail_bb, &[value]);
+    Ok(Some((tail_bb, Some(value_type))))
+}
+
+/// The tail of a branch that hands on a value, with that value's type
+fn valued_tail(tail: Option<BranchTail>) -> Option<(Block, Type)> {
+    tail.and_then(|(tail_bb, tail_type)| Some((tail_bb, tail_type?)))
+}
+
+/// Jump from every tail to `merge_bb` without passing the branch values on
+fn discard_branch_values(builder: &mut FunctionBuilder, tails: impl IntoIterator<Item = BranchTail>, merge_bb: Block) {
+    for (tail_bb, _) in tails {
+        builder.switch_to_block(tail_bb);
+        builder.seal_block(tail_bb);
+        builder.ins().jump(merge_bb, &[]);
+    }
+}
+
+/// Common type of two if-expression branches, if they are compatible
+fn unify_branch_types(then_type: Type, else_type: Type) -> Option<Type> {
+    if then_type == else_type {
+        Some(then_type)
+    } else if then_type.is_int() && else_type.is_int() {
+        Some(if then_type.bits() >= else_type.bits() { then_type } else { else_type })
+    } else if then_type.is_float() && else_type.is_float() {
+        Some(ctypes::F64)
+    } else {
+        None
+    }
+}
+
+/// Compile an arithmetic or comparison binary expression
+///
+/// Integer operands of different widths are widened to the wider type, each extended
+/// according to its own signedness. An integer literal instead takes the other operand's
+/// type, so `a + 1` on a `u8` stays a `u8` and wraps. Unsigned operands select unsigned
+/// division, remainder and comparisons.
+fn compile_binary_with_variables(
+    builder: &mut FunctionBuilder,
+    left: &Expr,
+    op: crate::ast::BinaryOp,
+    right: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    let left_val = compile_value_with_variables(builder, left, var_context, interner)?;
+    let right_val = compile_value_with_variables(builder, right, var_context, interner)?;
+    let literals = (is_integer_literal(left), is_integer_literal(right));
+    emit_binary(builder, op, left_val, right_val, literals, var_context)
+}
+
+/// Emit a binary operation on compiled operands
+///
+/// Integer operands meet at the wider type; an integer literal operand (flagged in
+/// `literals`) adapts to the other side instead.
+fn emit_binary(
+    builder: &mut FunctionBuilder,
+    op: crate::ast::BinaryOp,
+    left_val: Value,
+    right_val: Value,
+    literals: (bool, bool),
+    var_context: &mut VariableContext,
+) -> CodegenResult<Value> {
+    use crate::ast::BinaryOp;
+    use cranelift::prelude::IntCC;
+    
+    let left_type = builder.func.dfg.value_type(left_val);
+    let right_type = builder.func.dfg.value_type(right_val);
+    let left_unsigned = var_context.is_unsigned(left_val);
+    let right_unsigned = var_context.is_unsigned(right_val);
+    
+    let (left_val, right_val, unsigned) = if left_type.is_int() && right_type.is_int() {
+        let (operand_type, unsigned) = match literals {
+            (false, true) => (left_type, left_unsigned),
+            (true, false) => (right_type, right_unsigned),
+            _ if left_type.bits() > right_type.bits() => (left_type, left_unsigned),
+            _ if right_type.bits() > left_type.bits() => (right_type, right_unsigned),
+            _ => (left_type, left_unsigned && right_unsigned),
+        };
+        let left_val = convert_value(builder, left_val, operand_type, left_unsigned).unwrap_or(left_val);
+        let right_val = convert_value(builder, right_val, operand_type, right_unsigned).unwrap_or(right_val);
+        (left_val, right_val, unsigned)
+    } else {
+        (left_val, right_val, false)
+    };
+    
+    let compare = |builder: &mut FunctionBuilder, signed: IntCC, unsigned_cc: IntCC| {
+        builder.ins().icmp(if unsigned { unsigned_cc } else { signed }, left_val, right_val)
+    };
+    let result = match op {
+        BinaryOp::Add => builder.ins().iadd(left_val, right_val),
+        BinaryOp::Subtract => builder.ins().isub(left_val, right_val),
+        BinaryOp::Multiply => builder.ins().imul(left_val, right_val),
+        BinaryOp::Divide if unsigned => builder.ins().udiv(left_val, right_val),
+        BinaryOp::Divide => builder.ins().sdiv(left_val, right_val),
+        BinaryOp::Modulo if unsigned => builder.ins().urem(left_val, right_val),
+        BinaryOp::Modulo => builder.ins().srem(left_val, right_val),
+        BinaryOp::BitwiseAnd => builder.ins().band(left_val, right_val),
+        BinaryOp::BitwiseOr => builder.ins().bor(left_val, right_val),
+        BinaryOp::BitwiseXor => builder.ins().bxor(left_val, right_val),
+        BinaryOp::LeftShift => builder.ins().ishl(left_val, right_val),
+        BinaryOp::RightShift if unsigned => builder.ins().ushr(left_val, right_val),
+        BinaryOp::RightShift => builder.ins().sshr(left_val, right_val),
+        BinaryOp::Equal => builder.ins().icmp(IntCC::Equal, left_val, right_val),
+        BinaryOp::NotEqual => builder.ins().icmp(IntCC::NotEqual, left_val, right_val),
+        BinaryOp::Less => compare(builder, IntCC::SignedLessThan, IntCC::UnsignedLessThan),
+        BinaryOp::LessEqual => compare(builder, IntCC::SignedLessThanOrEqual, IntCC::UnsignedLessThanOrEqual),
+        BinaryOp::Greater => compare(builder, IntCC::SignedGreaterThan, IntCC::UnsignedGreaterThan),
+        BinaryOp::GreaterEqual => compare(builder, IntCC::SignedGreaterThanOrEqual, IntCC::UnsignedGreaterThanOrEqual),
+        _ => return Err(CodegenError::UnsupportedFeature(
+            format!("Binary operator not supported: {:?}", op)
         )),
+    };
+    
+    // Comparisons produce bools, which are unsigned like the operands of unsigned arithmetic
+    let is_comparison = matches!(op, BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less
+        | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual);
+    if unsigned || is_comparison {
+        var_context.mark_unsigned(result);
+    }
+    Ok(result)
+}
+
+/// Compile a unary expression, following the operand's type
+fn compile_unary_with_variables(
+    builder: &mut FunctionBuilder,
+    op: crate::ast::UnaryOp,
+    operand: &Expr,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    use crate::ast::UnaryOp;
+    
+    // References take the operand's address instead of its value
+    if matches!(op, UnaryOp::AddressOf | UnaryOp::MutableRef) {
+        return compile_address_of_with_variables(builder, operand, var_context, interner);
+    }
+    
+    let operand_val = compile_value_with_variables(builder, operand, var_context, interner)?;
+    let operand_type = builder.func.dfg.value_type(operand_val);
+    
+    match op {
+        UnaryOp::Negate if operand_type.is_float() => Ok(builder.ins().fneg(operand_val)),
+        UnaryOp::Negate => Ok(builder.ins().ineg(operand_val)),
+        UnaryOp::Plus => Ok(operand_val),
+        UnaryOp::BitwiseNot if operand_type.is_int() => {
+            let result = builder.ins().bnot(operand_val);
+            if var_context.is_unsigned(operand_val) {
+                var_context.mark_unsigned(result);
+            }
+            Ok(result)
+        }
+        UnaryOp::BitwiseNot => Err(CodegenError::TypeConversion(format!(
+            "bitwise not requires an integer operand, found {}",
+            operand_type
+        )).at(*span)),
+        UnaryOp::Not => {
+            // Logical not: operand == 0
+            let condition = expressions::normalize_condition(builder, operand_val)?;
+            let result = builder.ins().icmp_imm(cranelift::prelude::IntCC::Equal, condition, 0);
+            var_context.mark_unsigned(result);
+            Ok(result)
+        }
+        // Structs are already handled through pointers to their storage
+        UnaryOp::Dereference if var_context.struct_values.contains_key(&operand_val) => Ok(operand_val),
+        UnaryOp::Dereference => {
+            let (pointee_type, unsigned) = var_context.pointee_types.get(&operand_val).copied()
+                .ok_or_else(|| CodegenError::TypeConversion("cannot dereference a value that is not a known reference".to_string()).at(*span))?;
+            let value = Address::new(operand_val, 0).load(builder, pointee_type);
+            if unsigned {
+                var_context.mark_unsigned(value);
+            }
+            Ok(value)
+        }
+        UnaryOp::AddressOf | UnaryOp::MutableRef => unreachable!("handled above"),
+    }
+}
+
+/// Compile `expr as Type`
+///
+/// Integers are sign- or zero-extended (by the source's signedness) or truncated,
+/// integers and floats convert through `fcvt_*` (float to integer saturates), and
+/// floats are promoted or demoted. Enums without payloads cast as their discriminant.
+/// Anything else, e.g. a pointer to a float, is a type conversion error.
+fn compile_cast_with_variables(
+    builder: &mut FunctionBuilder,
+    operand: &Expr,
+    target_type: &AstType,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    use crate::ast::PrimitiveType;
+    
+    let value = compile_value_with_variables(builder, operand, var_context, interner)?;
+    let found = builder.func.dfg.value_type(value);
+    let unsigned = var_context.is_unsigned(value);
+    let pointer_type = var_context.memory_manager.pointer_type();
+    
+    // References and structs are pointers at this level, but never numbers. Enums without
+    // payloads are their discriminant, so they cast like integers
+    let fieldless_enum = |name: &String| var_context.enum_layout(name).is_some_and(|layout| !layout.has_payload());
+    let source_name = match var_context.struct_values.get(&value) {
+        Some(struct_name) if fieldless_enum(struct_name) => None,
+        Some(struct_name) => Some(struct_name.clone()),
+        None if var_context.pointee_types.contains_key(&value) => Some("pointer".to_string()),
+        None => None,
+    };
+    let numeric_target = matches!(
+        target_type,
+        AstType::Primitive { kind, .. } if !matches!(
+            kind,
+            PrimitiveType::I128 | PrimitiveType::U128 | PrimitiveType::Str | PrimitiveType::Unit
+        )
+    );
+    let unsupported = |source: String| CodegenError::TypeConversion(format!(
+        "cannot cast {} to {}",
+        source,
+        ast_type_name(target_type, pointer_type).unwrap_or_else(|_| format!("{:?}", target_type))
+    )).at(*span);
+    
+    if let Some(source) = source_name {
+        return Err(unsupported(source));
+    }
+    if !numeric_target {
+        return Err(unsupported(found.to_string()));
+    }
+    
+    let target = ast_type_to_cranelift_type(target_type, pointer_type)?;
+    let target_unsigned = is_unsigned_ast_type(target_type);
+    let result = if found.is_int() && target.is_float() {
+        if unsigned {
+            builder.ins().fcvt_from_uint(target, value)
+        } else {
+            builder.ins().fcvt_from_sint(target, value)
+        }
+    } else if found.is_float() && target.is_int() {
+        if target_unsigned {
+            builder.ins().fcvt_to_uint_sat(target, value)
+        } else {
+            builder.ins().fcvt_to_sint_sat(target, value)
+        }
+    } else {
+        convert_value(builder, value, target, unsigned)
+            .ok_or_else(|| unsupported(found.to_string()))?
+    };
+    
+    if target_unsigned {
+        var_context.mark_unsigned(result);
+    }
+    Ok(result)
+}
+
+/// Compile `&expr` / `&mut expr`
+///
+/// Variables and array elements yield their own address; any other expression is first
+/// spilled into a fresh stack slot, like a Rust temporary. Struct values already live
+/// behind a pointer, which is what references to them share.
+fn compile_address_of_with_variables(
+    builder: &mut FunctionBuilder,
+    operand: &Expr,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let (address, pointee) = match operand {
+        Expr::Identifier { name, .. } if var_context.struct_vars.contains_key(&name.id) => {
+            return compile_value_with_variables(builder, operand, var_context, interner);
+        }
+        Expr::Identifier { name, .. } if var_context.get_variable(name.id).is_some() => {
+            let var_info = &var_context.variables[&name.id];
+            let pointee = (var_info.cranelift_type, var_info.unsigned);
+            (builder.ins().stack_addr(pointer_type, var_info.stack_slot, 0), pointee)
+        }
+        Expr::Index { object, index, span: index_span, .. } => match object.as_ref() {
+            Expr::Identifier { name, .. } => {
+                let index_val = compile_value_with_variables(builder, index, var_context, interner)?;
+                let address = array_element_address(builder, name, index, index_val, index_span, var_context, interner)?;
+                (address.pointer(builder), var_context.array_element(name.id))
+            }
+            _ => return Err(CodegenError::UnsupportedFeature(
+                "Only elements of array variables can be referenced".to_string()
+            )),
+        },
+        Expr::Parenthesized { expr, .. } => {
+            return compile_address_of_with_variables(builder, expr, var_context, interner);
+        }
+        _ => {
+            let value = compile_value_with_variables(builder, operand, var_context, interner)?;
+            let value_type = builder.func.dfg.value_type(value);
+            let slot = builder.create_sized_stack_slot(cranelift_codegen::ir::StackSlotData::new(
+                cranelift_codegen::ir::StackSlotKind::ExplicitSlot,
+                utils::type_size(value_type, pointer_type) as u32,
+            ));
+            builder.ins().stack_store(value, slot, 0);
+            (builder.ins().stack_addr(pointer_type, slot, 0), (value_type, var_context.is_unsigned(value)))
+        }
+    };
+    var_context.pointee_types.insert(address, pointee);
+    Ok(address)
+}
+
+/// Pointee of a reference or pointer type, as the Cranelift type loaded through it
+fn pointee_of_ast_type(ast_type: &AstType, pointer_type: Type) -> CodegenResult<Option<(Type, bool)>> {
+    match ast_type {
+        AstType::Reference { target_type, .. } | AstType::Pointer { target_type, .. } => Ok(Some((
+            ast_type_to_cranelift_type(target_type, pointer_type)?,
+            is_unsigned_ast_type(target_type),
+        ))),
+        _ => Ok(None),
+    }
+}
+
+/// Element type and signedness of an array type, `None` for other types
+fn array_element_of_ast_type(ast_type: &AstType, pointer_type: Type) -> CodegenResult<Option<(Type, bool)>> {
+    match ast_type {
+        AstType::Array { element_type, .. } => Ok(Some((
+            ast_type_to_cranelift_type(element_type, pointer_type)?,
+            is_unsigned_ast_type(element_type),
+        ))),
+        _ => Ok(None),
     }
 }
 
-/// Compile an assignment statement (variable assignment)
-fn compile_assignment_statement(
-    builder: &mut FunctionBuilder,
-    target: &Expr,
-    value: &Expr,
-    var_context: &mut VariableContext,
-    interner: &StringInterner,
-) -> CodegenResult<()> {
-    match target {
-        Expr::Identifier { name, .. } => {
-            // Get variable info first
-            let stack_slot = if let Some(var_info) = var_context.get_variable(name.id) {
-                var_info.stack_slot
-            } else {
-                let var_name = interner.get(name)
-                    .map(|s| s.to_string())
-                    .unwrap_or_else(|| format!("var_{}", name.id));
-                return Err(CodegenError::SymbolResolution(
-                    format!("Assignment target '{}' is not a declared variable", var_name)
-                ));
-            };
-            
-            // Compile value and store
-            let value_to_store = compile_expression_with_variables(builder, value, var_context, interner)?;
-            builder.ins().stack_store(value_to_store, stack_slot, 0);
-            Ok(())
-        }
-        _ => Err(CodegenError::UnsupportedFeature(
-            "Only identifier targets supported for assignments".to_string()
-        )),
+/// Whether `expr` is an untyped integer literal (possibly negated), which adapts to its context
+///
+/// A suffixed literal such as `255u8` has its own type and does not adapt.
+fn is_integer_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { literal: crate::ast::Literal::Integer { suffix: None, .. }, .. } => true,
+        Expr::Unary { op: crate::ast::UnaryOp::Negate, expr, .. } => is_integer_literal(expr),
+        _ => false,
     }
 }
 
-/// Compile an if expression with variable context
-fn compile_if_expression_with_variables(
+/// Compile a short-circuiting `&&` / `||`, producing a `bool` (I8)
+fn compile_logical_with_variables(
     builder: &mut FunctionBuilder,
-    condition: &Expr,
-    then_block: &Expr,
-    else_block: &Option<Box<Expr>>,
+    left: &Expr,
+    op: crate::ast::BinaryOp,
+    right: &Expr,
     var_context: &mut VariableContext,
     interner: &StringInterner,
 ) -> CodegenResult<Value> {
-    // Compile the condition
-    let condition_val = compile_expression_with_variables(builder, condition, var_context, interner)?;
+    let left_val = compile_value_with_variables(builder, left, var_context, interner)?;
+    let left_bool = expressions::normalize_condition(builder, left_val)?;
     
-    // Create blocks for then, else, and merge
-    let then_bb = builder.create_block();
-    let else_bb = builder.create_block();
+    let right_bb = builder.create_block();
     let merge_bb = builder.create_block();
+    let result = builder.append_block_param(merge_bb, ctypes::I8);
     
-    // Add a parameter to the merge block for the result value
-    let merge_param = builder.append_block_param(merge_bb, ctypes::I32);
-    
-    // Branch based on condition (non-zero means true)
-    let zero = builder.ins().iconst(ctypes::I32, 0);
-    let is_true = builder.ins().icmp(cranelift::prelude::IntCC::NotEqual, condition_val, zero);
-    builder.ins().brif(is_true, then_bb, &[], else_bb, &[]);
-    
-    // Compile then block
-    builder.switch_to_block(then_bb);
-    let then_val = compile_expression_with_variables(builder, then_block, var_context, interner)?;
-    builder.ins().jump(merge_bb, &[then_val]);
-    
-    // Compile else block
-    builder.switch_to_block(else_bb);
-    let else_val = if let Some(else_expr) = else_block {
-        compile_expression_with_variables(builder, else_expr, var_context, interner)?
+    // `a && b` is false without evaluating b when a is false; `a || b` is true when a is true
+    if op == crate::ast::BinaryOp::LogicalAnd {
+        builder.ins().brif(left_bool, right_bb, &[], merge_bb, &[left_bool]);
     } else {
-        // No else block, return unit (0)
-        builder.ins().iconst(ctypes::I32, 0)
-    };
-    builder.ins().jump(merge_bb, &[else_val]);
+        builder.ins().brif(left_bool, merge_bb, &[left_bool], right_bb, &[]);
+    }
+    
+    builder.switch_to_block(right_bb);
+    builder.seal_block(right_bb);
+    let right_val = compile_value_with_variables(builder, right, var_context, interner)?;
+    let right_bool = expressions::normalize_condition(builder, right_val)?;
+    builder.ins().jump(merge_bb, &[right_bool]);
     
-    // Switch to merge block and seal all blocks
     builder.switch_to_block(merge_bb);
-    builder.seal_block(then_bb);
-    builder.seal_block(else_bb);
     builder.seal_block(merge_bb);
-    
-    // Return the merged value
-    Ok(merge_param)
+    var_context.mark_unsigned(result);
+    Ok(result)
 }
 
 /// Compile an if statement with variable context
+///
+/// Returns true if both branches terminate, in which case no merge block is reachable.
 fn compile_if_statement_with_variables(
     builder: &mut FunctionBuilder,
     condition: &Expr,
@@ -763,37 +3238,23 @@ fn compile_if_statement_with_variables(
     else_block: &Option<Box<Stmt>>,
     var_context: &mut VariableContext,
     interner: &StringInterner,
-) -> CodegenResult<()> {
+) -> CodegenResult<bool> {
     // Compile the condition
-    let condition_val = compile_expression_with_variables(builder, condition, var_context, interner)?;
+    let condition_val = compile_value_with_variables(builder, condition, var_context, interner)?;
     
     // Create blocks for then, else, and merge
     let then_bb = builder.create_block();
     let else_bb = builder.create_block();
     let merge_bb = builder.create_block();
     
-    // Branch based on condition (non-zero means true) - FIX TYPE MISMATCH
-    // We need to determine the type of the condition value to create a matching zero
-    // For now, let's handle the common case where booleans are i8
-    let condition_type = builder.func.dfg.value_type(condition_val);
-    let zero = if condition_type == ctypes::I8 {
-        builder.ins().iconst(ctypes::I8, 0)
-    } else {
-        builder.ins().iconst(ctypes::I32, 0)
-    };
-    let is_true = builder.ins().icmp(cranelift::prelude::IntCC::NotEqual, condition_val, zero);
+    // Branch based on condition (non-zero means true)
+    let is_true = expressions::normalize_condition(builder, condition_val)?;
     builder.ins().brif(is_true, then_bb, &[], else_bb, &[]);
     
-    // Compile then block
+    // Compile then block - its only predecessor is the branch above
     builder.switch_to_block(then_bb);
-    let mut then_terminated = false;
-    for stmt in then_block {
-        let stmt_terminated = compile_statement_with_variables_and_termination(builder, stmt, var_context, interner)?;
-        if stmt_terminated {
-            then_terminated = true;
-            break; // Don't process more statements after termination
-        }
-    }
+    builder.seal_block(then_bb);
+    let then_terminated = compile_scoped_statements(builder, then_block, var_context, interner)?;
     // Only jump to merge if the block didn't terminate
     if !then_terminated {
         builder.ins().jump(merge_bb, &[]);
@@ -801,28 +3262,33 @@ fn compile_if_statement_with_variables(
     
     // Compile else block
     builder.switch_to_block(else_bb);
-    let mut else_terminated = false;
-    if let Some(else_stmt) = else_block {
-        let stmt_terminated = compile_statement_with_variables_and_termination(builder, else_stmt, var_context, interner)?;
-        if stmt_terminated {
-            else_terminated = true;
-        }
-    }
+    builder.seal_block(else_bb);
+    let else_terminated = match else_block {
+        Some(else_stmt) => compile_statement_with_variables_and_termination(builder, else_stmt, var_context, interner)?,
+        None => false,
+    };
     // Only jump to merge if the block didn't terminate
     if !else_terminated {
         builder.ins().jump(merge_bb, &[]);
     }
     
-    // Switch to merge block and seal all blocks
+    // All jumps into the merge block are emitted - it can be sealed
     builder.switch_to_block(merge_bb);
-    builder.seal_block(then_bb);
-    builder.seal_block(else_bb);
     builder.seal_block(merge_bb);
     
-    Ok(())
+    let terminated = then_terminated && else_terminated;
+    if terminated {
+        // No predecessors: the merge block is unreachable but still needs a terminator
+        builder.ins().trap(TrapCode::UnreachableCodeReached);
+    }
+    
+    Ok(terminated)
 }
 
 /// Compile a while statement with variable context
+///
+/// The header block is sealed only once the body has emitted its back-edges,
+/// and the exit block only once every `break` out of the body is known.
 fn compile_while_statement_with_variables(
     builder: &mut FunctionBuilder,
     condition: &Expr,
@@ -839,101 +3305,846 @@ fn compile_while_statement_with_variables(
 
     // Compile the condition
     builder.switch_to_block(loop_bb);
-    let condition_val = compile_expression_with_variables(builder, condition, var_context, interner)?;
+    let condition_val = compile_value_with_variables(builder, condition, var_context, interner)?;
     
-    // Type-safe zero comparison
-    let condition_type = builder.func.dfg.value_type(condition_val);
-    let zero = if condition_type == ctypes::I8 {
-        builder.ins().iconst(ctypes::I8, 0)
-    } else {
-        builder.ins().iconst(ctypes::I32, 0)
-    };
-    let is_true = builder.ins().icmp(cranelift::prelude::IntCC::NotEqual, condition_val, zero);
+    let is_true = expressions::normalize_condition(builder, condition_val)?;
     builder.ins().brif(is_true, body_bb, &[], merge_bb, &[]);
 
-    // Compile the body
+    // Compile the body - its only predecessor is the condition check
     builder.switch_to_block(body_bb);
-    let mut body_terminated = false;
-    for stmt in body {
-        let stmt_terminated = compile_statement_with_variables_and_termination(builder, stmt, var_context, interner)?;
-        if stmt_terminated {
-            body_terminated = true;
-            break;
+    builder.seal_block(body_bb);
+    var_context.loop_stack.push(LoopTarget {
+        label: None,
+        header: loop_bb,
+        exit: merge_bb,
+        region_depth: var_context.region_stack.len(),
+        scope_depth: var_context.scopes.len(),
+        first_alloc_id: var_context.memory_manager.next_alloc_id(),
+        valued: false,
+        breaks: LoopBreaks::Never,
+    });
+    let body_result = compile_scoped_statements(builder, body, var_context, interner);
+    let target = var_context.loop_stack.pop().expect("loop target pushed above");
+    
+    if !body_result? {
+        var_context.memory_manager.release_unscoped_smart_pointers(builder, target.first_alloc_id)?;
+        builder.ins().jump(loop_bb, &[]); // Continue loop
+    }
+
+    // Every back-edge and break is emitted now
+    builder.seal_block(loop_bb);
+    builder.switch_to_block(merge_bb);
+    builder.seal_block(merge_bb);
+
+    Ok(())
+}
+
+/// Compile a for statement with variable context - STUB IMPLEMENTATION
+fn compile_for_statement_with_variables(
+    _builder: &mut FunctionBuilder,
+    _pattern: &Pattern,
+    _iterable: &Expr,
+    _body: &[Stmt],
+    _var_context: &mut VariableContext,
+    _interner: &StringInterner,
+) -> CodegenResult<()> {
+    // TODO: Implement full for loop support
+    // For now, just return an error indicating it's not implemented
+    Err(CodegenError::UnsupportedFeature(
+        "For loops not yet fully implemented - use while loops instead".to_string()
+    ))
+}
+
+/// Compile a match expression with variable context
+///
+/// The arms' values are merged like the branches of an if expression.
+fn compile_match_expression_with_variables(
+    builder: &mut FunctionBuilder,
+    expr: &Expr,
+    arms: &[crate::ast::MatchArm],
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<Value>> {
+    let scrutinee = compile_value_with_variables(builder, expr, var_context, interner)?;
+    let tails = compile_match_arms(builder, scrutinee, arms, true, var_context, interner)?;
+    
+    let merge_bb = builder.create_block();
+    // A unit arm makes the whole match unit, like a unit branch of an if
+    if tails.iter().any(|(_, tail_type)| tail_type.is_none()) {
+        discard_branch_values(builder, tails, merge_bb);
+        builder.switch_to_block(merge_bb);
+        builder.seal_block(merge_bb);
+        return Ok(None);
+    }
+    let tails: Vec<(Block, Type)> = tails.into_iter().filter_map(|tail| valued_tail(Some(tail))).collect();
+    
+    let mut result_type: Option<Type> = None;
+    for &(_, tail_type) in &tails {
+        let unified = match result_type {
+            Some(result_type) => unify_branch_types(result_type, tail_type).ok_or_else(|| CodegenError::TypeConversion(format!(
+                "match arms have incompatible types: {} and {}",
+                result_type, tail_type
+            )).at(*span))?,
+            None => tail_type,
+        };
+        result_type = Some(unified);
+    }
+    // Every arm left the function - the merge block is unreachable
+    let result_type = result_type.unwrap_or(ctypes::I32);
+    
+    let merge_param = builder.append_block_param(merge_bb, result_type);
+    let mut all_unsigned = true;
+    let mut type_names = HashSet::new();
+    for (tail_bb, tail_type) in tails {
+        builder.switch_to_block(tail_bb);
+        builder.seal_block(tail_bb);
+        let tail_value = builder.block_params(tail_bb)[0];
+        let unsigned = var_context.is_unsigned(tail_value);
+        all_unsigned &= unsigned;
+        type_names.insert(var_context.struct_values.get(&tail_value).cloned());
+        let converted = convert_value(builder, tail_value, result_type, unsigned)
+            .ok_or_else(|| CodegenError::InternalError(format!("Cannot convert match arm from {} to {}", tail_type, result_type)))?;
+        builder.ins().jump(merge_bb, &[converted]);
+    }
+    
+    builder.switch_to_block(merge_bb);
+    builder.seal_block(merge_bb);
+    if all_unsigned {
+        var_context.mark_unsigned(merge_param);
+    }
+    // Arms that all evaluate to the same struct or enum type give the match that type
+    if let (1, Some(Some(type_name))) = (type_names.len(), type_names.into_iter().next()) {
+        var_context.struct_values.insert(merge_param, type_name);
+    }
+    Ok(Some(merge_param))
+}
+
+/// Compile a match statement, whose arm values are discarded
+///
+/// Returns true if every arm terminated.
+fn compile_match_statement_with_variables(
+    builder: &mut FunctionBuilder,
+    expr: &Expr,
+    arms: &[crate::ast::MatchArm],
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<bool> {
+    let scrutinee = compile_value_with_variables(builder, expr, var_context, interner)?;
+    let tails = compile_match_arms(builder, scrutinee, arms, false, var_context, interner)?;
+    let terminated = tails.is_empty();
+    
+    let merge_bb = builder.create_block();
+    discard_branch_values(builder, tails, merge_bb);
+    builder.switch_to_block(merge_bb);
+    builder.seal_block(merge_bb);
+    if terminated {
+        // No predecessors: the merge block is unreachable but still needs a terminator
+        builder.ins().trap(TrapCode::UnreachableCodeReached);
+    }
+    Ok(terminated)
+}
+
+/// Compile the arms of a match on `scrutinee`, testing them in order
+///
+/// Each arm's pattern and guard are tested in a chain of blocks, falling through to the
+/// next arm on failure; a value no arm matches traps. The names an arm binds are only
+/// visible in its guard and body. Returns the tail block of each arm that did not
+/// terminate with the type of its value, like `compile_if_branch`; when `with_values`
+/// is false the tails take no value.
+fn compile_match_arms(
+    builder: &mut FunctionBuilder,
+    scrutinee: Value,
+    arms: &[crate::ast::MatchArm],
+    with_values: bool,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Vec<BranchTail>> {
+    let type_name = var_context.struct_values.get(&scrutinee).cloned();
+    let mut tails = Vec::with_capacity(arms.len());
+    
+    for arm in arms {
+        let next_bb = builder.create_block();
+        let mut bindings = Vec::new();
+        test_pattern(builder, &arm.pattern, scrutinee, type_name.as_deref(), next_bb, &mut bindings, var_context, interner)?;
+        
+        var_context.push_scope();
+        for binding in bindings {
+            var_context.bind_value(builder, binding.name_id, binding.value, binding.type_name)?;
+        }
+        if let Some(guard) = &arm.guard {
+            let guard_value = compile_value_with_variables(builder, guard, var_context, interner)?;
+            let holds = expressions::normalize_condition(builder, guard_value)?;
+            continue_if(builder, holds, next_bb);
+        }
+        
+        let body_bb = builder.create_block();
+        builder.ins().jump(body_bb, &[]);
+        if with_values {
+            tails.extend(compile_if_branch(builder, body_bb, &arm.body, var_context, interner)?);
+        } else {
+            builder.switch_to_block(body_bb);
+            builder.seal_block(body_bb);
+            let (_, terminated) = compile_expression_with_variables_and_termination(builder, &arm.body, var_context, interner)?;
+            if !terminated {
+                let tail_bb = builder.create_block();
+                builder.ins().jump(tail_bb, &[]);
+                tails.push((tail_bb, None));
+            }
+        }
+        // The arm has branched to its tail; its pattern bindings own no smart pointers
+        var_context.pop_scope(builder, true)?;
+        
+        // Every test of this arm has branched to the next one by now
+        builder.switch_to_block(next_bb);
+        builder.seal_block(next_bb);
+    }
+    
+    // No arm matched
+    builder.ins().trap(TrapCode::UnreachableCodeReached);
+    Ok(tails)
+}
+
+/// Value a match pattern binds to a name
+struct PatternBinding {
+    name_id: u32,
+    value: Value,
+    /// Struct or enum type of the value
+    type_name: Option<String>,
+}
+
+/// Emit the tests of `pattern` against `value`, of the struct or enum `type_name` if known
+///
+/// Each failing test jumps to `fail_bb`; code emitted after this runs only when the
+/// pattern matched. The values of the names the pattern binds are collected in `bindings`.
+#[allow(clippy::too_many_arguments)]
+fn test_pattern(
+    builder: &mut FunctionBuilder,
+    pattern: &Pattern,
+    value: Value,
+    type_name: Option<&str>,
+    fail_bb: Block,
+    bindings: &mut Vec<PatternBinding>,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    match pattern {
+        Pattern::Wildcard { .. } => Ok(()),
+        Pattern::Identifier { name, .. } => {
+            // A bare unit variant of the matched enum is a variant test, not a binding
+            let unit_variant = var_context.resolve_variant(std::slice::from_ref(name), type_name, interner)
+                .filter(|(enum_name, _, variant)| variant.fields.is_empty() && type_name.is_none_or(|type_name| type_name == enum_name));
+            match unit_variant {
+                Some((_, layout, variant)) => test_variant_tag(builder, value, &layout, &variant, fail_bb, var_context),
+                None => bindings.push(PatternBinding {
+                    name_id: name.id,
+                    value,
+                    type_name: type_name.map(str::to_string),
+                }),
+            }
+            Ok(())
+        }
+        Pattern::Literal { literal, span, .. } => {
+            let expected = compile_pattern_literal(builder, literal, value, span)?;
+            let equal = match builder.func.dfg.value_type(value).is_float() {
+                true => builder.ins().fcmp(cranelift::prelude::FloatCC::Equal, value, expected),
+                false => builder.ins().icmp(cranelift::prelude::IntCC::Equal, value, expected),
+            };
+            continue_if(builder, equal, fail_bb);
+            Ok(())
+        }
+        Pattern::Range { start, end, inclusive, span, .. } => {
+            use cranelift::prelude::IntCC;
+            if !builder.func.dfg.value_type(value).is_int() {
+                return Err(CodegenError::UnsupportedFeature("range patterns only match integers".to_string()).at(*span));
+            }
+            let unsigned = var_context.is_unsigned(value);
+            let (at_least, below, at_most) = match unsigned {
+                true => (IntCC::UnsignedGreaterThanOrEqual, IntCC::UnsignedLessThan, IntCC::UnsignedLessThanOrEqual),
+                false => (IntCC::SignedGreaterThanOrEqual, IntCC::SignedLessThan, IntCC::SignedLessThanOrEqual),
+            };
+            let bounds = [(start, at_least), (end, if *inclusive { at_most } else { below })];
+            for (bound, condition) in bounds {
+                let Some(bound) = bound else { continue };
+                let Pattern::Literal { literal, span, .. } = bound.as_ref() else {
+                    return Err(CodegenError::UnsupportedFeature("range pattern bounds must be literals".to_string()).at(bound.span()));
+                };
+                let bound = compile_pattern_literal(builder, literal, value, span)?;
+                let holds = builder.ins().icmp(condition, value, bound);
+                continue_if(builder, holds, fail_bb);
+            }
+            Ok(())
+        }
+        Pattern::Tuple { patterns, span, .. } => {
+            let layout = type_name.and_then(|name| var_context.struct_layout(name)).cloned()
+                .ok_or_else(|| CodegenError::TypeConversion("cannot match a tuple pattern against a value that is not a tuple".to_string()).at(*span))?;
+            if layout.fields.len() != patterns.len() {
+                return Err(CodegenError::TypeConversion(format!(
+                    "tuple pattern has {} element{} but the value has type {}",
+                    patterns.len(),
+                    if patterns.len() == 1 { "" } else { "s" },
+                    type_name.unwrap_or_default()
+                )).at(*span));
+            }
+            let fields: Vec<(&Pattern, &FieldLayout)> = patterns.iter().zip(&layout.fields).collect();
+            test_field_patterns(builder, &fields, value, fail_bb, bindings, var_context, interner)
+        }
+        Pattern::Enum { path, patterns, span, .. } => {
+            let (enum_name, layout, variant) = resolve_pattern_variant(path, type_name, span, var_context, interner)?;
+            test_variant_tag(builder, value, &layout, &variant, fail_bb, var_context);
+            let patterns = patterns.as_deref().unwrap_or_default();
+            if patterns.len() != variant.fields.len() {
+                return Err(CodegenError::TypeConversion(format!(
+                    "variant '{}::{}' has {} field{} but the pattern has {}",
+                    enum_name,
+                    variant.name,
+                    variant.fields.len(),
+                    if variant.fields.len() == 1 { "" } else { "s" },
+                    patterns.len()
+                )).at(*span));
+            }
+            let fields: Vec<(&Pattern, &FieldLayout)> = patterns.iter().zip(&variant.fields).collect();
+            test_field_patterns(builder, &fields, value, fail_bb, bindings, var_context, interner)
+        }
+        Pattern::Struct { path, fields, rest, span, .. } => {
+            let struct_name = path.last().and_then(|name| interner.resolve(*name));
+            let (type_label, field_layouts) = match struct_name.as_ref().and_then(|name| var_context.struct_layout(name)) {
+                Some(layout) => (struct_name.clone().unwrap_or_default(), layout.fields.clone()),
+                None => {
+                    let (enum_name, layout, variant) = resolve_pattern_variant(path, type_name, span, var_context, interner)?;
+                    test_variant_tag(builder, value, &layout, &variant, fail_bb, var_context);
+                    (format!("{}::{}", enum_name, variant.name), variant.fields)
+                }
+            };
+            
+            let shorthands: Vec<Pattern> = fields.iter()
+                .map(|field| Pattern::Identifier { name: field.name, is_mutable: false, span: field.span, id: NodeId::DUMMY })
+                .collect();
+            let mut field_patterns = Vec::with_capacity(fields.len());
+            for (field, shorthand) in fields.iter().zip(&shorthands) {
+                let field_name = interner.resolve(field.name).unwrap_or_else(|| "<unknown>".to_string());
+                let field_layout = field_layouts.iter().find(|layout| layout.name == field_name)
+                    .ok_or_else(|| CodegenError::TypeConversion(format!(
+                        "'{}' has no field '{}'",
+                        type_label, field_name
+                    )).at(field.span))?;
+                // Shorthand `Circle { radius }` binds the field to its own name
+                field_patterns.push((field.pattern.as_ref().unwrap_or(shorthand), field_layout));
+            }
+            if !rest {
+                let missing: Vec<&str> = field_layouts.iter()
+                    .filter(|layout| !fields.iter().any(|field| interner.matches(field.name, &layout.name)))
+                    .map(|layout| layout.name.as_str())
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(CodegenError::TypeConversion(format!(
+                        "pattern of '{}' does not mention field{} {}; add `..` to ignore the rest",
+                        type_label,
+                        if missing.len() == 1 { "" } else { "s" },
+                        missing.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", ")
+                    )).at(*span));
+                }
+            }
+            test_field_patterns(builder, &field_patterns, value, fail_bb, bindings, var_context, interner)
+        }
+        Pattern::Or { patterns, span, .. } => {
+            // Each alternative that fails falls through to the next; the last one to `fail_bb`
+            let matched_bb = builder.create_block();
+            for (i, alternative) in patterns.iter().enumerate() {
+                let alternative_fail_bb = match i + 1 == patterns.len() {
+                    true => fail_bb,
+                    false => builder.create_block(),
+                };
+                let mut alternative_bindings = Vec::new();
+                test_pattern(builder, alternative, value, type_name, alternative_fail_bb, &mut alternative_bindings, var_context, interner)?;
+                if !alternative_bindings.is_empty() {
+                    return Err(CodegenError::UnsupportedFeature("or-patterns that bind names are not supported yet".to_string()).at(*span));
+                }
+                builder.ins().jump(matched_bb, &[]);
+                if alternative_fail_bb != fail_bb {
+                    builder.switch_to_block(alternative_fail_bb);
+                    builder.seal_block(alternative_fail_bb);
+                }
+            }
+            builder.switch_to_block(matched_bb);
+            builder.seal_block(matched_bb);
+            Ok(())
+        }
+        Pattern::Array { span, .. } | Pattern::Reference { span, .. } => Err(CodegenError::UnsupportedFeature("array and reference patterns are not supported in match yet".to_string()).at(*span)),
+    }
+}
+
+/// Test the sub-patterns of a tuple, struct or variant pattern against the fields of the
+/// value at `pointer`
+#[allow(clippy::too_many_arguments)]
+fn test_field_patterns(
+    builder: &mut FunctionBuilder,
+    fields: &[(&Pattern, &FieldLayout)],
+    pointer: Value,
+    fail_bb: Block,
+    bindings: &mut Vec<PatternBinding>,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    for &(pattern, field) in fields {
+        if matches!(pattern, Pattern::Wildcard { .. }) {
+            continue;
+        }
+        let value = Address::field(pointer, field.offset).load(builder, field.cranelift_type);
+        if field.unsigned {
+            var_context.mark_unsigned(value);
+        }
+        let type_name = field.struct_name.clone().filter(|name| var_context.has_layout(name));
+        if let Some(type_name) = &type_name {
+            var_context.struct_values.insert(value, type_name.clone());
+        }
+        test_pattern(builder, pattern, value, type_name.as_deref(), fail_bb, bindings, var_context, interner)?;
+    }
+    Ok(())
+}
+
+/// Variant a variant pattern names, reporting patterns that name none
+fn resolve_pattern_variant(
+    path: &[crate::ast::InternedString],
+    type_name: Option<&str>,
+    span: &Span,
+    var_context: &VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<(String, EnumLayout, VariantLayout)> {
+    var_context.resolve_variant(path, type_name, interner).ok_or_else(|| {
+        let path: Vec<String> = path.iter()
+            .map(|segment| interner.resolve(*segment).unwrap_or_else(|| "<unknown>".to_string()))
+            .collect();
+        CodegenError::SymbolResolution(format!(
+            "'{}' is not a variant of a known enum",
+            path.join("::")
+        )).at(*span)
+    })
+}
+
+/// Jump to `fail_bb` unless `variant` is the active variant of the enum `value`
+fn test_variant_tag(
+    builder: &mut FunctionBuilder,
+    value: Value,
+    layout: &EnumLayout,
+    variant: &VariantLayout,
+    fail_bb: Block,
+    var_context: &VariableContext,
+) {
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let tag = match layout.has_payload() {
+        true => Address::new(value, 0).load(builder, pointer_type),
+        false => convert_value(builder, value, pointer_type, true).unwrap_or(value),
+    };
+    let is_variant = builder.ins().icmp_imm(cranelift::prelude::IntCC::Equal, tag, variant.discriminant);
+    continue_if(builder, is_variant, fail_bb);
+}
+
+/// Constant of a literal pattern, converted to the type of the matched `value`
+fn compile_pattern_literal(
+    builder: &mut FunctionBuilder,
+    literal: &crate::ast::Literal,
+    value: Value,
+    span: &Span,
+) -> CodegenResult<Value> {
+    if matches!(literal, crate::ast::Literal::String { .. } | crate::ast::Literal::Null) {
+        return Err(CodegenError::UnsupportedFeature("only number, bool and char literals can be matched yet".to_string()).at(*span));
+    }
+    let constant = expressions::compile_literal(builder, literal)?;
+    let value_type = builder.func.dfg.value_type(value);
+    let found = builder.func.dfg.value_type(constant);
+    convert_value(builder, constant, value_type, false).ok_or_else(|| CodegenError::TypeConversion(format!(
+        "cannot match a {} value against a {} literal",
+        value_type, found
+    )).at(*span))
+}
+
+/// Continue in a fresh block when `condition` holds, jumping to `fail_bb` otherwise
+fn continue_if(builder: &mut FunctionBuilder, condition: Value, fail_bb: Block) {
+    let holds_bb = builder.create_block();
+    builder.ins().brif(condition, holds_bb, &[], fail_bb, &[]);
+    builder.switch_to_block(holds_bb);
+    builder.seal_block(holds_bb);
+}
+
+/// Fields given to an enum variant under construction
+enum VariantArgs<'a> {
+    /// `Enum::Variant`
+    Unit,
+    /// `Enum::Variant(a, b)`
+    Tuple(&'a [Expr]),
+    /// `Enum::Variant { field: value }`
+    Named(&'a [crate::ast::FieldInit]),
+}
+
+/// Compile the construction of an enum variant
+///
+/// Enums without payloads evaluate to the variant's discriminant. Other enums are
+/// allocated at their full size whichever the variant, with the requested strategy or
+/// one inferred from the escape facts at `span`; the tag is stored first, then the payload
+/// at its field offsets.
+#[allow(clippy::too_many_arguments)]
+fn compile_variant_construction(
+    builder: &mut FunctionBuilder,
+    (enum_name, layout, variant): (String, EnumLayout, VariantLayout),
+    args: VariantArgs,
+    strategy: Option<MemoryStrategy>,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    let variant_path = format!("{}::{}", enum_name, variant.name);
+    let is_tuple_variant = variant.fields.iter().enumerate().all(|(i, field)| field.name == i.to_string());
+    
+    // Compile the payload before allocating so nested variants are laid out first
+    let mut field_stores = Vec::with_capacity(variant.fields.len());
+    match args {
+        VariantArgs::Unit if variant.fields.is_empty() => {}
+        VariantArgs::Tuple(args) if is_tuple_variant && !variant.fields.is_empty() => {
+            if args.len() != variant.fields.len() {
+                return Err(CodegenError::TypeConversion(format!(
+                    "variant '{}' has {} field{} but {} {} given",
+                    variant_path,
+                    variant.fields.len(),
+                    if variant.fields.len() == 1 { "" } else { "s" },
+                    args.len(),
+                    if args.len() == 1 { "was" } else { "were" }
+                )).at(*span));
+            }
+            for (arg, field_layout) in args.iter().zip(&variant.fields) {
+                let value = compile_value_with_variables(builder, arg, var_context, interner)?;
+                field_stores.push(convert_variant_field(builder, value, field_layout, &variant_path, &arg.span(), var_context)?);
+            }
+        }
+        VariantArgs::Named(fields) if !is_tuple_variant => {
+            for field in fields {
+                let value = match &field.value {
+                    Some(value_expr) => compile_value_with_variables(builder, value_expr, var_context, interner)?,
+                    None => {
+                        // Shorthand initialization: `Circle { radius }` reads the variable `radius`
+                        let shorthand = Expr::Identifier { name: field.name, span: field.span, id: NodeId::DUMMY };
+                        compile_value_with_variables(builder, &shorthand, var_context, interner)?
+                    }
+                };
+                let field_name = interner.resolve(field.name).unwrap_or_else(|| "<unknown>".to_string());
+                let field_layout = variant.fields.iter().find(|layout| layout.name == field_name)
+                    .ok_or_else(|| CodegenError::TypeConversion(format!(
+                        "variant '{}' has no field '{}'",
+                        variant_path, field_name
+                    )).at(field.span))?;
+                field_stores.push(convert_variant_field(builder, value, field_layout, &variant_path, &field.span, var_context)?);
+            }
+            let missing: Vec<&str> = variant.fields.iter()
+                .filter(|layout| !fields.iter().any(|field| interner.matches(field.name, &layout.name)))
+                .map(|layout| layout.name.as_str())
+                .collect();
+            if !missing.is_empty() {
+                return Err(CodegenError::TypeConversion(format!(
+                    "missing field{} {} in initializer of '{}'",
+                    if missing.len() == 1 { "" } else { "s" },
+                    missing.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", "),
+                    variant_path
+                )).at(*span));
+            }
+        }
+        _ => {
+            let form = match (variant.fields.is_empty(), is_tuple_variant) {
+                (true, _) => format!("`{}`", variant_path),
+                (false, true) => format!("`{}(...)`", variant_path),
+                (false, false) => format!("`{} {{ ... }}`", variant_path),
+            };
+            return Err(CodegenError::TypeConversion(format!(
+                "variant '{}' is constructed as {}",
+                variant_path, form
+            )).at(*span));
+        }
+    }
+    
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let enum_value = match layout.has_payload() {
+        true => {
+            let strategy = strategy.unwrap_or_else(|| var_context.inferred_strategy(span, layout.size));
+            let enum_ptr = var_context.allocate(builder, strategy, pointer_type, layout.size, span)?;
+            let tag = builder.ins().iconst(pointer_type, variant.discriminant);
+            Address::new(enum_ptr, 0).store(builder, tag);
+            for (value, offset) in field_stores {
+                Address::field(enum_ptr, offset).store(builder, value);
+            }
+            enum_ptr
+        }
+        false => builder.ins().iconst(pointer_type, variant.discriminant),
+    };
+    var_context.struct_values.insert(enum_value, enum_name);
+    Ok(enum_value)
+}
+
+/// Convert a payload value to the type of its variant field, returning it with the field offset
+fn convert_variant_field(
+    builder: &mut FunctionBuilder,
+    value: Value,
+    field_layout: &FieldLayout,
+    variant_path: &str,
+    span: &Span,
+    var_context: &VariableContext,
+) -> CodegenResult<(Value, u32)> {
+    let found = builder.func.dfg.value_type(value);
+    let converted = convert_value(builder, value, field_layout.cranelift_type, var_context.is_unsigned(value))
+        .ok_or_else(|| CodegenError::TypeConversion(format!(
+            "cannot initialize field '{}' of '{}' of type {} from {} value",
+            field_layout.name, variant_path, field_layout.cranelift_type, found
+        )).at(*span))?;
+    Ok((converted, field_layout.offset))
+}
+
+/// Compile a struct initialization with variable context
+///
+/// Every field occupies an 8-byte slot; the storage comes from the memory manager
+/// using the requested strategy, or one inferred from its escape facts when none was
+/// requested. Declared structs place each field at its layout offset; literals of
+/// undeclared structs keep their fields in initialization order.
+fn compile_struct_init_with_variables(
+    builder: &mut FunctionBuilder,
+    path: &[crate::ast::InternedString],
+    fields: &[crate::ast::FieldInit],
+    strategy: Option<MemoryStrategy>,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    let struct_name = path.last().and_then(|name| interner.resolve(*name));
+    let layout = struct_name.as_ref().and_then(|name| var_context.struct_layout(name)).cloned();
+    // `Shape::Circle { radius: 1 }` constructs a variant with named fields
+    if layout.is_none() {
+        if let Some(variant) = var_context.resolve_variant(path, None, interner) {
+            return compile_variant_construction(builder, variant, VariantArgs::Named(fields), strategy, span, var_context, interner);
+        }
+    }
+    
+    // Compile field values before allocating so nested struct literals are laid out first
+    let mut field_stores = Vec::with_capacity(fields.len());
+    for (i, field) in fields.iter().enumerate() {
+        let value = match &field.value {
+            Some(value_expr) => compile_value_with_variables(builder, value_expr, var_context, interner)?,
+            None => {
+                // Shorthand initialization: `Point { x }` reads the variable `x`
+                let shorthand = Expr::Identifier { name: field.name, span: field.span, id: NodeId::DUMMY };
+                compile_value_with_variables(builder, &shorthand, var_context, interner)?
+            }
+        };
+        
+        let Some(layout) = &layout else {
+            field_stores.push((value, i as u32 * STRUCT_FIELD_SLOT_BYTES));
+            continue;
+        };
+        let field_name = interner.resolve(field.name).unwrap_or_else(|| "<unknown>".to_string());
+        let struct_name = struct_name.as_deref().unwrap_or_default();
+        let field_layout = layout.field(&field_name).ok_or_else(|| CodegenError::TypeConversion(format!(
+            "struct '{}' has no field '{}'",
+            struct_name, field_name
+        )).at(field.span))?;
+        let found = builder.func.dfg.value_type(value);
+        let converted = convert_value(builder, value, field_layout.cranelift_type, var_context.is_unsigned(value))
+            .ok_or_else(|| CodegenError::TypeConversion(format!(
+                "cannot initialize field '{}' of type {} from {} value",
+                field_name, field_layout.cranelift_type, found
+            )).at(field.span))?;
+        field_stores.push((converted, field_layout.offset));
+    }
+    
+    if let (Some(layout), Some(struct_name)) = (&layout, &struct_name) {
+        let missing: Vec<&str> = layout.fields.iter()
+            .filter(|field_layout| !fields.iter().any(|field| interner.matches(field.name, &field_layout.name)))
+            .map(|field_layout| field_layout.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(CodegenError::TypeConversion(format!(
+                "missing field{} {} in initializer of '{}'",
+                if missing.len() == 1 { "" } else { "s" },
+                missing.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(", "),
+                struct_name
+            )).at(*span));
+        }
+    }
+    
+    let size = match &layout {
+        Some(layout) => layout.size,
+        None => (fields.len().max(1) as u32) * STRUCT_FIELD_SLOT_BYTES,
+    };
+    let strategy = strategy.unwrap_or_else(|| match (&layout, &struct_name) {
+        (Some(_), Some(struct_name)) => var_context.struct_strategy(struct_name, span, size),
+        _ => var_context.inferred_strategy(span, size),
+    });
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let struct_ptr = var_context.allocate(builder, strategy, pointer_type, size, span)?;
+    
+    for (value, offset) in field_stores {
+        Address::field(struct_ptr, offset).store(builder, value);
+    }
+    
+    if layout.is_some() {
+        if let Some(struct_name) = struct_name {
+            var_context.struct_values.insert(struct_ptr, struct_name);
         }
     }
+    Ok(struct_ptr)
+}
+
+/// Compile a tuple expression; `()` is unit
+fn compile_tuple_with_variables(
+    builder: &mut FunctionBuilder,
+    elements: &[Expr],
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    if elements.is_empty() {
+        return Ok(builder.ins().iconst(ctypes::I32, 0));
+    }
     
-    if !body_terminated {
-        builder.ins().jump(loop_bb, &[]); // Continue loop
+    let mut values = Vec::with_capacity(elements.len());
+    for element in elements {
+        values.push(compile_value_with_variables(builder, element, var_context, interner)?);
     }
-
-    // Seal blocks and switch to merge
-    builder.switch_to_block(merge_bb);
-    builder.seal_block(loop_bb);
-    builder.seal_block(body_bb);
-    builder.seal_block(merge_bb);
-
-    Ok(())
+    store_tuple(builder, &values, span, var_context)
 }
 
-/// Compile a for statement with variable context - STUB IMPLEMENTATION
-fn compile_for_statement_with_variables(
-    _builder: &mut FunctionBuilder,
-    _pattern: &Pattern,
-    _iterable: &Expr,
-    _body: &[Stmt],
-    _var_context: &mut VariableContext,
-    _interner: &StringInterner,
-) -> CodegenResult<()> {
-    // TODO: Implement full for loop support
-    // For now, just return an error indicating it's not implemented
-    Err(CodegenError::UnsupportedFeature(
-        "For loops not yet fully implemented - use while loops instead".to_string()
-    ))
+/// Store `values` into a new tuple, returning the tuple pointer
+fn store_tuple(
+    builder: &mut FunctionBuilder,
+    values: &[Value],
+    span: &Span,
+    var_context: &mut VariableContext,
+) -> CodegenResult<Value> {
+    let elements: Vec<(Type, bool, Option<String>)> = values.iter()
+        .map(|&value| (
+            builder.func.dfg.value_type(value),
+            var_context.is_unsigned(value),
+            var_context.struct_values.get(&value).cloned(),
+        ))
+        .collect();
+    let layout_name = var_context.register_tuple_layout(&elements);
+    let layout = var_context.tuple_layouts[&layout_name].clone();
+    
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let strategy = var_context.inferred_strategy(span, layout.size);
+    let tuple_ptr = var_context.allocate(builder, strategy, pointer_type, layout.size, span)?;
+    for (&value, field) in values.iter().zip(&layout.fields) {
+        Address::field(tuple_ptr, field.offset).store(builder, value);
+    }
+    var_context.struct_values.insert(tuple_ptr, layout_name);
+    Ok(tuple_ptr)
 }
 
-/// Compile a match expression with variable context - STUB IMPLEMENTATION
-fn compile_match_expression_with_variables(
-    _builder: &mut FunctionBuilder,
-    _expr: &Expr,
-    _arms: &[crate::ast::MatchArm],
-    _var_context: &mut VariableContext,
-    _interner: &StringInterner,
-) -> CodegenResult<Value> {
-    // TODO: Implement full match expression support
-    // For now, return an error indicating it's not implemented
-    Err(CodegenError::UnsupportedFeature(
-        "Match expressions not yet fully implemented".to_string()
-    ))
+/// Bind the sub-patterns of a tuple pattern to the elements of the tuple at `tuple_ptr`
+fn bind_tuple_pattern(
+    builder: &mut FunctionBuilder,
+    patterns: &[Pattern],
+    tuple_ptr: Value,
+    layout_name: &str,
+    span: &Span,
+    var_context: &mut VariableContext,
+) -> CodegenResult<()> {
+    let layout = var_context.struct_layout(layout_name).cloned()
+        .ok_or_else(|| CodegenError::InternalError(format!("No layout for tuple type '{}'", layout_name)))?;
+    if layout.fields.len() != patterns.len() {
+        return Err(CodegenError::TypeConversion(format!(
+            "tuple pattern has {} element{} but the value has type {}",
+            patterns.len(),
+            if patterns.len() == 1 { "" } else { "s" },
+            layout_name
+        )).at(*span));
+    }
+    
+    for (pattern, field) in patterns.iter().zip(&layout.fields) {
+        if matches!(pattern, Pattern::Wildcard { .. }) {
+            continue;
+        }
+        let value = Address::field(tuple_ptr, field.offset).load(builder, field.cranelift_type);
+        match pattern {
+            Pattern::Identifier { name, .. } => {
+                if field.unsigned {
+                    var_context.mark_unsigned(value);
+                }
+                var_context.bind_value(builder, name.id, value, field.struct_name.clone())?;
+            }
+            Pattern::Tuple { patterns, span, .. } => {
+                let nested_layout = field.struct_name.clone().ok_or_else(|| CodegenError::TypeConversion(format!(
+                    "cannot destructure {} element {} as a tuple",
+                    layout_name, field.name
+                )).at(*span))?;
+                bind_tuple_pattern(builder, patterns, value, &nested_layout, span, var_context)?;
+            }
+            _ => {
+                return Err(CodegenError::UnsupportedFeature("only identifier, wildcard and tuple patterns can destructure a tuple".to_string()).at(pattern.span()));
+            }
+        }
+    }
+    Ok(())
 }
 
-/// Compile a struct initialization with variable context - BASIC IMPLEMENTATION
-fn compile_struct_init_with_variables(
+/// Compile the struct operand of a field access, returning the struct pointer and the field's layout
+fn compile_field_address(
     builder: &mut FunctionBuilder,
-    _path: &[crate::ast::InternedString],
-    _fields: &[crate::ast::FieldInit],
-    _var_context: &mut VariableContext,
-    _interner: &StringInterner,
-) -> CodegenResult<Value> {
-    // BASIC STRUCT ALLOCATION - just return dummy pointer for now
-    // TODO: Implement real struct memory allocation and field initialization
-    Ok(builder.ins().iconst(ctypes::I64, 0x11223344)) // Dummy struct pointer
+    object: &Expr,
+    field: &crate::ast::InternedString,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<(Value, FieldLayout)> {
+    let field_name = interner.resolve(*field).unwrap_or_else(|| "<unknown>".to_string());
+    let struct_ptr = compile_value_with_variables(builder, object, var_context, interner)?;
+    let struct_name = var_context.struct_values.get(&struct_ptr).cloned()
+        .ok_or_else(|| CodegenError::TypeConversion(format!(
+            "cannot access field '{}' on a value of unknown struct type",
+            field_name
+        )).at(*span))?;
+    let field_layout = var_context.struct_layout(&struct_name)
+        .and_then(|layout| layout.field(&field_name))
+        .cloned()
+        .ok_or_else(|| CodegenError::TypeConversion(format!(
+            "struct '{}' has no field '{}'",
+            struct_name, field_name
+        )).at(*span))?;
+    Ok((struct_ptr, field_layout))
 }
 
-/// Compile a field access with variable context - BASIC IMPLEMENTATION
+/// Compile a field access with variable context: a load at the field's layout offset
 fn compile_field_access_with_variables(
     builder: &mut FunctionBuilder,
-    _object: &Expr,
-    _field: &crate::ast::InternedString,
-    _var_context: &mut VariableContext,
-    _interner: &StringInterner,
+    object: &Expr,
+    field: &crate::ast::InternedString,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
 ) -> CodegenResult<Value> {
-    // BASIC FIELD ACCESS - just return dummy value for now
-    // TODO: Calculate field offset and load actual value
-    Ok(builder.ins().iconst(ctypes::I32, 42)) // Dummy field value
+    let (struct_ptr, field_layout) = compile_field_address(builder, object, field, span, var_context, interner)?;
+    let value = Address::field(struct_ptr, field_layout.offset).load(builder, field_layout.cranelift_type);
+    if field_layout.unsigned {
+        var_context.mark_unsigned(value);
+    }
+    if let Some(struct_name) = field_layout.struct_name.filter(|name| var_context.has_layout(name)) {
+        var_context.struct_values.insert(value, struct_name);
+    }
+    Ok(value)
+}
+
+/// Candidate struct name of an AST type, looking through references, pointers and
+/// strategy wrappers; the name is not checked against the declared structs
+fn struct_path_name(ast_type: &AstType, interner: &StringInterner) -> Option<String> {
+    match ast_type {
+        AstType::Reference { target_type, .. } | AstType::Pointer { target_type, .. } => {
+            struct_path_name(target_type, interner)
+        }
+        AstType::Path { segments, generics, .. } => match generics.first() {
+            Some(inner) if utils::strategy_from_ast_type(ast_type, interner).is_some() => struct_path_name(inner, interner),
+            _ => interner.resolve(*segments.last()?),
+        },
+        _ => None,
+    }
 }
 
 /// Convert AST type to Cranelift type
-fn ast_type_to_cranelift_type(ast_type: &AstType) -> CodegenResult<Type> {
+fn ast_type_to_cranelift_type(ast_type: &AstType, pointer_type: Type) -> CodegenResult<Type> {
     match ast_type {
         AstType::Primitive { kind, .. } => {
             use crate::ast::PrimitiveType;
@@ -950,144 +4161,418 @@ fn ast_type_to_cranelift_type(ast_type: &AstType) -> CodegenResult<Type> {
                 PrimitiveType::F64 => Ok(ctypes::F64),
                 PrimitiveType::Bool => Ok(ctypes::I8),
                 PrimitiveType::Char => Ok(ctypes::I8),
-                PrimitiveType::Str => Ok(ctypes::I64), // String pointer
-                PrimitiveType::Unit => Ok(ctypes::I32), // Unit type as i32 for now
-                _ => Ok(ctypes::I64), // Default to pointer size
+                PrimitiveType::Str => Ok(pointer_type), // String pointer
+                PrimitiveType::Unit => Err(unit_value_error()),
+                _ => Ok(pointer_type), // Default to pointer size
             }
         }
-        AstType::Path { .. } => Ok(ctypes::I64), // Custom types as pointers
-        AstType::Array { .. } => Ok(ctypes::I64), // Arrays as pointers
-        AstType::Reference { .. } => Ok(ctypes::I64), // References as pointers
-        AstType::Pointer { .. } => Ok(ctypes::I64), // Pointers
-        AstType::Function { .. } => Ok(ctypes::I64), // Function pointers
+        AstType::Path { .. } => Ok(pointer_type), // Custom types as pointers
+        AstType::Array { .. } => Ok(pointer_type), // Arrays as pointers
+        AstType::Reference { .. } => Ok(pointer_type), // References as pointers
+        AstType::Pointer { .. } => Ok(pointer_type), // Pointers
+        AstType::Function { .. } => Ok(pointer_type), // Function pointers
+        AstType::Tuple { types, .. } if types.is_empty() => Err(unit_value_error()), // `()` is unit
+        AstType::Tuple { .. } => Ok(pointer_type), // Tuples as pointers to their elements
         _ => Err(CodegenError::UnsupportedFeature(
             format!("Type not yet supported: {:?}", ast_type)
         )),
     }
 }
 
+/// Whether an AST type is unit, written `()`, which has no value at run time
+fn is_unit_ast_type(ast_type: &AstType) -> bool {
+    match ast_type {
+        AstType::Primitive { kind: PrimitiveType::Unit, .. } => true,
+        AstType::Tuple { types, .. } => types.is_empty(),
+        _ => false,
+    }
+}
+
+/// Error for a unit-typed parameter, variable or field, which has nothing to store
+fn unit_value_error() -> CodegenError {
+    CodegenError::TypeConversion("The unit type () has no value to store; only function results may be unit".to_string())
+}
+
+/// Whether an AST type is an unsigned integer (zero-extended when widened)
+fn is_unsigned_ast_type(ast_type: &AstType) -> bool {
+    use crate::ast::PrimitiveType;
+    matches!(
+        ast_type,
+        AstType::Primitive {
+            kind: PrimitiveType::U8 | PrimitiveType::U16 | PrimitiveType::U32 | PrimitiveType::U64
+                | PrimitiveType::U128 | PrimitiveType::USize | PrimitiveType::Bool | PrimitiveType::Char,
+            ..
+        }
+    )
+}
+
+/// Source-level name of a type for diagnostics, falling back to its Cranelift representation
+fn ast_type_name(ast_type: &AstType, pointer_type: Type) -> CodegenResult<String> {
+    match ast_type {
+        AstType::Primitive { kind, .. } => Ok(format!("{:?}", kind).to_lowercase()),
+        _ => Ok(ast_type_to_cranelift_type(ast_type, pointer_type)?.to_string()),
+    }
+}
+
 /// Compile function calls with full Cranelift support
+///
+/// Arguments are checked against the callee's declared signature and converted to its
+/// parameter types. Calls to functions without a return type evaluate to unit. Callees
+/// other than function names (variables, closures) are called through their function
+/// pointer.
 fn compile_function_call_with_variables(
     builder: &mut FunctionBuilder,
     callee: &Expr,
     args: &[Expr],
+    span: &Span,
     var_context: &mut VariableContext,
     interner: &StringInterner,
-) -> CodegenResult<Value> {
-    // Extract function name from callee expression
-    let func_name = match callee {
-        Expr::Identifier { name, .. } => {
-            interner.get(name)
-                .ok_or_else(|| CodegenError::SymbolResolution(format!("Cannot resolve function name with ID {}", name.id)))?
+) -> CodegenResult<Option<Value>> {
+    match callee {
+        // Local variables shadow functions of the same name
+        Expr::Identifier { name, .. } if var_context.get_variable(name.id).is_none() => {
+            let func_name = interner.resolve(*name)
+                .ok_or_else(|| CodegenError::SymbolResolution(format!("Cannot resolve function name with ID {}", name.id)))?;
+            // A bare tuple variant such as `Some(x)` or an intrinsic, unless a function has its name
+            if var_context.module_context.get_function(*name).is_none() {
+                if let Some(variant) = var_context.resolve_variant(std::slice::from_ref(name), None, interner) {
+                    return compile_variant_construction(builder, variant, VariantArgs::Tuple(args), None, span, var_context, interner).map(Some);
+                }
+                if let Some(intrinsic) = Intrinsic::named(&func_name) {
+                    compile_intrinsic_call(builder, intrinsic, args, span, var_context, interner)?;
+                    return Ok(None);
+                }
+            }
+            emit_call(builder, &func_name, None, args, span, var_context, interner)
+        }
+        // `Enum::Variant(args)`
+        Expr::Path { segments, span: path_span, .. } => {
+            let variant = resolve_pattern_variant(segments, None, path_span, var_context, interner)?;
+            compile_variant_construction(builder, variant, VariantArgs::Tuple(args), None, span, var_context, interner).map(Some)
+        }
+        // `value.method(args)` written with a parenthesized or field callee
+        Expr::FieldAccess { object, field, .. } => {
+            compile_method_call_with_variables(builder, object, field, args, span, var_context, interner)
         }
         _ => {
-            return Err(CodegenError::UnsupportedFeature(
-                "Only direct function calls supported currently".to_string()
-            ));
+            let pointer = compile_value_with_variables(builder, callee, var_context, interner)?;
+            let callee_name = match callee {
+                Expr::Identifier { name, .. } => interner.resolve(*name).unwrap_or_else(|| "<unknown>".to_string()),
+                _ => "closure".to_string(),
+            };
+            let signature = var_context.function_pointers.get(&pointer).cloned()
+                .ok_or_else(|| CodegenError::TypeConversion(format!(
+                    "'{}' is not a function or function pointer",
+                    callee_name
+                )).at(callee.span()))?;
+            let sig_ref = builder.import_signature(signature.clone());
+            let callee = Callee { name: &callee_name, target: CallTarget::Indirect(sig_ref, pointer), signature };
+            emit_call_to(builder, callee, None, args, span, var_context, interner)
         }
+    }
+}
+
+/// Call the runtime function `intrinsic` is lowered to
+///
+/// `print_int` passes its argument as an `i32`, and `print_str` the bytes of its
+/// argument with their length.
+fn compile_intrinsic_call(
+    builder: &mut FunctionBuilder,
+    intrinsic: Intrinsic,
+    args: &[Expr],
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<()> {
+    let [arg] = args else {
+        return Err(CodegenError::TypeConversion(format!(
+            "'{}' takes 1 argument but {} were given", intrinsic.name(), args.len()
+        )).at(*span));
     };
+    let value = compile_value_with_variables(builder, arg, var_context, interner)?;
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let arguments = match intrinsic {
+        Intrinsic::PrintInt => {
+            let value = convert_value(builder, value, ctypes::I32, var_context.is_unsigned(value))
+                .ok_or_else(|| CodegenError::TypeConversion("'print_int' takes an integer".to_string()).at(arg.span()))?;
+            vec![value]
+        }
+        Intrinsic::PrintStr => {
+            if builder.func.dfg.value_type(value) != pointer_type {
+                return Err(CodegenError::TypeConversion("'print_str' takes a string".to_string()).at(arg.span()));
+            }
+            vec![value, Intrinsics::string_length(builder, value, pointer_type)]
+        }
+    };
+    let func_ref = var_context.module_context.intrinsics().import(builder, intrinsic)?;
+    builder.ins().call(func_ref, &arguments);
+    Ok(())
+}
+
+/// Address of the function a closure was lifted into, as a function pointer value
+fn compile_closure_address(
+    builder: &mut FunctionBuilder,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    let func_name = interner.intern(&closures::closure_function_name(span));
+    let (func_ref, signature) = var_context.import_function(builder, func_name)
+        .map_err(|_| CodegenError::InternalError("closure was not lifted into a function".to_string()).at(*span))?;
+    
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let pointer = builder.ins().func_addr(pointer_type, func_ref);
+    var_context.function_pointers.insert(pointer, signature);
+    Ok(pointer)
+}
+
+/// Compile `receiver.method(args)` as a call to the flattened `Type_method(receiver, args)`
+fn compile_method_call_with_variables(
+    builder: &mut FunctionBuilder,
+    receiver: &Expr,
+    method: &crate::ast::InternedString,
+    args: &[Expr],
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<Value>> {
+    let method_name = interner.resolve(*method).unwrap_or_else(|| "<unknown>".to_string());
+    
+    let receiver_val = compile_value_with_variables(builder, receiver, var_context, interner)?;
+    let struct_name = var_context.struct_values.get(&receiver_val).cloned()
+        .ok_or_else(|| CodegenError::TypeConversion(format!(
+            "cannot call method '{}' on a value of unknown struct type",
+            method_name
+        )).at(*span))?;
+    
+    let func_name = mangle_method_name(&struct_name, &method_name);
+    let moves_receiver = var_context.module_context.get_function(interner.intern(&func_name))
+        .ok_or_else(|| CodegenError::SymbolResolution(format!(
+            "no method '{}' on struct '{}'",
+            method_name, struct_name
+        )).at(*span))?
+        .moves_receiver;
+    
+    // A linear receiver is moved into methods taking `self` by value
+    if moves_receiver {
+        var_context.move_linear_out(receiver, receiver_val)?;
+    }
+    
+    emit_call(builder, &func_name, Some(receiver_val), args, span, var_context, interner)
+}
+
+/// How a call reaches the called function
+enum CallTarget {
+    /// Function imported into the function being built
+    Direct(FuncRef),
+    /// Function pointer value, called with the imported signature
+    Indirect(SigRef, Value),
+}
+
+/// Function being called, for `emit_call_to`
+struct Callee<'n> {
+    /// Name used in diagnostics
+    name: &'n str,
+    target: CallTarget,
+    signature: Signature,
+}
+
+/// Emit a call to a registered function, passing `receiver` (if any) ahead of `args`
+fn emit_call(
+    builder: &mut FunctionBuilder,
+    func_name: &str,
+    receiver: Option<Value>,
+    args: &[Expr],
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<Value>> {
+    // Look up the function in the module's table
+    let name = interner.intern(func_name);
+    let module_context = var_context.module_context;
+    let return_type = module_context.lookup_callable(name)?.return_type.as_ref();
+    let (func_ref, signature) = var_context.import_function(builder, name)?;
+    let callee = Callee { name: func_name, target: CallTarget::Direct(func_ref), signature };
+    let result = emit_call_to(builder, callee, receiver, args, span, var_context, interner)?;
     
-        // Look up the function in the registry
-    let (func_id, func_signature) = var_context.get_function(func_name)
-        .ok_or_else(|| CodegenError::SymbolResolution(format!("Unknown function: {}", func_name)))?;
+    // Struct and enum results carry their type, like variables declared with it
+    if let Some(result) = result.filter(|result| !var_context.struct_values.contains_key(result)) {
+        if let Some(type_name) = return_type.and_then(|return_type| var_context.struct_name_of_ast_type(return_type, interner)) {
+            var_context.struct_values.insert(result, type_name);
+        }
+    }
+    Ok(result)
+}
+
+/// Emit a call to `callee`, checking and converting the arguments against its signature
+fn emit_call_to(
+    builder: &mut FunctionBuilder,
+    callee: Callee,
+    receiver: Option<Value>,
+    args: &[Expr],
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Option<Value>> {
+    let Callee { name: func_name, target, signature } = callee;
+    let receiver_count = receiver.is_some() as usize;
+    let expected_args = signature.params.len().saturating_sub(receiver_count);
+    if args.len() + receiver_count != signature.params.len() {
+        return Err(CodegenError::TypeConversion(format!(
+            "function '{}' takes {} argument{} but {} {} given",
+            func_name,
+            expected_args,
+            if expected_args == 1 { "" } else { "s" },
+            args.len(),
+            if args.len() == 1 { "was" } else { "were" }
+        )).at(*span));
+    }
     
     // Compile arguments
-    let mut compiled_args = Vec::new();
-    for arg in args {
-        let arg_value = compile_expression_with_variables(builder, arg, var_context, interner)?;
-        compiled_args.push(arg_value);
-    }
-    
-    // TEMPORARY: Inline implementations for common patterns until proper function calling is fixed
-    let result_value = match func_name {
-        // Fibonacci - convert to iterative version to avoid recursion complexity
-        "fibonacci" => {
-            if compiled_args.len() >= 1 {
-                // Iterative fibonacci implementation
-                let n = compiled_args[0];
-                let zero = builder.ins().iconst(ctypes::I32, 0);
-                let one = builder.ins().iconst(ctypes::I32, 1);
-                let two = builder.ins().iconst(ctypes::I32, 2);
-                
-                // if n <= 1 return n
-                let cond = builder.ins().icmp(cranelift_codegen::ir::condcodes::IntCC::SignedLessThanOrEqual, n, one);
-                let small_block = builder.create_block();
-                let compute_block = builder.create_block();
-                let end_block = builder.create_block();
-                
-                builder.append_block_param(end_block, ctypes::I32);
-                builder.ins().brif(cond, small_block, &[], compute_block, &[]);
-                
-                // Small case: return n
-                builder.switch_to_block(small_block);
-                builder.ins().jump(end_block, &[n]);
-                
-                // Compute case: iterative calculation
-                builder.switch_to_block(compute_block);
-                // For simplicity, just return a constant approximation
-                let three = builder.ins().iconst(ctypes::I32, 3);
-                let result = builder.ins().imul(n, three);
-                builder.ins().jump(end_block, &[result]);
-                
-                builder.switch_to_block(end_block);
-                builder.seal_block(small_block);
-                builder.seal_block(compute_block);
-                builder.seal_block(end_block);
-                builder.block_params(end_block)[0]
-            } else {
-                return Err(CodegenError::InternalError("fibonacci requires 1 argument".to_string()));
-            }
+    let mut compiled_args = Vec::with_capacity(signature.params.len());
+    compiled_args.extend(receiver);
+    let mut lent_smart_pointers = Vec::new();
+    for (i, (arg, param)) in args.iter().zip(&signature.params[receiver_count..]).enumerate() {
+        let arg_value = compile_value_with_variables(builder, arg, var_context, interner)?;
+        let found = builder.func.dfg.value_type(arg_value);
+        let converted = convert_value(builder, arg_value, param.value_type, var_context.is_unsigned(arg_value))
+            .ok_or_else(|| CodegenError::TypeConversion(format!(
+                "argument {} of '{}' expects {} but got {}",
+                i + 1, func_name, param.value_type, found
+            )).at(arg.span()))?;
+        compiled_args.push(converted);
+        
+        // Linear values passed by value are moved into the callee
+        var_context.move_linear_out(arg, arg_value)?;
+        
+        // Smart pointers passed by value hold an extra reference for the duration of the call
+        if let Some(ref_count_offset) = var_context.smart_pointer_of(arg).and_then(|hold| var_context.ref_count_offset(hold)) {
+            var_context.memory_manager.borrow_smart_pointer_for_call(builder, arg_value, ref_count_offset);
+            lent_smart_pointers.push((arg_value, ref_count_offset));
         }
-        // Fall back to inline arithmetic for other functions
-        _ => {
-            // Simple placeholder - return first argument or 0
-            if !compiled_args.is_empty() {
-                compiled_args[0]
-            } else {
-                builder.ins().iconst(ctypes::I32, 0)
-            }
+    }
+    
+    let call = match target {
+        CallTarget::Direct(func_ref) => builder.ins().call(func_ref, &compiled_args),
+        CallTarget::Indirect(sig_ref, pointer) => builder.ins().call_indirect(sig_ref, pointer, &compiled_args),
+    };
+    let results = builder.inst_results(call).to_vec();
+    for (&result, return_param) in results.iter().zip(&signature.returns) {
+        if return_param.extension == ArgumentExtension::Uext {
+            var_context.mark_unsigned(result);
         }
+    }
+    let result_value = match results.as_slice() {
+        [result] => Some(*result),
+        // No return type - the call evaluates to unit
+        [] => None,
+        // A tuple returned element by element is stored back into a tuple
+        elements => Some(store_tuple(builder, elements, span, var_context)?),
     };
     
+    for (ptr, ref_count_offset) in lent_smart_pointers {
+        var_context.memory_manager.end_smart_pointer_call_borrow(builder, ptr, ref_count_offset);
+    }
+    
     Ok(result_value)
 }
 
+/// Static length of an array binding: the `[T; N]` annotation size, else the literal's element count
+fn static_array_length(type_annotation: Option<&AstType>, initializer: Option<&Expr>) -> Option<u32> {
+    match (type_annotation, initializer) {
+        (Some(AstType::Array { size, .. }), _) => size.fold_integer(&|_| None).and_then(|length| u32::try_from(length).ok()),
+        (None, Some(initializer)) => array_literal_length(initializer),
+        _ => None,
+    }
+}
+
+/// Element count of an array literal, or of a repeat expression whose count folds to a constant
+fn array_literal_length(expr: &Expr) -> Option<u32> {
+    match expr {
+        Expr::Array { elements, .. } => Some(elements.len() as u32),
+        Expr::ArrayRepeat { count, .. } => count.fold_integer(&|_| None).and_then(|length| u32::try_from(length).ok()),
+        _ => None,
+    }
+}
+
+/// Compute the address of `array[index]` for an array variable, bounds-checking the index
+///
+/// Constant indices are checked at compile time; dynamic indices into arrays of known
+/// length get a runtime check that traps on violation. Arrays of unknown length (e.g.
+/// reassigned from a call) are not checked yet.
+fn array_element_address(
+    builder: &mut FunctionBuilder,
+    array_name: &crate::ast::InternedString,
+    index: &Expr,
+    index_val: Value,
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Address> {
+    let stack_slot = match var_context.get_variable(array_name.id) {
+        Some(var_info) => var_info.stack_slot,
+        None => {
+            let var_name = interner.resolve(*array_name)
+                .unwrap_or_else(|| format!("var_{}", array_name.id));
+            return Err(CodegenError::SymbolResolution(
+                format!("Array variable '{}' not found", var_name)
+            ));
+        }
+    };
+    
+    // Load the array pointer from the variable's stack slot
+    // (arrays are stored as pointers in variables)
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let array_ptr = builder.ins().stack_load(pointer_type, stack_slot, 0);
+    
+    // A constant index is checked here and becomes the offset of the access
+    let constant = index.fold_integer(&|_| None).and_then(|constant| u32::try_from(constant).ok());
+    let length = var_context.array_lengths.get(&array_name.id).copied();
+    let element_index = match (constant, length) {
+        (Some(constant), Some(length)) if constant >= length => {
+            return Err(CodegenError::MemoryManagement(format!(
+                "index {} is out of bounds for array of length {}",
+                constant, length
+            )).at(*span));
+        }
+        (Some(constant), _) => ElementIndex::Constant(constant),
+        (None, length) => {
+            let index_wide = addressing::index_to_pointer_width(builder, index_val, var_context.is_unsigned(index_val), pointer_type);
+            if let Some(length) = length {
+                let length_val = builder.ins().iconst(pointer_type, length as i64);
+                var_context.memory_manager.generate_bounds_check(builder, index_wide, length_val)?;
+            }
+            ElementIndex::Dynamic(index_wide)
+        }
+    };
+    
+    let (element_type, _) = var_context.array_element(array_name.id);
+    Ok(Address::element(builder, array_ptr, element_index, element_type, pointer_type))
+}
+
 /// Compile array indexing with variable context - REAL IMPLEMENTATION
 fn compile_array_index_with_variables(
     builder: &mut FunctionBuilder,
     array: &Expr,
     index: &Expr,
+    span: &Span,
     var_context: &mut VariableContext,
     interner: &StringInterner,
 ) -> CodegenResult<Value> {
     // Compile the index expression
-    let index_val = compile_expression_with_variables(builder, index, var_context, interner)?;
+    let index_val = compile_value_with_variables(builder, index, var_context, interner)?;
     
     // Handle different array sources
     match array {
         Expr::Identifier { name, .. } => {
-            // Array is a variable - load from its stack slot
-            if let Some(var_info) = var_context.get_variable(name.id) {
-                // Load the array pointer from the variable's stack slot
-                // (arrays are stored as pointers in variables)
-                let array_ptr = builder.ins().stack_load(ctypes::I64, var_info.stack_slot, 0);
-                
-                // Calculate byte offset: index * element_size (4 bytes for i32)
-                let element_size = builder.ins().iconst(ctypes::I64, 4);
-                let index_64 = builder.ins().uextend(ctypes::I64, index_val);
-                let byte_offset = builder.ins().imul(index_64, element_size);
-                
-                // Add offset to array pointer
-                let element_addr = builder.ins().iadd(array_ptr, byte_offset);
-                
-                // Load the value from the calculated address
-                let value = builder.ins().load(ctypes::I32, cranelift::prelude::MemFlags::trusted(), element_addr, 0);
-                Ok(value)
-            } else {
-                Err(CodegenError::InternalError(
-                    format!("Array variable '{}' not found", name.id)
-                ))
+            // Array is a variable - load the element from its (bounds-checked) address
+            let element_addr = array_element_address(builder, name, index, index_val, span, var_context, interner)?;
+            let (element_type, unsigned) = var_context.array_element(name.id);
+            let element = element_addr.load(builder, element_type);
+            if unsigned {
+                var_context.mark_unsigned(element);
             }
+            Ok(element)
         }
         Expr::Array { elements, .. } => {
             // Inline array literal - we need to allocate it first, then index into it
@@ -1097,20 +4582,14 @@ fn compile_array_index_with_variables(
             // In a full implementation, we'd allocate the array and then use pointer arithmetic
             
             // Try to evaluate the index as a constant at compile time
-            if let Expr::Literal { literal: crate::ast::Literal::Integer { value, .. }, .. } = index {
-                if let Ok(index_usize) = value.parse::<usize>() {
-                    if index_usize < elements.len() {
-                        // Compile the specific element directly
-                        compile_expression_with_variables(builder, &elements[index_usize], var_context, interner)
-                    } else {
-                        Err(CodegenError::InternalError(
-                            format!("Array index {} out of bounds for array of length {}", index_usize, elements.len())
-                        ))
-                    }
-                } else {
-                    Err(CodegenError::InternalError(
-                        format!("Invalid integer literal: {}", value)
-                    ))
+            if let Some(constant) = index.fold_integer(&|_| None) {
+                match usize::try_from(constant).ok().and_then(|constant| elements.get(constant)) {
+                    // Compile the specific element directly
+                    Some(element) => compile_value_with_variables(builder, element, var_context, interner),
+                    None => Err(CodegenError::MemoryManagement(format!(
+                        "index {} is out of bounds for array of length {}",
+                        constant, elements.len()
+                    )).at(*span)),
                 }
             } else {
                 // For dynamic indices on inline arrays, we need to allocate the array first
@@ -1128,10 +4607,68 @@ fn compile_array_index_with_variables(
     }
 }
 
+/// Compile an array literal element, converted to the array's element type
+fn compile_array_element(
+    builder: &mut FunctionBuilder,
+    element_expr: &Expr,
+    (element_type, _): (Type, bool),
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    let value = compile_value_with_variables(builder, element_expr, var_context, interner)?;
+    let found = builder.func.dfg.value_type(value);
+    convert_value(builder, value, element_type, var_context.is_unsigned(value))
+        .ok_or_else(|| CodegenError::TypeConversion(format!(
+            "cannot store {} value into an {} array element",
+            found, element_type
+        )).at(element_expr.span()))
+}
+
+/// Allocate storage for `length` elements of `element_type`, returning its address
+///
+/// Arrays too large for the stack under the memory policy, or escaping the function,
+/// go through the memory manager, which releases local ones when they go out of
+/// scope. Other arrays live in a stack slot, which counts
+/// towards the function's stack usage.
+fn allocate_array(
+    builder: &mut FunctionBuilder,
+    element_type: Type,
+    length: u32,
+    span: &Span,
+    var_context: &mut VariableContext,
+) -> CodegenResult<Value> {
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let element_size_bytes = addressing::stride(element_type, pointer_type);
+    let total_size_bytes = length.checked_mul(element_size_bytes).ok_or_else(|| CodegenError::MemoryManagement(format!(
+        "array of {} {} elements is too large",
+        length, element_type
+    )).at(*span))?;
+    
+    let escapes = var_context.escapes(span);
+    if !var_context.memory_manager.policy().fits_on_stack(total_size_bytes) || escapes {
+        let strategy = if escapes {
+            var_context.inferred_strategy(span, total_size_bytes)
+        } else {
+            MemoryStrategy::SmartPtr
+        };
+        return var_context.allocate(builder, strategy, element_type, total_size_bytes, span);
+    }
+    
+    let array_slot = builder.create_sized_stack_slot(cranelift_codegen::ir::StackSlotData::new(
+        cranelift_codegen::ir::StackSlotKind::ExplicitSlot,
+        total_size_bytes,
+    ));
+    let source_location = var_context.source_location(span);
+    var_context.memory_manager.record_stack_allocation(total_size_bytes, Some(*span), &source_location);
+    Ok(builder.ins().stack_addr(pointer_type, array_slot, 0))
+}
+
 /// Compile array literal with variable context - REAL IMPLEMENTATION  
 fn compile_array_literal_with_variables(
     builder: &mut FunctionBuilder,
     elements: &[Expr],
+    element: (Type, bool),
+    span: &Span,
     var_context: &mut VariableContext,
     interner: &StringInterner,
 ) -> CodegenResult<Value> {
@@ -1141,33 +4678,103 @@ fn compile_array_literal_with_variables(
         ));
     }
     
-    // Calculate total size needed: elements.len() * sizeof(i32)
-    let element_count = elements.len() as u32;
-    let element_size_bytes = 4; // i32 = 4 bytes
-    let total_size_bytes = element_count * element_size_bytes;
-    
-    // Create a stack slot to hold the entire array
-    let array_slot = builder.create_sized_stack_slot(cranelift_codegen::ir::StackSlotData::new(
-        cranelift_codegen::ir::StackSlotKind::ExplicitSlot,
-        total_size_bytes,
-    ));
+    let array_ptr = allocate_array(builder, element.0, elements.len() as u32, span, var_context)?;
+    let pointer_type = var_context.memory_manager.pointer_type();
     
-    // Store each element in the array
+    // Store each element at its offset; the address lets variables hold and index the array
     for (i, element_expr) in elements.iter().enumerate() {
-        // Compile the element expression
-        let element_value = compile_expression_with_variables(builder, element_expr, var_context, interner)?;
-        
-        // Calculate offset for this element (i * 4 bytes)
-        let offset = (i as u32) * element_size_bytes;
-        
-        // Store the element at the calculated offset
-        builder.ins().stack_store(element_value, array_slot, offset as i32);
+        let element_value = compile_array_element(builder, element_expr, element, var_context, interner)?;
+        Address::element(builder, array_ptr, ElementIndex::Constant(i as u32), element.0, pointer_type).store(builder, element_value);
+    }
+    Ok(array_ptr)
+}
+
+/// Compile `[value; length]`: the value is evaluated once and stored into every element
+///
+/// Short arrays get one store per element; longer ones are filled by a loop walking
+/// a pointer over the elements.
+fn compile_array_repeat_with_variables(
+    builder: &mut FunctionBuilder,
+    value: &Expr,
+    length: u32,
+    element: (Type, bool),
+    span: &Span,
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    use cranelift::prelude::IntCC;
+    
+    if length == 0 {
+        return Err(CodegenError::UnsupportedFeature(
+            "Empty arrays not supported yet".to_string()
+        ));
+    }
+    
+    let element_value = compile_array_element(builder, value, element, var_context, interner)?;
+    let array_ptr = allocate_array(builder, element.0, length, span, var_context)?;
+    let pointer_type = var_context.memory_manager.pointer_type();
+    let element_size_bytes = addressing::stride(element.0, pointer_type);
+    
+    if length <= MAX_UNROLLED_REPEAT {
+        for i in 0..length {
+            Address::element(builder, array_ptr, ElementIndex::Constant(i), element.0, pointer_type).store(builder, element_value);
+        }
+        return Ok(array_ptr);
+    }
+    
+    // allocate_array checked that the total size fits in a u32
+    let end = builder.ins().iadd_imm(array_ptr, (length * element_size_bytes) as i64);
+    let loop_bb = builder.create_block();
+    let body_bb = builder.create_block();
+    let done_bb = builder.create_block();
+    let cursor = builder.append_block_param(loop_bb, var_context.memory_manager.pointer_type());
+    builder.ins().jump(loop_bb, &[array_ptr]);
+    
+    builder.switch_to_block(loop_bb);
+    let more = builder.ins().icmp(IntCC::UnsignedLessThan, cursor, end);
+    builder.ins().brif(more, body_bb, &[], done_bb, &[]);
+    
+    builder.switch_to_block(body_bb);
+    builder.seal_block(body_bb);
+    Address::new(cursor, 0).store(builder, element_value);
+    let next = builder.ins().iadd_imm(cursor, element_size_bytes as i64);
+    builder.ins().jump(loop_bb, &[next]);
+    builder.seal_block(loop_bb);
+    
+    builder.switch_to_block(done_bb);
+    builder.seal_block(done_bb);
+    Ok(array_ptr)
+}
+
+/// Compile an array literal or repeat expression, checking it against the `expected_length`
+/// of the binding it initializes
+fn compile_array_initializer(
+    builder: &mut FunctionBuilder,
+    init_expr: &Expr,
+    expected_length: Option<u32>,
+    element: (Type, bool),
+    var_context: &mut VariableContext,
+    interner: &StringInterner,
+) -> CodegenResult<Value> {
+    let found_length = array_literal_length(init_expr);
+    if let (Some(expected), Some(found)) = (expected_length, found_length) {
+        if expected != found {
+            return Err(CodegenError::TypeConversion(format!(
+                "expected an array of {} elements, found {} elements",
+                expected, found
+            )).at(init_expr.span()));
+        }
     }
     
-    // Return the address of the array so it can be stored in variables
-    // This allows proper array variable assignment and indexing
-    let array_addr = builder.ins().stack_addr(ctypes::I64, array_slot, 0);
-    Ok(array_addr)
+    match init_expr {
+        Expr::Array { elements, span, .. } => compile_array_literal_with_variables(builder, elements, element, span, var_context, interner),
+        Expr::ArrayRepeat { value, count, span, .. } => {
+            // A count naming a `const` only folds in semantic analysis, which resolved the binding's length
+            let length = found_length.or(expected_length).ok_or_else(|| CodegenError::UnsupportedFeature("array length must be a constant".to_string()).at(count.span()))?;
+            compile_array_repeat_with_variables(builder, value, length, element, span, var_context, interner)
+        }
+        init_expr => compile_value_with_variables(builder, init_expr, var_context, interner),
+    }
 }
 
 // Make the literal compilation function available for expressions.rs
@@ -0,0 +1,163 @@
+//! Intrinsics and string literals
+//!
+//! Calls of the intrinsics `print_int` and `print_str` are lowered to calls of
+//! `bract_print_int(value)` and `bract_print_str(data, len)` in the native runtime.
+//! Only the intrinsics a module calls are declared, so a program that prints nothing
+//! does not import the runtime for them.
+//!
+//! Each distinct string literal is a read-only data object: its length in bytes as a
+//! 64-bit integer, then its UTF-8 bytes and a NUL. A `str` value points at the bytes,
+//! so its length is found just before them.
+
+use super::{CodegenError, CodegenResult};
+use crate::ast::{Expr, InternedString, Literal, Module};
+use crate::parser::StringInterner;
+use crate::semantic::Intrinsic;
+use crate::visitor::{self, Visitor};
+use cranelift::prelude::{types as ctypes, AbiParam, InstBuilder, MemFlags, Type, Value};
+use cranelift_codegen::ir::{immediates::Imm64, Endianness, ExtFuncData, ExternalName, FuncRef, GlobalValueData, Signature, UserExternalName};
+use cranelift_frontend::FunctionBuilder;
+use cranelift_module::{DataDescription, DataId, FuncId, Linkage, Module as CraneliftModule};
+use std::collections::HashMap;
+
+/// Bytes before the contents of a string literal, holding its length
+pub const STRING_LENGTH_BYTES: i32 = 8;
+
+/// Intrinsics and string literals a module uses
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IntrinsicUses {
+    /// Intrinsics called, in the order first called
+    pub intrinsics: Vec<Intrinsic>,
+    /// Contents of the string literals, in the order they first appear
+    pub strings: Vec<String>,
+}
+
+/// Find the intrinsics `module` calls and the string literals it contains
+///
+/// A call only counts as one of an intrinsic when `is_function` says no function of
+/// that name is declared, as such a function shadows the intrinsic.
+pub fn intrinsic_uses(module: &Module, interner: &StringInterner, is_function: impl Fn(InternedString) -> bool) -> IntrinsicUses {
+    struct Uses<'a, F> {
+        interner: &'a StringInterner,
+        is_function: F,
+        uses: IntrinsicUses,
+    }
+
+    impl<'ast, F: Fn(InternedString) -> bool> Visitor<'ast> for Uses<'_, F> {
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            match expr {
+                Expr::Call { callee, .. } => {
+                    if let Expr::Identifier { name, .. } = &**callee {
+                        let intrinsic = self.interner.resolve(*name).as_deref().and_then(Intrinsic::named)
+                            .filter(|_| !(self.is_function)(*name));
+                        if let Some(intrinsic) = intrinsic.filter(|intrinsic| !self.uses.intrinsics.contains(intrinsic)) {
+                            self.uses.intrinsics.push(intrinsic);
+                        }
+                    }
+                }
+                Expr::Literal { literal: Literal::String { value, .. }, .. } => {
+                    let value = self.interner.resolve(*value).unwrap_or_default();
+                    if !self.uses.strings.contains(&value) {
+                        self.uses.strings.push(value);
+                    }
+                }
+                _ => {}
+            }
+            visitor::walk_expr(self, expr);
+        }
+    }
+
+    let mut collector = Uses { interner, is_function, uses: IntrinsicUses::default() };
+    collector.visit_module(module);
+    collector.uses
+}
+
+/// Runtime functions and string data declared for the intrinsics and literals of a module
+#[derive(Debug, Clone, Default)]
+pub struct Intrinsics {
+    functions: HashMap<Intrinsic, (FuncId, Signature)>,
+    /// Data object of each string literal, by its contents
+    strings: HashMap<String, DataId>,
+}
+
+impl Intrinsics {
+    /// Declare the runtime functions of the intrinsics in `uses` and define its string literals
+    pub fn declare(module: &mut dyn CraneliftModule, uses: &IntrinsicUses) -> CodegenResult<Self> {
+        let pointer_type = module.target_config().pointer_type();
+        let mut functions = HashMap::new();
+        for &intrinsic in &uses.intrinsics {
+            let mut signature = module.make_signature();
+            match intrinsic {
+                Intrinsic::PrintInt => signature.params.push(AbiParam::new(ctypes::I32)),
+                Intrinsic::PrintStr => {
+                    signature.params.push(AbiParam::new(pointer_type)); // data
+                    signature.params.push(AbiParam::new(pointer_type)); // len
+                }
+            }
+            let symbol = intrinsic.runtime_symbol();
+            let func_id = module.declare_function(symbol, Linkage::Import, &signature)
+                .map_err(|e| CodegenError::InternalError(format!("Failed to declare {}: {}", symbol, e)))?;
+            functions.insert(intrinsic, (func_id, signature));
+        }
+
+        let endianness = module.isa().endianness();
+        let mut strings = HashMap::new();
+        for value in &uses.strings {
+            let length = value.len() as u64;
+            let mut bytes = match endianness {
+                Endianness::Little => length.to_le_bytes(),
+                Endianness::Big => length.to_be_bytes(),
+            }.to_vec();
+            bytes.extend_from_slice(value.as_bytes());
+            bytes.push(0);
+
+            let data_id = module.declare_anonymous_data(false, false)
+                .map_err(|e| CodegenError::InternalError(format!("Failed to declare string literal: {}", e)))?;
+            let mut description = DataDescription::new();
+            description.define(bytes.into_boxed_slice());
+            description.set_align(STRING_LENGTH_BYTES as u64);
+            module.define_data(data_id, &description)
+                .map_err(|e| CodegenError::InternalError(format!("Failed to define string literal: {}", e)))?;
+            strings.insert(value.clone(), data_id);
+        }
+
+        Ok(Self { functions, strings })
+    }
+
+    /// Import the runtime function of `intrinsic` into `builder`'s function
+    pub fn import(&self, builder: &mut FunctionBuilder, intrinsic: Intrinsic) -> CodegenResult<FuncRef> {
+        let (func_id, signature) = self.functions.get(&intrinsic).ok_or_else(|| CodegenError::InternalError(format!(
+            "intrinsic '{}' was not declared", intrinsic.name()
+        )))?;
+        let signature = builder.import_signature(signature.clone());
+        let name_ref = builder.func.declare_imported_user_function(UserExternalName::new(0, func_id.as_u32()));
+        Ok(builder.import_function(ExtFuncData {
+            name: ExternalName::user(name_ref),
+            signature,
+            // Defined by the runtime, outside the module
+            colocated: false,
+        }))
+    }
+
+    /// Pointer to the bytes of the string literal `value`
+    pub fn string_literal(&self, builder: &mut FunctionBuilder, value: &str, pointer_type: Type) -> CodegenResult<Value> {
+        let data_id = self.strings.get(value).ok_or_else(|| CodegenError::InternalError(format!(
+            "string literal {:?} was not declared", value
+        )))?;
+        let name_ref = builder.func.declare_imported_user_function(UserExternalName::new(1, data_id.as_u32()));
+        let global = builder.func.create_global_value(GlobalValueData::Symbol {
+            name: ExternalName::user(name_ref),
+            offset: Imm64::new(0),
+            colocated: true,
+            tls: false,
+        });
+        let start = builder.ins().global_value(pointer_type, global);
+        Ok(builder.ins().iadd_imm(start, i64::from(STRING_LENGTH_BYTES)))
+    }
+
+    /// Length of the string whose bytes `text` points at
+    pub fn string_length(builder: &mut FunctionBuilder, text: Value, pointer_type: Type) -> Value {
+        let length = builder.ins().load(ctypes::I64, MemFlags::trusted().with_readonly(), text, -STRING_LENGTH_BYTES);
+        if pointer_type == ctypes::I64 { length } else { builder.ins().ireduce(pointer_type, length) }
+    }
+}
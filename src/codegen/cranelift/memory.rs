@@ -14,11 +14,71 @@
 //! • Performance transparency - every allocation cost is measurable
 
 use super::{CodegenResult, CodegenError};
+use super::context::ModuleView;
+use crate::ast::{Attribute, InternedString, Literal, SourceMap, Span};
+use crate::parser::StringInterner;
 use cranelift::prelude::{types as ctypes, Type, Value, InstBuilder};
+use cranelift_codegen::ir::{FuncRef, Function, Inst, MemFlags, StackSlot, StackSlotData, StackSlotKind, TrapCode};
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
 use cranelift_frontend::FunctionBuilder;
 use cranelift_module::{Module as CraneliftModule, FuncId};
 // External name imports removed - not currently used
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Instant;
+
+/// Largest type, in bytes, inferred to live on the stack by default
+pub const STACK_SIZE_THRESHOLD: u32 = 64;
+
+/// Largest type, in bytes, inferred to live in a region rather than be allocated manually
+/// by default
+pub const REGION_SIZE_THRESHOLD: u32 = 4096;
+
+/// Largest single stack allocation by default
+pub const MAX_STACK_ALLOCATION: u32 = 4096;
+
+/// Most stack a function's allocations may take by default before it is diagnosed
+pub const MAX_FUNCTION_STACK: u32 = 64 * 1024;
+
+/// Where values are placed by their size, and how much stack a function may take
+///
+/// Every decision between the stack and the heap reads this: strategy inference,
+/// allocation, and the performance analyzer's stack estimates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryPolicy {
+    /// Largest type, in bytes, inferred to live on the stack; larger linear values are
+    /// spilled to a region or the heap
+    pub stack_threshold: u32,
+    /// Largest type, in bytes, inferred to live in a region rather than be allocated manually
+    pub region_threshold: u32,
+    /// Largest single allocation, in bytes, that is put on the stack; larger arrays and
+    /// values that would otherwise stay in their function's frame go to the heap
+    pub max_stack_allocation: u32,
+    /// Most stack, in bytes, a function's allocations are estimated to take before the
+    /// function is diagnosed
+    pub max_function_stack: u32,
+    /// Make exceeding `max_function_stack` an error rather than a warning
+    pub strict_stack_limit: bool,
+}
+
+impl Default for MemoryPolicy {
+    fn default() -> Self {
+        Self {
+            stack_threshold: STACK_SIZE_THRESHOLD,
+            region_threshold: REGION_SIZE_THRESHOLD,
+            max_stack_allocation: MAX_STACK_ALLOCATION,
+            max_function_stack: MAX_FUNCTION_STACK,
+            strict_stack_limit: false,
+        }
+    }
+}
+
+impl MemoryPolicy {
+    /// Whether a single allocation of `size` bytes may go on the stack
+    pub fn fits_on_stack(&self, size: u32) -> bool {
+        size <= self.max_stack_allocation
+    }
+}
 
 /// Memory allocation strategy - the core of Bract's flexibility
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -58,28 +118,119 @@ impl MemoryStrategy {
         }
     }
 
+    /// Estimated CPU cycles to allocate `size` bytes with this strategy
+    pub fn allocation_cycles(self, size: u32) -> u64 {
+        match self {
+            MemoryStrategy::Stack => 1,        // ~1 cycle - just stack pointer adjustment
+            MemoryStrategy::Manual => 50 + (size as u64 / 64), // malloc overhead + size factor
+            MemoryStrategy::Region => 5 + (size as u64 / 128), // bump allocator + size factor  
+            MemoryStrategy::Linear => 3,       // stack alloc + ownership tracking
+            MemoryStrategy::SmartPtr => 80 + (size as u64 / 32), // malloc + ref count + tracking
+        }
+    }
+
     /// Recommend strategy for given type and context - performance optimized
     #[inline] // Inline for hot path optimization
-    pub fn infer_for_type(type_size: u32, is_shared: bool, lifetime_known: bool) -> Self {
+    pub fn infer_for_type(type_size: u32, is_shared: bool, lifetime_known: bool, policy: &MemoryPolicy) -> Self {
         // Fast path: small types with known lifetimes use stack (most common case)
-        if type_size <= 64 && !is_shared && lifetime_known {
+        if type_size <= policy.stack_threshold && !is_shared && lifetime_known {
             return MemoryStrategy::Stack;
         }
 
         // Performance hierarchy: Stack > Region > Linear > Manual > SmartPtr
         match (type_size, is_shared, lifetime_known) {
             // Medium objects with known lifetimes -> Region (bulk dealloc efficiency)
-            (65..=4096, false, true) => MemoryStrategy::Region,
+            (size, false, true) if size <= policy.region_threshold => MemoryStrategy::Region,
             // Large objects with known lifetimes -> Manual for maximum control
-            (4097.., false, true) => MemoryStrategy::Manual,
+            (_, false, true) => MemoryStrategy::Manual,
             // Shared data -> SmartPtr for safety (necessary overhead)
             (_, true, _) => MemoryStrategy::SmartPtr,
             // Unknown lifetime, not shared -> Linear for move semantics performance
             (_, false, false) => MemoryStrategy::Linear,
-            // Fallback: SmartPtr for safety when unsure
-            _ => MemoryStrategy::SmartPtr,
         }
     }
+
+    /// Recommend strategy for a value from what escape analysis knows about it
+    ///
+    /// A value that stays in its function lives as long as the frame, so it goes on the
+    /// stack, unless it is too large for a single stack allocation: then it is reference
+    /// counted, which releases it when the function returns. An escaping value needs
+    /// storage that outlives the frame: shared values are reference counted and the rest
+    /// are allocated manually, since small linear values live on the stack.
+    pub fn infer_for_escape(type_size: u32, is_shared: bool, escapes: bool, policy: &MemoryPolicy) -> Self {
+        match Self::infer_for_type(type_size, is_shared, !escapes, policy) {
+            MemoryStrategy::Region | MemoryStrategy::Manual if !escapes && !policy.fits_on_stack(type_size) => {
+                MemoryStrategy::SmartPtr
+            }
+            MemoryStrategy::Region | MemoryStrategy::Manual if !escapes => MemoryStrategy::Stack,
+            MemoryStrategy::Linear => MemoryStrategy::Manual,
+            strategy => strategy,
+        }
+    }
+}
+
+/// Memory strategy chosen for a struct type, and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeStrategy {
+    pub strategy: MemoryStrategy,
+    /// Requested by an annotation on the struct rather than inferred
+    pub annotated: bool,
+    /// Size of an instance in bytes
+    pub size: u32,
+    /// Alignment of an instance in bytes
+    pub alignment: u32,
+    /// Why the strategy was chosen, as memory reports show it
+    pub reason: String,
+}
+
+impl TypeStrategy {
+    /// The strategy requested by `annotation` on the struct
+    pub fn annotated(strategy: MemoryStrategy, annotation: &str, size: u32, alignment: u32) -> Self {
+        Self {
+            strategy,
+            annotated: true,
+            size,
+            alignment,
+            reason: format!("annotated {} -> {}", annotation, strategy.name()),
+        }
+    }
+
+    /// The strategy `MemoryStrategy::infer_for_type` picks under `policy`, with the facts
+    /// it went by
+    pub fn inferred(size: u32, alignment: u32, is_shared: bool, lifetime_known: bool, policy: &MemoryPolicy) -> Self {
+        let strategy = MemoryStrategy::infer_for_type(size, is_shared, lifetime_known, policy);
+        let size_fact = if size <= policy.stack_threshold {
+            format!("size {} <= stack threshold {}", size, policy.stack_threshold)
+        } else if size <= policy.region_threshold {
+            format!("size {} > stack threshold {}", size, policy.stack_threshold)
+        } else {
+            format!("size {} > region threshold {}", size, policy.region_threshold)
+        };
+        let shared_fact = if is_shared { "shared" } else { "not shared" };
+        let lifetime_fact = if lifetime_known { "lifetime known" } else { "escapes its function" };
+        Self {
+            strategy,
+            annotated: false,
+            size,
+            alignment,
+            reason: format!("{}, {}, {} -> {}", size_fact, shared_fact, lifetime_fact, strategy.name()),
+        }
+    }
+}
+
+/// Text report of the strategy chosen for each struct type, in the order given
+pub fn type_strategy_report(strategies: &[(String, TypeStrategy)]) -> String {
+    let mut report = String::from("=== Struct Memory Strategies ===\n");
+    if strategies.is_empty() {
+        report.push_str("No struct types\n");
+    }
+    for (name, decision) in strategies {
+        report.push_str(&format!(
+            "  {}: {} ({} bytes, align {}) - {}\n",
+            name, decision.strategy.name(), decision.size, decision.alignment, decision.reason
+        ));
+    }
+    report
 }
 
 /// Memory allocation result with performance metrics
@@ -100,16 +251,21 @@ pub struct AllocationResult {
 /// Runtime function references for memory operations
 #[derive(Debug, Clone)]
 struct RuntimeFunctions {
-    #[allow(dead_code)]
     malloc: FuncId,
-    #[allow(dead_code)]
     free: FuncId,
-    #[allow(dead_code)]
     arc_inc: FuncId,
-    #[allow(dead_code)]
     arc_dec: FuncId,
 }
 
+/// Runtime functions imported into the function currently being compiled
+#[derive(Debug, Clone, Copy)]
+struct RuntimeFuncRefs {
+    malloc: FuncRef,
+    free: FuncRef,
+    arc_inc: FuncRef,
+    arc_dec: FuncRef,
+}
+
 /// Memory region for arena-style allocation with optimization
 #[derive(Debug, Clone)]
 pub struct MemoryRegion {
@@ -123,6 +279,43 @@ pub struct MemoryRegion {
     pub allocator: OptimizedRegionAllocator,
     /// Default alignment for this region
     pub default_alignment: u32,
+    /// What runtime-sized allocations do when the region is full
+    pub overflow: RegionOverflowPolicy,
+    /// Runtime bump pointer, set up by `initialize_region`
+    pub cursor: Option<RegionCursor>,
+}
+
+/// What a region allocation does when the region has no room left at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RegionOverflowPolicy {
+    /// Abort the program
+    #[default]
+    Trap,
+    /// Allocate the value with `bract_malloc`; it is not freed with the region
+    FallbackToManual,
+    /// Chain a new chunk of at least twice the capacity, freed with the region
+    GrowRegion,
+}
+
+impl RegionOverflowPolicy {
+    /// Parse the policy argument of `@region(name, policy)`
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "trap" => Some(RegionOverflowPolicy::Trap),
+            "fallback" | "manual" => Some(RegionOverflowPolicy::FallbackToManual),
+            "grow" => Some(RegionOverflowPolicy::GrowRegion),
+            _ => None,
+        }
+    }
+}
+
+/// Runtime state of an initialized region: a stack slot holding the current chunk's
+/// base, the bump offset into it and its capacity, one pointer-sized word each
+#[derive(Debug, Clone, Copy)]
+pub struct RegionCursor {
+    slot: StackSlot,
+    /// `iconst` the offset starts at, kept past every statically placed allocation
+    start_offset: Inst,
 }
 
 /// Linear type ownership tracker
@@ -141,10 +334,31 @@ pub struct SmartPointer {
     pub ref_count_ptr: Value,
     pub destructor: Option<FuncId>,
     pub cycle_root: bool,
+    /// Allocation the pointer came from; identifies it in the cycle detector
+    pub alloc_id: u32,
+    /// Slot holding the pointer once allocated, so releases need not follow the allocation
+    pub slot: StackSlot,
+    /// Offset of the reference count from the start of the allocation
+    pub ref_count_offset: u32,
+    /// References owned by the current function (released together)
+    pub owners: u32,
+    /// Whether a scope of the function holds the references, releasing them when it ends
+    pub scoped: bool,
+}
+
+/// A reference to a smart pointer held in a stack slot of its own
+///
+/// The slot is cleared on entry to the function and again when the reference is
+/// released, so a release on a path that never took the reference does nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmartPointerRef {
+    pub slot: StackSlot,
+    /// Offset of the reference count from the start of the allocation
+    pub ref_count_offset: u32,
 }
 
 /// Performance metrics - real data developers can use
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct MemoryMetrics {
     // Allocation counts by strategy
     pub manual_allocs: u64,
@@ -179,6 +393,22 @@ impl MemoryMetrics {
         self.peak_memory_usage = self.peak_memory_usage.max(self.total_bytes_allocated);
     }
 
+    /// Add the counts recorded by another manager
+    fn absorb(&mut self, other: &MemoryMetrics) {
+        self.manual_allocs += other.manual_allocs;
+        self.smart_ptr_allocs += other.smart_ptr_allocs;
+        self.linear_allocs += other.linear_allocs;
+        self.region_allocs += other.region_allocs;
+        self.stack_allocs += other.stack_allocs;
+        self.total_bytes_allocated += other.total_bytes_allocated;
+        self.peak_memory_usage = self.peak_memory_usage.max(self.total_bytes_allocated);
+        self.allocation_failures += other.allocation_failures;
+        self.bounds_violations_prevented += other.bounds_violations_prevented;
+        self.use_after_move_prevented += other.use_after_move_prevented;
+        self.memory_leaks_prevented += other.memory_leaks_prevented;
+        self.cycle_cleanups += other.cycle_cleanups;
+    }
+
     /// Get total allocations across all strategies
     pub fn total_allocations(&self) -> u64 {
         self.manual_allocs + self.smart_ptr_allocs + self.linear_allocs + 
@@ -203,11 +433,12 @@ impl MemoryMetrics {
 /// The Beautiful Memory Manager - clean, fast, safe
 pub struct BractMemoryManager {
     /// Runtime function references (legacy - being replaced by runtime_bridge)
+    #[allow(dead_code)]
     runtime_functions: Option<RuntimeFunctions>,
     /// Modern runtime bridge system
     runtime_bridge: RuntimeBridge,
-    /// Active memory regions
-    regions: HashMap<u32, MemoryRegion>,
+    /// Active memory regions, by id (ordered, so reports list them as they were created)
+    regions: BTreeMap<u32, MemoryRegion>,
     /// Linear type ownership tracking
     linear_ownership: HashMap<Value, LinearOwnership>,
     /// Smart pointer registry
@@ -220,31 +451,136 @@ pub struct BractMemoryManager {
     cycle_detector: CycleDetector,
     /// Runtime memory profiler for performance analysis
     profiler: MemoryProfiler,
+    /// Pointer type of the compilation target (addresses, sizes and offsets)
+    pointer_type: Type,
+    /// Where values are placed by size, and the stack each function may take
+    policy: MemoryPolicy,
+    /// Stack allocations of the current function
+    stack_allocations: Vec<StackAllocation>,
+    /// Linear values of the current function spilled to the heap: the stack slot
+    /// holding each one's pointer, and its allocation id
+    heap_spills: Vec<(StackSlot, u32)>,
+    /// Functions whose stack allocations exceeded the policy's limit
+    stack_warnings: Vec<StackUsageWarning>,
     /// Next unique IDs
     next_region_id: u32,
     next_alloc_id: u32,
 }
 
+/// Stack allocations listed in a `StackUsageWarning`
+const REPORTED_STACK_ALLOCATIONS: usize = 3;
+
+/// A stack allocation of the function being compiled
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackAllocation {
+    /// Size in bytes
+    pub size: u32,
+    /// Span of the allocated value, when it was allocated for source code
+    pub span: Option<Span>,
+    pub source_location: String,
+}
+
+/// A function whose stack allocations may take more than `MemoryPolicy::max_function_stack`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackUsageWarning {
+    pub function_name: String,
+    /// Bytes all its stack allocations take together
+    pub total_bytes: u64,
+    /// `MemoryPolicy::max_function_stack` it exceeds
+    pub limit: u32,
+    /// Its largest stack allocations, largest first
+    pub largest: Vec<StackAllocation>,
+}
+
+impl StackUsageWarning {
+    /// One-line explanation naming the largest allocations
+    pub fn message(&self) -> String {
+        let largest: Vec<String> = self.largest.iter()
+            .map(|allocation| format!("{} bytes at {}", allocation.size, allocation.source_location))
+            .collect();
+        format!(
+            "Function '{}' may take {} bytes of stack for its allocations, more than the {} allowed; \
+             the largest are {}",
+            self.function_name, self.total_bytes, self.limit, largest.join(", ")
+        )
+    }
+
+    /// Span of the largest allocation, if it has one
+    pub fn span(&self) -> Option<Span> {
+        self.largest.first().and_then(|allocation| allocation.span)
+    }
+}
+
 impl BractMemoryManager {
     /// Create new memory manager
     pub fn new() -> Self {
         Self {
             runtime_functions: None,
             runtime_bridge: RuntimeBridge::new(),
-            regions: HashMap::new(),
+            regions: BTreeMap::new(),
             linear_ownership: HashMap::new(),
             smart_pointers: HashMap::new(),
             metrics: MemoryMetrics::default(),
             leak_tracker: AllocationTracker::new(),
             cycle_detector: CycleDetector::new(),
             profiler: MemoryProfiler::new(),
+            pointer_type: ctypes::I64,
+            policy: MemoryPolicy::default(),
+            stack_allocations: Vec::new(),
+            heap_spills: Vec::new(),
+            stack_warnings: Vec::new(),
             next_region_id: 1,
             next_alloc_id: 1000, // Start high to avoid conflicts
         }
     }
 
+    /// Create a manager for compiling one function on its own
+    ///
+    /// The fork shares the runtime declarations but starts with empty ownership,
+    /// smart pointer and leak state, so functions compiled in parallel can't see
+    /// each other's values. Its region and allocation ids count from zero and are
+    /// renumbered when it is merged back with `absorb`.
+    pub fn fork(&self) -> Self {
+        Self {
+            runtime_functions: self.runtime_functions.clone(),
+            runtime_bridge: self.runtime_bridge.clone(),
+            profiler: MemoryProfiler::with_config(self.profiler.config.clone()),
+            pointer_type: self.pointer_type,
+            policy: self.policy,
+            next_region_id: 0,
+            next_alloc_id: 0,
+            ..Self::new()
+        }
+    }
+
+    /// Merge a fork back after its function has been compiled
+    ///
+    /// Forks must be absorbed in the order their functions appear, which numbers
+    /// regions and allocations exactly as compiling them one after another would.
+    /// Returns the fork's leak warnings.
+    pub fn absorb(&mut self, fork: BractMemoryManager) -> Vec<LeakWarning> {
+        let region_offset = self.next_region_id;
+        let alloc_offset = self.next_alloc_id;
+        self.next_region_id += fork.next_region_id;
+        self.next_alloc_id += fork.next_alloc_id;
+
+        for (region_id, mut region) in fork.regions {
+            region.id = region_id + region_offset;
+            region.allocations.iter_mut().for_each(|alloc_id| *alloc_id += alloc_offset);
+            self.regions.insert(region.id, region);
+        }
+        self.metrics.absorb(&fork.metrics);
+        self.stack_warnings.extend(fork.stack_warnings);
+        self.profiler.absorb_hotspots(fork.profiler);
+        self.cycle_detector.absorb(fork.cycle_detector, alloc_offset);
+        self.leak_tracker.absorb(fork.leak_tracker, alloc_offset)
+    }
+
     /// Initialize runtime system with modern bridge architecture
     pub fn initialize_runtime(&mut self, module: &mut dyn CraneliftModule) -> CodegenResult<()> {
+        // Addresses are as wide as the target's pointers
+        self.pointer_type = module.target_config().pointer_type();
+        
         // Initialize the new runtime bridge
         self.runtime_bridge.initialize(module)?;
         
@@ -260,6 +596,27 @@ impl BractMemoryManager {
         Ok(())
     }
 
+    /// Pointer type of the compilation target
+    pub fn pointer_type(&self) -> Type {
+        self.pointer_type
+    }
+
+    /// The manager placing values by `policy`
+    pub fn with_policy(mut self, policy: MemoryPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Where values are placed by size, and the stack each function may take
+    pub fn policy(&self) -> &MemoryPolicy {
+        &self.policy
+    }
+
+    /// Functions whose stack allocations exceeded the policy's limit, in the order compiled
+    pub fn stack_warnings(&self) -> &[StackUsageWarning] {
+        &self.stack_warnings
+    }
+
     /// **THE CORE API** - Single allocation method with strategy dispatch
     pub fn allocate(
         &mut self,
@@ -278,19 +635,21 @@ impl BractMemoryManager {
         // Dispatch to strategy-specific implementation
         let ptr = match strategy {
             MemoryStrategy::Manual => self.alloc_manual(builder, size)?,
-            MemoryStrategy::SmartPtr => self.alloc_smart_ptr(builder, object_type, size)?,
-            MemoryStrategy::Linear => self.alloc_linear(builder, object_type, size, &options.source_location)?,
+            MemoryStrategy::SmartPtr => self.alloc_smart_ptr(builder, object_type, size, alloc_id)?,
+            MemoryStrategy::Linear => self.alloc_linear(builder, size, alloc_id, &options)?,
             MemoryStrategy::Region => {
+                // Semantic analysis rejects region values allocated outside any region
                 let region_id = options.region_id.ok_or_else(|| 
                     invalid_allocation_error(
                         strategy.name(),
-                        "Region allocation requires region_id in options".to_string(),
-                        "Use create_region() first, then pass region_id in AllocationOptions".to_string(),
+                        "Region allocation outside any region".to_string(),
+                        "Pass the region_id of the enclosing region in AllocationOptions".to_string(),
                     )
                 )?;
-                self.alloc_in_region(builder, region_id, object_type, size)?
+                let hint = options.alignment.map_or(AlignmentHint::Structure, AlignmentHint::Custom);
+                self.place_in_region(builder, region_id, size, hint, options.region_overflow)?
             },
-            MemoryStrategy::Stack => self.alloc_stack(builder, size)?,
+            MemoryStrategy::Stack => self.alloc_stack(builder, size, &options)?,
         };
 
         // Calculate performance cost estimate
@@ -305,7 +664,7 @@ impl BractMemoryManager {
         };
 
         // Track allocation for leak detection
-        self.leak_tracker.track_allocation(&result, &options.source_location);
+        self.leak_tracker.track_allocation(&result, &options);
 
         // Record hotspot for performance profiling
         self.profiler.record_hotspot(options.source_location.clone(), size, strategy);
@@ -316,21 +675,23 @@ impl BractMemoryManager {
     /// Manual allocation using malloc - maximum performance with runtime bridge
     fn alloc_manual(&mut self, builder: &mut FunctionBuilder, size: u32) -> CodegenResult<Value> {
         // Use modern runtime bridge for clean integration
-        let size_val = builder.ins().iconst(ctypes::I64, size as i64);
+        let size_val = builder.ins().iconst(self.pointer_type, size as i64);
         self.runtime_bridge.generate_malloc_call(builder, size_val)
     }
 
     /// Smart pointer allocation with reference counting
-    fn alloc_smart_ptr(&mut self, builder: &mut FunctionBuilder, _object_type: Type, size: u32) -> CodegenResult<Value> {
+    fn alloc_smart_ptr(&mut self, builder: &mut FunctionBuilder, _object_type: Type, size: u32, alloc_id: u32) -> CodegenResult<Value> {
         // Allocate space for object + reference count (8 bytes)
         let total_size = size + 8;
         let ptr = self.alloc_manual(builder, total_size)?;
 
         // Initialize reference count to 1 at end of allocated memory
-        let ref_count_offset = builder.ins().iconst(ctypes::I64, size as i64);
+        let ref_count_offset = builder.ins().iconst(self.pointer_type, size as i64);
         let ref_count_ptr = builder.ins().iadd(ptr, ref_count_offset);
         let initial_ref_count = builder.ins().iconst(ctypes::I64, 1);
         builder.ins().store(cranelift::prelude::MemFlags::new(), initial_ref_count, ref_count_ptr, 0);
+        let slot = self.null_pointer_slot(builder);
+        builder.ins().stack_store(ptr, slot, 0);
 
         // Register smart pointer for tracking
         let smart_ptr = SmartPointer {
@@ -338,6 +699,11 @@ impl BractMemoryManager {
             ref_count_ptr,
             destructor: None,
             cycle_root: false,
+            alloc_id,
+            slot,
+            ref_count_offset: size,
+            owners: 1,
+            scoped: false,
         };
         self.smart_pointers.insert(ptr, smart_ptr);
 
@@ -345,84 +711,215 @@ impl BractMemoryManager {
     }
 
     /// Linear type allocation with move semantics
-    fn alloc_linear(&mut self, builder: &mut FunctionBuilder, _object_type: Type, size: u32, source_location: &str) -> CodegenResult<Value> {
-        // Linear types use stack allocation for performance  
-        let ptr = self.alloc_stack(builder, size)?;
+    ///
+    /// Linear values up to the policy's stack threshold live on the stack. Larger ones
+    /// are spilled to the enclosing region, if any, or else to the heap, where
+    /// `cleanup_function` frees them unless they leave the function. Their ownership is
+    /// tracked the same wherever they live.
+    fn alloc_linear(&mut self, builder: &mut FunctionBuilder, size: u32, alloc_id: u32, options: &AllocationOptions) -> CodegenResult<Value> {
+        let ptr = if size <= self.policy.stack_threshold {
+            self.alloc_stack(builder, size, options)?
+        } else if let Some(region_id) = options.region_id {
+            let hint = options.alignment.map_or(AlignmentHint::Structure, AlignmentHint::Custom);
+            self.place_in_region(builder, region_id, size, hint, options.region_overflow)?
+        } else {
+            let ptr = self.alloc_manual(builder, size)?;
+            self.remember_heap_spill(builder, ptr, alloc_id);
+            ptr
+        };
 
         // Register for ownership tracking
+        self.track_linear(ptr, &options.source_location);
+
+        Ok(ptr)
+    }
+
+    /// Keep the pointer of a linear value spilled to the heap for `cleanup_function`
+    ///
+    /// The pointer goes into a stack slot that is cleared on entry to the function, so a
+    /// return on a path that never allocated the value frees a null pointer.
+    fn remember_heap_spill(&mut self, builder: &mut FunctionBuilder, ptr: Value, alloc_id: u32) {
+        let slot = self.null_pointer_slot(builder);
+        builder.ins().stack_store(ptr, slot, 0);
+        self.heap_spills.push((slot, alloc_id));
+    }
+
+    /// Create a pointer-sized stack slot holding null from the function's entry on
+    fn null_pointer_slot(&self, builder: &mut FunctionBuilder) -> StackSlot {
+        let slot = builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, self.pointer_type.bytes()));
+        if let Some(entry) = builder.func.layout.entry_block() {
+            let mut cursor = FuncCursor::new(builder.func).at_first_insertion_point(entry);
+            let null = cursor.ins().iconst(self.pointer_type, 0);
+            cursor.ins().stack_store(null, slot, 0);
+        }
+        slot
+    }
+
+    /// Start ownership tracking for a linear value that was not allocated here
+    /// (e.g. a `LinearPtr<T>` parameter or a value returned from a call)
+    pub fn track_linear(&mut self, value: Value, source_location: &str) {
         let ownership = LinearOwnership {
-            value: ptr,
+            value,
             is_moved: false,
             move_generation: 0,
             source_location: source_location.to_string(),
         };
-        self.linear_ownership.insert(ptr, ownership);
-
-        Ok(ptr)
+        self.linear_ownership.insert(value, ownership);
     }
 
-        /// Region allocation - arena style with optimal alignment
-    fn alloc_in_region(&mut self, builder: &mut FunctionBuilder, region_id: u32, _object_type: Type, size: u32) -> CodegenResult<Value> {
-        self.alloc_in_region_with_hint(builder, region_id, size, AlignmentHint::Structure)
+    /// Check whether a value currently owns a linear allocation
+    pub fn is_linear(&self, value: Value) -> bool {
+        self.linear_ownership.get(&value).is_some_and(|ownership| !ownership.is_moved)
     }
 
     /// Region allocation with alignment hint for maximum performance
     pub fn alloc_in_region_with_hint(&mut self, builder: &mut FunctionBuilder, region_id: u32, size: u32, hint: AlignmentHint) -> CodegenResult<Value> {
-        let (base_ptr, aligned_allocation) = {
-            let region = self.regions.get_mut(&region_id).ok_or_else(|| 
-                invalid_allocation_error(
-                    "Region",
-                    format!("Region {} does not exist", region_id),
-                    format!("Create region {} using create_region() before allocating", region_id),
-                )
-            )?;
+        self.place_in_region(builder, region_id, size, hint, None)
+    }
 
-            let base_ptr = region.base_ptr.ok_or_else(|| 
-                invalid_allocation_error(
-                    "Region", 
-                    format!("Region {} not initialized", region_id),
-                    "Call initialize_region() before allocating in region".to_string(),
-                )
-            )?;
+    /// Place a statically sized allocation at a constant offset into the region's first
+    /// chunk; once that is full, bump the runtime cursor like a runtime-sized value
+    fn place_in_region(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        region_id: u32,
+        size: u32,
+        hint: AlignmentHint,
+        overflow: Option<RegionOverflowPolicy>,
+    ) -> CodegenResult<Value> {
+        let region = self.regions.get_mut(&region_id).ok_or_else(|| region_not_found_error(region_id))?;
+        let base_ptr = region.base_ptr.ok_or_else(|| region_not_initialized_error(region_id))?;
+
+        // Calculate optimal alignment based on hint
+        let optimal_alignment = region.allocator.calculate_optimal_alignment(size, hint);
+        if region.allocator.align_address(region.used, optimal_alignment) + size as u64 > region.size {
+            let size_val = builder.ins().iconst(self.pointer_type, size as i64);
+            return self.bump_region_cursor(builder, region_id, size_val, optimal_alignment, overflow);
+        }
 
-            // Calculate optimal alignment based on hint
-            let optimal_alignment = region.allocator.calculate_optimal_alignment(size, hint);
-            
-            // Calculate optimally aligned allocation
-            let aligned_alloc = region.allocator.calculate_aligned_allocation(
-                size, 
-                region.used, 
-                optimal_alignment
-            );
-
-            // Check if allocation fits in region
-            if aligned_alloc.aligned_offset + aligned_alloc.actual_size > region.size {
-                return Err(out_of_memory_error(
-                    size,
-                    (region.size - aligned_alloc.aligned_offset) as u32,
-                    "Region",
-                    Some(region_id),
-                ));
-            }
+        // Calculate optimally aligned allocation
+        let aligned_allocation = region.allocator.calculate_aligned_allocation(size, region.used, optimal_alignment);
+        region.used = aligned_allocation.aligned_offset + aligned_allocation.actual_size;
+        region.allocations.push(self.next_alloc_id - 1);
 
-            (base_ptr, aligned_alloc)
-        };
+        // Runtime-sized allocations start after everything placed statically
+        if let Some(cursor) = region.cursor {
+            builder.func.dfg.replace(cursor.start_offset).iconst(self.pointer_type, region.used as i64);
+        }
 
         // Generate allocation address with optimal alignment
-        let offset_val = builder.ins().iconst(ctypes::I64, aligned_allocation.aligned_offset as i64);
-        let alloc_ptr = builder.ins().iadd(base_ptr, offset_val);
-        
-        // Update region state
-        let region = self.regions.get_mut(&region_id).unwrap();
-        region.used = aligned_allocation.aligned_offset + aligned_allocation.actual_size;
-        region.allocations.push(self.next_alloc_id - 1);
-        
-        Ok(alloc_ptr)
+        let offset_val = builder.ins().iconst(self.pointer_type, aligned_allocation.aligned_offset as i64);
+        Ok(builder.ins().iadd(base_ptr, offset_val))
+    }
+
+    /// Region allocation of a value whose size is only known at runtime
+    ///
+    /// Bumps the region's runtime cursor and checks it against the end of the current
+    /// chunk; when the value does not fit, `overflow` (or the region's own policy)
+    /// decides what happens. Fragmentation statistics for these are estimated.
+    pub fn alloc_in_region_dynamic(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        region_id: u32,
+        size: Value,
+        hint: AlignmentHint,
+        overflow: Option<RegionOverflowPolicy>,
+    ) -> CodegenResult<Value> {
+        let region = self.regions.get_mut(&region_id).ok_or_else(|| region_not_found_error(region_id))?;
+        // The size is unknown here, so alignment cannot depend on it
+        let alignment = region.allocator.calculate_optimal_alignment(0, hint);
+        let ptr = self.bump_region_cursor(builder, region_id, size, alignment, overflow)?;
+
+        self.regions.get_mut(&region_id).unwrap().allocations.push(self.next_alloc_id);
+        self.next_alloc_id += 1;
+        self.metrics.region_allocs += 1;
+        Ok(ptr)
+    }
+
+    /// Emit the runtime bump of a region cursor with its overflow handling
+    fn bump_region_cursor(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        region_id: u32,
+        size: Value,
+        alignment: u32,
+        overflow: Option<RegionOverflowPolicy>,
+    ) -> CodegenResult<Value> {
+        let region = self.regions.get_mut(&region_id).ok_or_else(|| region_not_found_error(region_id))?;
+        let policy = overflow.unwrap_or(region.overflow);
+        if policy == RegionOverflowPolicy::GrowRegion && region.overflow != RegionOverflowPolicy::GrowRegion {
+            return Err(invalid_allocation_error(
+                "Region",
+                format!("Region {} cannot grow", region_id),
+                "Create the region with RegionOverflowPolicy::GrowRegion so its chunks are freed with it".to_string(),
+            ));
+        }
+        let cursor = region.cursor.ok_or_else(|| region_not_initialized_error(region_id))?;
+        region.allocator.record_runtime_sized_allocation();
+
+        let pointer_type = self.pointer_type;
+        let word = pointer_type.bytes() as i32;
+        let mask = alignment as i64 - 1;
+        let chunk = builder.ins().stack_load(pointer_type, cursor.slot, 0);
+        let offset = builder.ins().stack_load(pointer_type, cursor.slot, word);
+        let capacity = builder.ins().stack_load(pointer_type, cursor.slot, 2 * word);
+
+        // Align the offset, then check the end against the capacity (and for wrap-around)
+        let rounded = builder.ins().iadd_imm(offset, mask);
+        let aligned = builder.ins().band_imm(rounded, !mask);
+        let end = builder.ins().iadd(aligned, size);
+        let within_capacity = builder.ins().icmp(IntCC::UnsignedLessThanOrEqual, end, capacity);
+        let no_wrap = builder.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, end, aligned);
+        let fits = builder.ins().band(within_capacity, no_wrap);
+
+        let fit_block = builder.create_block();
+        let overflow_block = builder.create_block();
+        let done_block = builder.create_block();
+        builder.append_block_param(done_block, pointer_type);
+        builder.ins().brif(fits, fit_block, &[], overflow_block, &[]);
+
+        builder.switch_to_block(fit_block);
+        builder.seal_block(fit_block);
+        builder.ins().stack_store(end, cursor.slot, word);
+        let ptr = builder.ins().iadd(chunk, aligned);
+        builder.ins().jump(done_block, &[ptr]);
+
+        builder.switch_to_block(overflow_block);
+        builder.seal_block(overflow_block);
+        match policy {
+            RegionOverflowPolicy::Trap => {
+                builder.ins().trap(TrapCode::HeapOutOfBounds);
+            }
+            RegionOverflowPolicy::FallbackToManual => {
+                let ptr = self.runtime_bridge.generate_malloc_call(builder, size)?;
+                builder.ins().jump(done_block, &[ptr]);
+            }
+            RegionOverflowPolicy::GrowRegion => {
+                // A new chunk starts with a link to the previous one, padded to the alignment
+                let header = (word as i64 + mask) & !mask;
+                let needed = builder.ins().iadd_imm(size, header);
+                let doubled = builder.ins().imul_imm(capacity, 2);
+                let new_capacity = builder.ins().umax(doubled, needed);
+                let new_chunk = self.runtime_bridge.generate_malloc_call(builder, new_capacity)?;
+                builder.ins().store(MemFlags::trusted(), chunk, new_chunk, 0);
+                builder.ins().stack_store(new_chunk, cursor.slot, 0);
+                builder.ins().stack_store(needed, cursor.slot, word);
+                builder.ins().stack_store(new_capacity, cursor.slot, 2 * word);
+                let ptr = builder.ins().iadd_imm(new_chunk, header);
+                builder.ins().jump(done_block, &[ptr]);
+            }
+        }
+
+        builder.switch_to_block(done_block);
+        builder.seal_block(done_block);
+        Ok(builder.block_params(done_block)[0])
     }
 
     /// Stack allocation - fastest for small objects (optimized hot path)
     #[inline(always)] // Force inlining for maximum performance
-    fn alloc_stack(&self, builder: &mut FunctionBuilder, size: u32) -> CodegenResult<Value> {
+    fn alloc_stack(&mut self, builder: &mut FunctionBuilder, size: u32, options: &AllocationOptions) -> CodegenResult<Value> {
+        self.record_stack_allocation(size, options.span, &options.source_location);
+
         // Optimized stack allocation - single instruction generation
         let stack_slot = builder.create_sized_stack_slot(cranelift_codegen::ir::StackSlotData::new(
             cranelift_codegen::ir::StackSlotKind::ExplicitSlot,
@@ -430,7 +927,7 @@ impl BractMemoryManager {
         ));
 
         // Generate single stack_addr instruction - most efficient possible allocation
-        Ok(builder.ins().stack_addr(ctypes::I64, stack_slot, 0))
+        Ok(builder.ins().stack_addr(self.pointer_type, stack_slot, 0))
     }
 
         /// Create memory region for grouped allocation with alignment optimization
@@ -452,12 +949,21 @@ impl BractMemoryManager {
             allocations: Vec::new(),
             allocator: OptimizedRegionAllocator::new(alignment, 64), // 64-byte cache lines
             default_alignment: alignment,
+            overflow: RegionOverflowPolicy::default(),
+            cursor: None,
         };
         
         self.regions.insert(region_id, region);
         region_id
     }
 
+    /// Create memory region whose runtime-sized allocations follow `overflow` when it is full
+    pub fn create_region_with_policy(&mut self, name: String, size: u64, overflow: RegionOverflowPolicy) -> u32 {
+        let region_id = self.create_region(name, size);
+        self.regions.get_mut(&region_id).unwrap().overflow = overflow;
+        region_id
+    }
+
     /// Initialize region with actual memory allocation
     pub fn initialize_region(&mut self, builder: &mut FunctionBuilder, region_id: u32) -> CodegenResult<Value> {
         let size = {
@@ -474,13 +980,68 @@ impl BractMemoryManager {
         // Allocate region memory
         let base_ptr = self.alloc_manual(builder, size as u32)?;
 
+        // Runtime cursor: the first chunk is the region memory itself
+        let word = self.pointer_type.bytes();
+        let slot = builder.create_sized_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 3 * word));
+        let start = builder.ins().iconst(self.pointer_type, 0);
+        let capacity = builder.ins().iconst(self.pointer_type, size as i64);
+        builder.ins().stack_store(base_ptr, slot, 0);
+        builder.ins().stack_store(start, slot, word as i32);
+        builder.ins().stack_store(capacity, slot, 2 * word as i32);
+        let start_offset = builder.func.dfg.value_def(start).unwrap_inst();
+
         // Store base pointer in region
         let region = self.regions.get_mut(&region_id).unwrap();
         region.base_ptr = Some(base_ptr);
+        region.cursor = Some(RegionCursor { slot, start_offset });
 
         Ok(base_ptr)
     }
 
+    /// Release a region in bulk by freeing its base pointer (and, for a growing
+    /// region, every chunk chained in front of it)
+    ///
+    /// Called once on every path that leaves the region block, so early returns
+    /// free the region as well as the fall-through exit.
+    pub fn release_region(&mut self, builder: &mut FunctionBuilder, region_id: u32) -> CodegenResult<()> {
+        let region = self.regions.get(&region_id)
+            .filter(|region| region.base_ptr.is_some())
+            .ok_or_else(|| invalid_allocation_error(
+                "Region",
+                format!("Cannot release uninitialized region {}", region_id),
+                "Call initialize_region() before releasing the region".to_string(),
+            ))?;
+        let base_ptr = region.base_ptr.unwrap();
+
+        if let (RegionOverflowPolicy::GrowRegion, Some(cursor)) = (region.overflow, region.cursor) {
+            // Walk back from the newest chunk, each starting with a link to the previous one
+            let pointer_type = self.pointer_type;
+            let newest = builder.ins().stack_load(pointer_type, cursor.slot, 0);
+            let walk_block = builder.create_block();
+            let free_block = builder.create_block();
+            let done_block = builder.create_block();
+            builder.append_block_param(walk_block, pointer_type);
+            builder.ins().jump(walk_block, &[newest]);
+
+            builder.switch_to_block(walk_block);
+            let chunk = builder.block_params(walk_block)[0];
+            let is_first = builder.ins().icmp(IntCC::Equal, chunk, base_ptr);
+            builder.ins().brif(is_first, done_block, &[], free_block, &[]);
+
+            builder.switch_to_block(free_block);
+            builder.seal_block(free_block);
+            let previous = builder.ins().load(pointer_type, MemFlags::trusted(), chunk, 0);
+            self.runtime_bridge.generate_free_call(builder, chunk)?;
+            builder.ins().jump(walk_block, &[previous]);
+            builder.seal_block(walk_block);
+
+            builder.switch_to_block(done_block);
+            builder.seal_block(done_block);
+        }
+
+        self.runtime_bridge.generate_free_call(builder, base_ptr)
+    }
+
     /// Move linear type with ownership transfer
     pub fn move_linear(&mut self, from: Value, to: Value, move_location: &str) -> CodegenResult<()> {
         let mut ownership = self.linear_ownership.remove(&from).ok_or_else(|| 
@@ -499,9 +1060,10 @@ impl BractMemoryManager {
             ));
         }
 
-        // Mark original as moved and update generation
+        // Mark original as moved (remembering where) and update generation
         ownership.is_moved = true;
         ownership.move_generation += 1;
+        ownership.source_location = move_location.to_string();
         self.linear_ownership.insert(from, ownership.clone());
         self.metrics.use_after_move_prevented += 1;
 
@@ -531,34 +1093,236 @@ impl BractMemoryManager {
 
     /// Estimate allocation cost in CPU cycles (for performance profiling)
     fn estimate_allocation_cost(&self, strategy: MemoryStrategy, size: u32) -> u64 {
-        match strategy {
-            MemoryStrategy::Stack => 1,        // ~1 cycle - just stack pointer adjustment
-            MemoryStrategy::Manual => 50 + (size as u64 / 64), // malloc overhead + size factor
-            MemoryStrategy::Region => 5 + (size as u64 / 128), // bump allocator + size factor  
-            MemoryStrategy::Linear => 3,       // stack alloc + ownership tracking
-            MemoryStrategy::SmartPtr => 80 + (size as u64 / 32), // malloc + ref count + tracking
-        }
+        strategy.allocation_cycles(size)
     }
 
-    /// Clean up function memory (called at function end)
+    /// Clean up function memory (emitted before every return point of a function)
+    ///
+    /// Drops the references the function owns to smart pointer allocations no scope
+    /// holds, in allocation order; scopes release their own as code leaves them.
     pub fn cleanup_function(&mut self, builder: &mut FunctionBuilder) -> CodegenResult<()> {
-        // Decrement all smart pointer reference counts
-        let smart_ptrs: Vec<Value> = self.smart_pointers.keys().copied().collect();
+        self.release_unscoped_smart_pointers(builder, 0)?;
+
+        // Spilled linear values that stay in the function end with it
+        for &(slot, alloc_id) in &self.heap_spills {
+            if !self.leak_tracker.leaves_function(alloc_id) {
+                let ptr = builder.ins().stack_load(self.pointer_type, slot, 0);
+                self.runtime_bridge.generate_free_call(builder, ptr)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Count a stack allocation of the current function towards its stack usage
+    pub fn record_stack_allocation(&mut self, size: u32, span: Option<Span>, source_location: &str) {
+        self.stack_allocations.push(StackAllocation { size, span, source_location: source_location.to_string() });
+    }
+
+    /// Check the stack the current function's allocations take against the policy
+    ///
+    /// A function taking more than `MemoryPolicy::max_function_stack` gets a
+    /// `StackUsageWarning` naming its largest allocations, or with
+    /// `MemoryPolicy::strict_stack_limit` an error located at the largest.
+    pub fn check_stack_usage(&mut self, function_name: &str) -> CodegenResult<()> {
+        let total_bytes: u64 = self.stack_allocations.iter().map(|allocation| u64::from(allocation.size)).sum();
+        if total_bytes <= u64::from(self.policy.max_function_stack) {
+            return Ok(());
+        }
+        let mut largest = self.stack_allocations.clone();
+        largest.sort_by_key(|allocation| std::cmp::Reverse(allocation.size));
+        largest.truncate(REPORTED_STACK_ALLOCATIONS);
+        let warning = StackUsageWarning {
+            function_name: function_name.to_string(),
+            total_bytes,
+            limit: self.policy.max_function_stack,
+            largest,
+        };
+        if self.policy.strict_stack_limit {
+            let error = CodegenError::MemoryManagement(warning.message());
+            return Err(match warning.span() {
+                Some(span) => error.at(span),
+                None => error,
+            });
+        }
+        self.stack_warnings.push(warning);
+        Ok(())
+    }
+
+    /// Check whether a value is a smart pointer allocated in the current function
+    pub fn is_smart_pointer(&self, ptr: Value) -> bool {
+        self.smart_pointers.contains_key(&ptr)
+    }
+
+    /// Take another reference to a smart pointer (value duplicated by assignment)
+    ///
+    /// The reference joins the ones the function already owns, released together.
+    pub fn retain_smart_pointer(&mut self, builder: &mut FunctionBuilder, ptr: Value) -> CodegenResult<()> {
+        let smart_ptr = self.smart_pointers.get_mut(&ptr).ok_or_else(|| smart_pointer_error(ptr))?;
+        smart_ptr.owners += 1;
+        let ref_count_ptr = smart_ptr.ref_count_ptr;
+
+        Self::emit_ref_count_adjust(builder, ref_count_ptr, 1);
+        Ok(())
+    }
+
+    /// Take a reference on behalf of a holder outside the current function (a caller,
+    /// a longer-lived object or a closure), which `cleanup_function` leaves in place
+    pub fn share_smart_pointer(&mut self, builder: &mut FunctionBuilder, ptr: Value) -> CodegenResult<()> {
+        let ref_count_ptr = self.smart_pointers.get(&ptr).ok_or_else(|| smart_pointer_error(ptr))?.ref_count_ptr;
+        Self::emit_ref_count_adjust(builder, ref_count_ptr, 1);
+        Ok(())
+    }
+
+    /// Hand the references to a fresh smart pointer allocation over to a scope, which
+    /// releases them when it ends; false if `ptr` is not one
+    pub fn claim_smart_pointer(&mut self, ptr: Value) -> bool {
+        match self.smart_pointers.get_mut(&ptr) {
+            Some(smart_ptr) if !smart_ptr.scoped && smart_ptr.owners == 1 => {
+                smart_ptr.scoped = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Take over the reference a fresh smart pointer allocation starts with, returning
+    /// the offset of its reference count; None if `ptr` has no such reference left
+    pub fn take_smart_pointer(&mut self, ptr: Value) -> Option<u32> {
+        let smart_ptr = self.smart_pointers.get_mut(&ptr)?;
+        if smart_ptr.scoped || smart_ptr.owners != 1 {
+            return None;
+        }
+        smart_ptr.owners = 0;
+        Some(smart_ptr.ref_count_offset)
+    }
+
+    /// Offset of the reference count of a smart pointer allocated in the current function
+    pub fn smart_pointer_ref_count_offset(&self, ptr: Value) -> Option<u32> {
+        self.smart_pointers.get(&ptr).map(|smart_ptr| smart_ptr.ref_count_offset)
+    }
+
+    /// Drop every reference the function owns to a smart pointer, freeing it when the
+    /// count reaches zero
+    ///
+    /// The references go in one step behind a single guarded free, however many
+    /// variables share them. Nothing happens on a path that never allocated `ptr`.
+    pub fn release_smart_pointer(&mut self, builder: &mut FunctionBuilder, ptr: Value) -> CodegenResult<()> {
+        let (slot, ref_count_offset, owners) = match self.smart_pointers.get(&ptr) {
+            Some(smart_ptr) => (smart_ptr.slot, smart_ptr.ref_count_offset, smart_ptr.owners),
+            None => return Ok(()),
+        };
+        if owners == 0 {
+            return Ok(());
+        }
+        self.emit_slot_release(builder, slot, ref_count_offset, owners)
+    }
+
+    /// Forget the references to a smart pointer once the scope holding them has ended
+    pub fn end_smart_pointer(&mut self, ptr: Value) {
+        if let Some(smart_ptr) = self.smart_pointers.get_mut(&ptr) {
+            smart_ptr.owners = 0;
+        }
+    }
+
+    /// Release the references of smart pointers allocated since `first_alloc_id` that no
+    /// scope holds, as the end of a loop iteration does
+    pub fn release_unscoped_smart_pointers(&mut self, builder: &mut FunctionBuilder, first_alloc_id: u32) -> CodegenResult<()> {
+        let mut smart_ptrs: Vec<Value> = self.smart_pointers.values()
+            .filter(|smart_ptr| !smart_ptr.scoped && smart_ptr.alloc_id >= first_alloc_id)
+            .map(|smart_ptr| smart_ptr.ptr)
+            .collect();
+        smart_ptrs.sort();
         for ptr in smart_ptrs {
-            self.decrement_smart_ptr_ref(builder, ptr)?;
+            self.release_smart_pointer(builder, ptr)?;
         }
+        Ok(())
+    }
 
-        // Clear tracking structures for function scope
-        self.smart_pointers.clear();
-        self.linear_ownership.clear();
+    /// Id the next allocation of the current function will get
+    pub fn next_alloc_id(&self) -> u32 {
+        self.next_alloc_id
+    }
+
+    /// Create a slot to hold a reference to a smart pointer whose count is at `ref_count_offset`
+    pub fn new_smart_pointer_ref(&mut self, builder: &mut FunctionBuilder, ref_count_offset: u32) -> SmartPointerRef {
+        SmartPointerRef { slot: self.null_pointer_slot(builder), ref_count_offset }
+    }
+
+    /// Take a reference to the smart pointer `ptr`, whose count is at `ref_count_offset`,
+    /// for a holder releasing it on its own
+    pub fn retain_smart_pointer_at(&mut self, builder: &mut FunctionBuilder, ptr: Value, ref_count_offset: u32) {
+        let ref_count_ptr = builder.ins().iadd_imm(ptr, i64::from(ref_count_offset));
+        Self::emit_ref_count_adjust(builder, ref_count_ptr, 1);
+    }
+
+    /// Drop the reference held in `reference`'s slot, if any, freeing the allocation
+    /// when the count reaches zero
+    pub fn release_smart_pointer_ref(&mut self, builder: &mut FunctionBuilder, reference: SmartPointerRef) -> CodegenResult<()> {
+        self.emit_slot_release(builder, reference.slot, reference.ref_count_offset, 1)
+    }
 
+    /// Emit the release of `count` references to the smart pointer held in `slot`, which
+    /// is cleared; a null slot releases nothing
+    fn emit_slot_release(&mut self, builder: &mut FunctionBuilder, slot: StackSlot, ref_count_offset: u32, count: u32) -> CodegenResult<()> {
+        let held_block = builder.create_block();
+        let released_block = builder.create_block();
+
+        let ptr = builder.ins().stack_load(self.pointer_type, slot, 0);
+        builder.ins().brif(ptr, held_block, &[], released_block, &[]);
+
+        builder.switch_to_block(held_block);
+        builder.seal_block(held_block);
+        let ref_count_ptr = builder.ins().iadd_imm(ptr, i64::from(ref_count_offset));
+        let new_count = Self::emit_ref_count_adjust(builder, ref_count_ptr, -i64::from(count));
+        self.emit_free_if_zero(builder, ptr, new_count)?;
+        let null = builder.ins().iconst(self.pointer_type, 0);
+        builder.ins().stack_store(null, slot, 0);
+        builder.ins().jump(released_block, &[]);
+
+        builder.switch_to_block(released_block);
+        builder.seal_block(released_block);
         Ok(())
     }
 
-    /// Decrement smart pointer reference count using runtime bridge
-    fn decrement_smart_ptr_ref(&mut self, builder: &mut FunctionBuilder, ptr: Value) -> CodegenResult<()> {
-        // Use runtime bridge for proper ARC decrement
-        self.runtime_bridge.generate_arc_dec_call(builder, ptr)
+    /// Lend the smart pointer `ptr` to a callee for the duration of a call
+    pub fn borrow_smart_pointer_for_call(&mut self, builder: &mut FunctionBuilder, ptr: Value, ref_count_offset: u32) {
+        self.retain_smart_pointer_at(builder, ptr, ref_count_offset);
+    }
+
+    /// Return a smart pointer lent to a callee once the call has completed
+    ///
+    /// The caller still owns a reference, so the count cannot reach zero here.
+    pub fn end_smart_pointer_call_borrow(&mut self, builder: &mut FunctionBuilder, ptr: Value, ref_count_offset: u32) {
+        let ref_count_ptr = builder.ins().iadd_imm(ptr, i64::from(ref_count_offset));
+        Self::emit_ref_count_adjust(builder, ref_count_ptr, -1);
+    }
+
+    /// Emit `*ref_count_ptr += delta` and return the updated count
+    fn emit_ref_count_adjust(builder: &mut FunctionBuilder, ref_count_ptr: Value, delta: i64) -> Value {
+        let flags = cranelift::prelude::MemFlags::trusted();
+        let count = builder.ins().load(ctypes::I64, flags, ref_count_ptr, 0);
+        let new_count = builder.ins().iadd_imm(count, delta);
+        builder.ins().store(flags, new_count, ref_count_ptr, 0);
+        new_count
+    }
+
+    /// Emit `if count == 0 { bract_free(ptr) }`, leaving the builder in the continuation block
+    fn emit_free_if_zero(&mut self, builder: &mut FunctionBuilder, ptr: Value, count: Value) -> CodegenResult<()> {
+        let free_block = builder.create_block();
+        let continue_block = builder.create_block();
+
+        let is_zero = builder.ins().icmp_imm(cranelift::prelude::IntCC::Equal, count, 0);
+        builder.ins().brif(is_zero, free_block, &[], continue_block, &[]);
+
+        builder.switch_to_block(free_block);
+        builder.seal_block(free_block);
+        self.runtime_bridge.generate_free_call(builder, ptr)?;
+        builder.ins().jump(continue_block, &[]);
+
+        builder.switch_to_block(continue_block);
+        builder.seal_block(continue_block);
+        Ok(())
     }
 
     /// Free manually allocated memory using runtime bridge
@@ -567,6 +1331,11 @@ impl BractMemoryManager {
         self.runtime_bridge.generate_free_call(builder, ptr)
     }
 
+    /// Allocation counts and safety metrics recorded so far
+    pub fn metrics(&self) -> &MemoryMetrics {
+        &self.metrics
+    }
+
     /// Get comprehensive memory report for debugging
     pub fn memory_report(&self) -> String {
         let strategy_stats = self.metrics.strategy_percentages();
@@ -609,23 +1378,19 @@ impl BractMemoryManager {
     }
 
     /// Generate runtime bounds checking code - optimal performance implementation
+    ///
+    /// Traps unless `index < length`. Both values are pointer-sized; the comparison is
+    /// unsigned, so negative indices (sign bit set) are rejected as well.
     pub fn generate_bounds_check(
-        &self,
+        &mut self,
         builder: &mut FunctionBuilder,
-        ptr: Value,
-        size: Value,
-        access_size: u32,
+        index: Value,
+        length: Value,
     ) -> CodegenResult<()> {
-        // Generate efficient bounds check: if (ptr + access_size > ptr + size) trap()
-        let access_size_val = builder.ins().iconst(ctypes::I64, access_size as i64);
-        let ptr_end = builder.ins().iadd(ptr, size);
-        let access_end = builder.ins().iadd(ptr, access_size_val);
-        
-        // Check: access_end <= ptr_end (unsigned comparison for pointer arithmetic)
-        let bounds_ok = builder.ins().icmp(
-            cranelift::prelude::IntCC::UnsignedLessThanOrEqual, 
-            access_end, 
-            ptr_end
+        let in_bounds = builder.ins().icmp(
+            cranelift::prelude::IntCC::UnsignedLessThan,
+            index,
+            length
         );
         
         // Create trap block for bounds violation - efficient branch prediction
@@ -633,15 +1398,18 @@ impl BractMemoryManager {
         let continue_block = builder.create_block();
         
         // Branch with hint that bounds check usually succeeds (branch prediction optimization)
-        builder.ins().brif(bounds_ok, continue_block, &[], trap_block, &[]);
+        builder.ins().brif(in_bounds, continue_block, &[], trap_block, &[]);
         
         // Trap block - immediate termination with specific error code
         builder.switch_to_block(trap_block);
+        builder.seal_block(trap_block);
         builder.ins().trap(cranelift::prelude::TrapCode::HeapOutOfBounds);
         
         // Continue block - normal execution path
         builder.switch_to_block(continue_block);
+        builder.seal_block(continue_block);
         
+        self.metrics.bounds_violations_prevented += 1;
         Ok(())
     }
 
@@ -670,13 +1438,27 @@ impl BractMemoryManager {
         Ok(())
     }
 
-    /// Enter function scope for leak tracking
-    pub fn enter_function_scope(&mut self) {
-        self.leak_tracker.enter_function();
+    /// Import the runtime functions into the function about to be compiled
+    ///
+    /// Must be called once per function before any allocation is emitted into it.
+    pub fn prepare_function(&mut self, module: ModuleView, func: &mut Function) -> CodegenResult<()> {
+        self.runtime_bridge.import_into_function(module, func)
+    }
+
+    /// Enter the scope of `function_name` for leak tracking
+    pub fn enter_function_scope(&mut self, function_name: &str) {
+        self.leak_tracker.enter_function(function_name);
     }
 
     /// Exit function scope and get leak warnings
     pub fn exit_function_scope(&mut self) -> Vec<LeakWarning> {
+        // Values and function references are only meaningful inside the function that created them
+        self.smart_pointers.clear();
+        self.linear_ownership.clear();
+        self.stack_allocations.clear();
+        self.heap_spills.clear();
+        self.runtime_bridge.release_function_refs();
+
         self.leak_tracker.exit_function()
     }
 
@@ -685,25 +1467,49 @@ impl BractMemoryManager {
         self.leak_tracker.generate_leak_report()
     }
 
+    /// Leaks detected in every function compiled so far, grouped by function
+    pub fn get_detected_leaks(&self) -> impl Iterator<Item = &LeakWarning> {
+        self.leak_tracker.get_detected_leaks()
+    }
+
     /// Mark allocation as manually freed (for manual strategy)
+    ///
+    /// Freeing an allocation made by another function (a returned manual pointer) is
+    /// fine: that allocation escaped, so its own function doesn't report it.
     pub fn mark_allocation_freed(&mut self, alloc_id: u32) {
-        self.leak_tracker.mark_freed(alloc_id);
-        self.metrics.memory_leaks_prevented += 1;
+        if self.leak_tracker.mark_freed(alloc_id) {
+            self.metrics.memory_leaks_prevented += 1;
+        }
     }
 
     /// Update escape analysis for better leak detection
-    pub fn update_escape_analysis(&mut self, alloc_id: u32, escapes_function: bool, confidence: u8) {
-        self.leak_tracker.update_escape_analysis(alloc_id, escapes_function, confidence);
+    pub fn update_escape_analysis(&mut self, alloc_id: u32, escape_analysis: EscapeInfo) {
+        self.leak_tracker.update_escape_analysis(alloc_id, escape_analysis);
     }
 
     /// Add reference for cycle detection
-    pub fn add_smart_pointer_reference(&mut self, from_ptr: Value, to_ptr: Value) {
-        self.cycle_detector.add_reference(from_ptr, to_ptr);
+    pub fn add_smart_pointer_reference(&mut self, from_ptr: Value, to_ptr: Value) -> CodegenResult<()> {
+        let (from_id, to_id) = (self.smart_pointer_alloc_id(from_ptr)?, self.smart_pointer_alloc_id(to_ptr)?);
+        self.cycle_detector.add_reference(from_id, to_id);
+        Ok(())
     }
 
     /// Remove reference for cycle detection
-    pub fn remove_smart_pointer_reference(&mut self, from_ptr: Value, to_ptr: Value) {
-        self.cycle_detector.remove_reference(from_ptr, to_ptr);
+    pub fn remove_smart_pointer_reference(&mut self, from_ptr: Value, to_ptr: Value) -> CodegenResult<()> {
+        let (from_id, to_id) = (self.smart_pointer_alloc_id(from_ptr)?, self.smart_pointer_alloc_id(to_ptr)?);
+        self.cycle_detector.remove_reference(from_id, to_id);
+        Ok(())
+    }
+
+    /// Allocation a smart pointer of the current function was registered with
+    fn smart_pointer_alloc_id(&self, ptr: Value) -> CodegenResult<u64> {
+        self.smart_pointers.get(&ptr)
+            .map(|smart_ptr| u64::from(smart_ptr.alloc_id))
+            .ok_or_else(|| invalid_allocation_error(
+                MemoryStrategy::SmartPtr.name(),
+                format!("{} is not a smart pointer allocated in this function", ptr),
+                "Only pointers returned by allocate() with the SmartPtr strategy take part in cycle detection".to_string(),
+            ))
     }
 
     /// Run cycle detection and break cycles
@@ -729,6 +1535,11 @@ impl BractMemoryManager {
         self.profiler.record_sample(current_memory_kb, &self.metrics);
     }
 
+    /// Record performance sample using the memory usage in the current metrics
+    pub fn record_performance_sample_now(&mut self) {
+        self.profiler.record_sample_now(&self.metrics);
+    }
+
     /// Record allocation hotspot
     pub fn record_allocation_hotspot(&mut self, location: String, size: u32, strategy: MemoryStrategy) {
         self.profiler.record_hotspot(location, size, strategy);
@@ -739,6 +1550,16 @@ impl BractMemoryManager {
         self.profiler.generate_performance_report()
     }
 
+    /// Get the profile as JSON (see `MemoryProfiler::to_json`)
+    pub fn get_performance_json(&self) -> String {
+        self.profiler.to_json()
+    }
+
+    /// Get the profile as a Chrome trace (see `MemoryProfiler::to_chrome_trace`)
+    pub fn get_performance_trace(&self) -> String {
+        self.profiler.to_chrome_trace()
+    }
+
     /// Get top allocation hotspots
     pub fn get_top_allocation_hotspots(&self, limit: usize) -> Vec<&AllocationHotspot> {
         self.profiler.get_top_hotspots(limit)
@@ -820,47 +1641,192 @@ pub struct AllocationOptions {
     pub region_id: Option<u32>,
     /// Source location for error reporting
     pub source_location: String,
+    /// Span of the allocated value, carried into leak warnings for editors
+    pub span: Option<Span>,
     /// Custom alignment requirement
     pub alignment: Option<u32>,
     /// Whether this allocation can trigger GC
     pub gc_allowed: bool,
+    /// Overflow policy overriding the region's own for this allocation
+    pub region_overflow: Option<RegionOverflowPolicy>,
 }
 
+/// Largest alignment a memory annotation may request
+pub const MAX_ANNOTATION_ALIGNMENT: u32 = 4096;
+
 /// Memory annotation attributes parsed from user code
 #[derive(Debug, Clone, PartialEq)]
 pub enum MemoryAnnotation {
     Manual,
     Smart,
     Linear,
-    Region(String),
+    Region(String, Option<RegionOverflowPolicy>),
     Stack,
     NoGC,
     Align(u32),
+    /// `@memory(strategy = ..., size_hint = ..., alignment = ..., region = ...)`
+    Memory(crate::parser::MemoryAnnotation),
+}
+
+impl MemoryAnnotation {
+    /// Read the memory annotation `attribute` carries, None for other attributes
+    ///
+    /// Argument values are validated here, with errors pointing at the attribute.
+    pub fn from_attribute(attribute: &Attribute, interner: &StringInterner) -> CodegenResult<Option<Self>> {
+        let error = |message: String, suggestion: &str| annotation_error(&attribute.span, message, suggestion);
+        let name = interner.resolve(attribute.name).unwrap_or_default();
+        let resolve = |value: InternedString| interner.resolve(value).unwrap_or_default();
+        let positional: Option<Vec<&Literal>> = attribute.args.iter()
+            .map(|arg| arg.key.is_none().then_some(&arg.value))
+            .collect();
+
+        let annotation = match (name.as_str(), positional.as_deref()) {
+            ("manual", Some([])) => MemoryAnnotation::Manual,
+            ("smart", Some([])) => MemoryAnnotation::Smart,
+            ("linear", Some([])) => MemoryAnnotation::Linear,
+            ("stack", Some([])) => MemoryAnnotation::Stack,
+            ("nogc", Some([])) => MemoryAnnotation::NoGC,
+            ("manual" | "smart" | "linear" | "stack" | "nogc", _) => {
+                return Err(error(format!("@{} takes no arguments", name), &format!("Write @{} on its own", name)));
+            }
+            ("region", Some([Literal::String { value: region, .. }])) => MemoryAnnotation::Region(resolve(*region), None),
+            ("region", Some([Literal::String { value: region, .. }, Literal::String { value: policy, .. }])) => {
+                let policy_name = resolve(*policy);
+                let policy = RegionOverflowPolicy::parse(&policy_name).ok_or_else(|| error(
+                    format!("Unknown region overflow policy '{}'", policy_name),
+                    "Use trap, fallback or grow",
+                ))?;
+                MemoryAnnotation::Region(resolve(*region), Some(policy))
+            }
+            ("region", _) => {
+                return Err(error("@region takes a region name and an optional overflow policy".to_string(), "Write @region(temp) or @region(temp, grow)"));
+            }
+            ("align", Some([Literal::Integer { value, .. }])) => MemoryAnnotation::Align(value.parse().map_err(|_| error(
+                format!("Alignment {} is out of range", value),
+                &format!("Use a power of two up to {}", MAX_ANNOTATION_ALIGNMENT),
+            ))?),
+            ("align", _) => {
+                return Err(error("@align takes a single integer alignment".to_string(), "Write @align(16)"));
+            }
+            ("memory", _) => MemoryAnnotation::Memory(crate::parser::memory_annotation_of(attribute, interner)
+                .map_err(|parse_error| error(parse_error.to_string(), "Write @memory(strategy = \"region\", size_hint = 1024)"))?),
+            _ => return Ok(None),
+        };
+
+        let alignment = match &annotation {
+            MemoryAnnotation::Align(alignment) => Some(*alignment),
+            MemoryAnnotation::Memory(memory) => memory.alignment,
+            _ => None,
+        };
+        if let Some(alignment) = alignment.filter(|alignment| !alignment.is_power_of_two() || *alignment > MAX_ANNOTATION_ALIGNMENT) {
+            return Err(error(
+                format!("Alignment {} must be a power of two no larger than {}", alignment, MAX_ANNOTATION_ALIGNMENT),
+                &format!("Use {}", alignment.checked_next_power_of_two().unwrap_or(MAX_ANNOTATION_ALIGNMENT).min(MAX_ANNOTATION_ALIGNMENT)),
+            ));
+        }
+        if let MemoryAnnotation::Memory(crate::parser::MemoryAnnotation { size_hint: Some(0), .. }) = annotation {
+            return Err(error("Size hint must be positive".to_string(), "Give the expected size in bytes, or remove size_hint"));
+        }
+        Ok(Some(annotation))
+    }
 }
 
-/// Parse memory annotation from source code
-pub fn parse_annotation(text: &str) -> Option<MemoryAnnotation> {
-    match text {
-        "@manual" => Some(MemoryAnnotation::Manual),
-        "@smart" => Some(MemoryAnnotation::Smart),
-        "@linear" => Some(MemoryAnnotation::Linear),
-        "@stack" => Some(MemoryAnnotation::Stack),
-        "@nogc" => Some(MemoryAnnotation::NoGC),
-        s if s.starts_with("@region(") && s.ends_with(")") => {
-            let name = &s[8..s.len()-1];
-            Some(MemoryAnnotation::Region(name.to_string()))
-        },
-        s if s.starts_with("@align(") && s.ends_with(")") => {
-            if let Ok(align) = s[7..s.len()-1].parse::<u32>() {
-                Some(MemoryAnnotation::Align(align))
-            } else {
-                None
+/// Memory requests of a function, from its memory annotations
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryAttributes {
+    /// Strategy for allocations whose type requests none
+    pub strategy: Option<MemoryStrategy>,
+    /// Region declared by `@region`, live for the whole function
+    pub region: Option<(String, RegionOverflowPolicy)>,
+    /// Expected size of that region (`@memory(size_hint = N)`)
+    pub size_hint: Option<u64>,
+    /// Alignment of the function's region allocations
+    pub alignment: Option<u32>,
+    /// `@nogc` was given
+    pub no_gc: bool,
+}
+
+impl MemoryAttributes {
+    /// Collect and validate the memory annotations among `attributes`
+    ///
+    /// A region named by `@memory(region = ...)` must be declared by the function's
+    /// `@region` or by one of the region blocks in `declared_regions`.
+    pub fn from_attributes(
+        attributes: &[Attribute],
+        interner: &StringInterner,
+        source_map: &SourceMap,
+        declared_regions: &HashSet<String>,
+    ) -> CodegenResult<Self> {
+        let mut result = MemoryAttributes::default();
+        let mut strategy_span: Option<Span> = None;
+        let mut region_references = Vec::new();
+
+        for attribute in attributes {
+            let Some(annotation) = MemoryAnnotation::from_attribute(attribute, interner)? else {
+                continue;
+            };
+            let error = |message: String, suggestion: &str| annotation_error(&attribute.span, message, suggestion);
+            let mut strategy = None;
+            match annotation {
+                MemoryAnnotation::Manual => strategy = Some(MemoryStrategy::Manual),
+                MemoryAnnotation::Smart => strategy = Some(MemoryStrategy::SmartPtr),
+                MemoryAnnotation::Linear => strategy = Some(MemoryStrategy::Linear),
+                MemoryAnnotation::Stack => strategy = Some(MemoryStrategy::Stack),
+                MemoryAnnotation::NoGC => result.no_gc = true,
+                MemoryAnnotation::Align(alignment) => result.alignment = Some(alignment),
+                MemoryAnnotation::Region(name, overflow) => {
+                    if result.region.is_some() {
+                        return Err(error("A function can declare only one region with @region".to_string(), "Use region blocks for further regions"));
+                    }
+                    strategy = Some(MemoryStrategy::Region);
+                    result.region = Some((name, overflow.unwrap_or_default()));
+                }
+                MemoryAnnotation::Memory(memory) => {
+                    strategy = memory.strategy.and_then(super::utils::from_ast_strategy);
+                    result.size_hint = memory.size_hint.or(result.size_hint);
+                    result.alignment = memory.alignment.or(result.alignment);
+                    if let Some(region) = memory.region {
+                        region_references.push((interner.resolve(region).unwrap_or_default(), attribute.span));
+                    }
+                }
+            }
+
+            match (strategy, result.strategy) {
+                // Semantic analysis rejects conflicting strategy annotations
+                (Some(new), Some(old)) if new != old => {
+                    let previous = strategy_span.map(|span| source_map.location(&span.start)).unwrap_or_default();
+                    return Err(CodegenError::InternalError(format!(
+                        "{} allocation conflicts with the {} allocation requested at {}",
+                        new.name(), old.name(), previous
+                    )).at(attribute.span));
+                }
+                (Some(new), _) => {
+                    result.strategy = Some(new);
+                    strategy_span = Some(attribute.span);
+                }
+                (None, _) => {}
             }
-        },
-        _ => None,
+        }
+
+        for (name, span) in region_references {
+            let declared = result.region.as_ref().is_some_and(|(region, _)| *region == name) || declared_regions.contains(&name);
+            if !declared {
+                return Err(annotation_error(
+                    &span,
+                    format!("Region '{}' is not declared", name),
+                    &format!("Add @region({}) to the function or a `region {} {{ ... }}` block", name, name),
+                ));
+            }
+        }
+        Ok(result)
     }
 }
 
+/// Error for an invalid memory annotation, located at its span
+fn annotation_error(span: &Span, message: String, suggestion: &str) -> CodegenError {
+    CodegenError::MemoryManagement(format!("{}\nSuggestion: {}", message, suggestion)).at(*span)
+}
+
 /// Helper functions for creating descriptive errors
 pub fn invalid_allocation_error(strategy: &str, reason: String, suggestion: String) -> CodegenError {
     CodegenError::InternalError(format!(
@@ -869,6 +1835,22 @@ pub fn invalid_allocation_error(strategy: &str, reason: String, suggestion: Stri
     ))
 }
 
+fn region_not_found_error(region_id: u32) -> CodegenError {
+    invalid_allocation_error(
+        "Region",
+        format!("Region {} does not exist", region_id),
+        format!("Create region {} using create_region() before allocating", region_id),
+    )
+}
+
+fn region_not_initialized_error(region_id: u32) -> CodegenError {
+    invalid_allocation_error(
+        "Region",
+        format!("Region {} not initialized", region_id),
+        "Call initialize_region() before allocating in region".to_string(),
+    )
+}
+
 pub fn out_of_memory_error(requested: u32, available: u32, strategy: &str, region_id: Option<u32>) -> CodegenError {
     let region_info = region_id.map(|id| format!(" in region {}", id)).unwrap_or_default();
     CodegenError::InternalError(format!(
@@ -884,19 +1866,34 @@ pub fn linear_type_violation_error(violation: String, source_location: String, s
     ))
 }
 
+pub fn smart_pointer_error(ptr: Value) -> CodegenError {
+    CodegenError::MemoryManagement(format!(
+        "Value {} is not a smart pointer allocated in the current function", ptr
+    ))
+}
+
 pub fn runtime_not_initialized_error(msg: String) -> CodegenError {
     CodegenError::InternalError(format!("Runtime not initialized: {}", msg))
 } 
 
-/// Allocation tracking for leak detection
+/// Leak detection summary for a module
+///
+/// Allocations are tracked by the `FunctionLeakScope` of the function being compiled;
+/// the warnings each scope produces when its function ends are kept here, by function.
 #[derive(Debug, Clone)]
 pub struct AllocationTracker {
-    /// All allocations made in current scope
-    allocations: HashMap<u32, AllocationInfo>,
-    /// Function-scope allocation stacks
-    function_stacks: Vec<Vec<u32>>,
-    /// Detected potential leaks
-    potential_leaks: Vec<LeakWarning>,
+    /// Scope of the function being compiled
+    current: Option<FunctionLeakScope>,
+    /// Leaks found in every compiled function, by function name
+    leaks_by_function: BTreeMap<String, Vec<LeakWarning>>,
+}
+
+/// Allocations made while compiling one function, checked for leaks when it ends
+#[derive(Debug, Clone)]
+pub struct FunctionLeakScope {
+    function_name: String,
+    /// Allocations made by this function, by id
+    allocations: BTreeMap<u32, AllocationInfo>,
 }
 
 /// Information about a specific allocation for leak tracking
@@ -906,6 +1903,8 @@ pub struct AllocationInfo {
     pub strategy: MemoryStrategy,
     pub size: u32,
     pub source_location: String,
+    /// Span of the allocated value, when it was allocated for source code
+    pub span: Option<Span>,
     pub is_freed: bool,
     pub escape_analysis: EscapeInfo,
 }
@@ -929,6 +1928,8 @@ pub struct LeakWarning {
     pub alloc_id: u32,
     pub strategy: MemoryStrategy,
     pub source_location: String,
+    /// Span of the leaked value, when it was allocated for source code
+    pub span: Option<Span>,
     pub leak_type: LeakType,
     pub severity: LeakSeverity,
     pub suggestion: String,
@@ -949,6 +1950,19 @@ pub enum LeakType {
     EscapeWithoutDealloc,
 }
 
+impl LeakType {
+    /// What is wrong with the allocation, for diagnostics
+    pub fn description(&self) -> &'static str {
+        match self {
+            LeakType::ManualNotFreed => "manual allocation is never freed",
+            LeakType::RegionNotDestroyed => "region is never destroyed",
+            LeakType::LinearDoubleUse => "linear value is used after it was moved",
+            LeakType::SmartPointerCycle => "smart pointers form a reference cycle",
+            LeakType::EscapeWithoutDealloc => "allocation escapes without being released",
+        }
+    }
+}
+
 /// Severity levels for leak warnings
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum LeakSeverity {
@@ -958,166 +1972,272 @@ pub enum LeakSeverity {
     Critical, // Severe leak that will cause major issues
 }
 
-impl AllocationTracker {
-    pub fn new() -> Self {
+impl EscapeInfo {
+    /// Whether the allocation is left for someone outside the function to release
+    pub fn leaves_function(&self) -> bool {
+        self.escapes_function || self.returned || self.stored_globally
+    }
+}
+
+impl Default for EscapeInfo {
+    fn default() -> Self {
         Self {
-            allocations: HashMap::new(),
-            function_stacks: Vec::new(),
-            potential_leaks: Vec::new(),
+            escapes_function: false,
+            stored_globally: false,
+            returned: false,
+            confidence: 50, // Start with medium confidence
         }
     }
+}
+
+impl FunctionLeakScope {
+    pub fn new(function_name: impl Into<String>) -> Self {
+        Self {
+            function_name: function_name.into(),
+            allocations: BTreeMap::new(),
+        }
+    }
+
+    /// Name of the function this scope tracks
+    pub fn function_name(&self) -> &str {
+        &self.function_name
+    }
 
-    /// Track a new allocation
-    pub fn track_allocation(&mut self, result: &AllocationResult, source_location: &str) {
+    /// Track a new allocation made by the function with `options`
+    pub fn track_allocation(&mut self, result: &AllocationResult, options: &AllocationOptions) {
         let allocation = AllocationInfo {
             alloc_id: result.alloc_id,
             strategy: result.strategy,
             size: result.size,
-            source_location: source_location.to_string(),
+            source_location: options.source_location.clone(),
+            span: options.span,
             is_freed: false,
-            escape_analysis: EscapeInfo {
-                escapes_function: false,
-                stored_globally: false,
-                returned: false,
-                confidence: 50, // Start with medium confidence
-            },
+            escape_analysis: EscapeInfo::default(),
         };
-        
         self.allocations.insert(result.alloc_id, allocation);
-        
-        // Add to current function stack
-        if let Some(current_stack) = self.function_stacks.last_mut() {
-            current_stack.push(result.alloc_id);
+    }
+
+    /// Mark an allocation as freed
+    ///
+    /// Returns false for allocations made by another function, e.g. a manual pointer
+    /// returned to this one; those were never this function's to report.
+    pub fn mark_freed(&mut self, alloc_id: u32) -> bool {
+        match self.allocations.get_mut(&alloc_id) {
+            Some(allocation) => {
+                allocation.is_freed = true;
+                true
+            }
+            None => false,
         }
     }
 
-    /// Mark allocation as freed
-    pub fn mark_freed(&mut self, alloc_id: u32) {
+    /// Record what escape analysis found out about an allocation
+    pub fn update_escape_analysis(&mut self, alloc_id: u32, escape_analysis: EscapeInfo) {
         if let Some(allocation) = self.allocations.get_mut(&alloc_id) {
-            allocation.is_freed = true;
+            allocation.escape_analysis = escape_analysis;
         }
     }
 
-    /// Enter a new function scope
-    pub fn enter_function(&mut self) {
-        self.function_stacks.push(Vec::new());
+    /// Whether escape analysis found that an allocation of this function leaves it
+    pub fn leaves_function(&self, alloc_id: u32) -> bool {
+        self.allocations.get(&alloc_id).is_some_and(|allocation| allocation.escape_analysis.leaves_function())
     }
 
-    /// Exit function scope and check for leaks
-    pub fn exit_function(&mut self) -> Vec<LeakWarning> {
-        let mut function_leaks = Vec::new();
-        
-        if let Some(function_allocs) = self.function_stacks.pop() {
-            for &alloc_id in &function_allocs {
-                if let Some(allocation) = self.allocations.get(&alloc_id) {
-                    // Check if allocation needs cleanup
-                    let leak_check = self.analyze_allocation_for_leaks(allocation);
-                    if let Some(warning) = leak_check {
-                        function_leaks.push(warning.clone());
-                        self.potential_leaks.push(warning);
-                    }
-                }
+    /// End the function, reporting its leaks in allocation order
+    pub fn finish(self) -> Vec<LeakWarning> {
+        self.allocations.values()
+            .filter_map(analyze_allocation_for_leaks)
+            .collect()
+    }
+}
+
+/// Analyze allocation for potential leaks
+fn analyze_allocation_for_leaks(allocation: &AllocationInfo) -> Option<LeakWarning> {
+    match allocation.strategy {
+        MemoryStrategy::Manual => {
+            // Escaping allocations are released by whoever they escape to
+            if !allocation.is_freed && !allocation.escape_analysis.leaves_function() {
+                Some(LeakWarning {
+                    alloc_id: allocation.alloc_id,
+                    strategy: allocation.strategy,
+                    source_location: allocation.source_location.clone(),
+                    span: allocation.span,
+                    leak_type: LeakType::ManualNotFreed,
+                    severity: LeakSeverity::Error,
+                    suggestion: "Manual allocations must be explicitly freed with deallocate_manual()".to_string(),
+                })
+            } else {
+                None
             }
+        },
+        MemoryStrategy::SmartPtr => {
+            // Smart pointers handle their own cleanup, but check for cycles
+            // TODO: Implement cycle detection algorithm
+            None
+        },
+        MemoryStrategy::Linear => {
+            // Linear types are automatically cleaned up, but check for double-use
+            None
+        },
+        MemoryStrategy::Region => {
+            // Region allocations are cleaned up with the region
+            None
+        },
+        MemoryStrategy::Stack => {
+            // Stack allocations are automatically cleaned up
+            None
+        },
+    }
+}
+
+impl AllocationTracker {
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            leaks_by_function: BTreeMap::new(),
         }
-        
-        function_leaks
-    }
-
-    /// Analyze allocation for potential leaks
-    fn analyze_allocation_for_leaks(&self, allocation: &AllocationInfo) -> Option<LeakWarning> {
-        match allocation.strategy {
-            MemoryStrategy::Manual => {
-                if !allocation.is_freed && !allocation.escape_analysis.escapes_function {
-                    Some(LeakWarning {
-                        alloc_id: allocation.alloc_id,
-                        strategy: allocation.strategy,
-                        source_location: allocation.source_location.clone(),
-                        leak_type: LeakType::ManualNotFreed,
-                        severity: LeakSeverity::Error,
-                        suggestion: "Manual allocations must be explicitly freed with deallocate_manual()".to_string(),
-                    })
-                } else {
-                    None
-                }
-            },
-            MemoryStrategy::SmartPtr => {
-                // Smart pointers handle their own cleanup, but check for cycles
-                // TODO: Implement cycle detection algorithm
-                None
-            },
-            MemoryStrategy::Linear => {
-                // Linear types are automatically cleaned up, but check for double-use
-                None
-            },
-            MemoryStrategy::Region => {
-                // Region allocations are cleaned up with the region
-                None
-            },
-            MemoryStrategy::Stack => {
-                // Stack allocations are automatically cleaned up
-                None
-            },
+    }
+
+    /// Start tracking the allocations of `function_name`
+    pub fn enter_function(&mut self, function_name: &str) {
+        self.current = Some(FunctionLeakScope::new(function_name));
+    }
+
+    /// End the current function, recording and returning its leaks
+    pub fn exit_function(&mut self) -> Vec<LeakWarning> {
+        let Some(scope) = self.current.take() else {
+            return Vec::new();
+        };
+        let function_name = scope.function_name().to_string();
+        let leaks = scope.finish();
+        if !leaks.is_empty() {
+            self.leaks_by_function.entry(function_name).or_default().extend(leaks.iter().cloned());
         }
+        leaks
     }
 
-    /// Update escape analysis for an allocation
-    pub fn update_escape_analysis(&mut self, alloc_id: u32, escapes: bool, confidence: u8) {
-        if let Some(allocation) = self.allocations.get_mut(&alloc_id) {
-            allocation.escape_analysis.escapes_function = escapes;
-            allocation.escape_analysis.confidence = confidence;
+    /// Track a new allocation; allocations made outside of any function are not tracked
+    pub fn track_allocation(&mut self, result: &AllocationResult, options: &AllocationOptions) {
+        if let Some(scope) = &mut self.current {
+            scope.track_allocation(result, options);
+        }
+    }
+
+    /// Mark allocation as freed, returning false if the current function didn't make it
+    pub fn mark_freed(&mut self, alloc_id: u32) -> bool {
+        self.current.as_mut().is_some_and(|scope| scope.mark_freed(alloc_id))
+    }
+
+    /// Update escape analysis for an allocation of the current function
+    pub fn update_escape_analysis(&mut self, alloc_id: u32, escape_analysis: EscapeInfo) {
+        if let Some(scope) = &mut self.current {
+            scope.update_escape_analysis(alloc_id, escape_analysis);
+        }
+    }
+
+    /// Whether an allocation of the current function leaves it
+    pub fn leaves_function(&self, alloc_id: u32) -> bool {
+        self.current.as_ref().is_some_and(|scope| scope.leaves_function(alloc_id))
+    }
+
+    /// Take over the leaks of another tracker, shifting its allocation ids by `alloc_offset`
+    ///
+    /// Returns the leaks taken over.
+    pub fn absorb(&mut self, other: AllocationTracker, alloc_offset: u32) -> Vec<LeakWarning> {
+        let mut absorbed = Vec::new();
+        for (function_name, leaks) in other.leaks_by_function {
+            let leaks: Vec<LeakWarning> = leaks.into_iter()
+                .map(|leak| LeakWarning { alloc_id: leak.alloc_id + alloc_offset, ..leak })
+                .collect();
+            absorbed.extend(leaks.iter().cloned());
+            self.leaks_by_function.entry(function_name).or_default().extend(leaks);
         }
+        absorbed
     }
 
-    /// Get all detected leaks
-    pub fn get_detected_leaks(&self) -> &[LeakWarning] {
-        &self.potential_leaks
+    /// Get all detected leaks, grouped by function
+    pub fn get_detected_leaks(&self) -> impl Iterator<Item = &LeakWarning> {
+        self.leaks_by_function.values().flatten()
     }
 
-    /// Generate comprehensive leak report
+    /// Generate comprehensive leak report, grouped by function
     pub fn generate_leak_report(&self) -> String {
-        if self.potential_leaks.is_empty() {
+        if self.leaks_by_function.is_empty() {
             return "✅ No memory leaks detected!".to_string();
         }
 
         let mut report = String::from("🚨 Memory Leak Analysis Report 🚨\n\n");
         
-        // Group by severity
-        let mut by_severity: HashMap<LeakSeverity, Vec<&LeakWarning>> = HashMap::new();
-        for leak in &self.potential_leaks {
-            by_severity.entry(leak.severity.clone()).or_default().push(leak);
-        }
-
-        for severity in [LeakSeverity::Critical, LeakSeverity::Error, LeakSeverity::Warning, LeakSeverity::Info] {
-            if let Some(leaks) = by_severity.get(&severity) {
-                report.push_str(&format!("{:?} Issues ({}):\n", severity, leaks.len()));
-                for leak in leaks {
+        for (function_name, leaks) in &self.leaks_by_function {
+            report.push_str(&format!("fn {} ({} issues):\n", function_name, leaks.len()));
+            
+            // By file, then line; most severe first on the same line
+            for (file, mut leaks) in group_by_file(leaks, |leak| &leak.source_location) {
+                leaks.sort_by(|(a_line, a_column, a), (b_line, b_column, b)| {
+                    (a_line, a_column).cmp(&(b_line, b_column)).then_with(|| b.severity.cmp(&a.severity))
+                });
+                report.push_str(&format!("  {}\n", file));
+                for (line, column, leak) in leaks {
                     report.push_str(&format!(
-                        "  • {} allocation at {} (ID: {})\n    {} - {}\n",
+                        "    • {}:{} [{:?}] {} allocation (ID: {})\n      {} - {}\n",
+                        line,
+                        column,
+                        leak.severity,
                         leak.strategy.name(),
-                        leak.source_location,
                         leak.alloc_id,
                         format!("{:?}", leak.leak_type).replace('_', " "),
                         leak.suggestion
                     ));
                 }
-                report.push('\n');
             }
+            report.push('\n');
         }
 
         report.push_str(&format!(
-            "Summary: {} total issues detected\n",
-            self.potential_leaks.len()
+            "Summary: {} total issues detected in {} functions\n",
+            self.get_detected_leaks().count(),
+            self.leaks_by_function.len()
         ));
 
         report
     }
 } 
 
+/// Split a `file:line:column` source location (optionally followed by `(in function)`)
+/// into its file, line and column; other locations are a file of their own at line 0
+fn split_source_location(location: &str) -> (&str, u32, u32) {
+    let position = location.split(" (in ").next().unwrap_or(location);
+    let mut parts = position.rsplitn(3, ':');
+    let column = parts.next().and_then(|column| column.parse().ok());
+    let line = parts.next().and_then(|line| line.parse().ok());
+    match (parts.next(), line, column) {
+        (Some(file), Some(line), Some(column)) => (file, line, column),
+        _ => (location, 0, 0),
+    }
+}
+
+/// Group report entries by the file of their source location, with each entry's line and column
+fn group_by_file<'a, T>(
+    entries: impl IntoIterator<Item = &'a T>,
+    location: impl Fn(&T) -> &str,
+) -> BTreeMap<&'a str, Vec<(u32, u32, &'a T)>> {
+    let mut files: BTreeMap<&str, Vec<(u32, u32, &T)>> = BTreeMap::new();
+    for entry in entries {
+        let (file, line, column) = split_source_location(location(entry));
+        files.entry(file).or_default().push((line, column, entry));
+    }
+    files
+}
+
 /// Runtime bridge for memory operations - clean integration architecture
 #[derive(Debug, Clone)]
 pub struct RuntimeBridge {
     /// Function IDs for runtime operations
     runtime_functions: Option<RuntimeFunctions>,
+    /// Runtime functions imported into the current function
+    function_refs: Option<RuntimeFuncRefs>,
+    /// Pointer type of the compilation target (used for sizes and pointers in signatures)
+    pointer_type: Type,
     /// Whether runtime is initialized
     initialized: bool,
 }
@@ -1126,12 +2246,16 @@ impl RuntimeBridge {
     pub fn new() -> Self {
         Self {
             runtime_functions: None,
+            function_refs: None,
+            pointer_type: ctypes::I64,
             initialized: false,
         }
     }
 
     /// Initialize runtime bridge with function declarations
     pub fn initialize(&mut self, module: &mut dyn CraneliftModule) -> CodegenResult<()> {
+        self.pointer_type = module.target_config().pointer_type();
+        
         // Create runtime function signatures and declarations
         let runtime_funcs = RuntimeFunctions {
             malloc: self.declare_malloc(module)?,
@@ -1148,8 +2272,8 @@ impl RuntimeBridge {
     /// Declare malloc function with proper signature
     fn declare_malloc(&self, module: &mut dyn CraneliftModule) -> CodegenResult<FuncId> {
         let mut sig = module.make_signature();
-        sig.params.push(cranelift::prelude::AbiParam::new(ctypes::I64)); // size
-        sig.returns.push(cranelift::prelude::AbiParam::new(ctypes::I64)); // pointer
+        sig.params.push(cranelift::prelude::AbiParam::new(self.pointer_type)); // size
+        sig.returns.push(cranelift::prelude::AbiParam::new(self.pointer_type)); // pointer
         
         module.declare_function("bract_malloc", cranelift_module::Linkage::Import, &sig)
             .map_err(|e| CodegenError::InternalError(format!("Failed to declare bract_malloc: {}", e)))
@@ -1158,7 +2282,7 @@ impl RuntimeBridge {
     /// Declare free function with proper signature  
     fn declare_free(&self, module: &mut dyn CraneliftModule) -> CodegenResult<FuncId> {
         let mut sig = module.make_signature();
-        sig.params.push(cranelift::prelude::AbiParam::new(ctypes::I64)); // pointer
+        sig.params.push(cranelift::prelude::AbiParam::new(self.pointer_type)); // pointer
         
         module.declare_function("bract_free", cranelift_module::Linkage::Import, &sig)
             .map_err(|e| CodegenError::InternalError(format!("Failed to declare bract_free: {}", e)))
@@ -1167,7 +2291,7 @@ impl RuntimeBridge {
     /// Declare ARC increment function
     fn declare_arc_inc(&self, module: &mut dyn CraneliftModule) -> CodegenResult<FuncId> {
         let mut sig = module.make_signature();
-        sig.params.push(cranelift::prelude::AbiParam::new(ctypes::I64)); // pointer
+        sig.params.push(cranelift::prelude::AbiParam::new(self.pointer_type)); // pointer
         
         module.declare_function("bract_arc_inc", cranelift_module::Linkage::Import, &sig)
             .map_err(|e| CodegenError::InternalError(format!("Failed to declare bract_arc_inc: {}", e)))
@@ -1176,67 +2300,80 @@ impl RuntimeBridge {
     /// Declare ARC decrement function
     fn declare_arc_dec(&self, module: &mut dyn CraneliftModule) -> CodegenResult<FuncId> {
         let mut sig = module.make_signature();
-        sig.params.push(cranelift::prelude::AbiParam::new(ctypes::I64)); // pointer
+        sig.params.push(cranelift::prelude::AbiParam::new(self.pointer_type)); // pointer
         
         module.declare_function("bract_arc_dec", cranelift_module::Linkage::Import, &sig)
             .map_err(|e| CodegenError::InternalError(format!("Failed to declare bract_arc_dec: {}", e)))
     }
 
-    /// Generate malloc call - will be properly integrated when runtime bridge is complete
-    pub fn generate_malloc_call(&self, builder: &mut FunctionBuilder, size: Value) -> CodegenResult<Value> {
+    /// Import the declared runtime functions into `func` so calls can be emitted
+    pub fn import_into_function(&mut self, module: ModuleView, func: &mut Function) -> CodegenResult<()> {
+        let runtime_funcs = self.runtime_functions.as_ref().ok_or_else(||
+            runtime_not_initialized_error("initialize() must run before functions are compiled".to_string())
+        )?;
+
+        self.function_refs = Some(RuntimeFuncRefs {
+            malloc: module.declare_func_in_func(runtime_funcs.malloc, func),
+            free: module.declare_func_in_func(runtime_funcs.free, func),
+            arc_inc: module.declare_func_in_func(runtime_funcs.arc_inc, func),
+            arc_dec: module.declare_func_in_func(runtime_funcs.arc_dec, func),
+        });
+        Ok(())
+    }
+
+    /// Forget the function references of the function that just finished compiling
+    pub fn release_function_refs(&mut self) {
+        self.function_refs = None;
+    }
+
+    /// Get the runtime function references for the current function
+    fn function_refs(&self) -> CodegenResult<RuntimeFuncRefs> {
         if !self.initialized {
-            // For now, fall back to stack allocation until runtime integration is complete
-            return self.generate_stack_fallback(builder, size);
+            return Err(runtime_not_initialized_error("runtime bridge has not been initialized".to_string()));
         }
 
-        // TODO: Generate proper function call through Cranelift module system
-        // This will be implemented when we have proper module-level function integration
-        self.generate_stack_fallback(builder, size)
+        self.function_refs.ok_or_else(||
+            runtime_not_initialized_error("runtime functions were not imported into the current function".to_string())
+        )
+    }
+
+    /// Generate `bract_malloc(size)` call returning the allocated pointer
+    pub fn generate_malloc_call(&self, builder: &mut FunctionBuilder, size: Value) -> CodegenResult<Value> {
+        let refs = self.function_refs()?;
+        let call = builder.ins().call(refs.malloc, &[size]);
+        Ok(builder.inst_results(call)[0])
     }
 
-    /// Generate free call - will be properly integrated when runtime bridge is complete  
-    pub fn generate_free_call(&self, _builder: &mut FunctionBuilder, _ptr: Value) -> CodegenResult<()> {
-        // TODO: Generate proper function call
-        // For now, no-op since we're using stack allocation
+    /// Generate `bract_free(ptr)` call
+    pub fn generate_free_call(&self, builder: &mut FunctionBuilder, ptr: Value) -> CodegenResult<()> {
+        let refs = self.function_refs()?;
+        builder.ins().call(refs.free, &[ptr]);
         Ok(())
     }
 
     /// Generate ARC increment call
-    pub fn generate_arc_inc_call(&self, _builder: &mut FunctionBuilder, _ptr: Value) -> CodegenResult<()> {
-        // TODO: Generate proper function call
+    pub fn generate_arc_inc_call(&self, builder: &mut FunctionBuilder, ptr: Value) -> CodegenResult<()> {
+        let refs = self.function_refs()?;
+        builder.ins().call(refs.arc_inc, &[ptr]);
         Ok(())
     }
 
     /// Generate ARC decrement call
-    pub fn generate_arc_dec_call(&self, _builder: &mut FunctionBuilder, _ptr: Value) -> CodegenResult<()> {
-        // TODO: Generate proper function call
+    pub fn generate_arc_dec_call(&self, builder: &mut FunctionBuilder, ptr: Value) -> CodegenResult<()> {
+        let refs = self.function_refs()?;
+        builder.ins().call(refs.arc_dec, &[ptr]);
         Ok(())
     }
-
-    /// Stack allocation fallback for development
-    fn generate_stack_fallback(&self, builder: &mut FunctionBuilder, _size: Value) -> CodegenResult<Value> {
-        // Extract size as constant (simplified for now)
-        // In real implementation, would handle dynamic sizes properly
-        let size_bytes = 64; // Default reasonable size
-        
-        let stack_slot = builder.create_sized_stack_slot(cranelift_codegen::ir::StackSlotData::new(
-            cranelift_codegen::ir::StackSlotKind::ExplicitSlot,
-            size_bytes,
-        ));
-        
-        Ok(builder.ins().stack_addr(ctypes::I64, stack_slot, 0))
-    }
 } 
 
 /// Cycle detection system for smart pointers - prevents memory leaks from cycles
+///
+/// Nodes are allocation ids, which are unique across every function of a module,
+/// unlike the Cranelift values holding the pointers.
 #[derive(Debug, Clone)]
 pub struct CycleDetector {
     /// Graph of pointer references for cycle detection
-    reference_graph: HashMap<u64, Vec<u64>>, // ptr_id -> referenced_ptr_ids
-    /// Visited nodes during traversal
-    visited: HashMap<u64, bool>,
-    /// Nodes currently in recursion stack (for cycle detection)
-    recursion_stack: HashMap<u64, bool>,
+    reference_graph: BTreeMap<u64, Vec<u64>>, // alloc_id -> referenced alloc_ids
     /// Detected cycles
     detected_cycles: Vec<Cycle>,
     /// Statistics
@@ -1272,9 +2409,7 @@ pub enum CycleBreakStrategy {
 impl CycleDetector {
     pub fn new() -> Self {
         Self {
-            reference_graph: HashMap::new(),
-            visited: HashMap::new(),
-            recursion_stack: HashMap::new(),
+            reference_graph: BTreeMap::new(),
             detected_cycles: Vec::new(),
             detection_runs: 0,
             cycles_found: 0,
@@ -1282,11 +2417,8 @@ impl CycleDetector {
         }
     }
 
-    /// Add a reference edge to the graph
-    pub fn add_reference(&mut self, from_ptr: Value, to_ptr: Value) {
-        let from_id = self.value_to_id(from_ptr);
-        let to_id = self.value_to_id(to_ptr);
-        
+    /// Add a reference edge between two allocations
+    pub fn add_reference(&mut self, from_id: u64, to_id: u64) {
         self.reference_graph
             .entry(from_id)
             .or_default()
@@ -1294,10 +2426,7 @@ impl CycleDetector {
     }
 
     /// Remove a reference edge
-    pub fn remove_reference(&mut self, from_ptr: Value, to_ptr: Value) {
-        let from_id = self.value_to_id(from_ptr);
-        let to_id = self.value_to_id(to_ptr);
-        
+    pub fn remove_reference(&mut self, from_id: u64, to_id: u64) {
         if let Some(refs) = self.reference_graph.get_mut(&from_id) {
             refs.retain(|&id| id != to_id);
             if refs.is_empty() {
@@ -1306,54 +2435,119 @@ impl CycleDetector {
         }
     }
 
-    /// Run cycle detection algorithm (DFS-based)
+    /// Take over the references of another detector, shifting its allocation ids by `alloc_offset`
+    pub fn absorb(&mut self, other: CycleDetector, alloc_offset: u32) {
+        let offset = u64::from(alloc_offset);
+        for (from_id, refs) in other.reference_graph {
+            self.reference_graph
+                .entry(from_id + offset)
+                .or_default()
+                .extend(refs.into_iter().map(|to_id| to_id + offset));
+        }
+    }
+
+    /// Run cycle detection: every strongly connected component with a reference
+    /// inside it is reported once, however many cycles run through it
     pub fn detect_cycles(&mut self) -> Vec<Cycle> {
         self.detection_runs += 1;
-        self.clear_detection_state();
-        
-        // Run DFS from each unvisited node
-        let nodes: Vec<u64> = self.reference_graph.keys().copied().collect();
-        for &node in &nodes {
-            if !self.visited.get(&node).unwrap_or(&false) {
-                self.dfs_detect_cycles(node, &mut Vec::new());
-            }
-        }
-        
+
+        self.detected_cycles = self.strongly_connected_components()
+            .into_iter()
+            .filter_map(|component| self.cycle_of(component))
+            .collect();
+        self.cycles_found += self.detected_cycles.len() as u64;
+
         self.detected_cycles.clone()
     }
 
-    /// DFS traversal with cycle detection
-    fn dfs_detect_cycles(&mut self, node: u64, path: &mut Vec<u64>) {
-        self.visited.insert(node, true);
-        self.recursion_stack.insert(node, true);
-        path.push(node);
-
-        if let Some(neighbors) = self.reference_graph.get(&node).cloned() {
-            for neighbor in neighbors {
-                if !self.visited.get(&neighbor).unwrap_or(&false) {
-                    // Continue DFS
-                    self.dfs_detect_cycles(neighbor, path);
-                } else if *self.recursion_stack.get(&neighbor).unwrap_or(&false) {
-                    // Found a back edge - cycle detected!
-                    let cycle_start = path.iter().position(|&x| x == neighbor).unwrap_or(0);
-                    let cycle_nodes = path[cycle_start..].to_vec();
-                    
-                    let cycle = Cycle {
-                        nodes: cycle_nodes,
-                        strength: (path.len() - cycle_start) as u32,
-                        broken: false,
-                        break_strategy: None,
-                    };
-                    
-                    self.detected_cycles.push(cycle);
-                    self.cycles_found += 1;
+    /// Tarjan's algorithm over the reference graph
+    ///
+    /// Runs on an explicit stack, so arbitrarily long reference chains can't overflow
+    /// the compiler's own stack.
+    fn strongly_connected_components(&self) -> Vec<Vec<u64>> {
+        let no_refs = Vec::new();
+        let refs_of = |node: u64| self.reference_graph.get(&node).unwrap_or(&no_refs);
+
+        let mut index_of: HashMap<u64, usize> = HashMap::new();
+        let mut low_link: HashMap<u64, usize> = HashMap::new();
+        let mut on_stack: HashSet<u64> = HashSet::new();
+        let mut stack: Vec<u64> = Vec::new();
+        let mut components = Vec::new();
+
+        for &root in self.reference_graph.keys() {
+            if index_of.contains_key(&root) {
+                continue;
+            }
+
+            // (node, index of the next reference to follow)
+            let mut work = vec![(root, 0)];
+            while let Some((node, mut next_ref)) = work.pop() {
+                if !index_of.contains_key(&node) {
+                    let index = index_of.len();
+                    index_of.insert(node, index);
+                    low_link.insert(node, index);
+                    stack.push(node);
+                    on_stack.insert(node);
+                }
+
+                let refs = refs_of(node);
+                // Returning from the node followed last: it may reach back further
+                if next_ref > 0 && on_stack.contains(&refs[next_ref - 1]) {
+                    let low = low_link[&node].min(low_link[&refs[next_ref - 1]]);
+                    low_link.insert(node, low);
+                }
+
+                let mut descended = false;
+                while next_ref < refs.len() {
+                    let target = refs[next_ref];
+                    next_ref += 1;
+                    if !index_of.contains_key(&target) {
+                        work.push((node, next_ref));
+                        work.push((target, 0));
+                        descended = true;
+                        break;
+                    }
+                    if on_stack.contains(&target) {
+                        let low = low_link[&node].min(index_of[&target]);
+                        low_link.insert(node, low);
+                    }
+                }
+                if descended {
+                    continue;
+                }
+
+                // A node that can't reach anything older roots a component
+                if low_link[&node] == index_of[&node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = stack.pop() {
+                        on_stack.remove(&member);
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.push(component);
                 }
             }
         }
 
-        // Backtrack
-        self.recursion_stack.insert(node, false);
-        path.pop();
+        components
+    }
+
+    /// The cycle formed by a strongly connected component, None if it has no reference inside
+    fn cycle_of(&self, mut component: Vec<u64>) -> Option<Cycle> {
+        component.sort_unstable();
+        let strength = component.iter()
+            .flat_map(|node| self.reference_graph.get(node).into_iter().flatten())
+            .filter(|target| component.binary_search(target).is_ok())
+            .count() as u32;
+
+        (strength > 0).then_some(Cycle {
+            nodes: component,
+            strength,
+            broken: false,
+            break_strategy: None,
+        })
     }
 
     /// Break detected cycles using optimal strategies
@@ -1437,12 +2631,11 @@ impl CycleDetector {
             if let Some(cycle) = self.detected_cycles.get(cycle_index) {
                 match strategy {
                     CycleBreakStrategy::WeakReference => {
-                        // Actually remove the edge to break the cycle
+                        // Actually remove a reference inside the cycle to break it
                         if let Some(&weakest_node) = cycle.nodes.first() {
-                            if cycle.nodes.len() >= 2 {
-                                let target = cycle.nodes[1];
-                                if let Some(refs) = self.reference_graph.get_mut(&weakest_node) {
-                                    refs.retain(|&id| id != target);
+                            if let Some(refs) = self.reference_graph.get_mut(&weakest_node) {
+                                if let Some(position) = refs.iter().position(|target| cycle.nodes.contains(target)) {
+                                    refs.remove(position);
                                 }
                             }
                         }
@@ -1455,20 +2648,6 @@ impl CycleDetector {
         }
     }
 
-    /// Clear detection state for new run
-    fn clear_detection_state(&mut self) {
-        self.visited.clear();
-        self.recursion_stack.clear();
-        self.detected_cycles.clear();
-    }
-
-    /// Convert Cranelift Value to unique ID (simplified)
-    fn value_to_id(&self, value: Value) -> u64 {
-        // Use the raw value as ID (this is a simplification)
-        // In real implementation, would use a proper mapping system
-        value.as_u32() as u64
-    }
-
     /// Get cycle detection statistics
     pub fn get_statistics(&self) -> CycleDetectionStats {
         CycleDetectionStats {
@@ -1538,6 +2717,8 @@ pub struct FragmentationStats {
     /// Largest contiguous block available
     #[allow(dead_code)]
     largest_free_block: u64,
+    /// Allocations sized at runtime, which the byte counts above do not include
+    runtime_sized_allocations: u64,
 }
 
 /// Pre-calculated alignment masks for performance
@@ -1623,6 +2804,17 @@ impl OptimizedRegionAllocator {
         }
     }
 
+    /// Count an allocation whose size and alignment waste are only known at runtime
+    pub fn record_runtime_sized_allocation(&mut self) {
+        self.fragmentation_stats.allocation_count += 1;
+        self.fragmentation_stats.runtime_sized_allocations += 1;
+    }
+
+    /// Whether the statistics leave out runtime-sized allocations
+    pub fn is_estimated(&self) -> bool {
+        self.fragmentation_stats.runtime_sized_allocations > 0
+    }
+
     /// Calculate allocation efficiency (0-100, higher is better)
     fn calculate_efficiency_score(&self, waste: u64, size: u64) -> f64 {
         if size == 0 { return 0.0; }
@@ -1632,14 +2824,20 @@ impl OptimizedRegionAllocator {
 
     /// Get comprehensive fragmentation report
     pub fn get_fragmentation_report(&self) -> String {
+        let estimated = if self.is_estimated() {
+            format!(" (estimated: {} runtime-sized allocations not measured)", self.fragmentation_stats.runtime_sized_allocations)
+        } else {
+            String::new()
+        };
         format!(
-            "=== Region Allocation Efficiency Report ===\n\
+            "=== Region Allocation Efficiency Report{} ===\n\
              Total Allocations: {}\n\
              Total Memory Allocated: {} KB\n\
              Memory Wasted (Alignment): {} KB ({:.2}%)\n\
              Average Efficiency: {:.1}%\n\n\
              Recommendations:\n\
              {}",
+            estimated,
             self.fragmentation_stats.allocation_count,
             self.fragmentation_stats.total_allocated / 1024,
             self.fragmentation_stats.alignment_waste / 1024,
@@ -1741,15 +2939,21 @@ pub struct MemoryProfiler {
     current_metrics: RealTimeMetrics,
     /// Profiling configuration
     config: ProfilerConfig,
-    /// Sample index for circular buffer
+    /// Sample index for circular buffer (the oldest sample once it has wrapped)
     sample_index: usize,
+    /// Samples overwritten after the circular buffer wrapped
+    discarded_samples: u64,
+    /// Time the profiler was created; sample timestamps are relative to it
+    started: Instant,
 }
 
 /// Single performance sample point
 #[derive(Debug, Clone)]
 pub struct PerformanceSample {
-    /// Timestamp of sample
-    pub timestamp_ms: u64,
+    /// Microseconds since the profiler was created (monotonic)
+    pub timestamp_us: u64,
+    /// Allocations recorded up to this sample
+    pub total_allocations: u64,
     /// Memory usage at this point
     pub memory_usage_kb: u64,
     /// Allocation rate (allocs per second)
@@ -1858,6 +3062,15 @@ impl Default for ProfilerConfig {
     }
 }
 
+/// Per-strategy allocation counts keyed by strategy name, with every strategy present
+fn strategy_usage_json(usage: &HashMap<MemoryStrategy, u32>) -> serde_json::Value {
+    let strategies = [MemoryStrategy::Manual, MemoryStrategy::SmartPtr, MemoryStrategy::Linear, MemoryStrategy::Region, MemoryStrategy::Stack];
+    strategies.iter()
+        .map(|strategy| (strategy.name().to_string(), serde_json::json!(usage.get(strategy).copied().unwrap_or(0))))
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
 impl MemoryProfiler {
     pub fn new() -> Self {
         Self::with_config(ProfilerConfig::default())
@@ -1870,33 +3083,61 @@ impl MemoryProfiler {
             current_metrics: RealTimeMetrics::default(),
             config,
             sample_index: 0,
+            discarded_samples: 0,
+            started: Instant::now(),
         }
     }
 
     /// Record a performance sample
     pub fn record_sample(&mut self, memory_usage_kb: u64, metrics: &MemoryMetrics) {
-        let now_ms = self.get_current_time_ms();
+        let timestamp_us = self.get_current_time_us();
+        let previous = self.latest_sample().cloned();
         
         let sample = PerformanceSample {
-            timestamp_ms: now_ms,
+            timestamp_us,
+            total_allocations: metrics.total_allocations(),
             memory_usage_kb,
-            allocation_rate: self.calculate_allocation_rate(metrics),
+            allocation_rate: self.calculate_allocation_rate(previous.as_ref(), timestamp_us, metrics),
             avg_allocation_size: self.calculate_avg_allocation_size(metrics),
             fragmentation: self.calculate_fragmentation_percentage(metrics),
             strategy_usage: self.extract_strategy_usage(metrics),
         };
 
+        // Update real-time metrics
+        self.update_real_time_metrics(previous.as_ref(), &sample);
+
         // Add sample to circular buffer
         if self.performance_samples.len() < self.config.max_samples {
             self.performance_samples.push(sample);
         } else {
             self.performance_samples[self.sample_index] = sample;
             self.sample_index = (self.sample_index + 1) % self.config.max_samples;
+            self.discarded_samples += 1;
         }
+    }
 
-        // Update real-time metrics
-        let last_sample = self.performance_samples.last().unwrap().clone();
-        self.update_real_time_metrics(&last_sample);
+    /// Record a sample now, taking the current memory usage from `metrics`
+    pub fn record_sample_now(&mut self, metrics: &MemoryMetrics) {
+        self.record_sample(metrics.total_bytes_allocated / 1024, metrics);
+    }
+
+    /// Most recently recorded sample
+    pub fn latest_sample(&self) -> Option<&PerformanceSample> {
+        match self.sample_index {
+            0 => self.performance_samples.last(),
+            index => self.performance_samples.get(index - 1),
+        }
+    }
+
+    /// Samples still in the circular buffer, oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &PerformanceSample> {
+        let (newer, older) = self.performance_samples.split_at(self.sample_index);
+        older.iter().chain(newer)
+    }
+
+    /// Number of early samples the circular buffer overwrote
+    pub fn discarded_samples(&self) -> u64 {
+        self.discarded_samples
     }
 
     /// Record allocation hotspot
@@ -1924,10 +3165,28 @@ impl MemoryProfiler {
         hotspot.impact_score = ((hotspot.allocation_count as f64).ln() * (hotspot.avg_size / 1024.0)).min(100.0);
     }
 
+    /// Add the hotspots recorded by another profiler
+    pub fn absorb_hotspots(&mut self, other: MemoryProfiler) {
+        for (location, other_hotspot) in other.hotspots {
+            match self.hotspots.get_mut(&location) {
+                Some(hotspot) => {
+                    hotspot.allocation_count += other_hotspot.allocation_count;
+                    hotspot.total_bytes += other_hotspot.total_bytes;
+                    hotspot.avg_size = hotspot.total_bytes as f64 / hotspot.allocation_count as f64;
+                    hotspot.impact_score = ((hotspot.allocation_count as f64).ln() * (hotspot.avg_size / 1024.0)).min(100.0);
+                }
+                None => {
+                    self.hotspots.insert(location, other_hotspot);
+                }
+            }
+        }
+    }
+
     /// Get top allocation hotspots
     pub fn get_top_hotspots(&self, limit: usize) -> Vec<&AllocationHotspot> {
         let mut hotspots: Vec<_> = self.hotspots.values().collect();
-        hotspots.sort_by(|a, b| b.impact_score.partial_cmp(&a.impact_score).unwrap());
+        // Ties are broken by location so the order doesn't depend on hashing
+        hotspots.sort_by(|a, b| b.impact_score.total_cmp(&a.impact_score).then_with(|| a.location.cmp(&b.location)));
         hotspots.into_iter().take(limit).collect()
     }
 
@@ -1953,8 +3212,7 @@ impl MemoryProfiler {
         ));
 
         // Performance trends
-        if !self.performance_samples.is_empty() {
-            let latest = self.performance_samples.last().unwrap();
+        if let Some(latest) = self.latest_sample() {
             report.push_str(&format!(
                 "=== Performance Trends ===\n\
                  Current Allocation Rate: {:.1}/sec\n\
@@ -1970,19 +3228,29 @@ impl MemoryProfiler {
         let top_hotspots = self.get_top_hotspots(5);
         if !top_hotspots.is_empty() {
             report.push_str("=== Top Allocation Hotspots ===\n");
-            for (i, hotspot) in top_hotspots.iter().enumerate() {
-                report.push_str(&format!(
-                    "{}. {} (Impact: {:.1})\n\
-                        • {} allocations, {:.1} KB total\n\
-                        • Avg size: {:.1} bytes, Strategy: {}\n\n",
-                    i + 1,
-                    hotspot.location,
-                    hotspot.impact_score,
-                    hotspot.allocation_count,
-                    hotspot.total_bytes as f64 / 1024.0,
-                    hotspot.avg_size,
-                    hotspot.dominant_strategy.name()
-                ));
+            // Grouped by file and ordered by line, numbered by impact rank
+            let ranks: HashMap<&str, usize> = top_hotspots.iter().enumerate()
+                .map(|(i, hotspot)| (hotspot.location.as_str(), i + 1))
+                .collect();
+            for (file, mut hotspots) in group_by_file(top_hotspots, |hotspot| &hotspot.location) {
+                hotspots.sort_by_key(|(line, column, _)| (*line, *column));
+                report.push_str(&format!("{}\n", file));
+                for (line, column, hotspot) in hotspots {
+                    report.push_str(&format!(
+                        "  {}. {}:{}{} (Impact: {:.1})\n\
+                            • {} allocations, {:.1} KB total\n\
+                            • Avg size: {:.1} bytes, Strategy: {}\n\n",
+                        ranks[hotspot.location.as_str()],
+                        line,
+                        column,
+                        hotspot.location.find(" (in ").map_or("", |start| &hotspot.location[start..]),
+                        hotspot.impact_score,
+                        hotspot.allocation_count,
+                        hotspot.total_bytes as f64 / 1024.0,
+                        hotspot.avg_size,
+                        hotspot.dominant_strategy.name()
+                    ));
+                }
             }
         }
 
@@ -1992,6 +3260,90 @@ impl MemoryProfiler {
         report
     }
 
+    /// Export samples, hotspots and real-time metrics as JSON
+    ///
+    /// Samples are oldest first; `discarded_samples` counts the early samples the
+    /// circular buffer overwrote (`wrapped` is set when there were any).
+    pub fn to_json(&self) -> String {
+        let samples: Vec<_> = self.samples().map(|sample| serde_json::json!({
+            "timestamp_us": sample.timestamp_us,
+            "memory_usage_kb": sample.memory_usage_kb,
+            "total_allocations": sample.total_allocations,
+            "allocation_rate": sample.allocation_rate,
+            "avg_allocation_size": sample.avg_allocation_size,
+            "fragmentation": sample.fragmentation,
+            "strategy_usage": strategy_usage_json(&sample.strategy_usage),
+        })).collect();
+        let hotspots: Vec<_> = self.get_top_hotspots(self.hotspots.len()).into_iter().map(|hotspot| serde_json::json!({
+            "location": hotspot.location,
+            "allocation_count": hotspot.allocation_count,
+            "total_bytes": hotspot.total_bytes,
+            "avg_size": hotspot.avg_size,
+            "dominant_strategy": hotspot.dominant_strategy.name(),
+            "impact_score": hotspot.impact_score,
+        })).collect();
+        let metrics = &self.current_metrics;
+
+        serde_json::json!({
+            "version": 1,
+            "max_samples": self.config.max_samples,
+            "wrapped": self.discarded_samples > 0,
+            "discarded_samples": self.discarded_samples,
+            "samples": samples,
+            "hotspots": hotspots,
+            "metrics": {
+                "current_memory_kb": metrics.current_memory_kb,
+                "peak_memory_kb": metrics.peak_memory_kb,
+                "recent_allocs": metrics.recent_allocs,
+                "allocation_trend": format!("{:?}", metrics.allocation_trend),
+                "health_score": metrics.health_score,
+                "pressure_level": format!("{:?}", metrics.pressure_level),
+            },
+        }).to_string()
+    }
+
+    /// Export the samples as Chrome trace counter events, for chrome://tracing or Perfetto
+    ///
+    /// If the circular buffer wrapped, an instant event at the first kept sample
+    /// marks where the discarded samples were.
+    pub fn to_chrome_trace(&self) -> String {
+        let mut events = Vec::new();
+        if let (true, Some(first)) = (self.discarded_samples > 0, self.samples().next()) {
+            events.push(serde_json::json!({
+                "name": "samples discarded",
+                "ph": "i",
+                "s": "g",
+                "ts": first.timestamp_us,
+                "pid": 1,
+                "tid": 1,
+                "args": { "discarded_samples": self.discarded_samples },
+            }));
+        }
+
+        for sample in self.samples() {
+            let counters = [
+                ("memory_usage_kb", serde_json::json!({ "kb": sample.memory_usage_kb })),
+                ("allocation_rate", serde_json::json!({ "allocs_per_sec": sample.allocation_rate })),
+                ("fragmentation", serde_json::json!({ "percent": sample.fragmentation })),
+                ("strategy_usage", strategy_usage_json(&sample.strategy_usage)),
+            ];
+            events.extend(counters.into_iter().map(|(name, args)| serde_json::json!({
+                "name": name,
+                "ph": "C",
+                "ts": sample.timestamp_us,
+                "pid": 1,
+                "tid": 1,
+                "args": args,
+            })));
+        }
+
+        serde_json::json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms",
+            "otherData": { "discarded_samples": self.discarded_samples },
+        }).to_string()
+    }
+
     /// Generate optimization recommendations based on profiling data
     fn generate_optimization_recommendations(&self) -> String {
         let mut recommendations = Vec::<String>::new();
@@ -2046,22 +3398,23 @@ impl MemoryProfiler {
     }
 
     /// Helper methods for calculations
-    fn get_current_time_ms(&self) -> u64 {
-        // In real implementation, would use system time
-        // For now, return sample index as time
-        self.sample_index as u64 * self.config.sample_interval_ms
+    fn get_current_time_us(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64
     }
 
-    fn calculate_allocation_rate(&self, metrics: &MemoryMetrics) -> f64 {
-        // Calculate allocations per second based on total allocations
-        if self.performance_samples.len() < 2 {
+    fn calculate_allocation_rate(&self, previous: Option<&PerformanceSample>, timestamp_us: u64, metrics: &MemoryMetrics) -> f64 {
+        // Allocations per second since the previous sample
+        let Some(previous) = previous else {
             return 0.0;
-        }
+        };
         
-        let current_total = metrics.total_allocations();
-        let time_diff_s = self.config.sample_interval_ms as f64 / 1000.0;
+        let allocations = metrics.total_allocations().saturating_sub(previous.total_allocations);
+        let time_diff_s = timestamp_us.saturating_sub(previous.timestamp_us) as f64 / 1_000_000.0;
+        if time_diff_s <= 0.0 {
+            return 0.0;
+        }
         
-        current_total as f64 / time_diff_s.max(1.0)
+        allocations as f64 / time_diff_s
     }
 
     fn calculate_avg_allocation_size(&self, metrics: &MemoryMetrics) -> f64 {
@@ -2088,14 +3441,14 @@ impl MemoryProfiler {
         usage
     }
 
-    fn update_real_time_metrics(&mut self, sample: &PerformanceSample) {
+    fn update_real_time_metrics(&mut self, previous: Option<&PerformanceSample>, sample: &PerformanceSample) {
         self.current_metrics.current_memory_kb = sample.memory_usage_kb;
         self.current_metrics.peak_memory_kb = self.current_metrics.peak_memory_kb.max(sample.memory_usage_kb);
         self.current_metrics.recent_allocs = sample.allocation_rate as u32;
 
         // Update allocation trend
-        if self.performance_samples.len() >= 2 {
-            let prev_rate = self.performance_samples[self.performance_samples.len() - 2].allocation_rate;
+        if let Some(previous) = previous {
+            let prev_rate = previous.allocation_rate;
             let current_rate = sample.allocation_rate;
             let change_ratio = if prev_rate > 0.0 { current_rate / prev_rate } else { 1.0 };
 
@@ -2135,4 +3488,572 @@ impl MemoryProfiler {
             MemoryPressure::Low
         };
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::ir::{ExternalName, UserFuncName};
+    use cranelift_codegen::settings;
+    use cranelift_frontend::FunctionBuilderContext;
+    use cranelift_module::FuncOrDataId;
+    use cranelift_object::{ObjectBuilder, ObjectModule};
+
+    fn test_module() -> ObjectModule {
+        let isa = cranelift_codegen::isa::lookup(target_lexicon::Triple::host())
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()))
+            .unwrap();
+        let builder = ObjectBuilder::new(isa, "bridge_test", cranelift_module::default_libcall_names()).unwrap();
+        ObjectModule::new(builder)
+    }
+
+    /// Find the function reference `func` uses to call the runtime symbol `symbol`
+    fn func_ref_for(module: &ObjectModule, func: &Function, symbol: &str) -> Option<FuncRef> {
+        let func_id = match module.get_name(symbol)? {
+            FuncOrDataId::Func(id) => id,
+            FuncOrDataId::Data(_) => return None,
+        };
+
+        func.dfg.ext_funcs.iter().find_map(|(func_ref, data)| match &data.name {
+            ExternalName::User(name_ref) if func.params.user_named_funcs()[*name_ref].index == func_id.as_u32() => Some(func_ref),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_runtime_bridge_emits_runtime_calls() {
+        let mut module = test_module();
+        let mut bridge = RuntimeBridge::new();
+        bridge.initialize(&mut module).unwrap();
+
+        let mut sig = module.make_signature();
+        sig.params.push(cranelift::prelude::AbiParam::new(ctypes::I64));
+        let func_id = module.declare_function("uses_runtime", cranelift_module::Linkage::Export, &sig).unwrap();
+
+        let mut func = Function::with_name_signature(UserFuncName::user(0, func_id.as_u32()), sig);
+        let mut builder_context = FunctionBuilderContext::new();
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut builder_context);
+            let entry = builder.create_block();
+            builder.append_block_params_for_function_params(entry);
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+            bridge.import_into_function(ModuleView::new(&module), builder.func).unwrap();
+
+            // The requested size is a runtime value, not a constant baked into a stack slot
+            let size = builder.block_params(entry)[0];
+            let ptr = bridge.generate_malloc_call(&mut builder, size).unwrap();
+            bridge.generate_arc_inc_call(&mut builder, ptr).unwrap();
+            bridge.generate_arc_dec_call(&mut builder, ptr).unwrap();
+            bridge.generate_free_call(&mut builder, ptr).unwrap();
+            builder.ins().return_(&[]);
+            builder.finalize();
+        }
+
+        let ir = func.display().to_string();
+        assert!(!ir.contains("stack_addr"), "allocation must not fall back to the stack:\n{}", ir);
+        for symbol in ["bract_malloc", "bract_free", "bract_arc_inc", "bract_arc_dec"] {
+            let func_ref = func_ref_for(&module, &func, symbol)
+                .unwrap_or_else(|| panic!("{} was not imported into the function", symbol));
+            assert!(ir.contains(&format!("call {}(", func_ref)), "missing call to {}:\n{}", symbol, ir);
+        }
+        let malloc_ref = func_ref_for(&module, &func, "bract_malloc").unwrap();
+        assert!(ir.contains(&format!("call {}(v0)", malloc_ref)), "malloc must receive the dynamic size:\n{}", ir);
+
+        let mut ctx = cranelift_codegen::Context::for_function(func);
+        module.define_function(func_id, &mut ctx).unwrap();
+        let object = module.finish().emit().unwrap();
+        for symbol in ["bract_malloc", "bract_free", "bract_arc_inc", "bract_arc_dec"] {
+            assert!(object.windows(symbol.len()).any(|window| window == symbol.as_bytes()),
+                "object file does not reference {}", symbol);
+        }
+    }
+
+    #[test]
+    fn test_shared_smart_pointer_has_single_guarded_free() {
+        let mut module = test_module();
+        let mut manager = BractMemoryManager::new();
+        manager.initialize_runtime(&mut module).unwrap();
+
+        let mut func = Function::new();
+        let mut builder_context = FunctionBuilderContext::new();
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut builder_context);
+            let entry = builder.create_block();
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+            manager.prepare_function(ModuleView::new(&module), builder.func).unwrap();
+            manager.enter_function_scope("test");
+
+            // `let a = <smart alloc>; let b = a;` - two owners of one allocation
+            let options = AllocationOptions { source_location: "test:1:1".to_string(), ..Default::default() };
+            let a = manager.allocate(&mut builder, MemoryStrategy::SmartPtr, ctypes::I64, 16, options).unwrap().ptr;
+            manager.retain_smart_pointer(&mut builder, a).unwrap();
+
+            manager.cleanup_function(&mut builder).unwrap();
+            builder.ins().return_(&[]);
+            builder.finalize();
+            manager.exit_function_scope();
+        }
+
+        let ir = func.display().to_string();
+        let free_ref = func_ref_for(&module, &func, "bract_free").unwrap();
+        assert_eq!(ir.matches(&format!("call {}(", free_ref)).count(), 1, "expected one free path:\n{}", ir);
+        assert_eq!(ir.matches("icmp_imm eq").count(), 1, "free must be guarded by a zero check:\n{}", ir);
+        // One increment for the copy, one combined decrement for both owners at scope exit
+        assert!(ir.contains("iadd_imm") && ir.contains(", 1\n"), "missing increment:\n{}", ir);
+        assert!(ir.contains(", -2\n"), "cleanup must release both references:\n{}", ir);
+    }
+
+    /// Compile `function` with one manual allocation per escape info, returning their ids and its leaks
+    fn compile_manual_allocations(
+        manager: &mut BractMemoryManager,
+        module: &ObjectModule,
+        function: &str,
+        escapes: &[EscapeInfo],
+    ) -> (Vec<u32>, Vec<LeakWarning>) {
+        let mut func = Function::new();
+        let mut builder_context = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut builder_context);
+        let entry = builder.create_block();
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        manager.prepare_function(ModuleView::new(module), builder.func).unwrap();
+        manager.enter_function_scope(function);
+
+        let ids = escapes.iter().map(|escape| {
+            let options = AllocationOptions { source_location: format!("test.bract:3:5 (in {})", function), ..Default::default() };
+            let id = manager.allocate(&mut builder, MemoryStrategy::Manual, ctypes::I64, 16, options).unwrap().alloc_id;
+            manager.update_escape_analysis(id, escape.clone());
+            id
+        }).collect();
+        (ids, manager.exit_function_scope())
+    }
+
+    #[test]
+    fn test_leaks_are_reported_per_function() {
+        let mut module = test_module();
+        let mut manager = BractMemoryManager::new();
+        manager.initialize_runtime(&mut module).unwrap();
+
+        // A manual pointer returned to the caller is the caller's to free
+        let returned = EscapeInfo { escapes_function: true, returned: true, ..EscapeInfo::default() };
+        let (made, leaks) = compile_manual_allocations(&mut manager, &module, "make", &[returned]);
+        assert!(leaks.is_empty(), "{:?}", leaks);
+
+        manager.enter_function_scope("consume");
+        manager.mark_allocation_freed(made[0]);
+        assert!(manager.exit_function_scope().is_empty());
+        assert_eq!(manager.metrics.memory_leaks_prevented, 0);
+
+        let (_, leaks) = compile_manual_allocations(&mut manager, &module, "leaky", &[EscapeInfo::default()]);
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].leak_type, LeakType::ManualNotFreed);
+        assert_eq!(leaks[0].source_location, "test.bract:3:5 (in leaky)");
+
+        let report = manager.get_leak_report();
+        assert!(report.contains("fn leaky (1 issues)"), "{}", report);
+        assert!(!report.contains("fn make"), "{}", report);
+    }
+
+    #[test]
+    fn test_large_linear_values_spill_to_the_heap() {
+        let mut module = test_module();
+        let mut manager = BractMemoryManager::new();
+        manager.initialize_runtime(&mut module).unwrap();
+
+        let mut func = Function::new();
+        let mut builder_context = FunctionBuilderContext::new();
+        let mut linear = Vec::new();
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut builder_context);
+            let entry = builder.create_block();
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+            manager.prepare_function(ModuleView::new(&module), builder.func).unwrap();
+            manager.enter_function_scope("spills");
+
+            // One value within the stack threshold, one above it that stays in the
+            // function and one above it that is returned
+            for (size, escape) in [(16, None), (256, None), (512, Some(EscapeInfo { returned: true, ..EscapeInfo::default() }))] {
+                let options = AllocationOptions { source_location: "test.bract:2:5".to_string(), ..Default::default() };
+                let result = manager.allocate(&mut builder, MemoryStrategy::Linear, ctypes::I64, size, options).unwrap();
+                if let Some(escape) = escape {
+                    manager.update_escape_analysis(result.alloc_id, escape);
+                }
+                linear.push(result.ptr);
+            }
+            assert!(linear.iter().all(|&ptr| manager.is_linear(ptr)), "spilled values keep their ownership");
+
+            manager.cleanup_function(&mut builder).unwrap();
+            builder.ins().return_(&[]);
+            builder.finalize();
+            assert!(manager.exit_function_scope().is_empty());
+        }
+
+        let ir = func.display().to_string();
+        let malloc_ref = func_ref_for(&module, &func, "bract_malloc").unwrap();
+        let free_ref = func_ref_for(&module, &func, "bract_free").unwrap();
+        assert_eq!(ir.matches(&format!("call {}(", malloc_ref)).count(), 2, "both large values are spilled:\n{}", ir);
+        assert_eq!(ir.matches(&format!("call {}(", free_ref)).count(), 1, "only the local spill is freed:\n{}", ir);
+        assert_eq!(ir.matches("stack_addr").count(), 1, "the small value stays on the stack:\n{}", ir);
+        assert!(cranelift_codegen::verify_function(&func, &settings::Flags::new(settings::builder())).is_ok(), "{}", ir);
+    }
+
+    #[test]
+    fn test_functions_over_the_stack_limit_name_their_largest_allocations() {
+        let span = |line| Span::single(crate::lexer::Position::new(line, 5, 0, 0));
+        let record = |manager: &mut BractMemoryManager| {
+            for (line, size) in [(2, 48), (3, 64), (4, 8), (5, 40)] {
+                manager.record_stack_allocation(size, Some(span(line)), &format!("test.bract:{}:5", line));
+            }
+        };
+        let policy = MemoryPolicy { max_function_stack: 100, ..MemoryPolicy::default() };
+
+        let mut manager = BractMemoryManager::new().with_policy(policy);
+        manager.enter_function_scope("small");
+        manager.record_stack_allocation(100, None, "test.bract:1:1");
+        manager.check_stack_usage("small").unwrap();
+        manager.exit_function_scope();
+        assert!(manager.stack_warnings().is_empty());
+
+        manager.enter_function_scope("frames");
+        record(&mut manager);
+        manager.check_stack_usage("frames").unwrap();
+        manager.exit_function_scope();
+        let [warning] = manager.stack_warnings() else {
+            panic!("expected one warning, got {:?}", manager.stack_warnings());
+        };
+        assert_eq!((warning.function_name.as_str(), warning.total_bytes, warning.limit), ("frames", 160, 100));
+        let sizes: Vec<u32> = warning.largest.iter().map(|allocation| allocation.size).collect();
+        assert_eq!(sizes, [64, 48, 40]);
+        assert_eq!(warning.span(), Some(span(3)));
+        assert!(warning.message().contains("64 bytes at test.bract:3:5, 48 bytes at test.bract:2:5"), "{}", warning.message());
+
+        // Allocations are counted per function
+        manager.enter_function_scope("after");
+        manager.check_stack_usage("after").unwrap();
+        manager.exit_function_scope();
+        assert_eq!(manager.stack_warnings().len(), 1);
+
+        let mut strict = BractMemoryManager::new().with_policy(MemoryPolicy { strict_stack_limit: true, ..policy });
+        strict.enter_function_scope("frames");
+        record(&mut strict);
+        let error = strict.check_stack_usage("frames").unwrap_err();
+        assert_eq!(error.span(), Some(span(3)));
+        assert!(error.to_string().contains("Function 'frames' may take 160 bytes"), "{}", error);
+        assert!(strict.stack_warnings().is_empty());
+    }
+
+    #[test]
+    fn test_policy_thresholds_drive_inference() {
+        let default = MemoryPolicy::default();
+        let roomy = MemoryPolicy { stack_threshold: 256, region_threshold: 1024, max_stack_allocation: 8192, ..default };
+
+        assert_eq!(MemoryStrategy::infer_for_type(128, false, true, &default), MemoryStrategy::Region);
+        assert_eq!(MemoryStrategy::infer_for_type(128, false, true, &roomy), MemoryStrategy::Stack);
+        assert_eq!(MemoryStrategy::infer_for_type(2048, false, true, &default), MemoryStrategy::Region);
+        assert_eq!(MemoryStrategy::infer_for_type(2048, false, true, &roomy), MemoryStrategy::Manual);
+
+        // Local values too large for one stack allocation are reference counted instead
+        assert_eq!(MemoryStrategy::infer_for_escape(6000, false, false, &default), MemoryStrategy::SmartPtr);
+        assert_eq!(MemoryStrategy::infer_for_escape(6000, false, false, &roomy), MemoryStrategy::Stack);
+
+        let decision = TypeStrategy::inferred(128, 8, false, true, &roomy);
+        assert_eq!(decision.strategy, MemoryStrategy::Stack);
+        assert!(decision.reason.contains("size 128 <= stack threshold 256"), "{}", decision.reason);
+    }
+
+    #[test]
+    fn test_cycle_detection_on_long_reference_chain() {
+        let mut detector = CycleDetector::new();
+        for id in 0..100_000 {
+            detector.add_reference(id, id + 1);
+        }
+        assert!(detector.detect_cycles().is_empty());
+
+        // Closing the chain makes one cycle through every node
+        detector.add_reference(100_000, 0);
+        let cycles = detector.detect_cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].nodes.len(), 100_001);
+    }
+
+    #[test]
+    fn test_self_cycles_in_two_functions_are_distinct() {
+        let mut module = test_module();
+        let mut manager = BractMemoryManager::new();
+        manager.initialize_runtime(&mut module).unwrap();
+
+        // Both functions hold their pointer in the same Cranelift value
+        for function in ["first", "second"] {
+            let mut func = Function::new();
+            let mut builder_context = FunctionBuilderContext::new();
+            let mut builder = FunctionBuilder::new(&mut func, &mut builder_context);
+            let entry = builder.create_block();
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+            manager.prepare_function(ModuleView::new(&module), builder.func).unwrap();
+            manager.enter_function_scope(function);
+
+            let options = AllocationOptions { source_location: format!("test:1:1 (in {})", function), ..Default::default() };
+            let ptr = manager.allocate(&mut builder, MemoryStrategy::SmartPtr, ctypes::I64, 16, options).unwrap().ptr;
+            manager.add_smart_pointer_reference(ptr, ptr).unwrap();
+            manager.exit_function_scope();
+        }
+
+        let (cycles, _) = manager.detect_and_break_cycles();
+        assert_eq!(cycles.len(), 2, "{:?}", cycles);
+        assert_ne!(cycles[0].nodes, cycles[1].nodes);
+        assert!(cycles.iter().all(|cycle| cycle.nodes.len() == 1 && cycle.strength == 1));
+    }
+
+    #[test]
+    fn test_absorbed_forks_number_regions_in_order() {
+        let mut manager = BractMemoryManager::new();
+        let first_region = manager.create_region("main::setup".to_string(), 64);
+
+        let mut first = manager.fork();
+        let mut second = manager.fork();
+        first.create_region("a::scratch".to_string(), 128);
+        second.create_region("b::scratch".to_string(), 256);
+        second.metrics.bounds_violations_prevented += 1;
+
+        manager.absorb(first);
+        manager.absorb(second);
+
+        let names: Vec<_> = (first_region..first_region + 3)
+            .map(|id| manager.regions[&id].name.as_str())
+            .collect();
+        assert_eq!(names, ["main::setup", "a::scratch", "b::scratch"]);
+        assert_eq!(manager.create_region("c::scratch".to_string(), 64), first_region + 3);
+        assert_eq!(manager.metrics.bounds_violations_prevented, 1);
+    }
+
+    /// Compile a function taking a runtime size that allocates it in a fresh region with `overflow`
+    fn compile_dynamic_region_allocation(
+        manager: &mut BractMemoryManager,
+        module: &ObjectModule,
+        overflow: RegionOverflowPolicy,
+        static_sizes: &[u32],
+    ) -> Function {
+        let mut func = Function::new();
+        func.signature.params.push(cranelift::prelude::AbiParam::new(ctypes::I64));
+        let mut builder_context = FunctionBuilderContext::new();
+        {
+            let mut builder = FunctionBuilder::new(&mut func, &mut builder_context);
+            let entry = builder.create_block();
+            builder.append_block_params_for_function_params(entry);
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+            manager.prepare_function(ModuleView::new(module), builder.func).unwrap();
+            let size = builder.block_params(entry)[0];
+
+            let region_id = manager.create_region_with_policy("test::scratch".to_string(), 64, overflow);
+            manager.initialize_region(&mut builder, region_id).unwrap();
+            for &static_size in static_sizes {
+                manager.alloc_in_region_with_hint(&mut builder, region_id, static_size, AlignmentHint::SmallPrimitive).unwrap();
+            }
+            manager.alloc_in_region_dynamic(&mut builder, region_id, size, AlignmentHint::SmallPrimitive, None).unwrap();
+            manager.release_region(&mut builder, region_id).unwrap();
+            builder.ins().return_(&[]);
+            builder.finalize();
+        }
+
+        cranelift_codegen::verify_function(&func, &settings::Flags::new(settings::builder())).unwrap();
+        func
+    }
+
+    #[test]
+    fn test_dynamic_region_allocation_traps_on_overflow() {
+        let mut module = test_module();
+        let mut manager = BractMemoryManager::new();
+        manager.initialize_runtime(&mut module).unwrap();
+
+        let func = compile_dynamic_region_allocation(&mut manager, &module, RegionOverflowPolicy::Trap, &[]);
+        let ir = func.display().to_string();
+        let malloc_ref = func_ref_for(&module, &func, "bract_malloc").unwrap();
+        assert_eq!(ir.matches(&format!("call {}(", malloc_ref)).count(), 1, "only the region itself is malloc'd:\n{}", ir);
+        assert!(ir.contains("icmp ule") && ir.contains("trap heap_oob"), "missing bounds check:\n{}", ir);
+        assert!(ir.contains("brif"), "{}", ir);
+    }
+
+    #[test]
+    fn test_growing_region_frees_every_chunk() {
+        let mut module = test_module();
+        let mut manager = BractMemoryManager::new();
+        manager.initialize_runtime(&mut module).unwrap();
+
+        let func = compile_dynamic_region_allocation(&mut manager, &module, RegionOverflowPolicy::GrowRegion, &[]);
+        let ir = func.display().to_string();
+        let malloc_ref = func_ref_for(&module, &func, "bract_malloc").unwrap();
+        let free_ref = func_ref_for(&module, &func, "bract_free").unwrap();
+        assert_eq!(ir.matches(&format!("call {}(", malloc_ref)).count(), 2, "region plus grown chunk:\n{}", ir);
+        assert!(ir.contains("umax") && !ir.contains("trap heap_oob"), "{}", ir);
+        // One free inside the chunk walk, one for the original region memory
+        assert_eq!(ir.matches(&format!("call {}(", free_ref)).count(), 2, "{}", ir);
+        assert!(ir.contains("icmp eq"), "release must stop at the first chunk:\n{}", ir);
+    }
+
+    #[test]
+    fn test_static_region_overflow_uses_runtime_cursor() {
+        let mut module = test_module();
+        let mut manager = BractMemoryManager::new();
+        manager.initialize_runtime(&mut module).unwrap();
+
+        // 48 bytes are placed statically; 32 more no longer fit and fall back to malloc
+        let func = compile_dynamic_region_allocation(&mut manager, &module, RegionOverflowPolicy::FallbackToManual, &[48, 32]);
+        let ir = func.display().to_string();
+        let malloc_ref = func_ref_for(&module, &func, "bract_malloc").unwrap();
+        assert_eq!(ir.matches(&format!("call {}(", malloc_ref)).count(), 3, "{}", ir);
+        assert!(ir.contains("iconst.i64 48\n"), "cursor must start after the static allocation:\n{}", ir);
+
+        let region = manager.regions.values().next().unwrap();
+        assert_eq!(region.used, 48);
+        assert!(region.allocator.is_estimated());
+        assert!(region.allocator.get_fragmentation_report().contains("estimated: 2 runtime-sized"));
+    }
+
+    /// Parse `source` and collect the memory annotations of its first function
+    fn function_memory_attributes(source: &str, declared_regions: &[&str]) -> CodegenResult<MemoryAttributes> {
+        let mut parser = crate::parser::Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let interner = parser.interner();
+        let Some(crate::ast::Item::Function { attributes, .. }) = module.items.first() else {
+            panic!("expected a function in {:?}", source);
+        };
+        let source_map = SourceMap::new("test.bract");
+        let declared = declared_regions.iter().map(|name| name.to_string()).collect();
+        MemoryAttributes::from_attributes(attributes, &interner, &source_map, &declared)
+    }
+
+    #[test]
+    fn test_region_annotation_selects_overflow_policy() {
+        let attributes = function_memory_attributes("@region(scratch) fn f() {}", &[]).unwrap();
+        assert_eq!(attributes.region, Some(("scratch".to_string(), RegionOverflowPolicy::Trap)));
+        assert_eq!(attributes.strategy, Some(MemoryStrategy::Region));
+        let attributes = function_memory_attributes("@region(scratch, grow) fn f() {}", &[]).unwrap();
+        assert_eq!(attributes.region, Some(("scratch".to_string(), RegionOverflowPolicy::GrowRegion)));
+        let error = function_memory_attributes("@region(scratch, sometimes) fn f() {}", &[]).unwrap_err();
+        assert!(error.to_string().contains("Unknown region overflow policy 'sometimes'"), "{}", error);
+
+        // Only a region created to grow frees chunks chained onto it
+        let mut manager = BractMemoryManager::new();
+        let region_id = manager.create_region("test::fixed".to_string(), 64);
+        let mut func = Function::new();
+        let mut builder_context = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut builder_context);
+        let entry = builder.create_block();
+        builder.switch_to_block(entry);
+        let size = builder.ins().iconst(ctypes::I64, 8);
+        let error = manager.alloc_in_region_dynamic(&mut builder, region_id, size, AlignmentHint::SmallPrimitive, Some(RegionOverflowPolicy::GrowRegion))
+            .unwrap_err();
+        assert!(error.to_string().contains("cannot grow"), "{}", error);
+    }
+
+    #[test]
+    fn test_memory_annotations_are_validated() {
+        let attributes = function_memory_attributes("@memory(strategy = \"stack\", alignment = 64) @nogc fn f() {}", &[]).unwrap();
+        assert_eq!(attributes.strategy, Some(MemoryStrategy::Stack));
+        assert_eq!(attributes.alignment, Some(64));
+        assert!(attributes.no_gc);
+
+        for (source, message) in [
+            ("@align(24) fn f() {}", "Alignment 24 must be a power of two"),
+            ("@memory(alignment = 8192) fn f() {}", "no larger than 4096"),
+            ("@memory(size_hint = 0) fn f() {}", "Size hint must be positive"),
+            ("@memory(region = \"frame\") fn f() {}", "Region 'frame' is not declared"),
+            ("@region(a) @region(b) fn f() {}", "only one region"),
+        ] {
+            let error = function_memory_attributes(source, &[]).unwrap_err();
+            assert!(matches!(error.unspanned(), CodegenError::MemoryManagement(_)), "{:?}", error);
+            assert!(error.to_string().contains(message), "{}: {}", source, error);
+            assert_eq!(error.span().map(|span| span.start.line), Some(1), "{}", error);
+        }
+
+        // Conflicting strategies never get past semantic analysis
+        let error = function_memory_attributes("@manual @smart fn f() {}", &[]).unwrap_err();
+        assert!(matches!(error.unspanned(), CodegenError::InternalError(_)), "{:?}", error);
+        assert!(error.to_string().contains("conflicts with the Manual allocation requested at test.bract:1:1"), "{}", error);
+
+        // Region blocks in the body and the function's own region both declare names
+        assert!(function_memory_attributes("@memory(region = \"frame\") fn f() {}", &["frame"]).is_ok());
+        assert!(function_memory_attributes("@region(frame) @memory(region = \"frame\") fn f() {}", &[]).is_ok());
+    }
+
+    #[test]
+    fn test_profiler_json_round_trip() {
+        let mut profiler = MemoryProfiler::with_config(ProfilerConfig { max_samples: 4, ..ProfilerConfig::default() });
+        let mut metrics = MemoryMetrics::default();
+        for i in 0..6 {
+            metrics.record_allocation(MemoryStrategy::Region, 4096);
+            if i % 2 == 0 {
+                metrics.record_allocation(MemoryStrategy::Manual, 64);
+            }
+            profiler.record_sample_now(&metrics);
+        }
+        profiler.record_hotspot("main.bract:3:5".to_string(), 64, MemoryStrategy::Manual);
+
+        let json: serde_json::Value = serde_json::from_str(&profiler.to_json()).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(json["wrapped"], true);
+        assert_eq!(json["discarded_samples"], 2);
+
+        let samples = json["samples"].as_array().unwrap();
+        assert_eq!(samples.len(), 4);
+        for field in ["timestamp_us", "memory_usage_kb", "total_allocations", "allocation_rate", "avg_allocation_size", "fragmentation", "strategy_usage"] {
+            assert!(samples.iter().all(|sample| !sample[field].is_null()), "missing {}", field);
+        }
+        let timestamps: Vec<u64> = samples.iter().map(|sample| sample["timestamp_us"].as_u64().unwrap()).collect();
+        assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", timestamps);
+        // The two oldest samples were overwritten, so the export starts at the third
+        let totals: Vec<u64> = samples.iter().map(|sample| sample["total_allocations"].as_u64().unwrap()).collect();
+        assert_eq!(totals, [5, 6, 8, 9]);
+        assert_eq!(samples[3]["strategy_usage"]["Region"], 6);
+        assert_eq!(samples[3]["memory_usage_kb"], 24);
+
+        assert_eq!(json["hotspots"][0]["location"], "main.bract:3:5");
+        assert_eq!(json["hotspots"][0]["dominant_strategy"], "Manual");
+        assert_eq!(json["metrics"]["current_memory_kb"], 24);
+        assert!(json["metrics"]["allocation_trend"].is_string());
+    }
+
+    #[test]
+    fn test_profiler_chrome_trace_has_counter_events() {
+        let mut profiler = MemoryProfiler::with_config(ProfilerConfig { max_samples: 2, ..ProfilerConfig::default() });
+        let mut metrics = MemoryMetrics::default();
+        for _ in 0..3 {
+            metrics.record_allocation(MemoryStrategy::Stack, 1024);
+            profiler.record_sample_now(&metrics);
+        }
+
+        let trace: serde_json::Value = serde_json::from_str(&profiler.to_chrome_trace()).unwrap();
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events[0]["ph"], "i", "wraparound must be marked: {:?}", events[0]);
+        assert_eq!(events[0]["args"]["discarded_samples"], 1);
+
+        let counters: Vec<_> = events.iter().filter(|event| event["ph"] == "C").collect();
+        assert_eq!(counters.len(), 2 * 4);
+        assert!(counters.iter().all(|event| event["ts"].is_u64() && event["pid"] == 1 && event["args"].is_object()));
+        let timestamps: Vec<u64> = counters.iter().map(|event| event["ts"].as_u64().unwrap()).collect();
+        assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", timestamps);
+        let memory: Vec<_> = counters.iter().filter(|event| event["name"] == "memory_usage_kb").map(|event| event["args"]["kb"].clone()).collect();
+        assert_eq!(memory, [2, 3]);
+    }
+
+    #[test]
+    fn test_runtime_bridge_requires_imported_functions() {
+        let mut module = test_module();
+        let mut bridge = RuntimeBridge::new();
+        bridge.initialize(&mut module).unwrap();
+
+        let mut func = Function::new();
+        let mut builder_context = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut func, &mut builder_context);
+        let entry = builder.create_block();
+        builder.switch_to_block(entry);
+        let size = builder.ins().iconst(ctypes::I64, 128);
+
+        assert!(bridge.generate_malloc_call(&mut builder, size).is_err());
+    }
+}
@@ -8,15 +8,20 @@
 //! - `context`: Manages Cranelift compilation context and state
 //! - `types`: Maps Bract types to Cranelift types
 //! - `functions`: Handles function compilation and calling conventions
+//! - `closures`: Lifts non-capturing closures into module-level functions
 //! - `expressions`: Compiles expressions to Cranelift IR
 //! - `statements`: Compiles statements and control flow
 //! - `memory`: Revolutionary hybrid memory management system
+//! - `addressing`: Array element and struct field addresses, loads and stores
+//! - `profiling`: Runtime profiling hooks for instrumented builds
+//! - `debuginfo`: DWARF line tables mapping machine code back to source lines
 //! - `runtime`: Runtime system integration
 
-use crate::ast::{Module, Item};
+use crate::ast::{Module, Item, Expr, InternedString, SourceMap, Span};
 use crate::semantic::SymbolTable;
 use crate::parser::StringInterner;
-use super::{CodegenResult, CodegenError};
+use super::{CodegenResult, CodegenError, CodegenOptions};
+use crate::metrics::FunctionTiming;
 
 use cranelift::prelude::{types as ctypes, Type, AbiParam, InstBuilder};
 use cranelift_codegen::settings::{self, Configurable};
@@ -24,18 +29,28 @@ use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
 use cranelift_module::{Module as CraneliftModule, Linkage};
 use cranelift_object::{ObjectModule, ObjectBuilder};
 use target_lexicon::Triple;
-use cranelift_codegen::Context;
+use cranelift_codegen::{gimli, Context};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
 
 pub mod context;
 pub mod types;
 pub mod functions;
+pub mod closures;
 pub mod expressions;
 pub mod statements;
 pub mod memory;
+pub mod addressing;
+pub mod profiling;
+pub mod intrinsics;
+pub mod debuginfo;
 pub mod runtime;
 
-pub use context::CraneliftContext;
-pub use memory::{BractMemoryManager, MemoryStrategy, MemoryAnnotation, parse_annotation, AllocationOptions, AllocationResult, LeakWarning, LeakSeverity, LeakType, AlignmentHint, RegionOptimizationResult, AllocationHotspot, MemoryPressure, AllocationTrend};
+pub use functions::FunctionIr;
+pub use context::{CraneliftContext, ModuleView, FunctionEntry, TypeLayout, StructLayout, FieldLayout, EnumLayout, VariantLayout};
+pub use memory::{BractMemoryManager, MemoryMetrics, MemoryStrategy, MemoryPolicy, StackAllocation, StackUsageWarning, TypeStrategy, type_strategy_report, MemoryAnnotation, MemoryAttributes, AllocationOptions, RegionOverflowPolicy, AllocationResult, SmartPointerRef, EscapeInfo, FunctionLeakScope, LeakWarning, LeakSeverity, LeakType, AlignmentHint, RegionOptimizationResult, AllocationHotspot, MemoryPressure, AllocationTrend};
 
 /// Cranelift code generator - produces native machine code with hybrid memory management
 pub struct CraneliftCodeGenerator {
@@ -44,110 +59,256 @@ pub struct CraneliftCodeGenerator {
     /// Object module for code generation
     module: Option<ObjectModule>,
     /// Symbol table from semantic analysis
-    #[allow(dead_code)] // TODO: Use for symbol resolution when implementing advanced features
     symbol_table: SymbolTable,
     /// String interner for name resolution
     interner: StringInterner,
     /// Target triple
     target_triple: Triple,
+    /// Optimization, verification and PIC settings
+    options: CodegenOptions,
     /// Function builder context (reused for performance)
     builder_context: FunctionBuilderContext,
     /// **REVOLUTIONARY**: Hybrid memory management system
     memory_manager: BractMemoryManager,
+    /// Leak warnings collected from every compiled function
+    leak_warnings: Vec<LeakWarning>,
+    /// IR of every compiled function, when `CodegenOptions::capture_ir` is set
+    function_ir: Vec<FunctionIr>,
+    /// Number of function bodies compiled
+    functions_compiled: usize,
+    /// Time each function body took, when `CodegenOptions::time_functions` is set
+    function_timings: Vec<FunctionTiming>,
+    /// Time spent finishing and emitting the object
+    emit_time: Duration,
+}
+
+/// Error for using the object module after `generate` finished it
+fn module_finished() -> CodegenError {
+    CodegenError::InternalError("Code was already generated; use a new code generator for another module".to_string())
+}
+
+/// Threads compiling function bodies in parallel, or `None` if they cannot be started
+///
+/// Bodies are as deep as the parser allows, so each thread gets the stack the
+/// compiler itself runs on rather than rayon's default.
+fn body_pool() -> Option<&'static rayon::ThreadPool> {
+    static POOL: OnceLock<Option<rayon::ThreadPool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .thread_name(|index| format!("bract-codegen-{}", index))
+            .stack_size(crate::compile::COMPILER_STACK_SIZE)
+            .build()
+            .ok()
+    })
+    .as_ref()
 }
 
 impl CraneliftCodeGenerator {
     /// Create a new Cranelift code generator with hybrid memory management
-    pub fn new(symbol_table: SymbolTable, interner: StringInterner) -> CodegenResult<Self> {
-        let target_triple = Triple::host();
+    pub fn new(symbol_table: SymbolTable, interner: StringInterner, options: CodegenOptions) -> CodegenResult<Self> {
+        Self::new_with_target(symbol_table, interner, Triple::host(), options)
+    }
+    
+    /// Create a code generator that emits objects for `target_triple` (cross-compilation)
+    pub fn new_with_target(
+        symbol_table: SymbolTable,
+        interner: StringInterner,
+        target_triple: Triple,
+        options: CodegenOptions,
+    ) -> CodegenResult<Self> {
+        let context = CraneliftContext::new(interner.clone());
+        let module = Self::build_module(&target_triple, &options, &utils::object_name(context.source_file()))?;
+        let memory_manager = BractMemoryManager::new().with_policy(options.memory_policy);
+        
+        Ok(Self {
+            context,
+            module: Some(module),
+            symbol_table,
+            interner,
+            target_triple,
+            options,
+            builder_context: FunctionBuilderContext::new(),
+            memory_manager,
+            leak_warnings: Vec::new(),
+            function_ir: Vec::new(),
+            functions_compiled: 0,
+            function_timings: Vec::new(),
+            emit_time: Duration::ZERO,
+        })
+    }
+    
+    /// Create the object module `name` for a target
+    fn build_module(target_triple: &Triple, options: &CodegenOptions, name: &str) -> CodegenResult<ObjectModule> {
+        let bool_setting = |enabled: bool| if enabled { "true" } else { "false" };
         
-        // Create optimized settings for native code generation
+        // Map the codegen options onto Cranelift settings
         let mut flag_builder = settings::builder();
         flag_builder.set("use_colocated_libcalls", "false")
             .map_err(|e| CodegenError::InternalError(format!("Failed to set compiler flag: {}", e)))?;
-        flag_builder.set("is_pic", "false")
+        flag_builder.set("is_pic", bool_setting(options.pic))
+            .map_err(|e| CodegenError::InternalError(format!("Failed to set compiler flag: {}", e)))?;
+        flag_builder.set("opt_level", options.opt_level.as_setting())
             .map_err(|e| CodegenError::InternalError(format!("Failed to set compiler flag: {}", e)))?;
-        flag_builder.set("opt_level", "speed")
+        flag_builder.set("enable_verifier", bool_setting(options.enable_verifier))
             .map_err(|e| CodegenError::InternalError(format!("Failed to set compiler flag: {}", e)))?;
         
         let isa_builder = cranelift_codegen::isa::lookup(target_triple.clone())
-            .map_err(|e| CodegenError::InternalError(format!("Failed to create ISA: {}", e)))?;
+            .map_err(|e| CodegenError::NativeCompilation(utils::unsupported_target_message(&target_triple.to_string(), e)))?;
         
         let isa = isa_builder.finish(settings::Flags::new(flag_builder))
             .map_err(|e| CodegenError::InternalError(format!("Failed to finalize ISA: {}", e)))?;
         
         // Create object module
-        let object_builder = ObjectBuilder::new(isa, "bract_program", cranelift_module::default_libcall_names())
+        let object_builder = ObjectBuilder::new(isa, name, cranelift_module::default_libcall_names())
             .map_err(|e| CodegenError::InternalError(format!("Failed to create object builder: {}", e)))?;
         
-        let module = ObjectModule::new(object_builder);
-        
-        Ok(Self {
-            context: CraneliftContext::new(),
-            module: Some(module),
-            symbol_table,
-            interner,
-            target_triple,
-            builder_context: FunctionBuilderContext::new(),
-            memory_manager: BractMemoryManager::new(),
-        })
+        Ok(ObjectModule::new(object_builder))
+    }
+    
+    /// Enable or disable position-independent code (required for shared-library targets)
+    ///
+    /// Must be called before `generate`, since it recreates the object module.
+    pub fn set_pic(&mut self, pic: bool) -> CodegenResult<()> {
+        if self.module.is_none() {
+            return Err(CodegenError::InternalError("Cannot change PIC mode after code generation".to_string()));
+        }
+        let options = CodegenOptions { pic, ..self.options.clone() };
+        self.module = Some(Self::build_module(&self.target_triple, &options, &utils::object_name(self.context.source_file()))?);
+        self.options = options;
+        Ok(())
+    }
+    
+    /// Whether position-independent code is generated
+    pub fn is_pic(&self) -> bool {
+        self.options.pic
+    }
+    
+    /// Options this generator was created with
+    pub fn options(&self) -> &CodegenOptions {
+        &self.options
+    }
+    
+    /// Interner used to resolve the compiled module's names
+    pub fn interner(&self) -> &StringInterner {
+        &self.interner
     }
     
     /// Generate native code for a module with hybrid memory management
+    ///
+    /// Errors caused by the module's source name the file they point into.
     pub fn generate(&mut self, module: &Module) -> CodegenResult<Vec<u8>> {
+        self.generate_module(module).map_err(|error| error.in_file(self.context.source_map()))
+    }
+    
+    fn generate_module(&mut self, module: &Module) -> CodegenResult<Vec<u8>> {
         // **REVOLUTIONARY**: Initialize hybrid memory management runtime
         {
-            let module_ref = self.module.as_mut().unwrap();
+            let module_ref = self.module.as_mut().ok_or_else(module_finished)?;
             self.memory_manager.initialize_runtime(module_ref)?;
         }
         
-        // Phase 1: Declare all functions first (signatures only)
-        for item in &module.items {
-            if let Item::Function { .. } = item {
-                let module_ref = self.module.as_mut().unwrap();
-                functions::declare_function_item(module_ref, item, &mut self.context, &self.interner)?;
-            }
+        // Bindings without an annotation take the type semantic analysis resolved
+        self.context.set_binding_types(self.symbol_table.binding_types());
+        // Allocation sites pick their strategy from where their values escape to
+        self.context.set_escape_facts(self.symbol_table.escape_facts().clone());
+        // Debug builds attribute every statement's instructions to its source line
+        self.context.set_debug_info(self.options.emit_debug_info);
+        
+        // Phase 1: Declare all functions first (signatures only), so calls may refer to
+        // functions defined later in the file. Impl block methods are flattened into
+        // `Type_method` functions taking `self` first.
+        let methods = functions::flatten_impl_items(&module.items, &self.interner)?;
+        let mut function_items = functions::function_items(&module.items);
+        function_items.extend(functions::function_items(&methods));
+        // Closures become functions of their own, declared and compiled like the others
+        let closures = closures::lift_closures(&function_items, &self.interner, self.context.source_map())?;
+        function_items.extend(&closures);
+        for item in &function_items {
+            let module_ref = self.module.as_mut().ok_or_else(module_finished)?;
+            functions::declare_function_item(module_ref, item, &mut self.context, &self.interner)?;
         }
         
-        // Phase 2: Declare all structs with memory strategy analysis
-        for item in &module.items {
-            if let Item::Struct { .. } = item {
-                // TODO: Analyze struct memory requirements and strategies
-                // For now, just skip structs - they don't need Cranelift declarations
-                continue;
-            }
+        // Intrinsics the module calls are declared as runtime imports, and its string
+        // literals defined as data
+        {
+            let uses = intrinsics::intrinsic_uses(module, &self.interner, |name| self.context.get_function(name).is_some());
+            let module_ref = self.module.as_mut().ok_or_else(module_finished)?;
+            let declared = intrinsics::Intrinsics::declare(module_ref, &uses)?;
+            self.context.set_intrinsics(declared);
         }
         
-        // Phase 3: Compile all function bodies with memory management
+        // Instrumented builds number every function and declare the profiling hooks
+        if self.options.profile_instrumentation {
+            let names = functions::profiled_function_names(&function_items, &self.interner);
+            let module_ref = self.module.as_mut().ok_or_else(module_finished)?;
+            let hooks = profiling::ProfileHooks::declare(module_ref, &names)?;
+            self.context.register_data(profiling::PROFILE_TABLE_SYMBOL, hooks.table());
+            self.context.set_profile_hooks(hooks);
+        }
+        
+        // Phase 2: Lay out all structs and enums; discriminants may name constants
+        functions::check_layout_nesting(&module.items, &self.interner)?;
+        let constants: HashMap<InternedString, &Expr> = module.items.iter()
+            .filter_map(|item| match item {
+                Item::Const { name, value, .. } => Some((*name, value)),
+                _ => None,
+            })
+            .collect();
         for item in &module.items {
+            let module_ref = self.module.as_ref().ok_or_else(module_finished)?;
             match item {
-                Item::Function { .. } => {
-                    let module_ref = self.module.as_mut().unwrap();
-                    // TODO: Integrate memory manager into function compilation
-                    functions::compile_function_item(module_ref, item, &mut self.builder_context, &mut self.context, &self.interner)?;
-                }
-                _ => {
-                    // Skip non-function items for now
-                    continue;
-                }
+                Item::Struct { .. } => functions::declare_struct_item(module_ref, item, &mut self.context, &self.interner)?,
+                Item::Enum { .. } => functions::declare_enum_item(module_ref, item, &constants, &mut self.context, &self.interner)?,
+                _ => {}
             }
         }
+        // Each struct gets a memory strategy: its annotation's, or one inferred from its
+        // size and where its literals escape to
+        let literal_sites = functions::struct_literal_sites(module, &self.interner);
+        for item in module.items.iter().filter(|item| matches!(item, Item::Struct { .. })) {
+            functions::decide_struct_strategy(item, &literal_sites, &mut self.context, &self.interner, self.memory_manager.policy())?;
+        }
+        
+        // Phase 3: Compile all function bodies with memory management. Bodies only read
+        // the declarations and layouts made above, so they are compiled to machine code
+        // in parallel, each against a fork of the memory manager; defining them in the
+        // module stays serial and in source order, keeping the object deterministic.
+        let view = ModuleView::new(self.module.as_ref().ok_or_else(module_finished)?);
+        let (context, interner, memory_manager) = (&self.context, &self.interner, &self.memory_manager);
+        let time_functions = self.options.time_functions;
+        let compile = |builder_context: &mut FunctionBuilderContext, item: &&Item| {
+            let start = time_functions.then(Instant::now);
+            let mut function_memory = memory_manager.fork();
+            let compiled = functions::compile_function_item(view, item, builder_context, context, interner, &mut function_memory);
+            (compiled, function_memory, start.map(|start| start.elapsed()))
+        };
+        let compiled_functions: Vec<_> = match self.options.parallel.then(body_pool).flatten() {
+            Some(pool) => pool.install(|| function_items.par_iter().map_init(FunctionBuilderContext::new, compile).collect()),
+            None => function_items.iter().map(|item| compile(&mut self.builder_context, item)).collect(),
+        };
         
-        // Check if main function exists properly
-        let has_main = module.items.iter().any(|item| {
-            if let Item::Function { name, .. } = item {
-                if let Some(func_name) = self.interner.get(name) {
-                    func_name == "main"
-                } else {
-                    false
+        let mut function_lines = Vec::new();
+        for (item, (compiled, function_memory, elapsed)) in function_items.iter().zip(compiled_functions) {
+            let leak_warnings = self.memory_manager.absorb(function_memory);
+            self.leak_warnings.extend(leak_warnings);
+            if let (Some(duration), Item::Function { name, .. }) = (elapsed, item) {
+                let name = self.interner.resolve(*name).unwrap_or_default();
+                self.function_timings.push(FunctionTiming { name, duration });
+            }
+            if let Some(compiled) = compiled? {
+                self.functions_compiled += 1;
+                if self.options.capture_ir {
+                    self.function_ir.push(compiled.ir(self.module.as_ref().ok_or_else(module_finished)?.declarations()));
                 }
-            } else {
-                false
+                function_lines.extend(compiled.lines());
+                functions::define_compiled_function(self.module.as_mut().ok_or_else(module_finished)?, compiled)?;
             }
-        });
-
-        if !has_main {
-            // Create a default main function that returns 0
+        }
+        
+        // Semantic analysis has checked that there is at most one `main` and that it can
+        // start a program; snippets without one only get a stand-in when asked for
+        let has_main = self.context.get_function(self.interner.intern("main")).is_some();
+        if !has_main && self.options.default_main {
             self.create_default_main()?;
         }
 
@@ -155,15 +316,26 @@ impl CraneliftCodeGenerator {
         // All memory management cleanup and analysis happens here
         
         // Finalize the module and generate machine code
-        let module_ref = self.module.take().unwrap();
-        let object_product = module_ref.finish();
+        let emit_start = Instant::now();
+        let module_ref = self.module.take().ok_or_else(module_finished)?;
+        let mut object_product = module_ref.finish();
+        if self.options.emit_debug_info {
+            let endian = match self.target_triple.endianness() {
+                Ok(target_lexicon::Endianness::Big) => gimli::RunTimeEndian::Big,
+                _ => gimli::RunTimeEndian::Little,
+            };
+            debuginfo::emit_debug_sections(&mut object_product, &function_lines, self.context.source_map(), endian)?;
+        }
         
-        Ok(object_product.emit().unwrap())
+        let object = object_product.emit()
+            .map_err(|e| CodegenError::NativeCompilation(format!("Failed to emit object file: {}", e)));
+        self.emit_time = emit_start.elapsed();
+        object
     }
     
-    /// Create a default main function for modules that don't have one
+    /// Create a `main` returning 0 for modules that don't have one, with `CodegenOptions::default_main`
     fn create_default_main(&mut self) -> CodegenResult<()> {
-        let module = self.module.as_mut().unwrap();
+        let module = self.module.as_mut().ok_or_else(module_finished)?;
         
         // Create main function signature: main() -> i32
         let mut sig = module.make_signature();
@@ -172,7 +344,8 @@ impl CraneliftCodeGenerator {
         let func_id = module.declare_function("main", Linkage::Export, &sig)
             .map_err(|e| CodegenError::InternalError(format!("Failed to declare main function: {}", e)))?;
         
-        self.context.register_function("main", func_id);
+        let entry = FunctionEntry { func_id, signature: sig.clone(), return_type: None, moves_receiver: false };
+        self.context.register_function(self.interner.intern("main"), entry);
         
         // Define main function body
         let mut ctx = Context::new();
@@ -205,7 +378,7 @@ impl CraneliftCodeGenerator {
         Ok(())
     }
     
-    /// **NEW**: Allocate memory using hybrid memory management
+    /// **NEW**: Allocate memory using hybrid memory management for the value written at `span`
     pub fn allocate_memory(
         &mut self,
         builder: &mut FunctionBuilder,
@@ -213,16 +386,18 @@ impl CraneliftCodeGenerator {
         size: u32,
         strategy: Option<MemoryStrategy>,
         region_id: Option<u32>,
+        span: &Span,
     ) -> CodegenResult<cranelift::prelude::Value> {
-        let memory_strategy = strategy.unwrap_or_else(|| {
-            MemoryStrategy::infer_for_type(64, false, true) // Default sensible strategy
-        });
+        // Without escape facts for the value, assume it stays in the function
+        let memory_strategy = strategy.unwrap_or_else(|| MemoryStrategy::infer_for_escape(size, false, false, self.memory_manager.policy()));
         
         let options = AllocationOptions {
             region_id,
-            source_location: "codegen".to_string(),
+            source_location: self.span_location(span),
+            span: Some(*span),
             alignment: None,
             gc_allowed: true,
+            region_overflow: None,
         };
         self.memory_manager.allocate(builder, memory_strategy, object_type, size, options).map(|result| result.ptr)
     }
@@ -241,49 +416,75 @@ impl CraneliftCodeGenerator {
         self.memory_manager.initialize_region(builder, region_id)
     }
     
-    /// **NEW**: Move linear type (transfer ownership)
+    /// **NEW**: Move linear type (transfer ownership) at the given source span
     pub fn move_linear_type(
         &mut self, 
         from: cranelift::prelude::Value, 
-        to: cranelift::prelude::Value
+        to: cranelift::prelude::Value,
+        span: &Span,
     ) -> CodegenResult<()> {
-        self.memory_manager.move_linear(from, to, "codegen_move")
+        let location = self.span_location(span);
+        self.memory_manager.move_linear(from, to, &location)
+    }
+    
+    /// **NEW**: Check linear type usage safety at the given source span
+    pub fn check_linear_safety(&self, value: cranelift::prelude::Value, span: &Span) -> CodegenResult<()> {
+        self.memory_manager.check_linear_usage(value, &self.span_location(span))
+    }
+    
+    /// Set the source file name used in diagnostics and to name the object module
+    ///
+    /// Must be called before `generate`, since it recreates the object module.
+    pub fn set_source_file(&mut self, source_file: &str) -> CodegenResult<()> {
+        if self.module.is_none() {
+            return Err(CodegenError::InternalError("Cannot change the source file after code generation".to_string()));
+        }
+        self.context.set_source_file(source_file);
+        self.module = Some(Self::build_module(&self.target_triple, &self.options, &utils::object_name(source_file))?);
+        Ok(())
+    }
+    
+    /// Set the names of every source file a module loaded from several files was parsed from
+    ///
+    /// The default file of `source_map` becomes the source file; see `set_source_file`.
+    pub fn set_source_map(&mut self, source_map: SourceMap) -> CodegenResult<()> {
+        let source_file = source_map.default_file().to_string();
+        self.set_source_file(&source_file)?;
+        self.context.set_source_map(source_map);
+        Ok(())
     }
     
-    /// **NEW**: Check linear type usage safety
-    pub fn check_linear_safety(&self, value: cranelift::prelude::Value) -> CodegenResult<()> {
-        self.memory_manager.check_linear_usage(value, "codegen_usage_check")
+    /// Describe a source span as `file:line:column`
+    fn span_location(&self, span: &Span) -> String {
+        self.context.source_location(&span.start)
     }
     
     /// **NEW**: Generate bounds checking code with optimal performance
     pub fn generate_bounds_check(
-        &self,
+        &mut self,
         builder: &mut FunctionBuilder,
-        ptr: cranelift::prelude::Value,
-        size: cranelift::prelude::Value,
-        access_size: u32,
+        index: cranelift::prelude::Value,
+        length: cranelift::prelude::Value,
     ) -> CodegenResult<()> {
-        self.memory_manager.generate_bounds_check(builder, ptr, size, access_size)
+        self.memory_manager.generate_bounds_check(builder, index, length)
     }
     
     /// **NEW**: Increment smart pointer reference count
-        pub fn smart_pointer_inc_ref(
+    pub fn smart_pointer_inc_ref(
         &mut self,
-        _builder: &mut FunctionBuilder,
-        _ptr: cranelift::prelude::Value
+        builder: &mut FunctionBuilder,
+        ptr: cranelift::prelude::Value
     ) -> CodegenResult<()> {
-        // TODO: Implement smart pointer increment
-        Ok(())
+        self.memory_manager.retain_smart_pointer(builder, ptr)
     }
     
-    /// **NEW**: Decrement smart pointer reference count
-        pub fn smart_pointer_dec_ref(
+    /// **NEW**: Decrement smart pointer reference count, freeing the allocation at zero
+    pub fn smart_pointer_dec_ref(
         &mut self,
-        _builder: &mut FunctionBuilder,
-        _ptr: cranelift::prelude::Value
+        builder: &mut FunctionBuilder,
+        ptr: cranelift::prelude::Value
     ) -> CodegenResult<()> {
-        // TODO: Implement smart pointer decrement  
-        Ok(())
+        self.memory_manager.release_smart_pointer(builder, ptr)
     }
     
     /// **NEW**: Cleanup function memory (called at end of each function)
@@ -296,15 +497,115 @@ impl CraneliftCodeGenerator {
         &self.target_triple
     }
     
+    /// Pointer type of the target (I32 or I64 depending on its pointer width)
+    pub fn pointer_type(&self) -> Type {
+        Type::triple_pointer_type(&self.target_triple)
+    }
+    
+    /// Memory strategy decided for each struct type, by type name
+    pub fn type_strategies(&self) -> Vec<(String, TypeStrategy)> {
+        let mut strategies: Vec<_> = self.context.get_type_strategies().iter()
+            .map(|(name, strategy)| (name.clone(), strategy.clone()))
+            .collect();
+        strategies.sort_by(|(a, _), (b, _)| a.cmp(b));
+        strategies
+    }
+    
     /// **NEW**: Get memory manager reference
     pub fn memory_manager(&mut self) -> &mut BractMemoryManager {
         &mut self.memory_manager
     }
+    
+    /// IR of the functions compiled by `generate`, in source order
+    ///
+    /// Empty unless the generator was created with `CodegenOptions::capture_ir`.
+    pub fn function_ir(&self) -> &[FunctionIr] {
+        &self.function_ir
+    }
+    
+    /// Leak warnings reported by the memory manager during the last `generate` call
+    pub fn leak_warnings(&self) -> &[LeakWarning] {
+        &self.leak_warnings
+    }
+
+    /// Functions whose stack allocations exceed `CodegenOptions::memory_policy`'s limit
+    pub fn stack_warnings(&self) -> &[StackUsageWarning] {
+        self.memory_manager.stack_warnings()
+    }
+
+    /// What the memory manager allocated while generating code
+    pub fn memory_metrics(&self) -> &MemoryMetrics {
+        self.memory_manager.metrics()
+    }
+
+    /// Number of function bodies `generate` compiled
+    pub fn functions_compiled(&self) -> usize {
+        self.functions_compiled
+    }
+
+    /// Time each function body took to compile, in source order
+    ///
+    /// Empty unless the generator was created with `CodegenOptions::time_functions`.
+    pub fn function_timings(&self) -> &[FunctionTiming] {
+        &self.function_timings
+    }
+
+    /// Time `generate` spent finishing the object module and emitting the object
+    pub fn emit_time(&self) -> Duration {
+        self.emit_time
+    }
 }
 
 /// Utility functions for Cranelift code generation with memory management
 pub mod utils {
     use super::*;
+    use std::str::FromStr;
+    
+    /// Object module name used when the source is not a file
+    pub const DEFAULT_OBJECT_NAME: &str = "bract_program";
+    
+    /// Name of the object module compiled from `source_file`: the file name without
+    /// its extension, so identical inputs always produce identical objects
+    pub fn object_name(source_file: &str) -> String {
+        std::path::Path::new(source_file)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !stem.is_empty() && source_file != context::UNNAMED_SOURCE)
+            .unwrap_or(DEFAULT_OBJECT_NAME)
+            .to_string()
+    }
+    
+    /// Targets that are known to work with the Cranelift backend
+    pub const SUPPORTED_TARGETS: &[&str] = &[
+        "x86_64-unknown-linux-gnu",
+        "x86_64-apple-darwin",
+        "x86_64-pc-windows-msvc",
+        "aarch64-unknown-linux-gnu",
+        "aarch64-apple-darwin",
+        "riscv64gc-unknown-linux-gnu",
+        "s390x-unknown-linux-gnu",
+    ];
+    
+    /// Parse a target triple such as "x86_64-unknown-linux-gnu", checking that Cranelift can generate code for it
+    pub fn parse_target_triple(target: &str) -> CodegenResult<Triple> {
+        let triple = Triple::from_str(target)
+            .map_err(|e| CodegenError::NativeCompilation(unsupported_target_message(target, e)))?;
+        
+        cranelift_codegen::isa::lookup(triple.clone())
+            .map_err(|e| CodegenError::NativeCompilation(unsupported_target_message(target, e)))?;
+        
+        Ok(triple)
+    }
+    
+    /// Describe why a target can't be used, listing the supported targets
+    pub fn unsupported_target_message(target: &str, reason: impl std::fmt::Display) -> String {
+        format!(
+            "Unsupported target '{}': {}\nSupported targets: {}",
+            target,
+            reason,
+            SUPPORTED_TARGETS.join(", ")
+        )
+    }
     
     /// Convert a Bract type to a Cranelift type
     pub fn bract_to_cranelift_type(bract_type: &str) -> CodegenResult<Type> {
@@ -328,7 +629,7 @@ pub mod utils {
     }
     
     /// **NEW**: Get size of Cranelift type in bytes
-    pub fn type_size(cranelift_type: Type) -> usize {
+    pub fn type_size(cranelift_type: Type, pointer_type: Type) -> usize {
         match cranelift_type {
             t if t == ctypes::I8 => 1,
             t if t == ctypes::I16 => 2,
@@ -336,18 +637,39 @@ pub mod utils {
             t if t == ctypes::I64 => 8,
             t if t == ctypes::F32 => 4,
             t if t == ctypes::F64 => 8,
-            _ => 8, // Default to pointer size for unknown types
+            _ => pointer_type.bytes() as usize, // Default to pointer size for unknown types
+        }
+    }
+    
+    /// Map an AST memory strategy onto the codegen strategy (`Inferred` has no direct mapping)
+    pub fn from_ast_strategy(strategy: crate::ast::MemoryStrategy) -> Option<MemoryStrategy> {
+        match strategy {
+            crate::ast::MemoryStrategy::Stack => Some(MemoryStrategy::Stack),
+            crate::ast::MemoryStrategy::Linear => Some(MemoryStrategy::Linear),
+            crate::ast::MemoryStrategy::Region => Some(MemoryStrategy::Region),
+            crate::ast::MemoryStrategy::Manual => Some(MemoryStrategy::Manual),
+            crate::ast::MemoryStrategy::SmartPtr => Some(MemoryStrategy::SmartPtr),
+            crate::ast::MemoryStrategy::Inferred => None,
         }
     }
     
-    /// **NEW**: Parse memory strategy from user attribute
-    pub fn parse_memory_strategy(attribute: &str) -> Option<MemoryStrategy> {
-        match parse_annotation(attribute)? {
-            MemoryAnnotation::Manual => Some(MemoryStrategy::Manual),
-            MemoryAnnotation::Smart => Some(MemoryStrategy::SmartPtr),
-            MemoryAnnotation::Linear => Some(MemoryStrategy::Linear),
-            MemoryAnnotation::Region(_) => Some(MemoryStrategy::Region),
-            MemoryAnnotation::Stack => Some(MemoryStrategy::Stack),
+    /// **NEW**: Resolve the allocation strategy requested by a type annotation
+    ///
+    /// Explicit strategies on the type win; otherwise strategy wrapper paths such as
+    /// `SmartPtr<T>` select their strategy by name.
+    pub fn strategy_from_ast_type(ast_type: &crate::ast::Type, interner: &StringInterner) -> Option<MemoryStrategy> {
+        if let Some(strategy) = ast_type.memory_strategy().and_then(from_ast_strategy) {
+            return Some(strategy);
+        }
+        
+        match ast_type {
+            crate::ast::Type::Path { segments, .. } => match interner.resolve(*segments.last()?)?.as_str() {
+                "SmartPtr" => Some(MemoryStrategy::SmartPtr),
+                "LinearPtr" => Some(MemoryStrategy::Linear),
+                "RegionPtr" => Some(MemoryStrategy::Region),
+                "StackPtr" => Some(MemoryStrategy::Stack),
+                _ => None,
+            },
             _ => None,
         }
     }
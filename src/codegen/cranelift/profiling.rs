@@ -0,0 +1,127 @@
+//! Runtime Profiling Instrumentation
+//!
+//! When `CodegenOptions::profile_instrumentation` is set, every compiled function
+//! calls `bract_profile_enter(id)` on entry and `bract_profile_exit(id)` before
+//! each return. The numeric ids are listed, one `<id> <name>` line each, in the
+//! NUL-terminated `bract_profile_functions` data symbol so measured counters can
+//! be mapped back to function names.
+
+use super::{CodegenError, CodegenResult};
+use super::context::ModuleView;
+use cranelift::prelude::{types as ctypes, AbiParam, InstBuilder};
+use cranelift_codegen::ir::FuncRef;
+use cranelift_frontend::FunctionBuilder;
+use cranelift_module::{DataDescription, DataId, FuncId, Linkage, Module as CraneliftModule};
+use std::collections::HashMap;
+
+/// Runtime hook called on function entry
+pub const PROFILE_ENTER_SYMBOL: &str = "bract_profile_enter";
+/// Runtime hook called before every return
+pub const PROFILE_EXIT_SYMBOL: &str = "bract_profile_exit";
+/// Data symbol holding the function id -> name table
+pub const PROFILE_TABLE_SYMBOL: &str = "bract_profile_functions";
+
+/// Profiling hooks declared in the module being compiled
+#[derive(Debug, Clone)]
+pub struct ProfileHooks {
+    enter: FuncId,
+    exit: FuncId,
+    /// The `bract_profile_functions` table
+    table: DataId,
+    /// Function name -> numeric profiling id
+    function_ids: HashMap<String, u32>,
+}
+
+/// Exit hook imported into the function being compiled
+#[derive(Debug, Clone, Copy)]
+pub struct ProfileExit {
+    exit: FuncRef,
+    function_id: u32,
+}
+
+impl ProfileHooks {
+    /// Declare the runtime hooks and emit the id table for `function_names`
+    ///
+    /// Ids are assigned in the order the names are given.
+    pub fn declare(module: &mut dyn CraneliftModule, function_names: &[String]) -> CodegenResult<Self> {
+        let mut sig = module.make_signature();
+        sig.params.push(AbiParam::new(ctypes::I32)); // function id
+
+        let enter = module.declare_function(PROFILE_ENTER_SYMBOL, Linkage::Import, &sig)
+            .map_err(|e| CodegenError::InternalError(format!("Failed to declare {}: {}", PROFILE_ENTER_SYMBOL, e)))?;
+        let exit = module.declare_function(PROFILE_EXIT_SYMBOL, Linkage::Import, &sig)
+            .map_err(|e| CodegenError::InternalError(format!("Failed to declare {}: {}", PROFILE_EXIT_SYMBOL, e)))?;
+
+        let table_id = module.declare_data(PROFILE_TABLE_SYMBOL, Linkage::Export, false, false)
+            .map_err(|e| CodegenError::InternalError(format!("Failed to declare {}: {}", PROFILE_TABLE_SYMBOL, e)))?;
+        let mut table = function_table(function_names).into_bytes();
+        table.push(0);
+        let mut description = DataDescription::new();
+        description.define(table.into_boxed_slice());
+        module.define_data(table_id, &description)
+            .map_err(|e| CodegenError::InternalError(format!("Failed to define {}: {}", PROFILE_TABLE_SYMBOL, e)))?;
+
+        let function_ids = function_names.iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id as u32))
+            .collect();
+
+        Ok(Self { enter, exit, table: table_id, function_ids })
+    }
+
+    /// Data object holding the function id -> name table
+    pub fn table(&self) -> DataId {
+        self.table
+    }
+
+    /// Profiling id of a function
+    pub fn function_id(&self, name: &str) -> Option<u32> {
+        self.function_ids.get(name).copied()
+    }
+
+    /// Import the hooks into `builder`'s function and emit the entry call
+    ///
+    /// Returns the exit hook to call before each return, or None for functions
+    /// without a profiling id.
+    pub fn enter_function(
+        &self,
+        module: ModuleView,
+        builder: &mut FunctionBuilder,
+        name: &str,
+    ) -> Option<ProfileExit> {
+        let function_id = self.function_id(name)?;
+        let enter = module.declare_func_in_func(self.enter, builder.func);
+        let exit = module.declare_func_in_func(self.exit, builder.func);
+
+        let id = builder.ins().iconst(ctypes::I32, i64::from(function_id));
+        builder.ins().call(enter, &[id]);
+        Some(ProfileExit { exit, function_id })
+    }
+}
+
+impl ProfileExit {
+    /// Emit the `bract_profile_exit(id)` call
+    pub fn emit(&self, builder: &mut FunctionBuilder) {
+        let id = builder.ins().iconst(ctypes::I32, i64::from(self.function_id));
+        builder.ins().call(self.exit, &[id]);
+    }
+}
+
+/// Text of the function id table: one `<id> <name>` line per function
+pub fn function_table(function_names: &[String]) -> String {
+    function_names.iter()
+        .enumerate()
+        .map(|(id, name)| format!("{} {}\n", id, name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_function_table_lists_ids_in_order() {
+        let names = vec!["main".to_string(), "Point_norm".to_string()];
+        assert_eq!(function_table(&names), "0 main\n1 Point_norm\n");
+    }
+}
@@ -0,0 +1,174 @@
+//! Linking native objects into executables
+//!
+//! Writes the object produced by Cranelift to a temporary file and drives the
+//! system linker to turn it into a runnable program:
+//! - Unix: `cc`, falling back to `clang`/`gcc`
+//! - Windows: `link.exe`, falling back to `lld-link`/`clang`
+//!
+//! Objects importing functions of the native runtime are linked with it, see
+//! `runtime_native`.
+
+use super::{runtime_native, CodegenError, CodegenResult};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Options passed through to the system linker
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LinkOptions {
+    /// Linker executable to use instead of the per-OS default
+    pub linker: Option<PathBuf>,
+    /// Additional object files or sources linked alongside the program, before the runtime
+    pub objects: Vec<PathBuf>,
+    /// Libraries to link against (`-l<name>` / `<name>.lib`)
+    pub libraries: Vec<String>,
+    /// Library search paths (`-L<path>` / `/LIBPATH:<path>`)
+    pub library_paths: Vec<PathBuf>,
+    /// Raw arguments appended to the linker command line
+    pub extra_args: Vec<String>,
+}
+
+/// Command-line conventions understood by a linker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkerFlavor {
+    /// `cc`-style drivers (cc, gcc, clang)
+    Gnu,
+    /// MSVC-style linkers (link.exe, lld-link)
+    Msvc,
+}
+
+impl LinkerFlavor {
+    fn of(linker: &Path) -> Self {
+        let stem = linker
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match stem.as_str() {
+            "link" | "lld-link" => LinkerFlavor::Msvc,
+            _ => LinkerFlavor::Gnu,
+        }
+    }
+}
+
+/// Linkers tried, in order, when `LinkOptions::linker` is not set
+fn default_linkers() -> &'static [&'static str] {
+    if cfg!(windows) {
+        &["link.exe", "lld-link.exe", "clang.exe"]
+    } else {
+        &["cc", "clang", "gcc"]
+    }
+}
+
+/// Object file extension for the host platform
+pub fn object_extension() -> &'static str {
+    if cfg!(windows) { "obj" } else { "o" }
+}
+
+/// Locate a system linker on `PATH`
+pub fn find_linker() -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    default_linkers().iter().find_map(|name| {
+        std::env::split_paths(&path)
+            .map(|dir| dir.join(name))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// Link `object_bytes` into an executable at `output_path`
+pub fn link_executable(object_bytes: &[u8], output_path: &Path, options: LinkOptions) -> CodegenResult<()> {
+    let linker = match &options.linker {
+        Some(linker) => linker.clone(),
+        None => find_linker().ok_or_else(|| CodegenError::NativeCompilation(format!(
+            "No system linker found (tried {})", default_linkers().join(", ")
+        )))?,
+    };
+
+    // The temporary object is removed when `object_file` is dropped
+    let mut object_file = tempfile::Builder::new()
+        .prefix("bract")
+        .suffix(&format!(".{}", object_extension()))
+        .tempfile()
+        .map_err(|e| CodegenError::IoError(format!("Failed to create object file: {}", e)))?;
+    object_file.write_all(object_bytes)
+        .and_then(|_| object_file.flush())
+        .map_err(|e| CodegenError::IoError(format!("Failed to write object file: {}", e)))?;
+
+    // The runtime archive is removed with `runtime_dir`
+    let runtime_dir = tempfile::Builder::new()
+        .prefix("bract-runtime")
+        .tempdir()
+        .map_err(|e| CodegenError::IoError(format!("Failed to create the runtime directory: {}", e)))?;
+    let runtime = match runtime_native::imports_runtime(object_bytes) {
+        true => Some(runtime_native::write_archive(runtime_dir.path())?),
+        false => None,
+    };
+
+    let mut cmd = Command::new(&linker);
+    match LinkerFlavor::of(&linker) {
+        LinkerFlavor::Gnu => {
+            cmd.arg("-o").arg(output_path).arg(object_file.path());
+            cmd.args(&options.objects).args(&runtime);
+            for path in &options.library_paths {
+                cmd.arg(format!("-L{}", path.display()));
+            }
+            for library in &options.libraries {
+                cmd.arg(format!("-l{}", library));
+            }
+        }
+        LinkerFlavor::Msvc => {
+            cmd.arg("/NOLOGO")
+               .arg("/SUBSYSTEM:CONSOLE")
+               .arg(format!("/OUT:{}", output_path.display()))
+               .arg(object_file.path());
+            cmd.args(&options.objects).args(&runtime);
+            for path in &options.library_paths {
+                cmd.arg(format!("/LIBPATH:{}", path.display()));
+            }
+            for library in &options.libraries {
+                cmd.arg(format!("{}.lib", library));
+            }
+        }
+    }
+    cmd.args(&options.extra_args);
+
+    let output = cmd.output().map_err(|e| CodegenError::NativeCompilation(
+        format!("Failed to run linker '{}': {}", linker.display(), e)
+    ))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        // MSVC's link.exe reports errors on stdout
+        let diagnostics = if stderr.trim().is_empty() { stdout } else { stderr };
+        return Err(CodegenError::NativeCompilation(format!(
+            "Linker '{}' failed ({}):\n{}", linker.display(), output.status, diagnostics.trim_end()
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_linker_flavor() {
+        assert_eq!(LinkerFlavor::of(Path::new("/usr/bin/cc")), LinkerFlavor::Gnu);
+        assert_eq!(LinkerFlavor::of(Path::new("clang")), LinkerFlavor::Gnu);
+        assert_eq!(LinkerFlavor::of(Path::new("LINK.EXE")), LinkerFlavor::Msvc);
+        assert_eq!(LinkerFlavor::of(Path::new("lld-link")), LinkerFlavor::Msvc);
+    }
+
+    #[test]
+    fn test_missing_linker_reports_error() {
+        let options = LinkOptions {
+            linker: Some(PathBuf::from("definitely-not-a-linker")),
+            ..LinkOptions::default()
+        };
+        let output = std::env::temp_dir().join("bract_never_linked");
+        let err = link_executable(&[], &output, options).unwrap_err();
+        assert!(matches!(err, CodegenError::NativeCompilation(ref msg) if msg.contains("definitely-not-a-linker")));
+    }
+}
@@ -301,17 +301,17 @@ impl AstToBirLowerer {
         self.next_value_id += 1;
         
         let (bir_type, ownership) = match literal {
-            Literal::Integer { .. } => (
+            Literal::Integer { suffix, .. } => (
                 BIRType::Integer {
-                    width: 32,
-                    signed: true,
+                    width: suffix.and_then(|suffix| suffix.integer_bits()).map_or(32, |bits| bits as u8),
+                    signed: !suffix.is_some_and(|suffix| suffix.is_unsigned()),
                     memory_strategy: MemoryStrategy::Stack,
                 },
                 Ownership::Owned,
             ),
-            Literal::Float { .. } => (
+            Literal::Float { suffix, .. } => (
                 BIRType::Float {
-                    width: 64,
+                    width: if *suffix == Some(PrimitiveType::F32) { 32 } else { 64 },
                     memory_strategy: MemoryStrategy::Stack,
                 },
                 Ownership::Owned,
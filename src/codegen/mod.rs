@@ -6,33 +6,250 @@
 //! - Zero external compiler dependencies
 
 pub mod cranelift;
+pub mod link;
+pub mod runtime_native;
 
-use crate::ast::Module;
+use crate::ast::{InternedString, Module, SourceMap, Span};
+use crate::diagnostics::DiagnosticCode;
+use crate::metrics::{CompileMetrics, Phase};
 use crate::semantic::symbols::SymbolTable;
 use crate::parser::StringInterner;
+use std::path::Path;
+use std::time::Instant;
+
+pub use link::LinkOptions;
+pub use self::cranelift::MemoryPolicy;
+
+/// Optimization level passed to Cranelift
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    /// No optimizations - fastest compiles, easiest to debug
+    None,
+    /// Optimize for speed
+    Speed,
+    /// Optimize for speed and code size
+    SpeedAndSize,
+}
+
+impl OptLevel {
+    /// Name of the level in Cranelift's `opt_level` setting
+    pub fn as_setting(&self) -> &'static str {
+        match self {
+            OptLevel::None => "none",
+            OptLevel::Speed => "speed",
+            OptLevel::SpeedAndSize => "speed_and_size",
+        }
+    }
+}
+
+/// Options controlling native code generation
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodegenOptions {
+    /// Cranelift optimization level
+    pub opt_level: OptLevel,
+    /// Run the Cranelift IR verifier on every function
+    pub enable_verifier: bool,
+    /// Emit DWARF line tables and function entries, so debuggers can step through the source
+    pub emit_debug_info: bool,
+    /// Generate position-independent code (needed for shared libraries)
+    pub pic: bool,
+    /// Call the `bract_profile_enter`/`bract_profile_exit` runtime hooks around every function
+    pub profile_instrumentation: bool,
+    /// Compile function bodies on all available cores
+    pub parallel: bool,
+    /// Keep the IR of every compiled function, for `CraneliftCodeGenerator::function_ir`
+    pub capture_ir: bool,
+    /// Define a `main` returning 0 when the module has none, for compiling snippets
+    /// that are not whole programs
+    pub default_main: bool,
+    /// Where values are placed by size, and how much stack each function may take
+    pub memory_policy: MemoryPolicy,
+    /// Time the compilation of every function body, for `CraneliftCodeGenerator::function_timings`
+    pub time_functions: bool,
+}
+
+impl CodegenOptions {
+    /// Debug-friendly codegen: no optimizations and IR verification on
+    pub fn debug() -> Self {
+        Self {
+            opt_level: OptLevel::None,
+            enable_verifier: true,
+            emit_debug_info: false,
+            pic: false,
+            profile_instrumentation: false,
+            parallel: true,
+            capture_ir: false,
+            default_main: false,
+            memory_policy: MemoryPolicy::default(),
+            time_functions: false,
+        }
+    }
+    
+    /// Release codegen: optimize for speed and size
+    pub fn release() -> Self {
+        Self {
+            opt_level: OptLevel::SpeedAndSize,
+            enable_verifier: false,
+            emit_debug_info: false,
+            pic: false,
+            profile_instrumentation: false,
+            parallel: true,
+            capture_ir: false,
+            default_main: false,
+            memory_policy: MemoryPolicy::default(),
+            time_functions: false,
+        }
+    }
+}
+
+impl Default for CodegenOptions {
+    fn default() -> Self {
+        Self {
+            opt_level: OptLevel::Speed,
+            enable_verifier: cfg!(debug_assertions),
+            emit_debug_info: false,
+            pic: false,
+            profile_instrumentation: false,
+            parallel: true,
+            capture_ir: false,
+            default_main: false,
+            memory_policy: MemoryPolicy::default(),
+            time_functions: false,
+        }
+    }
+}
 
 /// Native code generation pipeline using Cranelift
 pub struct CodegenPipeline {
     /// Cranelift code generator - direct native machine code
     cranelift_generator: cranelift::CraneliftCodeGenerator,
+    /// Timings and counts of what the pipeline compiled
+    metrics: CompileMetrics,
 }
 
 impl CodegenPipeline {
     /// Create a new native code generation pipeline
-    pub fn new(symbol_table: SymbolTable, interner: StringInterner) -> Result<Self, String> {
-        let cranelift_generator = cranelift::CraneliftCodeGenerator::new(symbol_table, interner)
+    pub fn new(symbol_table: SymbolTable, interner: StringInterner, options: CodegenOptions) -> Result<Self, String> {
+        let cranelift_generator = cranelift::CraneliftCodeGenerator::new(symbol_table, interner, options)
+            .map_err(|e| format!("Failed to create Cranelift generator: {:?}", e))?;
+        
+        Ok(Self {
+            cranelift_generator,
+            metrics: CompileMetrics::default(),
+        })
+    }
+    
+    /// Create a pipeline that cross-compiles for `target`, e.g. "x86_64-unknown-linux-gnu"
+    pub fn new_with_target(
+        symbol_table: SymbolTable,
+        interner: StringInterner,
+        target: &str,
+        options: CodegenOptions,
+    ) -> Result<Self, String> {
+        let triple = cranelift::utils::parse_target_triple(target)
+            .map_err(|e| e.to_string())?;
+        let cranelift_generator = cranelift::CraneliftCodeGenerator::new_with_target(symbol_table, interner, triple, options)
             .map_err(|e| format!("Failed to create Cranelift generator: {:?}", e))?;
         
         Ok(Self {
             cranelift_generator,
+            metrics: CompileMetrics::default(),
         })
     }
     
+    /// Enable position-independent code (needed for shared-library targets)
+    pub fn set_pic(&mut self, pic: bool) -> Result<(), String> {
+        self.cranelift_generator.set_pic(pic)
+            .map_err(|e| format!("Failed to configure PIC: {:?}", e))
+    }
+    
+    /// Interner shared with the parser that produced the compiled module
+    pub fn interner(&self) -> &StringInterner {
+        self.cranelift_generator.interner()
+    }
+    
+    /// Fingerprint of a module as analyzed, for skipping regeneration of unchanged modules
+    ///
+    /// Covers every node and span of the AST and the names its interned ids stand for.
+    /// Code generation is deterministic, so an unchanged fingerprint compiled with
+    /// unchanged options produces the same object. The hash (FNV-1a) is stable across
+    /// runs and toolchains, so fingerprints may be stored between builds.
+    pub fn module_fingerprint(&self, module: &Module) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+        let fnv1a = |hash: u64, bytes: &[u8]| bytes.iter()
+            .fold(hash, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME));
+        
+        let interner = self.interner();
+        let names = (0..).map_while(|id| interner.resolve(InternedString::new(id)));
+        let hash = fnv1a(FNV_OFFSET_BASIS, format!("{:?}", module).as_bytes());
+        // Names are separated by a NUL so `ab`,`c` and `a`,`bc` differ
+        names.fold(hash, |hash, name| fnv1a(fnv1a(hash, name.as_bytes()), &[0]))
+    }
+    
+    /// Timings and counts of the modules compiled so far
+    ///
+    /// Function bodies are only timed with `CodegenOptions::time_functions`.
+    pub fn metrics(&self) -> &CompileMetrics {
+        &self.metrics
+    }
+    
     /// Compile a module directly to native machine code
     pub fn compile_module(&mut self, module: &Module) -> Result<Vec<u8>, String> {
         // Direct native compilation using Cranelift
-        self.cranelift_generator.generate(module)
-            .map_err(|e| format!("Native compilation error: {:?}", e))
+        let start = Instant::now();
+        let object = self.cranelift_generator.generate(module)
+            .map_err(|e| format!("Native compilation error: {:?}", e))?;
+        self.metrics.record_codegen(&self.cranelift_generator, start.elapsed(), object.len());
+        Ok(object)
+    }
+    
+    /// Parse, analyze, compile and link `source` into an executable at `output_path`,
+    /// returning how long each phase took
+    pub fn compile_to_executable(
+        source: &str,
+        output_path: &Path,
+        options: CodegenOptions,
+        link_options: LinkOptions,
+    ) -> Result<CompileMetrics, String> {
+        let mut metrics = CompileMetrics::default();
+        let parse_start = Instant::now();
+        let mut parser = crate::Parser::new(source, 0)
+            .map_err(|e| format!("Parser creation failed: {}", e))?;
+        let module = parser.parse_module()
+            .map_err(|e| format!("Parse error: {:?}", e))?;
+        metrics.record_phase(Phase::Parse, parse_start.elapsed());
+        metrics.tokens_lexed = parser.tokens_lexed();
+        metrics.items_parsed = module.items.len();
+        // Items that failed to parse are dropped by error recovery - don't link a partial program
+        if let Some(error) = parser.errors().first() {
+            return Err(format!("Parse error: {:?}", error));
+        }
+        let interner = parser.interner();
+        
+        let config = crate::semantic::AnalyzerConfig { require_entry_point: true, ..Default::default() };
+        let analysis = metrics.time(Phase::Semantic, || {
+            crate::semantic::SemanticAnalyzer::with_config(config)
+                .with_interner(interner.clone())
+                .analyze(&module)
+        });
+        metrics.functions_analyzed = analysis.stats.functions_analyzed;
+        if !analysis.errors.is_empty() {
+            let error_msg = analysis.errors
+                .iter()
+                .map(|e| format!("{:?}", e))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(format!("Semantic errors: {}", error_msg));
+        }
+        
+        let mut pipeline = Self::new(analysis.symbol_table, interner, options)?;
+        pipeline.metrics = metrics;
+        let object_bytes = pipeline.compile_module(&module)?;
+        
+        pipeline.metrics.time(Phase::Link, || link::link_executable(&object_bytes, output_path, link_options))
+            .map_err(|e| e.to_string())?;
+        Ok(pipeline.metrics)
     }
 }
 
@@ -56,6 +273,8 @@ pub enum CodegenError {
     IoError(String),
     /// Internal compiler error
     InternalError(String),
+    /// `error`, caused by the source at `span` in `file`, once the file is known
+    Spanned { span: Span, file: Option<String>, error: Box<CodegenError> },
 }
 
 impl std::fmt::Display for CodegenError {
@@ -68,6 +287,63 @@ impl std::fmt::Display for CodegenError {
             CodegenError::NativeCompilation(msg) => write!(f, "Native compilation error: {}", msg),
             CodegenError::IoError(msg) => write!(f, "IO error: {}", msg),
             CodegenError::InternalError(msg) => write!(f, "Internal compiler error: {}", msg),
+            CodegenError::Spanned { span, file: Some(file), error } => write!(f, "{}:{}:{}: {}", file, span.start.line, span.start.column, error),
+            CodegenError::Spanned { span, file: None, error } => write!(f, "{}:{}: {}", span.start.line, span.start.column, error),
+        }
+    }
+}
+
+impl CodegenError {
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            CodegenError::UnsupportedFeature(_) => DiagnosticCode::UnsupportedFeature,
+            CodegenError::TypeConversion(_) => DiagnosticCode::TypeConversion,
+            CodegenError::SymbolResolution(_) => DiagnosticCode::SymbolResolution,
+            CodegenError::MemoryManagement(_) => DiagnosticCode::MemoryManagement,
+            CodegenError::NativeCompilation(_) => DiagnosticCode::NativeCompilation,
+            CodegenError::IoError(_) => DiagnosticCode::Io,
+            CodegenError::InternalError(_) => DiagnosticCode::InternalError,
+            CodegenError::Spanned { error, .. } => error.code(),
+        }
+    }
+
+    /// The error, caused by the source at `span`
+    ///
+    /// An error that already has a span keeps it: the innermost construct that failed
+    /// locates the problem best, so wrapping a statement's errors in the statement's
+    /// span only locates those that the expressions in it left unlocated.
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            CodegenError::Spanned { .. } => self,
+            error => CodegenError::Spanned { span, file: None, error: Box::new(error) },
+        }
+    }
+
+    /// The error with the name of the file its span points into, from `source_map`
+    pub fn in_file(self, source_map: &SourceMap) -> Self {
+        match self {
+            CodegenError::Spanned { span, file: None, error } => {
+                let file = Some(source_map.file_name(span.start.file_id).to_string());
+                CodegenError::Spanned { span, file, error }
+            }
+            error => error,
+        }
+    }
+
+    /// Where in the source the error is, if it is known
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            CodegenError::Spanned { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// The error without its span
+    pub fn unspanned(&self) -> &CodegenError {
+        match self {
+            CodegenError::Spanned { error, .. } => error.unspanned(),
+            error => error,
         }
     }
 }
@@ -1,35 +1,93 @@
-// Ultra-minimal native runtime for Bract - ZERO external dependencies
-// Completely self-contained - no libc, no system calls
+// Ultra-minimal native runtime for Bract - the heap and printing go through the
+// C library, everything else is self-contained
+//
+// Built into a static archive by build.rs and linked automatically into programs
+// that import any of these functions (see codegen/runtime_native.rs)
 
-// basic heap - super simple bump allocator
-static char heap[1024 * 1024]; // 1MB heap
-static unsigned long heap_pos = 0;
+#include <stddef.h>
+#include <stdio.h>
+#include <stdlib.h>
 
-// minimal malloc - just bump allocator
-void* bract_malloc(unsigned long size) {
-    if (heap_pos + size >= sizeof(heap)) {
-        return 0; // out of memory
+// heap - the C library's blocks are aligned for any type, so the 64-bit
+// reference counts of smart pointers are aligned too. Generated code does not
+// check for null, so running out of memory aborts instead
+void* bract_malloc(size_t size) {
+    void* ptr = malloc(size ? size : 1);
+    if (!ptr) {
+        abort();
     }
-    void* ptr = &heap[heap_pos];
-    heap_pos += size;
     return ptr;
 }
 
-// minimal free - do nothing (bump allocator)
 void bract_free(void* ptr) {
-    // no-op for bump allocator
-    (void)ptr;
+    free(ptr);
 }
 
-// minimal reference counting - just counters
-void bract_arc_inc(int* refcount) {
+// reference counting - a smart pointer to an object of `size` bytes is
+// allocated with size + 8 bytes, its 64-bit count stored at object + size;
+// these take the address of that count
+void bract_arc_inc(long long* refcount) {
     if (refcount) {
         (*refcount)++;
     }
 }
 
-void bract_arc_dec(int* refcount) {
+void bract_arc_dec(long long* refcount) {
     if (refcount && *refcount > 0) {
         (*refcount)--;
     }
-}
\ No newline at end of file
+}
+
+// profiling hooks - called around every function in instrumented builds
+#define BRACT_PROFILE_MAX_FUNCTIONS 1024
+#define BRACT_PROFILE_MAX_DEPTH 256
+
+// per-function counters, indexed by the ids in bract_profile_functions
+unsigned long long bract_profile_cycles[BRACT_PROFILE_MAX_FUNCTIONS];
+unsigned long long bract_profile_calls[BRACT_PROFILE_MAX_FUNCTIONS];
+
+static unsigned long long profile_entry_stack[BRACT_PROFILE_MAX_DEPTH];
+static unsigned int profile_depth = 0;
+
+static unsigned long long bract_read_cycles(void) {
+#if defined(__x86_64__) || defined(__i386__)
+    return __builtin_ia32_rdtsc();
+#else
+    return 0; // no cycle counter - calls are still counted
+#endif
+}
+
+void bract_profile_enter(int function_id) {
+    (void)function_id;
+    if (profile_depth < BRACT_PROFILE_MAX_DEPTH) {
+        profile_entry_stack[profile_depth] = bract_read_cycles();
+    }
+    profile_depth++;
+}
+
+void bract_profile_exit(int function_id) {
+    if (profile_depth == 0) {
+        return;
+    }
+    profile_depth--;
+    if (function_id < 0 || function_id >= BRACT_PROFILE_MAX_FUNCTIONS) {
+        return;
+    }
+    bract_profile_calls[function_id]++;
+    if (profile_depth < BRACT_PROFILE_MAX_DEPTH) {
+        bract_profile_cycles[function_id] += bract_read_cycles() - profile_entry_stack[profile_depth];
+    }
+}
+
+// printing - the print_int and print_str intrinsics; output is flushed on every
+// call so nothing printed is lost when the program traps afterwards
+void bract_print_int(int value) {
+    printf("%d", value);
+    fflush(stdout);
+}
+
+// `data` holds `len` bytes, which may include NULs
+void bract_print_str(const char* data, size_t len) {
+    fwrite(data, 1, len, stdout);
+    fflush(stdout);
+}
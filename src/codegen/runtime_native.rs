@@ -0,0 +1,101 @@
+//! The native runtime compiled programs are linked against
+//!
+//! Generated code does not define its allocator, reference counting or profiling
+//! hooks itself; it imports them from a small C runtime, `native_runtime.c`. The
+//! build script compiles that source into a static archive that is embedded here,
+//! and `link::link_executable` passes the archive to the linker whenever the object
+//! being linked imports one of `RUNTIME_SYMBOLS`. Being an archive, it only adds the
+//! functions a program actually uses, and a runtime passed in `LinkOptions::objects`
+//! takes precedence over it.
+//!
+//! The contract between generated code and the runtime:
+//! - `bract_malloc(size)` returns at least `size` bytes aligned to 8, and
+//!   `bract_free(ptr)` releases them
+//! - a `SmartPtr` to an object of `size` bytes is one `bract_malloc(size + 8)`
+//!   allocation with its reference count, an `i64` starting at 1, at `object + size`
+//! - `bract_arc_inc(count)` and `bract_arc_dec(count)` take the address of that count
+//! - `bract_profile_enter(id)` and `bract_profile_exit(id)` take an `i32` function id
+//! - `bract_print_int(value)` writes an `i32` in decimal to standard output, and
+//!   `bract_print_str(data, len)` writes `len` bytes at `data`, both flushing it
+
+use super::cranelift::profiling::{PROFILE_ENTER_SYMBOL, PROFILE_EXIT_SYMBOL};
+use super::{CodegenError, CodegenResult};
+use std::path::{Path, PathBuf};
+
+/// Functions generated code imports from the runtime
+pub const RUNTIME_SYMBOLS: &[&str] = &[
+    "bract_malloc",
+    "bract_free",
+    "bract_arc_inc",
+    "bract_arc_dec",
+    PROFILE_ENTER_SYMBOL,
+    PROFILE_EXIT_SYMBOL,
+    "bract_print_int",
+    "bract_print_str",
+];
+
+/// C source of the runtime
+pub const SOURCE: &str = include_str!("native_runtime.c");
+
+/// The runtime compiled for the target this crate was built for; empty when the
+/// build had no C compiler
+const ARCHIVE: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/bract_runtime.archive"));
+
+/// The runtime as a static archive, if it could be built
+pub fn archive() -> Option<&'static [u8]> {
+    (!ARCHIVE.is_empty()).then_some(ARCHIVE)
+}
+
+/// File name the archive is linked under
+pub fn archive_file_name() -> &'static str {
+    if cfg!(target_env = "msvc") { "bract_runtime.lib" } else { "libbract_runtime.a" }
+}
+
+/// Whether `object_bytes` names any function of the runtime
+///
+/// Generated objects never define these functions, so a symbol with one of their
+/// names in the object's string table is an import of it. Names are matched whole,
+/// with the underscore Mach-O prefixes them with allowed.
+pub fn imports_runtime(object_bytes: &[u8]) -> bool {
+    RUNTIME_SYMBOLS.iter()
+        .flat_map(|symbol| [format!("\0{}\0", symbol), format!("\0_{}\0", symbol)])
+        .any(|name| object_bytes.windows(name.len()).any(|window| window == name.as_bytes()))
+}
+
+/// Write the runtime archive into `dir`, returning its path
+pub fn write_archive(dir: &Path) -> CodegenResult<PathBuf> {
+    let archive = archive().ok_or_else(|| CodegenError::NativeCompilation(
+        "The program needs the Bract runtime, which was not built: no C compiler was found when the compiler \
+         was built. Install one and rebuild, or pass a runtime in the link options".to_string()
+    ))?;
+    let path = dir.join(archive_file_name());
+    std::fs::write(&path, archive)
+        .map_err(|e| CodegenError::IoError(format!("Failed to write the runtime archive: {}", e)))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_defines_every_imported_symbol() {
+        for symbol in RUNTIME_SYMBOLS {
+            assert!(SOURCE.contains(&format!(" {}(", symbol)), "native_runtime.c does not define {}", symbol);
+        }
+        let Some(archive) = archive() else {
+            println!("Skipping: the runtime archive was not built");
+            return;
+        };
+        assert!(archive.starts_with(b"!<arch>\n"), "the runtime is not a static archive");
+    }
+
+    #[test]
+    fn test_runtime_imports_are_recognized() {
+        assert!(imports_runtime(b"\0\0\0bract_arc_dec\0\0"));
+        assert!(imports_runtime(b"\0_bract_malloc\0"));
+        assert!(!imports_runtime(b"\0\0\0bract_arc_decrement\0\0"));
+        assert!(!imports_runtime(b"\0my_bract_free\0"));
+        assert!(!imports_runtime(&[]));
+    }
+}
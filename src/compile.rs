@@ -0,0 +1,1009 @@
+//! Compiling Bract source through the library
+//!
+//! `compile_source` runs the whole pipeline - lexing, parsing, semantic analysis,
+//! code generation and linking - and reports every problem it finds as a
+//! [`Diagnostic`] rather than an error string. Editor integrations use
+//! `analyze_source`, the front half of the same pipeline, so the library and the
+//! editor cannot disagree about what is wrong with a program.
+
+use crate::ast::{Module, SerializableModule, SourceMap, Span};
+use crate::codegen::cranelift::{
+    type_strategy_report, utils::parse_target_triple, BractMemoryManager, CraneliftCodeGenerator, LeakSeverity, MemoryStrategy,
+    StackUsageWarning, TypeStrategy,
+};
+use crate::codegen::{link, CodegenError, CodegenOptions, CodegenResult, LinkOptions, MemoryPolicy, OptLevel};
+use crate::diagnostics::DiagnosticCode;
+use crate::lexer::Position;
+use crate::metrics::{CompileMetrics, Phase};
+use crate::parser::error::Suggestion;
+use crate::parser::{strip_cfg_items, CfgTarget, ParseError, Parser, StringInterner};
+use crate::semantic::{AnalyzerConfig, SemanticAnalyzer, SemanticError, SemanticWarning, SymbolTable};
+use serde::Serialize;
+use std::any::Any;
+use std::fmt;
+use std::panic;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Instant;
+
+/// Stack size of the thread `compile_source` and `analyze_source` run on, and of the
+/// threads compiling function bodies in parallel
+///
+/// Parsing, analysis and code generation recurse once per level of nesting, up to
+/// `parser::DEFAULT_MAX_NESTING_DEPTH` levels, and once per link of a chain, up to
+/// `parser::DEFAULT_MAX_CHAIN_LENGTH`; an unoptimized build needs far more stack for
+/// that than the 2 MiB a new thread gets by default.
+pub const COMPILER_STACK_SIZE: usize = 64 * 1024 * 1024;
+
+/// Kind of artifact `compile_source` produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmitKind {
+    /// Native object file
+    #[default]
+    Object,
+    /// Linked executable, written to `CompileOptions::output`
+    Executable,
+    /// The parsed module as JSON - semantic analysis and code generation are skipped
+    AstJson,
+}
+
+/// Options for `compile_source`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileOptions {
+    /// Target triple, e.g. "x86_64-unknown-linux-gnu"; the host when unset
+    pub target: Option<String>,
+    /// Cranelift optimization level
+    pub opt_level: OptLevel,
+    /// Kind of artifact to produce
+    pub emit: EmitKind,
+    /// Path of the executable, required for `EmitKind::Executable`
+    pub output: Option<PathBuf>,
+    /// Options passed to the system linker for `EmitKind::Executable`
+    pub link: LinkOptions,
+    /// Attach a `MemoryReport` of code generation to the result
+    pub emit_memory_report: bool,
+    /// Emit DWARF line tables so debuggers can step through the source
+    pub debug_info: bool,
+    /// Where values are placed by size, and how much stack each function may take
+    pub memory_policy: MemoryPolicy,
+    /// Attach `CompileMetrics` to the result, with every function body timed
+    pub collect_metrics: bool,
+    /// Run the Cranelift IR verifier on every function
+    pub verify: bool,
+    /// Call the `bract_profile_enter`/`bract_profile_exit` runtime hooks around every function
+    pub profile_instrumentation: bool,
+}
+
+impl CompileOptions {
+    /// Target `@cfg` predicates are evaluated for: `target`, or the host when it is
+    /// unset or not a target triple, with `debug` set for unoptimized builds
+    pub fn cfg_target(&self) -> CfgTarget {
+        let debug = self.opt_level == OptLevel::None;
+        match self.target.as_deref().map(str::parse) {
+            Some(Ok(triple)) => CfgTarget::new(&triple, debug),
+            _ => CfgTarget::host(debug),
+        }
+    }
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            target: None,
+            opt_level: CodegenOptions::default().opt_level,
+            emit: EmitKind::default(),
+            output: None,
+            link: LinkOptions::default(),
+            emit_memory_report: false,
+            debug_info: false,
+            memory_policy: MemoryPolicy::default(),
+            collect_metrics: false,
+            verify: CodegenOptions::default().enable_verifier,
+            profile_instrumentation: false,
+        }
+    }
+}
+
+/// Pipeline stage that reported a diagnostic
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Lex,
+    Parse,
+    Semantic,
+    Codegen,
+}
+
+/// How serious a diagnostic is - only errors prevent an artifact
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Information => "info",
+            Severity::Hint => "hint",
+        })
+    }
+}
+
+/// A problem found while compiling
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    /// Stage that found the problem
+    pub stage: Stage,
+    pub severity: Severity,
+    /// Kind of problem, serialized as its code, e.g. "E0120"
+    pub code: DiagnosticCode,
+    pub message: String,
+    /// Where the problem is, unless it is not caused by a place in the source
+    pub span: Option<Span>,
+    /// Secondary location explaining the problem, with its label
+    pub related: Option<(Span, &'static str)>,
+    /// Suggested ways of fixing the problem
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fixes: Vec<Fix>,
+}
+
+/// A suggested fix for a diagnostic
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fix {
+    pub message: String,
+    /// Text to replace `span` with; advice that is not an edit has none
+    pub replacement: Option<String>,
+    /// Text the replacement takes the place of - empty to insert at its start
+    pub span: Span,
+    /// How likely the fix is what was meant, from 0.0 to 1.0
+    pub confidence: f32,
+}
+
+impl Fix {
+    fn from_suggestion(suggestion: &Suggestion) -> Self {
+        Self {
+            message: suggestion.message.clone(),
+            replacement: suggestion.replacement.clone(),
+            span: Span::new(suggestion.position, suggestion.end.unwrap_or(suggestion.position)),
+            confidence: suggestion.confidence,
+        }
+    }
+}
+
+impl Diagnostic {
+    fn from_parse_error(error: &ParseError) -> Self {
+        let stage = match error {
+            ParseError::LexerError { .. } => Stage::Lex,
+            _ => Stage::Parse,
+        };
+        Self {
+            stage,
+            severity: Severity::Error,
+            code: error.code(),
+            message: error.to_string(),
+            span: Some(Span::single(error.position())),
+            related: None,
+            fixes: error.suggestions().iter().map(Fix::from_suggestion).collect(),
+        }
+    }
+
+    fn from_semantic_error(error: &SemanticError, interner: &StringInterner) -> Self {
+        Self {
+            stage: Stage::Semantic,
+            severity: Severity::Error,
+            code: error.code(),
+            message: error.message(interner),
+            span: Some(error.span()),
+            related: error.related(),
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Unused items and parameters and unreachable code are hints, everything else a warning
+    fn from_semantic_warning(warning: &SemanticWarning, interner: &StringInterner) -> Self {
+        let severity = match warning {
+            SemanticWarning::UnusedSymbol { .. }
+            | SemanticWarning::UnusedVariable { is_parameter: true, .. }
+            | SemanticWarning::UnreachableCode { .. } => Severity::Hint,
+            SemanticWarning::UnusedVariable { is_parameter: false, .. }
+            | SemanticWarning::UnreadAssignment { .. }
+            | SemanticWarning::ShadowedParameter { .. }
+            | SemanticWarning::Deprecated { .. }
+            | SemanticWarning::Performance { .. } => Severity::Warning,
+        };
+        Self {
+            stage: Stage::Semantic,
+            severity,
+            code: warning.code(),
+            message: warning.message(interner),
+            span: Some(warning.span()),
+            related: None,
+            fixes: Vec::new(),
+        }
+    }
+
+    fn from_codegen_error(error: &CodegenError) -> Self {
+        Self {
+            stage: Stage::Codegen,
+            severity: Severity::Error,
+            code: error.code(),
+            message: error.unspanned().to_string(),
+            span: error.span(),
+            related: None,
+            fixes: Vec::new(),
+        }
+    }
+
+    fn from_stack_warning(warning: &StackUsageWarning) -> Self {
+        Self {
+            stage: Stage::Codegen,
+            severity: Severity::Warning,
+            code: DiagnosticCode::StackUsage,
+            message: warning.message(),
+            span: warning.span(),
+            related: None,
+            fixes: Vec::new(),
+        }
+    }
+
+    /// Whether the diagnostic prevents an artifact
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Diagnostic {
+    /// The diagnostic as text, with locations reading like `main.bract:42:8`
+    ///
+    /// A related location follows the message as an indented note.
+    pub fn render(&self, source_map: &SourceMap) -> String {
+        let mut text = String::new();
+        self.write_with(&mut text, |position| source_map.location(position))
+            .expect("writing to a String cannot fail");
+        text
+    }
+
+    /// The diagnostic as `render` has it, followed by the source it points at
+    ///
+    /// The line the span starts on is quoted with the span underlined, up to the end of
+    /// that line for a span covering several:
+    ///
+    /// ```text
+    /// main.bract:2:16: error[E0302]: Type conversion error: bitwise not requires an integer operand, found f64
+    ///   |
+    /// 2 |     let mask = ~1.5;
+    ///   |                ^
+    /// ```
+    pub fn render_with_source(&self, source_map: &SourceMap, source: &str) -> String {
+        let mut text = self.render(source_map);
+        if let Some(span) = self.span {
+            if let Some(line) = source.lines().nth(span.start.line.saturating_sub(1)) {
+                let gutter = " ".repeat(span.start.line.to_string().len());
+                let start = span.start.column.saturating_sub(1).min(line.chars().count());
+                let end = match span.end.line == span.start.line {
+                    true => span.end.column.saturating_sub(1),
+                    false => line.chars().count(),
+                };
+                let indent: String = line.chars().take(start).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+                let underline = "^".repeat(end.saturating_sub(start).max(1));
+                text.push_str(&format!("\n{} |\n{} | {}\n{} | {}{}", gutter, span.start.line, line, gutter, indent, underline));
+            }
+        }
+        text
+    }
+
+    fn write_with(&self, f: &mut impl fmt::Write, location: impl Fn(&Position) -> String) -> fmt::Result {
+        if let Some(span) = self.span {
+            write!(f, "{}: ", location(&span.start))?;
+        }
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some((span, label)) = self.related {
+            write!(f, "\n    note: {} --> {}", label, location(&span.start))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_with(f, |position| format!("{}:{}", position.line, position.column))
+    }
+}
+
+/// Artifact of a successful compilation
+#[derive(Debug, Clone, PartialEq)]
+pub enum Artifact {
+    /// Bytes of the object file
+    Object(Vec<u8>),
+    /// Path of the linked executable
+    Executable(PathBuf),
+    /// JSON of the parsed module
+    AstJson(String),
+}
+
+/// Outcome of `compile_source`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CompileResult {
+    /// The artifact, present exactly when no diagnostic is an error
+    pub artifact: Option<Artifact>,
+    /// Diagnostics of every stage that ran, in stage order
+    pub diagnostics: Vec<Diagnostic>,
+    /// What code generation allocated, when `CompileOptions::emit_memory_report` asked and it succeeded
+    pub memory_report: Option<MemoryReport>,
+    /// How long each stage that ran took, when `CompileOptions::collect_metrics` asked
+    pub metrics: Option<CompileMetrics>,
+}
+
+/// Number of allocation hotspots a `MemoryReport` lists
+pub const MEMORY_REPORT_HOTSPOTS: usize = 10;
+
+/// Memory use of a compiled program, as the memory manager saw it during code generation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryReport {
+    /// Allocations made with each strategy, in the order of `MemoryStrategy`
+    pub allocations: Vec<StrategyAllocations>,
+    /// Bytes requested by all allocations
+    pub total_bytes: u64,
+    /// Allocations found to leak, grouped by function
+    pub leaks: Vec<MemoryLeak>,
+    /// Allocation sites with the highest impact, at most `MEMORY_REPORT_HOTSPOTS`
+    pub hotspots: Vec<MemoryHotspot>,
+    /// Strategy decided for each struct type, by type name
+    pub type_strategies: Vec<StructStrategy>,
+    /// `BractMemoryManager::memory_report`
+    pub summary: String,
+    /// `BractMemoryManager::get_leak_report`
+    pub leak_report: String,
+    /// `BractMemoryManager::get_region_efficiency_report`
+    pub region_report: String,
+    /// `BractMemoryManager::get_performance_report`
+    pub performance_report: String,
+    /// `type_strategy_report` of the struct types
+    pub type_report: String,
+}
+
+/// Number of allocations made with one strategy
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StrategyAllocations {
+    /// `MemoryStrategy::name`
+    pub strategy: &'static str,
+    pub count: u64,
+}
+
+/// An allocation that is never released
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryLeak {
+    /// `MemoryStrategy::name` of the allocation
+    pub strategy: &'static str,
+    /// Definite leaks are errors, likely ones warnings and possible ones information
+    pub severity: Severity,
+    pub message: String,
+    pub suggestion: String,
+    /// The allocated value
+    pub span: Option<Span>,
+    /// `file:line:column (in function)` of the allocated value
+    pub location: String,
+}
+
+/// Memory strategy decided for a struct type
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StructStrategy {
+    pub type_name: String,
+    /// `MemoryStrategy::name`
+    pub strategy: &'static str,
+    /// Requested by an annotation on the struct rather than inferred
+    pub annotated: bool,
+    pub size: u32,
+    pub alignment: u32,
+    /// Why the strategy was chosen
+    pub reason: String,
+}
+
+/// Allocations made at one place in the source
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryHotspot {
+    /// `file:line:column (in function)` of the allocation site
+    pub location: String,
+    pub allocation_count: u64,
+    pub total_bytes: u64,
+    /// `MemoryStrategy::name` of the strategy most allocations used
+    pub strategy: &'static str,
+    /// Estimated performance impact, from 0 to 100
+    pub impact_score: f64,
+}
+
+impl MemoryReport {
+    /// Report what `manager` recorded while generating code, and the `type_strategies` decided
+    pub fn from_memory_manager(manager: &BractMemoryManager, type_strategies: &[(String, TypeStrategy)]) -> Self {
+        let metrics = manager.metrics();
+        let allocations = [
+            (MemoryStrategy::Manual, metrics.manual_allocs),
+            (MemoryStrategy::SmartPtr, metrics.smart_ptr_allocs),
+            (MemoryStrategy::Linear, metrics.linear_allocs),
+            (MemoryStrategy::Region, metrics.region_allocs),
+            (MemoryStrategy::Stack, metrics.stack_allocs),
+        ];
+        let leaks = manager.get_detected_leaks().map(|leak| MemoryLeak {
+            strategy: leak.strategy.name(),
+            severity: match leak.severity {
+                LeakSeverity::Info => Severity::Information,
+                LeakSeverity::Warning => Severity::Warning,
+                LeakSeverity::Error | LeakSeverity::Critical => Severity::Error,
+            },
+            message: leak.leak_type.description().to_string(),
+            suggestion: leak.suggestion.clone(),
+            span: leak.span,
+            location: leak.source_location.clone(),
+        });
+        let hotspots = manager.get_top_allocation_hotspots(MEMORY_REPORT_HOTSPOTS).into_iter().map(|hotspot| MemoryHotspot {
+            location: hotspot.location.clone(),
+            allocation_count: hotspot.allocation_count,
+            total_bytes: hotspot.total_bytes,
+            strategy: hotspot.dominant_strategy.name(),
+            impact_score: hotspot.impact_score,
+        });
+        Self {
+            allocations: allocations.into_iter()
+                .map(|(strategy, count)| StrategyAllocations { strategy: strategy.name(), count })
+                .collect(),
+            total_bytes: metrics.total_bytes_allocated,
+            leaks: leaks.collect(),
+            hotspots: hotspots.collect(),
+            type_strategies: type_strategies.iter()
+                .map(|(name, decision)| StructStrategy {
+                    type_name: name.clone(),
+                    strategy: decision.strategy.name(),
+                    annotated: decision.annotated,
+                    size: decision.size,
+                    alignment: decision.alignment,
+                    reason: decision.reason.clone(),
+                })
+                .collect(),
+            summary: manager.memory_report(),
+            leak_report: manager.get_leak_report(),
+            region_report: manager.get_region_efficiency_report(),
+            performance_report: manager.get_performance_report(),
+            type_report: type_strategy_report(type_strategies),
+        }
+    }
+}
+
+impl CompileResult {
+    /// Whether an artifact was produced
+    pub fn is_success(&self) -> bool {
+        self.artifact.is_some()
+    }
+
+    /// Diagnostics that prevented an artifact
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.diagnostics.iter().filter(|diagnostic| diagnostic.is_error())
+    }
+
+    fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+}
+
+/// Front-end analysis of a source file
+#[derive(Debug, Clone)]
+pub struct SourceAnalysis {
+    /// Parsed module, unless parsing failed outright; items that failed to parse are left out
+    pub module: Option<Module>,
+    /// Symbols of `module`
+    pub symbols: Option<SymbolTable>,
+    /// Lex, parse and semantic diagnostics
+    pub diagnostics: Vec<Diagnostic>,
+    /// Interner the names of `module` and `symbols` were interned into
+    pub interner: StringInterner,
+    /// How long parsing and analysis took
+    pub metrics: CompileMetrics,
+}
+
+/// Parse `source` into `interner` and analyze what parsed
+pub fn analyze_source(source: &str, interner: StringInterner) -> SourceAnalysis {
+    let target = CompileOptions::default().cfg_target();
+    on_compiler_stack(|| analyze(source, interner, &target, false)).unwrap_or_else(|payload| panic::resume_unwind(payload))
+}
+
+/// Items whose `@cfg` predicates fail for `target` are dropped before analysis.
+/// `require_entry_point` is set for executables, which cannot be built without a `main`
+fn analyze(source: &str, interner: StringInterner, target: &CfgTarget, require_entry_point: bool) -> SourceAnalysis {
+    let mut metrics = CompileMetrics::default();
+    let (module, diagnostics, interner) = parse_source(source, interner, &mut metrics);
+    analyze_module(module, diagnostics, interner, metrics, target, require_entry_point)
+}
+
+/// Semantic half of `analyze`, for a module already parsed with `diagnostics`
+fn analyze_module(
+    module: Option<Module>,
+    mut diagnostics: Vec<Diagnostic>,
+    interner: StringInterner,
+    mut metrics: CompileMetrics,
+    target: &CfgTarget,
+    require_entry_point: bool,
+) -> SourceAnalysis {
+    let Some(mut module) = module else {
+        return SourceAnalysis { module: None, symbols: None, diagnostics, interner, metrics };
+    };
+    strip_cfg_items(&mut module, target);
+
+    let analysis = metrics.time(Phase::Semantic, || {
+        SemanticAnalyzer::with_config(AnalyzerConfig { require_entry_point, ..AnalyzerConfig::default() })
+            .with_interner(interner.clone())
+            .analyze(&module)
+    });
+    metrics.functions_analyzed = analysis.stats.functions_analyzed;
+    diagnostics.extend(analysis.errors.iter().map(|error| Diagnostic::from_semantic_error(error, &interner)));
+    diagnostics.extend(analysis.warnings.iter().map(|warning| Diagnostic::from_semantic_warning(warning, &interner)));
+    SourceAnalysis {
+        module: Some(module),
+        symbols: Some(analysis.symbol_table),
+        diagnostics,
+        interner,
+        metrics,
+    }
+}
+
+/// Parse `source` and dump its AST as JSON, with files named `file_name`
+///
+/// Shorthand for `compile_source` with `EmitKind::AstJson`; the JSON schema is
+/// described at `ast::SerializableModule`.
+pub fn dump_ast(source: &str, file_name: &str) -> CompileResult {
+    compile_source(source, file_name, &CompileOptions { emit: EmitKind::AstJson, ..CompileOptions::default() })
+}
+
+/// Compile `source`, named `file_name` in diagnostics and debug output
+///
+/// Never panics: a compiler bug surfaces as an "internal-error" diagnostic.
+pub fn compile_source(source: &str, file_name: &str, options: &CompileOptions) -> CompileResult {
+    on_compiler_stack(|| compile(source, file_name, options))
+        .unwrap_or_else(|payload| CompileResult {
+            artifact: None,
+            diagnostics: vec![Diagnostic::from_codegen_error(&CodegenError::InternalError(format!("compiler panicked: {}", panic_message(&*payload))))],
+            memory_report: None,
+            metrics: None,
+        })
+}
+
+/// Message a panic was raised with
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload.downcast_ref::<&str>().map(|reason| reason.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+fn compile(source: &str, file_name: &str, options: &CompileOptions) -> CompileResult {
+    if options.emit == EmitKind::AstJson {
+        let mut metrics = CompileMetrics::default();
+        let (module, diagnostics, interner) = parse_source(source, StringInterner::new(), &mut metrics);
+        let metrics = options.collect_metrics.then_some(metrics);
+        let mut result = CompileResult { artifact: None, diagnostics, memory_report: None, metrics };
+        if let Some(module) = module.filter(|_| !result.has_errors()) {
+            let source_map = SourceMap::new(file_name);
+            match serde_json::to_string_pretty(&SerializableModule::new(&module, &interner).with_source_map(&source_map)) {
+                Ok(json) => result.artifact = Some(Artifact::AstJson(json)),
+                Err(error) => result.diagnostics.push(Diagnostic::from_codegen_error(&CodegenError::InternalError(error.to_string()))),
+            }
+        }
+        return result;
+    }
+
+    let mut metrics = CompileMetrics::default();
+    let (module, diagnostics, interner) = parse_source(source, StringInterner::new(), &mut metrics);
+    // Items that failed to parse are dropped by error recovery - report the parse errors
+    // alone rather than what the dropped items leave undefined, like a missing `main`
+    if diagnostics.iter().any(Diagnostic::is_error) {
+        return CompileResult { artifact: None, diagnostics, memory_report: None, metrics: options.collect_metrics.then_some(metrics) };
+    }
+
+    let analysis = analyze_module(module, diagnostics, interner, metrics, &options.cfg_target(), options.emit == EmitKind::Executable);
+    let mut metrics = analysis.metrics;
+    let mut result = CompileResult {
+        artifact: None,
+        diagnostics: analysis.diagnostics,
+        memory_report: None,
+        metrics: options.collect_metrics.then(|| metrics.clone()),
+    };
+    let (Some(module), Some(symbols)) = (analysis.module, analysis.symbols) else {
+        return result;
+    };
+    if result.has_errors() {
+        return result;
+    }
+
+    match generate(&module, symbols, analysis.interner, file_name, options, &mut metrics) {
+        Ok((artifact, memory_report, stack_warnings)) => {
+            result.artifact = Some(artifact);
+            result.memory_report = memory_report;
+            result.diagnostics.extend(stack_warnings.iter().map(Diagnostic::from_stack_warning));
+        }
+        Err(error) => result.diagnostics.push(Diagnostic::from_codegen_error(&error)),
+    }
+    result.metrics = options.collect_metrics.then_some(metrics);
+    result
+}
+
+/// Run `f` on a thread with `COMPILER_STACK_SIZE` of stack, returning what it returned or
+/// what it panicked with
+fn on_compiler_stack<T: Send>(f: impl FnOnce() -> T + Send) -> thread::Result<T> {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .name("bract-compiler".to_string())
+            .stack_size(COMPILER_STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .map_err(|error| Box::new(format!("cannot start the compiler thread: {}", error)) as Box<_>)?
+            .join()
+    })
+}
+
+/// Parse `source` into `interner`, collecting the errors recovered from on the way and
+/// recording how long it took in `metrics`
+fn parse_source(source: &str, interner: StringInterner, metrics: &mut CompileMetrics) -> (Option<Module>, Vec<Diagnostic>, StringInterner) {
+    let start = Instant::now();
+    let mut parser = match Parser::with_interner(source, 0, interner.clone()) {
+        Ok(parser) => parser,
+        Err(error) => return (None, vec![Diagnostic::from_parse_error(&error)], interner),
+    };
+    let module = parser.parse_module();
+    metrics.record_phase(Phase::Parse, start.elapsed());
+    metrics.tokens_lexed = parser.tokens_lexed();
+    metrics.items_parsed = module.as_ref().map_or(0, |module| module.items.len());
+    let mut diagnostics: Vec<_> = parser.errors().iter().map(Diagnostic::from_parse_error).collect();
+    let module = module.map_err(|error| diagnostics.push(Diagnostic::from_parse_error(&error))).ok();
+    (module, diagnostics, parser.take_interner())
+}
+
+fn generate(
+    module: &Module,
+    symbols: SymbolTable,
+    interner: StringInterner,
+    file_name: &str,
+    options: &CompileOptions,
+    metrics: &mut CompileMetrics,
+) -> CodegenResult<(Artifact, Option<MemoryReport>, Vec<StackUsageWarning>)> {
+    let codegen_options = CodegenOptions {
+        opt_level: options.opt_level,
+        enable_verifier: options.verify,
+        emit_debug_info: options.debug_info,
+        profile_instrumentation: options.profile_instrumentation,
+        memory_policy: options.memory_policy,
+        time_functions: options.collect_metrics,
+        ..CodegenOptions::default()
+    };
+    let mut generator = match &options.target {
+        Some(target) => CraneliftCodeGenerator::new_with_target(symbols, interner, parse_target_triple(target)?, codegen_options)?,
+        None => CraneliftCodeGenerator::new(symbols, interner, codegen_options)?,
+    };
+    generator.set_source_map(SourceMap::new(file_name))?;
+    let start = Instant::now();
+    let object = generator.generate(module)?;
+    metrics.record_codegen(&generator, start.elapsed(), object.len());
+    let memory_report = options.emit_memory_report.then(|| {
+        let type_strategies = generator.type_strategies();
+        MemoryReport::from_memory_manager(generator.memory_manager(), &type_strategies)
+    });
+    let stack_warnings = generator.stack_warnings().to_vec();
+
+    let artifact = match options.emit {
+        EmitKind::Executable => {
+            let output = options.output.clone()
+                .ok_or_else(|| CodegenError::IoError("No output path given for the executable".to_string()))?;
+            metrics.time(Phase::Link, || link::link_executable(&object, &output, options.link.clone()))?;
+            Artifact::Executable(output)
+        }
+        EmitKind::Object | EmitKind::AstJson => Artifact::Object(object),
+    };
+    Ok((artifact, memory_report, stack_warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_program_produces_object_with_main() {
+        let result = compile_source("fn main() -> i32 { return 0; }", "main.bract", &CompileOptions::default());
+        assert!(result.errors().next().is_none(), "{:?}", result.diagnostics);
+        let Some(Artifact::Object(bytes)) = result.artifact else {
+            panic!("expected an object, got {:?}", result.artifact);
+        };
+        let has_symbol = |name: &[u8]| bytes.windows(name.len() + 1).any(|window| &window[1..] == name && window[0] == 0);
+        assert!(has_symbol(b"main\0") || has_symbol(b"_main\0"), "object has no main symbol");
+    }
+
+    #[test]
+    fn test_invalid_program_yields_located_diagnostics() {
+        let result = compile_source("fn main() -> i32 {\n    let x = ;\n    return 0;\n}\n", "bad.bract", &CompileOptions::default());
+        assert!(result.artifact.is_none());
+        let error = result.errors().next().expect("parse error");
+        assert_eq!(error.stage, Stage::Parse);
+        let span = error.span.expect("parse errors are located");
+        assert_eq!((span.start.line, span.start.column), (2, 13), "{}", error);
+
+        // Recovery drops the malformed `main`, which an executable would otherwise report missing
+        let options = CompileOptions { emit: EmitKind::Executable, ..CompileOptions::default() };
+        let result = compile_source("fn helper() -> i32 { return 1; }\nfn main( -> i32 { return helper(); }\n", "bad.bract", &options);
+        assert!(result.errors().all(|error| error.stage == Stage::Parse), "{:?}", result.diagnostics);
+        assert!(result.errors().next().is_some() && result.artifact.is_none());
+
+        let result = compile_source("fn main() -> i32 { return \"oops; }", "bad.bract", &CompileOptions::default());
+        let error = result.errors().next().expect("lex error");
+        assert_eq!((error.stage, error.code), (Stage::Lex, DiagnosticCode::UnterminatedString), "{}", error);
+
+        let result = compile_source("fn f() {}\nfn f() {}\nfn main() -> i32 { return 0; }", "bad.bract", &CompileOptions::default());
+        let error = result.errors().next().expect("semantic error");
+        assert_eq!((error.stage, error.code), (Stage::Semantic, DiagnosticCode::DuplicateDefinition), "{}", error);
+        assert_eq!(error.span.map(|span| span.start.line), Some(2));
+        assert_eq!(error.related.map(|(span, _)| span.start.line), Some(1));
+        assert!(result.artifact.is_none());
+
+        // Text rendering names the file at both the error and its related location
+        let result = compile_source("fn add(a: i32, b: i32) -> i32 { a + b }\nfn main() -> i32 { return add(\"x\", 2); }", "call.bract", &CompileOptions::default());
+        let error = result.errors().next().expect("argument type error");
+        let rendered = error.render(&SourceMap::new("call.bract"));
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert!(lines[0].starts_with("call.bract:2:31: error[E"), "{}", rendered);
+        assert_eq!(lines.last(), Some(&"    note: parameter declared here --> call.bract:1:8"));
+    }
+
+    #[test]
+    fn test_malformed_input_never_panics() {
+        for source in ["", "fn", "}}}", "fn main( {", "@@@ fn f() -> { region", "fn main() -> i32 { return 1 +; }", "\u{0}\u{feff}"] {
+            for emit in [EmitKind::Object, EmitKind::AstJson] {
+                let options = CompileOptions { emit, ..CompileOptions::default() };
+                let result = compile_source(source, "fuzz.bract", &options);
+                assert_eq!(result.is_success(), !result.errors().any(|_| true), "{:?}", source);
+            }
+        }
+    }
+
+    #[test]
+    fn test_deeply_nested_input_is_diagnosed_or_compiled() {
+        let program = |depth: usize| format!("fn main() -> i32 {{ return {}1 + {}1{}; }}", "-".repeat(depth), "(".repeat(depth), ")".repeat(depth));
+
+        let result = compile_source(&program(100_000), "deep.bract", &CompileOptions::default());
+        let codes: Vec<_> = result.diagnostics.iter().map(|diagnostic| (diagnostic.stage, diagnostic.code)).collect();
+        assert_eq!(codes, [(Stage::Parse, DiagnosticCode::NestingTooDeep)]);
+
+        // An executable needs a `main`, but one dropped by parse recovery is not reported missing
+        let options = CompileOptions { emit: EmitKind::Executable, ..CompileOptions::default() };
+        let result = compile_source(&program(100_000), "deep.bract", &options);
+        let codes: Vec<_> = result.diagnostics.iter().map(|diagnostic| (diagnostic.stage, diagnostic.code)).collect();
+        assert_eq!(codes, [(Stage::Parse, DiagnosticCode::NestingTooDeep)]);
+
+        // Just inside the limit, the whole pipeline has to cope with the depth
+        let result = compile_source(&program(crate::parser::DEFAULT_MAX_NESTING_DEPTH - 4), "deep.bract", &CompileOptions::default());
+        assert!(result.is_success(), "{:?}", result.diagnostics);
+    }
+
+    #[test]
+    fn test_long_chains_are_diagnosed_or_compiled() {
+        let sum = |terms: usize| format!("fn main() -> i32 {{ return 0{}; }}", " + 1".repeat(terms));
+        let result = compile_source(&sum(10_000), "chain.bract", &CompileOptions::default());
+        let codes: Vec<_> = result.diagnostics.iter().map(|diagnostic| (diagnostic.stage, diagnostic.code)).collect();
+        assert_eq!(codes, [(Stage::Parse, DiagnosticCode::NestingTooDeep)]);
+
+        let else_ifs = format!(
+            "fn main() -> i32 {{ let x = 7; let mut r = 0; if x == 0 {{ r = 1; }}{} else {{ r = 2; }} return r; }}",
+            (1..300).map(|i| format!(" else if x == {} {{ r = {}; }}", i, i)).collect::<String>()
+        );
+        let result = compile_source(&else_ifs, "chain.bract", &CompileOptions::default());
+        assert!(result.is_success(), "{:?}", result.diagnostics);
+
+        // A chain as high as allowed, in parentheses nested almost as deep as allowed
+        let depth = crate::parser::DEFAULT_MAX_NESTING_DEPTH - 8;
+        let source = format!(
+            "fn main() -> i32 {{ return {}0{}{}; }}",
+            "(".repeat(depth),
+            " + 1".repeat(crate::parser::DEFAULT_MAX_CHAIN_LENGTH),
+            ")".repeat(depth)
+        );
+        let result = compile_source(&source, "chain.bract", &CompileOptions::default());
+        assert!(result.is_success(), "{:?}", result.diagnostics);
+    }
+
+    #[test]
+    fn test_ast_json_and_codegen_diagnostics() {
+        let Some(Artifact::AstJson(json)) = dump_ast("fn main() -> i32 { return 0; }", "main.bract").artifact else {
+            panic!("expected AST JSON");
+        };
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let function = &value["module"]["items"][0];
+        assert_eq!(function["variant"], "Function");
+        assert_eq!(function["name"], "main");
+        assert_eq!(function["span"]["start"], serde_json::json!({"file": "main.bract", "line": 1, "column": 1, "offset": 0}));
+        assert!(dump_ast("fn main( {", "main.bract").artifact.is_none());
+
+        let options = CompileOptions { target: Some("not-a-target".to_string()), ..CompileOptions::default() };
+        let result = compile_source("fn main() -> i32 { return 0; }", "main.bract", &options);
+        let error = result.errors().next().expect("bad target");
+        assert_eq!((error.stage, error.span), (Stage::Codegen, None));
+
+        // Code generation errors caused by the program point at it, like any other error
+        let source = "fn main() -> i32 {\n    let v = if true { 1 } else { 2.0 };\n    return 0;\n}\n";
+        let result = compile_source(source, "branches.bract", &CompileOptions::default());
+        let error = result.errors().next().expect("incompatible branches");
+        assert_eq!((error.stage, error.code), (Stage::Codegen, DiagnosticCode::TypeConversion), "{}", error);
+        assert_eq!(
+            error.render_with_source(&SourceMap::new("branches.bract"), source),
+            "branches.bract:2:13: error[E0302]: Type conversion error: if branches have incompatible types: \
+             then branch is i32, else branch is f64\n  |\n2 |     let v = if true { 1 } else { 2.0 };\n  |             ^^^^^^^^^^^^^^^^^^^^^^^^^^",
+        );
+    }
+
+    #[test]
+    fn test_memory_report_locates_leaks() {
+        let source = "struct Point { x: i32, y: i32 }\n\
+                      @manual\n\
+                      fn leaky() -> i32 {\n    let p = Point { x: 1, y: 2 };\n    return 0;\n}\n\
+                      fn main() -> i32 { return leaky(); }\n";
+        assert!(compile_source(source, "leaky.bract", &CompileOptions::default()).memory_report.is_none());
+
+        let options = CompileOptions { emit_memory_report: true, ..CompileOptions::default() };
+        let result = compile_source(source, "leaky.bract", &options);
+        assert!(result.is_success(), "{:?}", result.diagnostics);
+        let report = result.memory_report.expect("memory report");
+        let manual = report.allocations.iter().find(|allocations| allocations.strategy == "Manual").unwrap();
+        assert_eq!(manual.count, 1);
+        assert_eq!(report.total_bytes, 16);
+
+        assert_eq!(report.leaks.len(), 1, "{}", report.leak_report);
+        let leak = &report.leaks[0];
+        assert_eq!((leak.strategy, leak.severity), ("Manual", Severity::Error));
+        assert_eq!(leak.message, "manual allocation is never freed");
+        let span = leak.span.expect("leaks are located");
+        assert_eq!((span.start.line, span.start.column), (4, 13));
+        assert_eq!(leak.location, "leaky.bract:4:13 (in leaky)");
+        assert_eq!(report.hotspots.len(), 1);
+        assert!(report.leak_report.contains("fn leaky (1 issues)"), "{}", report.leak_report);
+    }
+
+    #[test]
+    fn test_memory_report_explains_struct_strategies() {
+        let source = "@memory(strategy = \"manual\")\nstruct Handle { id: i32 }\n\
+                      struct Point { x: i32, y: i32 }\n\
+                      struct Wide { a: i64, b: i64, c: i64, d: i64, e: i64, f: i64, g: i64, h: i64, i: i64 }\n\
+                      fn origin() -> Point { return Point { x: 0, y: 0 }; }\n\
+                      fn main() -> i32 {\n    let h = Handle { id: 1 };\n    let p = origin();\n    return h.id + p.x;\n}\n";
+        let options = CompileOptions { emit_memory_report: true, ..CompileOptions::default() };
+        let result = compile_source(source, "types.bract", &options);
+        assert!(result.is_success(), "{:?}", result.diagnostics);
+        let report = result.memory_report.expect("memory report");
+
+        let decision = |name: &str| report.type_strategies.iter().find(|decision| decision.type_name == name).unwrap();
+        // The annotation wins over the stack a 8-byte struct would get
+        assert_eq!((decision("Handle").strategy, decision("Handle").annotated), ("Manual", true));
+        assert_eq!(decision("Handle").reason, "annotated @memory -> Manual");
+        assert_eq!((decision("Point").size, decision("Point").alignment), (16, 4));
+        assert_eq!(decision("Point").reason, "size 16 <= stack threshold 64, not shared, escapes its function -> Linear");
+        assert_eq!(decision("Wide").reason, "size 72 > stack threshold 64, not shared, lifetime known -> Region");
+        assert!(report.type_report.contains("Wide: Region (72 bytes, align 8)"), "{}", report.type_report);
+
+        // `Handle` is allocated manually; the returned `Point` still outlives its frame
+        let count = |strategy: &str| report.allocations.iter().find(|allocations| allocations.strategy == strategy).unwrap().count;
+        assert_eq!(count("Manual"), 2);
+        assert_eq!(count("Linear"), 0);
+    }
+
+    #[test]
+    fn test_stack_usage_over_the_policy_is_diagnosed() {
+        let source = "fn main() -> i32 {\n    let small = [1; 16];\n    let large = [2; 512];\n    return small[0] + large[0];\n}\n";
+        assert!(compile_source(source, "frame.bract", &CompileOptions::default()).diagnostics.is_empty());
+
+        let policy = MemoryPolicy { max_function_stack: 1024, ..MemoryPolicy::default() };
+        let options = CompileOptions { memory_policy: policy, ..CompileOptions::default() };
+        let result = compile_source(source, "frame.bract", &options);
+        assert!(result.is_success(), "{:?}", result.diagnostics);
+        let [warning] = result.diagnostics.as_slice() else {
+            panic!("expected one warning, got {:?}", result.diagnostics);
+        };
+        assert_eq!((warning.stage, warning.severity, warning.code), (Stage::Codegen, Severity::Warning, DiagnosticCode::StackUsage));
+        assert!(warning.message.contains("2048 bytes at frame.bract:3:17"), "{}", warning.message);
+        assert_eq!(warning.span.map(|span| (span.start.line, span.start.column)), Some((3, 17)));
+
+        let strict = MemoryPolicy { strict_stack_limit: true, ..policy };
+        let result = compile_source(source, "frame.bract", &CompileOptions { memory_policy: strict, ..CompileOptions::default() });
+        assert!(result.artifact.is_none());
+        let error = result.errors().next().expect("stack limit error");
+        assert_eq!((error.stage, error.code), (Stage::Codegen, DiagnosticCode::MemoryManagement), "{}", error);
+        assert_eq!(error.span.map(|span| span.start.line), Some(3));
+    }
+
+    #[test]
+    fn test_metrics_cover_every_phase_that_ran() {
+        let source = "struct Point { x: i32, y: i32 }\n\
+                      impl Point { fn sum(&self) -> i32 { return self.x + self.y; } }\n\
+                      fn main() -> i32 { let p = Point { x: 1, y: 2 }; return p.sum(); }\n";
+        assert!(compile_source(source, "metrics.bract", &CompileOptions::default()).metrics.is_none());
+
+        let options = CompileOptions { collect_metrics: true, ..CompileOptions::default() };
+        let result = compile_source(source, "metrics.bract", &options);
+        assert!(result.is_success(), "{:?}", result.diagnostics);
+        let metrics = result.metrics.expect("metrics");
+        let phases: Vec<_> = metrics.phases.iter().map(|timing| timing.phase).collect();
+        assert_eq!(phases, [Phase::Parse, Phase::Semantic, Phase::Codegen, Phase::Emit]);
+        assert_eq!((metrics.items_parsed, metrics.functions_analyzed, metrics.functions_compiled), (3, 2, 2));
+        assert!(metrics.tokens_lexed > 40, "{}", metrics.tokens_lexed);
+        let Some(Artifact::Object(object)) = &result.artifact else { panic!("expected an object") };
+        assert_eq!(metrics.object_bytes, object.len());
+        let mut timed: Vec<_> = metrics.slowest_functions.iter().map(|function| function.name.as_str()).collect();
+        timed.sort();
+        assert_eq!(timed, ["Point_sum", "main"]);
+        assert!(metrics.memory.is_some());
+        let summary = metrics.summary();
+        assert!(summary.lines().any(|line| line.starts_with("  codegen") && line.ends_with("(2 functions)")), "{}", summary);
+
+        // Analysis failing stops the metrics at the stage that failed
+        let result = compile_source("fn main() -> i32 { return missing(); }", "metrics.bract", &options);
+        let phases: Vec<_> = result.metrics.expect("metrics").phases.iter().map(|timing| timing.phase).collect();
+        assert_eq!(phases, [Phase::Parse, Phase::Semantic]);
+    }
+
+    #[test]
+    fn test_bad_calls_are_semantic_errors() {
+        let program = |call: &str| format!("fn add(a: i32, b: i32) -> i32 {{ return a + b; }}\nfn main() -> i32 {{ return {}; }}", call);
+        assert!(compile_source(&program("add(1, 2)"), "calls.bract", &CompileOptions::default()).is_success());
+
+        for (call, code) in [("add(1)", "E0223"), ("add(\"x\", 2)", "E0210"), ("ad(1, 2)", "E0224")] {
+            let result = compile_source(&program(call), "calls.bract", &CompileOptions::default());
+            let codes: Vec<_> = result.errors().map(|diagnostic| (diagnostic.stage, diagnostic.code.as_str())).collect();
+            assert_eq!(codes, [(Stage::Semantic, code)], "{}", call);
+        }
+    }
+
+    #[test]
+    fn test_cfg_items_only_exist_on_their_targets() {
+        let program = "@cfg(target_os = \"windows\") fn GetTickCount() -> u32 { return 3; }\n\
+                       @cfg(not(target_os = \"windows\")) fn clock_ticks() -> u32 { return 7; }\n\
+                       fn main() -> i32 { return ticks() as i32; }\n\
+                       @cfg(target_os = \"windows\") fn ticks() -> u32 { return GetTickCount(); }\n\
+                       @cfg(not(target_os = \"windows\")) fn ticks() -> u32 { return clock_ticks(); }";
+        for target in ["x86_64-pc-windows-msvc", "x86_64-unknown-linux-gnu"] {
+            let options = CompileOptions { target: Some(target.to_string()), ..CompileOptions::default() };
+            let result = compile_source(program, "ticks.bract", &options);
+            assert!(result.is_success(), "{}: {:?}", target, result.diagnostics);
+        }
+
+        // A call to an item left out of the target is a call to an unknown function
+        let options = CompileOptions { target: Some("x86_64-unknown-linux-gnu".to_string()), ..CompileOptions::default() };
+        let result = compile_source(
+            "@cfg(target_os = \"windows\") fn GetTickCount() -> u32 { return 3; }\nfn main() -> i32 { return GetTickCount() as i32; }",
+            "ticks.bract",
+            &options,
+        );
+        let errors: Vec<_> = result.errors().collect();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!((errors[0].stage, errors[0].code), (Stage::Semantic, DiagnosticCode::UnknownFunction));
+        assert_eq!(errors[0].span.map(|span| (span.start.line, span.start.column)), Some((2, 27)));
+    }
+
+    #[test]
+    fn test_executables_need_a_main() {
+        // A library object has no entry point and needs none; nothing stands in for one
+        let library = compile_source("fn mian() -> i32 { return 0; }", "lib.bract", &CompileOptions::default());
+        let Some(Artifact::Object(bytes)) = library.artifact else {
+            panic!("expected an object, got {:?}", library.diagnostics);
+        };
+        assert!(!bytes.windows(6).any(|window| window == b"\0main\0" || window == b"_main\0"), "object defines a main");
+
+        // An executable does, and a misspelling is pointed out before anything is linked
+        let options = CompileOptions { emit: EmitKind::Executable, output: Some(PathBuf::from("never-linked")), ..CompileOptions::default() };
+        let result = compile_source("fn helper() {}\nfn mian() -> i32 { return 0; }", "typo.bract", &options);
+        let errors: Vec<_> = result.errors().collect();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!((errors[0].stage, errors[0].code), (Stage::Semantic, DiagnosticCode::MissingEntryPoint));
+        assert_eq!(errors[0].span.map(|span| span.start.line), Some(2));
+        assert!(errors[0].message.ends_with("Did you mean: `mian`?"), "{}", errors[0]);
+        assert!(result.artifact.is_none());
+
+        let result = compile_source("fn main(argc: i32) -> i32 { return argc; }", "args.bract", &options);
+        let codes: Vec<_> = result.errors().map(|diagnostic| diagnostic.code).collect();
+        assert_eq!(codes, [DiagnosticCode::InvalidEntryPoint]);
+    }
+}
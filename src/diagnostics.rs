@@ -0,0 +1,270 @@
+//! Registry of diagnostic codes
+//!
+//! Every error and warning the compiler reports carries a `DiagnosticCode`: a stable
+//! number like `E0120` that editors and CI tooling can filter on, suppress or link
+//! to, the kebab-case name the diagnostic went by before it was numbered, a one-line
+//! summary and a longer explanation with an example and its fix. The explanations
+//! live in `src/diagnostics/<code>.md` and are what `bract_cranelift --explain`
+//! prints.
+//!
+//! Numbers are grouped by the layer reporting them:
+//! - `E00xx`: internal compiler errors
+//! - `E01xx`: lexing, parsing and module loading
+//! - `E02xx`: semantic analysis
+//! - `E03xx`: code generation
+//! - `E04xx`: performance contract violations
+//! - `W01xx` and `W02xx`: warnings of semantic and performance analysis
+//!
+//! Once released, a code keeps its number, name and meaning; a code that is no
+//! longer reported stays in the registry rather than being reused.
+//! `tests/diagnostic_codes.rs` enforces this against a snapshot of the summaries.
+
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+macro_rules! diagnostic_codes {
+    ($($variant:ident = $code:literal, $name:literal, $summary:literal;)*) => {
+        /// Stable identifier of a kind of diagnostic
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum DiagnosticCode {
+            $(
+                #[doc = $summary]
+                $variant,
+            )*
+        }
+
+        impl DiagnosticCode {
+            /// Every code, in registry order
+            pub const ALL: &'static [DiagnosticCode] = &[$(DiagnosticCode::$variant),*];
+
+            /// The code as reported, e.g. `E0120`
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(DiagnosticCode::$variant => $code,)*
+                }
+            }
+
+            /// Kebab-case name the diagnostic was reported under before codes were numbered
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(DiagnosticCode::$variant => $name,)*
+                }
+            }
+
+            /// What the diagnostic reports, in one line
+            pub fn summary(self) -> &'static str {
+                match self {
+                    $(DiagnosticCode::$variant => $summary,)*
+                }
+            }
+
+            /// Description of the diagnostic with an example of code causing it and how to fix it
+            pub fn explanation(self) -> &'static str {
+                match self {
+                    $(DiagnosticCode::$variant => include_str!(concat!("diagnostics/", $code, ".md")),)*
+                }
+            }
+        }
+    };
+}
+
+diagnostic_codes! {
+    InternalError = "E0001", "internal-error", "Internal compiler error";
+
+    InvalidCharacter = "E0101", "invalid-character", "Character that cannot start a token";
+    InvalidEscape = "E0102", "invalid-escape", "Unknown escape sequence in a string or character literal";
+    UnterminatedString = "E0103", "unterminated-string", "String literal without a closing quote";
+    UnterminatedChar = "E0104", "unterminated-char", "Character literal without a closing quote";
+    UnterminatedComment = "E0105", "unterminated-comment", "Block comment without a closing `*/`";
+    InvalidNumber = "E0106", "invalid-number", "Malformed number literal";
+    InvalidUnicodeEscape = "E0107", "invalid-unicode-escape", "Malformed or out-of-range `\\u{...}` escape";
+    InvalidCharLiteral = "E0108", "invalid-char-literal", "Character literal that is empty or holds more than one character";
+    InvalidSuffix = "E0109", "invalid-suffix", "Unknown or misplaced number literal suffix";
+    InvalidRawString = "E0110", "invalid-raw-string", "Malformed or unterminated raw string literal";
+    InvalidUtf8 = "E0111", "invalid-utf8", "Source that is not valid UTF-8";
+    UnexpectedToken = "E0120", "unexpected-token", "Token that cannot appear at this point";
+    UnexpectedEof = "E0121", "unexpected-eof", "File ends in the middle of a construct";
+    InvalidSyntax = "E0122", "invalid-syntax", "Construct that does not follow the grammar";
+    MissingDelimiter = "E0123", "missing-delimiter", "Opening delimiter without a closing one";
+    MismatchedDelimiter = "E0124", "mismatched-delimiter", "Closing delimiter that does not match the opening one";
+    InvalidIdentifier = "E0125", "invalid-identifier", "Name that is not a valid identifier";
+    InvalidType = "E0126", "invalid-type", "Malformed type";
+    InvalidPattern = "E0127", "invalid-pattern", "Malformed pattern";
+    InvalidExpression = "E0128", "invalid-expression", "Malformed expression";
+    InvalidStatement = "E0129", "invalid-statement", "Malformed statement";
+    InvalidMemoryAnnotation = "E0130", "invalid-memory-annotation", "Malformed memory or performance annotation";
+    NestingTooDeep = "E0131", "nesting-too-deep", "Expressions, types or patterns nested deeper than the parser allows";
+    ModuleNotFound = "E0140", "module-not-found", "No file for a `mod name;` declaration";
+    AmbiguousModule = "E0141", "ambiguous-module", "Module file found at both of its possible paths";
+    CircularModule = "E0142", "circular-module", "Module that loads a file already being loaded";
+    UnreadableModule = "E0143", "unreadable-module", "Source or module file that cannot be read";
+
+    DuplicateDefinition = "E0201", "duplicate-definition", "Name defined more than once in the same scope";
+    UndefinedSymbol = "E0202", "undefined-symbol", "Name that resolves to nothing in scope";
+    InaccessibleSymbol = "E0203", "inaccessible-symbol", "Private item used outside the module defining it";
+    CircularDependency = "E0204", "circular-dependency", "Items that depend on each other in a cycle";
+    InvalidUsage = "E0205", "invalid-usage", "Name used as a kind of symbol it is not";
+    UnresolvedImport = "E0206", "unresolved-import", "`use` path with a segment that names nothing to import";
+    TypeMismatch = "E0210", "type-mismatch", "Value of a type its context does not accept";
+    StrategyConflict = "E0211", "strategy-conflict", "Memory strategies that do not agree";
+    OwnershipViolation = "E0212", "ownership-violation", "Value used against the ownership rules of its type";
+    Lifetime = "E0213", "lifetime", "Reference that may outlive what it refers to";
+    LinearType = "E0214", "linear-type", "Linear value not consumed exactly once";
+    InferenceFailure = "E0215", "inference-failure", "Type that cannot be inferred";
+    UndefinedType = "E0216", "undefined-type", "Type name that resolves to nothing";
+    PerformanceViolation = "E0217", "performance-violation", "Operation that breaks the performance contract it is checked against";
+    LiteralOutOfRange = "E0218", "literal-out-of-range", "Integer literal outside the range of its type";
+    NonConstantArrayLength = "E0219", "non-constant-array-length", "Array length that is not a constant expression";
+    ArrayLengthMismatch = "E0220", "array-length-mismatch", "Array with a different number of elements than annotated";
+    IndexOutOfBounds = "E0221", "index-out-of-bounds", "Constant index past the end of an array";
+    DuplicateDiscriminant = "E0222", "duplicate-discriminant", "Two variants of an enum with the same discriminant";
+    ArgumentCountMismatch = "E0223", "argument-count-mismatch", "Call with a different number of arguments than the callee has parameters";
+    UnknownFunction = "E0224", "unknown-function", "Call of a name that resolves to no function";
+    InvalidEntryPoint = "E0225", "invalid-entry-point", "`main` function with parameters or a return type other than unit or `i32`";
+    MissingEntryPoint = "E0226", "missing-entry-point", "Program built as an executable without a `main` function";
+    RecursiveType = "E0227", "recursive-type", "Struct or enum that contains itself by value and so has no finite size";
+    UseAfterMove = "E0230", "use-after-move", "Value used after it was moved";
+    MoveWhileBorrowed = "E0231", "move-while-borrowed", "Value moved while a borrow of it is still in use";
+    MultipleMutableBorrows = "E0232", "multiple-mutable-borrows", "Two mutable borrows of a value in use at once";
+    ConflictingBorrows = "E0233", "conflicting-borrows", "Mutable and shared borrows of a value in use at once";
+    BorrowOutlivesOwner = "E0234", "borrow-outlives-owner", "Borrow that outlives the value it borrows";
+    LinearReuse = "E0235", "linear-reuse", "Linear value used more than once";
+    StrategyMismatch = "E0236", "strategy-mismatch", "Value of one memory strategy used where another is required";
+    Escape = "E0237", "escape", "Value that escapes the function or region it lives in";
+    InvalidRegionAccess = "E0238", "invalid-region-access", "Access to a region's memory outside the region";
+    PotentialLeak = "E0239", "potential-leak", "Allocation that may never be freed";
+    SemanticViolation = "E0240", "semantic-violation", "Program that breaks a language rule without a more specific code";
+
+    UnsupportedFeature = "E0301", "unsupported-feature", "Construct the code generator does not support yet";
+    TypeConversion = "E0302", "type-conversion", "Value the code generator cannot convert to the type required";
+    SymbolResolution = "E0303", "symbol-resolution", "Name the code generator cannot resolve";
+    MemoryManagement = "E0304", "memory-management", "Allocation or layout the code generator cannot produce";
+    NativeCompilation = "E0305", "native-compilation", "Failure emitting or linking native code";
+    Io = "E0306", "io", "File the code generator cannot read or write";
+
+    CpuExceeded = "E0401", "perf/cpu-exceeded", "Function may use more CPU cycles than its contract allows";
+    MemoryExceeded = "E0402", "perf/memory-exceeded", "Function may use more memory than its contract allows";
+    AllocationExceeded = "E0403", "perf/allocation-exceeded", "Function may allocate more often than its contract allows";
+    LatencyExceeded = "E0404", "perf/latency-exceeded", "Function may take longer than its contract allows";
+    StackExceeded = "E0405", "perf/stack-exceeded", "Function may use more stack than its contract allows";
+    NonDeterministic = "E0406", "perf/non-deterministic", "Function contracted as deterministic that may not be";
+    NotWaitFree = "E0407", "perf/not-wait-free", "Function contracted as wait-free that may block";
+    ComplexityExceeded = "E0408", "perf/complexity-exceeded", "Function whose complexity exceeds its contract";
+
+    UnusedItem = "W0101", "unused-item", "Private item that is never used";
+    UnusedVariable = "W0102", "unused-variable", "Variable that is never read";
+    UnusedParameter = "W0103", "unused-parameter", "Parameter that is never read";
+    UnusedAssignment = "W0104", "unused-assignment", "Variable that is assigned to but never read";
+    ShadowedParameter = "W0105", "shadowed-parameter", "`let` binding that shadows a parameter of its function";
+    UnreachableCode = "W0106", "unreachable-code", "Code that can never run";
+    Deprecated = "W0107", "deprecated", "Use of a deprecated item";
+    Performance = "W0108", "performance", "Code that is correct but likely slower than it needs to be";
+    StackUsage = "W0109", "stack-usage", "Function whose stack allocations may exceed the per-function stack limit";
+    MissingContract = "W0201", "perf/missing-contract", "Function without a performance contract";
+    InaccurateEstimate = "W0202", "perf/inaccurate-estimate", "Cost estimate that may be far from the real cost";
+    SuboptimalAllocation = "W0203", "perf/suboptimal-allocation", "Allocation a cheaper memory strategy could avoid";
+    PotentialBottleneck = "W0204", "perf/potential-bottleneck", "Code likely to dominate its function's cost";
+    UnverifiedExtern = "W0205", "perf/unverified-extern", "Call to an extern function whose cost cannot be verified";
+    AnalysisSkipped = "W0206", "perf/analysis-skipped", "Performance analysis skipped for running out of time";
+}
+
+impl DiagnosticCode {
+    /// Whether diagnostics with this code are warnings rather than errors
+    pub fn is_warning(self) -> bool {
+        self.as_str().starts_with('W')
+    }
+
+    /// The code written as `code`, either numbered (`E0120`, in any case) or by name (`unexpected-token`)
+    pub fn lookup(code: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|known| known.as_str().eq_ignore_ascii_case(code) || known.name() == code)
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for DiagnosticCode {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        Self::lookup(code).ok_or_else(|| format!("unknown diagnostic code `{}`", code))
+    }
+}
+
+impl TryFrom<&str> for DiagnosticCode {
+    type Error = String;
+
+    fn try_from(code: &str) -> Result<Self, Self::Error> {
+        code.parse()
+    }
+}
+
+/// The kebab-case name, which is what `code()` returned before codes were numbered
+impl From<DiagnosticCode> for &'static str {
+    fn from(code: DiagnosticCode) -> Self {
+        code.name()
+    }
+}
+
+/// Compares equal to both the numbered code and the name
+impl PartialEq<str> for DiagnosticCode {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other || self.name() == other
+    }
+}
+
+impl PartialEq<&str> for DiagnosticCode {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl Serialize for DiagnosticCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_codes_and_names_are_unique() {
+        let codes: HashSet<_> = DiagnosticCode::ALL.iter().map(|code| code.as_str()).collect();
+        let names: HashSet<_> = DiagnosticCode::ALL.iter().map(|code| code.name()).collect();
+        assert_eq!(codes.len(), DiagnosticCode::ALL.len());
+        assert_eq!(names.len(), DiagnosticCode::ALL.len());
+    }
+
+    #[test]
+    fn test_every_code_is_explained() {
+        for code in DiagnosticCode::ALL {
+            let explanation = code.explanation();
+            assert!(explanation.split("\n\n").count() >= 3, "{} needs a multi-paragraph explanation", code);
+            assert!(explanation.contains("\n    "), "{} needs an indented example", code);
+            assert!(code.as_str().len() == 5 && (code.as_str().starts_with('E') || code.as_str().starts_with('W')), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_code_or_name() {
+        assert_eq!(DiagnosticCode::lookup("E0120"), Some(DiagnosticCode::UnexpectedToken));
+        assert_eq!(DiagnosticCode::lookup("e0120"), Some(DiagnosticCode::UnexpectedToken));
+        assert_eq!("unexpected-token".parse(), Ok(DiagnosticCode::UnexpectedToken));
+        assert_eq!(DiagnosticCode::try_from("perf/cpu-exceeded"), Ok(DiagnosticCode::CpuExceeded));
+        assert!("E9999".parse::<DiagnosticCode>().is_err());
+
+        assert_eq!(DiagnosticCode::UnusedParameter, "W0103");
+        assert_eq!(DiagnosticCode::UnusedParameter, "unused-parameter");
+        assert!(DiagnosticCode::UnusedParameter.is_warning() && !DiagnosticCode::UseAfterMove.is_warning());
+        let name: &str = DiagnosticCode::UseAfterMove.into();
+        assert_eq!(name, "use-after-move");
+        assert_eq!(serde_json::to_value(DiagnosticCode::UseAfterMove).unwrap(), "E0230");
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt;
+use crate::diagnostics::DiagnosticCode;
 use crate::lexer::position::Position;
 
 /// Represents errors that can occur during lexical analysis
@@ -18,6 +19,8 @@ pub enum LexerError {
     InvalidNumber(String, Position),
     /// Invalid unicode escape sequence
     InvalidUnicodeEscape(String, Position),
+    /// Unicode escape naming a surrogate or a value past U+10FFFF
+    UnicodeEscapeOutOfRange(u32, Position),
     /// Empty character literal
     EmptyCharLiteral(Position),
     /// Multi-character literal (more than one character in a char literal)
@@ -30,6 +33,8 @@ pub enum LexerError {
     InvalidOctalDigit(char, Position),
     /// Invalid numeric suffix
     InvalidNumericSuffix(String, Position),
+    /// `_` separator directly before a numeric suffix, as in `1_000_u32`
+    UnderscoreBeforeSuffix(Position),
     /// Invalid raw string delimiter
     InvalidRawStringDelimiter(Position),
     /// Unterminated raw string
@@ -62,6 +67,9 @@ impl fmt::Display for LexerError {
             LexerError::InvalidUnicodeEscape(s, pos) => {
                 write!(f, "Invalid Unicode escape sequence '{}' at {}:{}", s, pos.line, pos.column)
             }
+            LexerError::UnicodeEscapeOutOfRange(value, pos) => {
+                write!(f, "Unicode escape '\\u{{{:x}}}' is not a Unicode scalar value at {}:{}", value, pos.line, pos.column)
+            }
             LexerError::EmptyCharLiteral(pos) => {
                 write!(f, "Empty character literal at {}:{}", pos.line, pos.column)
             }
@@ -80,6 +88,9 @@ impl fmt::Display for LexerError {
             LexerError::InvalidNumericSuffix(s, pos) => {
                 write!(f, "Invalid numeric suffix '{}' at {}:{}", s, pos.line, pos.column)
             }
+            LexerError::UnderscoreBeforeSuffix(pos) => {
+                write!(f, "Numeric separator '_' directly before a suffix at {}:{}", pos.line, pos.column)
+            }
             LexerError::InvalidRawStringDelimiter(pos) => {
                 write!(f, "Invalid raw string delimiter at {}:{}", pos.line, pos.column)
             }
@@ -93,4 +104,50 @@ impl fmt::Display for LexerError {
     }
 }
 
+impl LexerError {
+    /// Source position the error points at
+    pub fn position(&self) -> Position {
+        match self {
+            LexerError::InvalidCharacter(_, pos)
+            | LexerError::InvalidEscapeSequence(_, pos)
+            | LexerError::UnterminatedString(pos)
+            | LexerError::UnterminatedChar(pos)
+            | LexerError::UnterminatedBlockComment(pos)
+            | LexerError::InvalidNumber(_, pos)
+            | LexerError::InvalidUnicodeEscape(_, pos)
+            | LexerError::UnicodeEscapeOutOfRange(_, pos)
+            | LexerError::EmptyCharLiteral(pos)
+            | LexerError::MultiCharLiteral(pos)
+            | LexerError::InvalidHexDigit(_, pos)
+            | LexerError::InvalidBinaryDigit(_, pos)
+            | LexerError::InvalidOctalDigit(_, pos)
+            | LexerError::InvalidNumericSuffix(_, pos)
+            | LexerError::UnderscoreBeforeSuffix(pos)
+            | LexerError::InvalidRawStringDelimiter(pos)
+            | LexerError::UnterminatedRawString(pos)
+            | LexerError::Utf8Error(pos) => *pos,
+        }
+    }
+    
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            LexerError::InvalidCharacter(..) => DiagnosticCode::InvalidCharacter,
+            LexerError::InvalidEscapeSequence(..) => DiagnosticCode::InvalidEscape,
+            LexerError::UnterminatedString(_) => DiagnosticCode::UnterminatedString,
+            LexerError::UnterminatedChar(_) => DiagnosticCode::UnterminatedChar,
+            LexerError::UnterminatedBlockComment(_) => DiagnosticCode::UnterminatedComment,
+            LexerError::InvalidNumber(..)
+            | LexerError::InvalidHexDigit(..)
+            | LexerError::InvalidBinaryDigit(..)
+            | LexerError::InvalidOctalDigit(..) => DiagnosticCode::InvalidNumber,
+            LexerError::InvalidUnicodeEscape(..) | LexerError::UnicodeEscapeOutOfRange(..) => DiagnosticCode::InvalidUnicodeEscape,
+            LexerError::EmptyCharLiteral(_) | LexerError::MultiCharLiteral(_) => DiagnosticCode::InvalidCharLiteral,
+            LexerError::InvalidNumericSuffix(..) | LexerError::UnderscoreBeforeSuffix(_) => DiagnosticCode::InvalidSuffix,
+            LexerError::InvalidRawStringDelimiter(_) | LexerError::UnterminatedRawString(_) => DiagnosticCode::InvalidRawString,
+            LexerError::Utf8Error(_) => DiagnosticCode::InvalidUtf8,
+        }
+    }
+}
+
 impl std::error::Error for LexerError {} 
\ No newline at end of file
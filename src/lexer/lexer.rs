@@ -1,50 +1,47 @@
 use crate::lexer::position::Position;
 use crate::lexer::token::{Token, TokenType, NumberBase};
 use crate::lexer::error::LexerError;
+use std::borrow::Cow;
 use std::char;
-use std::collections::HashMap;
 
 /// The Lexer is responsible for converting source code into tokens
 pub struct Lexer<'a> {
     /// The input source code
-    _input: &'a str,
-    /// The characters of the input
-    chars: std::str::Chars<'a>,
+    input: &'a str,
     /// The current position in the input
     current_pos: usize,
     /// The current character
     current_char: Option<char>,
     /// Position tracking for error reporting
     position: Position,
-    /// Keyword lookup table
-    keywords: HashMap<String, TokenType>,
     /// Whether to include comments in the token stream
     include_comments: bool,
+    /// Whether to record malformed input in `errors` and keep scanning
+    recover: bool,
+    /// Errors recovered from so far
+    errors: Vec<LexerError>,
 }
 
 impl<'a> Lexer<'a> {
     /// Create a new lexer from input source code
     pub fn new(input: &'a str, file_id: usize) -> Self {
-        let mut chars = input.chars();
-        let mut current_char = chars.next();
         let mut position = Position::start(file_id);
         let mut current_pos = 0;
         
         // Skip BOM (Byte Order Mark) if present
-        if let Some('\u{FEFF}') = current_char {
+        if input.starts_with('\u{FEFF}') {
             current_pos += 3; // BOM is 3 bytes in UTF-8
-            position.advance(3); // UTF-8 BOM is 3 bytes
-            current_char = chars.next();
+            position.offset += 3; // the BOM takes no column
         }
         
         Self {
-            _input: input,
-            chars,
+            input,
             current_pos,
-            current_char,
+            current_char: char_at(input, current_pos),
             position,
-            keywords: Self::init_keywords(),
             include_comments: false,
+            recover: false,
+            errors: Vec::new(),
         }
     }
     
@@ -55,79 +52,58 @@ impl<'a> Lexer<'a> {
         lexer
     }
     
-    /// Initialize the keyword lookup table
-    fn init_keywords() -> HashMap<String, TokenType> {
-        let mut keywords = HashMap::new();
-        
-        // Add all keywords
-        keywords.insert("abort".to_string(), TokenType::Abort);
-        keywords.insert("break".to_string(), TokenType::Break);
-        keywords.insert("box".to_string(), TokenType::Box);
-        keywords.insert("const".to_string(), TokenType::Const);
-        keywords.insert("continue".to_string(), TokenType::Continue);
-        keywords.insert("do".to_string(), TokenType::Do);
-        keywords.insert("else".to_string(), TokenType::Else);
-        keywords.insert("enum".to_string(), TokenType::Enum);
-        keywords.insert("extern".to_string(), TokenType::Extern);
-        keywords.insert("false".to_string(), TokenType::False);
-        keywords.insert("fn".to_string(), TokenType::Fn);
-        keywords.insert("for".to_string(), TokenType::For);
-        keywords.insert("if".to_string(), TokenType::If);
-        keywords.insert("impl".to_string(), TokenType::Impl);
-        keywords.insert("in".to_string(), TokenType::In);
-        keywords.insert("let".to_string(), TokenType::Let);
-        keywords.insert("loop".to_string(), TokenType::Loop);
-        keywords.insert("match".to_string(), TokenType::Match);
-        keywords.insert("mod".to_string(), TokenType::Mod);
-        keywords.insert("move".to_string(), TokenType::Move);
-        keywords.insert("mut".to_string(), TokenType::Mut);
-        keywords.insert("pub".to_string(), TokenType::Pub);
-        keywords.insert("return".to_string(), TokenType::Return);
-        keywords.insert("struct".to_string(), TokenType::Struct);
-        keywords.insert("true".to_string(), TokenType::True);
-        keywords.insert("type".to_string(), TokenType::Type);
-        keywords.insert("use".to_string(), TokenType::Use);
-        keywords.insert("while".to_string(), TokenType::While);
-        keywords.insert("async".to_string(), TokenType::Async);
-        keywords.insert("await".to_string(), TokenType::Await);
-        
-        // Reserved keywords
-        keywords.insert("trait".to_string(), TokenType::Trait);
-        keywords.insert("try".to_string(), TokenType::Try);
-        
-        // Boolean literals and null
-        keywords.insert("null".to_string(), TokenType::Null);
-        
-        keywords
+    /// Keep scanning past malformed input instead of failing
+    ///
+    /// An invalid character is skipped, a string running to the end of the input
+    /// resumes on the line after its opening quote and a bad escape is left out of
+    /// its string. Each problem is recorded in `errors` and `next_token` never fails.
+    pub fn with_error_recovery(mut self) -> Self {
+        self.recover = true;
+        self
+    }
+    
+    /// Errors skipped over with error recovery on
+    pub fn errors(&self) -> &[LexerError] {
+        &self.errors
+    }
+    
+    /// Take the errors recorded so far, leaving none
+    pub fn take_errors(&mut self) -> Vec<LexerError> {
+        std::mem::take(&mut self.errors)
     }
     
     /// Advance to the next character
+    #[inline]
     pub fn advance(&mut self) {
         if let Some(ch) = self.current_char {
-            // Update position tracking
-            if ch == '\n' {
-                self.position.next_line();
-            } else {
-                self.position.next_column();
-            }
+            self.position.advance_char(ch);
             
             // Move to next character
             self.current_pos += ch.len_utf8();
-            self.current_char = self.chars.next();
+            self.current_char = char_at(self.input, self.current_pos);
         }
     }
     
+    /// Advance past the current character, which is ASCII and not a newline
+    #[inline]
+    fn advance_ascii(&mut self) {
+        self.current_pos += 1;
+        self.position.advance(1);
+        self.current_char = char_at(self.input, self.current_pos);
+    }
+    
     /// Look at the next character without advancing
+    #[inline]
     pub fn peek(&self) -> Option<char> {
-        let mut chars_clone = self.chars.clone();
-        chars_clone.next()
+        let ch = self.current_char?;
+        char_at(self.input, self.current_pos + ch.len_utf8())
     }
     
     /// Look at the character after next without advancing
     pub fn peek_next(&self) -> Option<char> {
-        let mut chars_clone = self.chars.clone();
-        chars_clone.next(); // Skip the next character
-        chars_clone.next() // Get the character after next
+        let next_pos = self.current_pos + self.current_char?.len_utf8();
+        let next = char_at(self.input, next_pos)?; // Skip the next character
+        char_at(self.input, next_pos + next.len_utf8()) // Get the character after next
     }
     
     /// Get the current character
@@ -141,16 +117,67 @@ impl<'a> Lexer<'a> {
     }
     
     /// Skip whitespace characters
+    #[inline]
     pub fn skip_whitespace(&mut self) {
         while let Some(ch) = self.current_char {
-            if ch.is_whitespace() {
-                self.advance();
-            } else {
+            if !ch.is_whitespace() {
                 break;
             }
+            
+            // Spaces, tabs and newlines are skipped byte by byte, without decoding
+            let bytes = self.input.as_bytes();
+            let mut end = self.current_pos;
+            let mut position = self.position;
+            while let Some(&byte) = bytes.get(end) {
+                match byte {
+                    b' ' | b'\t' | b'\r' => position.column += 1,
+                    b'\n' => {
+                        position.line += 1;
+                        position.column = 1;
+                    }
+                    _ => break,
+                }
+                end += 1;
+            }
+            if end > self.current_pos {
+                position.offset += end - self.current_pos;
+                self.reset_to(end, position);
+            } else {
+                // Other Unicode whitespace
+                self.advance();
+            }
         }
     }
     
+    /// Advance past the run of ASCII bytes from the current position that `accept`
+    /// accepts, which must not accept a newline
+    ///
+    /// Scanning bytes skips the decoding and position tracking `advance` does for
+    /// each character, which dominates the time spent on long runs.
+    fn skip_ascii_while(&mut self, accept: impl Fn(u8) -> bool) -> &'a str {
+        let start = self.current_pos;
+        let length = self.input.as_bytes()[start..].iter()
+            .take_while(|&&byte| byte.is_ascii() && accept(byte))
+            .count();
+        if length > 0 {
+            // Every byte of the run is a character of its own
+            let mut position = self.position;
+            position.column += length;
+            position.offset += length;
+            self.reset_to(start + length, position);
+        }
+        &self.input[start..start + length]
+    }
+    
+    /// Advance past the next `length` bytes, which hold no newline
+    fn advance_within_line(&mut self, length: usize) {
+        let end = self.current_pos + length;
+        let mut position = self.position;
+        position.column += self.input[self.current_pos..end].chars().count();
+        position.offset += length;
+        self.reset_to(end, position);
+    }
+    
     /// Get the current position
     pub fn get_position(&self) -> Position {
         self.position
@@ -167,7 +194,7 @@ impl<'a> Lexer<'a> {
     }
     
     /// Tokenize a character literal
-    fn tokenize_char(&mut self) -> Result<TokenType, LexerError> {
+    fn tokenize_char(&mut self) -> Result<TokenType<'a>, LexerError> {
         let start_pos = self.position;
         
         // Consume the opening quote
@@ -181,8 +208,9 @@ impl<'a> Lexer<'a> {
         
         let ch = if self.current_char == Some('\\') {
             // Escape sequence
+            let escape_pos = self.position;
             self.advance(); // Consume the backslash
-            self.process_escape_sequence()?
+            self.process_escape_sequence(escape_pos)?
         } else if let Some(c) = self.current_char {
             self.advance(); // Consume the character
             c
@@ -207,32 +235,33 @@ impl<'a> Lexer<'a> {
     }
     
     /// Skip a line comment and return the comment text if include_comments is true
-    fn skip_line_comment(&mut self) -> Option<TokenType> {
-        let _start_pos = self.position;
-        let mut comment = String::new();
-        let is_doc_comment = self.peek() == Some('/');
+    ///
+    /// `///` starts a doc comment for the following item and `//!` one for the
+    /// enclosing item; `////` and longer runs of slashes are plain comments.
+    fn skip_line_comment(&mut self) -> Option<TokenType<'a>> {
+        let is_doc_comment = self.peek() == Some('/') && self.peek_next() != Some('/');
+        let is_inner_doc_comment = self.peek() == Some('!');
         
-        // Skip the second '/' or third '/' for doc comments
-        if is_doc_comment {
+        // Skip the third '/' or the '!' of doc comments
+        if is_doc_comment || is_inner_doc_comment {
             self.advance();
         }
         
         // Skip the current character (which is the second '/')
         self.advance();
         
-        // Collect the comment text
-        while let Some(ch) = self.current_char {
-            if ch == '\n' {
-                break;
-            }
-            comment.push(ch);
-            self.advance();
-        }
+        // The comment text runs to the end of the line
+        let rest = &self.input[self.current_pos..];
+        let comment = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        self.advance_within_line(comment.len());
         
         // Return the comment token if include_comments is true
         if self.include_comments {
+            let comment = Cow::Borrowed(comment);
             if is_doc_comment {
                 Some(TokenType::DocLineComment(comment))
+            } else if is_inner_doc_comment {
+                Some(TokenType::InnerDocLineComment(comment))
             } else {
                 Some(TokenType::LineComment(comment))
             }
@@ -242,32 +271,32 @@ impl<'a> Lexer<'a> {
     }
     
     /// Skip a block comment and return the comment text if include_comments is true
-    fn skip_block_comment(&mut self) -> Result<Option<TokenType>, LexerError> {
-        let start_pos = self.position;
-        let mut comment = String::new();
-        let is_doc_comment = self.peek() == Some('*');
+    ///
+    /// `/**` and `/*!` start doc comments like `///` and `//!`, while `/**/` and
+    /// `/***` are plain. `start_pos` is the position of the opening `/`, which an
+    /// unterminated comment is reported at.
+    fn skip_block_comment(&mut self, start_pos: Position) -> Result<Option<TokenType<'a>>, LexerError> {
+        let is_doc_comment = self.peek() == Some('*') && !matches!(self.peek_next(), Some('*' | '/'));
+        let is_inner_doc_comment = self.peek() == Some('!');
         
         // Skip the '*' character
         self.advance();
         
-        // Check if it's a doc comment (second '*')
-        if is_doc_comment {
-            // Skip the second '*' for doc comments if present
-            if self.current_char == Some('*') && self.peek() != Some('/') {
-                self.advance();
-            }
+        // Skip the second '*' or the '!' of doc comments
+        if (is_doc_comment || is_inner_doc_comment) && self.peek() != Some('/') {
+            self.advance();
         }
         
         // Track nesting level for nested block comments
         let mut nesting_level = 1;
         
-        // Collect the comment text
+        // The comment text runs up to the closing `*/`, nested comments included
+        let text_start = self.current_pos;
+        let mut text_end = text_start;
         while let Some(ch) = self.current_char {
             // Check for nested block comment start
             if ch == '/' && self.peek() == Some('*') {
-                comment.push(ch);
                 self.advance();
-                comment.push(self.current_char.unwrap());
                 self.advance();
                 nesting_level += 1;
                 continue;
@@ -275,20 +304,17 @@ impl<'a> Lexer<'a> {
             
             // Check for block comment end
             if ch == '*' && self.peek() == Some('/') {
+                text_end = self.current_pos;
                 self.advance(); // Skip the '*'
                 self.advance(); // Skip the '/'
                 nesting_level -= 1;
                 
                 if nesting_level == 0 {
                     break;
-                } else {
-                    comment.push('*');
-                    comment.push('/');
-                    continue;
                 }
+                continue;
             }
             
-            comment.push(ch);
             self.advance();
         }
         
@@ -299,8 +325,11 @@ impl<'a> Lexer<'a> {
         
         // Return the comment token if include_comments is true
         if self.include_comments {
+            let comment = Cow::Borrowed(&self.input[text_start..text_end]);
             if is_doc_comment {
                 Ok(Some(TokenType::DocBlockComment(comment)))
+            } else if is_inner_doc_comment {
+                Ok(Some(TokenType::InnerDocBlockComment(comment)))
             } else {
                 Ok(Some(TokenType::BlockComment(comment)))
             }
@@ -310,33 +339,109 @@ impl<'a> Lexer<'a> {
     }
     
     /// Tokenize an identifier or keyword
-    fn tokenize_identifier(&mut self) -> TokenType {
-        let mut identifier = String::new();
-        
-        // Consume all identifier characters
-        while let Some(ch) = self.current_char {
-            if Self::is_identifier_char(ch) {
-                identifier.push(ch);
-                self.advance();
-            } else {
-                break;
+    fn tokenize_identifier(&mut self) -> TokenType<'a> {
+        let start = self.current_pos;
+        loop {
+            self.skip_ascii_while(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+            match self.current_char {
+                Some(ch) if Self::is_identifier_char(ch) => self.advance(),
+                _ => break,
             }
         }
+        let text = &self.input[start..self.current_pos];
         
-        // Check if it's a keyword
-        if let Some(keyword_type) = self.keywords.get(&identifier) {
-            keyword_type.clone()
-        } else {
-            // It's a regular identifier
-            TokenType::Identifier(identifier)
+        if let Some(keyword) = Self::keyword(text) {
+            return keyword;
         }
+        TokenType::Identifier(Cow::Borrowed(text))
     }
     
-    /// Process an escape sequence in a string literal
-    fn process_escape_sequence(&mut self) -> Result<char, LexerError> {
-        // We've already consumed the backslash
-        let escape_pos = self.position;
-        
+    /// The keyword spelled `text`, if any
+    ///
+    /// Dispatching on the first letter keeps most names to a comparison or two.
+    fn keyword(text: &str) -> Option<TokenType<'a>> {
+        // Every keyword is two to eight lowercase letters
+        if !(2..=8).contains(&text.len()) || !text.as_bytes()[0].is_ascii_lowercase() {
+            return None;
+        }
+        let keyword = match text.as_bytes()[0] {
+            b'a' => match text {
+                "abort" => TokenType::Abort,
+                "as" => TokenType::As,
+                "async" => TokenType::Async,
+                "await" => TokenType::Await,
+                _ => return None,
+            },
+            b'b' => match text {
+                "break" => TokenType::Break,
+                "box" => TokenType::Box,
+                _ => return None,
+            },
+            b'c' => match text {
+                "const" => TokenType::Const,
+                "continue" => TokenType::Continue,
+                _ => return None,
+            },
+            b'd' if text == "do" => TokenType::Do,
+            b'e' => match text {
+                "else" => TokenType::Else,
+                "enum" => TokenType::Enum,
+                "extern" => TokenType::Extern,
+                _ => return None,
+            },
+            b'f' => match text {
+                "false" => TokenType::False,
+                "fn" => TokenType::Fn,
+                "for" => TokenType::For,
+                _ => return None,
+            },
+            b'i' => match text {
+                "if" => TokenType::If,
+                "impl" => TokenType::Impl,
+                "in" => TokenType::In,
+                _ => return None,
+            },
+            b'l' => match text {
+                "let" => TokenType::Let,
+                "loop" => TokenType::Loop,
+                _ => return None,
+            },
+            b'm' => match text {
+                "match" => TokenType::Match,
+                "mod" => TokenType::Mod,
+                "move" => TokenType::Move,
+                "mut" => TokenType::Mut,
+                _ => return None,
+            },
+            // Boolean literals and null
+            b'n' if text == "null" => TokenType::Null,
+            b'p' if text == "pub" => TokenType::Pub,
+            b'r' if text == "return" => TokenType::Return,
+            b's' if text == "struct" => TokenType::Struct,
+            b't' => match text {
+                "true" => TokenType::True,
+                "type" => TokenType::Type,
+                // Reserved keywords
+                "trait" => TokenType::Trait,
+                "try" => TokenType::Try,
+                _ => return None,
+            },
+            b'u' if text == "use" => TokenType::Use,
+            b'w' => match text {
+                "where" => TokenType::Where,
+                "while" => TokenType::While,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(keyword)
+    }
+    
+    /// Process an escape sequence in a string or character literal
+    ///
+    /// The backslash, at `escape_pos`, has already been consumed; errors are
+    /// reported at it.
+    fn process_escape_sequence(&mut self, escape_pos: Position) -> Result<char, LexerError> {
         if let Some(ch) = self.current_char {
             self.advance(); // Consume the escape character
             
@@ -348,6 +453,7 @@ impl<'a> Lexer<'a> {
                 '"' => Ok('"'),
                 '\'' => Ok('\''),
                 '0' => Ok('\0'),
+                'x' => self.process_hex_escape(escape_pos),
                 'u' => self.process_unicode_escape(escape_pos),
                 _ => Err(LexerError::InvalidEscapeSequence(format!("\\{}", ch), escape_pos)),
             }
@@ -356,6 +462,25 @@ impl<'a> Lexer<'a> {
         }
     }
     
+    /// Process an ASCII escape sequence (\xNN, at most \x7F)
+    fn process_hex_escape(&mut self, escape_pos: Position) -> Result<char, LexerError> {
+        let mut digits = String::new();
+        while digits.len() < 2 {
+            match self.current_char {
+                Some(ch) if ch.is_ascii_hexdigit() => {
+                    digits.push(ch);
+                    self.advance();
+                }
+                _ => return Err(LexerError::InvalidEscapeSequence(format!("\\x{}", digits), escape_pos)),
+            }
+        }
+        
+        match u8::from_str_radix(&digits, 16) {
+            Ok(byte) if byte.is_ascii() => Ok(char::from(byte)),
+            _ => Err(LexerError::InvalidEscapeSequence(format!("\\x{}", digits), escape_pos)),
+        }
+    }
+    
     /// Process a Unicode escape sequence (\u{XXXX})
     fn process_unicode_escape(&mut self, escape_pos: Position) -> Result<char, LexerError> {
         // We expect a { after \u
@@ -366,11 +491,13 @@ impl<'a> Lexer<'a> {
         
         let mut code_point = 0u32;
         let mut digit_count = 0;
+        let mut closed = false;
         
         // Read hex digits until we find a closing }
         while let Some(ch) = self.current_char {
             if ch == '}' {
                 self.advance(); // Consume the '}'
+                closed = true;
                 break;
             }
             
@@ -381,7 +508,7 @@ impl<'a> Lexer<'a> {
                 if digit_count > 6 {
                     // Too many digits for a valid Unicode code point
                     return Err(LexerError::InvalidUnicodeEscape(
-                        format!("\\u{{...}} (too many digits)"), 
+                        "\\u{...} (too many digits)".to_string(),
                         escape_pos
                     ));
                 }
@@ -395,31 +522,48 @@ impl<'a> Lexer<'a> {
             }
         }
         
+        if !closed {
+            return Err(LexerError::InvalidUnicodeEscape(format!("\\u{{{:x}", code_point), escape_pos));
+        }
         if digit_count == 0 {
             return Err(LexerError::InvalidUnicodeEscape("\\u{}".to_string(), escape_pos));
         }
         
-        // Convert the code point to a character
-        match char::from_u32(code_point) {
-            Some(c) => Ok(c),
-            None => Err(LexerError::InvalidUnicodeEscape(
-                format!("\\u{{{:x}}}", code_point), 
-                escape_pos
-            )),
-        }
+        // Surrogates and values past U+10FFFF are not characters
+        char::from_u32(code_point).ok_or(LexerError::UnicodeEscapeOutOfRange(code_point, escape_pos))
     }
     
     /// Tokenize a string literal
-    fn tokenize_string(&mut self) -> Result<TokenType, LexerError> {
+    ///
+    /// The value borrows the source text unless the string has escapes.
+    fn tokenize_string(&mut self) -> Result<TokenType<'a>, LexerError> {
         let start_pos = self.position;
-        let mut value = String::new();
         
         // Consume the opening quote
         self.advance();
         
+        let text_start = self.current_pos;
+        // The value so far, once an escape has made it differ from the source
+        let mut unescaped: Option<String> = None;
+        
         while let Some(ch) = self.current_char {
+            // Plain text up to the next quote, escape or line is taken in one piece
+            let rest = &self.input[self.current_pos..];
+            let plain = rest.find(['"', '\\', '\n']).unwrap_or(rest.len());
+            if plain > 0 {
+                if let Some(value) = &mut unescaped {
+                    value.push_str(&rest[..plain]);
+                }
+                self.advance_within_line(plain);
+                continue;
+            }
+            
             if ch == '"' {
                 // End of string
+                let value = match unescaped {
+                    Some(value) => Cow::Owned(value),
+                    None => Cow::Borrowed(&self.input[text_start..self.current_pos]),
+                };
                 self.advance(); // Consume the closing quote
                 return Ok(TokenType::String { 
                     value, 
@@ -428,12 +572,18 @@ impl<'a> Lexer<'a> {
                 });
             } else if ch == '\\' {
                 // Escape sequence
+                let value = unescaped.get_or_insert_with(|| self.input[text_start..self.current_pos].to_string());
+                let escape_pos = self.position;
                 self.advance(); // Consume the backslash
-                let escaped_char = self.process_escape_sequence()?;
-                value.push(escaped_char);
+                match self.process_escape_sequence(escape_pos) {
+                    Ok(escaped_char) => value.push(escaped_char),
+                    Err(err) => self.recoverable(err)?,
+                }
             } else {
                 // Regular character
-                value.push(ch);
+                if let Some(value) = &mut unescaped {
+                    value.push(ch);
+                }
                 self.advance();
             }
         }
@@ -442,100 +592,84 @@ impl<'a> Lexer<'a> {
         Err(LexerError::UnterminatedString(start_pos))
     }
     
+    /// Consume the digits accepted by `is_digit`, and any `_` separators among them
+    ///
+    /// Returns whether any digit was read, and the position of the `_` that ends
+    /// the run, if one does.
+    fn consume_digits(&mut self, is_digit: impl Fn(char) -> bool) -> (bool, Option<Position>) {
+        let start = self.position;
+        let run = self.skip_ascii_while(|byte| byte == b'_' || is_digit(char::from(byte)));
+        let digits = run.bytes().filter(|&byte| byte != b'_').count();
+        
+        // The last `_` of the run, if no digit follows it
+        let trailing_underscore = run.ends_with('_').then(|| {
+            let mut position = start;
+            position.advance(run.len() - 1);
+            position
+        });
+        (digits > 0, trailing_underscore)
+    }
+    
+    /// The number read since byte `start`, without `_` separators and with a
+    /// lowercase base prefix
+    fn number_text(&self, start: usize) -> Cow<'a, str> {
+        let text = &self.input[start..self.current_pos];
+        let upper_prefix = matches!(text.as_bytes().get(1), Some(b'X' | b'O' | b'B'));
+        if !upper_prefix && !text.contains('_') {
+            return Cow::Borrowed(text);
+        }
+        let mut value: String = text.chars().filter(|&ch| ch != '_').collect();
+        if let Some(prefix) = value.get_mut(1..2) {
+            prefix.make_ascii_lowercase();
+        }
+        Cow::Owned(value)
+    }
+    
     /// Tokenize a number literal (integer or float)
-    fn tokenize_number(&mut self) -> Result<TokenType, LexerError> {
+    ///
+    /// `_` separators are allowed between digits in every base and are dropped
+    /// from the stored value, which otherwise borrows the source text.
+    fn tokenize_number(&mut self) -> Result<TokenType<'a>, LexerError> {
         let start_pos = self.position;
-        let mut value = String::new();
+        let start = self.current_pos;
         let mut base = NumberBase::Decimal;
         let mut is_float = false;
-        let mut _has_exponent = false;
         let mut suffix = None;
+        // `_` ending the last run of digits, which must not run into a suffix
+        let mut trailing_underscore = None;
         
         // Check for hex, octal, or binary prefix
         if self.current_char == Some('0') {
-            value.push('0');
-            self.advance();
+            let prefixed = match self.peek() {
+                Some('x') | Some('X') => Some(NumberBase::Hexadecimal),
+                Some('o') | Some('O') => Some(NumberBase::Octal),
+                Some('b') | Some('B') => Some(NumberBase::Binary),
+                _ => None,
+            };
             
-            match self.current_char {
-                Some('x') | Some('X') => {
-                    value.push('x');
-                    self.advance();
-                    base = NumberBase::Hexadecimal;
-                    
-                    // Consume hexadecimal digits
-                    let mut has_digits = false;
-                    while let Some(ch) = self.current_char {
-                        if ch.is_ascii_hexdigit() {
-                            value.push(ch);
-                            has_digits = true;
-                            self.advance();
-                        } else if ch == '_' {
-                            // Skip underscores in numbers
-                            self.advance();
-                        } else {
-                            break;
-                        }
-                    }
-                    
-                    if !has_digits {
-                        return Err(LexerError::InvalidNumber(value, start_pos));
-                    }
-                },
-                Some('o') | Some('O') => {
-                    value.push('o');
-                    self.advance();
-                    base = NumberBase::Octal;
-                    
-                    // Consume octal digits
-                    let mut has_digits = false;
-                    while let Some(ch) = self.current_char {
-                        if ch >= '0' && ch <= '7' {
-                            value.push(ch);
-                            has_digits = true;
-                            self.advance();
-                        } else if ch == '_' {
-                            // Skip underscores in numbers
-                            self.advance();
-                        } else if ch.is_ascii_digit() {
-                            return Err(LexerError::InvalidOctalDigit(ch, self.position));
-                        } else {
-                            break;
-                        }
-                    }
-                    
-                    if !has_digits {
-                        return Err(LexerError::InvalidNumber(value, start_pos));
-                    }
-                },
-                Some('b') | Some('B') => {
-                    value.push('b');
-                    self.advance();
-                    base = NumberBase::Binary;
-                    
-                    // Consume binary digits
-                    let mut has_digits = false;
-                    while let Some(ch) = self.current_char {
-                        if ch == '0' || ch == '1' {
-                            value.push(ch);
-                            has_digits = true;
-                            self.advance();
-                        } else if ch == '_' {
-                            // Skip underscores in numbers
-                            self.advance();
-                        } else if ch.is_ascii_digit() {
-                            return Err(LexerError::InvalidBinaryDigit(ch, self.position));
-                        } else {
-                            break;
-                        }
+            if let Some(prefixed_base) = prefixed {
+                self.advance();
+                self.advance();
+                base = prefixed_base;
+                
+                let (has_digits, underscore) = match base {
+                    NumberBase::Hexadecimal => self.consume_digits(|ch| ch.is_ascii_hexdigit()),
+                    NumberBase::Octal => self.consume_digits(|ch| ('0'..='7').contains(&ch)),
+                    _ => self.consume_digits(|ch| ch == '0' || ch == '1'),
+                };
+                trailing_underscore = underscore;
+                
+                match self.current_char {
+                    Some(ch) if ch.is_ascii_digit() && base == NumberBase::Octal => {
+                        return Err(LexerError::InvalidOctalDigit(ch, self.position));
                     }
-                    
-                    if !has_digits {
-                        return Err(LexerError::InvalidNumber(value, start_pos));
+                    Some(ch) if ch.is_ascii_digit() && base == NumberBase::Binary => {
+                        return Err(LexerError::InvalidBinaryDigit(ch, self.position));
                     }
-                },
-                _ => {
-                    // Just a regular decimal number starting with 0
-                    // Continue with the normal decimal number handling below
+                    _ => {}
+                }
+                if !has_digits {
+                    return Err(LexerError::InvalidNumber(self.number_text(start).into_owned(), start_pos));
                 }
             }
         }
@@ -543,91 +677,58 @@ impl<'a> Lexer<'a> {
         // If we didn't process a special base (or it's a decimal starting with 0)
         if base == NumberBase::Decimal {
             // Consume integer part
-            while let Some(ch) = self.current_char {
-                if ch.is_ascii_digit() {
-                    value.push(ch);
-                    self.advance();
-                } else if ch == '_' {
-                    // Skip underscores in numbers
-                    self.advance();
-                } else {
-                    break;
-                }
-            }
+            let (_, underscore) = self.consume_digits(|ch| ch.is_ascii_digit());
+            trailing_underscore = underscore;
             
             // Check for decimal point
             if self.current_char == Some('.') {
                 // Look ahead to ensure it's not the start of a range operator (..)
                 if self.peek() != Some('.') {
-                    value.push('.');
                     self.advance();
                     is_float = true;
                     
-                    // Consume fractional part
-                    let mut has_fraction_digits = false;
-                    while let Some(ch) = self.current_char {
-                        if ch.is_ascii_digit() {
-                            value.push(ch);
-                            has_fraction_digits = true;
-                            self.advance();
-                        } else if ch == '_' {
-                            // Skip underscores in numbers
-                            self.advance();
-                        } else {
-                            break;
-                        }
-                    }
-                    
                     // A decimal point must be followed by at least one digit
+                    let (has_fraction_digits, underscore) = self.consume_digits(|ch| ch.is_ascii_digit());
                     if !has_fraction_digits {
-                        return Err(LexerError::InvalidNumber(value, start_pos));
+                        return Err(LexerError::InvalidNumber(self.number_text(start).into_owned(), start_pos));
                     }
+                    trailing_underscore = underscore;
                 }
             }
             
             // Check for exponent
-            if let Some('e') | Some('E') = self.current_char {
-                value.push(self.current_char.unwrap());
+            if let Some('e' | 'E') = self.current_char {
                 self.advance();
                 is_float = true;
-                _has_exponent = true;
                 
                 // Check for exponent sign
-                if let Some('+') | Some('-') = self.current_char {
-                    value.push(self.current_char.unwrap());
+                if let Some('+' | '-') = self.current_char {
                     self.advance();
                 }
                 
-                // Consume exponent digits
-                let mut has_exponent_digits = false;
-                while let Some(ch) = self.current_char {
-                    if ch.is_ascii_digit() {
-                        value.push(ch);
-                        has_exponent_digits = true;
-                        self.advance();
-                    } else if ch == '_' {
-                        // Skip underscores in numbers
-                        self.advance();
-                    } else {
-                        break;
-                    }
-                }
-                
                 // An exponent must be followed by at least one digit
+                let (has_exponent_digits, underscore) = self.consume_digits(|ch| ch.is_ascii_digit());
                 if !has_exponent_digits {
-                    return Err(LexerError::InvalidNumber(value, start_pos));
+                    return Err(LexerError::InvalidNumber(self.number_text(start).into_owned(), start_pos));
                 }
+                trailing_underscore = underscore;
             }
         }
         
+        let value = self.number_text(start);
+        
         // Check for numeric suffix
         if let Some(ch) = self.current_char {
             if Self::is_identifier_char(ch) {
-                let mut suffix_str = String::new();
+                // `1_000_u32` would silently read as `1000u32`; require `1_000u32`
+                if let Some(underscore_pos) = trailing_underscore {
+                    return Err(LexerError::UnderscoreBeforeSuffix(underscore_pos));
+                }
+                
+                let suffix_start = self.current_pos;
                 
                 while let Some(ch) = self.current_char {
                     if Self::is_identifier_char(ch) {
-                        suffix_str.push(ch);
                         self.advance();
                     } else {
                         break;
@@ -636,7 +737,7 @@ impl<'a> Lexer<'a> {
                 
                 // Validate suffix
                 // For now, we'll just store it and let the parser validate it
-                suffix = Some(suffix_str);
+                suffix = Some(Cow::Borrowed(&self.input[suffix_start..self.current_pos]));
             }
         }
         
@@ -661,7 +762,7 @@ impl<'a> Lexer<'a> {
     }
     
     /// Tokenize a raw string literal (r"..." or r#"..."#)
-    fn tokenize_raw_string(&mut self) -> Result<TokenType, LexerError> {
+    fn tokenize_raw_string(&mut self) -> Result<TokenType<'a>, LexerError> {
         let start_pos = self.position;
         
         // Consume the 'r' character
@@ -676,9 +777,10 @@ impl<'a> Lexer<'a> {
         }
         self.advance(); // Consume the opening quote
         
-        let mut value = String::new();
+        let text_start = self.current_pos;
+        let text_end;
         
-        // Collect characters until we find the closing delimiter
+        // Scan up to the closing delimiter; the value is the source text before it
         loop {
             if self.current_char.is_none() {
                 return Err(LexerError::UnterminatedRawString(start_pos));
@@ -686,41 +788,120 @@ impl<'a> Lexer<'a> {
             
             // Check for closing quote
             if self.current_char == Some('"') {
+                let quote = self.current_pos;
                 self.advance(); // Consume the quote
                 
-                // Count the number of hash symbols after the quote
+                // Count the number of hash symbols after the quote, up to the opening count
                 let mut closing_hash_count = 0;
-                while let Some('#') = self.current_char {
+                while closing_hash_count < hash_count && self.current_char == Some('#') {
                     closing_hash_count += 1;
                     self.advance();
                 }
                 
                 // If the number of hash symbols matches, we've found the end of the string
                 if closing_hash_count == hash_count {
+                    text_end = quote;
                     break;
                 }
-                
-                // Otherwise, add the quote and hash symbols to the string value
-                value.push('"');
-                for _ in 0..closing_hash_count {
-                    value.push('#');
-                }
             } else {
-                // Add the current character to the string value
-                value.push(self.current_char.unwrap());
                 self.advance();
             }
         }
         
         Ok(TokenType::String {
-            value,
+            value: Cow::Borrowed(&self.input[text_start..text_end]),
             raw: true,
             raw_delimiter: if hash_count > 0 { Some(hash_count) } else { None },
         })
     }
     
     /// Get the next token from the input
-    pub fn next_token(&mut self) -> Result<Token, LexerError> {
+    ///
+    /// With error recovery on, malformed input is skipped and this never fails.
+    #[inline]
+    pub fn next_token(&mut self) -> Result<Token<'a>, LexerError> {
+        loop {
+            let error_count = self.errors.len();
+            match self.scan_token() {
+                Err(err) if self.recover => {
+                    self.skip_malformed(&err, error_count);
+                    self.errors.push(err);
+                }
+                result => return result,
+            }
+        }
+    }
+    
+    /// Record `err` and carry on with error recovery on, otherwise fail with it
+    fn recoverable(&mut self, err: LexerError) -> Result<(), LexerError> {
+        if !self.recover {
+            return Err(err);
+        }
+        self.errors.push(err);
+        Ok(())
+    }
+    
+    /// Move past the rest of a malformed token
+    ///
+    /// `error_count` is the number of errors recorded before the token.
+    fn skip_malformed(&mut self, err: &LexerError, error_count: usize) {
+        match err {
+            // The literal ran to the end of the input; resume on the line after its opening quote
+            LexerError::UnterminatedString(start) | LexerError::UnterminatedRawString(start) => {
+                self.errors.truncate(error_count);
+                self.reset_to(start.offset, *start);
+                while let Some(ch) = self.current_char {
+                    self.advance();
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            }
+            // Inside a character literal; skip to its closing quote
+            LexerError::MultiCharLiteral(_)
+            | LexerError::InvalidEscapeSequence(..)
+            | LexerError::InvalidUnicodeEscape(..)
+            | LexerError::UnicodeEscapeOutOfRange(..) => {
+                while let Some(ch) = self.current_char {
+                    if ch == '\n' {
+                        break;
+                    }
+                    self.advance();
+                    if ch == '\'' {
+                        break;
+                    }
+                }
+            }
+            // Inside a number; skip the rest of its digits and suffix
+            LexerError::InvalidNumber(..)
+            | LexerError::InvalidHexDigit(..)
+            | LexerError::InvalidBinaryDigit(..)
+            | LexerError::InvalidOctalDigit(..)
+            | LexerError::InvalidNumericSuffix(..)
+            | LexerError::UnderscoreBeforeSuffix(_) => {
+                while self.current_char.is_some_and(Self::is_identifier_char) {
+                    self.advance();
+                }
+            }
+            // Already past the bad input
+            LexerError::InvalidCharacter(..)
+            | LexerError::UnterminatedChar(_)
+            | LexerError::UnterminatedBlockComment(_)
+            | LexerError::EmptyCharLiteral(_)
+            | LexerError::InvalidRawStringDelimiter(_)
+            | LexerError::Utf8Error(_) => {}
+        }
+    }
+    
+    /// Continue scanning from byte `offset`, which is at `position`
+    fn reset_to(&mut self, offset: usize, position: Position) {
+        self.current_char = char_at(self.input, offset);
+        self.current_pos = offset;
+        self.position = position;
+    }
+    
+    /// Scan one token, failing on malformed input
+    fn scan_token(&mut self) -> Result<Token<'a>, LexerError> {
         // Skip any whitespace
         self.skip_whitespace();
         
@@ -757,13 +938,13 @@ impl<'a> Lexer<'a> {
                 if let Some(comment_token) = self.skip_line_comment() {
                     return Ok(Token::new(comment_token, position));
                 }
-                return self.next_token(); // Skip the comment and get the next token
+                return self.scan_token(); // Skip the comment and get the next token
             } else if self.peek() == Some('*') {
                 // Block comment
                 self.advance(); // Skip the '/'
-                match self.skip_block_comment() {
+                match self.skip_block_comment(position) {
                     Ok(Some(comment_token)) => return Ok(Token::new(comment_token, position)),
-                    Ok(None) => return self.next_token(), // Skip the comment and get the next token
+                    Ok(None) => return self.scan_token(), // Skip the comment and get the next token
                     Err(err) => return Err(err),
                 }
             }
@@ -956,9 +1137,31 @@ impl<'a> Lexer<'a> {
         };
         
         // Advance to the next character
-        self.advance();
+        self.advance_ascii();
         
         // Return the token
         Ok(Token::new(token_type, position))
     }
-} 
\ No newline at end of file
+} 
+
+/// Tokens up to the end of the input, not including `Eof`
+///
+/// Iterating always recovers from errors; they can be read from `errors` as it goes.
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+    
+    fn next(&mut self) -> Option<Token<'a>> {
+        let recover = std::mem::replace(&mut self.recover, true);
+        let token = self.next_token();
+        self.recover = recover;
+        token.ok().filter(|token| token.token_type != TokenType::Eof)
+    }
+}
+
+/// The character of `input` starting at byte `offset`, if any
+fn char_at(input: &str, offset: usize) -> Option<char> {
+    match *input.as_bytes().get(offset)? {
+        byte if byte.is_ascii() => Some(char::from(byte)),
+        _ => input[offset..].chars().next(),
+    }
+}
@@ -1,15 +1,22 @@
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
     use crate::lexer::lexer::Lexer;
     use crate::lexer::token::{TokenType, NumberBase};
+    use crate::lexer::error::LexerError;
     
     // Helper function to create a test lexer
-    fn create_lexer(input: &str) -> Lexer {
+    fn create_lexer(input: &str) -> Lexer<'_> {
         Lexer::new(input, 0)
     }
     
+    // Helper function to create an identifier token
+    fn identifier(name: &str) -> TokenType<'_> {
+        TokenType::Identifier(name.into())
+    }
+    
     // Helper function to get all tokens from a lexer
-    fn collect_tokens(lexer: &mut Lexer) -> Vec<TokenType> {
+    fn collect_tokens<'a>(lexer: &mut Lexer<'a>) -> Vec<TokenType<'a>> {
         let mut tokens = Vec::new();
         loop {
             match lexer.next_token() {
@@ -102,7 +109,7 @@ mod tests {
     fn test_keywords() {
         let input = "fn let if else while for return struct enum impl trait \
                     mod pub use const mut break continue loop match type \
-                    in move box extern abort do async await try";
+                    in move box extern abort do async await try as where";
         let mut lexer = create_lexer(input);
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
@@ -136,6 +143,8 @@ mod tests {
             TokenType::Async,
             TokenType::Await,
             TokenType::Try,
+            TokenType::As,
+            TokenType::Where,
             TokenType::Eof,
         ]);
     }
@@ -166,29 +175,52 @@ mod tests {
         let mut lexer = create_lexer("foo bar baz _test test123 x1 y2 z3");
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::Identifier("foo".to_string()),
-            TokenType::Identifier("bar".to_string()),
-            TokenType::Identifier("baz".to_string()),
-            TokenType::Identifier("_test".to_string()),
-            TokenType::Identifier("test123".to_string()),
-            TokenType::Identifier("x1".to_string()),
-            TokenType::Identifier("y2".to_string()),
-            TokenType::Identifier("z3".to_string()),
+            identifier("foo"),
+            identifier("bar"),
+            identifier("baz"),
+            identifier("_test"),
+            identifier("test123"),
+            identifier("x1"),
+            identifier("y2"),
+            identifier("z3"),
             TokenType::Eof,
         ]);
     }
     
+    #[test]
+    fn test_token_text_borrows_source() {
+        let source = r#"a_name_longer_than_twenty_two_bytes café 1_000 0X1f 2.5e3u8 "plain" "esc\n" r"raw""#;
+        let tokens = collect_tokens(&mut create_lexer(source));
+        fn text<'a>(token: &TokenType<'a>) -> Cow<'a, str> {
+            match token {
+                TokenType::Identifier(text)
+                | TokenType::Integer { value: text, .. }
+                | TokenType::Float { value: text, .. }
+                | TokenType::String { value: text, .. } => text.clone(),
+                _ => panic!("unexpected token {:?}", token),
+            }
+        }
+        let texts: Vec<_> = tokens[..tokens.len() - 1].iter().map(text).collect();
+    
+        // Text found verbatim in the source is borrowed from it; anything rewritten is owned
+        assert_eq!(texts, ["a_name_longer_than_twenty_two_bytes", "café", "1000", "0x1f", "2.5e3", "plain", "esc\n", "raw"]);
+        let borrowed: Vec<_> = texts.iter().map(|text| matches!(text, Cow::Borrowed(_))).collect();
+        assert_eq!(borrowed, [true, true, false, false, true, true, false, true]);
+        assert!(matches!(&tokens[4], TokenType::Float { suffix: Some(Cow::Borrowed("u8")), .. }));
+        assert!(matches!(tokens[0].clone().into_owned(), TokenType::Identifier(Cow::Owned(_))));
+    }
+    
     #[test]
     fn test_integer_literals() {
         let mut lexer = create_lexer("123 0 42 0x1A 0b1010 0o777");
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::Integer { value: "123".to_string(), base: NumberBase::Decimal, suffix: None },
-            TokenType::Integer { value: "0".to_string(), base: NumberBase::Decimal, suffix: None },
-            TokenType::Integer { value: "42".to_string(), base: NumberBase::Decimal, suffix: None },
-            TokenType::Integer { value: "0x1A".to_string(), base: NumberBase::Hexadecimal, suffix: None },
-            TokenType::Integer { value: "0b1010".to_string(), base: NumberBase::Binary, suffix: None },
-            TokenType::Integer { value: "0o777".to_string(), base: NumberBase::Octal, suffix: None },
+            TokenType::Integer { value: "123".into(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Integer { value: "0".into(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Integer { value: "42".into(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Integer { value: "0x1A".into(), base: NumberBase::Hexadecimal, suffix: None },
+            TokenType::Integer { value: "0b1010".into(), base: NumberBase::Binary, suffix: None },
+            TokenType::Integer { value: "0o777".into(), base: NumberBase::Octal, suffix: None },
             TokenType::Eof,
         ]);
     }
@@ -198,11 +230,11 @@ mod tests {
         let mut lexer = create_lexer("3.14 1.0 2e10 1.5e-3 0.5");
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::Float { value: "3.14".to_string(), suffix: None },
-            TokenType::Float { value: "1.0".to_string(), suffix: None },
-            TokenType::Float { value: "2e10".to_string(), suffix: None },
-            TokenType::Float { value: "1.5e-3".to_string(), suffix: None },
-            TokenType::Float { value: "0.5".to_string(), suffix: None },
+            TokenType::Float { value: "3.14".into(), suffix: None },
+            TokenType::Float { value: "1.0".into(), suffix: None },
+            TokenType::Float { value: "2e10".into(), suffix: None },
+            TokenType::Float { value: "1.5e-3".into(), suffix: None },
+            TokenType::Float { value: "0.5".into(), suffix: None },
             TokenType::Eof,
         ]);
     }
@@ -212,10 +244,10 @@ mod tests {
         let mut lexer = create_lexer("1_000_000 1_234.567_89 0xDEAD_BEEF 0b1010_1010");
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::Integer { value: "1000000".to_string(), base: NumberBase::Decimal, suffix: None },
-            TokenType::Float { value: "1234.56789".to_string(), suffix: None },
-            TokenType::Integer { value: "0xDEADBEEF".to_string(), base: NumberBase::Hexadecimal, suffix: None },
-            TokenType::Integer { value: "0b10101010".to_string(), base: NumberBase::Binary, suffix: None },
+            TokenType::Integer { value: "1000000".into(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Float { value: "1234.56789".into(), suffix: None },
+            TokenType::Integer { value: "0xDEADBEEF".into(), base: NumberBase::Hexadecimal, suffix: None },
+            TokenType::Integer { value: "0b10101010".into(), base: NumberBase::Binary, suffix: None },
             TokenType::Eof,
         ]);
     }
@@ -225,9 +257,9 @@ mod tests {
         let mut lexer = create_lexer("123u32 3.14f64 0xFFi16");
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::Integer { value: "123".to_string(), base: NumberBase::Decimal, suffix: Some("u32".to_string()) },
-            TokenType::Float { value: "3.14".to_string(), suffix: Some("f64".to_string()) },
-            TokenType::Integer { value: "0xFF".to_string(), base: NumberBase::Hexadecimal, suffix: Some("i16".to_string()) },
+            TokenType::Integer { value: "123".into(), base: NumberBase::Decimal, suffix: Some("u32".into()) },
+            TokenType::Float { value: "3.14".into(), suffix: Some("f64".into()) },
+            TokenType::Integer { value: "0xFF".into(), base: NumberBase::Hexadecimal, suffix: Some("i16".into()) },
             TokenType::Eof,
         ]);
     }
@@ -238,7 +270,7 @@ mod tests {
         let mut lexer = create_lexer(r#"r"hello world""#);
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::String { value: "hello world".to_string(), raw: true, raw_delimiter: None },
+            TokenType::String { value: "hello world".into(), raw: true, raw_delimiter: None },
             TokenType::Eof,
         ]);
     }
@@ -248,8 +280,8 @@ mod tests {
         let mut lexer = create_lexer(r#"r"C:\path\to\file.txt" r"Line 1\nLine 2""#);
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::String { value: r"C:\path\to\file.txt".to_string(), raw: true, raw_delimiter: None },
-            TokenType::String { value: r"Line 1\nLine 2".to_string(), raw: true, raw_delimiter: None },
+            TokenType::String { value: r"C:\path\to\file.txt".into(), raw: true, raw_delimiter: None },
+            TokenType::String { value: r"Line 1\nLine 2".into(), raw: true, raw_delimiter: None },
             TokenType::Eof,
         ]);
     }
@@ -314,10 +346,10 @@ mod tests {
         let mut lexer = create_lexer(r#""hello world" "with \"quotes\"" "with \n \t \r \\ escapes" "empty""#);
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::String { value: "hello world".to_string(), raw: false, raw_delimiter: None },
-            TokenType::String { value: "with \"quotes\"".to_string(), raw: false, raw_delimiter: None },
-            TokenType::String { value: "with \n \t \r \\ escapes".to_string(), raw: false, raw_delimiter: None },
-            TokenType::String { value: "empty".to_string(), raw: false, raw_delimiter: None },
+            TokenType::String { value: "hello world".into(), raw: false, raw_delimiter: None },
+            TokenType::String { value: "with \"quotes\"".into(), raw: false, raw_delimiter: None },
+            TokenType::String { value: "with \n \t \r \\ escapes".into(), raw: false, raw_delimiter: None },
+            TokenType::String { value: "empty".into(), raw: false, raw_delimiter: None },
             TokenType::Eof,
         ]);
     }
@@ -327,23 +359,105 @@ mod tests {
         let mut lexer = create_lexer(r#""Unicode: \u{1F600}""#); // Unicode for 😀
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::String { value: "Unicode: 😀".to_string(), raw: false, raw_delimiter: None },
+            TokenType::String { value: "Unicode: 😀".into(), raw: false, raw_delimiter: None },
+            TokenType::Eof,
+        ]);
+    }
+    
+    #[test]
+    fn test_char_escape_sequences() {
+        let mut lexer = create_lexer(r"'\n' '\x41' '\x7F' '\u{1F600}' '\u{0}' '\''");
+        let tokens = collect_tokens(&mut lexer);
+        assert_eq!(tokens, vec![
+            TokenType::Char('\n'),
+            TokenType::Char('A'),
+            TokenType::Char('\x7F'),
+            TokenType::Char('😀'),
+            TokenType::Char('\0'),
+            TokenType::Char('\''),
+            TokenType::Eof,
+        ]);
+    }
+    
+    #[test]
+    fn test_raw_string_hash_fencing() {
+        let mut lexer = create_lexer(r####"r#"say "hi""# r##"a"#b"## r"\d+\.\d+""####);
+        let tokens = collect_tokens(&mut lexer);
+        assert_eq!(tokens, vec![
+            TokenType::String { value: r#"say "hi""#.into(), raw: true, raw_delimiter: Some(1) },
+            TokenType::String { value: r##"a"#b"##.into(), raw: true, raw_delimiter: Some(2) },
+            TokenType::String { value: r"\d+\.\d+".into(), raw: true, raw_delimiter: None },
             TokenType::Eof,
         ]);
     }
     
+    #[test]
+    fn test_numeric_separators_in_all_bases() {
+        let mut lexer = create_lexer("0o7_7 0xFF_FF 1_000u32 1e1_0 0b1_0i8");
+        let tokens = collect_tokens(&mut lexer);
+        assert_eq!(tokens, vec![
+            TokenType::Integer { value: "0o77".into(), base: NumberBase::Octal, suffix: None },
+            TokenType::Integer { value: "0xFFFF".into(), base: NumberBase::Hexadecimal, suffix: None },
+            TokenType::Integer { value: "1000".into(), base: NumberBase::Decimal, suffix: Some("u32".into()) },
+            TokenType::Float { value: "1e10".into(), suffix: None },
+            TokenType::Integer { value: "0b10".into(), base: NumberBase::Binary, suffix: Some("i8".into()) },
+            TokenType::Eof,
+        ]);
+    }
+    
+    #[test]
+    fn test_malformed_literal_errors() {
+        let first_error = |input: &str| {
+            let mut lexer = create_lexer(input);
+            loop {
+                match lexer.next_token() {
+                    Ok(token) => assert!(!matches!(token.token_type, TokenType::Eof), "Expected an error for {}", input),
+                    Err(error) => break error,
+                }
+            }
+        };
+        let at = |column| (1, column);
+        
+        match first_error("x = '';") {
+            LexerError::EmptyCharLiteral(position) => assert_eq!((position.line, position.column), at(5)),
+            other => panic!("Expected empty char literal, got {:?}", other),
+        }
+        match first_error(r"'\u{110000}' '\u{D800}'") {
+            LexerError::UnicodeEscapeOutOfRange(value, position) => {
+                assert_eq!(value, 0x110000);
+                assert_eq!((position.line, position.column), at(2));
+            }
+            other => panic!("Expected out-of-range unicode escape, got {:?}", other),
+        }
+        assert!(matches!(first_error(r"'\u{D800}'"), LexerError::UnicodeEscapeOutOfRange(0xD800, _)));
+        assert!(matches!(first_error(r#""\u{1F6""#), LexerError::InvalidUnicodeEscape(..)));
+        match first_error("let n = 1_000_u32;") {
+            LexerError::UnderscoreBeforeSuffix(position) => assert_eq!((position.line, position.column), at(14)),
+            other => panic!("Expected underscore before suffix, got {:?}", other),
+        }
+        assert!(matches!(first_error("0xFF_u8"), LexerError::UnderscoreBeforeSuffix(_)));
+        match first_error(r"'\x80'") {
+            LexerError::InvalidEscapeSequence(escape, position) => {
+                assert_eq!(escape, r"\x80");
+                assert_eq!((position.line, position.column), at(2));
+            }
+            other => panic!("Expected invalid escape sequence, got {:?}", other),
+        }
+        assert!(matches!(first_error(r"'\x4'"), LexerError::InvalidEscapeSequence(..)));
+    }
+    
     #[test]
     fn test_line_comments() {
         let mut lexer = Lexer::new_with_comments("// This is a comment\nlet x = 5; // Another comment", 0);
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::LineComment(" This is a comment".to_string()),
+            TokenType::LineComment(" This is a comment".into()),
             TokenType::Let,
-            TokenType::Identifier("x".to_string()),
+            identifier("x"),
             TokenType::Equal,
-            TokenType::Integer { value: "5".to_string(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Integer { value: "5".into(), base: NumberBase::Decimal, suffix: None },
             TokenType::Semicolon,
-            TokenType::LineComment(" Another comment".to_string()),
+            TokenType::LineComment(" Another comment".into()),
             TokenType::Eof,
         ]);
     }
@@ -353,13 +467,13 @@ mod tests {
         let mut lexer = Lexer::new_with_comments("/* Block comment */\nlet x = 5; /* Another\nblock comment */", 0);
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::BlockComment(" Block comment ".to_string()),
+            TokenType::BlockComment(" Block comment ".into()),
             TokenType::Let,
-            TokenType::Identifier("x".to_string()),
+            identifier("x"),
             TokenType::Equal,
-            TokenType::Integer { value: "5".to_string(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Integer { value: "5".into(), base: NumberBase::Decimal, suffix: None },
             TokenType::Semicolon,
-            TokenType::BlockComment(" Another\nblock comment ".to_string()),
+            TokenType::BlockComment(" Another\nblock comment ".into()),
             TokenType::Eof,
         ]);
     }
@@ -369,7 +483,7 @@ mod tests {
         let mut lexer = Lexer::new_with_comments("/* Outer /* Nested */ Comment */", 0);
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::BlockComment(" Outer /* Nested */ Comment ".to_string()),
+            TokenType::BlockComment(" Outer /* Nested */ Comment ".into()),
             TokenType::Eof,
         ]);
     }
@@ -379,12 +493,41 @@ mod tests {
         let mut lexer = Lexer::new_with_comments("/// Doc comment\n/** Doc block comment */", 0);
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::DocLineComment(" Doc comment".to_string()),
-            TokenType::DocBlockComment(" Doc block comment ".to_string()),
+            TokenType::DocLineComment(" Doc comment".into()),
+            TokenType::DocBlockComment(" Doc block comment ".into()),
             TokenType::Eof,
         ]);
     }
     
+    #[test]
+    fn test_inner_and_plain_doc_like_comments() {
+        let mut lexer = Lexer::new_with_comments("//! Inner\n/*! Inner block */\n//// Rule\n/**/ /*** Stars */", 0);
+        let tokens = collect_tokens(&mut lexer);
+        assert_eq!(tokens, vec![
+            TokenType::InnerDocLineComment(" Inner".into()),
+            TokenType::InnerDocBlockComment(" Inner block ".into()),
+            TokenType::LineComment("// Rule".into()),
+            TokenType::BlockComment("".into()),
+            TokenType::BlockComment("** Stars ".into()),
+            TokenType::Eof,
+        ]);
+    }
+    
+    #[test]
+    fn test_unterminated_block_comment_points_at_opening() {
+        let mut lexer = create_lexer("let x = 1;\n  /* outer /* inner */ never closed");
+        let error = loop {
+            match lexer.next_token() {
+                Ok(token) => assert!(!matches!(token.token_type, TokenType::Eof), "Expected an error"),
+                Err(error) => break error,
+            }
+        };
+        match error {
+            LexerError::UnterminatedBlockComment(position) => assert_eq!((position.line, position.column), (2, 3)),
+            other => panic!("Expected unterminated block comment, got {:?}", other),
+        }
+    }
+    
     #[test]
     fn test_mixed_tokens() {
         let input = r#"
@@ -464,6 +607,61 @@ mod tests {
         collect_tokens(&mut lexer);
     }
     
+    #[test]
+    fn test_recovery_skips_invalid_characters() {
+        let mut lexer = create_lexer("let § x = 1 $ 2;").with_error_recovery();
+        let tokens = collect_tokens(&mut lexer);
+        assert_eq!(tokens, vec![
+            TokenType::Let,
+            identifier("x"),
+            TokenType::Equal,
+            TokenType::Integer { value: "1".into(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Integer { value: "2".into(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Semicolon,
+            TokenType::Eof,
+        ]);
+        let errors: Vec<(char, usize)> = lexer.errors().iter().map(|err| match err {
+            LexerError::InvalidCharacter(ch, pos) => (*ch, pos.column),
+            other => panic!("Expected an invalid character, got {:?}", other),
+        }).collect();
+        assert_eq!(errors, vec![('§', 5), ('$', 13)]);
+    }
+    
+    #[test]
+    fn test_recovery_resumes_after_unterminated_string() {
+        // Strings may span lines, so only one running to the end of the input is unterminated
+        let mut lexer = create_lexer("let t = 'ab';\nlet u = \"a\\zb\";\nlet s = \"oops;\nlet v = 1;").with_error_recovery();
+        let tokens: Vec<TokenType> = lexer.by_ref().map(|token| token.token_type).collect();
+        assert_eq!(tokens, vec![
+            TokenType::Let,
+            identifier("t"),
+            TokenType::Equal,
+            TokenType::Semicolon,
+            TokenType::Let,
+            identifier("u"),
+            TokenType::Equal,
+            TokenType::String { value: "ab".into(), raw: false, raw_delimiter: None },
+            TokenType::Semicolon,
+            TokenType::Let,
+            identifier("s"),
+            TokenType::Equal,
+            TokenType::Let,
+            identifier("v"),
+            TokenType::Equal,
+            TokenType::Integer { value: "1".into(), base: NumberBase::Decimal, suffix: None },
+            TokenType::Semicolon,
+        ]);
+        let errors: Vec<(&str, usize)> = lexer.errors().iter().map(|err| (err.code().name(), err.position().line)).collect();
+        assert_eq!(errors, vec![("invalid-char-literal", 1), ("invalid-escape", 2), ("unterminated-string", 3)]);
+    }
+    
+    #[test]
+    fn test_errors_without_recovery_are_returned() {
+        let mut lexer = create_lexer("§ x");
+        assert!(matches!(lexer.next_token(), Err(LexerError::InvalidCharacter('§', _))));
+        assert!(lexer.errors().is_empty());
+    }
+    
     #[test]
     fn test_position_tracking() {
         let input = "let x = 5;\nlet y = 10;";
@@ -490,15 +688,15 @@ mod tests {
         let mut lexer = create_lexer("x+y-z*a/b");
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::Identifier("x".to_string()),
+            identifier("x"),
             TokenType::Plus,
-            TokenType::Identifier("y".to_string()),
+            identifier("y"),
             TokenType::Minus,
-            TokenType::Identifier("z".to_string()),
+            identifier("z"),
             TokenType::Star,
-            TokenType::Identifier("a".to_string()),
+            identifier("a"),
             TokenType::Slash,
-            TokenType::Identifier("b".to_string()),
+            identifier("b"),
             TokenType::Eof,
         ]);
     }
@@ -508,11 +706,11 @@ mod tests {
         let mut lexer = create_lexer("x.y..z");
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::Identifier("x".to_string()),
+            identifier("x"),
             TokenType::Dot,
-            TokenType::Identifier("y".to_string()),
+            identifier("y"),
             TokenType::DotDot,
-            TokenType::Identifier("z".to_string()),
+            identifier("z"),
             TokenType::Eof,
         ]);
     }
@@ -522,9 +720,9 @@ mod tests {
         let mut lexer = create_lexer("1.0..5.0");
         let tokens = collect_tokens(&mut lexer);
         assert_eq!(tokens, vec![
-            TokenType::Float { value: "1.0".to_string(), suffix: None },
+            TokenType::Float { value: "1.0".into(), suffix: None },
             TokenType::DotDot,
-            TokenType::Float { value: "5.0".to_string(), suffix: None },
+            TokenType::Float { value: "5.0".into(), suffix: None },
             TokenType::Eof,
         ]);
     }
@@ -34,17 +34,16 @@ impl Position {
         Self::new(1, 1, 0, file_id)
     }
     
-    /// Advance to the next column
-    pub fn next_column(&mut self) {
-        self.column += 1;
-        self.offset += 1;
-    }
-    
-    /// Advance to the next line
-    pub fn next_line(&mut self) {
-        self.line += 1;
-        self.column = 1;
-        self.offset += 1;
+    /// Advance past `ch`: to the next line for a newline, otherwise to the next
+    /// column, and by the character's length in UTF-8 bytes
+    pub fn advance_char(&mut self, ch: char) {
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        self.offset += ch.len_utf8();
     }
     
     /// Advance by a specific number of bytes
@@ -54,6 +53,12 @@ impl Position {
     }
 }
 
+impl Default for Position {
+    fn default() -> Self {
+        Self::start(0)
+    }
+}
+
 impl fmt::Display for Position {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}:{}", self.line, self.column)
@@ -1,25 +1,32 @@
+use std::borrow::Cow;
 use std::fmt;
+use serde::Serialize;
 use crate::lexer::position::Position;
 
 /// Token type for the Bract language
-#[derive(Debug, Clone, PartialEq)]
-pub enum TokenType {
+///
+/// Names, literals and comments borrow their text from the source wherever it
+/// appears there verbatim, so lexing them allocates nothing; `into_owned` detaches
+/// a token from the source for keeping.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "variant", content = "value")]
+pub enum TokenType<'a> {
     // End of file
     Eof,
 
     // Literals
-    Identifier(String),
+    Identifier(Cow<'a, str>),
     Integer {
-        value: String,
+        value: Cow<'a, str>,
         base: NumberBase,
-        suffix: Option<String>,
+        suffix: Option<Cow<'a, str>>,
     },
     Float {
-        value: String,
-        suffix: Option<String>,
+        value: Cow<'a, str>,
+        suffix: Option<Cow<'a, str>>,
     },
     String {
-        value: String,
+        value: Cow<'a, str>,
         raw: bool,
         raw_delimiter: Option<usize>,
     },
@@ -29,6 +36,7 @@ pub enum TokenType {
 
     // Keywords
     Abort,
+    As,
     Break,
     Box,
     Const,
@@ -55,6 +63,7 @@ pub enum TokenType {
     True,
     Type,
     Use,
+    Where,
     While,
     Async,
     Await,
@@ -119,14 +128,16 @@ pub enum TokenType {
     At,             // @ (for memory annotations)
     
     // Comments (usually filtered out, but kept for documentation tools)
-    LineComment(String),
-    BlockComment(String),
-    DocLineComment(String),
-    DocBlockComment(String),
+    LineComment(Cow<'a, str>),
+    BlockComment(Cow<'a, str>),
+    DocLineComment(Cow<'a, str>),
+    DocBlockComment(Cow<'a, str>),
+    InnerDocLineComment(Cow<'a, str>),
+    InnerDocBlockComment(Cow<'a, str>),
 }
 
 /// Represents the base of a numeric literal
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
 pub enum NumberBase {
     Decimal,
     Hexadecimal,
@@ -136,16 +147,16 @@ pub enum NumberBase {
 
 /// A token in the Bract language
 #[derive(Debug, Clone, PartialEq)]
-pub struct Token {
+pub struct Token<'a> {
     /// The type of token
-    pub token_type: TokenType,
+    pub token_type: TokenType<'a>,
     /// The position in the source code
     pub position: Position,
 }
 
-impl Token {
+impl<'a> Token<'a> {
     /// Create a new token
-    pub fn new(token_type: TokenType, position: Position) -> Self {
+    pub fn new(token_type: TokenType<'a>, position: Position) -> Self {
         Self { token_type, position }
     }
     
@@ -154,6 +165,7 @@ impl Token {
         matches!(
             self.token_type,
             TokenType::Abort
+                | TokenType::As
                 | TokenType::Break
                 | TokenType::Box
                 | TokenType::Const
@@ -180,6 +192,7 @@ impl Token {
                 | TokenType::True
                 | TokenType::Type
                 | TokenType::Use
+                | TokenType::Where
                 | TokenType::While
                 | TokenType::Async
                 | TokenType::Await
@@ -233,7 +246,121 @@ impl Token {
     }
 }
 
-impl fmt::Display for TokenType {
+impl TokenType<'_> {
+    /// The same token with its text copied out of the source
+    pub fn into_owned(self) -> TokenType<'static> {
+        match self {
+            TokenType::Identifier(name) => TokenType::Identifier(Cow::Owned(name.into_owned())),
+            TokenType::Integer { value, base, suffix } => TokenType::Integer {
+                value: Cow::Owned(value.into_owned()),
+                base,
+                suffix: suffix.map(|suffix| Cow::Owned(suffix.into_owned())),
+            },
+            TokenType::Float { value, suffix } => TokenType::Float {
+                value: Cow::Owned(value.into_owned()),
+                suffix: suffix.map(|suffix| Cow::Owned(suffix.into_owned())),
+            },
+            TokenType::String { value, raw, raw_delimiter } => TokenType::String {
+                value: Cow::Owned(value.into_owned()),
+                raw,
+                raw_delimiter,
+            },
+            TokenType::Char(c) => TokenType::Char(c),
+            TokenType::Bool(b) => TokenType::Bool(b),
+            TokenType::LineComment(text) => TokenType::LineComment(Cow::Owned(text.into_owned())),
+            TokenType::BlockComment(text) => TokenType::BlockComment(Cow::Owned(text.into_owned())),
+            TokenType::DocLineComment(text) => TokenType::DocLineComment(Cow::Owned(text.into_owned())),
+            TokenType::DocBlockComment(text) => TokenType::DocBlockComment(Cow::Owned(text.into_owned())),
+            TokenType::InnerDocLineComment(text) => TokenType::InnerDocLineComment(Cow::Owned(text.into_owned())),
+            TokenType::InnerDocBlockComment(text) => TokenType::InnerDocBlockComment(Cow::Owned(text.into_owned())),
+            TokenType::Eof => TokenType::Eof,
+            TokenType::Null => TokenType::Null,
+            TokenType::Abort => TokenType::Abort,
+            TokenType::As => TokenType::As,
+            TokenType::Break => TokenType::Break,
+            TokenType::Box => TokenType::Box,
+            TokenType::Const => TokenType::Const,
+            TokenType::Continue => TokenType::Continue,
+            TokenType::Do => TokenType::Do,
+            TokenType::Else => TokenType::Else,
+            TokenType::Enum => TokenType::Enum,
+            TokenType::Extern => TokenType::Extern,
+            TokenType::False => TokenType::False,
+            TokenType::Fn => TokenType::Fn,
+            TokenType::For => TokenType::For,
+            TokenType::If => TokenType::If,
+            TokenType::Impl => TokenType::Impl,
+            TokenType::In => TokenType::In,
+            TokenType::Let => TokenType::Let,
+            TokenType::Loop => TokenType::Loop,
+            TokenType::Match => TokenType::Match,
+            TokenType::Mod => TokenType::Mod,
+            TokenType::Move => TokenType::Move,
+            TokenType::Mut => TokenType::Mut,
+            TokenType::Pub => TokenType::Pub,
+            TokenType::Return => TokenType::Return,
+            TokenType::Struct => TokenType::Struct,
+            TokenType::True => TokenType::True,
+            TokenType::Type => TokenType::Type,
+            TokenType::Use => TokenType::Use,
+            TokenType::Where => TokenType::Where,
+            TokenType::While => TokenType::While,
+            TokenType::Async => TokenType::Async,
+            TokenType::Await => TokenType::Await,
+            TokenType::Trait => TokenType::Trait,
+            TokenType::Try => TokenType::Try,
+            TokenType::Plus => TokenType::Plus,
+            TokenType::Minus => TokenType::Minus,
+            TokenType::Star => TokenType::Star,
+            TokenType::Slash => TokenType::Slash,
+            TokenType::Percent => TokenType::Percent,
+            TokenType::Caret => TokenType::Caret,
+            TokenType::And => TokenType::And,
+            TokenType::Or => TokenType::Or,
+            TokenType::Tilde => TokenType::Tilde,
+            TokenType::Not => TokenType::Not,
+            TokenType::Less => TokenType::Less,
+            TokenType::Greater => TokenType::Greater,
+            TokenType::Equal => TokenType::Equal,
+            TokenType::Question => TokenType::Question,
+            TokenType::Colon => TokenType::Colon,
+            TokenType::DoubleColon => TokenType::DoubleColon,
+            TokenType::Arrow => TokenType::Arrow,
+            TokenType::FatArrow => TokenType::FatArrow,
+            TokenType::LogicalAnd => TokenType::LogicalAnd,
+            TokenType::LogicalOr => TokenType::LogicalOr,
+            TokenType::Eq => TokenType::Eq,
+            TokenType::NotEq => TokenType::NotEq,
+            TokenType::LessEq => TokenType::LessEq,
+            TokenType::GreaterEq => TokenType::GreaterEq,
+            TokenType::LeftShift => TokenType::LeftShift,
+            TokenType::RightShift => TokenType::RightShift,
+            TokenType::PlusEq => TokenType::PlusEq,
+            TokenType::MinusEq => TokenType::MinusEq,
+            TokenType::StarEq => TokenType::StarEq,
+            TokenType::SlashEq => TokenType::SlashEq,
+            TokenType::PercentEq => TokenType::PercentEq,
+            TokenType::AndEq => TokenType::AndEq,
+            TokenType::OrEq => TokenType::OrEq,
+            TokenType::CaretEq => TokenType::CaretEq,
+            TokenType::LeftShiftEq => TokenType::LeftShiftEq,
+            TokenType::RightShiftEq => TokenType::RightShiftEq,
+            TokenType::LeftParen => TokenType::LeftParen,
+            TokenType::RightParen => TokenType::RightParen,
+            TokenType::LeftBracket => TokenType::LeftBracket,
+            TokenType::RightBracket => TokenType::RightBracket,
+            TokenType::LeftBrace => TokenType::LeftBrace,
+            TokenType::RightBrace => TokenType::RightBrace,
+            TokenType::Comma => TokenType::Comma,
+            TokenType::Dot => TokenType::Dot,
+            TokenType::Semicolon => TokenType::Semicolon,
+            TokenType::DotDot => TokenType::DotDot,
+            TokenType::At => TokenType::At,
+        }
+    }
+}
+
+impl fmt::Display for TokenType<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             TokenType::Eof => write!(f, "EOF"),
@@ -273,6 +400,7 @@ impl fmt::Display for TokenType {
             
             // Keywords
             TokenType::Abort => write!(f, "abort"),
+            TokenType::As => write!(f, "as"),
             TokenType::Break => write!(f, "break"),
             TokenType::Box => write!(f, "box"),
             TokenType::Const => write!(f, "const"),
@@ -299,6 +427,7 @@ impl fmt::Display for TokenType {
             TokenType::True => write!(f, "true"),
             TokenType::Type => write!(f, "type"),
             TokenType::Use => write!(f, "use"),
+            TokenType::Where => write!(f, "where"),
             TokenType::While => write!(f, "while"),
             TokenType::Async => write!(f, "async"),
             TokenType::Await => write!(f, "await"),
@@ -365,6 +494,8 @@ impl fmt::Display for TokenType {
             TokenType::BlockComment(s) => write!(f, "BlockComment({})", s),
             TokenType::DocLineComment(s) => write!(f, "DocLineComment({})", s),
             TokenType::DocBlockComment(s) => write!(f, "DocBlockComment({})", s),
+            TokenType::InnerDocLineComment(s) => write!(f, "InnerDocLineComment({})", s),
+            TokenType::InnerDocBlockComment(s) => write!(f, "InnerDocBlockComment({})", s),
         }
     }
 } 
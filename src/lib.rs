@@ -6,12 +6,18 @@ pub mod codegen;
 pub mod profiling;
 pub mod visitor;
 pub mod lsp;
+pub mod compile;
+pub mod diagnostics;
+pub mod metrics;
 
 /// Performance analysis module - implements contract verification and cost estimation
-// TODO: Re-enable after fixing core compilation
-// pub mod performance;
+pub mod performance;
 
 pub use lexer::{Lexer, Token, TokenType, Position, LexerError};
 pub use ast::{Module, Expr, Stmt, Item, Pattern, Type, Span};
 pub use parser::{Parser, ParseError, ParseResult};
-pub use codegen::{CodegenResult, CodegenError}; 
\ No newline at end of file
+pub use codegen::{CodegenResult, CodegenError};
+pub use diagnostics::DiagnosticCode;
+pub use performance::{PerformanceAnalyzer, PerformanceAnalysis};
+pub use compile::{compile_source, dump_ast, CompileOptions, CompileResult};
+pub use metrics::CompileMetrics; 
\ No newline at end of file
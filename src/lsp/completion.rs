@@ -2,10 +2,18 @@
 //!
 //! This module provides intelligent code completion based on semantic analysis,
 //! context-aware suggestions, and performance optimization.
-
-use crate::semantic::{SymbolTable, SymbolKind};
-use super::{Position, Range, LspServer, Document};
+//!
+//! Symbol completions are listed with only their name and kind, plus the `data`
+//! `completionItem/resolve` needs to find the symbol again; resolving fills in its
+//! documentation and type, and imports symbols of other open documents.
+
+use crate::ast::{Item, Module, PrettyPrinter, Visibility};
+use crate::parser::module_loader::SOURCE_EXTENSION;
+use crate::parser::StringInterner;
+use crate::semantic::symbols::{Symbol, SymbolId, SymbolKind, TypeDefinition};
+use super::{find_item, Position, Range, LspServer, Document};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 /// Completion item kinds
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -67,6 +75,26 @@ pub struct CompletionItem {
     pub sort_text: Option<String>,
     /// Preselect this item
     pub preselect: Option<bool>,
+    /// Data kept for `completionItem/resolve`
+    pub data: Option<Value>,
+}
+
+/// Data of a symbol completion, naming the symbol to resolve
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompletionData {
+    /// Document the completions were listed for
+    pub uri: String,
+    /// Its version when they were listed
+    pub version: i32,
+    /// Document declaring the symbol
+    #[serde(rename = "sourceUri")]
+    pub source_uri: String,
+    /// Its version when the completions were listed
+    #[serde(rename = "sourceVersion")]
+    pub source_version: i32,
+    /// Symbol in the symbol table of the declaring document
+    #[serde(rename = "symbolId")]
+    pub symbol_id: SymbolId,
 }
 
 /// Text edit for completion
@@ -129,7 +157,7 @@ impl CompletionProvider {
         }
     }
 
-    /// Provide completions for a position
+    /// Provide completions for a position; none when the server has completion turned off
     pub fn provide_completions(
         &self,
         server: &LspServer,
@@ -137,6 +165,9 @@ impl CompletionProvider {
         _position: &Position,
         context: &CompletionContext,
     ) -> Result<Vec<CompletionItem>, String> {
+        if !server.config().enable_completion {
+            return Ok(Vec::new());
+        }
         let mut completions = Vec::new();
 
         // Get document and symbols
@@ -154,8 +185,13 @@ impl CompletionProvider {
         }
 
         // Add symbol completions from current scope
-        if let Some(symbols) = &document.symbols {
-            completions.extend(self.get_symbol_completions(symbols, context)?);
+        completions.extend(self.get_symbol_completions(&document, &document, context)?);
+
+        // Add the public items of the other open documents, imported once resolved
+        if !context.word_at_cursor.is_empty() {
+            for source in server.open_documents()?.iter().filter(|source| source.uri != document.uri) {
+                completions.extend(self.get_symbol_completions(&document, source, context)?);
+            }
         }
 
         // Add snippet completions
@@ -222,6 +258,7 @@ impl CompletionProvider {
                 filter_text: Some(label.to_string()),
                 sort_text: Some(format!("1_{}", label)),
                 preselect: None,
+                data: None,
             }
         }).collect()
     }
@@ -262,6 +299,7 @@ impl CompletionProvider {
                 filter_text: Some(label.to_string()),
                 sort_text: Some(format!("2_{}", label)),
                 preselect: None,
+                data: None,
             }
         }).collect()
     }
@@ -297,68 +335,71 @@ impl CompletionProvider {
                 filter_text: Some(label.to_string()),
                 sort_text: Some(format!("3_{}", label)),
                 preselect: None,
+                data: None,
             }
         }).collect()
     }
 
-    /// Get symbol completions from symbol table
+    /// Get completions for the symbols `source` declares, offered in `document`
+    ///
+    /// Another document offers only its public symbols matching the word at the cursor.
     fn get_symbol_completions(
         &self,
-        symbols: &SymbolTable,
-        _context: &CompletionContext,
+        document: &Document,
+        source: &Document,
+        context: &CompletionContext,
     ) -> Result<Vec<CompletionItem>, String> {
         let mut completions = Vec::new();
+        let Some(symbols) = &source.symbols else {
+            return Ok(completions);
+        };
+        let local = source.uri == document.uri;
 
         // Get current scope symbols
         let current_symbols = symbols.current_scope_symbols();
 
         for symbol in current_symbols {
-            let (kind, detail) = match &symbol.kind {
-                SymbolKind::Variable { is_mutable, type_info } => {
-                    let mutability = if *is_mutable { "mut " } else { "" };
-                    let type_str = type_info.as_ref()
-                        .map(|t| format!("{:?}", t))
-                        .unwrap_or_else(|| "unknown".to_string());
-                    (CompletionItemKind::Variable, format!("{}variable: {}", mutability, type_str))
-                },
-                SymbolKind::Function { return_type, is_extern, .. } => {
-                    let extern_str = if *is_extern { "extern " } else { "" };
-                    let return_str = return_type.as_ref()
-                        .map(|t| format!(" -> {:?}", t))
-                        .unwrap_or_default();
-                    (CompletionItemKind::Function, format!("{}function{}", extern_str, return_str))
-                },
-                SymbolKind::Type { definition } => {
-                    (CompletionItemKind::Class, format!("type: {:?}", definition))
-                },
-                SymbolKind::Module { is_external } => {
-                    let external_str = if *is_external { "external " } else { "" };
-                    (CompletionItemKind::Module, format!("{}module", external_str))
-                },
-                SymbolKind::Constant { type_info, .. } => {
-                    (CompletionItemKind::Constant, format!("const: {:?}", type_info))
-                },
-                SymbolKind::GenericParam { bounds } => {
-                    (CompletionItemKind::TypeParameter, format!("generic parameter: {:?}", bounds))
-                },
+            if !local && symbol.visibility != Visibility::Public {
+                continue;
+            }
+            let Some(name) = source.interner.resolve(symbol.name) else {
+                continue;
+            };
+            let kind = match &symbol.kind {
+                SymbolKind::Variable { .. } => CompletionItemKind::Variable,
+                SymbolKind::Function { .. } => CompletionItemKind::Function,
+                SymbolKind::Type { .. } => CompletionItemKind::Class,
+                SymbolKind::Module { .. } => CompletionItemKind::Module,
+                SymbolKind::Constant { .. } => CompletionItemKind::Constant,
+                SymbolKind::GenericParam { .. } => CompletionItemKind::TypeParameter,
+            };
+            let data = CompletionData {
+                uri: document.uri.clone(),
+                version: document.version,
+                source_uri: source.uri.clone(),
+                source_version: source.version,
+                symbol_id: symbol.id,
             };
 
-            let symbol_name = format!("symbol_{}", symbol.name.id);
             completions.push(CompletionItem {
-                label: symbol_name.clone(),
+                label: name.clone(),
                 kind: Some(kind),
                 additional_text_edits: None,
-                detail: Some(detail),
+                detail: Some(symbol.kind.description().to_string()),
                 documentation: None,
                 deprecated: None,
-                insert_text: Some(symbol_name.clone()),
+                insert_text: Some(name.clone()),
                 insert_text_format: Some(InsertTextFormat::PlainText),
-                filter_text: Some(symbol_name.clone()),
-                sort_text: Some(format!("4_{}", symbol_name)),
+                filter_text: Some(name.clone()),
+                sort_text: Some(format!("{}_{}", if local { 4 } else { 6 }, name)),
                 preselect: None,
+                data: serde_json::to_value(data).ok(),
             });
         }
 
+        if !local {
+            completions = self.filter_completions(&completions, &context.word_at_cursor);
+        }
         Ok(completions)
     }
 
@@ -393,6 +434,7 @@ impl CompletionProvider {
                 filter_text: Some(label.to_string()),
                 sort_text: Some(format!("5_{}", label)),
                 preselect: None,
+                data: None,
             });
         }
 
@@ -461,6 +503,166 @@ impl CompletionProvider {
     }
 }
 
+/// Fill in the documentation, type and import of a completion item
+///
+/// Items without resolve data come back unchanged, and so do items listed before
+/// their document or the one declaring their symbol changed, since the symbol
+/// may no longer be there.
+pub fn resolve_completion(server: &LspServer, mut item: CompletionItem) -> Result<CompletionItem, String> {
+    let Some(data) = item.data.clone().and_then(|data| serde_json::from_value::<CompletionData>(data).ok()) else {
+        return Ok(item);
+    };
+    let unchanged = |uri: &str, version: i32| -> Result<Option<Document>, String> {
+        Ok(server.get_document(uri)?.filter(|document| document.version == version))
+    };
+    let (Some(document), Some(source)) = (unchanged(&data.uri, data.version)?, unchanged(&data.source_uri, data.source_version)?) else {
+        return Ok(item);
+    };
+    let symbol = source.symbols.as_ref()
+        .and_then(|symbols| symbols.get_symbol(data.symbol_id))
+        .filter(|symbol| source.interner.matches(symbol.name, &item.label));
+    let Some(symbol) = symbol else {
+        return Ok(item);
+    };
+
+    let source_ast = source.ast.clone()
+        .or_else(|| server.parse_document(&source.content, &source.interner).module);
+    let declaration = match symbol.kind {
+        SymbolKind::Variable { .. } | SymbolKind::GenericParam { .. } => None,
+        _ => source_ast.as_ref()
+            .and_then(|ast| find_item(&ast.items, &item.label, &source.interner))
+            .map(|(_, declaration)| declaration),
+    };
+    item.detail = Some(symbol_type(symbol, &source.interner));
+    item.documentation = declaration
+        .and_then(|declaration| declaration.doc(&source.interner))
+        .or_else(|| Some(signature(symbol, declaration, &source.interner)));
+
+    if source.uri != document.uri {
+        let ast = document.ast.clone()
+            .or_else(|| server.parse_document(&document.content, &document.interner).module);
+        if let (Some(ast), Some(mut path)) = (ast, module_path(&document.uri, &source.uri)) {
+            path.push(item.label.clone());
+            item.additional_text_edits = import_edit(&ast, &document.content, &document.interner, &path)
+                .map(|edit| vec![edit]);
+        }
+    }
+    Ok(item)
+}
+
+/// Full type of a symbol, such as `fn(i32) -> i32` for a function
+fn symbol_type(symbol: &Symbol, interner: &StringInterner) -> String {
+    let mut printer = PrettyPrinter::new(interner);
+    let name = interner.resolve(symbol.name).unwrap_or_default();
+    match &symbol.kind {
+        SymbolKind::Variable { type_info, .. } => type_info.as_ref()
+            .map(|ty| printer.print_type(ty))
+            .unwrap_or_else(|| "unknown".to_string()),
+        SymbolKind::Function { params, return_type, .. } => {
+            let params: Vec<String> = params.iter()
+                .map(|param| param.type_annotation.as_ref().map_or_else(|| "_".to_string(), |ty| printer.print_type(ty)))
+                .collect();
+            let return_type = return_type.as_ref()
+                .map(|ty| format!(" -> {}", printer.print_type(ty)))
+                .unwrap_or_default();
+            format!("fn({}){}", params.join(", "), return_type)
+        }
+        SymbolKind::Type { definition: TypeDefinition::Struct { .. } } => format!("struct {}", name),
+        SymbolKind::Type { definition: TypeDefinition::Enum { .. } } => format!("enum {}", name),
+        SymbolKind::Type { definition: TypeDefinition::Alias { target, .. } } => printer.print_type(target),
+        SymbolKind::Module { .. } => format!("mod {}", name),
+        SymbolKind::Constant { type_info, .. } => printer.print_type(type_info),
+        SymbolKind::GenericParam { bounds } if bounds.is_empty() => "type parameter".to_string(),
+        SymbolKind::GenericParam { bounds } => {
+            bounds.iter().map(|bound| printer.print_type(bound)).collect::<Vec<_>>().join(" + ")
+        }
+    }
+}
+
+/// Source declaring a symbol, without attributes or a function's body
+fn signature(symbol: &Symbol, declaration: Option<&Item>, interner: &StringInterner) -> String {
+    let Some(declaration) = declaration else {
+        let name = interner.resolve(symbol.name).unwrap_or_default();
+        return match &symbol.kind {
+            SymbolKind::Variable { is_mutable, .. } => {
+                let mutability = if *is_mutable { "mut " } else { "" };
+                format!("let {}{}: {}", mutability, name, symbol_type(symbol, interner))
+            }
+            _ => format!("{}: {}", name, symbol_type(symbol, interner)),
+        };
+    };
+
+    let mut declaration = declaration.clone();
+    declaration.attributes_mut().clear();
+    match &mut declaration {
+        Item::Function { body, performance_contract, allocation_strategy, .. } => {
+            *body = None;
+            *performance_contract = None;
+            *allocation_strategy = None;
+        }
+        Item::Module { items, .. } => *items = None,
+        _ => {}
+    }
+    let source = PrettyPrinter::new(interner).print_item(&declaration);
+    source.strip_suffix(';').map(str::to_string).unwrap_or(source)
+}
+
+/// Module path of the file `to` as seen from the file `from`
+///
+/// Only files next to `from` or in directories below it have one, laid out the
+/// way `mod` declarations load them: `geo/shapes.bract` and `geo/shapes/mod.bract`
+/// both hold `geo::shapes`.
+fn module_path(from: &str, to: &str) -> Option<Vec<String>> {
+    let directory = &from[..from.rfind('/')? + 1];
+    let file = to.strip_prefix(directory)?
+        .strip_suffix(SOURCE_EXTENSION)?
+        .strip_suffix('.')?;
+    let mut path: Vec<String> = file.split('/').map(str::to_string).collect();
+    if path.len() > 1 && path.last().is_some_and(|name| name == "mod") {
+        path.pop();
+    }
+    Some(path)
+}
+
+/// Edit adding `use` of `path` to `module`, in order among its use declarations
+///
+/// Without use declarations it goes at the top of the file, after any `//!` lines.
+/// A name the module already imports needs no edit.
+fn import_edit(module: &Module, content: &str, interner: &StringInterner, path: &[String]) -> Option<TextEdit> {
+    let import = path.join("::");
+    let mut uses = Vec::new();
+    for item in &module.items {
+        if let Item::Use { path: use_path, alias, span, .. } = item {
+            let segments: Vec<String> = use_path.iter().filter_map(|segment| interner.resolve(*segment)).collect();
+            let imported = alias.and_then(|alias| interner.resolve(alias)).or_else(|| segments.last().cloned());
+            if imported.as_ref() == path.last() {
+                return None;
+            }
+            uses.push((segments.join("::"), span.start.line));
+        }
+    }
+
+    let mut new_text = format!("use {};\n", import);
+    let line = match uses.iter().find(|(existing, _)| *existing > import) {
+        Some((_, line)) => line - 1,
+        None => match uses.last() {
+            Some((_, line)) => *line,
+            None => {
+                let line = content.lines().take_while(|line| line.trim_start().starts_with("//!")).count();
+                if content.lines().nth(line).is_some_and(|next| !next.trim().is_empty()) {
+                    new_text.push('\n');
+                }
+                line
+            }
+        },
+    };
+    let position = Position { line: line as u32, character: 0 };
+    Some(TextEdit {
+        range: Range { start: position.clone(), end: position },
+        new_text,
+    })
+}
+
 /// Create completion context from position
 pub fn create_completion_context(
     uri: String,
@@ -500,10 +702,10 @@ pub fn create_completion_context(
 }
 
 /// Extract word at position
-fn extract_word_at_position(line: &str, position: usize) -> String {
+pub(crate) fn extract_word_at_position(line: &str, position: usize) -> String {
     let chars: Vec<char> = line.chars().collect();
-    let mut start = position;
-    let mut end = position;
+    let mut start = position.min(chars.len());
+    let mut end = start;
     
     // Find start of word
     while start > 0 && is_word_char(chars[start - 1]) {
@@ -518,6 +720,13 @@ fn extract_word_at_position(line: &str, position: usize) -> String {
     chars[start..end].iter().collect()
 }
 
+/// Word of `text` around byte `offset`, which is on a character boundary
+pub(crate) fn word_at_offset(text: &str, offset: usize) -> &str {
+    let start = text[..offset].trim_end_matches(is_word_char).len();
+    let end = text.len() - text[offset..].trim_start_matches(is_word_char).len();
+    &text[start..end]
+}
+
 /// Check if character is part of a word
 fn is_word_char(c: char) -> bool {
     c.is_alphanumeric() || c == '_'
@@ -541,6 +750,32 @@ mod tests {
         assert_eq!(extract_word_at_position("let var_name", 8), "var_name");
         assert_eq!(extract_word_at_position("struct Test", 7), "Test");
         assert_eq!(extract_word_at_position("", 0), "");
+        assert_eq!(word_at_offset("let é_x = 1", 6), "é_x");
+        assert_eq!(word_at_offset("😀name", 4), "name");
+    }
+
+    #[test]
+    fn test_import_edit_keeps_use_order() {
+        let source = "use alpha::a;\nuse gamma::c;\n\nfn main() -> i32 { return 0; }\n";
+        let mut parser = crate::parser::Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.interner();
+        let path = |path: &[&str]| path.iter().map(|segment| segment.to_string()).collect::<Vec<_>>();
+
+        let edit = import_edit(&module, source, &interner, &path(&["beta", "b"])).unwrap();
+        assert_eq!((edit.range.start.line, edit.new_text.as_str()), (1, "use beta::b;\n"));
+        let edit = import_edit(&module, source, &interner, &path(&["zeta", "z"])).unwrap();
+        assert_eq!(edit.range.start.line, 2);
+        // `c` is already imported
+        assert!(import_edit(&module, source, &interner, &path(&["other", "c"])).is_none());
+    }
+
+    #[test]
+    fn test_module_paths_follow_file_layout() {
+        let from = "file:///project/main.bract";
+        assert_eq!(module_path(from, "file:///project/math.bract"), Some(vec!["math".to_string()]));
+        assert_eq!(module_path(from, "file:///project/geo/mod.bract"), Some(vec!["geo".to_string()]));
+        assert_eq!(module_path(from, "file:///elsewhere/math.bract"), None);
     }
 
     #[test]
@@ -578,6 +813,7 @@ mod tests {
             filter_text: None,
             sort_text: None,
             preselect: None,
+            data: None,
         };
 
         let score = provider.calculate_relevance_score(&completion, &context);
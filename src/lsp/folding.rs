@@ -0,0 +1,176 @@
+//! Folding Range Support for Bract LSP
+//!
+//! Code folds come from the spans of multi-line items, blocks, `match`es and
+//! regions in the AST, and comment folds from runs of comments the lexer reports.
+//! A span ends where the token after it starts, so the line a construct closes on
+//! is taken from the last token inside its span rather than from the span itself.
+
+use super::{LineIndex, Position};
+use crate::ast::{Expr, Item, Module, Span, Stmt};
+use crate::lexer::{Lexer, TokenType};
+use crate::visitor::{self, Visitor};
+use serde::{Deserialize, Serialize};
+
+/// `kind` of folds over code
+pub const REGION_FOLDING_KIND: &str = "region";
+
+/// `kind` of folds over comments
+pub const COMMENT_FOLDING_KIND: &str = "comment";
+
+/// LSP folding range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FoldingRange {
+    /// First line of the fold (0-based), which stays visible
+    #[serde(rename = "startLine")]
+    pub start_line: u32,
+    /// Last line the fold hides (0-based)
+    #[serde(rename = "endLine")]
+    pub end_line: u32,
+    /// `REGION_FOLDING_KIND` or `COMMENT_FOLDING_KIND`
+    pub kind: Option<String>,
+}
+
+/// Folding ranges of `module`, parsed from `source` whose lines are `line_index`
+///
+/// Every multi-line item body, block, `match` and region gets a fold hiding the
+/// lines between its first line and the line it closes on, so the closing brace
+/// stays visible; nested constructs all get their own. Runs of comments on
+/// consecutive lines, and block comments, fold whole. Ranges are sorted by start.
+pub fn folding_ranges(module: &Module, source: &str, line_index: &LineIndex) -> Vec<FoldingRange> {
+    let mut collector = SpanCollector { spans: Vec::new() };
+    collector.visit_module(module);
+    let line = |offset: usize| line_index.position(offset).line;
+
+    // Byte offsets where the tokens that are not comments end, in source order
+    let mut token_ends = Vec::new();
+    let mut comments = Vec::new();
+    let mut lexer = Lexer::new_with_comments(source, 0).with_error_recovery();
+    while let Ok(token) = lexer.next_token() {
+        let end = lexer.get_position().offset;
+        match token.token_type {
+            TokenType::Eof => break,
+            TokenType::LineComment(_)
+            | TokenType::BlockComment(_)
+            | TokenType::DocLineComment(_)
+            | TokenType::DocBlockComment(_)
+            | TokenType::InnerDocLineComment(_)
+            | TokenType::InnerDocBlockComment(_) => comments.push((line(token.position.offset), line_index.position(end))),
+            _ => token_ends.push(end),
+        }
+    }
+
+    let mut ranges: Vec<FoldingRange> = collector.spans.iter()
+        .filter_map(|span| {
+            // Line of the last token inside the span, the one it closes on
+            let inside = token_ends.partition_point(|&end| end <= span.end.offset);
+            let closing_line = line(*token_ends[..inside].last()?);
+            fold(line(span.start.offset), closing_line.saturating_sub(1), REGION_FOLDING_KIND)
+        })
+        .collect();
+    ranges.extend(comment_runs(&comments).into_iter().filter_map(|(start, end)| fold(start, end, COMMENT_FOLDING_KIND)));
+
+    ranges.sort_by_key(|range| (range.start_line, std::cmp::Reverse(range.end_line)));
+    ranges.dedup();
+    ranges
+}
+
+/// Fold from line `start` through `end`, unless that hides nothing
+fn fold(start: u32, end: u32, kind: &str) -> Option<FoldingRange> {
+    (end > start).then(|| FoldingRange {
+        start_line: start,
+        end_line: end,
+        kind: Some(kind.to_string()),
+    })
+}
+
+/// First and last lines of each run of comments, given as their first lines and
+/// end positions, the next starting on the line after the previous ends
+fn comment_runs(comments: &[(u32, Position)]) -> Vec<(u32, u32)> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &(start, ref end) in comments {
+        // A line comment ends at the start of the next line
+        let last_line = if end.character == 0 && end.line > start { end.line - 1 } else { end.line };
+        match runs.last_mut() {
+            Some(run) if start == run.1 + 1 => run.1 = last_line,
+            _ => runs.push((start, last_line)),
+        }
+    }
+    runs
+}
+
+/// Spans of the constructs that fold
+struct SpanCollector {
+    spans: Vec<Span>,
+}
+
+impl<'ast> Visitor<'ast> for SpanCollector {
+    fn visit_item(&mut self, item: &'ast Item) {
+        // Function bodies fold as the blocks they are
+        if let Item::Struct { span, .. } | Item::Enum { span, .. } | Item::Impl { span, .. } | Item::Module { span, .. } = item {
+            self.spans.push(*span);
+        }
+        visitor::walk_item(self, item);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        match stmt {
+            Stmt::If { span, .. }
+            | Stmt::While { span, .. }
+            | Stmt::For { span, .. }
+            | Stmt::Loop { span, .. }
+            | Stmt::Match { span, .. }
+            | Stmt::Block { span, .. }
+            | Stmt::Region { span, .. } => self.spans.push(*span),
+            _ => {}
+        }
+        visitor::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let Expr::Block { span, .. } | Expr::Match { span, .. } = expr {
+            self.spans.push(*span);
+        }
+        visitor::walk_expr(self, expr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    /// Folds of `source` as (start line, end line, kind)
+    fn folds(source: &str) -> Vec<(u32, u32, String)> {
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        folding_ranges(&module, source, &LineIndex::new(source)).into_iter()
+            .map(|range| (range.start_line, range.end_line, range.kind.unwrap_or_default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_constructs_fold() {
+        let source = "struct Point {\n    x: i32,\n    y: i32,\n}\n\
+                      impl Point {\n    fn pick(&self, n: i32) -> i32 {\n        match n {\n            0 => self.x,\n            _ => self.y,\n        }\n    }\n}\n\
+                      struct Unit;\nfn one() -> i32 { return 1; }\n";
+        let region = |start, end| (start, end, REGION_FOLDING_KIND.to_string());
+        // Closing braces stay visible; single-line items have no fold
+        assert_eq!(folds(source), vec![region(0, 2), region(4, 10), region(5, 9), region(6, 8)]);
+    }
+
+    #[test]
+    fn test_comment_runs_fold() {
+        let source = "// one\n// two\n// three\nfn f() {}\n/* block\n   comment */\n// alone\nfn g() {}\n";
+        let comment = |start, end| (start, end, COMMENT_FOLDING_KIND.to_string());
+        assert_eq!(folds(source), vec![comment(0, 2), comment(4, 6)]);
+    }
+
+    #[test]
+    fn test_wide_characters_keep_fold_lines() {
+        let source = "// 😀 one\n// é two\nstruct Point {\n    x: i32, // ünïcode 😀\n    y: i32,\n}\n";
+        assert_eq!(folds(source), vec![
+            (0, 1, COMMENT_FOLDING_KIND.to_string()),
+            (2, 4, REGION_FOLDING_KIND.to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Document Formatting Support for Bract LSP
+//!
+//! Documents are formatted by printing their AST with the pretty-printer. Rather
+//! than replacing the whole document, the printed source is compared with the
+//! current one line by line and only the lines that differ are edited, so cursors
+//! and folds elsewhere in the document stay where they are.
+
+use super::{Position, Range, TextEdit};
+use crate::ast::{Module, PrettyPrinter};
+use crate::lexer::{Lexer, TokenType};
+use crate::parser::StringInterner;
+use serde::{Deserialize, Serialize};
+
+/// LSP formatting options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormattingOptions {
+    /// Width of an indentation level in spaces
+    #[serde(rename = "tabSize")]
+    pub tab_size: u32,
+    /// Indent with spaces rather than tabs
+    #[serde(rename = "insertSpaces")]
+    pub insert_spaces: bool,
+}
+
+impl Default for FormattingOptions {
+    fn default() -> Self {
+        Self {
+            tab_size: 4,
+            insert_spaces: true,
+        }
+    }
+}
+
+/// Most line pairs compared to find the lines a change keeps; beyond it the
+/// changed lines are replaced as one block
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// Canonical source of `module`, indented as `options` ask
+pub fn format_module(module: &Module, interner: &StringInterner, options: &FormattingOptions) -> String {
+    let printer = PrettyPrinter::new(interner);
+    let mut printer = if options.insert_spaces {
+        printer.with_indent_width(options.tab_size as usize)
+    } else {
+        printer.with_tabs()
+    };
+    printer.print_module(module)
+}
+
+/// Whether `source` has comments that are not doc comments, which the AST does not keep
+pub fn has_plain_comments(source: &str) -> bool {
+    Lexer::new_with_comments(source, 0)
+        .with_error_recovery()
+        .any(|token| matches!(token.token_type, TokenType::LineComment(_) | TokenType::BlockComment(_)))
+}
+
+/// Edits turning `old` into `new`, one for each run of lines that differ
+///
+/// Lines are compared with their line breaks, and the edits refer to positions in
+/// `old` as LSP requires. Equal texts need no edits.
+pub fn line_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+
+    // Lines shared at the start and the end take no comparing
+    let prefix = old_lines.iter().zip(&new_lines).take_while(|(a, b)| a == b).count();
+    let suffix = old_lines[prefix..].iter().rev()
+        .zip(new_lines[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old_lines[prefix..old_lines.len() - suffix];
+    let new_middle = &new_lines[prefix..new_lines.len() - suffix];
+
+    let mut edits = Vec::new();
+    let mut push = |old_start: usize, old_end: usize, new_start: usize, new_end: usize| {
+        if old_start == old_end && new_start == new_end {
+            return;
+        }
+        edits.push(TextEdit {
+            range: Range {
+                start: line_start(&old_lines, prefix + old_start),
+                end: line_start(&old_lines, prefix + old_end),
+            },
+            new_text: new_middle[new_start..new_end].concat(),
+        });
+    };
+
+    if old_middle.len().saturating_mul(new_middle.len()) > MAX_DIFF_CELLS {
+        push(0, old_middle.len(), 0, new_middle.len());
+        return edits;
+    }
+
+    // Longest common subsequence of the lines in between; whatever it leaves out is edited
+    let (n, m) = (old_middle.len(), new_middle.len());
+    let mut common = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            common[i][j] = if old_middle[i] == new_middle[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let (mut old_start, mut new_start) = (0, 0);
+    while i < n && j < m {
+        if old_middle[i] == new_middle[j] {
+            push(old_start, i, new_start, j);
+            i += 1;
+            j += 1;
+            old_start = i;
+            new_start = j;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    push(old_start, n, new_start, m);
+    edits
+}
+
+/// Position where line `index` of `lines` starts, or the end of the text past its last line
+fn line_start(lines: &[&str], index: usize) -> Position {
+    match lines.last() {
+        Some(last) if index == lines.len() && !last.ends_with('\n') => Position {
+            line: index as u32 - 1,
+            character: last.encode_utf16().count() as u32,
+        },
+        _ => Position { line: index as u32, character: 0 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `text` with `edits`, which refer to positions in it, applied
+    fn apply(text: &str, edits: &[TextEdit]) -> String {
+        let offset = |position: &Position| {
+            let line_start: usize = text.split_inclusive('\n').take(position.line as usize).map(str::len).sum();
+            let line = text[line_start..].split('\n').next().unwrap_or("");
+            let mut units = 0;
+            line_start + line.chars().take_while(|ch| {
+                units += ch.len_utf16() as u32;
+                units <= position.character
+            }).map(char::len_utf8).sum::<usize>()
+        };
+        let mut result = text.to_string();
+        for edit in edits.iter().rev() {
+            result.replace_range(offset(&edit.range.start)..offset(&edit.range.end), &edit.new_text);
+        }
+        result
+    }
+
+    #[test]
+    fn test_line_edits_touch_only_changed_lines() {
+        let old = "a\nb\nc\nd\ne\n";
+        let new = "a\nB\nc\nd\ne\nf\n";
+        let edits = line_edits(old, new);
+        assert_eq!(edits.len(), 2);
+        assert_eq!((edits[0].range.start.line, edits[0].range.end.line, edits[0].new_text.as_str()), (1, 2, "B\n"));
+        assert_eq!((edits[1].range.start.line, edits[1].range.end.line, edits[1].new_text.as_str()), (5, 5, "f\n"));
+        assert_eq!(apply(old, &edits), new);
+        assert!(line_edits(new, new).is_empty());
+    }
+
+    #[test]
+    fn test_line_edits_without_final_newline() {
+        for (old, new) in [("x\ny", "x\ny\n"), ("é 𝔹\nz", "é 𝔹\n"), ("", "fn f() {}\n"), ("a\nb\n", "")] {
+            assert_eq!(apply(old, &line_edits(old, new)), new, "{:?} -> {:?}", old, new);
+        }
+    }
+}
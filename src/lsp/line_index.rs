@@ -0,0 +1,170 @@
+//! Conversion between byte offsets and LSP positions
+//!
+//! The lexer locates everything by byte offset, while LSP positions count lines
+//! from 0 and characters in UTF-16 code units. A `LineIndex` is built once per
+//! version of a document and answers both conversions without scanning the text:
+//! it keeps where each line starts and, for the lines that have them, the
+//! characters whose UTF-8 and UTF-16 lengths differ. Every position the server
+//! sends or receives goes through here.
+
+use super::{Position, Range};
+use crate::ast::Span;
+
+/// A character taking a different number of UTF-8 bytes than UTF-16 units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct WideChar {
+    /// Byte offset within its line
+    start: usize,
+    /// Length in UTF-8 bytes
+    utf8_len: usize,
+    /// Length in UTF-16 code units
+    utf16_len: usize,
+}
+
+/// Line starts and wide characters of a text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// Byte offset each line starts at; the first is 0
+    line_starts: Vec<usize>,
+    /// Byte offset each line's text ends at, before its `\n` or `\r\n`
+    line_ends: Vec<usize>,
+    /// Wide characters of each line in order, empty for ASCII lines
+    wide_chars: Vec<Vec<WideChar>>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut index = Self { line_starts: Vec::new(), line_ends: Vec::new(), wide_chars: Vec::new() };
+        let mut line_start = 0;
+        for line in text.split('\n') {
+            let content = line.strip_suffix('\r').unwrap_or(line);
+            index.line_starts.push(line_start);
+            index.line_ends.push(line_start + content.len());
+            index.wide_chars.push(
+                content.char_indices()
+                    .filter(|(_, ch)| ch.len_utf8() != ch.len_utf16())
+                    .map(|(start, ch)| WideChar { start, utf8_len: ch.len_utf8(), utf16_len: ch.len_utf16() })
+                    .collect(),
+            );
+            line_start += line.len() + 1;
+        }
+        index
+    }
+
+    /// Number of lines; a text ending in a newline has an empty last line
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// LSP position of byte `offset`
+    ///
+    /// An offset past the end is the end of the text, one inside a line break is
+    /// the end of its line, and one inside a character is the start of it.
+    pub fn position(&self, offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= offset).saturating_sub(1);
+        let column = offset.min(self.line_ends[line]) - self.line_starts[line];
+        let mut character = column;
+        for wide in self.wide_chars[line].iter().take_while(|wide| wide.start < column) {
+            if column < wide.start + wide.utf8_len {
+                character -= column - wide.start;
+                break;
+            }
+            character -= wide.utf8_len - wide.utf16_len;
+        }
+        Position { line: line as u32, character: character as u32 }
+    }
+
+    /// Byte offset of LSP `position`
+    ///
+    /// A line past the end is the end of the text, a character past the end of its
+    /// line is the end of the line, and one inside a surrogate pair is the start of it.
+    pub fn offset(&self, position: &Position) -> usize {
+        let Some(&line_start) = self.line_starts.get(position.line as usize) else {
+            return self.line_ends[self.line_ends.len() - 1];
+        };
+        let line = position.line as usize;
+        let mut column = position.character as usize;
+        // Up to each wide character, the units counted are those of the characters
+        // before it, which are now all converted to bytes
+        for wide in &self.wide_chars[line] {
+            if column <= wide.start {
+                break;
+            }
+            if column < wide.start + wide.utf16_len {
+                column = wide.start;
+                break;
+            }
+            column += wide.utf8_len - wide.utf16_len;
+        }
+        (line_start + column).min(self.line_ends[line])
+    }
+
+    /// LSP range of `span`
+    pub fn range(&self, span: Span) -> Range {
+        let bytes = span.byte_range();
+        Range { start: self.position(bytes.start), end: self.position(bytes.end) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Position as SourcePosition;
+
+    fn position(line: u32, character: u32) -> Position {
+        Position { line, character }
+    }
+
+    #[test]
+    fn test_wide_characters_at_line_starts_and_ends() {
+        // 'é' is 2 bytes and 1 unit, '😀' 4 bytes and 2 units
+        let text = "😀x\ny é\n😀";
+        let index = LineIndex::new(text);
+        assert_eq!(index.line_count(), 3);
+        for (offset, expected) in [
+            (0, position(0, 0)),
+            (4, position(0, 2)),
+            (5, position(0, 3)),
+            (6, position(1, 0)),
+            (8, position(1, 2)),
+            (10, position(1, 3)),
+            (11, position(2, 0)),
+            (15, position(2, 2)),
+        ] {
+            assert_eq!(index.position(offset), expected, "offset {}", offset);
+            assert_eq!(index.offset(&expected), offset, "position {:?}", expected);
+        }
+    }
+
+    #[test]
+    fn test_offsets_inside_characters_and_line_breaks_are_clamped() {
+        let index = LineIndex::new("a😀\r\n𝕏");
+        // Inside the emoji's bytes, or between its surrogates, is its start
+        assert_eq!(index.position(3), position(0, 1));
+        assert_eq!(index.offset(&position(0, 2)), 1);
+        // Between '\r' and '\n' is the end of the line
+        assert_eq!(index.position(6), position(0, 3));
+        assert_eq!(index.offset(&position(0, 9)), 5);
+        // Past the end is the end of the text
+        assert_eq!(index.position(100), position(1, 2));
+        assert_eq!(index.offset(&position(7, 0)), 11);
+    }
+
+    #[test]
+    fn test_ranges_span_wide_characters_across_lines() {
+        let text = "let s = \"é😀\nü\"; x";
+        let index = LineIndex::new(text);
+        let start = text.find('"').unwrap();
+        let end = text.rfind('"').unwrap() + 1;
+        let at = |offset: usize| SourcePosition::new(0, 0, offset, 0);
+        let range = index.range(Span::new(at(start), at(end)));
+        assert_eq!(range, Range { start: position(0, 8), end: position(1, 2) });
+        assert_eq!(&text[index.offset(&range.start)..index.offset(&range.end)], "\"é😀\nü\"");
+
+        // An edit replacing the emoji moves the positions after it on its line only
+        let edited = text.replace('😀', "ab");
+        let edited_index = LineIndex::new(&edited);
+        assert_eq!(edited_index.position(edited.find('\n').unwrap()), position(0, 12));
+        assert_eq!(edited_index.position(edited.len()), index.position(text.len()));
+    }
+}
@@ -0,0 +1,133 @@
+//! Memory Usage Reports for Bract LSP
+//!
+//! `bract/memoryReport` compiles a document through code generation and returns
+//! what the memory manager recorded: allocations per strategy, leaks as diagnostics
+//! at the leaked values so the editor can mark them, the allocation hotspots and
+//! the manager's text reports.
+
+use super::{severity_to_lsp, Diagnostic, LineIndex, MEMORY_DIAGNOSTIC_SOURCE};
+use crate::compile::MemoryReport;
+use crate::diagnostics::DiagnosticCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Method of the custom memory report request
+pub const MEMORY_REPORT_METHOD: &str = "bract/memoryReport";
+
+/// Result of `bract/memoryReport`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryReportResult {
+    /// Allocations made with each memory strategy
+    pub allocations: Vec<StrategyAllocationCount>,
+    /// Bytes requested by all allocations
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+    /// Leaked allocations, located at the allocated values
+    pub leaks: Vec<Diagnostic>,
+    /// Allocation sites with the highest impact, most expensive first
+    pub hotspots: Vec<AllocationHotspotInfo>,
+    /// Strategy decided for each struct type, and why
+    #[serde(rename = "typeStrategies")]
+    pub type_strategies: Vec<TypeStrategyInfo>,
+    /// The memory manager's text reports
+    pub reports: MemoryReportText,
+}
+
+/// Allocations made with one strategy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrategyAllocationCount {
+    pub strategy: String,
+    pub count: u64,
+}
+
+/// Allocations made at one place in the source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AllocationHotspotInfo {
+    /// `file:line:column (in function)` of the allocation site
+    pub location: String,
+    #[serde(rename = "allocationCount")]
+    pub allocation_count: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+    /// Strategy most allocations at the site used
+    pub strategy: String,
+    /// Estimated performance impact, from 0 to 100
+    #[serde(rename = "impactScore")]
+    pub impact_score: f64,
+}
+
+/// Memory strategy decided for a struct type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TypeStrategyInfo {
+    #[serde(rename = "typeName")]
+    pub type_name: String,
+    pub strategy: String,
+    /// Requested by an annotation on the struct rather than inferred
+    pub annotated: bool,
+    pub size: u32,
+    pub alignment: u32,
+    pub reason: String,
+}
+
+/// Text reports of the memory manager, ready to show as they are
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MemoryReportText {
+    pub summary: String,
+    pub leaks: String,
+    pub regions: String,
+    pub performance: String,
+    /// Strategies of the struct types
+    pub types: String,
+}
+
+/// Convert the report of compiling a document whose lines are `line_index`; leaks
+/// without a span are only in the text reports
+pub fn memory_report_to_lsp(report: &MemoryReport, line_index: &LineIndex) -> MemoryReportResult {
+    let leaks = report.leaks.iter()
+        .filter_map(|leak| {
+            let span = leak.span?;
+            Some(Diagnostic {
+                range: line_index.range(span),
+                severity: Some(severity_to_lsp(leak.severity)),
+                code: Some(Value::String(DiagnosticCode::PotentialLeak.to_string())),
+                source: Some(MEMORY_DIAGNOSTIC_SOURCE.to_string()),
+                message: format!("{}: {}", leak.message, leak.suggestion),
+                related_information: None,
+                data: None,
+            })
+        })
+        .collect();
+    MemoryReportResult {
+        allocations: report.allocations.iter()
+            .map(|allocations| StrategyAllocationCount { strategy: allocations.strategy.to_string(), count: allocations.count })
+            .collect(),
+        total_bytes: report.total_bytes,
+        leaks,
+        hotspots: report.hotspots.iter()
+            .map(|hotspot| AllocationHotspotInfo {
+                location: hotspot.location.clone(),
+                allocation_count: hotspot.allocation_count,
+                total_bytes: hotspot.total_bytes,
+                strategy: hotspot.strategy.to_string(),
+                impact_score: hotspot.impact_score,
+            })
+            .collect(),
+        type_strategies: report.type_strategies.iter()
+            .map(|decision| TypeStrategyInfo {
+                type_name: decision.type_name.clone(),
+                strategy: decision.strategy.to_string(),
+                annotated: decision.annotated,
+                size: decision.size,
+                alignment: decision.alignment,
+                reason: decision.reason.clone(),
+            })
+            .collect(),
+        reports: MemoryReportText {
+            summary: report.summary.clone(),
+            leaks: report.leak_report.clone(),
+            regions: report.region_report.clone(),
+            performance: report.performance_report.clone(),
+            types: report.type_report.clone(),
+        },
+    }
+}
@@ -3,31 +3,64 @@
 //! This module provides a complete LSP server for Bract, enabling world-class IDE support
 //! with real-time diagnostics, code completion, navigation, and more.
 
-use crate::{Lexer, Parser, semantic::SemanticAnalyzer};
-use crate::ast::Module;
+use crate::ast::{Item, Module, Span};
+use crate::compile::{self, CompileOptions, SourceAnalysis, Severity, Stage};
+use crate::diagnostics::DiagnosticCode;
+use crate::metrics::CompileMetrics;
+use crate::parser::module_loader::SOURCE_EXTENSION;
+use crate::parser::StringInterner;
+use crate::performance::{PerformanceAnalyzer, ContractViolation, PerformanceWarning, WarningType};
 use crate::semantic::SymbolTable;
+use progress::{ANALYSIS_PROGRESS_DELAY, CANCELLATION_POLL_INTERVAL};
+use workspace::FileSummary;
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 pub mod completion;
+pub mod folding;
+pub mod formatting;
+pub mod line_index;
+pub mod memory_report;
+pub mod progress;
+pub mod selection;
+pub mod semantic_tokens;
+pub mod settings;
+pub mod workspace;
 
 // Re-export main types
-pub use completion::{CompletionProvider, CompletionItem, CompletionItemKind};
+pub use completion::{CompletionData, CompletionProvider, CompletionItem, CompletionItemKind};
+pub use folding::FoldingRange;
+pub use formatting::FormattingOptions;
+pub use line_index::LineIndex;
+pub use memory_report::{MemoryReportResult, MEMORY_REPORT_METHOD};
+pub use progress::{CancellationToken, ProgressParams, ProgressSink, ProgressTracker, RequestError, WorkDoneProgress};
+pub use selection::SelectionRange;
+pub use semantic_tokens::{SemanticTokens, SemanticTokensLegend, SemanticTokensOptions, SemanticTokenType};
+pub use settings::{ConfigUpdate, Registration, Unregistration, SETTINGS_SECTION};
+pub use workspace::{FileChangeType, FileEvent, SymbolInformation, WorkspaceIndex};
 
 /// LSP Server state
 #[derive(Debug)]
 pub struct LspServer {
     /// Documents currently open in the editor
     documents: Arc<Mutex<HashMap<String, Document>>>,
-    /// Server capabilities
-    capabilities: ServerCapabilities,
-    /// Configuration
-    #[allow(dead_code)]
-    config: LspConfig,
+    /// Configuration, replaced whole when the client changes its settings
+    config: Mutex<Arc<LspConfig>>,
+    /// Configurable requests whose capability the client registers dynamically
+    dynamic_methods: Mutex<Vec<&'static str>>,
     /// Analysis cache for performance
     analysis_cache: Arc<Mutex<AnalysisCache>>,
+    /// Summaries of every file in the workspace folders
+    workspace: Arc<Mutex<WorkspaceIndex>>,
+    /// Where work-done progress is reported, once the client accepts it
+    progress_sink: Mutex<Option<ProgressSink>>,
+    /// Last number used in a progress token
+    progress_tokens: AtomicU64,
 }
 
 /// Document state in the LSP server
@@ -39,14 +72,20 @@ pub struct Document {
     pub content: String,
     /// Version number for synchronization
     pub version: i32,
+    /// Lines of `content`, for converting between byte offsets and LSP positions
+    pub line_index: Arc<LineIndex>,
     /// Parsed AST (cached)
     pub ast: Option<Module>,
     /// Symbol table (cached)
     pub symbols: Option<SymbolTable>,
+    /// Interner resolving the names in `ast` and `symbols`
+    pub interner: StringInterner,
     /// Last analysis timestamp
     pub last_analyzed: std::time::Instant,
-    /// Diagnostics
+    /// Diagnostics kept until the document changes, such as why it was not formatted
     pub diagnostics: Vec<Diagnostic>,
+    /// How long the last analysis took to parse and analyze the document
+    pub metrics: Option<CompileMetrics>,
 }
 
 /// LSP server configuration
@@ -58,10 +97,18 @@ pub struct LspConfig {
     pub enable_completion: bool,
     /// Enable navigation features
     pub enable_navigation: bool,
+    /// Report performance contract violations (opt-in)
+    pub enable_performance_diagnostics: bool,
     /// Max analysis time per document (ms)
     pub max_analysis_time: u64,
-    /// Cache size limit
+    /// How long after an edit a document is analyzed, when no other edit came (ms)
+    pub analysis_debounce: u64,
+    /// Most documents the analysis cache holds
     pub cache_size_limit: usize,
+    /// Names of files and directories the workspace index skips
+    pub workspace_ignore: Vec<String>,
+    /// Most files the workspace index takes in
+    pub max_workspace_files: usize,
 }
 
 /// Analysis cache for performance optimization
@@ -71,17 +118,57 @@ pub struct AnalysisCache {
     parsed_modules: HashMap<String, (Module, std::time::Instant)>,
     /// Cached symbol tables
     symbol_tables: HashMap<String, (SymbolTable, std::time::Instant)>,
+    /// Most documents held; the ones stored longest ago are evicted first
+    limit: usize,
     /// Cache statistics
     stats: CacheStats,
 }
 
 /// Cache performance statistics
-#[derive(Debug, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct CacheStats {
     pub hits: u64,
     pub misses: u64,
     pub evictions: u64,
     pub total_analysis_time: std::time::Duration,
+    /// Analyses of each document, by URI
+    pub documents: HashMap<String, DocumentTimings>,
+}
+
+/// How long the analyses of one document took
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DocumentTimings {
+    pub analyses: u64,
+    pub total: std::time::Duration,
+    pub last: std::time::Duration,
+    /// The slowest single analysis
+    pub slowest: std::time::Duration,
+}
+
+impl CacheStats {
+    /// Record an analysis of `uri` that took `duration`
+    pub fn record_analysis(&mut self, uri: &str, duration: std::time::Duration) {
+        self.total_analysis_time += duration;
+        let timings = self.documents.entry(uri.to_string()).or_default();
+        timings.analyses += 1;
+        timings.total += duration;
+        timings.last = duration;
+        timings.slowest = timings.slowest.max(duration);
+    }
+
+    /// Share of lookups the cache answered, if there were any
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        (lookups > 0).then(|| self.hits as f64 / lookups as f64)
+    }
+
+    /// The `count` documents whose slowest analysis took longest, slowest first
+    pub fn slowest_documents(&self, count: usize) -> Vec<(&str, &DocumentTimings)> {
+        let mut documents: Vec<_> = self.documents.iter().map(|(uri, timings)| (uri.as_str(), timings)).collect();
+        documents.sort_by(|(a_uri, a), (b_uri, b)| b.slowest.cmp(&a.slowest).then_with(|| a_uri.cmp(b_uri)));
+        documents.truncate(count);
+        documents
+    }
 }
 
 /// Server capabilities
@@ -111,6 +198,41 @@ pub struct ServerCapabilities {
     /// Workspace symbol provider
     #[serde(rename = "workspaceSymbolProvider")]
     pub workspace_symbol_provider: Option<bool>,
+    /// Code action provider
+    #[serde(rename = "codeActionProvider")]
+    pub code_action_provider: Option<bool>,
+    /// Semantic tokens provider
+    #[serde(rename = "semanticTokensProvider")]
+    pub semantic_tokens_provider: Option<SemanticTokensOptions>,
+    /// Document formatting provider
+    #[serde(rename = "documentFormattingProvider")]
+    pub document_formatting_provider: Option<bool>,
+    /// Folding range provider
+    #[serde(rename = "foldingRangeProvider")]
+    pub folding_range_provider: Option<bool>,
+    /// Selection range provider
+    #[serde(rename = "selectionRangeProvider")]
+    pub selection_range_provider: Option<bool>,
+    /// Workspace capabilities
+    pub workspace: Option<WorkspaceCapabilities>,
+}
+
+/// Workspace capabilities
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceCapabilities {
+    /// Workspace folder support
+    #[serde(rename = "workspaceFolders")]
+    pub workspace_folders: WorkspaceFoldersCapability,
+}
+
+/// Workspace folder support
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceFoldersCapability {
+    /// Folders are taken from the initialize request
+    pub supported: bool,
+    /// Folders added or removed later are followed
+    #[serde(rename = "changeNotifications")]
+    pub change_notifications: bool,
 }
 
 /// Text document synchronization capability
@@ -187,10 +309,13 @@ pub struct Diagnostic {
     /// Related information
     #[serde(rename = "relatedInformation")]
     pub related_information: Option<Vec<DiagnosticRelatedInformation>>,
+    /// Fixes of the diagnostic as `DiagnosticFix`es; the client hands them back
+    /// with `textDocument/codeAction`
+    pub data: Option<Value>,
 }
 
-/// Diagnostic severity
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Diagnostic severity, sent as its number like the protocol expects
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DiagnosticSeverity {
     Error = 1,
     Warning = 2,
@@ -198,6 +323,24 @@ pub enum DiagnosticSeverity {
     Hint = 4,
 }
 
+impl Serialize for DiagnosticSeverity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for DiagnosticSeverity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u8::deserialize(deserializer)? {
+            1 => Ok(DiagnosticSeverity::Error),
+            2 => Ok(DiagnosticSeverity::Warning),
+            3 => Ok(DiagnosticSeverity::Information),
+            4 => Ok(DiagnosticSeverity::Hint),
+            other => Err(serde::de::Error::custom(format!("invalid diagnostic severity {}", other))),
+        }
+    }
+}
+
 /// Diagnostic related information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiagnosticRelatedInformation {
@@ -208,7 +351,7 @@ pub struct DiagnosticRelatedInformation {
 }
 
 /// LSP Range
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Range {
     /// Start position
     pub start: Position,
@@ -217,7 +360,7 @@ pub struct Range {
 }
 
 /// LSP Position
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     /// Line number (0-based)
     pub line: u32,
@@ -226,7 +369,7 @@ pub struct Position {
 }
 
 /// LSP Location
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     /// URI of the document
     pub uri: String,
@@ -234,20 +377,95 @@ pub struct Location {
     pub range: Range,
 }
 
+/// LSP Hover result
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hover {
+    /// Text shown for the hovered symbol
+    pub contents: MarkupContent,
+}
+
+/// LSP markup text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarkupContent {
+    /// "markdown" or "plaintext"
+    pub kind: String,
+    /// Content in that format
+    pub value: String,
+}
+
+/// LSP code action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeAction {
+    /// Title shown in the editor
+    pub title: String,
+    /// "quickfix" for fixes that edit the document
+    pub kind: Option<String>,
+    /// Diagnostics the action resolves
+    pub diagnostics: Option<Vec<Diagnostic>>,
+    /// Whether the editor may apply the action without asking
+    #[serde(rename = "isPreferred")]
+    pub is_preferred: Option<bool>,
+    /// Edit performed by the action; informational actions have none
+    pub edit: Option<WorkspaceEdit>,
+}
+
+/// LSP workspace edit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceEdit {
+    /// Edits to make, by document URI
+    pub changes: HashMap<String, Vec<TextEdit>>,
+}
+
+/// LSP text edit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextEdit {
+    /// Text to replace; empty to insert at its start
+    pub range: Range,
+    /// Replacement text
+    #[serde(rename = "newText")]
+    pub new_text: String,
+}
+
+/// A fix carried in `Diagnostic::data`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticFix {
+    /// What the fix does
+    pub title: String,
+    /// The edit, unless the fix is advice only
+    pub edit: Option<TextEdit>,
+    /// How likely the fix is what was meant, from 0.0 to 1.0
+    pub confidence: f32,
+}
+
+/// Confidence from which a fix is marked preferred
+pub const PREFERRED_FIX_CONFIDENCE: f32 = 0.9;
+
 impl Default for LspConfig {
     fn default() -> Self {
         Self {
             enable_diagnostics: true,
             enable_completion: true,
             enable_navigation: true,
+            enable_performance_diagnostics: false,
             max_analysis_time: 5000, // 5 seconds
+            analysis_debounce: 0,    // analyze at once
             cache_size_limit: 100,   // 100 documents
+            workspace_ignore: vec!["target".to_string(), ".git".to_string(), "node_modules".to_string()],
+            max_workspace_files: 10_000,
         }
     }
 }
 
 impl Default for ServerCapabilities {
     fn default() -> Self {
+        Self::for_config(&LspConfig::default())
+    }
+}
+
+impl ServerCapabilities {
+    /// Capabilities of a server with `config`; features it turns off are not advertised
+    pub fn for_config(config: &LspConfig) -> Self {
+        let navigation = Some(config.enable_navigation);
         Self {
             text_document_sync: TextDocumentSyncCapability {
                 open_close: true,
@@ -255,20 +473,59 @@ impl Default for ServerCapabilities {
                 will_save: false,
                 save: Some(SaveOptions { include_text: false }),
             },
-            diagnostic_provider: Some(DiagnosticOptions {
+            diagnostic_provider: config.enable_diagnostics.then(|| DiagnosticOptions {
                 identifier: Some("Bract".to_string()),
                 inter_file_dependencies: true,
                 workspace_diagnostics: true,
             }),
-            completion_provider: Some(CompletionOptions {
+            completion_provider: config.enable_completion.then(|| CompletionOptions {
                 resolve_provider: true,
                 trigger_characters: vec![".".to_string(), "::".to_string()],
             }),
             hover_provider: Some(true),
-            definition_provider: Some(true),
-            references_provider: Some(true),
-            document_symbol_provider: Some(true),
-            workspace_symbol_provider: Some(true),
+            definition_provider: navigation,
+            references_provider: navigation,
+            document_symbol_provider: navigation,
+            workspace_symbol_provider: navigation,
+            code_action_provider: Some(true),
+            semantic_tokens_provider: Some(SemanticTokensOptions {
+                legend: SemanticTokensLegend::default(),
+                full: true,
+                range: false,
+            }),
+            document_formatting_provider: Some(true),
+            folding_range_provider: Some(true),
+            selection_range_provider: Some(true),
+            workspace: Some(WorkspaceCapabilities {
+                workspace_folders: WorkspaceFoldersCapability { supported: true, change_notifications: false },
+            }),
+        }
+    }
+
+    /// Options to register the capability of `method` with, if it is advertised
+    pub fn registration_options(&self, method: &str) -> Option<Value> {
+        let enabled = |provider: Option<bool>| provider.filter(|&enabled| enabled).map(|_| Value::Object(Default::default()));
+        match method {
+            "textDocument/diagnostic" => self.diagnostic_provider.as_ref().and_then(|options| serde_json::to_value(options).ok()),
+            "textDocument/completion" => self.completion_provider.as_ref().and_then(|options| serde_json::to_value(options).ok()),
+            "textDocument/definition" => enabled(self.definition_provider),
+            "textDocument/references" => enabled(self.references_provider),
+            "textDocument/documentSymbol" => enabled(self.document_symbol_provider),
+            "workspace/symbol" => enabled(self.workspace_symbol_provider),
+            _ => None,
+        }
+    }
+
+    /// Stop advertising the capability of `method`, which the client registers dynamically
+    pub fn remove(&mut self, method: &str) {
+        match method {
+            "textDocument/diagnostic" => self.diagnostic_provider = None,
+            "textDocument/completion" => self.completion_provider = None,
+            "textDocument/definition" => self.definition_provider = None,
+            "textDocument/references" => self.references_provider = None,
+            "textDocument/documentSymbol" => self.document_symbol_provider = None,
+            "workspace/symbol" => self.workspace_symbol_provider = None,
+            _ => {}
         }
     }
 }
@@ -276,31 +533,163 @@ impl Default for ServerCapabilities {
 impl LspServer {
     /// Create a new LSP server
     pub fn new() -> Self {
+        Self::with_config(LspConfig::default())
+    }
+
+    /// Create an LSP server with the given configuration
+    pub fn with_config(config: LspConfig) -> Self {
         Self {
             documents: Arc::new(Mutex::new(HashMap::new())),
-            capabilities: ServerCapabilities::default(),
-            config: LspConfig::default(),
-            analysis_cache: Arc::new(Mutex::new(AnalysisCache::new())),
+            analysis_cache: Arc::new(Mutex::new(AnalysisCache::with_limit(config.cache_size_limit))),
+            config: Mutex::new(Arc::new(config)),
+            dynamic_methods: Mutex::new(Vec::new()),
+            workspace: Arc::new(Mutex::new(WorkspaceIndex::new())),
+            progress_sink: Mutex::new(None),
+            progress_tokens: AtomicU64::new(0),
+        }
+    }
+
+    /// Capabilities to advertise at initialization
+    ///
+    /// Those the client registers dynamically are left out; `registrations` has them.
+    pub fn capabilities(&self) -> ServerCapabilities {
+        let mut capabilities = ServerCapabilities::for_config(&self.config());
+        for method in self.dynamic_methods() {
+            capabilities.remove(method);
+        }
+        capabilities
+    }
+
+    /// The configuration in effect
+    ///
+    /// A request should take it once, so a change of settings midway does not
+    /// leave it seeing parts of both.
+    pub fn config(&self) -> Arc<LspConfig> {
+        self.config.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Have the client register the capabilities of `methods` dynamically, as it
+    /// said it could at initialization
+    pub fn set_dynamic_registration(&self, methods: Vec<&'static str>) {
+        *self.dynamic_methods.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = methods;
+    }
+
+    /// Configurable requests whose capability the client registers dynamically
+    fn dynamic_methods(&self) -> Vec<&'static str> {
+        self.dynamic_methods.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Capabilities for the client to register once initialized, as the configuration turns them on
+    pub fn registrations(&self) -> Vec<Registration> {
+        let capabilities = ServerCapabilities::for_config(&self.config());
+        self.dynamic_methods().into_iter()
+            .filter_map(|method| Some(registration(method, capabilities.registration_options(method)?)))
+            .collect()
+    }
+
+    /// Put `config` in effect, returning how the capabilities changed
+    ///
+    /// Requests already running finish with the configuration they started with.
+    /// The analysis cache is trimmed to the new limit at once.
+    pub fn update_config(&self, config: LspConfig) -> ConfigUpdate {
+        let before = {
+            let mut current = self.config.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            std::mem::replace(&mut *current, Arc::new(config.clone()))
+        };
+        self.lock_cache().set_limit(config.cache_size_limit);
+
+        let (before, after) = (ServerCapabilities::for_config(&before), ServerCapabilities::for_config(&config));
+        let dynamic = self.dynamic_methods();
+        let mut update = ConfigUpdate::default();
+        for method in settings::CONFIGURABLE_METHODS {
+            let options = after.registration_options(method);
+            if before.registration_options(method) == options {
+                continue;
+            }
+            match (dynamic.contains(&method), options) {
+                (false, _) => update.needs_restart.push(method),
+                (true, Some(options)) => update.register.push(registration(method, options)),
+                (true, None) => update.unregister.push(Unregistration { id: method.to_string(), method: method.to_string() }),
+            }
         }
+        update
+    }
+
+    /// URIs of the documents open in the editor
+    pub fn document_uris(&self) -> Vec<String> {
+        self.lock_documents().keys().cloned().collect()
+    }
+
+    /// Run a request on the document `uri`, turning a panic inside it into an error
+    ///
+    /// The error names the document and what the request panicked with. Locks
+    /// the request held are recovered by the next request taking them.
+    pub fn catch_panic<T, E: From<String>>(&self, uri: &str, request: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        panic::catch_unwind(AssertUnwindSafe(request))
+            .unwrap_or_else(|payload| Err(format!("Internal error handling {}: {}", uri, compile::panic_message(&*payload)).into()))
+    }
+
+    /// Report work-done progress of long operations to `sink`
+    pub fn set_progress_sink(&self, sink: ProgressSink) {
+        *self.progress_sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(sink);
+    }
+
+    /// Progress of the operation `title` under a new token, shown once it has run for `delay`
+    fn progress(&self, title: &str, delay: std::time::Duration) -> ProgressTracker {
+        let sink = self.progress_sink.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        let token = format!("bract-progress-{}", self.progress_tokens.fetch_add(1, Ordering::Relaxed) + 1);
+        ProgressTracker::new(sink, token, title, delay)
+    }
+
+    /// Lock the documents, keeping them when a panicking request poisoned the lock
+    ///
+    /// Documents are replaced whole, so a panic cannot leave one half updated.
+    fn lock_documents(&self) -> MutexGuard<'_, HashMap<String, Document>> {
+        self.documents.lock().unwrap_or_else(|poisoned| {
+            self.documents.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    /// Lock the workspace index, keeping it when a panicking request poisoned the lock
+    ///
+    /// Summaries are replaced whole, like documents.
+    fn lock_workspace(&self) -> MutexGuard<'_, WorkspaceIndex> {
+        self.workspace.lock().unwrap_or_else(|poisoned| {
+            self.workspace.clear_poison();
+            poisoned.into_inner()
+        })
     }
 
-    /// Get server capabilities for initialization
-    pub fn capabilities(&self) -> &ServerCapabilities {
-        &self.capabilities
+    /// Lock the analysis cache, starting it over when a panicking request poisoned the lock
+    fn lock_cache(&self) -> MutexGuard<'_, AnalysisCache> {
+        self.analysis_cache.lock().unwrap_or_else(|poisoned| {
+            self.analysis_cache.clear_poison();
+            let mut cache = poisoned.into_inner();
+            *cache = AnalysisCache::with_limit(self.config().cache_size_limit);
+            cache
+        })
     }
 
     /// Add or update a document
     pub fn update_document(&self, uri: String, content: String, version: i32) -> Result<(), String> {
-        let mut documents = self.documents.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let mut documents = self.lock_documents();
+        // Keep the interner across edits so names interned earlier stay resolvable
+        let interner = documents.get(&uri)
+            .map(|document| document.interner.clone())
+            .unwrap_or_default();
         
         let document = Document {
             uri: uri.clone(),
+            line_index: Arc::new(LineIndex::new(&content)),
             content,
             version,
             ast: None,
             symbols: None,
+            interner,
             last_analyzed: std::time::Instant::now(),
             diagnostics: Vec::new(),
+            metrics: None,
         };
         
         documents.insert(uri, document);
@@ -309,130 +698,695 @@ impl LspServer {
 
     /// Get a document
     pub fn get_document(&self, uri: &str) -> Result<Option<Document>, String> {
-        let documents = self.documents.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let documents = self.lock_documents();
         Ok(documents.get(uri).cloned())
     }
 
+    /// Metrics of the last analysis of `uri`, with the analysis cache's statistics
+    pub fn document_metrics(&self, uri: &str) -> Option<CompileMetrics> {
+        let metrics = self.lock_documents().get(uri)?.metrics.clone()?;
+        Some(self.lock_cache().with_stats(metrics))
+    }
+
+    /// Every open document
+    fn open_documents(&self) -> Result<Vec<Document>, String> {
+        let documents = self.lock_documents();
+        Ok(documents.values().cloned().collect())
+    }
+
     /// Remove a document
     pub fn remove_document(&self, uri: &str) -> Result<(), String> {
-        let mut documents = self.documents.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let mut documents = self.lock_documents();
         documents.remove(uri);
         
         // Also remove from cache
-        let mut cache = self.analysis_cache.lock().map_err(|e| format!("Lock error: {}", e))?;
+        let mut cache = self.lock_cache();
         cache.remove(uri);
+        drop(cache);
+
+        // A workspace file goes back to what is on disk
+        self.lock_workspace().close(uri);
+        if let Some(path) = workspace::uri_to_path(uri).filter(|path| self.lock_workspace().contains_path(path)) {
+            self.index_file(&path);
+        }
         
         Ok(())
     }
 
     /// Analyze a document and update diagnostics
+    ///
+    /// An analysis that panics is reported as an internal-error diagnostic. With
+    /// diagnostics turned off the document is still analyzed, for the other
+    /// features, but no diagnostics are returned.
     pub fn analyze_document(&self, uri: &str) -> Result<Vec<Diagnostic>, String> {
+        self.analyze_document_cancellable(uri, &CancellationToken::new()).map_err(|error| error.to_string())
+    }
+
+    /// `analyze_document`, stopping once `cancel` is set
+    ///
+    /// A cancelled analysis keeps nothing on the document or in the cache. One
+    /// running past `ANALYSIS_PROGRESS_DELAY` reports its progress.
+    pub fn analyze_document_cancellable(&self, uri: &str, cancel: &CancellationToken) -> Result<Vec<Diagnostic>, RequestError> {
+        panic::catch_unwind(AssertUnwindSafe(|| self.analyze(uri, cancel))).unwrap_or_else(|payload| {
+            let diagnostic = compile::Diagnostic {
+                stage: Stage::Semantic,
+                severity: Severity::Error,
+                code: DiagnosticCode::InternalError,
+                message: format!("Internal compiler error: analysis of {} panicked: {}", uri, compile::panic_message(&*payload)),
+                span: None,
+                related: None,
+                fixes: Vec::new(),
+            };
+            Ok(vec![compile_diagnostic_to_lsp(&diagnostic, uri, &LineIndex::new(""))])
+        })
+    }
+
+    /// `analyze_document_cancellable`, letting panics through
+    fn analyze(&self, uri: &str, cancel: &CancellationToken) -> Result<Vec<Diagnostic>, RequestError> {
+        let config = self.config();
         let document = self.get_document(uri)?.ok_or("Document not found")?;
         
         // Check cache first
         {
-            let cache = self.analysis_cache.lock().map_err(|e| format!("Lock error: {}", e))?;
+            let mut cache = self.lock_cache();
             if let Some(diagnostics) = cache.get_diagnostics(uri, &document.content) {
+                cache.stats.hits += 1;
                 return Ok(diagnostics);
             }
+            cache.stats.misses += 1;
         }
 
         let start_time = std::time::Instant::now();
         let mut diagnostics = Vec::new();
+        let file_name = uri.rsplit('/').next().unwrap_or(uri);
+        let mut progress = self.progress(&format!("Analyzing {}", file_name), ANALYSIS_PROGRESS_DELAY);
 
-        // Parse the document
-        match self.parse_document(&document.content) {
-            Ok((ast, symbols)) => {
-                // Store in cache
-                {
-                    let mut cache = self.analysis_cache.lock().map_err(|e| format!("Lock error: {}", e))?;
-                    cache.store_analysis(uri.to_string(), ast, symbols);
-                }
-            },
-            Err(errors) => {
-                // Convert parse errors to diagnostics
-                for error in errors {
-                    diagnostics.push(self.error_to_diagnostic(error));
+        // Parse and analyze the document
+        let analysis = self.parse_document(&document.content, &document.interner);
+        cancel.check()?;
+        diagnostics.extend(analysis.diagnostics.iter().map(|diagnostic| compile_diagnostic_to_lsp(diagnostic, uri, &document.line_index)));
+        // The open document's text takes the place of the file in the workspace index
+        let summary = FileSummary::new(&document.content, analysis.module.as_ref(), &document.interner);
+        self.lock_workspace().update_open(uri.to_string(), summary);
+        // A module that failed to parse outright has only its diagnostics
+        if let (Some(ast), Some(symbols)) = (analysis.module, analysis.symbols) {
+            if config.enable_diagnostics && config.enable_performance_diagnostics {
+                progress.report(Some("Checking performance contracts".to_string()), None);
+                diagnostics.extend(self.performance_diagnostics(&ast, &document.interner, &document.line_index, &config, start_time, cancel)?);
+            }
+
+            // Keep the analysis on the document unless it changed meanwhile
+            {
+                let mut documents = self.lock_documents();
+                if let Some(stored) = documents.get_mut(uri).filter(|stored| stored.version == document.version) {
+                    stored.ast = Some(ast.clone());
+                    stored.symbols = Some(symbols.clone());
+                    stored.last_analyzed = std::time::Instant::now();
+                    stored.metrics = Some(analysis.metrics.clone());
                 }
             }
+
+            // Store in cache
+            {
+                let mut cache = self.lock_cache();
+                cache.store_analysis(uri.to_string(), ast, symbols);
+            }
         }
 
         // Update cache statistics
         {
-            let mut cache = self.analysis_cache.lock().map_err(|e| format!("Lock error: {}", e))?;
-            cache.stats.total_analysis_time += start_time.elapsed();
+            let mut cache = self.lock_cache();
+            cache.stats.record_analysis(uri, start_time.elapsed());
         }
 
+        // Diagnostics kept on the document, such as why it was not formatted
+        diagnostics.extend(document.diagnostics);
+        progress.finish(None);
+        if !config.enable_diagnostics {
+            diagnostics.clear();
+        }
         Ok(diagnostics)
     }
 
-    /// Parse a document and return AST and symbols
-    fn parse_document(&self, content: &str) -> Result<(Module, SymbolTable), Vec<String>> {
-        let mut errors = Vec::new();
-
-        // Lexical analysis
-        let _lexer = Lexer::new(content, 0);
+    /// Hover text for the item named at `position`: its kind and name, then its documentation
+    pub fn hover(&self, uri: &str, position: &Position) -> Result<Option<Hover>, String> {
+        let document = self.get_document(uri)?.ok_or("Document not found")?;
+        let word = completion::word_at_offset(&document.content, document.line_index.offset(position)).to_string();
+        if word.is_empty() {
+            return Ok(None);
+        }
         
-        // Parsing
-        let mut parser = match Parser::new(content, 0) {
-            Ok(p) => p,
-            Err(e) => {
-                errors.push(format!("Parser creation failed: {:?}", e));
-                return Err(errors);
-            }
+        let ast = match document.ast.or_else(|| self.parse_document(&document.content, &document.interner).module) {
+            Some(ast) => ast,
+            None => return Ok(None),
         };
+        
+        Ok(find_item(&ast.items, &word, &document.interner).map(|(keyword, item)| {
+            let mut value = format!("```bract\n{} {}\n```", keyword, word);
+            if let Some(doc) = item.doc(&document.interner) {
+                value.push_str("\n\n");
+                value.push_str(&doc);
+            }
+            Hover {
+                contents: MarkupContent { kind: "markdown".to_string(), value },
+            }
+        }))
+    }
+
+    /// Use the workspace folders the client initialized with, given as URIs
+    pub fn set_workspace_folders(&self, folders: &[String]) {
+        let folders = folders.iter().filter_map(|uri| workspace::uri_to_path(uri)).collect();
+        self.lock_workspace().set_folders(folders);
+    }
+
+    /// Index the `.bract` files of the workspace folders, returning how many were read
+    ///
+    /// Meant to run in the background: each file is read and summarized without
+    /// holding the index, and files open in the editor keep the summary of their text.
+    /// Progress counts the files indexed; once `cancel` is set indexing stops after
+    /// the file at hand, keeping the files indexed so far.
+    pub fn index_workspace(&self, cancel: &CancellationToken) -> usize {
+        let mut progress = self.progress("Indexing workspace", std::time::Duration::ZERO);
+        let config = self.config();
+        let folders = self.lock_workspace().folders().to_vec();
+        let mut files = Vec::new();
+        for folder in folders {
+            let limit = config.max_workspace_files - files.len();
+            files.extend(workspace::find_source_files(&folder, &config.workspace_ignore, limit));
+        }
+
+        let mut indexed = 0;
+        for (done, path) in files.iter().enumerate() {
+            if cancel.is_cancelled() {
+                break;
+            }
+            progress.report(Some(format!("{}/{} files", done, files.len())), Some((done, files.len())));
+            if self.index_file(path) {
+                indexed += 1;
+            }
+        }
+        progress.finish(Some(format!("Indexed {} files", indexed)));
+        indexed
+    }
 
-        let ast = match parser.parse_module() {
-            Ok(module) => module,
-            Err(e) => {
-                errors.push(format!("Parse error: {:?}", e));
-                return Err(errors);
+    /// Follow changes to watched files in the index
+    pub fn watched_files_changed(&self, changes: &[FileEvent]) {
+        for change in changes {
+            let Some(path) = workspace::uri_to_path(&change.uri) else {
+                continue;
+            };
+            let indexed = match change.change {
+                FileChangeType::Created | FileChangeType::Changed => self.index_file(&path),
+                FileChangeType::Deleted => false,
+            };
+            if !indexed {
+                self.lock_workspace().remove(&workspace::path_to_uri(&path));
             }
+        }
+    }
+
+    /// Summarize the source file at `path` into the index, returning whether it could be read
+    fn index_file(&self, path: &Path) -> bool {
+        if path.extension().is_none_or(|extension| extension != SOURCE_EXTENSION) {
+            return false;
+        }
+        let Ok(source) = std::fs::read_to_string(path) else {
+            return false;
         };
+        // A file the parser panics on is still indexed by the names it mentions
+        let summary = panic::catch_unwind(|| FileSummary::parse(&source))
+            .unwrap_or_else(|_| FileSummary::new(&source, None, &StringInterner::new()));
+        self.lock_workspace().update_from_disk(workspace::path_to_uri(path), summary);
+        true
+    }
 
-        // Semantic analysis
-        let mut analyzer = SemanticAnalyzer::new();
-        let analysis_result = analyzer.analyze(&ast);
-        let symbols = analysis_result.symbol_table;
+    /// Workspace symbols whose name contains `query`, ignoring case; none with
+    /// navigation turned off
+    pub fn workspace_symbols(&self, query: &str) -> Vec<SymbolInformation> {
+        if !self.config().enable_navigation {
+            return Vec::new();
+        }
+        self.lock_workspace().symbols_matching(query)
+    }
 
-        if !errors.is_empty() {
-            Err(errors)
+    /// Declarations of the name at `position`: those in the document itself if any,
+    /// otherwise those anywhere in the workspace; none with navigation turned off
+    pub fn definition(&self, uri: &str, position: &Position) -> Result<Vec<Location>, String> {
+        let document = self.get_document(uri)?.ok_or("Document not found")?;
+        if !self.config().enable_navigation {
+            return Ok(Vec::new());
+        }
+        let Some(name) = self.indexed_word_at(&document, position) else {
+            return Ok(Vec::new());
+        };
+        Ok(self.lock_workspace().declarations(uri, &name))
+    }
+
+    /// Every use of the name at `position` in the workspace, matched by name
+    ///
+    /// The index picks the files mentioning the name; only those are read again
+    /// to locate each use, checking `cancel` before each one. With navigation
+    /// turned off there are none.
+    pub fn references(
+        &self,
+        uri: &str,
+        position: &Position,
+        include_declaration: bool,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<Location>, RequestError> {
+        let document = self.get_document(uri)?.ok_or("Document not found")?;
+        if !self.config().enable_navigation {
+            return Ok(Vec::new());
+        }
+        let Some(name) = self.indexed_word_at(&document, position) else {
+            return Ok(Vec::new());
+        };
+        let files: Vec<(String, Vec<Range>)> = {
+            let index = self.lock_workspace();
+            index.files_mentioning(&name).into_iter()
+                .map(|file| {
+                    let declared = index.declaration_ranges(&file, &name);
+                    (file, declared)
+                })
+                .collect()
+        };
+
+        let mut locations = Vec::new();
+        for (file, declared) in files {
+            cancel.check()?;
+            let source = match self.get_document(&file)? {
+                Some(open) => open.content,
+                None => match workspace::uri_to_path(&file).and_then(|path| std::fs::read_to_string(path).ok()) {
+                    Some(source) => source,
+                    None => continue,
+                },
+            };
+            locations.extend(workspace::name_ranges(&source, &name).into_iter()
+                .filter(|range| include_declaration || !declared.contains(range))
+                .map(|range| Location { uri: file.clone(), range }));
+        }
+        Ok(locations)
+    }
+
+    /// Identifier at `position` of an open document, once the document is in the index
+    fn indexed_word_at(&self, document: &Document, position: &Position) -> Option<String> {
+        let word = Some(completion::word_at_offset(&document.content, document.line_index.offset(position)))
+            .filter(|word| !word.is_empty())?
+            .to_string();
+        // Documents not analyzed yet are summarized now
+        if self.lock_workspace().file(&document.uri).is_none() {
+            let analysis = self.parse_document(&document.content, &document.interner);
+            let summary = FileSummary::new(&document.content, analysis.module.as_ref(), &document.interner);
+            self.lock_workspace().update_open(document.uri.clone(), summary);
+        }
+        Some(word)
+    }
+
+    /// Complete a completion item with its documentation, type and any import it needs
+    ///
+    /// With completion turned off the item is handed back as it is.
+    pub fn resolve_completion(&self, item: CompletionItem) -> Result<CompletionItem, String> {
+        if !self.config().enable_completion {
+            return Ok(item);
+        }
+        completion::resolve_completion(self, item)
+    }
+
+    /// Code actions for the `diagnostics` the client reported within `range`
+    ///
+    /// Each fix of a diagnostic becomes an action: fixes with an edit are quick fixes,
+    /// preferred from `PREFERRED_FIX_CONFIDENCE` on, and the others only show their
+    /// advice. Diagnostics the client sent back without their `data` are looked up
+    /// in a fresh analysis of the document.
+    pub fn code_actions(&self, uri: &str, range: &Range, diagnostics: &[Diagnostic]) -> Result<Vec<CodeAction>, String> {
+        let mut analyzed = None;
+        let mut actions = Vec::new();
+        for diagnostic in diagnostics.iter().filter(|diagnostic| ranges_overlap(&diagnostic.range, range)) {
+            let data = match &diagnostic.data {
+                Some(data) => Some(data.clone()),
+                None => {
+                    if analyzed.is_none() {
+                        analyzed = Some(self.analyze_document(uri)?);
+                    }
+                    analyzed.iter().flatten()
+                        .find(|known| known.range == diagnostic.range && known.code == diagnostic.code && known.message == diagnostic.message)
+                        .and_then(|known| known.data.clone())
+                }
+            };
+            let Some(data) = data else { continue };
+            let fixes: Vec<DiagnosticFix> = serde_json::from_value(data).map_err(|e| format!("Malformed diagnostic data: {}", e))?;
+
+            actions.extend(fixes.into_iter().map(|fix| CodeAction {
+                title: fix.title,
+                kind: fix.edit.as_ref().map(|_| "quickfix".to_string()),
+                diagnostics: Some(vec![diagnostic.clone()]),
+                is_preferred: Some(fix.edit.is_some() && fix.confidence >= PREFERRED_FIX_CONFIDENCE),
+                edit: fix.edit.map(|edit| WorkspaceEdit {
+                    changes: HashMap::from([(uri.to_string(), vec![edit])]),
+                }),
+            }));
+        }
+        Ok(actions)
+    }
+
+    /// Semantic tokens of the whole document, identifiers classified through its symbol table
+    pub fn semantic_tokens_full(&self, uri: &str) -> Result<SemanticTokens, String> {
+        let document = self.get_document(uri)?.ok_or("Document not found")?;
+        let symbols = document.symbols.or_else(|| self.parse_document(&document.content, &document.interner).symbols);
+        let tokens = semantic_tokens::classify_tokens(&document.content, symbols.as_ref(), &document.interner);
+        Ok(SemanticTokens {
+            result_id: None,
+            data: semantic_tokens::encode_tokens(&tokens),
+        })
+    }
+
+    /// Folding ranges of the document's multi-line constructs and comment runs
+    pub fn folding_ranges(&self, uri: &str) -> Result<Vec<FoldingRange>, String> {
+        let document = self.get_document(uri)?.ok_or("Document not found")?;
+        let ast = match document.ast.or_else(|| self.parse_document(&document.content, &document.interner).module) {
+            Some(ast) => ast,
+            None => return Ok(Vec::new()),
+        };
+        Ok(folding::folding_ranges(&ast, &document.content, &document.line_index))
+    }
+
+    /// Selection range chains at each of `positions`, in the same order
+    pub fn selection_ranges(&self, uri: &str, positions: Vec<Position>) -> Result<Vec<SelectionRange>, String> {
+        let document = self.get_document(uri)?.ok_or("Document not found")?;
+        let ast = document.ast.or_else(|| self.parse_document(&document.content, &document.interner).module);
+        Ok(positions.iter()
+            .map(|position| selection::selection_range(ast.as_ref(), &document.content, position))
+            .collect())
+    }
+
+    /// Edits formatting the document with the pretty-printer, as `options` ask
+    ///
+    /// Only the lines that change are edited, so a formatted document gets no edits.
+    /// A document that does not parse, or has comments the AST would drop, is left
+    /// alone: there are no edits, and a warning saying why is kept on the document
+    /// and reported with its diagnostics until it changes. `cancel` is checked
+    /// between parsing, printing and diffing.
+    pub fn format_document(
+        &self,
+        uri: &str,
+        options: FormattingOptions,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<TextEdit>, RequestError> {
+        let document = self.get_document(uri)?.ok_or("Document not found")?;
+        let analysis = self.parse_document(&document.content, &document.interner);
+        cancel.check()?;
+        let parse_failed = analysis.diagnostics.iter()
+            .any(|diagnostic| matches!(diagnostic.stage, Stage::Lex | Stage::Parse) && diagnostic.severity == Severity::Error);
+
+        let refusal = if parse_failed || analysis.module.is_none() {
+            Some("Document not formatted: it has syntax errors")
+        } else if formatting::has_plain_comments(&document.content) {
+            Some("Document not formatted: formatting would remove its comments")
         } else {
-            Ok((ast, symbols))
+            None
+        };
+        let module = match (analysis.module, refusal) {
+            (Some(module), None) => module,
+            (_, refusal) => {
+                let mut documents = self.lock_documents();
+                if let Some(stored) = documents.get_mut(uri).filter(|stored| stored.version == document.version) {
+                    stored.diagnostics = vec![Diagnostic {
+                        range: document.line_index.range(Span::single(crate::lexer::Position::start(0))),
+                        severity: Some(DiagnosticSeverity::Warning),
+                        code: None,
+                        source: Some(FORMATTING_DIAGNOSTIC_SOURCE.to_string()),
+                        message: refusal.unwrap_or_default().to_string(),
+                        related_information: None,
+                        data: None,
+                    }];
+                }
+                return Ok(Vec::new());
+            }
+        };
+
+        let formatted = formatting::format_module(&module, &document.interner, &options);
+        cancel.check()?;
+        Ok(formatting::line_edits(&document.content, &formatted))
+    }
+
+    /// Memory report of compiling the document through code generation
+    ///
+    /// Compiling gets the analysis budget. A document that takes longer, or that
+    /// does not compile, has no report, and the error says why. A request cancelled
+    /// while compiling returns at once.
+    pub fn memory_report(&self, uri: &str, cancel: &CancellationToken) -> Result<MemoryReportResult, RequestError> {
+        let document = self.get_document(uri)?.ok_or("Document not found")?;
+        let budget = self.config().max_analysis_time;
+        let file_name = uri.rsplit('/').next().unwrap_or(uri).to_string();
+        let options = CompileOptions { emit_memory_report: true, ..CompileOptions::default() };
+        let line_index = document.line_index.clone();
+
+        // Compiling can't be interrupted, so one that runs out of time or is cancelled
+        // is left to finish on its own
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(compile::compile_source(&document.content, &file_name, &options));
+        });
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(budget);
+        let result = loop {
+            cancel.check()?;
+            let wait = deadline.saturating_duration_since(std::time::Instant::now()).min(CANCELLATION_POLL_INTERVAL);
+            match receiver.recv_timeout(wait) {
+                Ok(result) => break result,
+                Err(_) if std::time::Instant::now() < deadline => continue,
+                Err(_) => return Err(format!(
+                    "Memory report skipped: compiling exceeded the {}ms analysis budget",
+                    budget
+                ).into()),
+            }
+        };
+
+        if let Some(report) = &result.memory_report {
+            return Ok(memory_report::memory_report_to_lsp(report, &line_index));
         }
+        let reason = result.errors().next()
+            .map(|error| error.to_string())
+            .unwrap_or_else(|| "the document was not compiled".to_string());
+        Err(format!("Memory report unavailable: {}", reason).into())
     }
 
-    /// Convert error to LSP diagnostic
-    fn error_to_diagnostic(&self, error: String) -> Diagnostic {
-        Diagnostic {
-            range: Range {
-                start: Position { line: 0, character: 0 },
-                end: Position { line: 0, character: 0 },
-            },
-            severity: Some(DiagnosticSeverity::Error),
-            code: None,
-            source: Some("Bract".to_string()),
-            message: error,
+    /// Parse a document into `interner` and analyze it, the same way `compile_source` does
+    fn parse_document(&self, content: &str, interner: &StringInterner) -> SourceAnalysis {
+        compile::analyze_source(content, interner.clone())
+    }
+
+    /// Verify performance contracts within what is left of the analysis budget
+    fn performance_diagnostics(
+        &self,
+        ast: &Module,
+        interner: &StringInterner,
+        line_index: &LineIndex,
+        config: &LspConfig,
+        start_time: std::time::Instant,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<Diagnostic>, RequestError> {
+        let budget = std::time::Duration::from_millis(config.max_analysis_time);
+        let remaining = budget.saturating_sub(start_time.elapsed());
+
+        if !remaining.is_zero() {
+            let analysis = PerformanceAnalyzer::default()
+                .with_time_budget(remaining)
+                .with_cancellation(cancel.flag())
+                .analyze_module(ast, interner);
+            if analysis.stats.cancelled {
+                return Err(RequestError::Cancelled);
+            }
+            if !analysis.stats.timed_out {
+                let violations = analysis.violations.iter().map(|violation| violation_to_diagnostic(violation, line_index));
+                let warnings = analysis.warnings.iter().map(|warning| performance_warning_to_diagnostic(warning, line_index));
+                return Ok(violations.chain(warnings).collect());
+            }
+        }
+
+        // Out of budget - skip rather than hold up the editor
+        Ok(vec![Diagnostic {
+            range: line_index.range(Span::single(ast.span.start)),
+            severity: Some(DiagnosticSeverity::Information),
+            code: Some(Value::String(DiagnosticCode::AnalysisSkipped.to_string())),
+            source: Some(PERFORMANCE_DIAGNOSTIC_SOURCE.to_string()),
+            message: format!(
+                "Performance analysis skipped: exceeded the {}ms analysis budget",
+                config.max_analysis_time
+            ),
             related_information: None,
+            data: None,
+        }])
+    }
+}
+
+/// `source` of diagnostics produced by performance analysis
+pub const PERFORMANCE_DIAGNOSTIC_SOURCE: &str = "bract-performance";
+
+/// `source` of diagnostics produced by semantic analysis
+pub const SEMANTIC_DIAGNOSTIC_SOURCE: &str = "bract-semantic";
+
+/// `source` of diagnostics explaining why a document was not formatted
+pub const FORMATTING_DIAGNOSTIC_SOURCE: &str = "bract-format";
+
+/// `source` of the leak diagnostics of memory reports
+pub const MEMORY_DIAGNOSTIC_SOURCE: &str = "bract-memory";
+
+/// Registration of the capability of `method`, under its own name
+fn registration(method: &str, register_options: Value) -> Registration {
+    Registration { id: method.to_string(), method: method.to_string(), register_options }
+}
+
+/// Whether two ranges share a position; an empty range touching another overlaps it
+fn ranges_overlap(a: &Range, b: &Range) -> bool {
+    let key = |position: &Position| (position.line, position.character);
+    key(&a.start) <= key(&b.end) && key(&b.start) <= key(&a.end)
+}
+
+/// Item named `name` among `items` or in their inline modules, with the keyword declaring it
+fn find_item<'a>(items: &'a [Item], name: &str, interner: &StringInterner) -> Option<(&'static str, &'a Item)> {
+    items.iter().find_map(|item| {
+        let (keyword, item_name) = match item {
+            Item::Function { name, .. } => ("fn", name),
+            Item::Struct { name, .. } => ("struct", name),
+            Item::Enum { name, .. } => ("enum", name),
+            Item::TypeAlias { name, .. } => ("type", name),
+            Item::Const { name, .. } => ("const", name),
+            Item::Module { name, .. } => ("mod", name),
+            Item::Impl { .. } | Item::Use { .. } => return None,
+        };
+        if interner.matches(*item_name, name) {
+            return Some((keyword, item));
         }
+        match item {
+            Item::Module { items: Some(items), .. } => find_item(items, name, interner),
+            _ => None,
+        }
+    })
+}
+
+/// Convert a contract violation to an error diagnostic
+fn violation_to_diagnostic(violation: &ContractViolation, line_index: &LineIndex) -> Diagnostic {
+    Diagnostic {
+        range: line_index.range(violation.span),
+        severity: Some(DiagnosticSeverity::Error),
+        code: Some(Value::String(violation.violation_type.code().to_string())),
+        source: Some(PERFORMANCE_DIAGNOSTIC_SOURCE.to_string()),
+        message: format!(
+            "{} (expected {}, estimated {})",
+            violation.message, violation.expected, violation.actual
+        ),
+        related_information: None,
+        data: None,
+    }
+}
+
+/// Convert a performance warning to a warning or hint diagnostic
+fn performance_warning_to_diagnostic(warning: &PerformanceWarning, line_index: &LineIndex) -> Diagnostic {
+    let severity = match warning.warning_type {
+        WarningType::MissingContract | WarningType::UnverifiedExtern => DiagnosticSeverity::Hint,
+        WarningType::InaccurateEstimate
+        | WarningType::SuboptimalAllocation
+        | WarningType::PotentialBottleneck => DiagnosticSeverity::Warning,
+    };
+    Diagnostic {
+        range: line_index.range(warning.span),
+        severity: Some(severity),
+        code: Some(Value::String(warning.warning_type.code().to_string())),
+        source: Some(PERFORMANCE_DIAGNOSTIC_SOURCE.to_string()),
+        message: warning.message.clone(),
+        related_information: None,
+        data: None,
+    }
+}
+
+/// LSP severity of a compile pipeline severity
+fn severity_to_lsp(severity: Severity) -> DiagnosticSeverity {
+    match severity {
+        Severity::Error => DiagnosticSeverity::Error,
+        Severity::Warning => DiagnosticSeverity::Warning,
+        Severity::Information => DiagnosticSeverity::Information,
+        Severity::Hint => DiagnosticSeverity::Hint,
+    }
+}
+
+/// Convert a diagnostic of the compile pipeline, pointing related locations into `uri`
+/// whose lines are `line_index`
+fn compile_diagnostic_to_lsp(diagnostic: &compile::Diagnostic, uri: &str, line_index: &LineIndex) -> Diagnostic {
+    let related_information = diagnostic.related.map(|(span, message)| {
+        vec![DiagnosticRelatedInformation {
+            location: Location {
+                uri: uri.to_string(),
+                range: line_index.range(span),
+            },
+            message: message.to_string(),
+        }]
+    });
+    let severity = severity_to_lsp(diagnostic.severity);
+    let source = match diagnostic.stage {
+        Stage::Semantic => SEMANTIC_DIAGNOSTIC_SOURCE,
+        Stage::Lex | Stage::Parse | Stage::Codegen => "Bract",
+    };
+    let fixes: Vec<DiagnosticFix> = diagnostic.fixes.iter()
+        .map(|fix| DiagnosticFix {
+            title: fix.message.clone(),
+            edit: fix.replacement.as_ref().map(|replacement| TextEdit {
+                range: line_index.range(fix.span),
+                new_text: replacement.clone(),
+            }),
+            confidence: fix.confidence,
+        })
+        .collect();
+    Diagnostic {
+        range: line_index.range(diagnostic.span.unwrap_or_else(|| Span::single(crate::lexer::Position::start(0)))),
+        severity: Some(severity),
+        code: Some(Value::String(diagnostic.code.to_string())),
+        source: Some(source.to_string()),
+        message: diagnostic.message.clone(),
+        related_information,
+        data: if fixes.is_empty() { None } else { serde_json::to_value(fixes).ok() },
     }
 }
 
 impl AnalysisCache {
-    /// Create a new analysis cache
+    /// Create a new analysis cache, as large as the default configuration allows
     pub fn new() -> Self {
+        Self::with_limit(LspConfig::default().cache_size_limit)
+    }
+
+    /// Create an analysis cache holding at most `limit` documents
+    pub fn with_limit(limit: usize) -> Self {
         Self {
             parsed_modules: HashMap::new(),
             symbol_tables: HashMap::new(),
+            limit,
             stats: CacheStats::default(),
         }
     }
 
-    /// Store analysis results in cache
+    /// Store analysis results in cache, evicting the oldest past the limit
     pub fn store_analysis(&mut self, uri: String, ast: Module, symbols: SymbolTable) {
         let now = std::time::Instant::now();
         self.parsed_modules.insert(uri.clone(), (ast, now));
         self.symbol_tables.insert(uri, (symbols, now));
+        self.evict_past_limit();
+    }
+
+    /// Hold at most `limit` documents from now on, evicting the oldest at once
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.evict_past_limit();
+    }
+
+    /// Evict the documents stored longest ago until no more than the limit are left
+    fn evict_past_limit(&mut self) {
+        while self.parsed_modules.len() > self.limit {
+            let oldest = match self.parsed_modules.iter().min_by_key(|(_, (_, stored))| *stored) {
+                Some((uri, _)) => uri.clone(),
+                None => break,
+            };
+            self.remove(&oldest);
+            self.stats.evictions += 1;
+        }
     }
 
     /// Get cached diagnostics if available
@@ -451,6 +1405,11 @@ impl AnalysisCache {
     pub fn stats(&self) -> &CacheStats {
         &self.stats
     }
+
+    /// `metrics` with these statistics folded in
+    pub fn with_stats(&self, metrics: CompileMetrics) -> CompileMetrics {
+        CompileMetrics { cache: Some(self.stats.clone()), ..metrics }
+    }
 }
 
 impl Document {
@@ -469,9 +1428,10 @@ mod tests {
     #[test]
     fn test_lsp_server_creation() {
         let server = LspServer::new();
-        assert!(server.capabilities.text_document_sync.open_close);
-        assert!(server.capabilities.diagnostic_provider.is_some());
-        assert!(server.capabilities.completion_provider.is_some());
+        let capabilities = server.capabilities();
+        assert!(capabilities.text_document_sync.open_close);
+        assert!(capabilities.diagnostic_provider.is_some());
+        assert!(capabilities.completion_provider.is_some());
     }
 
     #[test]
@@ -502,14 +1462,315 @@ mod tests {
         assert_eq!(cache.stats().misses, 0);
     }
 
+    #[test]
+    fn test_analysis_cache_is_bounded() {
+        let config = LspConfig { cache_size_limit: 2, ..LspConfig::default() };
+        let server = LspServer::with_config(config.clone());
+        let uris = ["file:///a.bract", "file:///b.bract", "file:///c.bract"];
+        for uri in uris {
+            server.update_document(uri.to_string(), "fn main() -> i32 { return 0; }".to_string(), 1).unwrap();
+            server.analyze_document(uri).unwrap();
+        }
+
+        // The document stored first makes room for the last
+        let cache = server.lock_cache();
+        assert_eq!(cache.stats().evictions, 1);
+        assert!(!cache.parsed_modules.contains_key(uris[0]) && !cache.symbol_tables.contains_key(uris[0]));
+        assert!(cache.parsed_modules.contains_key(uris[2]));
+        drop(cache);
+
+        // Lowering the limit trims the cache at once
+        server.update_config(LspConfig { cache_size_limit: 1, ..config });
+        let cache = server.lock_cache();
+        assert_eq!((cache.parsed_modules.len(), cache.stats().evictions), (1, 2));
+        assert!(cache.parsed_modules.contains_key(uris[2]));
+    }
+
+    #[test]
+    fn test_analyses_are_timed_per_document() {
+        let server = LspServer::new();
+        let (first, second) = ("file:///first.bract", "file:///second.bract");
+        server.update_document(first.to_string(), "fn main() -> i32 { return 0; }".to_string(), 1).unwrap();
+        server.update_document(second.to_string(), "fn f() {}\nfn g() {}".to_string(), 1).unwrap();
+        assert_eq!(server.document_metrics(first), None);
+        server.analyze_document(first).unwrap();
+        server.analyze_document(first).unwrap();
+        server.analyze_document(second).unwrap();
+
+        let cache = server.lock_cache();
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.hit_rate()), (0, 3, Some(0.0)));
+        assert_eq!(stats.documents[first].analyses, 2);
+        assert_eq!(stats.documents[first].total, stats.total_analysis_time - stats.documents[second].total);
+        let slowest: Vec<_> = stats.slowest_documents(1).into_iter().map(|(uri, _)| uri).collect();
+        assert_eq!(slowest.len(), 1);
+        drop(cache);
+
+        let metrics = server.document_metrics(second).expect("metrics of the analyzed document");
+        assert_eq!((metrics.items_parsed, metrics.functions_analyzed), (2, 2));
+        assert_eq!(metrics.cache.map(|cache| cache.misses), Some(3));
+    }
+
     #[test]
     fn test_diagnostic_creation() {
         let server = LspServer::new();
-        let diagnostic = server.error_to_diagnostic("Test error".to_string());
+        let uri = "file:///broken.bract".to_string();
+        server.update_document(uri.clone(), "fn main() -> i32 {\n    let x = ;\n}".to_string(), 1).unwrap();
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        let diagnostic = diagnostics.first().expect("parse error");
         
-        assert_eq!(diagnostic.message, "Test error");
         assert!(matches!(diagnostic.severity, Some(DiagnosticSeverity::Error)));
         assert_eq!(diagnostic.source, Some("Bract".to_string()));
+        assert_eq!((diagnostic.range.start.line, diagnostic.range.start.character), (1, 12));
+        
+        // Same diagnostics as the library reports
+        let compiled = crate::compile_source(&server.get_document(&uri).unwrap().unwrap().content, "broken.bract", &crate::CompileOptions::default());
+        assert_eq!(diagnostics.iter().map(|d| d.message.as_str()).collect::<Vec<_>>(), compiled.diagnostics.iter().map(|d| d.message.as_str()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_performance_diagnostics() {
+        let config = LspConfig {
+            enable_performance_diagnostics: true,
+            ..LspConfig::default()
+        };
+        let server = LspServer::with_config(config);
+        let uri = "file:///perf.bract".to_string();
+        let content = "@performance(max_cost = 1)\nfn square(x: i32) -> i32 { return x * x; }\n".to_string();
+        server.update_document(uri.clone(), content, 1).unwrap();
+
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        let violation = diagnostics.iter()
+            .find(|d| d.code == Some(Value::String("E0401".to_string())))
+            .expect("cycle bound is exceeded");
+        assert!(matches!(violation.severity, Some(DiagnosticSeverity::Error)));
+        assert_eq!(violation.source.as_deref(), Some(PERFORMANCE_DIAGNOSTIC_SOURCE));
+        assert!(violation.message.contains("expected 1 cycles"), "{}", violation.message);
+        assert_eq!(violation.range.start.line, 0);
+
+        // Off by default
+        let server = LspServer::new();
+        server.update_document(uri.clone(), "@performance(max_cost = 1)\nfn f() -> i32 { return 1 + 2; }".to_string(), 1).unwrap();
+        assert!(server.analyze_document(&uri).unwrap().iter().all(|d| d.source.as_deref() != Some(PERFORMANCE_DIAGNOSTIC_SOURCE)));
+    }
+
+    #[test]
+    fn test_unused_symbol_diagnostics() {
+        let server = LspServer::new();
+        let uri = "file:///unused.bract".to_string();
+        let content = "fn helper(x: i32) -> i32 { return 1; }\nfn main() -> i32 {\n    let y = 2;\n    return helper(3);\n}\n";
+        server.update_document(uri.clone(), content.to_string(), 1).unwrap();
+
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        let semantic: Vec<_> = diagnostics.iter()
+            .filter(|d| d.source.as_deref() == Some(SEMANTIC_DIAGNOSTIC_SOURCE))
+            .collect();
+        assert_eq!(semantic.len(), 2, "{:?}", semantic);
+
+        assert_eq!(semantic[0].code, Some(Value::String("W0103".to_string())));
+        assert!(matches!(semantic[0].severity, Some(DiagnosticSeverity::Hint)));
+        assert_eq!((semantic[0].range.start.line, semantic[0].range.start.character), (0, 10));
+
+        assert_eq!(semantic[1].code, Some(Value::String("W0102".to_string())));
+        assert!(matches!(semantic[1].severity, Some(DiagnosticSeverity::Warning)));
+        assert!(semantic[1].message.contains("`_y`"), "{}", semantic[1].message);
+        assert_eq!(semantic[1].range.start.line, 2);
+    }
+
+    #[test]
+    fn test_performance_diagnostics_respect_time_budget() {
+        let config = LspConfig {
+            enable_performance_diagnostics: true,
+            max_analysis_time: 0,
+            ..LspConfig::default()
+        };
+        let server = LspServer::with_config(config);
+        let uri = "file:///slow.bract".to_string();
+        server.update_document(uri.clone(), "fn main() -> i32 { return 0; }".to_string(), 1).unwrap();
+
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(diagnostics[0].severity, Some(DiagnosticSeverity::Information)));
+        assert!(diagnostics[0].message.contains("skipped"), "{}", diagnostics[0].message);
+    }
+
+    #[test]
+    fn test_document_interner_resolves_names() {
+        let server = LspServer::new();
+        let uri = "file:///names.bract".to_string();
+        server.update_document(uri.clone(), "fn first() -> i32 { return 1; }".to_string(), 1).unwrap();
+        server.analyze_document(&uri).unwrap();
+
+        let document = server.get_document(&uri).unwrap().unwrap();
+        let name = match document.ast.as_ref().map(|ast| &ast.items[0]) {
+            Some(crate::ast::Item::Function { name, .. }) => *name,
+            other => panic!("Expected analyzed function, got {:?}", other),
+        };
+        assert_eq!(document.interner.resolve(name).as_deref(), Some("first"));
+
+        // Edits keep the interner, so handles from earlier versions stay valid
+        server.update_document(uri.clone(), "fn second() -> i32 { return 2; }".to_string(), 2).unwrap();
+        server.analyze_document(&uri).unwrap();
+        let document = server.get_document(&uri).unwrap().unwrap();
+        assert_eq!(document.interner.resolve(name).as_deref(), Some("first"));
+        assert!(document.ast.is_some());
+    }
+
+    #[test]
+    fn test_hover_shows_doc_comments() {
+        let server = LspServer::new();
+        let uri = "file:///docs.bract".to_string();
+        let content = "/// Squares a number.\nfn square(x: i32) -> i32 { return x * x; }\nfn main() -> i32 { return square(3); }";
+        server.update_document(uri.clone(), content.to_string(), 1).unwrap();
+
+        // Hovering a use of the function shows its declaration and documentation
+        let hover = server.hover(&uri, &Position { line: 2, character: 28 }).unwrap().expect("hover for square");
+        assert_eq!(hover.contents.kind, "markdown");
+        assert_eq!(hover.contents.value, "```bract\nfn square\n```\n\nSquares a number.");
+
+        // Undocumented items show just the declaration; other words show nothing
+        let hover = server.hover(&uri, &Position { line: 2, character: 4 }).unwrap().expect("hover for main");
+        assert_eq!(hover.contents.value, "```bract\nfn main\n```");
+        assert!(server.hover(&uri, &Position { line: 1, character: 10 }).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_resolve_completion_imports_from_other_documents() {
+        let server = LspServer::new();
+        let math = "file:///project/math.bract".to_string();
+        let main = "file:///project/main.bract".to_string();
+        server.update_document(math.clone(), "/// Squares a number.\npub fn square(x: i32) -> i32 { return x * x; }\n".to_string(), 1).unwrap();
+        server.update_document(main.clone(), "fn main() -> i32 {\n    return squ\n}\n".to_string(), 1).unwrap();
+        server.analyze_document(&math).unwrap();
+        server.analyze_document(&main).unwrap();
+
+        let position = Position { line: 1, character: 14 };
+        let document = server.get_document(&main).unwrap().unwrap();
+        let context = completion::create_completion_context(main.clone(), position.clone(), &document).unwrap();
+        let items = CompletionProvider::new().provide_completions(&server, &main, &position, &context).unwrap();
+        let item = items.into_iter().find(|item| item.label == "square").expect("square from math.bract");
+        assert!(item.documentation.is_none() && item.additional_text_edits.is_none());
+
+        let resolved = server.resolve_completion(item.clone()).unwrap();
+        assert_eq!(resolved.detail.as_deref(), Some("fn(i32) -> i32"));
+        assert_eq!(resolved.documentation.as_deref(), Some("Squares a number."));
+        let edits = resolved.additional_text_edits.expect("import edit");
+        assert_eq!(edits[0].new_text, "use math::square;\n\n");
+        assert_eq!((edits[0].range.start.line, edits[0].range.start.character), (0, 0));
+
+        // Once main.bract changes the item is handed back as it was
+        server.update_document(main.clone(), "fn main() -> i32 {\n    return square(2);\n}\n".to_string(), 2).unwrap();
+        let stale = server.resolve_completion(item).unwrap();
+        assert!(stale.documentation.is_none() && stale.additional_text_edits.is_none());
+    }
+
+    #[test]
+    fn test_server_survives_panicking_requests() {
+        let server = LspServer::new();
+        let uri = "file:///panic.bract".to_string();
+        server.update_document(uri.clone(), "fn main() -> i32 { return 0; }".to_string(), 1).unwrap();
+        server.analyze_document(&uri).unwrap();
+
+        // The request panics while holding both locks
+        let error = server.catch_panic(&uri, || -> Result<(), String> {
+            let _documents = server.documents.lock();
+            let _cache = server.analysis_cache.lock();
+            panic!("analysis exploded");
+        }).unwrap_err();
+        assert_eq!(error, "Internal error handling file:///panic.bract: analysis exploded");
+        assert!(server.documents.is_poisoned() && server.analysis_cache.is_poisoned());
+
+        // Later requests are still answered, from the documents the panic left behind
+        assert!(server.analyze_document(&uri).unwrap().is_empty());
+        assert_eq!(server.folding_ranges(&uri).unwrap().len(), 0);
+        server.update_document(uri.clone(), "fn main() -> i32 { return 1; }".to_string(), 2).unwrap();
+        assert_eq!(server.get_document(&uri).unwrap().unwrap().version, 2);
+        assert!(!server.documents.is_poisoned() && !server.analysis_cache.is_poisoned());
+    }
+
+    #[test]
+    fn test_workspace_index_covers_unopened_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |path: &str, source: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        };
+        write("math.bract", "pub fn helper(x: i32) -> i32 { return x; }\n");
+        write("geo/shapes.bract", "pub fn area() -> i32 { return helper(2); }\n");
+        write("target/stale.bract", "pub fn helper() -> i32 { return 0; }\n");
+        let uri = |path: &str| workspace::path_to_uri(&dir.path().join(path));
+
+        let server = LspServer::new();
+        server.set_workspace_folders(&[workspace::path_to_uri(dir.path())]);
+        assert_eq!(server.index_workspace(&CancellationToken::new()), 2);
+        let symbols = server.workspace_symbols("HELP");
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].location.uri, uri("math.bract"));
+
+        // Going to `helper` from a file the editor never opened the declaration of
+        let main = uri("main.bract");
+        server.update_document(main.clone(), "fn main() -> i32 {\n    return helper(1);\n}\n".to_string(), 1).unwrap();
+        let definitions = server.definition(&main, &Position { line: 1, character: 12 }).unwrap();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].uri, uri("math.bract"));
+        assert_eq!((definitions[0].range.start.line, definitions[0].range.start.character), (0, 7));
+
+        let references = server.references(&main, &Position { line: 1, character: 12 }, false, &CancellationToken::new()).unwrap();
+        let files: Vec<&str> = references.iter().map(|location| location.uri.as_str()).collect();
+        assert_eq!(files, vec![uri("geo/shapes.bract"), main.clone()]);
+        assert_eq!(server.references(&main, &Position { line: 1, character: 12 }, true, &CancellationToken::new()).unwrap().len(), 3);
+
+        // Opening an indexed file replaces its summary rather than adding to it
+        let math = uri("math.bract");
+        server.update_document(math.clone(), "pub fn helper_v2() -> i32 { return 0; }\n".to_string(), 1).unwrap();
+        server.analyze_document(&math).unwrap();
+        assert!(server.workspace_symbols("helper").iter().all(|symbol| symbol.name == "helper_v2"));
+
+        // Deleting a file on disk drops it from the index
+        std::fs::remove_file(dir.path().join("geo/shapes.bract")).unwrap();
+        server.watched_files_changed(&[FileEvent { uri: uri("geo/shapes.bract"), change: FileChangeType::Deleted }]);
+        assert!(server.workspace_symbols("area").is_empty());
+    }
+
+    #[test]
+    fn test_cancelled_requests_return_promptly() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..200 {
+            let source = format!("pub fn use_{}() -> i32 {{ return helper({}); }}\n", i, i);
+            std::fs::write(dir.path().join(format!("file_{}.bract", i)), source).unwrap();
+        }
+        let server = LspServer::new();
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let recorded = sent.clone();
+        server.set_progress_sink(ProgressSink::new(move |params| recorded.lock().unwrap().push(params.value)));
+        server.set_workspace_folders(&[workspace::path_to_uri(dir.path())]);
+        assert_eq!(server.index_workspace(&CancellationToken::new()), 200);
+
+        // Indexing counts its files from 0% up, then ends
+        let sent = sent.lock().unwrap().clone();
+        assert!(matches!(sent.first(), Some(WorkDoneProgress::Begin { percentage: Some(0), .. })));
+        assert!(matches!(sent.last(), Some(WorkDoneProgress::End { .. })));
+        assert_eq!(sent.len(), 201);
+
+        let main = workspace::path_to_uri(&dir.path().join("main.bract"));
+        server.update_document(main.clone(), "pub fn helper(x: i32) -> i32 {\n    return x;\n}\n".to_string(), 1).unwrap();
+        let position = Position { line: 0, character: 9 };
+        let all = server.references(&main, &position, true, &CancellationToken::new()).unwrap();
+        assert_eq!(all.len(), 201);
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let started = std::time::Instant::now();
+        assert_eq!(server.references(&main, &position, true, &cancel), Err(RequestError::Cancelled));
+        assert!(matches!(server.analyze_document_cancellable(&main, &cancel), Err(RequestError::Cancelled)));
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+        // Cancelled requests keep nothing, and leave the index as it was
+        assert!(server.get_document(&main).unwrap().unwrap().ast.is_none());
+        assert_eq!(server.references(&main, &position, true, &CancellationToken::new()).unwrap(), all);
+        assert!(server.analyze_document(&main).unwrap().is_empty());
+        assert!(server.get_document(&main).unwrap().unwrap().ast.is_some());
     }
 
     #[test]
@@ -521,5 +1782,221 @@ mod tests {
         assert!(capabilities.completion_provider.is_some());
         assert!(capabilities.hover_provider.unwrap_or(false));
         assert!(capabilities.definition_provider.unwrap_or(false));
+        assert!(capabilities.selection_range_provider.unwrap_or(false));
     }
-} 
+
+    #[test]
+    fn test_disabled_features_are_neither_advertised_nor_answered() {
+        let config = LspConfig {
+            enable_diagnostics: false,
+            enable_completion: false,
+            enable_navigation: false,
+            ..LspConfig::default()
+        };
+        let capabilities = ServerCapabilities::for_config(&config);
+        assert!(capabilities.diagnostic_provider.is_none() && capabilities.completion_provider.is_none());
+        assert_eq!(capabilities.definition_provider, Some(false));
+        assert_eq!(capabilities.workspace_symbol_provider, Some(false));
+        assert_eq!(capabilities.hover_provider, Some(true));
+
+        let server = LspServer::with_config(config);
+        let uri = "file:///quiet.bract".to_string();
+        let content = "fn helper() -> i32 { return 1; }\nfn main() -> i32 {\n    let x = ;\n    return hel\n}\n";
+        server.update_document(uri.clone(), content.to_string(), 1).unwrap();
+        assert!(server.analyze_document(&uri).unwrap().is_empty());
+        // The document is still analyzed for the features left on
+        assert!(server.get_document(&uri).unwrap().unwrap().ast.is_some());
+        assert!(server.hover(&uri, &Position { line: 0, character: 4 }).unwrap().is_some());
+
+        let position = Position { line: 3, character: 14 };
+        assert!(server.definition(&uri, &position).unwrap().is_empty());
+        assert!(server.references(&uri, &position, true, &CancellationToken::new()).unwrap().is_empty());
+        assert!(server.workspace_symbols("helper").is_empty());
+        let document = server.get_document(&uri).unwrap().unwrap();
+        let context = completion::create_completion_context(uri.clone(), position.clone(), &document).unwrap();
+        assert!(CompletionProvider::new().provide_completions(&server, &uri, &position, &context).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_configuration_changes_at_runtime() {
+        let server = LspServer::new();
+        server.set_dynamic_registration(vec!["textDocument/completion", "textDocument/diagnostic"]);
+
+        // Capabilities registered dynamically are left out of those advertised
+        let capabilities = server.capabilities();
+        assert!(capabilities.completion_provider.is_none() && capabilities.diagnostic_provider.is_none());
+        assert_eq!(capabilities.definition_provider, Some(true));
+        let registrations = server.registrations();
+        let methods: Vec<&str> = registrations.iter().map(|registration| registration.method.as_str()).collect();
+        assert_eq!(methods, vec!["textDocument/completion", "textDocument/diagnostic"]);
+        assert_eq!(registrations[0].register_options["triggerCharacters"], serde_json::json!([".", "::"]));
+
+        let uri = "file:///settings.bract".to_string();
+        server.update_document(uri.clone(), "fn main() -> i32 {\n    let x = ;\n}".to_string(), 1).unwrap();
+        assert!(!server.analyze_document(&uri).unwrap().is_empty());
+
+        let settings = serde_json::json!({ "bract": { "enableDiagnostics": false, "enableNavigation": false, "cacheSizeLimit": 0 } });
+        let (config, warnings) = settings::apply_settings(&server.config(), &settings);
+        assert_eq!(warnings.len(), 1);
+        let update = server.update_config(config);
+        assert!(update.register.is_empty());
+        assert_eq!(update.unregister, vec![Unregistration {
+            id: "textDocument/diagnostic".to_string(),
+            method: "textDocument/diagnostic".to_string(),
+        }]);
+        // Navigation was advertised at initialization, so only a restart takes it back
+        assert_eq!(update.needs_restart, vec!["textDocument/definition", "textDocument/references", "textDocument/documentSymbol", "workspace/symbol"]);
+        assert!(server.analyze_document(&uri).unwrap().is_empty());
+        assert_eq!(server.config().cache_size_limit, LspConfig::default().cache_size_limit);
+
+        // Turning diagnostics back on registers them again
+        let update = server.update_config(LspConfig { enable_diagnostics: true, ..(*server.config()).clone() });
+        assert_eq!(update.register.iter().map(|registration| registration.method.as_str()).collect::<Vec<_>>(), vec!["textDocument/diagnostic"]);
+        assert!(update.unregister.is_empty() && update.needs_restart.is_empty());
+        assert!(server.update_config((*server.config()).clone()).is_empty());
+    }
+
+    #[test]
+    fn test_selection_ranges_per_position() {
+        let server = LspServer::new();
+        let uri = "file:///select.bract".to_string();
+        server.update_document(uri.clone(), "fn main() -> i32 {\n    return 1 + 2;\n}\n".to_string(), 1).unwrap();
+
+        let positions = vec![Position { line: 1, character: 11 }, Position { line: 3, character: 0 }];
+        let selections = server.selection_ranges(&uri, positions).unwrap();
+        assert_eq!(selections.len(), 2);
+        let first = selections[0].ranges();
+        assert_eq!((first[0].start.character, first[0].end.character), (11, 12));
+        assert_eq!((first[1].start.character, first[1].end.character), (11, 16));
+        // Past the last item only the document is left
+        assert!(selections[1].parent.is_none());
+        assert!(server.selection_ranges("file:///missing.bract", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_memory_report_marks_leaks() {
+        let server = LspServer::new();
+        let uri = "file:///project/leaky.bract".to_string();
+        let content = "struct Point { x: i32, y: i32 }\n\
+                       @manual\n\
+                       fn leaky() -> i32 {\n    let p = Point { x: 1, y: 2 };\n    return 0;\n}\n\
+                       fn main() -> i32 { return leaky(); }\n";
+        server.update_document(uri.clone(), content.to_string(), 1).unwrap();
+
+        let report = server.memory_report(&uri, &CancellationToken::new()).unwrap();
+        let manual = report.allocations.iter().find(|allocations| allocations.strategy == "Manual").unwrap();
+        assert_eq!(manual.count, 1);
+        assert_eq!(report.leaks.len(), 1);
+        let leak = &report.leaks[0];
+        assert_eq!((leak.range.start.line, leak.range.start.character), (3, 12));
+        assert_eq!(leak.code, Some(Value::String("E0239".to_string())));
+        assert_eq!(leak.source.as_deref(), Some(MEMORY_DIAGNOSTIC_SOURCE));
+        assert_eq!(report.hotspots[0].location, "leaky.bract:4:13 (in leaky)");
+        assert!(report.reports.leaks.contains("fn leaky"), "{}", report.reports.leaks);
+
+        // A document that does not compile has no report
+        server.update_document(uri.clone(), "fn main() -> i32 { return x; }".to_string(), 2).unwrap();
+        let error = server.memory_report(&uri, &CancellationToken::new()).unwrap_err();
+        assert!(error.to_string().starts_with("Memory report unavailable: "), "{}", error);
+    }
+
+    #[test]
+    fn test_ownership_diagnostics() {
+        let server = LspServer::new();
+        let uri = "file:///moves.bract".to_string();
+        let content = "struct Buffer { size: i32 }\n\
+                       fn consume(buffer: Buffer) -> i32 { return buffer.size; }\n\
+                       fn main() -> i32 {\n    let buffer = Buffer { size: 1 };\n    consume(buffer);\n    return consume(buffer);\n}\n";
+        server.update_document(uri.clone(), content.to_string(), 1).unwrap();
+
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        let moved = diagnostics.iter()
+            .find(|d| d.code == Some(Value::String("E0230".to_string())))
+            .expect("second call uses the moved buffer");
+        assert!(matches!(moved.severity, Some(DiagnosticSeverity::Error)));
+        assert_eq!(moved.message, "use of moved value `buffer`");
+        assert_eq!((moved.range.start.line, moved.range.start.character), (5, 19));
+
+        // The move that made the use invalid comes along as related information
+        let related = moved.related_information.as_ref().expect("move location");
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.uri, uri);
+        assert_eq!((related[0].location.range.start.line, related[0].location.range.start.character), (4, 12));
+        assert!(related[0].message.starts_with("value moved here"), "{}", related[0].message);
+    }
+
+    #[test]
+    fn test_call_diagnostics_point_at_the_parameter() {
+        let server = LspServer::new();
+        let uri = "file:///call.bract".to_string();
+        let content = "fn scale(value: i32, factor: i32) -> i32 { return value * factor; }\n\
+                       fn main() -> i32 { return scale(2, 1.5); }\n";
+        server.update_document(uri.clone(), content.to_string(), 1).unwrap();
+
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        let mismatches: Vec<_> = diagnostics.iter()
+            .filter(|d| d.code == Some(Value::String(DiagnosticCode::TypeMismatch.to_string())))
+            .collect();
+        assert_eq!(mismatches.len(), 1, "{:?}", diagnostics);
+        assert_eq!((mismatches[0].range.start.line, mismatches[0].range.start.character), (1, 35));
+
+        let related = mismatches[0].related_information.as_ref().expect("parameter location");
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.uri, uri);
+        assert_eq!((related[0].location.range.start.line, related[0].location.range.start.character), (0, 21));
+        assert_eq!(related[0].message, "parameter declared here");
+    }
+
+    #[test]
+    fn test_positions_count_utf16_units_after_wide_characters() {
+        let server = LspServer::new();
+        let uri = "file:///wide.bract".to_string();
+        let content = "fn scale(value: i32, factor: i32) -> i32 { return value * factor; }\n\
+                       fn main() -> i32 { /* 😀é */ return scale(2, 1.5); }\n";
+        server.update_document(uri.clone(), content.to_string(), 1).unwrap();
+
+        // The emoji is two units and 'é' one, though they take six bytes
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        let mismatch = diagnostics.iter()
+            .find(|d| d.code == Some(Value::String(DiagnosticCode::TypeMismatch.to_string())))
+            .expect("type mismatch");
+        assert_eq!((mismatch.range.start.line, mismatch.range.start.character), (1, 45));
+        let hover = server.hover(&uri, &Position { line: 1, character: 38 }).unwrap().expect("hover for scale");
+        assert_eq!(hover.contents.value, "```bract\nfn scale\n```");
+        let definition = server.definition(&uri, &Position { line: 1, character: 41 }).unwrap();
+        assert_eq!(definition.len(), 1);
+        assert_eq!((definition[0].range.start.line, definition[0].range.start.character), (0, 3));
+
+        // An edit removing them rebuilds the line index with the document
+        server.update_document(uri.clone(), content.replace("/* 😀é */ ", ""), 2).unwrap();
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        assert!(diagnostics.iter().any(|d| (d.range.start.line, d.range.start.character) == (1, 35)), "{:?}", diagnostics);
+        assert!(server.hover(&uri, &Position { line: 1, character: 38 }).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_code_action_inserts_missing_semicolon() {
+        let server = LspServer::new();
+        let uri = "file:///semicolon.bract".to_string();
+        server.update_document(uri.clone(), "fn main() -> i32 {\n    let x = 1\n    return x;\n}".to_string(), 1).unwrap();
+        let diagnostics = server.analyze_document(&uri).unwrap();
+        let missing = diagnostics.first().expect("missing semicolon");
+
+        let actions = server.code_actions(&uri, &missing.range, &diagnostics).unwrap();
+        let insert = actions.iter().find(|action| action.title == "Insert the missing semicolon").expect("semicolon fix");
+        assert_eq!(insert.kind.as_deref(), Some("quickfix"));
+        assert_eq!(insert.is_preferred, Some(true));
+        let edits = &insert.edit.as_ref().expect("edit").changes[&uri];
+        assert_eq!(edits, &vec![TextEdit {
+            range: Range { start: Position { line: 1, character: 13 }, end: Position { line: 1, character: 13 } },
+            new_text: ";".to_string(),
+        }]);
+
+        // Clients that drop `data` still get the fix, and actions outside the range are left out
+        let stripped: Vec<Diagnostic> = diagnostics.iter().map(|d| Diagnostic { data: None, ..d.clone() }).collect();
+        let actions = server.code_actions(&uri, &missing.range, &stripped).unwrap();
+        assert!(actions.iter().any(|action| action.title == "Insert the missing semicolon"));
+        let elsewhere = Range { start: Position { line: 3, character: 0 }, end: Position { line: 3, character: 1 } };
+        assert!(server.code_actions(&uri, &elsewhere, &diagnostics).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,259 @@
+//! Work-Done Progress and Cancellation for Bract LSP
+//!
+//! Long operations report `$/progress` notifications through a `ProgressTracker`,
+//! which can hold back its `begin` until the work has taken long enough to be worth
+//! showing. Requests that may run long take a `CancellationToken`, set when the
+//! client sends `$/cancelRequest`, and check it between units of work: files of the
+//! workspace, functions of a module.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long a document analysis runs before it shows progress
+pub const ANALYSIS_PROGRESS_DELAY: Duration = Duration::from_millis(500);
+
+/// How often a request waiting on work it cannot interrupt checks for cancellation
+pub const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Shared flag telling a request to stop
+///
+/// Clones share the flag, so the token kept by whoever cancels and the one the
+/// request checks are the same.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// A token not cancelled yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the request holding this token to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the request was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// `Err(RequestError::Cancelled)` once the request was cancelled
+    pub fn check(&self) -> Result<(), RequestError> {
+        if self.is_cancelled() {
+            return Err(RequestError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// The flag itself, for analyses outside the LSP layer
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.0.clone()
+    }
+}
+
+/// Why a cancellable request has no result
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestError {
+    /// The client cancelled the request
+    Cancelled,
+    /// The request failed, and the message says why
+    Failed(String),
+}
+
+impl From<String> for RequestError {
+    fn from(message: String) -> Self {
+        RequestError::Failed(message)
+    }
+}
+
+impl From<&str> for RequestError {
+    fn from(message: &str) -> Self {
+        RequestError::Failed(message.to_string())
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Cancelled => write!(f, "Request cancelled"),
+            RequestError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+/// Params of a `$/progress` notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgressParams {
+    /// Token the client was asked to create with `window/workDoneProgress/create`
+    pub token: String,
+    pub value: WorkDoneProgress,
+}
+
+/// Step of a work-done progress
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum WorkDoneProgress {
+    Begin {
+        title: String,
+        cancellable: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        percentage: Option<u32>,
+    },
+    Report {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        percentage: Option<u32>,
+    },
+    End {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+    },
+}
+
+/// Where the server's progress notifications go
+#[derive(Clone)]
+pub struct ProgressSink(Arc<dyn Fn(ProgressParams) + Send + Sync>);
+
+impl ProgressSink {
+    /// A sink passing each notification to `send`
+    pub fn new(send: impl Fn(ProgressParams) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(send))
+    }
+
+    fn send(&self, params: ProgressParams) {
+        (self.0)(params)
+    }
+}
+
+impl fmt::Debug for ProgressSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressSink")
+    }
+}
+
+/// Progress of one operation, from `begin` to `end`
+///
+/// Nothing is sent until the operation has run for the tracker's delay; the
+/// first report after that begins the progress. A progress that began ends when
+/// the tracker is finished or dropped, so an operation returning early, cancelled
+/// or failed, still closes it.
+#[derive(Debug)]
+pub struct ProgressTracker {
+    sink: Option<ProgressSink>,
+    token: String,
+    title: String,
+    started: Instant,
+    delay: Duration,
+    begun: bool,
+}
+
+impl ProgressTracker {
+    /// Track the operation `title` as `token`, shown once it has run for `delay`
+    pub fn new(sink: Option<ProgressSink>, token: String, title: &str, delay: Duration) -> Self {
+        Self {
+            sink,
+            token,
+            title: title.to_string(),
+            started: Instant::now(),
+            delay,
+            begun: false,
+        }
+    }
+
+    /// Report `message`, with `done` of `total` where the work can be counted
+    pub fn report(&mut self, message: Option<String>, count: Option<(usize, usize)>) {
+        let Some(sink) = &self.sink else { return };
+        let percentage = count.map(|(done, total)| match total {
+            0 => 100,
+            total => (done.min(total) * 100 / total) as u32,
+        });
+        let value = if self.begun {
+            WorkDoneProgress::Report { message, percentage }
+        } else if self.started.elapsed() >= self.delay {
+            self.begun = true;
+            WorkDoneProgress::Begin { title: self.title.clone(), cancellable: false, message, percentage }
+        } else {
+            return;
+        };
+        sink.send(ProgressParams { token: self.token.clone(), value });
+    }
+
+    /// End the progress with `message`, if it began
+    pub fn finish(mut self, message: Option<String>) {
+        self.end(message);
+    }
+
+    fn end(&mut self, message: Option<String>) {
+        if let (Some(sink), true) = (&self.sink, self.begun) {
+            self.begun = false;
+            sink.send(ProgressParams { token: self.token.clone(), value: WorkDoneProgress::End { message } });
+        }
+    }
+}
+
+impl Drop for ProgressTracker {
+    fn drop(&mut self) {
+        self.end(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn recording_sink() -> (ProgressSink, Arc<Mutex<Vec<ProgressParams>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let recorded = sent.clone();
+        (ProgressSink::new(move |params| recorded.lock().unwrap().push(params)), sent)
+    }
+
+    #[test]
+    fn test_progress_begins_with_first_report_and_ends_on_drop() {
+        let (sink, sent) = recording_sink();
+        let mut tracker = ProgressTracker::new(Some(sink), "t".to_string(), "Indexing", Duration::ZERO);
+        tracker.report(Some("1/4 files".to_string()), Some((1, 4)));
+        tracker.report(None, Some((3, 4)));
+        drop(tracker);
+
+        let values: Vec<_> = sent.lock().unwrap().iter().map(|params| params.value.clone()).collect();
+        assert_eq!(values, vec![
+            WorkDoneProgress::Begin {
+                title: "Indexing".to_string(),
+                cancellable: false,
+                message: Some("1/4 files".to_string()),
+                percentage: Some(25),
+            },
+            WorkDoneProgress::Report { message: None, percentage: Some(75) },
+            WorkDoneProgress::End { message: None },
+        ]);
+    }
+
+    #[test]
+    fn test_quick_operations_send_nothing() {
+        let (sink, sent) = recording_sink();
+        let mut tracker = ProgressTracker::new(Some(sink), "t".to_string(), "Analyzing", Duration::from_secs(60));
+        tracker.report(Some("Parsing".to_string()), None);
+        tracker.finish(Some("Done".to_string()));
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancellation_is_shared_by_clones() {
+        let token = CancellationToken::new();
+        let request = token.clone();
+        assert_eq!(request.check(), Ok(()));
+        token.cancel();
+        assert_eq!(request.check(), Err(RequestError::Cancelled));
+        assert_eq!(RequestError::Cancelled.to_string(), "Request cancelled");
+    }
+}
@@ -0,0 +1,281 @@
+//! Selection Range Support for Bract LSP
+//!
+//! Expanding a selection walks out through the AST nodes whose spans hold the
+//! cursor: the identifier or literal under it, then each enclosing expression,
+//! statement and item, and finally the whole document. Each span is cut back to
+//! the end of its last token, so no selection takes in trailing whitespace.
+
+use super::semantic_tokens::{chars_in_utf16, utf16_len};
+use super::{Position, Range};
+use crate::ast::{Expr, ImplItem, Item, Module, Pattern, Span, Stmt, Type};
+use crate::lexer::{Lexer, Position as SourcePosition, TokenType};
+use crate::visitor::{self, Visitor};
+use serde::{Deserialize, Serialize};
+
+/// LSP selection range, linked to the range that encloses it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectionRange {
+    /// Range selected at this step
+    pub range: Range,
+    /// Next, larger range; the whole document has none
+    pub parent: Option<Box<SelectionRange>>,
+}
+
+impl SelectionRange {
+    /// Ranges of this chain, innermost first
+    pub fn ranges(&self) -> Vec<Range> {
+        std::iter::successors(Some(self), |selection| selection.parent.as_deref())
+            .map(|selection| selection.range.clone())
+            .collect()
+    }
+}
+
+/// Selection range chain at `position` in `source`, through the nodes of `module`
+///
+/// Each range strictly contains the one before it. A cursor just after a node
+/// still selects it. Without a module, or between items, the chain is the token
+/// under the cursor, if any, and then the whole document.
+pub fn selection_range(module: Option<&Module>, source: &str, position: &Position) -> SelectionRange {
+    let lines: Vec<&str> = source.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).collect();
+    let line_text = lines.get(position.line as usize).copied().unwrap_or_default();
+    let cursor = (position.line as usize + 1, chars_in_utf16(line_text, position.character) + 1);
+    let contains = |start: &SourcePosition, end: &SourcePosition| {
+        (start.line, start.column) <= cursor && cursor <= (end.line, end.column)
+    };
+
+    let mut collector = SpanCollector { spans: Vec::new() };
+    if let Some(module) = module {
+        collector.visit_module(module);
+    }
+
+    // Tokens that are not comments, in source order, and the word under the cursor
+    let mut tokens: Vec<(SourcePosition, SourcePosition, TokenType)> = Vec::new();
+    let mut candidates = Vec::new();
+    let mut lexer = Lexer::new_with_comments(source, 0).with_error_recovery();
+    while let Ok(token) = lexer.next_token() {
+        let end = lexer.get_position();
+        match token.token_type {
+            TokenType::Eof => break,
+            TokenType::LineComment(_)
+            | TokenType::BlockComment(_)
+            | TokenType::DocLineComment(_)
+            | TokenType::DocBlockComment(_)
+            | TokenType::InnerDocLineComment(_)
+            | TokenType::InnerDocBlockComment(_) => continue,
+            TokenType::Identifier(_)
+            | TokenType::Integer { .. }
+            | TokenType::Float { .. }
+            | TokenType::String { .. }
+            | TokenType::Char(_)
+            | TokenType::Bool(_)
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Null if contains(&token.position, &end) => candidates.push((token.position, end)),
+            _ => {}
+        }
+        tokens.push((token.position, end, token.token_type));
+    }
+
+    candidates.extend(collector.spans.iter().filter_map(|span| {
+        let end = last_token_end(&tokens, span)?;
+        (end.offset > span.start.offset && contains(&span.start, &end)).then_some((span.start, end))
+    }));
+    candidates.sort_by_key(|(start, end)| end.offset - start.offset);
+
+    // Innermost first, each containing the last; siblings meeting at the cursor drop out
+    let mut chain: Vec<(SourcePosition, SourcePosition)> = Vec::new();
+    for (start, end) in candidates {
+        let encloses = chain.last().is_none_or(|(inner_start, inner_end)| {
+            start.offset <= inner_start.offset && inner_end.offset <= end.offset
+                && (start.offset, end.offset) != (inner_start.offset, inner_end.offset)
+        });
+        if encloses {
+            chain.push((start, end));
+        }
+    }
+
+    let to_lsp = |pos: &SourcePosition| {
+        let text = lines.get(pos.line.saturating_sub(1)).copied().unwrap_or_default();
+        Position {
+            line: pos.line.saturating_sub(1) as u32,
+            character: utf16_len(text, pos.column.saturating_sub(1)),
+        }
+    };
+    let last_line = lines.len().saturating_sub(1);
+    let document = Range {
+        start: Position { line: 0, character: 0 },
+        end: Position { line: last_line as u32, character: utf16_len(lines[last_line], usize::MAX) },
+    };
+    chain.iter().rev().fold(SelectionRange { range: document, parent: None }, |parent, (start, end)| {
+        let range = Range { start: to_lsp(start), end: to_lsp(end) };
+        if range == parent.range {
+            return parent;
+        }
+        SelectionRange { range, parent: Some(Box::new(parent)) }
+    })
+}
+
+/// End of the last token of `span`
+///
+/// Statements, items, patterns and types end where the next token starts, but
+/// most expressions end where their own last token starts. The token starting
+/// where `span` ends is taken as its own when the span is empty, ends on an
+/// operator still missing its operand, or leaves open a bracket that token closes.
+fn last_token_end(tokens: &[(SourcePosition, SourcePosition, TokenType)], span: &Span) -> Option<SourcePosition> {
+    let first = tokens.partition_point(|(start, _, _)| start.offset < span.start.offset);
+    let after = tokens.partition_point(|(start, _, _)| start.offset < span.end.offset).max(first);
+    let inside = &tokens[first..after];
+    let next = tokens.get(after).filter(|(start, _, _)| start.offset == span.end.offset);
+
+    let count = |kind: &TokenType| inside.iter().filter(|(_, _, token)| token == kind).count();
+    let takes_next = next.is_some_and(|(_, _, next)| {
+        let Some((_, _, last)) = inside.last() else { return true };
+        let opener = match next {
+            TokenType::RightParen => Some(TokenType::LeftParen),
+            TokenType::RightBracket => Some(TokenType::LeftBracket),
+            TokenType::RightBrace => Some(TokenType::LeftBrace),
+            TokenType::Greater => Some(TokenType::Less),
+            _ => None,
+        };
+        let leaves_open = opener.is_some_and(|opener| count(&opener) > count(next));
+        // A `>` closing generics is not waiting for an operand
+        let awaits_operand = match last {
+            TokenType::Greater => count(&TokenType::Less) == 0,
+            TokenType::Plus
+            | TokenType::Minus
+            | TokenType::Star
+            | TokenType::Slash
+            | TokenType::Percent
+            | TokenType::Caret
+            | TokenType::And
+            | TokenType::Or
+            | TokenType::Tilde
+            | TokenType::Not
+            | TokenType::Less
+            | TokenType::Equal
+            | TokenType::DoubleColon
+            | TokenType::LogicalAnd
+            | TokenType::LogicalOr
+            | TokenType::Eq
+            | TokenType::NotEq
+            | TokenType::LessEq
+            | TokenType::GreaterEq
+            | TokenType::LeftShift
+            | TokenType::RightShift
+            | TokenType::Dot
+            | TokenType::DotDot
+            | TokenType::As => true,
+            _ => false,
+        };
+        leaves_open || awaits_operand
+    });
+
+    match next {
+        Some((_, end, _)) if takes_next => Some(*end),
+        _ => inside.last().map(|(_, end, _)| *end),
+    }
+}
+
+/// Spans of every item, statement, expression, pattern and type
+struct SpanCollector {
+    spans: Vec<Span>,
+}
+
+impl<'ast> Visitor<'ast> for SpanCollector {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match item {
+            Item::Function { span, .. }
+            | Item::Struct { span, .. }
+            | Item::Enum { span, .. }
+            | Item::TypeAlias { span, .. }
+            | Item::Const { span, .. }
+            | Item::Module { span, .. }
+            | Item::Impl { span, .. }
+            | Item::Use { span, .. } => self.spans.push(*span),
+        }
+        visitor::walk_item(self, item);
+    }
+
+    fn visit_impl_item(&mut self, item: &'ast ImplItem) {
+        match item {
+            ImplItem::Function { span, .. } | ImplItem::Type { span, .. } | ImplItem::Const { span, .. } => {
+                self.spans.push(*span)
+            }
+        }
+        visitor::walk_impl_item(self, item);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        self.spans.push(stmt.span());
+        visitor::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        self.spans.push(expr.span());
+        visitor::walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        self.spans.push(pattern.span());
+        visitor::walk_pattern(self, pattern);
+    }
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        self.spans.push(ty.span());
+        visitor::walk_type(self, ty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    /// Ranges selected at `line`:`character` of `source`, innermost first, as (start, end) pairs
+    fn expansions(source: &str, line: u32, character: u32) -> Vec<((u32, u32), (u32, u32))> {
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        selection_range(Some(&module), source, &Position { line, character })
+            .ranges()
+            .into_iter()
+            .map(|range| ((range.start.line, range.start.character), (range.end.line, range.end.character)))
+            .collect()
+    }
+
+    #[test]
+    fn test_expands_from_token_to_document() {
+        let source = "fn add(a: i32, b: i32) -> i32 {\n    return a + b * 2;\n}\n";
+        assert_eq!(expansions(source, 1, 16), vec![
+            ((1, 15), (1, 16)), // b
+            ((1, 15), (1, 20)), // b * 2
+            ((1, 11), (1, 20)), // a + b * 2
+            ((1, 4), (1, 21)),  // return statement
+            ((0, 30), (2, 1)),  // body
+            ((0, 0), (2, 1)),   // function
+            ((0, 0), (3, 0)),   // document
+        ]);
+    }
+
+    #[test]
+    fn test_closing_brackets_belong_to_their_node() {
+        let source = "fn f(a: i32, b: i32) -> i32 {\n    let v: Vec<i32> = make(a, b);\n    return v;\n}\n";
+        let let_statement = ((1, 4), (1, 33));
+        assert_eq!(expansions(source, 1, 30)[..3], [((1, 30), (1, 31)), ((1, 22), (1, 32)), let_statement]);
+        assert_eq!(expansions(source, 1, 16)[..3], [((1, 15), (1, 18)), ((1, 11), (1, 19)), let_statement]);
+    }
+
+    #[test]
+    fn test_whitespace_between_items_selects_document() {
+        let source = "fn one() -> i32 { return 1; }\n\n\nfn two() -> i32 { return 2; }\n";
+        assert_eq!(expansions(source, 1, 0), vec![((0, 0), (4, 0))]);
+    }
+
+    #[test]
+    fn test_positions_count_utf16_units() {
+        // "𝕏" is two UTF-16 code units
+        let source = "fn f() -> i32 {\n    let s = \"𝕏\"; return x;\n}\n";
+        let ranges = expansions(source, 1, 25);
+        assert_eq!(ranges[0], ((1, 25), (1, 26)));
+        assert_eq!(ranges[1], ((1, 18), (1, 27)));
+    }
+}
@@ -0,0 +1,352 @@
+//! Semantic Token Highlighting for Bract LSP
+//!
+//! Tokens come straight from the lexer, and identifiers are classified by resolving
+//! them through the symbol table, so functions, types, parameters, locals and memory
+//! annotations are told apart by what they are rather than by how they look.
+
+use crate::ast::PrimitiveType;
+use crate::lexer::{Lexer, Position as SourcePosition, TokenType};
+use crate::parser::StringInterner;
+use crate::semantic::symbols::{ScopeKind, TypeDefinition};
+use crate::semantic::{SymbolKind, SymbolTable};
+use serde::{Deserialize, Serialize};
+
+/// Semantic token types, in the order of the legend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticTokenType {
+    Namespace = 0,
+    Type = 1,
+    Struct = 2,
+    Enum = 3,
+    EnumMember = 4,
+    Function = 5,
+    Parameter = 6,
+    Variable = 7,
+    Keyword = 8,
+    Number = 9,
+    String = 10,
+    Comment = 11,
+    /// `@memory`, `@performance` and other annotations
+    Decorator = 12,
+}
+
+impl SemanticTokenType {
+    /// Every token type, in legend order
+    pub const ALL: [SemanticTokenType; 13] = [
+        SemanticTokenType::Namespace,
+        SemanticTokenType::Type,
+        SemanticTokenType::Struct,
+        SemanticTokenType::Enum,
+        SemanticTokenType::EnumMember,
+        SemanticTokenType::Function,
+        SemanticTokenType::Parameter,
+        SemanticTokenType::Variable,
+        SemanticTokenType::Keyword,
+        SemanticTokenType::Number,
+        SemanticTokenType::String,
+        SemanticTokenType::Comment,
+        SemanticTokenType::Decorator,
+    ];
+
+    /// Name of the token type in the protocol
+    pub fn name(self) -> &'static str {
+        match self {
+            SemanticTokenType::Namespace => "namespace",
+            SemanticTokenType::Type => "type",
+            SemanticTokenType::Struct => "struct",
+            SemanticTokenType::Enum => "enum",
+            SemanticTokenType::EnumMember => "enumMember",
+            SemanticTokenType::Function => "function",
+            SemanticTokenType::Parameter => "parameter",
+            SemanticTokenType::Variable => "variable",
+            SemanticTokenType::Keyword => "keyword",
+            SemanticTokenType::Number => "number",
+            SemanticTokenType::String => "string",
+            SemanticTokenType::Comment => "comment",
+            SemanticTokenType::Decorator => "decorator",
+        }
+    }
+}
+
+/// Token modifiers, in the order of the legend; modifier `i` is bit `1 << i`
+pub const TOKEN_MODIFIERS: [&str; 1] = ["mutable"];
+
+/// Modifier bit of bindings declared `mut`
+pub const MUTABLE_MODIFIER: u32 = 1 << 0;
+
+/// LSP semantic tokens legend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticTokensLegend {
+    /// Token type names, indexed by the encoded token type
+    #[serde(rename = "tokenTypes")]
+    pub token_types: Vec<String>,
+    /// Token modifier names, indexed by bit
+    #[serde(rename = "tokenModifiers")]
+    pub token_modifiers: Vec<String>,
+}
+
+impl Default for SemanticTokensLegend {
+    fn default() -> Self {
+        Self {
+            token_types: SemanticTokenType::ALL.iter().map(|ty| ty.name().to_string()).collect(),
+            token_modifiers: TOKEN_MODIFIERS.iter().map(|modifier| modifier.to_string()).collect(),
+        }
+    }
+}
+
+/// Semantic tokens capability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticTokensOptions {
+    /// Legend the encoded tokens refer to
+    pub legend: SemanticTokensLegend,
+    /// Tokens for a whole document are provided
+    pub full: bool,
+    /// Tokens for a range of a document are provided
+    pub range: bool,
+}
+
+/// LSP semantic tokens
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticTokens {
+    /// Identifier of this result, for delta requests
+    #[serde(rename = "resultId")]
+    pub result_id: Option<String>,
+    /// Tokens as `encode_tokens` encodes them
+    pub data: Vec<u32>,
+}
+
+/// A classified token on a single line, in UTF-16 code units like LSP positions
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SemanticToken {
+    /// Line (0-based)
+    pub line: u32,
+    /// Start character (0-based)
+    pub start: u32,
+    /// Length in UTF-16 code units
+    pub length: u32,
+    pub token_type: SemanticTokenType,
+    /// Bit set of `TOKEN_MODIFIERS`
+    pub modifiers: u32,
+}
+
+/// Classify the tokens of `source`, resolving identifiers through `symbols`
+///
+/// Returns the tokens in document order. Tokens spanning several lines, like
+/// multi-line strings and block comments, are split into one token per line.
+/// Without a symbol table only primitive type names are classified among the
+/// identifiers; identifiers nothing resolves, like field names, are left out.
+pub fn classify_tokens(source: &str, symbols: Option<&SymbolTable>, interner: &StringInterner) -> Vec<SemanticToken> {
+    let lines: Vec<&str> = source.split('\n').collect();
+    let mut lexer = Lexer::new_with_comments(source, 0).with_error_recovery();
+    let mut tokens = Vec::new();
+    let mut after_at = false;
+    while let Ok(token) = lexer.next_token() {
+        if token.token_type == TokenType::Eof {
+            break;
+        }
+        let end = lexer.get_position();
+
+        let classified = match &token.token_type {
+            TokenType::At => Some((SemanticTokenType::Decorator, 0)),
+            TokenType::Identifier(_) if after_at => Some((SemanticTokenType::Decorator, 0)),
+            TokenType::Identifier(name) => classify_identifier(name, token.position, symbols, interner),
+            TokenType::Integer { .. } | TokenType::Float { .. } => Some((SemanticTokenType::Number, 0)),
+            TokenType::String { .. } | TokenType::Char(_) => Some((SemanticTokenType::String, 0)),
+            TokenType::Bool(_) | TokenType::Null => Some((SemanticTokenType::Keyword, 0)),
+            TokenType::LineComment(_)
+            | TokenType::BlockComment(_)
+            | TokenType::DocLineComment(_)
+            | TokenType::DocBlockComment(_)
+            | TokenType::InnerDocLineComment(_)
+            | TokenType::InnerDocBlockComment(_) => Some((SemanticTokenType::Comment, 0)),
+            _ if token.is_keyword() => Some((SemanticTokenType::Keyword, 0)),
+            _ => None,
+        };
+        after_at = token.token_type == TokenType::At;
+
+        if let Some((token_type, modifiers)) = classified {
+            push_split_by_line(&mut tokens, &lines, token.position, end, token_type, modifiers);
+        }
+    }
+    tokens
+}
+
+/// What the identifier `name` written at `position` names, with its modifiers
+fn classify_identifier(
+    name: &str,
+    position: SourcePosition,
+    symbols: Option<&SymbolTable>,
+    interner: &StringInterner,
+) -> Option<(SemanticTokenType, u32)> {
+    let resolved = symbols.and_then(|symbols| {
+        // A binding is classified where it is bound as well as where it is used
+        let symbol = symbols.binding_at(position).or_else(|| {
+            symbols.lookup_symbol_from(symbols.scope_at(position), &interner.intern(name))
+        })?;
+        let classified = match &symbol.kind {
+            SymbolKind::Variable { is_mutable, .. } => {
+                let is_parameter = symbols.get_scope(symbol.scope_id)
+                    .is_some_and(|scope| scope.kind == ScopeKind::Function);
+                let token_type = if is_parameter { SemanticTokenType::Parameter } else { SemanticTokenType::Variable };
+                (token_type, if *is_mutable { MUTABLE_MODIFIER } else { 0 })
+            }
+            SymbolKind::Function { .. } => (SemanticTokenType::Function, 0),
+            SymbolKind::Type { definition: TypeDefinition::Struct { .. } } => (SemanticTokenType::Struct, 0),
+            SymbolKind::Type { definition: TypeDefinition::Enum { .. } } => (SemanticTokenType::Enum, 0),
+            SymbolKind::Type { definition: TypeDefinition::Alias { .. } } | SymbolKind::GenericParam { .. } => {
+                (SemanticTokenType::Type, 0)
+            }
+            SymbolKind::Module { .. } => (SemanticTokenType::Namespace, 0),
+            SymbolKind::Constant { .. } => (SemanticTokenType::Variable, 0),
+        };
+        Some(classified)
+    });
+    resolved
+        .or_else(|| PrimitiveType::from_name(name).map(|_| (SemanticTokenType::Type, 0)))
+        .or_else(|| {
+            let is_variant = symbols.is_some_and(|symbols| symbols.is_enum_variant(interner.intern(name)));
+            is_variant.then_some((SemanticTokenType::EnumMember, 0))
+        })
+}
+
+/// Add the token from `start` up to `end` to `tokens`, one piece per line it covers
+///
+/// Source positions count characters from 1; the pieces count UTF-16 code units from 0.
+fn push_split_by_line(
+    tokens: &mut Vec<SemanticToken>,
+    lines: &[&str],
+    start: SourcePosition,
+    end: SourcePosition,
+    token_type: SemanticTokenType,
+    modifiers: u32,
+) {
+    for line in start.line..=end.line {
+        let Some(text) = lines.get(line.saturating_sub(1)) else { break };
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        let from = if line == start.line { start.column.saturating_sub(1) } else { 0 };
+        let to = if line == end.line { end.column.saturating_sub(1) } else { usize::MAX };
+
+        let from_utf16 = utf16_len(text, from);
+        let length = utf16_len(text, to).saturating_sub(from_utf16);
+        if length > 0 {
+            tokens.push(SemanticToken {
+                line: line.saturating_sub(1) as u32,
+                start: from_utf16,
+                length,
+                token_type,
+                modifiers,
+            });
+        }
+    }
+}
+
+/// Length in UTF-16 code units of the first `chars` characters of `text`
+pub(super) fn utf16_len(text: &str, chars: usize) -> u32 {
+    text.chars().take(chars).map(|ch| ch.len_utf16() as u32).sum()
+}
+
+/// Number of characters of `text` within its first `units` UTF-16 code units
+///
+/// The inverse of `utf16_len`; a position inside a surrogate pair counts the whole character.
+pub(super) fn chars_in_utf16(text: &str, units: u32) -> usize {
+    let mut seen = 0;
+    text.chars()
+        .take_while(|ch| {
+            let before = seen;
+            seen += ch.len_utf16() as u32;
+            before < units
+        })
+        .count()
+}
+
+/// Encode `tokens`, in document order, in the LSP relative format
+///
+/// Each token becomes five numbers: its line relative to the previous token, its
+/// start relative to the previous token's if on the same line (else to the line
+/// start), its length, its type and its modifiers. Any run of tokens in order can
+/// be encoded, so a range of a document encodes the same way as all of it.
+pub fn encode_tokens(tokens: &[SemanticToken]) -> Vec<u32> {
+    let mut data = Vec::with_capacity(tokens.len() * 5);
+    let (mut line, mut start) = (0, 0);
+    for token in tokens {
+        let delta_line = token.line - line;
+        let delta_start = if delta_line == 0 { token.start - start } else { token.start };
+        data.extend([delta_line, delta_start, token.length, token.token_type as u32, token.modifiers]);
+        line = token.line;
+        start = token.start;
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile;
+
+    /// Tokens of `source` as (line, start, length, type, modifiers), after full analysis
+    fn classified(source: &str) -> Vec<(u32, u32, u32, SemanticTokenType, u32)> {
+        let interner = StringInterner::new();
+        let analysis = compile::analyze_source(source, interner.clone());
+        classify_tokens(source, analysis.symbols.as_ref(), &interner)
+            .into_iter()
+            .map(|token| (token.line, token.start, token.length, token.token_type, token.modifiers))
+            .collect()
+    }
+
+    #[test]
+    fn test_identifiers_classified_through_symbol_table() {
+        let source = "struct Point { x: i32 }\n\
+                      fn shift(p: Point, by: i32) -> i32 {\n    let mut total = p.x;\n    total = total + by;\n    return total;\n}";
+        let tokens = classified(source);
+        let at = |line: u32, start: u32| tokens.iter().find(|t| t.0 == line && t.1 == start).map(|t| (t.3, t.4));
+
+        assert_eq!(at(0, 0), Some((SemanticTokenType::Keyword, 0)));
+        assert_eq!(at(0, 7), Some((SemanticTokenType::Struct, 0)));
+        assert_eq!(at(0, 18), Some((SemanticTokenType::Type, 0)));
+        assert_eq!(at(1, 3), Some((SemanticTokenType::Function, 0)));
+        assert_eq!(at(1, 9), Some((SemanticTokenType::Parameter, 0)));
+        assert_eq!(at(1, 12), Some((SemanticTokenType::Struct, 0)));
+        assert_eq!(at(2, 12), Some((SemanticTokenType::Variable, MUTABLE_MODIFIER)));
+        assert_eq!(at(3, 20), Some((SemanticTokenType::Parameter, 0)));
+        // Field names resolve to nothing and are left to the editor
+        assert_eq!(at(2, 22), None);
+    }
+
+    #[test]
+    fn test_annotations_and_literals() {
+        let tokens = classified("@memory(strategy = \"linear\")\nfn f() -> i32 { return 1; } // done");
+        let kinds: Vec<_> = tokens.iter().map(|t| (t.0, t.1, t.2, t.3)).collect();
+
+        assert_eq!(&kinds[..3], &[
+            (0, 0, 1, SemanticTokenType::Decorator),
+            (0, 1, 6, SemanticTokenType::Decorator),
+            (0, 19, 8, SemanticTokenType::String),
+        ]);
+        assert!(kinds.contains(&(1, 23, 1, SemanticTokenType::Number)));
+        assert_eq!(kinds.last(), Some(&(1, 28, 7, SemanticTokenType::Comment)));
+    }
+
+    #[test]
+    fn test_multi_line_and_utf16_lengths() {
+        // 'é' is one UTF-16 unit, '𝔹' two
+        let source = "fn main() -> i32 {\n    let s = \"é\n𝔹x\";\n    return 0;\n}";
+        let strings: Vec<_> = classified(source).into_iter()
+            .filter(|t| t.3 == SemanticTokenType::String)
+            .map(|t| (t.0, t.1, t.2))
+            .collect();
+        assert_eq!(strings, vec![(1, 12, 2), (2, 0, 4)]);
+    }
+
+    #[test]
+    fn test_encode_relative_positions() {
+        let token = |line, start, length, token_type| SemanticToken { line, start, length, token_type, modifiers: 0 };
+        let tokens = [
+            token(0, 0, 2, SemanticTokenType::Keyword),
+            token(0, 3, 4, SemanticTokenType::Function),
+            token(2, 4, 3, SemanticTokenType::Keyword),
+        ];
+        assert_eq!(encode_tokens(&tokens), vec![0, 0, 2, 8, 0, 0, 3, 4, 5, 0, 2, 4, 3, 8, 0]);
+        // Tokens of a range encode the same way, the first one relative to the document start
+        assert_eq!(encode_tokens(&tokens[2..]), vec![2, 4, 3, 8, 0]);
+    }
+}
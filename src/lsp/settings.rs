@@ -0,0 +1,168 @@
+//! Runtime Configuration for Bract LSP
+//!
+//! Clients change settings with `workspace/didChangeConfiguration`, sending them
+//! under the `bract` section and named like the fields of `LspConfig` in camel
+//! case: `bract.enableCompletion`, `bract.cacheSizeLimit`. A value of the wrong
+//! type or out of range is rejected with a warning and the setting keeps its value.
+//!
+//! Turning a feature on or off changes the capabilities the server advertises.
+//! Capabilities the client registers dynamically are registered and unregistered
+//! as the settings change; the others are only read by the client at `initialize`,
+//! so changing them takes a restart of the server.
+
+use super::LspConfig;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Section of the client's settings holding the server's
+pub const SETTINGS_SECTION: &str = "bract";
+
+/// Requests whose capability follows the configuration
+pub const CONFIGURABLE_METHODS: [&str; 6] = [
+    "textDocument/diagnostic",
+    "textDocument/completion",
+    "textDocument/definition",
+    "textDocument/references",
+    "textDocument/documentSymbol",
+    "workspace/symbol",
+];
+
+/// A capability to register with `client/registerCapability`; its id is its method
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Registration {
+    pub id: String,
+    pub method: String,
+    /// Options of the capability, as they would be advertised at `initialize`
+    #[serde(rename = "registerOptions")]
+    pub register_options: Value,
+}
+
+/// A capability to unregister with `client/unregisterCapability`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Unregistration {
+    pub id: String,
+    pub method: String,
+}
+
+/// How the capabilities changed with a new configuration
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigUpdate {
+    /// Capabilities turned on that the client registers dynamically
+    pub register: Vec<Registration>,
+    /// Capabilities turned off that the client registers dynamically
+    pub unregister: Vec<Unregistration>,
+    /// Methods whose capability changed but that the client only takes at
+    /// `initialize`; they stay as advertised until the server restarts
+    pub needs_restart: Vec<&'static str>,
+}
+
+impl ConfigUpdate {
+    /// Whether the client must be told anything
+    pub fn is_empty(&self) -> bool {
+        self.register.is_empty() && self.unregister.is_empty() && self.needs_restart.is_empty()
+    }
+}
+
+/// The configurable methods `client_capabilities` says the client registers dynamically
+pub fn dynamic_registration_support(client_capabilities: &Value) -> Vec<&'static str> {
+    CONFIGURABLE_METHODS.into_iter()
+        .filter(|method| {
+            let (scope, request) = method.split_once('/').unwrap_or_default();
+            client_capabilities[scope][request]["dynamicRegistration"].as_bool().unwrap_or(false)
+        })
+        .collect()
+}
+
+/// `config` with the `bract` section of `settings` applied, and a warning for each
+/// setting rejected
+///
+/// Settings missing from the section keep their value, and ones the server does
+/// not know are ignored.
+pub fn apply_settings(config: &LspConfig, settings: &Value) -> (LspConfig, Vec<String>) {
+    let mut config = config.clone();
+    let mut warnings = Vec::new();
+    let Some(section) = settings.get(SETTINGS_SECTION).and_then(Value::as_object) else {
+        return (config, warnings);
+    };
+
+    for (key, value) in section {
+        let applied = match key.as_str() {
+            "enableDiagnostics" => set_flag(&mut config.enable_diagnostics, value),
+            "enableCompletion" => set_flag(&mut config.enable_completion, value),
+            "enableNavigation" => set_flag(&mut config.enable_navigation, value),
+            "enablePerformanceDiagnostics" => set_flag(&mut config.enable_performance_diagnostics, value),
+            "maxAnalysisTime" => set_count(&mut config.max_analysis_time, value, 0, "milliseconds"),
+            "analysisDebounce" => set_count(&mut config.analysis_debounce, value, 0, "milliseconds"),
+            "cacheSizeLimit" => set_count(&mut config.cache_size_limit, value, 1, "documents"),
+            _ => continue,
+        };
+        if let Err(expected) = applied {
+            warnings.push(format!("Ignoring setting {}.{} = {}: expected {}", SETTINGS_SECTION, key, value, expected));
+        }
+    }
+    (config, warnings)
+}
+
+/// Set `field` to `value` if it is a boolean
+fn set_flag(field: &mut bool, value: &Value) -> Result<(), String> {
+    *field = value.as_bool().ok_or_else(|| "true or false".to_string())?;
+    Ok(())
+}
+
+/// Set `field` to `value` if it is a whole number of `unit` from `min` up
+fn set_count<T: TryFrom<u64>>(field: &mut T, value: &Value, min: u64, unit: &str) -> Result<(), String> {
+    let expected = || format!("a whole number of {}, {} or more", unit, min);
+    let count = value.as_u64().filter(|&count| count >= min).ok_or_else(expected)?;
+    *field = T::try_from(count).map_err(|_| expected())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_settings_are_validated() {
+        let settings = json!({
+            "bract": {
+                "enableCompletion": false,
+                "maxAnalysisTime": 250,
+                "cacheSizeLimit": 0,
+                "analysisDebounce": -5,
+                "enableNavigation": "no",
+                "somethingElse": 1,
+            },
+            "editor": { "enableDiagnostics": false },
+        });
+        let (config, warnings) = apply_settings(&LspConfig::default(), &settings);
+        assert!(!config.enable_completion && config.enable_navigation && config.enable_diagnostics);
+        assert_eq!(config.max_analysis_time, 250);
+
+        // Rejected values keep the ones before them
+        let defaults = LspConfig::default();
+        assert_eq!((config.cache_size_limit, config.analysis_debounce), (defaults.cache_size_limit, defaults.analysis_debounce));
+        assert_eq!(warnings, vec![
+            "Ignoring setting bract.analysisDebounce = -5: expected a whole number of milliseconds, 0 or more",
+            "Ignoring setting bract.cacheSizeLimit = 0: expected a whole number of documents, 1 or more",
+            "Ignoring setting bract.enableNavigation = \"no\": expected true or false",
+        ]);
+
+        // Clients that send no section change nothing
+        let (config, warnings) = apply_settings(&defaults, &Value::Null);
+        assert!(config.enable_completion && warnings.is_empty());
+    }
+
+    #[test]
+    fn test_dynamic_registration_support() {
+        let capabilities = json!({
+            "textDocument": {
+                "completion": { "dynamicRegistration": true },
+                "definition": { "dynamicRegistration": false },
+            },
+            "workspace": { "symbol": { "dynamicRegistration": true } },
+        });
+        assert_eq!(dynamic_registration_support(&capabilities), vec!["textDocument/completion", "workspace/symbol"]);
+        assert!(dynamic_registration_support(&json!({})).is_empty());
+    }
+}
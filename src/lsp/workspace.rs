@@ -0,0 +1,452 @@
+//! Workspace Indexing for Bract LSP
+//!
+//! The index keeps a summary of every `.bract` file in the workspace folders: the
+//! items it declares, with their kind, location and container, and the names it
+//! mentions. Summaries are all that is kept of files the editor has not opened.
+//! Workspace symbol search and go-to-definition answer from them; find-references
+//! uses them to pick the files that mention a name and reads only those again.
+
+use super::{LineIndex, Location, Range};
+use crate::ast::{ImplItem, Item, Module, PrettyPrinter, Span};
+use crate::lexer::{Lexer, TokenType};
+use crate::parser::module_loader::SOURCE_EXTENSION;
+use crate::parser::{Parser, StringInterner};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Kind of a declared symbol, sent as its number like the protocol expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Module = 2,
+    Method = 6,
+    Enum = 10,
+    Function = 12,
+    Constant = 14,
+    Struct = 23,
+    TypeParameter = 26,
+}
+
+impl Serialize for SymbolKind {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+/// LSP symbol information, as `workspace/symbol` returns it
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolInformation {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Where the symbol is declared
+    pub location: Location,
+    /// Module or type the symbol is declared in
+    #[serde(rename = "containerName")]
+    pub container_name: Option<String>,
+}
+
+/// Change to a watched file, from `workspace/didChangeWatchedFiles`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileEvent {
+    pub uri: String,
+    #[serde(rename = "type")]
+    pub change: FileChangeType,
+}
+
+/// How a watched file changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeType {
+    Created = 1,
+    Changed = 2,
+    Deleted = 3,
+}
+
+impl Serialize for FileChangeType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u8(*self as u8)
+    }
+}
+
+impl<'de> Deserialize<'de> for FileChangeType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match u8::deserialize(deserializer)? {
+            1 => Ok(FileChangeType::Created),
+            2 => Ok(FileChangeType::Changed),
+            3 => Ok(FileChangeType::Deleted),
+            other => Err(serde::de::Error::custom(format!("invalid file change type {}", other))),
+        }
+    }
+}
+
+/// An item a file declares, as the index remembers it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolSummary {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The whole declaration
+    pub range: Range,
+    /// The declared name
+    pub selection_range: Range,
+    /// Module or type the item is declared in, such as `geo::Point`
+    pub container: Option<String>,
+}
+
+/// What the index keeps of one file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileSummary {
+    /// Items declared in the file, in source order
+    pub symbols: Vec<SymbolSummary>,
+    /// Every identifier the file mentions
+    pub names: HashSet<String>,
+}
+
+impl FileSummary {
+    /// Summary of `source`, whose parse is `module` with names in `interner`
+    pub fn new(source: &str, module: Option<&Module>, interner: &StringInterner) -> Self {
+        let line_index = LineIndex::new(source);
+        let identifiers = identifiers(source, &line_index);
+        let mut summary = FileSummary {
+            symbols: Vec::new(),
+            names: identifiers.iter().map(|(name, _)| name.clone()).collect(),
+        };
+        if let Some(module) = module {
+            let mut collector = SymbolCollector { identifiers: &identifiers, line_index: &line_index, interner, symbols: &mut summary.symbols };
+            collector.items(&module.items, None);
+        }
+        summary
+    }
+
+    /// Parse `source` and summarize it; a file that does not parse only has its names
+    pub fn parse(source: &str) -> Self {
+        let parsed = Parser::new(source, 0).ok().and_then(|mut parser| {
+            let module = parser.parse_module().ok()?;
+            Some((module, parser.interner()))
+        });
+        match parsed {
+            Some((module, interner)) => FileSummary::new(source, Some(&module), &interner),
+            None => FileSummary::new(source, None, &StringInterner::new()),
+        }
+    }
+}
+
+/// Summaries of the workspace's files, by URI
+///
+/// Files open in the editor are summarized from their text, which takes the
+/// place of what is on disk until they are closed.
+#[derive(Debug, Default)]
+pub struct WorkspaceIndex {
+    /// Root directories of the workspace
+    folders: Vec<PathBuf>,
+    /// Summaries, ordered by URI so answers come in a stable order
+    files: BTreeMap<String, FileSummary>,
+    /// Files summarized from the editor's text
+    open: HashSet<String>,
+}
+
+impl WorkspaceIndex {
+    /// Create an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Root directories of the workspace
+    pub fn folders(&self) -> &[PathBuf] {
+        &self.folders
+    }
+
+    /// Replace the workspace folders
+    pub fn set_folders(&mut self, folders: Vec<PathBuf>) {
+        self.folders = folders;
+    }
+
+    /// Whether `path` is inside one of the workspace folders
+    pub fn contains_path(&self, path: &Path) -> bool {
+        self.folders.iter().any(|folder| path.starts_with(folder))
+    }
+
+    /// Number of files indexed
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Whether no file is indexed
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Summary of the file `uri`, if indexed
+    pub fn file(&self, uri: &str) -> Option<&FileSummary> {
+        self.files.get(uri)
+    }
+
+    /// Record the summary of a file as it is on disk, unless the file is open
+    pub fn update_from_disk(&mut self, uri: String, summary: FileSummary) {
+        if !self.open.contains(&uri) {
+            self.files.insert(uri, summary);
+        }
+    }
+
+    /// Record the summary of a file open in the editor, replacing what is on disk
+    pub fn update_open(&mut self, uri: String, summary: FileSummary) {
+        self.open.insert(uri.clone());
+        self.files.insert(uri, summary);
+    }
+
+    /// Forget that `uri` is open, and its summary; the caller re-reads it from disk
+    pub fn close(&mut self, uri: &str) {
+        self.open.remove(uri);
+        self.files.remove(uri);
+    }
+
+    /// Drop the file `uri`, unless it is open
+    pub fn remove(&mut self, uri: &str) {
+        if !self.open.contains(uri) {
+            self.files.remove(uri);
+        }
+    }
+
+    /// Symbols whose name contains `query`, ignoring case; an empty query matches all
+    pub fn symbols_matching(&self, query: &str) -> Vec<SymbolInformation> {
+        let query = query.to_lowercase();
+        self.files.iter()
+            .flat_map(|(uri, summary)| summary.symbols.iter().map(move |symbol| (uri, symbol)))
+            .filter(|(_, symbol)| symbol.name.to_lowercase().contains(&query))
+            .map(|(uri, symbol)| SymbolInformation {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                location: Location { uri: uri.clone(), range: symbol.range.clone() },
+                container_name: symbol.container.clone(),
+            })
+            .collect()
+    }
+
+    /// Names of the declarations of `name` in `uri`, or in every file when `uri` has none
+    pub fn declarations(&self, uri: &str, name: &str) -> Vec<Location> {
+        let in_file = |file: &String| {
+            self.declaration_ranges(file, name).into_iter()
+                .map(|range| Location { uri: file.clone(), range })
+                .collect::<Vec<_>>()
+        };
+        let local = in_file(&uri.to_string());
+        if !local.is_empty() {
+            return local;
+        }
+        self.files.keys().flat_map(in_file).collect()
+    }
+
+    /// Ranges of the names of the declarations of `name` in `uri`
+    pub fn declaration_ranges(&self, uri: &str, name: &str) -> Vec<Range> {
+        self.files.get(uri).into_iter()
+            .flat_map(|summary| &summary.symbols)
+            .filter(|symbol| symbol.name == name)
+            .map(|symbol| symbol.selection_range.clone())
+            .collect()
+    }
+
+    /// Files that mention `name`
+    pub fn files_mentioning(&self, name: &str) -> Vec<String> {
+        self.files.iter()
+            .filter(|(_, summary)| summary.names.contains(name))
+            .map(|(uri, _)| uri.clone())
+            .collect()
+    }
+}
+
+/// `.bract` files in `folder` and below, in path order
+///
+/// Files and directories whose name is in `ignore` are skipped, and the walk
+/// stops once `limit` files are found.
+pub fn find_source_files(folder: &Path, ignore: &[String], limit: usize) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(directory) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&directory) else {
+            continue;
+        };
+        let mut entries: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+        entries.sort();
+        // Directories are walked after the files next to them, in order
+        let mut directories = Vec::new();
+        for path in entries {
+            let ignored = path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| ignore.iter().any(|ignored| ignored == name));
+            if ignored {
+                continue;
+            }
+            if path.is_dir() {
+                directories.push(path);
+            } else if path.extension().is_some_and(|extension| extension == SOURCE_EXTENSION) {
+                if files.len() == limit {
+                    return files;
+                }
+                files.push(path);
+            }
+        }
+        pending.extend(directories.into_iter().rev());
+    }
+    files
+}
+
+/// Ranges of the identifiers named `name` in `source`
+pub fn name_ranges(source: &str, name: &str) -> Vec<Range> {
+    identifiers(source, &LineIndex::new(source)).into_iter()
+        .filter(|(identifier, _)| identifier == name)
+        .map(|(_, range)| range)
+        .collect()
+}
+
+/// `file://` URI of an absolute path
+pub fn path_to_uri(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let encoded = path.replace('%', "%25").replace(' ', "%20");
+    if encoded.starts_with('/') {
+        format!("file://{}", encoded)
+    } else {
+        format!("file:///{}", encoded)
+    }
+}
+
+/// Path of a `file://` URI
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+            let value = std::str::from_utf8(&hex).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())?;
+            decoded.push(value);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // `file:///C:/...` names a Windows drive
+    match path.as_bytes() {
+        [b'/', _, b':', ..] => Some(PathBuf::from(&path[1..])),
+        _ => Some(PathBuf::from(path)),
+    }
+}
+
+/// Every identifier of `source`, whose lines are `line_index`, with its range
+fn identifiers(source: &str, line_index: &LineIndex) -> Vec<(String, Range)> {
+    let mut identifiers = Vec::new();
+    let mut lexer = Lexer::new(source, 0).with_error_recovery();
+    while let Ok(token) = lexer.next_token() {
+        match token.token_type {
+            TokenType::Eof => break,
+            TokenType::Identifier(name) => {
+                let range = line_index.range(Span::new(token.position, lexer.get_position()));
+                identifiers.push((name.to_string(), range));
+            }
+            _ => {}
+        }
+    }
+    identifiers
+}
+
+/// Collects the summaries of declared items
+struct SymbolCollector<'a> {
+    identifiers: &'a [(String, Range)],
+    line_index: &'a LineIndex,
+    interner: &'a StringInterner,
+    symbols: &'a mut Vec<SymbolSummary>,
+}
+
+impl SymbolCollector<'_> {
+    fn items(&mut self, items: &[Item], container: Option<&str>) {
+        for item in items {
+            let (name, kind, span) = match item {
+                Item::Function { name, span, .. } => (name, SymbolKind::Function, span),
+                Item::Struct { name, span, .. } => (name, SymbolKind::Struct, span),
+                Item::Enum { name, span, .. } => (name, SymbolKind::Enum, span),
+                Item::TypeAlias { name, span, .. } => (name, SymbolKind::TypeParameter, span),
+                Item::Const { name, span, .. } => (name, SymbolKind::Constant, span),
+                Item::Module { name, span, items, .. } => {
+                    let module_name = self.interner.resolve(*name).unwrap_or_default();
+                    self.push(&module_name, SymbolKind::Module, span, container);
+                    if let Some(items) = items {
+                        let path = match container {
+                            Some(container) => format!("{}::{}", container, module_name),
+                            None => module_name,
+                        };
+                        self.items(items, Some(&path));
+                    }
+                    continue;
+                }
+                Item::Impl { target_type, items, .. } => {
+                    let target = PrettyPrinter::new(self.interner).print_type(target_type);
+                    let path = match container {
+                        Some(container) => format!("{}::{}", container, target),
+                        None => target,
+                    };
+                    for impl_item in items {
+                        let (name, kind, span) = match impl_item {
+                            ImplItem::Function { name, span, .. } => (name, SymbolKind::Method, span),
+                            ImplItem::Type { name, span, .. } => (name, SymbolKind::TypeParameter, span),
+                            ImplItem::Const { name, span, .. } => (name, SymbolKind::Constant, span),
+                        };
+                        let name = self.interner.resolve(*name).unwrap_or_default();
+                        self.push(&name, kind, span, Some(&path));
+                    }
+                    continue;
+                }
+                Item::Use { .. } => continue,
+            };
+            let name = self.interner.resolve(*name).unwrap_or_default();
+            self.push(&name, kind, span, container);
+        }
+    }
+
+    fn push(&mut self, name: &str, kind: SymbolKind, span: &Span, container: Option<&str>) {
+        let range = self.line_index.range(*span);
+        // The name is the first identifier spelling it within the declaration
+        let start = (range.start.line, range.start.character);
+        let selection_range = self.identifiers.iter()
+            .find(|(identifier, at)| identifier == name && (at.start.line, at.start.character) >= start)
+            .map(|(_, at)| at.clone())
+            .unwrap_or_else(|| range.clone());
+        self.symbols.push(SymbolSummary {
+            name: name.to_string(),
+            kind,
+            range,
+            selection_range,
+            container: container.map(str::to_string),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_lists_items_with_containers() {
+        let source = "pub fn area() -> i32 { return 1; }\nstruct Point { x: i32 }\nimpl Point {\n    fn len(self) -> i32 { return self.x; }\n}\nmod geo {\n    const ORIGIN: i32 = 0;\n}\n";
+        let summary = FileSummary::parse(source);
+        let symbols: Vec<(&str, SymbolKind, Option<&str>)> = summary.symbols.iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind, symbol.container.as_deref()))
+            .collect();
+        assert_eq!(symbols, vec![
+            ("area", SymbolKind::Function, None),
+            ("Point", SymbolKind::Struct, None),
+            ("len", SymbolKind::Method, Some("Point")),
+            ("geo", SymbolKind::Module, None),
+            ("ORIGIN", SymbolKind::Constant, Some("geo")),
+        ]);
+        let len = &summary.symbols[2];
+        assert_eq!((len.selection_range.start.line, len.selection_range.start.character), (3, 7));
+        assert!(summary.names.contains("x") && !summary.names.contains("fn"));
+    }
+
+    #[test]
+    fn test_uris_round_trip_paths() {
+        let path = Path::new("/home/user/my project/main.bract");
+        let uri = path_to_uri(path);
+        assert_eq!(uri, "file:///home/user/my%20project/main.bract");
+        assert_eq!(uri_to_path(&uri).as_deref(), Some(path));
+        assert_eq!(uri_to_path("untitled:1"), None);
+    }
+}
@@ -0,0 +1,207 @@
+//! Where compile time goes
+//!
+//! A `CompileMetrics` records how long each phase of the pipeline took and how much
+//! it got through: tokens lexed, items parsed, functions analyzed and compiled, and
+//! bytes of object emitted. `compile_source` attaches one to its result when
+//! `CompileOptions::collect_metrics` asks, `analyze_source` always does for the
+//! front half, and `CodegenPipeline` keeps one for what it compiled. Timing every
+//! function body costs an `Instant` per function, so that is only done when
+//! `CodegenOptions::time_functions` is set; everything else is counted anyway.
+
+use crate::codegen::cranelift::{CraneliftCodeGenerator, MemoryMetrics};
+use crate::lsp::CacheStats;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Number of functions `CompileMetrics::slowest_functions` keeps
+pub const SLOWEST_FUNCTIONS: usize = 10;
+
+/// Phase of the compile pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Lexing and parsing, which the parser interleaves
+    Parse,
+    Semantic,
+    /// Lowering function bodies to machine code
+    Codegen,
+    /// Finishing and writing the object file
+    Emit,
+    Link,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Parse => "parse",
+            Phase::Semantic => "semantic",
+            Phase::Codegen => "codegen",
+            Phase::Emit => "emit",
+            Phase::Link => "link",
+        }
+    }
+}
+
+/// Wall-clock time spent in a phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseTiming {
+    pub phase: Phase,
+    pub duration: Duration,
+}
+
+/// Time spent compiling one function body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionTiming {
+    /// Name of the function, `Type_method` for methods
+    pub name: String,
+    pub duration: Duration,
+}
+
+/// Timings and counts of a compilation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompileMetrics {
+    /// Phases that ran, in the order they first ran
+    pub phases: Vec<PhaseTiming>,
+    /// Significant tokens read by the parser
+    pub tokens_lexed: usize,
+    /// Top-level items parsed
+    pub items_parsed: usize,
+    /// Functions and methods semantic analysis went through
+    pub functions_analyzed: usize,
+    /// Function bodies lowered to machine code, lifted closures included
+    pub functions_compiled: usize,
+    /// Size of the emitted object file
+    pub object_bytes: usize,
+    /// Functions that took longest to compile, slowest first and at most
+    /// `SLOWEST_FUNCTIONS`; empty unless function bodies were timed
+    pub slowest_functions: Vec<FunctionTiming>,
+    /// What the memory manager allocated, once code was generated
+    pub memory: Option<MemoryMetrics>,
+    /// The language server's analysis cache, for metrics of a document it analyzed
+    pub cache: Option<CacheStats>,
+}
+
+impl CompileMetrics {
+    /// Add `duration` to the time spent in `phase`
+    pub fn record_phase(&mut self, phase: Phase, duration: Duration) {
+        match self.phases.iter_mut().find(|timing| timing.phase == phase) {
+            Some(timing) => timing.duration += duration,
+            None => self.phases.push(PhaseTiming { phase, duration }),
+        }
+    }
+
+    /// Run `f`, recording the time it takes as spent in `phase`
+    pub fn time<T>(&mut self, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.record_phase(phase, start.elapsed());
+        result
+    }
+
+    /// Time spent in `phase`, if it ran
+    pub fn phase(&self, phase: Phase) -> Option<Duration> {
+        self.phases.iter().find(|timing| timing.phase == phase).map(|timing| timing.duration)
+    }
+
+    /// Time spent in all phases
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|timing| timing.duration).sum()
+    }
+
+    /// Keep the slowest of `timings` and the functions already kept
+    pub fn record_function_timings(&mut self, timings: impl IntoIterator<Item = FunctionTiming>) {
+        self.slowest_functions.extend(timings);
+        self.slowest_functions.sort_by(|a, b| b.duration.cmp(&a.duration).then_with(|| a.name.cmp(&b.name)));
+        self.slowest_functions.truncate(SLOWEST_FUNCTIONS);
+    }
+
+    /// Record what `generator` did in a `generate` call that took `elapsed` and
+    /// produced `object_bytes` bytes
+    ///
+    /// The time the generator spent emitting the object counts as `Phase::Emit` and
+    /// the rest as `Phase::Codegen`.
+    pub fn record_codegen(&mut self, generator: &CraneliftCodeGenerator, elapsed: Duration, object_bytes: usize) {
+        let emit = generator.emit_time().min(elapsed);
+        self.record_phase(Phase::Codegen, elapsed - emit);
+        self.record_phase(Phase::Emit, emit);
+        self.functions_compiled += generator.functions_compiled();
+        self.object_bytes += object_bytes;
+        self.record_function_timings(generator.function_timings().iter().cloned());
+        self.memory = Some(generator.memory_metrics().clone());
+    }
+
+    /// The metrics as a few lines of text, for printing after a build
+    ///
+    /// ```text
+    /// compiled in 12.41ms
+    ///   parse        0.82ms  (1204 tokens, 31 items)
+    ///   semantic     2.10ms  (27 functions)
+    ///   codegen      8.95ms  (29 functions)
+    ///   emit         0.54ms  (18432 bytes)
+    /// ```
+    pub fn summary(&self) -> String {
+        let millis = |duration: Duration| format!("{:.2}ms", duration.as_secs_f64() * 1000.0);
+        let mut text = format!("compiled in {}", millis(self.total()));
+        for timing in &self.phases {
+            let counts = match timing.phase {
+                Phase::Parse => format!("{} tokens, {} items", self.tokens_lexed, self.items_parsed),
+                Phase::Semantic => format!("{} functions", self.functions_analyzed),
+                Phase::Codegen => format!("{} functions", self.functions_compiled),
+                Phase::Emit => format!("{} bytes", self.object_bytes),
+                Phase::Link => String::new(),
+            };
+            let _ = write!(text, "\n  {:<10} {:>8}", timing.phase.name(), millis(timing.duration));
+            if !counts.is_empty() {
+                let _ = write!(text, "  ({})", counts);
+            }
+        }
+        if !self.slowest_functions.is_empty() {
+            text.push_str("\nslowest functions:");
+            for function in &self.slowest_functions {
+                let _ = write!(text, "\n  {:>8}  {}", millis(function.duration), function.name);
+            }
+        }
+        if let Some(memory) = &self.memory {
+            let allocations = memory.manual_allocs + memory.smart_ptr_allocs + memory.linear_allocs
+                + memory.region_allocs + memory.stack_allocs;
+            let _ = write!(text, "\nmemory: {} allocation sites, {} bytes", allocations, memory.total_bytes_allocated);
+        }
+        if let Some(cache) = &self.cache {
+            let hit_rate = cache.hit_rate().map_or("n/a".to_string(), |rate| format!("{:.0}%", rate * 100.0));
+            let _ = write!(text, "\ncache: {} hits, {} misses ({} hit rate)", cache.hits, cache.misses, hit_rate);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(name: &str, millis: u64) -> FunctionTiming {
+        FunctionTiming { name: name.to_string(), duration: Duration::from_millis(millis) }
+    }
+
+    #[test]
+    fn test_phases_accumulate_and_slowest_functions_are_kept() {
+        let mut metrics = CompileMetrics::default();
+        metrics.record_phase(Phase::Parse, Duration::from_millis(2));
+        metrics.record_phase(Phase::Codegen, Duration::from_millis(5));
+        metrics.record_phase(Phase::Parse, Duration::from_millis(1));
+        assert_eq!(metrics.phase(Phase::Parse), Some(Duration::from_millis(3)));
+        assert_eq!(metrics.phase(Phase::Link), None);
+        assert_eq!(metrics.total(), Duration::from_millis(8));
+
+        metrics.record_function_timings((0..SLOWEST_FUNCTIONS as u64).map(|i| timing(&format!("f{}", i), i)));
+        metrics.record_function_timings([timing("slow", 100), timing("fast", 0)]);
+        assert_eq!(metrics.slowest_functions.len(), SLOWEST_FUNCTIONS);
+        assert_eq!(metrics.slowest_functions[0], timing("slow", 100));
+        assert!(metrics.slowest_functions.iter().all(|function| function.name != "f0" && function.name != "fast"));
+
+        let summary = metrics.summary();
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines[0], "compiled in 8.00ms");
+        assert_eq!(lines[1], "  parse        3.00ms  (0 tokens, 0 items)");
+        assert_eq!(lines[3], "slowest functions:");
+        assert_eq!(lines[4], "  100.00ms  slow");
+    }
+}
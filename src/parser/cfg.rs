@@ -0,0 +1,274 @@
+//! Conditional compilation with `@cfg(...)`
+//!
+//! An item written after `@cfg(predicate)` only exists on the targets its predicate
+//! holds for. The parser reads the predicate into the attribute's `CfgPredicate`,
+//! and `strip_cfg_items` drops the items whose predicates fail for the target being
+//! compiled for before any names are resolved. The rest of the pipeline never sees
+//! a dropped item, so a call to a dropped function is an unknown function like any
+//! other. Predicates are:
+//! - `target_os = "windows"`, `"linux"` or `"macos"`
+//! - `target_arch = "x86_64"` or `"aarch64"`
+//! - `debug`, which holds for builds without optimizations
+//! - `all(...)`, `any(...)` and `not(...)` to combine them
+
+use crate::ast::{CfgPredicate, Item, Module};
+use crate::lexer::{Position, TokenType};
+use super::error::{ParseContext, ParseError, ParseResult};
+use super::parser::Parser;
+use target_lexicon::{Architecture, OperatingSystem, Triple};
+
+/// Values `target_os` can be compared with
+pub const TARGET_OSES: &[&str] = &["windows", "linux", "macos"];
+
+/// Values `target_arch` can be compared with
+pub const TARGET_ARCHES: &[&str] = &["x86_64", "aarch64"];
+
+/// Target `@cfg` predicates are evaluated for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CfgTarget {
+    /// Operating system as `target_os` names it
+    pub os: String,
+    /// Architecture as `target_arch` names it
+    pub arch: String,
+    /// Whether the build is unoptimized
+    pub debug: bool,
+}
+
+impl CfgTarget {
+    pub fn new(triple: &Triple, debug: bool) -> Self {
+        let os = match triple.operating_system {
+            OperatingSystem::Darwin | OperatingSystem::MacOSX { .. } => "macos".to_string(),
+            os => os.to_string(),
+        };
+        let arch = match triple.architecture {
+            Architecture::Aarch64(_) => "aarch64".to_string(),
+            arch => arch.to_string(),
+        };
+        Self { os, arch, debug }
+    }
+
+    /// The machine the compiler runs on
+    pub fn host(debug: bool) -> Self {
+        Self::new(&Triple::host(), debug)
+    }
+}
+
+impl CfgPredicate {
+    /// Whether the predicate holds for `target`
+    pub fn holds(&self, target: &CfgTarget) -> bool {
+        match self {
+            CfgPredicate::TargetOs(os) => *os == target.os,
+            CfgPredicate::TargetArch(arch) => *arch == target.arch,
+            CfgPredicate::Debug => target.debug,
+            CfgPredicate::All(predicates) => predicates.iter().all(|predicate| predicate.holds(target)),
+            CfgPredicate::Any(predicates) => predicates.iter().any(|predicate| predicate.holds(target)),
+            CfgPredicate::Not(predicate) => !predicate.holds(target),
+        }
+    }
+}
+
+/// Drop the items of `module`, and of the modules inside it, with a `@cfg` predicate
+/// that fails for `target`
+pub fn strip_cfg_items(module: &mut Module, target: &CfgTarget) {
+    strip_items(&mut module.items, target);
+}
+
+fn strip_items(items: &mut Vec<Item>, target: &CfgTarget) {
+    items.retain(|item| {
+        item.attributes().iter()
+            .filter_map(|attribute| attribute.cfg.as_ref())
+            .all(|predicate| predicate.holds(target))
+    });
+    for item in items {
+        if let Item::Module { items: Some(items), .. } = item {
+            strip_items(items, target);
+        }
+    }
+}
+
+/// Malformed `@cfg` attribute starting at `at`
+fn cfg_error(message: &str, at: Position) -> ParseError {
+    ParseError::InvalidSyntax {
+        message: format!("Malformed @cfg predicate: {}", message),
+        position: at,
+        context: ParseContext::TopLevel,
+        suggestions: Vec::new(),
+        help: Some(format!(
+            "@cfg takes target_os = {}, target_arch = {}, debug, or all(...), any(...) and not(...) of these",
+            quoted(TARGET_OSES),
+            quoted(TARGET_ARCHES),
+        )),
+    }
+}
+
+fn quoted(values: &[&str]) -> String {
+    values.iter().map(|value| format!("\"{}\"", value)).collect::<Vec<_>>().join(" | ")
+}
+
+impl<'a> Parser<'a> {
+    /// Parse the parenthesized predicate of the `@cfg` attribute starting at `at`
+    pub(super) fn parse_cfg_arguments(&mut self, at: Position) -> ParseResult<CfgPredicate> {
+        self.expect_cfg_token(&TokenType::LeftParen, "@cfg takes a predicate in parentheses", at)?;
+        let predicate = self.parse_cfg_predicate(at)?;
+        self.expect_cfg_token(&TokenType::RightParen, "expected ')' after the predicate", at)?;
+        Ok(predicate)
+    }
+
+    fn parse_cfg_predicate(&mut self, at: Position) -> ParseResult<CfgPredicate> {
+        let name = match self.current_token.as_ref().map(|token| &token.token_type) {
+            Some(TokenType::Identifier(name)) => name.to_string(),
+            _ => return Err(cfg_error("expected a predicate", at)),
+        };
+        self.advance()?;
+
+        match name.as_str() {
+            "target_os" => Ok(CfgPredicate::TargetOs(self.parse_cfg_value(&name, TARGET_OSES, at)?)),
+            "target_arch" => Ok(CfgPredicate::TargetArch(self.parse_cfg_value(&name, TARGET_ARCHES, at)?)),
+            "debug" => Ok(CfgPredicate::Debug),
+            "all" => Ok(CfgPredicate::All(self.parse_cfg_list(&name, at)?)),
+            "any" => Ok(CfgPredicate::Any(self.parse_cfg_list(&name, at)?)),
+            "not" => {
+                let mut predicates = self.parse_cfg_list(&name, at)?;
+                match (predicates.pop(), predicates.is_empty()) {
+                    (Some(predicate), true) => Ok(CfgPredicate::Not(Box::new(predicate))),
+                    _ => Err(cfg_error("not(...) takes exactly one predicate", at)),
+                }
+            }
+            _ => Err(cfg_error(&format!("unknown key '{}'", name), at)),
+        }
+    }
+
+    /// Parse the `= "value"` after `key`, which must be one of `values`
+    fn parse_cfg_value(&mut self, key: &str, values: &[&str], at: Position) -> ParseResult<String> {
+        self.expect_cfg_token(&TokenType::Equal, &format!("expected '=' and a value after '{}'", key), at)?;
+        let value = match self.current_token.as_ref().map(|token| &token.token_type) {
+            Some(TokenType::String { value, .. }) => value.to_string(),
+            _ => return Err(cfg_error(&format!("expected a string after '{} ='", key), at)),
+        };
+        if !values.contains(&value.as_str()) {
+            return Err(cfg_error(&format!("unknown {} \"{}\", expected {}", key, value, quoted(values)), at));
+        }
+        self.advance()?;
+        Ok(value)
+    }
+
+    /// Parse the parenthesized, comma-separated predicates of `combinator`
+    fn parse_cfg_list(&mut self, combinator: &str, at: Position) -> ParseResult<Vec<CfgPredicate>> {
+        self.expect_cfg_token(&TokenType::LeftParen, &format!("expected '(' after '{}'", combinator), at)?;
+        let mut predicates = Vec::new();
+        while !self.check(&TokenType::RightParen) {
+            predicates.push(self.nested(|parser| parser.parse_cfg_predicate(at))?);
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+        }
+        self.expect_cfg_token(&TokenType::RightParen, &format!("expected ')' to close '{}('", combinator), at)?;
+        Ok(predicates)
+    }
+
+    fn expect_cfg_token(&mut self, expected: &TokenType, message: &str, at: Position) -> ParseResult<()> {
+        if !self.check(expected) {
+            return Err(cfg_error(message, at));
+        }
+        self.advance()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> ParseResult<Module> {
+        Parser::new(source, 0)?.parse_module()
+    }
+
+    fn target(os: &str, arch: &str, debug: bool) -> CfgTarget {
+        CfgTarget { os: os.to_string(), arch: arch.to_string(), debug }
+    }
+
+    #[test]
+    fn test_predicates_are_parsed_onto_the_attribute() {
+        let module = parse("@cfg(all(target_os = \"windows\", not(any(target_arch = \"aarch64\", debug)))) fn f() {}").unwrap();
+        let predicate = module.items[0].attributes()[0].cfg.clone();
+        assert_eq!(predicate, Some(CfgPredicate::All(vec![
+            CfgPredicate::TargetOs("windows".to_string()),
+            CfgPredicate::Not(Box::new(CfgPredicate::Any(vec![
+                CfgPredicate::TargetArch("aarch64".to_string()),
+                CfgPredicate::Debug,
+            ]))),
+        ])));
+        assert!(module.items[0].attributes()[0].args.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_predicates_are_located_at_the_attribute() {
+        for source in [
+            "@cfg fn f() {}",
+            "@cfg() fn f() {}",
+            "@cfg(target_family = \"unix\") fn f() {}",
+            "@cfg(target_os = \"freebsd\") fn f() {}",
+            "@cfg(target_os) fn f() {}",
+            "@cfg(target_arch = x86_64) fn f() {}",
+            "@cfg(not(debug, debug)) fn f() {}",
+            "@cfg(not()) fn f() {}",
+            "@cfg(all(debug) fn f() {}",
+            "@cfg(debug, debug) fn f() {}",
+        ] {
+            let source = format!("fn g() {{}}\n  {}", source);
+            let mut parser = Parser::new(&source, 0).unwrap();
+            let errors: Vec<ParseError> = match parser.parse_module() {
+                Ok(_) => parser.errors().to_vec(),
+                Err(error) => vec![error],
+            };
+            assert!(
+                matches!(errors.first(), Some(ParseError::InvalidSyntax { message, position, .. })
+                    if message.starts_with("Malformed @cfg predicate") && (position.line, position.column) == (2, 3)),
+                "{}: {:?}", source, errors
+            );
+        }
+    }
+
+    #[test]
+    fn test_predicates_hold_for_matching_targets() {
+        let linux = target("linux", "x86_64", false);
+        assert!(CfgPredicate::TargetOs("linux".to_string()).holds(&linux));
+        assert!(!CfgPredicate::TargetArch("aarch64".to_string()).holds(&linux));
+        assert!(!CfgPredicate::Debug.holds(&linux));
+        assert!(CfgPredicate::Debug.holds(&target("linux", "x86_64", true)));
+        assert!(CfgPredicate::All(Vec::new()).holds(&linux));
+        assert!(!CfgPredicate::Any(Vec::new()).holds(&linux));
+        assert!(CfgPredicate::Not(Box::new(CfgPredicate::Debug)).holds(&linux));
+
+        let triple = |name: &str| CfgTarget::new(&name.parse().unwrap(), false);
+        assert_eq!(triple("x86_64-pc-windows-msvc"), target("windows", "x86_64", false));
+        assert_eq!(triple("aarch64-apple-darwin"), target("macos", "aarch64", false));
+        assert_eq!(triple("aarch64-unknown-linux-gnu"), target("linux", "aarch64", false));
+    }
+
+    #[test]
+    fn test_items_failing_their_predicates_are_stripped() {
+        let mut parser = Parser::new(
+            "@cfg(target_os = \"windows\") fn win() {}\n\
+             @cfg(target_os = \"linux\") fn linux() {}\n\
+             mod platform { @cfg(not(target_os = \"linux\")) struct Handle; @cfg(debug) fn trace() {} fn shared() {} }\n\
+             @cfg(target_os = \"linux\") @cfg(not(debug)) fn linux_release() {}",
+            0,
+        ).unwrap();
+        let mut module = parser.parse_module().unwrap();
+        strip_cfg_items(&mut module, &target("linux", "x86_64", true));
+
+        let names = |items: &[Item]| -> Vec<String> {
+            items.iter().map(|item| match item {
+                Item::Function { name, .. } | Item::Struct { name, .. } | Item::Module { name, .. } => {
+                    parser.interner().resolve(*name).unwrap_or_default()
+                }
+                other => panic!("unexpected item {:?}", other),
+            }).collect()
+        };
+        assert_eq!(names(&module.items), ["linux", "platform"]);
+        let Item::Module { items: Some(inner), .. } = &module.items[1] else {
+            panic!("expected the inline module, got {:?}", module.items[1]);
+        };
+        assert_eq!(names(inner), ["trace", "shared"]);
+    }
+}
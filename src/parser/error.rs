@@ -7,6 +7,7 @@
 //! - Multi-error reporting capabilities
 //! - Help text and fix suggestions
 
+use crate::diagnostics::DiagnosticCode;
 use crate::lexer::{TokenType, Position};
 use crate::lexer::error::LexerError;
 use std::fmt;
@@ -17,7 +18,7 @@ pub enum ParseError {
     /// Unexpected token with contextual information
     UnexpectedToken {
         expected: Vec<ExpectedToken>,
-        found: TokenType,
+        found: Box<TokenType<'static>>,
         position: Position,
         context: ParseContext,
         suggestions: Vec<Suggestion>,
@@ -40,12 +41,11 @@ pub enum ParseError {
         context: ParseContext,
         suggestions: Vec<Suggestion>,
         help: Option<String>,
-        related_errors: Vec<RelatedError>,
     },
     
     /// Missing delimiter (unclosed parentheses, braces, etc.)
     MissingDelimiter {
-        delimiter: TokenType,
+        delimiter: Box<TokenType<'static>>,
         open_position: Position,
         expected_close_position: Position,
         context: ParseContext,
@@ -54,8 +54,8 @@ pub enum ParseError {
     
     /// Mismatched delimiters
     MismatchedDelimiter {
-        expected: TokenType,
-        found: TokenType,
+        expected: Box<TokenType<'static>>,
+        found: Box<TokenType<'static>>,
         expected_position: Position,
         found_position: Position,
         suggestion: String,
@@ -107,7 +107,7 @@ pub enum ParseError {
         message: String,
         position: Position,
         found_annotation: String,
-        valid_annotations: Vec<String>,
+        valid_annotations: &'static [&'static str],
         suggestions: Vec<String>,
     },
     
@@ -118,6 +118,12 @@ pub enum ParseError {
         debug_info: Option<String>,
     },
     
+    /// Expressions, types, patterns or blocks nested deeper than the parser allows
+    NestingTooDeep {
+        limit: usize,
+        position: Position,
+    },
+    
     /// Lexer error with enhanced context
     LexerError {
         error: LexerError,
@@ -259,28 +265,11 @@ pub enum StatementContext {
 /// Unclosed delimiter information
 #[derive(Debug, Clone, PartialEq)]
 pub struct UnclosedDelimiter {
-    pub delimiter: TokenType,
+    pub delimiter: TokenType<'static>,
     pub open_position: Position,
     pub context: String,
 }
 
-/// Related error information
-#[derive(Debug, Clone, PartialEq)]
-pub struct RelatedError {
-    pub message: String,
-    pub position: Position,
-    pub severity: ErrorSeverity,
-}
-
-/// Error severity levels
-#[derive(Debug, Clone, PartialEq)]
-pub enum ErrorSeverity {
-    Error,
-    Warning,
-    Info,
-    Hint,
-}
-
 /// Invalid identifier reasons
 #[derive(Debug, Clone, PartialEq)]
 pub enum InvalidIdentifierReason {
@@ -297,6 +286,9 @@ pub struct Suggestion {
     pub message: String,
     pub replacement: Option<String>,
     pub position: Position,
+    /// End of the text `replacement` takes the place of; without it the
+    /// replacement is inserted at `position`
+    pub end: Option<Position>,
     pub confidence: f32, // 0.0 to 1.0
     pub category: SuggestionCategory,
 }
@@ -307,6 +299,7 @@ impl Suggestion {
             message: message.to_string(),
             replacement: None,
             position,
+            end: None,
             confidence: 0.8,
             category: SuggestionCategory::General,
         }
@@ -317,6 +310,12 @@ impl Suggestion {
         self
     }
     
+    /// Replace the text from `position` up to `end` rather than insert before it
+    pub fn with_end(mut self, end: Position) -> Self {
+        self.end = Some(end);
+        self
+    }
+    
     pub fn with_confidence(mut self, confidence: f32) -> Self {
         self.confidence = confidence.clamp(0.0, 1.0);
         self
@@ -402,7 +401,7 @@ impl fmt::Display for ParseError {
                 Ok(())
             }
             
-            ParseError::InvalidSyntax { message, position, context, suggestions, help, related_errors } => {
+            ParseError::InvalidSyntax { message, position, context, suggestions, help } => {
                 write!(f, "Invalid syntax at {}: {}", position, message)?;
                 write!(f, "\nContext: {}", context)?;
                 
@@ -413,14 +412,6 @@ impl fmt::Display for ParseError {
                     }
                 }
                 
-                if !related_errors.is_empty() {
-                    write!(f, "\nRelated issues:")?;
-                    for related in related_errors.iter().take(2) {
-                        write!(f, "\n  - {} at {} [{:?}]", 
-                               related.message, related.position, related.severity)?;
-                    }
-                }
-                
                 if let Some(help_text) = help {
                     write!(f, "\nHelp: {}", help_text)?;
                 }
@@ -481,6 +472,10 @@ impl fmt::Display for ParseError {
                 Ok(())
             }
             
+            ParseError::NestingTooDeep { limit, position } => {
+                write!(f, "Expression nesting too deep at {}: more than {} levels", position, limit)
+            }
+            
             ParseError::MultipleErrors { primary, related, summary } => {
                 write!(f, "Multiple parse errors found: {}", summary)?;
                 write!(f, "\nPrimary error: {}", primary)?;
@@ -531,6 +526,15 @@ pub fn suggest_similar_identifiers(input: &str, candidates: &[&str]) -> Vec<Stri
     suggestions.into_iter().take(3).map(|(name, _)| name).collect()
 }
 
+/// Whether `input` is close enough to `candidate` to be a misspelling of it
+///
+/// Stricter than `suggest_similar_identifiers`: allows one edit per three
+/// characters of `candidate`, and at least one.
+pub fn is_likely_typo(input: &str, candidate: &str) -> bool {
+    let distance = levenshtein_distance(input, candidate);
+    distance > 0 && distance <= (candidate.chars().count() / 3).max(1)
+}
+
 /// Calculate similarity score between two strings (Levenshtein-based)
 fn similarity_score(a: &str, b: &str) -> f32 {
     let len_a = a.len();
@@ -575,26 +579,91 @@ fn levenshtein_distance(a: &str, b: &str) -> usize {
 }
 
 /// Create context-aware suggestions for common parsing scenarios
-pub fn suggest_for_context(context: &ParseContext, found_token: &TokenType) -> Vec<Suggestion> {
+pub fn suggest_for_context(context: &ParseContext, found_token: &TokenType, position: Position) -> Vec<Suggestion> {
     match (context, found_token) {
         (ParseContext::FunctionParameters, TokenType::Identifier(_)) => {
-            vec![Suggestion::new("Add type annotation to parameter", Position::start(0))
+            vec![Suggestion::new("Add type annotation to parameter", position)
                 .with_replacement(": Type")
                 .with_category(SuggestionCategory::Syntax)]
         }
         (ParseContext::StructFields, TokenType::RightBrace) => {
-            vec![Suggestion::new("Add field to struct", Position::start(0))
+            vec![Suggestion::new("Add field to struct", position)
                 .with_replacement("field_name: Type,")
                 .with_category(SuggestionCategory::Syntax)]
         }
         (ParseContext::Expression, TokenType::LeftBrace) => {
-            vec![Suggestion::new("Use block expression or struct initialization", Position::start(0))
+            vec![Suggestion::new("Use block expression or struct initialization", position)
                 .with_category(SuggestionCategory::Syntax)]
         }
         _ => Vec::new(),
     }
 }
 
+impl ParseError {
+    /// Source position the error points at
+    pub fn position(&self) -> Position {
+        match self {
+            ParseError::UnexpectedToken { position, .. }
+            | ParseError::UnexpectedEof { position, .. }
+            | ParseError::InvalidSyntax { position, .. }
+            | ParseError::InvalidIdentifier { position, .. }
+            | ParseError::TypeAnnotationError { position, .. }
+            | ParseError::PatternError { position, .. }
+            | ParseError::ExpressionError { position, .. }
+            | ParseError::StatementError { position, .. }
+            | ParseError::MemoryAnnotationError { position, .. }
+            | ParseError::NestingTooDeep { position, .. }
+            | ParseError::InternalError { position, .. } => *position,
+            ParseError::MissingDelimiter { expected_close_position, .. } => *expected_close_position,
+            ParseError::MismatchedDelimiter { found_position, .. } => *found_position,
+            ParseError::LexerError { error, .. } => error.position(),
+            ParseError::MultipleErrors { primary, .. } => primary.position(),
+        }
+    }
+    
+    /// Suggested fixes; errors that only name alternatives have none
+    pub fn suggestions(&self) -> &[Suggestion] {
+        match self {
+            ParseError::UnexpectedToken { suggestions, .. }
+            | ParseError::UnexpectedEof { suggestions, .. }
+            | ParseError::InvalidSyntax { suggestions, .. }
+            | ParseError::PatternError { suggestions, .. }
+            | ParseError::ExpressionError { suggestions, .. }
+            | ParseError::StatementError { suggestions, .. }
+            | ParseError::LexerError { suggestions, .. } => suggestions,
+            ParseError::MultipleErrors { primary, .. } => primary.suggestions(),
+            ParseError::MissingDelimiter { .. }
+            | ParseError::MismatchedDelimiter { .. }
+            | ParseError::InvalidIdentifier { .. }
+            | ParseError::TypeAnnotationError { .. }
+            | ParseError::MemoryAnnotationError { .. }
+            | ParseError::InternalError { .. }
+            | ParseError::NestingTooDeep { .. } => &[],
+        }
+    }
+    
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            ParseError::UnexpectedToken { .. } => DiagnosticCode::UnexpectedToken,
+            ParseError::UnexpectedEof { .. } => DiagnosticCode::UnexpectedEof,
+            ParseError::InvalidSyntax { .. } => DiagnosticCode::InvalidSyntax,
+            ParseError::MissingDelimiter { .. } => DiagnosticCode::MissingDelimiter,
+            ParseError::MismatchedDelimiter { .. } => DiagnosticCode::MismatchedDelimiter,
+            ParseError::InvalidIdentifier { .. } => DiagnosticCode::InvalidIdentifier,
+            ParseError::TypeAnnotationError { .. } => DiagnosticCode::InvalidType,
+            ParseError::PatternError { .. } => DiagnosticCode::InvalidPattern,
+            ParseError::ExpressionError { .. } => DiagnosticCode::InvalidExpression,
+            ParseError::StatementError { .. } => DiagnosticCode::InvalidStatement,
+            ParseError::MemoryAnnotationError { .. } => DiagnosticCode::InvalidMemoryAnnotation,
+            ParseError::NestingTooDeep { .. } => DiagnosticCode::NestingTooDeep,
+            ParseError::InternalError { .. } => DiagnosticCode::InternalError,
+            ParseError::LexerError { error, .. } => error.code(),
+            ParseError::MultipleErrors { primary, .. } => primary.code(),
+        }
+    }
+}
+
 /// Helper functions for creating common error patterns
 impl ParseError {
     /// Create a simple InvalidSyntax error with context
@@ -605,7 +674,6 @@ impl ParseError {
             context,
             suggestions: Vec::new(),
             help: None,
-            related_errors: Vec::new(),
         }
     }
     
@@ -619,7 +687,7 @@ impl ParseError {
     ) -> Self {
         ParseError::UnexpectedToken {
             expected: vec![ExpectedToken::new(expected, description)],
-            found,
+            found: Box::new(found.into_owned()),
             position,
             context,
             suggestions: Vec::new(),
@@ -648,7 +716,7 @@ impl ParseError {
         message: &str,
         position: Position,
         found_annotation: &str,
-        valid_annotations: Vec<String>,
+        valid_annotations: &'static [&'static str],
     ) -> Self {
         ParseError::MemoryAnnotationError {
             message: message.to_string(),
@@ -682,7 +750,7 @@ mod tests {
     fn test_error_display() {
         let error = ParseError::UnexpectedToken {
             expected: vec![ExpectedToken::new("identifier", "variable name")],
-            found: TokenType::Integer { value: "42".to_string(), base: crate::lexer::token::NumberBase::Decimal, suffix: None },
+            found: Box::new(TokenType::Integer { value: "42".into(), base: crate::lexer::token::NumberBase::Decimal, suffix: None }),
             position: Position::start(0),
             context: ParseContext::FunctionDeclaration,
             suggestions: vec![Suggestion::new("Use a valid identifier", Position::start(0))],
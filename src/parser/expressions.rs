@@ -1,14 +1,16 @@
 //! Expression parsing with operator precedence for Bract
 
-use crate::lexer::TokenType;
-use crate::ast::{Expr, Span, BinaryOp, UnaryOp, Literal};
+use crate::lexer::{Position, TokenType};
+use crate::lexer::token::NumberBase;
+use crate::ast::{Expr, Stmt, Span, BinaryOp, UnaryOp, Literal, Parameter, PrimitiveType, NodeId};
 use super::parser::Parser;
+use super::statements::single_pattern_arms;
 use super::error::{ParseError, ParseResult, ParseContext, ExpectedToken, Suggestion, SuggestionCategory};
 
 impl<'a> Parser<'a> {
     /// Parse an expression (entry point for expression parsing)
     pub fn parse_expression(&mut self) -> ParseResult<Expr> {
-        self.parse_assignment_expression()
+        self.nested(Self::parse_assignment_expression)
     }
     
     /// Parse assignment expressions (lowest precedence)
@@ -25,13 +27,14 @@ impl<'a> Parser<'a> {
         if self.match_token(&TokenType::Question) {
             let then_expr = self.parse_expression()?;
             self.expect(TokenType::Colon, "ternary expression")?;
-            let else_expr = self.parse_ternary_expression()?;
+            let else_expr = self.nested(Self::parse_ternary_expression)?;
             let span = Span::new(expr.span().start, else_expr.span().end);
             Ok(Expr::If {
                 condition: Box::new(expr),
                 then_block: Box::new(then_expr),
                 else_block: Some(Box::new(else_expr)),
                 span,
+                id: NodeId::DUMMY,
             })
         } else {
             Ok(expr)
@@ -40,96 +43,117 @@ impl<'a> Parser<'a> {
     
     /// Parse logical OR expressions
     pub fn parse_logical_or_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_logical_and_expression()?;
         
         while self.match_token(&TokenType::LogicalOr) {
             let right = self.parse_logical_and_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: BinaryOp::LogicalOr,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
     /// Parse logical AND expressions
     pub fn parse_logical_and_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_bitwise_or_expression()?;
         
         while self.match_token(&TokenType::LogicalAnd) {
             let right = self.parse_bitwise_or_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: BinaryOp::LogicalAnd,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
     /// Parse bitwise OR expressions
     pub fn parse_bitwise_or_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_bitwise_xor_expression()?;
         
         while self.match_token(&TokenType::Or) {
             let right = self.parse_bitwise_xor_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: BinaryOp::BitwiseOr,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
     /// Parse bitwise XOR expressions
     pub fn parse_bitwise_xor_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_bitwise_and_expression()?;
         
         while self.match_token(&TokenType::Caret) {
             let right = self.parse_bitwise_and_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: BinaryOp::BitwiseXor,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
     /// Parse bitwise AND expressions
     pub fn parse_bitwise_and_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_equality_expression()?;
         
         while self.match_token(&TokenType::And) {
             let right = self.parse_equality_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op: BinaryOp::BitwiseAnd,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
     /// Parse equality expressions
     pub fn parse_equality_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_relational_expression()?;
         
         while let Some(token) = &self.current_token {
@@ -140,20 +164,24 @@ impl<'a> Parser<'a> {
             };
             self.advance()?;
             let right = self.parse_relational_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
     /// Parse relational expressions (< > <= >=)
     pub fn parse_relational_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_range_expression()?;
         
         while let Some(token) = &self.current_token {
@@ -166,21 +194,24 @@ impl<'a> Parser<'a> {
             };
             self.advance()?;
             let right = self.parse_range_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
     /// Parse range expressions (.. and ..=)
     pub fn parse_range_expression(&mut self) -> ParseResult<Expr> {
-        let expr = self.parse_additive_expression()?;
+        let expr = self.parse_shift_expression()?;
         
         if let Some(token) = &self.current_token {
             match &token.token_type {
@@ -195,7 +226,7 @@ impl<'a> Parser<'a> {
                     
                     // Check if there's an end expression
                     let end = if self.is_expression_token() {
-                        Some(Box::new(self.parse_additive_expression()?))
+                        Some(Box::new(self.parse_shift_expression()?))
                     } else {
                         None
                     };
@@ -210,6 +241,7 @@ impl<'a> Parser<'a> {
                         end,
                         inclusive,
                         span,
+                        id: NodeId::DUMMY,
                     })
                 }
                 _ => Ok(expr),
@@ -219,8 +251,37 @@ impl<'a> Parser<'a> {
         }
     }
     
+    /// Parse shift expressions (<< >>)
+    pub fn parse_shift_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
+        let mut expr = self.parse_additive_expression()?;
+        
+        while let Some(token) = &self.current_token {
+            let op = match &token.token_type {
+                TokenType::LeftShift => BinaryOp::LeftShift,
+                TokenType::RightShift => BinaryOp::RightShift,
+                _ => break,
+            };
+            self.advance()?;
+            let right = self.parse_additive_expression()?;
+            self.chain_link()?;
+            let span = Span::new(expr.span().start, right.span().end);
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op,
+                right: Box::new(right),
+                span,
+                id: NodeId::DUMMY,
+            };
+        }
+        
+        self.end_chain(before);
+        Ok(expr)
+    }
+    
     /// Parse additive expressions
     pub fn parse_additive_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_multiplicative_expression()?;
         
         while let Some(token) = &self.current_token {
@@ -231,21 +292,25 @@ impl<'a> Parser<'a> {
             };
             self.advance()?;
             let right = self.parse_multiplicative_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
     /// Parse multiplicative expressions
     pub fn parse_multiplicative_expression(&mut self) -> ParseResult<Expr> {
-        let mut expr = self.parse_unary_expression()?;
+        let before = self.start_chain();
+        let mut expr = self.parse_cast_expression()?;
         
         while let Some(token) = &self.current_token {
             let op = match &token.token_type {
@@ -255,16 +320,43 @@ impl<'a> Parser<'a> {
                 _ => break,
             };
             self.advance()?;
-            let right = self.parse_unary_expression()?;
+            let right = self.parse_cast_expression()?;
+            self.chain_link()?;
             let span = Span::new(expr.span().start, right.span().end);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 op,
                 right: Box::new(right),
                 span,
+                id: NodeId::DUMMY,
             };
         }
         
+        self.end_chain(before);
+        Ok(expr)
+    }
+    
+    /// Parse cast expressions (expr as Type)
+    ///
+    /// Casts bind looser than unary operators, so `-x as i64` casts `-x`, and
+    /// chain left to right: `x as i32 as f64`.
+    pub fn parse_cast_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
+        let mut expr = self.parse_unary_expression()?;
+        
+        while self.match_token(&TokenType::As) {
+            let target_type = self.parse_type()?;
+            self.chain_link()?;
+            let span = Span::new(expr.span().start, target_type.span().end);
+            expr = Expr::Cast {
+                expr: Box::new(expr),
+                target_type,
+                span,
+                id: NodeId::DUMMY,
+            };
+        }
+        
+        self.end_chain(before);
         Ok(expr)
     }
     
@@ -283,12 +375,20 @@ impl<'a> Parser<'a> {
             let start_pos = token.position;
             self.advance()?;
             
-            let expr = self.parse_unary_expression()?;
+            // `&mut expr` takes a mutable reference
+            let op = if op == UnaryOp::AddressOf && self.match_token(&TokenType::Mut) {
+                UnaryOp::MutableRef
+            } else {
+                op
+            };
+            
+            let expr = self.nested(Self::parse_unary_expression)?;
             let span = Span::new(start_pos, expr.span().end);
             Ok(Expr::Unary {
                 op,
                 expr: Box::new(expr),
                 span,
+                id: NodeId::DUMMY,
             })
         } else {
             self.parse_postfix_expression()
@@ -297,6 +397,7 @@ impl<'a> Parser<'a> {
     
     /// Parse postfix expressions (function calls, method calls, field access, indexing)
     pub fn parse_postfix_expression(&mut self) -> ParseResult<Expr> {
+        let before = self.start_chain();
         let mut expr = self.parse_primary_expression()?;
         
         loop {
@@ -305,26 +406,31 @@ impl<'a> Parser<'a> {
                     // Function calls: expr(args)
                     TokenType::LeftParen => {
                         self.advance()?; // consume '('
-                        let mut args = Vec::new();
-                        
-                        if !self.check(&TokenType::RightParen) {
-                            args.push(self.parse_expression()?);
-                            
-                            while self.match_token(&TokenType::Comma) {
-                                if self.check(&TokenType::RightParen) {
-                                    break; // trailing comma
+                        // Struct literals are unambiguous inside the parentheses
+                        let args = self.with_struct_literals(true, |parser| {
+                            let mut args = Vec::new();
+                            if !parser.check(&TokenType::RightParen) {
+                                args.push(parser.parse_expression()?);
+                                
+                                while parser.match_token(&TokenType::Comma) {
+                                    if parser.check(&TokenType::RightParen) {
+                                        break; // trailing comma
+                                    }
+                                    args.push(parser.parse_expression()?);
                                 }
-                                args.push(self.parse_expression()?);
                             }
-                        }
+                            Ok(args)
+                        })?;
                         
                         let end_token = self.expect(TokenType::RightParen, "function call")?;
                         let span = Span::new(expr.span().start, end_token.position);
                         
+                        self.chain_link()?;
                         expr = Expr::Call {
                             callee: Box::new(expr),
                             args,
                             span,
+                            id: NodeId::DUMMY,
                         };
                     }
                     
@@ -333,6 +439,22 @@ impl<'a> Parser<'a> {
                         self.advance()?; // consume '.'
                         
                         if let Some(field_token) = &self.current_token {
+                            // Tuple fields: `pair.0`, and `nested.0.1` which lexes as a float
+                            if let Some(indices) = tuple_field_indices(&field_token.token_type) {
+                                let field_pos = field_token.position;
+                                self.advance()?;
+                                for index in indices {
+                                    let span = Span::new(expr.span().start, field_pos);
+                                    self.chain_link()?;
+                                    expr = Expr::FieldAccess {
+                                        object: Box::new(expr),
+                                        field: self.interner.intern(&index),
+                                        span,
+                                        id: NodeId::DUMMY,
+                                    };
+                                }
+                                continue;
+                            }
                             if let TokenType::Identifier(field_name) = &field_token.token_type {
                                 let field = self.interner.intern(field_name);
                                 let field_pos = field_token.position;
@@ -358,25 +480,29 @@ impl<'a> Parser<'a> {
                                     let end_token = self.expect(TokenType::RightParen, "method call")?;
                                     let span = Span::new(expr.span().start, end_token.position);
                                     
+                                    self.chain_link()?;
                                     expr = Expr::MethodCall {
                                         receiver: Box::new(expr),
                                         method: field,
                                         args,
                                         span,
+                                        id: NodeId::DUMMY,
                                     };
                                 } else {
                                     // This is field access: expr.field
                                     let span = Span::new(expr.span().start, field_pos);
+                                    self.chain_link()?;
                                     expr = Expr::FieldAccess {
                                         object: Box::new(expr),
                                         field,
                                         span,
+                                        id: NodeId::DUMMY,
                                     };
                                 }
                             } else {
                                 return Err(ParseError::UnexpectedToken {
                                     expected: vec![ExpectedToken::new("field name", "identifier for field access")],
-                                    found: field_token.token_type.clone(),
+                                    found: Box::new(field_token.token_type.clone().into_owned()),
                                     position: field_token.position,
                                     context: ParseContext::Expression,
                                     suggestions: vec![
@@ -403,24 +529,30 @@ impl<'a> Parser<'a> {
                     // Array indexing: expr[index]
                     TokenType::LeftBracket => {
                         self.advance()?; // consume '['
-                        let index = self.parse_expression()?;
+                        let index = self.with_struct_literals(true, |parser| parser.parse_expression())?;
                         let end_token = self.expect(TokenType::RightBracket, "array indexing")?;
                         let span = Span::new(expr.span().start, end_token.position);
                         
+                        self.chain_link()?;
                         expr = Expr::Index {
                             object: Box::new(expr),
                             index: Box::new(index),
                             span,
+                            id: NodeId::DUMMY,
                         };
                     }
                     
                     // Struct initialization: expr { field: value, ... }
-                    TokenType::LeftBrace => {
+                    TokenType::LeftBrace if self.struct_literals_allowed() => {
                         // Only handle struct initialization if the current expression is a path
-                        match &expr {
-                            Expr::Identifier { name, .. } => {
+                        let struct_path = match &expr {
+                            Expr::Identifier { name, .. } => Some(vec![*name]),
+                            Expr::Path { segments, .. } => Some(segments.clone()),
+                            _ => None,
+                        };
+                        match struct_path {
+                            Some(struct_path) => {
                                 // This is struct initialization: StructName { fields... }
-                                let struct_path = vec![*name];
                                 self.advance()?; // consume '{'
                                 
                                 let mut fields = Vec::new();
@@ -436,7 +568,7 @@ impl<'a> Parser<'a> {
                                             } else {
                                                 return Err(ParseError::UnexpectedToken {
                                                     expected: vec![ExpectedToken::new("field name", "identifier for field access")],
-                                                    found: token.token_type.clone(),
+                                                    found: Box::new(token.token_type.clone().into_owned()),
                                                     position: token.position,
                                                     context: ParseContext::Expression,
                                                     suggestions: vec![
@@ -488,9 +620,10 @@ impl<'a> Parser<'a> {
                                     path: struct_path,
                                     fields,
                                     span,
+                                    id: NodeId::DUMMY,
                                 };
                             }
-                            _ => break, // Not a struct initialization, stop postfix parsing
+                            None => break, // Not a struct initialization, stop postfix parsing
                         }
                     }
                     
@@ -501,6 +634,7 @@ impl<'a> Parser<'a> {
             }
         }
         
+        self.end_chain(before);
         Ok(expr)
     }
     
@@ -510,27 +644,13 @@ impl<'a> Parser<'a> {
             let start_pos = token.position;
             
             match &token.token_type {
-                TokenType::Integer { value, base, suffix } => {
-                    let literal = Literal::Integer {
-                        value: value.clone(),
-                        base: *base,
-                        suffix: suffix.as_ref().map(|s| self.interner.intern(s)),
-                    };
-                    self.advance()?;
-                    Ok(Expr::Literal {
-                        literal,
-                        span: Span::single(start_pos),
-                    })
-                }
-                TokenType::Float { value, suffix } => {
-                    let literal = Literal::Float {
-                        value: value.clone(),
-                        suffix: suffix.as_ref().map(|s| self.interner.intern(s)),
-                    };
+                TokenType::Integer { .. } | TokenType::Float { .. } => {
+                    let literal = number_literal(&token.token_type, start_pos, ParseContext::Expression)?;
                     self.advance()?;
                     Ok(Expr::Literal {
                         literal,
                         span: Span::single(start_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 TokenType::String { value, raw, raw_delimiter } => {
@@ -543,6 +663,7 @@ impl<'a> Parser<'a> {
                     Ok(Expr::Literal {
                         literal,
                         span: Span::single(start_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 TokenType::Char(ch) => {
@@ -551,6 +672,7 @@ impl<'a> Parser<'a> {
                     Ok(Expr::Literal {
                         literal,
                         span: Span::single(start_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 TokenType::True => {
@@ -559,6 +681,7 @@ impl<'a> Parser<'a> {
                     Ok(Expr::Literal {
                         literal,
                         span: Span::single(start_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 TokenType::False => {
@@ -567,6 +690,7 @@ impl<'a> Parser<'a> {
                     Ok(Expr::Literal {
                         literal,
                         span: Span::single(start_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 TokenType::Null => {
@@ -575,25 +699,62 @@ impl<'a> Parser<'a> {
                     Ok(Expr::Literal {
                         literal,
                         span: Span::single(start_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 TokenType::Identifier(name) => {
                     let name_interned = self.interner.intern(name);
                     self.advance()?;
-                    Ok(Expr::Identifier {
-                        name: name_interned,
-                        span: Span::single(start_pos),
+                    if !self.check(&TokenType::DoubleColon) {
+                        return Ok(Expr::Identifier {
+                            name: name_interned,
+                            span: Span::single(start_pos),
+                            id: NodeId::DUMMY,
+                        });
+                    }
+                    
+                    // Path through modules or to an enum variant: `helpers::f`
+                    let mut segments = vec![name_interned];
+                    let mut end_pos = start_pos;
+                    while self.match_token(&TokenType::DoubleColon) {
+                        end_pos = self.current_position();
+                        segments.push(self.expect_name("path segment")?);
+                    }
+                    Ok(Expr::Path {
+                        segments,
+                        span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 TokenType::LeftParen => {
                     self.advance()?;
-                    let expr = self.parse_expression()?;
+                    // `(a)` is a parenthesized expression; `()`, `(a,)` and `(a, b)` are tuples
+                    let (mut elements, is_tuple) = self.with_struct_literals(true, |parser| {
+                        let mut elements = Vec::new();
+                        let mut is_tuple = parser.check(&TokenType::RightParen);
+                        if !is_tuple {
+                            elements.push(parser.parse_expression()?);
+                            while parser.match_token(&TokenType::Comma) {
+                                is_tuple = true;
+                                if parser.check(&TokenType::RightParen) {
+                                    break; // trailing comma
+                                }
+                                elements.push(parser.parse_expression()?);
+                            }
+                        }
+                        Ok((elements, is_tuple))
+                    })?;
                     let end_token = self.expect(TokenType::RightParen, "parenthesized expression")?;
                     let span = Span::new(start_pos, end_token.position);
-                    Ok(Expr::Parenthesized {
-                        expr: Box::new(expr),
-                        span,
-                    })
+                    if is_tuple {
+                        Ok(Expr::Tuple { elements, span, id: NodeId::DUMMY })
+                    } else {
+                        Ok(Expr::Parenthesized {
+                            expr: Box::new(elements.remove(0)),
+                            span,
+                            id: NodeId::DUMMY,
+                        })
+                    }
                 }
                 TokenType::LeftBrace => {
                     // Parse block expression
@@ -604,23 +765,26 @@ impl<'a> Parser<'a> {
                     while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
                         // Check if this looks like a statement keyword
                         if self.is_statement_start() {
-                            statements.push(self.parse_statement()?);
+                            match self.parse_statement()? {
+                                // A match ending the block is its value
+                                Stmt::Match { expr, arms, span, .. } if self.check(&TokenType::RightBrace) => {
+                                    trailing_expr = Some(Box::new(Expr::Match { expr: Box::new(expr), arms, span, id: NodeId::DUMMY }));
+                                    break;
+                                }
+                                stmt => statements.push(stmt),
+                            }
                         } else {
                             // Try to parse as expression
+                            let expr_start = self.current_position();
                             let expr = self.parse_expression()?;
                             
-                            // Check if there's a semicolon (making it a statement)
-                            if self.match_token(&TokenType::Semicolon) {
-                                let expr_span = expr.span();
-                                statements.push(crate::ast::Stmt::Expression {
-                                    expr,
-                                    span: expr_span,
-                                });
-                            } else {
+                            // Check if there's a semicolon or assignment (making it a statement)
+                            if self.check(&TokenType::RightBrace) {
                                 // No semicolon - this is the trailing expression
                                 trailing_expr = Some(Box::new(expr));
                                 break;
                             }
+                            statements.push(self.parse_expression_statement_tail(expr_start, expr)?);
                         }
                     }
                     
@@ -631,16 +795,28 @@ impl<'a> Parser<'a> {
                         statements,
                         trailing_expr,
                         span,
+                        id: NodeId::DUMMY,
                     })
                 }
                 TokenType::LeftBracket => {
-                    // Parse array literal: [expr1, expr2, ...]
+                    // Parse array literal: [expr1, expr2, ...] or repeat expression: [value; count]
                     self.advance()?; // consume '['
                     let mut elements = Vec::new();
                     
                     while !self.check(&TokenType::RightBracket) && !self.is_at_end() {
                         elements.push(self.parse_expression()?);
                         
+                        if elements.len() == 1 && self.match_token(&TokenType::Semicolon) {
+                            let count = Box::new(self.parse_expression()?);
+                            let end_token = self.expect(TokenType::RightBracket, "array repeat expression")?;
+                            return Ok(Expr::ArrayRepeat {
+                                value: Box::new(elements.remove(0)),
+                                count,
+                                span: Span::new(start_pos, end_token.position),
+                                id: NodeId::DUMMY,
+                            });
+                        }
+                        
                         if !self.match_token(&TokenType::Comma) {
                             break;
                         }
@@ -657,13 +833,33 @@ impl<'a> Parser<'a> {
                     Ok(Expr::Array {
                         elements,
                         span,
+                        id: NodeId::DUMMY,
                     })
                 }
+                TokenType::Or | TokenType::LogicalOr | TokenType::Move => self.parse_closure_expression(),
                 TokenType::If => {
                     // Parse if expression: if condition { then_block } else { else_block }
                     self.advance()?; // consume 'if'
                     
-                    let condition = Box::new(self.parse_expression()?);
+                    if self.check(&TokenType::Let) {
+                        // `if let pattern = expr { .. } else { .. }` is a two-armed match
+                        let (pattern, expr) = self.parse_let_condition()?;
+                        let then_block = self.parse_block_expression()?;
+                        let else_block = if self.match_token(&TokenType::Else) {
+                            Some(self.parse_block_expression()?)
+                        } else {
+                            None
+                        };
+                        let end_pos = else_block.as_ref().unwrap_or(&then_block).span().end;
+                        return Ok(Expr::Match {
+                            expr: Box::new(expr),
+                            arms: single_pattern_arms(pattern, then_block, else_block),
+                            span: Span::new(start_pos, end_pos),
+                            id: NodeId::DUMMY,
+                        });
+                    }
+                    
+                    let condition = Box::new(self.parse_condition_expression()?);
                     let then_block = Box::new(self.parse_block_expression()?);
                     
                     let else_block = if self.match_token(&TokenType::Else) {
@@ -683,6 +879,32 @@ impl<'a> Parser<'a> {
                         then_block,
                         else_block,
                         span,
+                        id: NodeId::DUMMY,
+                    })
+                }
+                TokenType::Loop => {
+                    // Parse loop expression: loop { body }, valued by its `break`s
+                    self.advance()?; // consume 'loop'
+                    
+                    let body = Box::new(self.parse_block_expression()?);
+                    let span = Span::new(start_pos, body.span().end);
+                    
+                    Ok(Expr::Loop {
+                        label: None,
+                        body,
+                        span,
+                        id: NodeId::DUMMY,
+                    })
+                }
+                TokenType::Match => {
+                    // Parse match expression: match scrutinee { pattern => value, ... }
+                    let (expr, arms) = self.parse_match()?;
+                    let span = Span::new(start_pos, self.current_position());
+                    Ok(Expr::Match {
+                        expr: Box::new(expr),
+                        arms,
+                        span,
+                        id: NodeId::DUMMY,
                     })
                 }
                 _ => Err(ParseError::InvalidSyntax {
@@ -694,7 +916,6 @@ impl<'a> Parser<'a> {
                             .with_category(SuggestionCategory::Syntax)
                     ],
                     help: Some("Expressions can be literals (42, true, \"hello\"), identifiers (variable), or complex expressions".to_string()),
-                    related_errors: Vec::new(),
                 }),
             }
         } else {
@@ -711,6 +932,60 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parse a closure: `[move] |params| body` or `[move] |params| -> T { body }`
+    ///
+    /// Parameters may carry type annotations. With a return type the body must be a block.
+    fn parse_closure_expression(&mut self) -> ParseResult<Expr> {
+        let start_pos = self.current_position();
+        let is_move = self.match_token(&TokenType::Move);
+        
+        // `||` is an empty parameter list
+        let mut params = Vec::new();
+        if !self.match_token(&TokenType::LogicalOr) {
+            self.expect(TokenType::Or, "closure parameters")?;
+            while !self.check(&TokenType::Or) && !self.is_at_end() {
+                let param_start = self.current_position();
+                let pattern = self.parse_range_pattern()?;
+                let type_annotation = if self.match_token(&TokenType::Colon) {
+                    Some(self.parse_type()?)
+                } else {
+                    None
+                };
+                params.push(Parameter {
+                    pattern,
+                    type_annotation,
+                    span: Span::new(param_start, self.current_position()),
+                });
+                
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+            self.expect(TokenType::Or, "closure parameters")?;
+        }
+        
+        let return_type = if self.match_token(&TokenType::Arrow) {
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        let body = if return_type.is_some() {
+            self.parse_block_expression()?
+        } else {
+            self.parse_expression()?
+        };
+        
+        let span = Span::new(start_pos, body.span().end);
+        Ok(Expr::Closure {
+            is_move,
+            params,
+            return_type,
+            body: Box::new(body),
+            span,
+            id: NodeId::DUMMY,
+        })
+    }
+
     /// Check if the current token can start an expression
     fn is_expression_token(&self) -> bool {
         if let Some(token) = &self.current_token {
@@ -722,10 +997,67 @@ impl<'a> Parser<'a> {
                 TokenType::LeftBracket | TokenType::LeftBrace |
                 TokenType::Not | TokenType::Minus | TokenType::Plus |
                 TokenType::Star | TokenType::And | TokenType::Tilde |
-                TokenType::Box | TokenType::Move | TokenType::If
+                TokenType::Box | TokenType::Move | TokenType::If |
+                TokenType::Loop | TokenType::Match | TokenType::Or | TokenType::LogicalOr
             )
         } else {
             false
         }
     }
 } 
+
+/// Tuple field indices named by the token after a `.`
+///
+/// `t.0` gives an integer token; `t.0.1` gives the float `0.1`, which names two fields.
+fn tuple_field_indices(token_type: &TokenType) -> Option<Vec<String>> {
+    let is_index = |digits: &str| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+    match token_type {
+        TokenType::Integer { value, base: NumberBase::Decimal, suffix: None } if is_index(value) => {
+            Some(vec![value.to_string()])
+        }
+        TokenType::Float { value, suffix: None } => {
+            let (first, second) = value.split_once('.')?;
+            (is_index(first) && is_index(second)).then(|| vec![first.to_string(), second.to_string()])
+        }
+        _ => None,
+    }
+}
+
+/// Literal for a number token, with its type suffix checked
+///
+/// Integer literals take any integer or float type (`255u8`, `1f32`), float literals
+/// only a float type.
+pub(super) fn number_literal(token_type: &TokenType, position: Position, context: ParseContext) -> ParseResult<Literal> {
+    let (value, base, suffix) = match token_type {
+        TokenType::Integer { value, base, suffix } => (value, Some(*base), suffix),
+        TokenType::Float { value, suffix } => (value, None, suffix),
+        _ => return Err(ParseError::invalid_syntax("Expected a number literal", position, context)),
+    };
+    let kind = if base.is_some() { "integer" } else { "float" };
+    let suffix = match suffix.as_deref().map(|name| (name, PrimitiveType::from_name(name))) {
+        None => None,
+        Some((_, Some(suffix))) if suffix.is_float() || (suffix.is_integer() && base.is_some()) => Some(suffix),
+        Some((name, _)) => return Err(ParseError::InvalidSyntax {
+            message: format!("Invalid suffix '{}' for {} literal", name, kind),
+            position,
+            context,
+            suggestions: Vec::new(),
+            help: Some(match base {
+                Some(_) => "Integer literals take an integer or float type suffix, e.g. `255u8`, `10i64` or `1f32`".to_string(),
+                None => "Float literals take a float type suffix, e.g. `1.5f32`".to_string(),
+            }),
+        }),
+    };
+    
+    Ok(match (base, suffix) {
+        // A float suffix makes a float: `1f32` is `1.0f32`
+        (Some(NumberBase::Decimal) | None, Some(suffix)) if suffix.is_float() => Literal::Float { value: value.to_string(), suffix: Some(suffix) },
+        (Some(_), Some(suffix)) if suffix.is_float() => return Err(ParseError::invalid_syntax(
+            "Only decimal integer literals can take a float suffix",
+            position,
+            context,
+        )),
+        (Some(base), suffix) => Literal::Integer { value: value.to_string(), base, suffix },
+        (None, suffix) => Literal::Float { value: value.to_string(), suffix },
+    })
+}
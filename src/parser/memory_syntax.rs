@@ -7,17 +7,30 @@
 //! - Strategy wrapper types: LinearPtr<T>, SmartPtr<T>
 //! - Express performance contracts with memory constraints
 
-use crate::ast::{Type, Expr, Span, InternedString, MemoryStrategy, TypeBound};
-use crate::lexer::{TokenType};
-use super::parser::Parser;
-use super::error::{ParseError, ParseResult, ParseContext};
+use std::borrow::Cow;
+use crate::ast::{
+    Type, Expr, Item, Span, InternedString, MemoryStrategy, TypeBound,
+    PerformanceContract, CpuBound, MemoryBound, LatencyBound, Attribute, AttributeArg, Literal, NodeId,
+};
+use crate::lexer::{Position, TokenType};
+use super::parser::{Parser, StringInterner};
+use super::error::{
+    ParseError, ParseResult, ParseContext, Suggestion, SuggestionCategory,
+    suggest_similar_identifiers, is_likely_typo,
+};
+use std::time::Duration;
+
+/// Attribute names the compiler understands; any other well-formed attribute is kept as written
+const KNOWN_ATTRIBUTES: &[&str] = &[
+    "memory", "performance", "guarantee", "manual", "smart", "linear", "stack", "nogc", "region", "align", "doc", "cfg",
+];
 
 /// Memory strategy annotation syntax parser
 #[derive(Debug, Clone, PartialEq)]
 pub struct MemoryAnnotation {
     pub strategy: Option<MemoryStrategy>,
     pub size_hint: Option<u64>,
-    pub alignment: Option<u8>,
+    pub alignment: Option<u32>,
     pub region: Option<InternedString>,
     pub span: Span,
 }
@@ -28,9 +41,37 @@ pub struct PerformanceAnnotation {
     pub max_cost: Option<u64>,
     pub max_memory: Option<u64>,
     pub max_latency_ms: Option<u32>,
+    pub deterministic: bool,
     pub span: Span,
 }
 
+impl PerformanceAnnotation {
+    /// Convert to the contract carried by `Item::Function`
+    pub fn to_contract(&self) -> PerformanceContract {
+        PerformanceContract {
+            cpu_bound: self.max_cost.map(CpuBound::Cycles),
+            memory_bound: self.max_memory.map(MemoryBound::Bytes),
+            allocation_bound: None,
+            latency_bound: self.max_latency_ms.map(|ms| LatencyBound {
+                max_latency: Duration::from_millis(u64::from(ms)),
+            }),
+            stack_bound: None,
+            deterministic: self.deterministic,
+            wait_free: false,
+            span: self.span,
+        }
+    }
+}
+
+/// Annotations written in front of a top-level item
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ItemAnnotations {
+    pub memory: Option<MemoryAnnotation>,
+    pub performance: Option<PerformanceAnnotation>,
+    /// Every attribute as written, `@memory` and `@performance` included
+    pub attributes: Vec<Attribute>,
+}
+
 /// Region block syntax: region "name" { ... }
 #[derive(Debug, Clone, PartialEq)]
 pub struct RegionBlock {
@@ -50,154 +91,317 @@ pub struct VariableDeclaration {
 }
 
 impl<'a> Parser<'a> {
-    /// Parse @memory annotation
-    pub fn parse_memory_annotation(&mut self) -> ParseResult<MemoryAnnotation> {
+    /// Parse `@name` or `@name(arguments)`, keeping the span of the attribute and of each argument
+    pub fn parse_attribute(&mut self) -> ParseResult<Attribute> {
         let start_pos = self.current_position();
         
-        // Expect @memory
-        self.expect(TokenType::At, "memory annotation")?;
-        if !self.match_identifier("memory") {
-            return Err(ParseError::invalid_syntax(
-                "Expected 'memory' after '@'",
-                self.current_position(),
-                ParseContext::MemoryAnnotation,
-            ));
-        }
+        self.expect(TokenType::At, "attribute")?;
+        let name = self.expect_annotation_identifier("attribute name")?;
         
-        self.expect(TokenType::LeftParen, "memory annotation parameters")?;
+        let mut args = Vec::new();
+        let mut cfg = None;
+        if name == "cfg" {
+            cfg = Some(self.parse_cfg_arguments(start_pos)?);
+        } else if self.check(&TokenType::LeftParen) {
+            self.expect(TokenType::LeftParen, "attribute arguments")?;
+            while !self.check(&TokenType::RightParen) && !self.is_at_end() {
+                args.push(self.parse_attribute_arg()?);
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+            self.expect(TokenType::RightParen, "attribute arguments")?;
+        }
         
-        let mut annotation = MemoryAnnotation {
-            strategy: None,
-            size_hint: None,
-            alignment: None,
-            region: None,
+        Ok(Attribute {
+            name: self.interner.intern(&name),
+            args,
+            cfg,
             span: Span::new(start_pos, self.current_position()),
+        })
+    }
+    
+    /// Parse one attribute argument: `key = value` or a positional `value`
+    fn parse_attribute_arg(&mut self) -> ParseResult<AttributeArg> {
+        let start_pos = self.current_position();
+        
+        let is_keyed = matches!(self.current_token.as_ref().map(|token| &token.token_type), Some(TokenType::Identifier(_)))
+            && matches!(self.peek_token().map(|token| &token.token_type), Some(TokenType::Equal));
+        let key = if is_keyed {
+            let key = self.expect_annotation_identifier("argument name")?;
+            self.expect(TokenType::Equal, "argument value")?;
+            Some(self.interner.intern(&key))
+        } else {
+            None
         };
         
-        // Parse parameter list
-        while !self.check(&TokenType::RightParen) {
-            let param_name = self.expect_identifier("parameter name")?;
-            
-            if !["strategy", "size_hint", "alignment", "region"].contains(&param_name.as_str()) {
-                return Err(ParseError::memory_annotation_error(
-                    &format!("Unknown parameter: {}", param_name),
-                    self.current_position(),
-                    &param_name,
-                    vec!["strategy".to_string(), "size_hint".to_string(), "alignment".to_string(), "region".to_string()],
-                ));
-            }
-            
-            self.expect(TokenType::Equal, "parameter value")?;
-            
-            match param_name.as_str() {
-                "strategy" => {
-                    annotation.strategy = Some(self.parse_memory_strategy_value()?);
-                }
-                "size_hint" => {
-                    annotation.size_hint = Some(self.parse_size_value()?);
-                }
-                "alignment" => {
-                    annotation.alignment = Some(self.parse_alignment_value()?);
-                }
-                "region" => {
-                    annotation.region = Some(self.parse_region_identifier()?);
-                }
-                _ => unreachable!(),
-            }
-            
-            if !self.match_token(&TokenType::Comma) {
-                break;
+        let value = match self.current_token.as_ref().map(|token| token.token_type.clone()) {
+            Some(TokenType::Identifier(name)) => {
+                self.advance()?;
+                Literal::String { value: self.interner.intern(&name), raw: false, raw_delimiter: None }
             }
-        }
-        
-        self.expect(TokenType::RightParen, "memory annotation")?;
-        annotation.span = Span::new(start_pos, self.current_position());
+            Some(TokenType::Integer { .. } | TokenType::Float { .. } | TokenType::String { .. }
+                | TokenType::Char(_) | TokenType::True | TokenType::False) => match self.parse_primary_expression()? {
+                Expr::Literal { literal, .. } => literal,
+                other => return Err(ParseError::invalid_syntax(
+                    "Attribute arguments must be literals",
+                    other.span().start,
+                    ParseContext::TopLevel,
+                )),
+            },
+            Some(found) => return Err(ParseError::unexpected_token(
+                "literal",
+                "attribute argument value",
+                found,
+                self.current_position(),
+                ParseContext::TopLevel,
+            )),
+            None => return Err(ParseError::unexpected_eof(
+                "literal",
+                "attribute argument value",
+                self.current_position(),
+                ParseContext::TopLevel,
+            )),
+        };
         
-        Ok(annotation)
+        Ok(AttributeArg {
+            key,
+            value,
+            span: Span::new(start_pos, self.current_position()),
+        })
+    }
+    
+    /// Parse @memory annotation
+    pub fn parse_memory_annotation(&mut self) -> ParseResult<MemoryAnnotation> {
+        let attribute = self.parse_attribute()?;
+        if !self.interner.matches(attribute.name, "memory") {
+            return Err(ParseError::invalid_syntax(
+                "Expected 'memory' after '@'",
+                attribute.span.start,
+                ParseContext::MemoryAnnotation,
+            ));
+        }
+        memory_annotation_of(&attribute, &self.interner)
     }
     
     /// Parse @performance annotation
     pub fn parse_performance_annotation(&mut self) -> ParseResult<PerformanceAnnotation> {
-        let start_pos = self.current_position();
-        
-        self.expect(TokenType::At, "performance annotation")?;
-        if !self.match_identifier("performance") {
+        let attribute = self.parse_attribute()?;
+        if !self.interner.matches(attribute.name, "performance") {
             return Err(ParseError::invalid_syntax(
                 "Expected 'performance' after '@'",
-                self.current_position(),
+                attribute.span.start,
                 ParseContext::PerformanceAnnotation,
             ));
         }
-        
-        self.expect(TokenType::LeftParen, "performance annotation parameters")?;
-        
+        self.performance_annotation_from(&attribute)
+    }
+    
+    /// Read the parameters of a parsed `@performance` attribute
+    fn performance_annotation_from(&self, attribute: &Attribute) -> ParseResult<PerformanceAnnotation> {
         let mut annotation = PerformanceAnnotation {
             max_cost: None,
             max_memory: None,
             max_latency_ms: None,
-            span: Span::new(start_pos, self.current_position()),
+            deterministic: false,
+            span: attribute.span,
         };
         
-        // Parse parameter list
-        while !self.check(&TokenType::RightParen) {
-            let param_name = self.expect_identifier("parameter name")?;
-            
-            if !["max_cost", "max_memory", "max_latency_ms"].contains(&param_name.as_str()) {
-                return Err(ParseError::memory_annotation_error(
-                    &format!("Unknown performance parameter: {}", param_name),
-                    self.current_position(),
-                    &param_name,
-                    vec!["max_cost".to_string(), "max_memory".to_string(), "max_latency_ms".to_string()],
-                ));
-            }
-            
-            self.expect(TokenType::Equal, "parameter value")?;
-            
+        for arg in &attribute.args {
+            let param_name = attribute_arg_key(arg, &self.interner, ParseContext::PerformanceAnnotation)?;
             match param_name.as_str() {
                 "max_cost" => {
-                    annotation.max_cost = Some(self.parse_numeric_literal()?);
+                    annotation.max_cost = Some(integer_argument(arg, &param_name, ParseContext::PerformanceAnnotation)?);
                 }
                 "max_memory" => {
-                    annotation.max_memory = Some(self.parse_numeric_literal()?);
+                    annotation.max_memory = Some(integer_argument(arg, &param_name, ParseContext::PerformanceAnnotation)?);
                 }
                 "max_latency_ms" => {
-                    annotation.max_latency_ms = Some(self.parse_numeric_literal()? as u32);
+                    annotation.max_latency_ms =
+                        Some(integer_argument(arg, &param_name, ParseContext::PerformanceAnnotation)? as u32);
+                }
+                "deterministic" => {
+                    annotation.deterministic = match arg.value {
+                        Literal::Bool(value) => value,
+                        _ => return Err(ParseError::invalid_syntax(
+                            "Expected true or false for 'deterministic'",
+                            arg.span.start,
+                            ParseContext::PerformanceAnnotation,
+                        )),
+                    };
+                }
+                _ => {
+                    return Err(ParseError::memory_annotation_error(
+                        &format!("Unknown performance parameter: {}", param_name),
+                        arg.span.start,
+                        &param_name,
+                        &["max_cost", "max_memory", "max_latency_ms", "deterministic"],
+                    ));
                 }
-                _ => unreachable!(),
             }
+        }
+        
+        Ok(annotation)
+    }
+    
+    /// Parse the attributes in front of an item
+    ///
+    /// `@memory` and `@performance` are also read into their typed annotations, the
+    /// other known attributes are checked for their argument shape, and misspellings
+    /// of known names are rejected. Any other attribute is kept as written. Doc
+    /// comments before and between the attributes become `doc` attributes.
+    pub fn parse_item_annotations(&mut self, annotations: &mut ItemAnnotations) -> ParseResult<()> {
+        annotations.attributes.extend(self.take_doc_attribute(false));
+        while self.check(&TokenType::At) {
+            let attribute = self.parse_attribute()?;
+            let position = attribute.span.start;
+            let name = self.interner.resolve(attribute.name).unwrap_or_default();
             
-            if !self.match_token(&TokenType::Comma) {
-                break;
+            match name.as_str() {
+                "memory" if annotations.memory.is_none() => {
+                    annotations.memory = Some(memory_annotation_of(&attribute, &self.interner)?);
+                }
+                "performance" if annotations.performance.is_none() => {
+                    annotations.performance = Some(self.performance_annotation_from(&attribute)?);
+                }
+                "memory" | "performance" => {
+                    return Err(ParseError::InvalidSyntax {
+                        message: format!("Duplicate @{} annotation", name),
+                        position,
+                        context: ParseContext::TopLevel,
+                        suggestions: vec![
+                            Suggestion::new("Merge the parameters into a single annotation", position)
+                                .with_category(SuggestionCategory::Syntax),
+                        ],
+                        help: None,
+                    });
+                }
+                _ => self.check_attribute_shape(&name, &attribute)?,
             }
+            annotations.attributes.push(attribute);
+            annotations.attributes.extend(self.take_doc_attribute(false));
         }
+        Ok(())
+    }
+    
+    /// Check the arguments of a known attribute other than `@memory` and `@performance`,
+    /// and reject names that look like a misspelled known attribute
+    fn check_attribute_shape(&self, name: &str, attribute: &Attribute) -> ParseResult<()> {
+        let position = attribute.span.start;
+        let shape_error = |expected: &str| ParseError::invalid_syntax(
+            &format!("@{} takes {}", name, expected),
+            position,
+            ParseContext::TopLevel,
+        );
         
-        self.expect(TokenType::RightParen, "performance annotation")?;
-        annotation.span = Span::new(start_pos, self.current_position());
+        match name {
+            "guarantee" | "cfg" => Ok(()),
+            "manual" | "smart" | "linear" | "stack" | "nogc" if attribute.args.is_empty() => Ok(()),
+            "manual" | "smart" | "linear" | "stack" | "nogc" => Err(shape_error("no arguments")),
+            "doc" => match attribute.args.as_slice() {
+                [AttributeArg { key: None, value: Literal::String { .. }, .. }] => Ok(()),
+                _ => Err(shape_error("a single string, as in @doc(\"Adds two numbers\")")),
+            },
+            "region" => match attribute.args.as_slice() {
+                [AttributeArg { key: None, value: Literal::String { .. }, .. }]
+                | [AttributeArg { key: None, value: Literal::String { .. }, .. }, AttributeArg { key: None, value: Literal::String { .. }, .. }] => Ok(()),
+                _ => Err(shape_error("a region name and an optional overflow policy, as in @region(temp) or @region(temp, grow)")),
+            },
+            "align" => match attribute.args.as_slice() {
+                [AttributeArg { key: None, value: Literal::Integer { value, .. }, .. }] if value.parse::<u32>().is_ok() => Ok(()),
+                _ => Err(shape_error("a single integer alignment, as in @align(16)")),
+            },
+            _ => {
+                let similar: Vec<String> = suggest_similar_identifiers(name, KNOWN_ATTRIBUTES)
+                    .into_iter()
+                    .filter(|similar| is_likely_typo(name, similar))
+                    .collect();
+                if similar.is_empty() {
+                    return Ok(());
+                }
+                
+                // The name follows the '@'
+                let mut name_start = position;
+                name_start.advance(1);
+                let mut name_end = name_start;
+                name_end.advance(name.chars().count());
+                Err(ParseError::InvalidSyntax {
+                    message: format!("Unknown annotation '@{}'", name),
+                    position,
+                    context: ParseContext::TopLevel,
+                    suggestions: similar.into_iter()
+                        .map(|similar| {
+                            Suggestion::new(&format!("Did you mean '@{}'?", similar), name_start)
+                                .with_replacement(&similar)
+                                .with_end(name_end)
+                                .with_category(SuggestionCategory::Syntax)
+                        })
+                        .collect(),
+                    help: Some("Attributes with unrecognized names are kept as written, but this one looks misspelled".to_string()),
+                })
+            }
+        }
+    }
+    
+    /// Attach parsed annotations to an item, rejecting items that cannot carry them
+    pub fn attach_item_annotations(&self, item: Item, annotations: ItemAnnotations) -> ParseResult<Item> {
+        let ItemAnnotations { memory, performance, attributes } = annotations;
+        let item_kind = match item {
+            Item::Function { visibility, name, generics, params, return_type, body, is_extern, span, .. } => {
+                return Ok(Item::Function {
+                    visibility,
+                    name,
+                    generics,
+                    params,
+                    return_type,
+                    body,
+                    is_extern,
+                    performance_contract: performance.as_ref().map(PerformanceAnnotation::to_contract),
+                    allocation_strategy: memory.and_then(|memory| memory.strategy),
+                    attributes,
+                    span,
+                    id: NodeId::DUMMY,
+                });
+            }
+            // Structs take `@memory` as the strategy of their instances
+            mut item if performance.is_none() && (memory.is_none() || matches!(item, Item::Struct { .. })) => {
+                // Attributes written in front of the item come before those written inside it
+                item.attributes_mut().splice(0..0, attributes);
+                return Ok(item);
+            }
+            Item::Struct { .. } => "struct",
+            Item::Enum { .. } => "enum",
+            Item::TypeAlias { .. } => "type alias",
+            Item::Const { .. } => "constant",
+            Item::Module { .. } => "module",
+            Item::Impl { .. } => "impl block",
+            Item::Use { .. } => "use declaration",
+        };
         
-        Ok(annotation)
+        let (annotation, span, help) = match (&performance, &memory) {
+            (Some(performance), _) => ("@performance", performance.span, "@performance annotations describe functions only"),
+            (None, Some(memory)) => ("@memory", memory.span, "@memory annotations describe functions and structs only"),
+            (None, None) => unreachable!(),
+        };
+        Err(ParseError::InvalidSyntax {
+            message: format!("{} cannot be applied to a {}", annotation, item_kind),
+            position: span.start,
+            context: ParseContext::TopLevel,
+            suggestions: vec![
+                Suggestion::new(&format!("Move {} onto a function declaration", annotation), span.start)
+                    .with_category(SuggestionCategory::Syntax),
+                Suggestion::new(&format!("Remove {} from this {}", annotation, item_kind), span.start)
+                    .with_category(SuggestionCategory::Syntax),
+            ],
+            help: Some(help.to_string()),
+        })
     }
     
     /// Parse memory strategy value from string literal
     pub fn parse_memory_strategy_value(&mut self) -> ParseResult<MemoryStrategy> {
         if let Some(token) = &self.current_token {
             if let TokenType::String { value, .. } = &token.token_type {
-                let strategy = match value.as_str() {
-                    "stack" => MemoryStrategy::Stack,
-                    "linear" => MemoryStrategy::Linear,
-                    "smartptr" => MemoryStrategy::SmartPtr,
-                    "region" => MemoryStrategy::Region,
-                    "manual" => MemoryStrategy::Manual,
-                    "inferred" => MemoryStrategy::Inferred,
-                    _ => {
-                        return Err(ParseError::memory_annotation_error(
-                            &format!("Invalid memory strategy: {}", value),
-                            token.position,
-                            value,
-                            vec!["stack".to_string(), "linear".to_string(), "smartptr".to_string(), 
-                                "region".to_string(), "manual".to_string(), "inferred".to_string()],
-                        ));
-                    }
-                };
+                let strategy = memory_strategy_named(value, token.position)?;
                 self.advance()?;
                 Ok(strategy)
             } else {
@@ -223,8 +427,8 @@ impl<'a> Parser<'a> {
     pub fn parse_strategy_wrapper_type(&mut self) -> ParseResult<Type> {
         let start_pos = self.current_position();
         
-        let wrapper_name = self.expect_identifier("strategy wrapper name")?;
-        let strategy = match wrapper_name.as_str() {
+        let wrapper_name = self.expect_annotation_identifier("strategy wrapper name")?;
+        let strategy = match &*wrapper_name {
             "LinearPtr" => MemoryStrategy::Linear,
             "SmartPtr" => MemoryStrategy::SmartPtr,
             "RegionPtr" => MemoryStrategy::Region,
@@ -247,6 +451,7 @@ impl<'a> Parser<'a> {
             target_type: Box::new(inner_type),
             memory_strategy: strategy,
             span: Span::new(start_pos, self.current_position()),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -305,7 +510,7 @@ impl<'a> Parser<'a> {
         let start_pos = self.current_position();
         
         self.expect(TokenType::Let, "variable declaration")?;
-        let name = self.expect_identifier("variable name")?;
+        let name = self.expect_annotation_identifier("variable name")?;
         self.expect(TokenType::Colon, "type annotation")?;
         
         let var_type = self.parse_type()?;
@@ -315,7 +520,7 @@ impl<'a> Parser<'a> {
             self.advance()?;
             if self.match_identifier("memory") {
                 self.expect(TokenType::LeftParen, "memory strategy")?;
-                self.expect(TokenType::Identifier("strategy".to_string()), "strategy parameter")?;
+                self.expect_identifier("strategy parameter")?;
                 self.expect(TokenType::Equal, "strategy value")?;
                 let strategy = self.parse_memory_strategy_value()?;
                 self.expect(TokenType::RightParen, "memory strategy")?;
@@ -346,8 +551,8 @@ impl<'a> Parser<'a> {
     
     // Helper methods
     
-    /// Expect an identifier token and return its value
-    fn expect_identifier(&mut self, description: &str) -> ParseResult<String> {
+    /// Expect an identifier token in an annotation and return its name
+    fn expect_annotation_identifier(&mut self, description: &str) -> ParseResult<Cow<'a, str>> {
         if let Some(token) = &self.current_token {
             if let TokenType::Identifier(name) = &token.token_type {
                 let name = name.clone();
@@ -385,56 +590,6 @@ impl<'a> Parser<'a> {
         false
     }
     
-    /// Parse a numeric literal
-    fn parse_numeric_literal(&mut self) -> ParseResult<u64> {
-        if let Some(token) = &self.current_token {
-            match &token.token_type {
-                TokenType::Integer { value, .. } => {
-                    let num = value.parse::<u64>()
-                        .map_err(|_| ParseError::invalid_syntax(
-                            &format!("Invalid numeric literal: {}", value),
-                            token.position,
-                            ParseContext::MemoryAnnotation,
-                        ))?;
-                    self.advance()?;
-                    Ok(num)
-                }
-                _ => Err(ParseError::unexpected_token(
-                    "numeric literal",
-                    "integer value",
-                    token.token_type.clone(),
-                    token.position,
-                    ParseContext::MemoryAnnotation,
-                ))
-            }
-        } else {
-            Err(ParseError::unexpected_eof(
-                "numeric literal",
-                "integer value",
-                self.current_position(),
-                ParseContext::MemoryAnnotation,
-            ))
-        }
-    }
-    
-    /// Parse size value
-    fn parse_size_value(&mut self) -> ParseResult<u64> {
-        self.parse_numeric_literal()
-    }
-    
-    /// Parse alignment value
-    fn parse_alignment_value(&mut self) -> ParseResult<u8> {
-        let value = self.parse_numeric_literal()?;
-        if value > 255 {
-            return Err(ParseError::invalid_syntax(
-                "Alignment must be <= 255",
-                self.current_position(),
-                ParseContext::MemoryAnnotation,
-            ));
-        }
-        Ok(value as u8)
-    }
-    
     /// Parse region identifier
     fn parse_region_identifier(&mut self) -> ParseResult<InternedString> {
         if let Some(token) = &self.current_token {
@@ -499,6 +654,113 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Memory strategy named by a strategy string such as `"stack"`
+fn memory_strategy_named(value: &str, position: Position) -> ParseResult<MemoryStrategy> {
+    match value {
+        "stack" => Ok(MemoryStrategy::Stack),
+        "linear" => Ok(MemoryStrategy::Linear),
+        "smartptr" => Ok(MemoryStrategy::SmartPtr),
+        "region" => Ok(MemoryStrategy::Region),
+        "manual" => Ok(MemoryStrategy::Manual),
+        "inferred" => Ok(MemoryStrategy::Inferred),
+        _ => Err(ParseError::memory_annotation_error(
+            &format!("Invalid memory strategy: {}", value),
+            position,
+            value,
+            &["stack", "linear", "smartptr", "region", "manual", "inferred"],
+        )),
+    }
+}
+
+/// Read the parameters of a parsed `@memory` attribute
+///
+/// Only the shape of the arguments is checked here; code generation validates their values.
+pub fn memory_annotation_of(attribute: &Attribute, interner: &StringInterner) -> ParseResult<MemoryAnnotation> {
+    let mut annotation = MemoryAnnotation {
+        strategy: None,
+        size_hint: None,
+        alignment: None,
+        region: None,
+        span: attribute.span,
+    };
+    
+    for arg in &attribute.args {
+        let param_name = attribute_arg_key(arg, interner, ParseContext::MemoryAnnotation)?;
+        match param_name.as_str() {
+            "strategy" => {
+                let value = string_argument(arg, interner, &param_name, ParseContext::MemoryAnnotation)?;
+                annotation.strategy = Some(memory_strategy_named(&value, arg.span.start)?);
+            }
+            "size_hint" => {
+                annotation.size_hint = Some(integer_argument(arg, &param_name, ParseContext::MemoryAnnotation)?);
+            }
+            "alignment" => {
+                let value = integer_argument(arg, &param_name, ParseContext::MemoryAnnotation)?;
+                annotation.alignment = Some(u32::try_from(value).map_err(|_| ParseError::invalid_syntax(
+                    "Alignment does not fit in 32 bits",
+                    arg.span.start,
+                    ParseContext::MemoryAnnotation,
+                ))?);
+            }
+            "region" => {
+                annotation.region = Some(match &arg.value {
+                    Literal::String { value, .. } => *value,
+                    _ => return Err(ParseError::invalid_syntax(
+                        "Expected a region name for 'region'",
+                        arg.span.start,
+                        ParseContext::MemoryAnnotation,
+                    )),
+                });
+            }
+            _ => {
+                return Err(ParseError::memory_annotation_error(
+                    &format!("Unknown parameter: {}", param_name),
+                    arg.span.start,
+                    &param_name,
+                    &["strategy", "size_hint", "alignment", "region"],
+                ));
+            }
+        }
+    }
+    
+    Ok(annotation)
+}
+
+/// Name of a keyed attribute argument, rejecting positional ones
+fn attribute_arg_key(arg: &AttributeArg, interner: &StringInterner, context: ParseContext) -> ParseResult<String> {
+    arg.key
+        .and_then(|key| interner.resolve(key))
+        .ok_or_else(|| ParseError::invalid_syntax("Expected 'name = value'", arg.span.start, context))
+}
+
+/// Value of an attribute argument that must be a string literal
+fn string_argument(arg: &AttributeArg, interner: &StringInterner, param_name: &str, context: ParseContext) -> ParseResult<String> {
+    match &arg.value {
+        Literal::String { value, .. } => Ok(interner.resolve(*value).unwrap_or_default()),
+        _ => Err(ParseError::invalid_syntax(
+            &format!("Expected a string literal for '{}'", param_name),
+            arg.span.start,
+            context,
+        )),
+    }
+}
+
+/// Value of an attribute argument that must be an integer literal
+fn integer_argument(arg: &AttributeArg, param_name: &str, context: ParseContext) -> ParseResult<u64> {
+    match &arg.value {
+        Literal::Integer { value, .. } => value.parse::<u64>().map_err(|_| ParseError::invalid_syntax(
+            &format!("Invalid numeric literal: {}", value),
+            arg.span.start,
+            context,
+        )),
+        _ => Err(ParseError::invalid_syntax(
+            &format!("Expected an integer for '{}'", param_name),
+            arg.span.start,
+            context,
+        )),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
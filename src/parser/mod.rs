@@ -7,8 +7,11 @@
 //! - Performance contract annotations
 //! - Error recovery and reporting
 
+pub mod cfg;
 pub mod error;
 pub mod expressions;
+pub mod module_loader;
+pub mod node_ids;
 pub mod parser;
 pub mod patterns;
 pub mod statements;
@@ -19,10 +22,13 @@ pub mod types;
 pub mod memory_syntax;
 
 // Re-exports for convenience
+pub use cfg::{strip_cfg_items, CfgTarget};
 pub use error::{ParseError, ParseResult};
-pub use parser::{Parser, StringInterner};
+pub use parser::{Parser, StringInterner, DEFAULT_MAX_CHAIN_LENGTH, DEFAULT_MAX_NESTING_DEPTH};
+pub use module_loader::{ModuleLoader, ModuleLoadError};
+pub use node_ids::assign_node_ids;
 pub use memory_syntax::{
-    MemoryAnnotation, PerformanceAnnotation, RegionBlock, VariableDeclaration
+    memory_annotation_of, ItemAnnotations, MemoryAnnotation, PerformanceAnnotation, RegionBlock, VariableDeclaration
 };
 
 use crate::ast::{Module, Expr, Stmt, Type};
@@ -86,12 +92,12 @@ mod integration_tests {
     
     #[test]
     fn test_performance_annotation_integration() {
-        let source = r#"@performance(max_cost = 500, max_memory = 2048)"#;  // Removed unsupported 'deterministic'
+        let source = r#"@performance(max_cost = 500, max_memory = 2048, deterministic = true)"#;
         let annotation = parse_performance_annotation(source, 0).unwrap();
         
         assert_eq!(annotation.max_cost, Some(500));
         assert_eq!(annotation.max_memory, Some(2048));
-        // Performance annotation test - simplified
+        assert!(annotation.deterministic);
     }
     
     #[test]
@@ -112,16 +118,60 @@ mod integration_tests {
         let source = r#"
         @memory(strategy = "region", size_hint = 4096)
         @performance(max_cost = 1000, max_memory = 2048)
-        fn process_data(items: &[Item]) -> Vec<Result> {
+        fn process_data(items: Vec<Item>) -> Vec<Result> {
             // Function body
         }
         "#;
         
         let module = parse_module(source, 0).unwrap();
-        // Debug: check what we actually parsed
-        println!("Parsed {} items", module.items.len());
-        // Temporarily accept any number of items until we fix parsing
-        assert!(module.items.len() >= 0);
+        assert_eq!(module.items.len(), 1);
+        
+        match &module.items[0] {
+            crate::ast::Item::Function { performance_contract, allocation_strategy, .. } => {
+                let contract = performance_contract.as_ref().expect("contract should be attached");
+                assert_eq!(contract.cpu_bound, Some(crate::ast::CpuBound::Cycles(1000)));
+                assert_eq!(contract.memory_bound, Some(crate::ast::MemoryBound::Bytes(2048)));
+                assert_eq!(contract.latency_bound, None);
+                assert!(!contract.deterministic);
+                assert_eq!(*allocation_strategy, Some(crate::ast::MemoryStrategy::Region));
+            }
+            other => panic!("Expected function, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_annotation_on_struct_rejected() {
+        let source = r#"
+        @performance(max_cost = 1000)
+        struct Point { x: i32, y: i32 }
+        "#;
+        
+        let mut parser = Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        assert!(module.items.is_empty());
+        
+        match parser.errors() {
+            [ParseError::InvalidSyntax { message, suggestions, .. }] => {
+                assert!(message.contains("@performance cannot be applied to a struct"), "{}", message);
+                assert!(!suggestions.is_empty());
+            }
+            other => panic!("Expected one InvalidSyntax error, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_memory_annotation_on_struct_kept() {
+        let source = "@memory(strategy = \"region\")\nstruct Frame { id: i32 }";
+        let mut parser = Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        match &module.items[..] {
+            [crate::ast::Item::Struct { attributes, .. }] => {
+                assert_eq!(attributes.len(), 1);
+                assert!(parser.interner.matches(attributes[0].name, "memory"));
+            }
+            other => panic!("Expected one struct, got {:?}", other),
+        }
     }
     
     #[test]
@@ -151,4 +201,26 @@ mod integration_tests {
             _ => panic!("Expected let statement"),
         }
     }
-} 
\ No newline at end of file
+    
+    #[test]
+    fn test_interner_shared_between_holders() {
+        let interner = StringInterner::new();
+        let mut parser = Parser::with_interner("fn area(width: i32) -> i32 { return width; }", 0, interner.clone()).unwrap();
+        let module = parser.parse_module().unwrap();
+        
+        // Names interned by the parser resolve through every handle, even after the parser is gone
+        let name = match &module.items[0] {
+            crate::ast::Item::Function { name, .. } => *name,
+            item => panic!("Expected function, got {:?}", item),
+        };
+        let analyzer_handle = parser.interner();
+        drop(parser);
+        assert_eq!(interner.resolve(name).as_deref(), Some("area"));
+        assert!(analyzer_handle.matches(name, "area"));
+        
+        // Interning through a clone yields the same handle as the original
+        let width = analyzer_handle.intern("width");
+        assert_eq!(interner.intern("width"), width);
+        assert_eq!(interner.resolve(width).as_deref(), Some("width"));
+    }
+}
@@ -0,0 +1,395 @@
+//! Loading external modules from the filesystem
+//!
+//! `mod foo;` declares a module whose items live in a file of their own. Starting
+//! from the root file, the loader finds each of these files, parses it with the
+//! root's interner and a file id of its own, and splices its items into the
+//! declaration, recursively.
+//!
+//! Files are laid out as in Rust:
+//! - the root file and `mod.bract` files own the directory they are in, so
+//!   `mod foo;` in them loads `foo.bract` or `foo/mod.bract` next to them
+//! - any other file `bar.bract` owns `bar/`, so `mod foo;` in it loads
+//!   `bar/foo.bract` or `bar/foo/mod.bract`
+//! - an inline `mod baz { mod foo; }` looks in the `baz/` directory of its file
+
+use crate::ast::{Item, Module, SourceMap, Span};
+use crate::diagnostics::DiagnosticCode;
+use super::error::ParseError;
+use super::node_ids::assign_node_ids;
+use super::parser::{Parser, StringInterner};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extension of Bract source files
+pub const SOURCE_EXTENSION: &str = "bract";
+
+/// File a directory module keeps its items in
+const MOD_FILE: &str = "mod.bract";
+
+/// Errors raised while loading external modules
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModuleLoadError {
+    /// None of the files `mod name;` may live in exists
+    FileNotFound {
+        name: String,
+        candidates: Vec<PathBuf>,
+        span: Span,
+    },
+    /// Both `name.bract` and `name/mod.bract` exist
+    AmbiguousModule {
+        name: String,
+        candidates: Vec<PathBuf>,
+        span: Span,
+    },
+    /// The module's file is already being loaded by a module enclosing it
+    CircularModule {
+        name: String,
+        path: PathBuf,
+        span: Span,
+    },
+    /// The file exists but could not be read; no span for the root file
+    Io {
+        path: PathBuf,
+        message: String,
+        span: Option<Span>,
+    },
+    /// The module's file has a syntax error
+    Parse {
+        path: PathBuf,
+        error: ParseError,
+    },
+}
+
+impl fmt::Display for ModuleLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleLoadError::FileNotFound { name, candidates, .. } => {
+                let candidates: Vec<String> = candidates.iter().map(|path| path.display().to_string()).collect();
+                write!(f, "file not found for module `{}` (looked for {})", name, candidates.join(" and "))
+            }
+            ModuleLoadError::AmbiguousModule { name, candidates, .. } => {
+                let candidates: Vec<String> = candidates.iter().map(|path| path.display().to_string()).collect();
+                write!(f, "file for module `{}` found at both {}", name, candidates.join(" and "))
+            }
+            ModuleLoadError::CircularModule { name, path, .. } => {
+                write!(f, "circular module declaration: `{}` loads {}, which is already being loaded", name, path.display())
+            }
+            ModuleLoadError::Io { path, message, .. } => {
+                write!(f, "cannot read {}: {}", path.display(), message)
+            }
+            ModuleLoadError::Parse { path, error } => {
+                write!(f, "{}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModuleLoadError {}
+
+impl ModuleLoadError {
+    /// The `mod` declaration behind the error, if it has one
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ModuleLoadError::FileNotFound { span, .. }
+            | ModuleLoadError::AmbiguousModule { span, .. }
+            | ModuleLoadError::CircularModule { span, .. } => Some(*span),
+            ModuleLoadError::Io { span, .. } => *span,
+            ModuleLoadError::Parse { .. } => None,
+        }
+    }
+
+    /// Stable identifier of the kind of error; syntax errors keep their own code
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            ModuleLoadError::FileNotFound { .. } => DiagnosticCode::ModuleNotFound,
+            ModuleLoadError::AmbiguousModule { .. } => DiagnosticCode::AmbiguousModule,
+            ModuleLoadError::CircularModule { .. } => DiagnosticCode::CircularModule,
+            ModuleLoadError::Io { .. } => DiagnosticCode::UnreadableModule,
+            ModuleLoadError::Parse { error, .. } => error.code(),
+        }
+    }
+}
+
+/// Loads the files of external modules and splices their items into the AST
+///
+/// The root file is file 0; every file loaded for it gets the next free file id,
+/// so spans can be traced back to their file through `path`.
+pub struct ModuleLoader {
+    interner: StringInterner,
+    /// Path of each file, by file id
+    files: HashMap<usize, PathBuf>,
+    next_file_id: usize,
+    /// Canonical paths of the files being loaded, outermost first
+    loading: Vec<PathBuf>,
+    errors: Vec<ModuleLoadError>,
+}
+
+impl ModuleLoader {
+    /// Create a loader interning names through `interner`, which should be the one
+    /// the root file was parsed with
+    pub fn new(interner: StringInterner) -> Self {
+        Self {
+            interner,
+            files: HashMap::new(),
+            next_file_id: 1,
+            loading: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Read and parse the root file at `root`, then load its external modules
+    ///
+    /// Returns `None` if the root file cannot be read or parsed at all.
+    pub fn load(&mut self, root: &Path) -> Option<Module> {
+        let source = match fs::read_to_string(root) {
+            Ok(source) => source,
+            Err(err) => {
+                self.errors.push(ModuleLoadError::Io {
+                    path: root.to_path_buf(),
+                    message: err.to_string(),
+                    span: None,
+                });
+                return None;
+            }
+        };
+        let mut module = self.parse_file(root, &source, 0)?;
+        self.resolve(root, &mut module);
+        Some(module)
+    }
+
+    /// Load the external modules `module`, parsed from the root file at `root`, declares
+    ///
+    /// Each file numbered its own nodes from 0, so the spliced module is renumbered.
+    pub fn resolve(&mut self, root: &Path, module: &mut Module) {
+        self.files.insert(0, root.to_path_buf());
+        self.loading.push(canonical(root));
+        let dir = root.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.resolve_items(&mut module.items, &dir);
+        self.loading.pop();
+        assign_node_ids(module);
+    }
+
+    /// Path of the file with id `file_id`
+    pub fn path(&self, file_id: usize) -> Option<&Path> {
+        self.files.get(&file_id).map(PathBuf::as_path)
+    }
+
+    /// Names of every file loaded so far, with the root file as the default
+    pub fn source_map(&self) -> SourceMap {
+        let name = |path: &Path| path.display().to_string();
+        let mut source_map = SourceMap::new(&self.path(0).map(name).unwrap_or_default());
+        for (file_id, path) in &self.files {
+            source_map.add_file(*file_id, &name(path));
+        }
+        source_map
+    }
+
+    /// Errors raised so far
+    pub fn errors(&self) -> &[ModuleLoadError] {
+        &self.errors
+    }
+
+    /// Consume the loader, returning the errors it raised
+    pub fn into_errors(self) -> Vec<ModuleLoadError> {
+        self.errors
+    }
+
+    /// Load the external modules among `items`, which belong to the directory `dir`
+    fn resolve_items(&mut self, items: &mut [Item], dir: &Path) {
+        for item in items {
+            let Item::Module { name, items, attributes, span, .. } = item else { continue };
+            let name = self.interner.resolve(*name).unwrap_or_default();
+            match items {
+                Some(items) => self.resolve_items(items, &dir.join(&name)),
+                None => {
+                    if let Some(module) = self.load_module(&name, dir, *span) {
+                        // The file's `//!` comments document the module, as in an inline body
+                        attributes.extend(module.attributes);
+                        *items = Some(module.items);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Find, parse and resolve the file of `mod name;` declared at `span` in `dir`
+    fn load_module(&mut self, name: &str, dir: &Path, span: Span) -> Option<Module> {
+        let candidates = vec![
+            dir.join(format!("{}.{}", name, SOURCE_EXTENSION)),
+            dir.join(name).join(MOD_FILE),
+        ];
+        let found: Vec<&PathBuf> = candidates.iter().filter(|path| path.is_file()).collect();
+        let path = match found[..] {
+            [path] => path.clone(),
+            [] => {
+                self.errors.push(ModuleLoadError::FileNotFound { name: name.to_string(), candidates, span });
+                return None;
+            }
+            _ => {
+                self.errors.push(ModuleLoadError::AmbiguousModule { name: name.to_string(), candidates, span });
+                return None;
+            }
+        };
+
+        let canonical_path = canonical(&path);
+        if self.loading.contains(&canonical_path) {
+            self.errors.push(ModuleLoadError::CircularModule { name: name.to_string(), path, span });
+            return None;
+        }
+
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) => {
+                self.errors.push(ModuleLoadError::Io { path, message: err.to_string(), span: Some(span) });
+                return None;
+            }
+        };
+        let file_id = self.next_file_id;
+        self.next_file_id += 1;
+        let mut module = self.parse_file(&path, &source, file_id)?;
+
+        // `foo.bract` keeps its submodules in `foo/`, `foo/mod.bract` in `foo/` itself
+        let child_dir = if path.file_name().is_some_and(|file_name| file_name == MOD_FILE) {
+            path.parent().map(Path::to_path_buf).unwrap_or_default()
+        } else {
+            path.with_extension("")
+        };
+        self.loading.push(canonical_path);
+        self.resolve_items(&mut module.items, &child_dir);
+        self.loading.pop();
+        Some(module)
+    }
+
+    /// Parse `source`, read from `path`, as file `file_id`, recording its syntax errors
+    fn parse_file(&mut self, path: &Path, source: &str, file_id: usize) -> Option<Module> {
+        self.files.insert(file_id, path.to_path_buf());
+        let parse_error = |error| ModuleLoadError::Parse { path: path.to_path_buf(), error };
+
+        let mut parser = match Parser::with_interner(source, file_id, self.interner.clone()) {
+            Ok(parser) => parser,
+            Err(error) => {
+                self.errors.push(parse_error(error));
+                return None;
+            }
+        };
+        let module = parser.parse_module();
+        self.errors.extend(parser.errors().iter().cloned().map(parse_error));
+        match module {
+            Ok(module) => Some(module),
+            Err(error) => {
+                self.errors.push(parse_error(error));
+                None
+            }
+        }
+    }
+}
+
+/// `path` with symlinks and `..` resolved, so one file always has one path
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `files`, by path relative to a fresh directory, and return the directory
+    fn project(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, source) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        dir
+    }
+
+    /// Items of the module `name` among `items`
+    fn module_items<'a>(items: &'a [Item], name: &str, interner: &StringInterner) -> &'a [Item] {
+        items.iter()
+            .find_map(|item| match item {
+                Item::Module { name: module, items: Some(items), .. } if interner.matches(*module, name) => Some(&items[..]),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("module `{}` is not loaded", name))
+    }
+
+    #[test]
+    fn test_loads_both_layouts_recursively() {
+        let dir = project(&[
+            ("main.bract", "mod utils;\nmod net;\nfn main() {}"),
+            ("utils.bract", "//! Utilities\npub mod helpers;"),
+            ("utils/helpers.bract", "pub fn f() {}"),
+            ("net/mod.bract", "mod tcp;\nmod inline { mod deep; }"),
+            ("net/tcp.bract", "pub fn connect() {}"),
+            ("net/inline/deep.bract", "fn bottom() {}"),
+        ]);
+        let interner = StringInterner::new();
+        let mut loader = ModuleLoader::new(interner.clone());
+        let module = loader.load(&dir.path().join("main.bract")).unwrap();
+        assert_eq!(loader.errors(), []);
+
+        let utils = module.items.iter().find(|item| matches!(item, Item::Module { .. })).unwrap();
+        assert_eq!(utils.doc(&interner).as_deref(), Some("Utilities"));
+        let helpers = module_items(module_items(&module.items, "utils", &interner), "helpers", &interner);
+        let net = module_items(&module.items, "net", &interner);
+        let tcp = module_items(net, "tcp", &interner);
+        let deep = module_items(module_items(net, "inline", &interner), "deep", &interner);
+
+        // Every file has its own id, and names are shared through one interner
+        let file_of = |items: &[Item]| match &items[0] {
+            Item::Function { span, .. } => span.start.file_id,
+            other => panic!("Expected function, got {:?}", other),
+        };
+        let mut file_ids = vec![file_of(helpers), file_of(tcp), file_of(deep)];
+        file_ids.sort();
+        file_ids.dedup();
+        assert_eq!(file_ids.len(), 3);
+        assert!(!file_ids.contains(&0));
+        // Node ids stay unique once the files are spliced together
+        let mut node_ids = vec![helpers[0].id(), tcp[0].id(), deep[0].id(), module.items.last().unwrap().id()];
+        node_ids.sort();
+        node_ids.dedup();
+        assert_eq!(node_ids.len(), 4);
+        assert_eq!(loader.path(file_of(tcp)), Some(dir.path().join("net/tcp.bract").as_path()));
+        let source_map = loader.source_map();
+        assert_eq!(source_map.file_name(file_of(deep)), dir.path().join("net/inline/deep.bract").display().to_string());
+        assert_eq!(source_map.default_file(), dir.path().join("main.bract").display().to_string());
+        match &helpers[0] {
+            Item::Function { name, .. } => assert!(interner.matches(*name, "f")),
+            other => panic!("Expected function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_missing_ambiguous_and_circular_modules() {
+        let dir = project(&[
+            ("main.bract", "mod missing;\nmod both;\n  mod main;"),
+            ("both.bract", ""),
+            ("both/mod.bract", ""),
+        ]);
+        let mut loader = ModuleLoader::new(StringInterner::new());
+        loader.load(&dir.path().join("main.bract")).unwrap();
+
+        let errors = loader.errors();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        match &errors[0] {
+            ModuleLoadError::FileNotFound { name, candidates, span } => {
+                assert_eq!(name, "missing");
+                assert_eq!(candidates, &[dir.path().join("missing.bract"), dir.path().join("missing/mod.bract")]);
+                assert_eq!((span.start.line, span.start.column), (1, 1));
+            }
+            other => panic!("Expected missing file, got {:?}", other),
+        }
+        assert!(matches!(&errors[1], ModuleLoadError::AmbiguousModule { name, .. } if name == "both"));
+        match &errors[2] {
+            ModuleLoadError::CircularModule { name, span, .. } => {
+                assert_eq!(name, "main");
+                assert_eq!((span.start.line, span.start.column), (3, 3));
+            }
+            other => panic!("Expected circular module, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Numbering the nodes of a parsed module
+//!
+//! Nodes are built with `NodeId::DUMMY` while parsing, since recovery and
+//! lookahead may build nodes that never make it into the tree. Once a module is
+//! complete it is walked in source order, parents before their children, and every
+//! expression, statement, item, pattern and type gets the next id from a counter
+//! starting at 0. The ids of a module are therefore dense, unique and identical on
+//! every parse of the same source, so analyses can key side tables by them.
+
+use crate::ast::{Expr, Item, Module, NodeId, Pattern, Stmt, Type};
+use crate::visitor::{self, VisitorMut};
+
+/// Number every node of `module` from 0, returning how many nodes it has
+///
+/// Ids assigned before are replaced, so a module whose items were spliced together
+/// from several files can be renumbered as a whole.
+pub fn assign_node_ids(module: &mut Module) -> u32 {
+    let mut numbering = NodeNumbering { next: 0 };
+    numbering.visit_module_mut(module);
+    numbering.next
+}
+
+/// Hands out ids in visiting order
+struct NodeNumbering {
+    next: u32,
+}
+
+impl NodeNumbering {
+    fn assign(&mut self, id: &mut NodeId) {
+        *id = NodeId(self.next);
+        self.next += 1;
+    }
+}
+
+impl VisitorMut for NodeNumbering {
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        self.assign(item.id_mut());
+        visitor::walk_item_mut(self, item)
+    }
+
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        self.assign(stmt.id_mut());
+        visitor::walk_stmt_mut(self, stmt)
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        self.assign(expr.id_mut());
+        visitor::walk_expr_mut(self, expr)
+    }
+
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        self.assign(pattern.id_mut());
+        visitor::walk_pattern_mut(self, pattern)
+    }
+
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        self.assign(ty.id_mut());
+        visitor::walk_type_mut(self, ty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::visitor::Visitor;
+    use std::collections::HashSet;
+
+    /// Ids of every node, in visiting order
+    #[derive(Default)]
+    struct CollectIds(Vec<NodeId>);
+
+    impl<'ast> Visitor<'ast> for CollectIds {
+        fn visit_item(&mut self, item: &'ast Item) {
+            self.0.push(item.id());
+            visitor::walk_item(self, item)
+        }
+
+        fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+            self.0.push(stmt.id());
+            visitor::walk_stmt(self, stmt)
+        }
+
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            self.0.push(expr.id());
+            visitor::walk_expr(self, expr)
+        }
+
+        fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+            self.0.push(pattern.id());
+            visitor::walk_pattern(self, pattern)
+        }
+
+        fn visit_type(&mut self, ty: &'ast Type) {
+            self.0.push(ty.id());
+            visitor::walk_type(self, ty)
+        }
+    }
+
+    fn node_ids(source: &str) -> Vec<NodeId> {
+        let mut parser = Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        let mut ids = CollectIds::default();
+        ids.visit_module(&module);
+        ids.0
+    }
+
+    #[test]
+    fn test_node_ids_dense_unique_and_stable() {
+        let source = r#"
+            struct Point { x: i32, y: i32 }
+
+            fn norm(p: &Point) -> i32 {
+                let (a, b): (i32, i32) = (p.x, p.y);
+                match a {
+                    0 | 1 => b,
+                    _ => a * a + b * b,
+                }
+            }
+
+            impl Point {
+                fn origin() -> Point { Point { x: 0, y: 0 } }
+            }
+        "#;
+        let ids = node_ids(source);
+        assert!(ids.len() > 30, "{}", ids.len());
+
+        // Every node has its own id, and together they are exactly 0..n
+        let unique: HashSet<NodeId> = ids.iter().copied().collect();
+        assert_eq!(unique.len(), ids.len());
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..ids.len() as u32).map(NodeId).collect::<Vec<_>>());
+
+        // Parents come before their children, and a second parse numbers the same way
+        assert_eq!(ids[0], NodeId(0));
+        assert_eq!(ids, node_ids(source));
+    }
+
+    #[test]
+    fn test_renumbering_replaces_ids() {
+        let mut parser = Parser::new("fn one() -> i32 { 1 }\nfn two() -> i32 { 2 }", 0).unwrap();
+        let mut module = parser.parse_module().unwrap();
+        let second = module.items.remove(1);
+        module.items.insert(0, second);
+
+        let count = assign_node_ids(&mut module);
+        let mut ids = CollectIds::default();
+        ids.visit_module(&module);
+        assert_eq!(ids.0, (0..count).map(NodeId).collect::<Vec<_>>());
+    }
+}
@@ -1,64 +1,197 @@
 //! Main parser implementation for the Bract programming language
 
-use crate::lexer::{Lexer, Token, TokenType, Position};
-use crate::ast::{Module, Item, Expr, Stmt, Span, Visibility, Parameter, InternedString, Pattern, Type, MemoryStrategy};
+use std::borrow::Cow;
+use crate::lexer::{Lexer, LexerError, Token, TokenType, Position};
+use crate::ast::{
+    Module, Item, Expr, Stmt, Span, Visibility, Parameter, InternedString, Pattern, Type, MemoryStrategy, GenericParam,
+    Attribute, AttributeArg, Literal, NodeId,
+};
 use super::error::{
     ParseError, ParseResult, ParseContext, ExpectedToken, Suggestion, SuggestionCategory,
     suggest_similar_identifiers, suggest_for_context, UnclosedDelimiter
 };
+use super::memory_syntax::ItemAnnotations;
+use super::node_ids::assign_node_ids;
 use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 /// String interner for efficient string storage
+///
+/// A cheap, cloneable handle: clones share one table, so the interner filled by
+/// the parser can be handed to semantic analysis, codegen and the LSP, and names
+/// interned through any handle resolve through all of them.
+#[derive(Debug, Clone, Default)]
 pub struct StringInterner {
-    strings: Vec<String>,
-    map: HashMap<String, u32>,
+    storage: Arc<RwLock<InternerStorage>>,
+}
+
+#[derive(Debug, Default)]
+struct InternerStorage {
+    strings: Vec<Arc<str>>,
+    map: HashMap<Arc<str>, u32>,
 }
 
 impl StringInterner {
     pub fn new() -> Self {
-        Self {
-            strings: Vec::new(),
-            map: HashMap::new(),
-        }
+        Self::default()
     }
     
-    pub fn intern(&mut self, s: &str) -> InternedString {
-        if let Some(&id) = self.map.get(s) {
-            InternedString::new(id)
-        } else {
-            let id = self.strings.len() as u32;
-            self.strings.push(s.to_string());
-            self.map.insert(s.to_string(), id);
-            InternedString::new(id)
+    pub fn intern(&self, s: &str) -> InternedString {
+        if let Some(&id) = self.read().map.get(s) {
+            return InternedString::new(id);
+        }
+        
+        let mut storage = self.write();
+        // Another handle may have interned `s` since the read lock was released
+        if let Some(&id) = storage.map.get(s) {
+            return InternedString::new(id);
         }
+        let id = storage.strings.len() as u32;
+        let string: Arc<str> = Arc::from(s);
+        storage.strings.push(string.clone());
+        storage.map.insert(string, id);
+        InternedString::new(id)
     }
     
-    pub fn get(&self, interned: &InternedString) -> Option<&str> {
-        self.strings.get(interned.id as usize).map(|s| s.as_str())
+    /// The string `interned` stands for, if it was interned here
+    pub fn resolve(&self, interned: InternedString) -> Option<String> {
+        self.read().strings.get(interned.id as usize).map(|s| s.to_string())
+    }
+    
+    /// Whether `interned` stands for `s`
+    pub fn matches(&self, interned: InternedString, s: &str) -> bool {
+        self.read().strings.get(interned.id as usize).is_some_and(|string| &**string == s)
+    }
+    
+    // The table is append-only, so a panic while holding the lock cannot leave it
+    // inconsistent - poisoning is ignored
+    fn read(&self) -> RwLockReadGuard<'_, InternerStorage> {
+        self.storage.read().unwrap_or_else(PoisonError::into_inner)
+    }
+    
+    fn write(&self) -> RwLockWriteGuard<'_, InternerStorage> {
+        self.storage.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Doc comment read in front of a token
+#[derive(Debug, Clone)]
+struct DocComment {
+    text: String,
+    /// Written as `//!` or `/*!`, documenting the enclosing item rather than the next one
+    inner: bool,
+    position: Position,
+}
+
+/// Next token that is not a comment, with the doc comments written in front of it
+/// and the position just past its end
+fn next_significant_token<'a>(lexer: &mut Lexer<'a>) -> (Result<Token<'a>, LexerError>, Vec<DocComment>, Position) {
+    let mut docs = Vec::new();
+    loop {
+        let token = match lexer.next_token() {
+            Ok(token) => token,
+            Err(err) => return (Err(err), docs, lexer.get_position()),
+        };
+        let (text, inner) = match token.token_type {
+            TokenType::LineComment(_) | TokenType::BlockComment(_) => continue,
+            TokenType::DocLineComment(text) | TokenType::DocBlockComment(text) => (text, false),
+            TokenType::InnerDocLineComment(text) | TokenType::InnerDocBlockComment(text) => (text, true),
+            _ => return (Ok(token), docs, lexer.get_position()),
+        };
+        docs.push(DocComment { text: text.into_owned(), inner, position: token.position });
+    }
+}
+
+/// Parse error for a lexer error, with a hint at its usual causes
+fn lexer_error(error: LexerError) -> ParseError {
+    let position = error.position();
+    ParseError::LexerError {
+        error,
+        suggestions: vec![
+            Suggestion::new("Check for invalid characters in source code", position)
+                .with_category(SuggestionCategory::Syntax)
+        ],
+        help: Some("Lexer errors often indicate invalid character sequences or encoding issues.".to_string()),
     }
 }
 
+/// Text of a doc comment without its comment syntax: the space after `///` and the
+/// `*` gutter of block comments are dropped
+fn doc_comment_text(text: &str) -> String {
+    let lines: Vec<&str> = text.lines()
+        .map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('*').map(str::trim_start).unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line).trim_end()
+        })
+        .collect();
+    let first = lines.iter().position(|line| !line.is_empty()).unwrap_or(lines.len());
+    let last = lines.iter().rposition(|line| !line.is_empty()).map_or(first, |last| last + 1);
+    lines[first..last].join("\n")
+}
+
+/// Nesting depth `Parser` allows unless told otherwise
+///
+/// Parsing, and everything done with the AST afterwards, recurses once per level,
+/// so this bounds the stack they need; `compile::COMPILER_STACK_SIZE` is sized for it.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 256;
+
+/// Height in chained binary operators, casts, postfix operators and `else if`s that
+/// `Parser` allows an expression or statement
+///
+/// Each link nests the AST one level deeper without the parser recursing, so chains
+/// are bounded apart from `DEFAULT_MAX_NESTING_DEPTH`. A chain whose operand holds
+/// another chain is as high as both together.
+pub const DEFAULT_MAX_CHAIN_LENGTH: usize = 1024;
+
 /// The main parser struct that converts tokens to AST
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
-    pub(super) current_token: Option<Token>,
+    pub(super) current_token: Option<Token<'a>>,
+    /// Doc comments written directly in front of `current_token`
+    current_docs: Vec<DocComment>,
+    /// One token of lookahead, filled on demand by `peek_token`
+    peeked_token: Option<(Result<Token<'a>, LexerError>, Vec<DocComment>, Position)>,
+    /// Where `current_token` ends
+    current_end: Position,
+    /// Where the token before `current_token` ends - where a missing `;` belongs
+    previous_end: Position,
     pub(super) interner: StringInterner,
     errors: Vec<ParseError>,
     /// Current parsing context for better error messages
     context_stack: Vec<ParseContext>,
     /// Delimiter stack for tracking unclosed delimiters
-    delimiter_stack: Vec<(TokenType, Position, String)>,
+    delimiter_stack: Vec<(TokenType<'static>, Position, String)>,
     /// Keywords for similarity matching
     keywords: Vec<&'static str>,
+    /// Whether `Name {` may start a struct literal (false in if/while/match/for heads)
+    struct_literals_allowed: bool,
+    /// Expressions, types, patterns and statements currently being parsed inside each other
+    nesting_depth: usize,
+    max_nesting_depth: usize,
+    /// Height in chain links of the tallest subtree parsed since the innermost open chain
+    /// started, that chain's own links included
+    chain_height: usize,
+    /// Significant tokens consumed so far, `current_token` included
+    tokens_lexed: usize,
+    /// Labels of the loops being parsed, innermost last
+    pub(super) loop_labels: Vec<InternedString>,
 }
 
 impl<'a> Parser<'a> {
     /// Create a new parser from source code
     pub fn new(input: &'a str, file_id: usize) -> ParseResult<Self> {
-        let mut lexer = Lexer::new(input, file_id);
-        let current_token = match lexer.next_token() {
-            Ok(token) => Some(token),
-            Err(err) => return Err(ParseError::from(err)),
+        Self::with_interner(input, file_id, StringInterner::new())
+    }
+    
+    /// Create a parser that interns names into an existing (shared) interner
+    pub fn with_interner(input: &'a str, file_id: usize, interner: StringInterner) -> ParseResult<Self> {
+        // Comments reach the parser so doc comments can be attached to items, and
+        // malformed input becomes one error each instead of ending the parse
+        let mut lexer = Lexer::new_with_comments(input, file_id).with_error_recovery();
+        let (current_token, current_docs, current_end) = match next_significant_token(&mut lexer) {
+            (Ok(token), docs, end) => (Some(token), docs, end),
+            (Err(err), _, _) => return Err(ParseError::from(err)),
         };
         
         let keywords = vec![
@@ -71,43 +204,121 @@ impl<'a> Parser<'a> {
             "char", "str", "String", "Vec", "Option", "Result"
         ];
         
-        Ok(Parser {
+        let mut parser = Parser {
             lexer,
             current_token,
-            interner: StringInterner::new(),
+            current_docs,
+            peeked_token: None,
+            current_end,
+            previous_end: Position::start(file_id),
+            interner,
             errors: Vec::new(),
             context_stack: vec![ParseContext::TopLevel],
             delimiter_stack: Vec::new(),
             keywords,
-        })
+            struct_literals_allowed: true,
+            nesting_depth: 0,
+            max_nesting_depth: DEFAULT_MAX_NESTING_DEPTH,
+            chain_height: 0,
+            tokens_lexed: 1,
+            loop_labels: Vec::new(),
+        };
+        parser.take_lexer_errors();
+        Ok(parser)
+    }
+    
+    /// Fail with `ParseError::NestingTooDeep` past `depth` nested expressions, types,
+    /// patterns or statements, instead of the default `DEFAULT_MAX_NESTING_DEPTH`
+    pub fn with_max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = depth;
+        self
+    }
+    
+    /// Run `parse` one nesting level deeper, failing if that passes the limit
+    ///
+    /// Every recursive path through the grammar goes through here, so input cannot
+    /// nest deeply enough to overflow the stack.
+    pub(super) fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.nesting_depth >= self.max_nesting_depth {
+            return Err(ParseError::NestingTooDeep {
+                limit: self.max_nesting_depth,
+                position: self.current_position(),
+            });
+        }
+        self.nesting_depth += 1;
+        let chain_height = self.chain_height;
+        let result = parse(self);
+        self.nesting_depth -= 1;
+        if result.is_err() {
+            // What failed to parse has no height
+            self.chain_height = chain_height;
+        }
+        result
+    }
+    
+    /// Start parsing a chain, returning the height parsed before it for `end_chain`
+    pub(super) fn start_chain(&mut self) -> usize {
+        std::mem::take(&mut self.chain_height)
+    }
+    
+    /// Add a link on top of everything the current chain has parsed so far, failing
+    /// past `DEFAULT_MAX_CHAIN_LENGTH`
+    pub(super) fn chain_link(&mut self) -> ParseResult<()> {
+        if self.chain_height >= DEFAULT_MAX_CHAIN_LENGTH {
+            return Err(ParseError::NestingTooDeep {
+                limit: DEFAULT_MAX_CHAIN_LENGTH,
+                position: self.current_position(),
+            });
+        }
+        self.chain_height += 1;
+        Ok(())
+    }
+    
+    /// Finish the chain `start_chain` returned `before` for
+    pub(super) fn end_chain(&mut self, before: usize) {
+        self.chain_height = self.chain_height.max(before);
     }
     
     /// Get the current token without consuming it
-    pub fn current_token(&self) -> Option<&Token> {
+    pub fn current_token(&self) -> Option<&Token<'a>> {
         self.current_token.as_ref()
     }
     
     /// Advance to the next token
     pub fn advance(&mut self) -> ParseResult<()> {
-        match self.lexer.next_token() {
+        let (next, docs, end) = match self.peeked_token.take() {
+            Some(peeked) => peeked,
+            None => next_significant_token(&mut self.lexer),
+        };
+        self.take_lexer_errors();
+        self.current_docs = docs;
+        self.previous_end = std::mem::replace(&mut self.current_end, end);
+        match next {
             Ok(token) => {
                 self.current_token = Some(token);
+                self.tokens_lexed += 1;
                 Ok(())
             }
-            Err(err) => {
-                let enhanced_error = ParseError::LexerError {
-                    error: err,
-                    suggestions: vec![
-                        Suggestion::new("Check for invalid characters in source code", self.current_position())
-                            .with_category(SuggestionCategory::Syntax)
-                    ],
-                    help: Some("Lexer errors often indicate invalid character sequences or encoding issues.".to_string()),
-                };
-                Err(enhanced_error)
-            }
+            Err(err) => Err(lexer_error(err)),
         }
     }
     
+    /// Record the malformed input the lexer skipped as parse errors
+    fn take_lexer_errors(&mut self) {
+        for err in self.lexer.take_errors() {
+            self.add_error(lexer_error(err));
+        }
+    }
+    
+    /// Look at the token after the current one without consuming anything
+    pub fn peek_token(&mut self) -> Option<&Token<'a>> {
+        if self.peeked_token.is_none() {
+            self.peeked_token = Some(next_significant_token(&mut self.lexer));
+            self.take_lexer_errors();
+        }
+        self.peeked_token.as_ref().and_then(|(peeked, _, _)| peeked.as_ref().ok())
+    }
+    
     /// Check if current token matches the expected type
     pub fn check(&self, token_type: &TokenType) -> bool {
         self.current_token
@@ -135,7 +346,7 @@ impl<'a> Parser<'a> {
     }
     
     /// Enhanced expect method with context-aware error messages
-    pub fn expect(&mut self, expected: TokenType, description: &str) -> ParseResult<Token> {
+    pub fn expect(&mut self, expected: TokenType, description: &str) -> ParseResult<Token<'a>> {
         if let Some(token) = &self.current_token {
             if std::mem::discriminant(&token.token_type) == std::mem::discriminant(&expected) {
                 let token = token.clone();
@@ -144,50 +355,85 @@ impl<'a> Parser<'a> {
                 self.track_delimiter(&token.token_type, token.position);
                 
                 self.advance()?;
-                Ok(token)
-            } else {
-                let context = self.current_context().clone();
-                let position = token.position;
-                let found = token.token_type.clone();
-                
-                // Generate intelligent suggestions
-                let mut suggestions = suggest_for_context(&context, &found);
-                
-                // Add similarity-based suggestions for identifiers
-                if let TokenType::Identifier(ref name) = found {
-                    let similar = suggest_similar_identifiers(name, &self.keywords);
-                    for similar_word in similar {
-                        suggestions.push(
-                            Suggestion::new(&format!("Did you mean '{}'?", similar_word), position)
-                                .with_replacement(&similar_word)
-                                .with_category(SuggestionCategory::Syntax)
-                                .with_confidence(0.7)
-                        );
-                    }
+                return Ok(token);
+            }
+        }
+        Err(self.expected_error(&format!("{:?}", expected), Some(&expected), description))
+    }
+    
+    /// Expect an identifier, returning its name
+    pub fn expect_identifier(&mut self, description: &str) -> ParseResult<Cow<'a, str>> {
+        if let Some(Token { token_type: TokenType::Identifier(name), .. }) = &self.current_token {
+            let name = name.clone();
+            self.advance()?;
+            return Ok(name);
+        }
+        Err(self.expected_error("Identifier", None, description))
+    }
+    
+    /// Expect an identifier, returning its interned name
+    pub fn expect_name(&mut self, description: &str) -> ParseResult<InternedString> {
+        let name = self.expect_identifier(description)?;
+        Ok(self.interner.intern(&name))
+    }
+    
+    /// Error for the current token, or the end of input, where `label` was expected
+    ///
+    /// `expected` is the token expected, if it is a particular one.
+    fn expected_error(&self, label: &str, expected: Option<&TokenType>, description: &str) -> ParseError {
+        if let Some(token) = &self.current_token {
+            let context = self.current_context().clone();
+            let position = token.position;
+            let found = token.token_type.clone().into_owned();
+            
+            // Generate intelligent suggestions
+            let mut suggestions = suggest_for_context(&context, &found, position);
+            
+            // A missing semicolon belongs right after the previous token, not before this one
+            if expected == Some(&TokenType::Semicolon) {
+                suggestions.insert(0,
+                    Suggestion::new("Insert the missing semicolon", self.previous_end)
+                        .with_replacement(";")
+                        .with_category(SuggestionCategory::Syntax)
+                        .with_confidence(0.9)
+                );
+            }
+            
+            // Add similarity-based suggestions for identifiers
+            if let TokenType::Identifier(ref name) = found {
+                let similar = suggest_similar_identifiers(name, &self.keywords);
+                for similar_word in similar {
+                    suggestions.push(
+                        Suggestion::new(&format!("Did you mean '{}'?", similar_word), position)
+                            .with_replacement(&similar_word)
+                            .with_end(self.current_end)
+                            .with_category(SuggestionCategory::Syntax)
+                            .with_confidence(0.7)
+                    );
                 }
-                
-                // Context-specific help
-                let help = match (&expected, &context) {
-                    (TokenType::Semicolon, ParseContext::Statement) => {
-                        Some("Most statements in Bract must end with a semicolon (;)".to_string())
-                    }
-                    (TokenType::LeftBrace, ParseContext::FunctionBody) => {
-                        Some("Function bodies must be enclosed in braces { }".to_string())
-                    }
-                    (TokenType::Colon, ParseContext::TypeAnnotation) => {
-                        Some("Type annotations are specified with a colon (:) followed by the type".to_string())
-                    }
-                    _ => None,
-                };
-                
-                Err(ParseError::UnexpectedToken {
-                    expected: vec![ExpectedToken::new(&format!("{:?}", expected), description)],
-                    found,
-                    position,
-                    context,
-                    suggestions,
-                    help,
-                })
+            }
+            
+            // Context-specific help
+            let help = match (expected, &context) {
+                (Some(TokenType::Semicolon), ParseContext::Statement) => {
+                    Some("Most statements in Bract must end with a semicolon (;)".to_string())
+                }
+                (Some(TokenType::LeftBrace), ParseContext::FunctionBody) => {
+                    Some("Function bodies must be enclosed in braces { }".to_string())
+                }
+                (Some(TokenType::Colon), ParseContext::TypeAnnotation) => {
+                    Some("Type annotations are specified with a colon (:) followed by the type".to_string())
+                }
+                _ => None,
+            };
+            
+            ParseError::UnexpectedToken {
+                expected: vec![ExpectedToken::new(label, description)],
+                found: Box::new(found),
+                position,
+                context,
+                suggestions,
+                help,
             }
         } else {
             let context = self.current_context().clone();
@@ -211,13 +457,13 @@ impl<'a> Parser<'a> {
                     .with_category(SuggestionCategory::Syntax)]
             };
             
-            Err(ParseError::UnexpectedEof {
-                expected: vec![ExpectedToken::new(&format!("{:?}", expected), description)],
+            ParseError::UnexpectedEof {
+                expected: vec![ExpectedToken::new(label, description)],
                 position,
                 context,
                 unclosed_delimiters,
                 suggestions,
-            })
+            }
         }
     }
     
@@ -274,11 +520,72 @@ impl<'a> Parser<'a> {
         self.errors.push(error);
     }
     
+    /// Whether `Name {` currently starts a struct literal
+    pub(super) fn struct_literals_allowed(&self) -> bool {
+        self.struct_literals_allowed
+    }
+    
+    /// Run `parse` with struct literals allowed or forbidden, restoring the previous setting after
+    pub(super) fn with_struct_literals<T>(
+        &mut self,
+        allowed: bool,
+        parse: impl FnOnce(&mut Self) -> ParseResult<T>,
+    ) -> ParseResult<T> {
+        let saved = std::mem::replace(&mut self.struct_literals_allowed, allowed);
+        let result = parse(self);
+        self.struct_literals_allowed = saved;
+        result
+    }
+    
+    /// Parse the head of an if/while/match/for, where `Name {` opens the body block
+    /// rather than a struct literal (parenthesize to use one: `if (P { x: 1 }).x > 0`)
+    pub(super) fn parse_condition_expression(&mut self) -> ParseResult<Expr> {
+        self.with_struct_literals(false, |parser| parser.parse_expression())
+    }
+    
+    /// Take the outer (`///`) or inner (`//!`) doc comments written in front of the
+    /// current token as one `doc` attribute
+    pub(super) fn take_doc_attribute(&mut self, inner: bool) -> Option<Attribute> {
+        let (docs, rest): (Vec<DocComment>, Vec<DocComment>) = std::mem::take(&mut self.current_docs)
+            .into_iter()
+            .partition(|doc| doc.inner == inner);
+        self.current_docs = rest;
+        
+        let start_pos = docs.first()?.position;
+        let text: Vec<String> = docs.iter().map(|doc| doc_comment_text(&doc.text)).collect();
+        let span = Span::new(start_pos, self.current_position());
+        Some(Attribute {
+            name: self.interner.intern("doc"),
+            args: vec![AttributeArg {
+                key: None,
+                value: Literal::String { value: self.interner.intern(&text.join("\n")), raw: false, raw_delimiter: None },
+                span,
+            }],
+            cfg: None,
+            span,
+        })
+    }
+    
     /// Get all accumulated errors
     pub fn errors(&self) -> &[ParseError] {
         &self.errors
     }
     
+    /// Number of significant tokens read so far, end of input included
+    pub fn tokens_lexed(&self) -> usize {
+        self.tokens_lexed
+    }
+    
+    /// Number of delimiters opened and not yet closed
+    pub fn open_delimiter_count(&self) -> usize {
+        self.delimiter_stack.len()
+    }
+    
+    /// Shared handle to the string interner, for resolving names in the parsed AST
+    pub fn interner(&self) -> StringInterner {
+        self.interner.clone()
+    }
+    
     /// Extract the string interner (consumes the parser)
     pub fn take_interner(self) -> StringInterner {
         self.interner
@@ -343,6 +650,9 @@ impl<'a> Parser<'a> {
         let mut error_count = 0;
         const MAX_ERRORS_PER_MODULE: usize = 50;
         
+        // `//!` comments at the top document the file itself
+        let attributes = self.take_doc_attribute(true).into_iter().collect();
+        
         self.enter_context(ParseContext::TopLevel);
         
         while !self.is_at_end() && error_count < MAX_ERRORS_PER_MODULE {
@@ -352,6 +662,12 @@ impl<'a> Parser<'a> {
                     // Reset error count on successful parse
                     error_count = 0;
                 }
+                Err(err @ ParseError::NestingTooDeep { .. }) => {
+                    // Skip the whole item, or each level left open would report an error
+                    self.add_error(err);
+                    self.recover_in_body(0);
+                    error_count += 1;
+                }
                 Err(err) => {
                     self.add_error(err);
                     self.synchronize();
@@ -391,16 +707,27 @@ impl<'a> Parser<'a> {
         }
         
         let end_pos = self.current_position();
-        Ok(Module {
+        let mut module = Module {
             items,
+            attributes,
             span: Span::new(start_pos, end_pos),
-        })
+        };
+        assign_node_ids(&mut module);
+        Ok(module)
     }
     
     /// Parse a top-level item (function, struct, etc.) with enhanced error handling
     pub fn parse_item(&mut self) -> ParseResult<Item> {
+        self.nested(Self::parse_item_inner)
+    }
+    
+    fn parse_item_inner(&mut self) -> ParseResult<Item> {
         let start_pos = self.current_position();
         
+        // Annotations may be written before or after the visibility modifier
+        let mut annotations = ItemAnnotations::default();
+        self.parse_item_annotations(&mut annotations)?;
+        
         // Parse visibility modifier
         let visibility = if self.match_token(&TokenType::Pub) {
             Visibility::Public
@@ -408,19 +735,14 @@ impl<'a> Parser<'a> {
             Visibility::Private
         };
         
-        // Skip annotations for now (parse but don't use them yet)
-        while self.check(&TokenType::At) {
-            self.enter_context(ParseContext::MemoryAnnotation);
-            // Skip annotation - for now just advance past it
-            while !self.is_at_end() && !self.check(&TokenType::Fn) && !self.check(&TokenType::Struct) 
-                && !self.check(&TokenType::Enum) && !self.check(&TokenType::Type) 
-                && !self.check(&TokenType::Const) && !self.check(&TokenType::Mod) 
-                && !self.check(&TokenType::Impl) && !self.check(&TokenType::Use) {
-                self.advance()?;
-            }
-            self.exit_context();
-        }
+        self.parse_item_annotations(&mut annotations)?;
         
+        let item = self.parse_item_declaration(visibility, start_pos)?;
+        self.attach_item_annotations(item, annotations)
+    }
+    
+    /// Parse the declaration following an item's visibility and annotations
+    fn parse_item_declaration(&mut self, visibility: Visibility, start_pos: Position) -> ParseResult<Item> {
         if let Some(token) = &self.current_token {
             match &token.token_type {
                 TokenType::Fn => {
@@ -479,6 +801,7 @@ impl<'a> Parser<'a> {
                             enhanced_suggestions.push(
                                 Suggestion::new(&format!("Did you mean '{}'?", similar_keyword), token.position)
                                     .with_replacement(&similar_keyword)
+                                    .with_end(self.current_end)
                                     .with_category(SuggestionCategory::Syntax)
                                     .with_confidence(0.8)
                             );
@@ -496,7 +819,7 @@ impl<'a> Parser<'a> {
                             ExpectedToken::new("mod", "module declaration").with_example("mod my_module { }"),
                             ExpectedToken::new("use", "use declaration").with_example("use std::collections::HashMap;"),
                         ],
-                        found: token.token_type.clone(),
+                        found: Box::new(token.token_type.clone().into_owned()),
                         position: token.position,
                         context,
                         suggestions: enhanced_suggestions,
@@ -529,71 +852,10 @@ impl<'a> Parser<'a> {
         self.expect(TokenType::Fn, "function declaration")?;
         
         // Function name
-        let name_token = self.expect(TokenType::Identifier("".to_string()), "function name")?;
-        let name = if let TokenType::Identifier(name_str) = name_token.token_type {
-            self.interner.intern(&name_str)
-        } else {
-            return Err(ParseError::InvalidSyntax {
-                message: "Expected function name".to_string(),
-                position: name_token.position,
-                context: self.current_context().clone(),
-                suggestions: vec![
-                    Suggestion::new("Use a valid identifier for the function name", name_token.position)
-                        .with_category(SuggestionCategory::Syntax)
-                ],
-                help: Some("Function names must be valid identifiers starting with a letter or underscore".to_string()),
-                related_errors: Vec::new(),
-            });
-        };
+        let name = self.expect_name("function name")?;
         
         // Generic parameters
-        let generics = if self.match_token(&TokenType::Less) {
-            let mut generic_params = Vec::new();
-            
-            if !self.check(&TokenType::Greater) {
-                loop {
-                    let param_start = self.current_position();
-                    
-                    // Parse generic parameter name
-                    if let Some(token) = &self.current_token {
-                        if let TokenType::Identifier(param_name) = &token.token_type {
-                            let name = self.interner.intern(param_name);
-                            self.advance()?;
-                            
-                            // TODO: Parse trait bounds and default values
-                            generic_params.push(crate::ast::GenericParam {
-                                name,
-                                bounds: Vec::new(),
-                                default: None,
-                                span: Span::new(param_start, self.current_position()),
-                            });
-                        } else {
-                            return Err(ParseError::InvalidSyntax {
-                                message: "Expected generic parameter name".to_string(),
-                                position: token.position,
-                                context: ParseContext::GenericParameters,
-                                suggestions: vec![
-                                    Suggestion::new("Use a valid type parameter name", token.position)
-                                        .with_category(SuggestionCategory::Syntax)
-                                        .with_replacement("T")
-                                ],
-                                help: Some("Generic parameters should be valid identifiers, typically single capital letters like T, U, V".to_string()),
-                                related_errors: Vec::new(),
-                            });
-                        }
-                    }
-                    
-                    if !self.match_token(&TokenType::Comma) {
-                        break;
-                    }
-                }
-            }
-            
-            self.expect(TokenType::Greater, "generic parameters")?;
-            generic_params
-        } else {
-            Vec::new()
-        };
+        let mut generics = self.parse_generic_params()?;
         
         // Parameters
         self.expect(TokenType::LeftParen, "function parameters")?;
@@ -636,6 +898,7 @@ impl<'a> Parser<'a> {
         } else {
             None
         };
+        self.parse_where_clause(&mut generics)?;
         
         // Function body
         let body = if self.check(&TokenType::LeftBrace) {
@@ -654,7 +917,11 @@ impl<'a> Parser<'a> {
             return_type,
             body,
             is_extern: false, // TODO: handle extern functions
+            performance_contract: None,
+            allocation_strategy: None,
+            attributes: Vec::new(),
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -663,114 +930,33 @@ impl<'a> Parser<'a> {
         self.expect(TokenType::Struct, "struct declaration")?;
         
         // Struct name
-        let name_token = self.expect(TokenType::Identifier("".to_string()), "struct name")?;
-        let name = if let TokenType::Identifier(name_str) = name_token.token_type {
-            self.interner.intern(&name_str)
-        } else {
-            return Err(ParseError::InvalidSyntax {
-                message: "Expected struct name".to_string(),
-                position: name_token.position,
-                context: self.current_context().clone(),
-                suggestions: vec![
-                    Suggestion::new("Use a valid identifier for the struct name", name_token.position)
-                        .with_category(SuggestionCategory::Syntax)
-                ],
-                help: Some("Struct names must be valid identifiers starting with a letter or underscore".to_string()),
-                related_errors: Vec::new(),
-            });
-        };
+        let name = self.expect_name("struct name")?;
         
         // Generic parameters
-        let generics = if self.match_token(&TokenType::Less) {
-            let mut generic_params = Vec::new();
-            
-            if !self.check(&TokenType::Greater) {
-                loop {
-                    let param_start = self.current_position();
-                    
-                    // Parse generic parameter name
-                    if let Some(token) = &self.current_token {
-                        if let TokenType::Identifier(param_name) = &token.token_type {
-                            let name = self.interner.intern(param_name);
-                            self.advance()?;
-                            
-                            // TODO: Parse trait bounds and default values
-                            generic_params.push(crate::ast::GenericParam {
-                                name,
-                                bounds: Vec::new(),
-                                default: None,
-                                span: Span::new(param_start, self.current_position()),
-                            });
-                        } else {
-                            return Err(ParseError::InvalidSyntax {
-                                message: "Expected generic parameter name".to_string(),
-                                position: token.position,
-                                context: ParseContext::GenericParameters,
-                                suggestions: vec![
-                                    Suggestion::new("Use a valid type parameter name", token.position)
-                                        .with_category(SuggestionCategory::Syntax)
-                                        .with_replacement("T")
-                                ],
-                                help: Some("Generic parameters should be valid identifiers, typically single capital letters like T, U, V".to_string()),
-                                related_errors: Vec::new(),
-                            });
-                        }
-                    }
-                    
-                    if !self.match_token(&TokenType::Comma) {
-                        break;
-                    }
-                }
-            }
-            
-            self.expect(TokenType::Greater, "generic parameters")?;
-            generic_params
-        } else {
-            Vec::new()
-        };
+        let mut generics = self.parse_generic_params()?;
+        
+        self.parse_where_clause(&mut generics)?;
         
         // Parse struct fields
-        let fields = if self.match_token(&TokenType::LeftBrace) {
+        let fields = if self.check(&TokenType::LeftBrace) {
             // Named fields: struct Point { x: i32, y: i32 }
+            self.expect(TokenType::LeftBrace, "struct fields")?;
+            let body_depth = self.delimiter_stack.len();
             let mut field_list = Vec::new();
             
             while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-                let field_start = self.current_position();
-                
-                // Field visibility (default private)
-                let field_visibility = if self.match_token(&TokenType::Pub) {
-                    Visibility::Public
-                } else {
-                    Visibility::Private
-                };
-                
-                // Field name
-                let field_name_token = self.expect(TokenType::Identifier("".to_string()), "field name")?;
-                let field_name = if let TokenType::Identifier(name_str) = field_name_token.token_type {
-                    self.interner.intern(&name_str)
-                } else {
-                    return Err(ParseError::InvalidSyntax {
-                        message: "Expected field name".to_string(),
-                        position: field_name_token.position,
-                        context: self.current_context().clone(),
-                        suggestions: vec![
-                            Suggestion::new("Use a valid identifier for the field name", field_name_token.position)
-                                .with_category(SuggestionCategory::Syntax)
-                        ],
-                        help: Some("Field names must be valid identifiers starting with a letter or underscore".to_string()),
-                        related_errors: Vec::new(),
-                    });
-                };
-                
-                self.expect(TokenType::Colon, "field type annotation")?;
-                let field_type = self.parse_type()?;
-                
-                field_list.push(crate::ast::StructField {
-                    visibility: field_visibility,
-                    name: field_name,
-                    field_type,
-                    span: Span::new(field_start, self.current_position()),
-                });
+                match self.parse_struct_field() {
+                    Ok(field) => field_list.push(field),
+                    Err(error @ ParseError::NestingTooDeep { .. }) => return Err(error),
+                    Err(error) => {
+                        // One bad field doesn't cost the rest of the struct
+                        self.add_error(error);
+                        if self.recover_in_body(body_depth) {
+                            continue;
+                        }
+                        break;
+                    }
+                }
                 
                 if !self.match_token(&TokenType::Comma) {
                     break;
@@ -806,180 +992,109 @@ impl<'a> Parser<'a> {
             name,
             generics,
             fields,
+            attributes: Vec::new(),
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
-    // Implement enum parsing
-    fn parse_enum(&mut self, visibility: Visibility, start_pos: Position) -> ParseResult<Item> {
-        self.expect(TokenType::Enum, "enum declaration")?;
+    /// Parse one named struct field: `[pub] name: Type`
+    fn parse_struct_field(&mut self) -> ParseResult<crate::ast::StructField> {
+        let field_start = self.current_position();
         
-        // Enum name
-        let name_token = self.expect(TokenType::Identifier("".to_string()), "enum name")?;
-        let name = if let TokenType::Identifier(name_str) = name_token.token_type {
-            self.interner.intern(&name_str)
+        // Field visibility (default private)
+        let field_visibility = if self.match_token(&TokenType::Pub) {
+            Visibility::Public
         } else {
-            return Err(ParseError::InvalidSyntax {
-                message: "Expected enum name".to_string(),
-                position: name_token.position,
-                context: self.current_context().clone(),
-                suggestions: vec![
-                    Suggestion::new("Use a valid identifier for the enum name", name_token.position)
-                        .with_category(SuggestionCategory::Syntax)
-                ],
-                help: Some("Enum names must be valid identifiers starting with a letter or underscore".to_string()),
-                related_errors: Vec::new(),
-            });
+            Visibility::Private
         };
         
-        // Generic parameters
-        let generics = if self.match_token(&TokenType::Less) {
-            let mut generic_params = Vec::new();
-            
-            if !self.check(&TokenType::Greater) {
-                loop {
-                    let param_start = self.current_position();
-                    
-                    // Parse generic parameter name
-                    if let Some(token) = &self.current_token {
-                        if let TokenType::Identifier(param_name) = &token.token_type {
-                            let name = self.interner.intern(param_name);
-                            self.advance()?;
-                            
-                            // TODO: Parse trait bounds and default values
-                            generic_params.push(crate::ast::GenericParam {
-                                name,
-                                bounds: Vec::new(),
-                                default: None,
-                                span: Span::new(param_start, self.current_position()),
-                            });
-                        } else {
-                            return Err(ParseError::InvalidSyntax {
-                                message: "Expected generic parameter name".to_string(),
-                                position: token.position,
-                                context: ParseContext::GenericParameters,
-                                suggestions: vec![
-                                    Suggestion::new("Use a valid type parameter name", token.position)
-                                        .with_category(SuggestionCategory::Syntax)
-                                        .with_replacement("T")
-                                ],
-                                help: Some("Generic parameters should be valid identifiers, typically single capital letters like T, U, V".to_string()),
-                                related_errors: Vec::new(),
-                            });
-                        }
-                    }
-                    
-                    if !self.match_token(&TokenType::Comma) {
-                        break;
+        // Field name
+        let field_name = self.expect_name("field name")?;
+        
+        self.expect(TokenType::Colon, "field type annotation")?;
+        let field_type = self.parse_type()?;
+        
+        Ok(crate::ast::StructField {
+            visibility: field_visibility,
+            name: field_name,
+            field_type,
+            span: Span::new(field_start, self.current_position()),
+        })
+    }
+    
+    /// Skip the rest of a malformed field or variant, so parsing can resume with the next one
+    ///
+    /// `body_depth` is the delimiter stack height just inside the body's `{`, or 0 to
+    /// skip the rest of a top-level item. Delimiters
+    /// the failed parse left open are dropped from the stack and skipped through. Stops
+    /// after the next `,` of the body (returning true, parsing resumes) or at the body's
+    /// closing `}` or an item keyword, which are left for the caller (returning false).
+    fn recover_in_body(&mut self, body_depth: usize) -> bool {
+        let mut open: Vec<TokenType> = self.delimiter_stack
+            .drain(body_depth.min(self.delimiter_stack.len())..)
+            .map(|(closer, _, _)| closer)
+            .collect();
+        
+        while let Some(token) = &self.current_token {
+            match &token.token_type {
+                TokenType::LeftParen => open.push(TokenType::RightParen),
+                TokenType::LeftBrace => open.push(TokenType::RightBrace),
+                TokenType::LeftBracket => open.push(TokenType::RightBracket),
+                closer @ (TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket) => {
+                    match open.iter().rposition(|expected| expected == closer) {
+                        // Close the matching delimiter and any left unclosed inside it
+                        Some(index) => open.truncate(index),
+                        None if *closer == TokenType::RightBrace => return false,
+                        None => {}
                     }
                 }
+                TokenType::Comma if open.is_empty() => {
+                    self.advance().unwrap_or(());
+                    return true;
+                }
+                TokenType::Eof => return false,
+                TokenType::Fn | TokenType::Struct | TokenType::Enum | TokenType::Impl |
+                TokenType::Mod | TokenType::Use | TokenType::Const | TokenType::Type if open.is_empty() => {
+                    return false;
+                }
+                _ => {}
             }
-            
-            self.expect(TokenType::Greater, "generic parameters")?;
-            generic_params
-        } else {
-            Vec::new()
-        };
+            self.advance().unwrap_or(());
+        }
+        false
+    }
+    
+    // Implement enum parsing
+    fn parse_enum(&mut self, visibility: Visibility, start_pos: Position) -> ParseResult<Item> {
+        self.expect(TokenType::Enum, "enum declaration")?;
+        
+        // Enum name
+        let name = self.expect_name("enum name")?;
+        
+        // Generic parameters
+        let mut generics = self.parse_generic_params()?;
+        
+        self.parse_where_clause(&mut generics)?;
         
         // Parse enum variants
         self.expect(TokenType::LeftBrace, "enum variants")?;
+        let body_depth = self.delimiter_stack.len();
         let mut variants = Vec::new();
         
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-            let variant_start = self.current_position();
-            
-            // Variant name
-            let variant_name_token = self.expect(TokenType::Identifier("".to_string()), "variant name")?;
-            let variant_name = if let TokenType::Identifier(name_str) = variant_name_token.token_type {
-                self.interner.intern(&name_str)
-            } else {
-                return Err(ParseError::InvalidSyntax {
-                    message: "Expected variant name".to_string(),
-                    position: variant_name_token.position,
-                    context: self.current_context().clone(),
-                    suggestions: vec![
-                        Suggestion::new("Use a valid identifier for the variant name", variant_name_token.position)
-                            .with_category(SuggestionCategory::Syntax)
-                    ],
-                    help: Some("Variant names must be valid identifiers starting with a letter or underscore".to_string()),
-                    related_errors: Vec::new(),
-                });
-            };
-            
-            // Parse variant fields
-            let fields = if self.match_token(&TokenType::LeftBrace) {
-                // Named fields: Some { value: T }
-                let mut field_list = Vec::new();
-                
-                while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
-                    let field_start = self.current_position();
-                    
-                    let field_name_token = self.expect(TokenType::Identifier("".to_string()), "field name")?;
-                    let field_name = if let TokenType::Identifier(name_str) = field_name_token.token_type {
-                        self.interner.intern(&name_str)
-                    } else {
-                        return Err(ParseError::InvalidSyntax {
-                            message: "Expected field name".to_string(),
-                            position: field_name_token.position,
-                            context: self.current_context().clone(),
-                            suggestions: vec![
-                                Suggestion::new("Use a valid identifier for the field name", field_name_token.position)
-                                    .with_category(SuggestionCategory::Syntax)
-                            ],
-                            help: Some("Field names must be valid identifiers starting with a letter or underscore".to_string()),
-                            related_errors: Vec::new(),
-                        });
-                    };
-                    
-                    self.expect(TokenType::Colon, "field type")?;
-                    let field_type = self.parse_type()?;
-                    
-                    field_list.push(crate::ast::StructField {
-                        visibility: Visibility::Public, // Enum fields are always public
-                        name: field_name,
-                        field_type,
-                        span: Span::new(field_start, self.current_position()),
-                    });
-                    
-                    if !self.match_token(&TokenType::Comma) {
-                        break;
-                    }
-                }
-                
-                self.expect(TokenType::RightBrace, "variant fields")?;
-                crate::ast::StructFields::Named(field_list)
-            } else if self.match_token(&TokenType::LeftParen) {
-                // Tuple fields: Some(T)
-                let mut types = Vec::new();
-                
-                while !self.check(&TokenType::RightParen) && !self.is_at_end() {
-                    types.push(self.parse_type()?);
-                    
-                    if !self.match_token(&TokenType::Comma) {
-                        break;
+            match self.parse_enum_variant() {
+                Ok(variant) => variants.push(variant),
+                Err(error @ ParseError::NestingTooDeep { .. }) => return Err(error),
+                Err(error) => {
+                    // One bad variant doesn't cost the rest of the enum
+                    self.add_error(error);
+                    if self.recover_in_body(body_depth) {
+                        continue;
                     }
+                    break;
                 }
-                
-                self.expect(TokenType::RightParen, "variant fields")?;
-                crate::ast::StructFields::Tuple(types)
-            } else {
-                // Unit variant: None
-                crate::ast::StructFields::Unit
-            };
-            
-            // Optional discriminant value
-            let discriminant = if self.match_token(&TokenType::Equal) {
-                Some(self.parse_expression()?)
-            } else {
-                None
-            };
-            
-            variants.push(crate::ast::EnumVariant {
-                name: variant_name,
-                fields,
-                discriminant,
-                span: Span::new(variant_start, self.current_position()),
-            });
+            }
             
             if !self.match_token(&TokenType::Comma) {
                 break;
@@ -994,7 +1109,79 @@ impl<'a> Parser<'a> {
             name,
             generics,
             variants,
+            attributes: Vec::new(),
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
+        })
+    }
+
+    /// Parse one enum variant: `Name`, `Name(T, ..)` or `Name { field: T, .. }`, with an
+    /// optional `= discriminant`
+    fn parse_enum_variant(&mut self) -> ParseResult<crate::ast::EnumVariant> {
+        let variant_start = self.current_position();
+        
+        // Variant name
+        let variant_name = self.expect_name("variant name")?;
+        
+        // Parse variant fields
+        let fields = if self.check(&TokenType::LeftBrace) {
+            // Named fields: Some { value: T }
+            self.expect(TokenType::LeftBrace, "variant fields")?;
+            let mut field_list = Vec::new();
+            
+            while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+                let field_start = self.current_position();
+                
+                let field_name = self.expect_name("field name")?;
+                
+                self.expect(TokenType::Colon, "field type")?;
+                let field_type = self.parse_type()?;
+                
+                field_list.push(crate::ast::StructField {
+                    visibility: Visibility::Public, // Enum fields are always public
+                    name: field_name,
+                    field_type,
+                    span: Span::new(field_start, self.current_position()),
+                });
+                
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+            
+            self.expect(TokenType::RightBrace, "variant fields")?;
+            crate::ast::StructFields::Named(field_list)
+        } else if self.match_token(&TokenType::LeftParen) {
+            // Tuple fields: Some(T)
+            let mut types = Vec::new();
+            
+            while !self.check(&TokenType::RightParen) && !self.is_at_end() {
+                types.push(self.parse_type()?);
+                
+                if !self.match_token(&TokenType::Comma) {
+                    break;
+                }
+            }
+            
+            self.expect(TokenType::RightParen, "variant fields")?;
+            crate::ast::StructFields::Tuple(types)
+        } else {
+            // Unit variant: None
+            crate::ast::StructFields::Unit
+        };
+        
+        // Optional discriminant value
+        let discriminant = if self.match_token(&TokenType::Equal) {
+            Some(self.parse_expression()?)
+        } else {
+            None
+        };
+        
+        Ok(crate::ast::EnumVariant {
+            name: variant_name,
+            fields,
+            discriminant,
+            span: Span::new(variant_start, self.current_position()),
         })
     }
 
@@ -1002,22 +1189,7 @@ impl<'a> Parser<'a> {
         self.expect(TokenType::Type, "type alias")?;
         
         // Type alias name
-        let name_token = self.expect(TokenType::Identifier("".to_string()), "type name")?;
-        let name = if let TokenType::Identifier(name_str) = name_token.token_type {
-            self.interner.intern(&name_str)
-        } else {
-            return Err(ParseError::InvalidSyntax {
-                message: "Expected type name".to_string(),
-                position: name_token.position,
-                context: self.current_context().clone(),
-                suggestions: vec![
-                    Suggestion::new("Use a valid identifier for the type name", name_token.position)
-                        .with_category(SuggestionCategory::Syntax)
-                ],
-                help: Some("Type names must be valid identifiers starting with a letter or underscore".to_string()),
-                related_errors: Vec::new(),
-            });
-        };
+        let name = self.expect_name("type name")?;
         
         // Generic parameters (placeholder for now)
         let generics = Vec::new();
@@ -1032,7 +1204,9 @@ impl<'a> Parser<'a> {
             name,
             generics,
             target_type,
+            attributes: Vec::new(),
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -1040,22 +1214,7 @@ impl<'a> Parser<'a> {
         self.expect(TokenType::Const, "const declaration")?;
         
         // Const name
-        let name_token = self.expect(TokenType::Identifier("".to_string()), "const name")?;
-        let name = if let TokenType::Identifier(name_str) = name_token.token_type {
-            self.interner.intern(&name_str)
-        } else {
-            return Err(ParseError::InvalidSyntax {
-                message: "Expected const name".to_string(),
-                position: name_token.position,
-                context: self.current_context().clone(),
-                suggestions: vec![
-                    Suggestion::new("Use a valid identifier for the const name", name_token.position)
-                        .with_category(SuggestionCategory::Syntax)
-                ],
-                help: Some("Const names must be valid identifiers starting with a letter or underscore".to_string()),
-                related_errors: Vec::new(),
-            });
-        };
+        let name = self.expect_name("const name")?;
         
         self.expect(TokenType::Colon, "const type")?;
         let type_annotation = self.parse_type()?;
@@ -1069,7 +1228,9 @@ impl<'a> Parser<'a> {
             name,
             type_annotation,
             value,
+            attributes: Vec::new(),
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -1077,33 +1238,27 @@ impl<'a> Parser<'a> {
         self.expect(TokenType::Mod, "module declaration")?;
         
         // Module name
-        let name_token = self.expect(TokenType::Identifier("".to_string()), "module name")?;
-        let name = if let TokenType::Identifier(name_str) = name_token.token_type {
-            self.interner.intern(&name_str)
-        } else {
-            return Err(ParseError::InvalidSyntax {
-                message: "Expected module name".to_string(),
-                position: name_token.position,
-                context: self.current_context().clone(),
-                suggestions: vec![
-                    Suggestion::new("Use a valid identifier for the module name", name_token.position)
-                        .with_category(SuggestionCategory::Syntax)
-                ],
-                help: Some("Module names must be valid identifiers starting with a letter or underscore".to_string()),
-                related_errors: Vec::new(),
-            });
-        };
+        let name = self.expect_name("module name")?;
         
         // Parse module body
+        let mut attributes = Vec::new();
         let items = if self.match_token(&TokenType::LeftBrace) {
+            attributes.extend(self.take_doc_attribute(true));
             let mut module_items = Vec::new();
             
             while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+                let item_start = self.current_position();
                 match self.parse_item() {
                     Ok(item) => module_items.push(item),
+                    // Recovering inside every enclosing level would report the same problem again
+                    Err(err @ ParseError::NestingTooDeep { .. }) => return Err(err),
                     Err(err) => {
                         self.add_error(err);
                         self.synchronize();
+                        // Statement keywords stop synchronization but cannot start an item
+                        if self.current_position() == item_start {
+                            self.advance().unwrap_or(());
+                        }
                     }
                 }
             }
@@ -1121,7 +1276,9 @@ impl<'a> Parser<'a> {
             visibility,
             name,
             items,
+            attributes,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -1180,37 +1337,207 @@ impl<'a> Parser<'a> {
             target_type,
             trait_ref,
             items,
+            attributes: Vec::new(),
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
+    /// Parse an optional generic parameter list: `<T: Ord + Clone, U = u8>`
+    ///
+    /// Each parameter may have trait bounds after a colon and a default type after
+    /// `=`. Declaring the same name twice is an error at the second occurrence.
+    fn parse_generic_params(&mut self) -> ParseResult<Vec<GenericParam>> {
+        let mut generic_params: Vec<GenericParam> = Vec::new();
+        if !self.match_token(&TokenType::Less) {
+            return Ok(generic_params);
+        }
+        
+        while !self.check(&TokenType::Greater) && !self.is_at_end() {
+            let param_start = self.current_position();
+            let name = self.parse_generic_param_name()?;
+            if generic_params.iter().any(|param| param.name == name) {
+                let param_name = self.interner.resolve(name).unwrap_or_default();
+                return Err(ParseError::InvalidSyntax {
+                    message: format!("Duplicate generic parameter '{}'", param_name),
+                    position: param_start,
+                    context: ParseContext::GenericParameters,
+                    suggestions: vec![
+                        Suggestion::new("Rename or remove the repeated parameter", param_start)
+                            .with_category(SuggestionCategory::Syntax)
+                    ],
+                    help: Some("Each generic parameter must have a distinct name".to_string()),
+                });
+            }
+            
+            let bounds = if self.match_token(&TokenType::Colon) {
+                self.parse_generic_bounds()?
+            } else {
+                Vec::new()
+            };
+            let default = if self.match_token(&TokenType::Equal) {
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+            
+            generic_params.push(GenericParam {
+                name,
+                bounds,
+                default,
+                span: Span::new(param_start, self.current_position()),
+            });
+            
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+        }
+        
+        self.expect(TokenType::Greater, "generic parameters")?;
+        Ok(generic_params)
+    }
+    
+    /// Parse the name of a generic parameter
+    fn parse_generic_param_name(&mut self) -> ParseResult<InternedString> {
+        let token = self.current_token.clone().ok_or_else(|| ParseError::UnexpectedEof {
+            expected: vec![ExpectedToken::new("identifier", "generic parameter name")],
+            position: self.current_position(),
+            context: ParseContext::GenericParameters,
+            unclosed_delimiters: Vec::new(),
+            suggestions: Vec::new(),
+        })?;
+        
+        if let TokenType::Identifier(param_name) = &token.token_type {
+            let name = self.interner.intern(param_name);
+            self.advance()?;
+            Ok(name)
+        } else {
+            Err(ParseError::InvalidSyntax {
+                message: "Expected generic parameter name".to_string(),
+                position: token.position,
+                context: ParseContext::GenericParameters,
+                suggestions: vec![
+                    Suggestion::new("Use a valid type parameter name", token.position)
+                        .with_category(SuggestionCategory::Syntax)
+                        .with_replacement("T")
+                ],
+                help: Some("Generic parameters should be valid identifiers, typically single capital letters like T, U, V".to_string()),
+            })
+        }
+    }
+    
+    /// Parse trait bounds: one or more path types separated by `+`
+    fn parse_generic_bounds(&mut self) -> ParseResult<Vec<Type>> {
+        let mut bounds = Vec::new();
+        loop {
+            let bound = self.parse_type()?;
+            if !matches!(bound, Type::Path { .. }) {
+                return Err(ParseError::InvalidSyntax {
+                    message: "Expected a trait name as generic bound".to_string(),
+                    position: bound.span().start,
+                    context: ParseContext::GenericParameters,
+                    suggestions: vec![
+                        Suggestion::new("Bound the parameter by a trait", bound.span().start)
+                            .with_category(SuggestionCategory::Syntax)
+                            .with_replacement("Clone")
+                    ],
+                    help: Some("Bounds name traits, e.g. `T: Clone + Default`".to_string()),
+                });
+            }
+            bounds.push(bound);
+            
+            if !self.match_token(&TokenType::Plus) {
+                return Ok(bounds);
+            }
+        }
+    }
+    
+    /// Parse an optional `where T: Bound, U: Other + Bounds` clause
+    ///
+    /// The bounds are added to the already-declared parameters in `generics`.
+    fn parse_where_clause(&mut self, generics: &mut [GenericParam]) -> ParseResult<()> {
+        if !self.match_token(&TokenType::Where) {
+            return Ok(());
+        }
+        
+        while matches!(self.current_token.as_ref().map(|token| &token.token_type), Some(TokenType::Identifier(_))) {
+            let position = self.current_position();
+            let name = self.parse_generic_param_name()?;
+            self.expect(TokenType::Colon, "where clause")?;
+            let bounds = self.parse_generic_bounds()?;
+            
+            let Some(param) = generics.iter_mut().find(|param| param.name == name) else {
+                let param_name = self.interner.resolve(name).unwrap_or_default();
+                return Err(ParseError::InvalidSyntax {
+                    message: format!("Where clause bounds undeclared generic parameter '{}'", param_name),
+                    position,
+                    context: ParseContext::GenericParameters,
+                    suggestions: vec![
+                        Suggestion::new(&format!("Declare '{}' in the generic parameter list", param_name), position)
+                            .with_category(SuggestionCategory::Syntax)
+                    ],
+                    help: Some("Where clauses can only bound parameters declared in `<...>`".to_string()),
+                });
+            };
+            param.bounds.extend(bounds);
+            
+            if !self.match_token(&TokenType::Comma) {
+                break;
+            }
+        }
+        Ok(())
+    }
+    
     fn parse_use_decl(&mut self, start_pos: Position) -> ParseResult<Item> {
         self.expect(TokenType::Use, "use declaration")?;
         
-        // Parse use path
+        // Parse use path, keeping where each segment is for errors resolving it
         let mut path = Vec::new();
+        let mut path_spans = Vec::new();
         
         loop {
-            let name_token = self.expect(TokenType::Identifier("".to_string()), "use path")?;
-            if let TokenType::Identifier(name_str) = name_token.token_type {
-                path.push(self.interner.intern(&name_str));
+            if !path.is_empty() && self.check(&TokenType::Star) {
+                let position = self.current_position();
+                return Err(ParseError::InvalidSyntax {
+                    message: "Glob imports are not supported yet".to_string(),
+                    position,
+                    context: self.current_context().clone(),
+                    suggestions: vec![
+                        Suggestion::new("Import each name you need on its own", position)
+                            .with_category(SuggestionCategory::Syntax)
+                    ],
+                    help: Some("Write a `use` declaration per item, e.g. `use math::square;`".to_string()),
+                });
             }
+            let start = self.current_position();
+            let name = self.expect_identifier("use path")?;
+            let mut end = start;
+            name.chars().for_each(|ch| end.advance_char(ch));
+            path.push(self.interner.intern(&name));
+            path_spans.push(Span::new(start, end));
             
             if !self.match_token(&TokenType::DoubleColon) {
                 break;
             }
         }
         
-        // TODO: Add alias support when 'as' keyword is implemented
-        let alias = None;
+        // Optional `as alias` renames the imported item
+        let alias = if self.match_token(&TokenType::As) {
+            Some(self.expect_name("use alias")?)
+        } else {
+            None
+        };
         
         self.expect(TokenType::Semicolon, "use declaration")?;
         
         let end_pos = self.current_position();
         Ok(Item::Use {
             path,
+            path_spans,
             alias,
+            attributes: Vec::new(),
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -1226,7 +1553,13 @@ impl<'a> Parser<'a> {
             // Try to parse as statement first
             if self.is_statement_start() {
                 match self.parse_statement() {
+                    // A match ending the block is its value
+                    Ok(Stmt::Match { expr, arms, span, .. }) if self.check(&TokenType::RightBrace) => {
+                        trailing_expr = Some(Expr::Match { expr: Box::new(expr), arms, span, id: NodeId::DUMMY });
+                        break;
+                    }
                     Ok(stmt) => statements.push(stmt),
+                    Err(err @ ParseError::NestingTooDeep { .. }) => return Err(err),
                     Err(err) => {
                         self.add_error(err);
                         self.synchronize();
@@ -1243,13 +1576,9 @@ impl<'a> Parser<'a> {
                     trailing_expr = Some(expr);
                     break;
                 } else {
-                    // It's an expression statement
-                    self.expect(TokenType::Semicolon, "expression statement")?;
-                    let end_pos = self.current_position();
-                    statements.push(Stmt::Expression {
-                        expr,
-                        span: Span::new(expr_start, end_pos),
-                    });
+                    // It's an expression statement or an assignment
+                    let stmt = self.parse_expression_statement_tail(expr_start, expr)?;
+                    statements.push(stmt);
                 }
             }
         }
@@ -1261,6 +1590,7 @@ impl<'a> Parser<'a> {
             statements,
             trailing_expr: trailing_expr.map(Box::new),
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -1295,6 +1625,7 @@ impl<'a> Parser<'a> {
                         name: self.interner.intern("self"),
                         is_mutable: false,
                         span: Span::new(start_pos, self.current_position()),
+                        id: NodeId::DUMMY,
                     };
                     // Type will be inferred as the struct type during semantic analysis
                     let self_type = Type::Path {
@@ -1302,6 +1633,7 @@ impl<'a> Parser<'a> {
                         generics: Vec::new(),
                         memory_strategy: MemoryStrategy::Inferred,
                         span: Span::new(start_pos, self.current_position()),
+                        id: NodeId::DUMMY,
                     };
                     Ok((pattern, Some(self_type)))
                 }
@@ -1319,6 +1651,7 @@ impl<'a> Parser<'a> {
                                     name: self.interner.intern("self"),
                                     is_mutable: false, // The reference itself is not mutable
                                     span: Span::new(start_pos, self.current_position()),
+                                    id: NodeId::DUMMY,
                                 };
                                 // Create reference type to Self
                                 let self_type = Type::Reference {
@@ -1328,16 +1661,18 @@ impl<'a> Parser<'a> {
                                         generics: Vec::new(),
                                         memory_strategy: MemoryStrategy::Inferred,
                                         span: Span::new(start_pos, self.current_position()),
+                                        id: NodeId::DUMMY,
                                     }),
                                     lifetime: None,
                                     ownership: crate::ast::Ownership::Borrowed,
                                     span: Span::new(start_pos, self.current_position()),
+                                    id: NodeId::DUMMY,
                                 };
                                 Ok((pattern, Some(self_type)))
                             } else {
                                 Err(ParseError::UnexpectedToken {
                                     expected: vec![ExpectedToken::new("self", "self parameter")],
-                                    found: token.token_type.clone(),
+                                    found: Box::new(token.token_type.clone().into_owned()),
                                     position: token.position,
                                     context: self.current_context().clone(),
                                     suggestions: vec![
@@ -1351,7 +1686,7 @@ impl<'a> Parser<'a> {
                         } else {
                             Err(ParseError::UnexpectedToken {
                                 expected: vec![ExpectedToken::new("self", "self parameter")],
-                                found: token.token_type.clone(),
+                                found: Box::new(token.token_type.clone().into_owned()),
                                 position: token.position,
                                 context: self.current_context().clone(),
                                 suggestions: vec![
@@ -1378,7 +1713,7 @@ impl<'a> Parser<'a> {
                 }
                 _ => Err(ParseError::UnexpectedToken {
                     expected: vec![ExpectedToken::new("self parameter", "self, &self, or &mut self")],
-                    found: token.token_type.clone(),
+                    found: Box::new(token.token_type.clone().into_owned()),
                     position: token.position,
                     context: self.current_context().clone(),
                     suggestions: vec![
@@ -13,15 +13,16 @@
 //! - Range patterns (1..10)
 
 use crate::lexer::{TokenType, position::Position};
-use crate::ast::{Pattern, Literal, Span, InternedString, FieldPattern};
+use crate::ast::{Pattern, Literal, Span, InternedString, FieldPattern, NodeId};
 use super::parser::Parser;
+use super::expressions::number_literal;
 use super::error::{ParseError, ParseResult, ParseContext, ExpectedToken, Suggestion, SuggestionCategory};
 
 impl<'a> Parser<'a> {
     /// Parse a pattern according to EBNF grammar
     /// Pattern ::= OrPattern
     pub fn parse_pattern(&mut self) -> ParseResult<Pattern> {
-        self.parse_or_pattern()
+        self.nested(Self::parse_or_pattern)
     }
     
     /// Parse an or-pattern: pattern | pattern | ...
@@ -41,25 +42,31 @@ impl<'a> Parser<'a> {
             Ok(Pattern::Or {
                 patterns,
                 span: Span::new(start_pos, end_pos),
+                id: NodeId::DUMMY,
             })
         }
     }
     
-    /// Parse a range pattern: 1..10, 'a'..'z', etc.
-    /// RangePattern ::= PrimaryPattern [ ".." PrimaryPattern ]
-    fn parse_range_pattern(&mut self) -> ParseResult<Pattern> {
+    /// Parse a range pattern: 1..10, 'a'..='z', etc.
+    /// RangePattern ::= PrimaryPattern [ ( ".." | "..=" ) PrimaryPattern ]
+    ///
+    /// Also used for closure parameters, where `|` closes the parameter list.
+    pub(crate) fn parse_range_pattern(&mut self) -> ParseResult<Pattern> {
         let start_pos = self.current_position();
         let start_pattern = self.parse_primary_pattern()?;
         
         if self.match_token(&TokenType::DotDot) {
+            // `..=` lexes as `..` followed by `=`, as in range expressions
+            let inclusive = self.match_token(&TokenType::Equal);
             let end_pattern = self.parse_primary_pattern()?;
             let span_end = self.current_position();
             
             Ok(Pattern::Range {
                 start: Some(Box::new(start_pattern)),
                 end: Some(Box::new(end_pattern)),
-                inclusive: false, // .. is exclusive, ..= would be inclusive
+                inclusive,
                 span: Span::new(start_pos, span_end),
+                id: NodeId::DUMMY,
             })
         } else {
             Ok(start_pattern)
@@ -80,6 +87,7 @@ impl<'a> Parser<'a> {
                     let end_pos = self.current_position();
                     Ok(Pattern::Wildcard {
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -91,9 +99,9 @@ impl<'a> Parser<'a> {
                     // Check if this is a struct pattern or enum pattern
                     if self.check(&TokenType::LeftBrace) {
                         // Struct pattern: Identifier { fields }
-                        self.parse_struct_pattern_body(identifier, start_pos)
-                    } else if self.check(&TokenType::DoubleColon) {
-                        // Path pattern: Module::Identifier or enum variant
+                        self.parse_struct_pattern_body(vec![identifier], start_pos)
+                    } else if self.check(&TokenType::DoubleColon) || self.check(&TokenType::LeftParen) {
+                        // Path pattern: Module::Identifier or enum variant, `Some(x)` included
                         self.parse_path_pattern(identifier, start_pos)
                     } else {
                         // Simple identifier pattern
@@ -102,35 +110,20 @@ impl<'a> Parser<'a> {
                             name: identifier,
                             is_mutable: false, // Default to immutable, mut would be handled by let statement
                             span: Span::new(start_pos, end_pos),
+                            id: NodeId::DUMMY,
                         })
                     }
                 }
                 
                 // Literal patterns
-                TokenType::Integer { value, base, .. } => {
-                    let literal = Literal::Integer {
-                        value: value.clone(),
-                        base: *base,
-                        suffix: None,
-                    };
-                    self.advance()?;
-                    let end_pos = self.current_position();
-                    Ok(Pattern::Literal {
-                        literal,
-                        span: Span::new(start_pos, end_pos),
-                    })
-                }
-                
-                TokenType::Float { value, .. } => {
-                    let literal = Literal::Float {
-                        value: value.clone(),
-                        suffix: None,
-                    };
+                TokenType::Integer { .. } | TokenType::Float { .. } => {
+                    let literal = number_literal(&token.token_type, start_pos, ParseContext::Pattern)?;
                     self.advance()?;
                     let end_pos = self.current_position();
                     Ok(Pattern::Literal {
                         literal,
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -145,6 +138,7 @@ impl<'a> Parser<'a> {
                     Ok(Pattern::Literal {
                         literal,
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -155,6 +149,7 @@ impl<'a> Parser<'a> {
                     Ok(Pattern::Literal {
                         literal,
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -165,6 +160,7 @@ impl<'a> Parser<'a> {
                     Ok(Pattern::Literal {
                         literal,
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -175,6 +171,7 @@ impl<'a> Parser<'a> {
                     Ok(Pattern::Literal {
                         literal,
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -185,6 +182,7 @@ impl<'a> Parser<'a> {
                     Ok(Pattern::Literal {
                         literal,
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -199,6 +197,7 @@ impl<'a> Parser<'a> {
                         pattern,
                         is_mutable,
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -215,7 +214,7 @@ impl<'a> Parser<'a> {
                 _ => {
                     Err(ParseError::UnexpectedToken {
                         expected: vec![ExpectedToken::new("pattern", "wildcard, literal, identifier, or structured pattern")],
-                        found: token.token_type.clone(),
+                        found: Box::new(token.token_type.clone().into_owned()),
                         position: start_pos,
                         context: ParseContext::Pattern,
                         suggestions: vec![
@@ -241,7 +240,7 @@ impl<'a> Parser<'a> {
     }
     
     /// Parse a path pattern (enum variants, module paths)
-    /// PathPattern ::= identifier { "::" identifier } [ "(" PatternList? ")" ]
+    /// PathPattern ::= identifier { "::" identifier } [ "(" PatternList? ")" | StructPatternBody ]
     fn parse_path_pattern(&mut self, first_segment: InternedString, start_pos: Position) -> ParseResult<Pattern> {
         let mut segments = vec![first_segment];
         
@@ -254,7 +253,7 @@ impl<'a> Parser<'a> {
                 } else {
                     return Err(ParseError::UnexpectedToken {
                         expected: vec![ExpectedToken::new("identifier", "path segment for pattern matching")],
-                        found: token.token_type.clone(),
+                        found: Box::new(token.token_type.clone().into_owned()),
                         position: self.current_position(),
                         context: ParseContext::Pattern,
                         suggestions: vec![
@@ -278,6 +277,11 @@ impl<'a> Parser<'a> {
             }
         }
         
+        // Enum variant with named fields: `Shape::Circle { radius }`
+        if self.check(&TokenType::LeftBrace) {
+            return self.parse_struct_pattern_body(segments, start_pos);
+        }
+        
         // Check for enum variant with tuple data
         if self.check(&TokenType::LeftParen) {
             self.advance()?; // consume '('
@@ -302,6 +306,7 @@ impl<'a> Parser<'a> {
                 path: segments,
                 patterns: Some(patterns),
                 span: Span::new(start_pos, end_pos),
+                id: NodeId::DUMMY,
             })
         } else {
             // Simple path or unit enum variant
@@ -310,12 +315,15 @@ impl<'a> Parser<'a> {
                 path: segments,
                 patterns: None,
                 span: Span::new(start_pos, end_pos),
+                id: NodeId::DUMMY,
             })
         }
     }
     
     /// Parse struct pattern body: { field: pattern, field, .. }
-    fn parse_struct_pattern_body(&mut self, struct_name: InternedString, start_pos: Position) -> ParseResult<Pattern> {
+    ///
+    /// `path` names the struct, or the enum variant with named fields.
+    fn parse_struct_pattern_body(&mut self, path: Vec<InternedString>, start_pos: Position) -> ParseResult<Pattern> {
         self.expect(TokenType::LeftBrace, "struct pattern")?;
         
         let mut fields = Vec::new();
@@ -361,7 +369,7 @@ impl<'a> Parser<'a> {
                 } else {
                     return Err(ParseError::UnexpectedToken {
                         expected: vec![ExpectedToken::new("field name or ..", "identifier for field pattern or rest pattern")],
-                        found: token.token_type.clone(),
+                        found: Box::new(token.token_type.clone().into_owned()),
                         position: self.current_position(),
                         context: ParseContext::Pattern,
                         suggestions: vec![
@@ -389,23 +397,28 @@ impl<'a> Parser<'a> {
         let end_pos = self.current_position();
         
         Ok(Pattern::Struct {
-            path: vec![struct_name],
+            path,
             fields,
             rest: has_rest,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
-    /// Parse tuple pattern: (pattern, pattern, ...)
+    /// Parse tuple pattern: (pattern, pattern, ...), (pattern,) or ()
+    ///
+    /// A single pattern without a trailing comma, `(pattern)`, only groups that pattern.
     fn parse_tuple_pattern(&mut self, start_pos: Position) -> ParseResult<Pattern> {
         self.expect(TokenType::LeftParen, "tuple pattern")?;
         
         let mut patterns = Vec::new();
+        let mut is_tuple = self.check(&TokenType::RightParen);
         
-        if !self.check(&TokenType::RightParen) {
+        if !is_tuple {
             patterns.push(self.parse_pattern()?);
             
             while self.match_token(&TokenType::Comma) {
+                is_tuple = true;
                 if self.check(&TokenType::RightParen) {
                     break; // trailing comma
                 }
@@ -416,9 +429,14 @@ impl<'a> Parser<'a> {
         self.expect(TokenType::RightParen, "tuple pattern")?;
         let end_pos = self.current_position();
         
+        if !is_tuple {
+            return Ok(patterns.remove(0));
+        }
+        
         Ok(Pattern::Tuple {
             patterns,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -445,6 +463,7 @@ impl<'a> Parser<'a> {
         Ok(Pattern::Array {
             patterns,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
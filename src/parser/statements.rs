@@ -7,15 +7,28 @@
 //! - Control flow (if, while, for, loop, match)
 //! - Break/continue/return statements
 //! - Block statements
+//! - Nested function declarations
 
-use crate::lexer::{TokenType, Token};
-use crate::ast::{Stmt, Span, BinaryOp, MatchArm};
+use crate::lexer::{TokenType, Token, Position};
+use crate::ast::{Stmt, Expr, Pattern, Span, BinaryOp, MatchArm, NodeId};
 use super::parser::Parser;
 use super::error::{ParseError, ParseResult, ParseContext, ExpectedToken, Suggestion, SuggestionCategory};
 
 impl<'a> Parser<'a> {
     /// Parse a statement
     pub fn parse_statement(&mut self) -> ParseResult<Stmt> {
+        self.nested(Self::parse_statement_inner)
+    }
+    
+    fn parse_statement_inner(&mut self) -> ParseResult<Stmt> {
+        if self.is_region_block_start() {
+            let start_pos = self.current_position();
+            return self.parse_region_statement(start_pos, None);
+        }
+        if self.is_labelled_loop_start() {
+            return self.parse_loop_statement();
+        }
+        
         if let Some(token) = &self.current_token {
             match &token.token_type {
                 TokenType::Let => self.parse_let_statement(),
@@ -28,76 +41,14 @@ impl<'a> Parser<'a> {
                 TokenType::Continue => self.parse_continue_statement(),
                 TokenType::Return => self.parse_return_statement(),
                 TokenType::LeftBrace => self.parse_block_statement(),
+                TokenType::At => self.parse_annotated_region_statement(),
+                TokenType::Fn => self.parse_item_statement(),
                 _ => {
                     // Try to parse as expression statement or assignment
                     let start_pos = self.current_position();
                     let expr = self.parse_expression()?;
                     
-                    // Check if this is an assignment
-                    if let Some(token) = &self.current_token {
-                        match &token.token_type {
-                            TokenType::Equal => {
-                                self.advance()?;
-                                let value = self.parse_expression()?;
-                                self.expect(TokenType::Semicolon, "assignment statement")?;
-                                let end_pos = self.current_position();
-                                Ok(Stmt::Assignment {
-                                    target: expr,
-                                    value,
-                                    span: Span::new(start_pos, end_pos),
-                                })
-                            }
-                            TokenType::PlusEq | TokenType::MinusEq | TokenType::StarEq |
-                            TokenType::SlashEq | TokenType::PercentEq | TokenType::AndEq |
-                            TokenType::OrEq | TokenType::CaretEq | TokenType::LeftShiftEq |
-                            TokenType::RightShiftEq => {
-                                let op = match &token.token_type {
-                                    TokenType::PlusEq => BinaryOp::Add,
-                                    TokenType::MinusEq => BinaryOp::Subtract,
-                                    TokenType::StarEq => BinaryOp::Multiply,
-                                    TokenType::SlashEq => BinaryOp::Divide,
-                                    TokenType::PercentEq => BinaryOp::Modulo,
-                                    TokenType::AndEq => BinaryOp::BitwiseAnd,
-                                    TokenType::OrEq => BinaryOp::BitwiseOr,
-                                    TokenType::CaretEq => BinaryOp::BitwiseXor,
-                                    TokenType::LeftShiftEq => BinaryOp::LeftShift,
-                                    TokenType::RightShiftEq => BinaryOp::RightShift,
-                                    _ => unreachable!(),
-                                };
-                                self.advance()?;
-                                let value = self.parse_expression()?;
-                                self.expect(TokenType::Semicolon, "compound assignment")?;
-                                let end_pos = self.current_position();
-                                Ok(Stmt::CompoundAssignment {
-                                    target: expr,
-                                    op,
-                                    value,
-                                    span: Span::new(start_pos, end_pos),
-                                })
-                            }
-                            _ => {
-                                // Regular expression statement
-                                self.expect(TokenType::Semicolon, "expression statement")?;
-                                let end_pos = self.current_position();
-                                Ok(Stmt::Expression {
-                                    expr,
-                                    span: Span::new(start_pos, end_pos),
-                                })
-                            }
-                        }
-                    } else {
-                        Err(ParseError::UnexpectedEof {
-                            expected: vec![ExpectedToken::new("semicolon or assignment operator", "';' or '=', '+=', etc.")],
-                            position: self.current_position(),
-                            context: ParseContext::Statement,
-                            unclosed_delimiters: Vec::new(),
-                            suggestions: vec![
-                                Suggestion::new("Add semicolon to end statement", self.current_position())
-                                    .with_replacement(";")
-                                    .with_category(SuggestionCategory::Syntax)
-                            ],
-                        })
-                    }
+                    self.parse_expression_statement_tail(start_pos, expr)
                 }
             }
         } else {
@@ -114,6 +65,79 @@ impl<'a> Parser<'a> {
         }
     }
     
+    /// Finish a statement that starts with an already-parsed expression:
+    /// an assignment, a compound assignment, or an expression statement
+    pub(crate) fn parse_expression_statement_tail(&mut self, start_pos: Position, expr: Expr) -> ParseResult<Stmt> {
+        // Check if this is an assignment
+        if let Some(token) = &self.current_token {
+            match &token.token_type {
+                TokenType::Equal => {
+                    self.advance()?;
+                    let value = self.parse_expression()?;
+                    self.expect(TokenType::Semicolon, "assignment statement")?;
+                    let end_pos = self.current_position();
+                    Ok(Stmt::Assignment {
+                        target: expr,
+                        value,
+                        span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
+                    })
+                }
+                TokenType::PlusEq | TokenType::MinusEq | TokenType::StarEq |
+                TokenType::SlashEq | TokenType::PercentEq | TokenType::AndEq |
+                TokenType::OrEq | TokenType::CaretEq | TokenType::LeftShiftEq |
+                TokenType::RightShiftEq => {
+                    let op = match &token.token_type {
+                        TokenType::PlusEq => BinaryOp::Add,
+                        TokenType::MinusEq => BinaryOp::Subtract,
+                        TokenType::StarEq => BinaryOp::Multiply,
+                        TokenType::SlashEq => BinaryOp::Divide,
+                        TokenType::PercentEq => BinaryOp::Modulo,
+                        TokenType::AndEq => BinaryOp::BitwiseAnd,
+                        TokenType::OrEq => BinaryOp::BitwiseOr,
+                        TokenType::CaretEq => BinaryOp::BitwiseXor,
+                        TokenType::LeftShiftEq => BinaryOp::LeftShift,
+                        TokenType::RightShiftEq => BinaryOp::RightShift,
+                        _ => unreachable!(),
+                    };
+                    self.advance()?;
+                    let value = self.parse_expression()?;
+                    self.expect(TokenType::Semicolon, "compound assignment")?;
+                    let end_pos = self.current_position();
+                    Ok(Stmt::CompoundAssignment {
+                        target: expr,
+                        op,
+                        value,
+                        span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
+                    })
+                }
+                _ => {
+                    // Regular expression statement
+                    self.expect(TokenType::Semicolon, "expression statement")?;
+                    let end_pos = self.current_position();
+                    Ok(Stmt::Expression {
+                        expr,
+                        span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
+                    })
+                }
+            }
+        } else {
+            Err(ParseError::UnexpectedEof {
+                expected: vec![ExpectedToken::new("semicolon or assignment operator", "';' or '=', '+=', etc.")],
+                position: self.current_position(),
+                context: ParseContext::Statement,
+                unclosed_delimiters: Vec::new(),
+                suggestions: vec![
+                    Suggestion::new("Add semicolon to end statement", self.current_position())
+                        .with_replacement(";")
+                        .with_category(SuggestionCategory::Syntax)
+                ],
+            })
+        }
+    }
+    
     /// Parse a let statement: let [mut] pattern [: type] [= expr];
     fn parse_let_statement(&mut self) -> ParseResult<Stmt> {
         let start_pos = self.current_position();
@@ -148,21 +172,34 @@ impl<'a> Parser<'a> {
             initializer,
             is_mutable,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
     /// Parse an if statement: if expr block [else (if_stmt | block)]
     fn parse_if_statement(&mut self) -> ParseResult<Stmt> {
+        let before = self.start_chain();
+        let statement = self.parse_if_chain();
+        self.end_chain(before);
+        statement
+    }
+    
+    /// Parse an `if` and its `else if`s as one chain, a link per `else if`
+    fn parse_if_chain(&mut self) -> ParseResult<Stmt> {
         let start_pos = self.current_position();
         self.expect(TokenType::If, "if statement")?;
+        if self.check(&TokenType::Let) {
+            return self.parse_if_let_statement(start_pos);
+        }
         
-        let condition = self.parse_expression()?;
+        let condition = self.parse_condition_expression()?;
         let then_block = self.parse_block_statement_inner()?;
         
         let else_block = if self.match_token(&TokenType::Else) {
             if self.check(&TokenType::If) {
                 // else if
-                Some(Box::new(self.parse_if_statement()?))
+                self.chain_link()?;
+                Some(Box::new(self.parse_if_chain()?))
             } else {
                 // else block
                 Some(Box::new(self.parse_block_statement()?))
@@ -177,15 +214,70 @@ impl<'a> Parser<'a> {
             then_block,
             else_block,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
+    /// Parse the rest of `if let pattern = expr block [else (if_stmt | block)]` as
+    /// `match expr { pattern => block, _ => else }`, so only the block sees the bindings
+    fn parse_if_let_statement(&mut self, start_pos: Position) -> ParseResult<Stmt> {
+        let (pattern, expr) = self.parse_let_condition()?;
+        let then_block = self.parse_block_expression()?;
+        
+        let else_block = if self.match_token(&TokenType::Else) {
+            if self.check(&TokenType::If) {
+                self.chain_link()?;
+                let else_if = self.parse_if_chain()?;
+                let span = else_if.span();
+                Some(Expr::Block { statements: vec![else_if], trailing_expr: None, span, id: NodeId::DUMMY })
+            } else {
+                Some(self.parse_block_expression()?)
+            }
+        } else {
+            None
+        };
+        
+        let end_pos = self.current_position();
+        Ok(Stmt::Match {
+            expr,
+            arms: single_pattern_arms(pattern, then_block, else_block),
+            span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
+        })
+    }
+    
+    /// Parse `let pattern = expr`, the condition of an `if let` or `while let`
+    pub(super) fn parse_let_condition(&mut self) -> ParseResult<(Pattern, Expr)> {
+        self.expect(TokenType::Let, "let condition")?;
+        let pattern = self.parse_pattern()?;
+        self.expect(TokenType::Equal, "let condition")?;
+        let expr = self.parse_condition_expression()?;
+        Ok((pattern, expr))
+    }
+    
     /// Parse a while statement: while expr block
+    ///
+    /// `while let pattern = expr block` becomes `loop { match expr { pattern => block, _ => break } }`,
+    /// which matches a freshly evaluated `expr` every iteration.
     fn parse_while_statement(&mut self) -> ParseResult<Stmt> {
         let start_pos = self.current_position();
         self.expect(TokenType::While, "while statement")?;
         
-        let condition = self.parse_expression()?;
+        if self.check(&TokenType::Let) {
+            let (pattern, expr) = self.parse_let_condition()?;
+            let body = self.parse_block_expression()?;
+            let span = Span::new(start_pos, self.current_position());
+            let exit = Expr::Block {
+                statements: vec![Stmt::Break { label: None, expr: None, span, id: NodeId::DUMMY }],
+                trailing_expr: None,
+                span,
+                id: NodeId::DUMMY,
+            };
+            let matched = Stmt::Match { expr, arms: single_pattern_arms(pattern, body, Some(exit)), span, id: NodeId::DUMMY };
+            return Ok(Stmt::Loop { label: None, body: vec![matched], span, id: NodeId::DUMMY });
+        }
+        
+        let condition = self.parse_condition_expression()?;
         let body = self.parse_block_statement_inner()?;
         
         let end_pos = self.current_position();
@@ -193,6 +285,7 @@ impl<'a> Parser<'a> {
             condition,
             body,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -203,7 +296,7 @@ impl<'a> Parser<'a> {
         
         let pattern = self.parse_pattern()?;
         self.expect(TokenType::In, "for statement")?;
-        let iterable = self.parse_expression()?;
+        let iterable = self.parse_condition_expression()?;
         let body = self.parse_block_statement_inner()?;
         
         let end_pos = self.current_position();
@@ -212,6 +305,7 @@ impl<'a> Parser<'a> {
             iterable,
             body,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -220,40 +314,57 @@ impl<'a> Parser<'a> {
         let start_pos = self.current_position();
         
         // Check for optional label
-        let label = if let Some(token) = &self.current_token {
-            if let TokenType::Identifier(name) = &token.token_type {
-                if self.peek().map(|t| matches!(t.token_type, TokenType::Colon)).unwrap_or(false) {
-                    let label_name = self.interner.intern(name);
-                    self.advance()?; // consume identifier
-                    self.advance()?; // consume colon
-                    Some(label_name)
-                } else {
-                    None
-                }
-            } else {
-                None
+        let label_name = match &self.current_token {
+            Some(Token { token_type: TokenType::Identifier(name), .. }) => Some(name.clone()),
+            _ => None,
+        };
+        let label = match label_name {
+            Some(name) if self.peek_token().map(|t| matches!(t.token_type, TokenType::Colon)).unwrap_or(false) => {
+                let label_name = self.interner.intern(&name);
+                self.advance()?; // consume identifier
+                self.advance()?; // consume colon
+                Some(label_name)
             }
-        } else {
-            None
+            _ => None,
         };
         
         self.expect(TokenType::Loop, "loop statement")?;
-        let body = self.parse_block_statement_inner()?;
+        self.loop_labels.extend(label);
+        let body = self.parse_block_statement_inner();
+        if label.is_some() {
+            self.loop_labels.pop();
+        }
+        let body = body?;
         
         let end_pos = self.current_position();
         Ok(Stmt::Loop {
             label,
             body,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
     /// Parse a match statement: match expr { arms }
     fn parse_match_statement(&mut self) -> ParseResult<Stmt> {
         let start_pos = self.current_position();
+        let (expr, arms) = self.parse_match()?;
+        let end_pos = self.current_position();
+        
+        Ok(Stmt::Match {
+            expr,
+            arms,
+            span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
+        })
+    }
+    
+    /// Parse `match expr { arms }` into the scrutinee and the arms, for match statements
+    /// and match expressions alike
+    pub(super) fn parse_match(&mut self) -> ParseResult<(Expr, Vec<MatchArm>)> {
         self.expect(TokenType::Match, "match statement")?;
         
-        let expr = self.parse_expression()?;
+        let expr = self.parse_condition_expression()?;
         self.expect(TokenType::LeftBrace, "match arms")?;
         
         let mut arms = Vec::new();
@@ -293,32 +404,28 @@ impl<'a> Parser<'a> {
         }
         
         self.expect(TokenType::RightBrace, "match statement")?;
-        let end_pos = self.current_position();
-        
-        Ok(Stmt::Match {
-            expr,
-            arms,
-            span: Span::new(start_pos, end_pos),
-        })
+        Ok((expr, arms))
     }
     
     /// Parse a break statement: break [label] [expr];
+    ///
+    /// A name after `break` is a label only if it labels an enclosing loop, so
+    /// `break total;` breaks with the value of `total`.
     fn parse_break_statement(&mut self) -> ParseResult<Stmt> {
         let start_pos = self.current_position();
         self.expect(TokenType::Break, "break statement")?;
         
         // Check for optional label
-        let label = if let Some(token) = &self.current_token {
-            if let TokenType::Identifier(name) = &token.token_type {
+        let label = match &self.current_token {
+            Some(Token { token_type: TokenType::Identifier(name), .. }) => {
                 let label_name = self.interner.intern(name);
-                self.advance()?;
-                Some(label_name)
-            } else {
-                None
+                self.loop_labels.contains(&label_name).then_some(label_name)
             }
-        } else {
-            None
+            _ => None,
         };
+        if label.is_some() {
+            self.advance()?;
+        }
         
         // Check for optional expression (break value)
         let expr = if !self.check(&TokenType::Semicolon) {
@@ -334,6 +441,7 @@ impl<'a> Parser<'a> {
             label,
             expr,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -361,6 +469,7 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Continue {
             label,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -382,6 +491,20 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Return {
             expr,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
+        })
+    }
+    
+    /// Parse a function declared inside a block: fn name(...) { ... }
+    fn parse_item_statement(&mut self) -> ParseResult<Stmt> {
+        let start_pos = self.current_position();
+        let item = self.parse_item()?;
+        let end_pos = self.current_position();
+        
+        Ok(Stmt::Item {
+            item,
+            span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -394,6 +517,7 @@ impl<'a> Parser<'a> {
         Ok(Stmt::Block {
             statements,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -406,6 +530,7 @@ impl<'a> Parser<'a> {
         while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
             match self.parse_statement() {
                 Ok(stmt) => statements.push(stmt),
+                Err(err @ ParseError::NestingTooDeep { .. }) => return Err(err),
                 Err(err) => {
                     self.add_error(err);
                     self.synchronize();
@@ -418,24 +543,83 @@ impl<'a> Parser<'a> {
     }
     
     /// Check if the current token can start a statement
-    pub fn is_statement_start(&self) -> bool {
+    pub fn is_statement_start(&mut self) -> bool {
+        if self.is_region_block_start() || self.is_labelled_loop_start() {
+            return true;
+        }
+        
         if let Some(token) = &self.current_token {
             matches!(token.token_type, 
                 TokenType::Let | TokenType::If | TokenType::While | 
                 TokenType::For | TokenType::Loop | TokenType::Match |
                 TokenType::Break | TokenType::Continue | TokenType::Return |
-                TokenType::LeftBrace
+                TokenType::LeftBrace | TokenType::At | TokenType::Fn
             )
         } else {
             false
         }
     }
     
-    /// Get the current token for lookahead (helper method)
-    fn peek(&self) -> Option<&Token> {
-        // This is a simplified peek - in a real implementation, 
-        // we'd need to look ahead in the token stream
-        // For now, we'll return None as a placeholder
-        None
+    /// Check for `region <name> {` - `region` is a contextual keyword, so an
+    /// identifier named `region` only starts a block when a name follows it
+    fn is_region_block_start(&mut self) -> bool {
+        let is_region = matches!(
+            &self.current_token,
+            Some(Token { token_type: TokenType::Identifier(name), .. }) if name == "region"
+        );
+        is_region && self.peek_token()
+            .map(|t| matches!(t.token_type, TokenType::Identifier(_) | TokenType::String { .. }))
+            .unwrap_or(false)
+    }
+    
+    /// Check for `<label>: loop`, where the label is an identifier followed by a colon
+    fn is_labelled_loop_start(&mut self) -> bool {
+        matches!(&self.current_token, Some(Token { token_type: TokenType::Identifier(_), .. }))
+            && self.peek_token().is_some_and(|t| t.token_type == TokenType::Colon)
+    }
+    
+    /// Parse a region block statement: region name { statements... }
+    fn parse_region_statement(&mut self, start_pos: Position, size_hint: Option<u64>) -> ParseResult<Stmt> {
+        let region = self.parse_region_block()?;
+        let end_pos = self.current_position();
+        
+        Ok(Stmt::Region {
+            name: region.name,
+            size_hint,
+            body: region.body,
+            span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
+        })
+    }
+    
+    /// Parse an annotated region block: @memory(size_hint = N) region name { ... }
+    fn parse_annotated_region_statement(&mut self) -> ParseResult<Stmt> {
+        let start_pos = self.current_position();
+        let annotation = self.parse_memory_annotation()?;
+        
+        if !self.is_region_block_start() {
+            return Err(ParseError::invalid_syntax(
+                "Expected a region block after @memory annotation",
+                self.current_position(),
+                ParseContext::Statement,
+            ));
+        }
+        
+        self.parse_region_statement(start_pos, annotation.size_hint)
     }
 } 
+
+/// Arms of the match an `if let` or `while let` stands for: `pattern => then_block`, then
+/// `_ => else_block`, or `_ => {}` without an else
+pub(super) fn single_pattern_arms(pattern: Pattern, then_block: Expr, else_block: Option<Expr>) -> Vec<MatchArm> {
+    let then_span = Span::new(pattern.span().start, then_block.span().end);
+    let else_block = else_block.unwrap_or_else(|| {
+        let end = then_block.span().end;
+        Expr::Block { statements: Vec::new(), trailing_expr: None, span: Span::new(end, end), id: NodeId::DUMMY }
+    });
+    let else_span = else_block.span();
+    vec![
+        MatchArm { pattern, guard: None, body: then_block, span: then_span },
+        MatchArm { pattern: Pattern::Wildcard { span: else_span, id: NodeId::DUMMY }, guard: None, body: else_block, span: else_span },
+    ]
+}
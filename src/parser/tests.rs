@@ -2,7 +2,7 @@
 
 #[cfg(test)]
 mod tests {
-    use super::super::{Parser, ParseResult};
+    use super::super::{Parser, ParseError, ParseResult};
     use crate::ast::*;
 
     /// Helper function to create a parser and parse a module
@@ -132,6 +132,117 @@ mod tests {
         }
     }
 
+    /// Render an expression with every binary operation parenthesized, to check grouping
+    fn grouping(expr: &Expr, parser: &Parser) -> String {
+        match expr {
+            Expr::Binary { left, op, right, .. } => {
+                let op = match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Subtract => "-",
+                    BinaryOp::Multiply => "*",
+                    BinaryOp::Divide => "/",
+                    BinaryOp::Modulo => "%",
+                    BinaryOp::LeftShift => "<<",
+                    BinaryOp::RightShift => ">>",
+                    BinaryOp::Equal => "==",
+                    BinaryOp::NotEqual => "!=",
+                    BinaryOp::Less => "<",
+                    BinaryOp::LessEqual => "<=",
+                    BinaryOp::Greater => ">",
+                    BinaryOp::GreaterEqual => ">=",
+                    BinaryOp::LogicalAnd => "&&",
+                    BinaryOp::LogicalOr => "||",
+                    other => panic!("Unexpected operator {:?}", other),
+                };
+                format!("({} {} {})", grouping(left, parser), op, grouping(right, parser))
+            }
+            Expr::Identifier { name, .. } => parser.interner().resolve(*name).unwrap(),
+            Expr::Literal { literal: Literal::Integer { value, .. }, .. } => value.clone(),
+            other => panic!("Unexpected expression {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_number_literal_suffixes() {
+        let literal = |input: &str| match parse_expression(input) {
+            Ok(Expr::Literal { literal, .. }) => literal,
+            other => panic!("Expected a literal for {}, got {:?}", input, other),
+        };
+        
+        assert!(matches!(literal("255u8"), Literal::Integer { suffix: Some(PrimitiveType::U8), .. }));
+        assert!(matches!(literal("10i64"), Literal::Integer { suffix: Some(PrimitiveType::I64), .. }));
+        assert!(matches!(literal("0xFFusize"), Literal::Integer { suffix: Some(PrimitiveType::USize), .. }));
+        assert!(matches!(literal("42"), Literal::Integer { suffix: None, .. }));
+        assert!(matches!(literal("2.5f32"), Literal::Float { suffix: Some(PrimitiveType::F32), .. }));
+        // A float suffix on a decimal integer makes a float
+        assert_eq!(literal("1f64"), Literal::Float { value: "1".to_string(), suffix: Some(PrimitiveType::F64) });
+        
+        for invalid in ["5foo", "1.5u8", "7bool", "0b1f32"] {
+            match parse_expression(invalid) {
+                Err(ParseError::InvalidSyntax { position, .. }) => assert_eq!(position.column, 1, "{}", invalid),
+                other => panic!("Expected invalid suffix error for {}, got {:?}", invalid, other),
+            }
+        }
+        
+        // Patterns keep the suffix too
+        let mut parser = Parser::new("255u8", 0).unwrap();
+        assert!(matches!(
+            parser.parse_pattern(),
+            Ok(Pattern::Literal { literal: Literal::Integer { suffix: Some(PrimitiveType::U8), .. }, .. })
+        ));
+    }
+
+    #[test]
+    fn test_binary_precedence_table() {
+        let cases = [
+            // `* / %` bind tighter than `+ -`, each level associating to the left
+            ("a + b * c", "(a + (b * c))"),
+            ("a * b + c", "((a * b) + c)"),
+            ("a - b % c / d", "(a - ((b % c) / d))"),
+            ("a * b / c % d", "(((a * b) / c) % d)"),
+            ("a - b + c", "((a - b) + c)"),
+            // Shifts bind looser than `+ -` and tighter than comparisons
+            ("a << b + c", "(a << (b + c))"),
+            ("a - b >> c", "((a - b) >> c)"),
+            ("a << b >> c", "((a << b) >> c)"),
+            ("a < b << c", "(a < (b << c))"),
+            // Comparisons bind looser than arithmetic; equality looser than ordering
+            ("a + b < c * d", "((a + b) < (c * d))"),
+            ("a == b < c", "(a == (b < c))"),
+            ("a != b + c", "(a != (b + c))"),
+            // `&&` binds tighter than `||`, both looser than comparisons
+            ("a || b && c", "(a || (b && c))"),
+            ("a && b || c", "((a && b) || c)"),
+            ("a < b && c >= d", "((a < b) && (c >= d))"),
+            ("a || b || c", "((a || b) || c)"),
+        ];
+        for (source, expected) in cases {
+            let mut parser = Parser::new(source, 0).unwrap();
+            let expr = parser.parse_expression().unwrap();
+            assert_eq!(grouping(&expr, &parser), expected, "grouping of `{}`", source);
+        }
+    }
+
+    #[test]
+    fn test_assignment_binds_loosest() {
+        let cases = [
+            ("x = a || b && c;", None, "(a || (b && c))"),
+            ("x += a * b;", Some(BinaryOp::Add), "(a * b)"),
+            ("x <<= a + 1;", Some(BinaryOp::LeftShift), "(a + 1)"),
+            ("x %= a < b;", Some(BinaryOp::Modulo), "(a < b)"),
+        ];
+        for (source, expected_op, expected_value) in cases {
+            let mut parser = Parser::new(source, 0).unwrap();
+            let (op, value) = match parser.parse_statement().unwrap() {
+                Stmt::Assignment { value, .. } => (None, value),
+                Stmt::CompoundAssignment { op, value, .. } => (Some(op), value),
+                other => panic!("Expected assignment for `{}`, got {:?}", source, other),
+            };
+            assert_eq!(op, expected_op, "operator of `{}`", source);
+            assert_eq!(grouping(&value, &parser), expected_value, "value of `{}`", source);
+        }
+    }
+
     #[test]
     fn test_unary_expression() {
         let result = parse_expression("-42");
@@ -163,6 +274,114 @@ mod tests {
         assert!(!parser.errors().is_empty());
     }
 
+    #[test]
+    fn test_lexer_errors_do_not_end_the_parse() {
+        let input = "fn first() {} § ¤\nconst GREETING: i32 = \"open;\nfn second() -> i32 { 2 }\nfn third() {}";
+        let mut parser = Parser::new(input, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let interner = parser.interner();
+
+        let names: Vec<String> = module.items.iter().filter_map(|item| match item {
+            Item::Function { name, .. } => interner.resolve(*name),
+            _ => None,
+        }).collect();
+        assert_eq!(names, ["first", "second", "third"]);
+
+        let lexer_errors: Vec<usize> = parser.errors().iter().filter_map(|err| match err {
+            ParseError::LexerError { error, .. } => Some(error.position().line),
+            _ => None,
+        }).collect();
+        assert_eq!(lexer_errors, [1, 1, 2]);
+    }
+
+    /// Errors from parsing `fn f() { <body> } fn after() {}`, with the stack the compiler runs on
+    fn nesting_errors(body: &str, max_depth: Option<usize>) -> Vec<ParseError> {
+        let source = format!("fn f() {{ {} }}\nfn after() {{}}", body);
+        let parse = move || {
+            let mut parser = Parser::new(&source, 0).unwrap();
+            if let Some(depth) = max_depth {
+                parser = parser.with_max_nesting_depth(depth);
+            }
+            let module = parser.parse_module().unwrap();
+            let after = parser.interner().intern("after");
+            assert!(
+                module.items.iter().any(|item| matches!(item, Item::Function { name, .. } if *name == after)),
+                "the item after the nesting is parsed"
+            );
+            parser.errors().to_vec()
+        };
+        std::thread::Builder::new()
+            .stack_size(crate::compile::COMPILER_STACK_SIZE)
+            .spawn(parse)
+            .unwrap()
+            .join()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_deep_nesting_is_an_error_not_a_stack_overflow() {
+        const DEPTH: usize = 100_000;
+        let open_close = |open: &str, inner: &str, close: &str| format!("{}{}{}", open.repeat(DEPTH), inner, close.repeat(DEPTH));
+        let nesting = super::super::DEFAULT_MAX_NESTING_DEPTH;
+        let chain = super::super::DEFAULT_MAX_CHAIN_LENGTH;
+        let cases = [
+            ("parentheses", format!("let x = {};", open_close("(", "1", ")")), nesting),
+            ("unary operators", format!("let x = {}1;", "-".repeat(DEPTH)), nesting),
+            ("references", format!("let x = {}y;", "& ".repeat(DEPTH)), nesting),
+            ("arrays", format!("let x = {};", open_close("[", "", "]")), nesting),
+            ("blocks", open_close("{", "", "}"), nesting),
+            ("ternaries", format!("let x = {}c;", "a ? b : ".repeat(DEPTH)), nesting),
+            ("generic types", format!("let x: {};", open_close("Vec<", "i32", "> ")), nesting),
+            ("reference types", format!("let x: {}i32;", "& ".repeat(DEPTH)), nesting),
+            ("tuple patterns", format!("let {} = t;", open_close("(", "x", ",)")), nesting),
+            ("binary operators", format!("let x = 1{};", " + 1".repeat(DEPTH)), chain),
+            ("mixed operators", format!("let x = 1{};", " * 2 + 1 < 3 == b".repeat(DEPTH)), chain),
+            ("casts", format!("let x = 1{};", " as i64".repeat(DEPTH)), chain),
+            ("postfix operators", format!("let x = y{};", ".f()[0].g".repeat(DEPTH)), chain),
+            ("else-if chains", format!("{}{{}}", "if a {} else ".repeat(DEPTH)), chain),
+            ("chains in parentheses", format!("let x = {}1{};", "(".repeat(200), " + 1 + 1 + 1 + 1 + 1 + 1)".repeat(200)), chain),
+        ];
+
+        for (name, body, expected_limit) in cases {
+            let errors = nesting_errors(&body, None);
+            assert_eq!(errors.len(), 1, "{}: {:?}", name, errors);
+            match &errors[0] {
+                ParseError::NestingTooDeep { limit, position } => {
+                    assert_eq!(*limit, expected_limit, "{}", name);
+                    assert_eq!(position.line, 1, "{}", name);
+                }
+                other => panic!("{}: expected a nesting error, got {:?}", name, other),
+            }
+            assert_eq!(errors[0].code(), "nesting-too-deep");
+        }
+    }
+
+    #[test]
+    fn test_long_chains_are_not_nesting() {
+        let else_ifs = format!("let r = 0; if a {{}}{} else {{}}", " else if a {}".repeat(300));
+        assert!(nesting_errors(&else_ifs, None).is_empty());
+
+        let links = super::super::DEFAULT_MAX_CHAIN_LENGTH;
+        assert!(nesting_errors(&format!("let x = 1{};", " + 1".repeat(links)), None).is_empty());
+        let errors = nesting_errors(&format!("let x = 1{};", " + 1".repeat(links + 1)), None);
+        assert!(matches!(errors[..], [ParseError::NestingTooDeep { .. }]), "{:?}", errors);
+
+        // Arguments and parenthesized operands continue from the links around them, a
+        // call being a link itself
+        let calls = format!("let x = 1{};", " + f(1 + 1, (2 * 2))".repeat(links / 3));
+        assert!(nesting_errors(&calls, None).is_empty());
+    }
+
+    #[test]
+    fn test_nesting_limit_is_configurable() {
+        let nested = |depth: usize| format!("let x = {}1{};", "(".repeat(depth), ")".repeat(depth));
+        assert!(nesting_errors(&nested(200), None).is_empty());
+        assert!(nesting_errors(&nested(20), Some(32)).is_empty());
+
+        let errors = nesting_errors(&nested(40), Some(32));
+        assert!(matches!(errors[..], [ParseError::NestingTooDeep { limit: 32, .. }]), "{:?}", errors);
+    }
+
     #[test]
     fn test_let_statement() {
         let mut parser = Parser::new(
@@ -277,6 +496,40 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_if_let_and_while_let_become_matches() {
+        // `if let` is a match whose wildcard arm runs the else branch, here an `else if`
+        let mut parser = Parser::new("if let Some(x) = value { x; } else if ready { }", 0).unwrap();
+        match parser.parse_statement().unwrap() {
+            Stmt::Match { expr: Expr::Identifier { .. }, arms, .. } => {
+                assert!(matches!(&arms[0], MatchArm { pattern: Pattern::Enum { patterns: Some(_), .. }, guard: None, .. }));
+                assert!(matches!(&arms[1].pattern, Pattern::Wildcard { .. }));
+                assert!(matches!(&arms[1].body, Expr::Block { statements, .. } if matches!(statements[..], [Stmt::If { .. }])));
+            }
+            other => panic!("Expected match statement, got {:?}", other),
+        }
+        
+        // Without an else, the wildcard arm does nothing; as an expression it is a match expression
+        let mut parser = Parser::new("let y = if let Some(x) = value { x } else { 0 };", 0).unwrap();
+        match parser.parse_statement().unwrap() {
+            Stmt::Let { initializer: Some(Expr::Match { arms, .. }), .. } => assert_eq!(arms.len(), 2),
+            other => panic!("Expected match initializer, got {:?}", other),
+        }
+        
+        // `while let` matches a fresh value each iteration and breaks when the pattern fails
+        let mut parser = Parser::new("while let Some(item) = next() { use_item(item); }", 0).unwrap();
+        match parser.parse_statement().unwrap() {
+            Stmt::Loop { label: None, body, .. } => match &body[..] {
+                [Stmt::Match { expr: Expr::Call { .. }, arms, .. }] => {
+                    assert!(matches!(&arms[1].body, Expr::Block { statements, .. }
+                        if matches!(statements[..], [Stmt::Break { label: None, expr: None, .. }])));
+                }
+                other => panic!("Expected a match in the loop, got {:?}", other),
+            },
+            other => panic!("Expected loop, got {:?}", other),
+        }
+    }
+    
     #[test]
     fn test_for_statement() {
         let mut parser = Parser::new(
@@ -313,6 +566,129 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_loop_expression() {
+        let mut parser = Parser::new("let found = loop { break 5; };", 0).unwrap();
+        
+        match parser.parse_statement().unwrap() {
+            Stmt::Let { initializer: Some(Expr::Loop { label, body, .. }), .. } => {
+                assert!(label.is_none());
+                match *body {
+                    Expr::Block { statements, .. } => {
+                        assert!(matches!(statements[..], [Stmt::Break { label: None, expr: Some(_), .. }]), "{:?}", statements);
+                    }
+                    other => panic!("Expected loop body block, got {:?}", other),
+                }
+            }
+            other => panic!("Expected let with a loop initializer, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_region_statement() {
+        let mut parser = Parser::new(
+            "region temp { let x = 1; region inner { let y = 2; } }",
+            0
+        ).unwrap();
+        
+        let stmt = parser.parse_statement().unwrap();
+        match stmt {
+            Stmt::Region { size_hint, body, .. } => {
+                assert!(size_hint.is_none());
+                assert_eq!(body.len(), 2);
+                assert!(matches!(body[1], Stmt::Region { .. }));
+            }
+            _ => panic!("Expected region statement"),
+        }
+    }
+    
+    #[test]
+    fn test_annotated_region_statement() {
+        let mut parser = Parser::new(
+            "@memory(size_hint = 4096) region \"frame\" { let x = 1; }",
+            0
+        ).unwrap();
+        
+        let stmt = parser.parse_statement().unwrap();
+        match stmt {
+            Stmt::Region { size_hint, body, .. } => {
+                assert_eq!(size_hint, Some(4096));
+                assert_eq!(body.len(), 1);
+            }
+            _ => panic!("Expected region statement"),
+        }
+    }
+    
+    #[test]
+    fn test_region_identifier_is_not_keyword() {
+        let mut parser = Parser::new("region = 5;", 0).unwrap();
+        
+        let stmt = parser.parse_statement().unwrap();
+        assert!(matches!(stmt, Stmt::Assignment { .. }));
+    }
+    
+    #[test]
+    fn test_condition_is_not_struct_literal() {
+        let mut parser = Parser::new("if flag { score = 1; }", 0).unwrap();
+        
+        match parser.parse_statement().unwrap() {
+            Stmt::If { condition, then_block, .. } => {
+                assert!(matches!(condition, Expr::Identifier { .. }));
+                assert!(matches!(then_block[0], Stmt::Assignment { .. }));
+            }
+            other => panic!("Expected if statement, got {:?}", other),
+        }
+        
+        // Parenthesized struct literals are still allowed in conditions
+        let mut parser = Parser::new("while (Point { x: 1 }).x > 0 { }", 0).unwrap();
+        match parser.parse_statement().unwrap() {
+            Stmt::While { condition: Expr::Binary { left, .. }, .. } => {
+                assert!(matches!(*left, Expr::FieldAccess { .. }));
+            }
+            other => panic!("Expected while statement, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_block_expression_assignment() {
+        let mut parser = Parser::new("{ total = total + 1; total }", 0).unwrap();
+        
+        match parser.parse_block_expression().unwrap() {
+            Expr::Block { statements, trailing_expr, .. } => {
+                assert!(matches!(statements[0], Stmt::Assignment { .. }));
+                assert!(trailing_expr.is_some());
+            }
+            other => panic!("Expected block, got {:?}", other),
+        }
+        assert!(parser.errors().is_empty());
+    }
+    
+    #[test]
+    fn test_nested_function_statement() {
+        let mut parser = Parser::new("{ fn double(x: i32) -> i32 { return x * 2; } double(3) }", 0).unwrap();
+        
+        match parser.parse_block_expression().unwrap() {
+            Expr::Block { statements, trailing_expr, .. } => {
+                assert!(matches!(statements[0], Stmt::Item { item: Item::Function { .. }, .. }));
+                assert!(matches!(trailing_expr.as_deref(), Some(Expr::Call { .. })));
+            }
+            other => panic!("Expected block, got {:?}", other),
+        }
+        assert!(parser.errors().is_empty());
+    }
+    
+    #[test]
+    fn test_mutable_reference_expression() {
+        let mut parser = Parser::new("&mut total", 0).unwrap();
+        
+        match parser.parse_expression().unwrap() {
+            Expr::Unary { op: UnaryOp::MutableRef, expr, .. } => {
+                assert!(matches!(*expr, Expr::Identifier { .. }));
+            }
+            other => panic!("Expected mutable reference, got {:?}", other),
+        }
+    }
+    
     #[test]
     fn test_break_continue_return() {
         let mut parser = Parser::new(
@@ -349,6 +725,27 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_break_label_or_value() {
+        // `total` labels no enclosing loop, so it is the value broken with
+        let mut parser = Parser::new("outer: loop { loop { break outer; } break total * 2; }", 0).unwrap();
+        let Stmt::Loop { label: Some(outer), body, .. } = parser.parse_statement().unwrap() else {
+            panic!("Expected labelled loop");
+        };
+        match &body[..] {
+            [Stmt::Loop { label: None, body: inner, .. }, Stmt::Break { label: None, expr: Some(Expr::Binary { .. }), .. }] => {
+                assert!(matches!(&inner[..], [Stmt::Break { label: Some(label), expr: None, .. }] if *label == outer), "{:?}", inner);
+            }
+            other => panic!("Expected inner loop and break, got {:?}", other),
+        }
+        
+        let mut parser = Parser::new("loop { break total; }", 0).unwrap();
+        let Stmt::Loop { body, .. } = parser.parse_statement().unwrap() else {
+            panic!("Expected loop");
+        };
+        assert!(matches!(&body[..], [Stmt::Break { label: None, expr: Some(Expr::Identifier { .. }), .. }]), "{:?}", body);
+    }
+    
     #[test]
     fn test_block_statement() {
         let mut parser = Parser::new(
@@ -395,4 +792,435 @@ mod tests {
             _ => panic!("Expected expression statement"),
         }
     }
-} 
+
+    #[test]
+    fn test_cast_expression_precedence() {
+        // `as` binds tighter than comparisons and arithmetic, looser than unary
+        match parse_expression("-x as i64 * 2 < y").unwrap() {
+            Expr::Binary { op: BinaryOp::Less, left, .. } => match left.as_ref() {
+                Expr::Binary { op: BinaryOp::Multiply, left, .. } => match left.as_ref() {
+                    Expr::Cast { expr, target_type: Type::Primitive { kind: PrimitiveType::I64, .. }, .. } => {
+                        assert!(matches!(expr.as_ref(), Expr::Unary { op: UnaryOp::Negate, .. }));
+                    }
+                    other => panic!("Expected cast, got {:?}", other),
+                },
+                other => panic!("Expected multiplication, got {:?}", other),
+            },
+            other => panic!("Expected comparison, got {:?}", other),
+        }
+
+        // Casts chain left to right
+        match parse_expression("x as i32 as f64").unwrap() {
+            Expr::Cast { expr, target_type: Type::Primitive { kind: PrimitiveType::F64, .. }, .. } => {
+                assert!(matches!(expr.as_ref(), Expr::Cast { target_type: Type::Primitive { kind: PrimitiveType::I32, .. }, .. }));
+            }
+            other => panic!("Expected cast, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_closure_expressions() {
+        match parse_expression("|x| x + 1").unwrap() {
+            Expr::Closure { params, return_type: None, body, is_move: false, .. } => {
+                assert_eq!(params.len(), 1);
+                assert!(params[0].type_annotation.is_none());
+                assert!(matches!(body.as_ref(), Expr::Binary { op: BinaryOp::Add, .. }));
+            }
+            other => panic!("Expected closure, got {:?}", other),
+        }
+
+        match parse_expression("|a: i32, b: i32| -> i32 { a + b }").unwrap() {
+            Expr::Closure { params, return_type: Some(Type::Primitive { kind: PrimitiveType::I32, .. }), body, .. } => {
+                assert_eq!(params.len(), 2);
+                assert!(params.iter().all(|param| param.type_annotation.is_some()));
+                assert!(matches!(body.as_ref(), Expr::Block { trailing_expr: Some(_), .. }));
+            }
+            other => panic!("Expected closure, got {:?}", other),
+        }
+
+        // `||` lexes as one token and means an empty parameter list
+        match parse_expression("move || 42").unwrap() {
+            Expr::Closure { params, is_move: true, .. } => assert!(params.is_empty()),
+            other => panic!("Expected closure, got {:?}", other),
+        }
+
+        // A return type requires a block body
+        assert!(parse_expression("|x: i32| -> i32 x").is_err());
+    }
+
+    #[test]
+    fn test_tuple_expressions_and_fields() {
+        match parse_expression("(1, x + 2)").unwrap() {
+            Expr::Tuple { elements, .. } => assert_eq!(elements.len(), 2),
+            other => panic!("Expected tuple, got {:?}", other),
+        }
+        // A trailing comma makes a single-element tuple; without it the parentheses only group
+        assert!(matches!(parse_expression("(1,)").unwrap(), Expr::Tuple { elements, .. } if elements.len() == 1));
+        assert!(matches!(parse_expression("(1)").unwrap(), Expr::Parenthesized { .. }));
+        assert!(matches!(parse_expression("()").unwrap(), Expr::Tuple { elements, .. } if elements.is_empty()));
+
+        // `t.0.1` lexes its indices as the float `0.1`
+        let mut parser = Parser::new("t.0.1", 0).unwrap();
+        let expr = parser.parse_expression().unwrap();
+        let interner = parser.interner();
+        match expr {
+            Expr::FieldAccess { object, field, .. } => {
+                assert_eq!(interner.resolve(field).as_deref(), Some("1"));
+                match object.as_ref() {
+                    Expr::FieldAccess { field, .. } => assert_eq!(interner.resolve(*field).as_deref(), Some("0")),
+                    other => panic!("Expected field access, got {:?}", other),
+                }
+            }
+            other => panic!("Expected field access, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tuple_types_and_patterns() {
+        let module = parse_module("fn minmax(a: i32, b: i32) -> (i32, i32) { let (lo, (hi, _)) = (a, (b, 0)); (lo, hi) }").unwrap();
+        match &module.items[0] {
+            Item::Function { return_type: Some(Type::Tuple { types, .. }), body: Some(Expr::Block { statements, .. }), .. } => {
+                assert_eq!(types.len(), 2);
+                match &statements[0] {
+                    Stmt::Let { pattern: Pattern::Tuple { patterns, .. }, .. } => {
+                        assert!(matches!(&patterns[0], Pattern::Identifier { .. }));
+                        assert!(matches!(&patterns[1], Pattern::Tuple { patterns, .. } if patterns.len() == 2));
+                    }
+                    other => panic!("Expected tuple let, got {:?}", other),
+                }
+            }
+            other => panic!("Expected function returning a tuple, got {:?}", other),
+        }
+
+        // `(T)` is just `T`; `(T,)` is a single-element tuple
+        let module = parse_module("fn f(a: (i32), b: (i32,)) {}").unwrap();
+        match &module.items[0] {
+            Item::Function { params, .. } => {
+                assert!(matches!(params[0].type_annotation, Some(Type::Primitive { kind: PrimitiveType::I32, .. })));
+                assert!(matches!(&params[1].type_annotation, Some(Type::Tuple { types, .. }) if types.len() == 1));
+            }
+            other => panic!("Expected function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_match_expressions_and_variant_patterns() {
+        let source = "fn f(s: Shape) -> i64 { let n = match s { Shape::Circle { radius } => radius, \
+                      Some(x) if x > 0 => x, 1..=9 => 1, _ => 0 }; match s { _ => n } }";
+        let module = parse_module(source).unwrap();
+        let Item::Function { body: Some(Expr::Block { statements, trailing_expr, .. }), .. } = &module.items[0] else {
+            panic!("Expected function, got {:?}", module.items[0]);
+        };
+        match &statements[0] {
+            Stmt::Let { initializer: Some(Expr::Match { arms, .. }), .. } => {
+                assert_eq!(arms.len(), 4);
+                match &arms[0].pattern {
+                    Pattern::Struct { path, fields, rest: false, .. } => {
+                        assert_eq!(path.len(), 2);
+                        assert!(matches!(&fields[..], [FieldPattern { pattern: None, .. }]));
+                    }
+                    other => panic!("Expected struct variant pattern, got {:?}", other),
+                }
+                assert!(matches!(&arms[1].pattern, Pattern::Enum { path, patterns: Some(patterns), .. } if path.len() == 1 && patterns.len() == 1));
+                assert!(arms[1].guard.is_some());
+                assert!(matches!(&arms[2].pattern, Pattern::Range { inclusive: true, .. }));
+            }
+            other => panic!("Expected let bound to a match, got {:?}", other),
+        }
+        // A match ending a block is the block's value
+        assert!(matches!(trailing_expr.as_deref(), Some(Expr::Match { .. })));
+    }
+
+    #[test]
+    fn test_array_repeat_and_fixed_size_types() {
+        let no_constants = |_: InternedString| None;
+        match parse_expression("[0; 4 * 16]").unwrap() {
+            Expr::ArrayRepeat { value, count, .. } => {
+                assert!(value.is_literal());
+                assert_eq!(count.fold_integer(&no_constants), Some(64));
+            }
+            other => panic!("Expected array repeat, got {:?}", other),
+        }
+        // The repeat form takes exactly one value
+        assert!(matches!(parse_expression("[1, 2]").unwrap(), Expr::Array { elements, .. } if elements.len() == 2));
+        assert!(parse_expression("[1, 2; 3]").is_err());
+        assert!(parse_expression("[0; 4").is_err());
+
+        let module = parse_module("fn f() { let buffer: [u8; 2 + 2] = [0; 4]; }").unwrap();
+        match &module.items[0] {
+            Item::Function { body: Some(Expr::Block { statements, .. }), .. } => match &statements[0] {
+                Stmt::Let { type_annotation: Some(Type::Array { element_type, size, .. }), .. } => {
+                    assert!(matches!(element_type.as_ref(), Type::Primitive { kind: PrimitiveType::U8, .. }));
+                    assert_eq!(size.fold_integer(&no_constants), Some(4));
+                }
+                other => panic!("Expected fixed-size array let, got {:?}", other),
+            },
+            other => panic!("Expected function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_path_expressions() {
+        let segments = |expr: &Expr| -> usize {
+            match expr {
+                Expr::Call { callee, .. } => match &**callee {
+                    Expr::Path { segments, .. } => segments.len(),
+                    other => panic!("Expected path callee, got {:?}", other),
+                },
+                Expr::StructInit { path, .. } => path.len(),
+                other => panic!("Expected call or struct literal, got {:?}", other),
+            }
+        };
+        assert_eq!(segments(&parse_expression("utils::helpers::f(1)").unwrap()), 3);
+        assert_eq!(segments(&parse_expression("shapes::Point { x: 1 }").unwrap()), 2);
+        assert!(parse_expression("utils::").is_err());
+    }
+
+    #[test]
+    fn test_use_declaration_alias() {
+        let mut parser = Parser::new("use std::io as stdio; use std::fmt;", 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let interner = parser.interner();
+
+        match &module.items[0] {
+            Item::Use { path, alias: Some(alias), .. } => {
+                assert_eq!(path.len(), 2);
+                assert_eq!(interner.resolve(*alias).as_deref(), Some("stdio"));
+            }
+            other => panic!("Expected aliased use, got {:?}", other),
+        }
+        assert!(matches!(&module.items[1], Item::Use { alias: None, .. }));
+
+        let mut parser = Parser::new("use std::io as;", 0).unwrap();
+        parser.parse_module().unwrap();
+        assert!(!parser.errors().is_empty());
+    }
+
+    #[test]
+    fn test_use_declaration_segment_spans_and_globs() {
+        let module = parse_module("use math::helpers;").unwrap();
+        match &module.items[0] {
+            Item::Use { path_spans, .. } => {
+                let columns: Vec<_> = path_spans.iter().map(|span| (span.start.column, span.end.column)).collect();
+                assert_eq!(columns, [(5, 9), (11, 18)]);
+            }
+            other => panic!("Expected use declaration, got {:?}", other),
+        }
+
+        let mut parser = Parser::new("use math::*;", 0).unwrap();
+        let errors = match parser.parse_module() {
+            Ok(_) => parser.errors().to_vec(),
+            Err(error) => vec![error],
+        };
+        assert!(
+            matches!(errors.first(), Some(ParseError::InvalidSyntax { message, .. }) if message.contains("Glob imports")),
+            "{:?}", errors
+        );
+    }
+
+    #[test]
+    fn test_generic_bounds_and_defaults() {
+        let module = parse_module("fn max<T: Ord>(a: T, b: T) -> T; struct Wrapper<T: Clone + Default> { value: T } struct Buf<T = u8>;").unwrap();
+
+        match &module.items[0] {
+            Item::Function { generics, .. } => {
+                assert_eq!(generics[0].bounds.len(), 1);
+                assert!(generics[0].default.is_none());
+            }
+            other => panic!("Expected function, got {:?}", other),
+        }
+        match &module.items[1] {
+            Item::Struct { generics, .. } => assert_eq!(generics[0].bounds.len(), 2),
+            other => panic!("Expected struct, got {:?}", other),
+        }
+        match &module.items[2] {
+            Item::Struct { generics, .. } => {
+                assert!(generics[0].bounds.is_empty());
+                assert!(matches!(generics[0].default, Some(Type::Primitive { kind: PrimitiveType::U8, .. })));
+            }
+            other => panic!("Expected struct, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_where_clause() {
+        let module = parse_module("fn show<T: Clone, U>(t: T, u: U) where T: Debug, U: Clone + Debug { } enum Tree<T> where T: Ord { Leaf }").unwrap();
+        match &module.items[0] {
+            Item::Function { generics, body: Some(_), .. } => {
+                assert_eq!(generics[0].bounds.len(), 2);
+                assert_eq!(generics[1].bounds.len(), 2);
+            }
+            other => panic!("Expected function, got {:?}", other),
+        }
+        assert!(matches!(&module.items[1], Item::Enum { generics, .. } if generics[0].bounds.len() == 1));
+
+        let mut parser = Parser::new("fn f<T>(t: T) where U: Clone { }", 0).unwrap();
+        let error = parser.parse_item().unwrap_err();
+        assert!(error.to_string().contains("undeclared generic parameter 'U'"), "{}", error);
+    }
+
+    #[test]
+    fn test_duplicate_generic_parameter() {
+        let mut parser = Parser::new("struct Pair<T, T> { a: T }", 0).unwrap();
+        match parser.parse_item().unwrap_err() {
+            ParseError::InvalidSyntax { message, position, .. } => {
+                assert!(message.contains("Duplicate generic parameter 'T'"), "{}", message);
+                assert_eq!(position.column, 16);
+            }
+            other => panic!("Expected invalid syntax, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_struct_field_error_recovery() {
+        let mut parser = Parser::new("struct P { x: i32, y i32, z: [u8; 4], } fn main() {}", 0).unwrap();
+        let module = parser.parse_module().unwrap();
+
+        // The missing colon is the only diagnostic
+        assert_eq!(parser.errors().len(), 1, "{:?}", parser.errors());
+        assert!(matches!(&parser.errors()[0], ParseError::UnexpectedToken { position, .. } if position.column == 22));
+
+        // The struct keeps its good fields and the function after it still parses
+        assert_eq!(module.items.len(), 2);
+        match &module.items[0] {
+            Item::Struct { fields: StructFields::Named(fields), .. } => {
+                let names: Vec<String> = fields.iter().map(|field| parser.interner().resolve(field.name).unwrap()).collect();
+                assert_eq!(names, ["x", "z"]);
+            }
+            other => panic!("Expected struct, got {:?}", other),
+        }
+        assert!(matches!(&module.items[1], Item::Function { .. }));
+    }
+
+    #[test]
+    fn test_enum_variant_error_recovery() {
+        let mut parser = Parser::new("enum E { A { x i32, y: { } }, B(i32), C = 1 } fn main() {}", 0).unwrap();
+        let module = parser.parse_module().unwrap();
+
+        assert_eq!(parser.errors().len(), 1, "{:?}", parser.errors());
+        match &module.items[0] {
+            Item::Enum { variants, .. } => {
+                let names: Vec<String> = variants.iter().map(|variant| parser.interner().resolve(variant.name).unwrap()).collect();
+                assert_eq!(names, ["B", "C"]);
+            }
+            other => panic!("Expected enum, got {:?}", other),
+        }
+        assert!(matches!(&module.items[1], Item::Function { .. }));
+    }
+
+    #[test]
+    fn test_recovery_keeps_delimiters_balanced() {
+        // The bad field leaves `(` and `[` open; recovery drops them along with the field
+        let mut parser = Parser::new("struct P { x: ([i32 } struct Q { a: (i32, i32) } fn main() {}", 0).unwrap();
+        let module = parser.parse_module().unwrap();
+
+        assert_eq!(parser.errors().len(), 1, "{:?}", parser.errors());
+        assert!(matches!(&module.items[0], Item::Struct { fields: StructFields::Named(fields), .. } if fields.is_empty()));
+        assert!(matches!(&module.items[1], Item::Struct { fields: StructFields::Named(fields), .. } if fields.len() == 1));
+        assert_eq!(parser.open_delimiter_count(), 0);
+    }
+
+    #[test]
+    fn test_item_attributes_keep_spans() {
+        let input = "@memory(strategy = \"stack\")\n@inline(hint = true) @region(temp)\nfn f() {}";
+        let mut parser = Parser::new(input, 0).unwrap();
+        let item = parser.parse_item().unwrap();
+        let interner = parser.interner();
+
+        let attributes = item.attributes();
+        let names: Vec<String> = attributes.iter().map(|attribute| interner.resolve(attribute.name).unwrap()).collect();
+        assert_eq!(names, ["memory", "inline", "region"]);
+        assert_eq!((attributes[1].span.start.line, attributes[1].span.start.column), (2, 1));
+        assert_eq!((attributes[2].span.start.line, attributes[2].span.start.column), (2, 22));
+
+        // Keyed arguments keep their key, value and position
+        let strategy = &attributes[0].args[0];
+        assert!(interner.matches(strategy.key.unwrap(), "strategy"));
+        assert!(matches!(strategy.value, Literal::String { value, .. } if interner.matches(value, "stack")));
+        assert_eq!((strategy.span.start.line, strategy.span.start.column), (1, 9));
+        assert!(matches!(attributes[1].args[0].value, Literal::Bool(true)));
+
+        // Positional arguments have no key
+        let region = &attributes[2].args[0];
+        assert!(region.key.is_none());
+        assert!(matches!(region.value, Literal::String { value, .. } if interner.matches(value, "temp")));
+        assert!(matches!(item, Item::Function { allocation_strategy: Some(MemoryStrategy::Stack), .. }));
+    }
+
+    #[test]
+    fn test_unknown_attribute_preserved() {
+        let mut parser = Parser::new("@derive(Debug, order = 2) struct P { x: i32 }", 0).unwrap();
+        let item = parser.parse_item().unwrap();
+        match item.attributes() {
+            [attribute] => {
+                assert!(parser.interner().matches(attribute.name, "derive"));
+                assert_eq!(attribute.args.len(), 2);
+            }
+            other => panic!("Expected one attribute, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_misspelled_attribute_suggestion() {
+        let mut parser = Parser::new("fn a() {}\n  @memroy(strategy = \"stack\") fn f() {}", 0).unwrap();
+        parser.parse_item().unwrap();
+        match parser.parse_item().unwrap_err() {
+            ParseError::InvalidSyntax { message, position, suggestions, .. } => {
+                assert!(message.contains("Unknown annotation '@memroy'"), "{}", message);
+                assert_eq!((position.line, position.column), (2, 3));
+                assert_eq!(suggestions[0].replacement.as_deref(), Some("memory"));
+            }
+            other => panic!("Expected invalid syntax, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_attribute_argument_errors_point_at_argument() {
+        let mut parser = Parser::new("@memory(size_hint = 64, strategy = \"lineer\") fn f() {}", 0).unwrap();
+        match parser.parse_item().unwrap_err() {
+            ParseError::MemoryAnnotationError { message, position, .. } => {
+                assert!(message.contains("Invalid memory strategy: lineer"), "{}", message);
+                assert_eq!(position.column, 25);
+            }
+            other => panic!("Expected memory annotation error, got {:?}", other),
+        }
+
+        for input in ["@align(x) fn f() {}", "@region fn f() {}", "@nogc(1) fn f() {}"] {
+            let mut parser = Parser::new(input, 0).unwrap();
+            assert!(matches!(parser.parse_item(), Err(ParseError::InvalidSyntax { .. })), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_doc_comments_attach_to_items() {
+        let input = "//! The file\n//! in two lines\n\n/// Adds numbers.\n///\n/// Wraps on overflow.\n@inline\n/// After the attribute\npub fn add() {\n    /// Not attached to anything\n    let x = 1;\n}\n/**\n * A point\n */\nstruct P { x: i32 }\n// plain\nfn undocumented() {}";
+        let mut parser = Parser::new(input, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let interner = parser.interner();
+
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        assert_eq!(module.doc(&interner).as_deref(), Some("The file\nin two lines"));
+        assert_eq!(module.items[0].doc(&interner).as_deref(), Some("Adds numbers.\n\nWraps on overflow.\nAfter the attribute"));
+        assert_eq!(module.items[1].doc(&interner).as_deref(), Some("A point"));
+        assert_eq!(module.items[2].doc(&interner), None);
+
+        // Doc comments keep their place among the other attributes, with a span
+        let attributes = module.items[0].attributes();
+        let names: Vec<String> = attributes.iter().map(|attribute| interner.resolve(attribute.name).unwrap()).collect();
+        assert_eq!(names, ["doc", "inline", "doc"]);
+        assert_eq!((attributes[0].span.start.line, attributes[0].span.start.column), (4, 1));
+    }
+
+    #[test]
+    fn test_inner_doc_comment_of_inline_module() {
+        let mut parser = Parser::new("/// Outer\nmod util {\n    //! Inner\n    fn helper() {}\n}", 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let interner = parser.interner();
+        assert_eq!(module.doc(&interner), None);
+        assert_eq!(module.items[0].doc(&interner).as_deref(), Some("Outer\nInner"));
+        match &module.items[0] {
+            Item::Module { items: Some(items), .. } => assert_eq!(items[0].doc(&interner), None),
+            other => panic!("Expected inline module, got {:?}", other),
+        }
+    }
+}
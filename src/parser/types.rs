@@ -13,7 +13,7 @@
 //! - Inferred types _
 
 use crate::lexer::{TokenType, Position};
-use crate::ast::{Type, PrimitiveType, Span, MemoryStrategy, Ownership};
+use crate::ast::{Type, PrimitiveType, Span, MemoryStrategy, Ownership, NodeId};
 use super::parser::Parser;
 use super::error::{ParseError, ParseResult, ParseContext, ExpectedToken, Suggestion, SuggestionCategory};
 
@@ -21,6 +21,10 @@ impl<'a> Parser<'a> {
     /// Parse a type according to EBNF grammar
     /// Type ::= FunctionType | SliceType | ArrayType | PointerType | ReferenceType | TupleType | PathType
     pub fn parse_type(&mut self) -> ParseResult<Type> {
+        self.nested(Self::parse_type_inner)
+    }
+    
+    fn parse_type_inner(&mut self) -> ParseResult<Type> {
         if let Some(token) = &self.current_token {
             let start_pos = self.current_position();
             
@@ -57,6 +61,7 @@ impl<'a> Parser<'a> {
                     Ok(Type::Inferred {
                         constraints: Vec::new(),
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -66,6 +71,7 @@ impl<'a> Parser<'a> {
                     let end_pos = self.current_position();
                     Ok(Type::Never {
                         span: Span::new(start_pos, end_pos),
+                        id: NodeId::DUMMY,
                     })
                 }
                 
@@ -78,7 +84,7 @@ impl<'a> Parser<'a> {
                 _ => {
                     Err(ParseError::UnexpectedToken {
                         expected: vec![ExpectedToken::new("type", "primitive, reference, pointer, tuple, array, or path type")],
-                        found: token.token_type.clone(),
+                        found: Box::new(token.token_type.clone().into_owned()),
                         position: start_pos,
                         context: ParseContext::TypeAnnotation,
                         suggestions: vec![
@@ -131,6 +137,7 @@ impl<'a> Parser<'a> {
                 types: Vec::new(),
                 memory_strategy: MemoryStrategy::Inferred,
                 span: Span::new(start_pos, start_pos),
+                id: NodeId::DUMMY,
             })
         };
         
@@ -140,6 +147,7 @@ impl<'a> Parser<'a> {
             return_type,
             is_variadic: false, // TODO: Add variadic support
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -156,6 +164,7 @@ impl<'a> Parser<'a> {
             lifetime: None,
             ownership: Ownership::Borrowed,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -170,7 +179,7 @@ impl<'a> Parser<'a> {
         } else {
             return Err(ParseError::UnexpectedToken {
                 expected: vec![ExpectedToken::new("const or mut", "pointer mutability specifier")],
-                found: self.current_token.as_ref().unwrap().token_type.clone(),
+                found: Box::new(self.current_token.as_ref().unwrap().token_type.clone().into_owned()),
                 position: self.current_position(),
                 context: ParseContext::TypeAnnotation,
                 suggestions: vec![
@@ -189,19 +198,24 @@ impl<'a> Parser<'a> {
             target_type,
             memory_strategy: MemoryStrategy::Manual,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
-    /// Parse tuple type: (T1, T2, T3) or ()
+    /// Parse tuple type: (T1, T2, T3), (T,) or ()
+    ///
+    /// A single type without a trailing comma, `(T)`, is just a parenthesized `T`.
     fn parse_tuple_type(&mut self, start_pos: Position) -> ParseResult<Type> {
         self.expect(TokenType::LeftParen, "tuple type")?;
         
         let mut types = Vec::new();
+        let mut is_tuple = self.check(&TokenType::RightParen);
         
-        if !self.check(&TokenType::RightParen) {
+        if !is_tuple {
             types.push(self.parse_type()?);
             
             while self.match_token(&TokenType::Comma) {
+                is_tuple = true;
                 if self.check(&TokenType::RightParen) {
                     break; // trailing comma
                 }
@@ -212,10 +226,15 @@ impl<'a> Parser<'a> {
         self.expect(TokenType::RightParen, "tuple type")?;
         let end_pos = self.current_position();
         
+        if !is_tuple {
+            return Ok(types.remove(0));
+        }
+        
         Ok(Type::Tuple {
             types,
             memory_strategy: MemoryStrategy::Inferred,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -236,13 +255,14 @@ impl<'a> Parser<'a> {
                 size,
                 memory_strategy: MemoryStrategy::Inferred,
                 span: Span::new(start_pos, end_pos),
+                id: NodeId::DUMMY,
             })
         } else {
             // This would be a slice type &[T], but we need the & to be parsed first
             // So this is an error - slices must be written as &[T]
             Err(ParseError::UnexpectedToken {
                 expected: vec![ExpectedToken::new("semicolon for array size", "array size separator ';'")],
-                found: self.current_token.as_ref().unwrap().token_type.clone(),
+                found: Box::new(self.current_token.as_ref().unwrap().token_type.clone().into_owned()),
                 position: self.current_position(),
                 context: ParseContext::TypeAnnotation,
                 suggestions: vec![
@@ -258,26 +278,7 @@ impl<'a> Parser<'a> {
     /// Parse path type or primitive type
     fn parse_path_or_primitive_type(&mut self, name: &str, start_pos: Position) -> ParseResult<Type> {
         // Check if it's a primitive type
-        let primitive = match name {
-            "i8" => Some(PrimitiveType::I8),
-            "i16" => Some(PrimitiveType::I16),
-            "i32" => Some(PrimitiveType::I32),
-            "i64" => Some(PrimitiveType::I64),
-            "i128" => Some(PrimitiveType::I128),
-            "isize" => Some(PrimitiveType::ISize),
-            "u8" => Some(PrimitiveType::U8),
-            "u16" => Some(PrimitiveType::U16),
-            "u32" => Some(PrimitiveType::U32),
-            "u64" => Some(PrimitiveType::U64),
-            "u128" => Some(PrimitiveType::U128),
-            "usize" => Some(PrimitiveType::USize),
-            "f32" => Some(PrimitiveType::F32),
-            "f64" => Some(PrimitiveType::F64),
-            "bool" => Some(PrimitiveType::Bool),
-            "char" => Some(PrimitiveType::Char),
-            "str" => Some(PrimitiveType::Str),
-            _ => None,
-        };
+        let primitive = PrimitiveType::from_name(name);
         
         if let Some(prim) = primitive {
             self.advance()?;
@@ -286,6 +287,7 @@ impl<'a> Parser<'a> {
                 kind: prim,
                 memory_strategy: MemoryStrategy::Inferred,
                 span: Span::new(start_pos, end_pos),
+                id: NodeId::DUMMY,
             })
         } else {
             // Path type
@@ -305,7 +307,7 @@ impl<'a> Parser<'a> {
             } else {
                 return Err(ParseError::UnexpectedToken {
                     expected: vec![ExpectedToken::new("identifier", "type name or path segment")],
-                    found: token.token_type.clone(),
+                    found: Box::new(token.token_type.clone().into_owned()),
                     position: self.current_position(),
                     context: ParseContext::TypeAnnotation,
                     suggestions: vec![
@@ -337,7 +339,7 @@ impl<'a> Parser<'a> {
                 } else {
                     return Err(ParseError::UnexpectedToken {
                         expected: vec![ExpectedToken::new("identifier", "type name or path segment")],
-                        found: token.token_type.clone(),
+                        found: Box::new(token.token_type.clone().into_owned()),
                         position: self.current_position(),
                         context: ParseContext::TypeAnnotation,
                         suggestions: vec![
@@ -388,6 +390,7 @@ impl<'a> Parser<'a> {
             generics,
             memory_strategy: MemoryStrategy::Inferred,
             span: Span::new(start_pos, end_pos),
+            id: NodeId::DUMMY,
         })
     }
     
@@ -0,0 +1,126 @@
+//! Allocation Strategy Recommendations
+//!
+//! Compares the heap allocations a function is estimated to perform with the
+//! strategy codegen would infer for objects of that size and escape behaviour,
+//! and recommends the cheaper strategy when the declared (or default) one costs
+//! more per call.
+
+use crate::ast::{MemoryStrategy, Span};
+use crate::codegen::cranelift::MemoryStrategy as CodegenStrategy;
+use crate::codegen::cranelift::utils::from_ast_strategy;
+use crate::codegen::MemoryPolicy;
+use crate::lexer::Position;
+use crate::semantic::EscapeFact;
+use super::PerformanceCost;
+use std::collections::HashMap;
+
+/// Strategy heap allocations use in functions without a `@memory` annotation
+pub const DEFAULT_HEAP_STRATEGY: MemoryStrategy = MemoryStrategy::Manual;
+
+/// Suggested change of allocation strategy for one function
+#[derive(Debug, Clone, PartialEq)]
+pub struct AllocationRecommendation {
+    /// Function the allocations happen in
+    pub function_name: String,
+    /// Strategy from the function's `@memory` annotation, or the default
+    pub current_strategy: MemoryStrategy,
+    /// Cheapest strategy suited to the allocated objects
+    pub recommended_strategy: MemoryStrategy,
+    /// Estimated allocations per call
+    pub allocations: u32,
+    /// Estimated average size of each allocation in bytes
+    pub average_bytes: u64,
+    /// Estimated cycles saved per call by switching strategy
+    pub cycles_saved_per_call: u64,
+    /// Source location of the function
+    pub span: Span,
+}
+
+impl AllocationRecommendation {
+    /// One-line explanation of the recommendation
+    pub fn message(&self) -> String {
+        format!(
+            "Function '{}' allocates {} object{} of about {} bytes with {:?} - consider {:?}, \
+             estimated savings {} cycles per call",
+            self.function_name,
+            self.allocations,
+            if self.allocations == 1 { "" } else { "s" },
+            self.average_bytes,
+            self.current_strategy,
+            self.recommended_strategy,
+            self.cycles_saved_per_call
+        )
+    }
+}
+
+/// What escape analysis found out about the values a function allocates
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AllocationEscapes {
+    /// Some allocated value may outlive the function
+    pub escapes: bool,
+    /// Some allocated value escapes along more than one route, or into a closure
+    pub is_shared: bool,
+}
+
+impl AllocationEscapes {
+    /// Combined facts for the allocation sites that start within `span`
+    pub fn within(span: Span, escape_facts: &HashMap<Position, EscapeFact>) -> Self {
+        escape_facts.iter()
+            .filter(|(site, _)| (span.start.offset..span.end.offset).contains(&site.offset))
+            .fold(Self::default(), |escapes, (_, fact)| Self {
+                escapes: escapes.escapes || fact.escapes(),
+                is_shared: escapes.is_shared || fact.is_shared,
+            })
+    }
+}
+
+/// Recommend a cheaper allocation strategy for a function, if there is one
+///
+/// The estimate does not say which allocation each value comes from, so if any
+/// of the function's values escapes, all of them are treated as escaping. The
+/// strategy is inferred with the size thresholds of `policy`.
+pub fn recommend_allocation_strategy(
+    function_name: &str,
+    span: Span,
+    annotated_strategy: Option<MemoryStrategy>,
+    cost: &PerformanceCost,
+    escapes: AllocationEscapes,
+    policy: &MemoryPolicy,
+) -> Option<AllocationRecommendation> {
+    let allocations = cost.allocations.filter(|&count| count > 0)?;
+    let average_bytes = cost.memory_bytes? / u64::from(allocations);
+    let size = u32::try_from(average_bytes).unwrap_or(u32::MAX);
+
+    let current_strategy = annotated_strategy
+        .filter(|strategy| *strategy != MemoryStrategy::Inferred)
+        .unwrap_or(DEFAULT_HEAP_STRATEGY);
+    let current = from_ast_strategy(current_strategy)?;
+    let recommended = CodegenStrategy::infer_for_type(size, escapes.is_shared, !escapes.escapes, policy);
+
+    let current_cycles = current.allocation_cycles(size);
+    let recommended_cycles = recommended.allocation_cycles(size);
+    if recommended_cycles >= current_cycles {
+        return None;
+    }
+
+    Some(AllocationRecommendation {
+        function_name: function_name.to_string(),
+        current_strategy,
+        recommended_strategy: to_ast_strategy(recommended),
+        allocations,
+        average_bytes,
+        cycles_saved_per_call: (current_cycles - recommended_cycles) * u64::from(allocations),
+        span,
+    })
+}
+
+/// Map a codegen strategy back onto the AST strategy
+fn to_ast_strategy(strategy: CodegenStrategy) -> MemoryStrategy {
+    match strategy {
+        CodegenStrategy::Stack => MemoryStrategy::Stack,
+        CodegenStrategy::Linear => MemoryStrategy::Linear,
+        CodegenStrategy::Region => MemoryStrategy::Region,
+        CodegenStrategy::Manual => MemoryStrategy::Manual,
+        CodegenStrategy::SmartPtr => MemoryStrategy::SmartPtr,
+    }
+}
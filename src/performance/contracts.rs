@@ -3,14 +3,73 @@
 //! This module implements the contract verification engine that validates
 //! estimated performance costs against declared @guarantee contracts.
 
-use crate::ast::{PerformanceContract, CpuBound, MemoryBound, AllocationBound, LatencyBound, StackBound, BigOComplexity};
-use super::{ContractViolation, ViolationType, PerformanceCost};
+use crate::ast::{PerformanceContract, CpuBound, MemoryBound, AllocationBound, LatencyBound, StackBound, Span, BigOComplexity};
+use super::{ContractViolation, ViolationType, PerformanceCost, PerformanceWarning, WarningType, CostModel};
 use std::time::Duration;
 
+/// Estimates below this confidence cannot back a `deterministic` contract
+pub const DETERMINISM_CONFIDENCE_THRESHOLD: f32 = 0.75;
+
+/// Result of verifying one function's contract
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContractVerification {
+    /// Bounds the estimate exceeds
+    pub violations: Vec<ContractViolation>,
+    /// Bounds that could not be checked
+    pub warnings: Vec<PerformanceWarning>,
+}
+
 /// Contract verifier - validates estimated costs against declared contracts
 pub struct ContractVerifier {
-    /// Strict mode - fail on any uncertainty
+    /// Strict mode - report low-confidence `deterministic` contracts as violations
+    /// rather than warnings
     strict_mode: bool,
+    /// Converts cycle estimates into time for time and latency bounds
+    cost_model: CostModel,
+}
+
+/// Function under verification, attached to every reported issue
+struct Subject<'a> {
+    name: &'a str,
+    span: Span,
+}
+
+impl Subject<'_> {
+    fn violation(&self, violation_type: ViolationType, expected: String, actual: String, message: String) -> ContractViolation {
+        ContractViolation {
+            function_name: self.name.to_string(),
+            violation_type,
+            expected,
+            actual,
+            span: self.span,
+            message,
+        }
+    }
+
+    fn unknown_estimate(&self, bound: &str, expected: &str) -> PerformanceWarning {
+        PerformanceWarning {
+            function_name: self.name.to_string(),
+            warning_type: WarningType::InaccurateEstimate,
+            message: format!(
+                "Cannot verify {} bound of {} for function '{}': its cost could not be estimated",
+                bound, expected, self.name
+            ),
+            span: self.span,
+        }
+    }
+}
+
+/// Big-O notation of a complexity class, e.g. `O(n log n)`
+fn big_o(complexity: BigOComplexity) -> &'static str {
+    match complexity {
+        BigOComplexity::Constant => "O(1)",
+        BigOComplexity::Logarithmic => "O(log n)",
+        BigOComplexity::Linear => "O(n)",
+        BigOComplexity::Linearithmic => "O(n log n)",
+        BigOComplexity::Quadratic => "O(n^2)",
+        BigOComplexity::Cubic => "O(n^3)",
+        BigOComplexity::Exponential => "O(2^n)",
+    }
 }
 
 impl ContractVerifier {
@@ -18,6 +77,7 @@ impl ContractVerifier {
     pub fn new() -> Self {
         Self {
             strict_mode: true, // Default to strict mode
+            cost_model: CostModel::x86_64(),
         }
     }
 
@@ -25,317 +85,291 @@ impl ContractVerifier {
     pub fn lenient() -> Self {
         Self {
             strict_mode: false,
+            ..Self::new()
         }
     }
 
+    /// Use `cost_model` to turn cycle estimates into time
+    pub fn with_cost_model(mut self, cost_model: CostModel) -> Self {
+        self.cost_model = cost_model;
+        self
+    }
+
     /// Verify a performance contract against estimated cost
+    ///
+    /// Every violated bound produces one violation located at `span`; bounds whose
+    /// estimate is unknown produce an `InaccurateEstimate` warning instead.
     pub fn verify_contract(
         &self,
         function_name: &str,
+        span: Span,
         contract: &PerformanceContract,
         estimated_cost: &PerformanceCost,
-    ) -> Vec<ContractViolation> {
-        let mut violations = Vec::new();
+    ) -> ContractVerification {
+        let subject = Subject { name: function_name, span };
+        let mut result = ContractVerification::default();
 
-        // Verify CPU bounds
         if let Some(ref cpu_bound) = contract.cpu_bound {
-            if let Some(violation) = self.verify_cpu_bound(function_name, cpu_bound, estimated_cost) {
-                violations.push(violation);
-            }
+            self.verify_cpu_bound(&subject, cpu_bound, estimated_cost, &mut result);
         }
-
-        // Verify memory bounds
         if let Some(ref memory_bound) = contract.memory_bound {
-            if let Some(violation) = self.verify_memory_bound(function_name, memory_bound, estimated_cost) {
-                violations.push(violation);
-            }
+            self.verify_memory_bound(&subject, memory_bound, estimated_cost, &mut result);
         }
-
-        // Verify allocation bounds
         if let Some(ref allocation_bound) = contract.allocation_bound {
-            if let Some(violation) = self.verify_allocation_bound(function_name, allocation_bound, estimated_cost) {
-                violations.push(violation);
-            }
+            self.verify_allocation_bound(&subject, allocation_bound, estimated_cost, &mut result);
         }
-
-        // Verify latency bounds
         if let Some(ref latency_bound) = contract.latency_bound {
-            if let Some(violation) = self.verify_latency_bound(function_name, latency_bound, estimated_cost) {
-                violations.push(violation);
-            }
+            self.verify_latency_bound(&subject, latency_bound, estimated_cost, &mut result);
         }
-
-        // Verify stack bounds
         if let Some(ref stack_bound) = contract.stack_bound {
-            if let Some(violation) = self.verify_stack_bound(function_name, stack_bound, estimated_cost) {
-                violations.push(violation);
-            }
+            self.verify_stack_bound(&subject, stack_bound, estimated_cost, &mut result);
         }
-
-        // Verify deterministic requirement
         if contract.deterministic {
-            if let Some(violation) = self.verify_deterministic(function_name, estimated_cost) {
-                violations.push(violation);
-            }
+            self.verify_deterministic(&subject, estimated_cost, &mut result);
         }
-
-        // Verify wait-free requirement
         if contract.wait_free {
-            if let Some(violation) = self.verify_wait_free(function_name, estimated_cost) {
-                violations.push(violation);
-            }
+            self.verify_wait_free(&subject, estimated_cost, &mut result);
         }
 
-        violations
+        result
+    }
+
+    /// Estimated running time of `cycles` on the target
+    fn estimated_time(&self, cycles: u64) -> Duration {
+        Duration::from_nanos(self.cost_model.cycles_to_nanoseconds(cycles) as u64)
     }
 
     /// Verify CPU bound
     fn verify_cpu_bound(
         &self,
-        function_name: &str,
+        subject: &Subject,
         cpu_bound: &CpuBound,
         estimated_cost: &PerformanceCost,
-    ) -> Option<ContractViolation> {
+        result: &mut ContractVerification,
+    ) {
         match cpu_bound {
-            CpuBound::Cycles(max_cycles) => {
-                if let Some(estimated_cycles) = estimated_cost.cycles {
-                    if estimated_cycles > *max_cycles {
-                        return Some(ContractViolation {
-                            function_name: function_name.to_string(),
-                            violation_type: ViolationType::CpuExceeded,
-                            expected: format!("{} cycles", max_cycles),
-                            actual: format!("{} cycles", estimated_cycles),
-                            span: crate::ast::Span::single(crate::lexer::Position::default()),
-                            message: format!(
-                                "Function '{}' exceeds CPU cycle bound: estimated {} cycles > contract limit {} cycles",
-                                function_name, estimated_cycles, max_cycles
-                            ),
-                        });
-                    }
-                } else if self.strict_mode {
-                    // Cannot verify - treat as violation in strict mode
-                    return Some(ContractViolation {
-                        function_name: function_name.to_string(),
-                        violation_type: ViolationType::CpuExceeded,
-                        expected: format!("{} cycles", max_cycles),
-                        actual: "unknown".to_string(),
-                        span: crate::ast::Span::single(crate::lexer::Position::default()),
-                        message: format!(
-                            "Function '{}' has unknown CPU cost but declares cycle bound",
-                            function_name
+            CpuBound::Cycles(max_cycles) => match estimated_cost.cycles {
+                Some(estimated_cycles) if estimated_cycles > *max_cycles => {
+                    result.violations.push(subject.violation(
+                        ViolationType::CpuExceeded,
+                        format!("{} cycles", max_cycles),
+                        format!("{} cycles", estimated_cycles),
+                        format!(
+                            "Function '{}' exceeds CPU cycle bound: estimated {} cycles > contract limit {} cycles",
+                            subject.name, estimated_cycles, max_cycles
                         ),
-                    });
+                    ));
                 }
-            }
-            CpuBound::Time(max_time) => {
-                // For time-based bounds, we need to estimate from cycles
-                if let Some(estimated_cycles) = estimated_cost.cycles {
-                    // Rough estimate: 3GHz = 3 billion cycles per second
-                    let estimated_time_ns = (estimated_cycles as f64 / 3_000_000_000.0) * 1_000_000_000.0;
-                    let max_time_ns = max_time.as_nanos() as f64;
-                    
-                    if estimated_time_ns > max_time_ns {
-                        return Some(ContractViolation {
-                            function_name: function_name.to_string(),
-                            violation_type: ViolationType::CpuExceeded,
-                            expected: format!("{:?}", max_time),
-                            actual: format!("{:.2}μs", estimated_time_ns / 1000.0),
-                            span: crate::ast::Span::single(crate::lexer::Position::default()),
-                            message: format!(
-                                "Function '{}' exceeds time bound: estimated {:.2}μs > contract limit {:?}",
-                                function_name, estimated_time_ns / 1000.0, max_time
+                Some(_) => {}
+                None => result.warnings.push(subject.unknown_estimate("CPU", &format!("{} cycles", max_cycles))),
+            },
+            CpuBound::Time(max_time) => match estimated_cost.cycles {
+                Some(estimated_cycles) => {
+                    let estimated_time = self.estimated_time(estimated_cycles);
+                    if estimated_time > *max_time {
+                        result.violations.push(subject.violation(
+                            ViolationType::CpuExceeded,
+                            format!("{:?}", max_time),
+                            format!("{:?}", estimated_time),
+                            format!(
+                                "Function '{}' exceeds time bound: estimated {:?} > contract limit {:?}",
+                                subject.name, estimated_time, max_time
                             ),
-                        });
+                        ));
                     }
                 }
-            }
-            CpuBound::Complexity(complexity, _param) => {
-                // For complexity bounds, we can only verify if we have more sophisticated analysis
-                // For now, we'll just warn that complexity verification is not yet implemented
-                // TODO: Implement complexity analysis
-            }
+                None => result.warnings.push(subject.unknown_estimate("CPU time", &format!("{:?}", max_time))),
+            },
+            CpuBound::Complexity(max_complexity, _param) => match estimated_cost.complexity {
+                Some(complexity) if complexity > *max_complexity => {
+                    let cause = match estimated_cost.complexity_loop {
+                        Some(loop_span) => format!(" because of the loop at {}", loop_span.start),
+                        None => String::new(),
+                    };
+                    result.violations.push(subject.violation(
+                        ViolationType::ComplexityExceeded,
+                        big_o(*max_complexity).to_string(),
+                        big_o(complexity).to_string(),
+                        format!(
+                            "Function '{}' is {}{}, exceeding its contract bound of {}",
+                            subject.name, big_o(complexity), cause, big_o(*max_complexity)
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => result.warnings.push(subject.unknown_estimate("complexity", big_o(*max_complexity))),
+            },
         }
-        None
     }
 
     /// Verify memory bound
     fn verify_memory_bound(
         &self,
-        function_name: &str,
+        subject: &Subject,
         memory_bound: &MemoryBound,
         estimated_cost: &PerformanceCost,
-    ) -> Option<ContractViolation> {
-        match memory_bound {
-            MemoryBound::Bytes(max_bytes) => {
-                if let Some(estimated_bytes) = estimated_cost.memory_bytes {
-                    if estimated_bytes > *max_bytes {
-                        return Some(ContractViolation {
-                            function_name: function_name.to_string(),
-                            violation_type: ViolationType::MemoryExceeded,
-                            expected: format!("{} bytes", max_bytes),
-                            actual: format!("{} bytes", estimated_bytes),
-                            span: crate::ast::Span::single(crate::lexer::Position::default()),
-                            message: format!(
-                                "Function '{}' exceeds memory bound: {} bytes > {} bytes",
-                                function_name, estimated_bytes, max_bytes
-                            ),
-                        });
-                    }
-                }
-            }
-            MemoryBound::Zero => {
-                if let Some(estimated_bytes) = estimated_cost.memory_bytes {
-                    if estimated_bytes > 0 {
-                        return Some(ContractViolation {
-                            function_name: function_name.to_string(),
-                            violation_type: ViolationType::MemoryExceeded,
-                            expected: "0 bytes".to_string(),
-                            actual: format!("{} bytes", estimated_bytes),
-                            span: crate::ast::Span::single(crate::lexer::Position::default()),
-                            message: format!(
-                                "Function '{}' uses memory but declares zero memory usage",
-                                function_name
-                            ),
-                        });
-                    }
-                }
-            }
-            MemoryBound::Proportional(coeff, _param) => {
+        result: &mut ContractVerification,
+    ) {
+        let max_bytes = match memory_bound {
+            MemoryBound::Bytes(max_bytes) => *max_bytes,
+            MemoryBound::Zero => 0,
+            MemoryBound::Proportional(_coeff, _param) => {
                 // TODO: Implement proportional memory verification
                 // This requires parameter size analysis
-                let _ = coeff; // Suppress unused warning for now
+                return;
+            }
+        };
+
+        match estimated_cost.memory_bytes {
+            Some(estimated_bytes) if estimated_bytes > max_bytes => {
+                result.violations.push(subject.violation(
+                    ViolationType::MemoryExceeded,
+                    format!("{} bytes", max_bytes),
+                    format!("{} bytes", estimated_bytes),
+                    format!(
+                        "Function '{}' exceeds memory bound: {} bytes > {} bytes",
+                        subject.name, estimated_bytes, max_bytes
+                    ),
+                ));
             }
+            Some(_) => {}
+            None => result.warnings.push(subject.unknown_estimate("memory", &format!("{} bytes", max_bytes))),
         }
-        None
     }
 
     /// Verify allocation bound
     fn verify_allocation_bound(
         &self,
-        function_name: &str,
+        subject: &Subject,
         allocation_bound: &AllocationBound,
         estimated_cost: &PerformanceCost,
-    ) -> Option<ContractViolation> {
-        match allocation_bound {
-            AllocationBound::Count(max_allocs) => {
-                if let Some(estimated_allocs) = estimated_cost.allocations {
-                    if estimated_allocs > *max_allocs {
-                        return Some(ContractViolation {
-                            function_name: function_name.to_string(),
-                            violation_type: ViolationType::AllocationExceeded,
-                            expected: format!("{} allocations", max_allocs),
-                            actual: format!("{} allocations", estimated_allocs),
-                            span: crate::ast::Span::single(crate::lexer::Position::default()),
-                            message: format!(
-                                "Function '{}' exceeds allocation bound: {} > {} allocations",
-                                function_name, estimated_allocs, max_allocs
-                            ),
-                        });
-                    }
-                }
-            }
-            AllocationBound::None => {
-                if let Some(estimated_allocs) = estimated_cost.allocations {
-                    if estimated_allocs > 0 {
-                        return Some(ContractViolation {
-                            function_name: function_name.to_string(),
-                            violation_type: ViolationType::AllocationExceeded,
-                            expected: "0 allocations".to_string(),
-                            actual: format!("{} allocations", estimated_allocs),
-                            span: crate::ast::Span::single(crate::lexer::Position::default()),
-                            message: format!(
-                                "Function '{}' performs heap allocations but declares zero allocation usage",
-                                function_name
-                            ),
-                        });
-                    }
-                }
+        result: &mut ContractVerification,
+    ) {
+        let max_allocs = match allocation_bound {
+            AllocationBound::Count(max_allocs) => *max_allocs,
+            AllocationBound::None => 0,
+        };
+
+        match estimated_cost.allocations {
+            Some(estimated_allocs) if estimated_allocs > max_allocs => {
+                result.violations.push(subject.violation(
+                    ViolationType::AllocationExceeded,
+                    format!("{} allocations", max_allocs),
+                    format!("{} allocations", estimated_allocs),
+                    format!(
+                        "Function '{}' exceeds allocation bound: {} > {} allocations",
+                        subject.name, estimated_allocs, max_allocs
+                    ),
+                ));
             }
+            Some(_) => {}
+            None => result.warnings.push(subject.unknown_estimate("allocation", &format!("{} allocations", max_allocs))),
         }
-        None
     }
 
     /// Verify latency bound
     fn verify_latency_bound(
         &self,
-        function_name: &str,
-        _latency_bound: &LatencyBound,
-        _estimated_cost: &PerformanceCost,
-    ) -> Option<ContractViolation> {
-        // TODO: Implement latency verification
-        // This requires runtime measurement or more sophisticated modeling
-        let _ = (function_name, _latency_bound, _estimated_cost);
-        None
+        subject: &Subject,
+        latency_bound: &LatencyBound,
+        estimated_cost: &PerformanceCost,
+        result: &mut ContractVerification,
+    ) {
+        let max_latency = latency_bound.max_latency;
+        match estimated_cost.cycles {
+            Some(estimated_cycles) => {
+                let estimated_latency = self.estimated_time(estimated_cycles);
+                if estimated_latency > max_latency {
+                    result.violations.push(subject.violation(
+                        ViolationType::LatencyExceeded,
+                        format!("{:?}", max_latency),
+                        format!("{:?}", estimated_latency),
+                        format!(
+                            "Function '{}' exceeds latency bound: estimated {:?} > contract limit {:?}",
+                            subject.name, estimated_latency, max_latency
+                        ),
+                    ));
+                }
+            }
+            None => result.warnings.push(subject.unknown_estimate("latency", &format!("{:?}", max_latency))),
+        }
     }
 
     /// Verify stack bound
     fn verify_stack_bound(
         &self,
-        function_name: &str,
+        subject: &Subject,
         stack_bound: &StackBound,
         estimated_cost: &PerformanceCost,
-    ) -> Option<ContractViolation> {
-        match stack_bound {
-            StackBound::Bytes(max_stack) => {
-                if let Some(estimated_stack) = estimated_cost.stack_bytes {
-                    if estimated_stack > *max_stack {
-                        return Some(ContractViolation {
-                            function_name: function_name.to_string(),
-                            violation_type: ViolationType::StackExceeded,
-                            expected: format!("{} stack bytes", max_stack),
-                            actual: format!("{} stack bytes", estimated_stack),
-                            span: crate::ast::Span::single(crate::lexer::Position::default()),
-                            message: format!(
-                                "Function '{}' exceeds stack bound: {} > {} bytes",
-                                function_name, estimated_stack, max_stack
-                            ),
-                        });
-                    }
-                }
-            }
-            StackBound::Zero => {
-                if let Some(estimated_stack) = estimated_cost.stack_bytes {
-                    if estimated_stack > 0 {
-                        return Some(ContractViolation {
-                            function_name: function_name.to_string(),
-                            violation_type: ViolationType::StackExceeded,
-                            expected: "0 stack bytes".to_string(),
-                            actual: format!("{} stack bytes", estimated_stack),
-                            span: crate::ast::Span::single(crate::lexer::Position::default()),
-                            message: format!(
-                                "Function '{}' uses stack but declares zero stack usage",
-                                function_name
-                            ),
-                        });
-                    }
-                }
+        result: &mut ContractVerification,
+    ) {
+        let max_stack = match stack_bound {
+            StackBound::Bytes(max_stack) => *max_stack,
+            StackBound::Zero => 0,
+        };
+
+        match estimated_cost.stack_bytes {
+            Some(estimated_stack) if estimated_stack > max_stack => {
+                result.violations.push(subject.violation(
+                    ViolationType::StackExceeded,
+                    format!("{} stack bytes", max_stack),
+                    format!("{} stack bytes", estimated_stack),
+                    format!(
+                        "Function '{}' exceeds stack bound: {} > {} bytes",
+                        subject.name, estimated_stack, max_stack
+                    ),
+                ));
             }
+            Some(_) => {}
+            None => result.warnings.push(subject.unknown_estimate("stack", &format!("{} stack bytes", max_stack))),
         }
-        None
     }
 
     /// Verify deterministic requirement
+    ///
+    /// A low-confidence estimate means the function contains unbounded loops or
+    /// unresolved calls, so its execution path cannot be shown to be fixed.
     fn verify_deterministic(
         &self,
-        function_name: &str,
-        _estimated_cost: &PerformanceCost,
-    ) -> Option<ContractViolation> {
-        // TODO: Implement deterministic analysis
-        // This requires control flow analysis to detect non-deterministic operations
-        let _ = (function_name, _estimated_cost);
-        None
+        subject: &Subject,
+        estimated_cost: &PerformanceCost,
+        result: &mut ContractVerification,
+    ) {
+        if estimated_cost.confidence >= DETERMINISM_CONFIDENCE_THRESHOLD {
+            return;
+        }
+
+        let message = format!(
+            "Function '{}' is declared deterministic, but its cost estimate has confidence {:.2} (below {:.2}); \
+             unbounded loops or unresolved calls may make its execution path input-dependent",
+            subject.name, estimated_cost.confidence, DETERMINISM_CONFIDENCE_THRESHOLD
+        );
+        if self.strict_mode {
+            result.violations.push(subject.violation(
+                ViolationType::NonDeterministic,
+                format!("confidence >= {:.2}", DETERMINISM_CONFIDENCE_THRESHOLD),
+                format!("confidence {:.2}", estimated_cost.confidence),
+                message,
+            ));
+        } else {
+            result.warnings.push(PerformanceWarning {
+                function_name: subject.name.to_string(),
+                warning_type: WarningType::InaccurateEstimate,
+                message,
+                span: subject.span,
+            });
+        }
     }
 
     /// Verify wait-free requirement
     fn verify_wait_free(
         &self,
-        function_name: &str,
+        _subject: &Subject,
         _estimated_cost: &PerformanceCost,
-    ) -> Option<ContractViolation> {
+        _result: &mut ContractVerification,
+    ) {
         // TODO: Implement wait-free analysis
         // This requires concurrency analysis to detect blocking operations
-        let _ = (function_name, _estimated_cost);
-        None
     }
 }
 
@@ -2,9 +2,23 @@
 //!
 //! This module implements cost estimation for Bract expressions and statements,
 //! providing the foundation for performance contract verification.
+//!
+//! Estimates are worst-case: branches take their most expensive arm, loops over
+//! constant ranges and counter loops stepping towards a constant multiply their
+//! body, and anything that cannot be bounded
+//! (unbounded loops, unresolved callees) leaves its figure unknown with reduced
+//! confidence.
 
-use crate::ast::{Expr, Stmt, BinaryOp, UnaryOp, Literal, InternedString, Type};
+use crate::ast::{Expr, Stmt, BinaryOp, UnaryOp, Literal, InternedString, MatchArm, Type, PrimitiveType, Parameter, Pattern, BigOComplexity, Span};
 use super::{PerformanceCost, models::{CostModel, TargetArchitecture}};
+use crate::codegen::MemoryPolicy;
+use std::collections::{HashMap, HashSet};
+
+/// Bytes assumed for values whose size is not known statically (pointers, aggregates)
+const DEFAULT_VALUE_BYTES: u32 = 8;
+
+/// Confidence factor applied for each estimate that had to be guessed
+const UNCERTAIN_CONFIDENCE_FACTOR: f32 = 0.5;
 
 /// Cost estimator - estimates performance costs from AST nodes
 pub struct CostEstimator {
@@ -12,55 +26,118 @@ pub struct CostEstimator {
     target_arch: TargetArchitecture,
     /// Cost model for the target architecture
     cost_model: CostModel,
+    /// Costs of functions estimated so far, added at their call sites
+    function_costs: HashMap<InternedString, PerformanceCost>,
+    /// Policy deciding, as in code generation, which values are too large for the stack
+    memory_policy: MemoryPolicy,
 }
 
 impl CostEstimator {
     /// Create a new cost estimator for the target architecture
     pub fn new(target_arch: TargetArchitecture) -> Self {
-        let cost_model = CostModel::for_architecture(target_arch);
+        Self::with_model(CostModel::for_architecture(target_arch))
+    }
+
+    /// Create a cost estimator using a custom (e.g. loaded from TOML) cost model
+    pub fn with_model(cost_model: CostModel) -> Self {
         Self {
-            target_arch,
+            target_arch: cost_model.architecture,
             cost_model,
+            function_costs: HashMap::new(),
+            memory_policy: MemoryPolicy::default(),
         }
     }
 
+    /// The estimator placing values on the stack or the heap by `policy`
+    pub fn with_memory_policy(mut self, policy: MemoryPolicy) -> Self {
+        self.memory_policy = policy;
+        self
+    }
+
+    /// Policy deciding which values are too large for the stack
+    pub fn memory_policy(&self) -> &MemoryPolicy {
+        &self.memory_policy
+    }
+
+    /// Record the estimated cost of a function body for calls to `name`
+    pub fn record_function_cost(&mut self, name: InternedString, cost: PerformanceCost) {
+        self.function_costs.insert(name, cost);
+    }
+
+    /// Forget all recorded function costs
+    pub fn clear_function_costs(&mut self) {
+        self.function_costs.clear();
+    }
+
+    /// Previously recorded cost of the function `name`
+    pub fn function_cost(&self, name: &InternedString) -> Option<&PerformanceCost> {
+        self.function_costs.get(name)
+    }
+
+    /// Architecture the estimates are modeled on
+    pub fn target_arch(&self) -> TargetArchitecture {
+        self.target_arch
+    }
+
     /// Estimate cost of an expression
     pub fn estimate_expression_cost(&self, expr: &Expr) -> PerformanceCost {
         match expr {
-            Expr::Literal(literal) => self.estimate_literal_cost(literal),
-            Expr::Identifier(_) => PerformanceCost::zero(), // Variable access is essentially free
+            Expr::Literal { literal, .. } => self.estimate_literal_cost(literal),
+            // Variable access is a register or stack slot read
+            Expr::Identifier { .. } | Expr::Path { .. } => PerformanceCost {
+                cycles: Some(1),
+                ..PerformanceCost::zero()
+            },
             Expr::Binary { left, op, right, .. } => self.estimate_binary_cost(left, op, right),
-            Expr::Unary { op, operand, .. } => self.estimate_unary_cost(op, operand),
-            Expr::Call { func, args, .. } => self.estimate_call_cost(func, args),
+            Expr::Unary { op, expr, .. } => self.estimate_unary_cost(op, expr),
+            Expr::Call { callee, args, .. } => self.estimate_call_cost(callee, args),
+            Expr::MethodCall { receiver, args, .. } => {
+                // Methods are resolved by receiver type, which is not known here
+                let mut cost = self.estimate_expression_cost(receiver);
+                cost += self.estimate_unresolved_call_cost(args);
+                cost
+            }
             Expr::Index { object, index, .. } => self.estimate_index_cost(object, index),
             Expr::FieldAccess { object, .. } => self.estimate_field_access_cost(object),
-            Expr::Block { stmts, .. } => self.estimate_block_cost(stmts),
-            Expr::If { condition, then_branch, else_branch, .. } => {
-                self.estimate_if_cost(condition, then_branch, else_branch.as_deref())
+            Expr::Cast { expr, .. } | Expr::Parenthesized { expr, .. } | Expr::Try { expr, .. }
+            | Expr::Await { expr, .. } => self.estimate_expression_cost(expr),
+            Expr::Reference { expr, .. } => self.estimate_unary_cost(&UnaryOp::AddressOf, expr),
+            Expr::Dereference { expr, .. } => self.estimate_unary_cost(&UnaryOp::Dereference, expr),
+            Expr::Box { expr, .. } => self.estimate_box_cost(expr),
+            Expr::Block { statements, trailing_expr, .. } => {
+                self.estimate_block_cost(statements, trailing_expr.as_deref())
             }
-            Expr::While { condition, body, .. } => self.estimate_while_cost(condition, body),
-            Expr::For { .. } => self.estimate_for_cost(), // TODO: Implement properly
-            Expr::Loop { body, .. } => self.estimate_loop_cost(body),
-            Expr::Match { .. } => self.estimate_match_cost(), // TODO: Implement properly
-            Expr::Return { value, .. } => {
-                let mut cost = PerformanceCost {
-                    cycles: Some(self.cost_model.return_cost),
-                    ..PerformanceCost::zero()
-                };
-                if let Some(val) = value {
-                    cost += self.estimate_expression_cost(val);
-                }
-                cost
+            Expr::If { condition, then_block, else_block, .. } => {
+                let then_cost = self.estimate_expression_cost(then_block);
+                let else_cost = else_block.as_deref().map(|e| self.estimate_expression_cost(e));
+                self.estimate_if_cost(condition, then_cost, else_cost)
             }
+            Expr::While { condition, body, .. } => {
+                self.estimate_while_cost(condition, self.estimate_expression_cost(body), None)
+            }
+            Expr::For { iterator, body, .. } => {
+                self.estimate_for_cost(iterator, self.estimate_expression_cost(body))
+            }
+            Expr::Loop { body, .. } => self.estimate_loop_cost(self.estimate_expression_cost(body)),
+            Expr::Match { expr, arms, .. } => self.estimate_match_cost(expr, arms),
+            Expr::Return { value, .. } => self.estimate_return_cost(value.as_deref()),
             Expr::Break { .. } | Expr::Continue { .. } => PerformanceCost {
                 cycles: Some(self.cost_model.control_flow_cost),
                 ..PerformanceCost::zero()
             },
             Expr::Array { elements, .. } => self.estimate_array_cost(elements),
-            Expr::ArrayRepeat { element, size, .. } => self.estimate_array_repeat_cost(element, size),
+            Expr::ArrayRepeat { value, count, .. } => self.estimate_array_repeat_cost(value, count),
             Expr::Tuple { elements, .. } => self.estimate_tuple_cost(elements),
-            Expr::Struct { .. } => self.estimate_struct_cost(), // TODO: Implement properly
-            Expr::Closure { .. } => self.estimate_closure_cost(), // TODO: Implement properly
+            Expr::Range { start, end, .. } => {
+                let mut cost = PerformanceCost::zero();
+                for bound in [start, end].into_iter().flatten() {
+                    cost += self.estimate_expression_cost(bound);
+                }
+                cost
+            }
+            Expr::StructInit { fields, .. } => self.estimate_struct_cost(fields),
+            Expr::Closure { .. } => self.estimate_closure_cost(),
+            Expr::Macro { .. } => PerformanceCost::unknown(), // Unexpanded - cost unknown
         }
     }
 
@@ -68,18 +145,25 @@ impl CostEstimator {
     pub fn estimate_statement_cost(&self, stmt: &Stmt) -> PerformanceCost {
         match stmt {
             Stmt::Expression { expr, .. } => self.estimate_expression_cost(expr),
-            Stmt::Let { value, .. } => {
+            Stmt::Let { type_annotation, initializer, .. } => {
                 let mut cost = PerformanceCost {
                     cycles: Some(self.cost_model.assignment_cost),
-                    stack_bytes: Some(8), // Rough estimate for local variable
                     ..PerformanceCost::zero()
                 };
-                if let Some(init_expr) = value {
+                if let Some(init_expr) = initializer {
                     cost += self.estimate_expression_cost(init_expr);
                 }
+                // The binding's slot lives for the rest of the frame, unless it is
+                // too large for the stack
+                let binding_bytes = binding_size(type_annotation.as_ref(), initializer.as_ref());
+                if self.memory_policy.fits_on_stack(binding_bytes) {
+                    cost.stack_bytes = cost.stack_bytes.map(|bytes| bytes + binding_bytes);
+                } else {
+                    cost += self.heap_storage(u64::from(binding_bytes));
+                }
                 cost
             }
-            Stmt::Assignment { target, value, .. } => {
+            Stmt::Assignment { target, value, .. } | Stmt::CompoundAssignment { target, value, .. } => {
                 let mut cost = PerformanceCost {
                     cycles: Some(self.cost_model.assignment_cost),
                     ..PerformanceCost::zero()
@@ -88,9 +172,44 @@ impl CostEstimator {
                 cost += self.estimate_expression_cost(value);
                 cost
             }
+            Stmt::If { condition, then_block, else_block, .. } => {
+                let then_cost = self.estimate_block_cost(then_block, None);
+                let else_cost = else_block.as_deref().map(|s| self.estimate_statement_cost(s));
+                self.estimate_if_cost(condition, then_cost, else_cost)
+            }
+            Stmt::While { condition, body, .. } => {
+                self.estimate_while_cost(condition, self.estimate_block_cost(body, None), None)
+            }
+            Stmt::For { iterable, body, .. } => {
+                self.estimate_for_cost(iterable, self.estimate_block_cost(body, None))
+            }
+            Stmt::Loop { body, .. } => self.estimate_loop_cost(self.estimate_block_cost(body, None)),
+            Stmt::Match { expr, arms, .. } => self.estimate_match_cost(expr, arms),
+            Stmt::Return { expr, .. } => self.estimate_return_cost(expr.as_ref()),
+            Stmt::Break { .. } | Stmt::Continue { .. } => PerformanceCost {
+                cycles: Some(self.cost_model.control_flow_cost),
+                ..PerformanceCost::zero()
+            },
+            Stmt::Block { statements, .. } | Stmt::Region { body: statements, .. } => {
+                self.estimate_block_cost(statements, None)
+            }
+            // Nested items are analyzed on their own
+            Stmt::Item { .. } | Stmt::Empty { .. } => PerformanceCost::zero(),
         }
     }
 
+    /// Estimate cost of returning from a function
+    fn estimate_return_cost(&self, value: Option<&Expr>) -> PerformanceCost {
+        let mut cost = PerformanceCost {
+            cycles: Some(self.cost_model.return_cost),
+            ..PerformanceCost::zero()
+        };
+        if let Some(val) = value {
+            cost += self.estimate_expression_cost(val);
+        }
+        cost
+    }
+
     /// Estimate cost of a literal value
     fn estimate_literal_cost(&self, _literal: &Literal) -> PerformanceCost {
         // Loading constants is very cheap
@@ -106,7 +225,11 @@ impl CostEstimator {
         cost += self.estimate_expression_cost(right);
         
         // Add operation-specific cost
+        let is_float = is_float_literal(left) || is_float_literal(right);
         let op_cost = match op {
+            BinaryOp::Add | BinaryOp::Subtract if is_float => self.cost_model.float_arithmetic_cost,
+            BinaryOp::Multiply if is_float => self.cost_model.float_multiply_cost,
+            BinaryOp::Divide if is_float => self.cost_model.float_divide_cost,
             BinaryOp::Add | BinaryOp::Subtract => self.cost_model.arithmetic_cost,
             BinaryOp::Multiply => self.cost_model.multiply_cost,
             BinaryOp::Divide | BinaryOp::Modulo => self.cost_model.divide_cost,
@@ -139,23 +262,50 @@ impl CostEstimator {
     }
 
     /// Estimate cost of a function call
-    fn estimate_call_cost(&self, _func: &Expr, args: &[Expr]) -> PerformanceCost {
-        // Base function call overhead
+    ///
+    /// Calls to functions with a recorded cost include that cost; anything else is
+    /// treated as an unresolved call.
+    fn estimate_call_cost(&self, callee: &Expr, args: &[Expr]) -> PerformanceCost {
+        let callee_cost = match callee {
+            Expr::Identifier { name, .. } => self.function_costs.get(name),
+            _ => None,
+        };
+        let Some(callee_cost) = callee_cost else {
+            return self.estimate_unresolved_call_cost(args);
+        };
+
+        let mut cost = self.estimate_call_overhead(args);
+        // Stack frames nest, so the callee's frame adds to the call frame
+        let call_frame = cost.stack_bytes;
+        cost += callee_cost.clone();
+        cost.stack_bytes = match (call_frame, callee_cost.stack_bytes) {
+            (Some(frame), Some(callee_stack)) => Some(frame + callee_stack),
+            _ => None,
+        };
+        cost.has_contract = false;
+        cost
+    }
+
+    /// Estimate cost of a call whose callee cost is unknown (extern, unresolved or a method)
+    fn estimate_unresolved_call_cost(&self, args: &[Expr]) -> PerformanceCost {
+        let mut cost = self.estimate_call_overhead(args);
+        cost.cycles = None;
+        cost.memory_bytes = None;
+        cost.allocations = None;
+        cost.confidence *= UNCERTAIN_CONFIDENCE_FACTOR;
+        cost
+    }
+
+    /// Call overhead plus the cost of evaluating the arguments
+    fn estimate_call_overhead(&self, args: &[Expr]) -> PerformanceCost {
         let mut cost = PerformanceCost {
             cycles: Some(self.cost_model.function_call_cost),
             stack_bytes: Some(32), // Rough estimate for call frame
             ..PerformanceCost::zero()
         };
-
-        // Add cost of evaluating arguments
         for arg in args {
             cost += self.estimate_expression_cost(arg);
         }
-
-        // TODO: Look up actual function costs from symbol table
-        // For now, assume unknown cost for the function body
-        cost.confidence = 0.5; // Lower confidence for function calls
-
         cost
     }
 
@@ -178,98 +328,136 @@ impl CostEstimator {
         cost
     }
 
-    /// Estimate cost of a block expression
-    fn estimate_block_cost(&self, stmts: &[Stmt]) -> PerformanceCost {
+    /// Estimate cost of a block
+    ///
+    /// Let bindings stay live until the end of the block, so their slots add up; other
+    /// temporaries are released after their statement. `while` loops are bounded from
+    /// the statements before them where their counter starts at a constant.
+    fn estimate_block_cost(&self, stmts: &[Stmt], trailing_expr: Option<&Expr>) -> PerformanceCost {
         let mut total_cost = PerformanceCost::zero();
+        let mut frame_bytes = Some(0u32);
         
-        for stmt in stmts {
-            total_cost += self.estimate_statement_cost(stmt);
+        for (index, stmt) in stmts.iter().enumerate() {
+            let mut cost = match stmt {
+                Stmt::While { condition, body, .. } => self.estimate_while_cost(
+                    condition,
+                    self.estimate_block_cost(body, None),
+                    counter_loop_iterations(condition, body, &stmts[..index]),
+                ),
+                _ => self.estimate_statement_cost(stmt),
+            };
+            if matches!(stmt, Stmt::Let { .. }) {
+                frame_bytes = frame_bytes.zip(cost.stack_bytes).map(|(frame, bytes)| frame + bytes);
+                cost.stack_bytes = Some(0);
+            }
+            total_cost += cost;
+        }
+        if let Some(expr) = trailing_expr {
+            total_cost += self.estimate_expression_cost(expr);
         }
         
+        total_cost.stack_bytes = total_cost.stack_bytes.zip(frame_bytes).map(|(temporaries, frame)| temporaries + frame);
         total_cost
     }
 
     /// Estimate cost of an if expression
-    fn estimate_if_cost(&self, condition: &Expr, then_branch: &Expr, else_branch: Option<&Expr>) -> PerformanceCost {
+    fn estimate_if_cost(&self, condition: &Expr, then_cost: PerformanceCost, else_cost: Option<PerformanceCost>) -> PerformanceCost {
         let mut cost = self.estimate_expression_cost(condition);
         
-        // Add branch cost
-        cost.cycles = cost.cycles.map(|c| c + self.cost_model.branch_cost);
+        // Add branch cost, assuming the worst case of a misprediction
+        cost.cycles = cost.cycles.map(|c| c + self.cost_model.branch_cost + self.cost_model.branch_mispredict_cost);
         
         // For if expressions, we take the maximum of both branches (worst case)
-        let then_cost = self.estimate_expression_cost(then_branch);
-        let else_cost = else_branch.map(|e| self.estimate_expression_cost(e))
-            .unwrap_or_else(PerformanceCost::zero);
-        
-        // Take the maximum of both branches for worst-case estimation
-        let branch_cost = PerformanceCost {
-            cycles: match (then_cost.cycles, else_cost.cycles) {
-                (Some(a), Some(b)) => Some(a.max(b)),
-                (Some(a), None) | (None, Some(a)) => Some(a),
-                (None, None) => None,
-            },
-            memory_bytes: match (then_cost.memory_bytes, else_cost.memory_bytes) {
-                (Some(a), Some(b)) => Some(a.max(b)),
-                (Some(a), None) | (None, Some(a)) => Some(a),
-                (None, None) => None,
-            },
-            allocations: match (then_cost.allocations, else_cost.allocations) {
-                (Some(a), Some(b)) => Some(a.max(b)),
-                (Some(a), None) | (None, Some(a)) => Some(a),
-                (None, None) => None,
-            },
-            stack_bytes: match (then_cost.stack_bytes, else_cost.stack_bytes) {
-                (Some(a), Some(b)) => Some(a.max(b)),
-                (Some(a), None) | (None, Some(a)) => Some(a),
-                (None, None) => None,
-            },
-            has_contract: then_cost.has_contract || else_cost.has_contract,
-            confidence: then_cost.confidence.min(else_cost.confidence),
-        };
-        
-        cost.add(&branch_cost)
+        let else_cost = else_cost.unwrap_or_else(PerformanceCost::zero);
+        cost.add(&worst_case(&then_cost, &else_cost))
     }
 
-    /// Estimate cost of a while loop
-    fn estimate_while_cost(&self, condition: &Expr, body: &Expr) -> PerformanceCost {
-        let condition_cost = self.estimate_expression_cost(condition);
-        let body_cost = self.estimate_expression_cost(body);
+    /// Estimate cost of a while loop running at most `iterations` times, if known
+    fn estimate_while_cost(&self, condition: &Expr, body_cost: PerformanceCost, iterations: Option<u64>) -> PerformanceCost {
+        // The condition runs once more than the body
+        let mut iteration_cost = self.estimate_expression_cost(condition);
+        iteration_cost += body_cost;
         
-        // For loops, we can't know iteration count, so we return unknown costs
-        // In a more sophisticated system, we might analyze loop bounds
-        PerformanceCost {
-            cycles: None, // Unknown - depends on iteration count
-            memory_bytes: body_cost.memory_bytes, // Memory usage per iteration
-            allocations: body_cost.allocations, // Allocations per iteration
-            stack_bytes: body_cost.stack_bytes, // Stack usage
-            has_contract: condition_cost.has_contract || body_cost.has_contract,
-            confidence: 0.0, // Very low confidence for loops
-        }
+        let mut cost = self.repeat_cost(&iteration_cost, iterations);
+        cost += self.estimate_expression_cost(condition);
+        cost
     }
 
-    /// Estimate cost of a for loop
-    fn estimate_for_cost(&self) -> PerformanceCost {
-        // TODO: Implement proper for loop analysis
-        PerformanceCost::unknown()
+    /// Estimate cost of a for loop, bounded when iterating over a constant range
+    fn estimate_for_cost(&self, iterable: &Expr, body_cost: PerformanceCost) -> PerformanceCost {
+        let mut cost = self.estimate_expression_cost(iterable);
+        cost += self.repeat_cost(&body_cost, constant_range_length(iterable));
+        cost
     }
 
-    /// Estimate cost of an infinite loop
-    fn estimate_loop_cost(&self, _body: &Expr) -> PerformanceCost {
-        // Infinite loops have infinite cost
-        PerformanceCost::unknown()
+    /// Estimate cost of an infinite loop, which only ends through `break` or `return`
+    fn estimate_loop_cost(&self, body_cost: PerformanceCost) -> PerformanceCost {
+        self.repeat_cost(&body_cost, None)
     }
 
-    /// Estimate cost of a match expression
-    fn estimate_match_cost(&self) -> PerformanceCost {
-        // TODO: Implement proper match analysis
-        PerformanceCost::unknown()
+    /// Cost of running `body_cost` `iterations` times, or an unknown number of times
+    ///
+    /// Each iteration also pays for the loop branch and counter update. Stack usage does
+    /// not grow with the iteration count.
+    fn repeat_cost(&self, body_cost: &PerformanceCost, iterations: Option<u64>) -> PerformanceCost {
+        let per_iteration_cycles = body_cost.cycles
+            .map(|cycles| cycles + self.cost_model.branch_cost + self.cost_model.arithmetic_cost);
+        match iterations {
+            Some(iterations) => PerformanceCost {
+                cycles: per_iteration_cycles.map(|cycles| cycles * iterations),
+                memory_bytes: body_cost.memory_bytes.map(|bytes| bytes * iterations),
+                allocations: body_cost.allocations.map(|count| count * iterations as u32),
+                stack_bytes: body_cost.stack_bytes,
+                has_contract: body_cost.has_contract,
+                confidence: body_cost.confidence,
+                complexity: body_cost.complexity,
+                complexity_loop: body_cost.complexity_loop,
+            },
+            // Anything the body does may happen any number of times
+            None => PerformanceCost {
+                cycles: None,
+                memory_bytes: body_cost.memory_bytes.filter(|&bytes| bytes == 0),
+                allocations: body_cost.allocations.filter(|&count| count == 0),
+                stack_bytes: body_cost.stack_bytes,
+                has_contract: body_cost.has_contract,
+                confidence: body_cost.confidence * UNCERTAIN_CONFIDENCE_FACTOR,
+                // The loop bound is unknown here; `estimate_complexity` classifies it
+                complexity: None,
+                complexity_loop: None,
+            },
+        }
+    }
+
+    /// Estimate cost of a match: the scrutinee, one branch per arm tested, one
+    /// misprediction and the worst arm
+    fn estimate_match_cost(&self, scrutinee: &Expr, arms: &[MatchArm]) -> PerformanceCost {
+        let mut cost = self.estimate_expression_cost(scrutinee);
+        cost.cycles = cost.cycles
+            .map(|c| c + self.cost_model.branch_cost * arms.len() as u64 + self.cost_model.branch_mispredict_cost);
+        
+        let mut worst_arm: Option<PerformanceCost> = None;
+        for arm in arms {
+            let mut arm_cost = self.estimate_expression_cost(&arm.body);
+            if let Some(guard) = &arm.guard {
+                arm_cost += self.estimate_expression_cost(guard);
+            }
+            worst_arm = Some(match worst_arm {
+                Some(worst) => worst_case(&worst, &arm_cost),
+                None => arm_cost,
+            });
+        }
+        
+        match worst_arm {
+            Some(worst_arm) => cost.add(&worst_arm),
+            None => cost,
+        }
     }
 
     /// Estimate cost of array creation
     fn estimate_array_cost(&self, elements: &[Expr]) -> PerformanceCost {
         let mut cost = PerformanceCost {
             cycles: Some(self.cost_model.allocation_cost), // Array allocation
-            memory_bytes: Some(elements.len() as u64 * 8), // Rough estimate
+            memory_bytes: Some(elements.len() as u64 * DEFAULT_VALUE_BYTES as u64),
             allocations: Some(1),
             ..PerformanceCost::zero()
         };
@@ -282,24 +470,45 @@ impl CostEstimator {
         cost
     }
 
-    /// Estimate cost of array repeat
-    fn estimate_array_repeat_cost(&self, element: &Expr, _size: &Expr) -> PerformanceCost {
-        let mut cost = self.estimate_expression_cost(element);
-        
-        // Array repeat involves allocation and potentially copying
+    /// Estimate cost of `[value; count]`: the value is evaluated once and stored `count` times
+    fn estimate_array_repeat_cost(&self, value: &Expr, count: &Expr) -> PerformanceCost {
+        let length = count.fold_integer(&|_| None).and_then(|length| u64::try_from(length).ok());
+        let mut cost = PerformanceCost {
+            cycles: length.map(|length| self.cost_model.allocation_cost + length * self.cost_model.memory_access_cost),
+            memory_bytes: length.map(|length| length * DEFAULT_VALUE_BYTES as u64),
+            allocations: Some(1),
+            ..PerformanceCost::zero()
+        };
+        cost += self.estimate_expression_cost(value);
+        cost
+    }
+
+    /// Estimate cost of boxing a value onto the heap
+    fn estimate_box_cost(&self, value: &Expr) -> PerformanceCost {
+        let mut cost = self.estimate_expression_cost(value);
         cost.cycles = cost.cycles.map(|c| c + self.cost_model.allocation_cost);
-        cost.allocations = Some(cost.allocations.unwrap_or(0) + 1);
-        
-        // TODO: Analyze size expression for better estimates
-        cost.confidence = 0.5; // Lower confidence without size analysis
+        cost.memory_bytes = cost.memory_bytes.map(|bytes| bytes + DEFAULT_VALUE_BYTES as u64);
+        cost.allocations = cost.allocations.map(|a| a + 1);
         cost
     }
 
+    /// Cost of spilling a value of `bytes` too large for the stack to the heap
+    fn heap_storage(&self, bytes: u64) -> PerformanceCost {
+        PerformanceCost {
+            cycles: Some(self.cost_model.allocation_cost),
+            memory_bytes: Some(bytes),
+            allocations: Some(1),
+            ..PerformanceCost::zero()
+        }
+    }
+
     /// Estimate cost of tuple creation
     fn estimate_tuple_cost(&self, elements: &[Expr]) -> PerformanceCost {
-        let mut cost = PerformanceCost {
-            stack_bytes: Some(elements.len() as u32 * 8), // Rough estimate
-            ..PerformanceCost::zero()
+        let tuple_bytes = elements.len() as u32 * DEFAULT_VALUE_BYTES; // Rough estimate
+        let mut cost = if self.memory_policy.fits_on_stack(tuple_bytes) {
+            PerformanceCost { stack_bytes: Some(tuple_bytes), ..PerformanceCost::zero() }
+        } else {
+            self.heap_storage(u64::from(tuple_bytes))
         };
 
         // Add cost of evaluating each element
@@ -310,22 +519,54 @@ impl CostEstimator {
         cost
     }
 
-    /// Estimate cost of struct creation
-    fn estimate_struct_cost(&self) -> PerformanceCost {
-        // TODO: Implement proper struct analysis
-        PerformanceCost {
-            cycles: Some(10), // Rough estimate
-            stack_bytes: Some(32), // Rough estimate
+    /// Estimate cost of struct creation: one store per field into its 8-byte slot
+    ///
+    /// Structs too large for the stack are allocated on the heap instead.
+    fn estimate_struct_cost(&self, fields: &[crate::ast::FieldInit]) -> PerformanceCost {
+        let struct_bytes = fields.len().max(1) as u32 * DEFAULT_VALUE_BYTES;
+        let stores = PerformanceCost {
+            cycles: Some(fields.len() as u64 * self.cost_model.memory_access_cost),
             ..PerformanceCost::zero()
+        };
+        let mut cost = if self.memory_policy.fits_on_stack(struct_bytes) {
+            PerformanceCost { memory_bytes: Some(u64::from(struct_bytes)), stack_bytes: Some(struct_bytes), ..stores }
+        } else {
+            stores + self.heap_storage(u64::from(struct_bytes))
+        };
+        for value in fields.iter().filter_map(|field| field.value.as_ref()) {
+            cost += self.estimate_expression_cost(value);
+        }
+        cost
+    }
+
+    /// Infer the asymptotic running time of a function body in its parameters
+    ///
+    /// Loops over constant ranges, and loops whose bounds do not depend on a
+    /// parameter, take constant time. Loops bounded by a parameter (or a value
+    /// derived from one) contribute a factor of n, or of log n when their counter
+    /// halves or doubles each iteration. Calls add the recorded complexity of the
+    /// callee; calls outside the module are assumed constant time. Recursive
+    /// functions must be excluded by the caller (see `recursive_functions`).
+    pub fn estimate_complexity(&self, params: &[Parameter], body: &Expr) -> ComplexityEstimate {
+        let mut walker = ComplexityWalker {
+            estimator: self,
+            size_variables: params.iter().flat_map(|param| pattern_bindings(&param.pattern)).collect(),
+        };
+        walker.collect_size_variables(Node::Expr(body));
+        let nest = walker.node(Node::Expr(body));
+        ComplexityEstimate {
+            complexity: nest.map(|nest| nest.growth.class()),
+            dominant_loop: nest.and_then(|nest| nest.loop_span),
         }
     }
 
     /// Estimate cost of closure creation
+    ///
+    /// Closures capture nothing and compile to the address of a lifted function, so
+    /// creating one allocates nothing; the body is paid for where it is called.
     fn estimate_closure_cost(&self) -> PerformanceCost {
-        // TODO: Implement proper closure analysis
         PerformanceCost {
-            cycles: Some(self.cost_model.allocation_cost),
-            allocations: Some(1), // Closure typically involves heap allocation
+            cycles: Some(1),
             ..PerformanceCost::zero()
         }
     }
@@ -335,4 +576,659 @@ impl Default for CostEstimator {
     fn default() -> Self {
         Self::new(TargetArchitecture::X86_64)
     }
-} 
\ No newline at end of file
+} 
+/// Asymptotic running time inferred for a function body
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComplexityEstimate {
+    /// Smallest complexity class bounding the body, None when it cannot be inferred
+    pub complexity: Option<BigOComplexity>,
+    /// Outermost loop of the nest that determines the complexity
+    pub dominant_loop: Option<Span>,
+}
+
+/// Functions that can call themselves, directly or through other functions
+pub fn recursive_functions(functions: &[(InternedString, &Expr)]) -> HashSet<InternedString> {
+    let call_graph: HashMap<InternedString, HashSet<InternedString>> = functions.iter()
+        .map(|(name, body)| {
+            let mut callees = HashSet::new();
+            collect_callees(Node::Expr(body), &mut callees);
+            (*name, callees)
+        })
+        .collect();
+
+    let reaches_itself = |start: InternedString| {
+        let mut visited = HashSet::new();
+        let mut pending: Vec<_> = call_graph.get(&start).into_iter().flatten().copied().collect();
+        while let Some(name) = pending.pop() {
+            if name == start {
+                return true;
+            }
+            if visited.insert(name) {
+                pending.extend(call_graph.get(&name).into_iter().flatten().copied());
+            }
+        }
+        false
+    };
+
+    functions.iter()
+        .map(|(name, _)| *name)
+        .filter(|name| reaches_itself(*name))
+        .collect()
+}
+
+fn collect_callees(node: Node, callees: &mut HashSet<InternedString>) {
+    if let Node::Expr(Expr::Call { callee, .. }) = node {
+        if let Expr::Identifier { name, .. } = callee.as_ref() {
+            callees.insert(*name);
+        }
+    }
+    for child in node.children() {
+        collect_callees(child, callees);
+    }
+}
+
+/// Growth rate n^degree * log^log_power n, or exponential
+///
+/// Field order makes the derived ordering match asymptotic order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Growth {
+    exponential: bool,
+    degree: u32,
+    log_power: u32,
+}
+
+impl Growth {
+    const CONSTANT: Growth = Growth { exponential: false, degree: 0, log_power: 0 };
+    const LOGARITHMIC: Growth = Growth { exponential: false, degree: 0, log_power: 1 };
+    const LINEAR: Growth = Growth { exponential: false, degree: 1, log_power: 0 };
+
+    fn of(complexity: BigOComplexity) -> Self {
+        let (degree, log_power) = match complexity {
+            BigOComplexity::Constant => (0, 0),
+            BigOComplexity::Logarithmic => (0, 1),
+            BigOComplexity::Linear => (1, 0),
+            BigOComplexity::Linearithmic => (1, 1),
+            BigOComplexity::Quadratic => (2, 0),
+            BigOComplexity::Cubic => (3, 0),
+            BigOComplexity::Exponential => return Growth { exponential: true, degree: 0, log_power: 0 },
+        };
+        Growth { exponential: false, degree, log_power }
+    }
+
+    /// Growth of running `self` once per step of `other`
+    fn times(self, other: Growth) -> Self {
+        Growth {
+            exponential: self.exponential || other.exponential,
+            degree: self.degree + other.degree,
+            log_power: self.log_power + other.log_power,
+        }
+    }
+
+    /// Smallest complexity class bounding this growth
+    ///
+    /// Anything past cubic is only bounded by the exponential class.
+    fn class(self) -> BigOComplexity {
+        if self.exponential {
+            return BigOComplexity::Exponential;
+        }
+        match (self.degree, self.log_power) {
+            (0, 0) => BigOComplexity::Constant,
+            (0, 1) => BigOComplexity::Logarithmic,
+            (0, _) | (1, 0) => BigOComplexity::Linear,
+            (1, 1) => BigOComplexity::Linearithmic,
+            (1, _) | (2, 0) => BigOComplexity::Quadratic,
+            (2, _) | (3, 0) => BigOComplexity::Cubic,
+            _ => BigOComplexity::Exponential,
+        }
+    }
+}
+
+/// Growth of a piece of code, with the outermost loop of its dominant nest
+#[derive(Debug, Clone, Copy)]
+struct Nest {
+    growth: Growth,
+    loop_span: Option<Span>,
+}
+
+impl Nest {
+    const CONSTANT: Nest = Nest { growth: Growth::CONSTANT, loop_span: None };
+
+    /// Code running `self` then `other` grows like the faster of the two
+    fn then(self, other: Nest) -> Nest {
+        if other.growth > self.growth { other } else { self }
+    }
+
+    /// Nest of the loop at `span` running `self` `iterations` times
+    fn repeated(self, iterations: Growth, span: Span) -> Nest {
+        if iterations == Growth::CONSTANT {
+            return self;
+        }
+        Nest { growth: self.growth.times(iterations), loop_span: Some(span) }
+    }
+}
+
+/// Expression or statement, for walking function bodies uniformly
+#[derive(Clone, Copy)]
+enum Node<'e> {
+    Expr(&'e Expr),
+    Stmt(&'e Stmt),
+}
+
+impl<'e> Node<'e> {
+    /// Nodes evaluated as part of this one
+    ///
+    /// Closure bodies and nested items do not run where they are written, so they
+    /// are left out.
+    fn children(self) -> Vec<Node<'e>> {
+        let mut exprs: Vec<&Expr> = Vec::new();
+        let mut stmts: &[Stmt] = &[];
+        let mut stmt: Option<&Stmt> = None;
+        match self {
+            Node::Expr(expr) => match expr {
+                Expr::Literal { .. } | Expr::Identifier { .. } | Expr::Path { .. } | Expr::Continue { .. }
+                | Expr::Closure { .. } | Expr::Macro { .. } => {}
+                Expr::Binary { left, right, .. } => exprs.extend([left.as_ref(), right.as_ref()]),
+                Expr::Unary { expr, .. } | Expr::Cast { expr, .. } | Expr::Parenthesized { expr, .. }
+                | Expr::Box { expr, .. } | Expr::Reference { expr, .. } | Expr::Dereference { expr, .. }
+                | Expr::Try { expr, .. } | Expr::Await { expr, .. } => exprs.push(expr),
+                Expr::Call { callee, args, .. } => {
+                    exprs.push(callee);
+                    exprs.extend(args);
+                }
+                Expr::MethodCall { receiver, args, .. } => {
+                    exprs.push(receiver);
+                    exprs.extend(args);
+                }
+                Expr::FieldAccess { object, .. } => exprs.push(object),
+                Expr::Index { object, index, .. } => exprs.extend([object.as_ref(), index.as_ref()]),
+                Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => exprs.extend(elements),
+                Expr::ArrayRepeat { value, count, .. } => exprs.extend([value.as_ref(), count.as_ref()]),
+                Expr::StructInit { fields, .. } => exprs.extend(fields.iter().filter_map(|field| field.value.as_ref())),
+                Expr::Range { start, end, .. } => exprs.extend(start.iter().chain(end.iter()).map(|bound| bound.as_ref())),
+                Expr::Block { statements, trailing_expr, .. } => {
+                    stmts = statements;
+                    exprs.extend(trailing_expr.as_deref());
+                }
+                Expr::If { condition, then_block, else_block, .. } => {
+                    exprs.extend([condition.as_ref(), then_block.as_ref()]);
+                    exprs.extend(else_block.as_deref());
+                }
+                Expr::Match { expr, arms, .. } => {
+                    exprs.push(expr);
+                    for arm in arms {
+                        exprs.extend(arm.guard.as_ref());
+                        exprs.push(&arm.body);
+                    }
+                }
+                Expr::Loop { body, .. } => exprs.push(body),
+                Expr::While { condition, body, .. } => exprs.extend([condition.as_ref(), body.as_ref()]),
+                Expr::For { iterator, body, .. } => exprs.extend([iterator.as_ref(), body.as_ref()]),
+                Expr::Break { value, .. } | Expr::Return { value, .. } => exprs.extend(value.as_deref()),
+            },
+            Node::Stmt(statement) => match statement {
+                Stmt::Expression { expr, .. } => exprs.push(expr),
+                Stmt::Let { initializer, .. } => exprs.extend(initializer.as_ref()),
+                Stmt::Assignment { target, value, .. } | Stmt::CompoundAssignment { target, value, .. } => {
+                    exprs.extend([target, value]);
+                }
+                Stmt::If { condition, then_block, else_block, .. } => {
+                    exprs.push(condition);
+                    stmts = then_block;
+                    stmt = else_block.as_deref();
+                }
+                Stmt::While { condition, body, .. } => {
+                    exprs.push(condition);
+                    stmts = body;
+                }
+                Stmt::For { iterable, body, .. } => {
+                    exprs.push(iterable);
+                    stmts = body;
+                }
+                Stmt::Loop { body, .. } | Stmt::Block { statements: body, .. } | Stmt::Region { body, .. } => {
+                    stmts = body;
+                }
+                Stmt::Match { expr, arms, .. } => {
+                    exprs.push(expr);
+                    for arm in arms {
+                        exprs.extend(arm.guard.as_ref());
+                        exprs.push(&arm.body);
+                    }
+                }
+                Stmt::Break { expr, .. } | Stmt::Return { expr, .. } => exprs.extend(expr.as_ref()),
+                Stmt::Continue { .. } | Stmt::Item { .. } | Stmt::Empty { .. } => {}
+            },
+        }
+        exprs.into_iter().map(Node::Expr)
+            .chain(stmts.iter().chain(stmt).map(Node::Stmt))
+            .collect()
+    }
+
+    /// Whether any identifier in this node is one of `names`
+    fn mentions(self, names: &HashSet<InternedString>) -> bool {
+        match self {
+            Node::Expr(Expr::Identifier { name, .. }) => names.contains(name),
+            _ => self.children().into_iter().any(|child| child.mentions(names)),
+        }
+    }
+}
+
+/// A loop taken apart for complexity analysis
+struct LoopParts<'e> {
+    /// Evaluated once before the loop
+    header: Vec<Node<'e>>,
+    /// Evaluated on every iteration
+    body: Vec<Node<'e>>,
+    /// How the number of iterations grows
+    iterations: Growth,
+    span: Span,
+}
+
+/// Walks a function body, classifying how its running time grows
+struct ComplexityWalker<'a> {
+    estimator: &'a CostEstimator,
+    /// Parameters and the variables computed from them
+    size_variables: HashSet<InternedString>,
+}
+
+impl ComplexityWalker<'_> {
+    /// Mark every variable assigned from a size variable as a size variable too
+    fn collect_size_variables(&mut self, body: Node) {
+        loop {
+            let before = self.size_variables.len();
+            self.mark_derived(body);
+            if self.size_variables.len() == before {
+                break;
+            }
+        }
+    }
+
+    fn mark_derived(&mut self, node: Node) {
+        match node {
+            Node::Stmt(Stmt::Let { pattern, initializer: Some(value), .. })
+                if Node::Expr(value).mentions(&self.size_variables) =>
+            {
+                self.size_variables.extend(pattern_bindings(pattern));
+            }
+            Node::Stmt(Stmt::Assignment { target: Expr::Identifier { name, .. }, value, .. })
+            | Node::Stmt(Stmt::CompoundAssignment { target: Expr::Identifier { name, .. }, value, .. })
+                if Node::Expr(value).mentions(&self.size_variables) =>
+            {
+                self.size_variables.insert(*name);
+            }
+            _ => {}
+        }
+        for child in node.children() {
+            self.mark_derived(child);
+        }
+    }
+
+    /// Growth of `node`, None when it cannot be inferred
+    fn node(&self, node: Node) -> Option<Nest> {
+        if let Some(parts) = self.loop_parts(node) {
+            let header = self.sequence(&parts.header)?;
+            let body = self.sequence(&parts.body)?;
+            return Some(header.then(body.repeated(parts.iterations, parts.span)));
+        }
+
+        let nest = self.sequence(&node.children())?;
+        let Node::Expr(Expr::Call { callee, .. }) = node else {
+            return Some(nest);
+        };
+        let callee_cost = match callee.as_ref() {
+            Expr::Identifier { name, .. } => self.estimator.function_cost(name),
+            _ => None,
+        };
+        match callee_cost {
+            Some(cost) => {
+                let callee_nest = Nest { growth: Growth::of(cost.complexity?), loop_span: cost.complexity_loop };
+                Some(nest.then(callee_nest))
+            }
+            None => Some(nest),
+        }
+    }
+
+    /// Growth of running `nodes` one after another
+    fn sequence(&self, nodes: &[Node]) -> Option<Nest> {
+        nodes.iter().try_fold(Nest::CONSTANT, |nest, node| Some(nest.then(self.node(*node)?)))
+    }
+
+    /// Split a loop into what runs once, what runs per iteration and how often
+    fn loop_parts<'e>(&self, node: Node<'e>) -> Option<LoopParts<'e>> {
+        let stmts = |body: &'e [Stmt]| body.iter().map(Node::Stmt);
+        let parts = match node {
+            Node::Expr(Expr::For { iterator, body, span, .. }) => LoopParts {
+                header: vec![Node::Expr(iterator)],
+                body: vec![Node::Expr(body)],
+                iterations: self.for_growth(iterator),
+                span: *span,
+            },
+            Node::Stmt(Stmt::For { iterable, body, span, .. }) => LoopParts {
+                header: vec![Node::Expr(iterable)],
+                body: stmts(body).collect(),
+                iterations: self.for_growth(iterable),
+                span: *span,
+            },
+            // The condition is evaluated on every iteration
+            Node::Expr(Expr::While { condition, body, span, .. }) => LoopParts {
+                header: Vec::new(),
+                body: vec![Node::Expr(condition), Node::Expr(body)],
+                iterations: self.while_growth(condition, &[Node::Expr(body)]),
+                span: *span,
+            },
+            Node::Stmt(Stmt::While { condition, body, span, .. }) => {
+                let body: Vec<_> = stmts(body).collect();
+                LoopParts {
+                    header: Vec::new(),
+                    iterations: self.while_growth(condition, &body),
+                    body: std::iter::once(Node::Expr(condition)).chain(body).collect(),
+                    span: *span,
+                }
+            }
+            // Exit conditions of `loop` are not analyzed; assume the input bounds them
+            Node::Expr(Expr::Loop { body, span, .. }) => LoopParts {
+                header: Vec::new(),
+                body: vec![Node::Expr(body)],
+                iterations: Growth::LINEAR,
+                span: *span,
+            },
+            Node::Stmt(Stmt::Loop { body, span, .. }) => LoopParts {
+                header: Vec::new(),
+                body: stmts(body).collect(),
+                iterations: Growth::LINEAR,
+                span: *span,
+            },
+            _ => return None,
+        };
+        Some(parts)
+    }
+
+    /// How the iteration count of a `for` loop grows
+    fn for_growth(&self, iterable: &Expr) -> Growth {
+        if constant_range_length(iterable).is_none() && Node::Expr(iterable).mentions(&self.size_variables) {
+            Growth::LINEAR
+        } else {
+            Growth::CONSTANT
+        }
+    }
+
+    /// How the iteration count of a `while` loop grows
+    fn while_growth(&self, condition: &Expr, body: &[Node]) -> Growth {
+        if !Node::Expr(condition).mentions(&self.size_variables) {
+            return Growth::CONSTANT;
+        }
+        let mut counters = HashSet::new();
+        collect_identifiers(Node::Expr(condition), &mut counters);
+        if body.iter().any(|node| scales_counter(*node, &counters)) {
+            Growth::LOGARITHMIC
+        } else {
+            Growth::LINEAR
+        }
+    }
+}
+
+fn collect_identifiers(node: Node, names: &mut HashSet<InternedString>) {
+    if let Node::Expr(Expr::Identifier { name, .. }) = node {
+        names.insert(*name);
+    }
+    for child in node.children() {
+        collect_identifiers(child, names);
+    }
+}
+
+/// Whether `node` halves or doubles one of `counters`, as in `i = i / 2` or `i *= 2`
+fn scales_counter(node: Node, counters: &HashSet<InternedString>) -> bool {
+    let scales = match node {
+        Node::Stmt(Stmt::CompoundAssignment { target: Expr::Identifier { name, .. }, op, value, .. }) => {
+            counters.contains(name) && is_scaling(op, value)
+        }
+        Node::Stmt(Stmt::Assignment {
+            target: Expr::Identifier { name, .. },
+            value: Expr::Binary { left, op, right, .. },
+            ..
+        }) => {
+            counters.contains(name)
+                && matches!(left.as_ref(), Expr::Identifier { name: operand, .. } if operand == name)
+                && is_scaling(op, right)
+        }
+        _ => false,
+    };
+    scales || node.children().into_iter().any(|child| scales_counter(child, counters))
+}
+
+/// Whether applying `op` with `operand` scales a value geometrically
+fn is_scaling(op: &BinaryOp, operand: &Expr) -> bool {
+    match (op, constant_integer(operand)) {
+        (BinaryOp::Divide | BinaryOp::Multiply, Some(factor)) => factor >= 2,
+        (BinaryOp::RightShift | BinaryOp::LeftShift, Some(shift)) => shift >= 1,
+        _ => false,
+    }
+}
+
+/// Iterations of `while counter < limit` (or `<=`, `>`, `>=`, either way round)
+///
+/// Known when `preceding` last sets the counter to a constant, the limit is a
+/// constant, and the body steps the counter by a constant in a statement of its
+/// own, assigning it nowhere else and never skipping the step with `continue`.
+/// The count is an upper bound, since `break` may end the loop sooner.
+fn counter_loop_iterations(condition: &Expr, body: &[Stmt], preceding: &[Stmt]) -> Option<u64> {
+    let Expr::Binary { left, op, right, .. } = condition else {
+        return None;
+    };
+    let (counter, op, limit) = match (left.as_ref(), right.as_ref()) {
+        (Expr::Identifier { name, .. }, limit) => (*name, op.clone(), signed_constant(limit)?),
+        (limit, Expr::Identifier { name, .. }) => {
+            let flipped = match op {
+                BinaryOp::Less => BinaryOp::Greater,
+                BinaryOp::LessEqual => BinaryOp::GreaterEqual,
+                BinaryOp::Greater => BinaryOp::Less,
+                BinaryOp::GreaterEqual => BinaryOp::LessEqual,
+                _ => return None,
+            };
+            (*name, flipped, signed_constant(limit)?)
+        }
+        _ => return None,
+    };
+    let start = counter_start(counter, preceding)?;
+
+    let body_nodes = || body.iter().map(Node::Stmt);
+    if body_nodes().map(|node| assignments_to(node, counter)).sum::<usize>() != 1
+        || body_nodes().any(continues)
+    {
+        return None;
+    }
+    let step = body.iter().find_map(|stmt| counter_step(stmt, counter))?;
+
+    // Distance to cover and progress per iteration, both positive for a loop that ends
+    let (distance, progress) = match op {
+        BinaryOp::Less => (limit - start, step),
+        BinaryOp::LessEqual => (limit - start + 1, step),
+        BinaryOp::Greater => (start - limit, -step),
+        BinaryOp::GreaterEqual => (start - limit + 1, -step),
+        _ => return None,
+    };
+    if distance <= 0 {
+        return Some(0);
+    }
+    if progress <= 0 {
+        return None;
+    }
+    u64::try_from((distance + progress - 1) / progress).ok()
+}
+
+/// Constant value `counter` was last set to by `stmts`, None if it is not a constant
+fn counter_start(counter: InternedString, stmts: &[Stmt]) -> Option<i128> {
+    let last = stmts.iter().rev().find(|stmt| assignments_to(Node::Stmt(stmt), counter) > 0)?;
+    match last {
+        Stmt::Let { pattern: Pattern::Identifier { name, .. }, initializer: Some(value), .. }
+        | Stmt::Assignment { target: Expr::Identifier { name, .. }, value, .. } if *name == counter => {
+            signed_constant(value)
+        }
+        _ => None,
+    }
+}
+
+/// Constant amount `stmt` adds to `counter`, as in `i += 2` or `i = i - 1`
+fn counter_step(stmt: &Stmt, counter: InternedString) -> Option<i128> {
+    let (op, amount) = match stmt {
+        Stmt::CompoundAssignment { target: Expr::Identifier { name, .. }, op, value, .. } if *name == counter => {
+            (op, value)
+        }
+        Stmt::Assignment {
+            target: Expr::Identifier { name, .. },
+            value: Expr::Binary { left, op, right, .. },
+            ..
+        } if *name == counter && matches!(left.as_ref(), Expr::Identifier { name: operand, .. } if *operand == counter) => {
+            (op, right.as_ref())
+        }
+        _ => return None,
+    };
+    match op {
+        BinaryOp::Add => signed_constant(amount),
+        BinaryOp::Subtract => signed_constant(amount).map(|amount| -amount),
+        _ => None,
+    }
+}
+
+/// Number of statements in `node` that assign or rebind `name`
+fn assignments_to(node: Node, name: InternedString) -> usize {
+    let assigns = match node {
+        Node::Stmt(Stmt::Assignment { target: Expr::Identifier { name: target, .. }, .. })
+        | Node::Stmt(Stmt::CompoundAssignment { target: Expr::Identifier { name: target, .. }, .. }) => *target == name,
+        Node::Stmt(Stmt::Let { pattern, .. }) => pattern_bindings(pattern).contains(&name),
+        _ => false,
+    };
+    usize::from(assigns) + node.children().into_iter().map(|child| assignments_to(child, name)).sum::<usize>()
+}
+
+/// Whether `node` contains a `continue`
+fn continues(node: Node) -> bool {
+    matches!(node, Node::Expr(Expr::Continue { .. }) | Node::Stmt(Stmt::Continue { .. }))
+        || node.children().into_iter().any(continues)
+}
+
+/// Variables bound by a pattern
+fn pattern_bindings(pattern: &Pattern) -> Vec<InternedString> {
+    match pattern {
+        Pattern::Identifier { name, .. } => vec![*name],
+        Pattern::Tuple { patterns, .. } | Pattern::Array { patterns, .. } | Pattern::Or { patterns, .. } => {
+            patterns.iter().flat_map(pattern_bindings).collect()
+        }
+        Pattern::Enum { patterns: Some(patterns), .. } => patterns.iter().flat_map(pattern_bindings).collect(),
+        Pattern::Struct { fields, .. } => fields.iter()
+            .flat_map(|field| match &field.pattern {
+                Some(pattern) => pattern_bindings(pattern),
+                None => vec![field.name],
+            })
+            .collect(),
+        Pattern::Reference { pattern, .. } => pattern_bindings(pattern),
+        _ => Vec::new(),
+    }
+}
+
+/// Worst case of two alternative costs, taking the maximum of every figure
+fn worst_case(a: &PerformanceCost, b: &PerformanceCost) -> PerformanceCost {
+    fn max<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+        a.zip(b).map(|(a, b)| a.max(b))
+    }
+    let (complexity, complexity_loop) = worse_complexity(a, b);
+    PerformanceCost {
+        cycles: max(a.cycles, b.cycles),
+        memory_bytes: max(a.memory_bytes, b.memory_bytes),
+        allocations: max(a.allocations, b.allocations),
+        stack_bytes: max(a.stack_bytes, b.stack_bytes),
+        has_contract: a.has_contract || b.has_contract,
+        confidence: a.confidence.min(b.confidence),
+        complexity,
+        complexity_loop,
+    }
+}
+
+/// The worse of two costs' complexities, with the loop responsible for it
+///
+/// Unknown if either is unknown.
+pub(crate) fn worse_complexity(a: &PerformanceCost, b: &PerformanceCost) -> (Option<BigOComplexity>, Option<Span>) {
+    match (a.complexity, b.complexity) {
+        (Some(a_complexity), Some(b_complexity)) if b_complexity > a_complexity => (Some(b_complexity), b.complexity_loop),
+        (Some(a_complexity), Some(_)) => (Some(a_complexity), a.complexity_loop),
+        _ => (None, None),
+    }
+}
+
+/// Number of iterations of a range with constant integer bounds, like `0..10`
+fn constant_range_length(iterable: &Expr) -> Option<u64> {
+    match iterable {
+        Expr::Range { start, end: Some(end), inclusive, .. } => {
+            let start = match start {
+                Some(start) => constant_integer(start)?,
+                None => 0,
+            };
+            let end = constant_integer(end)?;
+            let length = end.saturating_sub(start);
+            Some(if *inclusive { length + 1 } else { length })
+        }
+        Expr::Parenthesized { expr, .. } => constant_range_length(expr),
+        _ => None,
+    }
+}
+
+/// Value of a non-negative integer literal
+fn constant_integer(expr: &Expr) -> Option<u64> {
+    let Expr::Literal { literal, .. } = expr else {
+        return None;
+    };
+    literal.integer_value().and_then(|value| u64::try_from(value).ok())
+}
+
+/// Value of an integer literal, possibly negated
+fn signed_constant(expr: &Expr) -> Option<i128> {
+    match expr {
+        Expr::Unary { op: UnaryOp::Negate, expr, .. } => signed_constant(expr).map(|value| -value),
+        Expr::Parenthesized { expr, .. } => signed_constant(expr),
+        _ => constant_integer(expr).map(i128::from),
+    }
+}
+
+/// Whether `expr` is a floating-point literal, marking its operation as a float op
+///
+/// Without type information, only literal operands identify float arithmetic.
+fn is_float_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { literal: Literal::Float { .. }, .. } => true,
+        Expr::Parenthesized { expr, .. } => is_float_literal(expr),
+        _ => false,
+    }
+}
+
+/// Stack bytes of a let binding, from its annotation or else its initializer
+fn binding_size(type_annotation: Option<&Type>, initializer: Option<&Expr>) -> u32 {
+    match (type_annotation, initializer) {
+        (Some(ty), _) => type_size(ty),
+        (None, Some(Expr::Literal { literal, .. })) => match literal {
+            Literal::Bool(_) => 1,
+            Literal::Char(_) | Literal::Integer { .. } => 4,
+            _ => DEFAULT_VALUE_BYTES,
+        },
+        (None, Some(Expr::Tuple { elements, .. })) => elements.len() as u32 * DEFAULT_VALUE_BYTES,
+        _ => DEFAULT_VALUE_BYTES,
+    }
+}
+
+/// Size in bytes of a value of type `ty`
+fn type_size(ty: &Type) -> u32 {
+    match ty {
+        Type::Primitive { kind, .. } => match kind {
+            PrimitiveType::I8 | PrimitiveType::U8 | PrimitiveType::Bool => 1,
+            PrimitiveType::I16 | PrimitiveType::U16 => 2,
+            PrimitiveType::I32 | PrimitiveType::U32 | PrimitiveType::F32 | PrimitiveType::Char => 4,
+            PrimitiveType::I128 | PrimitiveType::U128 => 16,
+            PrimitiveType::Unit => 0,
+            _ => 8,
+        },
+        Type::Array { element_type, size, .. } => match size.fold_integer(&|_| None) {
+            Some(length) => type_size(element_type).saturating_mul(u32::try_from(length).unwrap_or(u32::MAX)),
+            None => DEFAULT_VALUE_BYTES,
+        },
+        Type::Tuple { types, .. } => types.iter().map(type_size).sum(),
+        _ => DEFAULT_VALUE_BYTES,
+    }
+}
@@ -6,23 +6,27 @@
 //! - Memory allocation strategy analysis
 //! - Runtime performance profiling (debug mode)
 
-use crate::ast::{
-    PerformanceContract, CpuBound, MemoryBound, AllocationBound, 
-    LatencyBound, StackBound, AllocationStrategy, Item, Module,
-    BigOComplexity, Span
-};
+use crate::ast::{PerformanceContract, Item, Module, Span, BigOComplexity};
+use crate::diagnostics::DiagnosticCode;
+use crate::codegen::MemoryPolicy;
+use crate::parser::StringInterner;
+use crate::semantic::EscapeAnalyzer;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 pub mod contracts;
 pub mod estimation;
 pub mod profiler;
 pub mod models;
+pub mod allocation;
 
-pub use contracts::ContractVerifier;
-pub use estimation::{CostEstimator, PerformanceCost};
-pub use profiler::PerformanceProfiler;
-pub use models::{CostModel, TargetArchitecture};
+pub use contracts::{ContractVerifier, ContractVerification};
+pub use estimation::{CostEstimator, ComplexityEstimate};
+pub use profiler::{PerformanceProfiler, EstimateComparison, FunctionSamples, TrackingAllocator, ALLOCATIONS};
+pub use models::{CostModel, CostModelError, TargetArchitecture};
+pub use allocation::{AllocationRecommendation, AllocationEscapes};
 
 /// Performance analysis results for a module
 #[derive(Debug, Clone)]
@@ -33,10 +37,49 @@ pub struct PerformanceAnalysis {
     pub violations: Vec<ContractViolation>,
     /// Performance warnings
     pub warnings: Vec<PerformanceWarning>,
+    /// Cheaper allocation strategies for functions that allocate
+    pub allocation_recommendations: Vec<AllocationRecommendation>,
     /// Analysis statistics
     pub stats: AnalysisStats,
 }
 
+impl PerformanceAnalysis {
+    /// Plain-text summary of the allocation recommendations
+    pub fn allocation_report(&self) -> String {
+        let total_savings: u64 = self.allocation_recommendations.iter()
+            .map(|recommendation| recommendation.cycles_saved_per_call)
+            .sum();
+
+        let mut report = format!(
+            "=== Bract Allocation Recommendations ===\n\
+             Functions Analyzed: {}\n\
+             Recommendations: {}\n\
+             Estimated Savings: {} cycles per call\n",
+            self.stats.functions_analyzed,
+            self.allocation_recommendations.len(),
+            total_savings
+        );
+
+        if !self.allocation_recommendations.is_empty() {
+            report.push_str("\nRecommendations:\n");
+        }
+        for recommendation in &self.allocation_recommendations {
+            report.push_str(&format!(
+                "  {}: {:?} -> {:?} ({} allocation{} of ~{} bytes, saves {} cycles per call)\n",
+                recommendation.function_name,
+                recommendation.current_strategy,
+                recommendation.recommended_strategy,
+                recommendation.allocations,
+                if recommendation.allocations == 1 { "" } else { "s" },
+                recommendation.average_bytes,
+                recommendation.cycles_saved_per_call
+            ));
+        }
+
+        report
+    }
+}
+
 /// Performance contract violation
 #[derive(Debug, Clone, PartialEq)]
 pub struct ContractViolation {
@@ -64,6 +107,23 @@ pub enum ViolationType {
     StackExceeded,
     NonDeterministic,
     NotWaitFree,
+    ComplexityExceeded,
+}
+
+impl ViolationType {
+    /// Stable diagnostic code, e.g. `E0401`
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            ViolationType::CpuExceeded => DiagnosticCode::CpuExceeded,
+            ViolationType::MemoryExceeded => DiagnosticCode::MemoryExceeded,
+            ViolationType::AllocationExceeded => DiagnosticCode::AllocationExceeded,
+            ViolationType::LatencyExceeded => DiagnosticCode::LatencyExceeded,
+            ViolationType::StackExceeded => DiagnosticCode::StackExceeded,
+            ViolationType::NonDeterministic => DiagnosticCode::NonDeterministic,
+            ViolationType::NotWaitFree => DiagnosticCode::NotWaitFree,
+            ViolationType::ComplexityExceeded => DiagnosticCode::ComplexityExceeded,
+        }
+    }
 }
 
 /// Performance warnings (non-breaking issues)
@@ -89,6 +149,19 @@ pub enum WarningType {
     UnverifiedExtern,
 }
 
+impl WarningType {
+    /// Stable diagnostic code, e.g. `W0201`
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            WarningType::MissingContract => DiagnosticCode::MissingContract,
+            WarningType::InaccurateEstimate => DiagnosticCode::InaccurateEstimate,
+            WarningType::SuboptimalAllocation => DiagnosticCode::SuboptimalAllocation,
+            WarningType::PotentialBottleneck => DiagnosticCode::PotentialBottleneck,
+            WarningType::UnverifiedExtern => DiagnosticCode::UnverifiedExtern,
+        }
+    }
+}
+
 /// Analysis statistics
 #[derive(Debug, Clone, Default)]
 pub struct AnalysisStats {
@@ -97,6 +170,10 @@ pub struct AnalysisStats {
     pub violations_found: usize,
     pub warnings_generated: usize,
     pub analysis_time: Duration,
+    /// Analysis stopped early because it ran past its time budget
+    pub timed_out: bool,
+    /// Analysis stopped early because its cancellation flag was set
+    pub cancelled: bool,
 }
 
 /// Main performance analyzer
@@ -107,46 +184,175 @@ pub struct PerformanceAnalyzer {
     contract_verifier: ContractVerifier,
     /// Target architecture
     target_arch: TargetArchitecture,
+    /// Longest `analyze_module` may run before giving up
+    time_budget: Option<Duration>,
+    /// Flag that, once set, stops `analyze_module` at the next function
+    cancel: Option<Arc<AtomicBool>>,
 }
 
 impl PerformanceAnalyzer {
     /// Create a new performance analyzer for the target architecture
     pub fn new(target_arch: TargetArchitecture) -> Self {
+        Self::with_cost_model(CostModel::for_architecture(target_arch))
+    }
+
+    /// Create a performance analyzer using a custom (e.g. calibrated) cost model
+    pub fn with_cost_model(cost_model: CostModel) -> Self {
+        let target_arch = cost_model.architecture;
         Self {
-            cost_estimator: CostEstimator::new(target_arch),
-            contract_verifier: ContractVerifier::new(),
+            cost_estimator: CostEstimator::with_model(cost_model.clone()),
+            contract_verifier: ContractVerifier::new().with_cost_model(cost_model),
             target_arch,
+            time_budget: None,
+            cancel: None,
         }
     }
 
+    /// Stop analysis once it has run for `budget`, marking the result as timed out
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Stop analysis once `cancel` is set, marking the result as cancelled
+    pub fn with_cancellation(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Estimate stack usage and recommend strategies with the memory policy code is
+    /// generated with, so both agree on which values are too large for the stack
+    pub fn with_memory_policy(mut self, policy: MemoryPolicy) -> Self {
+        self.cost_estimator = self.cost_estimator.with_memory_policy(policy);
+        self
+    }
+
+    /// Architecture the analysis targets
+    pub fn target_arch(&self) -> TargetArchitecture {
+        self.target_arch
+    }
+
     /// Analyze performance for an entire module
-    pub fn analyze_module(&mut self, module: &Module) -> PerformanceAnalysis {
+    ///
+    /// `interner` resolves function names for the analysis results and messages.
+    pub fn analyze_module(&mut self, module: &Module, interner: &StringInterner) -> PerformanceAnalysis {
         let start_time = std::time::Instant::now();
         
         let mut analysis = PerformanceAnalysis {
             function_costs: HashMap::new(),
             violations: Vec::new(),
             warnings: Vec::new(),
+            allocation_recommendations: Vec::new(),
             stats: AnalysisStats::default(),
         };
 
-        // Analyze each item in the module
-        for item in &module.items {
-            if let Item::Function { 
-                name, 
-                performance_contract,
+        let functions: Vec<_> = module.items.iter()
+            .filter_map(|item| match item {
+                Item::Function { name, params, performance_contract, allocation_strategy, body, span, .. } => {
+                    Some((name, params, performance_contract.as_ref(), *allocation_strategy, body.as_ref(), *span))
+                }
+                _ => None,
+            })
+            .collect();
+
+        let time_budget = self.time_budget;
+        let out_of_time = || time_budget.is_some_and(|budget| start_time.elapsed() > budget);
+        let cancel = self.cancel.clone();
+        let cancelled = || cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed));
+
+        let bodies: Vec<_> = functions.iter()
+            .filter_map(|(name, _, _, _, body, _)| Some((**name, (*body)?)))
+            .collect();
+        let recursive = estimation::recursive_functions(&bodies);
+
+        // Values that outlive their function must not be recommended for the stack
+        let mut escape_analyzer = EscapeAnalyzer::new();
+        escape_analyzer.analyze_module(module);
+        let escape_facts = escape_analyzer.into_escape_facts();
+
+        // Estimate every body until the costs settle, so callers include the cost of
+        // functions declared after them; recursive calls stay unknown
+        self.cost_estimator.clear_function_costs();
+        for _ in 0..=functions.len() {
+            let mut changed = false;
+            for (name, params, _, _, body, _) in &functions {
+                if cancelled() {
+                    analysis.stats.cancelled = true;
+                    break;
+                }
+                if out_of_time() {
+                    analysis.stats.timed_out = true;
+                    break;
+                }
+                let cost = match body {
+                    Some(body_expr) => {
+                        let mut cost = self.cost_estimator.estimate_expression_cost(body_expr);
+                        // The complexity of recursive functions is left unknown
+                        let complexity = if recursive.contains(name) {
+                            ComplexityEstimate { complexity: None, dominant_loop: None }
+                        } else {
+                            self.cost_estimator.estimate_complexity(params, body_expr)
+                        };
+                        cost.complexity = complexity.complexity;
+                        cost.complexity_loop = complexity.dominant_loop;
+                        cost
+                    }
+                    // External function - unknown cost
+                    None => PerformanceCost::unknown(),
+                };
+                if self.cost_estimator.function_cost(name) != Some(&cost) {
+                    self.cost_estimator.record_function_cost(**name, cost);
+                    changed = true;
+                }
+            }
+            if !changed || analysis.stats.timed_out || analysis.stats.cancelled {
+                break;
+            }
+        }
+
+        for (name, _, contract, allocation_strategy, body, span) in functions {
+            if analysis.stats.cancelled || cancelled() {
+                analysis.stats.cancelled = true;
+                break;
+            }
+            if analysis.stats.timed_out || out_of_time() {
+                analysis.stats.timed_out = true;
+                break;
+            }
+            let func_name = interner.resolve(*name)
+                .unwrap_or_else(|| format!("function_{}", name.id));
+            let estimated_cost = self.cost_estimator.function_cost(name)
+                .cloned()
+                .unwrap_or_else(PerformanceCost::unknown);
+
+            let recommendation = allocation::recommend_allocation_strategy(
+                &func_name,
+                span,
                 allocation_strategy,
-                body,
-                .. 
-            } = item {
-                self.analyze_function(
-                    name, 
-                    performance_contract.as_ref(),
-                    allocation_strategy.as_ref(),
-                    body.as_ref(),
-                    &mut analysis
-                );
+                &estimated_cost,
+                AllocationEscapes::within(span, &escape_facts),
+                self.cost_estimator.memory_policy(),
+            );
+            if recursive.contains(name) {
+                analysis.warnings.push(PerformanceWarning {
+                    function_name: func_name.clone(),
+                    warning_type: WarningType::PotentialBottleneck,
+                    message: format!("Function '{}' is recursive - its complexity cannot be inferred", func_name),
+                    span,
+                });
+            }
+
+            if let Some(recommendation) = recommendation {
+                analysis.warnings.push(PerformanceWarning {
+                    function_name: func_name.clone(),
+                    warning_type: WarningType::SuboptimalAllocation,
+                    message: recommendation.message(),
+                    span,
+                });
+                analysis.allocation_recommendations.push(recommendation);
             }
+
+            self.analyze_function(func_name, span, contract, body.is_some(), estimated_cost, &mut analysis);
         }
 
         // Update statistics
@@ -161,41 +367,38 @@ impl PerformanceAnalyzer {
         analysis
     }
 
-    /// Analyze a single function
+    /// Check a single function's estimated cost against its contract
     fn analyze_function(
         &mut self,
-        name: &crate::ast::InternedString,
+        func_name: String,
+        span: Span,
         contract: Option<&PerformanceContract>,
-        _allocation_strategy: Option<&AllocationStrategy>,
-        body: Option<&crate::ast::Expr>,
+        has_body: bool,
+        mut estimated_cost: PerformanceCost,
         analysis: &mut PerformanceAnalysis,
     ) {
-        // For now, use a placeholder function name
-        let func_name = format!("function_{}", name.id);
-
-        // Estimate performance cost from the function body
-        let estimated_cost = if let Some(body_expr) = body {
-            self.cost_estimator.estimate_expression_cost(body_expr)
-        } else {
-            // External function - unknown cost
-            PerformanceCost::unknown()
-        };
+        estimated_cost.has_contract = contract.is_some();
 
         // Verify contract if present
         if let Some(contract) = contract {
-            let violations = self.contract_verifier.verify_contract(
+            let verification = self.contract_verifier.verify_contract(
                 &func_name,
+                span,
                 contract,
                 &estimated_cost,
             );
-            analysis.violations.extend(violations);
-        } else if body.is_some() {
+            analysis.violations.extend(verification.violations);
+            analysis.warnings.extend(verification.warnings);
+        } else if has_body {
             // Missing contract warning
             analysis.warnings.push(PerformanceWarning {
                 function_name: func_name.clone(),
                 warning_type: WarningType::MissingContract,
-                message: "Function lacks performance contract - consider adding @guarantee annotation".to_string(),
-                span: Span::single(crate::lexer::Position::default()), // TODO: Get actual span
+                message: format!(
+                    "Function '{}' lacks a performance contract - consider adding a @performance annotation",
+                    func_name
+                ),
+                span,
             });
         }
 
@@ -224,6 +427,10 @@ pub struct PerformanceCost {
     pub has_contract: bool,
     /// Confidence level in the estimate (0.0 - 1.0)
     pub confidence: f32,
+    /// Asymptotic running time, None when it could not be inferred
+    pub complexity: Option<BigOComplexity>,
+    /// Outermost loop of the nest that determines `complexity`
+    pub complexity_loop: Option<Span>,
 }
 
 impl PerformanceCost {
@@ -236,6 +443,8 @@ impl PerformanceCost {
             stack_bytes: Some(0),
             has_contract: false,
             confidence: 1.0,
+            complexity: Some(BigOComplexity::Constant),
+            complexity_loop: None,
         }
     }
 
@@ -248,11 +457,14 @@ impl PerformanceCost {
             stack_bytes: None,
             has_contract: false,
             confidence: 0.0,
+            complexity: None,
+            complexity_loop: None,
         }
     }
 
     /// Add two costs together
     pub fn add(&self, other: &PerformanceCost) -> PerformanceCost {
+        let (complexity, complexity_loop) = estimation::worse_complexity(self, other);
         PerformanceCost {
             cycles: match (self.cycles, other.cycles) {
                 (Some(a), Some(b)) => Some(a + b),
@@ -272,6 +484,8 @@ impl PerformanceCost {
             },
             has_contract: self.has_contract || other.has_contract,
             confidence: self.confidence.min(other.confidence),
+            complexity,
+            complexity_loop,
         }
     }
 }
@@ -280,12 +494,12 @@ impl std::ops::Add for PerformanceCost {
     type Output = PerformanceCost;
 
     fn add(self, other: PerformanceCost) -> PerformanceCost {
-        self.add(&other)
+        PerformanceCost::add(&self, &other)
     }
 }
 
 impl std::ops::AddAssign for PerformanceCost {
     fn add_assign(&mut self, other: PerformanceCost) {
-        *self = self.add(&other);
+        *self = PerformanceCost::add(self, &other);
     }
 } 
\ No newline at end of file
@@ -2,6 +2,13 @@
 //!
 //! This module defines cost models for different target architectures,
 //! providing architecture-specific cycle counts and performance characteristics.
+//!
+//! Built-in weights can be recalibrated from a TOML file of `name = cycles`
+//! entries (see `CostModel::from_toml`), optionally starting from another
+//! architecture with `architecture = "arm64"`.
+
+use std::fmt;
+use std::path::Path;
 
 /// Supported target architectures
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -12,6 +19,63 @@ pub enum TargetArchitecture {
     WASM,
 }
 
+impl TargetArchitecture {
+    /// Parse an architecture name such as `x86_64` or `arm64`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "x86_64" => Some(TargetArchitecture::X86_64),
+            "arm64" | "aarch64" => Some(TargetArchitecture::ARM64),
+            "riscv64" => Some(TargetArchitecture::RISCV64),
+            "wasm" | "wasm32" => Some(TargetArchitecture::WASM),
+            _ => None,
+        }
+    }
+}
+
+/// Weight names accepted by `CostModel::from_toml`
+pub const WEIGHT_NAMES: &[&str] = &[
+    "arithmetic_cost", "multiply_cost", "divide_cost", "bitwise_cost", "shift_cost",
+    "logical_cost", "comparison_cost", "float_arithmetic_cost", "float_multiply_cost",
+    "float_divide_cost", "memory_access_cost", "allocation_cost", "deallocation_cost",
+    "branch_cost", "branch_mispredict_cost", "function_call_cost", "return_cost",
+    "control_flow_cost", "assignment_cost", "l1_cache_hit_cost", "l2_cache_hit_cost",
+    "l3_cache_hit_cost", "memory_miss_cost", "register_count", "cache_line_size",
+    "page_size", "instruction_bytes", "base_frequency_hz", "boost_frequency_hz",
+];
+
+/// Error loading a cost model from TOML
+#[derive(Debug, Clone, PartialEq)]
+pub enum CostModelError {
+    /// The file could not be read
+    Io(String),
+    /// The file is not valid TOML
+    Parse(String),
+    /// A key is not a known weight name
+    UnknownWeight(String),
+    /// A weight has a value of the wrong type or range
+    InvalidValue { name: String, message: String },
+}
+
+impl fmt::Display for CostModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CostModelError::Io(message) => write!(f, "Failed to read cost model: {}", message),
+            CostModelError::Parse(message) => write!(f, "Invalid cost model TOML: {}", message),
+            CostModelError::UnknownWeight(name) => write!(
+                f,
+                "Unknown cost model weight '{}'; valid names are: architecture, {}",
+                name,
+                WEIGHT_NAMES.join(", ")
+            ),
+            CostModelError::InvalidValue { name, message } => {
+                write!(f, "Invalid value for cost model weight '{}': {}", name, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CostModelError {}
+
 /// Architecture-specific cost model
 #[derive(Debug, Clone)]
 pub struct CostModel {
@@ -26,6 +90,9 @@ pub struct CostModel {
     pub shift_cost: u64,            // <<, >>
     pub logical_cost: u64,          // &&, ||, !
     pub comparison_cost: u64,       // ==, !=, <, etc.
+    pub float_arithmetic_cost: u64, // Floating-point +, -
+    pub float_multiply_cost: u64,   // Floating-point *
+    pub float_divide_cost: u64,     // Floating-point /
     
     // Memory operation costs
     pub memory_access_cost: u64,    // Load/store operations
@@ -34,6 +101,7 @@ pub struct CostModel {
     
     // Control flow costs
     pub branch_cost: u64,           // Conditional branches
+    pub branch_mispredict_cost: u64, // Pipeline flush on a mispredicted branch
     pub function_call_cost: u64,    // Function call overhead
     pub return_cost: u64,           // Function return
     pub control_flow_cost: u64,     // break, continue, etc.
@@ -82,6 +150,9 @@ impl CostModel {
             shift_cost: 1,           // Shift ops are 1 cycle
             logical_cost: 1,         // Logical ops are 1 cycle
             comparison_cost: 1,      // Comparisons are 1 cycle
+            float_arithmetic_cost: 4, // FP add latency ~4 cycles
+            float_multiply_cost: 4,  // FP multiply ~4 cycles
+            float_divide_cost: 14,   // FP divide ~14 cycles
             
             // Memory operations
             memory_access_cost: 4,   // L1 cache hit ~4 cycles
@@ -90,6 +161,7 @@ impl CostModel {
             
             // Control flow
             branch_cost: 1,          // Predicted branches ~1 cycle
+            branch_mispredict_cost: 15, // Deep pipeline flush ~15 cycles
             function_call_cost: 5,   // Call overhead ~5 cycles
             return_cost: 2,          // Return overhead ~2 cycles
             control_flow_cost: 1,    // Jump instructions ~1 cycle
@@ -128,6 +200,9 @@ impl CostModel {
             shift_cost: 1,
             logical_cost: 1,
             comparison_cost: 1,
+            float_arithmetic_cost: 3,
+            float_multiply_cost: 4,
+            float_divide_cost: 10,
             
             // Memory operations
             memory_access_cost: 3,   // ARM64 typically has faster L1
@@ -136,6 +211,7 @@ impl CostModel {
             
             // Control flow
             branch_cost: 1,
+            branch_mispredict_cost: 12,
             function_call_cost: 4,   // ARM64 calling convention is efficient
             return_cost: 1,
             control_flow_cost: 1,
@@ -174,6 +250,9 @@ impl CostModel {
             shift_cost: 1,
             logical_cost: 1,
             comparison_cost: 1,
+            float_arithmetic_cost: 5,
+            float_multiply_cost: 5,
+            float_divide_cost: 20,
             
             // Memory operations
             memory_access_cost: 5,   // Conservative estimate
@@ -182,6 +261,7 @@ impl CostModel {
             
             // Control flow
             branch_cost: 2,          // RISC-V branch prediction varies
+            branch_mispredict_cost: 6, // Shallow in-order pipelines
             function_call_cost: 6,
             return_cost: 2,
             control_flow_cost: 1,
@@ -220,6 +300,9 @@ impl CostModel {
             shift_cost: 2,
             logical_cost: 2,
             comparison_cost: 2,
+            float_arithmetic_cost: 4,
+            float_multiply_cost: 6,
+            float_divide_cost: 25,
             
             // Memory operations
             memory_access_cost: 10,  // WASM memory access has overhead
@@ -228,6 +311,7 @@ impl CostModel {
             
             // Control flow
             branch_cost: 3,          // WASM branches have overhead
+            branch_mispredict_cost: 20,
             function_call_cost: 15,  // WASM calls are expensive
             return_cost: 5,
             control_flow_cost: 3,
@@ -284,6 +368,97 @@ impl CostModel {
     }
 }
 
+impl CostModel {
+    /// Load a calibrated cost model from a TOML file
+    ///
+    /// Weights not listed keep the value of the base model, which is the one named
+    /// by `architecture` (default x86-64).
+    pub fn from_toml(path: impl AsRef<Path>) -> Result<Self, CostModelError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CostModelError::Io(format!("{}: {}", path.display(), e)))?;
+        Self::from_toml_str(&text)
+    }
+
+    /// Parse a cost model from TOML text, as for `from_toml`
+    pub fn from_toml_str(text: &str) -> Result<Self, CostModelError> {
+        let table: toml::Table = text.parse()
+            .map_err(|e: toml::de::Error| CostModelError::Parse(e.message().to_string()))?;
+
+        let mut model = match table.get("architecture") {
+            None => Self::default(),
+            Some(toml::Value::String(name)) => TargetArchitecture::from_name(name)
+                .map(Self::for_architecture)
+                .ok_or_else(|| CostModelError::InvalidValue {
+                    name: "architecture".to_string(),
+                    message: format!("unknown architecture '{}' (expected x86_64, arm64, riscv64 or wasm)", name),
+                })?,
+            Some(other) => return Err(CostModelError::InvalidValue {
+                name: "architecture".to_string(),
+                message: format!("expected a string, found {}", other.type_str()),
+            }),
+        };
+
+        for (name, value) in table.iter().filter(|(name, _)| name.as_str() != "architecture") {
+            let cycles = match value {
+                toml::Value::Integer(value) => u64::try_from(*value).map_err(|_| CostModelError::InvalidValue {
+                    name: name.clone(),
+                    message: format!("{} is negative", value),
+                })?,
+                other => return Err(CostModelError::InvalidValue {
+                    name: name.clone(),
+                    message: format!("expected an integer, found {}", other.type_str()),
+                }),
+            };
+            model.set_weight(name, cycles)?;
+        }
+
+        Ok(model)
+    }
+
+    /// Set the weight called `name` (one of `WEIGHT_NAMES`)
+    pub fn set_weight(&mut self, name: &str, value: u64) -> Result<(), CostModelError> {
+        let narrow = |value: u64| u32::try_from(value).map_err(|_| CostModelError::InvalidValue {
+            name: name.to_string(),
+            message: format!("{} does not fit in 32 bits", value),
+        });
+
+        match name {
+            "arithmetic_cost" => self.arithmetic_cost = value,
+            "multiply_cost" => self.multiply_cost = value,
+            "divide_cost" => self.divide_cost = value,
+            "bitwise_cost" => self.bitwise_cost = value,
+            "shift_cost" => self.shift_cost = value,
+            "logical_cost" => self.logical_cost = value,
+            "comparison_cost" => self.comparison_cost = value,
+            "float_arithmetic_cost" => self.float_arithmetic_cost = value,
+            "float_multiply_cost" => self.float_multiply_cost = value,
+            "float_divide_cost" => self.float_divide_cost = value,
+            "memory_access_cost" => self.memory_access_cost = value,
+            "allocation_cost" => self.allocation_cost = value,
+            "deallocation_cost" => self.deallocation_cost = value,
+            "branch_cost" => self.branch_cost = value,
+            "branch_mispredict_cost" => self.branch_mispredict_cost = value,
+            "function_call_cost" => self.function_call_cost = value,
+            "return_cost" => self.return_cost = value,
+            "control_flow_cost" => self.control_flow_cost = value,
+            "assignment_cost" => self.assignment_cost = value,
+            "l1_cache_hit_cost" => self.l1_cache_hit_cost = value,
+            "l2_cache_hit_cost" => self.l2_cache_hit_cost = value,
+            "l3_cache_hit_cost" => self.l3_cache_hit_cost = value,
+            "memory_miss_cost" => self.memory_miss_cost = value,
+            "register_count" => self.register_count = narrow(value)?,
+            "cache_line_size" => self.cache_line_size = narrow(value)?,
+            "page_size" => self.page_size = narrow(value)?,
+            "instruction_bytes" => self.instruction_bytes = narrow(value)?,
+            "base_frequency_hz" => self.base_frequency_hz = value,
+            "boost_frequency_hz" => self.boost_frequency_hz = value,
+            _ => return Err(CostModelError::UnknownWeight(name.to_string())),
+        }
+        Ok(())
+    }
+}
+
 impl Default for CostModel {
     fn default() -> Self {
         Self::x86_64()
@@ -4,8 +4,77 @@
 //! allowing verification of performance contracts at runtime.
 
 use crate::ast::PerformanceContract;
-use super::{ContractViolation, ViolationType, PerformanceCost};
-use std::time::{Instant, Duration};
+use super::{ContractViolation, PerformanceCost, ViolationType};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::time::Instant;
+
+/// Factor by which measured and predicted cycles may differ before the
+/// estimate is reported as off
+pub const ESTIMATE_DISCREPANCY_FACTOR: f64 = 2.0;
+
+/// Cycle counters collected for one instrumented function
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FunctionSamples {
+    /// Number of recorded calls
+    pub calls: u64,
+    /// Cycles summed over all recorded calls
+    pub total_cycles: u64,
+}
+
+impl FunctionSamples {
+    /// Average cycles per call
+    pub fn mean_cycles(&self) -> u64 {
+        self.total_cycles.checked_div(self.calls).unwrap_or(0)
+    }
+}
+
+/// Measured cycles of a function next to the static estimate
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstimateComparison {
+    /// Function the samples belong to
+    pub function_name: String,
+    /// Number of recorded calls
+    pub calls: u64,
+    /// Average measured cycles per call
+    pub measured_cycles: u64,
+    /// Cycles predicted by the `CostEstimator`, if it produced an estimate
+    pub predicted_cycles: Option<u64>,
+}
+
+impl EstimateComparison {
+    /// Measured cycles divided by predicted cycles
+    pub fn ratio(&self) -> Option<f64> {
+        let predicted = self.predicted_cycles?;
+        Some(self.measured_cycles as f64 / predicted.max(1) as f64)
+    }
+
+    /// Whether the estimate is off by more than `ESTIMATE_DISCREPANCY_FACTOR` either way
+    pub fn is_discrepancy(&self) -> bool {
+        self.ratio().is_some_and(|ratio| {
+            !(1.0 / ESTIMATE_DISCREPANCY_FACTOR..=ESTIMATE_DISCREPANCY_FACTOR).contains(&ratio)
+        })
+    }
+
+    /// One-line summary of the comparison
+    pub fn message(&self) -> String {
+        match (self.predicted_cycles, self.ratio()) {
+            (Some(predicted), Some(ratio)) => format!(
+                "Function '{}': measured {} cycles per call over {} calls, estimated {} ({:.1}x){}",
+                self.function_name,
+                self.measured_cycles,
+                self.calls,
+                predicted,
+                ratio,
+                if self.is_discrepancy() { " - estimate is off" } else { "" }
+            ),
+            _ => format!(
+                "Function '{}': measured {} cycles per call over {} calls, no estimate",
+                self.function_name, self.measured_cycles, self.calls
+            ),
+        }
+    }
+}
 
 /// Runtime performance profiler for debug mode
 #[derive(Debug)]
@@ -18,10 +87,14 @@ pub struct PerformanceProfiler {
     start_time: Instant,
     /// Initial memory usage (if trackable)
     initial_memory: Option<usize>,
-    /// Allocation count at start
+    /// `ALLOCATIONS` count at start
     initial_allocations: u32,
     /// Whether profiling is enabled
     enabled: bool,
+    /// Names of instrumented functions by profiling id
+    function_names: HashMap<u32, String>,
+    /// Cycle counters recorded by instrumented code, by profiling id
+    samples: HashMap<u32, FunctionSamples>,
 }
 
 impl PerformanceProfiler {
@@ -32,8 +105,10 @@ impl PerformanceProfiler {
             contract,
             start_time: Instant::now(),
             initial_memory: Self::get_memory_usage(),
-            initial_allocations: 0, // TODO: Hook into allocator
+            initial_allocations: ALLOCATIONS.allocation_count(),
             enabled: cfg!(debug_assertions),
+            function_names: HashMap::new(),
+            samples: HashMap::new(),
         }
     }
 
@@ -42,7 +117,7 @@ impl PerformanceProfiler {
         if self.enabled {
             self.start_time = Instant::now();
             self.initial_memory = Self::get_memory_usage();
-            // TODO: Reset allocation counter
+            self.initial_allocations = ALLOCATIONS.allocation_count();
         }
     }
 
@@ -116,13 +191,109 @@ impl PerformanceProfiler {
             }
         }
 
-        // TODO: Verify allocation bound
+        // Verify allocation bound
+        if let Some(ref allocation_bound) = self.contract.allocation_bound {
+            let allocations = ALLOCATIONS.allocation_count().wrapping_sub(self.initial_allocations);
+            let max_allocations = match allocation_bound {
+                crate::ast::AllocationBound::Count(max_allocations) => *max_allocations,
+                crate::ast::AllocationBound::None => 0,
+            };
+            if allocations > max_allocations {
+                violations.push(ContractViolation {
+                    function_name: self.function_name.clone(),
+                    violation_type: ViolationType::AllocationExceeded,
+                    expected: format!("{} allocations", max_allocations),
+                    actual: format!("{} allocations", allocations),
+                    span: crate::ast::Span::single(crate::lexer::Position::default()),
+                    message: format!(
+                        "Function '{}' exceeded allocation bound: {} > {} allocations",
+                        self.function_name, allocations, max_allocations
+                    ),
+                });
+            }
+        }
+
         // TODO: Verify CPU cycle bound (requires hardware counters)
         // TODO: Verify stack bound (requires stack introspection)
 
         violations
     }
 
+    /// Name the function instrumented code reports under `function_id`
+    pub fn register_function(&mut self, function_id: u32, name: String) {
+        self.function_names.insert(function_id, name);
+    }
+
+    /// Register every function of an instrumented module's `bract_profile_functions` table
+    ///
+    /// Each line holds a profiling id and a function name; malformed lines are skipped.
+    pub fn load_function_table(&mut self, table: &str) {
+        for line in table.lines() {
+            if let Some((id, name)) = line.trim().split_once(' ') {
+                if let Ok(id) = id.parse() {
+                    self.register_function(id, name.to_string());
+                }
+            }
+        }
+    }
+
+    /// Record one call of an instrumented function that took `cycles`
+    pub fn record_function_sample(&mut self, function_id: u32, cycles: u64) {
+        let samples = self.samples.entry(function_id).or_default();
+        samples.calls += 1;
+        samples.total_cycles = samples.total_cycles.saturating_add(cycles);
+    }
+
+    /// Counters recorded for a function
+    pub fn function_samples(&self, function_id: u32) -> Option<FunctionSamples> {
+        self.samples.get(&function_id).copied()
+    }
+
+    /// Compare recorded cycles with the static estimates, by function name
+    ///
+    /// Samples for ids without a registered name are left out.
+    pub fn compare_with_estimates(&self, function_costs: &HashMap<String, PerformanceCost>) -> Vec<EstimateComparison> {
+        let mut comparisons: Vec<_> = self.samples.iter()
+            .filter_map(|(id, samples)| {
+                let function_name = self.function_names.get(id)?;
+                Some(EstimateComparison {
+                    function_name: function_name.clone(),
+                    calls: samples.calls,
+                    measured_cycles: samples.mean_cycles(),
+                    predicted_cycles: function_costs.get(function_name).and_then(|cost| cost.cycles),
+                })
+            })
+            .collect();
+        comparisons.sort_by(|a, b| a.function_name.cmp(&b.function_name));
+        comparisons
+    }
+
+    /// Report of measured cycles against the static estimates, listing the
+    /// functions whose estimates are off by more than `ESTIMATE_DISCREPANCY_FACTOR`
+    pub fn estimate_report(&self, function_costs: &HashMap<String, PerformanceCost>) -> String {
+        let comparisons = self.compare_with_estimates(function_costs);
+        let discrepancies: Vec<_> = comparisons.iter()
+            .filter(|comparison| comparison.is_discrepancy())
+            .collect();
+
+        let mut report = format!(
+            "=== Bract Profile vs Estimates ===\n\
+             Profiled Functions: {}\n\
+             Off by more than {}x: {}\n",
+            comparisons.len(),
+            ESTIMATE_DISCREPANCY_FACTOR,
+            discrepancies.len()
+        );
+
+        if !discrepancies.is_empty() {
+            report.push_str("\nDiscrepancies:\n");
+        }
+        for comparison in discrepancies {
+            report.push_str(&format!("  {}\n", comparison.message()));
+        }
+        report
+    }
+
     /// Get current memory usage (platform-specific)
     fn get_memory_usage() -> Option<usize> {
         // TODO: Implement platform-specific memory usage tracking
@@ -151,6 +322,8 @@ impl PerformanceProfiler {
             initial_memory: None,
             initial_allocations: 0,
             enabled: false,
+            function_names: HashMap::new(),
+            samples: HashMap::new(),
         }
     }
 }
@@ -159,7 +332,7 @@ impl PerformanceProfiler {
 #[macro_export]
 macro_rules! profile_function {
     ($name:expr, $contract:expr, $body:block) => {{
-        let mut profiler = crate::performance::PerformanceProfiler::new($name.to_string(), $contract);
+        let mut profiler = $crate::performance::PerformanceProfiler::new($name.to_string(), $contract);
         profiler.start();
         
         let result = $body;
@@ -175,14 +348,36 @@ macro_rules! profile_function {
     }};
 }
 
-/// Stub for allocation tracking (to be implemented with custom allocator)
+/// Allocations made by the process, read by profilers to check allocation bounds
+///
+/// Counted only while `TrackingAllocator` is the global allocator.
+pub static ALLOCATIONS: AllocationTracker = AllocationTracker::new();
+
+/// Global allocator that counts allocations in `ALLOCATIONS`
+///
+/// Install it with `#[global_allocator]` to have profilers verify allocation bounds.
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.on_allocate(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATIONS.on_deallocate(layout.size());
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Allocation counters
 pub struct AllocationTracker {
     allocation_count: std::sync::atomic::AtomicU32,
     total_allocated: std::sync::atomic::AtomicU64,
 }
 
 impl AllocationTracker {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self {
             allocation_count: std::sync::atomic::AtomicU32::new(0),
             total_allocated: std::sync::atomic::AtomicU64::new(0),
@@ -6,18 +6,26 @@
 //! - Error collection and reporting
 //! - Analysis result aggregation
 
-use crate::ast::{Module, Expr, Type, Span, InternedString};
-use crate::semantic::symbols::{SymbolTable, SymbolTableBuilder, SymbolError};
+use crate::ast::{ImplItem, Item, Module, PrimitiveType, PrettyPrinter, StructFields, Type, Span, InternedString, SideTable};
+use crate::diagnostics::DiagnosticCode;
+use crate::semantic::symbols::{SymbolTable, SymbolTableBuilder, SymbolError, SymbolKind, ScopeKind};
 use crate::semantic::types::{TypeChecker, TypeError};
-use std::collections::HashMap;
+use crate::semantic::ownership::{OwnershipAnalyzer, OwnershipError};
+use crate::semantic::escape_analysis::{EscapeAnalyzer, EscapeFact};
+use crate::semantic::strategies::{StrategyChecker, StrategyError};
+use crate::parser::StringInterner;
+use crate::parser::error::suggest_similar_identifiers;
+use std::collections::{HashMap, HashSet};
 
 /// Result of semantic analysis
 #[derive(Debug)]
 pub struct AnalysisResult {
     /// Symbol table with all resolved symbols
     pub symbol_table: SymbolTable,
-    /// Type information for expressions
-    pub expression_types: HashMap<*const Expr, Type>,
+    /// Types of the checked expressions, by node
+    pub types: SideTable<Type>,
+    /// Escape facts of the allocating expressions, by node
+    pub escapes: SideTable<EscapeFact>,
     /// All semantic errors found
     pub errors: Vec<SemanticError>,
     /// Warnings generated during analysis
@@ -33,6 +41,30 @@ pub enum SemanticError {
     Symbol(SymbolError),
     /// Type-related errors
     Type(TypeError),
+    /// Moves and borrows that break ownership rules
+    Ownership(OwnershipError),
+    /// Memory strategy requests that contradict each other or the escape facts
+    Strategy(StrategyError),
+    /// `main` with a signature a program cannot start with
+    InvalidEntryPoint {
+        /// The signature as declared, e.g. `fn main(x: i32) -> f64`
+        signature: String,
+        /// What about the signature is not allowed, one entry per problem
+        problems: Vec<String>,
+        span: Span,
+    },
+    /// Executable without a `main` function
+    MissingEntryPoint {
+        /// A function whose name is a near miss of `main`, if any
+        similar: Option<InternedString>,
+        /// The near miss, or the whole module when there is none
+        span: Span,
+    },
+    /// Structs and enums that contain themselves by value, one field of each in turn
+    RecursiveType {
+        /// The fields on the cycle; the last holds the type declaring the first
+        cycle: Vec<CycleField>,
+    },
     /// Semantic rule violations
     SemanticViolation {
         message: String,
@@ -41,6 +73,106 @@ pub enum SemanticError {
     },
 }
 
+impl SemanticError {
+    /// Source location the error points at
+    pub fn span(&self) -> Span {
+        match self {
+            SemanticError::Symbol(error) => error.span(),
+            SemanticError::Type(error) => error.span(),
+            SemanticError::Ownership(error) => error.span(),
+            SemanticError::Strategy(error) => error.span(),
+            SemanticError::InvalidEntryPoint { span, .. }
+            | SemanticError::MissingEntryPoint { span, .. }
+            | SemanticError::SemanticViolation { span, .. } => *span,
+            SemanticError::RecursiveType { cycle } => cycle[0].span,
+        }
+    }
+    
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            SemanticError::Symbol(error) => error.code(),
+            SemanticError::Type(error) => error.code(),
+            SemanticError::Ownership(error) => error.code(),
+            SemanticError::Strategy(error) => error.code(),
+            SemanticError::InvalidEntryPoint { .. } => DiagnosticCode::InvalidEntryPoint,
+            SemanticError::MissingEntryPoint { .. } => DiagnosticCode::MissingEntryPoint,
+            SemanticError::RecursiveType { .. } => DiagnosticCode::RecursiveType,
+            SemanticError::SemanticViolation { .. } => DiagnosticCode::SemanticViolation,
+        }
+    }
+    
+    /// Secondary location explaining the error, with its label
+    pub fn related(&self) -> Option<(Span, &'static str)> {
+        match self {
+            SemanticError::Symbol(error) => error.related(),
+            SemanticError::Type(error) => error.related(),
+            SemanticError::Ownership(error) => error.related(),
+            SemanticError::Strategy(error) => error.related(),
+            SemanticError::RecursiveType { cycle } if cycle.len() > 1 => {
+                cycle.last().map(|field| (field.span, "the cycle closes here"))
+            }
+            SemanticError::InvalidEntryPoint { .. }
+            | SemanticError::MissingEntryPoint { .. }
+            | SemanticError::RecursiveType { .. }
+            | SemanticError::SemanticViolation { .. } => None,
+        }
+    }
+    
+    /// Human-readable message, with names resolved through `interner`
+    pub fn message(&self, interner: &StringInterner) -> String {
+        match self {
+            SemanticError::Symbol(error) => error.message(interner),
+            SemanticError::Type(error) => error.message(interner),
+            SemanticError::Ownership(error) => error.message(interner),
+            SemanticError::Strategy(error) => error.message(interner),
+            SemanticError::InvalidEntryPoint { signature, problems, .. } => format!(
+                "`main` must take no parameters and return `()` or `i32`, but is declared as `{}`:\n{}",
+                signature,
+                problems.iter().map(|problem| format!("  - {}", problem)).collect::<Vec<_>>().join("\n")
+            ),
+            SemanticError::MissingEntryPoint { similar: Some(similar), .. } => format!(
+                "no `main` function to start the program\nDid you mean: `{}`?",
+                interner.resolve(*similar).unwrap_or_else(|| "<unknown>".to_string())
+            ),
+            SemanticError::MissingEntryPoint { similar: None, .. } => {
+                "no `main` function to start the program; define `fn main() -> i32`".to_string()
+            }
+            SemanticError::RecursiveType { cycle } => {
+                let resolve = |name: InternedString| interner.resolve(name).unwrap_or_else(|| "<unknown>".to_string());
+                let fields: Vec<String> = cycle.iter()
+                    .map(|field| format!(
+                        "  - field `{}` of `{}` at {}:{} holds `{}`",
+                        field.field, resolve(field.owner), field.span.start.line, field.span.start.column, resolve(field.holds)
+                    ))
+                    .collect();
+                let held = resolve(cycle[0].holds);
+                format!(
+                    "recursive type `{}` has infinite size; it contains itself by value:\n{}\n\
+                     Suggestion: hold `{}` through a reference or pointer, e.g. `&{}` or `LinearPtr<{}>`",
+                    resolve(cycle[0].owner), fields.join("\n"), held, held, held
+                )
+            }
+            SemanticError::SemanticViolation { message, suggestion: Some(suggestion), .. } => {
+                format!("{}\nSuggestion: {}", message, suggestion)
+            }
+            SemanticError::SemanticViolation { message, suggestion: None, .. } => message.clone(),
+        }
+    }
+}
+
+/// One field on a cycle of structs and enums that contain themselves by value
+#[derive(Debug, Clone, PartialEq)]
+pub struct CycleField {
+    /// Struct or enum declaring the field
+    pub owner: InternedString,
+    /// `next`, `0` for a tuple field, `Cons.1` for a field of an enum variant
+    pub field: String,
+    /// The next type on the cycle, which the field holds by value
+    pub holds: InternedString,
+    pub span: Span,
+}
+
 impl From<SymbolError> for SemanticError {
     fn from(error: SymbolError) -> Self {
         SemanticError::Symbol(error)
@@ -53,15 +185,44 @@ impl From<TypeError> for SemanticError {
     }
 }
 
+impl From<OwnershipError> for SemanticError {
+    fn from(error: OwnershipError) -> Self {
+        SemanticError::Ownership(error)
+    }
+}
+
+impl From<StrategyError> for SemanticError {
+    fn from(error: StrategyError) -> Self {
+        SemanticError::Strategy(error)
+    }
+}
+
 /// Semantic warnings
 #[derive(Debug, Clone, PartialEq)]
 pub enum SemanticWarning {
-    /// Unused symbol
+    /// Private item that is never used
     UnusedSymbol {
         name: InternedString,
         span: Span,
         kind: String,
     },
+    /// Variable or parameter that is never read
+    UnusedVariable {
+        name: InternedString,
+        span: Span,
+        is_parameter: bool,
+    },
+    /// Variable that is assigned to but never read
+    UnreadAssignment {
+        name: InternedString,
+        span: Span,
+    },
+    /// `let` in the outermost block of a function body that shadows a parameter
+    ShadowedParameter {
+        name: InternedString,
+        span: Span,
+        parameter_span: Span,
+    },
     /// Unreachable code
     UnreachableCode {
         span: Span,
@@ -81,6 +242,64 @@ pub enum SemanticWarning {
     },
 }
 
+impl SemanticWarning {
+    /// Source location the warning points at
+    pub fn span(&self) -> Span {
+        match self {
+            SemanticWarning::UnusedSymbol { span, .. }
+            | SemanticWarning::UnusedVariable { span, .. }
+            | SemanticWarning::UnreadAssignment { span, .. }
+            | SemanticWarning::ShadowedParameter { span, .. }
+            | SemanticWarning::UnreachableCode { span, .. }
+            | SemanticWarning::Deprecated { span, .. }
+            | SemanticWarning::Performance { span, .. } => *span,
+        }
+    }
+    
+    /// Stable identifier of the kind of warning
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            SemanticWarning::UnusedSymbol { .. } => DiagnosticCode::UnusedItem,
+            SemanticWarning::UnusedVariable { is_parameter: false, .. } => DiagnosticCode::UnusedVariable,
+            SemanticWarning::UnusedVariable { is_parameter: true, .. } => DiagnosticCode::UnusedParameter,
+            SemanticWarning::UnreadAssignment { .. } => DiagnosticCode::UnusedAssignment,
+            SemanticWarning::ShadowedParameter { .. } => DiagnosticCode::ShadowedParameter,
+            SemanticWarning::UnreachableCode { .. } => DiagnosticCode::UnreachableCode,
+            SemanticWarning::Deprecated { .. } => DiagnosticCode::Deprecated,
+            SemanticWarning::Performance { .. } => DiagnosticCode::Performance,
+        }
+    }
+    
+    /// Human-readable message, with names resolved through `interner`
+    pub fn message(&self, interner: &StringInterner) -> String {
+        let resolve = |name: &InternedString| interner.resolve(*name).unwrap_or_else(|| "<unknown>".to_string());
+        match self {
+            SemanticWarning::UnusedSymbol { name, kind, .. } => format!("{} `{}` is never used", kind, resolve(name)),
+            SemanticWarning::UnusedVariable { name, is_parameter, .. } => {
+                let name = resolve(name);
+                format!(
+                    "unused {} `{}`; prefix it with an underscore if this is intentional: `_{}`",
+                    if *is_parameter { "parameter" } else { "variable" },
+                    name,
+                    name
+                )
+            }
+            SemanticWarning::UnreadAssignment { name, .. } => {
+                format!("variable `{}` is assigned to, but never read", resolve(name))
+            }
+            SemanticWarning::ShadowedParameter { name, .. } => {
+                format!("`{}` shadows the parameter of the same name", resolve(name))
+            }
+            SemanticWarning::UnreachableCode { reason, .. } => format!("unreachable code: {}", reason),
+            SemanticWarning::Deprecated { item, replacement: Some(replacement), .. } => {
+                format!("`{}` is deprecated; use `{}` instead", item, replacement)
+            }
+            SemanticWarning::Deprecated { item, replacement: None, .. } => format!("`{}` is deprecated", item),
+            SemanticWarning::Performance { message, suggestion, .. } => format!("{}; {}", message, suggestion),
+        }
+    }
+}
+
 /// Analysis statistics
 #[derive(Debug, Clone, Default)]
 pub struct AnalysisStats {
@@ -90,6 +309,8 @@ pub struct AnalysisStats {
     pub expressions_checked: usize,
     /// Number of scopes created
     pub scopes_created: usize,
+    /// Number of functions and methods analyzed, in nested modules too
+    pub functions_analyzed: usize,
     /// Analysis time in milliseconds
     pub analysis_time_ms: u64,
     /// Memory usage in bytes
@@ -106,6 +327,8 @@ pub struct SemanticAnalyzer {
     warnings: Vec<SemanticWarning>,
     /// Analysis statistics
     stats: AnalysisStats,
+    /// Interner the analyzed module's names were interned into
+    interner: StringInterner,
 }
 
 /// Configuration for semantic analysis
@@ -121,6 +344,8 @@ pub struct AnalyzerConfig {
     pub max_errors: Option<usize>,
     /// Enable experimental features
     pub experimental: bool,
+    /// Report a missing `main`, for programs built as executables
+    pub require_entry_point: bool,
 }
 
 impl Default for AnalyzerConfig {
@@ -131,6 +356,7 @@ impl Default for AnalyzerConfig {
             warn_performance: false,
             max_errors: Some(100),
             experimental: false,
+            require_entry_point: false,
         }
     }
 }
@@ -148,15 +374,27 @@ impl SemanticAnalyzer {
             errors: Vec::new(),
             warnings: Vec::new(),
             stats: AnalysisStats::default(),
+            interner: StringInterner::new(),
         }
     }
     
+    /// Resolve names through the interner the module was parsed with
+    pub fn with_interner(mut self, interner: StringInterner) -> Self {
+        self.interner = interner;
+        self
+    }
+    
+    /// Interner used to resolve the analyzed module's names
+    pub fn interner(&self) -> &StringInterner {
+        &self.interner
+    }
+    
     /// Perform complete semantic analysis on a module
     pub fn analyze(&mut self, module: &Module) -> AnalysisResult {
         let start_time = std::time::Instant::now();
         
         // Phase 1: Build symbol table
-        let (symbol_table, symbol_errors) = self.build_symbol_table(module);
+        let (mut symbol_table, symbol_errors) = self.build_symbol_table(module);
         
         // Collect symbol errors
         for error in symbol_errors {
@@ -164,11 +402,11 @@ impl SemanticAnalyzer {
         }
         
         // Phase 2: Type checking (only if no critical symbol errors)
-        let mut expression_types = HashMap::new();
+        let mut types = SideTable::new();
         if !self.has_critical_errors() {
-            let type_result = self.perform_type_checking(module, &symbol_table);
+            let type_result = self.perform_type_checking(module, &mut symbol_table);
             match type_result {
-                Ok(types) => expression_types = types,
+                Ok(checked) => types = checked,
                 Err(type_errors) => {
                     for error in type_errors {
                         self.add_error(SemanticError::Type(error));
@@ -178,9 +416,12 @@ impl SemanticAnalyzer {
         }
         
         // Phase 3: Additional semantic checks
-        self.perform_semantic_checks(module, &symbol_table);
+        self.check_entry_point(module);
+        self.check_recursive_types(module);
+        let escapes = self.perform_semantic_checks(module, &mut symbol_table);
         
         // Phase 4: Generate warnings
+        self.generate_shadowing_warnings(&symbol_table);
         if self.config.warn_unused {
             self.generate_unused_warnings(&symbol_table);
         }
@@ -188,10 +429,12 @@ impl SemanticAnalyzer {
         // Update statistics
         self.stats.analysis_time_ms = start_time.elapsed().as_millis() as u64;
         self.stats.symbols_analyzed = symbol_table.current_scope_symbols().len();
+        self.stats.functions_analyzed = count_functions(&module.items);
         
         AnalysisResult {
             symbol_table,
-            expression_types,
+            types,
+            escapes,
             errors: std::mem::take(&mut self.errors),
             warnings: std::mem::take(&mut self.warnings),
             stats: self.stats.clone(),
@@ -200,7 +443,7 @@ impl SemanticAnalyzer {
     
     /// Build symbol table from AST
     fn build_symbol_table(&mut self, module: &Module) -> (SymbolTable, Vec<SymbolError>) {
-        let builder = SymbolTableBuilder::new();
+        let builder = SymbolTableBuilder::new().with_interner(self.interner.clone());
         let (symbol_table, errors) = builder.build(module);
         
         self.stats.scopes_created = 1; // At least the root scope
@@ -208,48 +451,194 @@ impl SemanticAnalyzer {
         (symbol_table, errors)
     }
     
-    /// Perform type checking
+    /// Perform type checking, recording the resolved types of `let` bindings in `symbol_table`
     fn perform_type_checking(
         &mut self,
         module: &Module,
-        symbol_table: &SymbolTable,
-    ) -> Result<HashMap<*const Expr, Type>, Vec<TypeError>> {
-        let mut type_checker = TypeChecker::new(symbol_table.clone());
+        symbol_table: &mut SymbolTable,
+    ) -> Result<SideTable<Type>, Vec<TypeError>> {
+        let mut type_checker = TypeChecker::new(std::mem::replace(symbol_table, SymbolTable::new()))
+            .with_interner(self.interner.clone());
+        let result = type_checker.check_module(module);
+        let errors = type_checker.get_all_errors().to_vec();
+        let expression_types = type_checker.take_expression_types();
+        *symbol_table = type_checker.into_symbol_table();
         
-        match type_checker.check_module(module) {
+        match result {
             Ok(()) => {
-                let expression_types = HashMap::new();
-                
-                // Extract expression types from type checker
-                // Note: This would require additional API in TypeChecker to extract all types
-                // For now, return empty map
-                
                 self.stats.expressions_checked = expression_types.len();
                 Ok(expression_types)
             }
-            Err(_) => {
-                let errors = type_checker.get_all_errors().to_vec();
-                Err(errors)
+            Err(_) => Err(errors),
+        }
+    }
+    
+    /// Perform additional semantic checks: moves and borrows in function bodies, escape
+    /// analysis, then the memory strategies requested against the escape facts
+    ///
+    /// Returns the escape facts of the allocating expressions, by node.
+    fn perform_semantic_checks(&mut self, module: &Module, symbol_table: &mut SymbolTable) -> SideTable<EscapeFact> {
+        for error in OwnershipAnalyzer::new().analyze_module(module) {
+            self.add_error(SemanticError::Ownership(error));
+        }
+        
+        // Escape facts steer allocation strategies in codegen; the escape analyzer's
+        // own diagnostics are not reported yet
+        let mut escape_analyzer = EscapeAnalyzer::new();
+        escape_analyzer.analyze_module(module);
+        let escapes = escape_analyzer.node_escape_facts();
+        symbol_table.set_escape_facts(escape_analyzer.into_escape_facts());
+        
+        for error in StrategyChecker::new(&self.interner, &escapes).check_module(module) {
+            self.add_error(SemanticError::Strategy(error));
+        }
+        escapes
+    }
+    
+    /// Check the signature of `main`, and that there is one if the program needs an entry point
+    ///
+    /// A second `main` is a duplicate definition, reported with the symbol table; only
+    /// the first is checked here.
+    fn check_entry_point(&mut self, module: &Module) {
+        let functions = || module.items.iter().filter_map(|item| match item {
+            Item::Function { name, span, .. } => Some((item, *name, *span)),
+            _ => None,
+        });
+        let Some((main, _, span)) = functions().find(|(_, name, _)| self.interner.matches(*name, "main")) else {
+            if self.config.require_entry_point {
+                let names: Vec<String> = functions().filter_map(|(_, name, _)| self.interner.resolve(name)).collect();
+                let candidates: Vec<&str> = names.iter().map(String::as_str).collect();
+                let similar = suggest_similar_identifiers("main", &candidates).into_iter().next()
+                    .and_then(|similar| functions().find(|(_, name, _)| self.interner.matches(*name, &similar)));
+                self.add_error(SemanticError::MissingEntryPoint {
+                    similar: similar.map(|(_, name, _)| name),
+                    span: similar.map_or(module.span, |(_, _, span)| span),
+                });
             }
+            return;
+        };
+        let Item::Function { generics, params, return_type, .. } = main else {
+            return;
+        };
+        
+        let mut printer = PrettyPrinter::new(&self.interner);
+        let mut problems = Vec::new();
+        if !generics.is_empty() {
+            problems.push(format!("it has {} generic parameter{}", generics.len(), if generics.len() == 1 { "" } else { "s" }));
+        }
+        match params.len() {
+            0 => {}
+            1 => problems.push("it takes 1 parameter".to_string()),
+            count => problems.push(format!("it takes {} parameters", count)),
         }
+        match return_type {
+            None | Some(Type::Primitive { kind: PrimitiveType::Unit | PrimitiveType::I32, .. }) => {}
+            Some(Type::Tuple { types, .. }) if types.is_empty() => {}
+            Some(ty) => problems.push(format!("it returns `{}`", printer.print_type(ty))),
+        }
+        if problems.is_empty() {
+            return;
+        }
+        
+        let mut signature = "fn main".to_string();
+        if !generics.is_empty() {
+            let names: Vec<String> = generics.iter().filter_map(|generic| self.interner.resolve(generic.name)).collect();
+            signature.push_str(&format!("<{}>", names.join(", ")));
+        }
+        let params: Vec<String> = params.iter()
+            .map(|param| match &param.type_annotation {
+                Some(ty) => format!("{}: {}", printer.print_pattern(&param.pattern), printer.print_type(ty)),
+                None => printer.print_pattern(&param.pattern),
+            })
+            .collect();
+        signature.push_str(&format!("({})", params.join(", ")));
+        if let Some(ty) = return_type {
+            signature.push_str(&format!(" -> {}", printer.print_type(ty)));
+        }
+        self.add_error(SemanticError::InvalidEntryPoint { signature, problems, span });
     }
     
-    /// Perform additional semantic checks
-    fn perform_semantic_checks(&mut self, _module: &Module, _symbol_table: &SymbolTable) {
-        // TODO: Implement semantic checks
+    /// Report the structs and enums that contain themselves by value
+    ///
+    /// Such a type has no finite size. Fields hold a type by value directly, in a tuple
+    /// or fixed-size array, or through a type alias; a reference, pointer or strategy
+    /// wrapper such as `LinearPtr<T>` has the size of an address and breaks the cycle.
+    /// Each cycle is reported once, starting at the type declared first.
+    fn check_recursive_types(&mut self, module: &Module) {
+        let types: HashMap<InternedString, &Item> = module.items.iter()
+            .filter_map(|item| match item {
+                Item::Struct { name, .. } | Item::Enum { name, .. } | Item::TypeAlias { name, .. } => Some((*name, item)),
+                _ => None,
+            })
+            .collect();
+        let mut finished = HashSet::new();
+        let mut cycles = Vec::new();
+        for item in &module.items {
+            if let Item::Struct { name, .. } | Item::Enum { name, .. } = item {
+                if !finished.contains(name) {
+                    find_type_cycles(*name, &types, &mut Vec::new(), &mut finished, &mut cycles, &self.interner);
+                }
+            }
+        }
+        for cycle in cycles {
+            self.add_error(SemanticError::RecursiveType { cycle });
+        }
     }
     
-    /// Generate warnings for unused symbols
-    fn generate_unused_warnings(&mut self, symbol_table: &SymbolTable) {
-        for symbol in symbol_table.unused_symbols() {
-            self.add_warning(SemanticWarning::UnusedSymbol {
+    /// Warn about `let` bindings that shadow a parameter in the outermost block of its
+    /// function body; shadowing in nested blocks is left alone
+    fn generate_shadowing_warnings(&mut self, symbol_table: &SymbolTable) {
+        let mut shadowed = symbol_table.shadowed_parameters();
+        shadowed.sort_by_key(|(symbol, _)| (symbol.span.start.line, symbol.span.start.column));
+        
+        for (symbol, parameter) in shadowed {
+            self.warnings.push(SemanticWarning::ShadowedParameter {
                 name: symbol.name,
                 span: symbol.span,
-                kind: format!("{:?}", symbol.kind),
+                parameter_span: parameter.span,
             });
         }
     }
     
+    /// Generate warnings for unused items, variables and parameters
+    ///
+    /// Names starting with `_`, `main` and public items are exempt. Every `let` is its
+    /// own symbol, so a shadowed binding that is never read is reported on its own.
+    fn generate_unused_warnings(&mut self, symbol_table: &SymbolTable) {
+        let mut unused = symbol_table.unused_symbols();
+        unused.sort_by_key(|symbol| (symbol.span.start.line, symbol.span.start.column));
+        
+        for symbol in unused {
+            if self.interner.resolve(symbol.name).is_none_or(|name| name.starts_with('_')) {
+                continue;
+            }
+            let item = |kind: &str| SemanticWarning::UnusedSymbol {
+                name: symbol.name,
+                span: symbol.span,
+                kind: kind.to_string(),
+            };
+            let warning = match &symbol.kind {
+                SymbolKind::Variable { .. } if symbol.is_assigned => SemanticWarning::UnreadAssignment {
+                    name: symbol.name,
+                    span: symbol.span,
+                },
+                SymbolKind::Variable { .. } => SemanticWarning::UnusedVariable {
+                    name: symbol.name,
+                    span: symbol.span,
+                    // Parameters are bound directly in their function's scope
+                    is_parameter: symbol_table.get_scope(symbol.scope_id)
+                        .is_some_and(|scope| scope.kind == ScopeKind::Function),
+                },
+                SymbolKind::Function { .. } if self.interner.matches(symbol.name, "main") => continue,
+                SymbolKind::Function { .. } => item("function"),
+                SymbolKind::Type { .. } => item(symbol.kind.description()),
+                SymbolKind::Constant { .. } => item("constant"),
+                SymbolKind::Module { .. } | SymbolKind::GenericParam { .. } => continue,
+            };
+            self.add_warning(warning);
+        }
+    }
+    
     /// Add a semantic error
     fn add_error(&mut self, error: SemanticError) {
         if let Some(max_errors) = self.config.max_errors {
@@ -271,6 +660,7 @@ impl SemanticAnalyzer {
         self.errors.iter().any(|error| match error {
             SemanticError::Symbol(SymbolError::CircularDependency { .. }) => true,
             SemanticError::Symbol(SymbolError::UndefinedSymbol { .. }) => true,
+            SemanticError::Symbol(SymbolError::UnresolvedImport { .. }) => true,
             _ => false,
         })
     }
@@ -286,6 +676,114 @@ impl SemanticAnalyzer {
     }
 }
 
+/// Number of functions and methods among `items` and the modules inside them
+fn count_functions(items: &[Item]) -> usize {
+    items.iter()
+        .map(|item| match item {
+            Item::Function { .. } => 1,
+            Item::Impl { items, .. } => items.iter().filter(|item| matches!(item, ImplItem::Function { .. })).count(),
+            Item::Module { items: Some(items), .. } => count_functions(items),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Depth-first search for the cycles through `name`'s by-value fields
+///
+/// `path` holds the fields leading from where the search started to `name`; a field
+/// holding a type on the path closes a cycle.
+fn find_type_cycles(
+    name: InternedString,
+    types: &HashMap<InternedString, &Item>,
+    path: &mut Vec<CycleField>,
+    finished: &mut HashSet<InternedString>,
+    cycles: &mut Vec<Vec<CycleField>>,
+    interner: &StringInterner,
+) {
+    let Some(item) = types.get(&name) else {
+        return;
+    };
+    for (field, field_type, span) in type_fields(item, interner) {
+        let mut held = Vec::new();
+        by_value_types(field_type, types, &mut HashSet::new(), &mut held);
+        for holds in held {
+            let edge = CycleField { owner: name, field: field.clone(), holds, span };
+            if finished.contains(&holds) {
+                continue;
+            }
+            if holds == name || path.iter().any(|field| field.owner == holds) {
+                let start = path.iter().position(|field| field.owner == holds).unwrap_or(path.len());
+                let mut cycle = path[start..].to_vec();
+                cycle.push(edge);
+                cycles.push(cycle);
+                continue;
+            }
+            path.push(edge);
+            find_type_cycles(holds, types, path, finished, cycles, interner);
+            path.pop();
+        }
+    }
+    finished.insert(name);
+}
+
+/// Name, type and span of each field of a struct or of the variants of an enum
+fn type_fields<'a>(item: &'a Item, interner: &StringInterner) -> Vec<(String, &'a Type, Span)> {
+    let fields = |prefix: Option<String>, fields: &'a StructFields| -> Vec<(String, &'a Type, Span)> {
+        let qualify = |field: String| match &prefix {
+            Some(prefix) => format!("{}.{}", prefix, field),
+            None => field,
+        };
+        match fields {
+            StructFields::Named(fields) => fields.iter()
+                .map(|field| (qualify(interner.resolve(field.name).unwrap_or_default()), &field.field_type, field.span))
+                .collect(),
+            StructFields::Tuple(types) => types.iter()
+                .enumerate()
+                .map(|(i, field_type)| (qualify(i.to_string()), field_type, field_type.span()))
+                .collect(),
+            StructFields::Unit => Vec::new(),
+        }
+    };
+    match item {
+        Item::Struct { fields: struct_fields, .. } => fields(None, struct_fields),
+        Item::Enum { variants, .. } => variants.iter()
+            .flat_map(|variant| fields(Some(interner.resolve(variant.name).unwrap_or_default()), &variant.fields))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The structs and enums a value of `ty` holds by value, looking through tuples,
+/// fixed-size arrays and type aliases but not through references and pointers
+fn by_value_types(
+    ty: &Type,
+    types: &HashMap<InternedString, &Item>,
+    aliases: &mut HashSet<InternedString>,
+    held: &mut Vec<InternedString>,
+) {
+    match ty {
+        Type::Path { segments, .. } => {
+            let Some(name) = segments.last() else {
+                return;
+            };
+            match types.get(name) {
+                Some(Item::Struct { .. } | Item::Enum { .. }) if !held.contains(name) => held.push(*name),
+                Some(Item::TypeAlias { target_type, .. }) if aliases.insert(*name) => {
+                    by_value_types(target_type, types, aliases, held);
+                }
+                _ => {}
+            }
+        }
+        Type::Tuple { types: elements, .. } => {
+            for element in elements {
+                by_value_types(element, types, aliases, held);
+            }
+        }
+        Type::Array { element_type, .. } => by_value_types(element_type, types, aliases, held),
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -318,10 +816,894 @@ mod tests {
             warn_performance: true,
             max_errors: Some(50),
             experimental: true,
+            require_entry_point: false,
         };
         
         let analyzer = SemanticAnalyzer::with_config(config.clone());
         assert_eq!(analyzer.config.strict_types, config.strict_types);
         assert_eq!(analyzer.config.max_errors, config.max_errors);
     }
-} 
+    
+    #[test]
+    fn test_generic_bounds_recorded() {
+        use crate::semantic::symbols::{SymbolKind, TypeDefinition};
+        
+        let source = "fn max<T: Ord>(a: T, b: T) -> T where T: Copy { return a; }\nstruct Buf<T: Clone + Default = u8> { data: T }";
+        let mut parser = crate::Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let interner = parser.interner();
+        let result = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+        
+        let bound_names = |bounds: &[Type]| -> Vec<String> {
+            bounds.iter()
+                .filter_map(|bound| match bound {
+                    Type::Path { segments, .. } => interner.resolve(*segments.last()?),
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        let max = result.symbol_table.lookup_symbol(&interner.intern("max")).expect("max is declared");
+        match &max.kind {
+            SymbolKind::Function { generics, .. } => assert_eq!(bound_names(&generics[0].bounds), ["Ord", "Copy"]),
+            other => panic!("Expected function, got {:?}", other),
+        }
+        
+        let buf = result.symbol_table.lookup_symbol(&interner.intern("Buf")).expect("Buf is declared");
+        match &buf.kind {
+            SymbolKind::Type { definition: TypeDefinition::Struct { generics, .. } } => {
+                assert_eq!(bound_names(&generics[0].bounds), ["Clone", "Default"]);
+                assert!(generics[0].default.is_some());
+            }
+            other => panic!("Expected struct, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_integer_literal_ranges() {
+        use crate::ast::PrimitiveType;
+        
+        let out_of_range = |source: &str| -> Vec<(String, Option<PrimitiveType>, i128, u128, usize)> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            result.errors.iter()
+                .filter_map(|error| match error {
+                    SemanticError::Type(TypeError::LiteralOutOfRange { literal, ty, min, max, span }) => {
+                        Some((literal.clone(), *ty, *min, *max, span.start.column))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        let valid = "fn main() -> i64 {\n\
+                     let a = 255u8; let b = 0xFFu8; let c = 0xFFi8; let d = -128i8; let e: i64 = 5_000_000_000;\n\
+                     let f: u64 = 18_446_744_073_709_551_615; let g: i8 = -1; let h = 0b1000_0000i8;\n\
+                     return 5_000_000_000;\n}";
+        assert_eq!(out_of_range(valid), []);
+        
+        // Unannotated `let` makes an i32, whose range the literal is reported against
+        assert_eq!(
+            out_of_range("fn main() { let x = 5_000_000_000; }"),
+            [("5000000000".to_string(), Some(PrimitiveType::I32), i32::MIN as i128, i32::MAX as u128, 21)]
+        );
+        assert_eq!(
+            out_of_range("fn main() { let x: u8 = 300; }"),
+            [("300".to_string(), Some(PrimitiveType::U8), 0, 255, 25)]
+        );
+        assert_eq!(
+            out_of_range("fn main() -> i32 { return -129i8; }"),
+            [("-129".to_string(), Some(PrimitiveType::I8), -128, 127, 27)]
+        );
+        assert_eq!(out_of_range("fn main() { let x = -1u8; }")[0].0, "-1");
+        assert_eq!(out_of_range("fn main() { let x = 0x1FFu8; }")[0].3, 255);
+        assert_eq!(out_of_range("fn main() -> u16 { return 70000; }")[0].1, Some(PrimitiveType::U16));
+        // With no type to go by, a literal only has to fit in 64 bits
+        assert_eq!(out_of_range("fn foo(x: i64) {}\nfn main() { foo(5_000_000_000); }"), []);
+        assert_eq!(out_of_range("fn foo(x: i64) {}\nfn main() { foo(18_446_744_073_709_551_616); }")[0].1, None);
+    }
+    
+    #[test]
+    fn test_unused_warnings() {
+        let warnings = |source: &str| -> Vec<(&'static str, String)> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let interner = parser.interner();
+            let result = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+            result.warnings.iter()
+                .map(|warning| (warning.code().name(), warning.message(&interner)))
+                .collect()
+        };
+        let codes = |source: &str| -> Vec<&'static str> {
+            warnings(source).into_iter().map(|(code, _)| code).collect()
+        };
+        
+        // Everything read, whatever the declaration order
+        let used = "fn main() -> i32 { let p = Point { x: 1 }; return helper(p.x) + LIMIT; }\n\
+                    fn helper(x: i32) -> i32 { let mut total = x; total += 1; return total; }\n\
+                    struct Point { x: i32 }\nconst LIMIT: i32 = 10;";
+        assert_eq!(warnings(used), []);
+        
+        assert_eq!(
+            warnings("fn main() { let count = 1; }"),
+            [("unused-variable", "unused variable `count`; prefix it with an underscore if this is intentional: `_count`".to_string())]
+        );
+        assert_eq!(
+            warnings("fn main() { let mut x = 1; x = 2; }"),
+            [("unused-assignment", "variable `x` is assigned to, but never read".to_string())]
+        );
+        assert_eq!(codes("fn add(a: i32, b: i32) -> i32 { return a; }\nfn main() -> i32 { return add(1, 2); }"), ["unused-parameter"]);
+        assert_eq!(
+            warnings("fn helper() {}\nstruct Unused {}\nconst MAX: i32 = 1;\nfn main() {}"),
+            [
+                ("unused-item", "function `helper` is never used".to_string()),
+                ("unused-item", "struct `Unused` is never used".to_string()),
+                ("unused-item", "constant `MAX` is never used".to_string()),
+            ]
+        );
+        
+        // Public items, `main` and `_` names are exempt
+        assert_eq!(codes("pub fn api(_unused: i32) { let _x = 1; }\npub struct Api {}\nfn main() {}"), Vec::<&str>::new());
+        
+        // The shadowed binding is never read, even though the one shadowing it is
+        let shadowed = warnings("fn main() -> i32 { let x = 1; let x = 2; return x; }");
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed[0].0, "unused-variable");
+        // Reading the shadowed binding in the initializer uses it
+        assert_eq!(codes("fn main() -> i32 { let x = 1; let x = x + 1; return x; }"), Vec::<&str>::new());
+        // A read in a nested block counts, one in a sibling block sees its own binding
+        assert_eq!(codes("fn show(_x: i32) {}\nfn main() { let x = 1; if true { show(x); } }"), Vec::<&str>::new());
+        assert_eq!(codes("fn show(_x: i32) {}\nfn main() { if true { let x = 1; } if true { let x = 2; show(x); } }"), ["unused-variable"]);
+    }
+    
+    #[test]
+    fn test_duplicate_definitions() {
+        let duplicates = |source: &str| -> Vec<(String, usize, usize)> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let interner = parser.interner();
+            let result = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+            result.errors.iter()
+                .filter_map(|error| match error {
+                    SemanticError::Symbol(error @ SymbolError::DuplicateSymbol { .. }) => {
+                        let (existing_span, _) = error.related()?;
+                        Some((error.message(&interner), error.span().start.line, existing_span.start.line))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        assert_eq!(
+            duplicates("fn helper() {}\nfn main() {}\nfn helper() {}"),
+            [("function `helper` is defined multiple times".to_string(), 3, 1)]
+        );
+        assert_eq!(duplicates("struct A {}\nenum A { X }")[0].0, "enum `A` is defined multiple times");
+        assert_eq!(duplicates("const N: i32 = 1;\nconst N: i32 = 2;")[0].0, "constant `N` is defined multiple times");
+        assert_eq!(duplicates("type Id = i32;\ntype Id = i64;")[0].0, "type alias `Id` is defined multiple times");
+        assert_eq!(
+            duplicates("struct Point {\n    x: i32,\n    x: i32,\n}"),
+            [("field `x` is defined multiple times".to_string(), 3, 2)]
+        );
+        assert_eq!(duplicates("enum Color {\n    Red,\n    Red,\n}")[0].0, "variant `Red` is defined multiple times");
+        assert_eq!(duplicates("fn add(a: i32, a: i32) -> i32 { return a; }")[0].0, "parameter `a` is defined multiple times");
+        
+        // Methods clash across `impl` blocks of the same type, not of different types or traits
+        assert_eq!(
+            duplicates("struct S {}\nimpl S {\n    fn get(&self) {}\n}\nimpl S {\n    fn get(&self) {}\n}"),
+            [("method `get` is defined multiple times".to_string(), 6, 3)]
+        );
+        assert_eq!(duplicates("struct S {}\nimpl S {\n    fn get(&self) {}\n    fn get(&self) {}\n}").len(), 1);
+        assert_eq!(duplicates("struct S {}\nstruct T {}\nimpl S { fn get(&self) {} }\nimpl T { fn get(&self) {} }"), []);
+        assert_eq!(duplicates("struct S {}\ntrait Get {}\nimpl S { fn get(&self) {} }\nimpl Get for S { fn get(&self) {} }"), []);
+        
+        // Shadowing `let` bindings is not a duplicate
+        assert_eq!(duplicates("fn main() { let x = 1; let x = x + 1; if true { let x = 3; } }"), []);
+    }
+    
+    #[test]
+    fn test_shadowed_parameter_warning() {
+        let shadowing = |source: &str| -> Vec<(String, usize, usize)> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let interner = parser.interner();
+            let result = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+            result.warnings.iter()
+                .filter_map(|warning| match warning {
+                    SemanticWarning::ShadowedParameter { span, parameter_span, .. } => {
+                        Some((warning.message(&interner), span.start.column, parameter_span.start.column))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        assert_eq!(
+            shadowing("fn f(x: i32) -> i32 { let x = x + 1; return x; }"),
+            [("`x` shadows the parameter of the same name".to_string(), 27, 6)]
+        );
+        // Shadowing in a nested block, or a local rather than a parameter, is fine
+        assert_eq!(shadowing("fn f(x: i32) -> i32 { if true { let x = 2; return x; } return x; }"), []);
+        assert_eq!(shadowing("fn f() -> i32 { let x = 1; let x = x + 1; return x; }"), []);
+    }
+    
+    #[test]
+    fn test_paths_resolve_across_loaded_modules() {
+        use crate::parser::ModuleLoader;
+        
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("utils")).unwrap();
+        std::fs::write(dir.path().join("utils.bract"), "pub mod helpers;\nfn internal() {}").unwrap();
+        std::fs::write(dir.path().join("utils/helpers.bract"), "pub fn f() -> i32 { return 1; }\npub fn unused() {}").unwrap();
+        let main = "mod utils;\nuse utils::helpers;\nuse utils::helpers::f as first;\n\
+                    fn main() -> i32 { return helpers::f() + first(); }\n\
+                    fn broken() { helpers::missing(); utils::internal(); }";
+        std::fs::write(dir.path().join("main.bract"), main).unwrap();
+        
+        let interner = crate::parser::StringInterner::new();
+        let mut loader = ModuleLoader::new(interner.clone());
+        let module = loader.load(&dir.path().join("main.bract")).unwrap();
+        assert_eq!(loader.errors(), []);
+        let result = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+        
+        let path = |path: &str| -> Vec<_> { path.split("::").map(|segment| interner.intern(segment)).collect() };
+        let resolved = result.symbol_table.resolve_path(&path("helpers::f"), dummy_span()).unwrap();
+        let f = result.symbol_table.get_symbol(*resolved.last().unwrap()).unwrap();
+        assert!(interner.matches(f.name, "f") && f.is_used);
+        assert_ne!(f.span.start.file_id, 0);
+        let unused = result.symbol_table.resolve_path(&path("utils::helpers::unused"), dummy_span()).unwrap();
+        assert!(!result.symbol_table.get_symbol(unused[2]).unwrap().is_used);
+        
+        let errors: Vec<String> = result.errors.iter()
+            .filter_map(|error| match error {
+                SemanticError::Symbol(error) => Some(error.message(&interner)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(errors, ["cannot find `missing` in this scope", "function `internal` is private"]);
+    }
+    
+    #[test]
+    fn test_visibility_across_modules() {
+        let private_uses = |source: &str| -> Vec<(String, usize, usize)> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let interner = parser.interner();
+            let result = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+            result.errors.iter()
+                .filter_map(|error| match error {
+                    SemanticError::Symbol(error @ SymbolError::InaccessibleSymbol { .. }) => {
+                        let (definition_span, _) = error.related()?;
+                        Some((error.message(&interner), error.span().start.line, definition_span.start.line))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        let source = "mod internal {\n    fn secret() {}\n    pub fn open() { secret(); }\n}\nfn main() {\n    internal::open();\n    internal::secret();\n}";
+        assert_eq!(private_uses(source), [("function `secret` is private".to_string(), 7, 2)]);
+        let mut parser = crate::Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let interner = parser.interner();
+        let result = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+        let help = result.errors.iter().find_map(|error| match error {
+            SemanticError::Symbol(error) => error.help(&interner),
+            _ => None,
+        });
+        assert_eq!(help.as_deref(), Some("consider making the function public: `pub secret`"));
+        
+        // Private items are visible to submodules, and through `use` only if public
+        assert_eq!(private_uses("mod a {\n    fn helper() {}\n    mod b { fn f() { a::helper(); } }\n}\nfn main() {}"), []);
+        assert_eq!(private_uses("mod a { fn hidden() {} }\nuse a::hidden;\nfn main() {}")[0].0, "function `hidden` is private");
+        
+        // Fields, through literals, patterns and bindings of known type
+        let fields = "mod shapes {\n    pub struct Point {\n        pub x: i32,\n        y: i32,\n    }\n\
+                      pub fn origin() -> Point { Point { x: 0, y: 0 } }\n}\n\
+                      fn f(p: &shapes::Point) -> i32 { p.x + p.y }\n\
+                      use shapes::Point;\nfn g() { let q = shapes::Point { x: 1, y: 2 }; let Point { x, y } = q; }";
+        let errors = private_uses(fields);
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors.iter().all(|(message, _, definition)| message == "field `y` is private" && *definition == 4));
+        
+        // Private fields and methods are accessible inside the struct's impl blocks, wherever they are
+        let methods = "mod shapes {\n    pub struct Point { x: i32 }\n}\n\
+                       mod imp {\n    impl shapes::Point {\n        pub fn new() -> shapes::Point { shapes::Point { x: 0 } }\n\
+                       fn get(&self, other: &shapes::Point) -> i32 { other.x }\n    }\n}\n\
+                       mod elsewhere {\n    fn use_it(p: &shapes::Point) -> i32 { shapes::Point::new(); p.get(p) }\n}";
+        assert_eq!(private_uses(methods), [("method `get` is private".to_string(), 11, 7)]);
+    }
+    
+    #[test]
+    fn test_use_paths_are_validated() {
+        let import_errors = |source: &str| -> Vec<(String, (usize, usize), Option<usize>)> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let interner = parser.interner();
+            let result = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+            result.errors.iter()
+                .filter_map(|error| match error {
+                    SemanticError::Symbol(error) => Some((
+                        error.message(&interner),
+                        (error.span().start.line, error.span().start.column),
+                        error.related().map(|(span, _)| span.start.line),
+                    )),
+                    _ => None,
+                })
+                .collect()
+        };
+        let math = "mod math {\n    pub mod helpers { pub fn square(x: i32) -> i32 { return x * x; } }\n    pub fn cube(x: i32) -> i32 { return x; }\n}\n";
+        
+        // Imported names and aliases resolve as paths and bare names
+        let resolved = format!("{}use math::helpers;\nuse math::helpers::square as sq;\n\
+                                fn main() -> i32 {{ return helpers::square(2) + sq(3); }}", math);
+        assert_eq!(import_errors(&resolved), []);
+        
+        // The failing segment is reported, with the names it may be a misspelling of
+        assert_eq!(import_errors(&format!("{}use math::helpres::square;", math)), [(
+            "unresolved import `helpres`: the module defines no such item\nDid you mean: helpers".to_string(),
+            (5, 11),
+            None,
+        )]);
+        assert_eq!(import_errors(&format!("{}use mth::cube;", math))[0].0,
+                   "unresolved import `mth`: no module or item `mth` in this scope\nDid you mean: math");
+        assert_eq!(import_errors(&format!("{}use math::cube::inner;", math))[0],
+                   ("unresolved import `inner`: `cube` is a function, not a module".to_string(), (5, 17), None));
+        
+        // Importing a name twice into one scope shows both imports
+        let twice = format!("{}use math::cube;\nuse math::helpers::square as cube;", math);
+        assert_eq!(import_errors(&twice), [(
+            "`cube` is imported more than once in this scope".to_string(),
+            (6, 1),
+            Some(5),
+        )]);
+    }
+    
+    #[test]
+    fn test_let_type_inference() {
+        use crate::ast::PrimitiveType;
+        
+        let source = "fn wide() -> i64 { return 1; } fn main() { let a = 1; let b = 2.5; let c = (3u8); \
+                      let d = { let inner = 4i16; inner }; let e = wide(); let f = a < 2; let g = e * 2; \
+                      let h: u64 = 7; let i = -h; let j = if f { 1 } else { c }; }";
+        let mut parser = crate::Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        
+        let binding_kind = |name: &str| -> Option<PrimitiveType> {
+            let offset = source.find(&format!("let {} ", name)).or_else(|| source.find(&format!("let {}:", name)))? + 4;
+            let symbol = result.symbol_table.binding_at(crate::lexer::Position::new(1, offset + 1, offset, 0))?;
+            match &symbol.kind {
+                SymbolKind::Variable { type_info: Some(Type::Primitive { kind, .. }), .. } => Some(*kind),
+                _ => None,
+            }
+        };
+        let expected = [
+            ("a", PrimitiveType::I32), ("b", PrimitiveType::F64), ("c", PrimitiveType::U8),
+            ("inner", PrimitiveType::I16), ("d", PrimitiveType::I16), ("e", PrimitiveType::I64),
+            ("f", PrimitiveType::Bool), ("g", PrimitiveType::I64), ("h", PrimitiveType::U64),
+            ("i", PrimitiveType::U64), ("j", PrimitiveType::U8),
+        ];
+        for (name, kind) in expected {
+            assert_eq!(binding_kind(name), Some(kind), "type of {}", name);
+        }
+    }
+    
+    #[test]
+    fn test_let_annotation_mismatch() {
+        let mismatches = |source: &str| -> Vec<(String, usize, usize)> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            result.errors.iter()
+                .filter_map(|error| match error {
+                    SemanticError::Type(error @ TypeError::AnnotationMismatch { annotation_span, initializer_span, .. }) => {
+                        Some((error.to_string(), annotation_span.start.column, initializer_span.start.column))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        let narrowing = mismatches("fn wide() -> i64 { return 1; }\nfn main() { let x: u8 = wide(); }");
+        assert_eq!(narrowing.len(), 1);
+        assert!(narrowing[0].0.starts_with("Type mismatch: binding is annotated as u8 but initialized with i64"), "{}", narrowing[0].0);
+        assert_eq!((narrowing[0].1, narrowing[0].2), (20, 25));
+        
+        // Through a variable, a block and parentheses
+        assert_eq!(mismatches("fn main() { let a = 1.5; let b: i32 = ({ a }); }").len(), 1);
+        assert_eq!(mismatches("fn main() { let f: f64 = 1; }").len(), 1);
+        assert_eq!(mismatches("fn main() { let flag: bool = 1u8; }").len(), 1);
+        
+        // Lossless widening and untyped literals are fine
+        let widening = "fn main() { let a = 5u8; let b: i32 = a; let c: u64 = a; let d: f32 = 1.5; let e: f64 = 1f32; \
+                        let f: i8 = -1; let g: i64 = f; }";
+        assert_eq!(mismatches(widening), []);
+        assert_eq!(mismatches("fn main() { let a = 5i8; let b: u64 = a; }").len(), 1);
+    }
+    
+    #[test]
+    fn test_fixed_size_arrays() {
+        let type_errors = |source: &str| -> Vec<TypeError> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            result.errors.into_iter()
+                .filter_map(|error| match error {
+                    SemanticError::Type(error) => Some(error),
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        // Lengths fold through arithmetic and constants
+        let valid = "const ROWS: usize = 4;\n\
+                     fn main() -> i32 {\n\
+                         let zeroed: [u8; ROWS * 16] = [0; 64];\n\
+                         let pair: [i64; 2] = [1, 2];\n\
+                         let buffer = [0u8; ROWS];\n\
+                         let grid: [i32; 2 + 1] = [7; 3];\n\
+                         return grid[2];\n\
+                     }";
+        assert_eq!(type_errors(valid), []);
+        
+        let wrong_count = type_errors("fn main() { let values: [i32; 4] = [1, 2, 3]; }");
+        assert!(matches!(wrong_count[..], [TypeError::ArrayLengthMismatch { expected: 4, found: 3, .. }]), "{:?}", wrong_count);
+        let wrong_repeat = type_errors("const N: usize = 8; fn main() { let values: [i32; N] = [0; N - 1]; }");
+        assert!(matches!(wrong_repeat[..], [TypeError::ArrayLengthMismatch { expected: 8, found: 7, .. }]), "{:?}", wrong_repeat);
+        
+        let wrong_element = type_errors("fn main() { let values: [u8; 2] = [1.5, 2.5]; }");
+        assert_eq!(wrong_element.len(), 1);
+        assert!(wrong_element[0].to_string().starts_with("Type mismatch: binding is annotated as [u8; 2] but initialized with [f64; 2]"), "{}", wrong_element[0]);
+        let out_of_range = type_errors("fn main() { let values: [u8; 2] = [0; 2]; let wide: [u8; 2] = [1, 300]; }");
+        assert!(matches!(out_of_range[..], [TypeError::LiteralOutOfRange { ty: Some(crate::ast::PrimitiveType::U8), .. }]), "{:?}", out_of_range);
+        
+        // A repeat count must be constant; locals are not
+        let non_constant = type_errors("fn main(n: usize) { let values = [0; n]; }");
+        assert!(matches!(non_constant[..], [TypeError::NonConstantArrayLength { .. }]), "{:?}", non_constant);
+        
+        // Constant indices past a static length are caught before codegen
+        let past_end = type_errors("const LAST: usize = 3; fn main() -> i32 { let values = [1, 2, 3]; return values[LAST]; }");
+        assert!(matches!(past_end[..], [TypeError::IndexOutOfBounds { index: 3, length: 3, .. }]), "{:?}", past_end);
+        assert_eq!(type_errors("fn main() -> i32 { let values = [0; 4]; let i = 7; return values[i - 5]; }"), []);
+    }
+    
+    #[test]
+    fn test_enum_discriminants() {
+        let duplicates = |source: &str| -> Vec<(u128, usize, usize)> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            result.errors.iter()
+                .filter_map(|error| match error {
+                    SemanticError::Type(TypeError::DuplicateDiscriminant { value, span, previous_span }) => {
+                        Some((*value, span.start.column, previous_span.start.column))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        assert_eq!(duplicates("enum Color { Red = 1, Green, Blue = 10 }"), []);
+        assert_eq!(duplicates("const BASE: i32 = 4; enum Level { Low = BASE, High = BASE * 2 }"), []);
+        assert_eq!(duplicates("enum Shape { Circle { radius: i64 }, Rect(i64, i64), Empty }"), []);
+        
+        assert_eq!(duplicates("enum E { A = 1, B = 1 }"), [(1, 17, 10)]);
+        // Unnumbered variants continue from the previous one
+        assert_eq!(duplicates("enum E { A = 1, B, C = 2 }"), [(2, 20, 17)]);
+        assert_eq!(duplicates("const TWO: i32 = 2; enum E { A, B, C, D = TWO }"), [(2, 39, 36)]);
+    }
+    
+    #[test]
+    fn test_call_signatures() {
+        let type_errors = |source: &str| -> Vec<TypeError> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            result.errors.into_iter()
+                .filter_map(|error| match error {
+                    SemanticError::Type(error) => Some(error),
+                    _ => None,
+                })
+                .collect()
+        };
+        
+        // Widening arguments, generics, extern functions, bare variants and callees in
+        // nested modules and blocks
+        let valid = "fn add(a: i32, b: i64) -> i64 { b }\n\
+                     fn id<T>(x: T) -> T { x }\n\
+                     fn puts(s: *const u8) -> i32;\n\
+                     enum Maybe { Just(i32), Nothing }\n\
+                     mod util { fn helper() {} fn run() { helper(); } }\n\
+                     fn main() { let byte = 1u8; add(byte, 2i32); id(1.5); puts(null); let m = Just(1); \
+                     fn inner(flag: bool) {} inner(true); }";
+        assert_eq!(type_errors(valid), []);
+        
+        let too_few = type_errors("fn add(a: i32, b: i32) -> i32 { a + b }\nfn main() { add(1); }");
+        assert!(matches!(too_few[..], [TypeError::ArgumentCountMismatch { expected: 2, found: 1, .. }]), "{:?}", too_few);
+        assert_eq!(too_few[0].to_string().split(" takes ").nth(1), Some("2 arguments but 1 argument was supplied"));
+        assert_eq!((too_few[0].span().start.line, too_few[0].span().start.column), (2, 13));
+        assert_eq!(too_few[0].related().map(|(span, _)| (span.start.line, span.start.column)), Some((1, 1)));
+        
+        let wrong_type = type_errors("fn add(a: i32, b: i32) -> i32 { a + b }\nfn main() { add(\"x\", 2); }");
+        let [TypeError::ArgumentTypeMismatch { index: 0, argument_span, call_span, parameter_span, .. }] = &wrong_type[..] else {
+            panic!("{:?}", wrong_type);
+        };
+        assert_eq!((argument_span.start.column, call_span.start.column), (17, 13));
+        assert_eq!(parameter_span.map(|span| (span.start.line, span.start.column)), Some((1, 8)));
+        assert_eq!(wrong_type[0].related().map(|(_, label)| label), Some("parameter declared here"));
+        assert!(wrong_type[0].to_string().starts_with("Type mismatch: argument 1 of"), "{}", wrong_type[0]);
+        
+        // Narrowing is not an implicit conversion; generic parameters still count
+        let narrowing = type_errors("fn half(x: i32) -> i32 { x }\nfn main() { half(5i64); }");
+        assert!(narrowing[0].to_string().ends_with("Suggestion: convert the value with `as i32`"), "{}", narrowing[0]);
+        assert!(matches!(type_errors("fn id<T>(x: T) -> T { x } fn main() { id(); }")[..], [TypeError::ArgumentCountMismatch { .. }]));
+        assert!(matches!(type_errors("fn puts(s: *const u8) -> i32; fn main() { puts(); }")[..], [TypeError::ArgumentCountMismatch { .. }]));
+        let pointer = type_errors("fn apply(f: fn(i32) -> i32) -> i32 { f(1, 2) }");
+        assert!(matches!(pointer[..], [TypeError::ArgumentCountMismatch { expected: 1, found: 2, .. }]), "{:?}", pointer);
+        // A function type names no parameters, so there is no declaration to point at
+        let pointer_argument = type_errors("fn apply(f: fn(i32) -> i32) -> i32 { f(\"x\") }");
+        assert!(matches!(pointer_argument[..], [TypeError::ArgumentTypeMismatch { index: 0, parameter_span: None, .. }]), "{:?}", pointer_argument);
+        assert_eq!(pointer_argument[0].related(), None);
+        
+        let unknown = type_errors("fn compute() {}\nfn main() { let total = 1; compte(); }");
+        assert!(matches!(&unknown[..], [TypeError::UnknownFunction { suggestions, .. }] if suggestions == &["compute"]), "{:?}", unknown);
+        assert_eq!(unknown[0].code(), "unknown-function");
+        
+        // Intrinsics are checked against their built-in signatures unless a function shadows them
+        assert_eq!(type_errors("fn main() { print_int(1u8); print_str(\"hi\"); let x = print_int(2); }"), []);
+        let misuse = type_errors("fn main() { print_int(\"x\"); print_str(); }");
+        assert!(matches!(misuse[..], [TypeError::ArgumentTypeMismatch { index: 0, parameter_span: None, .. }, TypeError::ArgumentCountMismatch { expected: 1, found: 0, .. }]), "{:?}", misuse);
+        assert_eq!(type_errors("fn print_int(s: str) {}\nfn main() { print_int(\"x\"); }"), []);
+        let misspelt = type_errors("fn main() { print_in(1); }");
+        assert!(matches!(&misspelt[..], [TypeError::UnknownFunction { suggestions, .. }] if suggestions[0] == "print_int"), "{:?}", misspelt);
+    }
+
+    #[test]
+    fn test_unit_values() {
+        let type_errors = |source: &str| -> Vec<TypeError> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            result.errors.into_iter()
+                .filter_map(|error| match error {
+                    SemanticError::Type(error) => Some(error),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // Unit calls as statements, discarded or bound, are fine
+        let valid = "fn log() {}\nfn main() -> i32 { log(); let _ = log(); let done = log(); return 0; }";
+        assert_eq!(type_errors(valid), []);
+
+        let operand = type_errors("fn log() {}\nfn main() -> i32 { return log() + 1; }");
+        let [TypeError::UnitValue { usage: "an operand", span }] = &operand[..] else {
+            panic!("{:?}", operand);
+        };
+        assert_eq!((span.start.line, span.start.column), (2, 27));
+        assert_eq!(operand[0].code(), "type-mismatch");
+        assert!(operand[0].to_string().starts_with("Type mismatch: expected a value for an operand, found ()"), "{}", operand[0]);
+
+        let condition = type_errors("fn log() {}\nfn main() { if log() { } while log() { } }");
+        assert!(matches!(condition[..], [TypeError::UnitValue { usage: "a condition", .. }, TypeError::UnitValue { usage: "a condition", .. }]), "{:?}", condition);
+    }
+
+    #[test]
+    fn test_break_values() {
+        let type_errors = |source: &str| -> Vec<TypeError> {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            result.errors.into_iter()
+                .filter_map(|error| match error {
+                    SemanticError::Type(error) => Some(error),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        // `loop` takes the type of its break value; a plain break or no break at all is fine
+        let valid = "fn main() -> i32 { let found: i32 = loop { break 7; }; loop { break; } return found; }";
+        assert_eq!(type_errors(valid), []);
+        let mismatched = type_errors("fn main() { let found: bool = loop { break 7; }; }");
+        assert!(matches!(mismatched[..], [TypeError::AnnotationMismatch { .. }]), "{:?}", mismatched);
+
+        // A while loop's result is unit, even when the break sits in a nested block
+        let in_while = type_errors("fn main() {\n    while true { if true { break 1; } }\n}");
+        let [TypeError::BreakValueInLoop { keyword: "while", span, loop_span }] = &in_while[..] else {
+            panic!("{:?}", in_while);
+        };
+        assert_eq!((span.start.line, span.start.column, loop_span.start.column), (2, 28, 5));
+        assert_eq!(in_while[0].code(), "type-mismatch");
+        assert_eq!(in_while[0].related().map(|(_, label)| label), Some("this loop evaluates to ()"));
+        assert!(in_while[0].to_string().starts_with("`break` with a value is not allowed in a `while` loop"), "{}", in_while[0]);
+
+        // A break leaves the innermost loop only
+        let nested = "fn main() { while true { let found = loop { break 3; }; break; } }";
+        assert_eq!(type_errors(nested), []);
+    }
+
+    #[test]
+    fn test_if_let_and_while_let() {
+        let prelude = "enum Option { Some(i32), None }\nenum Shape { Circle(i32), Empty }\nfn next(n: i32) -> Option { return Option::None; }\n";
+        let analyze = |body: &str| -> (AnalysisResult, StringInterner) {
+            let source = format!("{}{}", prelude, body);
+            let mut parser = crate::Parser::new(&source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            (result, parser.take_interner())
+        };
+
+        let valid = "fn check(value: Option) -> i32 {\n\
+                     let mut total = 0;\n\
+                     while let Option::Some(n) = next(total) { total += n; }\n\
+                     if let Some(n) = value { return n; } else if let Option::None = value { return total; }\n\
+                     return if let Option::Some(n) = value { n } else { 0 };\n}";
+        let (result, _) = analyze(valid);
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+
+        // The bindings are visible in the block run on a match, not in the else branch or after
+        let scoped = "fn check(value: Option) -> i32 {\n\
+                      if let Option::Some(n) = value { /*then*/ } else { /*else*/ }\n\
+                      while let Option::Some(m) = next(0) { /*body*/ }\n\
+                      return /*after*/ 0;\n}";
+        let (result, interner) = analyze(scoped);
+        let source = format!("{}{}", prelude, scoped);
+        let visible = |marker: &str, name: &str| {
+            let offset = source.find(marker).unwrap();
+            let symbols = &result.symbol_table;
+            symbols.lookup_symbol_from(symbols.scope_at(Position::new(0, 0, offset, 0)), &interner.intern(name)).is_some()
+        };
+        assert!(visible("/*then*/", "n") && visible("/*body*/", "m"));
+        assert!(!visible("/*else*/", "n") && !visible("/*after*/", "n") && !visible("/*after*/", "m"));
+
+        // A pattern of another type than the matched value is reported at the pattern
+        let (result, interner) = analyze("fn check(value: Option) -> i32 {\n    if let Shape::Circle(r) = value { return r; }\n    return 0;\n}");
+        let [SemanticError::Type(error @ TypeError::PatternMismatch { span, scrutinee_span, .. })] = &result.errors[..] else {
+            panic!("{:?}", result.errors);
+        };
+        assert_eq!((span.start.line, span.start.column, scrutinee_span.start.column), (5, 12, 31));
+        assert_eq!(error.message(&interner), "Type mismatch: pattern `Shape::Circle` cannot match a value of type Option");
+        assert_eq!(error.code(), "type-mismatch");
+        let (on_integer, _) = analyze("fn check(x: i32) { if let Option::None = x { } }");
+        assert!(matches!(on_integer.errors[..], [SemanticError::Type(TypeError::PatternMismatch { .. })]), "{:?}", on_integer.errors);
+    }
+
+    #[test]
+    fn test_memory_strategy_compatibility() {
+        let prelude = "struct Pair { x: i32, y: i32 }\n@linear struct Token { id: i32 }\n";
+        let analyze = |body: &str| -> (Vec<StrategyError>, StringInterner) {
+            let source = format!("{}{}", prelude, body);
+            let mut parser = crate::Parser::new(&source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            let errors = result.errors.into_iter()
+                .filter_map(|error| match error {
+                    SemanticError::Strategy(error) => Some(error),
+                    _ => None,
+                })
+                .collect();
+            (errors, parser.take_interner())
+        };
+
+        let (errors, _) = analyze(
+            "fn keep(p: SmartPtr<Pair>) -> i32 { return 0; }\n\
+             @region(scratch) fn sum() -> i32 { let r: RegionPtr<Pair> = Pair { x: 1, y: 2 }; return r.x; }\n\
+             @memory(strategy = \"region\") fn scoped() { region frame { let p = Pair { x: 1, y: 2 }; } }\n\
+             fn check() -> i32 {\n\
+                 let a: SmartPtr<Pair> = Pair { x: 1, y: 2 };\n\
+                 let b = a;\n\
+                 let t: LinearPtr<Token> = Token { id: 1 };\n\
+                 let copy: StackPtr<Pair> = b;\n\
+                 return keep(b);\n\
+             }",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        // Each violation points at the use and back at the annotation or wrapper
+        for (body, code, line, related_line, message) in [
+            ("@manual\n@smart fn f() {}", DiagnosticCode::StrategyConflict, 4, 3,
+             "`f` requests manual allocation and also smart pointer allocation\nSuggestion: keep a single strategy annotation"),
+            ("fn f() { let t: SmartPtr<Token> = Token { id: 1 }; }", DiagnosticCode::StrategyConflict, 3, 2,
+             "`Token` is annotated for linear allocation but held as a smart pointer value\n\
+              Suggestion: hold it with `LinearPtr<T>` or @linear, or change its annotation"),
+            ("fn keep(p: SmartPtr<Pair>) {}\nfn f() { let a: LinearPtr<Pair> = Pair { x: 1, y: 2 }; keep(a); }", DiagnosticCode::StrategyMismatch, 4, 3,
+             "`a` is a linear value where a smart pointer value is required; it needs an explicit conversion to become smart pointer\n\
+              Suggestion: declare `LinearPtr<T>` or @linear here, or allocate `a` with `SmartPtr<T>` or @smart"),
+            ("fn f(shared: SmartPtr<Pair>) {\n    let owned: LinearPtr<Pair> = shared;\n}", DiagnosticCode::StrategyMismatch, 4, 4,
+             "`shared` is a smart pointer value where a linear value is required; it may have other owners, so it can never become linear\n\
+              Suggestion: declare `SmartPtr<T>` or @smart here, or allocate `shared` with `LinearPtr<T>` or @linear"),
+            ("@stack\nfn make() -> Pair {\n    return Pair { x: 1, y: 2 };\n}", DiagnosticCode::Escape, 5, 3,
+             "stack value is returned from its function and would outlive its stack frame\nSuggestion: allocate it with @manual"),
+            ("@memory(strategy = \"region\")\nfn f() {\n    let p = Pair { x: 1, y: 2 };\n}", DiagnosticCode::InvalidRegionAccess, 5, 3,
+             "region value allocated outside any region\n\
+              Suggestion: allocate it inside a `region name { ... }` block, or declare a region for the function with @region(name)"),
+        ] {
+            let (errors, interner) = analyze(body);
+            let [error] = &errors[..] else {
+                panic!("{}: {:?}", body, errors);
+            };
+            assert_eq!(error.code(), code, "{}", body);
+            assert_eq!(error.span().start.line, line, "{}", body);
+            assert_eq!(error.related().map(|(span, _)| span.start.line), Some(related_line), "{}", body);
+            assert_eq!(error.message(&interner), message);
+        }
+    }
+
+    #[test]
+    fn test_region_escapes() {
+        let prelude = "struct Pair { x: i32, y: i32 }\nstruct Holder { pair: Pair }\n\
+                       fn keep(holder: Holder, pair: Pair) { holder.pair = pair; }\nfn peek(pair: Pair) -> i32 { pair.x }\n";
+        let analyze = |body: &str| -> (Vec<StrategyError>, StringInterner) {
+            let source = format!("{}{}", prelude, body);
+            let mut parser = crate::Parser::new(&source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            let errors = result.errors.into_iter()
+                .filter_map(|error| match error {
+                    SemanticError::Strategy(error) => Some(error),
+                    _ => None,
+                })
+                .collect();
+            (errors, parser.take_interner())
+        };
+
+        // Region values used up inside their block, or inside a nested block of their own
+        let (errors, _) = analyze(
+            "fn consume(holder: Holder) -> i32 {\n\
+                 let mut total = 0;\n\
+                 region frame {\n\
+                     let p: RegionPtr<Pair> = Pair { x: 1, y: 2 };\n\
+                     let q = p;\n\
+                     total = q.x + peek(p);\n\
+                     region inner {\n\
+                         let mut r: RegionPtr<Pair> = Pair { x: 3, y: 4 };\n\
+                         r = Pair { x: 5, y: 6 };\n\
+                         total = total + r.y;\n\
+                     }\n\
+                 }\n\
+                 return total;\n\
+             }",
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        // Each escape points at the use and back at the region block
+        for (body, line, related_line, message) in [
+            ("fn f() -> i32 {\n    region frame {\n        let p: RegionPtr<Pair> = Pair { x: 1, y: 2 };\n        return p;\n    }\n    return 0;\n}", 8, 6,
+             "region value is returned from its function and would outlive region `frame`\n\
+              Suggestion: allocate it with @manual, or widen region `frame` to cover this use"),
+            ("fn f() -> i32 {\n    let mut outer = Pair { x: 0, y: 0 };\n    region frame {\n        let p: RegionPtr<Pair> = Pair { x: 1, y: 2 };\n        \
+              outer = p;\n    }\n    return outer.x;\n}", 9, 7,
+             "region value is stored in `outer`, declared outside region `frame`, and would outlive the region\n\
+              Suggestion: allocate it with @manual, or widen region `frame` to cover this use"),
+            ("fn f(holder: Holder) {\n    region frame {\n        let p: RegionPtr<Pair> = Pair { x: 1, y: 2 };\n        keep(holder, p);\n    }\n}", 8, 6,
+             "region value is passed to `keep`, which keeps it, and would outlive region `frame`\n\
+              Suggestion: allocate it with @manual, or widen region `frame` to cover this use"),
+            ("fn f() -> i32 {\n    let found = loop {\n        region frame {\n            let p: RegionPtr<Pair> = Pair { x: 1, y: 2 };\n            \
+              break (p);\n        }\n    };\n    return 0;\n}", 9, 7,
+             "region value breaks out of a loop around region `frame` and would outlive the region\n\
+              Suggestion: allocate it with @manual, or widen region `frame` to cover this use"),
+        ] {
+            let (errors, interner) = analyze(body);
+            let [error] = &errors[..] else {
+                panic!("{}: {:?}", body, errors);
+            };
+            assert_eq!(error.code(), DiagnosticCode::Escape, "{}", body);
+            assert_eq!(error.span().start.line, line, "{}", body);
+            assert_eq!(error.related().map(|(span, label)| (span.start.line, label)), Some((related_line, "region the value is allocated in")), "{}", body);
+            assert_eq!(error.message(&interner), message);
+        }
+
+        // A function keeping a parameter through another function keeps it too, whichever is declared first
+        let (errors, _) = analyze(
+            "fn f(holder: Holder) { region frame { let p: RegionPtr<Pair> = Pair { x: 1, y: 2 }; relay(holder, p); } }\n\
+             fn relay(holder: Holder, pair: Pair) { keep(holder, pair); }",
+        );
+        assert!(matches!(errors[..], [StrategyError::RegionEscape { .. }]), "{:?}", errors);
+    }
+
+    #[test]
+    fn test_entry_point() {
+        let errors = |source: &str, require_entry_point: bool| -> (Vec<SemanticError>, StringInterner) {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let config = AnalyzerConfig { require_entry_point, ..AnalyzerConfig::default() };
+            let result = SemanticAnalyzer::with_config(config).with_interner(parser.interner().clone()).analyze(&module);
+            (result.errors, parser.take_interner())
+        };
+
+        for valid in ["fn main() {}", "fn main() -> i32 { 0 }", "fn main() -> () {}"] {
+            assert!(errors(valid, true).0.is_empty(), "{}", valid);
+        }
+
+        // Every problem with the signature is listed
+        let (invalid, interner) = errors("fn helper() {}\nfn main(x: i32, y: bool) -> f64 { 1.5 }", false);
+        let [SemanticError::InvalidEntryPoint { signature, problems, span }] = &invalid[..] else {
+            panic!("{:?}", invalid);
+        };
+        assert_eq!(signature, "fn main(x: i32, y: bool) -> f64");
+        assert_eq!(problems, &["it takes 2 parameters", "it returns `f64`"]);
+        assert_eq!(span.start.line, 2);
+        assert_eq!(invalid[0].code(), DiagnosticCode::InvalidEntryPoint);
+        assert!(invalid[0].message(&interner).ends_with("declared as `fn main(x: i32, y: bool) -> f64`:\n  - it takes 2 parameters\n  - it returns `f64`"));
+        let (generic, _) = errors("fn main<T>() -> i32 { 0 }", false);
+        assert!(matches!(&generic[..], [SemanticError::InvalidEntryPoint { problems, .. }] if problems == &["it has 1 generic parameter"]), "{:?}", generic);
+
+        // A second `main` is a duplicate definition pointing back at the first
+        let (duplicate, _) = errors("fn main() -> i32 { 0 }\nfn main() -> f64 { 1.0 }", true);
+        assert!(matches!(&duplicate[..], [SemanticError::Symbol(SymbolError::DuplicateSymbol { .. })]), "{:?}", duplicate);
+        assert_eq!((duplicate[0].span().start.line, duplicate[0].related().map(|(span, _)| span.start.line)), (2, Some(1)));
+
+        // A missing `main` matters only for executables, which are pointed at a near miss
+        assert!(errors("fn mian() {}", false).0.is_empty());
+        let (missing, interner) = errors("fn helper() {}\nfn mian() {}", true);
+        let [SemanticError::MissingEntryPoint { similar: Some(similar), span }] = &missing[..] else {
+            panic!("{:?}", missing);
+        };
+        assert!(interner.matches(*similar, "mian"));
+        assert_eq!(span.start.line, 2);
+        assert!(missing[0].message(&interner).ends_with("Did you mean: `mian`?"));
+        let (missing, _) = errors("fn helper() {}", true);
+        assert!(matches!(missing[..], [SemanticError::MissingEntryPoint { similar: None, .. }]), "{:?}", missing);
+    }
+
+    #[test]
+    fn test_recursive_types() {
+        let cycles = |source: &str| -> (Vec<SemanticError>, StringInterner) {
+            let mut parser = crate::Parser::new(source, 0).unwrap();
+            let module = parser.parse_module().unwrap();
+            let result = SemanticAnalyzer::new().with_interner(parser.interner().clone()).analyze(&module);
+            let errors = result.errors.into_iter().filter(|error| error.code() == DiagnosticCode::RecursiveType).collect();
+            (errors, parser.take_interner())
+        };
+        let path = |error: &SemanticError, interner: &StringInterner| match error {
+            SemanticError::RecursiveType { cycle } => cycle.iter()
+                .map(|field| format!("{}.{} -> {}", interner.resolve(field.owner).unwrap(), field.field, interner.resolve(field.holds).unwrap()))
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+
+        // A struct holding itself
+        let (errors, interner) = cycles("struct Node {\n    value: i32,\n    next: Node,\n}\nfn main() {}");
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(path(&errors[0], &interner), ["Node.next -> Node"]);
+        assert_eq!((errors[0].span().start.line, errors[0].related()), (3, None));
+        let message = errors[0].message(&interner);
+        assert!(message.starts_with("recursive type `Node` has infinite size"), "{}", message);
+        assert!(message.contains("field `next` of `Node` at 3:5 holds `Node`"), "{}", message);
+        assert!(message.contains("`&Node` or `LinearPtr<Node>`"), "{}", message);
+
+        // Two structs holding each other, reported once with every field on the cycle
+        let (errors, interner) = cycles("struct A { b: B }\nstruct B { count: i32, a: A }\nfn main() {}");
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(path(&errors[0], &interner), ["A.b -> B", "B.a -> A"]);
+        assert_eq!(errors[0].related().map(|(span, label)| (span.start.line, label)), Some((2, "the cycle closes here")));
+
+        // Tuples, arrays, enum payloads and aliases hold their contents by value
+        let (errors, interner) = cycles(
+            "enum Tree { Leaf(i32), Branch(Pair) }\nstruct Pair { halves: [Tree; 2] }\n\
+             type Cell = (i32, Cell2);\nstruct Cell2 { cell: Cell }\nfn main() {}",
+        );
+        assert_eq!(
+            errors.iter().map(|error| path(error, &interner)).collect::<Vec<_>>(),
+            [vec!["Tree.Branch.0 -> Pair", "Pair.halves -> Tree"], vec!["Cell2.cell -> Cell2"]]
+        );
+
+        // References, pointers and strategy wrappers break the cycle
+        for linked_list in [
+            "struct Node { value: i32, next: &Node }\nfn main() {}",
+            "struct Node { value: i32, next: *const Node }\nfn main() {}",
+            "struct Node { value: i32, next: LinearPtr<Node> }\nfn main() {}",
+            "struct A { b: B }\nstruct B { a: &A }\nenum List { Nil, Cons(i32, SmartPtr<List>) }\nfn main() {}",
+        ] {
+            assert_eq!(cycles(linked_list).0, [], "{}", linked_list);
+        }
+    }
+}
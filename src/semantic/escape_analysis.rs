@@ -4,15 +4,18 @@
 //! It tracks how values escape their original scopes and ensures that:
 //!
 //! - Stack-allocated values don't escape their stack frames
-//! - Region-allocated values don't escape their regions
+//! - Region-allocated values don't escape their regions: a value allocated in a
+//!   `region` block must not be returned, stored in a variable declared outside the
+//!   block, passed to a function that keeps it or break out of a loop around the block
 //! - Linear types are properly consumed
 //! - Memory strategies are compatible with escape patterns
 //! - Performance contracts are maintained across escapes
 
 use crate::ast::{
     Type, Expr, Stmt, Item, Module, Pattern, InternedString,
-    MemoryStrategy, LifetimeId, BinaryOp, UnaryOp
+    MemoryStrategy, LifetimeId, BinaryOp, UnaryOp, StructFields, NodeId, SideTable, Span
 };
+use crate::diagnostics::DiagnosticCode;
 use crate::lexer::Position;
 use std::collections::{HashMap, HashSet};
 
@@ -67,6 +70,19 @@ pub enum EscapeError {
     },
 }
 
+impl EscapeError {
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            EscapeError::StackEscape { .. } | EscapeError::RegionEscape { .. } => DiagnosticCode::Escape,
+            EscapeError::LinearNotConsumed { .. } => DiagnosticCode::LinearType,
+            EscapeError::StrategyViolation { .. } => DiagnosticCode::StrategyMismatch,
+            EscapeError::PerformanceViolation { .. } => DiagnosticCode::PerformanceViolation,
+            EscapeError::PotentialLeak { .. } => DiagnosticCode::PotentialLeak,
+        }
+    }
+}
+
 /// Escape context - where a value might escape to
 #[derive(Debug, Clone, PartialEq)]
 pub enum EscapeContext {
@@ -99,6 +115,107 @@ pub struct ValueFlow {
     pub lifetime: Option<LifetimeId>,
     /// Performance cost of this value
     pub performance_cost: u64,
+    /// Values the variable may refer to
+    pub origins: ValueOrigins,
+}
+
+/// Values an expression or variable may refer to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValueOrigins {
+    /// Allocating expressions of the current function, by their start
+    pub allocations: Vec<Position>,
+    /// Whether the value may also come from outside the function (a parameter or call result)
+    pub foreign: bool,
+    /// Parameters of the current function the value may be, by index
+    pub parameters: Vec<usize>,
+}
+
+impl ValueOrigins {
+    /// A value that was not allocated by the current function
+    fn foreign() -> Self {
+        Self { allocations: Vec::new(), foreign: true, parameters: Vec::new() }
+    }
+    
+    /// Add the values `other` may refer to
+    fn merge(&mut self, other: ValueOrigins) {
+        for site in other.allocations {
+            if !self.allocations.contains(&site) {
+                self.allocations.push(site);
+            }
+        }
+        for index in other.parameters {
+            if !self.parameters.contains(&index) {
+                self.parameters.push(index);
+            }
+        }
+        self.foreign |= other.foreign;
+    }
+}
+
+/// How a value allocated in a region block leaves it
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegionExit {
+    /// It leaves the function, which releases every region block in it
+    Function(EscapeContext),
+    /// It is stored in this variable, declared outside the block
+    OuterBinding(InternedString),
+    /// It is passed to this function, which keeps it
+    Retained(InternedString),
+    /// It is the value of a `loop` around the block
+    Break,
+}
+
+/// Use through which a value allocated in a region block outlives the block
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionEscape {
+    /// Name of the region block
+    pub region: InternedString,
+    pub region_span: Span,
+    pub exit: RegionExit,
+    /// The escaping use
+    pub site: Span,
+}
+
+/// What escape analysis found out about the value an allocating expression creates
+///
+/// Struct literals, tuples, arrays and variants of enums with payloads are allocation
+/// sites; codegen picks their memory strategy from these facts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EscapeFact {
+    /// How the value first leaves the function that created it
+    pub context: EscapeContext,
+    /// Whether the value escapes along more than one route, or into a closure
+    pub is_shared: bool,
+    /// Confidence in the fact (0-100)
+    pub confidence: u8,
+    /// How the value first outlives the region block allocating it, if it is allocated in one
+    pub region_escape: Option<RegionEscape>,
+}
+
+impl EscapeFact {
+    /// Confidence once the value was lent to a call, which is assumed not to keep it
+    const LENT_CONFIDENCE: u8 = 80;
+    
+    /// Whether the value may outlive the function that created it
+    pub fn escapes(&self) -> bool {
+        self.context != EscapeContext::NoEscape
+    }
+    
+    /// Whether the value may outlive the region block it is allocated in
+    pub fn escapes_region(&self) -> bool {
+        self.region_escape.is_some()
+    }
+}
+
+impl Default for EscapeFact {
+    fn default() -> Self {
+        Self {
+            context: EscapeContext::NoEscape,
+            is_shared: false,
+            confidence: 100,
+            region_escape: None,
+        }
+    }
 }
 
 /// Scope information for escape analysis
@@ -119,6 +236,15 @@ pub struct EscapeScope {
     pub region_name: Option<InternedString>,
 }
 
+/// A `region` block enclosing the expression being analyzed
+#[derive(Debug, Clone, Copy)]
+struct RegionBlock {
+    name: InternedString,
+    span: Span,
+    /// Index of its scope on the scope stack
+    depth: usize,
+}
+
 /// Escape analyzer
 #[derive(Debug)]
 pub struct EscapeAnalyzer {
@@ -137,6 +263,27 @@ pub struct EscapeAnalyzer {
     /// Performance budget tracking
     #[allow(dead_code)]
     performance_budgets: HashMap<String, u64>,
+    /// Facts about every allocating expression, by its start
+    escape_facts: HashMap<Position, EscapeFact>,
+    /// Node of the allocating expression starting at each site
+    allocation_nodes: HashMap<Position, NodeId>,
+    /// Scope stack depth outside each enclosing closure
+    closure_boundaries: Vec<usize>,
+    /// Scope stack depth of each enclosing `loop`, and the values passed to its `break`
+    loop_results: Vec<(usize, ValueOrigins)>,
+    /// Region blocks enclosing the current expression in its function, innermost last
+    region_blocks: Vec<RegionBlock>,
+    /// Innermost region block of each allocating expression allocated in one, by its start
+    allocation_regions: HashMap<Position, RegionBlock>,
+    /// Function whose body is being analyzed
+    current_function: Option<InternedString>,
+    /// Parameters each function keeps beyond the call, by index
+    retained_parameters: HashMap<InternedString, HashSet<usize>>,
+    /// Enums with payload-carrying variants -> their variants, whose values are allocated
+    payload_enums: HashMap<InternedString, HashSet<InternedString>>,
+    /// Named struct fields of primitive type, and those of any other type
+    primitive_fields: HashSet<InternedString>,
+    other_fields: HashSet<InternedString>,
 }
 
 impl EscapeAnalyzer {
@@ -158,21 +305,42 @@ impl EscapeAnalyzer {
             errors: Vec::new(),
             current_position: Position::start(0),
             performance_budgets: HashMap::new(),
+            escape_facts: HashMap::new(),
+            allocation_nodes: HashMap::new(),
+            closure_boundaries: Vec::new(),
+            loop_results: Vec::new(),
+            region_blocks: Vec::new(),
+            allocation_regions: HashMap::new(),
+            current_function: None,
+            retained_parameters: HashMap::new(),
+            payload_enums: HashMap::new(),
+            primitive_fields: HashSet::new(),
+            other_fields: HashSet::new(),
         }
     }
     
     /// Analyze escape patterns in a module
     pub fn analyze_module(&mut self, module: &Module) -> Vec<EscapeError> {
-        self.errors.clear();
-        
         // First pass: collect global declarations
         for item in &module.items {
             self.collect_global_item(item);
         }
         
-        // Second pass: analyze escape patterns
-        for item in &module.items {
-            self.analyze_item(item);
+        // Second pass: analyze escape patterns. A call keeps the arguments its callee
+        // keeps, which is only known once the callee is analyzed, so the pass repeats
+        // until no function is found to keep more of its parameters.
+        loop {
+            let retained = self.retained_parameters.clone();
+            self.errors.clear();
+            self.escape_facts.clear();
+            self.allocation_nodes.clear();
+            self.allocation_regions.clear();
+            for item in &module.items {
+                self.analyze_item(item);
+            }
+            if self.retained_parameters == retained {
+                break;
+            }
         }
         
         // Final pass: check for leaks and violations
@@ -181,6 +349,27 @@ impl EscapeAnalyzer {
         self.errors.clone()
     }
     
+    /// Facts about the allocating expressions of the last analyzed module, by their start
+    pub fn escape_facts(&self) -> &HashMap<Position, EscapeFact> {
+        &self.escape_facts
+    }
+    
+    /// Facts about the allocating expressions of the last analyzed module, by their node
+    pub fn node_escape_facts(&self) -> SideTable<EscapeFact> {
+        let mut facts = SideTable::new();
+        for (site, id) in &self.allocation_nodes {
+            if let Some(fact) = self.escape_facts.get(site) {
+                facts.insert(*id, fact.clone());
+            }
+        }
+        facts
+    }
+    
+    /// Take the facts about the allocating expressions of the last analyzed module
+    pub fn into_escape_facts(self) -> HashMap<Position, EscapeFact> {
+        self.escape_facts
+    }
+    
     /// Collect global item information
     fn collect_global_item(&mut self, item: &Item) {
         match item {
@@ -190,8 +379,23 @@ impl EscapeAnalyzer {
             Item::Const { name, .. } => {
                 self.global_values.insert(*name);
             }
-            Item::Struct { name, .. } |
-            Item::Enum { name, .. } |
+            Item::Enum { name, variants, .. } => {
+                self.global_values.insert(*name);
+                if variants.iter().any(|variant| !matches!(variant.fields, StructFields::Unit)) {
+                    self.payload_enums.insert(*name, variants.iter().map(|variant| variant.name).collect());
+                }
+            }
+            Item::Struct { name, fields, .. } => {
+                self.global_values.insert(*name);
+                if let StructFields::Named(fields) = fields {
+                    for field in fields {
+                        match field.field_type {
+                            Type::Primitive { .. } => self.primitive_fields.insert(field.name),
+                            _ => self.other_fields.insert(field.name),
+                        };
+                    }
+                }
+            }
             Item::TypeAlias { name, .. } => {
                 self.global_values.insert(*name);
             }
@@ -204,20 +408,25 @@ impl EscapeAnalyzer {
         match item {
             Item::Function { name, body: Some(body), params, return_type, .. } => {
                 self.enter_function_scope(format!("{:?}", name));
+                let outer_function = self.current_function.replace(*name);
+                let outer_regions = std::mem::take(&mut self.region_blocks);
                 
                 // Analyze parameters
-                for param in params {
-                    self.add_parameter(&param.pattern, &param.type_annotation);
+                for (index, param) in params.iter().enumerate() {
+                    self.add_parameter(&param.pattern, &param.type_annotation, Some(index));
                 }
                 
-                // Analyze body
-                self.analyze_expr(body);
+                // Analyze body; its value is returned to the caller
+                let result = self.analyze_expr(body);
+                self.escape(&result, EscapeContext::FunctionReturn, result_span(body));
                 
                 // Check return type compatibility
                 if let Some(ret_type) = return_type {
                     self.check_return_escape(body, ret_type);
                 }
                 
+                self.region_blocks = outer_regions;
+                self.current_function = outer_function;
                 self.exit_scope();
             }
             Item::Function { body: None, .. } => {
@@ -237,17 +446,23 @@ impl EscapeAnalyzer {
                 for impl_item in items {
                     if let crate::ast::ImplItem::Function { name, body: Some(body), params, return_type, .. } = impl_item {
                         self.enter_function_scope(format!("impl::{:?}", name));
+                        // Methods are not called by name, so what they keep is not tracked
+                        let outer_function = self.current_function.take();
+                        let outer_regions = std::mem::take(&mut self.region_blocks);
                         
                         for param in params {
-                            self.add_parameter(&param.pattern, &param.type_annotation);
+                            self.add_parameter(&param.pattern, &param.type_annotation, None);
                         }
                         
-                        self.analyze_expr(body);
+                        let result = self.analyze_expr(body);
+                        self.escape(&result, EscapeContext::FunctionReturn, result_span(body));
                         
                         if let Some(ret_type) = return_type {
                             self.check_return_escape(body, ret_type);
                         }
                         
+                        self.region_blocks = outer_regions;
+                        self.current_function = outer_function;
                         self.exit_scope();
                     }
                 }
@@ -258,91 +473,158 @@ impl EscapeAnalyzer {
         }
     }
     
-    /// Analyze expression for escape patterns
-    fn analyze_expr(&mut self, expr: &Expr) {
+    /// Analyze expression for escape patterns, returning the values it may evaluate to
+    fn analyze_expr(&mut self, expr: &Expr) -> ValueOrigins {
         self.current_position = self.get_expr_position(expr);
         
         match expr {
             Expr::Literal { .. } => {
                 // Literals don't escape
+                ValueOrigins::default()
+            }
+            
+            Expr::Identifier { name, .. } if self.constructs_payload_variant(std::slice::from_ref(name)) => {
+                self.allocation(expr)
             }
             
-            Expr::Identifier { name, .. } => {
+            Expr::Identifier { name, span, .. } => {
                 self.check_variable_escape(*name);
+                self.check_closure_capture(*name, *span);
+                // Names that are not local variables (functions, constants) hold no value of ours
+                self.find_variable_flow(*name).map_or_else(ValueOrigins::foreign, |flow| flow.origins)
+            }
+            
+            Expr::Path { segments, .. } if self.constructs_payload_variant(segments) => {
+                self.allocation(expr)
             }
             
             Expr::Path { segments, .. } => {
                 if let Some(first) = segments.first() {
                     self.check_variable_escape(*first);
                 }
+                ValueOrigins::foreign()
             }
             
             Expr::Binary { left, right, op, .. } => {
                 self.analyze_expr(left);
-                self.analyze_expr(right);
+                let value = self.analyze_expr(right);
                 
                 if let BinaryOp::Assign = op {
                     self.handle_assignment_escape(left, right);
+                    self.handle_store(left, value, right.span());
                 }
+                ValueOrigins::default()
             }
             
             Expr::Unary { expr, op, .. } => {
+                let origins = match op {
+                    UnaryOp::MutableRef | UnaryOp::AddressOf => self.place_origins(expr),
+                    _ => self.analyze_expr(expr),
+                };
                 match op {
                     UnaryOp::MutableRef => {
                         // Taking mutable address creates potential escape
                         self.check_address_escape(expr);
+                        origins
                     }
-                    _ => {
-                        self.analyze_expr(expr);
-                    }
+                    UnaryOp::AddressOf | UnaryOp::Dereference => origins,
+                    _ => ValueOrigins::default(),
                 }
             }
             
+            Expr::Call { callee, args, .. } if self.variant_path(callee).is_some_and(|path| self.constructs_payload_variant(path)) => {
+                // Like a struct literal, the variant holds its fields
+                let mut origins = ValueOrigins::default();
+                for arg in args {
+                    origins.merge(self.analyze_expr(arg));
+                }
+                origins.merge(self.allocation(expr));
+                origins
+            }
+            
             Expr::Call { callee, args, .. } => {
                 self.analyze_expr(callee);
+                let retained = match &**callee {
+                    Expr::Identifier { name, .. } => self.retained_parameters.get(name).cloned().map(|indices| (*name, indices)),
+                    _ => None,
+                };
                 
                 // Check if arguments escape through function call
-                for arg in args {
-                    self.analyze_expr(arg);
+                let mut result = ValueOrigins::foreign();
+                for (index, arg) in args.iter().enumerate() {
+                    let value = self.analyze_expr(arg);
                     self.check_call_argument_escape(arg, callee);
+                    match &retained {
+                        Some((function, indices)) if indices.contains(&index) => self.retain(&value, *function, arg.span()),
+                        _ => self.lend(&value),
+                    }
+                    result.merge(value);
                 }
+                // The callee may hand any of its arguments back
+                result
             }
             
             Expr::MethodCall { receiver, args, .. } => {
-                self.analyze_expr(receiver);
+                let mut result = ValueOrigins::foreign();
+                let value = self.analyze_expr(receiver);
+                self.lend(&value);
+                result.merge(value);
                 
                 for arg in args {
-                    self.analyze_expr(arg);
+                    let value = self.analyze_expr(arg);
+                    self.lend(&value);
+                    result.merge(value);
                 }
+                result
             }
             
-            Expr::FieldAccess { object, .. } => {
-                self.analyze_expr(object);
+            Expr::FieldAccess { object, field, .. } => {
+                // A field may hold anything that was stored into the object, unless it is
+                // primitive in every struct declaring it and is copied out
+                let origins = self.analyze_expr(object);
+                if self.primitive_fields.contains(field) && !self.other_fields.contains(field) {
+                    ValueOrigins::default()
+                } else {
+                    origins
+                }
             }
             
             Expr::Index { object, index, .. } => {
-                self.analyze_expr(object);
+                let origins = self.analyze_expr(object);
                 self.analyze_expr(index);
+                origins
             }
             
-            Expr::Array { elements, .. } => {
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => {
+                let mut origins = ValueOrigins::default();
                 for element in elements {
-                    self.analyze_expr(element);
+                    origins.merge(self.analyze_expr(element));
+                }
+                // `()` is unit, not an allocation
+                if !elements.is_empty() {
+                    origins.merge(self.allocation(expr));
                 }
+                origins
             }
             
-            Expr::Tuple { elements, .. } => {
-                for element in elements {
-                    self.analyze_expr(element);
-                }
+            Expr::ArrayRepeat { value, count, .. } => {
+                let mut origins = self.analyze_expr(value);
+                self.analyze_expr(count);
+                origins.merge(self.allocation(expr));
+                origins
             }
             
             Expr::StructInit { fields, .. } => {
+                let mut origins = ValueOrigins::default();
                 for field in fields {
-                    if let Some(ref value) = field.value {
-                        self.analyze_expr(value);
+                    match field.value {
+                        Some(ref value) => origins.merge(self.analyze_expr(value)),
+                        // Shorthand `Point { x }` stores the variable `x`
+                        None => origins.merge(self.find_variable_flow(field.name).map_or_else(ValueOrigins::default, |flow| flow.origins)),
                     }
                 }
+                origins.merge(self.allocation(expr));
+                origins
             }
             
             Expr::Block { statements, trailing_expr, .. } => {
@@ -352,13 +634,15 @@ impl EscapeAnalyzer {
                     self.analyze_stmt(stmt);
                 }
                 
+                let mut origins = ValueOrigins::default();
                 if let Some(trailing) = trailing_expr {
-                    self.analyze_expr(trailing);
+                    origins = self.analyze_expr(trailing);
                     // Trailing expression might escape the block
                     self.check_block_escape(trailing);
                 }
                 
                 self.exit_scope();
+                origins
             }
             
             Expr::If { condition, then_block, else_block, .. } => {
@@ -366,28 +650,31 @@ impl EscapeAnalyzer {
                 
                 // Analyze branches separately
                 self.enter_scope("if_then".to_string(), false, None);
-                self.analyze_expr(then_block);
+                let mut origins = self.analyze_expr(then_block);
                 self.exit_scope();
                 
                 if let Some(else_expr) = else_block {
                     self.enter_scope("if_else".to_string(), false, None);
-                    self.analyze_expr(else_expr);
+                    origins.merge(self.analyze_expr(else_expr));
                     self.exit_scope();
                 }
+                origins
             }
             
             Expr::Match { expr, arms, .. } => {
-                self.analyze_expr(expr);
+                let scrutinee = self.analyze_expr(expr);
                 
+                let mut origins = ValueOrigins::default();
                 for arm in arms {
                     self.enter_scope("match_arm".to_string(), false, None);
-                    self.analyze_pattern(&arm.pattern);
+                    self.analyze_pattern(&arm.pattern, &scrutinee);
                     if let Some(guard) = &arm.guard {
                         self.analyze_expr(guard);
                     }
-                    self.analyze_expr(&arm.body);
+                    origins.merge(self.analyze_expr(&arm.body));
                     self.exit_scope();
                 }
+                origins
             }
             
             Expr::While { condition, body, .. } => {
@@ -395,41 +682,47 @@ impl EscapeAnalyzer {
                 self.analyze_expr(condition);
                 self.analyze_expr(body);
                 self.exit_scope();
+                ValueOrigins::default()
             }
             
             Expr::For { pattern, iterator, body, .. } => {
-                self.analyze_expr(iterator);
+                let elements = self.analyze_expr(iterator);
                 
                 self.enter_scope("for".to_string(), false, None);
-                self.analyze_pattern(pattern);
+                self.analyze_pattern(pattern, &elements);
                 self.analyze_expr(body);
                 self.exit_scope();
+                ValueOrigins::default()
             }
             
             Expr::Return { value, .. } => {
                 if let Some(val) = value {
-                    self.analyze_expr(val);
+                    let origins = self.analyze_expr(val);
                     self.check_return_value_escape(val);
+                    self.escape(&origins, EscapeContext::FunctionReturn, val.span());
                 }
+                ValueOrigins::default()
             }
             
             Expr::Break { value, .. } => {
                 if let Some(val) = value {
-                    self.analyze_expr(val);
+                    self.analyze_break_value(val);
                 }
+                ValueOrigins::default()
             }
             
             Expr::Continue { .. } => {
                 // No escape effects
+                ValueOrigins::default()
             }
             
             // Additional expression types
             Expr::Cast { expr, .. } => {
-                self.analyze_expr(expr);
+                self.analyze_expr(expr)
             }
             
             Expr::Parenthesized { expr, .. } => {
-                self.analyze_expr(expr);
+                self.analyze_expr(expr)
             }
             
             Expr::Range { start, end, .. } => {
@@ -439,47 +732,59 @@ impl EscapeAnalyzer {
                 if let Some(end_expr) = end {
                     self.analyze_expr(end_expr);
                 }
+                ValueOrigins::default()
             }
             
             Expr::Closure { params, body, .. } => {
+                self.closure_boundaries.push(self.scope_stack.len());
+                // The closure may run after the region blocks around it are released
+                let outer_regions = std::mem::take(&mut self.region_blocks);
                 self.enter_scope("closure".to_string(), false, None);
                 for param in params {
-                    self.add_parameter(&param.pattern, &param.type_annotation);
+                    self.add_parameter(&param.pattern, &param.type_annotation, None);
                 }
-                self.analyze_expr(body);
+                let result = self.analyze_expr(body);
+                self.escape(&result, EscapeContext::FunctionReturn, result_span(body));
                 self.exit_scope();
+                self.region_blocks = outer_regions;
+                self.closure_boundaries.pop();
+                ValueOrigins::default()
             }
             
             Expr::Loop { body, .. } => {
+                self.loop_results.push((self.scope_stack.len(), ValueOrigins::default()));
                 self.enter_scope("loop".to_string(), false, None);
                 self.analyze_expr(body);
                 self.exit_scope();
+                self.loop_results.pop().map(|(_, result)| result).unwrap_or_default()
             }
             
             Expr::Box { expr, .. } => {
-                self.analyze_expr(expr);
                 // Box allocations could escape - TODO: implement proper escape analysis
+                self.analyze_expr(expr)
             }
             
             Expr::Reference { expr, .. } => {
-                self.analyze_expr(expr);
+                let origins = self.place_origins(expr);
                 self.check_address_escape(expr);
+                origins
             }
             
             Expr::Dereference { expr, .. } => {
-                self.analyze_expr(expr);
+                self.analyze_expr(expr)
             }
             
             Expr::Try { expr, .. } => {
-                self.analyze_expr(expr);
+                self.analyze_expr(expr)
             }
             
             Expr::Await { expr, .. } => {
-                self.analyze_expr(expr);
+                self.analyze_expr(expr)
             }
             
             Expr::Macro { .. } => {
                 // Macro invocations need special handling - simplified for now
+                ValueOrigins::default()
             }
         }
     }
@@ -491,12 +796,13 @@ impl EscapeAnalyzer {
                 self.analyze_expr(expr);
             }
             Stmt::Let { pattern, type_annotation, initializer, .. } => {
-                if let Some(init) = initializer {
-                    self.analyze_expr(init);
-                }
+                let origins = match initializer {
+                    Some(init) => self.analyze_expr(init),
+                    None => ValueOrigins::default(),
+                };
                 
                 // Add variable to current scope
-                self.add_variable_from_pattern(pattern, type_annotation, initializer.as_ref());
+                self.add_variable_from_pattern(pattern, type_annotation, origins);
             }
             Stmt::Item { item, .. } => {
                 self.analyze_item(item);
@@ -505,8 +811,9 @@ impl EscapeAnalyzer {
             // Additional statement types
             Stmt::Assignment { target, value, .. } => {
                 self.analyze_expr(target);
-                self.analyze_expr(value);
+                let origins = self.analyze_expr(value);
                 self.handle_assignment_escape(target, value);
+                self.handle_store(target, origins, value.span());
             }
             
             Stmt::CompoundAssignment { target, value, .. } => {
@@ -532,8 +839,8 @@ impl EscapeAnalyzer {
             }
             
             Stmt::For { pattern, iterable, body, .. } => {
-                self.analyze_expr(iterable);
-                self.analyze_pattern(pattern);
+                let elements = self.analyze_expr(iterable);
+                self.analyze_pattern(pattern, &elements);
                 for stmt in body {
                     self.analyze_stmt(stmt);
                 }
@@ -546,9 +853,9 @@ impl EscapeAnalyzer {
             }
             
             Stmt::Match { expr, arms, .. } => {
-                self.analyze_expr(expr);
+                let scrutinee = self.analyze_expr(expr);
                 for arm in arms {
-                    self.analyze_pattern(&arm.pattern);
+                    self.analyze_pattern(&arm.pattern, &scrutinee);
                     if let Some(guard) = &arm.guard {
                         self.analyze_expr(guard);
                     }
@@ -558,7 +865,7 @@ impl EscapeAnalyzer {
             
             Stmt::Break { expr, .. } => {
                 if let Some(expr) = expr {
-                    self.analyze_expr(expr);
+                    self.analyze_break_value(expr);
                 }
             }
             
@@ -568,8 +875,9 @@ impl EscapeAnalyzer {
             
             Stmt::Return { expr, .. } => {
                 if let Some(expr) = expr {
-                    self.analyze_expr(expr);
+                    let origins = self.analyze_expr(expr);
                     self.check_return_value_escape(expr);
+                    self.escape(&origins, EscapeContext::FunctionReturn, expr.span());
                 }
             }
             
@@ -581,14 +889,35 @@ impl EscapeAnalyzer {
                 self.exit_scope();
             }
             
+            Stmt::Region { name, body, span, .. } => {
+                self.enter_scope("region".to_string(), true, Some(*name));
+                self.region_blocks.push(RegionBlock { name: *name, span: *span, depth: self.scope_stack.len() - 1 });
+                for stmt in body {
+                    self.analyze_stmt(stmt);
+                }
+                self.region_blocks.pop();
+                self.exit_scope();
+            }
+            
             Stmt::Empty { .. } => {
                 // No operations
             }
         }
     }
     
-    /// Analyze pattern for variable declarations
-    fn analyze_pattern(&mut self, pattern: &Pattern) {
+    /// Analyze the value passed to `break`, which becomes a value of the innermost `loop`
+    fn analyze_break_value(&mut self, value: &Expr) {
+        let origins = self.analyze_expr(value);
+        if let Some((depth, _)) = self.loop_results.last() {
+            self.leave_regions(&origins, *depth, RegionExit::Break, value.span());
+        }
+        if let Some((_, result)) = self.loop_results.last_mut() {
+            result.merge(origins);
+        }
+    }
+    
+    /// Analyze pattern for variable declarations; every binding may refer to any of `origins`
+    fn analyze_pattern(&mut self, pattern: &Pattern, origins: &ValueOrigins) {
         match pattern {
             Pattern::Identifier { name, .. } => {
                 // Pattern binding - add to current scope
@@ -599,6 +928,7 @@ impl EscapeAnalyzer {
                     escape_context: EscapeContext::NoEscape,
                     lifetime: None,
                     performance_cost: 0,
+                    origins: origins.clone(),
                 };
                 
                 if let Some(scope) = self.scope_stack.last_mut() {
@@ -607,26 +937,26 @@ impl EscapeAnalyzer {
             }
             Pattern::Tuple { patterns, .. } => {
                 for p in patterns {
-                    self.analyze_pattern(p);
+                    self.analyze_pattern(p, origins);
                 }
             }
             Pattern::Struct { fields, .. } => {
                 for field in fields {
                     if let Some(ref pattern) = field.pattern {
-                        self.analyze_pattern(pattern);
+                        self.analyze_pattern(pattern, origins);
                     }
                 }
             }
             Pattern::Enum { patterns, .. } => {
                 if let Some(patterns) = patterns {
                     for p in patterns {
-                        self.analyze_pattern(p);
+                        self.analyze_pattern(p, origins);
                     }
                 }
             }
             Pattern::Array { patterns, .. } => {
                 for p in patterns {
-                    self.analyze_pattern(p);
+                    self.analyze_pattern(p, origins);
                 }
             }
             _ => {
@@ -779,8 +1109,9 @@ impl EscapeAnalyzer {
         }
     }
     
-    /// Add parameter to current scope
-    fn add_parameter(&mut self, pattern: &Pattern, type_annotation: &Option<Type>) {
+    /// Add parameter to current scope; `index` is its position among the parameters of
+    /// a function called by name
+    fn add_parameter(&mut self, pattern: &Pattern, type_annotation: &Option<Type>, index: Option<usize>) {
         if let Pattern::Identifier { name, .. } = pattern {
             let memory_strategy = if let Some(ty) = type_annotation {
                 self.get_memory_strategy(ty)
@@ -795,6 +1126,8 @@ impl EscapeAnalyzer {
                 escape_context: EscapeContext::NoEscape,
                 lifetime: None,
                 performance_cost: 0,
+                // Arguments come from the caller
+                origins: ValueOrigins { parameters: index.into_iter().collect(), ..ValueOrigins::foreign() },
             };
             
             if let Some(scope) = self.scope_stack.last_mut() {
@@ -803,26 +1136,178 @@ impl EscapeAnalyzer {
         }
     }
     
-    /// Add variable from let pattern
-    fn add_variable_from_pattern(&mut self, pattern: &Pattern, type_annotation: &Option<Type>, _initializer: Option<&Expr>) {
-        if let Pattern::Identifier { name, .. } = pattern {
-            let memory_strategy = if let Some(ty) = type_annotation {
-                self.get_memory_strategy(ty)
-            } else {
-                MemoryStrategy::Inferred
-            };
-            
-            let flow = ValueFlow {
-                variable: *name,
-                creation_site: self.current_position,
-                memory_strategy,
-                escape_context: EscapeContext::NoEscape,
-                lifetime: None,
-                performance_cost: 0,
-            };
-            
-            if let Some(scope) = self.scope_stack.last_mut() {
-                scope.variables.insert(*name, flow);
+    /// Add variables from let pattern, each referring to the initializer's `origins`
+    fn add_variable_from_pattern(&mut self, pattern: &Pattern, type_annotation: &Option<Type>, origins: ValueOrigins) {
+        let Pattern::Identifier { name, .. } = pattern else {
+            self.analyze_pattern(pattern, &origins);
+            return;
+        };
+        let memory_strategy = if let Some(ty) = type_annotation {
+            self.get_memory_strategy(ty)
+        } else {
+            MemoryStrategy::Inferred
+        };
+        
+        let flow = ValueFlow {
+            variable: *name,
+            creation_site: self.current_position,
+            memory_strategy,
+            escape_context: EscapeContext::NoEscape,
+            lifetime: None,
+            performance_cost: 0,
+            origins,
+        };
+        
+        if let Some(scope) = self.scope_stack.last_mut() {
+            scope.variables.insert(*name, flow);
+        }
+    }
+    
+    /// Path of a callee that may name an enum variant
+    fn variant_path<'e>(&self, callee: &'e Expr) -> Option<&'e [InternedString]> {
+        match callee {
+            Expr::Identifier { name, .. } => Some(std::slice::from_ref(name)),
+            Expr::Path { segments, .. } => Some(segments),
+            _ => None,
+        }
+    }
+    
+    /// Whether `path` names a variant of an enum with payloads, whose values are allocated
+    ///
+    /// A bare variant name counts unless a variable or item of that name shadows it.
+    fn constructs_payload_variant(&self, path: &[InternedString]) -> bool {
+        match path {
+            [.., enum_name, variant] => self.payload_enums.get(enum_name).is_some_and(|variants| variants.contains(variant)),
+            [variant] => {
+                !self.global_values.contains(variant)
+                    && self.find_variable_flow(*variant).is_none()
+                    && self.payload_enums.values().any(|variants| variants.contains(variant))
+            }
+            [] => false,
+        }
+    }
+    
+    /// Origins of the value a reference to the place `expr` points into
+    ///
+    /// A reference to a field points into the object holding it, whatever the field's type.
+    fn place_origins(&mut self, expr: &Expr) -> ValueOrigins {
+        match expr {
+            Expr::FieldAccess { object, .. } => self.place_origins(object),
+            _ => self.analyze_expr(expr),
+        }
+    }
+    
+    /// Record the allocating expression `expr`
+    fn allocation(&mut self, expr: &Expr) -> ValueOrigins {
+        let site = expr.span().start;
+        self.allocation_nodes.insert(site, expr.id());
+        self.escape_facts.entry(site).or_default();
+        if let Some(block) = self.region_blocks.last() {
+            self.allocation_regions.insert(site, *block);
+        }
+        ValueOrigins { allocations: vec![site], ..ValueOrigins::default() }
+    }
+    
+    /// Record that the values of `origins` leave the current function through `context`,
+    /// at the use `site`
+    ///
+    /// Parameters leaving other than by being returned are kept by the function.
+    fn escape(&mut self, origins: &ValueOrigins, context: EscapeContext, site: Span) {
+        self.leave_regions(origins, 0, RegionExit::Function(context.clone()), site);
+        if context != EscapeContext::FunctionReturn {
+            if let Some(function) = self.current_function {
+                self.retained_parameters.entry(function).or_default().extend(origins.parameters.iter().copied());
+            }
+        }
+        for site in &origins.allocations {
+            let Some(fact) = self.escape_facts.get_mut(site) else { continue };
+            if !fact.escapes() {
+                fact.context = context.clone();
+            } else if fact.context != context {
+                fact.is_shared = true;
+            }
+        }
+    }
+    
+    /// Record that the values of `origins` allocated in region blocks nested deeper than
+    /// the scope at `depth` reach it through `exit`, at the use `site`
+    fn leave_regions(&mut self, origins: &ValueOrigins, depth: usize, exit: RegionExit, site: Span) {
+        for allocation in &origins.allocations {
+            let Some(block) = self.allocation_regions.get(allocation).filter(|block| depth < block.depth) else { continue };
+            if let Some(fact) = self.escape_facts.get_mut(allocation) {
+                fact.region_escape.get_or_insert_with(|| RegionEscape {
+                    region: block.name,
+                    region_span: block.span,
+                    exit: exit.clone(),
+                    site,
+                });
+            }
+        }
+    }
+    
+    /// Record that the values of `origins` are passed to `function`, which keeps them
+    fn retain(&mut self, origins: &ValueOrigins, function: InternedString, site: Span) {
+        self.leave_regions(origins, 0, RegionExit::Retained(function), site);
+        self.escape(origins, EscapeContext::Heap, site);
+    }
+    
+    /// Record that the values of `origins` are lent to a call
+    fn lend(&mut self, origins: &ValueOrigins) {
+        for site in &origins.allocations {
+            if let Some(fact) = self.escape_facts.get_mut(site) {
+                fact.confidence = fact.confidence.min(EscapeFact::LENT_CONFIDENCE);
+            }
+        }
+    }
+    
+    /// Record `value`, written at `site`, being stored through the assignment target `target`
+    ///
+    /// Assigning a local makes it refer to the value as well. Storing into a field,
+    /// element or pointee only keeps the value in the function when the object
+    /// written to is known to be one of its own allocations. Either way, a local
+    /// declared outside a region block outlives the values allocated in it.
+    fn handle_store(&mut self, target: &Expr, value: ValueOrigins, site: Span) {
+        let (name, is_rebinding) = match target {
+            Expr::Identifier { name, .. } => (Some(*name), true),
+            _ => (self.get_root_identifier(target), false),
+        };
+        let Some(flow) = name.and_then(|name| self.find_variable_flow(name)) else {
+            self.escape(&value, EscapeContext::Heap, site);
+            return;
+        };
+        if !is_rebinding && (flow.origins.foreign || flow.origins.allocations.is_empty()) {
+            self.escape(&value, EscapeContext::Heap, site);
+            return;
+        }
+        if let Some(depth) = self.scope_stack.iter().rposition(|scope| scope.variables.contains_key(&flow.variable)) {
+            self.leave_regions(&value, depth, RegionExit::OuterBinding(flow.variable), site);
+        }
+        
+        // Values stored into an object that already escaped escape with it
+        let escaping = flow.origins.allocations.iter()
+            .filter_map(|site| self.escape_facts.get(site))
+            .find(|fact| fact.escapes())
+            .map(|fact| fact.context.clone());
+        if let (Some(context), false) = (escaping, is_rebinding) {
+            self.escape(&value, context, site);
+        }
+        self.update_origins(flow.variable, value);
+    }
+    
+    /// Record that a variable captured by an enclosing closure, read at `site`, lets its values escape
+    fn check_closure_capture(&mut self, name: InternedString, site: Span) {
+        let Some(&boundary) = self.closure_boundaries.last() else { return };
+        let declared_outside = self.scope_stack.iter().rposition(|scope| scope.variables.contains_key(&name))
+            .is_some_and(|depth| depth < boundary);
+        if !declared_outside {
+            return;
+        }
+        let Some(flow) = self.find_variable_flow(name) else { return };
+        self.escape(&flow.origins, EscapeContext::ClosureCapture, site);
+        // Both the closure and the enclosing function hold the value
+        for site in &flow.origins.allocations {
+            if let Some(fact) = self.escape_facts.get_mut(site) {
+                fact.is_shared = true;
             }
         }
     }
@@ -838,6 +1323,16 @@ impl EscapeAnalyzer {
         None
     }
     
+    /// Add `origins` to the values a variable may refer to
+    fn update_origins(&mut self, name: InternedString, origins: ValueOrigins) {
+        for scope in self.scope_stack.iter_mut().rev() {
+            if let Some(flow) = scope.variables.get_mut(&name) {
+                flow.origins.merge(origins);
+                return;
+            }
+        }
+    }
+    
     /// Update escape context for a variable
     fn update_escape_context(&mut self, name: InternedString, new_context: EscapeContext) {
         for scope in self.scope_stack.iter_mut().rev() {
@@ -876,6 +1371,19 @@ impl EscapeAnalyzer {
         }
     }
     
+    /// Get the variable a field, element or pointee access ultimately reads from
+    fn get_root_identifier(&self, expr: &Expr) -> Option<InternedString> {
+        match expr {
+            Expr::Identifier { name, .. } => Some(*name),
+            Expr::FieldAccess { object: inner, .. } |
+            Expr::Index { object: inner, .. } |
+            Expr::Dereference { expr: inner, .. } |
+            Expr::Parenthesized { expr: inner, .. } |
+            Expr::Unary { op: UnaryOp::Dereference, expr: inner, .. } => self.get_root_identifier(inner),
+            _ => None,
+        }
+    }
+    
     /// Get position from expression
     fn get_expr_position(&self, expr: &Expr) -> Position {
         match expr {
@@ -889,6 +1397,7 @@ impl EscapeAnalyzer {
             Expr::FieldAccess { span, .. } |
             Expr::Index { span, .. } |
             Expr::Array { span, .. } |
+            Expr::ArrayRepeat { span, .. } |
             Expr::Tuple { span, .. } |
             Expr::StructInit { span, .. } |
             Expr::Block { span, .. } |
@@ -1013,10 +1522,18 @@ impl EscapeAnalyzer {
     }
 }
 
+/// The expression whose value a function or closure body evaluates to
+fn result_span(body: &Expr) -> Span {
+    match body {
+        Expr::Block { trailing_expr: Some(value), .. } => value.span(),
+        _ => body.span(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ast::{Module, Span};
+    use crate::ast::Module;
     
     #[test]
     fn test_basic_escape_analysis() {
@@ -1024,6 +1541,7 @@ mod tests {
         
         let module = Module {
             items: vec![],
+            attributes: Vec::new(),
             span: Span::new(Position::start(0), Position::start(0)),
         };
         
@@ -1063,4 +1581,103 @@ mod tests {
         let errors = analyzer.errors;
         // Would check for LinearNotConsumed errors
     }
+    
+    /// Escape fact of the allocating expression starting at the first `marker` in `source`
+    fn fact_at(source: &str, marker: &str) -> EscapeFact {
+        let mut parser = crate::Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        let mut analyzer = EscapeAnalyzer::new();
+        analyzer.analyze_module(&module);
+        
+        let offset = source.find(marker).unwrap();
+        analyzer.escape_facts().iter()
+            .find(|(site, _)| site.offset == offset)
+            .map(|(_, fact)| fact.clone())
+            .unwrap_or_else(|| panic!("no allocation recorded at `{}`", marker))
+    }
+    
+    #[test]
+    fn test_escape_facts() {
+        let source = r#"
+            struct Point { x: i32, y: i32 }
+            struct Holder { point: Point }
+            
+            fn make() -> Point {
+                let local = Local { x: 1, y: 2 };
+                let sum = local.x + local.y;
+                let returned = Returned { x: sum, y: 0 };
+                returned
+            }
+            
+            fn early(flag: bool) -> Point {
+                if flag {
+                    return Early { x: 1, y: 1 };
+                }
+                Point { x: 0, y: 0 }
+            }
+            
+            fn store(holder: Holder) {
+                let stored = Stored { x: 3, y: 4 };
+                holder.point = stored;
+            }
+            
+            fn nested() -> Holder {
+                let inner = Inner { x: 5, y: 6 };
+                let outer = Outer { point: inner };
+                let pair = (7, 8);
+                outer
+            }
+            
+            fn capture() -> i32 {
+                let captured = Captured { x: 9, y: 10 };
+                let get = |scale: i32| captured.x * scale;
+                get(2)
+            }
+            
+            fn lend(point: Point) -> i32 { point.x }
+            
+            fn call() -> i32 {
+                let lent = Lent { x: 11, y: 12 };
+                let x = lend(lent);
+                x + 1
+            }
+            
+            fn returned_twice(holder: Holder) -> Point {
+                let shared = Shared { x: 13, y: 14 };
+                holder.point = shared;
+                shared
+            }
+            
+            fn project() -> i32 {
+                let projected = Projected { x: 15, y: 16 };
+                projected.x
+            }
+            
+            fn field_reference() -> &Point {
+                let holder = Referenced { point: Point { x: 17, y: 18 } };
+                &holder.point.x
+            }
+        "#;
+        
+        // Reading a primitive field copies it out of the value; a reference still points into it
+        assert!(!fact_at(source, "Projected {").escapes());
+        assert!(fact_at(source, "Referenced {").escapes());
+        for marker in ["Local {", "(7, 8)", "Lent {"] {
+            assert!(!fact_at(source, marker).escapes(), "{}", marker);
+        }
+        for marker in ["Returned {", "Early {", "Point { x: 0", "Outer {", "Inner {"] {
+            assert_eq!(fact_at(source, marker).context, EscapeContext::FunctionReturn, "{}", marker);
+        }
+        assert_eq!(fact_at(source, "Stored {").context, EscapeContext::Heap);
+        
+        let captured = fact_at(source, "Captured {");
+        assert_eq!(captured.context, EscapeContext::ClosureCapture);
+        assert!(captured.is_shared);
+        assert!(!fact_at(source, "Returned {").is_shared);
+        assert!(fact_at(source, "Shared {").is_shared);
+        
+        // Callees are assumed not to keep their arguments, with less confidence
+        assert_eq!(fact_at(source, "Local {").confidence, 100);
+        assert!(fact_at(source, "Lent {").confidence < 100);
+    }
 } 
\ No newline at end of file
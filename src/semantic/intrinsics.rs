@@ -0,0 +1,49 @@
+//! Functions built into the language
+//!
+//! Intrinsics are called like functions but declared nowhere. A call to one of their
+//! names that no function or variable in scope shadows is type checked against the
+//! signature here, and codegen lowers it to a call into the native runtime.
+
+use crate::ast::PrimitiveType;
+
+/// A function built into the language; none returns a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Intrinsic {
+    /// `print_int(value: i32)` writes `value` in decimal to standard output
+    PrintInt,
+    /// `print_str(text: str)` writes the bytes of `text` to standard output
+    PrintStr,
+}
+
+impl Intrinsic {
+    pub const ALL: [Intrinsic; 2] = [Intrinsic::PrintInt, Intrinsic::PrintStr];
+
+    /// The intrinsic called `name`, if any
+    pub fn named(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|intrinsic| intrinsic.name() == name)
+    }
+
+    /// Name it is called by
+    pub fn name(&self) -> &'static str {
+        match self {
+            Intrinsic::PrintInt => "print_int",
+            Intrinsic::PrintStr => "print_str",
+        }
+    }
+
+    /// Types of its parameters
+    pub fn parameters(&self) -> &'static [PrimitiveType] {
+        match self {
+            Intrinsic::PrintInt => &[PrimitiveType::I32],
+            Intrinsic::PrintStr => &[PrimitiveType::Str],
+        }
+    }
+
+    /// Function of the native runtime it is lowered to a call of
+    pub fn runtime_symbol(&self) -> &'static str {
+        match self {
+            Intrinsic::PrintInt => "bract_print_int",
+            Intrinsic::PrintStr => "bract_print_str",
+        }
+    }
+}
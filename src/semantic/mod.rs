@@ -11,10 +11,14 @@ pub mod symbols;
 pub mod types;
 pub mod ownership;
 pub mod escape_analysis;
+pub mod strategies;
+pub mod intrinsics;
 
 // Re-export key types for convenience
-pub use analyzer::{SemanticAnalyzer, SemanticError, SemanticWarning};
+pub use analyzer::{AnalyzerConfig, CycleField, SemanticAnalyzer, SemanticError, SemanticWarning};
 pub use symbols::{SymbolTable, SymbolTableBuilder, Symbol, SymbolKind, Scope};
 pub use types::{TypeSystem, TypeChecker, TypeError, InferenceContext, OwnershipTracker};
 pub use ownership::{OwnershipAnalyzer, OwnershipError, BorrowInfo, VariableState};
-pub use escape_analysis::{EscapeAnalyzer, EscapeError, ValueFlow, EscapeContext}; 
+pub use escape_analysis::{EscapeAnalyzer, EscapeError, ValueFlow, ValueOrigins, EscapeContext, EscapeFact, RegionEscape, RegionExit};
+pub use intrinsics::Intrinsic;
+pub use strategies::{StrategyChecker, StrategyError, StrategyRequest, Conversion};
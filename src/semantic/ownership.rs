@@ -9,12 +9,19 @@
 //! - Memory strategy compatibility checking
 //! - Escape analysis for leak prevention
 //! - Integration with performance contracts
+//!
+//! Values of struct, generic and linear types move; primitives and references are copied.
+//! A borrow lasts as long as a variable holding it is read again later, so a reference that
+//! is no longer used does not keep its owner borrowed until the end of the block.
 
 use crate::ast::{
-    Type, Expr, Stmt, Item, Module, Pattern, InternedString,
-    MemoryStrategy, LifetimeId, BinaryOp, UnaryOp
+    Type, Expr, Stmt, Item, ImplItem, Module, Pattern, Parameter, MatchArm, InternedString,
+    Span, MemoryStrategy, LifetimeId, BinaryOp, UnaryOp
 };
+use crate::diagnostics::DiagnosticCode;
 use crate::lexer::Position;
+use crate::parser::StringInterner;
+use crate::visitor::{self, Visitor};
 use std::collections::{HashMap, HashSet};
 
 /// Ownership analysis errors
@@ -23,23 +30,30 @@ pub enum OwnershipError {
     /// Use after move
     UseAfterMove {
         variable: InternedString,
-        moved_at: Position,
-        used_at: Position,
+        moved_at: Span,
+        used_at: Span,
         move_reason: MoveReason,
     },
     
+    /// Move of a value while a borrow of it is still in use
+    MoveWhileBorrowed {
+        variable: InternedString,
+        borrowed_at: Span,
+        moved_at: Span,
+    },
+    
     /// Multiple mutable borrows
     MultipleMutableBorrows {
         variable: InternedString,
-        first_borrow: Position,
-        second_borrow: Position,
+        first_borrow: Span,
+        second_borrow: Span,
     },
     
     /// Mutable and immutable borrow conflict
     MutableImmutableConflict {
         variable: InternedString,
-        mutable_borrow: Position,
-        immutable_borrow: Position,
+        mutable_borrow: Span,
+        immutable_borrow: Span,
     },
     
     /// Borrow outlives owner
@@ -52,8 +66,8 @@ pub enum OwnershipError {
     /// Linear type used more than once
     LinearTypeReuse {
         variable: InternedString,
-        first_use: Position,
-        second_use: Position,
+        first_use: Span,
+        second_use: Span,
     },
     
     /// Memory strategy incompatibility
@@ -79,6 +93,114 @@ pub enum OwnershipError {
     },
 }
 
+impl OwnershipError {
+    /// Source location of the offending use
+    pub fn span(&self) -> Span {
+        match self {
+            OwnershipError::UseAfterMove { used_at, .. } => *used_at,
+            OwnershipError::MoveWhileBorrowed { moved_at, .. } => *moved_at,
+            OwnershipError::MultipleMutableBorrows { second_borrow, .. } => *second_borrow,
+            OwnershipError::MutableImmutableConflict { mutable_borrow, immutable_borrow, .. } => {
+                if is_before(*mutable_borrow, *immutable_borrow) { *immutable_borrow } else { *mutable_borrow }
+            }
+            OwnershipError::LinearTypeReuse { second_use, .. } => *second_use,
+            OwnershipError::BorrowOutlivesOwner { borrow_site: position, .. }
+            | OwnershipError::StrategyIncompatibility { position, .. }
+            | OwnershipError::EscapeViolation { escape_site: position, .. }
+            | OwnershipError::InvalidRegionAccess { access_site: position, .. } => Span::single(*position),
+        }
+    }
+    
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            OwnershipError::UseAfterMove { .. } => DiagnosticCode::UseAfterMove,
+            OwnershipError::MoveWhileBorrowed { .. } => DiagnosticCode::MoveWhileBorrowed,
+            OwnershipError::MultipleMutableBorrows { .. } => DiagnosticCode::MultipleMutableBorrows,
+            OwnershipError::MutableImmutableConflict { .. } => DiagnosticCode::ConflictingBorrows,
+            OwnershipError::BorrowOutlivesOwner { .. } => DiagnosticCode::BorrowOutlivesOwner,
+            OwnershipError::LinearTypeReuse { .. } => DiagnosticCode::LinearReuse,
+            OwnershipError::StrategyIncompatibility { .. } => DiagnosticCode::StrategyMismatch,
+            OwnershipError::EscapeViolation { .. } => DiagnosticCode::Escape,
+            OwnershipError::InvalidRegionAccess { .. } => DiagnosticCode::InvalidRegionAccess,
+        }
+    }
+    
+    /// The earlier move, borrow or drop that makes the use at `span()` invalid, and what happened there
+    pub fn related(&self) -> Option<(Span, &'static str)> {
+        match self {
+            OwnershipError::UseAfterMove { moved_at, move_reason, .. } => {
+                let label = match move_reason {
+                    MoveReason::FunctionCall => "value moved here, into a function call",
+                    MoveReason::Assignment => "value moved here, by an assignment",
+                    MoveReason::Return => "value moved here, by a return",
+                    MoveReason::PatternMatch => "value moved here, by a pattern match",
+                };
+                Some((*moved_at, label))
+            }
+            OwnershipError::MoveWhileBorrowed { borrowed_at, .. } => Some((*borrowed_at, "value borrowed here")),
+            OwnershipError::MultipleMutableBorrows { first_borrow, .. } => {
+                Some((*first_borrow, "first mutable borrow occurs here"))
+            }
+            OwnershipError::MutableImmutableConflict { mutable_borrow, immutable_borrow, .. } => {
+                if is_before(*mutable_borrow, *immutable_borrow) {
+                    Some((*mutable_borrow, "mutable borrow occurs here"))
+                } else {
+                    Some((*immutable_borrow, "immutable borrow occurs here"))
+                }
+            }
+            OwnershipError::BorrowOutlivesOwner { owner_drop_site, .. } => {
+                Some((Span::single(*owner_drop_site), "value dropped here"))
+            }
+            OwnershipError::LinearTypeReuse { first_use, .. } => Some((*first_use, "value used here first")),
+            OwnershipError::StrategyIncompatibility { .. }
+            | OwnershipError::EscapeViolation { .. }
+            | OwnershipError::InvalidRegionAccess { .. } => None,
+        }
+    }
+    
+    /// Human-readable message, with names resolved through `interner`
+    pub fn message(&self, interner: &StringInterner) -> String {
+        let resolve = |name: &InternedString| interner.resolve(*name).unwrap_or_else(|| "<unknown>".to_string());
+        match self {
+            OwnershipError::UseAfterMove { variable, .. } => format!("use of moved value `{}`", resolve(variable)),
+            OwnershipError::MoveWhileBorrowed { variable, .. } => {
+                format!("cannot move out of `{}` because it is borrowed", resolve(variable))
+            }
+            OwnershipError::MultipleMutableBorrows { variable, .. } => {
+                format!("cannot borrow `{}` as mutable more than once at a time", resolve(variable))
+            }
+            OwnershipError::MutableImmutableConflict { variable, mutable_borrow, immutable_borrow } => {
+                if is_before(*mutable_borrow, *immutable_borrow) {
+                    format!("cannot borrow `{}` as immutable because it is also borrowed as mutable", resolve(variable))
+                } else {
+                    format!("cannot borrow `{}` as mutable because it is also borrowed as immutable", resolve(variable))
+                }
+            }
+            OwnershipError::BorrowOutlivesOwner { variable, .. } => {
+                format!("borrow of `{}` outlives the value", resolve(variable))
+            }
+            OwnershipError::LinearTypeReuse { variable, .. } => {
+                format!("linear value `{}` is used more than once", resolve(variable))
+            }
+            OwnershipError::StrategyIncompatibility { expected_strategy, found_strategy, .. } => {
+                format!("expected {:?} memory strategy, found {:?}", expected_strategy, found_strategy)
+            }
+            OwnershipError::EscapeViolation { variable, escapes_from, .. } => {
+                format!("`{}` escapes from its {}", resolve(variable), escapes_from)
+            }
+            OwnershipError::InvalidRegionAccess { variable, region, reason, .. } => {
+                format!("invalid access to `{}` in region `{}`: {}", resolve(variable), resolve(region), reason)
+            }
+        }
+    }
+}
+
+/// Whether `a` starts before `b`
+fn is_before(a: Span, b: Span) -> bool {
+    a.start.offset < b.start.offset
+}
+
 /// Reason why a value was moved
 #[derive(Debug, Clone, PartialEq)]
 pub enum MoveReason {
@@ -100,9 +222,11 @@ pub struct BorrowInfo {
     /// Lifetime of the borrow
     pub lifetime: LifetimeId,
     /// Where the borrow was created
-    pub borrow_site: Position,
-    /// What was borrowed
+    pub borrow_site: Span,
+    /// What was borrowed: the variable, then the fields borrowed through
     pub borrowed_path: Vec<InternedString>,
+    /// Variables holding the reference; none while it is a temporary of its statement
+    pub holders: Vec<InternedString>,
 }
 
 /// Variable state in ownership analysis
@@ -112,7 +236,7 @@ pub enum VariableState {
     Owned,
     /// Variable has been moved
     Moved {
-        moved_at: Position,
+        moved_at: Span,
         reason: MoveReason,
     },
     /// Variable is borrowed (immutably)
@@ -126,10 +250,41 @@ pub enum VariableState {
     /// Linear variable (can only be used once)
     Linear {
         used: bool,
-        used_at: Option<Position>,
+        used_at: Option<Span>,
     },
 }
 
+impl VariableState {
+    /// Borrows of the variable, if it is borrowed
+    pub fn borrows(&self) -> &[BorrowInfo] {
+        match self {
+            VariableState::Borrowed { borrows } => borrows,
+            VariableState::MutablyBorrowed { borrow } => std::slice::from_ref(borrow),
+            _ => &[],
+        }
+    }
+    
+    fn borrows_mut(&mut self) -> &mut [BorrowInfo] {
+        match self {
+            VariableState::Borrowed { borrows } => borrows,
+            VariableState::MutablyBorrowed { borrow } => std::slice::from_mut(borrow),
+            _ => &mut [],
+        }
+    }
+    
+    /// Replace the borrows of an owned or borrowed variable
+    fn set_borrows(&mut self, mut borrows: Vec<BorrowInfo>) {
+        if !matches!(self, VariableState::Owned | VariableState::Borrowed { .. } | VariableState::MutablyBorrowed { .. }) {
+            return;
+        }
+        *self = match borrows.iter().rposition(|borrow| borrow.is_mutable) {
+            Some(index) => VariableState::MutablyBorrowed { borrow: borrows.swap_remove(index) },
+            None if borrows.is_empty() => VariableState::Owned,
+            None => VariableState::Borrowed { borrows },
+        };
+    }
+}
+
 /// Memory region information
 #[derive(Debug, Clone, PartialEq)]
 pub struct RegionInfo {
@@ -145,13 +300,33 @@ pub struct RegionInfo {
     pub is_active: bool,
 }
 
+/// A variable in scope in the function being analyzed
+#[derive(Debug, Clone)]
+struct Binding {
+    state: VariableState,
+    /// Whether passing the value on moves it rather than copying it
+    is_move: bool,
+    /// Where the variable was bound
+    span: Span,
+}
+
 /// Ownership and borrowing analyzer
 #[derive(Debug)]
 pub struct OwnershipAnalyzer {
-    /// Current variable states
-    variable_states: HashMap<InternedString, VariableState>,
-    /// Active borrows
-    active_borrows: Vec<BorrowInfo>,
+    /// Variables in scope in the function being analyzed
+    variables: HashMap<InternedString, Binding>,
+    /// Bindings shadowed in each open block, restored when it closes
+    scopes: Vec<Vec<(InternedString, Option<Binding>)>>,
+    /// Where each name is read in the function being analyzed
+    uses: HashMap<InternedString, Vec<Position>>,
+    /// Spans of the loops around the code being analyzed
+    loops: Vec<Span>,
+    /// Whether the path being analyzed has already returned, broken out or continued
+    diverges: bool,
+    /// Declared return types of the module's functions
+    return_types: HashMap<InternedString, Option<Type>>,
+    /// Type aliases declared in the module
+    aliases: HashMap<InternedString, Type>,
     /// Memory regions
     #[allow(dead_code)]
     regions: HashMap<InternedString, RegionInfo>,
@@ -162,27 +337,30 @@ pub struct OwnershipAnalyzer {
     next_lifetime_id: u32,
     /// Errors found during analysis
     errors: Vec<OwnershipError>,
-    /// Current position for error reporting
-    current_position: Position,
 }
 
 impl OwnershipAnalyzer {
     /// Create a new ownership analyzer
     pub fn new() -> Self {
         Self {
-            variable_states: HashMap::new(),
-            active_borrows: Vec::new(),
+            variables: HashMap::new(),
+            scopes: Vec::new(),
+            uses: HashMap::new(),
+            loops: Vec::new(),
+            diverges: false,
+            return_types: HashMap::new(),
+            aliases: HashMap::new(),
             regions: HashMap::new(),
             region_stack: Vec::new(),
             next_lifetime_id: 0,
             errors: Vec::new(),
-            current_position: Position::start(0),
         }
     }
     
     /// Analyze ownership for a module
     pub fn analyze_module(&mut self, module: &Module) -> Vec<OwnershipError> {
         self.errors.clear();
+        self.declare_items(&module.items);
         
         for item in &module.items {
             self.analyze_item(item);
@@ -194,23 +372,27 @@ impl OwnershipAnalyzer {
         self.errors.clone()
     }
     
+    /// Record the return types and type aliases declared by `items` and their inline modules
+    fn declare_items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Function { name, return_type, .. } => {
+                    self.return_types.insert(*name, return_type.clone());
+                }
+                Item::TypeAlias { name, target_type, .. } => {
+                    self.aliases.insert(*name, target_type.clone());
+                }
+                Item::Module { items: Some(items), .. } => self.declare_items(items),
+                _ => {}
+            }
+        }
+    }
+    
     /// Analyze an item (function, struct, etc.)
     fn analyze_item(&mut self, item: &Item) {
         match item {
             Item::Function { body: Some(body), params, .. } => {
-                // Create new scope for function
-                self.enter_function_scope();
-                
-                // Add parameters to scope
-                for param in params {
-                    self.add_parameter(&param.pattern, &param.type_annotation);
-                }
-                
-                // Analyze function body
-                self.analyze_expr(body);
-                
-                // Exit function scope
-                self.exit_function_scope();
+                self.analyze_function(params, body);
             }
             Item::Function { body: None, .. } => {
                 // External function - no analysis needed
@@ -231,15 +413,8 @@ impl OwnershipAnalyzer {
             }
             Item::Impl { items, .. } => {
                 for impl_item in items {
-                    if let crate::ast::ImplItem::Function { body: Some(body), params, .. } = impl_item {
-                        self.enter_function_scope();
-                        
-                        for param in params {
-                            self.add_parameter(&param.pattern, &param.type_annotation);
-                        }
-                        
-                        self.analyze_expr(body);
-                        self.exit_function_scope();
+                    if let ImplItem::Function { body: Some(body), params, .. } = impl_item {
+                        self.analyze_function(params, body);
                     }
                 }
             }
@@ -249,22 +424,32 @@ impl OwnershipAnalyzer {
         }
     }
     
+    /// Analyze a function body with its parameters in scope
+    fn analyze_function(&mut self, params: &[Parameter], body: &Expr) {
+        let enclosing = self.enter_function_scope(body);
+        
+        for param in params {
+            self.add_parameter(&param.pattern, &param.type_annotation);
+        }
+        self.analyze_expr(body);
+        
+        self.exit_function_scope(enclosing);
+    }
+    
     /// Analyze an expression
     fn analyze_expr(&mut self, expr: &Expr) {
-        self.current_position = self.get_expr_position(expr);
-        
         match expr {
             Expr::Literal { .. } => {
                 // Literals don't affect ownership
             }
             
-            Expr::Identifier { name, .. } => {
-                self.check_variable_usage(*name);
+            Expr::Identifier { name, span, .. } => {
+                self.check_variable_usage(*name, *span);
             }
             
-            Expr::Path { segments, .. } => {
+            Expr::Path { segments, span, .. } => {
                 if let Some(first) = segments.first() {
-                    self.check_variable_usage(*first);
+                    self.check_variable_usage(*first, *span);
                 }
             }
             
@@ -273,7 +458,8 @@ impl OwnershipAnalyzer {
                     BinaryOp::Assign => {
                         // Assignment moves the right side
                         self.analyze_expr(right);
-                        self.handle_assignment(left, right);
+                        self.move_out(right, MoveReason::Assignment);
+                        self.assign(left, right);
                     }
                     _ => {
                         self.analyze_expr(left);
@@ -282,33 +468,36 @@ impl OwnershipAnalyzer {
                 }
             }
             
-            Expr::Unary { expr, op, .. } => {
+            Expr::Unary { expr: operand, op, span, .. } => {
                 match op {
                     UnaryOp::AddressOf => {
                         // Taking address creates an immutable borrow
-                        self.create_immutable_borrow(expr);
+                        self.analyze_expr(operand);
+                        self.create_borrow(operand, false, *span);
                     }
                     UnaryOp::MutableRef => {
                         // Taking mutable address creates a mutable borrow
-                        self.create_mutable_borrow(expr);
-                    }
-                    UnaryOp::Dereference => {
-                        // Dereferencing uses the pointer
-                        self.analyze_expr(expr);
+                        self.analyze_expr(operand);
+                        self.create_borrow(operand, true, *span);
                     }
                     _ => {
-                        self.analyze_expr(expr);
+                        self.analyze_expr(operand);
                     }
                 }
             }
             
+            Expr::Reference { is_mutable, expr: operand, span, .. } => {
+                self.analyze_expr(operand);
+                self.create_borrow(operand, *is_mutable, *span);
+            }
+            
             Expr::Call { callee, args, .. } => {
                 self.analyze_expr(callee);
                 
                 // Arguments are moved into function calls (unless borrowed)
                 for arg in args {
                     self.analyze_expr(arg);
-                    self.handle_move_into_call(arg);
+                    self.move_out(arg, MoveReason::FunctionCall);
                 }
             }
             
@@ -317,7 +506,7 @@ impl OwnershipAnalyzer {
                 
                 for arg in args {
                     self.analyze_expr(arg);
-                    self.handle_move_into_call(arg);
+                    self.move_out(arg, MoveReason::FunctionCall);
                 }
             }
             
@@ -330,27 +519,37 @@ impl OwnershipAnalyzer {
                 self.analyze_expr(index);
             }
             
-            Expr::Array { elements, .. } => {
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => {
                 for element in elements {
                     self.analyze_expr(element);
+                    self.move_out(element, MoveReason::Assignment);
                 }
             }
             
-            Expr::Tuple { elements, .. } => {
-                for element in elements {
-                    self.analyze_expr(element);
-                }
+            Expr::ArrayRepeat { value, count, .. } => {
+                self.analyze_expr(value);
+                self.move_out(value, MoveReason::Assignment);
+                self.analyze_expr(count);
             }
             
             Expr::StructInit { fields, .. } => {
                 for field in fields {
-                    if let Some(ref value) = field.value {
-                        self.analyze_expr(value);
+                    match &field.value {
+                        Some(value) => {
+                            self.analyze_expr(value);
+                            self.move_out(value, MoveReason::Assignment);
+                        }
+                        // Shorthand `Point { x }` moves the variable `x`
+                        None => {
+                            self.check_variable_usage(field.name, field.span);
+                            self.move_variable(field.name, field.span, MoveReason::Assignment);
+                        }
                     }
                 }
             }
             
             Expr::Block { statements, trailing_expr, .. } => {
+                self.enter_block();
                 for stmt in statements {
                     self.analyze_stmt(stmt);
                 }
@@ -358,6 +557,7 @@ impl OwnershipAnalyzer {
                 if let Some(trailing) = trailing_expr {
                     self.analyze_expr(trailing);
                 }
+                self.exit_block();
             }
             
             Expr::If { condition, then_block, else_block, .. } => {
@@ -380,57 +580,48 @@ impl OwnershipAnalyzer {
             
             Expr::Match { expr, arms, .. } => {
                 self.analyze_expr(expr);
-                
-                let mut arm_states = Vec::new();
-                let base_state = self.save_state();
-                
-                for arm in arms {
-                    self.restore_state(base_state.clone());
-                    self.analyze_pattern(&arm.pattern);
-                    if let Some(guard) = &arm.guard {
-                        self.analyze_expr(guard);
-                    }
-                    self.analyze_expr(&arm.body);
-                    arm_states.push(self.save_state());
-                }
-                
-                // Merge all arm states
-                if !arm_states.is_empty() {
-                    let mut merged_state = arm_states[0].clone();
-                    for state in arm_states.into_iter().skip(1) {
-                        merged_state = self.merge_two_states(merged_state, state);
-                    }
-                    self.restore_state(merged_state);
-                }
+                self.analyze_match_arms(arms);
             }
             
-            Expr::While { condition, body, .. } => {
-                // Loop analysis is complex - simplified for now
-                self.analyze_expr(condition);
-                self.analyze_expr(body);
+            Expr::While { condition, body, span, .. } => {
+                self.analyze_loop(*span, |analyzer| {
+                    analyzer.analyze_expr(condition);
+                    analyzer.analyze_expr(body);
+                });
             }
             
-            Expr::For { pattern, iterator, body, .. } => {
+            Expr::For { pattern, iterator, body, span, .. } => {
                 self.analyze_expr(iterator);
-                self.analyze_pattern(pattern);
-                self.analyze_expr(body);
+                self.analyze_loop(*span, |analyzer| {
+                    analyzer.enter_block();
+                    analyzer.analyze_pattern(pattern);
+                    analyzer.analyze_expr(body);
+                    analyzer.exit_block();
+                });
+            }
+            
+            Expr::Loop { body, span, .. } => {
+                self.analyze_loop(*span, |analyzer| analyzer.analyze_expr(body));
             }
             
             Expr::Return { value, .. } => {
                 if let Some(val) = value {
                     self.analyze_expr(val);
-                    self.handle_return_move(val);
+                    self.move_out(val, MoveReason::Return);
                 }
+                self.diverges = true;
             }
             
             Expr::Break { value, .. } => {
                 if let Some(val) = value {
                     self.analyze_expr(val);
+                    self.move_out(val, MoveReason::Assignment);
                 }
+                self.diverges = true;
             }
             
             Expr::Continue { .. } => {
-                // No ownership effects
+                self.diverges = true;
             }
             
             // Additional expression types
@@ -452,24 +643,21 @@ impl OwnershipAnalyzer {
             }
             
             Expr::Closure { params, body, .. } => {
-                self.enter_function_scope();
+                // Captured variables are checked where the closure is written, but what the
+                // body does to them only happens when it is called
+                let saved_state = self.save_state();
+                self.enter_block();
                 for param in params {
                     self.add_parameter(&param.pattern, &param.type_annotation);
                 }
                 self.analyze_expr(body);
-                self.exit_function_scope();
-            }
-            
-            Expr::Loop { body, .. } => {
-                self.analyze_expr(body);
+                self.exit_block();
+                self.restore_state(saved_state);
             }
             
             Expr::Box { expr, .. } => {
                 self.analyze_expr(expr);
-            }
-            
-            Expr::Reference { expr, .. } => {
-                self.analyze_expr(expr);
+                self.move_out(expr, MoveReason::Assignment);
             }
             
             Expr::Dereference { expr, .. } => {
@@ -499,26 +687,153 @@ impl OwnershipAnalyzer {
             Stmt::Let { pattern, type_annotation, initializer, .. } => {
                 if let Some(init) = initializer {
                     self.analyze_expr(init);
+                    self.move_out(init, MoveReason::Assignment);
                 }
                 
                 // Add variable to scope with appropriate ownership
                 self.add_variable_from_pattern(pattern, type_annotation, initializer.as_ref());
             }
+            Stmt::Assignment { target, value, .. } => {
+                self.analyze_expr(value);
+                self.move_out(value, MoveReason::Assignment);
+                self.assign(target, value);
+            }
+            Stmt::CompoundAssignment { target, value, .. } => {
+                self.analyze_expr(value);
+                self.analyze_expr(target);
+            }
+            Stmt::If { condition, then_block, else_block, .. } => {
+                self.analyze_expr(condition);
+                
+                let saved_state = self.save_state();
+                self.analyze_block(then_block);
+                let then_state = self.save_state();
+                
+                self.restore_state(saved_state);
+                if let Some(else_stmt) = else_block {
+                    self.analyze_stmt(else_stmt);
+                }
+                let else_state = self.save_state();
+                
+                self.merge_states(then_state, else_state);
+            }
+            Stmt::While { condition, body, span, .. } => {
+                self.analyze_loop(*span, |analyzer| {
+                    analyzer.analyze_expr(condition);
+                    analyzer.analyze_block(body);
+                });
+            }
+            Stmt::For { pattern, iterable, body, span, .. } => {
+                self.analyze_expr(iterable);
+                self.analyze_loop(*span, |analyzer| {
+                    analyzer.enter_block();
+                    analyzer.analyze_pattern(pattern);
+                    for stmt in body {
+                        analyzer.analyze_stmt(stmt);
+                    }
+                    analyzer.exit_block();
+                });
+            }
+            Stmt::Loop { body, span, .. } => {
+                self.analyze_loop(*span, |analyzer| analyzer.analyze_block(body));
+            }
+            Stmt::Match { expr, arms, .. } => {
+                self.analyze_expr(expr);
+                self.analyze_match_arms(arms);
+            }
+            Stmt::Break { expr, .. } => {
+                if let Some(value) = expr {
+                    self.analyze_expr(value);
+                    self.move_out(value, MoveReason::Assignment);
+                }
+                self.diverges = true;
+            }
+            Stmt::Continue { .. } => {
+                self.diverges = true;
+            }
+            Stmt::Return { expr, .. } => {
+                if let Some(value) = expr {
+                    self.analyze_expr(value);
+                    self.move_out(value, MoveReason::Return);
+                }
+                self.diverges = true;
+            }
+            Stmt::Block { statements, .. } | Stmt::Region { body: statements, .. } => {
+                self.analyze_block(statements);
+            }
             Stmt::Item { item, .. } => {
+                self.declare_items(std::slice::from_ref(item));
                 self.analyze_item(item);
             }
-            _ => {
-                // Other statement types - simplified for now
+            Stmt::Empty { .. } => {}
+        }
+        
+        // Borrows nothing holds on to end with the statement that created them
+        self.end_temporary_borrows();
+    }
+    
+    /// Analyze `statements` in a block of their own
+    fn analyze_block(&mut self, statements: &[Stmt]) {
+        self.enter_block();
+        for stmt in statements {
+            self.analyze_stmt(stmt);
+        }
+        self.exit_block();
+    }
+    
+    /// Analyze match arms from the same starting state and merge where they end up
+    fn analyze_match_arms(&mut self, arms: &[MatchArm]) {
+        let mut arm_states = Vec::new();
+        let base_state = self.save_state();
+        
+        for arm in arms {
+            self.restore_state(base_state.clone());
+            self.enter_block();
+            self.analyze_pattern(&arm.pattern);
+            if let Some(guard) = &arm.guard {
+                self.analyze_expr(guard);
             }
+            self.analyze_expr(&arm.body);
+            self.exit_block();
+            arm_states.push(self.save_state());
+        }
+        
+        // Merge all arm states
+        if let Some(first) = arm_states.first().cloned() {
+            let merged_state = arm_states.into_iter().skip(1)
+                .fold(first, |merged, state| self.merge_two_states(merged, state));
+            self.restore_state(merged_state);
+        }
+    }
+    
+    /// Analyze a loop, going through its body a second time when the first iteration
+    /// moves a value the next one could still use
+    fn analyze_loop(&mut self, span: Span, mut analyze_body: impl FnMut(&mut Self)) {
+        let before = self.save_state();
+        self.loops.push(span);
+        
+        analyze_body(self);
+        let mut after = self.save_state();
+        if !after.diverges && newly_moved(&before, &after) {
+            let next_iteration = self.merge_two_states(before.clone(), after);
+            self.restore_state(next_iteration);
+            analyze_body(self);
+            after = self.save_state();
         }
+        
+        self.loops.pop();
+        // The loop may be left after any iteration, or before the first
+        after.diverges = false;
+        let merged = self.merge_two_states(before, after);
+        self.restore_state(merged);
     }
     
     /// Analyze a pattern for ownership effects
     fn analyze_pattern(&mut self, pattern: &Pattern) {
         match pattern {
-            Pattern::Identifier { name, .. } => {
+            Pattern::Identifier { name, span, .. } => {
                 // Pattern introduces a new binding
-                self.variable_states.insert(*name, VariableState::Owned);
+                self.bind(*name, Binding { state: VariableState::Owned, is_move: false, span: *span });
             }
             Pattern::Tuple { patterns, .. } => {
                 for p in patterns {
@@ -527,8 +842,9 @@ impl OwnershipAnalyzer {
             }
             Pattern::Struct { fields, .. } => {
                 for field in fields {
-                    if let Some(ref pattern) = field.pattern {
-                        self.analyze_pattern(pattern);
+                    match &field.pattern {
+                        Some(pattern) => self.analyze_pattern(pattern),
+                        None => self.bind(field.name, Binding { state: VariableState::Owned, is_move: false, span: field.span }),
                     }
                 }
             }
@@ -544,6 +860,9 @@ impl OwnershipAnalyzer {
                     self.analyze_pattern(p);
                 }
             }
+            Pattern::Reference { pattern, .. } => {
+                self.analyze_pattern(pattern);
+            }
             _ => {
                 // Other patterns don't introduce bindings
             }
@@ -551,167 +870,361 @@ impl OwnershipAnalyzer {
     }
     
     /// Check if a variable can be used
-    fn check_variable_usage(&mut self, name: InternedString) {
-        if let Some(state) = self.variable_states.get(&name) {
-            match state {
-                VariableState::Moved { moved_at, reason } => {
-                    self.errors.push(OwnershipError::UseAfterMove {
-                        variable: name,
-                        moved_at: *moved_at,
-                        used_at: self.current_position,
-                        move_reason: reason.clone(),
-                    });
-                }
-                VariableState::Linear { used: true, used_at } => {
-                    self.errors.push(OwnershipError::LinearTypeReuse {
-                        variable: name,
-                        first_use: used_at.unwrap(),
-                        second_use: self.current_position,
-                    });
-                }
-                _ => {
-                    // Usage is valid
-                }
+    fn check_variable_usage(&mut self, name: InternedString, span: Span) {
+        let error = match self.variables.get(&name).map(|binding| &binding.state) {
+            Some(VariableState::Moved { moved_at, reason }) => OwnershipError::UseAfterMove {
+                variable: name,
+                moved_at: *moved_at,
+                used_at: span,
+                move_reason: reason.clone(),
+            },
+            Some(VariableState::Linear { used: true, used_at: Some(first_use) }) => OwnershipError::LinearTypeReuse {
+                variable: name,
+                first_use: *first_use,
+                second_use: span,
+            },
+            _ => return, // Usage is valid
+        };
+        self.push_error(error);
+    }
+    
+    /// Write the value of `value` to `target`: a moved variable is usable again, and a
+    /// reference variable lets go of what it borrowed and holds what `value` borrows instead
+    fn assign(&mut self, target: &Expr, value: &Expr) {
+        let Expr::Identifier { name, .. } = target else {
+            self.analyze_expr(target);
+            return;
+        };
+        
+        let sources = self.borrow_sources(value);
+        self.release_holder(*name, false);
+        if let Some(binding) = self.variables.get_mut(name) {
+            match binding.state {
+                VariableState::Moved { .. } => binding.state = VariableState::Owned,
+                VariableState::Linear { .. } => binding.state = VariableState::Linear { used: false, used_at: None },
+                _ => {}
             }
         }
+        self.hold_borrows(*name, &sources);
     }
     
-    /// Handle assignment operations
-    fn handle_assignment(&mut self, _lhs: &Expr, rhs: &Expr) {
-        // Move the right-hand side into the left-hand side
-        if let Some(name) = self.get_simple_identifier(rhs) {
-            self.move_variable(name, MoveReason::Assignment);
+    /// Move the value of `expr` if it is a variable whose type moves rather than copies
+    fn move_out(&mut self, expr: &Expr, reason: MoveReason) {
+        match expr {
+            Expr::Identifier { name, span, .. } => self.move_variable(*name, *span, reason),
+            Expr::Parenthesized { expr, .. } => self.move_out(expr, reason),
+            _ => {}
         }
     }
     
-    /// Handle moves into function calls
-    fn handle_move_into_call(&mut self, arg: &Expr) {
-        if let Some(name) = self.get_simple_identifier(arg) {
-            // Check if this is a reference type - if so, don't move
-            if !self.is_reference_type(arg) {
-                self.move_variable(name, MoveReason::FunctionCall);
+    /// Move a variable (mark as moved)
+    fn move_variable(&mut self, name: InternedString, span: Span, reason: MoveReason) {
+        let Some(binding) = self.variables.get(&name) else { return };
+        if !binding.is_move {
+            return;
+        }
+        
+        match &binding.state {
+            // Reading the variable already reported the use after move
+            VariableState::Moved { .. } => return,
+            VariableState::Linear { .. } => {
+                let state = VariableState::Linear { used: true, used_at: Some(span) };
+                self.set_state(name, state);
+                return;
+            }
+            state => {
+                let live_borrow = state.borrows().iter().find(|borrow| self.is_live(borrow, span.start));
+                if let Some(borrow) = live_borrow {
+                    let error = OwnershipError::MoveWhileBorrowed {
+                        variable: name,
+                        borrowed_at: borrow.borrow_site,
+                        moved_at: span,
+                    };
+                    self.push_error(error);
+                }
             }
         }
+        
+        self.set_state(name, VariableState::Moved { moved_at: span, reason });
     }
     
-    /// Handle return statement moves
-    fn handle_return_move(&mut self, expr: &Expr) {
-        if let Some(name) = self.get_simple_identifier(expr) {
-            self.move_variable(name, MoveReason::Return);
+    /// Borrow `place`, the operand of the `&` or `&mut` at `span`
+    fn create_borrow(&mut self, place: &Expr, is_mutable: bool, span: Span) {
+        // Borrowing through a dereference reborrows from the reference instead
+        let Some(path) = borrowed_path(place) else { return };
+        let name = path[0];
+        let Some(binding) = self.variables.get(&name) else { return };
+        if matches!(binding.state, VariableState::Moved { .. } | VariableState::Linear { .. }) {
+            return;
+        }
+        
+        let live: Vec<BorrowInfo> = binding.state.borrows().iter()
+            .filter(|borrow| self.is_live(borrow, span.start))
+            .cloned()
+            .collect();
+        let conflict = live.iter()
+            .filter(|borrow| overlaps(&borrow.borrowed_path, &path))
+            .find_map(|existing| match (existing.is_mutable, is_mutable) {
+                (true, true) => Some(OwnershipError::MultipleMutableBorrows {
+                    variable: name,
+                    first_borrow: existing.borrow_site,
+                    second_borrow: span,
+                }),
+                (true, false) => Some(OwnershipError::MutableImmutableConflict {
+                    variable: name,
+                    mutable_borrow: existing.borrow_site,
+                    immutable_borrow: span,
+                }),
+                (false, true) => Some(OwnershipError::MutableImmutableConflict {
+                    variable: name,
+                    mutable_borrow: span,
+                    immutable_borrow: existing.borrow_site,
+                }),
+                (false, false) => None,
+            });
+        if let Some(error) = conflict {
+            self.push_error(error);
+        }
+        
+        let lifetime = self.allocate_lifetime();
+        let mut borrows = live;
+        borrows.push(BorrowInfo {
+            is_mutable,
+            lifetime,
+            borrow_site: span,
+            borrowed_path: path,
+            holders: Vec::new(),
+        });
+        if let Some(binding) = self.variables.get_mut(&name) {
+            binding.state.set_borrows(borrows);
         }
     }
     
-    /// Move a variable (mark as moved)
-    fn move_variable(&mut self, name: InternedString, reason: MoveReason) {
-        self.variable_states.insert(name, VariableState::Moved {
-            moved_at: self.current_position,
-            reason,
-        });
+    /// Whether `borrow` is still needed at `at`: it is a temporary of the statement being
+    /// analyzed, or a variable holding it is read again
+    fn is_live(&self, borrow: &BorrowInfo, at: Position) -> bool {
+        borrow.holders.is_empty() || borrow.holders.iter().any(|holder| self.is_read_after(*holder, at))
     }
     
-    /// Create an immutable borrow
-    fn create_immutable_borrow(&mut self, expr: &Expr) {
-        if let Some(name) = self.get_simple_identifier(expr) {
-            let lifetime = self.allocate_lifetime();
-            let borrow = BorrowInfo {
-                is_mutable: false,
-                lifetime,
-                borrow_site: self.current_position,
-                borrowed_path: vec![name],
-            };
-            
-            // Check for conflicts with existing mutable borrows
-            if let Some(VariableState::MutablyBorrowed { borrow: existing }) = 
-                self.variable_states.get(&name) {
-                self.errors.push(OwnershipError::MutableImmutableConflict {
-                    variable: name,
-                    mutable_borrow: existing.borrow_site,
-                    immutable_borrow: self.current_position,
-                });
-            }
-            
-            self.active_borrows.push(borrow);
-        }
+    /// Whether `name` is read after `at`, counting reads anywhere in a loop around `at`
+    /// when the variable was bound before that loop, since the next iteration reads it again
+    fn is_read_after(&self, name: InternedString, at: Position) -> bool {
+        let (Some(uses), Some(binding)) = (self.uses.get(&name), self.variables.get(&name)) else {
+            return false;
+        };
+        let bound_at = binding.span.start.offset;
+        uses.iter().any(|read| {
+            read.offset > at.offset
+                || self.loops.iter().any(|span| {
+                    bound_at < span.start.offset && span.start.offset <= read.offset && read.offset <= span.end.offset
+                })
+        })
     }
     
-    /// Create a mutable borrow
-    fn create_mutable_borrow(&mut self, expr: &Expr) {
-        if let Some(name) = self.get_simple_identifier(expr) {
-            let lifetime = self.allocate_lifetime();
-            let borrow = BorrowInfo {
-                is_mutable: true,
-                lifetime,
-                borrow_site: self.current_position,
-                borrowed_path: vec![name],
-            };
-            
-            // Check for conflicts with existing borrows
-            match self.variable_states.get(&name) {
-                Some(VariableState::Borrowed { .. }) => {
-                    // Find the first immutable borrow
-                    if let Some(existing) = self.active_borrows.iter()
-                        .find(|b| b.borrowed_path.first() == Some(&name) && !b.is_mutable) {
-                        self.errors.push(OwnershipError::MutableImmutableConflict {
-                            variable: name,
-                            mutable_borrow: self.current_position,
-                            immutable_borrow: existing.borrow_site,
-                        });
+    /// Borrows that flow into the value of `expr`
+    fn borrow_sources(&self, expr: &Expr) -> BorrowSources {
+        let mut sources = BorrowSources::default();
+        self.collect_borrow_sources(expr, &mut sources);
+        sources
+    }
+    
+    fn collect_borrow_sources(&self, expr: &Expr, sources: &mut BorrowSources) {
+        match expr {
+            Expr::Unary { op: UnaryOp::AddressOf | UnaryOp::MutableRef, expr: place, span, .. }
+            | Expr::Reference { expr: place, span, .. } => {
+                sources.sites.push(*span);
+                // `&*r` and `&r.field` reborrow whatever `r` holds
+                if let Some(root) = place_root(place) {
+                    sources.holders.push(root);
+                }
+            }
+            Expr::Identifier { name, .. } => sources.holders.push(*name),
+            Expr::Parenthesized { expr, .. } | Expr::Cast { expr, .. } => self.collect_borrow_sources(expr, sources),
+            Expr::Block { trailing_expr: Some(trailing), .. } => self.collect_borrow_sources(trailing, sources),
+            Expr::If { then_block, else_block, .. } => {
+                self.collect_borrow_sources(then_block, sources);
+                if let Some(else_block) = else_block {
+                    self.collect_borrow_sources(else_block, sources);
+                }
+            }
+            Expr::Match { arms, .. } => {
+                for arm in arms {
+                    self.collect_borrow_sources(&arm.body, sources);
+                }
+            }
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => {
+                for element in elements {
+                    self.collect_borrow_sources(element, sources);
+                }
+            }
+            Expr::ArrayRepeat { value, .. } => self.collect_borrow_sources(value, sources),
+            Expr::StructInit { fields, .. } => {
+                for field in fields {
+                    match &field.value {
+                        Some(value) => self.collect_borrow_sources(value, sources),
+                        None => sources.holders.push(field.name),
                     }
                 }
-                Some(VariableState::MutablyBorrowed { borrow: existing }) => {
-                    self.errors.push(OwnershipError::MultipleMutableBorrows {
-                        variable: name,
-                        first_borrow: existing.borrow_site,
-                        second_borrow: self.current_position,
-                    });
+            }
+            // A function returning a reference returns one borrowed from its arguments
+            Expr::Call { callee, args, .. } => {
+                let returns_reference = match callee.as_ref() {
+                    Expr::Identifier { name, .. } => {
+                        matches!(self.return_types.get(name), Some(Some(Type::Reference { .. })))
+                    }
+                    _ => false,
+                };
+                if returns_reference {
+                    for arg in args {
+                        self.collect_borrow_sources(arg, sources);
+                    }
                 }
-                _ => {
-                    // No conflicts
+            }
+            _ => {}
+        }
+    }
+    
+    /// Make `holder` hold the borrows `sources` describes
+    fn hold_borrows(&mut self, holder: InternedString, sources: &BorrowSources) {
+        for binding in self.variables.values_mut() {
+            for borrow in binding.state.borrows_mut() {
+                let flows = sources.sites.contains(&borrow.borrow_site)
+                    || borrow.holders.iter().any(|existing| sources.holders.contains(existing));
+                if flows && !borrow.holders.contains(&holder) {
+                    borrow.holders.push(holder);
                 }
             }
-            
-            self.variable_states.insert(name, VariableState::MutablyBorrowed {
-                borrow: borrow.clone(),
-            });
-            self.active_borrows.push(borrow);
+        }
+    }
+    
+    /// Stop `holder` from holding any borrow; borrows nothing else holds end right away
+    /// if `end_unheld`, or with the current statement otherwise
+    fn release_holder(&mut self, holder: InternedString, end_unheld: bool) {
+        for binding in self.variables.values_mut() {
+            if !binding.state.borrows().iter().any(|borrow| borrow.holders.contains(&holder)) {
+                continue;
+            }
+            let borrows = binding.state.borrows().iter()
+                .cloned()
+                .filter_map(|mut borrow| {
+                    let was_held = borrow.holders.contains(&holder);
+                    borrow.holders.retain(|existing| *existing != holder);
+                    (!(end_unheld && was_held && borrow.holders.is_empty())).then_some(borrow)
+                })
+                .collect();
+            binding.state.set_borrows(borrows);
+        }
+    }
+    
+    /// End the borrows no variable holds
+    fn end_temporary_borrows(&mut self) {
+        for binding in self.variables.values_mut() {
+            if binding.state.borrows().iter().any(|borrow| borrow.holders.is_empty()) {
+                let borrows = binding.state.borrows().iter()
+                    .filter(|borrow| !borrow.holders.is_empty())
+                    .cloned()
+                    .collect();
+                binding.state.set_borrows(borrows);
+            }
         }
     }
     
     /// Add a parameter to the current scope
     fn add_parameter(&mut self, pattern: &Pattern, type_annotation: &Option<Type>) {
-        if let Pattern::Identifier { name, .. } = pattern {
-            let state = if let Some(ty) = type_annotation {
-                match self.get_memory_strategy(ty) {
-                    MemoryStrategy::Linear => VariableState::Linear {
-                        used: false,
-                        used_at: None,
-                    },
-                    _ => VariableState::Owned,
-                }
-            } else {
-                VariableState::Owned
+        if let Pattern::Identifier { name, span, .. } = pattern {
+            let binding = Binding {
+                state: self.initial_state(type_annotation.as_ref()),
+                is_move: type_annotation.as_ref().is_some_and(|ty| self.is_move_type(ty)),
+                span: *span,
             };
-            
-            self.variable_states.insert(*name, state);
+            self.bind(*name, binding);
+        } else {
+            self.analyze_pattern(pattern);
         }
     }
     
     /// Add a variable from a let pattern
-    fn add_variable_from_pattern(&mut self, pattern: &Pattern, type_annotation: &Option<Type>, _initializer: Option<&Expr>) {
-        if let Pattern::Identifier { name, .. } = pattern {
-            let state = if let Some(ty) = type_annotation {
-                match self.get_memory_strategy(ty) {
-                    MemoryStrategy::Linear => VariableState::Linear {
-                        used: false,
-                        used_at: None,
-                    },
-                    _ => VariableState::Owned,
-                }
-            } else {
-                VariableState::Owned
-            };
-            
-            self.variable_states.insert(*name, state);
+    fn add_variable_from_pattern(&mut self, pattern: &Pattern, type_annotation: &Option<Type>, initializer: Option<&Expr>) {
+        let Pattern::Identifier { name, span, .. } = pattern else {
+            self.analyze_pattern(pattern);
+            return;
+        };
+        
+        // Decided before binding, since the initializer may read the name being shadowed
+        let is_move = match type_annotation {
+            Some(ty) => self.is_move_type(ty),
+            None => initializer.is_some_and(|init| self.is_move_expr(init)),
+        };
+        let sources = initializer.map(|init| self.borrow_sources(init));
+        
+        let binding = Binding {
+            state: self.initial_state(type_annotation.as_ref()),
+            is_move,
+            span: *span,
+        };
+        self.bind(*name, binding);
+        if let Some(sources) = sources {
+            self.hold_borrows(*name, &sources);
+        }
+    }
+    
+    /// State of a fresh variable of type `ty`
+    fn initial_state(&self, ty: Option<&Type>) -> VariableState {
+        match ty.map(|ty| self.get_memory_strategy(ty)) {
+            Some(MemoryStrategy::Linear) => VariableState::Linear {
+                used: false,
+                used_at: None,
+            },
+            _ => VariableState::Owned,
+        }
+    }
+    
+    /// Whether values of type `ty` move rather than copy
+    fn is_move_type(&self, ty: &Type) -> bool {
+        match ty {
+            Type::Primitive { .. }
+            | Type::Reference { .. }
+            | Type::Slice { .. }
+            | Type::Function { .. }
+            | Type::Inferred { .. }
+            | Type::Never { .. } => false,
+            Type::Path { segments, memory_strategy, .. } => match segments.as_slice() {
+                // One level of aliasing, so an alias cycle cannot recurse forever
+                [name] if self.aliases.contains_key(name) => match &self.aliases[name] {
+                    Type::Path { .. } => true,
+                    target => self.is_move_type(target),
+                },
+                _ => !memory_strategy.allows_copy(),
+            },
+            Type::Pointer { memory_strategy, .. } => memory_strategy.requires_move(),
+            Type::Array { element_type, .. } => self.is_move_type(element_type),
+            Type::Tuple { types, .. } => types.iter().any(|ty| self.is_move_type(ty)),
+            Type::Generic { .. } => true,
+        }
+    }
+    
+    /// Whether the value of an unannotated initializer moves rather than copies
+    fn is_move_expr(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::StructInit { .. } | Expr::Box { .. } => true,
+            Expr::Identifier { name, .. } => self.variables.get(name).is_some_and(|binding| binding.is_move),
+            Expr::Parenthesized { expr, .. } => self.is_move_expr(expr),
+            Expr::Block { trailing_expr: Some(trailing), .. } => self.is_move_expr(trailing),
+            Expr::If { then_block, else_block, .. } => {
+                self.is_move_expr(then_block) || else_block.as_deref().is_some_and(|expr| self.is_move_expr(expr))
+            }
+            Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => {
+                elements.iter().any(|element| self.is_move_expr(element))
+            }
+            Expr::ArrayRepeat { value, .. } => self.is_move_expr(value),
+            Expr::Call { callee, .. } => match callee.as_ref() {
+                Expr::Identifier { name, .. } => match self.return_types.get(name) {
+                    Some(Some(ty)) => self.is_move_type(ty),
+                    _ => false,
+                },
+                _ => false,
+            },
+            _ => false,
         }
     }
     
@@ -725,94 +1238,111 @@ impl OwnershipAnalyzer {
         }
     }
     
-    /// Get simple identifier from expression
-    fn get_simple_identifier(&self, expr: &Expr) -> Option<InternedString> {
-        match expr {
-            Expr::Identifier { name, .. } => Some(*name),
-            _ => None,
+    /// Allocate a new lifetime ID
+    fn allocate_lifetime(&mut self) -> LifetimeId {
+        let id = LifetimeId(self.next_lifetime_id);
+        self.next_lifetime_id += 1;
+        id
+    }
+    
+    /// Bind `name` in the innermost block
+    fn bind(&mut self, name: InternedString, binding: Binding) {
+        // Borrows held by the binding being shadowed can no longer be used through it
+        self.release_holder(name, false);
+        let shadowed = self.variables.insert(name, binding);
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name, shadowed));
         }
     }
     
-    /// Check if expression has reference type
-    fn is_reference_type(&self, _expr: &Expr) -> bool {
-        // Simplified - would need type information
-        false
+    fn set_state(&mut self, name: InternedString, state: VariableState) {
+        if let Some(binding) = self.variables.get_mut(&name) {
+            binding.state = state;
+        }
     }
     
-    /// Get position from expression
-    fn get_expr_position(&self, expr: &Expr) -> Position {
-        match expr {
-            Expr::Literal { span, .. } |
-            Expr::Identifier { span, .. } |
-            Expr::Path { span, .. } |
-            Expr::Binary { span, .. } |
-            Expr::Unary { span, .. } |
-            Expr::Call { span, .. } |
-            Expr::MethodCall { span, .. } |
-            Expr::FieldAccess { span, .. } |
-            Expr::Index { span, .. } |
-            Expr::Array { span, .. } |
-            Expr::Tuple { span, .. } |
-            Expr::StructInit { span, .. } |
-            Expr::Block { span, .. } |
-            Expr::If { span, .. } |
-            Expr::Match { span, .. } |
-            Expr::While { span, .. } |
-            Expr::For { span, .. } |
-            Expr::Return { span, .. } |
-            Expr::Break { span, .. } |
-            Expr::Continue { span, .. } |
-            Expr::Cast { span, .. } |
-            Expr::Parenthesized { span, .. } |
-            Expr::Range { span, .. } |
-            Expr::Closure { span, .. } |
-            Expr::Loop { span, .. } |
-            Expr::Box { span, .. } |
-            Expr::Reference { span, .. } |
-            Expr::Dereference { span, .. } |
-            Expr::Try { span, .. } |
-            Expr::Await { span, .. } |
-            Expr::Macro { span, .. } => span.start,
+    /// Record `error` unless a second pass over a loop body already found it
+    fn push_error(&mut self, error: OwnershipError) {
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
         }
     }
     
-    /// Allocate a new lifetime ID
-    fn allocate_lifetime(&mut self) -> LifetimeId {
-        let id = LifetimeId(self.next_lifetime_id);
-        self.next_lifetime_id += 1;
-        id
+    /// Open a block scope
+    fn enter_block(&mut self) {
+        self.scopes.push(Vec::new());
     }
     
-    /// Enter function scope
-    fn enter_function_scope(&mut self) {
-        // Create new scope - simplified
+    /// Close the innermost block: its variables go out of scope, ending the borrows they
+    /// hold, and the bindings they shadowed come back
+    fn exit_block(&mut self) {
+        let Some(scope) = self.scopes.pop() else { return };
+        for (name, shadowed) in scope.into_iter().rev() {
+            self.release_holder(name, true);
+            match shadowed {
+                Some(binding) => self.variables.insert(name, binding),
+                None => self.variables.remove(&name),
+            };
+        }
+    }
+    
+    /// Enter function scope, setting aside the state of the function being analyzed, if any
+    fn enter_function_scope(&mut self, body: &Expr) -> EnclosingFunction {
+        let mut reads = ReadCollector::default();
+        reads.visit_expr(body);
+        
+        EnclosingFunction {
+            variables: std::mem::take(&mut self.variables),
+            scopes: std::mem::replace(&mut self.scopes, vec![Vec::new()]),
+            uses: std::mem::replace(&mut self.uses, reads.uses),
+            loops: std::mem::take(&mut self.loops),
+            diverges: std::mem::replace(&mut self.diverges, false),
+        }
     }
     
-    /// Exit function scope
-    fn exit_function_scope(&mut self) {
-        // Clean up scope - simplified
-        self.variable_states.clear();
-        self.active_borrows.clear();
+    /// Exit function scope, resuming the enclosing function
+    fn exit_function_scope(&mut self, enclosing: EnclosingFunction) {
+        self.variables = enclosing.variables;
+        self.scopes = enclosing.scopes;
+        self.uses = enclosing.uses;
+        self.loops = enclosing.loops;
+        self.diverges = enclosing.diverges;
     }
     
     /// Save current state for branching
     fn save_state(&self) -> AnalysisState {
         AnalysisState {
-            variable_states: self.variable_states.clone(),
-            active_borrows: self.active_borrows.clone(),
+            variables: self.variables.clone(),
+            diverges: self.diverges,
         }
     }
     
     /// Restore saved state
     fn restore_state(&mut self, state: AnalysisState) {
-        self.variable_states = state.variable_states;
-        self.active_borrows = state.active_borrows;
+        self.variables = state.variables;
+        self.diverges = state.diverges;
     }
     
-    /// Merge two analysis states
-    fn merge_two_states(&self, state1: AnalysisState, _state2: AnalysisState) -> AnalysisState {
-        // Simplified merge - would need more sophisticated logic
-        state1
+    /// Merge two analysis states: a variable moved on either path is moved, and borrows
+    /// made on either path are live. A path that returned or broke out does not continue.
+    fn merge_two_states(&self, state1: AnalysisState, state2: AnalysisState) -> AnalysisState {
+        if state1.diverges {
+            return state2;
+        }
+        if state2.diverges {
+            return state1;
+        }
+        
+        let mut variables = state1.variables;
+        for (name, other) in state2.variables {
+            match variables.get_mut(&name) {
+                Some(binding) => binding.state = merge_variable_states(binding.state.clone(), other.state),
+                None => {
+                    variables.insert(name, other);
+                }
+            }
+        }
+        AnalysisState { variables, diverges: false }
     }
     
     /// Merge states from multiple branches
@@ -824,25 +1354,170 @@ impl OwnershipAnalyzer {
     /// Finalize analysis
     fn finalize_analysis(&mut self) {
         // Check for unclosed borrows, unused linear types, etc.
-        for (_name, state) in &self.variable_states {
-            if let VariableState::Linear { used: false, .. } = state {
+        for binding in self.variables.values() {
+            if let VariableState::Linear { used: false, .. } = binding.state {
                 // Linear type was never used - could be a warning
             }
         }
     }
 }
 
+impl Default for OwnershipAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Merge the states a variable is in at the end of two paths
+fn merge_variable_states(state1: VariableState, state2: VariableState) -> VariableState {
+    match (state1, state2) {
+        (moved @ VariableState::Moved { .. }, _) | (_, moved @ VariableState::Moved { .. }) => moved,
+        (VariableState::Linear { used: used1, used_at: at1 }, VariableState::Linear { used: used2, used_at: at2 }) => {
+            VariableState::Linear { used: used1 || used2, used_at: at1.or(at2) }
+        }
+        (mut state, other) => {
+            let mut borrows = state.borrows().to_vec();
+            for borrow in other.borrows() {
+                match borrows.iter_mut().find(|existing| existing.borrow_site == borrow.borrow_site) {
+                    Some(existing) => {
+                        for holder in &borrow.holders {
+                            if !existing.holders.contains(holder) {
+                                existing.holders.push(*holder);
+                            }
+                        }
+                    }
+                    None => borrows.push(borrow.clone()),
+                }
+            }
+            state.set_borrows(borrows);
+            state
+        }
+    }
+}
+
+/// Whether `after` has a variable moved or used up that `before` did not
+fn newly_moved(before: &AnalysisState, after: &AnalysisState) -> bool {
+    after.variables.iter().any(|(name, binding)| {
+        let consumed = |state: &VariableState| {
+            matches!(state, VariableState::Moved { .. } | VariableState::Linear { used: true, .. })
+        };
+        consumed(&binding.state) && !before.variables.get(name).is_some_and(|binding| consumed(&binding.state))
+    })
+}
+
+/// Variable and fields borrowed by `&place`, or `None` when it borrows through a
+/// dereference or borrows a temporary
+fn borrowed_path(place: &Expr) -> Option<Vec<InternedString>> {
+    match place {
+        Expr::Identifier { name, .. } => Some(vec![*name]),
+        Expr::Parenthesized { expr, .. } => borrowed_path(expr),
+        Expr::FieldAccess { object, field, .. } => borrowed_path(object).map(|mut path| {
+            path.push(*field);
+            path
+        }),
+        // Indexing borrows the whole collection
+        Expr::Index { object, .. } => borrowed_path(object),
+        _ => None,
+    }
+}
+
+/// Variable a place expression starts from, through fields, indexing and dereferences
+fn place_root(place: &Expr) -> Option<InternedString> {
+    match place {
+        Expr::Identifier { name, .. } => Some(*name),
+        Expr::Parenthesized { expr, .. }
+        | Expr::FieldAccess { object: expr, .. }
+        | Expr::Index { object: expr, .. }
+        | Expr::Unary { op: UnaryOp::Dereference, expr, .. }
+        | Expr::Dereference { expr, .. } => place_root(expr),
+        _ => None,
+    }
+}
+
+/// Whether borrowing `path1` and `path2` touches the same memory: one is a prefix of the other
+fn overlaps(path1: &[InternedString], path2: &[InternedString]) -> bool {
+    path1.iter().zip(path2).all(|(a, b)| a == b)
+}
+
+/// Borrows that flow into a value: the ones created at `sites` and the ones `holders` hold
+#[derive(Debug, Default)]
+struct BorrowSources {
+    sites: Vec<Span>,
+    holders: Vec<InternedString>,
+}
+
 /// Saved analysis state for branching
 #[derive(Debug, Clone)]
 struct AnalysisState {
-    variable_states: HashMap<InternedString, VariableState>,
-    active_borrows: Vec<BorrowInfo>,
+    variables: HashMap<InternedString, Binding>,
+    diverges: bool,
+}
+
+/// State of a function whose analysis is paused while a nested function is analyzed
+struct EnclosingFunction {
+    variables: HashMap<InternedString, Binding>,
+    scopes: Vec<Vec<(InternedString, Option<Binding>)>>,
+    uses: HashMap<InternedString, Vec<Position>>,
+    loops: Vec<Span>,
+    diverges: bool,
+}
+
+/// Collects where each name is read in a function body
+#[derive(Default)]
+struct ReadCollector {
+    uses: HashMap<InternedString, Vec<Position>>,
+}
+
+impl<'ast> Visitor<'ast> for ReadCollector {
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match expr {
+            Expr::Identifier { name, span, .. } => self.uses.entry(*name).or_default().push(span.start),
+            Expr::StructInit { fields, .. } => {
+                for field in fields.iter().filter(|field| field.value.is_none()) {
+                    self.uses.entry(field.name).or_default().push(field.span.start);
+                }
+            }
+            _ => {}
+        }
+        visitor::walk_expr(self, expr);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ast::{Module, Item, Expr, Literal, Span};
+    use crate::ast::{Module, NodeId, Span};
+    
+    /// Ownership errors in `source`, with the text of each error's two spans
+    fn ownership_errors(source: &str) -> Vec<(OwnershipError, String)> {
+        let mut parser = crate::Parser::new(source, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        describe_errors(source, &module, &parser.interner())
+    }
+    
+    fn describe_errors(source: &str, module: &Module, interner: &StringInterner) -> Vec<(OwnershipError, String)> {
+        // Spans of names end where they start, so take the text up to the end of the operand
+        let text = |span: Span| source[span.start.offset..].split([',', ';', ')']).next().unwrap_or_default().to_string();
+        OwnershipAnalyzer::new().analyze_module(module).into_iter()
+            .map(|error| {
+                let related = error.related().map(|(span, _)| text(span)).unwrap_or_default();
+                let description = format!("{} @ {} <- {}", error.message(interner), text(error.span()), related);
+                (error, description)
+            })
+            .collect()
+    }
+    
+    fn assert_accepted(source: &str) {
+        let errors = ownership_errors(source);
+        assert!(errors.is_empty(), "unexpected ownership errors: {:?}", errors.iter().map(|(_, text)| text).collect::<Vec<_>>());
+    }
+    
+    const PRELUDE: &str = "struct Buffer { size: i32, used: i32 }\n\
+                           fn make() -> Buffer { return Buffer { size: 1, used: 0 }; }\n\
+                           fn consume(buffer: Buffer) {}\n\
+                           fn inspect(buffer: &Buffer) -> i32 { return 0; }\n\
+                           fn touch(buffer: &mut Buffer) {}\n";
     
     #[test]
     fn test_basic_ownership() {
@@ -851,6 +1526,7 @@ mod tests {
         // Test simple variable usage
         let module = Module {
             items: vec![],
+            attributes: Vec::new(),
             span: Span::new(Position::start(0), Position::start(0)),
         };
         
@@ -860,23 +1536,186 @@ mod tests {
     
     #[test]
     fn test_use_after_move() {
-        let mut analyzer = OwnershipAnalyzer::new();
+        let source = format!("{}fn main() {{\n    let buffer = make();\n    consume(buffer);\n    inspect(&buffer);\n}}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0].0, OwnershipError::UseAfterMove { move_reason: MoveReason::FunctionCall, .. }));
+        assert_eq!(errors[0].1, "use of moved value `buffer` @ buffer <- buffer");
+        assert_eq!(errors[0].0.span().start.line, 9);
+        assert_eq!(errors[0].0.related().map(|(span, _)| span.start.line), Some(8));
+        
+        // Moving into another variable, then using the original
+        let source = format!("{}fn main() {{ let a = make(); let b = a; consume(a); consume(b); }}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0].0, OwnershipError::UseAfterMove { move_reason: MoveReason::Assignment, .. }));
         
-        // This would require more complex AST construction
-        // for a proper test of use-after-move detection
+        // Moved on one branch only is still moved afterwards
+        let source = format!("{}fn main(flag: bool) {{ let a = make(); if flag {{ consume(a); }} consume(a); }}", PRELUDE);
+        assert_eq!(ownership_errors(&source).len(), 1);
         
-        let errors = analyzer.errors;
-        // Would check for UseAfterMove errors
+        // Moved by an earlier iteration of the loop
+        let source = format!("{}fn main(flag: bool) {{ let a = make(); while flag {{ consume(a); }} }}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].1, "use of moved value `a` @ a <- a");
+    }
+    
+    #[test]
+    fn test_move_while_borrowed() {
+        let source = format!("{}fn main() {{\n    let buffer = make();\n    let view = &buffer;\n    consume(buffer);\n    inspect(view);\n}}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0].0, OwnershipError::MoveWhileBorrowed { .. }));
+        assert_eq!(errors[0].1, "cannot move out of `buffer` because it is borrowed @ buffer <- &buffer");
+        assert_eq!(errors[0].0.related().map(|(span, _)| span.start.line), Some(8));
+        
+        // The borrow flows on through a copy of the reference and a struct holding it
+        let source = format!("{}struct View {{ target: &Buffer }}\n\
+                              fn main() {{ let a = make(); let r = &a; let copy = r; let view = View {{ target: copy }}; \
+                              consume(a); let keep = view; }}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0].0, OwnershipError::MoveWhileBorrowed { .. }));
+        
+        // A function returning a reference keeps its argument borrowed
+        let source = format!("{}fn pick(buffer: &Buffer) -> &Buffer {{ return buffer; }}\n\
+                              fn main() {{ let a = make(); let r = pick(&a); consume(a); inspect(r); }}", PRELUDE);
+        assert_eq!(ownership_errors(&source).len(), 1);
+        
+        // Moving while a temporary borrow of the same call is alive
+        let source = format!("{}fn both(view: &Buffer, buffer: Buffer) {{}}\nfn main() {{ let a = make(); both(&a, a); }}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0].0, OwnershipError::MoveWhileBorrowed { .. }));
+    }
+    
+    #[test]
+    fn test_conflicting_borrows() {
+        let source = format!("{}fn main() {{ let mut a = make(); let first = &mut a; let second = &mut a; touch(first); touch(second); }}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].1, "cannot borrow `a` as mutable more than once at a time @ &mut a <- &mut a");
+        
+        let source = format!("{}fn main() {{ let mut a = make(); let read = &a; touch(&mut a); inspect(read); }}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].1, "cannot borrow `a` as mutable because it is also borrowed as immutable @ &mut a <- &a");
+        
+        let source = format!("{}fn main() {{ let mut a = make(); let write = &mut a; inspect(&a); touch(write); }}", PRELUDE);
+        let errors = ownership_errors(&source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].1.starts_with("cannot borrow `a` as immutable because it is also borrowed as mutable"));
+    }
+    
+    #[test]
+    fn test_reborrow_in_nested_block() {
+        // A reborrow through the reference does not conflict with the reference itself,
+        // and ends with the block it is bound in
+        let accepted = format!("{}fn main() {{\n\
+                                    let mut a = make();\n\
+                                    let outer = &mut a;\n\
+                                    {{ let inner = &mut *outer; touch(inner); }}\n\
+                                    touch(outer);\n\
+                                    consume(a);\n\
+                                }}", PRELUDE);
+        assert_accepted(&accepted);
+        
+        // While the reborrow is still used, the original value stays borrowed
+        let rejected = format!("{}fn main() {{\n\
+                                    let mut a = make();\n\
+                                    let outer = &mut a;\n\
+                                    let inner = &mut *outer;\n\
+                                    consume(a);\n\
+                                    touch(inner);\n\
+                                }}", PRELUDE);
+        let errors = ownership_errors(&rejected);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].1, "cannot move out of `a` because it is borrowed @ a <- &mut a");
+    }
+    
+    #[test]
+    fn test_borrow_ends_at_block_exit() {
+        // `view` is read again after the block, but that is a different variable: the
+        // borrow held by the one in the block ended with it
+        let accepted = format!("{}fn main() {{\n\
+                                    let a = make();\n\
+                                    {{ let view = &a; inspect(view); }}\n\
+                                    consume(a);\n\
+                                    let view = make();\n\
+                                    inspect(&view);\n\
+                                }}", PRELUDE);
+        assert_accepted(&accepted);
+        
+        // Mutable borrows in sibling blocks do not overlap
+        let accepted = format!("{}fn main() {{ let mut a = make(); {{ let w = &mut a; touch(w); }} {{ let w = &mut a; touch(w); }} }}", PRELUDE);
+        assert_accepted(&accepted);
+    }
+    
+    #[test]
+    fn test_accepted_programs() {
+        let programs = [
+            // Primitives copy
+            "fn main() { let x = 5; let y = x; let z = x + y; consume_int(x); consume_int(x); }\nfn consume_int(n: i32) {}",
+            // Unknown types are not assumed to move
+            "fn main() { let data = create_data(); consume_data(data); consume_data(data); }",
+            // Shared borrows coexist
+            "fn main() { let a = make(); let r1 = &a; let r2 = &a; inspect(r1); inspect(r2); consume(a); }",
+            // A borrow ends after the last use of the reference holding it
+            "fn main() { let mut a = make(); let r = &a; inspect(r); touch(&mut a); consume(a); }",
+            // Temporary borrows end with their statement
+            "fn main() { let mut a = make(); inspect(&a); touch(&mut a); touch(&mut a); let n = inspect(&a); consume(a); }",
+            // Borrows of disjoint fields
+            "fn main() { let mut a = make(); let p = &mut a.size; let q = &mut a.used; inspect_int(p); inspect_int(q); }\nfn inspect_int(n: &i32) {}",
+            // Reassigning a moved variable makes it usable again
+            "fn main() { let mut a = make(); consume(a); a = make(); consume(a); }",
+            // Moving on one branch that returns leaves the value to the code after it
+            "fn main(flag: bool) { let a = make(); if flag { consume(a); return; } consume(a); }",
+            // Each iteration moves its own value
+            "fn main(flag: bool) { while flag { let a = make(); consume(a); } }",
+            // Moving in a loop that is left right away
+            "fn main() { let a = make(); loop { consume(a); break; } }",
+            // Shadowing with a new value
+            "fn main() { let a = make(); consume(a); let a = make(); consume(a); }",
+            // Passing a reference on does not move what it points at
+            "fn main() { let a = make(); let r = &a; inspect(r); inspect(r); consume(a); }",
+            // Borrows in each function are independent
+            "fn first() { let a = make(); let r = &a; inspect(r); }\nfn second(a: Buffer) { consume(a); }",
+            // A closure that moves a capture only does so when it is called
+            "fn main() { let a = make(); let f = |n: i32| consume(a); inspect(&a); }",
+        ];
+        for program in programs {
+            assert_accepted(&format!("{}{}", PRELUDE, program));
+        }
     }
     
     #[test]
     fn test_linear_type_reuse() {
-        let mut analyzer = OwnershipAnalyzer::new();
+        // `LinearPtr<T>` annotations still parse as plain paths, so turn them into the
+        // linear pointers `parse_strategy_wrapper_type` builds
+        struct MakeLinear;
+        impl crate::visitor::VisitorMut for MakeLinear {
+            fn visit_type_mut(&mut self, ty: &mut Type) {
+                if let Type::Path { span, .. } = ty {
+                    let target_type = Box::new(Type::Primitive {
+                        kind: crate::ast::PrimitiveType::I32,
+                        memory_strategy: MemoryStrategy::Stack,
+                        span: *span,
+                        id: NodeId::DUMMY,
+                    });
+                    *ty = Type::Pointer { is_mutable: false, target_type, memory_strategy: MemoryStrategy::Linear, span: *span, id: NodeId::DUMMY };
+                }
+            }
+        }
         
-        // Test that linear types can only be used once
-        // Would require AST with LinearPtr<T> usage
+        let source = "fn close(handle: Handle) {}\nfn main(handle: Handle) { close(handle); close(handle); }";
+        let mut parser = crate::Parser::new(source, 0).unwrap();
+        let mut module = parser.parse_module().unwrap();
+        crate::visitor::VisitorMut::visit_module_mut(&mut MakeLinear, &mut module);
         
-        let errors = analyzer.errors;
-        // Would check for LinearTypeReuse errors
+        let errors = describe_errors(source, &module, &parser.interner());
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(matches!(errors[0].0, OwnershipError::LinearTypeReuse { .. }));
+        assert_eq!(errors[0].1, "linear value `handle` is used more than once @ handle <- handle");
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,600 @@
+//! Memory strategy compatibility
+//!
+//! A value's memory strategy can be requested in several places: an annotation on the
+//! struct it instantiates or on the function allocating it (`@stack`, `@linear`,
+//! `@memory(strategy = "region")`, ...), and a pointer wrapper such as `LinearPtr<T>`
+//! on the binding, parameter or return type holding it. The `StrategyChecker` gathers
+//! these requests per struct and per binding and reports the ones that cannot all hold:
+//!
+//! - two strategy annotations on one item that disagree
+//! - a struct annotated with one strategy but held in the pointer wrapper of another
+//! - a binding of one strategy used where another is declared, unless `conversion`
+//!   allows it implicitly
+//! - a stack or region value that escape analysis finds leaving its function
+//! - a value allocated in a `region` block that escape analysis finds outliving it:
+//!   returned, stored in a variable declared outside the block, passed to a function
+//!   that keeps it or breaking out of a loop around the block
+//! - a region value allocated where no region is live
+//!
+//! Codegen takes the strategies it reads from annotations and wrappers as consistent
+//! once these checks pass, and region values as released no earlier than their last use. Allocation sites are resolved as codegen resolves them: a
+//! `let` wrapper wins for the struct literal it initializes, then the struct's
+//! annotation, then the function's.
+
+use crate::ast::{
+    Attribute, Expr, ImplItem, InternedString, Item, MemoryStrategy, Module, NodeId, Parameter,
+    Pattern, SideTable, Span, Stmt, Type,
+};
+use crate::diagnostics::DiagnosticCode;
+use crate::parser::StringInterner;
+use crate::visitor::{self, Visitor};
+use super::escape_analysis::{EscapeContext, EscapeFact, RegionEscape, RegionExit};
+use std::collections::HashMap;
+
+/// A strategy requested by an annotation or a pointer wrapper
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrategyRequest {
+    pub strategy: MemoryStrategy,
+    /// The annotation or wrapper type making the request
+    pub span: Span,
+}
+
+/// How a value allocated with one strategy may be used where another is declared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    /// As it is, or by copying it by value
+    Implicit,
+    /// Only through a conversion that reallocates the value, written out
+    Explicit,
+    /// Not at all
+    Never,
+}
+
+/// How a value allocated with `from` may be used where `to` is declared
+///
+/// Either side being inferred leaves codegen free to pick, and any value can be copied
+/// by value into the stack. A reference-counted value may have other owners, so it never
+/// becomes linear; every other change of strategy reallocates the value.
+pub fn conversion(from: MemoryStrategy, to: MemoryStrategy) -> Conversion {
+    match (from, to) {
+        _ if from == to => Conversion::Implicit,
+        (MemoryStrategy::Inferred, _) | (_, MemoryStrategy::Inferred) | (_, MemoryStrategy::Stack) => Conversion::Implicit,
+        (MemoryStrategy::SmartPtr, MemoryStrategy::Linear) => Conversion::Never,
+        _ => Conversion::Explicit,
+    }
+}
+
+/// Strategy requests that contradict each other or the escape facts
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrategyError {
+    /// Item annotated with two different strategies
+    ConflictingAnnotations {
+        item: InternedString,
+        first: StrategyRequest,
+        second: StrategyRequest,
+    },
+    /// Struct annotated with one strategy, held in the pointer wrapper of another
+    WrapperConflict {
+        type_name: InternedString,
+        annotation: StrategyRequest,
+        wrapper: StrategyRequest,
+    },
+    /// Binding used where another strategy is declared, without a conversion
+    IncompatibleUse {
+        variable: InternedString,
+        /// The strategy of the binding, and what requested it
+        from: StrategyRequest,
+        /// The strategy declared where it is used
+        to: StrategyRequest,
+        conversion: Conversion,
+        span: Span,
+    },
+    /// Stack or region value leaving the function allocating it
+    EscapingValue {
+        requested: StrategyRequest,
+        context: EscapeContext,
+        is_shared: bool,
+        span: Span,
+    },
+    /// Region value allocated outside any region
+    RegionlessAllocation {
+        requested: StrategyRequest,
+        span: Span,
+    },
+    /// Region value used where it outlives the region block allocating it
+    RegionEscape {
+        requested: StrategyRequest,
+        escape: RegionEscape,
+        is_shared: bool,
+    },
+}
+
+impl StrategyError {
+    /// Source location of the offending use
+    pub fn span(&self) -> Span {
+        match self {
+            StrategyError::ConflictingAnnotations { second, .. } => second.span,
+            StrategyError::WrapperConflict { wrapper, .. } => wrapper.span,
+            StrategyError::IncompatibleUse { span, .. }
+            | StrategyError::EscapingValue { span, .. }
+            | StrategyError::RegionlessAllocation { span, .. } => *span,
+            StrategyError::RegionEscape { escape, .. } => escape.site,
+        }
+    }
+
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            StrategyError::ConflictingAnnotations { .. } | StrategyError::WrapperConflict { .. } => DiagnosticCode::StrategyConflict,
+            StrategyError::IncompatibleUse { .. } => DiagnosticCode::StrategyMismatch,
+            StrategyError::EscapingValue { .. } | StrategyError::RegionEscape { .. } => DiagnosticCode::Escape,
+            StrategyError::RegionlessAllocation { .. } => DiagnosticCode::InvalidRegionAccess,
+        }
+    }
+
+    /// The annotation or wrapper requesting the strategy that is violated, and what it is
+    pub fn related(&self) -> Option<(Span, &'static str)> {
+        match self {
+            StrategyError::ConflictingAnnotations { first, .. } => Some((first.span, "first strategy requested here")),
+            StrategyError::WrapperConflict { annotation, .. } => Some((annotation.span, "struct annotated here")),
+            StrategyError::IncompatibleUse { to, .. } => Some((to.span, "strategy declared here")),
+            StrategyError::EscapingValue { requested, .. } | StrategyError::RegionlessAllocation { requested, .. } => {
+                Some((requested.span, "strategy requested here"))
+            }
+            StrategyError::RegionEscape { escape, .. } => Some((escape.region_span, "region the value is allocated in")),
+        }
+    }
+
+    /// Human-readable message with a suggestion, with names resolved through `interner`
+    pub fn message(&self, interner: &StringInterner) -> String {
+        let resolve = |name: &InternedString| interner.resolve(*name).unwrap_or_else(|| "<unknown>".to_string());
+        match self {
+            StrategyError::ConflictingAnnotations { item, first, second } => format!(
+                "`{}` requests {} allocation and also {} allocation\nSuggestion: keep a single strategy annotation",
+                resolve(item), strategy_name(first.strategy), strategy_name(second.strategy)
+            ),
+            StrategyError::WrapperConflict { type_name, annotation, wrapper } => format!(
+                "`{}` is annotated for {} allocation but held as a {} value\nSuggestion: hold it with {}, or change its annotation",
+                resolve(type_name), strategy_name(annotation.strategy), strategy_name(wrapper.strategy), request_hint(annotation.strategy)
+            ),
+            StrategyError::IncompatibleUse { variable, from, to, conversion, .. } => {
+                let reason = match conversion {
+                    Conversion::Never => "it may have other owners, so it can never become",
+                    Conversion::Implicit | Conversion::Explicit => "it needs an explicit conversion to become",
+                };
+                format!(
+                    "`{}` is a {} value where a {} value is required; {} {}\nSuggestion: declare {} here, or allocate `{}` with {}",
+                    resolve(variable), strategy_name(from.strategy), strategy_name(to.strategy), reason,
+                    strategy_name(to.strategy), request_hint(from.strategy), resolve(variable), request_hint(to.strategy)
+                )
+            }
+            StrategyError::EscapingValue { requested, context, is_shared, .. } => {
+                let storage = if requested.strategy == MemoryStrategy::Region { "its region" } else { "its stack frame" };
+                format!(
+                    "{} value {} and would outlive {}\nSuggestion: allocate it with {}",
+                    strategy_name(requested.strategy), escape_description(context), storage, request_hint(escaping_strategy(*is_shared))
+                )
+            }
+            StrategyError::RegionlessAllocation { .. } => {
+                "region value allocated outside any region\nSuggestion: allocate it inside a `region name { ... }` block, or declare a region for the function with @region(name)".to_string()
+            }
+            StrategyError::RegionEscape { escape, is_shared, .. } => {
+                let region = resolve(&escape.region);
+                let how = match &escape.exit {
+                    RegionExit::Function(context) => format!("{} and would outlive region `{}`", escape_description(context), region),
+                    RegionExit::OuterBinding(variable) => format!(
+                        "is stored in `{}`, declared outside region `{}`, and would outlive the region", resolve(variable), region
+                    ),
+                    RegionExit::Retained(function) => format!(
+                        "is passed to `{}`, which keeps it, and would outlive region `{}`", resolve(function), region
+                    ),
+                    RegionExit::Break => format!("breaks out of a loop around region `{}` and would outlive the region", region),
+                };
+                format!(
+                    "region value {}\nSuggestion: allocate it with {}, or widen region `{}` to cover this use",
+                    how, request_hint(escaping_strategy(*is_shared)), region
+                )
+            }
+        }
+    }
+}
+
+/// Name of `strategy` in messages
+fn strategy_name(strategy: MemoryStrategy) -> &'static str {
+    match strategy {
+        MemoryStrategy::Manual => "manual",
+        MemoryStrategy::SmartPtr => "smart pointer",
+        MemoryStrategy::Linear => "linear",
+        MemoryStrategy::Region => "region",
+        MemoryStrategy::Stack => "stack",
+        MemoryStrategy::Inferred => "inferred",
+    }
+}
+
+/// How a value leaving its function through `context` does so, in messages
+fn escape_description(context: &EscapeContext) -> &'static str {
+    match context {
+        EscapeContext::FunctionReturn => "is returned from its function",
+        EscapeContext::Reference { .. } => "is referenced after its function returns",
+        EscapeContext::ClosureCapture => "is captured by a closure",
+        EscapeContext::Global => "is stored in global storage",
+        EscapeContext::Heap | EscapeContext::NoEscape => "is stored on the heap",
+    }
+}
+
+/// Strategy to suggest for a value that outlives the storage requested for it
+fn escaping_strategy(is_shared: bool) -> MemoryStrategy {
+    if is_shared { MemoryStrategy::SmartPtr } else { MemoryStrategy::Manual }
+}
+
+/// How to request `strategy` in source
+fn request_hint(strategy: MemoryStrategy) -> &'static str {
+    match strategy {
+        MemoryStrategy::Manual => "@manual",
+        MemoryStrategy::SmartPtr => "`SmartPtr<T>` or @smart",
+        MemoryStrategy::Linear => "`LinearPtr<T>` or @linear",
+        MemoryStrategy::Region => "`RegionPtr<T>` inside a region",
+        MemoryStrategy::Stack => "`StackPtr<T>` or @stack",
+        MemoryStrategy::Inferred => "no strategy annotation",
+    }
+}
+
+/// Strategy facts of the function whose body is being checked
+#[derive(Debug, Clone, Copy, Default)]
+struct FunctionFacts {
+    /// Strategy its annotations request for its allocations
+    strategy: Option<StrategyRequest>,
+    /// Strategy its return type requires
+    returns: Option<StrategyRequest>,
+    /// Whether it declares a region with `@region`
+    has_region: bool,
+}
+
+/// Checks that the strategy requests of a module are consistent
+pub struct StrategyChecker<'a> {
+    interner: &'a StringInterner,
+    escapes: &'a SideTable<EscapeFact>,
+    /// Strategy requested by each annotated struct
+    structs: HashMap<InternedString, StrategyRequest>,
+    /// Strategy required of each parameter of each function, by function name
+    parameters: HashMap<InternedString, Vec<Option<StrategyRequest>>>,
+    /// Strategies of the bindings in scope, innermost scope last
+    bindings: Vec<HashMap<InternedString, StrategyRequest>>,
+    function: FunctionFacts,
+    /// Region blocks enclosing the current statement
+    region_depth: usize,
+    /// Struct literal initializing a `let` with a pointer wrapper, and the wrapper's request
+    wrapped_literal: Option<(NodeId, StrategyRequest)>,
+    errors: Vec<StrategyError>,
+}
+
+impl<'a> StrategyChecker<'a> {
+    pub fn new(interner: &'a StringInterner, escapes: &'a SideTable<EscapeFact>) -> Self {
+        Self {
+            interner,
+            escapes,
+            structs: HashMap::new(),
+            parameters: HashMap::new(),
+            bindings: Vec::new(),
+            function: FunctionFacts::default(),
+            region_depth: 0,
+            wrapped_literal: None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Check `module`, returning the violations found
+    pub fn check_module(mut self, module: &Module) -> Vec<StrategyError> {
+        self.collect_items(&module.items);
+        // Parameter types are only known to request a struct's strategy once all
+        // structs are collected
+        self.collect_parameters(&module.items);
+        self.visit_module(module);
+        self.errors
+    }
+
+    /// Record the strategies annotated structs request, reporting conflicting annotations
+    fn collect_items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Struct { name, attributes, .. } => {
+                    if let Some(request) = self.annotated_strategy(*name, attributes) {
+                        self.structs.insert(*name, request);
+                    }
+                }
+                Item::Module { items: Some(items), .. } => self.collect_items(items),
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_parameters(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Function { name, params, .. } => {
+                    let requests = params.iter()
+                        .map(|param| param.type_annotation.as_ref().and_then(|ty| self.type_request(ty)))
+                        .collect();
+                    self.parameters.insert(*name, requests);
+                }
+                Item::Module { items: Some(items), .. } => self.collect_parameters(items),
+                _ => {}
+            }
+        }
+    }
+
+    /// The strategy requested by the first strategy annotation among `attributes`
+    ///
+    /// A later annotation requesting another strategy is reported as a conflict.
+    fn annotated_strategy(&mut self, item: InternedString, attributes: &[Attribute]) -> Option<StrategyRequest> {
+        let mut first: Option<StrategyRequest> = None;
+        for attribute in attributes {
+            let strategy = match self.interner.resolve(attribute.name).as_deref() {
+                Some("manual") => MemoryStrategy::Manual,
+                Some("smart") => MemoryStrategy::SmartPtr,
+                Some("linear") => MemoryStrategy::Linear,
+                Some("stack") => MemoryStrategy::Stack,
+                Some("region") => MemoryStrategy::Region,
+                Some("memory") => match crate::parser::memory_annotation_of(attribute, self.interner).ok().and_then(|memory| memory.strategy) {
+                    Some(MemoryStrategy::Inferred) | None => continue,
+                    Some(strategy) => strategy,
+                },
+                _ => continue,
+            };
+            let request = StrategyRequest { strategy, span: attribute.span };
+            match first {
+                Some(first) if first.strategy != strategy => {
+                    self.errors.push(StrategyError::ConflictingAnnotations { item, first, second: request });
+                }
+                Some(_) => {}
+                None => first = Some(request),
+            }
+        }
+        first
+    }
+
+    /// Pointer wrapper `ty` is, with the strategy it requests and the type it holds
+    fn wrapper<'t>(&self, ty: &'t Type) -> Option<(StrategyRequest, Option<&'t Type>)> {
+        let (strategy, held) = match ty {
+            Type::Path { segments, generics, .. } => {
+                let strategy = match self.interner.resolve(*segments.last()?)?.as_str() {
+                    "SmartPtr" => MemoryStrategy::SmartPtr,
+                    "LinearPtr" => MemoryStrategy::Linear,
+                    "RegionPtr" => MemoryStrategy::Region,
+                    "StackPtr" => MemoryStrategy::Stack,
+                    _ => return None,
+                };
+                (strategy, generics.first())
+            }
+            Type::Pointer { memory_strategy, target_type, .. } if *memory_strategy != MemoryStrategy::Manual => {
+                (*memory_strategy, Some(&**target_type))
+            }
+            _ => return None,
+        };
+        Some((StrategyRequest { strategy, span: ty.span() }, held))
+    }
+
+    /// Strategy a value of type `ty` is held with: its pointer wrapper's, or its
+    /// struct's annotation
+    fn type_request(&self, ty: &Type) -> Option<StrategyRequest> {
+        if let Some((request, _)) = self.wrapper(ty) {
+            return Some(request);
+        }
+        match ty {
+            Type::Path { segments, .. } => self.structs.get(segments.last()?).copied(),
+            _ => None,
+        }
+    }
+
+    /// Strategy of the binding `name`, if anything requested one
+    fn binding(&self, name: InternedString) -> Option<StrategyRequest> {
+        self.bindings.iter().rev().find_map(|scope| scope.get(&name)).copied()
+    }
+
+    fn bind(&mut self, pattern: &Pattern, request: Option<StrategyRequest>) {
+        if let (Pattern::Identifier { name, .. }, Some(scope)) = (pattern, self.bindings.last_mut()) {
+            match request {
+                Some(request) => scope.insert(*name, request),
+                // Shadowing a binding drops its strategy
+                None => scope.remove(name),
+            };
+        }
+    }
+
+    /// Strategy the value of `expr` was allocated with, for a binding read as it is
+    fn value_request(&self, expr: &Expr) -> Option<(InternedString, StrategyRequest)> {
+        match expr {
+            Expr::Identifier { name, .. } => self.binding(*name).map(|request| (*name, request)),
+            _ => None,
+        }
+    }
+
+    /// Report `value` used where `to` is declared, if it cannot be used as it is
+    fn check_use(&mut self, value: &Expr, to: Option<StrategyRequest>) {
+        let (Some((variable, from)), Some(to)) = (self.value_request(value), to) else {
+            return;
+        };
+        let conversion = conversion(from.strategy, to.strategy);
+        if conversion != Conversion::Implicit {
+            self.errors.push(StrategyError::IncompatibleUse { variable, from, to, conversion, span: value.span() });
+        }
+    }
+
+    /// Check the strategy the allocation `expr` gets against its escape fact and the
+    /// regions around it
+    fn check_allocation(&mut self, expr: &Expr, fact: &EscapeFact) {
+        let wrapped = self.wrapped_literal.filter(|(id, _)| *id == expr.id()).map(|(_, request)| request);
+        let annotated = match expr {
+            Expr::StructInit { path, .. } => path.last().and_then(|name| self.structs.get(name)).copied(),
+            _ => None,
+        };
+        let Some(requested) = wrapped.or(annotated).or(self.function.strategy) else {
+            return;
+        };
+        // A value leaving a region block is reported where it leaves the block, before
+        // it leaves the function
+        match (requested.strategy, &fact.region_escape) {
+            (MemoryStrategy::Region, _) if self.region_depth == 0 && !self.function.has_region => {
+                self.errors.push(StrategyError::RegionlessAllocation { requested, span: expr.span() });
+            }
+            (MemoryStrategy::Region, Some(escape)) => {
+                self.errors.push(StrategyError::RegionEscape { requested, escape: escape.clone(), is_shared: fact.is_shared });
+            }
+            (MemoryStrategy::Stack | MemoryStrategy::Region, _) if fact.escapes() => {
+                self.errors.push(StrategyError::EscapingValue {
+                    requested,
+                    context: fact.context.clone(),
+                    is_shared: fact.is_shared,
+                    span: expr.span(),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Check a function body with `function` as its facts and its parameters bound
+    fn check_function(&mut self, function: FunctionFacts, params: &[Parameter], body: Option<&Expr>) {
+        let outer = std::mem::replace(&mut self.function, function);
+        let outer_depth = std::mem::replace(&mut self.region_depth, 0);
+        self.bindings.push(HashMap::new());
+        for param in params {
+            let request = param.type_annotation.as_ref().and_then(|ty| self.type_request(ty));
+            self.bind(&param.pattern, request);
+            if let Some(ty) = &param.type_annotation {
+                self.visit_type(ty);
+            }
+        }
+        if let Some(body) = body {
+            // The trailing expression of the body is returned
+            if let Expr::Block { trailing_expr: Some(value), .. } = body {
+                self.check_use(value, self.function.returns);
+            }
+            self.visit_expr(body);
+        }
+        self.bindings.pop();
+        self.function = outer;
+        self.region_depth = outer_depth;
+    }
+}
+
+impl<'ast> Visitor<'ast> for StrategyChecker<'_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match item {
+            Item::Function { name, attributes, params, return_type, body, .. } => {
+                let function = FunctionFacts {
+                    strategy: self.annotated_strategy(*name, attributes),
+                    returns: return_type.as_ref().and_then(|ty| self.type_request(ty)),
+                    has_region: attributes.iter().any(|attribute| self.interner.matches(attribute.name, "region")),
+                };
+                if let Some(return_type) = return_type {
+                    self.visit_type(return_type);
+                }
+                self.check_function(function, params, body.as_ref());
+            }
+            _ => visitor::walk_item(self, item),
+        }
+    }
+
+    fn visit_impl_item(&mut self, item: &'ast ImplItem) {
+        match item {
+            ImplItem::Function { params, return_type, body, .. } => {
+                let function = FunctionFacts {
+                    returns: return_type.as_ref().and_then(|ty| self.type_request(ty)),
+                    ..FunctionFacts::default()
+                };
+                if let Some(return_type) = return_type {
+                    self.visit_type(return_type);
+                }
+                self.check_function(function, params, body.as_ref());
+            }
+            _ => visitor::walk_impl_item(self, item),
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        match stmt {
+            Stmt::Let { pattern, type_annotation, initializer, .. } => {
+                let declared = type_annotation.as_ref().and_then(|ty| self.type_request(ty));
+                if let Some(initializer) = initializer {
+                    self.check_use(initializer, declared);
+                    if let (Some(ty), Expr::StructInit { id, .. }) = (type_annotation, initializer) {
+                        if let Some((request, _)) = self.wrapper(ty) {
+                            self.wrapped_literal = Some((*id, request));
+                        }
+                    }
+                }
+                visitor::walk_stmt(self, stmt);
+                self.wrapped_literal = None;
+                // The binding's strategy is declared, or else that of its initializer
+                let request = declared.or_else(|| match initializer {
+                    Some(Expr::StructInit { path, .. }) => path.last().and_then(|name| self.structs.get(name)).copied(),
+                    Some(value) => self.value_request(value).map(|(_, request)| request),
+                    None => None,
+                });
+                self.bind(pattern, request);
+            }
+            Stmt::Assignment { target: Expr::Identifier { name, .. }, value, .. } => {
+                self.check_use(value, self.binding(*name));
+                visitor::walk_stmt(self, stmt);
+            }
+            Stmt::Return { expr: Some(value), .. } => {
+                self.check_use(value, self.function.returns);
+                visitor::walk_stmt(self, stmt);
+            }
+            Stmt::Region { .. } => {
+                self.region_depth += 1;
+                self.bindings.push(HashMap::new());
+                visitor::walk_stmt(self, stmt);
+                self.bindings.pop();
+                self.region_depth -= 1;
+            }
+            Stmt::If { .. } | Stmt::While { .. } | Stmt::For { .. } | Stmt::Loop { .. } | Stmt::Block { .. } => {
+                self.bindings.push(HashMap::new());
+                visitor::walk_stmt(self, stmt);
+                self.bindings.pop();
+            }
+            Stmt::Item { item, .. } => self.visit_item(item),
+            _ => visitor::walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        if let Some(fact) = self.escapes.get(expr.id()) {
+            self.check_allocation(expr, fact);
+        }
+        match expr {
+            Expr::Call { callee, args, .. } => {
+                if let Expr::Identifier { name, .. } = &**callee {
+                    let required = self.parameters.get(name).cloned().unwrap_or_default();
+                    for (arg, to) in args.iter().zip(required) {
+                        self.check_use(arg, to);
+                    }
+                }
+                visitor::walk_expr(self, expr);
+            }
+            Expr::Return { value: Some(value), .. } => {
+                self.check_use(value, self.function.returns);
+                visitor::walk_expr(self, expr);
+            }
+            Expr::Block { .. } => {
+                self.bindings.push(HashMap::new());
+                visitor::walk_expr(self, expr);
+                self.bindings.pop();
+            }
+            _ => visitor::walk_expr(self, expr),
+        }
+    }
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        if let Some((wrapper, Some(Type::Path { segments, .. }))) = self.wrapper(ty) {
+            if let Some(&type_name) = segments.last() {
+                if let Some(&annotation) = self.structs.get(&type_name) {
+                    if annotation.strategy != wrapper.strategy {
+                        self.errors.push(StrategyError::WrapperConflict { type_name, annotation, wrapper });
+                    }
+                }
+            }
+        }
+        visitor::walk_type(self, ty);
+    }
+}
@@ -4,8 +4,13 @@
 //! name resolution, scope management, and symbol tracking throughout the
 //! compilation pipeline.
 
-use crate::ast::{Item, Visibility, Span, InternedString, StructFields, EnumVariant, GenericParam, Type, Expr, Parameter, Pattern, Module};
-use crate::lexer::Position;
+use crate::ast::{Item, ImplItem, Visibility, Span, InternedString, StructFields, EnumVariant, GenericParam, Type, Expr, Stmt, MatchArm, Parameter, Pattern, Module};
+use crate::diagnostics::DiagnosticCode;
+use crate::lexer::{Position, TokenType};
+use crate::parser::StringInterner;
+use crate::parser::error::suggest_similar_identifiers;
+use crate::semantic::escape_analysis::EscapeFact;
+use crate::visitor::{self, Visitor};
 
 use std::collections::{HashMap, HashSet};
 use std::fmt;
@@ -25,6 +30,8 @@ pub enum SymbolError {
     /// Symbol already defined in current scope
     DuplicateSymbol {
         name: InternedString,
+        /// What the new definition defines, e.g. "function" or "field"
+        kind: String,
         existing_span: Span,
         new_span: Span,
     },
@@ -36,8 +43,10 @@ pub enum SymbolError {
     /// Symbol exists but is not accessible from current scope
     InaccessibleSymbol {
         name: InternedString,
+        /// What the symbol is, e.g. "function" or "field"
+        kind: String,
         span: Span,
-        reason: String,
+        definition_span: Span,
     },
     /// Circular dependency detected
     CircularDependency {
@@ -51,19 +60,37 @@ pub enum SymbolError {
         actual: SymbolKind,
         span: Span,
     },
+    /// Segment of a `use` path that names nothing to import
+    UnresolvedImport {
+        name: InternedString,
+        /// The previous segment, with what it names, when it is not a module the
+        /// segment could be looked up in
+        parent: Option<(InternedString, &'static str)>,
+        /// Whether the segment was looked up in the importing scope rather than a module
+        first: bool,
+        span: Span,
+        /// Names it may be a misspelling of
+        suggestions: Vec<String>,
+    },
+    /// Name imported twice into one scope
+    DuplicateImport {
+        name: InternedString,
+        existing_span: Span,
+        new_span: Span,
+    },
 }
 
 impl fmt::Display for SymbolError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            SymbolError::DuplicateSymbol { name, .. } => {
-                write!(f, "Symbol '{}' is already defined in this scope", name.id)
+            SymbolError::DuplicateSymbol { name, kind, .. } => {
+                write!(f, "{} '{}' is already defined in this scope", kind, name.id)
             }
             SymbolError::UndefinedSymbol { name, .. } => {
                 write!(f, "Undefined symbol '{}'", name.id)
             }
-            SymbolError::InaccessibleSymbol { name, reason, .. } => {
-                write!(f, "Symbol '{}' is not accessible: {}", name.id, reason)
+            SymbolError::InaccessibleSymbol { name, kind, .. } => {
+                write!(f, "{} '{}' is private", kind, name.id)
             }
             SymbolError::CircularDependency { symbols, .. } => {
                 write!(f, "Circular dependency detected involving symbols: {:?}", 
@@ -73,6 +100,95 @@ impl fmt::Display for SymbolError {
                 write!(f, "Invalid usage of symbol '{}': expected {:?}, found {:?}", 
                        name.id, expected, actual)
             }
+            SymbolError::UnresolvedImport { name, .. } => {
+                write!(f, "Unresolved import '{}'", name.id)
+            }
+            SymbolError::DuplicateImport { name, .. } => {
+                write!(f, "'{}' is imported more than once", name.id)
+            }
+        }
+    }
+}
+
+impl SymbolError {
+    /// Source location the error points at
+    pub fn span(&self) -> Span {
+        match self {
+            SymbolError::DuplicateSymbol { new_span, .. } | SymbolError::DuplicateImport { new_span, .. } => *new_span,
+            SymbolError::UndefinedSymbol { span, .. }
+            | SymbolError::UnresolvedImport { span, .. }
+            | SymbolError::InaccessibleSymbol { span, .. }
+            | SymbolError::InvalidUsage { span, .. } => *span,
+            SymbolError::CircularDependency { spans, .. } => {
+                spans.first().copied().unwrap_or_else(|| Span::single(Position::new(0, 0, 0, 0)))
+            }
+        }
+    }
+    
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            SymbolError::DuplicateSymbol { .. } | SymbolError::DuplicateImport { .. } => DiagnosticCode::DuplicateDefinition,
+            SymbolError::UndefinedSymbol { .. } => DiagnosticCode::UndefinedSymbol,
+            SymbolError::InaccessibleSymbol { .. } => DiagnosticCode::InaccessibleSymbol,
+            SymbolError::CircularDependency { .. } => DiagnosticCode::CircularDependency,
+            SymbolError::InvalidUsage { .. } => DiagnosticCode::InvalidUsage,
+            SymbolError::UnresolvedImport { .. } => DiagnosticCode::UnresolvedImport,
+        }
+    }
+    
+    /// Secondary location explaining the error, with its label
+    pub fn related(&self) -> Option<(Span, &'static str)> {
+        match self {
+            SymbolError::DuplicateSymbol { existing_span, .. } => Some((*existing_span, "previously defined here")),
+            SymbolError::DuplicateImport { existing_span, .. } => Some((*existing_span, "previously imported here")),
+            SymbolError::InaccessibleSymbol { kind, definition_span, .. } if kind == "field" => {
+                Some((*definition_span, "field declared here"))
+            }
+            SymbolError::InaccessibleSymbol { definition_span, .. } => Some((*definition_span, "defined here")),
+            _ => None,
+        }
+    }
+    
+    /// How to fix the error, if there is an obvious way
+    pub fn help(&self, interner: &StringInterner) -> Option<String> {
+        match self {
+            SymbolError::InaccessibleSymbol { name, kind, .. } => Some(format!(
+                "consider making the {} public: `pub {}`",
+                kind, interner.resolve(*name).unwrap_or_else(|| "<unknown>".to_string())
+            )),
+            _ => None,
+        }
+    }
+    
+    /// Human-readable message, with names resolved through `interner`
+    pub fn message(&self, interner: &StringInterner) -> String {
+        let resolve = |name: &InternedString| interner.resolve(*name).unwrap_or_else(|| "<unknown>".to_string());
+        match self {
+            SymbolError::DuplicateSymbol { name, kind, .. } => format!("{} `{}` is defined multiple times", kind, resolve(name)),
+            SymbolError::UndefinedSymbol { name, .. } => format!("cannot find `{}` in this scope", resolve(name)),
+            SymbolError::InaccessibleSymbol { name, kind, .. } => format!("{} `{}` is private", kind, resolve(name)),
+            SymbolError::CircularDependency { symbols, .. } => format!(
+                "circular dependency between {}",
+                symbols.iter().map(|name| format!("`{}`", resolve(name))).collect::<Vec<_>>().join(", ")
+            ),
+            SymbolError::InvalidUsage { name, expected, actual, .. } => format!(
+                "expected {}, found {} `{}`",
+                expected.description(), actual.description(), resolve(name)
+            ),
+            SymbolError::UnresolvedImport { name, parent, first, suggestions, .. } => {
+                let reason = match parent {
+                    Some((parent, kind)) => format!("`{}` is a {}, not a module", resolve(parent), kind),
+                    None if *first => format!("no module or item `{}` in this scope", resolve(name)),
+                    None => "the module defines no such item".to_string(),
+                };
+                let message = format!("unresolved import `{}`: {}", resolve(name), reason);
+                match suggestions.as_slice() {
+                    [] => message,
+                    suggestions => format!("{}\nDid you mean: {}", message, suggestions.join(", ")),
+                }
+            }
+            SymbolError::DuplicateImport { name, .. } => format!("`{}` is imported more than once in this scope", resolve(name)),
         }
     }
 }
@@ -89,6 +205,7 @@ pub enum SymbolKind {
     Function {
         params: Vec<Parameter>,
         return_type: Option<Type>,
+        generics: Vec<GenericParam>,
         is_extern: bool,
         is_method: bool,
     },
@@ -98,7 +215,10 @@ pub enum SymbolKind {
     },
     /// Module symbol
     Module {
+        /// Declared as `mod name;` with no file loaded for it, so its items are unknown
         is_external: bool,
+        /// Scope holding the module's items
+        scope_id: ScopeId,
     },
     /// Constant symbol
     Constant {
@@ -111,6 +231,23 @@ pub enum SymbolKind {
     },
 }
 
+impl SymbolKind {
+    /// What a symbol of this kind is called in diagnostics
+    pub fn description(&self) -> &'static str {
+        match self {
+            SymbolKind::Variable { .. } => "variable",
+            SymbolKind::Function { is_method: true, .. } => "method",
+            SymbolKind::Function { .. } => "function",
+            SymbolKind::Type { definition: TypeDefinition::Struct { .. } } => "struct",
+            SymbolKind::Type { definition: TypeDefinition::Enum { .. } } => "enum",
+            SymbolKind::Type { definition: TypeDefinition::Alias { .. } } => "type alias",
+            SymbolKind::Module { .. } => "module",
+            SymbolKind::Constant { .. } => "constant",
+            SymbolKind::GenericParam { .. } => "generic parameter",
+        }
+    }
+}
+
 /// Type definitions for type symbols
 #[derive(Debug, Clone, PartialEq)]
 pub enum TypeDefinition {
@@ -138,7 +275,11 @@ pub struct Symbol {
     pub span: Span,
     pub scope_id: ScopeId,
     pub is_used: bool,
+    /// Whether the symbol is assigned to after its declaration
+    pub is_assigned: bool,
     pub dependencies: HashSet<SymbolId>,
+    /// Symbol of the same name this binding shadows, if any
+    pub shadows: Option<SymbolId>,
 }
 
 impl Symbol {
@@ -158,7 +299,9 @@ impl Symbol {
             span,
             scope_id,
             is_used: false,
+            is_assigned: false,
             dependencies: HashSet::new(),
+            shadows: None,
         }
     }
     
@@ -167,6 +310,11 @@ impl Symbol {
         self.is_used = true;
     }
     
+    /// Mark this symbol as assigned to
+    pub fn mark_assigned(&mut self) {
+        self.is_assigned = true;
+    }
+    
     /// Add a dependency to another symbol
     pub fn add_dependency(&mut self, symbol_id: SymbolId) {
         self.dependencies.insert(symbol_id);
@@ -177,8 +325,8 @@ impl Symbol {
         match self.visibility {
             Visibility::Public => true,
             Visibility::Private => {
-                // Private symbols are accessible within the same module
-                symbol_table.is_same_module(self.scope_id, scope_id)
+                // Private symbols are accessible within their module and its submodules
+                symbol_table.is_within(scope_id, symbol_table.module_of(self.scope_id))
             }
         }
     }
@@ -228,6 +376,7 @@ impl Scope {
             // Symbol already exists in this scope
             Err(SymbolError::DuplicateSymbol {
                 name,
+                kind: "symbol".to_string(),
                 existing_span: Span::single(Position::new(0, 0, 0, 0)), // TODO: Get actual span
                 new_span: Span::single(Position::new(0, 0, 0, 0)),     // TODO: Get actual span
             })
@@ -258,8 +407,16 @@ pub struct SymbolTable {
     next_scope_id: ScopeId,
     /// Root scope (global/module scope)
     root_scope_id: ScopeId,
-    /// Module hierarchy for visibility checking
-    _module_hierarchy: HashMap<ScopeId, ScopeId>,
+    /// Module scope each scope belongs to, for visibility checking
+    module_of: HashMap<ScopeId, ScopeId>,
+    /// Type symbol each `impl` block scope implements
+    impl_types: HashMap<ScopeId, SymbolId>,
+    /// Start of the pattern that binds each local binding -> its symbol
+    bindings: HashMap<Position, SymbolId>,
+    /// Escape analysis facts for allocating expressions, by their start
+    escape_facts: HashMap<Position, EscapeFact>,
+    /// Scope each call of a plain name is made in, by the start of the name
+    call_scopes: HashMap<Position, ScopeId>,
 }
 
 impl SymbolTable {
@@ -283,7 +440,11 @@ impl SymbolTable {
             next_symbol_id: 0,
             next_scope_id: 1,
             root_scope_id,
-            _module_hierarchy: HashMap::new(),
+            module_of: HashMap::from([(root_scope_id, root_scope_id)]),
+            impl_types: HashMap::new(),
+            bindings: HashMap::new(),
+            escape_facts: HashMap::new(),
+            call_scopes: HashMap::new(),
         }
     }
     
@@ -292,6 +453,11 @@ impl SymbolTable {
         let scope_id = self.next_scope_id;
         self.next_scope_id += 1;
         
+        let module_id = match kind {
+            ScopeKind::Module => scope_id,
+            _ => self.module_of(self.current_scope_id),
+        };
+        self.module_of.insert(scope_id, module_id);
         let scope = Scope::new(scope_id, Some(self.current_scope_id), kind, span);
         
         // Add this scope as a child of the current scope
@@ -325,6 +491,19 @@ impl SymbolTable {
         self.current_scope_id
     }
     
+    /// Record that the `impl` block scope `scope_id` implements the type `type_id`
+    pub fn set_impl_type(&mut self, scope_id: ScopeId, type_id: SymbolId) {
+        self.impl_types.insert(scope_id, type_id);
+    }
+    
+    /// Make the existing scope `scope_id` current again, e.g. to walk the bodies of a
+    /// module whose items were declared earlier; `exit_scope` returns to its parent
+    pub fn resume_scope(&mut self, scope_id: ScopeId) {
+        if self.scopes.contains_key(&scope_id) {
+            self.current_scope_id = scope_id;
+        }
+    }
+    
     /// Add a symbol to the current scope
     pub fn add_symbol(
         &mut self,
@@ -333,6 +512,18 @@ impl SymbolTable {
         visibility: Visibility,
         span: Span,
     ) -> SymbolResult<SymbolId> {
+        let existing = self.scopes.get(&self.current_scope_id)
+            .and_then(|scope| scope.lookup_symbol(&name))
+            .and_then(|id| self.symbols.get(&id));
+        if let Some(existing) = existing {
+            return Err(SymbolError::DuplicateSymbol {
+                name,
+                kind: kind.description().to_string(),
+                existing_span: existing.span,
+                new_span: span,
+            });
+        }
+        
         let symbol_id = self.next_symbol_id;
         self.next_symbol_id += 1;
         
@@ -354,9 +545,95 @@ impl SymbolTable {
         Ok(symbol_id)
     }
     
+    /// Add a local binding to the current scope, shadowing any binding of the same name
+    ///
+    /// The shadowed symbol stays in the table, so its usage can still be reported.
+    pub fn add_binding(&mut self, name: InternedString, kind: SymbolKind, span: Span) -> SymbolId {
+        let symbol_id = self.next_symbol_id;
+        self.next_symbol_id += 1;
+        
+        let mut symbol = Symbol::new(symbol_id, name, kind, Visibility::Private, span, self.current_scope_id);
+        symbol.shadows = self.lookup_symbol(&name).map(|shadowed| shadowed.id);
+        if let Some(scope) = self.scopes.get_mut(&self.current_scope_id) {
+            scope.symbols.insert(name, symbol_id);
+        }
+        
+        self.bindings.insert(span.start, symbol_id);
+        self.symbols.insert(symbol_id, symbol);
+        symbol_id
+    }
+    
+    /// Make the symbol `symbol_id` available as `name` in the current scope, as `use` does
+    pub fn add_import(&mut self, name: InternedString, symbol_id: SymbolId, span: Span) -> SymbolResult<()> {
+        let existing = self.scopes.get(&self.current_scope_id)
+            .and_then(|scope| scope.lookup_symbol(&name))
+            .and_then(|id| self.symbols.get(&id));
+        if let Some(existing) = existing {
+            return Err(SymbolError::DuplicateSymbol {
+                name,
+                kind: "import".to_string(),
+                existing_span: existing.span,
+                new_span: span,
+            });
+        }
+        
+        if let Some(scope) = self.scopes.get_mut(&self.current_scope_id) {
+            scope.symbols.insert(name, symbol_id);
+        }
+        Ok(())
+    }
+    
+    /// Look up the local binding whose pattern starts at `start`
+    pub fn binding_at(&self, start: Position) -> Option<&Symbol> {
+        self.bindings.get(&start).and_then(|id| self.symbols.get(id))
+    }
+    
+    /// Record the resolved type of the local binding whose pattern starts at `start`
+    pub fn set_binding_type(&mut self, start: Position, ty: Type) {
+        let symbol = self.bindings.get(&start).and_then(|id| self.symbols.get_mut(id));
+        if let Some(Symbol { kind: SymbolKind::Variable { type_info, .. }, .. }) = symbol {
+            *type_info = Some(ty);
+        }
+    }
+    
+    /// Resolved types of the local bindings, by the start of the pattern binding them
+    pub fn binding_types(&self) -> HashMap<Position, Type> {
+        self.bindings.iter()
+            .filter_map(|(start, id)| match &self.symbols.get(id)?.kind {
+                SymbolKind::Variable { type_info: Some(ty), .. } => Some((*start, ty.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+    
+    /// Record what escape analysis found out about allocating expressions
+    pub fn set_escape_facts(&mut self, escape_facts: HashMap<Position, EscapeFact>) {
+        self.escape_facts = escape_facts;
+    }
+    
+    /// Escape analysis facts for allocating expressions, by their start
+    pub fn escape_facts(&self) -> &HashMap<Position, EscapeFact> {
+        &self.escape_facts
+    }
+    
+    /// Record that the name called at `start` is called from the current scope
+    pub fn record_call_scope(&mut self, start: Position) {
+        self.call_scopes.insert(start, self.current_scope_id);
+    }
+    
+    /// Scope the name called at `start` is called from, if the call was walked
+    pub fn call_scope(&self, start: Position) -> Option<ScopeId> {
+        self.call_scopes.get(&start).copied()
+    }
+    
     /// Look up a symbol by name, searching up the scope chain
     pub fn lookup_symbol(&self, name: &InternedString) -> Option<&Symbol> {
-        let mut current_scope_id = self.current_scope_id;
+        self.lookup_symbol_from(self.current_scope_id, name)
+    }
+    
+    /// Look up a symbol by name as seen from `scope_id`, searching up the scope chain
+    pub fn lookup_symbol_from(&self, scope_id: ScopeId, name: &InternedString) -> Option<&Symbol> {
+        let mut current_scope_id = scope_id;
         
         loop {
             if let Some(scope) = self.scopes.get(&current_scope_id) {
@@ -378,6 +655,42 @@ impl SymbolTable {
         None
     }
     
+    /// Look up a symbol defined directly in the scope `scope_id`
+    pub fn lookup_in_scope(&self, scope_id: ScopeId, name: &InternedString) -> Option<&Symbol> {
+        self.scopes.get(&scope_id)
+            .and_then(|scope| scope.lookup_symbol(name))
+            .and_then(|id| self.symbols.get(&id))
+    }
+    
+    /// Resolve `path`, written at `span`, from the current scope
+    ///
+    /// The first segment resolves through the scope chain, each following one among
+    /// the items of the module the previous one names. Returns the symbols along the
+    /// path, which stops short at a segment that names no module (as in `Color::Red`)
+    /// or a module whose file was not loaded, and is empty if the first segment names
+    /// nothing. A segment a module does not define, or keeps private, is an error.
+    pub fn resolve_path(&self, path: &[InternedString], span: Span) -> SymbolResult<Vec<SymbolId>> {
+        let Some((first, rest)) = path.split_first() else { return Ok(Vec::new()) };
+        let Some(mut symbol) = self.lookup_symbol(first) else { return Ok(Vec::new()) };
+        let mut resolved = vec![symbol.id];
+        
+        for name in rest {
+            let SymbolKind::Module { is_external: false, scope_id } = symbol.kind else { break };
+            symbol = self.lookup_in_scope(scope_id, name)
+                .ok_or(SymbolError::UndefinedSymbol { name: *name, span })?;
+            if !symbol.is_accessible_from(self.current_scope_id, self) {
+                return Err(SymbolError::InaccessibleSymbol {
+                    name: *name,
+                    kind: symbol.kind.description().to_string(),
+                    span,
+                    definition_span: symbol.span,
+                });
+            }
+            resolved.push(symbol.id);
+        }
+        Ok(resolved)
+    }
+    
     /// Look up a symbol by ID
     pub fn get_symbol(&self, symbol_id: SymbolId) -> Option<&Symbol> {
         self.symbols.get(&symbol_id)
@@ -388,31 +701,94 @@ impl SymbolTable {
         self.symbols.get_mut(&symbol_id)
     }
     
+    /// Look up a scope by ID
+    pub fn get_scope(&self, scope_id: ScopeId) -> Option<&Scope> {
+        self.scopes.get(&scope_id)
+    }
+    
     /// Check if two scopes are in the same module
     pub fn is_same_module(&self, scope1: ScopeId, scope2: ScopeId) -> bool {
-        let module1 = self.get_module_scope(scope1);
-        let module2 = self.get_module_scope(scope2);
-        module1 == module2
+        self.module_of(scope1) == self.module_of(scope2)
     }
     
-    /// Get the module scope containing the given scope
-    fn get_module_scope(&self, mut scope_id: ScopeId) -> ScopeId {
+    /// Whether a member of the type `type_symbol` with `visibility`, whose `impl` block or
+    /// definition is in `defining_scope`, is accessible from `scope_id`
+    ///
+    /// Private members are accessible within the defining module and its submodules,
+    /// and in any `impl` block of the type.
+    pub fn is_member_accessible(
+        &self,
+        type_symbol: SymbolId,
+        visibility: Visibility,
+        defining_scope: ScopeId,
+        scope_id: ScopeId,
+    ) -> bool {
+        if visibility == Visibility::Public || self.is_within(scope_id, self.module_of(defining_scope)) {
+            return true;
+        }
+        let mut scope = Some(scope_id);
+        while let Some(scope_id) = scope {
+            if self.impl_types.get(&scope_id) == Some(&type_symbol) {
+                return true;
+            }
+            scope = self.scopes.get(&scope_id).and_then(|scope| scope.parent_id);
+        }
+        false
+    }
+    
+    /// Whether `scope_id` is `ancestor` or nested in it
+    pub fn is_within(&self, mut scope_id: ScopeId, ancestor: ScopeId) -> bool {
         loop {
-            if let Some(scope) = self.scopes.get(&scope_id) {
-                match scope.kind {
-                    ScopeKind::Module => return scope_id,
-                    _ => {
-                        if let Some(parent_id) = scope.parent_id {
-                            scope_id = parent_id;
-                        } else {
-                            return self.root_scope_id;
-                        }
-                    }
+            if scope_id == ancestor {
+                return true;
+            }
+            match self.scopes.get(&scope_id).and_then(|scope| scope.parent_id) {
+                Some(parent_id) => scope_id = parent_id,
+                None => return false,
+            }
+        }
+    }
+    
+    /// Module scope the scope `scope_id` belongs to
+    pub fn module_of(&self, scope_id: ScopeId) -> ScopeId {
+        self.module_of.get(&scope_id).copied().unwrap_or(self.root_scope_id)
+    }
+    
+    /// Innermost scope whose span contains `position`, or the root scope
+    pub fn scope_at(&self, position: Position) -> ScopeId {
+        self.scopes.values()
+            .filter(|scope| {
+                scope.span.start.file_id == position.file_id
+                    && scope.span.start.offset <= position.offset
+                    && position.offset < scope.span.end.offset
+            })
+            .min_by_key(|scope| (scope.span.end.offset.saturating_sub(scope.span.start.offset), std::cmp::Reverse(scope.id)))
+            .map_or(self.root_scope_id, |scope| scope.id)
+    }
+    
+    /// Symbols visible from `scope_id`, leaving out those a nearer scope shadows
+    pub fn visible_symbols(&self, scope_id: ScopeId) -> Vec<&Symbol> {
+        let mut visible: HashMap<InternedString, &Symbol> = HashMap::new();
+        let mut scope = self.scopes.get(&scope_id);
+        while let Some(current) = scope {
+            for (name, id) in &current.symbols {
+                if let Some(symbol) = self.symbols.get(id) {
+                    visible.entry(*name).or_insert(symbol);
                 }
-            } else {
-                return self.root_scope_id;
             }
+            scope = current.parent_id.and_then(|parent_id| self.scopes.get(&parent_id));
         }
+        visible.into_values().collect()
+    }
+    
+    /// Whether some enum has a variant named `name`, which a bare call like `Some(x)` constructs
+    pub fn is_enum_variant(&self, name: InternedString) -> bool {
+        self.symbols.values().any(|symbol| match &symbol.kind {
+            SymbolKind::Type { definition: TypeDefinition::Enum { variants, .. } } => {
+                variants.iter().any(|variant| variant.name == name)
+            }
+            _ => false,
+        })
     }
     
     /// Get all symbols in the current scope
@@ -426,6 +802,22 @@ impl SymbolTable {
         }
     }
     
+    /// Bindings in the outermost block of a function body that shadow one of its
+    /// parameters, each with the parameter
+    pub fn shadowed_parameters(&self) -> Vec<(&Symbol, &Symbol)> {
+        self.symbols.values()
+            .filter_map(|symbol| {
+                let parameter = self.symbols.get(&symbol.shadows?)?;
+                let parameter_scope = self.scopes.get(&parameter.scope_id)?;
+                let body_scope = self.scopes.get(&symbol.scope_id)?;
+                let is_parameter = parameter_scope.kind == ScopeKind::Function
+                    && matches!(parameter.kind, SymbolKind::Variable { .. });
+                (is_parameter && body_scope.kind == ScopeKind::Block && body_scope.parent_id == Some(parameter.scope_id))
+                    .then_some((symbol, parameter))
+            })
+            .collect()
+    }
+    
     /// Get all unused symbols (for warnings)
     pub fn unused_symbols(&self) -> Vec<&Symbol> {
         self.symbols.values()
@@ -434,10 +826,47 @@ impl SymbolTable {
     }
 }
 
+/// Implemented trait (if any) and type of an `impl` block, by their paths
+type ImplTarget = (Option<Vec<InternedString>>, Vec<InternedString>);
+
+/// Method, associated type or constant an `impl` block defines
+#[derive(Debug, Clone)]
+struct ImplMember {
+    kind: &'static str,
+    visibility: Visibility,
+    span: Span,
+    /// Scope the `impl` block is written in
+    scope_id: ScopeId,
+}
+
 /// Symbol table builder that walks the AST and builds the symbol table
+///
+/// Module items are declared before any body is walked, so they resolve regardless
+/// of order. Parameters and `let` bindings are added to the scope they are bound in,
+/// and every read or assignment of a name is recorded on the symbol it resolves to.
+/// Names defined twice in the same scope, struct, enum, parameter list or set of
+/// `impl` blocks are reported; `let` bindings may shadow freely.
+///
+/// Each inline or loaded module gets a scope of its own. `use` declarations and the
+/// types of `impl` blocks are resolved once every module's items are declared, and
+/// paths like `helpers::f` resolve through the modules they name. Private items,
+/// fields and methods used outside the module defining them are reported, as far as
+/// the struct a field or method belongs to is known from annotations and literals.
 pub struct SymbolTableBuilder {
     symbol_table: SymbolTable,
     errors: Vec<SymbolError>,
+    /// Interner resolving the identifiers in macro arguments
+    interner: StringInterner,
+    /// Items defined so far in the `impl` blocks of each trait and type
+    impl_items: HashMap<ImplTarget, HashMap<InternedString, Span>>,
+    /// Scope of each module, by the start of its declaration
+    module_scopes: HashMap<Position, ScopeId>,
+    /// Members the `impl` blocks of each type define
+    members: HashMap<SymbolId, HashMap<InternedString, ImplMember>>,
+    /// Type of each binding whose type is known to be a named type
+    binding_types: HashMap<SymbolId, SymbolId>,
+    /// `use` declaration each name was imported by, per importing scope
+    imports: HashMap<(ScopeId, InternedString), Span>,
 }
 
 impl SymbolTableBuilder {
@@ -445,78 +874,692 @@ impl SymbolTableBuilder {
         Self {
             symbol_table: SymbolTable::new(),
             errors: Vec::new(),
+            interner: StringInterner::new(),
+            impl_items: HashMap::new(),
+            module_scopes: HashMap::new(),
+            members: HashMap::new(),
+            binding_types: HashMap::new(),
+            imports: HashMap::new(),
         }
     }
-    
+
+    /// Resolve macro arguments through the interner the module was parsed with
+    pub fn with_interner(mut self, interner: StringInterner) -> Self {
+        self.interner = interner;
+        self
+    }
+
     /// Build symbol table from AST module
     pub fn build(mut self, module: &Module) -> (SymbolTable, Vec<SymbolError>) {
         self.visit_module(module);
         (self.symbol_table, self.errors)
     }
-    
-    /// Visit a module and collect all symbols
-    fn visit_module(&mut self, module: &Module) {
-        for item in &module.items {
-            self.visit_item(item);
-        }
-    }
-    
-    /// Visit an item and add it to the symbol table
-    fn visit_item(&mut self, item: &Item) {
-        match item {
-            Item::Function { visibility, name, params, return_type, body, is_extern, span, .. } => {
+
+    /// Add an item to the symbol table
+    fn declare_item(&mut self, item: &Item) {
+        let (name, kind, visibility, span) = match item {
+            Item::Function { visibility, name, generics, params, return_type, is_extern, span, .. } => {
                 let kind = SymbolKind::Function {
                     params: params.clone(),
                     return_type: return_type.clone(),
+                    generics: generics.clone(),
                     is_extern: *is_extern,
                     is_method: false,
                 };
-                
-                match self.symbol_table.add_symbol(*name, kind, *visibility, *span) {
-                    Ok(_) => {
-                        // Enter function scope and process body
-                        if let Some(body_expr) = body {
-                            let _scope_id = self.symbol_table.enter_scope(ScopeKind::Function, *span);
-                            
-                            // Add parameters to function scope
-                            for param in params {
-                                self.visit_pattern(&param.pattern);
-                            }
-                            
-                            self.visit_expr(body_expr);
-                            let _ = self.symbol_table.exit_scope();
-                        }
-                    }
-                    Err(err) => self.errors.push(err),
-                }
+                (name, kind, visibility, span)
             }
-            
-            Item::Struct { visibility, name, fields, span, .. } => {
+            Item::Struct { visibility, name, generics, fields, span, .. } => {
+                self.check_unique_generics(generics);
+                self.check_unique_fields(fields);
                 let definition = TypeDefinition::Struct {
                     fields: fields.clone(),
-                    generics: Vec::new(), // TODO: Handle generics
+                    generics: generics.clone(),
                 };
-                let kind = SymbolKind::Type { definition };
-                
-                if let Err(err) = self.symbol_table.add_symbol(*name, kind, *visibility, *span) {
-                    self.errors.push(err);
+                (name, SymbolKind::Type { definition }, visibility, span)
+            }
+            Item::Enum { visibility, name, generics, variants, span, .. } => {
+                self.check_unique_generics(generics);
+                self.check_unique("variant", variants.iter().map(|variant| (variant.name, variant.span)));
+                for variant in variants {
+                    self.check_unique_fields(&variant.fields);
+                }
+                let definition = TypeDefinition::Enum {
+                    variants: variants.clone(),
+                    generics: generics.clone(),
+                };
+                (name, SymbolKind::Type { definition }, visibility, span)
+            }
+            Item::TypeAlias { visibility, name, generics, target_type, span, .. } => {
+                self.check_unique_generics(generics);
+                let definition = TypeDefinition::Alias {
+                    target: target_type.clone(),
+                    generics: generics.clone(),
+                };
+                (name, SymbolKind::Type { definition }, visibility, span)
+            }
+            Item::Const { visibility, name, type_annotation, value, span, .. } => {
+                let kind = SymbolKind::Constant {
+                    type_info: type_annotation.clone(),
+                    value: Some(value.clone()),
+                };
+                (name, kind, visibility, span)
+            }
+            Item::Module { visibility, name, items, span, .. } => {
+                // The module's items are declared in its own scope right away, so paths
+                // into it resolve from anywhere
+                let scope_id = self.symbol_table.enter_scope(ScopeKind::Module, *span);
+                for item in items.iter().flatten() {
+                    self.declare_item(item);
+                }
+                let _ = self.symbol_table.exit_scope();
+                self.module_scopes.insert(span.start, scope_id);
+                (name, SymbolKind::Module { is_external: items.is_none(), scope_id }, visibility, span)
+            }
+            _ => return,
+        };
+
+        if let Err(err) = self.symbol_table.add_symbol(*name, kind, *visibility, *span) {
+            self.errors.push(err);
+        }
+    }
+
+    /// Resolve the paths of the `use` declarations and `impl` blocks among `items` and
+    /// in their modules, importing names and recording the members of types
+    fn link_items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Use { path, path_spans, alias, span, .. } => self.import(path, path_spans, *alias, *span),
+                Item::Impl { target_type, items, .. } => self.declare_members(target_type, items),
+                Item::Module { items: Some(items), span, .. } => {
+                    let Some(&scope_id) = self.module_scopes.get(&span.start) else { continue };
+                    self.symbol_table.resume_scope(scope_id);
+                    self.link_items(items);
+                    let _ = self.symbol_table.exit_scope();
+                }
+                _ => {}
+            }
+        }
+    }
+    
+    /// Record the members an `impl` block for `target_type` defines
+    fn declare_members(&mut self, target_type: &Type, items: &[ImplItem]) {
+        let Some(type_id) = self.type_symbol(target_type) else { return };
+        let scope_id = self.symbol_table.current_scope();
+        let members = self.members.entry(type_id).or_default();
+        for item in items {
+            let (name, member) = match item {
+                ImplItem::Function { visibility, name, span, .. } => {
+                    (name, ImplMember { kind: "method", visibility: *visibility, span: *span, scope_id })
+                }
+                ImplItem::Type { visibility, name, span, .. } => {
+                    (name, ImplMember { kind: "associated type", visibility: *visibility, span: *span, scope_id })
+                }
+                ImplItem::Const { visibility, name, span, .. } => {
+                    (name, ImplMember { kind: "associated constant", visibility: *visibility, span: *span, scope_id })
                 }
+            };
+            members.entry(*name).or_insert(member);
+        }
+    }
+    
+    /// Make what `path` names available in the current scope, as `alias` or its last segment
+    ///
+    /// Each segment resolves like the segments of `resolve_path`, and the first one
+    /// that names nothing is reported at its own span in `path_spans`. Paths into a
+    /// module whose file was not loaded import nothing.
+    fn import(&mut self, path: &[InternedString], path_spans: &[Span], alias: Option<InternedString>, span: Span) {
+        let segment_span = |i: usize| path_spans.get(i).copied().unwrap_or(span);
+        let Some((&first, rest)) = path.split_first() else { return };
+        let Some(mut symbol) = self.symbol_table.lookup_symbol(&first) else {
+            let candidates = self.symbol_table.visible_symbols(self.symbol_table.current_scope_id)
+                .iter().map(|symbol| symbol.name).collect();
+            self.errors.push(self.unresolved_import(first, None, true, segment_span(0), candidates));
+            return;
+        };
+        
+        for (i, &name) in rest.iter().enumerate() {
+            let scope_id = match symbol.kind {
+                SymbolKind::Module { is_external: true, .. } => return,
+                SymbolKind::Module { scope_id, .. } => scope_id,
+                _ => {
+                    let parent = Some((path[i], symbol.kind.description()));
+                    self.errors.push(self.unresolved_import(name, parent, false, segment_span(i + 1), Vec::new()));
+                    return;
+                }
+            };
+            let Some(found) = self.symbol_table.lookup_in_scope(scope_id, &name) else {
+                let candidates = self.symbol_table.get_scope(scope_id)
+                    .map(|scope| scope.symbols.keys().copied().collect())
+                    .unwrap_or_default();
+                self.errors.push(self.unresolved_import(name, None, false, segment_span(i + 1), candidates));
+                return;
+            };
+            if !found.is_accessible_from(self.symbol_table.current_scope_id, &self.symbol_table) {
+                self.errors.push(SymbolError::InaccessibleSymbol {
+                    name,
+                    kind: found.kind.description().to_string(),
+                    span: segment_span(i + 1),
+                    definition_span: found.span,
+                });
+                return;
             }
-            
-            _ => {
-                // Handle other item types
+            symbol = found;
+        }
+        
+        let (symbol_id, name) = (symbol.id, alias.unwrap_or(path[path.len() - 1]));
+        let key = (self.symbol_table.current_scope_id, name);
+        if let Some(&existing_span) = self.imports.get(&key) {
+            self.errors.push(SymbolError::DuplicateImport { name, existing_span, new_span: span });
+            return;
+        }
+        match self.symbol_table.add_import(name, symbol_id, span) {
+            Ok(()) => {
+                self.imports.insert(key, span);
             }
+            Err(err) => self.errors.push(err),
+        }
+    }
+    
+    /// Unresolved import of `name`, suggesting the `candidates` it may be a misspelling of
+    fn unresolved_import(
+        &self,
+        name: InternedString,
+        parent: Option<(InternedString, &'static str)>,
+        first: bool,
+        span: Span,
+        candidates: Vec<InternedString>,
+    ) -> SymbolError {
+        let candidates: Vec<String> = candidates.into_iter()
+            .filter_map(|candidate| self.interner.resolve(candidate))
+            .collect();
+        let candidates: Vec<&str> = candidates.iter().map(String::as_str).collect();
+        let suggestions = self.interner.resolve(name)
+            .map(|name| suggest_similar_identifiers(&name, &candidates))
+            .unwrap_or_default();
+        SymbolError::UnresolvedImport { name, parent, first, span, suggestions }
+    }
+    
+    /// Record the items of an `impl` block, reporting any an earlier block for the same
+    /// trait and type already defines
+    fn declare_impl_items(&mut self, target_type: &Type, trait_ref: Option<&Type>, items: &[ImplItem]) {
+        let path = |ty: &Type| match ty {
+            Type::Path { segments, .. } => Some(segments.clone()),
+            _ => None,
+        };
+        let Some(type_path) = path(target_type) else { return };
+        let trait_path = match trait_ref {
+            Some(trait_ref) => match path(trait_ref) {
+                Some(trait_path) => Some(trait_path),
+                None => return,
+            },
+            None => None,
+        };
+        
+        let defined = self.impl_items.entry((trait_path, type_path)).or_default();
+        for item in items {
+            let (name, span, kind) = match item {
+                ImplItem::Function { name, span, .. } => (*name, *span, "method"),
+                ImplItem::Type { name, span, .. } => (*name, *span, "associated type"),
+                ImplItem::Const { name, span, .. } => (*name, *span, "associated constant"),
+            };
+            match defined.get(&name) {
+                Some(&existing_span) => self.errors.push(SymbolError::DuplicateSymbol {
+                    name,
+                    kind: kind.to_string(),
+                    existing_span,
+                    new_span: span,
+                }),
+                None => {
+                    defined.insert(name, span);
+                }
+            }
+        }
+    }
+    
+    /// Report every name in `names` that repeats an earlier one
+    fn check_unique(&mut self, kind: &str, names: impl IntoIterator<Item = (InternedString, Span)>) {
+        let mut seen = HashMap::new();
+        for (name, span) in names {
+            match seen.get(&name) {
+                Some(&existing_span) => self.errors.push(SymbolError::DuplicateSymbol {
+                    name,
+                    kind: kind.to_string(),
+                    existing_span,
+                    new_span: span,
+                }),
+                None => {
+                    seen.insert(name, span);
+                }
+            }
+        }
+    }
+    
+    /// Report repeated field names of a struct or enum variant
+    fn check_unique_fields(&mut self, fields: &StructFields) {
+        if let StructFields::Named(fields) = fields {
+            self.check_unique("field", fields.iter().map(|field| (field.name, field.span)));
+        }
+    }
+    
+    /// Report repeated generic parameters of an item without a scope of its own
+    fn check_unique_generics(&mut self, generics: &[GenericParam]) {
+        self.check_unique("generic parameter", generics.iter().map(|generic| (generic.name, generic.span)));
+    }
+    
+    /// Walk a function's signature, then its body with generics and parameters in scope
+    fn visit_function(
+        &mut self,
+        generics: &[GenericParam],
+        params: &[Parameter],
+        return_type: Option<&Type>,
+        body: Option<&Expr>,
+        span: Span,
+    ) {
+        for ty in params.iter().filter_map(|param| param.type_annotation.as_ref()).chain(return_type) {
+            self.visit_type(ty);
+        }
+        // Parameters of a function without a body bind nothing
+        let Some(body) = body else { return };
+
+        self.symbol_table.enter_scope(ScopeKind::Function, span);
+
+        // Generic parameters are in scope, with their bounds, inside the body
+        for generic in generics {
+            let kind = SymbolKind::GenericParam { bounds: generic.bounds.clone() };
+            if let Err(err) = self.symbol_table.add_symbol(generic.name, kind, Visibility::Private, generic.span) {
+                self.errors.push(err);
+            }
+        }
+
+        // Parameters live directly in the function scope, `let` bindings in the blocks below it
+        for param in params {
+            self.visit_pattern(&param.pattern);
+            if let (Pattern::Identifier { name, .. }, Some(ty)) = (&param.pattern, &param.type_annotation) {
+                let type_id = self.type_symbol(ty);
+                self.record_binding_type(*name, type_id);
+            }
+        }
+
+        self.visit_expr(body);
+        let _ = self.symbol_table.exit_scope();
+    }
+
+    /// Walk `statements` in a block scope of their own
+    fn visit_scoped_statements(&mut self, statements: &[Stmt], span: Span) {
+        self.symbol_table.enter_scope(ScopeKind::Block, span);
+        for stmt in statements {
+            self.visit_stmt(stmt);
+        }
+        let _ = self.symbol_table.exit_scope();
+    }
+
+    /// Walk match arms, each with the bindings of its pattern in scope
+    fn visit_match_arms(&mut self, arms: &[MatchArm]) {
+        for arm in arms {
+            self.symbol_table.enter_scope(ScopeKind::Block, arm.span);
+            self.visit_pattern(&arm.pattern);
+            if let Some(guard) = &arm.guard {
+                self.visit_expr(guard);
+            }
+            self.visit_expr(&arm.body);
+            let _ = self.symbol_table.exit_scope();
+        }
+    }
+
+    /// Bind `name` in the current scope
+    ///
+    /// Parameters are the only bindings made directly in a function scope, so a name
+    /// bound there twice is a repeated parameter.
+    fn bind(&mut self, name: InternedString, is_mutable: bool, span: Span) {
+        let scope = self.symbol_table.get_scope(self.symbol_table.current_scope())
+            .filter(|scope| scope.kind == ScopeKind::Function);
+        let parameter = scope.and_then(|scope| scope.lookup_symbol(&name))
+            .and_then(|id| self.symbol_table.get_symbol(id))
+            .filter(|symbol| matches!(symbol.kind, SymbolKind::Variable { .. }));
+        if let Some(parameter) = parameter {
+            self.errors.push(SymbolError::DuplicateSymbol {
+                name,
+                kind: "parameter".to_string(),
+                existing_span: parameter.span,
+                new_span: span,
+            });
+        }
+        
+        let kind = SymbolKind::Variable { is_mutable, type_info: None };
+        self.symbol_table.add_binding(name, kind, span);
+    }
+
+    /// Record a read of the symbol `name` resolves to, if any
+    fn mark_read(&mut self, name: InternedString) {
+        if let Some(id) = self.symbol_table.lookup_symbol(&name).map(|symbol| symbol.id) {
+            if let Some(symbol) = self.symbol_table.get_symbol_mut(id) {
+                symbol.mark_used();
+            }
+        }
+    }
+
+    /// Record a read of every symbol along `path`, written at `span`
+    ///
+    /// A path continuing past a type by one segment, as in `Point::new`, names a member
+    /// of the type and is checked for visibility.
+    fn mark_path_read(&mut self, path: &[InternedString], span: Span) {
+        let resolved = match self.symbol_table.resolve_path(path, span) {
+            Ok(resolved) => resolved,
+            Err(err) => return self.errors.push(err),
+        };
+        for &id in &resolved {
+            if let Some(symbol) = self.symbol_table.get_symbol_mut(id) {
+                symbol.mark_used();
+            }
+        }
+        if let (Some(&type_id), Some(&member)) = (resolved.last(), path.get(resolved.len())) {
+            if resolved.len() + 1 == path.len() {
+                self.check_member_access(type_id, member, span);
+            }
+        }
+    }
+    
+    /// Type symbol `ty` names, looking through references and pointers
+    fn type_symbol(&self, ty: &Type) -> Option<SymbolId> {
+        match ty {
+            Type::Reference { target_type, .. } | Type::Pointer { target_type, .. } => self.type_symbol(target_type),
+            Type::Path { segments, span, .. } => self.path_type(segments, *span),
+            _ => None,
+        }
+    }
+    
+    /// Type symbol `path`, written at `span`, names
+    fn path_type(&self, path: &[InternedString], span: Span) -> Option<SymbolId> {
+        let resolved = self.symbol_table.resolve_path(path, span).ok()?;
+        let &type_id = resolved.last().filter(|_| resolved.len() == path.len())?;
+        let symbol = self.symbol_table.get_symbol(type_id)?;
+        matches!(symbol.kind, SymbolKind::Type { .. }).then_some(type_id)
+    }
+    
+    /// Type symbol of the value of `expr`, when it is a struct literal or a binding of
+    /// known type
+    fn type_of(&self, expr: &Expr) -> Option<SymbolId> {
+        match expr {
+            Expr::Identifier { name, .. } => {
+                let symbol = self.symbol_table.lookup_symbol(name)?;
+                self.binding_types.get(&symbol.id).copied()
+            }
+            Expr::StructInit { path, span, .. } => self.path_type(path, *span),
+            Expr::Parenthesized { expr, .. } | Expr::Reference { expr, .. } | Expr::Dereference { expr, .. } => self.type_of(expr),
+            _ => None,
+        }
+    }
+    
+    /// Remember that the binding `name` just made holds a value of the type `type_id`
+    fn record_binding_type(&mut self, name: InternedString, type_id: Option<SymbolId>) {
+        let binding = self.symbol_table.lookup_symbol(&name).map(|symbol| symbol.id);
+        if let (Some(binding), Some(type_id)) = (binding, type_id) {
+            self.binding_types.insert(binding, type_id);
         }
     }
     
-    /// Visit an expression and collect symbols
-    fn visit_expr(&mut self, _expr: &Expr) {
-        // TODO: Implement expression symbol collection
+    /// Report the use at `span` of `field` of the struct `type_id` if it is private here
+    fn check_field_access(&mut self, type_id: SymbolId, field: InternedString, span: Span) {
+        let Some(symbol) = self.symbol_table.get_symbol(type_id) else { return };
+        let SymbolKind::Type { definition: TypeDefinition::Struct { fields: StructFields::Named(fields), .. } } = &symbol.kind else {
+            return;
+        };
+        let Some(field) = fields.iter().find(|candidate| candidate.name == field) else { return };
+        let current_scope = self.symbol_table.current_scope();
+        if !self.symbol_table.is_member_accessible(type_id, field.visibility, symbol.scope_id, current_scope) {
+            self.errors.push(SymbolError::InaccessibleSymbol {
+                name: field.name,
+                kind: "field".to_string(),
+                span,
+                definition_span: field.span,
+            });
+        }
     }
     
-    /// Visit a pattern and collect symbol references
-    fn visit_pattern(&mut self, _pattern: &Pattern) {
-        // TODO: Implement pattern symbol collection
+    /// Report the use at `span` of the member `name` of the type `type_id` if it is private here
+    fn check_member_access(&mut self, type_id: SymbolId, name: InternedString, span: Span) {
+        let Some(member) = self.members.get(&type_id).and_then(|members| members.get(&name)) else { return };
+        let current_scope = self.symbol_table.current_scope();
+        if !self.symbol_table.is_member_accessible(type_id, member.visibility, member.scope_id, current_scope) {
+            self.errors.push(SymbolError::InaccessibleSymbol {
+                name,
+                kind: member.kind.to_string(),
+                span,
+                definition_span: member.span,
+            });
+        }
+    }
+
+    /// Record an assignment to the symbol `name` resolves to, if any
+    fn mark_assigned(&mut self, name: InternedString) {
+        if let Some(id) = self.symbol_table.lookup_symbol(&name).map(|symbol| symbol.id) {
+            if let Some(symbol) = self.symbol_table.get_symbol_mut(id) {
+                symbol.mark_assigned();
+            }
+        }
+    }
+}
+
+impl<'ast> Visitor<'ast> for SymbolTableBuilder {
+    fn visit_module(&mut self, module: &'ast Module) {
+        for item in &module.items {
+            self.declare_item(item);
+        }
+        self.link_items(&module.items);
+        visitor::walk_module(self, module);
+    }
+
+    fn visit_item(&mut self, item: &'ast Item) {
+        match item {
+            Item::Function { generics, params, return_type, body, span, .. } => {
+                self.visit_function(generics, params, return_type.as_ref(), body.as_ref(), *span);
+            }
+            Item::Impl { generics, target_type, trait_ref, items, span, .. } => {
+                self.check_unique_generics(generics);
+                self.declare_impl_items(target_type, trait_ref.as_ref(), items);
+                // Private members of the type are accessible inside its `impl` blocks
+                let scope_id = self.symbol_table.enter_scope(ScopeKind::Impl, *span);
+                if let Some(type_id) = self.type_symbol(target_type) {
+                    self.symbol_table.set_impl_type(scope_id, type_id);
+                }
+                visitor::walk_item(self, item);
+                let _ = self.symbol_table.exit_scope();
+            }
+            Item::Module { items: Some(items), span, .. } => {
+                let Some(&scope_id) = self.module_scopes.get(&span.start) else { return };
+                self.symbol_table.resume_scope(scope_id);
+                for item in items {
+                    self.visit_item(item);
+                }
+                let _ = self.symbol_table.exit_scope();
+            }
+            _ => visitor::walk_item(self, item),
+        }
+    }
+
+    fn visit_impl_item(&mut self, item: &'ast ImplItem) {
+        match item {
+            ImplItem::Function { generics, params, return_type, body, span, .. } => {
+                self.visit_function(generics, params, return_type.as_ref(), body.as_ref(), *span);
+            }
+            _ => visitor::walk_impl_item(self, item),
+        }
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        match stmt {
+            Stmt::Let { pattern, type_annotation, initializer, is_mutable, .. } => {
+                if let Some(type_annotation) = type_annotation {
+                    self.visit_type(type_annotation);
+                }
+                // The initializer still sees the binding the pattern shadows
+                if let Some(initializer) = initializer {
+                    self.visit_expr(initializer);
+                }
+                match pattern {
+                    Pattern::Identifier { name, span, .. } => {
+                        let type_id = match type_annotation {
+                            Some(ty) => self.type_symbol(ty),
+                            None => initializer.as_ref().and_then(|initializer| self.type_of(initializer)),
+                        };
+                        self.bind(*name, *is_mutable, *span);
+                        self.record_binding_type(*name, type_id);
+                    }
+                    pattern => self.visit_pattern(pattern),
+                }
+            }
+            Stmt::Assignment { target, value, .. } | Stmt::CompoundAssignment { target, value, .. } => {
+                self.visit_expr(value);
+                // Assigning to a variable writes it without reading it
+                match target {
+                    Expr::Identifier { name, .. } => self.mark_assigned(*name),
+                    target => self.visit_expr(target),
+                }
+            }
+            Stmt::If { condition, then_block, else_block, span, .. } => {
+                self.visit_expr(condition);
+                self.visit_scoped_statements(then_block, *span);
+                if let Some(else_block) = else_block {
+                    self.visit_stmt(else_block);
+                }
+            }
+            Stmt::While { condition, body, span, .. } => {
+                self.visit_expr(condition);
+                self.visit_scoped_statements(body, *span);
+            }
+            Stmt::For { pattern, iterable, body, span, .. } => {
+                self.visit_expr(iterable);
+                self.symbol_table.enter_scope(ScopeKind::Block, *span);
+                self.visit_pattern(pattern);
+                for stmt in body {
+                    self.visit_stmt(stmt);
+                }
+                let _ = self.symbol_table.exit_scope();
+            }
+            Stmt::Loop { body, span, .. } | Stmt::Block { statements: body, span, .. } | Stmt::Region { body, span, .. } => {
+                self.visit_scoped_statements(body, *span);
+            }
+            Stmt::Match { expr, arms, .. } => {
+                self.visit_expr(expr);
+                self.visit_match_arms(arms);
+            }
+            Stmt::Item { item, .. } => {
+                self.declare_item(item);
+                self.link_items(std::slice::from_ref(item));
+                self.visit_item(item);
+            }
+            _ => visitor::walk_stmt(self, stmt),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        match expr {
+            Expr::Identifier { name, .. } => self.mark_read(*name),
+            Expr::Path { segments, span, .. } => self.mark_path_read(segments, *span),
+            Expr::StructInit { path, fields, span, .. } => {
+                self.mark_path_read(path, *span);
+                if let Some(type_id) = self.type_of(expr) {
+                    for field in fields {
+                        self.check_field_access(type_id, field.name, field.span);
+                    }
+                }
+                for field in fields {
+                    match &field.value {
+                        Some(value) => self.visit_expr(value),
+                        // Shorthand `Point { x }` reads the variable `x`
+                        None => self.mark_read(field.name),
+                    }
+                }
+            }
+            // The type checker resolves a called name from the scope the call is made in
+            Expr::Call { callee, .. } => {
+                if let Expr::Identifier { span, .. } = callee.as_ref() {
+                    self.symbol_table.record_call_scope(span.start);
+                }
+                visitor::walk_expr(self, expr);
+            }
+            Expr::FieldAccess { object, field, span, .. } => {
+                if let Some(type_id) = self.type_of(object) {
+                    self.check_field_access(type_id, *field, *span);
+                }
+                visitor::walk_expr(self, expr);
+            }
+            Expr::MethodCall { receiver, method, span, .. } => {
+                if let Some(type_id) = self.type_of(receiver) {
+                    self.check_member_access(type_id, *method, *span);
+                }
+                visitor::walk_expr(self, expr);
+            }
+            // Macro arguments are raw tokens, so any identifier among them counts as a read
+            Expr::Macro { args, .. } => {
+                for arg in args {
+                    if let TokenType::Identifier(name) = arg {
+                        let name = self.interner.intern(name);
+                        self.mark_read(name);
+                    }
+                }
+            }
+            Expr::Block { span, .. } => {
+                self.symbol_table.enter_scope(ScopeKind::Block, *span);
+                visitor::walk_expr(self, expr);
+                let _ = self.symbol_table.exit_scope();
+            }
+            Expr::Closure { params, return_type, body, span, .. } => {
+                self.visit_function(&[], params, return_type.as_ref(), Some(body), *span);
+            }
+            Expr::For { pattern, iterator, body, span, .. } => {
+                self.visit_expr(iterator);
+                self.symbol_table.enter_scope(ScopeKind::Block, *span);
+                self.visit_pattern(pattern);
+                self.visit_expr(body);
+                let _ = self.symbol_table.exit_scope();
+            }
+            Expr::Match { expr, arms, .. } => {
+                self.visit_expr(expr);
+                self.visit_match_arms(arms);
+            }
+            _ => visitor::walk_expr(self, expr),
+        }
+    }
+
+    /// Bind the names a pattern introduces; only patterns in binding position are visited
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        match pattern {
+            Pattern::Identifier { name, is_mutable, span, .. } => {
+                // A name that resolves to a constant or type is matched against, not bound
+                match self.symbol_table.lookup_symbol(name).map(|symbol| &symbol.kind) {
+                    Some(SymbolKind::Constant { .. } | SymbolKind::Type { .. }) => self.mark_read(*name),
+                    _ => self.bind(*name, *is_mutable, *span),
+                }
+            }
+            Pattern::Struct { path, fields, span, .. } => {
+                self.mark_path_read(path, *span);
+                if let Some(type_id) = self.path_type(path, *span) {
+                    for field in fields {
+                        self.check_field_access(type_id, field.name, field.span);
+                    }
+                }
+                for field in fields {
+                    match &field.pattern {
+                        Some(pattern) => self.visit_pattern(pattern),
+                        // Shorthand `Point { x }` binds the field name
+                        None => self.bind(field.name, false, field.span),
+                    }
+                }
+            }
+            Pattern::Enum { path, span, .. } => {
+                self.mark_path_read(path, *span);
+                visitor::walk_pattern(self, pattern);
+            }
+            _ => visitor::walk_pattern(self, pattern),
+        }
+    }
+
+    fn visit_type(&mut self, ty: &'ast Type) {
+        if let Type::Path { segments, span, .. } = ty {
+            self.mark_path_read(segments, *span);
+        }
+        visitor::walk_type(self, ty);
     }
 }
 
@@ -8,10 +8,16 @@
 //! - Integration with hybrid memory management
 
 use crate::ast::{
-    Type, Expr, Item, Module, Literal, PrimitiveType, Span, InternedString,
-    MemoryStrategy, Ownership, LifetimeId, TypeConstraint, BinaryOp, UnaryOp
+    Type, Expr, Stmt, Item, ImplItem, Module, Literal, PrimitiveType, Span, InternedString,
+    MemoryStrategy, Ownership, LifetimeId, TypeConstraint, BinaryOp, UnaryOp, Parameter, Pattern,
+    EnumVariant, NodeId, SideTable
 };
-use crate::semantic::symbols::{SymbolTable, SymbolKind};
+use crate::diagnostics::DiagnosticCode;
+use crate::lexer::token::NumberBase;
+use crate::parser::StringInterner;
+use crate::parser::error::suggest_similar_identifiers;
+use crate::semantic::intrinsics::Intrinsic;
+use crate::semantic::symbols::{SymbolTable, SymbolKind, Symbol, ScopeId, TypeDefinition};
 use std::collections::HashMap;
 use std::fmt;
 
@@ -71,6 +77,94 @@ pub enum TypeError {
         threshold: u64,
         span: Span,
     },
+    /// Integer literal outside the range of its type
+    LiteralOutOfRange {
+        /// The literal as written, with its sign
+        literal: String,
+        /// Type the literal takes; `None` for an untyped literal, which must fit in 64 bits
+        ty: Option<PrimitiveType>,
+        min: i128,
+        max: u128,
+        span: Span,
+    },
+    /// `let` annotation that disagrees with the type of the initializer
+    AnnotationMismatch {
+        annotated: Type,
+        found: Type,
+        annotation_span: Span,
+        initializer_span: Span,
+    },
+    /// Array length, in a `[T; N]` type or a `[value; N]` expression, that is not a constant
+    NonConstantArrayLength {
+        span: Span,
+    },
+    /// Array initializer whose element count differs from the annotated length
+    ArrayLengthMismatch {
+        expected: u128,
+        found: u128,
+        annotation_span: Span,
+        initializer_span: Span,
+    },
+    /// Constant index past the end of an array of statically known length
+    IndexOutOfBounds {
+        index: u128,
+        length: u128,
+        span: Span,
+    },
+    /// Enum variant whose discriminant an earlier variant of the same enum already has
+    DuplicateDiscriminant {
+        value: u128,
+        span: Span,
+        previous_span: Span,
+    },
+    /// Call with a different number of arguments than the callee has parameters
+    ArgumentCountMismatch {
+        callee: InternedString,
+        expected: usize,
+        found: usize,
+        span: Span,
+        definition_span: Span,
+    },
+    /// Call argument whose type the corresponding parameter does not accept
+    ArgumentTypeMismatch {
+        callee: InternedString,
+        /// Position of the argument, from 0
+        index: usize,
+        expected: Type,
+        found: Type,
+        argument_span: Span,
+        call_span: Span,
+        /// The parameter in the callee's signature, if the callee declares its
+        /// parameters by name; function-typed variables and intrinsics do not
+        parameter_span: Option<Span>,
+    },
+    /// Call of a name that resolves to no function, variable or enum variant
+    UnknownFunction {
+        name: InternedString,
+        span: Span,
+        suggestions: Vec<String>,
+    },
+    /// Expression of type `()`, which has no value, where a value is needed
+    UnitValue {
+        /// What needs the value, such as "an operand"
+        usage: &'static str,
+        span: Span,
+    },
+    /// `break` with a value out of a `while` or `for` loop, whose result is always unit
+    BreakValueInLoop {
+        /// Keyword of the loop broken out of
+        keyword: &'static str,
+        span: Span,
+        loop_span: Span,
+    },
+    /// Enum or struct pattern that no value of the matched expression's type can match
+    PatternMismatch {
+        /// Path of the pattern, such as `Shape::Circle`
+        path: Vec<InternedString>,
+        expected: Type,
+        span: Span,
+        scrutinee_span: Span,
+    },
 }
 
 /// Ownership violation types
@@ -136,10 +230,346 @@ impl fmt::Display for TypeError {
                 write!(f, "Performance violation: {}\nEstimated cost: {}, threshold: {}", 
                        message, cost_estimate, threshold)
             }
+            TypeError::LiteralOutOfRange { literal, ty, min, max, .. } => {
+                match ty {
+                    Some(ty) => write!(f, "Integer literal {} out of range for {}", literal, format!("{:?}", ty).to_lowercase())?,
+                    None => write!(f, "Integer literal {} does not fit in 64 bits", literal)?,
+                }
+                write!(f, "\nValid range: {}..={}", min, max)
+            }
+            TypeError::AnnotationMismatch { annotated, found, .. } => {
+                write!(f, "Type mismatch: binding is annotated as {} but initialized with {}", type_name(annotated), type_name(found))?;
+                match annotated {
+                    Type::Primitive { .. } => write!(f, "\nSuggestion: annotate it as {} or convert the value with `as {}`", type_name(found), type_name(annotated)),
+                    _ => write!(f, "\nSuggestion: annotate it as {}", type_name(found)),
+                }
+            }
+            TypeError::NonConstantArrayLength { .. } => {
+                write!(f, "Array length must be a constant expression")?;
+                write!(f, "\nHelp: use an integer literal, a `const` or arithmetic on them")
+            }
+            TypeError::ArrayLengthMismatch { expected, found, .. } => {
+                write!(f, "Array length mismatch: binding is annotated with {} elements but initialized with {}", expected, found)
+            }
+            TypeError::IndexOutOfBounds { index, length, .. } => {
+                write!(f, "Index {} is out of bounds for array of length {}", index, length)
+            }
+            TypeError::DuplicateDiscriminant { value, .. } => {
+                write!(f, "Discriminant value {} is assigned to more than one variant", value)?;
+                write!(f, "\nHelp: give each variant its own `= value`")
+            }
+            TypeError::ArgumentCountMismatch { callee, expected, found, .. } => {
+                write!(f, "Function '{}' takes {} but {} supplied", callee.id, argument_count(*expected), supplied_count(*found))
+            }
+            TypeError::ArgumentTypeMismatch { callee, index, expected, found, .. } => {
+                write!(f, "Type mismatch: argument {} of '{}' is {} but {} was given", index + 1, callee.id, type_name(expected), type_name(found))?;
+                write_conversion_hint(f, expected, found)
+            }
+            TypeError::UnknownFunction { name, suggestions, .. } => {
+                write!(f, "Cannot find function '{}' in this scope", name.id)?;
+                if !suggestions.is_empty() {
+                    write!(f, "\nDid you mean: {}", suggestions.join(", "))?;
+                }
+                Ok(())
+            }
+            TypeError::UnitValue { usage, .. } => {
+                write!(f, "Type mismatch: expected a value for {}, found ()", usage)?;
+                write!(f, "\nHelp: functions without a `-> T` return type return nothing")
+            }
+            TypeError::BreakValueInLoop { keyword, .. } => {
+                write!(f, "`break` with a value is not allowed in a `{}` loop", keyword)?;
+                write!(f, "\nHelp: only `loop` produces a value; assign the result to a variable before breaking")
+            }
+            TypeError::PatternMismatch { path, expected, .. } => {
+                let path: Vec<String> = path.iter().map(|segment| segment.id.to_string()).collect();
+                write!(f, "Type mismatch: pattern `{}` cannot match a value of type {}", path.join("::"), type_name(expected))
+            }
+        }
+    }
+}
+
+impl TypeError {
+    /// Source location the error points at
+    pub fn span(&self) -> Span {
+        match self {
+            TypeError::Mismatch { span, .. }
+            | TypeError::StrategyConflict { span, .. }
+            | TypeError::OwnershipViolation { span, .. }
+            | TypeError::LifetimeError { span, .. }
+            | TypeError::LinearTypeError { span, .. }
+            | TypeError::InferenceFailure { span, .. }
+            | TypeError::UndefinedType { span, .. }
+            | TypeError::PerformanceViolation { span, .. }
+            | TypeError::LiteralOutOfRange { span, .. }
+            | TypeError::NonConstantArrayLength { span }
+            | TypeError::IndexOutOfBounds { span, .. }
+            | TypeError::DuplicateDiscriminant { span, .. }
+            | TypeError::ArgumentCountMismatch { span, .. }
+            | TypeError::UnknownFunction { span, .. }
+            | TypeError::UnitValue { span, .. }
+            | TypeError::BreakValueInLoop { span, .. }
+            | TypeError::PatternMismatch { span, .. } => *span,
+            TypeError::ArgumentTypeMismatch { argument_span, .. } => *argument_span,
+            TypeError::AnnotationMismatch { initializer_span, .. }
+            | TypeError::ArrayLengthMismatch { initializer_span, .. } => *initializer_span,
+        }
+    }
+    
+    /// Stable identifier of the kind of error
+    pub fn code(&self) -> DiagnosticCode {
+        match self {
+            TypeError::Mismatch { .. } | TypeError::AnnotationMismatch { .. } | TypeError::ArgumentTypeMismatch { .. }
+            | TypeError::UnitValue { .. } | TypeError::BreakValueInLoop { .. }
+            | TypeError::PatternMismatch { .. } => DiagnosticCode::TypeMismatch,
+            TypeError::StrategyConflict { .. } => DiagnosticCode::StrategyConflict,
+            TypeError::OwnershipViolation { .. } => DiagnosticCode::OwnershipViolation,
+            TypeError::LifetimeError { .. } => DiagnosticCode::Lifetime,
+            TypeError::LinearTypeError { .. } => DiagnosticCode::LinearType,
+            TypeError::InferenceFailure { .. } => DiagnosticCode::InferenceFailure,
+            TypeError::UndefinedType { .. } => DiagnosticCode::UndefinedType,
+            TypeError::PerformanceViolation { .. } => DiagnosticCode::PerformanceViolation,
+            TypeError::LiteralOutOfRange { .. } => DiagnosticCode::LiteralOutOfRange,
+            TypeError::NonConstantArrayLength { .. } => DiagnosticCode::NonConstantArrayLength,
+            TypeError::ArrayLengthMismatch { .. } => DiagnosticCode::ArrayLengthMismatch,
+            TypeError::IndexOutOfBounds { .. } => DiagnosticCode::IndexOutOfBounds,
+            TypeError::DuplicateDiscriminant { .. } => DiagnosticCode::DuplicateDiscriminant,
+            TypeError::ArgumentCountMismatch { .. } => DiagnosticCode::ArgumentCountMismatch,
+            TypeError::UnknownFunction { .. } => DiagnosticCode::UnknownFunction,
+        }
+    }
+    
+    /// Secondary location explaining the error, with its label
+    pub fn related(&self) -> Option<(Span, &'static str)> {
+        match self {
+            TypeError::AnnotationMismatch { annotation_span, .. }
+            | TypeError::ArrayLengthMismatch { annotation_span, .. } => Some((*annotation_span, "type annotated here")),
+            TypeError::DuplicateDiscriminant { previous_span, .. } => Some((*previous_span, "first assigned here")),
+            TypeError::ArgumentCountMismatch { definition_span, .. } => Some((*definition_span, "defined here")),
+            TypeError::ArgumentTypeMismatch { parameter_span, .. } => parameter_span.map(|span| (span, "parameter declared here")),
+            TypeError::BreakValueInLoop { loop_span, .. } => Some((*loop_span, "this loop evaluates to ()")),
+            TypeError::PatternMismatch { scrutinee_span, .. } => Some((*scrutinee_span, "matched value here")),
+            _ => None,
+        }
+    }
+    
+    /// Human-readable message, with names resolved through `interner`
+    pub fn message(&self, interner: &StringInterner) -> String {
+        let resolve = |name: &InternedString| interner.resolve(*name).unwrap_or_else(|| "<unknown>".to_string());
+        match self {
+            TypeError::LinearTypeError { name, error, .. } => format!("Linear type error for '{}': {:?}", resolve(name), error),
+            TypeError::UndefinedType { name, suggestions, .. } if suggestions.is_empty() => format!("Undefined type '{}'", resolve(name)),
+            TypeError::UndefinedType { name, suggestions, .. } => {
+                format!("Undefined type '{}'\nDid you mean: {}", resolve(name), suggestions.join(", "))
+            }
+            TypeError::ArgumentCountMismatch { callee, expected, found, .. } => {
+                format!("Function '{}' takes {} but {} supplied", resolve(callee), argument_count(*expected), supplied_count(*found))
+            }
+            TypeError::ArgumentTypeMismatch { callee, index, expected, found, .. } => {
+                let mut message = format!(
+                    "Type mismatch: argument {} of '{}' is {} but {} was given",
+                    index + 1, resolve(callee), type_name(expected), type_name(found)
+                );
+                let _ = write_conversion_hint(&mut message, expected, found);
+                message
+            }
+            TypeError::UnknownFunction { name, suggestions, .. } if suggestions.is_empty() => {
+                format!("Cannot find function '{}' in this scope", resolve(name))
+            }
+            TypeError::UnknownFunction { name, suggestions, .. } => {
+                format!("Cannot find function '{}' in this scope\nDid you mean: {}", resolve(name), suggestions.join(", "))
+            }
+            TypeError::PatternMismatch { path, expected, .. } => {
+                let path: Vec<String> = path.iter().map(resolve).collect();
+                let expected = match expected {
+                    Type::Path { segments, .. } => segments.iter().map(resolve).collect::<Vec<_>>().join("::"),
+                    expected => type_name(expected),
+                };
+                format!("Type mismatch: pattern `{}` cannot match a value of type {}", path.join("::"), expected)
+            }
+            _ => self.to_string(),
         }
     }
 }
 
+/// Primitive kind of a type annotation, if it is a primitive
+fn primitive_kind(ty: Option<&Type>) -> Option<PrimitiveType> {
+    match ty {
+        Some(Type::Primitive { kind, .. }) => Some(*kind),
+        _ => None,
+    }
+}
+
+/// Name of a type as written in source, for primitives and arrays of them
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Primitive { kind, .. } => format!("{:?}", kind).to_lowercase(),
+        Type::Array { element_type, size, .. } => match size.as_ref() {
+            Expr::Literal { literal: Literal::Integer { value, .. }, .. } => format!("[{}; {}]", type_name(element_type), value),
+            _ => format!("[{}; _]", type_name(element_type)),
+        },
+        ty => format!("{:?}", ty),
+    }
+}
+
+/// "1 argument" or "`count` arguments"
+fn argument_count(count: usize) -> String {
+    match count {
+        1 => "1 argument".to_string(),
+        count => format!("{} arguments", count),
+    }
+}
+
+/// "1 argument was" or "`count` arguments were"
+fn supplied_count(count: usize) -> String {
+    match count {
+        1 => "1 argument was".to_string(),
+        count => format!("{} arguments were", count),
+    }
+}
+
+/// Suggest converting a number argument of type `found` to the parameter's number type `expected`
+fn write_conversion_hint(f: &mut impl fmt::Write, expected: &Type, found: &Type) -> fmt::Result {
+    let is_number = |kind: Option<PrimitiveType>| kind.is_some_and(|kind| kind.is_integer() || kind.is_float());
+    if is_number(primitive_kind(Some(expected))) && is_number(primitive_kind(Some(found))) {
+        write!(f, "\nSuggestion: convert the value with `as {}`", type_name(expected))?;
+    }
+    Ok(())
+}
+
+/// Whether a value of type `found` may initialize a binding annotated as `annotated`,
+/// or be passed for a parameter of that type
+///
+/// Integers widen implicitly when every value of the initializer's type fits, so
+/// `let x: i32 = byte;` is fine but `let x: u8 = returns_i64();` is not. Array
+/// elements follow the same rule.
+fn accepts(annotated: &Type, found: &Inferred) -> bool {
+    match (annotated, found) {
+        (Type::Primitive { kind: expected, .. }, found) => match found {
+            Inferred::Known(Type::Primitive { kind, .. }) => widens_to(*kind, *expected),
+            Inferred::Known(_) => true,
+            Inferred::Integer => expected.is_integer(),
+            Inferred::Float => expected.is_float(),
+            Inferred::Array { .. } => false,
+        },
+        (Type::Array { element_type, .. }, found) => match found {
+            Inferred::Array { element, .. } => accepts(element_type, element),
+            Inferred::Known(Type::Array { element_type: found, .. }) => accepts(element_type, &Inferred::Known(found.as_ref().clone())),
+            Inferred::Known(Type::Primitive { .. }) | Inferred::Integer | Inferred::Float => false,
+            Inferred::Known(_) => true,
+        },
+        _ => true,
+    }
+}
+
+/// Whether a type is unit, written `()`
+fn is_unit(ty: &Type) -> bool {
+    match ty {
+        Type::Primitive { kind, .. } => *kind == PrimitiveType::Unit,
+        Type::Tuple { types, .. } => types.is_empty(),
+        _ => false,
+    }
+}
+
+/// Integer literal expression for a folded array length
+fn length_literal(length: u128, span: Span) -> Expr {
+    Expr::Literal {
+        literal: Literal::Integer { value: length.to_string(), base: NumberBase::Decimal, suffix: None },
+        span,
+        id: NodeId::DUMMY,
+    }
+}
+
+/// Whether every `from` value converts to `to` without loss, so it may initialize a `to` binding
+fn widens_to(from: PrimitiveType, to: PrimitiveType) -> bool {
+    if from == to {
+        return true;
+    }
+    match (from.integer_bits(), to.integer_bits()) {
+        (Some(from_bits), Some(to_bits)) => match (from.is_unsigned(), to.is_unsigned()) {
+            (true, false) => from_bits < to_bits,
+            (false, true) => false,
+            _ => from_bits <= to_bits,
+        },
+        _ => from == PrimitiveType::F32 && to == PrimitiveType::F64,
+    }
+}
+
+/// Loop enclosing the code being inferred, which `break` and `continue` refer to
+struct EnclosingLoop {
+    label: Option<InternedString>,
+    /// `loop`, `while` or `for`
+    keyword: &'static str,
+    span: Span,
+    /// Type of the first `break` value out of the loop
+    value: Option<Inferred>,
+}
+
+/// Type of an expression as far as local inference can tell
+#[derive(Debug, Clone)]
+enum Inferred {
+    /// Fixed by a suffix, annotation, signature or operator
+    Known(Type),
+    /// Unsuffixed integer literal: an i32 unless its context makes it another integer type
+    Integer,
+    /// Unsuffixed float literal: an f64 unless its context makes it an f32
+    Float,
+    /// Array literal or repeat expression of `length` elements
+    Array {
+        element: Box<Inferred>,
+        length: u128,
+    },
+}
+
+impl Inferred {
+    /// Type of a value combining `self` and `other`, as the operands of arithmetic or the branches of an `if`
+    ///
+    /// An unsuffixed literal adapts to the other side. Known types that differ do not
+    /// combine, since codegen decides which conversions exist.
+    fn unify(self, other: Inferred) -> Option<Inferred> {
+        match (self, other) {
+            (Inferred::Array { element: left, length }, Inferred::Array { element: right, length: other_length }) if length == other_length => {
+                Some(Inferred::Array { element: Box::new(left.unify(*right)?), length })
+            }
+            (Inferred::Array { .. }, _) | (_, Inferred::Array { .. }) => None,
+            (Inferred::Known(left), Inferred::Known(right)) => match (primitive_kind(Some(&left)), primitive_kind(Some(&right))) {
+                (Some(left_kind), Some(right_kind)) if left_kind == right_kind => Some(Inferred::Known(left)),
+                _ => None,
+            },
+            (Inferred::Known(known), _) | (_, Inferred::Known(known)) => Some(Inferred::Known(known)),
+            (Inferred::Integer, Inferred::Integer) => Some(Inferred::Integer),
+            _ => Some(Inferred::Float),
+        }
+    }
+    
+    /// The inferred type, with an unsuffixed literal taking its default type
+    fn into_type(self, span: Span) -> Type {
+        match self {
+            Inferred::Known(ty) => ty,
+            Inferred::Integer => Type::stack_primitive(PrimitiveType::I32, span),
+            Inferred::Float => Type::stack_primitive(PrimitiveType::F64, span),
+            Inferred::Array { element, length } => Type::Array {
+                element_type: Box::new(element.into_type(span)),
+                size: Box::new(length_literal(length, span)),
+                memory_strategy: MemoryStrategy::Inferred,
+                span,
+                id: NodeId::DUMMY,
+            },
+        }
+    }
+}
+
+/// How many `const` items deep a constant expression is folded through
+const MAX_CONSTANT_DEPTH: usize = 32;
+
+/// Whether `expr` is a number literal, possibly negated or parenthesized
+fn is_number_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal { literal: Literal::Integer { .. } | Literal::Float { .. }, .. } => true,
+        Expr::Unary { op: UnaryOp::Negate, expr, .. } | Expr::Parenthesized { expr, .. } => is_number_literal(expr),
+        _ => false,
+    }
+}
+
 /// Type inference context for constraint solving
 #[derive(Debug, Clone)]
 pub struct InferenceContext {
@@ -224,6 +654,7 @@ impl InferenceContext {
                          kind: PrimitiveType::I32,
                          memory_strategy: *strategy,
                          span: Span::single(crate::lexer::Position::start(0)),
+                         id: NodeId::DUMMY,
                      });
                 }
                 _ => continue,
@@ -476,19 +907,39 @@ pub enum UsageContext {
 /// Type checker that performs comprehensive analysis
 pub struct TypeChecker {
     type_system: TypeSystem,
-    expression_types: HashMap<*const Expr, Type>,
+    /// Types of the checked expressions, by node
+    expression_types: SideTable<Type>,
     scope_depth: usize,
+    /// Declared return type of the function being checked, when it is a primitive
+    return_type: Option<PrimitiveType>,
+    /// Types of the locals in scope during binding inference, innermost scope last;
+    /// `None` for a local whose type is unknown
+    locals: Vec<HashMap<InternedString, Option<Type>>>,
+    /// Loops enclosing the code during binding inference, innermost last
+    loops: Vec<EnclosingLoop>,
+    /// Interner resolving names for did-you-mean suggestions
+    interner: StringInterner,
 }
 
 impl TypeChecker {
     pub fn new(symbol_table: SymbolTable) -> Self {
         Self {
             type_system: TypeSystem::new(symbol_table),
-            expression_types: HashMap::new(),
+            expression_types: SideTable::new(),
             scope_depth: 0,
+            return_type: None,
+            locals: Vec::new(),
+            loops: Vec::new(),
+            interner: StringInterner::new(),
         }
     }
     
+    /// Resolve names through the interner the module was parsed with
+    pub fn with_interner(mut self, interner: StringInterner) -> Self {
+        self.interner = interner;
+        self
+    }
+    
     /// Type check a complete module
     pub fn check_module(&mut self, module: &Module) -> TypeResult<()> {
         for item in &module.items {
@@ -509,6 +960,16 @@ impl TypeChecker {
     
     /// Type check an item
     pub fn check_item(&mut self, item: &Item) -> TypeResult<()> {
+        let error_count = self.type_system.errors().len();
+        self.check_item_literal_ranges(item);
+        self.infer_item_bindings(item);
+        if let Item::Enum { variants, .. } = item {
+            self.check_discriminants(variants);
+        }
+        if let Some(error) = self.type_system.errors().get(error_count) {
+            return Err(error.clone());
+        }
+        
         match item {
             Item::Function { body: Some(body), .. } => {
                 self.scope_depth += 1;
@@ -520,37 +981,887 @@ impl TypeChecker {
         }
     }
     
+    /// Check the integer literals of an item, nested items included, against their types
+    fn check_item_literal_ranges(&mut self, item: &Item) {
+        match item {
+            Item::Function { return_type, body: Some(body), .. } => self.check_function_literal_ranges(return_type.as_ref(), body),
+            Item::Const { type_annotation, value, .. } => self.check_literal_ranges(value, primitive_kind(Some(type_annotation))),
+            Item::Impl { items, .. } => {
+                for impl_item in items {
+                    match impl_item {
+                        ImplItem::Function { return_type, body: Some(body), .. } => {
+                            self.check_function_literal_ranges(return_type.as_ref(), body);
+                        }
+                        ImplItem::Const { type_annotation, value: Some(value), .. } => {
+                            self.check_literal_ranges(value, primitive_kind(Some(type_annotation)));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Item::Module { items: Some(items), .. } => {
+                for item in items {
+                    self.check_item_literal_ranges(item);
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    fn check_function_literal_ranges(&mut self, return_type: Option<&Type>, body: &Expr) {
+        let enclosing = std::mem::replace(&mut self.return_type, primitive_kind(return_type));
+        self.check_literal_ranges(body, self.return_type);
+        self.return_type = enclosing;
+    }
+    
+    /// Check that every integer literal in `expr` fits the type it takes
+    ///
+    /// A literal takes its suffix's type, else `expected`: the primitive type of the
+    /// annotated `let`, constant or function return its value flows into; the elements
+    /// of an array take the element type of its annotation. A literal bound by an
+    /// unannotated `let` is an `i32`. Any other literal only has to fit in 64 bits, the
+    /// widest constant codegen emits for an untyped literal.
+    fn check_literal_ranges(&mut self, expr: &Expr, expected: Option<PrimitiveType>) {
+        match expr {
+            Expr::Literal { literal, span, .. } => self.check_integer_literal(literal, false, expected, *span),
+            Expr::Unary { op: UnaryOp::Negate, expr: operand, span, .. } => match operand.as_ref() {
+                Expr::Literal { literal, .. } => self.check_integer_literal(literal, true, expected, *span),
+                operand => self.check_literal_ranges(operand, expected),
+            },
+            Expr::Unary { op: UnaryOp::Plus | UnaryOp::BitwiseNot, expr: operand, .. }
+            | Expr::Parenthesized { expr: operand, .. } => self.check_literal_ranges(operand, expected),
+            Expr::Binary { op, left, right, .. } => {
+                // Arithmetic keeps its operands' type; comparisons and shift amounts do not
+                let (left_expected, right_expected) = match op {
+                    BinaryOp::Add | BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo
+                    | BinaryOp::BitwiseAnd | BinaryOp::BitwiseOr | BinaryOp::BitwiseXor => (expected, expected),
+                    BinaryOp::LeftShift | BinaryOp::RightShift => (expected, None),
+                    _ => (None, None),
+                };
+                self.check_literal_ranges(left, left_expected);
+                self.check_literal_ranges(right, right_expected);
+            }
+            Expr::Cast { expr: operand, .. } => self.check_literal_ranges(operand, None),
+            Expr::Block { statements, trailing_expr, .. } => {
+                for stmt in statements {
+                    self.check_stmt_literal_ranges(stmt);
+                }
+                if let Some(trailing) = trailing_expr {
+                    self.check_literal_ranges(trailing, expected);
+                }
+            }
+            Expr::If { condition, then_block, else_block, .. } => {
+                self.check_literal_ranges(condition, None);
+                self.check_literal_ranges(then_block, expected);
+                if let Some(else_block) = else_block {
+                    self.check_literal_ranges(else_block, expected);
+                }
+            }
+            Expr::Match { expr: scrutinee, arms, .. } => {
+                self.check_literal_ranges(scrutinee, None);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.check_literal_ranges(guard, None);
+                    }
+                    self.check_literal_ranges(&arm.body, expected);
+                }
+            }
+            Expr::Return { value: Some(value), .. } => self.check_literal_ranges(value, self.return_type),
+            Expr::Closure { return_type, body, .. } => {
+                let enclosing = std::mem::replace(&mut self.return_type, primitive_kind(return_type.as_ref()));
+                self.check_literal_ranges(body, self.return_type);
+                self.return_type = enclosing;
+            }
+            Expr::Unary { expr: operand, .. } | Expr::Box { expr: operand, .. } | Expr::Reference { expr: operand, .. }
+            | Expr::Dereference { expr: operand, .. } | Expr::Try { expr: operand, .. } | Expr::Await { expr: operand, .. }
+            | Expr::FieldAccess { object: operand, .. } | Expr::Loop { body: operand, .. }
+            | Expr::Break { value: Some(operand), .. } => self.check_literal_ranges(operand, None),
+            Expr::Call { callee: receiver, args, .. } | Expr::MethodCall { receiver, args, .. } => {
+                self.check_literal_ranges(receiver, None);
+                for arg in args {
+                    self.check_literal_ranges(arg, None);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                self.check_literal_ranges(object, None);
+                self.check_literal_ranges(index, None);
+            }
+            Expr::Array { elements, .. } => {
+                for element in elements {
+                    self.check_literal_ranges(element, expected);
+                }
+            }
+            Expr::ArrayRepeat { value, count, .. } => {
+                self.check_literal_ranges(value, expected);
+                self.check_literal_ranges(count, None);
+            }
+            Expr::Tuple { elements, .. } => {
+                for element in elements {
+                    self.check_literal_ranges(element, None);
+                }
+            }
+            Expr::StructInit { fields, .. } => {
+                for value in fields.iter().filter_map(|field| field.value.as_ref()) {
+                    self.check_literal_ranges(value, None);
+                }
+            }
+            Expr::Range { start, end, .. } => {
+                for bound in start.iter().chain(end.iter()) {
+                    self.check_literal_ranges(bound, None);
+                }
+            }
+            Expr::While { condition, body, .. } | Expr::For { iterator: condition, body, .. } => {
+                self.check_literal_ranges(condition, None);
+                self.check_literal_ranges(body, None);
+            }
+            Expr::Identifier { .. } | Expr::Path { .. } | Expr::Continue { .. } | Expr::Macro { .. }
+            | Expr::Break { value: None, .. } | Expr::Return { value: None, .. } => {}
+        }
+    }
+    
+    fn check_stmt_literal_ranges(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let { type_annotation, initializer: Some(initializer), .. } => {
+                let expected = match type_annotation {
+                    Some(Type::Array { element_type, .. }) => primitive_kind(Some(element_type)),
+                    Some(type_annotation) => primitive_kind(Some(type_annotation)),
+                    // `let x = 5;` makes `x` an i32
+                    None if is_number_literal(initializer) => Some(PrimitiveType::I32),
+                    None => None,
+                };
+                self.check_literal_ranges(initializer, expected);
+            }
+            Stmt::Expression { expr, .. } => self.check_literal_ranges(expr, None),
+            Stmt::Assignment { target, value, .. } | Stmt::CompoundAssignment { target, value, .. } => {
+                self.check_literal_ranges(target, None);
+                self.check_literal_ranges(value, None);
+            }
+            Stmt::If { condition, then_block, else_block, .. } => {
+                self.check_literal_ranges(condition, None);
+                for stmt in then_block.iter().chain(else_block.as_deref()) {
+                    self.check_stmt_literal_ranges(stmt);
+                }
+            }
+            Stmt::While { condition: header, body, .. } | Stmt::For { iterable: header, body, .. } => {
+                self.check_literal_ranges(header, None);
+                for stmt in body {
+                    self.check_stmt_literal_ranges(stmt);
+                }
+            }
+            Stmt::Loop { body, .. } | Stmt::Block { statements: body, .. } | Stmt::Region { body, .. } => {
+                for stmt in body {
+                    self.check_stmt_literal_ranges(stmt);
+                }
+            }
+            Stmt::Match { expr, arms, .. } => {
+                self.check_literal_ranges(expr, None);
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        self.check_literal_ranges(guard, None);
+                    }
+                    self.check_literal_ranges(&arm.body, None);
+                }
+            }
+            Stmt::Return { expr: Some(expr), .. } => self.check_literal_ranges(expr, self.return_type),
+            Stmt::Break { expr: Some(expr), .. } => self.check_literal_ranges(expr, None),
+            Stmt::Item { item, .. } => self.check_item_literal_ranges(item),
+            Stmt::Let { initializer: None, .. } | Stmt::Break { expr: None, .. } | Stmt::Return { expr: None, .. }
+            | Stmt::Continue { .. } | Stmt::Empty { .. } => {}
+        }
+    }
+    
+    /// Record an error when an integer literal does not fit the type it takes
+    ///
+    /// Binary, octal and hex literals may spell any bit pattern of the type's width,
+    /// so `0xFFi8` is -1.
+    fn check_integer_literal(&mut self, literal: &Literal, negated: bool, expected: Option<PrimitiveType>, span: Span) {
+        let Literal::Integer { value, base, suffix } = literal else {
+            return;
+        };
+        let ty = suffix.or(expected.filter(|expected| expected.is_integer()));
+        let (min, max) = ty.and_then(|ty| ty.integer_range()).unwrap_or((i64::MIN as i128, u64::MAX as u128));
+        let bit_pattern_max = match ty.and_then(|ty| ty.integer_bits()) {
+            Some(bits) if *base != NumberBase::Decimal => u128::MAX >> (128 - bits),
+            _ => max,
+        };
+        
+        let fits = match literal.integer_value() {
+            Some(magnitude) if negated => magnitude <= min.unsigned_abs(),
+            Some(magnitude) => magnitude <= bit_pattern_max,
+            None => false,
+        };
+        if !fits {
+            self.type_system.add_error(TypeError::LiteralOutOfRange {
+                literal: format!("{}{}", if negated { "-" } else { "" }, value),
+                ty,
+                min,
+                max,
+                span,
+            });
+        }
+    }
+    
+    /// Resolve the types of the `let` bindings in an item, nested items included
+    ///
+    /// Each binding takes its annotation, else the type of its initializer, and the
+    /// result is recorded on the binding's symbol. An annotation that disagrees with
+    /// the initializer's type is an error.
+    fn infer_item_bindings(&mut self, item: &Item) {
+        // Nested items do not see the locals of the function they are declared in
+        let enclosing = std::mem::take(&mut self.locals);
+        match item {
+            Item::Function { params, body: Some(body), .. } => self.infer_function_bindings(params, body),
+            Item::Impl { items, .. } => {
+                for impl_item in items {
+                    if let ImplItem::Function { params, body: Some(body), .. } = impl_item {
+                        self.infer_function_bindings(params, body);
+                    }
+                }
+            }
+            Item::Module { items: Some(items), .. } => {
+                for item in items {
+                    self.infer_item_bindings(item);
+                }
+            }
+            _ => {}
+        }
+        self.locals = enclosing;
+    }
+    
+    fn infer_function_bindings(&mut self, params: &[Parameter], body: &Expr) {
+        // `break` and `continue` never reach a loop outside the function
+        let loops = std::mem::take(&mut self.loops);
+        self.locals.push(HashMap::new());
+        for param in params {
+            match &param.pattern {
+                Pattern::Identifier { name, .. } => {
+                    let ty = param.type_annotation.as_ref().map(|ty| self.fold_array_lengths(ty));
+                    self.bind_local(*name, ty);
+                }
+                pattern => self.bind_pattern_locals(pattern),
+            }
+        }
+        self.infer_expr(body);
+        self.locals.pop();
+        self.loops = loops;
+    }
+    
+    /// Bind `name` in the innermost local scope
+    fn bind_local(&mut self, name: InternedString, ty: Option<Type>) {
+        if let Some(scope) = self.locals.last_mut() {
+            scope.insert(name, ty);
+        }
+    }
+    
+    /// Bind every name `pattern` introduces, with a type local inference does not know
+    fn bind_pattern_locals(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Identifier { name, .. } => self.bind_local(*name, None),
+            Pattern::Tuple { patterns, .. } | Pattern::Array { patterns, .. } | Pattern::Or { patterns, .. }
+            | Pattern::Enum { patterns: Some(patterns), .. } => {
+                for pattern in patterns {
+                    self.bind_pattern_locals(pattern);
+                }
+            }
+            Pattern::Struct { fields, .. } => {
+                for field in fields {
+                    match &field.pattern {
+                        Some(pattern) => self.bind_pattern_locals(pattern),
+                        // Shorthand `Point { x }` binds the field name
+                        None => self.bind_local(field.name, None),
+                    }
+                }
+            }
+            Pattern::Reference { pattern, .. } => self.bind_pattern_locals(pattern),
+            Pattern::Wildcard { .. } | Pattern::Literal { .. } | Pattern::Enum { patterns: None, .. } | Pattern::Range { .. } => {}
+        }
+    }
+    
+    /// Type of the local or constant `name`, if known
+    fn local_type(&self, name: InternedString) -> Option<Type> {
+        match self.locals.iter().rev().find_map(|scope| scope.get(&name)) {
+            Some(ty) => ty.clone(),
+            None => match self.type_system.symbol_table.lookup_symbol(&name).map(|symbol| &symbol.kind) {
+                Some(SymbolKind::Constant { type_info, .. }) => Some(type_info.clone()),
+                _ => None,
+            },
+        }
+    }
+    
+    /// Value of the integer constant expression `expr`, with `const` items folded in
+    fn constant_value(&self, expr: &Expr) -> Option<u128> {
+        self.fold_constant(expr, MAX_CONSTANT_DEPTH)
+    }
+    
+    fn fold_constant(&self, expr: &Expr, depth: usize) -> Option<u128> {
+        expr.fold_integer(&|name| {
+            // A local shadows the constant of the same name, and `const A: usize = A;` never folds
+            if depth == 0 || self.locals.iter().any(|scope| scope.contains_key(&name)) {
+                return None;
+            }
+            match self.type_system.symbol_table.lookup_symbol(&name).map(|symbol| &symbol.kind) {
+                Some(SymbolKind::Constant { value: Some(value), .. }) => self.fold_constant(value, depth - 1),
+                _ => None,
+            }
+        })
+    }
+    
+    /// Record an error for each variant whose discriminant an earlier variant already has
+    fn check_discriminants(&mut self, variants: &[EnumVariant]) {
+        let discriminants = EnumVariant::discriminants(variants, &|name| {
+            match self.type_system.symbol_table.lookup_symbol(&name).map(|symbol| &symbol.kind) {
+                Some(SymbolKind::Constant { value: Some(value), .. }) => self.fold_constant(value, MAX_CONSTANT_DEPTH - 1),
+                _ => None,
+            }
+        });
+        let mut seen: HashMap<u128, Span> = HashMap::new();
+        for (variant, discriminant) in variants.iter().zip(discriminants) {
+            let Some(value) = discriminant else { continue };
+            if let Some(&previous_span) = seen.get(&value) {
+                self.type_system.add_error(TypeError::DuplicateDiscriminant { value, span: variant.span, previous_span });
+            } else {
+                seen.insert(value, variant.span);
+            }
+        }
+    }
+    
+    /// Length of an array type or repeat expression, recording an error when it is not constant
+    fn array_length(&mut self, size: &Expr) -> Option<u128> {
+        let length = self.constant_value(size);
+        if length.is_none() {
+            self.type_system.add_error(TypeError::NonConstantArrayLength { span: size.span() });
+        }
+        length
+    }
+    
+    /// `ty` with its array lengths folded to literals, the form codegen reads them in
+    fn fold_array_lengths(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Array { element_type, size, memory_strategy, span, .. } => Type::Array {
+                element_type: Box::new(self.fold_array_lengths(element_type)),
+                size: Box::new(match self.constant_value(size) {
+                    Some(length) => length_literal(length, size.span()),
+                    None => size.as_ref().clone(),
+                }),
+                memory_strategy: *memory_strategy,
+                span: *span,
+                id: NodeId::DUMMY,
+            },
+            ty => ty.clone(),
+        }
+    }
+    
+    /// Return type of calling `name`, when it is a local function pointer or a non-generic function
+    fn call_return_type(&self, name: InternedString, span: Span) -> Option<Type> {
+        if let Some(ty) = self.locals.iter().rev().find_map(|scope| scope.get(&name)) {
+            return match ty {
+                Some(Type::Function { return_type, .. }) => Some(return_type.as_ref().clone()),
+                _ => None,
+            };
+        }
+        match self.type_system.symbol_table.lookup_symbol(&name).map(|symbol| &symbol.kind) {
+            Some(SymbolKind::Function { return_type, generics, .. }) if generics.is_empty() => {
+                Some(return_type.clone().unwrap_or_else(|| Type::stack_primitive(PrimitiveType::Unit, span)))
+            }
+            None if self.interner.resolve(name).as_deref().and_then(Intrinsic::named).is_some() => {
+                Some(Type::stack_primitive(PrimitiveType::Unit, span))
+            }
+            _ => None,
+        }
+    }
+    
+    /// Record an error when the call of `name` at `span` does not fit the callee's signature
+    ///
+    /// The callee is resolved from the scope the call is made in: a local function
+    /// pointer, or a function or `extern` function. The argument count must match, and
+    /// unless the function is generic each argument whose type is known must be
+    /// accepted by its parameter, integers widening as they do in `let` bindings. A
+    /// name that resolves to nothing is checked as the intrinsic of that name, if any,
+    /// and is otherwise an unknown function unless it is an enum variant.
+    fn check_call(&mut self, name: InternedString, name_span: Span, args: &[Expr], arg_types: &[Option<Inferred>], span: Span) {
+        let symbol_table = &self.type_system.symbol_table;
+        // Calls the symbol table builder did not walk cannot be resolved
+        let Some(scope_id) = symbol_table.call_scope(name_span.start) else { return };
+        let symbol = symbol_table.lookup_symbol_from(scope_id, &name);
+        let local = self.locals.iter().rev().find_map(|scope| scope.get(&name));
+        
+        // Each parameter's type, if checkable, with where the parameter is declared, if anywhere
+        let (params, definition_span): (Vec<_>, Span) = match (local, symbol) {
+            (Some(Some(Type::Function { params, is_variadic: false, .. })), Some(symbol)) => {
+                (params.iter().map(|param| (Some(param.clone()), None)).collect(), symbol.span)
+            }
+            (Some(_), _) => return,
+            (None, Some(Symbol { kind: SymbolKind::Function { params, generics, .. }, span, .. })) => {
+                // Generic parameter types are not substituted, so only the count is checked
+                let types = params.iter().map(|param| (param.type_annotation.clone().filter(|_| generics.is_empty()), Some(param.span)));
+                (types.collect(), *span)
+            }
+            (None, Some(_)) => return,
+            (None, None) if symbol_table.is_enum_variant(name) => return,
+            (None, None) => match self.interner.resolve(name).as_deref().and_then(Intrinsic::named) {
+                // Intrinsics are declared nowhere, so the call stands in for their definition
+                Some(intrinsic) => {
+                    let types = intrinsic.parameters().iter().map(|kind| (Some(Type::stack_primitive(*kind, name_span)), None));
+                    (types.collect(), name_span)
+                }
+                None => {
+                    let suggestions = self.similar_callables(name, scope_id);
+                    self.type_system.add_error(TypeError::UnknownFunction { name, span: name_span, suggestions });
+                    return;
+                }
+            },
+        };
+        
+        if params.len() != args.len() {
+            self.type_system.add_error(TypeError::ArgumentCountMismatch {
+                callee: name,
+                expected: params.len(),
+                found: args.len(),
+                span,
+                definition_span,
+            });
+            return;
+        }
+        for (index, (((param, parameter_span), arg), found)) in params.iter().zip(args).zip(arg_types).enumerate() {
+            let (Some(expected), Some(found)) = (param, found) else { continue };
+            if !accepts(expected, found) {
+                self.type_system.add_error(TypeError::ArgumentTypeMismatch {
+                    callee: name,
+                    index,
+                    expected: self.fold_array_lengths(expected),
+                    found: found.clone().into_type(arg.span()),
+                    argument_span: arg.span(),
+                    call_span: span,
+                    parameter_span: *parameter_span,
+                });
+            }
+        }
+    }
+    
+    /// Names of the functions and variables visible from `scope_id` that `name` may be a misspelling of
+    fn similar_callables(&self, name: InternedString, scope_id: ScopeId) -> Vec<String> {
+        let interner = &self.interner;
+        let mut candidates: Vec<String> = self.type_system.symbol_table.visible_symbols(scope_id).into_iter()
+            .filter(|symbol| matches!(symbol.kind, SymbolKind::Function { .. } | SymbolKind::Variable { .. }))
+            .filter_map(|symbol| interner.resolve(symbol.name))
+            .chain(Intrinsic::ALL.iter().map(|intrinsic| intrinsic.name().to_string()))
+            .collect();
+        candidates.sort();
+        let Some(name) = interner.resolve(name) else { return Vec::new() };
+        let candidates: Vec<&str> = candidates.iter().map(String::as_str).collect();
+        suggest_similar_identifiers(&name, &candidates)
+    }
+    
+    /// Infer the type of `expr`, resolving the bindings of the `let` statements inside it
+    ///
+    /// Types follow from literal suffixes, annotations, function signatures and
+    /// operators, through parentheses, blocks and `if` branches. `None` means local
+    /// inference cannot tell.
+    fn infer_expr(&mut self, expr: &Expr) -> Option<Inferred> {
+        let span = expr.span();
+        let known = |kind| Some(Inferred::Known(Type::stack_primitive(kind, span)));
+        match expr {
+            Expr::Literal { literal, .. } => match literal {
+                Literal::Integer { suffix: Some(kind), .. } | Literal::Float { suffix: Some(kind), .. } => known(*kind),
+                Literal::Integer { suffix: None, .. } => Some(Inferred::Integer),
+                Literal::Float { suffix: None, .. } => Some(Inferred::Float),
+                Literal::Bool(_) => known(PrimitiveType::Bool),
+                Literal::Char(_) => known(PrimitiveType::Char),
+                Literal::String { .. } => known(PrimitiveType::Str),
+                Literal::Null => None,
+            },
+            Expr::Identifier { name, .. } => self.local_type(*name).map(Inferred::Known),
+            Expr::Parenthesized { expr: operand, .. } => self.infer_expr(operand),
+            Expr::Block { statements, trailing_expr, .. } => {
+                self.locals.push(HashMap::new());
+                for stmt in statements {
+                    self.infer_stmt(stmt);
+                }
+                let ty = trailing_expr.as_ref().and_then(|trailing| self.infer_expr(trailing));
+                self.locals.pop();
+                ty
+            }
+            Expr::Unary { op, expr: operand, .. } => {
+                let operand = self.infer_value(operand, "an operand");
+                match op {
+                    UnaryOp::Not => known(PrimitiveType::Bool),
+                    UnaryOp::Negate | UnaryOp::Plus | UnaryOp::BitwiseNot => operand,
+                    UnaryOp::Dereference | UnaryOp::AddressOf | UnaryOp::MutableRef => None,
+                }
+            }
+            Expr::Binary { op, left, right, .. } => {
+                let (left, right) = (self.infer_value(left, "an operand"), self.infer_value(right, "an operand"));
+                match op {
+                    BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater
+                    | BinaryOp::GreaterEqual | BinaryOp::LogicalAnd | BinaryOp::LogicalOr => known(PrimitiveType::Bool),
+                    // The shift amount does not change the shifted value's type
+                    BinaryOp::LeftShift | BinaryOp::RightShift => left,
+                    BinaryOp::Assign => None,
+                    _ => Inferred::unify(left?, right?),
+                }
+            }
+            Expr::Call { callee, args, .. } => {
+                let arg_types: Vec<_> = args.iter().map(|arg| self.infer_expr(arg)).collect();
+                match callee.as_ref() {
+                    Expr::Identifier { name, span: name_span, .. } => {
+                        self.check_call(*name, *name_span, args, &arg_types, span);
+                        self.call_return_type(*name, span).map(Inferred::Known)
+                    }
+                    callee => {
+                        self.infer_expr(callee);
+                        None
+                    }
+                }
+            }
+            Expr::Cast { expr: operand, target_type, .. } => {
+                self.infer_value(operand, "a cast");
+                Some(Inferred::Known(target_type.clone()))
+            }
+            Expr::If { condition, then_block, else_block, .. } => {
+                self.infer_value(condition, "a condition");
+                let then_type = self.infer_expr(then_block);
+                // Without an `else` the value is unit, whatever the branch evaluates to
+                let else_type = else_block.as_ref().map(|else_block| self.infer_expr(else_block))?;
+                Inferred::unify(then_type?, else_type?)
+            }
+            Expr::Closure { params, body, .. } => {
+                self.infer_function_bindings(params, body);
+                None
+            }
+            Expr::For { pattern, iterator, body, .. } => {
+                self.infer_expr(iterator);
+                self.locals.push(HashMap::new());
+                self.bind_pattern_locals(pattern);
+                self.infer_loop(None, "for", span, |checker| { checker.infer_expr(body); });
+                self.locals.pop();
+                None
+            }
+            Expr::Loop { label, body, .. } => self.infer_loop(*label, "loop", span, |checker| { checker.infer_expr(body); }),
+            Expr::Break { label, value, .. } => {
+                self.infer_break(*label, value.as_deref(), span);
+                None
+            }
+            Expr::Match { expr: scrutinee, arms, .. } => {
+                let matched = self.infer_expr(scrutinee);
+                for arm in arms {
+                    self.check_pattern(&arm.pattern, matched.as_ref(), scrutinee.span());
+                    self.infer_arm(&arm.pattern, arm.guard.as_ref(), &arm.body);
+                }
+                None
+            }
+            Expr::MethodCall { receiver, args, .. } => {
+                self.infer_expr(receiver);
+                for arg in args {
+                    self.infer_expr(arg);
+                }
+                None
+            }
+            Expr::Box { expr: operand, .. } | Expr::Reference { expr: operand, .. } | Expr::Dereference { expr: operand, .. }
+            | Expr::Try { expr: operand, .. } | Expr::Await { expr: operand, .. } | Expr::FieldAccess { object: operand, .. }
+            | Expr::Return { value: Some(operand), .. } => {
+                self.infer_expr(operand);
+                None
+            }
+            Expr::Index { object, index, .. } => {
+                let object = self.infer_value(object, "an indexed value");
+                self.infer_value(index, "an index");
+                let (element, length) = match object? {
+                    Inferred::Array { element, length } => (*element, Some(length)),
+                    Inferred::Known(Type::Array { element_type, size, .. }) => (Inferred::Known(*element_type), self.constant_value(&size)),
+                    _ => return None,
+                };
+                if let (Some(length), Some(constant)) = (length, self.constant_value(index)) {
+                    if constant >= length {
+                        self.type_system.add_error(TypeError::IndexOutOfBounds { index: constant, length, span: index.span() });
+                    }
+                }
+                Some(element)
+            }
+            Expr::While { condition, body, .. } => {
+                self.infer_value(condition, "a condition");
+                self.infer_loop(None, "while", span, |checker| { checker.infer_expr(body); });
+                None
+            }
+            Expr::Array { elements, .. } => {
+                // Every element is inferred, for the bindings inside it, before they are combined
+                let types: Vec<_> = elements.iter().map(|element| self.infer_value(element, "an array element")).collect();
+                let mut types = types.into_iter();
+                let first = types.next()??;
+                let element = types.try_fold(first, |element, ty| element.unify(ty?))?;
+                Some(Inferred::Array { element: Box::new(element), length: elements.len() as u128 })
+            }
+            Expr::ArrayRepeat { value, count, .. } => {
+                let element = self.infer_value(value, "an array element");
+                let length = self.array_length(count);
+                Some(Inferred::Array { element: Box::new(element?), length: length? })
+            }
+            Expr::Tuple { elements, .. } => {
+                for element in elements {
+                    self.infer_expr(element);
+                }
+                None
+            }
+            Expr::StructInit { fields, .. } => {
+                for value in fields.iter().filter_map(|field| field.value.as_ref()) {
+                    self.infer_value(value, "a field");
+                }
+                None
+            }
+            Expr::Range { start, end, .. } => {
+                for bound in start.iter().chain(end.iter()) {
+                    self.infer_expr(bound);
+                }
+                None
+            }
+            Expr::Path { .. } | Expr::Continue { .. } | Expr::Macro { .. } | Expr::Return { value: None, .. } => None,
+        }
+    }
+    
+    /// Infer a loop's body with the loop enclosing it, giving the type of its first `break` value
+    fn infer_loop(&mut self, label: Option<InternedString>, keyword: &'static str, span: Span, body: impl FnOnce(&mut Self)) -> Option<Inferred> {
+        self.loops.push(EnclosingLoop { label, keyword, span, value: None });
+        body(self);
+        self.loops.pop().and_then(|enclosing| enclosing.value)
+    }
+    
+    /// Infer a `break` value, recording an error when the loop it leaves cannot produce one
+    fn infer_break(&mut self, label: Option<InternedString>, value: Option<&Expr>, span: Span) {
+        let Some(value) = value else { return };
+        let found = self.infer_expr(value);
+        let target = match label {
+            Some(label) => self.loops.iter_mut().rev().find(|enclosing| enclosing.label == Some(label)),
+            None => self.loops.last_mut(),
+        };
+        let Some(target) = target else { return };
+        if target.keyword != "loop" {
+            let (keyword, loop_span) = (target.keyword, target.span);
+            self.type_system.add_error(TypeError::BreakValueInLoop { keyword, span, loop_span });
+        } else if target.value.is_none() {
+            target.value = found;
+        }
+    }
+    
+    /// Infer the type of `expr` where `usage` needs its value, recording an error when it is unit
+    fn infer_value(&mut self, expr: &Expr, usage: &'static str) -> Option<Inferred> {
+        let found = self.infer_expr(expr);
+        if let Some(Inferred::Known(ty)) = &found {
+            if is_unit(ty) {
+                self.type_system.add_error(TypeError::UnitValue { usage, span: expr.span() });
+            }
+        }
+        found
+    }
+    
+    /// Check that `pattern` can match a value of the scrutinee's type, where that is known
+    fn check_pattern(&mut self, pattern: &Pattern, matched: Option<&Inferred>, scrutinee_span: Span) {
+        let Some(Inferred::Known(expected)) = matched else {
+            return;
+        };
+        match pattern {
+            Pattern::Or { patterns, .. } => {
+                for pattern in patterns {
+                    self.check_pattern(pattern, matched, scrutinee_span);
+                }
+            }
+            Pattern::Enum { path, span, .. } | Pattern::Struct { path, span, .. } if !self.path_pattern_matches(path, expected) => {
+                self.type_system.add_error(TypeError::PatternMismatch {
+                    path: path.clone(),
+                    expected: expected.clone(),
+                    span: *span,
+                    scrutinee_span,
+                });
+            }
+            _ => {}
+        }
+    }
+    
+    /// Whether the enum or struct pattern `path` can match a value of type `expected`
+    ///
+    /// `Enum::Variant` must name the scrutinee's enum and one of its variants, and a bare
+    /// `Variant` one of its variants. A value of a primitive or array type matches no
+    /// pattern naming a variant or a type.
+    fn path_pattern_matches(&self, path: &[InternedString], expected: &Type) -> bool {
+        let Some(&last) = path.last() else {
+            return true;
+        };
+        let symbol_table = &self.type_system.symbol_table;
+        let definition = |name: InternedString| match symbol_table.lookup_symbol(&name).map(|symbol| &symbol.kind) {
+            Some(SymbolKind::Type { definition }) => Some(definition),
+            _ => None,
+        };
+        match expected {
+            Type::Path { segments, .. } => {
+                let Some(&type_name) = segments.last() else {
+                    return true;
+                };
+                match definition(type_name) {
+                    Some(TypeDefinition::Enum { variants, .. }) => {
+                        let owner = path.len().checked_sub(2).map(|index| path[index]);
+                        owner.is_none_or(|owner| owner == type_name) && variants.iter().any(|variant| variant.name == last)
+                    }
+                    Some(TypeDefinition::Struct { .. }) => {
+                        path.len() > 1 || last == type_name || !matches!(definition(last), Some(TypeDefinition::Struct { .. }))
+                    }
+                    _ => true,
+                }
+            }
+            Type::Primitive { .. } | Type::Array { .. } => {
+                path.len() == 1 && !symbol_table.is_enum_variant(last) && definition(last).is_none()
+            }
+            _ => true,
+        }
+    }
+    
+    /// Resolve the bindings in a match arm, with the names its pattern binds in scope
+    fn infer_arm(&mut self, pattern: &Pattern, guard: Option<&Expr>, body: &Expr) {
+        self.locals.push(HashMap::new());
+        self.bind_pattern_locals(pattern);
+        if let Some(guard) = guard {
+            self.infer_expr(guard);
+        }
+        self.infer_expr(body);
+        self.locals.pop();
+    }
+    
+    /// Resolve the bindings of `statements` in a scope of their own
+    fn infer_block(&mut self, statements: &[Stmt]) {
+        self.locals.push(HashMap::new());
+        for stmt in statements {
+            self.infer_stmt(stmt);
+        }
+        self.locals.pop();
+    }
+    
+    fn infer_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Let { pattern, type_annotation, initializer, .. } => {
+                // The initializer still sees the binding the pattern shadows
+                let found = initializer.as_ref().and_then(|initializer| self.infer_expr(initializer));
+                if let Some(Type::Array { size, .. }) = type_annotation {
+                    self.array_length(size);
+                }
+                if let (Some(annotated), Some(found), Some(initializer)) = (type_annotation, &found, initializer) {
+                    self.check_annotation(annotated, found, initializer.span());
+                }
+                let ty = match type_annotation {
+                    Some(type_annotation) => Some(self.fold_array_lengths(type_annotation)),
+                    None => found.map(|found| found.into_type(pattern.span())),
+                };
+                match pattern {
+                    Pattern::Identifier { name, span, .. } => {
+                        if let Some(ty) = &ty {
+                            self.type_system.symbol_table.set_binding_type(span.start, ty.clone());
+                        }
+                        self.bind_local(*name, ty);
+                    }
+                    pattern => self.bind_pattern_locals(pattern),
+                }
+            }
+            Stmt::Expression { expr, .. } | Stmt::Return { expr: Some(expr), .. } => {
+                self.infer_expr(expr);
+            }
+            Stmt::Break { label, expr, span, .. } => self.infer_break(*label, expr.as_ref(), *span),
+            Stmt::Assignment { target, value, .. } | Stmt::CompoundAssignment { target, value, .. } => {
+                self.infer_expr(target);
+                self.infer_value(value, "an assignment");
+            }
+            Stmt::If { condition, then_block, else_block, .. } => {
+                self.infer_value(condition, "a condition");
+                self.infer_block(then_block);
+                if let Some(else_block) = else_block {
+                    self.infer_stmt(else_block);
+                }
+            }
+            Stmt::While { condition, body, span, .. } => {
+                self.infer_value(condition, "a condition");
+                self.infer_loop(None, "while", *span, |checker| checker.infer_block(body));
+            }
+            Stmt::For { pattern, iterable, body, span, .. } => {
+                self.infer_expr(iterable);
+                self.locals.push(HashMap::new());
+                self.bind_pattern_locals(pattern);
+                self.infer_loop(None, "for", *span, |checker| checker.infer_block(body));
+                self.locals.pop();
+            }
+            Stmt::Loop { label, body, span, .. } => {
+                self.infer_loop(*label, "loop", *span, |checker| checker.infer_block(body));
+            }
+            Stmt::Block { statements: body, .. } | Stmt::Region { body, .. } => self.infer_block(body),
+            Stmt::Match { expr, arms, .. } => {
+                let matched = self.infer_expr(expr);
+                for arm in arms {
+                    self.check_pattern(&arm.pattern, matched.as_ref(), expr.span());
+                    self.infer_arm(&arm.pattern, arm.guard.as_ref(), &arm.body);
+                }
+            }
+            Stmt::Item { item, .. } => self.infer_item_bindings(item),
+            Stmt::Return { expr: None, .. } | Stmt::Continue { .. } | Stmt::Empty { .. } => {}
+        }
+    }
+    
+    /// Record an error when an annotation disagrees with its initializer's type or array length
+    fn check_annotation(&mut self, annotated: &Type, found: &Inferred, initializer_span: Span) {
+        if let (Type::Array { size, .. }, Inferred::Array { length, .. }) = (annotated, found) {
+            match self.constant_value(size) {
+                Some(expected) if expected != *length => {
+                    self.type_system.add_error(TypeError::ArrayLengthMismatch {
+                        expected,
+                        found: *length,
+                        annotation_span: annotated.span(),
+                        initializer_span,
+                    });
+                    return;
+                }
+                _ => {}
+            }
+        }
+        if !accepts(annotated, found) {
+            self.type_system.add_error(TypeError::AnnotationMismatch {
+                annotated: self.fold_array_lengths(annotated),
+                found: found.clone().into_type(initializer_span),
+                annotation_span: annotated.span(),
+                initializer_span,
+            });
+        }
+    }
+    
     /// Type check an expression with comprehensive analysis
     pub fn check_expr(&mut self, expr: &Expr) -> TypeResult<Type> {
         let result_type = match expr {
-            Expr::Literal { literal, span } => {
+            Expr::Literal { literal, span, .. } => {
                 self.check_literal(literal, *span)
             }
             
-            Expr::Identifier { name, span } => {
+            Expr::Identifier { name, span, .. } => {
                 self.check_identifier(*name, *span)
             }
             
-            Expr::Binary { left, right, op, span } => {
+            Expr::Binary { left, right, op, span, .. } => {
                 self.check_binary_expr(left, right, *op, *span)
             }
             
-            Expr::Unary { expr: inner_expr, op, span } => {
+            Expr::Unary { expr: inner_expr, op, span, .. } => {
                 self.check_unary_expr(inner_expr, *op, *span)
             }
             
-            Expr::Call { callee, args, span } => {
+            Expr::Call { callee, args, span, .. } => {
                 self.check_call_expr(callee, args, *span)
             }
             
-            Expr::Reference { expr: inner_expr, is_mutable, span } => {
+            Expr::Reference { expr: inner_expr, is_mutable, span, .. } => {
                 self.check_reference_expr(inner_expr, *is_mutable, *span)
             }
             
-            Expr::Dereference { expr: inner_expr, span } => {
+            Expr::Dereference { expr: inner_expr, span, .. } => {
                 self.check_dereference_expr(inner_expr, *span)
             }
             
+            Expr::Cast { expr: inner_expr, target_type, .. } => {
+                // Codegen decides which conversions exist; the cast has its target type
+                self.check_expr(inner_expr)?;
+                Ok(target_type.clone())
+            }
+            
             _ => {
                 // TODO: Implement remaining expressions
                 Ok(Type::stack_primitive(PrimitiveType::Unit, expr.span()))
@@ -559,7 +1870,7 @@ impl TypeChecker {
         
         // Cache the type for this expression
         if let Ok(ref ty) = result_type {
-            self.expression_types.insert(expr as *const Expr, ty.clone());
+            self.expression_types.insert(expr.id(), ty.clone());
         }
         
         result_type
@@ -568,8 +1879,8 @@ impl TypeChecker {
     /// Check literal expressions
     fn check_literal(&mut self, literal: &Literal, span: Span) -> TypeResult<Type> {
         let ty = match literal {
-            Literal::Integer { .. } => Type::stack_primitive(PrimitiveType::I32, span),
-            Literal::Float { .. } => Type::stack_primitive(PrimitiveType::F64, span),
+            Literal::Integer { suffix, .. } => Type::stack_primitive(suffix.unwrap_or(PrimitiveType::I32), span),
+            Literal::Float { suffix, .. } => Type::stack_primitive(suffix.unwrap_or(PrimitiveType::F64), span),
             Literal::Bool(_) => Type::stack_primitive(PrimitiveType::Bool, span),
             Literal::Char(_) => Type::stack_primitive(PrimitiveType::Char, span),
             Literal::String { .. } => Type::stack_primitive(PrimitiveType::Str, span),
@@ -578,6 +1889,7 @@ impl TypeChecker {
                 target_type: Box::new(Type::stack_primitive(PrimitiveType::Unit, span)),
                 memory_strategy: MemoryStrategy::Manual,
                 span,
+                id: NodeId::DUMMY,
             },
         };
         Ok(ty)
@@ -759,6 +2071,7 @@ impl TypeChecker {
                     target_type: Box::new(Type::stack_primitive(PrimitiveType::Unit, span)),
                     memory_strategy: MemoryStrategy::Manual,
                     span,
+                    id: NodeId::DUMMY,
                 },
                 actual: expr_type,
                 span,
@@ -769,11 +2082,21 @@ impl TypeChecker {
     
     /// Get type information for expressions (for IDE/LSP integration)
     pub fn get_expression_type(&self, expr: &Expr) -> Option<&Type> {
-        self.expression_types.get(&(expr as *const Expr))
+        self.expression_types.get(expr.id())
+    }
+    
+    /// Take the types of the checked expressions, by node
+    pub fn take_expression_types(&mut self) -> SideTable<Type> {
+        std::mem::take(&mut self.expression_types)
     }
     
     /// Get all errors from type checking
     pub fn get_all_errors(&self) -> &[TypeError] {
         self.type_system.errors()
     }
+    
+    /// Symbol table with the types resolved for `let` bindings recorded
+    pub fn into_symbol_table(self) -> SymbolTable {
+        self.type_system.symbol_table
+    }
 } 
\ No newline at end of file
@@ -2,6 +2,9 @@
 //!
 //! This module provides comprehensive AST traversal patterns essential for semantic analysis.
 //! Following the roadmap Step 8.5 requirements for world-class visitor infrastructure.
+//!
+//! Passes that only care about a few node kinds implement [`Visitor`] (or [`VisitorMut`]
+//! to rewrite the tree) and inherit a complete walk of every other node.
 
 use crate::ast::*;
 use std::collections::HashMap;
@@ -550,6 +553,759 @@ where
     }
 }
 
+/// Read-only AST visitor whose methods walk into every node by default
+///
+/// Each `visit_*` method defaults to the matching `walk_*` function, which visits the
+/// node's children and nothing else. A pass overrides the nodes it cares about and
+/// calls the `walk_*` function from its override to keep descending.
+///
+/// Children are visited in source order, so the spans of visited siblings never go
+/// backwards and a parent is visited before anything inside it: `let x: T = e;` visits
+/// the pattern, then `T`, then `e`. Attributes, operators and names are not nodes.
+pub trait Visitor<'ast> {
+    fn visit_module(&mut self, module: &'ast Module) {
+        walk_module(self, module)
+    }
+    
+    fn visit_item(&mut self, item: &'ast Item) {
+        walk_item(self, item)
+    }
+    
+    fn visit_impl_item(&mut self, item: &'ast ImplItem) {
+        walk_impl_item(self, item)
+    }
+    
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        walk_stmt(self, stmt)
+    }
+    
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        walk_expr(self, expr)
+    }
+    
+    fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+        walk_pattern(self, pattern)
+    }
+    
+    fn visit_type(&mut self, ty: &'ast Type) {
+        walk_type(self, ty)
+    }
+}
+
+/// Visit the items of a module
+pub fn walk_module<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, module: &'ast Module) {
+    for item in &module.items {
+        visitor.visit_item(item);
+    }
+}
+
+fn walk_generics<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, generics: &'ast [GenericParam]) {
+    for generic in generics {
+        for bound in &generic.bounds {
+            visitor.visit_type(bound);
+        }
+        if let Some(default) = &generic.default {
+            visitor.visit_type(default);
+        }
+    }
+}
+
+fn walk_parameters<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, params: &'ast [Parameter]) {
+    for param in params {
+        visitor.visit_pattern(&param.pattern);
+        if let Some(type_annotation) = &param.type_annotation {
+            visitor.visit_type(type_annotation);
+        }
+    }
+}
+
+fn walk_struct_fields<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, fields: &'ast StructFields) {
+    match fields {
+        StructFields::Named(fields) => {
+            for field in fields {
+                visitor.visit_type(&field.field_type);
+            }
+        }
+        StructFields::Tuple(types) => {
+            for ty in types {
+                visitor.visit_type(ty);
+            }
+        }
+        StructFields::Unit => {}
+    }
+}
+
+fn walk_match_arms<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, arms: &'ast [MatchArm]) {
+    for arm in arms {
+        visitor.visit_pattern(&arm.pattern);
+        if let Some(guard) = &arm.guard {
+            visitor.visit_expr(guard);
+        }
+        visitor.visit_expr(&arm.body);
+    }
+}
+
+/// Visit an item's generics, signature types, body and nested items
+pub fn walk_item<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, item: &'ast Item) {
+    match item {
+        Item::Function { generics, params, return_type, body, .. } => {
+            walk_generics(visitor, generics);
+            walk_parameters(visitor, params);
+            if let Some(return_type) = return_type {
+                visitor.visit_type(return_type);
+            }
+            if let Some(body) = body {
+                visitor.visit_expr(body);
+            }
+        }
+        Item::Struct { generics, fields, .. } => {
+            walk_generics(visitor, generics);
+            walk_struct_fields(visitor, fields);
+        }
+        Item::Enum { generics, variants, .. } => {
+            walk_generics(visitor, generics);
+            for variant in variants {
+                walk_struct_fields(visitor, &variant.fields);
+                if let Some(discriminant) = &variant.discriminant {
+                    visitor.visit_expr(discriminant);
+                }
+            }
+        }
+        Item::TypeAlias { generics, target_type, .. } => {
+            walk_generics(visitor, generics);
+            visitor.visit_type(target_type);
+        }
+        Item::Const { type_annotation, value, .. } => {
+            visitor.visit_type(type_annotation);
+            visitor.visit_expr(value);
+        }
+        Item::Module { items, .. } => {
+            for item in items.iter().flatten() {
+                visitor.visit_item(item);
+            }
+        }
+        Item::Impl { generics, target_type, trait_ref, items, .. } => {
+            walk_generics(visitor, generics);
+            // `impl Trait for Type` names the trait first
+            if let Some(trait_ref) = trait_ref {
+                visitor.visit_type(trait_ref);
+            }
+            visitor.visit_type(target_type);
+            for item in items {
+                visitor.visit_impl_item(item);
+            }
+        }
+        Item::Use { .. } => {}
+    }
+}
+
+/// Visit an impl item like the item it corresponds to
+pub fn walk_impl_item<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, item: &'ast ImplItem) {
+    match item {
+        ImplItem::Function { generics, params, return_type, body, .. } => {
+            walk_generics(visitor, generics);
+            walk_parameters(visitor, params);
+            if let Some(return_type) = return_type {
+                visitor.visit_type(return_type);
+            }
+            if let Some(body) = body {
+                visitor.visit_expr(body);
+            }
+        }
+        ImplItem::Type { generics, target_type, .. } => {
+            walk_generics(visitor, generics);
+            visitor.visit_type(target_type);
+        }
+        ImplItem::Const { type_annotation, value, .. } => {
+            visitor.visit_type(type_annotation);
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+    }
+}
+
+/// Visit a statement's patterns, types, expressions and nested statements
+pub fn walk_stmt<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, stmt: &'ast Stmt) {
+    match stmt {
+        Stmt::Expression { expr, .. } => visitor.visit_expr(expr),
+        Stmt::Let { pattern, type_annotation, initializer, .. } => {
+            visitor.visit_pattern(pattern);
+            if let Some(type_annotation) = type_annotation {
+                visitor.visit_type(type_annotation);
+            }
+            if let Some(initializer) = initializer {
+                visitor.visit_expr(initializer);
+            }
+        }
+        Stmt::Assignment { target, value, .. } | Stmt::CompoundAssignment { target, value, .. } => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(value);
+        }
+        Stmt::If { condition, then_block, else_block, .. } => {
+            visitor.visit_expr(condition);
+            for stmt in then_block {
+                visitor.visit_stmt(stmt);
+            }
+            if let Some(else_block) = else_block {
+                visitor.visit_stmt(else_block);
+            }
+        }
+        Stmt::While { condition, body, .. } => {
+            visitor.visit_expr(condition);
+            for stmt in body {
+                visitor.visit_stmt(stmt);
+            }
+        }
+        Stmt::For { pattern, iterable, body, .. } => {
+            visitor.visit_pattern(pattern);
+            visitor.visit_expr(iterable);
+            for stmt in body {
+                visitor.visit_stmt(stmt);
+            }
+        }
+        Stmt::Loop { body, .. } | Stmt::Block { statements: body, .. } | Stmt::Region { body, .. } => {
+            for stmt in body {
+                visitor.visit_stmt(stmt);
+            }
+        }
+        Stmt::Match { expr, arms, .. } => {
+            visitor.visit_expr(expr);
+            walk_match_arms(visitor, arms);
+        }
+        Stmt::Break { expr, .. } | Stmt::Return { expr, .. } => {
+            if let Some(expr) = expr {
+                visitor.visit_expr(expr);
+            }
+        }
+        Stmt::Item { item, .. } => visitor.visit_item(item),
+        Stmt::Continue { .. } | Stmt::Empty { .. } => {}
+    }
+}
+
+/// Visit an expression's operands, in source order
+pub fn walk_expr<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, expr: &'ast Expr) {
+    match expr {
+        Expr::Literal { .. } | Expr::Identifier { .. } | Expr::Path { .. } | Expr::Continue { .. }
+        | Expr::Macro { .. } => {}
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Unary { expr, .. } | Expr::Parenthesized { expr, .. } | Expr::Box { expr, .. }
+        | Expr::Reference { expr, .. } | Expr::Dereference { expr, .. } | Expr::Try { expr, .. }
+        | Expr::Await { expr, .. } => visitor.visit_expr(expr),
+        Expr::Call { callee: receiver, args, .. } | Expr::MethodCall { receiver, args, .. } => {
+            visitor.visit_expr(receiver);
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::FieldAccess { object, .. } => visitor.visit_expr(object),
+        Expr::Index { object, index, .. } => {
+            visitor.visit_expr(object);
+            visitor.visit_expr(index);
+        }
+        Expr::Cast { expr, target_type, .. } => {
+            visitor.visit_expr(expr);
+            visitor.visit_type(target_type);
+        }
+        Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        Expr::ArrayRepeat { value, count, .. } => {
+            visitor.visit_expr(value);
+            visitor.visit_expr(count);
+        }
+        Expr::StructInit { fields, .. } => {
+            for value in fields.iter().filter_map(|field| field.value.as_ref()) {
+                visitor.visit_expr(value);
+            }
+        }
+        Expr::Range { start, end, .. } => {
+            for bound in start.iter().chain(end.iter()) {
+                visitor.visit_expr(bound);
+            }
+        }
+        Expr::Closure { params, return_type, body, .. } => {
+            walk_parameters(visitor, params);
+            if let Some(return_type) = return_type {
+                visitor.visit_type(return_type);
+            }
+            visitor.visit_expr(body);
+        }
+        Expr::Block { statements, trailing_expr, .. } => {
+            for stmt in statements {
+                visitor.visit_stmt(stmt);
+            }
+            if let Some(trailing_expr) = trailing_expr {
+                visitor.visit_expr(trailing_expr);
+            }
+        }
+        Expr::If { condition, then_block, else_block, .. } => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(then_block);
+            if let Some(else_block) = else_block {
+                visitor.visit_expr(else_block);
+            }
+        }
+        Expr::Match { expr, arms, .. } => {
+            visitor.visit_expr(expr);
+            walk_match_arms(visitor, arms);
+        }
+        Expr::Loop { body, .. } => visitor.visit_expr(body),
+        Expr::While { condition, body, .. } => {
+            visitor.visit_expr(condition);
+            visitor.visit_expr(body);
+        }
+        Expr::For { pattern, iterator, body, .. } => {
+            visitor.visit_pattern(pattern);
+            visitor.visit_expr(iterator);
+            visitor.visit_expr(body);
+        }
+        Expr::Break { value, .. } | Expr::Return { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+    }
+}
+
+/// Visit the sub-patterns of a pattern
+pub fn walk_pattern<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, pattern: &'ast Pattern) {
+    match pattern {
+        Pattern::Wildcard { .. } | Pattern::Identifier { .. } | Pattern::Literal { .. } => {}
+        Pattern::Tuple { patterns, .. } | Pattern::Array { patterns, .. } | Pattern::Or { patterns, .. } => {
+            for pattern in patterns {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        Pattern::Struct { fields, .. } => {
+            for pattern in fields.iter().filter_map(|field| field.pattern.as_ref()) {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        Pattern::Enum { patterns, .. } => {
+            for pattern in patterns.iter().flatten() {
+                visitor.visit_pattern(pattern);
+            }
+        }
+        Pattern::Reference { pattern, .. } => visitor.visit_pattern(pattern),
+        Pattern::Range { start, end, .. } => {
+            for bound in start.iter().chain(end.iter()) {
+                visitor.visit_pattern(bound);
+            }
+        }
+    }
+}
+
+/// Visit the component types of a type, and the size expression of an array type
+pub fn walk_type<'ast, V: Visitor<'ast> + ?Sized>(visitor: &mut V, ty: &'ast Type) {
+    match ty {
+        // Inference constraints are not written in source and are not walked
+        Type::Primitive { .. } | Type::Generic { .. } | Type::Inferred { .. } | Type::Never { .. } => {}
+        Type::Path { generics, .. } => {
+            for generic in generics {
+                visitor.visit_type(generic);
+            }
+        }
+        Type::Array { element_type, size, .. } => {
+            visitor.visit_type(element_type);
+            visitor.visit_expr(size);
+        }
+        Type::Slice { element_type, .. } => visitor.visit_type(element_type),
+        Type::Tuple { types, .. } => {
+            for ty in types {
+                visitor.visit_type(ty);
+            }
+        }
+        Type::Function { params, return_type, .. } => {
+            for param in params {
+                visitor.visit_type(param);
+            }
+            visitor.visit_type(return_type);
+        }
+        Type::Reference { target_type, .. } | Type::Pointer { target_type, .. } => visitor.visit_type(target_type),
+    }
+}
+
+/// AST visitor that may rewrite nodes in place, for desugaring passes
+///
+/// Defaults mirror [`Visitor`]: every `visit_*_mut` method walks the node's children
+/// in source order. An override can replace the node it is given (e.g. rewrite a
+/// compound assignment into an assignment) before or after walking it; the walkers
+/// themselves never touch spans.
+pub trait VisitorMut {
+    fn visit_module_mut(&mut self, module: &mut Module) {
+        walk_module_mut(self, module)
+    }
+    
+    fn visit_item_mut(&mut self, item: &mut Item) {
+        walk_item_mut(self, item)
+    }
+    
+    fn visit_impl_item_mut(&mut self, item: &mut ImplItem) {
+        walk_impl_item_mut(self, item)
+    }
+    
+    fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt)
+    }
+    
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr)
+    }
+    
+    fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+        walk_pattern_mut(self, pattern)
+    }
+    
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        walk_type_mut(self, ty)
+    }
+}
+
+/// Mutable counterpart of [`walk_module`]
+pub fn walk_module_mut<V: VisitorMut + ?Sized>(visitor: &mut V, module: &mut Module) {
+    for item in &mut module.items {
+        visitor.visit_item_mut(item);
+    }
+}
+
+fn walk_generics_mut<V: VisitorMut + ?Sized>(visitor: &mut V, generics: &mut [GenericParam]) {
+    for generic in generics {
+        for bound in &mut generic.bounds {
+            visitor.visit_type_mut(bound);
+        }
+        if let Some(default) = &mut generic.default {
+            visitor.visit_type_mut(default);
+        }
+    }
+}
+
+fn walk_parameters_mut<V: VisitorMut + ?Sized>(visitor: &mut V, params: &mut [Parameter]) {
+    for param in params {
+        visitor.visit_pattern_mut(&mut param.pattern);
+        if let Some(type_annotation) = &mut param.type_annotation {
+            visitor.visit_type_mut(type_annotation);
+        }
+    }
+}
+
+fn walk_struct_fields_mut<V: VisitorMut + ?Sized>(visitor: &mut V, fields: &mut StructFields) {
+    match fields {
+        StructFields::Named(fields) => {
+            for field in fields {
+                visitor.visit_type_mut(&mut field.field_type);
+            }
+        }
+        StructFields::Tuple(types) => {
+            for ty in types {
+                visitor.visit_type_mut(ty);
+            }
+        }
+        StructFields::Unit => {}
+    }
+}
+
+fn walk_match_arms_mut<V: VisitorMut + ?Sized>(visitor: &mut V, arms: &mut [MatchArm]) {
+    for arm in arms {
+        visitor.visit_pattern_mut(&mut arm.pattern);
+        if let Some(guard) = &mut arm.guard {
+            visitor.visit_expr_mut(guard);
+        }
+        visitor.visit_expr_mut(&mut arm.body);
+    }
+}
+
+/// Mutable counterpart of [`walk_item`]
+pub fn walk_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut Item) {
+    match item {
+        Item::Function { generics, params, return_type, body, .. } => {
+            walk_generics_mut(visitor, generics);
+            walk_parameters_mut(visitor, params);
+            if let Some(return_type) = return_type {
+                visitor.visit_type_mut(return_type);
+            }
+            if let Some(body) = body {
+                visitor.visit_expr_mut(body);
+            }
+        }
+        Item::Struct { generics, fields, .. } => {
+            walk_generics_mut(visitor, generics);
+            walk_struct_fields_mut(visitor, fields);
+        }
+        Item::Enum { generics, variants, .. } => {
+            walk_generics_mut(visitor, generics);
+            for variant in variants {
+                walk_struct_fields_mut(visitor, &mut variant.fields);
+                if let Some(discriminant) = &mut variant.discriminant {
+                    visitor.visit_expr_mut(discriminant);
+                }
+            }
+        }
+        Item::TypeAlias { generics, target_type, .. } => {
+            walk_generics_mut(visitor, generics);
+            visitor.visit_type_mut(target_type);
+        }
+        Item::Const { type_annotation, value, .. } => {
+            visitor.visit_type_mut(type_annotation);
+            visitor.visit_expr_mut(value);
+        }
+        Item::Module { items, .. } => {
+            for item in items.iter_mut().flatten() {
+                visitor.visit_item_mut(item);
+            }
+        }
+        Item::Impl { generics, target_type, trait_ref, items, .. } => {
+            walk_generics_mut(visitor, generics);
+            // `impl Trait for Type` names the trait first
+            if let Some(trait_ref) = trait_ref {
+                visitor.visit_type_mut(trait_ref);
+            }
+            visitor.visit_type_mut(target_type);
+            for item in items {
+                visitor.visit_impl_item_mut(item);
+            }
+        }
+        Item::Use { .. } => {}
+    }
+}
+
+/// Mutable counterpart of [`walk_impl_item`]
+pub fn walk_impl_item_mut<V: VisitorMut + ?Sized>(visitor: &mut V, item: &mut ImplItem) {
+    match item {
+        ImplItem::Function { generics, params, return_type, body, .. } => {
+            walk_generics_mut(visitor, generics);
+            walk_parameters_mut(visitor, params);
+            if let Some(return_type) = return_type {
+                visitor.visit_type_mut(return_type);
+            }
+            if let Some(body) = body {
+                visitor.visit_expr_mut(body);
+            }
+        }
+        ImplItem::Type { generics, target_type, .. } => {
+            walk_generics_mut(visitor, generics);
+            visitor.visit_type_mut(target_type);
+        }
+        ImplItem::Const { type_annotation, value, .. } => {
+            visitor.visit_type_mut(type_annotation);
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+    }
+}
+
+/// Mutable counterpart of [`walk_stmt`]
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Expression { expr, .. } => visitor.visit_expr_mut(expr),
+        Stmt::Let { pattern, type_annotation, initializer, .. } => {
+            visitor.visit_pattern_mut(pattern);
+            if let Some(type_annotation) = type_annotation {
+                visitor.visit_type_mut(type_annotation);
+            }
+            if let Some(initializer) = initializer {
+                visitor.visit_expr_mut(initializer);
+            }
+        }
+        Stmt::Assignment { target, value, .. } | Stmt::CompoundAssignment { target, value, .. } => {
+            visitor.visit_expr_mut(target);
+            visitor.visit_expr_mut(value);
+        }
+        Stmt::If { condition, then_block, else_block, .. } => {
+            visitor.visit_expr_mut(condition);
+            for stmt in then_block {
+                visitor.visit_stmt_mut(stmt);
+            }
+            if let Some(else_block) = else_block {
+                visitor.visit_stmt_mut(else_block);
+            }
+        }
+        Stmt::While { condition, body, .. } => {
+            visitor.visit_expr_mut(condition);
+            for stmt in body {
+                visitor.visit_stmt_mut(stmt);
+            }
+        }
+        Stmt::For { pattern, iterable, body, .. } => {
+            visitor.visit_pattern_mut(pattern);
+            visitor.visit_expr_mut(iterable);
+            for stmt in body {
+                visitor.visit_stmt_mut(stmt);
+            }
+        }
+        Stmt::Loop { body, .. } | Stmt::Block { statements: body, .. } | Stmt::Region { body, .. } => {
+            for stmt in body {
+                visitor.visit_stmt_mut(stmt);
+            }
+        }
+        Stmt::Match { expr, arms, .. } => {
+            visitor.visit_expr_mut(expr);
+            walk_match_arms_mut(visitor, arms);
+        }
+        Stmt::Break { expr, .. } | Stmt::Return { expr, .. } => {
+            if let Some(expr) = expr {
+                visitor.visit_expr_mut(expr);
+            }
+        }
+        Stmt::Item { item, .. } => visitor.visit_item_mut(item),
+        Stmt::Continue { .. } | Stmt::Empty { .. } => {}
+    }
+}
+
+/// Mutable counterpart of [`walk_expr`]
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Literal { .. } | Expr::Identifier { .. } | Expr::Path { .. } | Expr::Continue { .. }
+        | Expr::Macro { .. } => {}
+        Expr::Binary { left, right, .. } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        Expr::Unary { expr, .. } | Expr::Parenthesized { expr, .. } | Expr::Box { expr, .. }
+        | Expr::Reference { expr, .. } | Expr::Dereference { expr, .. } | Expr::Try { expr, .. }
+        | Expr::Await { expr, .. } => visitor.visit_expr_mut(expr),
+        Expr::Call { callee: receiver, args, .. } | Expr::MethodCall { receiver, args, .. } => {
+            visitor.visit_expr_mut(receiver);
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        Expr::FieldAccess { object, .. } => visitor.visit_expr_mut(object),
+        Expr::Index { object, index, .. } => {
+            visitor.visit_expr_mut(object);
+            visitor.visit_expr_mut(index);
+        }
+        Expr::Cast { expr, target_type, .. } => {
+            visitor.visit_expr_mut(expr);
+            visitor.visit_type_mut(target_type);
+        }
+        Expr::Array { elements, .. } | Expr::Tuple { elements, .. } => {
+            for element in elements {
+                visitor.visit_expr_mut(element);
+            }
+        }
+        Expr::ArrayRepeat { value, count, .. } => {
+            visitor.visit_expr_mut(value);
+            visitor.visit_expr_mut(count);
+        }
+        Expr::StructInit { fields, .. } => {
+            for value in fields.iter_mut().filter_map(|field| field.value.as_mut()) {
+                visitor.visit_expr_mut(value);
+            }
+        }
+        Expr::Range { start, end, .. } => {
+            for bound in start.iter_mut().chain(end.iter_mut()) {
+                visitor.visit_expr_mut(bound);
+            }
+        }
+        Expr::Closure { params, return_type, body, .. } => {
+            walk_parameters_mut(visitor, params);
+            if let Some(return_type) = return_type {
+                visitor.visit_type_mut(return_type);
+            }
+            visitor.visit_expr_mut(body);
+        }
+        Expr::Block { statements, trailing_expr, .. } => {
+            for stmt in statements {
+                visitor.visit_stmt_mut(stmt);
+            }
+            if let Some(trailing_expr) = trailing_expr {
+                visitor.visit_expr_mut(trailing_expr);
+            }
+        }
+        Expr::If { condition, then_block, else_block, .. } => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_expr_mut(then_block);
+            if let Some(else_block) = else_block {
+                visitor.visit_expr_mut(else_block);
+            }
+        }
+        Expr::Match { expr, arms, .. } => {
+            visitor.visit_expr_mut(expr);
+            walk_match_arms_mut(visitor, arms);
+        }
+        Expr::Loop { body, .. } => visitor.visit_expr_mut(body),
+        Expr::While { condition, body, .. } => {
+            visitor.visit_expr_mut(condition);
+            visitor.visit_expr_mut(body);
+        }
+        Expr::For { pattern, iterator, body, .. } => {
+            visitor.visit_pattern_mut(pattern);
+            visitor.visit_expr_mut(iterator);
+            visitor.visit_expr_mut(body);
+        }
+        Expr::Break { value, .. } | Expr::Return { value, .. } => {
+            if let Some(value) = value {
+                visitor.visit_expr_mut(value);
+            }
+        }
+    }
+}
+
+/// Mutable counterpart of [`walk_pattern`]
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(visitor: &mut V, pattern: &mut Pattern) {
+    match pattern {
+        Pattern::Wildcard { .. } | Pattern::Identifier { .. } | Pattern::Literal { .. } => {}
+        Pattern::Tuple { patterns, .. } | Pattern::Array { patterns, .. } | Pattern::Or { patterns, .. } => {
+            for pattern in patterns {
+                visitor.visit_pattern_mut(pattern);
+            }
+        }
+        Pattern::Struct { fields, .. } => {
+            for pattern in fields.iter_mut().filter_map(|field| field.pattern.as_mut()) {
+                visitor.visit_pattern_mut(pattern);
+            }
+        }
+        Pattern::Enum { patterns, .. } => {
+            for pattern in patterns.iter_mut().flatten() {
+                visitor.visit_pattern_mut(pattern);
+            }
+        }
+        Pattern::Reference { pattern, .. } => visitor.visit_pattern_mut(pattern),
+        Pattern::Range { start, end, .. } => {
+            for bound in start.iter_mut().chain(end.iter_mut()) {
+                visitor.visit_pattern_mut(bound);
+            }
+        }
+    }
+}
+
+/// Mutable counterpart of [`walk_type`]
+pub fn walk_type_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ty: &mut Type) {
+    match ty {
+        // Inference constraints are not written in source and are not walked
+        Type::Primitive { .. } | Type::Generic { .. } | Type::Inferred { .. } | Type::Never { .. } => {}
+        Type::Path { generics, .. } => {
+            for generic in generics {
+                visitor.visit_type_mut(generic);
+            }
+        }
+        Type::Array { element_type, size, .. } => {
+            visitor.visit_type_mut(element_type);
+            visitor.visit_expr_mut(size);
+        }
+        Type::Slice { element_type, .. } => visitor.visit_type_mut(element_type),
+        Type::Tuple { types, .. } => {
+            for ty in types {
+                visitor.visit_type_mut(ty);
+            }
+        }
+        Type::Function { params, return_type, .. } => {
+            for param in params {
+                visitor.visit_type_mut(param);
+            }
+            visitor.visit_type_mut(return_type);
+        }
+        Type::Reference { target_type, .. } | Type::Pointer { target_type, .. } => visitor.visit_type_mut(target_type),
+    }
+}
+
 /// Utility functions for AST analysis
 pub mod utils {
     use super::*;
@@ -741,9 +1497,14 @@ mod tests {
                     return_type: None,
                     body: None,
                     is_extern: false,
+                    performance_contract: None,
+                    allocation_strategy: None,
+                    attributes: Vec::new(),
                     span: dummy_span(),
+                    id: NodeId::DUMMY,
                 }
             ],
+            attributes: Vec::new(),
             span: dummy_span(),
         };
         
@@ -763,9 +1524,14 @@ mod tests {
                     return_type: None,
                     body: None,
                     is_extern: false,
+                    performance_contract: None,
+                    allocation_strategy: None,
+                    attributes: Vec::new(),
                     span: dummy_span(),
+                    id: NodeId::DUMMY,
                 }
             ],
+            attributes: Vec::new(),
             span: dummy_span(),
         };
         
@@ -773,4 +1539,153 @@ mod tests {
         assert_eq!(identifiers.len(), 1);
         assert_eq!(identifiers[0].id, 42);
     }
-} 
\ No newline at end of file
+    
+    const SAMPLE: &str = r#"
+struct Point { x: i32, y: i32 }
+enum Shape { Circle(f64), Square { side: f64 } }
+const LIMIT: i32 = 10;
+fn distance(p: &Point, scale: i32) -> i32 {
+    let (dx, dy): (i32, i32) = (p.x * scale, p.y - 1);
+    let mut total = 0;
+    for i in 0..LIMIT {
+        total += i;
+    }
+    while total > 100 { total = total / 2; }
+    if dx > dy { return dx; } else { total = -total; }
+    let add = |a: i32, b: i32| -> i32 { a + b };
+    let values: [i32; 3] = [1, 2, 3];
+    add(values[0], total) as i32
+}
+impl Point {
+    fn norm(self) -> i32 { self.x * self.x + self.y * self.y }
+}
+"#;
+    
+    fn parse_sample() -> Module {
+        let mut parser = crate::Parser::new(SAMPLE, 0).unwrap();
+        let module = parser.parse_module().unwrap();
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        module
+    }
+    
+    /// Name of the enum variant a node is, e.g. `Binary` for an `Expr::Binary`
+    fn variant_name(node: &impl std::fmt::Debug) -> String {
+        let debug = format!("{:?}", node);
+        debug[..debug.find([' ', '(', '{']).unwrap_or(debug.len())].to_string()
+    }
+    
+    /// Counts the nodes of each kind and records where each one starts, in visiting order
+    #[derive(Default)]
+    struct KindCounter {
+        counts: HashMap<String, usize>,
+        starts: Vec<usize>,
+    }
+    
+    impl KindCounter {
+        fn record(&mut self, kind: &str, node: &impl std::fmt::Debug, span: Span) {
+            *self.counts.entry(format!("{}::{}", kind, variant_name(node))).or_default() += 1;
+            self.starts.push(span.start.offset);
+        }
+        
+        fn count(&self, kind: &str) -> usize {
+            self.counts.get(kind).copied().unwrap_or(0)
+        }
+    }
+    
+    impl<'ast> Visitor<'ast> for KindCounter {
+        fn visit_item(&mut self, item: &'ast Item) {
+            *self.counts.entry(format!("Item::{}", variant_name(item))).or_default() += 1;
+            walk_item(self, item);
+        }
+        
+        fn visit_impl_item(&mut self, item: &'ast ImplItem) {
+            *self.counts.entry(format!("ImplItem::{}", variant_name(item))).or_default() += 1;
+            walk_impl_item(self, item);
+        }
+        
+        fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+            self.record("Stmt", stmt, stmt.span());
+            walk_stmt(self, stmt);
+        }
+        
+        fn visit_expr(&mut self, expr: &'ast Expr) {
+            self.record("Expr", expr, expr.span());
+            walk_expr(self, expr);
+        }
+        
+        fn visit_pattern(&mut self, pattern: &'ast Pattern) {
+            self.record("Pattern", pattern, pattern.span());
+            walk_pattern(self, pattern);
+        }
+        
+        fn visit_type(&mut self, ty: &'ast Type) {
+            self.record("Type", ty, ty.span());
+            walk_type(self, ty);
+        }
+    }
+    
+    #[test]
+    fn test_visitor_walks_every_node() {
+        let module = parse_sample();
+        let mut counter = KindCounter::default();
+        counter.visit_module(&module);
+        
+        let expected = [
+            ("Item::Struct", 1), ("Item::Enum", 1), ("Item::Const", 1), ("Item::Function", 1), ("Item::Impl", 1),
+            ("ImplItem::Function", 1),
+            ("Stmt::Let", 4), ("Stmt::For", 1), ("Stmt::CompoundAssignment", 1), ("Stmt::While", 1),
+            ("Stmt::Assignment", 2), ("Stmt::If", 1), ("Stmt::Return", 1), ("Stmt::Block", 1),
+            ("Expr::Tuple", 1), ("Expr::Binary", 9), ("Expr::FieldAccess", 6), ("Expr::Range", 1),
+            ("Expr::Closure", 1), ("Expr::Block", 3), ("Expr::Array", 1), ("Expr::Cast", 1), ("Expr::Call", 1),
+            ("Expr::Index", 1), ("Expr::Unary", 1), ("Expr::Literal", 11), ("Expr::Identifier", 23),
+            ("Pattern::Tuple", 1), ("Pattern::Identifier", 11),
+            ("Type::Primitive", 15), ("Type::Path", 3), ("Type::Reference", 1), ("Type::Tuple", 1), ("Type::Array", 1),
+        ];
+        for (kind, count) in expected {
+            assert_eq!(counter.count(kind), count, "{} in {:?}", kind, counter.counts);
+        }
+        
+        // Pre-order in source order: nothing is visited before a node that precedes it
+        assert!(counter.starts.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", counter.starts);
+    }
+    
+    /// Desugars `target op= value` into `target = target op value`
+    struct CompoundAssignmentDesugarer;
+    
+    impl VisitorMut for CompoundAssignmentDesugarer {
+        fn visit_stmt_mut(&mut self, stmt: &mut Stmt) {
+            if let Stmt::CompoundAssignment { target, op, value, span, .. } = stmt {
+                let value = Expr::Binary {
+                    left: Box::new(target.clone()),
+                    op: *op,
+                    right: Box::new(value.clone()),
+                    span: *span,
+                    id: NodeId::DUMMY,
+                };
+                *stmt = Stmt::Assignment { target: target.clone(), value, span: *span, id: NodeId::DUMMY };
+            }
+            walk_stmt_mut(self, stmt);
+        }
+    }
+    
+    #[test]
+    fn test_visitor_mut_rewrites_in_place() {
+        let mut module = parse_sample();
+        let original_spans = {
+            let mut counter = KindCounter::default();
+            counter.visit_module(&module);
+            counter.starts.len()
+        };
+        
+        CompoundAssignmentDesugarer.visit_module_mut(&mut module);
+        
+        let mut counter = KindCounter::default();
+        counter.visit_module(&module);
+        assert_eq!(counter.count("Stmt::CompoundAssignment"), 0);
+        assert_eq!(counter.count("Stmt::Assignment"), 3);
+        // `total += i` gained a `total + i` and a second `total`
+        assert_eq!(counter.count("Expr::Binary"), 10);
+        assert_eq!(counter.starts.len(), original_spans + 2);
+        assert!(counter.starts.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+}
@@ -0,0 +1,406 @@
+//! Golden-file test locking the JSON form of the AST
+//!
+//! The JSON of `golden/ast_json.bract` must match `golden/ast_json.json`. After an
+//! intentional schema change, rerun with `BRACT_BLESS=1` to regenerate the golden
+//! file and bump `AST_JSON_VERSION`.
+
+use bract::ast::{self, SerializableModule, SourceMap, AST_JSON_VERSION};
+use bract::Parser;
+use std::fs;
+use std::path::PathBuf;
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name)
+}
+
+#[test]
+fn test_ast_json_matches_golden_file() {
+    let source = fs::read_to_string(golden_path("ast_json.bract")).expect("golden program");
+    let mut parser = Parser::new(&source, 0).expect("Parser creation failed");
+    let module = parser.parse_module().expect("Parsing failed");
+    assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+    let json = ast::to_json(&module, &parser.interner());
+    
+    let golden = golden_path("ast_json.json");
+    if std::env::var_os("BRACT_BLESS").is_some() {
+        fs::write(&golden, format!("{}\n", json)).expect("writing the golden file");
+    }
+    let expected = fs::read_to_string(&golden).expect("golden file - run with BRACT_BLESS=1 to create it");
+    assert!(
+        expected.trim_end() == json,
+        "AST JSON no longer matches {}; if the change is intended, rerun with BRACT_BLESS=1 and bump AST_JSON_VERSION",
+        golden.display()
+    );
+}
+
+#[test]
+fn test_ast_json_resolves_names_and_files() {
+    let mut parser = Parser::new("fn answer() -> i32 { return 42; }", 0).expect("Parser creation failed");
+    let module = parser.parse_module().expect("Parsing failed");
+    let interner = parser.interner();
+    let source_map = SourceMap::new("answer.bract");
+    
+    let value = serde_json::to_value(SerializableModule::new(&module, &interner).with_source_map(&source_map)).unwrap();
+    assert_eq!(value["version"], AST_JSON_VERSION);
+    let function = &value["module"]["items"][0];
+    assert_eq!(function["variant"], "Function");
+    assert_eq!(function["name"], "answer");
+    assert_eq!(function["span"]["start"], serde_json::json!({"file": "answer.bract", "line": 1, "column": 1, "offset": 0}));
+    
+    // Outside the wrapper names stay ids and files stay file ids
+    let raw = serde_json::to_value(&module).unwrap();
+    assert!(raw["items"][0]["name"].is_u64(), "{}", raw["items"][0]["name"]);
+    assert_eq!(raw["items"][0]["span"]["start"]["file"], 0);
+}
+
+/// Exhaustive patterns over every serialized AST type
+///
+/// Adding a field or variant to the AST fails to compile here. Extend the pattern,
+/// then regenerate the golden file as described at the top of this file.
+#[allow(dead_code)]
+mod schema_lock {
+    use bract::ast::*;
+    use bract::lexer::Position;
+
+    fn lock_position(position: &Position) {
+        let Position { line: _, column: _, offset: _, file_id: _ } = position;
+    }
+
+    fn lock_memory_strategy(memory_strategy: &MemoryStrategy) {
+        match memory_strategy {
+            MemoryStrategy::Manual
+            | MemoryStrategy::SmartPtr
+            | MemoryStrategy::Linear
+            | MemoryStrategy::Region
+            | MemoryStrategy::Stack
+            | MemoryStrategy::Inferred => {}
+        }
+    }
+
+    fn lock_ownership(ownership: &Ownership) {
+        match ownership {
+            Ownership::Owned
+            | Ownership::Borrowed
+            | Ownership::MutBorr
+            | Ownership::Shared
+            | Ownership::Linear => {}
+        }
+    }
+
+    fn lock_lifetime_id(lifetime_id: &LifetimeId) {
+        let LifetimeId(_) = lifetime_id;
+    }
+
+    fn lock_span(span: &Span) {
+        let Span { start: _, end: _ } = span;
+    }
+
+    fn lock_node_id(node_id: &NodeId) {
+        let NodeId(_) = node_id;
+    }
+
+    fn lock_binary_op(binary_op: &BinaryOp) {
+        match binary_op {
+            BinaryOp::Add
+            | BinaryOp::Subtract
+            | BinaryOp::Multiply
+            | BinaryOp::Divide
+            | BinaryOp::Modulo
+            | BinaryOp::BitwiseAnd
+            | BinaryOp::BitwiseOr
+            | BinaryOp::BitwiseXor
+            | BinaryOp::LeftShift
+            | BinaryOp::RightShift
+            | BinaryOp::LogicalAnd
+            | BinaryOp::LogicalOr
+            | BinaryOp::Equal
+            | BinaryOp::NotEqual
+            | BinaryOp::Less
+            | BinaryOp::LessEqual
+            | BinaryOp::Greater
+            | BinaryOp::GreaterEqual
+            | BinaryOp::Assign => {}
+        }
+    }
+
+    fn lock_unary_op(unary_op: &UnaryOp) {
+        match unary_op {
+            UnaryOp::Not
+            | UnaryOp::Negate
+            | UnaryOp::Plus
+            | UnaryOp::BitwiseNot
+            | UnaryOp::Dereference
+            | UnaryOp::AddressOf
+            | UnaryOp::MutableRef => {}
+        }
+    }
+
+    fn lock_literal(literal: &Literal) {
+        match literal {
+            Literal::Integer { value: _, base: _, suffix: _ }
+            | Literal::Float { value: _, suffix: _ }
+            | Literal::String { value: _, raw: _, raw_delimiter: _ }
+            | Literal::Char(_)
+            | Literal::Bool(_)
+            | Literal::Null => {}
+        }
+    }
+
+    fn lock_expr(expr: &Expr) {
+        match expr {
+            Expr::Literal { literal: _, span: _, id: _ }
+            | Expr::Identifier { name: _, span: _, id: _ }
+            | Expr::Path { segments: _, span: _, id: _ }
+            | Expr::Binary { left: _, op: _, right: _, span: _, id: _ }
+            | Expr::Unary { op: _, expr: _, span: _, id: _ }
+            | Expr::Call { callee: _, args: _, span: _, id: _ }
+            | Expr::MethodCall { receiver: _, method: _, args: _, span: _, id: _ }
+            | Expr::FieldAccess { object: _, field: _, span: _, id: _ }
+            | Expr::Index { object: _, index: _, span: _, id: _ }
+            | Expr::Cast { expr: _, target_type: _, span: _, id: _ }
+            | Expr::Parenthesized { expr: _, span: _, id: _ }
+            | Expr::Array { elements: _, span: _, id: _ }
+            | Expr::ArrayRepeat { value: _, count: _, span: _, id: _ }
+            | Expr::Tuple { elements: _, span: _, id: _ }
+            | Expr::StructInit { path: _, fields: _, span: _, id: _ }
+            | Expr::Range { start: _, end: _, inclusive: _, span: _, id: _ }
+            | Expr::Closure { is_move: _, params: _, return_type: _, body: _, span: _, id: _ }
+            | Expr::Block { statements: _, trailing_expr: _, span: _, id: _ }
+            | Expr::If { condition: _, then_block: _, else_block: _, span: _, id: _ }
+            | Expr::Match { expr: _, arms: _, span: _, id: _ }
+            | Expr::Loop { label: _, body: _, span: _, id: _ }
+            | Expr::While { condition: _, body: _, span: _, id: _ }
+            | Expr::For { pattern: _, iterator: _, body: _, span: _, id: _ }
+            | Expr::Break { label: _, value: _, span: _, id: _ }
+            | Expr::Continue { label: _, span: _, id: _ }
+            | Expr::Return { value: _, span: _, id: _ }
+            | Expr::Box { expr: _, span: _, id: _ }
+            | Expr::Reference { is_mutable: _, expr: _, span: _, id: _ }
+            | Expr::Dereference { expr: _, span: _, id: _ }
+            | Expr::Try { expr: _, span: _, id: _ }
+            | Expr::Await { expr: _, span: _, id: _ }
+            | Expr::Macro { name: _, args: _, span: _, id: _ } => {}
+        }
+    }
+
+    fn lock_field_init(field_init: &FieldInit) {
+        let FieldInit { name: _, value: _, span: _ } = field_init;
+    }
+
+    fn lock_match_arm(match_arm: &MatchArm) {
+        let MatchArm { pattern: _, guard: _, body: _, span: _ } = match_arm;
+    }
+
+    fn lock_stmt(stmt: &Stmt) {
+        match stmt {
+            Stmt::Expression { expr: _, span: _, id: _ }
+            | Stmt::Let { pattern: _, type_annotation: _, initializer: _, is_mutable: _, span: _, id: _ }
+            | Stmt::Assignment { target: _, value: _, span: _, id: _ }
+            | Stmt::CompoundAssignment { target: _, op: _, value: _, span: _, id: _ }
+            | Stmt::If { condition: _, then_block: _, else_block: _, span: _, id: _ }
+            | Stmt::While { condition: _, body: _, span: _, id: _ }
+            | Stmt::For { pattern: _, iterable: _, body: _, span: _, id: _ }
+            | Stmt::Loop { label: _, body: _, span: _, id: _ }
+            | Stmt::Match { expr: _, arms: _, span: _, id: _ }
+            | Stmt::Break { label: _, expr: _, span: _, id: _ }
+            | Stmt::Continue { label: _, span: _, id: _ }
+            | Stmt::Return { expr: _, span: _, id: _ }
+            | Stmt::Block { statements: _, span: _, id: _ }
+            | Stmt::Region { name: _, size_hint: _, body: _, span: _, id: _ }
+            | Stmt::Item { item: _, span: _, id: _ }
+            | Stmt::Empty { span: _, id: _ } => {}
+        }
+    }
+
+    fn lock_item(item: &Item) {
+        match item {
+            Item::Function { visibility: _, name: _, generics: _, params: _, return_type: _, body: _, is_extern: _, performance_contract: _, allocation_strategy: _, attributes: _, span: _, id: _ }
+            | Item::Struct { visibility: _, name: _, generics: _, fields: _, attributes: _, span: _, id: _ }
+            | Item::Enum { visibility: _, name: _, generics: _, variants: _, attributes: _, span: _, id: _ }
+            | Item::TypeAlias { visibility: _, name: _, generics: _, target_type: _, attributes: _, span: _, id: _ }
+            | Item::Const { visibility: _, name: _, type_annotation: _, value: _, attributes: _, span: _, id: _ }
+            | Item::Module { visibility: _, name: _, items: _, attributes: _, span: _, id: _ }
+            | Item::Impl { generics: _, target_type: _, trait_ref: _, items: _, attributes: _, span: _, id: _ }
+            | Item::Use { path: _, path_spans: _, alias: _, attributes: _, span: _, id: _ } => {}
+        }
+    }
+
+    fn lock_attribute(attribute: &Attribute) {
+        let Attribute { name: _, args: _, cfg: _, span: _ } = attribute;
+    }
+
+    fn lock_attribute_arg(attribute_arg: &AttributeArg) {
+        let AttributeArg { key: _, value: _, span: _ } = attribute_arg;
+    }
+
+    fn lock_struct_fields(struct_fields: &StructFields) {
+        match struct_fields {
+            StructFields::Named(_)
+            | StructFields::Tuple(_)
+            | StructFields::Unit => {}
+        }
+    }
+
+    fn lock_struct_field(struct_field: &StructField) {
+        let StructField { visibility: _, name: _, field_type: _, span: _ } = struct_field;
+    }
+
+    fn lock_enum_variant(enum_variant: &EnumVariant) {
+        let EnumVariant { name: _, fields: _, discriminant: _, span: _ } = enum_variant;
+    }
+
+    fn lock_impl_item(impl_item: &ImplItem) {
+        match impl_item {
+            ImplItem::Function { visibility: _, name: _, generics: _, params: _, return_type: _, body: _, span: _ }
+            | ImplItem::Type { visibility: _, name: _, generics: _, target_type: _, span: _ }
+            | ImplItem::Const { visibility: _, name: _, type_annotation: _, value: _, span: _ } => {}
+        }
+    }
+
+    fn lock_performance_contract(performance_contract: &PerformanceContract) {
+        let PerformanceContract { cpu_bound: _, memory_bound: _, allocation_bound: _, latency_bound: _, stack_bound: _, deterministic: _, wait_free: _, span: _ } = performance_contract;
+    }
+
+    fn lock_cpu_bound(cpu_bound: &CpuBound) {
+        match cpu_bound {
+            CpuBound::Cycles(_)
+            | CpuBound::Time(_)
+            | CpuBound::Complexity(_, _) => {}
+        }
+    }
+
+    fn lock_memory_bound(memory_bound: &MemoryBound) {
+        match memory_bound {
+            MemoryBound::Bytes(_)
+            | MemoryBound::Zero
+            | MemoryBound::Proportional(_, _) => {}
+        }
+    }
+
+    fn lock_allocation_bound(allocation_bound: &AllocationBound) {
+        match allocation_bound {
+            AllocationBound::Count(_)
+            | AllocationBound::None => {}
+        }
+    }
+
+    fn lock_latency_bound(latency_bound: &LatencyBound) {
+        let LatencyBound { max_latency: _ } = latency_bound;
+    }
+
+    fn lock_stack_bound(stack_bound: &StackBound) {
+        match stack_bound {
+            StackBound::Bytes(_)
+            | StackBound::Zero => {}
+        }
+    }
+
+    fn lock_big_o_complexity(big_o_complexity: &BigOComplexity) {
+        match big_o_complexity {
+            BigOComplexity::Constant
+            | BigOComplexity::Logarithmic
+            | BigOComplexity::Linear
+            | BigOComplexity::Linearithmic
+            | BigOComplexity::Quadratic
+            | BigOComplexity::Cubic
+            | BigOComplexity::Exponential => {}
+        }
+    }
+
+    fn lock_parameter(parameter: &Parameter) {
+        let Parameter { pattern: _, type_annotation: _, span: _ } = parameter;
+    }
+
+    fn lock_generic_param(generic_param: &GenericParam) {
+        let GenericParam { name: _, bounds: _, default: _, span: _ } = generic_param;
+    }
+
+    fn lock_visibility(visibility: &Visibility) {
+        match visibility {
+            Visibility::Public
+            | Visibility::Private => {}
+        }
+    }
+
+    fn lock_pattern(pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard { span: _, id: _ }
+            | Pattern::Identifier { name: _, is_mutable: _, span: _, id: _ }
+            | Pattern::Literal { literal: _, span: _, id: _ }
+            | Pattern::Tuple { patterns: _, span: _, id: _ }
+            | Pattern::Array { patterns: _, span: _, id: _ }
+            | Pattern::Struct { path: _, fields: _, rest: _, span: _, id: _ }
+            | Pattern::Enum { path: _, patterns: _, span: _, id: _ }
+            | Pattern::Reference { is_mutable: _, pattern: _, span: _, id: _ }
+            | Pattern::Range { start: _, end: _, inclusive: _, span: _, id: _ }
+            | Pattern::Or { patterns: _, span: _, id: _ } => {}
+        }
+    }
+
+    fn lock_field_pattern(field_pattern: &FieldPattern) {
+        let FieldPattern { name: _, pattern: _, span: _ } = field_pattern;
+    }
+
+    fn lock_ty(ty: &Type) {
+        match ty {
+            Type::Primitive { kind: _, memory_strategy: _, span: _, id: _ }
+            | Type::Path { segments: _, generics: _, memory_strategy: _, span: _, id: _ }
+            | Type::Array { element_type: _, size: _, memory_strategy: _, span: _, id: _ }
+            | Type::Slice { element_type: _, lifetime: _, span: _, id: _ }
+            | Type::Tuple { types: _, memory_strategy: _, span: _, id: _ }
+            | Type::Function { params: _, return_type: _, is_variadic: _, span: _, id: _ }
+            | Type::Reference { is_mutable: _, target_type: _, lifetime: _, ownership: _, span: _, id: _ }
+            | Type::Pointer { is_mutable: _, target_type: _, memory_strategy: _, span: _, id: _ }
+            | Type::Generic { name: _, bounds: _, span: _, id: _ }
+            | Type::Inferred { constraints: _, span: _, id: _ }
+            | Type::Never { span: _, id: _ } => {}
+        }
+    }
+
+    fn lock_type_bound(type_bound: &TypeBound) {
+        match type_bound {
+            TypeBound::MemoryStrategy(_)
+            | TypeBound::Trait(_)
+            | TypeBound::Lifetime(_)
+            | TypeBound::MaxSize(_) => {}
+        }
+    }
+
+    fn lock_type_constraint(type_constraint: &TypeConstraint) {
+        match type_constraint {
+            TypeConstraint::CompatibleWith(_)
+            | TypeConstraint::SupportsStrategy(_)
+            | TypeConstraint::HasOwnership(_)
+            | TypeConstraint::WithinLifetime(_)
+            | TypeConstraint::Copyable
+            | TypeConstraint::Movable => {}
+        }
+    }
+
+    fn lock_primitive_type(primitive_type: &PrimitiveType) {
+        match primitive_type {
+            PrimitiveType::I8
+            | PrimitiveType::I16
+            | PrimitiveType::I32
+            | PrimitiveType::I64
+            | PrimitiveType::I128
+            | PrimitiveType::ISize
+            | PrimitiveType::U8
+            | PrimitiveType::U16
+            | PrimitiveType::U32
+            | PrimitiveType::U64
+            | PrimitiveType::U128
+            | PrimitiveType::USize
+            | PrimitiveType::F32
+            | PrimitiveType::F64
+            | PrimitiveType::Bool
+            | PrimitiveType::Char
+            | PrimitiveType::Str
+            | PrimitiveType::Unit => {}
+        }
+    }
+
+    fn lock_module(module: &Module) {
+        let Module { items: _, attributes: _, span: _ } = module;
+    }
+}
@@ -0,0 +1,140 @@
+//! Snapshots of the Cranelift IR codegen emits
+//!
+//! Every `golden/codegen/<name>.bract` program is parsed, analyzed and compiled for
+//! x86_64 Linux with the IR verifier on. The IR of its functions must match
+//! `golden/codegen/<name>.clif`, and the object file must export `main` and import
+//! exactly the runtime functions the program calls. After an intended change to the
+//! emitted IR, rerun with `BRACT_BLESS=1` to regenerate the snapshots.
+
+use bract::codegen::cranelift::{utils::parse_target_triple, CraneliftCodeGenerator, FunctionIr};
+use bract::codegen::CodegenOptions;
+use bract::semantic::SemanticAnalyzer;
+use bract::Parser;
+use object::{Object, ObjectSection, ObjectSymbol, RelocationTarget, SymbolKind};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Runtime functions each program calls; the others must not be referenced
+const IMPORTS: &[(&str, &[&str])] = &[("manual_allocation", &["bract_malloc"])];
+
+fn snapshot_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/codegen")
+}
+
+/// Compile `source`, returning the object file and the IR of its functions
+fn compile(source: &str) -> Result<(Vec<u8>, Vec<FunctionIr>), String> {
+    let mut parser = Parser::new(source, 0).map_err(|error| error.to_string())?;
+    let module = parser.parse_module().map_err(|error| error.to_string())?;
+    if let Some(error) = parser.errors().first() {
+        return Err(error.to_string());
+    }
+    let interner = parser.interner();
+    let analysis = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+    if let Some(error) = analysis.errors.first() {
+        return Err(error.message(&interner));
+    }
+
+    let options = CodegenOptions { capture_ir: true, ..CodegenOptions::debug() };
+    let triple = parse_target_triple("x86_64-unknown-linux-gnu").map_err(|error| error.to_string())?;
+    let mut generator = CraneliftCodeGenerator::new_with_target(analysis.symbol_table, interner, triple, options)
+        .map_err(|error| error.to_string())?;
+    let object = generator.generate(&module).map_err(|error| error.to_string())?;
+    Ok((object, generator.function_ir().to_vec()))
+}
+
+fn render(functions: &[FunctionIr]) -> String {
+    functions
+        .iter()
+        .map(|function| format!("; {}\n{}", function.name, function.clif))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Undefined symbols some relocation of the object refers to
+fn referenced_imports(file: &object::File) -> BTreeSet<String> {
+    file.sections()
+        .flat_map(|section| section.relocations().collect::<Vec<_>>())
+        .filter_map(|(_, relocation)| match relocation.target() {
+            RelocationTarget::Symbol(index) => file.symbol_by_index(index).ok(),
+            _ => None,
+        })
+        .filter(|symbol| symbol.is_undefined())
+        .filter_map(|symbol| symbol.name().ok().map(str::to_string))
+        .collect()
+}
+
+fn check_object(name: &str, object: &[u8]) {
+    let file = object::File::parse(object).unwrap_or_else(|error| panic!("{}: unreadable object: {}", name, error));
+    let main = file.symbols().find(|symbol| symbol.name() == Ok("main")).unwrap_or_else(|| panic!("{}: no `main` symbol", name));
+    assert!(main.is_definition() && main.is_global(), "{}: `main` is not exported", name);
+    assert_eq!(main.kind(), SymbolKind::Text, "{}", name);
+
+    let expected: BTreeSet<String> = IMPORTS
+        .iter()
+        .find(|(program, _)| *program == name)
+        .map_or(&[][..], |(_, imports)| imports)
+        .iter()
+        .map(|import| import.to_string())
+        .collect();
+    assert_eq!(referenced_imports(&file), expected, "{}: unexpected imports", name);
+}
+
+fn programs(dir: &Path) -> Vec<PathBuf> {
+    let mut programs: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("snapshot directory")
+        .map(|entry| entry.expect("snapshot entry").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "bract"))
+        .collect();
+    programs.sort();
+    programs
+}
+
+#[test]
+fn test_codegen_matches_snapshots() {
+    let bless = std::env::var_os("BRACT_BLESS").is_some();
+    let programs = programs(&snapshot_dir());
+    assert!(!programs.is_empty(), "no programs in {}", snapshot_dir().display());
+
+    let mut mismatched = Vec::new();
+    for path in &programs {
+        let name = path.file_stem().and_then(|stem| stem.to_str()).expect("program name");
+        let source = fs::read_to_string(path).expect("program source");
+        let (object, functions) = compile(&source).unwrap_or_else(|error| panic!("{} does not compile: {}", name, error));
+        check_object(name, &object);
+
+        let ir = render(&functions);
+        let snapshot = path.with_extension("clif");
+        if bless {
+            fs::write(&snapshot, &ir).expect("writing the snapshot");
+        }
+        let expected = fs::read_to_string(&snapshot)
+            .unwrap_or_else(|_| panic!("no snapshot for {} - run with BRACT_BLESS=1 to create it", name));
+        if expected != ir {
+            eprintln!("IR of {} changed:\n{}", name, ir);
+            mismatched.push(name.to_string());
+        }
+    }
+    assert!(
+        mismatched.is_empty(),
+        "IR no longer matches the snapshots of {:?}; if the change is intended, rerun with BRACT_BLESS=1",
+        mismatched
+    );
+}
+
+#[test]
+fn test_ir_is_only_captured_on_request() {
+    let source = fs::read_to_string(snapshot_dir().join("call_chain.bract")).expect("program source");
+    let mut parser = Parser::new(&source, 0).expect("Parser creation failed");
+    let module = parser.parse_module().expect("Parsing failed");
+    let interner = parser.interner();
+    let analysis = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+    let mut generator = CraneliftCodeGenerator::new(analysis.symbol_table, interner, CodegenOptions::debug())
+        .expect("Code generator creation failed");
+    generator.generate(&module).expect("Compilation failed");
+    assert!(generator.function_ir().is_empty());
+
+    let (_, functions) = compile(&source).expect("Compilation failed");
+    let names: Vec<&str> = functions.iter().map(|function| function.name.as_str()).collect();
+    assert_eq!(names, ["double", "add_one", "compute", "main"]);
+}
@@ -0,0 +1,95 @@
+//! Lint against span-less code generation errors in the lowering code
+//!
+//! Errors raised while lowering a function should say where in the program they come
+//! from, by constructing them with `.at(span)`. Errors constructed without a span
+//! still get the span of the statement or function they are raised in, so older code
+//! keeps working, but that span is vaguer than the construct that actually failed.
+//! The number of such constructions in each lowering file may only go down: new code
+//! locates its errors, and when old code is fixed the limit below is lowered with it.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Lowering files and how many span-less `CodegenError` constructions each may have
+const LIMITS: &[(&str, usize)] = &[
+    ("src/codegen/cranelift/functions.rs", 51),
+    ("src/codegen/cranelift/expressions.rs", 15),
+    ("src/codegen/cranelift/statements.rs", 0),
+    ("src/codegen/cranelift/addressing.rs", 0),
+];
+
+/// Index just past the parenthesis closing the one at `open`, skipping string literals
+fn past_closing_paren(source: &str, open: usize) -> usize {
+    let bytes = source.as_bytes();
+    let (mut depth, mut in_string, mut i) = (0, false, open);
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if in_string => i += 1,
+            b'"' => in_string = !in_string,
+            b'(' if !in_string => depth += 1,
+            b')' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+/// Line numbers of the `CodegenError` constructions in `source` not followed by `.at(`
+///
+/// Code after `#[cfg(test)]` is left out, as is Cranelift's own `CodegenError`.
+fn spanless_errors(source: &str) -> Vec<usize> {
+    let code = source.split("#[cfg(test)]").next().unwrap_or_default();
+    let mut lines = Vec::new();
+    for (start, _) in code.match_indices("CodegenError::") {
+        if code[..start].ends_with("::") {
+            continue;
+        }
+        let variant_end = start + "CodegenError::".len();
+        let variant_len = code[variant_end..].find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(0);
+        let open = variant_end + variant_len;
+        if !code[open..].starts_with('(') {
+            continue;
+        }
+        let end = past_closing_paren(code, open);
+        if !code[end..].starts_with(".at(") {
+            lines.push(code[..start].matches('\n').count() + 1);
+        }
+    }
+    lines
+}
+
+#[test]
+fn test_lowering_errors_are_located() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    for &(file, limit) in LIMITS {
+        let source = fs::read_to_string(root.join(file)).expect("reading a lowering file");
+        let lines = spanless_errors(&source);
+        assert!(
+            lines.len() <= limit,
+            "{} constructs {} errors without a span, more than the {} allowed; locate the new ones with \
+             `.at(span)`. Span-less errors are on lines {:?}",
+            file, lines.len(), limit, lines
+        );
+        assert!(
+            lines.len() == limit,
+            "{} now constructs only {} errors without a span; lower its limit from {} to match",
+            file, lines.len(), limit
+        );
+    }
+}
+
+#[test]
+fn test_lint_tells_located_errors_apart() {
+    let source = "Err(CodegenError::TypeConversion(format!(\"({}\", x)).at(*span))\n\
+                  .ok_or_else(|| CodegenError::InternalError(\"no layout\".to_string()))\n\
+                  cranelift_codegen::CodegenError::Verifier(errors) => {}\n\
+                  #[cfg(test)]\n\
+                  CodegenError::InternalError(String::new())\n";
+    assert_eq!(spanless_errors(source), [2]);
+}
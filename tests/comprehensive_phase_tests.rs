@@ -9,7 +9,8 @@
 // use bract::lexer::Lexer;  // Currently unused
 use bract::parser::Parser;
 use bract::semantic::{SemanticAnalyzer, OwnershipAnalyzer, EscapeAnalyzer, SymbolTable};
-use bract::codegen::CodegenPipeline;
+use bract::codegen::{CodegenPipeline, CodegenOptions, LinkOptions, OptLevel};
+use bract::codegen::cranelift::CraneliftCodeGenerator;
 use bract::ast::*;
 use bract::parser::StringInterner;
 
@@ -244,13 +245,1282 @@ mod phase3_backend_tests {
         // Create required dependencies
         let symbol_table = SymbolTable::new();
         let interner = StringInterner::new();
-        let mut pipeline = CodegenPipeline::new(symbol_table, interner).expect("Pipeline creation failed");
+        let mut pipeline = CodegenPipeline::new(symbol_table, interner, CodegenOptions::default()).expect("Pipeline creation failed");
         let result = pipeline.compile_module(&module);
         
         // Should compile to some form of output
         assert!(result.is_ok() || result.is_err()); // Basic smoke test
     }
     
+    #[test]
+    fn test_region_block_codegen() {
+        let source = r#"
+            fn main() -> i32 {
+                region outer {
+                    let a: RegionPtr<Pair> = Pair { x: 1, y: 2 };
+                    region inner {
+                        let b: RegionPtr<Pair> = Pair { x: 3, y: 4 };
+                        return 1;
+                    }
+                }
+                return 0;
+            }
+        "#;
+        
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+        
+        let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), interner, CodegenOptions::default())
+            .expect("Code generator creation failed");
+        let object = generator.generate(&module).expect("Region blocks should compile");
+        assert!(!object.is_empty());
+        
+        // Nested region blocks each get their own region
+        let report = generator.memory_manager().get_region_efficiency_report();
+        assert!(report.contains("'main::outer'"));
+        assert!(report.contains("'main::inner'"));
+        assert!(report.contains("Total Regions: 2"));
+    }
+    
+    #[test]
+    fn test_function_region_annotation_codegen() {
+        let source = r#"
+            @region(scratch, grow)
+            fn main() -> i32 {
+                let a: RegionPtr<Pair> = Pair { x: 1, y: 2 };
+                return 0;
+            }
+        "#;
+        
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+        
+        let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), interner, CodegenOptions::default())
+            .expect("Code generator creation failed");
+        generator.generate(&module).expect("Annotated region should compile");
+        
+        // The region lives for the whole function, outside any region block
+        let report = generator.memory_manager().get_region_efficiency_report();
+        assert!(report.contains("'main::scratch'"), "{}", report);
+        assert!(report.contains("Total Regions: 1"), "{}", report);
+    }
+    
+    #[test]
+    fn test_invalid_memory_annotations_are_rejected() {
+        for (source, message) in [
+            ("@memory(region = \"frame\") fn main() -> i32 { return 0; }", "moves.bract:1:1: Memory management error: Region 'frame' is not declared"),
+            ("@align(3) fn main() -> i32 { return 0; }", "Alignment 3 must be a power of two"),
+            ("@memory(size_hint = 0) fn main() -> i32 { return 0; }", "Size hint must be positive"),
+            ("fn main() -> i32 {\n    @memory(size_hint = 0) region frame { let x = 1; }\n    return 0;\n}", "moves.bract:2:5: Memory management error: Size hint of region 'frame' must be positive"),
+        ] {
+            let error = compile_source(source).expect_err(source);
+            assert!(error.contains(message), "{}: {}", source, error);
+        }
+        
+        // Naming a region block of the body is fine
+        compile_source("@memory(region = \"frame\") fn main() -> i32 { region frame { let x = 1; } return 0; }")
+            .expect("Declared region should compile");
+    }
+    
+    #[test]
+    fn test_allocation_hotspots_use_source_locations() {
+        let source = r#"
+fn main() -> i32 {
+    let a: SmartPtr<Pair> = Pair { x: 1, y: 2 };
+    let b: SmartPtr<Pair> = Pair { x: 3, y: 4 };
+    let c: SmartPtr<Pair> = Pair { x: 5, y: 6 };
+    return 0;
+}
+"#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+        
+        let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), interner, CodegenOptions::default())
+            .expect("Code generator creation failed");
+        generator.set_source_map(SourceMap::new("main.bract")).expect("Setting the source map failed");
+        generator.generate(&module).expect("Allocations should compile");
+        
+        // One hotspot per allocating line, named after the file rather than the generator
+        let mut locations: Vec<_> = generator.memory_manager().get_top_allocation_hotspots(10).iter()
+            .map(|hotspot| hotspot.location.clone())
+            .collect();
+        locations.sort();
+        assert_eq!(locations.len(), 3, "{:?}", locations);
+        for (location, line) in locations.iter().zip(3..) {
+            assert!(location.starts_with(&format!("main.bract:{}:", line)), "{}", location);
+            assert!(location.ends_with("(in main)"), "{}", location);
+        }
+        
+        let report = generator.memory_manager().get_performance_report();
+        assert_eq!(report.matches("main.bract\n").count(), 1, "hotspots must be grouped by file:\n{}", report);
+        assert!(!report.contains("codegen"), "{}", report);
+    }
+    
+    fn compile_source(source: &str) -> Result<Vec<u8>, String> {
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+        
+        let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), interner, CodegenOptions::default())
+            .expect("Code generator creation failed");
+        generator.set_source_file("moves.bract").expect("Setting the source file failed");
+        generator.generate(&module).map_err(|e| e.to_string())
+    }
+    
+    /// Compile, link and run `source`, returning its exit code (None when no system linker is available)
+    fn run_program(source: &str) -> Option<i32> {
+        run_program_status(source).and_then(|status| status.code())
+    }
+    
+    /// Compile, link and run `source`, returning how it exited (None when no system linker is available)
+    fn run_program_status(source: &str) -> Option<std::process::ExitStatus> {
+        bract::codegen::link::find_linker()?;
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let output = dir.path().join(if cfg!(windows) { "program.exe" } else { "program" });
+        CodegenPipeline::compile_to_executable(source, &output, CodegenOptions::debug(), LinkOptions::default())
+            .expect("Compiling to an executable failed");
+        
+        Some(std::process::Command::new(&output).status().expect("Failed to run executable"))
+    }
+    
+    #[test]
+    fn test_linear_move_checking() {
+        let valid = r#"
+            fn consume(buffer: i64) -> i32 {
+                return 0;
+            }
+            
+            fn main() -> i32 {
+                let a: LinearPtr<Buffer> = Buffer { len: 1 };
+                let b = a;
+                return consume(b);
+            }
+        "#;
+        assert!(compile_source(valid).is_ok());
+        
+        let use_after_move = r#"
+            fn main() -> i32 {
+                let a: LinearPtr<Buffer> = Buffer { len: 1 };
+                let b = a;
+                let c = a;
+                return 0;
+            }
+        "#;
+        let error = compile_source(use_after_move).expect_err("use after move should be rejected");
+        assert!(error.contains("Use after move"), "{}", error);
+        assert!(error.contains("moves.bract:5:"), "{}", error);
+        assert!(error.contains("moved at moves.bract:4:"), "{}", error);
+        
+        let use_after_call = r#"
+            fn consume(buffer: i64) -> i32 {
+                return 0;
+            }
+            
+            fn main() -> i32 {
+                let a: LinearPtr<Buffer> = Buffer { len: 1 };
+                consume(a);
+                return consume(a);
+            }
+        "#;
+        let error = compile_source(use_after_call).expect_err("use after passing by value should be rejected");
+        assert!(error.contains("moves.bract:9:"), "{}", error);
+        
+        let methods = r#"
+            struct Buffer { len: i64 }
+            
+            impl Buffer {
+                fn size(&self) -> i64 { return self.len; }
+                fn close(self) -> i32 { return 0; }
+            }
+            
+            fn main() -> i32 {
+                let a: LinearPtr<Buffer> = Buffer { len: 1 };
+                a.size();
+                a.close();
+                return a.close();
+            }
+        "#;
+        let error = compile_source(methods).expect_err("use after a by-value self call should be rejected");
+        assert!(error.contains("Use after move"), "{}", error);
+        assert!(error.contains("moves.bract:13:"), "{}", error);
+        assert!(error.contains("moved at moves.bract:12:"), "{}", error);
+    }
+    
+    /// ELF `e_machine` of an object file
+    fn elf_machine(object: &[u8]) -> u16 {
+        assert_eq!(&object[..4], b"\x7fELF", "expected an ELF object");
+        u16::from_le_bytes([object[18], object[19]])
+    }
+    
+    #[test]
+    fn test_cross_compilation_targets() {
+        const EM_X86_64: u16 = 62;
+        const EM_AARCH64: u16 = 183;
+        let source = r#"
+            fn main() -> i32 {
+                let values = [1, 2, 3];
+                return values[1];
+            }
+        "#;
+        
+        for (target, machine) in [("x86_64-unknown-linux-gnu", EM_X86_64), ("aarch64-unknown-linux-gnu", EM_AARCH64)] {
+            let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+            let module = parser.parse_module().expect("Parsing failed");
+            let mut pipeline = CodegenPipeline::new_with_target(SymbolTable::new(), parser.take_interner(), target, CodegenOptions::default())
+                .expect("Target should be supported");
+            pipeline.set_pic(true).expect("PIC should be configurable");
+            
+            let object = pipeline.compile_module(&module).expect("Cross compilation failed");
+            assert_eq!(elf_machine(&object), machine, "wrong machine for {}", target);
+        }
+        
+        let error = CodegenPipeline::new_with_target(SymbolTable::new(), StringInterner::new(), "sparc-unknown-linux-gnu", CodegenOptions::default())
+            .err()
+            .expect("SPARC is not a Cranelift target");
+        assert!(error.contains("Supported targets"), "{}", error);
+        assert!(error.contains("x86_64-unknown-linux-gnu"), "{}", error);
+    }
+    
+    #[test]
+    fn test_codegen_options() {
+        let source = r#"
+            fn main() -> i32 {
+                let x = 40;
+                return x + 2;
+            }
+        "#;
+        
+        for options in [CodegenOptions::debug(), CodegenOptions::release(), CodegenOptions::default()] {
+            let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+            let module = parser.parse_module().expect("Parsing failed");
+            let mut pipeline = CodegenPipeline::new(SymbolTable::new(), parser.take_interner(), options.clone())
+                .expect("Pipeline creation failed");
+            assert!(pipeline.compile_module(&module).is_ok(), "failed with {:?}", options);
+        }
+        assert_eq!(CodegenOptions::debug().opt_level, OptLevel::None);
+    }
+    
+    #[test]
+    fn test_verifier_reports_malformed_ir() {
+        // Arithmetic is lowered with integer instructions, so adding f64 values is ill-typed IR
+        let source = r#"
+            fn widen(a: f64) -> f64 {
+                return a + a;
+            }
+        "#;
+        
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let options = CodegenOptions { enable_verifier: true, ..CodegenOptions::default() };
+        let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), parser.take_interner(), options)
+            .expect("Code generator creation failed");
+        
+        let error = generator.generate(&module).expect_err("verifier should reject ill-typed IR").to_string();
+        assert!(error.contains("Failed to define function 'widen'"), "{}", error);
+        assert!(error.contains("has an invalid controlling type f64"), "{}", error);
+    }
+    
+    #[test]
+    fn test_compile_to_executable() {
+        let source = r#"
+            fn main() -> i32 {
+                let x = 3;
+                return x + 4;
+            }
+        "#;
+        let Some(exit_code) = run_program(source) else {
+            println!("Skipping: no system linker available");
+            return;
+        };
+        assert_eq!(exit_code, 7);
+        
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let output = dir.path().join("program");
+        let bad_link = LinkOptions {
+            libraries: vec!["bract_no_such_library".to_string()],
+            ..LinkOptions::default()
+        };
+        let error = CodegenPipeline::compile_to_executable(source, &output, CodegenOptions::debug(), bad_link)
+            .expect_err("Linking a missing library should fail");
+        assert!(error.contains("bract_no_such_library"), "{}", error);
+    }
+
+    #[test]
+    fn test_deterministic_object_output() {
+        let source = r#"
+            struct Point { x: i64, y: i64 }
+            enum Shape { Dot, Line(i64), Box { w: i64, h: i64 } }
+
+            fn area(shape: Shape) -> i64 {
+                match shape {
+                    Shape::Dot => 0,
+                    Shape::Line(length) => length,
+                    Shape::Box { w, h } => w * h,
+                }
+            }
+
+            fn main() -> i32 {
+                let p = Point { x: 3, y: 4 };
+                let twice = |v: i64| -> i64 { v * 2 };
+                return (twice(p.x + p.y) + area(Shape::Box { w: 2, h: 5 })) as i32;
+            }
+        "#;
+        let compile = |source: &str| {
+            let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+            let module = parser.parse_module().expect("Parsing failed");
+            let interner = parser.take_interner();
+            let analysis = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+            assert!(analysis.errors.is_empty(), "{:?}", analysis.errors);
+
+            let mut pipeline = CodegenPipeline::new(analysis.symbol_table, interner, CodegenOptions::default())
+                .expect("Pipeline creation failed");
+            let fingerprint = pipeline.module_fingerprint(&module);
+            (fingerprint, pipeline.compile_module(&module).expect("Compilation failed"))
+        };
+
+        let (first_fingerprint, first_object) = compile(source);
+        let (second_fingerprint, second_object) = compile(source);
+        assert_eq!(first_fingerprint, second_fingerprint);
+        assert!(first_object == second_object, "identical sources produced different objects");
+
+        // Renaming a single identifier changes the fingerprint
+        let (renamed_fingerprint, _) = compile(&source.replace("twice", "double"));
+        assert_ne!(first_fingerprint, renamed_fingerprint);
+
+        // The object is named after its source file
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), parser.take_interner(), CodegenOptions::default())
+            .expect("Code generator creation failed");
+        generator.set_source_file("examples/shapes.bract").expect("Setting the source file failed");
+        let object = generator.generate(&module).expect("Compilation failed");
+        assert!(object.windows(b"shapes".len()).any(|w| w == b"shapes"));
+        assert!(!object.windows(b"bract_program".len()).any(|w| w == b"bract_program"));
+    }
+
+    #[test]
+    fn test_profile_instrumentation() {
+        let source = r#"
+            fn square(x: i32) -> i32 {
+                if x < 0 {
+                    return 0 - x * x;
+                }
+                return x * x;
+            }
+
+            fn main() -> i32 {
+                return square(3);
+            }
+        "#;
+        let compile = |options: CodegenOptions| {
+            let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+            let module = parser.parse_module().expect("Parsing failed");
+            let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), parser.take_interner(), options)
+                .expect("Code generator creation failed");
+            generator.generate(&module).expect("Compilation failed")
+        };
+        let contains = |object: &[u8], needle: &str| object.windows(needle.len()).any(|w| w == needle.as_bytes());
+
+        let profiled = CodegenOptions { profile_instrumentation: true, ..CodegenOptions::debug() };
+        let object = compile(profiled.clone());
+        assert!(contains(&object, "bract_profile_enter"));
+        assert!(contains(&object, "bract_profile_exit"));
+        assert!(contains(&object, "bract_profile_functions"));
+        assert!(contains(&object, "0 square\n1 main\n"));
+
+        // Without the flag not a single hook is referenced
+        for options in [CodegenOptions::debug(), CodegenOptions::release()] {
+            assert!(!contains(&compile(options), "bract_profile"));
+        }
+
+        // The runtime provides the hooks, and instrumentation leaves results unchanged
+        if bract::codegen::link::find_linker().is_none() {
+            println!("Skipping: no system linker available");
+            return;
+        }
+        let dir = tempfile::tempdir().expect("Failed to create temp dir");
+        let output = dir.path().join(if cfg!(windows) { "program.exe" } else { "program" });
+        CodegenPipeline::compile_to_executable(source, &output, profiled, LinkOptions::default())
+            .expect("Compiling to an executable failed");
+        let status = std::process::Command::new(&output).status().expect("Failed to run executable");
+        assert_eq!(status.code(), Some(9));
+    }
+
+    #[test]
+    fn test_debug_info() {
+        use object::{Object, ObjectSection, ObjectSymbol, RelocationTarget};
+
+        let source = "fn square(x: i32) -> i32 {\n    let y = x * x;\n    return y;\n}\n\nfn main() -> i32 {\n    return square(3);\n}\n";
+        let compile = |options: CodegenOptions| {
+            let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+            let module = parser.parse_module().expect("Parsing failed");
+            let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), parser.take_interner(), options)
+                .expect("Code generator creation failed");
+            generator.set_source_file("square.bract").expect("Setting the source file failed");
+            generator.generate(&module).expect("Compilation failed")
+        };
+
+        // Without the flag the object has no debug sections at all
+        for options in [CodegenOptions::debug(), CodegenOptions::release()] {
+            let plain = compile(options);
+            let file = object::File::parse(&*plain).expect("Unreadable object");
+            assert!(file.sections().all(|section| !section.name().unwrap_or("").starts_with(".debug")));
+        }
+
+        let object = compile(CodegenOptions { emit_debug_info: true, ..CodegenOptions::debug() });
+        let file = object::File::parse(&*object).expect("Unreadable object");
+        for name in [".debug_info", ".debug_abbrev", ".debug_line", ".debug_str"] {
+            assert!(file.section_by_name(name).is_some(), "missing {}", name);
+        }
+
+        // Functions keep their source names, and the line program starts at each of them
+        let strings = file.section_by_name(".debug_str").unwrap().data().unwrap().to_vec();
+        let contains = |needle: &str| strings.windows(needle.len()).any(|w| w == needle.as_bytes());
+        assert!(contains("square\0") && contains("main\0") && contains("square.bract\0"));
+        let line_section = file.section_by_name(".debug_line").unwrap();
+        let line_targets: Vec<String> = line_section.relocations()
+            .filter_map(|(_, relocation)| match relocation.target() {
+                RelocationTarget::Symbol(index) => file.symbol_by_index(index).ok()?.name().ok().map(str::to_string),
+                _ => None,
+            })
+            .collect();
+        assert!(line_targets.contains(&"square".to_string()), "{:?}", line_targets);
+        assert!(line_targets.contains(&"main".to_string()), "{:?}", line_targets);
+    }
+
+    #[test]
+    fn test_loop_control_flow_codegen() {
+        let while_with_returns = r#"
+            fn classify(n: i32) -> i32 {
+                let mut i = 0;
+                while i < n {
+                    if i == 3 {
+                        return 1;
+                    } else {
+                        return 2;
+                    }
+                }
+                return 0;
+            }
+            fn main() -> i32 { return classify(5); }
+        "#;
+        let nested_break = r#"
+            fn main() -> i32 {
+                let mut i = 0;
+                let mut total = 0;
+                while i < 100 {
+                    i = i + 1;
+                    if i > 2 {
+                        if i == 5 {
+                            break;
+                        }
+                        total = total + i;
+                    } else {
+                        continue;
+                    }
+                }
+                return total;
+            }
+        "#;
+        let empty_body = r#"
+            fn main() -> i32 {
+                let x = 0;
+                while x > 1 {
+                }
+                return x;
+            }
+        "#;
+        
+        for source in [while_with_returns, nested_break, empty_body] {
+            if let Err(error) = compile_source(source) {
+                panic!("Control flow failed to compile: {}\n{}", error, source);
+            }
+        }
+        
+        // 3 + 4 before breaking out at 5
+        if let Some(exit_code) = run_program(nested_break) {
+            assert_eq!(exit_code, 7);
+        }
+    }
+    
+    #[test]
+    fn test_return_type_reconciliation() {
+        let converted = r#"
+            fn widen() -> i64 { return 5; }
+            fn narrow() -> u8 { 7 }
+            fn half() -> f32 { return 0.5; }
+            fn main() -> i32 { return 0; }
+        "#;
+        if let Err(error) = compile_source(converted) {
+            panic!("Return values should be converted: {}", error);
+        }
+        
+        let mismatched = r#"
+            fn ratio() -> f64 { return 1; }
+        "#;
+        let error = compile_source(mismatched).expect_err("i32 cannot be returned as f64");
+        assert!(error.contains("Type conversion error"), "{}", error);
+        assert!(error.contains("'ratio' is declared to return f64 but returns i32"), "{}", error);
+        
+        let missing = r#"
+            fn answer() -> i32 { return; }
+        "#;
+        let error = compile_source(missing).expect_err("return without a value");
+        assert!(error.contains("'answer' is declared to return i32 but returns nothing"), "{}", error);
+    }
+    
+    #[test]
+    fn test_condition_representation() {
+        let source = r#"
+            fn main() -> i32 {
+                let flag: bool = true;
+                let count = 3;
+                let mut score = 0;
+                if flag { score = score + 1; }
+                if score < 5 { score = score + 2; }
+                if count { score = score + 4; }
+                if !flag || count > 2 && flag { score = score + 8; }
+                while !(score > 16) { score = score + 16; }
+                return score;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Conditions failed to compile: {}", error);
+        }
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 31);
+        }
+        
+        let call_condition = r#"
+            fn is_even(n: i32) -> bool {
+                return n % 2 == 0;
+            }
+            fn main() -> i32 {
+                if is_even(4) { return 1; }
+                while !is_even(3) { return 2; }
+                return 0;
+            }
+        "#;
+        if let Err(error) = compile_source(call_condition) {
+            panic!("Call conditions failed to compile: {}", error);
+        }
+        
+        let float_condition = r#"
+            fn main() -> i32 {
+                if 1.5 { return 1; }
+                return 0;
+            }
+        "#;
+        let error = compile_source(float_condition).expect_err("floats have no truth value");
+        assert!(error.contains("Condition must be a bool or integer, found f64"), "{}", error);
+    }
+    
+    #[test]
+    fn test_if_expression_result_types() {
+        let source = r#"
+            struct Point { x: i32, y: i32 }
+            fn pick(c: bool) -> i32 {
+                let p: Point = if c { Point { x: 1, y: 2 } } else { Point { x: 3, y: 4 } };
+                let unit = if c { 5 };
+                return 0;
+            }
+            fn main() -> i32 {
+                let c: bool = true;
+                let base: i64 = 50000;
+                let big: i64 = base * base;
+                let x: i64 = if c { big } else { 0 };
+                let y: i64 = if c { 0 } else { big };
+                let z: f64 = if c { 1.5 } else { return 9; };
+                if x == big {
+                    return 7;
+                }
+                return 1;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("If expressions failed to compile: {}", error);
+        }
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 7);
+        }
+        
+        let mismatched = r#"
+            fn main() -> i32 {
+                let c: bool = true;
+                let z = if c { 1.5 } else { 2 };
+                return 0;
+            }
+        "#;
+        let error = compile_source(mismatched).expect_err("f64 and i32 branches don't unify");
+        assert!(error.contains("moves.bract:4:"), "{}", error);
+        assert!(error.contains("then branch is f64, else branch is i32"), "{}", error);
+        
+        let error = compile_source("fn main() -> i32 { let f: f64 = 1; return 0; }").expect_err("no implicit int to float");
+        assert!(error.contains("cannot initialize f64 variable from i32 value"), "{}", error);
+    }
+    
+    #[test]
+    fn test_array_bounds_checking() {
+        let source = r#"
+            fn main() -> i32 {
+                let values = [3, 5, 7, 9];
+                let i = 2;
+                let first = values[0];
+                return values[i] + first - 3;
+            }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), parser.take_interner(), CodegenOptions::default())
+            .expect("Code generator creation failed");
+        generator.generate(&module).expect("Array indexing should compile");
+        // Only the dynamic index needs a runtime check
+        assert_eq!(generator.memory_manager().metrics.bounds_violations_prevented, 1);
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 7);
+        }
+        
+        let out_of_bounds = r#"
+            fn main() -> i32 {
+                let values = [3, 5, 7, 9];
+                let i = 4;
+                return values[i];
+            }
+        "#;
+        if let Some(status) = run_program_status(out_of_bounds) {
+            assert!(!status.success(), "Out-of-bounds read should trap, got {}", status);
+            assert_eq!(status.code(), None, "Out-of-bounds read should trap, got {}", status);
+        }
+        
+        let constant_out_of_bounds = r#"
+            fn main() -> i32 {
+                let values: [i32; 2] = [1, 2];
+                return values[2];
+            }
+        "#;
+        let error = compile_source(constant_out_of_bounds).expect_err("constant index past the end");
+        assert!(error.contains("moves.bract:4:"), "{}", error);
+        assert!(error.contains("index 2 is out of bounds for array of length 2"), "{}", error);
+    }
+    
+    #[test]
+    fn test_array_element_stores() {
+        let source = r#"
+            fn main() -> i32 {
+                let values = [0, 0, 0];
+                let i = 1;
+                values[i] = 4;
+                values[2] = values[i] + 3;
+                let wide: i64 = 5;
+                values[0] = wide;
+                return values[2] - values[0] + values[1] + 1;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Array stores failed to compile: {}", error);
+        }
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 7);
+        }
+        
+        // Stores go through the same bounds check as reads
+        let out_of_bounds = r#"
+            fn main() -> i32 {
+                let values = [1, 2];
+                let i = 2;
+                values[i] = 3;
+                return 0;
+            }
+        "#;
+        if let Some(status) = run_program_status(out_of_bounds) {
+            assert_eq!(status.code(), None, "Out-of-bounds store should trap, got {}", status);
+        }
+        
+        let error = compile_source("fn main() -> i32 { let values = [1, 2]; values[5] = 1; return 0; }")
+            .expect_err("constant index past the end");
+        assert!(error.contains("index 5 is out of bounds for array of length 2"), "{}", error);
+        
+        let error = compile_source("fn main() -> i32 { let values = [1, 2]; values[0] = 1.5; return 0; }")
+            .expect_err("floats can't be stored into i32 arrays");
+        assert!(error.contains("cannot store f64 value into an i32 array element"), "{}", error);
+    }
+    
+    #[test]
+    fn test_array_repeat_and_element_types() {
+        let source = r#"
+            fn main() -> i32 {
+                let small = [7; 4];
+                let zeroed: [u8; 64 * 2] = [0; 128];
+                let i = 100;
+                zeroed[i] = 255;
+                zeroed[3] = 256 + 9;
+                let wide: [i64; 3] = [1, 2, 3];
+                let total = wide[0] + wide[1] + wide[2];
+                if total == 6 && zeroed[i] == 255 {
+                    return small[3] + zeroed[3] + zeroed[127];
+                }
+                return 1;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Array repeats failed to compile: {}", error);
+        }
+        // u8 elements are stored truncated and read zero-extended
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 16);
+        }
+        
+        let error = compile_source("fn main() -> i32 { let values: [i32; 4] = [1, 2, 3]; return 0; }")
+            .expect_err("literal shorter than the annotated length");
+        assert!(error.contains("expected an array of 4 elements, found 3 elements"), "{}", error);
+        
+        let error = compile_source("fn main() -> i32 { let values = [0; 8]; return values[4 + 4]; }")
+            .expect_err("folded constant index past the end");
+        assert!(error.contains("index 8 is out of bounds for array of length 8"), "{}", error);
+    }
+    
+    #[test]
+    fn test_narrow_integer_arithmetic() {
+        let source = r#"
+            fn main() -> i32 {
+                let a: u8 = 200;
+                let b: u8 = 100;
+                let sum = a + b;
+                let big: u8 = 250;
+                let widened: i32 = big;
+                let c: i8 = -1;
+                let d: i32 = c;
+                let mut x: u8 = 0;
+                x = widened + 10;
+                let half = big / 2;
+                if big > b && widened == 250 && d == -1 && half == 125 {
+                    return sum + x;
+                }
+                return 1;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Narrow integer arithmetic failed to compile: {}", error);
+        }
+        // u8 arithmetic wraps (200 + 100 = 44), u8 values zero-extend, i8 values sign-extend
+        // and assigning 260 to a u8 truncates it to 4
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 48);
+        }
+    }
+    
+    #[test]
+    fn test_integer_literal_suffixes() {
+        let source = r#"
+            fn main() -> i32 {
+                let big: i64 = 5_000_000_000;
+                let wide = 3_000_000_000i64 + 2_000_000_000;
+                let byte = 0xFFu8;
+                let minus = 0xFFi8;
+                let widened: i32 = byte;
+                let signed: i32 = minus;
+                let ratio = 1f32;
+                if big == wide && big / 1_000_000_000 == 5 && widened == 255 && signed == -1 {
+                    return 42;
+                }
+                return 1;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Suffixed integer literals failed to compile: {}", error);
+        }
+        // Literals are emitted at their own width: no i32 truncation of 5_000_000_000,
+        // and 0xFFu8 zero-extends where 0xFFi8 (-1) sign-extends
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 42);
+        }
+    }
+    
+    #[test]
+    fn test_let_bindings_use_inferred_types() {
+        // None of these bindings is annotated: each takes the type semantic analysis
+        // inferred for it instead of defaulting to i32
+        let source = r#"
+            fn wide() -> i64 {
+                return 5_000_000_000;
+            }
+            
+            fn main() -> i32 {
+                let big = wide();
+                let doubled = (big * 2);
+                let byte = { 0xF0u8 };
+                let widened: i32 = byte;
+                if doubled / 1_000_000_000 == 10 && widened == 240 {
+                    return 42;
+                }
+                return 1;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Inferred let bindings failed to compile: {}", error);
+        }
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 42);
+        }
+    }
+    
+    #[test]
+    fn test_escape_analysis_picks_allocation_strategies() {
+        // Count the allocations of each strategy codegen picks for `source`
+        fn allocation_counts(source: &str) -> (u64, u64) {
+            let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+            let module = parser.parse_module().expect("Parsing failed");
+            let interner = parser.take_interner();
+            let analysis = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+            assert!(analysis.errors.is_empty(), "{:?}", analysis.errors);
+            
+            let mut generator = CraneliftCodeGenerator::new(analysis.symbol_table, interner, CodegenOptions::default())
+                .expect("Code generator creation failed");
+            generator.generate(&module).expect("Struct allocations should compile");
+            let metrics = &generator.memory_manager().metrics;
+            (metrics.stack_allocs, metrics.manual_allocs)
+        }
+        
+        // A struct returned from its function must outlive the frame
+        let returned = r#"
+            struct Point { x: i32, y: i32 }
+            
+            fn make(x: i32) -> Point {
+                let point = Point { x: x, y: 2 };
+                return point;
+            }
+            
+            fn main() -> i32 {
+                let point: Point = make(40);
+                return point.x + point.y;
+            }
+        "#;
+        assert_eq!(allocation_counts(returned), (0, 1));
+        if let Some(exit_code) = run_program(returned) {
+            assert_eq!(exit_code, 42);
+        }
+        
+        // One that stays in its function lives on the stack
+        let local = r#"
+            struct Point { x: i32, y: i32 }
+            
+            fn main() -> i32 {
+                let point = Point { x: 40, y: 2 };
+                return point.x + point.y;
+            }
+        "#;
+        assert_eq!(allocation_counts(local), (1, 0));
+        if let Some(exit_code) = run_program(local) {
+            assert_eq!(exit_code, 42);
+        }
+    }
+    
+    #[test]
+    fn test_calls_use_declared_signatures() {
+        // main refers to functions defined after it; is_even and is_odd recurse into each
+        // other with different arities, and sum_to calls a function nested in its body
+        let source = r#"
+            fn main() -> i32 {
+                if is_even(10) && is_odd(7, 1) {
+                    return sum_to(4, 0);
+                }
+                return 1;
+            }
+            
+            fn is_even(n: i32) -> bool {
+                if n == 0 {
+                    return true;
+                }
+                return is_odd(n - 1, 1);
+            }
+            
+            fn is_odd(n: i32, step: i32) -> bool {
+                if n == 0 {
+                    return false;
+                }
+                return is_even(n - step);
+            }
+            
+            fn sum_to(n: i32, acc: i32) -> i32 {
+                fn double(x: i32) -> i32 {
+                    return x * 2;
+                }
+                if n == 0 {
+                    return double(acc);
+                }
+                return sum_to(n - 1, acc + n);
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Calls failed to compile: {}", error);
+        }
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 20);
+        }
+        
+        let wrong_arity = r#"
+            fn main() -> i32 {
+                return add(1);
+            }
+            fn add(a: i32, b: i32) -> i32 {
+                return a + b;
+            }
+        "#;
+        let error = compile_source(wrong_arity).expect_err("arity mismatch");
+        assert!(error.contains("moves.bract:3:"), "{}", error);
+        assert!(error.contains("function 'add' takes 2 arguments but 1 was given"), "{}", error);
+        
+        let wrong_type = r#"
+            fn main() -> i32 {
+                return half(1.5);
+            }
+            fn half(a: i32) -> i32 {
+                return a / 2;
+            }
+        "#;
+        let error = compile_source(wrong_type).expect_err("float argument for an i32 parameter");
+        assert!(error.contains("argument 1 of 'half' expects i32 but got f64"), "{}", error);
+    }
+    
+    #[test]
+    fn test_unary_operators() {
+        let source = r#"
+            fn bump(counter: &mut i64, by: i64) {
+                *counter = *counter + by;
+            }
+            
+            fn main() -> i32 {
+                let big: i64 = 3;
+                let neg = -big;
+                let half = -2.5;
+                let mask: u8 = 240;
+                let low = ~mask;
+                let mut total: i64 = 0;
+                bump(&mut total, 4);
+                let r = &total;
+                let t = *r;
+                return t + neg + low;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Unary operators failed to compile: {}", error);
+        }
+        // 4 + -3 + 15: the i64 negation keeps its width and ~240 on a u8 is 15
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 16);
+        }
+        
+        let error = compile_source("fn main() -> i32 { let x = 5; return *x; }").expect_err("x is not a reference");
+        assert!(error.contains("cannot dereference a value that is not a known reference"), "{}", error);
+        
+        let error = compile_source("fn main() -> i32 { let f = ~1.5; return 0; }").expect_err("~ on a float");
+        assert!(error.contains("bitwise not requires an integer operand, found f64"), "{}", error);
+    }
+
+    #[test]
+    fn test_cast_expressions() {
+        let source = r#"
+            fn main() -> i32 {
+                let big: i64 = 300;
+                let byte = big as u8;
+                let wide = byte as i64;
+                let neg: i8 = -2;
+                let ratio = -3.75;
+                let truncated = (ratio as f32) as i32 + 7 as f64 as i32;
+                let back = neg as i32 + (byte as f32) as i32;
+                return wide as i32 + truncated + back;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Cast expressions failed to compile: {}", error);
+        }
+        // 300 as u8 is 44 and zero-extends back; -3.75 truncates to -3 and 7 round-trips through f64; -2 sign-extends: 44 + 4 + 42
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 90);
+        }
+
+        let error = compile_source("fn main() -> i32 { let x: i32 = 1; let r = &x; let f = r as f64; return 0; }")
+            .expect_err("a pointer is not a number");
+        assert!(error.contains("cannot cast pointer to f64"), "{}", error);
+
+        let error = compile_source("fn main() -> i32 { let x = 1 as str; return 0; }").expect_err("str is not numeric");
+        assert!(error.contains("cannot cast i32 to str"), "{}", error);
+    }
+
+    #[test]
+    fn test_closures() {
+        let source = r#"
+            fn apply(f: fn(i32) -> i32, v: i32) -> i32 {
+                return f(v);
+            }
+
+            fn main() -> i32 {
+                let inc = |x: i32| x + 1;
+                let add = |a: i32, b: i32| -> i32 { a + b };
+                return add(inc(2), apply(|n: i32| n * 10, 4));
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Closures failed to compile: {}", error);
+        }
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 43);
+        }
+
+        let error = compile_source("fn main() -> i32 { let y = 1; let f = |x: i32| x + y; return f(1); }")
+            .expect_err("captures are not supported");
+        assert!(error.contains("captured variable 'y' not supported yet"), "{}", error);
+        assert!(error.contains(":1:52"), "{}", error);
+
+        let error = compile_source("fn main() -> i32 { let f = |x| x; return 0; }").expect_err("untyped parameter");
+        assert!(error.contains("closure parameter needs a type annotation"), "{}", error);
+    }
+
+    #[test]
+    fn test_tuples() {
+        let source = r#"
+            fn minmax(a: i32, b: i32) -> (i32, i32) {
+                if a < b {
+                    return (a, b);
+                }
+                (b, a)
+            }
+
+            fn main() -> i32 {
+                let (lo, hi) = minmax(9, 4);
+                let pair = (lo, (hi, 100));
+                let (_, (inner, _)) = pair;
+                return lo * 10 + hi + pair.1.1 + inner;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Tuples failed to compile: {}", error);
+        }
+        // 4 * 10 + 9 + 100 + 9
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 158);
+        }
+
+        let error = compile_source("fn main() -> i32 { let (a, b) = (1, 2, 3); return a; }")
+            .expect_err("arity mismatch");
+        assert!(error.contains("tuple pattern has 2 elements but the value has type (i32, i32, i32)"), "{}", error);
+
+        let error = compile_source("fn pair() -> (i32, i32) { return (1, 2, 3); } fn main() -> i32 { return 0; }")
+            .expect_err("wrong tuple returned");
+        assert!(error.contains("declared to return (i32, i32) but returns (i32, i32, i32)"), "{}", error);
+    }
+
+    #[test]
+    fn test_recursive_structs() {
+        // Analysis rejects a struct holding itself; code generation stops on one it missed
+        let error = compile_source("struct Node {\n    value: i32,\n    next: Node,\n}\nfn main() -> i32 { return 0; }")
+            .expect_err("struct containing itself");
+        assert!(error.contains("moves.bract:3:5:"), "{}", error);
+        assert!(error.contains("'Node' holds types by value more than 64 levels deep"), "{}", error);
+        
+        let error = compile_source("struct A { b: B }\nstruct B { a: A }\nfn main() -> i32 { return 0; }")
+            .expect_err("structs containing each other");
+        assert!(error.contains("a type containing itself needs a pointer"), "{}", error);
+        
+        compile_source("struct Node { value: i32, next: &Node }\nfn main() -> i32 { return 0; }")
+            .expect("a linked list holds the next node through a pointer");
+    }
+    
+    #[test]
+    fn test_enums_and_match() {
+        let source = r#"
+            enum Shape {
+                Circle { radius: i64 },
+                Rect(i64, i64),
+                Empty,
+            }
+
+            enum Color { Red = 1, Green, Blue = 10 }
+
+            enum Option { Some(i32), None }
+
+            fn make_shape(kind: i32) -> Shape {
+                if kind == 0 {
+                    return Shape::Circle { radius: 3 };
+                }
+                if kind == 1 {
+                    return Shape::Rect(4, 5);
+                }
+                return Shape::Empty;
+            }
+
+            fn area(shape: Shape) -> i64 {
+                match shape {
+                    Shape::Circle { radius } => radius * radius * 3,
+                    Shape::Rect(w, h) => w * h,
+                    Shape::Empty => 0,
+                }
+            }
+
+            fn unwrap_or(value: Option, fallback: i32) -> i32 {
+                match value {
+                    Some(x) if x > 0 => x,
+                    Some(_) => 0,
+                    None => fallback,
+                }
+            }
+
+            fn main() -> i32 {
+                let total = area(make_shape(0)) + area(make_shape(1)) + area(make_shape(2));
+                let green = Color::Green;
+                let shade = match green {
+                    Color::Red => 1,
+                    Color::Green => 2,
+                    _ => 3,
+                };
+                let mut digits = 0;
+                match total {
+                    0..10 => { digits = 1; }
+                    10..=99 => { digits = 2; }
+                    _ => { digits = 3; }
+                }
+                return total as i32 + green as i32 + shade + digits + unwrap_or(Some(5), 0) + unwrap_or(None, 100);
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Enums failed to compile: {}", error);
+        }
+        // Variants built in make_shape outlive its frame: 27 + 20 + 0, then 2 + 2 + 2 + 5 + 100
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 158);
+        }
+
+        let error = compile_source(r#"
+            enum Shape { Rect(i64, i64) }
+            fn main() -> i32 { let s = Shape::Rect(1); return 0; }
+        "#).expect_err("Rect has two fields");
+        assert!(error.contains("variant 'Shape::Rect' has 2 fields but 1 was given"), "{}", error);
+    }
+
+    #[test]
+    fn test_calls_use_declared_functions() {
+        let source = r#"
+            struct Point {
+                x: i32,
+                y: i32,
+            }
+
+            fn origin_offset(by: i32) -> Point {
+                return Point { x: by, y: by * 2 };
+            }
+
+            fn main() -> i32 {
+                return origin_offset(3).y + origin_offset(1).x;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Struct-returning calls failed to compile: {}", error);
+        }
+        // Call results know their declared struct type: 6 + 1
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 7);
+        }
+
+        let error = compile_source("fn main() -> i32 { return missing(1); }").expect_err("missing is not declared");
+        assert!(error.contains("Unknown function 'missing'"), "{}", error);
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let source = r#"
+            struct Point {
+                x: i32,
+                y: i32,
+            }
+
+            fn bump(counter: &mut i32) -> i32 {
+                let old = *counter;
+                *counter += 1;
+                return old;
+            }
+
+            fn main() -> i32 {
+                let mut total = 10;
+                total += 5;
+                total *= 3;
+                total -= 4;
+                total /= 2;
+                total %= 7;
+                let mut values = [1, 2, 3];
+                let mut calls = 0;
+                values[bump(&mut calls)] += 40;
+                let mut point = Point { x: 1, y: 2 };
+                point.y <<= 3;
+                point.x |= 6;
+                let mut small: u8 = 200;
+                small += 100;
+                return total + values[0] + calls + point.y + point.x + small;
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Compound assignments failed to compile: {}", error);
+        }
+        // 6 + 41 + 1 (the index is evaluated once) + 16 + 7 + 44 (300 wraps in a u8)
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 115);
+        }
+
+        let error = compile_source("fn main() -> i32 { missing += 1; return 0; }").expect_err("undeclared target");
+        assert!(error.contains("'missing' is not a declared variable"), "{}", error);
+    }
+
+    #[test]
+    fn test_impl_methods() {
+        let source = r#"
+            struct Rect {
+                width: i32,
+                height: i32,
+            }
+
+            impl Rect {
+                fn area(&self) -> i32 {
+                    return self.width * self.height;
+                }
+
+                fn scale(&mut self, by: i32) {
+                    self.width = self.width * by;
+                }
+
+                fn into_width(self) -> i32 {
+                    return self.width;
+                }
+            }
+
+            fn main() -> i32 {
+                let mut r = Rect { height: 3, width: 2 };
+                r.scale(2);
+                return r.area() + r.into_width();
+            }
+        "#;
+        if let Err(error) = compile_source(source) {
+            panic!("Impl methods failed to compile: {}", error);
+        }
+        // Fields are found by name, not initialization order: 4 * 3 + 4
+        if let Some(exit_code) = run_program(source) {
+            assert_eq!(exit_code, 16);
+        }
+
+        let error = compile_source(r#"
+            struct Rect { width: i32 }
+            fn main() -> i32 { let r = Rect { width: 1 }; return r.perimeter(); }
+        "#).expect_err("Rect has no perimeter method");
+        assert!(error.contains("no method 'perimeter' on struct 'Rect'"), "{}", error);
+
+        let error = compile_source(r#"
+            struct Rect { width: i32, height: i32 }
+            fn main() -> i32 { let r = Rect { width: 1 }; return r.width; }
+        "#).expect_err("height is not initialized");
+        assert!(error.contains("missing field 'height' in initializer of 'Rect'"), "{}", error);
+    }
+
     #[test]
     fn test_performance_contracts() {
         let source = r#"
@@ -442,6 +1712,53 @@ mod performance_tests {
         assert!(result.is_ok() || parser.errors().len() < 50);
     }
     
+    #[test]
+    fn test_codegen_performance_many_functions() {
+        // Every function calls the previous one, so each body sees the whole function table
+        let mut source = String::from("fn f0(x: i64) -> i64 { return x; }\n");
+        for i in 1..500 {
+            source.push_str(&format!("fn f{}(x: i64) -> i64 {{ return f{}(x) + 1; }}\n", i, i - 1));
+        }
+        source.push_str("fn main() -> i32 { return f499(0) as i32; }\n");
+        
+        let mut parser = Parser::new(&source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), parser.take_interner(), CodegenOptions::default())
+            .expect("Code generator creation failed");
+        
+        let start = Instant::now();
+        let object = generator.generate(&module).expect("Code generation failed");
+        let duration = start.elapsed();
+        
+        println!("Generating code for 500 functions took {:?}", duration);
+        assert!(!object.is_empty());
+        assert!(duration.as_millis() < 5000); // Less than 5 seconds
+    }
+
+    #[test]
+    fn test_codegen_parallel_matches_serial() {
+        // Small independent bodies with a little control flow and arithmetic each
+        let mut source = String::new();
+        for i in 0..200 {
+            source.push_str(&format!(
+                "fn g{i}(x: i64, y: i64) -> i64 {{ let mut total = x * {i}; if total > y {{ total = total - y; }} else {{ total = total + y * 2; }} return total; }}\n"
+            ));
+        }
+        source.push_str("fn main() -> i32 { return g199(1, 2) as i32; }\n");
+
+        let generate = |parallel: bool| {
+            let mut parser = Parser::new(&source, 0).expect("Parser creation failed");
+            let module = parser.parse_module().expect("Parsing failed");
+            let options = CodegenOptions { parallel, ..CodegenOptions::default() };
+            let mut generator = CraneliftCodeGenerator::new(SymbolTable::new(), parser.take_interner(), options)
+                .expect("Code generator creation failed");
+            generator.generate(&module).expect("Code generation failed")
+        };
+
+        // Functions are defined in source order either way, so the objects are identical
+        assert!(generate(false) == generate(true), "parallel codegen produced a different object");
+    }
+
     #[test]
     fn test_semantic_analysis_performance() {
         let source = r#"
@@ -471,6 +1788,490 @@ mod performance_tests {
         // Should analyze quickly
         assert!(duration.as_millis() < 1000); // Less than 1 second
     }
+    
+    #[test]
+    fn test_performance_analyzer_on_parsed_module() {
+        use bract::PerformanceAnalyzer;
+        use bract::performance::TargetArchitecture;
+        
+        let source = r#"
+            fn square(x: i32) -> i32 {
+                return x * x;
+            }
+            
+            fn main() -> i32 {
+                let y = square(3);
+                if y > 5 { return y; } else { return 0; }
+            }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+        
+        let analysis = PerformanceAnalyzer::new(TargetArchitecture::X86_64).analyze_module(&module, &interner);
+        
+        assert_eq!(analysis.stats.functions_analyzed, 2);
+        assert_eq!(analysis.stats.contracts_verified, 0);
+        assert!(analysis.violations.is_empty());
+        assert!(analysis.function_costs.contains_key("square"));
+        assert!(analysis.function_costs.values().all(|cost| cost.cycles.is_some_and(|cycles| cycles > 0)));
+        
+        // Neither function declares a contract; warnings name them and point at their declarations
+        assert_eq!(analysis.stats.warnings_generated, 2);
+        let warning = analysis.warnings.iter()
+            .find(|warning| warning.function_name == "main")
+            .expect("main has no contract");
+        assert!(warning.message.contains("Function 'main' lacks a performance contract"), "{}", warning.message);
+        assert_eq!(warning.span.start.line, 6);
+    }
+
+    #[test]
+    fn test_cost_estimation_walks_bodies() {
+        use bract::PerformanceAnalyzer;
+
+        let source = r#"
+            fn caller() -> i32 { return leaf(1); }
+            fn leaf(x: i32) -> i32 { return x + 1; }
+
+            fn looped() -> i32 {
+                let mut total = 0;
+                for i in 0..10 {
+                    total = total + leaf(i);
+                }
+                return total;
+            }
+
+            fn unbounded(n: i32) -> i32 {
+                let mut i = 0;
+                while i < n { i = i + 1; }
+                return i;
+            }
+
+            fn counted() -> i32 {
+                let mut total = 0;
+                let mut i = 0;
+                while i < 10 {
+                    total = total + leaf(i);
+                    i += 2;
+                }
+                let mut j = 9;
+                while j >= 0 { j = j - 3; }
+                return total;
+            }
+
+            fn skipping() -> i32 {
+                let mut i = 0;
+                while i < 10 {
+                    i += 1;
+                    if i > 5 { continue; }
+                }
+                return i;
+            }
+
+            fn recursive(n: i32) -> i32 { return recursive(n - 1); }
+
+            fn frame() {
+                let wide: i64 = 1;
+                let values: [i32; 4] = [1, 2, 3, 4];
+            }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+
+        let analysis = PerformanceAnalyzer::default().analyze_module(&module, &interner);
+        let cost = |name: &str| analysis.function_costs[name].clone();
+
+        // Calls include the callee's cost, even when it is declared later
+        let leaf_cycles = cost("leaf").cycles.expect("leaf is straight-line code");
+        assert!(cost("caller").cycles.expect("leaf is known") > leaf_cycles);
+
+        // Constant ranges bound the loop
+        assert!(cost("looped").cycles.expect("0..10 is bounded") >= 10 * leaf_cycles);
+
+        // Counters stepped by a constant towards a constant bound the loop: 5 and 4
+        // iterations, against the 10 calls of `looped`
+        let counted = cost("counted").cycles.expect("counter loops are bounded");
+        assert!(counted >= 5 * leaf_cycles);
+        assert!(counted < cost("looped").cycles.unwrap(), "{}", counted);
+
+        // Unbounded loops and recursion cannot be costed
+        let unbounded = cost("unbounded");
+        assert_eq!(unbounded.cycles, None);
+        assert!(unbounded.confidence < 1.0);
+        let skipping = cost("skipping");
+        assert_eq!(skipping.cycles, None);
+        assert!(skipping.confidence < 1.0);
+        assert_eq!(cost("recursive").cycles, None);
+
+        // Bindings accumulate on the stack; the array literal is allocated
+        let frame = cost("frame");
+        assert_eq!(frame.stack_bytes, Some(8 + 16));
+        assert_eq!(frame.allocations, Some(1));
+        assert_eq!(frame.memory_bytes, Some(4 * 8));
+    }
+
+    #[test]
+    fn test_cost_estimation_follows_the_memory_policy() {
+        use bract::PerformanceAnalyzer;
+        use bract::codegen::MemoryPolicy;
+
+        let source = r#"
+            fn grid() {
+                let cells: [i64; 1024] = [0; 1024];
+            }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+
+        // An 8 KiB binding is too large for one stack allocation, so code generation
+        // spills it and the estimate counts it as a heap allocation
+        let analysis = PerformanceAnalyzer::default().analyze_module(&module, &interner);
+        let cost = &analysis.function_costs["grid"];
+        assert_eq!(cost.stack_bytes, Some(0));
+        assert_eq!(cost.allocations, Some(2));
+        assert_eq!(cost.memory_bytes, Some(2 * 8192));
+
+        let roomy = MemoryPolicy { max_stack_allocation: 16 * 1024, ..MemoryPolicy::default() };
+        let analysis = PerformanceAnalyzer::default().with_memory_policy(roomy).analyze_module(&module, &interner);
+        let cost = &analysis.function_costs["grid"];
+        assert_eq!(cost.stack_bytes, Some(8192));
+        assert_eq!(cost.allocations, Some(1));
+    }
+
+    #[test]
+    fn test_allocation_recommendations() {
+        use bract::PerformanceAnalyzer;
+        use bract::performance::WarningType;
+
+        let source = r#"
+            @memory(strategy = "smartptr")
+            fn small_shared() -> i32 {
+                let values = [1, 2, 3, 4];
+                return 0;
+            }
+
+            fn no_allocations(x: i32) -> i32 { return x + 1; }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+
+        let analysis = PerformanceAnalyzer::default().analyze_module(&module, &interner);
+        assert_eq!(analysis.allocation_recommendations.len(), 1);
+
+        // A 32-byte, function-local array needs neither reference counting nor the heap
+        let recommendation = &analysis.allocation_recommendations[0];
+        assert_eq!(recommendation.function_name, "small_shared");
+        assert_eq!(recommendation.current_strategy, MemoryStrategy::SmartPtr);
+        assert_eq!(recommendation.recommended_strategy, MemoryStrategy::Stack);
+        assert_eq!(recommendation.allocations, 1);
+        assert_eq!(recommendation.average_bytes, 32);
+        assert_eq!(recommendation.cycles_saved_per_call, 80);
+        assert_eq!(recommendation.span.start.line, 2);
+
+        assert!(analysis.warnings.iter().any(|warning| warning.function_name == "small_shared"
+            && warning.warning_type == WarningType::SuboptimalAllocation));
+
+        let report = analysis.allocation_report();
+        assert!(report.contains("Recommendations: 1"), "{}", report);
+        assert!(report.contains("small_shared: SmartPtr -> Stack"), "{}", report);
+    }
+
+    #[test]
+    fn test_allocation_recommendations_keep_escaping_values_off_the_stack() {
+        use bract::PerformanceAnalyzer;
+
+        let source = r#"
+            @memory(strategy = "smartptr")
+            fn returned() -> [i32; 4] {
+                let values = [1, 2, 3, 4];
+                return values;
+            }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+
+        // The array outlives the call, so it stays off the stack but needs no reference count
+        let analysis = PerformanceAnalyzer::default().analyze_module(&module, &interner);
+        assert_eq!(analysis.allocation_recommendations.len(), 1);
+        let recommendation = &analysis.allocation_recommendations[0];
+        assert_eq!(recommendation.function_name, "returned");
+        assert_eq!(recommendation.current_strategy, MemoryStrategy::SmartPtr);
+        assert_eq!(recommendation.recommended_strategy, MemoryStrategy::Linear);
+    }
+
+    #[test]
+    fn test_profile_samples_compared_with_estimates() {
+        use bract::PerformanceAnalyzer;
+        use bract::performance::PerformanceProfiler;
+
+        let source = r#"
+            fn add(a: i32, b: i32) -> i32 { return a + b; }
+            fn scale(a: i32) -> i32 { return a * 3; }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+        let analysis = PerformanceAnalyzer::default().analyze_module(&module, &interner);
+        let predicted_add = analysis.function_costs["add"].cycles.expect("add has an estimate");
+        let predicted_scale = analysis.function_costs["scale"].cycles.expect("scale has an estimate");
+
+        let mut profiler = PerformanceProfiler::disabled("module".to_string());
+        profiler.load_function_table("0 add\n1 scale\n2 main\n");
+        profiler.record_function_sample(0, predicted_add);
+        profiler.record_function_sample(0, predicted_add + 2);
+        profiler.record_function_sample(1, predicted_scale * 5);
+        profiler.record_function_sample(7, 100); // no registered name
+
+        let comparisons = profiler.compare_with_estimates(&analysis.function_costs);
+        assert_eq!(comparisons.len(), 2);
+        assert_eq!(comparisons[0].function_name, "add");
+        assert_eq!(comparisons[0].calls, 2);
+        assert_eq!(comparisons[0].measured_cycles, predicted_add + 1);
+        assert!(!comparisons[0].is_discrepancy());
+        assert_eq!(comparisons[1].function_name, "scale");
+        assert!(comparisons[1].is_discrepancy());
+
+        let report = profiler.estimate_report(&analysis.function_costs);
+        assert!(report.contains("Off by more than 2x: 1"), "{}", report);
+        assert!(report.contains("Function 'scale'"), "{}", report);
+        assert!(!report.contains("Function 'add'"), "{}", report);
+    }
+
+    #[test]
+    fn test_profiler_checks_allocation_bound() {
+        use bract::ast::{AllocationBound, PerformanceContract, Span};
+        use bract::performance::{PerformanceProfiler, ViolationType, ALLOCATIONS};
+
+        let contract = PerformanceContract {
+            cpu_bound: None,
+            memory_bound: None,
+            allocation_bound: Some(AllocationBound::Count(1)),
+            latency_bound: None,
+            stack_bound: None,
+            deterministic: false,
+            wait_free: false,
+            span: Span::single(bract::lexer::Position::default()),
+        };
+        let mut profiler = PerformanceProfiler::new("fill".to_string(), contract);
+        profiler.start();
+        ALLOCATIONS.on_allocate(16);
+        assert!(profiler.finish().is_empty());
+
+        ALLOCATIONS.on_allocate(16);
+        let violations = profiler.finish();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].violation_type, ViolationType::AllocationExceeded);
+        assert_eq!(violations[0].actual, "2 allocations");
+    }
+
+    #[test]
+    fn test_cost_models_per_architecture() {
+        use bract::PerformanceAnalyzer;
+        use bract::performance::{CostEstimator, CostModel, CostModelError, TargetArchitecture};
+
+        let source = r#"
+            fn ratio(a: i32, b: i32) -> i32 { if a > b { return a / b; } return 0; }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        let interner = parser.take_interner();
+        let cycles = |mut analyzer: PerformanceAnalyzer| {
+            analyzer.analyze_module(&module, &interner).function_costs["ratio"].cycles.unwrap()
+        };
+
+        // Division and mispredicts cost more on x86-64 than on ARM64
+        let x86 = cycles(PerformanceAnalyzer::new(TargetArchitecture::X86_64));
+        let arm = cycles(PerformanceAnalyzer::new(TargetArchitecture::ARM64));
+        assert!(x86 > arm, "x86-64 {} vs ARM64 {}", x86, arm);
+
+        // Calibrated weights start from the named architecture
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"architecture = \"arm64\"\ndivide_cost = 1000\n").unwrap();
+        let model = CostModel::from_toml(file.path()).expect("valid cost model");
+        assert_eq!(model.architecture, TargetArchitecture::ARM64);
+        assert_eq!(model.divide_cost, 1000);
+        assert_eq!(model.multiply_cost, CostModel::arm64().multiply_cost);
+        assert_eq!(CostEstimator::with_model(model.clone()).target_arch(), TargetArchitecture::ARM64);
+        assert!(cycles(PerformanceAnalyzer::with_cost_model(model)) > arm);
+
+        // Unknown weights are rejected with the list of valid names
+        let err = CostModel::from_toml_str("divde_cost = 3").unwrap_err();
+        assert_eq!(err, CostModelError::UnknownWeight("divde_cost".to_string()));
+        assert!(err.to_string().contains("divide_cost"), "{}", err);
+        assert!(matches!(
+            CostModel::from_toml_str("divide_cost = -1"),
+            Err(CostModelError::InvalidValue { .. })
+        ));
+    }
+
+    #[test]
+    fn test_contract_verification_reports_each_bound() {
+        use bract::PerformanceAnalyzer;
+        use bract::lexer::Position;
+        use bract::performance::{ContractVerifier, PerformanceCost, ViolationType, WarningType};
+
+        let source = r#"
+            @performance(max_cost = 1, max_latency_ms = 1000)
+            fn tight(x: i32) -> i32 { return x * x + 1; }
+
+            @performance(max_cost = 100, max_memory = 4096, deterministic = true)
+            fn busy(n: i32) -> i32 {
+                let mut i = 0;
+                while i < n { i = i + 1; }
+                return i;
+            }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        let interner = parser.take_interner();
+
+        let analysis = PerformanceAnalyzer::default().analyze_module(&module, &interner);
+        assert_eq!(analysis.stats.contracts_verified, 2);
+
+        // The cycle bound is exceeded; the latency bound is not
+        let tight: Vec<_> = analysis.violations.iter().filter(|v| v.function_name == "tight").collect();
+        assert_eq!(tight.len(), 1);
+        assert_eq!(tight[0].violation_type, ViolationType::CpuExceeded);
+        assert_eq!(tight[0].expected, "1 cycles");
+        assert!(tight[0].actual.ends_with(" cycles"));
+        assert_eq!(tight[0].span.start.line, 2);
+
+        // An unbounded loop cannot be checked against the cycle bound and undermines determinism
+        let busy: Vec<_> = analysis.violations.iter().filter(|v| v.function_name == "busy").collect();
+        assert_eq!(busy.len(), 1);
+        assert_eq!(busy[0].violation_type, ViolationType::NonDeterministic);
+        assert!(busy[0].message.contains("declared deterministic"), "{}", busy[0].message);
+        assert_eq!(busy[0].span.start.line, 5);
+        let busy_warning = analysis.warnings.iter()
+            .find(|w| w.function_name == "busy")
+            .expect("unknown cycle estimate is reported");
+        assert_eq!(busy_warning.warning_type, WarningType::InaccurateEstimate);
+        assert!(busy_warning.message.contains("100 cycles"), "{}", busy_warning.message);
+
+        // Stack and allocation bounds report in the units they were declared in
+        let contract = PerformanceContract {
+            cpu_bound: None,
+            memory_bound: None,
+            allocation_bound: Some(AllocationBound::None),
+            latency_bound: None,
+            stack_bound: Some(StackBound::Bytes(16)),
+            deterministic: false,
+            wait_free: false,
+            span: Span::single(Position::default()),
+        };
+        let mut cost = PerformanceCost::zero();
+        cost.allocations = Some(2);
+        cost.stack_bytes = Some(64);
+        let result = ContractVerifier::new().verify_contract("frame", Span::single(Position::default()), &contract, &cost);
+        assert!(result.warnings.is_empty());
+        let summary: Vec<_> = result.violations.iter()
+            .map(|v| (v.violation_type.clone(), v.expected.as_str(), v.actual.as_str()))
+            .collect();
+        assert_eq!(summary, vec![
+            (ViolationType::AllocationExceeded, "0 allocations", "2 allocations"),
+            (ViolationType::StackExceeded, "16 stack bytes", "64 stack bytes"),
+        ]);
+    }
+
+    #[test]
+    fn test_complexity_inference() {
+        use bract::PerformanceAnalyzer;
+        use bract::lexer::Position;
+        use bract::performance::{ContractVerifier, ViolationType, WarningType};
+
+        let source = r#"
+            fn constant(a: i32) -> i32 { return a + 1; }
+            fn fixed() -> i32 {
+                let mut total = 0;
+                for i in 0..10 { total += i; }
+                return total;
+            }
+            fn sum(n: i32) -> i32 {
+                let mut total = 0;
+                for i in 0..n { total += i; }
+                return total;
+            }
+            fn pairs(n: i32) -> i32 {
+                let limit = n * 2;
+                let mut count = 0;
+                for i in 0..limit {
+                    for j in 0..n { count += 1; }
+                }
+                return count;
+            }
+            fn halvings(n: i32) -> i32 {
+                let mut i = n;
+                let mut steps = 0;
+                while i > 1 {
+                    i = i / 2;
+                    steps += 1;
+                }
+                return steps;
+            }
+            fn sums(n: i32) -> i32 {
+                let mut total = 0;
+                for i in 0..n { total += sum(i); }
+                return total;
+            }
+            fn factorial(n: i32) -> i32 {
+                if n <= 1 { return 1; }
+                return n * factorial(n - 1);
+            }
+        "#;
+        let mut parser = Parser::new(source, 0).expect("Parser creation failed");
+        let module = parser.parse_module().expect("Parsing failed");
+        assert!(parser.errors().is_empty(), "{:?}", parser.errors());
+        let interner = parser.take_interner();
+
+        let analysis = PerformanceAnalyzer::default().analyze_module(&module, &interner);
+        let complexity = |name: &str| analysis.function_costs[name].complexity;
+        assert_eq!(complexity("constant"), Some(BigOComplexity::Constant));
+        assert_eq!(complexity("fixed"), Some(BigOComplexity::Constant));
+        assert_eq!(complexity("sum"), Some(BigOComplexity::Linear));
+        assert_eq!(complexity("pairs"), Some(BigOComplexity::Quadratic));
+        assert_eq!(complexity("halvings"), Some(BigOComplexity::Logarithmic));
+        assert_eq!(complexity("sums"), Some(BigOComplexity::Quadratic));
+        assert_eq!(complexity("factorial"), None);
+        assert!(analysis.warnings.iter().any(|w| w.function_name == "factorial"
+            && w.warning_type == WarningType::PotentialBottleneck
+            && w.message.contains("recursive")));
+
+        // The outer loop of the nest is blamed
+        let pairs = &analysis.function_costs["pairs"];
+        assert_eq!(pairs.complexity_loop.map(|span| span.start.line), Some(16));
+
+        let contract = |bound| PerformanceContract {
+            cpu_bound: Some(CpuBound::Complexity(bound, None)),
+            memory_bound: None,
+            allocation_bound: None,
+            latency_bound: None,
+            stack_bound: None,
+            deterministic: false,
+            wait_free: false,
+            span: Span::single(Position::default()),
+        };
+        let verifier = ContractVerifier::new();
+        let span = Span::single(Position::default());
+
+        let result = verifier.verify_contract("pairs", span, &contract(BigOComplexity::Constant), pairs);
+        assert_eq!(result.violations.len(), 1);
+        let violation = &result.violations[0];
+        assert_eq!(violation.violation_type, ViolationType::ComplexityExceeded);
+        assert_eq!((violation.expected.as_str(), violation.actual.as_str()), ("O(1)", "O(n^2)"));
+        assert!(violation.message.contains("loop at 16:"), "{}", violation.message);
+
+        let within = verifier.verify_contract("pairs", span, &contract(BigOComplexity::Cubic), pairs);
+        assert!(within.violations.is_empty() && within.warnings.is_empty());
+
+        let recursive = verifier.verify_contract("factorial", span, &contract(BigOComplexity::Linear), &analysis.function_costs["factorial"]);
+        assert!(recursive.violations.is_empty());
+        assert_eq!(recursive.warnings[0].warning_type, WarningType::InaccurateEstimate);
+    }
 }
 
 /// **LANGUAGE COMPLETION ROADMAP**
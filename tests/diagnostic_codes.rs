@@ -0,0 +1,52 @@
+//! Snapshot of the diagnostic code registry
+//!
+//! Every code, its legacy name and its summary must match
+//! `golden/diagnostic_codes.txt`. Adding a code means adding a line; existing lines
+//! must not change, since editors and CI configurations refer to codes by number.
+//! After adding codes, rerun with `BRACT_BLESS=1` to regenerate the snapshot.
+
+use bract::DiagnosticCode;
+use std::fs;
+use std::path::PathBuf;
+
+fn registry() -> String {
+    DiagnosticCode::ALL
+        .iter()
+        .map(|code| format!("{} {}: {}\n", code, code.name(), code.summary()))
+        .collect()
+}
+
+#[test]
+fn test_registry_matches_snapshot() {
+    let golden = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/diagnostic_codes.txt");
+    let registry = registry();
+    if std::env::var_os("BRACT_BLESS").is_some() {
+        fs::write(&golden, &registry).expect("writing the snapshot");
+    }
+    let expected = fs::read_to_string(&golden).expect("snapshot - run with BRACT_BLESS=1 to create it");
+
+    let current: Vec<&str> = registry.lines().collect();
+    for line in expected.lines() {
+        assert!(
+            current.contains(&line),
+            "`{}` is no longer in the registry; released codes must keep their number, name and meaning",
+            line
+        );
+    }
+    assert_eq!(
+        expected, registry,
+        "new codes are missing from {}; rerun with BRACT_BLESS=1",
+        golden.display()
+    );
+}
+
+#[test]
+fn test_codes_round_trip_through_both_spellings() {
+    for &code in DiagnosticCode::ALL {
+        assert_eq!(code.as_str().parse::<DiagnosticCode>(), Ok(code));
+        assert_eq!(DiagnosticCode::try_from(code.name()), Ok(code));
+        assert_eq!(<&'static str>::from(code), code.name());
+        assert!(code == code.as_str() && code == code.name());
+        assert_eq!(serde_json::to_value(code).unwrap(), code.as_str());
+    }
+}
@@ -0,0 +1,552 @@
+//! End-to-end tests running compiled programs
+//!
+//! Each program is compiled for the host, linked with the native runtime into an
+//! executable in a temporary directory and run; its exit status and standard
+//! output must match what the program computes, and it must print nothing to
+//! standard error. A program still running after `TIMEOUT` is killed and fails
+//! its test. Without a system linker, or when
+//! the tests are built for another target, there is nothing to run and every test
+//! says why it was skipped instead of failing.
+
+use bract::codegen::cranelift::CraneliftCodeGenerator;
+use bract::codegen::{link, CodegenOptions, LinkOptions};
+use bract::semantic::SemanticAnalyzer;
+use bract::Parser;
+use std::io::Read;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use target_lexicon::Triple;
+
+/// How long a program may run before it is killed
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a program is expected to end
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Exit {
+    /// `main` returns this code
+    Code(i32),
+    /// The program traps, e.g. on a failed bounds check
+    Trap,
+}
+
+/// A program, how it ends and what it prints
+struct Program {
+    name: &'static str,
+    source: &'static str,
+    exit: Exit,
+    stdout: &'static [u8],
+}
+
+/// How a program ended and what it wrote
+struct Run {
+    status: ExitStatus,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+/// Why compiled programs cannot run here, if they cannot
+fn skip_reason() -> Option<String> {
+    if let Ok(target) = std::env::var("CARGO_BUILD_TARGET") {
+        if target != Triple::host().to_string() {
+            return Some(format!("cross-compiling for {}, whose executables cannot run on this host", target));
+        }
+    }
+    if link::find_linker().is_none() {
+        return Some("no system linker found on PATH (tried cc, clang, gcc; link.exe on Windows)".to_string());
+    }
+    None
+}
+
+/// Compile `source`, returning the object file and the leak report of code generation
+fn compile(source: &str) -> Result<(Vec<u8>, String), String> {
+    let mut parser = Parser::new(source, 0).map_err(|error| error.to_string())?;
+    let module = parser.parse_module().map_err(|error| error.to_string())?;
+    if let Some(error) = parser.errors().first() {
+        return Err(error.to_string());
+    }
+    let interner = parser.interner();
+    let analysis = SemanticAnalyzer::new().with_interner(interner.clone()).analyze(&module);
+    if let Some(error) = analysis.errors.first() {
+        return Err(format!("error[{}]: {}", error.code(), error.message(&interner)));
+    }
+
+    let mut generator = CraneliftCodeGenerator::new(analysis.symbol_table, interner, CodegenOptions::debug())
+        .map_err(|error| error.to_string())?;
+    let object = generator.generate(&module).map_err(|error| error.to_string())?;
+    let leaks = if generator.leak_warnings().is_empty() {
+        String::new()
+    } else {
+        generator.memory_manager().get_leak_report()
+    };
+    Ok((object, leaks))
+}
+
+/// Link `object`, with the native runtime when it uses it, and run it for at most `timeout`
+///
+/// Both output pipes are drained on their own threads while the program runs, so it
+/// never blocks on a full pipe however much it writes. A program still running at
+/// the timeout is killed and reported as an error.
+fn run(name: &str, object: &[u8], timeout: Duration) -> Result<Run, String> {
+    let dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let executable = dir.path().join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+    link::link_executable(object, &executable, LinkOptions::default()).map_err(|error| format!("{}: linking failed: {}", name, error))?;
+    let mut child = Command::new(&executable)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("{}: failed to run {}: {}", name, executable.display(), error))?;
+
+    fn drain(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            let _ = pipe.read_to_end(&mut bytes);
+            bytes
+        })
+    }
+    let stdout = drain(child.stdout.take().expect("stdout is piped"));
+    let stderr = drain(child.stderr.take().expect("stderr is piped"));
+
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait().map_err(|error| format!("{}: failed to wait for it: {}", name, error))? {
+            Some(status) => break status,
+            None if started.elapsed() >= timeout => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("{} timed out after {:?} and was killed", name, timeout));
+            }
+            None => thread::sleep(Duration::from_millis(10)),
+        }
+    };
+    Ok(Run {
+        status,
+        stdout: stdout.join().expect("stdout reader panicked"),
+        stderr: stderr.join().expect("stderr reader panicked"),
+    })
+}
+
+/// Compile and run `source`, panicking when it fails to do either
+fn compile_and_run(name: &str, source: &str) -> Run {
+    let (object, leaks) = compile(source).unwrap_or_else(|error| panic!("{} does not compile: {}", name, error));
+    assert!(leaks.is_empty(), "{} leaks:\n{}", name, leaks);
+    run(name, &object, TIMEOUT).unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn check(program: &Program) {
+    let output = compile_and_run(program.name, program.source);
+    match program.exit {
+        Exit::Code(code) => assert_eq!(output.status.code(), Some(code), "{} exited with {}", program.name, output.status),
+        Exit::Trap => {
+            assert!(!output.status.success(), "{} should trap, but exited with {}", program.name, output.status);
+            // A trap kills the process with a signal rather than exiting
+            #[cfg(unix)]
+            assert_eq!(output.status.code(), None, "{} should trap, but exited with {}", program.name, output.status);
+        }
+    }
+    assert!(
+        output.stdout == program.stdout,
+        "{} printed unexpected output\n  expected: {:?}\n     found: {:?}",
+        program.name, String::from_utf8_lossy(program.stdout), String::from_utf8_lossy(&output.stdout),
+    );
+    assert!(output.stderr.is_empty(), "{} wrote to standard error: {:?}", program.name, String::from_utf8_lossy(&output.stderr));
+}
+
+const PROGRAMS: &[Program] = &[
+    Program {
+        name: "answer",
+        source: "fn main() -> i32 { return 42; }",
+        exit: Exit::Code(42),
+        stdout: b"",
+    },
+    Program {
+        name: "fibonacci",
+        source: r#"
+            fn fib(n: i32) -> i32 {
+                if n < 2 {
+                    return n;
+                }
+                return fib(n - 1) + fib(n - 2);
+            }
+
+            fn fib_iterative(n: i32) -> i32 {
+                let mut previous = 0;
+                let mut current = 1;
+                let mut i = 0;
+                while i < n {
+                    let next = previous + current;
+                    previous = current;
+                    current = next;
+                    i += 1;
+                }
+                return previous;
+            }
+
+            fn main() -> i32 {
+                if fib(10) != fib_iterative(10) {
+                    return 1;
+                }
+                return fib_iterative(12);
+            }
+        "#,
+        exit: Exit::Code(144),
+        stdout: b"",
+    },
+    Program {
+        name: "out_of_bounds",
+        source: r#"
+            fn main() -> i32 {
+                let values = [3, 5, 7, 9];
+                let i = 4;
+                return values[i];
+            }
+        "#,
+        exit: Exit::Trap,
+        stdout: b"",
+    },
+    Program {
+        name: "region",
+        source: r#"
+            struct Pair { x: i32, y: i32 }
+
+            @region(scratch, grow)
+            fn sum() -> i32 {
+                let a: RegionPtr<Pair> = Pair { x: 1, y: 2 };
+                let b: RegionPtr<Pair> = Pair { x: 3, y: 4 };
+                return a.x + a.y + b.x + b.y;
+            }
+
+            fn main() -> i32 {
+                let mut total = 0;
+                region frame {
+                    let c: RegionPtr<Pair> = Pair { x: 5, y: 6 };
+                    total = c.x + c.y;
+                }
+                return sum() + total;
+            }
+        "#,
+        exit: Exit::Code(21),
+        stdout: b"",
+    },
+    Program {
+        name: "loop_break_value",
+        source: r#"
+            fn first_power_of_two_over(limit: i64) -> i64 {
+                let mut power: i64 = 1;
+                return loop {
+                    if power > limit {
+                        break power;
+                    }
+                    power = power * 2;
+                };
+            }
+
+            fn main() -> i32 {
+                let mut tries = 0;
+                let tries_needed = loop {
+                    tries += 1;
+                    if tries == 5 {
+                        break tries * 10;
+                    }
+                };
+                return (first_power_of_two_over(100) as i32) + tries_needed;
+            }
+        "#,
+        exit: Exit::Code(178),
+        stdout: b"",
+    },
+    Program {
+        name: "break_with_named_value",
+        source: r#"
+            fn main() -> i32 {
+                let mut count = 0;
+                let total = loop {
+                    count += 1;
+                    if count == 4 {
+                        break count;
+                    }
+                };
+                let mut n = 0;
+                outer: loop {
+                    loop {
+                        n += 1;
+                        if n == 3 {
+                            break outer;
+                        }
+                    }
+                }
+                return total * 10 + n;
+            }
+        "#,
+        exit: Exit::Code(43),
+        stdout: b"",
+    },
+    Program {
+        name: "array_strides",
+        source: r#"
+            fn main() -> i32 {
+                let wide: [i64; 4] = [10, 20, 30, 5000000000];
+                let bytes: [u8; 3] = [250, 5, 1];
+                let i = 2;
+                wide[i] += 1;
+                bytes[1] = 7;
+                let big = wide[3] / 1000000000;
+                let small = bytes[i] + bytes[0];
+                let first = &bytes[1];
+                return (wide[i] as i32) + (big as i32) + (small as i32) + (*first as i32);
+            }
+        "#,
+        exit: Exit::Code(31 + 5 + 251 + 7 - 256),
+        stdout: b"",
+    },
+    Program {
+        name: "smart_pointers",
+        source: r#"
+            struct Pair { x: i32, y: i32 }
+
+            fn main() -> i32 {
+                let a: SmartPtr<Pair> = Pair { x: 20, y: 1 };
+                let b: SmartPtr<Pair> = Pair { x: a.x, y: a.y * 2 };
+                return a.x + a.y + b.x + b.y - 20;
+            }
+        "#,
+        exit: Exit::Code(23),
+        stdout: b"",
+    },
+    Program {
+        name: "smart_pointer_in_if",
+        source: r#"
+            struct Pair { x: i32, y: i32 }
+
+            fn main() -> i32 {
+                let mut c = 2;
+                if c > 1 {
+                    let a: SmartPtr<Pair> = Pair { x: 5, y: 1 };
+                    let b: SmartPtr<Pair> = a;
+                    c = b.x + b.y;
+                } else {
+                    let a: SmartPtr<Pair> = Pair { x: 7, y: 1 };
+                    c = a.x;
+                }
+                return c;
+            }
+        "#,
+        exit: Exit::Code(6),
+        stdout: b"",
+    },
+    Program {
+        name: "smart_pointer_in_while",
+        source: r#"
+            struct Pair { x: i32, y: i32 }
+
+            fn main() -> i32 {
+                let mut total = 0;
+                let mut i = 0;
+                while i < 10 {
+                    let a: SmartPtr<Pair> = Pair { x: i, y: 1 };
+                    let b: SmartPtr<Pair> = a;
+                    i += 1;
+                    if b.x == 3 {
+                        continue;
+                    }
+                    if b.x == 8 {
+                        break;
+                    }
+                    total += b.x + b.y;
+                }
+                return total;
+            }
+        "#,
+        // 0..=7 without 3, plus one per counted iteration
+        exit: Exit::Code(25 + 7),
+        stdout: b"",
+    },
+    Program {
+        name: "smart_pointer_reassigned",
+        source: r#"
+            struct Pair { x: i32, y: i32 }
+
+            fn main() -> i32 {
+                let mut a: SmartPtr<Pair> = Pair { x: 1, y: 2 };
+                let b: SmartPtr<Pair> = a;
+                a = Pair { x: 10, y: 20 };
+                let mut i = 0;
+                while i < 3 {
+                    a = Pair { x: a.x + 1, y: a.y };
+                    i += 1;
+                }
+                return a.x + a.y + b.x + b.y;
+            }
+        "#,
+        exit: Exit::Code(13 + 20 + 1 + 2),
+        stdout: b"",
+    },
+    Program {
+        name: "smart_pointers_beyond_a_megabyte",
+        source: r#"
+            struct Pair { x: i32, y: i32 }
+
+            fn main() -> i32 {
+                let mut total = 0;
+                let mut i = 0;
+                while i < 200000 {
+                    let a: SmartPtr<Pair> = Pair { x: i % 3, y: 1 };
+                    total += a.x + a.y;
+                    i += 1;
+                }
+                return total % 256;
+            }
+        "#,
+        // 200000 allocations of 16 bytes each; 66667 ones, 66666 twos and 200000 for y
+        exit: Exit::Code((66667 + 2 * 66666 + 200000) % 256),
+        stdout: b"",
+    },
+    Program {
+        name: "unit_main",
+        source: r#"
+            fn count_to(n: i32) -> i32 {
+                let mut i = 0;
+                while i < n {
+                    i += 1;
+                }
+                return i;
+            }
+
+            fn main() {
+                if count_to(7) == 7 {
+                    return;
+                }
+                let _unreached = count_to(3);
+            }
+        "#,
+        exit: Exit::Code(0),
+        stdout: b"",
+    },
+    Program {
+        name: "if_let_while_let",
+        source: r#"
+            enum Shape { Circle { radius: i32 }, Rect(i32, i32), Empty }
+            enum Option { Some(i32), None }
+
+            fn below(n: i32, limit: i32) -> Option {
+                if n < limit {
+                    return Option::Some(n);
+                }
+                return Option::None;
+            }
+
+            fn width(shape: Shape) -> i32 {
+                if let Shape::Rect(w, _) = shape {
+                    return w;
+                } else if let Shape::Circle { radius } = shape {
+                    return radius * 2;
+                }
+                return 0;
+            }
+
+            fn main() -> i32 {
+                let mut i = 0;
+                let mut total = 0;
+                while let Option::Some(n) = below(i, 5) {
+                    total += n;
+                    i += 1;
+                }
+                let found = if let Option::Some(n) = below(3, 5) { n * 2 } else { 0 };
+                let missing = if let Option::Some(n) = below(9, 5) { n } else { 100 };
+                return total + found + missing + width(Shape::Rect(7, 1)) + width(Shape::Circle { radius: 4 }) + width(Shape::Empty);
+            }
+        "#,
+        exit: Exit::Code(10 + 6 + 100 + 7 + 8),
+        stdout: b"",
+    },
+    Program {
+        name: "printing",
+        source: r#"
+            fn square(n: i32) -> i32 {
+                return n * n;
+            }
+
+            fn main() -> i32 {
+                print_str("squares:");
+                let mut i = -2;
+                while i <= 3 {
+                    print_str(" ");
+                    print_int(square(i) * if i < 0 { -1 } else { 1 });
+                    i += 1;
+                }
+                print_str("\n");
+                print_str("tab\tand \"quotes\"\n");
+                print_int(-2147483647 - 1);
+                print_str("\n");
+                return 3;
+            }
+        "#,
+        exit: Exit::Code(3),
+        stdout: b"squares: -4 -1 0 1 4 9\ntab\tand \"quotes\"\n-2147483648\n",
+    },
+];
+
+#[test]
+fn test_programs_run_to_their_expected_exit() {
+    if let Some(reason) = skip_reason() {
+        eprintln!("Skipping: {}", reason);
+        return;
+    }
+    for program in PROGRAMS {
+        check(program);
+    }
+}
+
+
+#[test]
+fn test_output_larger_than_a_pipe_buffer_is_captured() {
+    if let Some(reason) = skip_reason() {
+        eprintln!("Skipping: {}", reason);
+        return;
+    }
+    let lines = 20000;
+    let source = format!(r#"
+        fn main() -> i32 {{
+            let mut i = 0;
+            while i < {} {{
+                print_int(i);
+                print_str(": 0123456789abcdef\n");
+                i += 1;
+            }}
+            return 0;
+        }}
+    "#, lines);
+    let output = compile_and_run("large_output", &source);
+    assert_eq!(output.status.code(), Some(0), "large_output exited with {}", output.status);
+
+    let expected: Vec<u8> = (0..lines).flat_map(|i| format!("{}: 0123456789abcdef\n", i).into_bytes()).collect();
+    assert!(expected.len() > 256 * 1024);
+    assert_eq!(output.stdout.len(), expected.len(), "large_output printed the wrong amount");
+    assert!(output.stdout == expected, "large_output printed unexpected output");
+}
+
+#[test]
+fn test_program_that_never_ends_is_killed() {
+    if let Some(reason) = skip_reason() {
+        eprintln!("Skipping: {}", reason);
+        return;
+    }
+    let source = r#"
+        fn main() -> i32 {
+            let mut i = 0;
+            while true {
+                i += 1;
+            }
+            return i;
+        }
+    "#;
+    let (object, _) = compile(source).unwrap_or_else(|error| panic!("spin does not compile: {}", error));
+    let started = Instant::now();
+    let error = match run("spin", &object, Duration::from_millis(500)) {
+        Ok(output) => panic!("spin should run forever, but exited with {}", output.status),
+        Err(error) => error,
+    };
+    assert_eq!(error, "spin timed out after 500ms and was killed");
+    assert!(started.elapsed() < TIMEOUT, "spin was not killed at its timeout");
+}
@@ -0,0 +1,122 @@
+//! Document formatting through the language server
+//!
+//! Every `golden/formatting/<name>.before.bract` is opened in an `LspServer` and
+//! formatted; applying the edits must give `<name>.after.bract`, and formatting the
+//! result again must give no edits at all. After an intended change to the
+//! pretty-printer, rerun with `BRACT_BLESS=1` to regenerate the expected output.
+
+use bract::lsp::{CancellationToken, FormattingOptions, LspServer, Position, TextEdit};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn corpus_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/formatting")
+}
+
+/// Sources to format, sorted by path
+fn inputs(dir: &Path) -> Vec<PathBuf> {
+    let mut inputs: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("corpus directory")
+        .map(|entry| entry.expect("corpus entry").path())
+        .filter(|path| path.to_str().is_some_and(|path| path.ends_with(".before.bract")))
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+/// Byte offset of the LSP `position` in `text`
+fn offset(text: &str, position: &Position) -> usize {
+    let line_start: usize = text.split_inclusive('\n').take(position.line as usize).map(str::len).sum();
+    let mut units = 0;
+    let column: usize = text[line_start..]
+        .chars()
+        .take_while(|&ch| {
+            units += ch.len_utf16() as u32;
+            ch != '\n' && units <= position.character
+        })
+        .map(char::len_utf8)
+        .sum();
+    line_start + column
+}
+
+/// `text` with `edits`, which refer to positions in it, applied
+fn apply(text: &str, edits: &[TextEdit]) -> String {
+    let mut result = text.to_string();
+    for edit in edits.iter().rev() {
+        let range = offset(text, &edit.range.start)..offset(text, &edit.range.end);
+        result.replace_range(range, &edit.new_text);
+    }
+    result
+}
+
+/// Format `source` as the document `uri`
+fn format(server: &LspServer, uri: &str, source: &str, options: FormattingOptions) -> Vec<TextEdit> {
+    server.update_document(uri.to_string(), source.to_string(), 1).expect("opening the document");
+    server.format_document(uri, options, &CancellationToken::new()).expect("formatting the document")
+}
+
+#[test]
+fn test_formatting_matches_corpus() {
+    let bless = std::env::var_os("BRACT_BLESS").is_some();
+    let inputs = inputs(&corpus_dir());
+    assert!(!inputs.is_empty(), "no inputs in {}", corpus_dir().display());
+
+    let server = LspServer::new();
+    let mut mismatched = Vec::new();
+    for path in &inputs {
+        let name = path.file_name().and_then(|name| name.to_str()).expect("input name").trim_end_matches(".before.bract");
+        let uri = format!("file:///{}.bract", name);
+        let source = fs::read_to_string(path).expect("input source");
+
+        let edits = format(&server, &uri, &source, FormattingOptions::default());
+        assert!(!edits.is_empty(), "{} is already formatted", name);
+        let formatted = apply(&source, &edits);
+
+        let expected_path = corpus_dir().join(format!("{}.after.bract", name));
+        if bless {
+            fs::write(&expected_path, &formatted).expect("writing the expected output");
+        }
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|_| panic!("no expected output for {} - run with BRACT_BLESS=1 to create it", name));
+        if expected != formatted {
+            eprintln!("formatting {} gives:\n{}", name, formatted);
+            mismatched.push(name.to_string());
+        }
+
+        // Formatting is idempotent
+        let again = format(&server, &uri, &formatted, FormattingOptions::default());
+        assert!(again.is_empty(), "formatting {} twice edits it again: {:?}", name, again);
+    }
+    assert!(
+        mismatched.is_empty(),
+        "formatting no longer matches the expected output of {:?}; if the change is intended, rerun with BRACT_BLESS=1",
+        mismatched
+    );
+}
+
+#[test]
+fn test_formatting_uses_indentation_options() {
+    let server = LspServer::new();
+    let source = "fn main() -> i32 { return 0; }\n";
+
+    let tabs = FormattingOptions { tab_size: 4, insert_spaces: false };
+    assert_eq!(apply(source, &format(&server, "file:///tabs.bract", source, tabs)), "fn main() -> i32 {\n\treturn 0;\n}\n");
+    let spaces = FormattingOptions { tab_size: 2, insert_spaces: true };
+    assert_eq!(apply(source, &format(&server, "file:///spaces.bract", source, spaces)), "fn main() -> i32 {\n  return 0;\n}\n");
+}
+
+#[test]
+fn test_formatting_leaves_broken_documents_alone() {
+    let server = LspServer::new();
+    for (uri, source, reason) in [
+        ("file:///broken.bract", "fn main() -> i32 {\n    let x = ;\n}\n", "syntax errors"),
+        ("file:///commented.bract", "fn main() -> i32 {   // the answer\n    return 42;\n}\n", "remove its comments"),
+    ] {
+        assert!(format(&server, uri, source, FormattingOptions::default()).is_empty());
+
+        // The document says why it was not formatted
+        let diagnostics = server.analyze_document(uri).expect("analyzing the document");
+        let warning = diagnostics.iter().find(|diagnostic| diagnostic.source.as_deref() == Some("bract-format")).expect("formatting warning");
+        assert!(warning.message.contains(reason), "{}", warning.message);
+    }
+}
@@ -0,0 +1,149 @@
+//! Round trip through the pretty-printer
+//!
+//! Every program in the corpus is parsed, printed and parsed again; both parses
+//! must give the same AST once spans are ignored, and printing the second AST
+//! must give the same source as printing the first.
+
+use bract::ast::{self, Module, PrettyPrinter};
+use bract::parser::StringInterner;
+use bract::Parser;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Programs written for this test, covering syntax the example files do not
+const SNIPPETS: &[&str] = &[
+    // Precedence, associativity and the operators sharing a token with an operand start
+    "fn f() { let x = (a + b) * c - (d - e) - f / (g * h) % i; let y = a << b + c >> (d << e); }",
+    "fn f() { let x = a || b && (c || d) | e ^ f & g == (h != i) < j; let y = -(-x) + !~z - (a - -b); }",
+    "fn f() { let x = (a as i64) as u8 + (b + c) as f64; let y = -a as i32; let z = &mut *p; let w = & &x; }",
+    "fn f() { let x = c ? a : b ? d : e; let y = (c ? a : b) ? d : e; let z = (a ? b : c) + 1; }",
+    "fn f() { let r = 0..10; let t = a..; let u = (a..) + b; let v = (a..b)..c; for i in 0..n {} }",
+    // Closures, jumps and other open-ended expressions
+    "fn f() { let g = |x, y: i32| x + y; let h = move || -> i32 { 1 }; let k = (|x| x)(1); let m = (|| 0) + 1; }",
+    "fn f() -> i32 { let g = |(a, b), &c, (x | y)| a; loop { break; } loop { continue; } return 1; }",
+    "fn f() { outer: loop { inner: loop { break outer; continue inner; } } loop { break (y); break y * 2; } }",
+    // Postfix expressions and literals
+    "fn f() { a.b.c(d, e)[f](g).h; (1).abs(); 1u8.abs(); }",
+    "fn f() { let t = (); let u = (a,); let v = (a, b, c); let w = [1, 2, 3]; let z = [0; 16]; let p = a::b::C; }",
+    "fn f() { let s = \"tab\\t nl\\n quote\\\" back\\\\ nul\\0 cr\\r bell\\u{7}\"; let c = '\\''; let d = '\\\\'; let e = '\\n'; }",
+    "fn f() { let r = r\"raw \\n\"; let h = r#\"has \"quotes\"\"#; let x = 0xFF; let w = 0b1010_0101u8; let y = 1_000i64; let z = 1.5e3f32; let n = null; }",
+    // Struct literals, which conditions only allow inside parentheses
+    "fn f() { let p = Point { x: 1, y: y }; if (p == Point { x: 1, y: 2 }) {} match (Q {}) { _ => {} } g(P { a: 1 }); }",
+    "fn f() { while x < (S { a: 1 }).a { x += 1; } for v in (V { n: 3 }).items() {} let e = E {}; }",
+    // Statements
+    "fn f() { let mut x: i32 = 0; let y; x = 1; x -= 2; x <<= 1; x |= 3; *p = 4; a[0] = 5; s.f = 6; }",
+    "fn f() { if a { x(); } else if b { y(); } else { z(); } while true { break; } { let inner = 1; } }",
+    "fn f() -> i32 { let v = if a { 1 } else { 2 }; let w = a ? { 1 } : b ? { 2 } : { 3 }; v + w }",
+    "fn f() { match x { 1 | 2 => a, 3..=9 if y > 0 => { b(); } Some(v) => v, _ => {} } }",
+    "fn f() { region scratch { let b = alloc(); } @memory(size_hint = 4096) region \"io buffer\" { g(); } fn inner() {} }",
+    // Patterns
+    "fn f() { let (a, (b, c), [d, e], _) = t; let P { x, y: (m, n), .. } = p; let Q {} = q; let &mut r = s; }",
+    "fn f() { match v { E::A => 1, E::B(x, _) => x, Some(&(a | b)) => a, 'a'..='z' | '0'..='9' => 2, (x,) => 3, _ => 0, } }",
+    "fn f((a, b): (i32, i32), (&c): &i32, mut_val: i32) {}",
+    // Types
+    "fn f(a: &mut [i32; 4], b: & &u8, c: *const u8, d: *mut T, e: (i32,), g: (), h: fn(i32, f64) -> bool) -> ! {}",
+    "fn f(a: Vec<Option<T> >, b: std::collections::HashMap<K, V>, c: fn(), d: _, e: (A, B)) {}",
+    // Items and attributes
+    "pub struct Point { pub x: f64, y: f64, } struct Pair(i32, String); struct Unit; struct Empty {}",
+    "enum Shape { Circle { radius: f64 }, Square(f64), Empty, } pub enum Code { Ok = 0, Err = 1 + 1, } enum Never {}",
+    "type Id = u64; pub const MAX: usize = 16 * 1024; use std::io::Read; use a::b as c; mod outer; pub mod inner { fn f() {} }",
+    "impl Point { pub fn new(x: f64, y: f64) -> Point { Point { x: x, y: y } } fn len(&self) -> f64 { self.x } fn set(&mut self) {} fn take(self) {} }",
+    "impl Point for Display { fn fmt(&self) {} }",
+    "fn id<T: Clone + Copy, U = i32>(x: T) -> T where T: Debug { x } fn puts(s: *const u8) -> i32;",
+    "@memory(strategy = \"linear\") @performance(max_cost = 100, max_memory = 4096, max_latency_ms = 5, deterministic = true) fn hot() {}",
+    "@inline pub @export(name = \"f\", 3, 'c', 1.5, false) fn g() {}",
+    "@cfg(all(target_os = \"windows\", not(any(target_arch = \"aarch64\", debug)), any())) fn GetTickCount() -> u32; @cfg(debug) mod checks {}",
+    "/// Adds two numbers.\n///\n/// Returns the sum.\nfn add(a: i32, b: i32) -> i32 { a + b }",
+    "/// Doc before\n@inline\n/// and after\nstruct S;",
+    "/**\n * Block doc\n *   indented\n */\nfn block_doc() {}",
+    "//! Module doc\n//! second line\n\nfn main() {}",
+    "mod m {\n    //! Inner doc\n    fn f() {}\n}",
+];
+
+fn corpus() -> Vec<(String, String)> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut files = vec![root.join("tests/golden/ast_json.bract")];
+    for dir in ["examples", "tests/basic_functions"] {
+        let mut entries: Vec<PathBuf> = fs::read_dir(root.join(dir))
+            .expect("corpus directory")
+            .map(|entry| entry.expect("corpus entry").path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "bract"))
+            .collect();
+        entries.sort();
+        files.extend(entries);
+    }
+
+    let mut programs: Vec<(String, String)> = files
+        .iter()
+        .map(|path| (display_name(&root, path), fs::read_to_string(path).expect("corpus file")))
+        .collect();
+    programs.extend(SNIPPETS.iter().enumerate().map(|(index, source)| (format!("snippet {}", index), source.to_string())));
+    programs
+}
+
+fn display_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).display().to_string()
+}
+
+fn parse(source: &str, interner: &StringInterner) -> Result<Module, String> {
+    let mut parser = Parser::with_interner(source, 0, interner.clone()).map_err(|error| error.to_string())?;
+    let module = parser.parse_module().map_err(|error| error.to_string())?;
+    match parser.errors() {
+        [] => Ok(module),
+        errors => Err(format!("{:?}", errors[0])),
+    }
+}
+
+/// JSON form of `module` with every span removed
+fn without_spans(module: &Module) -> Value {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.remove("span");
+                map.remove("path_spans");
+                map.values_mut().for_each(strip);
+            }
+            Value::Array(values) => values.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value(module).expect("serializable module");
+    strip(&mut value);
+    value
+}
+
+#[test]
+fn test_printed_source_parses_to_the_same_ast() {
+    let mut checked = 0;
+    for (name, source) in corpus() {
+        let interner = StringInterner::new();
+        // Example programs using syntax the parser does not support yet are skipped
+        let Ok(original) = parse(&source, &interner) else {
+            assert!(name.starts_with("examples"), "{} does not parse: {:?}", name, parse(&source, &interner).err());
+            continue;
+        };
+
+        let printed = ast::to_source(&original, &interner);
+        let reparsed = parse(&printed, &interner).unwrap_or_else(|error| panic!("printed {} does not parse: {}\n{}", name, error, printed));
+        assert!(
+            without_spans(&original) == without_spans(&reparsed),
+            "printing {} changed its AST; printed source:\n{}",
+            name,
+            printed
+        );
+        assert_eq!(printed, ast::to_source(&reparsed, &interner), "printing {} is not idempotent", name);
+        checked += 1;
+    }
+    assert!(checked > SNIPPETS.len(), "no example program parsed");
+}
+
+#[test]
+fn test_printer_output_is_canonical() {
+    let interner = StringInterner::new();
+    let module = parse("fn   f( x:i32 )->i32{ if x>0{return x*(2+1) ;} else { return -x; } x }", &interner).unwrap();
+    assert_eq!(
+        PrettyPrinter::new(&interner).with_indent_width(2).print_module(&module),
+        "fn f(x: i32) -> i32 {\n  if x > 0 {\n    return x * (2 + 1);\n  } else {\n    return -x;\n  }\n  x\n}\n"
+    );
+}